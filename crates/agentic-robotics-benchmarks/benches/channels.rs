@@ -0,0 +1,81 @@
+//! Passing values from one thread to another through the lock-free
+//! channels and through tokio's mpsc
+//!
+//! The group is `channel_throughput`; each benchmark times one value from
+//! the producer thread to the consumer.
+
+use agentic_robotics_core::channel::{mpsc, spsc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use std::thread;
+use std::time::Instant;
+
+const CAPACITY: usize = 1024;
+
+fn benchmark_channels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("channel_throughput");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("spsc", |b| {
+        b.iter_custom(|iters| {
+            let (mut tx, mut rx) = spsc::channel_with_wakeup(CAPACITY);
+            let start = Instant::now();
+            let producer = thread::spawn(move || {
+                for i in 0..iters {
+                    let mut value = i;
+                    while let Err(rejected) = tx.try_push(value) {
+                        value = rejected;
+                        std::hint::spin_loop();
+                    }
+                }
+            });
+            while let Some(value) = rx.pop_blocking() {
+                black_box(value);
+            }
+            producer.join().unwrap();
+            start.elapsed()
+        })
+    });
+
+    group.bench_function("mpsc", |b| {
+        b.iter_custom(|iters| {
+            let (tx, mut rx) = mpsc::channel_with_wakeup(CAPACITY);
+            let start = Instant::now();
+            let producer = thread::spawn(move || {
+                for i in 0..iters {
+                    let mut value = i;
+                    while let Err(rejected) = tx.try_push(value) {
+                        value = rejected;
+                        std::hint::spin_loop();
+                    }
+                }
+            });
+            while let Some(value) = rx.pop_blocking() {
+                black_box(value);
+            }
+            producer.join().unwrap();
+            start.elapsed()
+        })
+    });
+
+    group.bench_function("tokio_mpsc", |b| {
+        b.iter_custom(|iters| {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(CAPACITY);
+            let start = Instant::now();
+            let producer = thread::spawn(move || {
+                for i in 0..iters {
+                    tx.blocking_send(i).unwrap();
+                }
+            });
+            while let Some(value) = rx.blocking_recv() {
+                black_box(value);
+            }
+            producer.join().unwrap();
+            start.elapsed()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_channels);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+//! Overhead of recording into a `LatencyTracker`
+//!
+//! The group is `latency_tracker`.
+
+use agentic_robotics_rt::LatencyTracker;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::time::Duration;
+
+fn benchmark_latency_tracker(c: &mut Criterion) {
+    let mut group = c.benchmark_group("latency_tracker");
+    let tracker = LatencyTracker::new("benchmark");
+
+    group.bench_function("record", |b| {
+        let duration = Duration::from_micros(100);
+        b.iter(|| tracker.record(black_box(duration)))
+    });
+
+    // Starting and dropping a measurement guard
+    group.bench_function("measure", |b| b.iter(|| drop(black_box(tracker.measure()))));
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_latency_tracker);
+criterion_main!(benches);
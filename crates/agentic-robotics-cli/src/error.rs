@@ -0,0 +1,24 @@
+//! Error types for the command-line tools
+
+use std::time::Duration;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Core(#[from] agentic_robotics_core::Error),
+
+    #[error("No message on '{topic}' for {timeout:?}")]
+    Timeout { topic: String, timeout: Duration },
+
+    #[error("No field '{0}' in the message")]
+    Field(String),
+
+    #[error("{at}: {reason}")]
+    Payload { at: String, reason: String },
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
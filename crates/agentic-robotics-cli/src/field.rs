@@ -0,0 +1,42 @@
+//! Selecting a field of a JSON message by path
+
+use serde_json::Value;
+
+/// The field of `value` at a dot-separated `path`, e.g. `position.0`
+///
+/// Segments name object keys or, on arrays, indices. The empty path
+/// selects `value` itself.
+pub fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+    path.split('.')
+        .try_fold(value, |value, segment| match value {
+            Value::Object(fields) => fields.get(segment),
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select() {
+        let msg = json!({
+            "position": [1.0, 2.0, 3.0],
+            "header": { "frame_id": "map", "stamp": { "sec": 4 } },
+        });
+        assert_eq!(select(&msg, ""), Some(&msg));
+        assert_eq!(select(&msg, "position.1"), Some(&json!(2.0)));
+        assert_eq!(select(&msg, "header.stamp.sec"), Some(&json!(4)));
+        assert_eq!(select(&msg, "header.frame_id"), Some(&json!("map")));
+
+        assert_eq!(select(&msg, "position.3"), None);
+        assert_eq!(select(&msg, "position.x"), None);
+        assert_eq!(select(&msg, "header.frame_id.0"), None);
+        assert_eq!(select(&msg, "velocity"), None);
+    }
+}
@@ -0,0 +1,15 @@
+//! Command-line tools for ros3 topics
+//!
+//! The `ros3-cli` binary inspects the topics of nodes on the UDP transport
+//! from another process: it lists them, prints their messages as JSON and
+//! measures their rate and bandwidth. Messages are received as
+//! [`RawMessage`](agentic_robotics_core::RawMessage)s and decoded through
+//! the [type registry](agentic_robotics_core::types), so the tools need no
+//! compile-time knowledge of the message types.
+
+pub mod error;
+pub mod field;
+pub mod stats;
+pub mod topic;
+
+pub use error::{Error, Result};
@@ -0,0 +1,217 @@
+//! `ros3-cli topic <list|echo|hz|bw|pub> ...`
+//!
+//! Inspects and publishes to the topics of nodes on the UDP transport until
+//! done or interrupted. See `ros3-cli --help`.
+
+use agentic_robotics_cli::stats::DEFAULT_WINDOW;
+use agentic_robotics_cli::topic::{self, EchoOptions, PublishOptions, NODE_NAME};
+use agentic_robotics_core::transport::DEFAULT_DISCOVERY_PORT;
+use agentic_robotics_core::{Node, TransportConfig, UdpConfig, UdpTransport};
+use clap::{Parser, Subcommand};
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "ros3-cli", version, about = "Inspect ros3 topics")]
+struct Cli {
+    /// Discovery port of the UDP transport
+    #[arg(long, global = true, default_value_t = DEFAULT_DISCOVERY_PORT)]
+    discovery_port: u16,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Topic commands
+    #[command(subcommand)]
+    Topic(TopicCommand),
+}
+
+#[derive(Subcommand)]
+enum TopicCommand {
+    /// List the topics announced by other processes
+    List {
+        /// Seconds to wait for announcements
+        #[arg(long, default_value = "2", value_parser = parse_seconds)]
+        wait: Duration,
+    },
+    /// Print the messages on a topic as JSON
+    Echo {
+        topic: String,
+        /// Print one message and exit
+        #[arg(long, conflicts_with = "count")]
+        once: bool,
+        /// Print this many messages and exit
+        #[arg(long)]
+        count: Option<usize>,
+        /// Print only this field, e.g. `position.0`
+        #[arg(long)]
+        field: Option<String>,
+        #[command(flatten)]
+        timeout: Timeout,
+    },
+    /// Print the publishing rate of a topic
+    Hz {
+        topic: String,
+        /// Messages the rate is measured over
+        #[arg(long, default_value_t = DEFAULT_WINDOW)]
+        window: usize,
+        #[command(flatten)]
+        timeout: Timeout,
+    },
+    /// Print the bandwidth used by a topic
+    Bw {
+        topic: String,
+        /// Messages the bandwidth is measured over
+        #[arg(long, default_value_t = DEFAULT_WINDOW)]
+        window: usize,
+        #[command(flatten)]
+        timeout: Timeout,
+    },
+    /// Publish messages given as JSON to a topic
+    Pub {
+        topic: String,
+        /// Registered type of the messages, e.g. `ros3_msgs/RobotState`
+        #[arg(value_name = "TYPE")]
+        type_name: String,
+        /// The message as a JSON document
+        #[arg(required_unless_present = "file")]
+        json: Option<String>,
+        /// Read messages from a file, one JSON document per line; `-` reads
+        /// standard input
+        #[arg(long, conflicts_with = "json")]
+        file: Option<PathBuf>,
+        /// Messages per second
+        #[arg(long, default_value = "1", value_parser = parse_rate)]
+        rate: f64,
+        /// Publish this many messages and exit; by default each message of
+        /// a file once, or a single message until interrupted
+        #[arg(long)]
+        count: Option<usize>,
+        /// Keep the last message for late subscribers until interrupted
+        #[arg(long)]
+        latch: bool,
+        /// Seconds to wait for a subscriber before publishing
+        #[arg(long, default_value = "2", value_parser = parse_seconds)]
+        wait: Duration,
+    },
+}
+
+#[derive(clap::Args)]
+struct Timeout {
+    /// Seconds without a message before giving up; 0 waits forever
+    #[arg(long = "timeout", default_value = "10", value_parser = parse_seconds)]
+    seconds: Duration,
+}
+
+impl Timeout {
+    fn get(&self) -> Option<Duration> {
+        (!self.seconds.is_zero()).then_some(self.seconds)
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s.parse().map_err(|_| format!("'{}' is not a number", s))?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("'{}' is not a duration", s))
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(format!("'{}' is not a positive rate", s)),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let config = UdpConfig {
+        discovery_port: cli.discovery_port,
+        ..Default::default()
+    };
+    let result = tokio::select! {
+        result = run(cli.command, config) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ros3-cli: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command, config: UdpConfig) -> agentic_robotics_cli::Result<()> {
+    let Command::Topic(command) = command;
+    let out = &mut std::io::stdout();
+    if let TopicCommand::List { wait } = command {
+        let transport = UdpTransport::shared(&config)?;
+        let topics = topic::list(&transport, wait).await;
+        return topic::print_list(&topics, out);
+    }
+
+    let node = Node::new(NODE_NAME)?.with_transport(TransportConfig::Udp(config.clone()))?;
+    match command {
+        TopicCommand::List { .. } => unreachable!("handled above"),
+        TopicCommand::Echo {
+            topic,
+            once,
+            count,
+            field,
+            timeout,
+        } => {
+            let options = EchoOptions {
+                field,
+                count: if once { Some(1) } else { count },
+                timeout: timeout.get(),
+            };
+            topic::echo(&node, &topic, &options, out).await?;
+        }
+        TopicCommand::Hz {
+            topic,
+            window,
+            timeout,
+        } => topic::hz(&node, &topic, window, timeout.get(), out).await?,
+        TopicCommand::Bw {
+            topic,
+            window,
+            timeout,
+        } => topic::bw(&node, &topic, window, timeout.get(), out).await?,
+        TopicCommand::Pub {
+            topic,
+            type_name,
+            json,
+            file,
+            rate,
+            count,
+            latch,
+            wait,
+        } => {
+            let messages = match (json, file) {
+                (Some(json), _) => vec![topic::message(&type_name, &json)?],
+                (None, Some(path)) if path.as_os_str() == "-" => {
+                    topic::messages_from_lines(&type_name, std::io::stdin().lock())?
+                }
+                (None, Some(path)) => {
+                    let file = std::fs::File::open(path)?;
+                    topic::messages_from_lines(&type_name, BufReader::new(file))?
+                }
+                (None, None) => unreachable!("required by clap"),
+            };
+            let options = PublishOptions {
+                rate_hz: rate,
+                count,
+                latch,
+                wait,
+            };
+            let transport = UdpTransport::shared(&config)?;
+            topic::publish(&node, Some(&transport), &topic, &messages, &options, out).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+//! Rate and bandwidth over a window of recent messages
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Messages kept by default
+pub const DEFAULT_WINDOW: usize = 10_000;
+
+/// Arrival rate and the spread of the intervals between messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateStats {
+    /// Messages per second, from the mean interval
+    pub rate_hz: f64,
+    pub min: Duration,
+    pub max: Duration,
+    /// Standard deviation of the intervals
+    pub jitter: Duration,
+    /// Intervals measured
+    pub intervals: usize,
+}
+
+/// Arrival times of the last messages
+#[derive(Debug, Clone)]
+pub struct RateWindow {
+    arrivals: VecDeque<Instant>,
+    capacity: usize,
+}
+
+impl RateWindow {
+    /// A window of the last `capacity` messages (at least 2)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            arrivals: VecDeque::new(),
+            capacity: capacity.max(2),
+        }
+    }
+
+    pub fn push(&mut self, at: Instant) {
+        if self.arrivals.len() == self.capacity {
+            self.arrivals.pop_front();
+        }
+        self.arrivals.push_back(at);
+    }
+
+    /// `None` until two messages have arrived
+    pub fn stats(&self) -> Option<RateStats> {
+        let intervals: Vec<f64> = self
+            .arrivals
+            .iter()
+            .zip(self.arrivals.iter().skip(1))
+            .map(|(earlier, later)| later.duration_since(*earlier).as_secs_f64())
+            .collect();
+        if intervals.is_empty() {
+            return None;
+        }
+        let n = intervals.len() as f64;
+        let mean = intervals.iter().sum::<f64>() / n;
+        let variance = intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / n;
+        let min = intervals.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = intervals.iter().copied().fold(0.0, f64::max);
+        Some(RateStats {
+            rate_hz: if mean > 0.0 {
+                1.0 / mean
+            } else {
+                f64::INFINITY
+            },
+            min: Duration::from_secs_f64(min),
+            max: Duration::from_secs_f64(max),
+            jitter: Duration::from_secs_f64(variance.sqrt()),
+            intervals: intervals.len(),
+        })
+    }
+}
+
+/// Bytes per second and message sizes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandwidthStats {
+    pub bytes_per_sec: f64,
+    pub mean_size: f64,
+    pub min_size: usize,
+    pub max_size: usize,
+    pub messages: usize,
+}
+
+/// Arrival times and serialized sizes of the last messages
+#[derive(Debug, Clone)]
+pub struct BandwidthWindow {
+    messages: VecDeque<(Instant, usize)>,
+    capacity: usize,
+}
+
+impl BandwidthWindow {
+    /// A window of the last `capacity` messages (at least 1)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            messages: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&mut self, at: Instant, bytes: usize) {
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((at, bytes));
+    }
+
+    /// The bytes in the window over the time from its first message to
+    /// `now`, so the bandwidth falls once messages stop; `None` while empty
+    pub fn stats(&self, now: Instant) -> Option<BandwidthStats> {
+        let (first, _) = self.messages.front()?;
+        let sizes = self.messages.iter().map(|(_, bytes)| *bytes);
+        let total: usize = sizes.clone().sum();
+        let elapsed = now.saturating_duration_since(*first).as_secs_f64();
+        Some(BandwidthStats {
+            bytes_per_sec: if elapsed > 0.0 {
+                total as f64 / elapsed
+            } else {
+                0.0
+            },
+            mean_size: total as f64 / self.messages.len() as f64,
+            min_size: sizes.clone().min().unwrap_or(0),
+            max_size: sizes.max().unwrap_or(0),
+            messages: self.messages.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_window() {
+        let start = Instant::now();
+        let mut window = RateWindow::new(4);
+        window.push(start);
+        assert_eq!(window.stats(), None);
+
+        // Intervals of 100 ms and 300 ms; the first arrival falls out
+        for ms in [100, 200, 300, 600] {
+            window.push(start + Duration::from_millis(ms));
+        }
+        let stats = window.stats().unwrap();
+        assert_eq!(stats.intervals, 3);
+        assert!((stats.rate_hz - 6.0).abs() < 1e-9);
+        assert_eq!(stats.min, Duration::from_millis(100));
+        assert_eq!(stats.max, Duration::from_millis(300));
+        // Mean 166.7 ms, deviations 66.7, 66.7 and 133.3 ms
+        assert!((stats.jitter.as_secs_f64() - 0.0943).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_bandwidth_window() {
+        let start = Instant::now();
+        let mut window = BandwidthWindow::new(3);
+        assert_eq!(window.stats(start), None);
+
+        for (ms, bytes) in [(0, 1000), (500, 100), (1000, 200), (1500, 300)] {
+            window.push(start + Duration::from_millis(ms), bytes);
+        }
+        let stats = window.stats(start + Duration::from_millis(2500)).unwrap();
+        assert_eq!(stats.messages, 3);
+        assert_eq!(stats.min_size, 100);
+        assert_eq!(stats.max_size, 300);
+        assert_eq!(stats.mean_size, 200.0);
+        // 600 bytes from 0.5 s to 2.5 s
+        assert_eq!(stats.bytes_per_sec, 300.0);
+    }
+}
@@ -0,0 +1,520 @@
+//! The `topic` commands
+//!
+//! Commands that read a topic subscribe with a best-effort [`RawMessage`]
+//! subscriber, so they match publishers of any type and reliability, and
+//! give up with [`Error::Timeout`] once the topic has been silent for their
+//! timeout. [`publish`] builds messages from JSON through the type
+//! registry and publishes them with a [`RawMessage`] publisher.
+
+use crate::error::{Error, Result};
+use crate::field;
+use crate::stats::{BandwidthWindow, RateWindow};
+use agentic_robotics_core::graph::TopicInfo;
+use agentic_robotics_core::UdpTransport;
+use agentic_robotics_core::{types, Node, QosProfile, RawMessage, Reliability, Subscriber};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+/// Name of the node the commands subscribe with
+pub const NODE_NAME: &str = "ros3_cli";
+
+/// How often `hz` and `bw` print
+pub const REPORT_PERIOD: Duration = Duration::from_secs(1);
+
+/// Messages queued while a command falls behind
+const QUEUE_DEPTH: usize = 1000;
+
+/// How often [`publish`] checks for subscribers while it waits for them
+const DISCOVERY_POLL: Duration = Duration::from_millis(50);
+
+/// How long [`publish`] waits for the transport to send the last messages
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Topics other processes announce on `transport`
+///
+/// Waits `wait` first for announcements to arrive; processes announce
+/// themselves once per announce period.
+pub async fn list(transport: &UdpTransport, wait: Duration) -> Vec<TopicInfo> {
+    tokio::time::sleep(wait).await;
+    transport.remote_topics()
+}
+
+/// Print the topics of [`list`]
+pub fn print_list(topics: &[TopicInfo], out: &mut impl Write) -> Result<()> {
+    let width = topics
+        .iter()
+        .map(|topic| topic.name.len())
+        .max()
+        .unwrap_or(0);
+    for topic in topics {
+        writeln!(
+            out,
+            "{:<width$}  {}  ({} publishers, {} subscribers)",
+            topic.name,
+            topic.type_name.as_deref().unwrap_or("*"),
+            topic.publisher_count,
+            topic.subscriber_count,
+        )?;
+    }
+    Ok(())
+}
+
+/// Settings of [`echo`]
+#[derive(Debug, Clone, Default)]
+pub struct EchoOptions {
+    /// Print only this field, see [`field::select`]
+    pub field: Option<String>,
+    /// Stop after this many messages
+    pub count: Option<usize>,
+    /// Give up after this long without a message
+    pub timeout: Option<Duration>,
+}
+
+/// Print the messages on `topic` as pretty JSON, each followed by `---`
+///
+/// Returns the number of messages printed once `options.count` is reached.
+pub async fn echo(
+    node: &Node,
+    topic: &str,
+    options: &EchoOptions,
+    out: &mut impl Write,
+) -> Result<usize> {
+    let subscriber = subscribe(node, topic)?;
+    let mut printed = 0;
+    while options.count.is_none_or(|count| printed < count) {
+        let raw = next(&subscriber, topic, options.timeout).await?;
+        let msg = types::to_json(&raw)?;
+        let path = options.field.as_deref().unwrap_or("");
+        let value = field::select(&msg, path).ok_or_else(|| Error::Field(path.to_string()))?;
+        let text = serde_json::to_string_pretty(value).expect("JSON values always serialize");
+        writeln!(out, "{}\n---", text)?;
+        out.flush()?;
+        printed += 1;
+    }
+    Ok(printed)
+}
+
+/// A message of the type registered as `type_name` from a JSON document
+///
+/// A document that does not match the type's schema is reported with every
+/// mismatched field.
+pub fn message(type_name: &str, json: &str) -> Result<RawMessage> {
+    parse(type_name, json, "message")
+}
+
+/// Messages of the type registered as `type_name`, one JSON document per
+/// line; blank lines are skipped
+pub fn messages_from_lines(type_name: &str, lines: impl BufRead) -> Result<Vec<RawMessage>> {
+    let mut messages = Vec::new();
+    for (index, line) in lines.lines().enumerate() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            messages.push(parse(type_name, &line, &format!("line {}", index + 1))?);
+        }
+    }
+    if messages.is_empty() {
+        return Err(Error::Payload {
+            at: "input".to_string(),
+            reason: "no messages".to_string(),
+        });
+    }
+    Ok(messages)
+}
+
+fn parse(type_name: &str, json: &str, at: &str) -> Result<RawMessage> {
+    let payload_error = |reason: String| Error::Payload {
+        at: at.to_string(),
+        reason,
+    };
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| payload_error(format!("invalid JSON: {}", e)))?;
+    types::from_json(type_name, value).map_err(|e| payload_error(e.to_string()))
+}
+
+/// Settings of [`publish`]
+#[derive(Debug, Clone)]
+pub struct PublishOptions {
+    pub rate_hz: f64,
+    /// Messages to publish, cycling through the given ones; by default
+    /// each of several messages once, or a single message until
+    /// interrupted
+    pub count: Option<usize>,
+    /// Latch the last message and keep it available until interrupted
+    pub latch: bool,
+    /// Longest wait for a subscriber in another process before the first
+    /// message
+    pub wait: Duration,
+}
+
+/// Publish `messages` on `topic` at `options.rate_hz`
+///
+/// Messages published before other processes have discovered the
+/// publisher would be lost, so this first waits up to `options.wait` for
+/// `transport` to see a subscriber.
+pub async fn publish(
+    node: &Node,
+    transport: Option<&UdpTransport>,
+    topic: &str,
+    messages: &[RawMessage],
+    options: &PublishOptions,
+    out: &mut impl Write,
+) -> Result<()> {
+    let publisher = node
+        .create_publisher::<RawMessage>(topic)?
+        .latch(options.latch);
+    if let Some(transport) = transport {
+        let name = node.resolve(topic)?;
+        wait_for_subscriber(transport, name.as_str(), options.wait).await;
+    }
+
+    let count = match options.count {
+        Some(count) => count,
+        None if messages.len() > 1 => messages.len(),
+        None => usize::MAX,
+    };
+    let mut ticks = tokio::time::interval(Duration::from_secs_f64(1.0 / options.rate_hz));
+    for (index, msg) in messages.iter().cycle().take(count).enumerate() {
+        ticks.tick().await;
+        publisher.publish(msg).await?;
+        writeln!(out, "publishing #{}", index + 1)?;
+        out.flush()?;
+    }
+    let _ = tokio::time::timeout(FLUSH_TIMEOUT, publisher.flush()).await;
+
+    if options.latch {
+        writeln!(out, "latched; press Ctrl-C to exit")?;
+        out.flush()?;
+        std::future::pending::<()>().await;
+    }
+    Ok(())
+}
+
+async fn wait_for_subscriber(transport: &UdpTransport, topic: &str, wait: Duration) {
+    let subscribed = || {
+        transport
+            .remote_topics()
+            .iter()
+            .any(|info| info.name == topic && info.subscriber_count > 0)
+    };
+    let _ = tokio::time::timeout(wait, async {
+        while !subscribed() {
+            tokio::time::sleep(DISCOVERY_POLL).await;
+        }
+    })
+    .await;
+}
+
+/// Print the rate of `topic` over the last `window` messages every
+/// [`REPORT_PERIOD`] until it is silent for `timeout`
+pub async fn hz(
+    node: &Node,
+    topic: &str,
+    window: usize,
+    timeout: Option<Duration>,
+    out: &mut impl Write,
+) -> Result<()> {
+    let subscriber = subscribe(node, topic)?;
+    monitor(subscriber, topic, timeout, RateWindow::new(window), out).await
+}
+
+/// Print the bandwidth of `topic` over the last `window` messages every
+/// [`REPORT_PERIOD`] until it is silent for `timeout`
+pub async fn bw(
+    node: &Node,
+    topic: &str,
+    window: usize,
+    timeout: Option<Duration>,
+    out: &mut impl Write,
+) -> Result<()> {
+    let subscriber = subscribe(node, topic)?;
+    monitor(
+        subscriber,
+        topic,
+        timeout,
+        BandwidthWindow::new(window),
+        out,
+    )
+    .await
+}
+
+/// What `hz` and `bw` measure
+trait Meter {
+    fn push(&mut self, at: Instant, raw: &RawMessage);
+
+    /// Print the measurement; `fresh` tells whether a message arrived
+    /// since the last report
+    fn report(&self, fresh: bool, out: &mut dyn Write) -> std::io::Result<()>;
+}
+
+impl Meter for RateWindow {
+    fn push(&mut self, at: Instant, _: &RawMessage) {
+        RateWindow::push(self, at);
+    }
+
+    fn report(&self, fresh: bool, out: &mut dyn Write) -> std::io::Result<()> {
+        match self.stats().filter(|_| fresh) {
+            Some(stats) => writeln!(
+                out,
+                "average rate: {:.3}\n\tmin: {:.4}s max: {:.4}s jitter: {:.5}s window: {}",
+                stats.rate_hz,
+                stats.min.as_secs_f64(),
+                stats.max.as_secs_f64(),
+                stats.jitter.as_secs_f64(),
+                stats.intervals + 1,
+            ),
+            None => writeln!(out, "no new messages"),
+        }
+    }
+}
+
+impl Meter for BandwidthWindow {
+    fn push(&mut self, at: Instant, raw: &RawMessage) {
+        BandwidthWindow::push(self, at, raw.data.len());
+    }
+
+    fn report(&self, _: bool, out: &mut dyn Write) -> std::io::Result<()> {
+        match self.stats(Instant::now()) {
+            Some(stats) => writeln!(
+                out,
+                "{}/s from {} messages\n\tMessage size mean: {} min: {} max: {}",
+                format_bytes(stats.bytes_per_sec),
+                stats.messages,
+                format_bytes(stats.mean_size),
+                format_bytes(stats.min_size as f64),
+                format_bytes(stats.max_size as f64),
+            ),
+            None => writeln!(out, "no new messages"),
+        }
+    }
+}
+
+/// Feed each message to `meter` and print it every [`REPORT_PERIOD`]
+async fn monitor(
+    subscriber: Subscriber<RawMessage>,
+    topic: &str,
+    timeout: Option<Duration>,
+    mut meter: impl Meter,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut ticks =
+        tokio::time::interval_at(tokio::time::Instant::now() + REPORT_PERIOD, REPORT_PERIOD);
+    let mut fresh = false;
+    let mut last = tokio::time::Instant::now();
+    loop {
+        let silent = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep_until(last + timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            raw = subscriber.recv() => {
+                meter.push(Instant::now(), &raw?);
+                last = tokio::time::Instant::now();
+                fresh = true;
+            }
+            _ = ticks.tick() => {
+                meter.report(fresh, out)?;
+                out.flush()?;
+                fresh = false;
+            }
+            _ = silent => {
+                return Err(Error::Timeout {
+                    topic: topic.to_string(),
+                    timeout: timeout.unwrap_or_default(),
+                });
+            }
+        }
+    }
+}
+
+fn subscribe(node: &Node, topic: &str) -> Result<Subscriber<RawMessage>> {
+    // Best effort so best-effort publishers match too
+    let qos = QosProfile {
+        reliability: Reliability::BestEffort,
+        history_depth: QUEUE_DEPTH,
+        ..Default::default()
+    };
+    Ok(node.create_subscriber_with_qos::<RawMessage>(topic, qos)?)
+}
+
+/// The next message, or [`Error::Timeout`] after `timeout` without one
+async fn next(
+    subscriber: &Subscriber<RawMessage>,
+    topic: &str,
+    timeout: Option<Duration>,
+) -> Result<RawMessage> {
+    let Some(timeout) = timeout else {
+        return Ok(subscriber.recv().await?);
+    };
+    match tokio::time::timeout(timeout, subscriber.recv()).await {
+        Ok(raw) => Ok(raw?),
+        Err(_) => Err(Error::Timeout {
+            topic: topic.to_string(),
+            timeout,
+        }),
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    if bytes >= 1e6 {
+        format!("{:.2} MB", bytes / 1e6)
+    } else if bytes >= 1e3 {
+        format!("{:.2} KB", bytes / 1e3)
+    } else {
+        format!("{:.0} B", bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentic_robotics_core::serialization::Format;
+    use agentic_robotics_core::{Publisher, RobotState};
+
+    fn state(timestamp: i64) -> RobotState {
+        RobotState {
+            position: [1.0, 2.0, 3.0],
+            velocity: [0.0; 3],
+            timestamp,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_echo_field_and_count() {
+        let node = Node::new(NODE_NAME).unwrap();
+        let topic = "/cli_test/echo";
+        let publisher = Publisher::<RobotState>::with_format(topic, Format::Cdr).unwrap();
+        let publishing = tokio::spawn(async move {
+            for i in 0.. {
+                publisher.publish(&state(i)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let options = EchoOptions {
+            field: Some("position.2".to_string()),
+            count: Some(2),
+            timeout: Some(Duration::from_secs(5)),
+        };
+        let mut out = Vec::new();
+        assert_eq!(echo(&node, topic, &options, &mut out).await.unwrap(), 2);
+        assert_eq!(String::from_utf8(out).unwrap(), "3.0\n---\n3.0\n---\n");
+
+        let options = EchoOptions {
+            field: Some("position.x".to_string()),
+            ..options
+        };
+        let err = echo(&node, topic, &options, &mut Vec::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Field(path) if path == "position.x"));
+        publishing.abort();
+    }
+
+    #[tokio::test]
+    async fn test_silent_topic_times_out() {
+        let node = Node::new(NODE_NAME).unwrap();
+        let topic = "/cli_test/silent";
+        let publisher = Publisher::<RobotState>::new(topic).unwrap();
+        let timeout = Some(Duration::from_millis(200));
+
+        // A message, then silence
+        let options = EchoOptions {
+            timeout,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let echoing = echo(&node, topic, &options, &mut out);
+        let publishing = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            publisher.publish(&state(1)).await.unwrap();
+        };
+        let (result, ()) = tokio::join!(echoing, publishing);
+        assert!(matches!(result, Err(Error::Timeout { .. })));
+        assert!(String::from_utf8(out).unwrap().contains("\"timestamp\": 1"));
+
+        let result = hz(&node, topic, 10, timeout, &mut Vec::new()).await;
+        assert!(matches!(result, Err(Error::Timeout { .. })));
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let raw = message(
+            "ros3_msgs/RobotState",
+            &serde_json::to_string(&state(7)).unwrap(),
+        );
+        assert_eq!(raw.unwrap().decode::<RobotState>().unwrap().timestamp, 7);
+
+        let err = message("ros3_msgs/RobotState", r#"{"position": [1, 2]}"#).unwrap_err();
+        let text = err.to_string();
+        assert!(text.starts_with("message: Invalid ros3_msgs/RobotState message: "));
+        assert!(text.contains("/position: must have at least 3 items"));
+        assert!(text.contains("missing required property 'velocity'"));
+
+        let lines =
+            "{\"position\": [0, 0, 0], \"velocity\": [0, 0, 0], \"timestamp\": 1}\n\n{oops\n";
+        let err = messages_from_lines("ros3_msgs/RobotState", lines.as_bytes()).unwrap_err();
+        assert!(err.to_string().starts_with("line 3: invalid JSON"));
+        assert!(messages_from_lines("ros3_msgs/RobotState", "\n".as_bytes()).is_err());
+        let err = message("test_msgs/Unknown", "{}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "message: Unknown message type 'test_msgs/Unknown'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_cycles_through_messages() {
+        let node = Node::new(NODE_NAME).unwrap();
+        let topic = "/cli_test/publish";
+        let subscriber = Subscriber::<RobotState>::new(topic).unwrap();
+        let lines = [1, 2]
+            .map(|i| serde_json::to_string(&state(i)).unwrap())
+            .join("\n");
+        let messages = messages_from_lines("ros3_msgs/RobotState", lines.as_bytes()).unwrap();
+        let options = PublishOptions {
+            rate_hz: 100.0,
+            count: Some(3),
+            latch: false,
+            wait: Duration::ZERO,
+        };
+
+        let mut out = Vec::new();
+        publish(&node, None, topic, &messages, &options, &mut out)
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        while let Some(msg) = subscriber.try_recv().unwrap() {
+            received.push(msg.timestamp);
+        }
+        assert_eq!(received, [1, 2, 1]);
+        assert!(String::from_utf8(out).unwrap().ends_with("publishing #3\n"));
+    }
+
+    #[test]
+    fn test_print_list() {
+        let topics = vec![
+            TopicInfo {
+                name: "/robot_state".to_string(),
+                type_name: Some("ros3_msgs/RobotState".to_string()),
+                publisher_count: 1,
+                subscriber_count: 2,
+            },
+            TopicInfo {
+                name: "/raw".to_string(),
+                type_name: None,
+                publisher_count: 0,
+                subscriber_count: 1,
+            },
+        ];
+        let mut out = Vec::new();
+        print_list(&topics, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "/robot_state  ros3_msgs/RobotState  (1 publishers, 2 subscribers)\n\
+             /raw          *  (0 publishers, 1 subscribers)\n"
+        );
+    }
+}
@@ -1,21 +1,23 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use ros3_core::{Publisher, RobotState};
+use agentic_robotics_core::{Publisher, RobotState};
 
 fn benchmark_publish(c: &mut Criterion) {
     let rt = tokio::runtime::Runtime::new().unwrap();
 
     c.bench_function("ros3_publish", |b| {
-        let publisher = Publisher::<RobotState>::new("benchmark/topic");
+        let publisher = Publisher::<RobotState>::new("benchmark/topic").unwrap();
         let msg = RobotState::default();
 
-        b.to_async(&rt).iter(|| async {
-            black_box(publisher.publish(&msg).await).unwrap();
+        b.iter(|| {
+            rt.block_on(async {
+                black_box(publisher.publish(&msg).await).unwrap();
+            })
         });
     });
 }
 
 fn benchmark_serialization(c: &mut Criterion) {
-    use ros3_core::serialization::{serialize_cdr, serialize_rkyv};
+    use agentic_robotics_core::serialization::serialize_cdr;
 
     let msg = RobotState::default();
 
@@ -24,12 +26,6 @@ fn benchmark_serialization(c: &mut Criterion) {
             black_box(serialize_cdr(&msg)).unwrap();
         });
     });
-
-    c.bench_function("rkyv_serialize", |b| {
-        b.iter(|| {
-            black_box(serialize_rkyv(&msg)).unwrap();
-        });
-    });
 }
 
 criterion_group!(benches, benchmark_publish, benchmark_serialization);
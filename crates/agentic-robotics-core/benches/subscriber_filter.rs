@@ -0,0 +1,72 @@
+//! Filtering in the publishing task against filtering after `recv()`
+//!
+//! Publishes a burst of `PointCloud`s of which one in a hundred is wanted.
+//! A filtered subscriber decodes each message once to test it and queues
+//! only the wanted ones; a plain subscriber queues all of them and the
+//! consumer decodes and discards the rest.
+
+use agentic_robotics_core::message::Point3D;
+use agentic_robotics_core::{PointCloud, Publisher, Subscriber};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const BURST: usize = 1000;
+
+fn cloud(i: usize, points: usize) -> PointCloud {
+    PointCloud {
+        points: vec![Point3D { x: 1.0, y: 2.0, z: 3.0 }; points],
+        intensities: Vec::new(),
+        timestamp: i as i64,
+    }
+}
+
+fn wanted(cloud: &PointCloud) -> bool {
+    cloud.timestamp % 100 == 0
+}
+
+fn benchmark_filtering(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("subscriber_filter");
+
+    for points in [16, 1024] {
+        let messages: Vec<PointCloud> = (0..BURST).map(|i| cloud(i, points)).collect();
+
+        group.bench_with_input(BenchmarkId::new("at_source", points), &points, |b, _| {
+            let topic = format!("bench/filter/source/{}", points);
+            let publisher = Publisher::<PointCloud>::new(&topic).unwrap();
+            let subscriber = Subscriber::filtered(&topic, wanted).unwrap();
+            b.iter(|| {
+                rt.block_on(async {
+                    for msg in &messages {
+                        publisher.publish(msg).await.unwrap();
+                    }
+                });
+                while let Some(msg) = subscriber.try_recv().unwrap() {
+                    black_box(msg);
+                }
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("after_recv", points), &points, |b, _| {
+            let topic = format!("bench/filter/recv/{}", points);
+            let publisher = Publisher::<PointCloud>::new(&topic).unwrap();
+            let subscriber = Subscriber::<PointCloud>::new(&topic).unwrap();
+            b.iter(|| {
+                rt.block_on(async {
+                    for msg in &messages {
+                        publisher.publish(msg).await.unwrap();
+                    }
+                });
+                while let Some(msg) = subscriber.try_recv().unwrap() {
+                    if wanted(&msg) {
+                        black_box(msg);
+                    }
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_filtering);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+//! `ros3 bag info` and export in miniature
+//!
+//! ```sh
+//! cargo run -p agentic-robotics-core --example bag_info -- run.bag
+//! cargo run -p agentic-robotics-core --example bag_info -- run.bag --jsonl run.jsonl
+//! cargo run -p agentic-robotics-core --example bag_info -- run.bag --csv state.csv /robot_state
+//! ```
+//!
+//! Prints a summary of a bag, or exports its messages as JSON lines or CSV
+//! for loading into pandas. Trailing topics limit the export to those
+//! topics; CSV exports should name topics of a single message type.
+//! Built-in message types and JSON or MessagePack payloads can be exported.
+
+use agentic_robotics_core::recording::JsonConverter;
+use agentic_robotics_core::{BagReader, MessageFilter, Result};
+use std::fs::File;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: bag_info FILE [--jsonl OUT | --csv OUT] [TOPIC...]";
+
+enum Export {
+    Jsonl(PathBuf),
+    Csv(PathBuf),
+}
+
+fn parse_args() -> std::result::Result<(PathBuf, Option<Export>, MessageFilter), String> {
+    let mut path = None;
+    let mut export = None;
+    let mut topics = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--jsonl" => export = Some(Export::Jsonl(value()?.into())),
+            "--csv" => export = Some(Export::Csv(value()?.into())),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => topics.push(arg),
+        }
+    }
+    let filter = MessageFilter {
+        topics: (!topics.is_empty()).then_some(topics),
+        ..Default::default()
+    };
+    Ok((path.ok_or_else(|| USAGE.to_string())?, export, filter))
+}
+
+fn run(path: PathBuf, export: Option<Export>, filter: MessageFilter) -> Result<()> {
+    let mut reader = BagReader::open(&path)?;
+    let converter = JsonConverter::with_builtin_types();
+    match export {
+        None => print!("{}", reader.info()?),
+        Some(Export::Jsonl(out)) => {
+            let count = reader.export_jsonl(filter, &converter, File::create(&out)?)?;
+            println!("Wrote {} messages to {}", count, out.display());
+        }
+        Some(Export::Csv(out)) => {
+            let count = reader.export_csv(filter, &converter, File::create(&out)?)?;
+            println!("Wrote {} rows to {}", count, out.display());
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let (path, export, filter) = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(path, export, filter) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("bag_info: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
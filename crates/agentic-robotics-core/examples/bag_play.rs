@@ -0,0 +1,94 @@
+//! `ros3 bag play` in miniature
+//!
+//! ```sh
+//! cargo run -p agentic-robotics-core --example bag_play -- run.bag
+//! cargo run -p agentic-robotics-core --example bag_play -- run.bag --rate 2 --loop /scan
+//! ```
+//!
+//! Republishes a bag on the default UDP transport with its original timing,
+//! scaled by `--rate`. `--start-offset <SECONDS>` skips the beginning of the
+//! bag, `--loop` starts over after the last message and `--clock` publishes
+//! the bag's time on `/clock` so nodes using `Clock::sim_from_topic` follow
+//! the replay. Trailing topics limit playback to those topics.
+
+use agentic_robotics_core::time::CLOCK_TOPIC;
+use agentic_robotics_core::{Player, PlayerConfig, TopicFilter, TransportConfig, UdpConfig};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE: &str =
+    "usage: bag_play FILE [--rate RATE] [--loop] [--start-offset SECONDS] [--clock] [TOPIC...]";
+
+fn parse_args() -> Result<(PathBuf, PlayerConfig), String> {
+    let mut path = None;
+    let mut topics = Vec::new();
+    let mut config = PlayerConfig {
+        transport: TransportConfig::Udp(UdpConfig::default()),
+        ..Default::default()
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--rate" => {
+                config.rate = value()?.parse().map_err(|_| "--rate takes a number".to_string())?;
+            }
+            "--loop" => config.loop_playback = true,
+            "--start-offset" => {
+                let seconds: f64 = value()?
+                    .parse()
+                    .map_err(|_| "--start-offset takes seconds".to_string())?;
+                config.start_offset = Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| "--start-offset takes seconds".to_string())?;
+            }
+            "--clock" => config.clock_topic = Some(CLOCK_TOPIC.to_string()),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => topics.push(arg),
+        }
+    }
+    if !topics.is_empty() {
+        config.topics = Some(TopicFilter::List(topics));
+    }
+    Ok((path.ok_or_else(|| USAGE.to_string())?, config))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let (path, config) = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut player = match Player::open(&path, config) {
+        Ok(player) => player,
+        Err(e) => {
+            eprintln!("bag_play: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let duration = player.end_time().saturating_duration_since(player.start_time());
+    println!(
+        "Playing {} messages spanning {:.1} s from {}",
+        player.reader().message_count(),
+        duration.as_secs_f64(),
+        path.display()
+    );
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => ExitCode::SUCCESS,
+        result = player.play() => match result {
+            Ok(published) => {
+                println!("Published {} messages", published);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("bag_play: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
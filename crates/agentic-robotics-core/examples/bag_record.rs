@@ -0,0 +1,122 @@
+//! `ros3 bag record` in miniature
+//!
+//! ```sh
+//! cargo run -p agentic-robotics-core --example bag_record -- -o run.bag /robot_state /scan
+//! cargo run -p agentic-robotics-core --example bag_record -- --regex '^/sensors/' --split 64
+//! ```
+//!
+//! Records the given topics from nodes on the default UDP transport until
+//! Ctrl-C, printing per-topic rates every second, then writes the index.
+//! `--split <MiB>` starts a new file whenever the current one reaches that
+//! size, and `--zstd` compresses chunks (needs the `zstd` feature).
+//! `--regex` only sees topics that publishers or subscribers in this process
+//! have joined, or that the transport has announced to it.
+
+#[cfg(feature = "zstd")]
+use agentic_robotics_core::recording::Compression;
+use agentic_robotics_core::{
+    Recorder, RecorderConfig, RecorderStats, TopicFilter, TransportConfig, UdpConfig,
+};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE: &str =
+    "usage: bag_record [-o FILE] [--split MIB] [--zstd] (--regex PATTERN | TOPIC...)";
+
+fn parse_args() -> Result<RecorderConfig, String> {
+    let mut path = PathBuf::from("recording.bag");
+    let mut topics = Vec::new();
+    let mut regex = None;
+    let mut split = None;
+    let mut zstd = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-o" | "--output" => path = value()?.into(),
+            "--regex" => regex = Some(value()?),
+            "--split" => {
+                let mib: u64 = value()?.parse().map_err(|_| "--split takes MiB".to_string())?;
+                split = Some(mib * 1024 * 1024);
+            }
+            "--zstd" => zstd = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => topics.push(arg),
+        }
+    }
+    let filter = match (regex, topics.is_empty()) {
+        (Some(pattern), true) => TopicFilter::Regex(pattern),
+        (None, false) => TopicFilter::List(topics),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let mut config = RecorderConfig::new(path, filter);
+    config.split_size = split;
+    config.transport = TransportConfig::Udp(UdpConfig::default());
+    if zstd {
+        #[cfg(feature = "zstd")]
+        {
+            config.compression = Compression::Zstd { level: 3 };
+        }
+        #[cfg(not(feature = "zstd"))]
+        return Err("--zstd needs the `zstd` feature".to_string());
+    }
+    Ok(config)
+}
+
+fn print_stats(stats: &RecorderStats) {
+    for (topic, topic_stats) in &stats.topics {
+        println!(
+            "  {:<32} {:>8} msgs {:>10} bytes {:>8.1} Hz",
+            topic, topic_stats.messages, topic_stats.bytes, topic_stats.rate_hz
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let config = match parse_args() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+    let recorder = match Recorder::start(config) {
+        Ok(recorder) => recorder,
+        Err(e) => {
+            eprintln!("bag_record: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut report = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = report.tick() => {
+                let stats = recorder.stats();
+                println!(
+                    "{} messages, {} bytes, {} dropped",
+                    stats.messages, stats.bytes, stats.dropped
+                );
+                print_stats(&stats);
+            }
+        }
+    }
+
+    match recorder.finalize().await {
+        Ok(stats) => {
+            println!("Recorded {} messages to:", stats.messages);
+            for file in &stats.files {
+                println!("  {}", file.display());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("bag_record: finalizing failed: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
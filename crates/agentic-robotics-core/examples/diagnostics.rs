@@ -0,0 +1,113 @@
+//! Battery and temperature diagnostics
+//!
+//! ```sh
+//! cargo run -p agentic-robotics-core --example diagnostics
+//! ```
+//!
+//! A `power_node` reports a draining battery and a heating motor through a
+//! diagnostic updater, which publishes them on `/diagnostics` once a second
+//! together with the built-in subscriber drop counts. The temperature sensor
+//! goes quiet after eight seconds, so its status turns stale. A subscriber
+//! prints every update until Ctrl-C or fifteen updates.
+
+use agentic_robotics_core::diagnostics::{self, DIAGNOSTICS_TOPIC};
+use agentic_robotics_core::{DiagnosticArray, DiagnosticLevel, KeyValue, Node, Result};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const PERIOD: Duration = Duration::from_secs(1);
+
+fn print_update(array: &DiagnosticArray) {
+    println!(
+        "[{:.1}s] overall {}",
+        array.stamp.as_secs_f64(),
+        array.level()
+    );
+    for status in &array.status {
+        let values: Vec<String> = status
+            .values
+            .iter()
+            .map(|value| format!("{}={}", value.key, value.value))
+            .collect();
+        println!(
+            "  {:<6} {:<28} {:<40} {}",
+            status.level.to_string(),
+            status.name,
+            status.message,
+            values.join(" ")
+        );
+    }
+}
+
+async fn run() -> Result<()> {
+    let node = Node::new("power_node")?;
+    let updater = node.create_diagnostic_updater(PERIOD)?;
+    updater.add_collector(diagnostics::subscriber_drops());
+    let battery = updater.register("battery", PERIOD * 2);
+    let temperature = updater.register("motor_temperature", PERIOD * 2);
+
+    let updates = node.create_subscriber::<DiagnosticArray>(DIAGNOSTICS_TOPIC)?;
+    let printer = tokio::spawn(async move {
+        for _ in 0..15 {
+            match updates.recv().await {
+                Ok(array) => print_update(&array),
+                Err(_) => break,
+            }
+        }
+    });
+
+    let sensors = tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_millis(500));
+        for step in 0u32.. {
+            tick.tick().await;
+            let charge = 100.0 - step as f64 * 1.5;
+            let volts = 10.5 + charge / 100.0 * 2.1;
+            let (level, message) = match charge {
+                c if c < 80.0 => (DiagnosticLevel::Error, "Battery critical"),
+                c if c < 90.0 => (DiagnosticLevel::Warn, "Battery low"),
+                _ => (DiagnosticLevel::Ok, "Battery OK"),
+            };
+            battery.report(
+                level,
+                message,
+                vec![
+                    KeyValue::new("charge_percent", format!("{:.1}", charge)),
+                    KeyValue::new("voltage", format!("{:.2}", volts)),
+                ],
+            );
+
+            // The sensor stops answering after eight seconds
+            if step < 16 {
+                let celsius = 40.0 + step as f64 * 2.0;
+                let level = if celsius > 60.0 {
+                    DiagnosticLevel::Warn
+                } else {
+                    DiagnosticLevel::Ok
+                };
+                temperature.report(
+                    level,
+                    format!("Motor at {:.0} °C", celsius),
+                    vec![KeyValue::new("celsius", celsius)],
+                );
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = printer => {}
+    }
+    sensors.abort();
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("diagnostics: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
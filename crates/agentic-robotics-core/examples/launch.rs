@@ -0,0 +1,114 @@
+//! The stress-test topology brought up from a launch file
+//!
+//! ```sh
+//! cargo run -p agentic-robotics-core --example launch -- rate_hz=1000 points=100
+//! ```
+//!
+//! Registers the node factories used by
+//! `examples/launch/stress_test.launch.yaml` and launches it, with the
+//! `name=value` arguments given on the command line. Publishers send point
+//! clouds at `rate_hz`, subscribers count what they receive, and the monitor
+//! prints the received rate every second until Ctrl-C or ten seconds.
+
+use agentic_robotics_core::message::Point3D;
+use agentic_robotics_core::{Error, Launcher, Node, PointCloud, Result};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const LAUNCH_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/examples/launch/stress_test.launch.yaml"
+);
+
+async fn publish(node: Arc<Node>) -> Result<()> {
+    let rate: i64 = node.parameters().get("rate_hz")?;
+    let points: i64 = node.parameters().get("points")?;
+    let publisher = node.create_publisher::<PointCloud>("stress_topic")?;
+    let point = Point3D {
+        x: 1.0,
+        y: 2.0,
+        z: 3.0,
+    };
+    let mut cloud = PointCloud {
+        points: vec![point; points.max(0) as usize],
+        intensities: vec![1.0; points.max(0) as usize],
+        timestamp: 0,
+    };
+    let mut tick = tokio::time::interval(Duration::from_secs_f64(1.0 / rate.max(1) as f64));
+    loop {
+        tick.tick().await;
+        cloud.timestamp += 1;
+        publisher.publish(&cloud).await?;
+    }
+}
+
+async fn count(node: Arc<Node>, received: Arc<AtomicU64>) -> Result<()> {
+    let subscriber = node.create_subscriber::<PointCloud>("stress_topic")?;
+    loop {
+        subscriber.recv().await?;
+        received.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+async fn monitor(node: Arc<Node>, received: Arc<AtomicU64>) -> Result<()> {
+    let period: i64 = node.parameters().get("period_ms")?;
+    let period = Duration::from_millis(period.max(1) as u64);
+    let mut last = received.load(Ordering::Relaxed);
+    loop {
+        tokio::time::sleep(period).await;
+        let now = received.load(Ordering::Relaxed);
+        println!(
+            "received {:.0} msg/s",
+            (now - last) as f64 / period.as_secs_f64()
+        );
+        last = now;
+    }
+}
+
+async fn run() -> Result<()> {
+    let args = std::env::args()
+        .skip(1)
+        .map(|arg| match arg.split_once('=') {
+            Some((name, value)) => Ok((name.to_string(), value.to_string())),
+            None => Err(Error::Configuration(format!(
+                "Expected name=value, found '{}'",
+                arg
+            ))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let received = Arc::new(AtomicU64::new(0));
+    let mut launcher = Launcher::new();
+    launcher.register("stress_publisher", publish);
+    let counter = received.clone();
+    launcher.register("stress_subscriber", move |node| {
+        count(node, counter.clone())
+    });
+    let counter = received.clone();
+    launcher.register("stress_monitor", move |node| monitor(node, counter.clone()));
+
+    let launch = launcher.launch(LAUNCH_FILE, args)?;
+    for node in launch.nodes() {
+        println!("{}/{} ({})", node.namespace, node.name, node.factory);
+    }
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = tokio::time::sleep(Duration::from_secs(10)) => {}
+    }
+    println!("{} messages received", received.load(Ordering::Relaxed));
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("launch: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,448 @@
+//! Action implementation
+//!
+//! Actions are long-running, cancellable calls: an [`ActionClient`] sends a
+//! goal, receives a stream of feedback while the [`ActionServer`] works on
+//! it, and finally gets a result. Every goal runs in its own task with its
+//! own feedback channel, so a server can pursue several goals at once.
+
+use crate::error::{Error, Result};
+use crate::message::Message;
+use crate::name::TopicName;
+use parking_lot::RwLock;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// A goal on its way to a server, with the channels back to its client
+struct GoalRequest<G, F, R> {
+    id: u64,
+    goal: G,
+    feedback: mpsc::UnboundedSender<F>,
+    cancel: Arc<CancelFlag>,
+    result: oneshot::Sender<Result<R>>,
+}
+
+/// Cancellation request shared by both ends of a goal
+#[derive(Default)]
+struct CancelFlag {
+    requested: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelFlag {
+    fn request(&self) {
+        self.requested.store(true, Ordering::Release);
+        self.notify.notify_waiters();
+    }
+
+    fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Acquire)
+    }
+}
+
+/// A server's entry in the action registry
+struct ActionEntry {
+    /// `mpsc::UnboundedSender<GoalRequest<G, F, R>>` for the server's types
+    goals: Box<dyn Any + Send + Sync>,
+    types: String,
+}
+
+/// Process-wide map of action names to their servers
+fn registry() -> &'static RwLock<HashMap<String, Arc<ActionEntry>>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, Arc<ActionEntry>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+fn action_types<G: Message, F: Message, R: Message>() -> String {
+    format!(
+        "{} -> {} -> {}",
+        G::type_name(),
+        F::type_name(),
+        R::type_name()
+    )
+}
+
+/// Server-side view of a goal, passed to the action handler
+pub struct ServerGoalHandle<G, F> {
+    id: u64,
+    goal: G,
+    feedback: mpsc::UnboundedSender<F>,
+    cancel: Arc<CancelFlag>,
+}
+
+impl<G, F> ServerGoalHandle<G, F> {
+    /// Goal id, unique within the process
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The goal to pursue
+    pub fn goal(&self) -> &G {
+        &self.goal
+    }
+
+    /// Send feedback to the client; ignored once the client has gone
+    pub fn publish_feedback(&self, feedback: F) {
+        let _ = self.feedback.send(feedback);
+    }
+
+    /// Whether the client asked to cancel the goal
+    ///
+    /// The handler decides how to wind down, typically by returning
+    /// [`canceled`](Self::canceled).
+    pub fn is_cancel_requested(&self) -> bool {
+        self.cancel.is_requested()
+    }
+
+    /// Wait until the client asks to cancel the goal
+    pub async fn cancel_requested(&self) {
+        loop {
+            let notified = self.cancel.notify.notified();
+            if self.is_cancel_requested() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Error to return from the handler after honoring a cancel request
+    pub fn canceled(&self) -> Error {
+        Error::GoalCanceled(self.id)
+    }
+}
+
+/// Server pursuing goals with an async handler
+///
+/// Dropping the server unregisters it; goals that were never started fail
+/// with [`Error::ServiceUnavailable`].
+pub struct ActionServer<G: Message, F: Message, R: Message> {
+    name: String,
+    entry: Arc<ActionEntry>,
+    dispatcher: JoinHandle<()>,
+    _phantom: std::marker::PhantomData<fn(G) -> (F, R)>,
+}
+
+impl<G: Message, F: Message, R: Message> ActionServer<G, F, R> {
+    /// Create a server running `handler` for every goal
+    ///
+    /// Fails with [`Error::Configuration`] if the action already has a
+    /// server. Must be called from within a Tokio runtime.
+    pub fn new<H, Fut>(name: impl Into<String>, handler: H) -> Result<Self>
+    where
+        H: Fn(ServerGoalHandle<G, F>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+    {
+        let name = name.into();
+        TopicName::new(name.as_str())?;
+        debug!("Creating action server: {}", name);
+
+        let (tx, rx) = mpsc::unbounded_channel::<GoalRequest<G, F, R>>();
+        let entry = Arc::new(ActionEntry {
+            goals: Box::new(tx),
+            types: action_types::<G, F, R>(),
+        });
+        {
+            let mut actions = registry().write();
+            if actions.contains_key(&name) {
+                return Err(Error::Configuration(format!(
+                    "Action '{}' already has a server",
+                    name
+                )));
+            }
+            actions.insert(name.clone(), entry.clone());
+        }
+
+        let dispatcher = tokio::spawn(Self::dispatch(name.clone(), rx, Arc::new(handler)));
+
+        Ok(Self {
+            name,
+            entry,
+            dispatcher,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    async fn dispatch<H, Fut>(
+        name: String,
+        mut goals: mpsc::UnboundedReceiver<GoalRequest<G, F, R>>,
+        handler: Arc<H>,
+    ) where
+        H: Fn(ServerGoalHandle<G, F>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+    {
+        while let Some(request) = goals.recv().await {
+            let handle = ServerGoalHandle {
+                id: request.id,
+                goal: request.goal,
+                feedback: request.feedback,
+                cancel: request.cancel,
+            };
+            let task = tokio::spawn(handler(handle));
+            let (name, reply) = (name.clone(), request.result);
+            tokio::spawn(async move {
+                let result = match task.await {
+                    Ok(result) => result,
+                    Err(e) if e.is_panic() => Err(Error::ServicePanicked(name)),
+                    Err(_) => Err(Error::ServiceUnavailable(name)),
+                };
+                let _ = reply.send(result);
+            });
+        }
+    }
+
+    /// Get action name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<G: Message, F: Message, R: Message> Drop for ActionServer<G, F, R> {
+    fn drop(&mut self) {
+        let mut actions = registry().write();
+        if actions
+            .get(&self.name)
+            .is_some_and(|entry| Arc::ptr_eq(entry, &self.entry))
+        {
+            actions.remove(&self.name);
+        }
+        self.dispatcher.abort();
+    }
+}
+
+/// Client sending goals to an [`ActionServer`]
+pub struct ActionClient<G: Message, F: Message, R: Message> {
+    name: String,
+    _phantom: std::marker::PhantomData<fn(G) -> (F, R)>,
+}
+
+impl<G: Message, F: Message, R: Message> ActionClient<G, F, R> {
+    /// Create a client for an action, which need not have a server yet
+    pub fn new(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        TopicName::new(name.as_str())?;
+        debug!("Creating action client: {}", name);
+
+        Ok(Self {
+            name,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    /// Send a goal to the server
+    ///
+    /// Fails immediately with [`Error::ServiceUnavailable`] if no server is
+    /// registered, or with [`Error::TopicTypeMismatch`] if the server uses
+    /// different goal, feedback or result types.
+    pub fn send_goal(&self, goal: G) -> Result<GoalHandle<F, R>> {
+        static NEXT_GOAL_ID: AtomicU64 = AtomicU64::new(1);
+
+        let entry = registry()
+            .read()
+            .get(&self.name)
+            .cloned()
+            .ok_or_else(|| Error::ServiceUnavailable(self.name.clone()))?;
+        let goals = entry
+            .goals
+            .downcast_ref::<mpsc::UnboundedSender<GoalRequest<G, F, R>>>()
+            .ok_or_else(|| Error::TopicTypeMismatch {
+                topic: self.name.clone(),
+                expected: entry.types.clone(),
+                found: action_types::<G, F, R>(),
+            })?;
+
+        let id = NEXT_GOAL_ID.fetch_add(1, Ordering::Relaxed);
+        let (feedback_tx, feedback) = mpsc::unbounded_channel();
+        let (result_tx, result) = oneshot::channel();
+        let cancel = Arc::new(CancelFlag::default());
+        goals
+            .send(GoalRequest {
+                id,
+                goal,
+                feedback: feedback_tx,
+                cancel: cancel.clone(),
+                result: result_tx,
+            })
+            .map_err(|_| Error::ServiceUnavailable(self.name.clone()))?;
+
+        Ok(GoalHandle {
+            id,
+            action: self.name.clone(),
+            feedback,
+            cancel,
+            result,
+        })
+    }
+
+    /// Whether a server is currently registered for the action
+    pub fn is_available(&self) -> bool {
+        registry().read().contains_key(&self.name)
+    }
+
+    /// Get action name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Client-side view of a goal
+pub struct GoalHandle<F, R> {
+    id: u64,
+    action: String,
+    feedback: mpsc::UnboundedReceiver<F>,
+    cancel: Arc<CancelFlag>,
+    result: oneshot::Receiver<Result<R>>,
+}
+
+impl<F, R> GoalHandle<F, R> {
+    /// Goal id, unique within the process
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Next feedback from the server, or `None` once the goal has finished
+    pub async fn feedback(&mut self) -> Option<F> {
+        self.feedback.recv().await
+    }
+
+    /// Ask the server to cancel the goal
+    ///
+    /// The server decides when to stop; await [`result`](Self::result) to
+    /// learn the outcome, usually [`Error::GoalCanceled`].
+    pub fn cancel(&self) {
+        self.cancel.request();
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called
+    pub fn is_cancel_requested(&self) -> bool {
+        self.cancel.is_requested()
+    }
+
+    /// Wait for the outcome of the goal
+    pub async fn result(self) -> Result<R> {
+        match self.result.await {
+            Ok(result) => result,
+            Err(_) => Err(Error::ServiceUnavailable(self.action)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+    use std::time::Duration;
+
+    type Client = ActionClient<RobotState, RobotState, RobotState>;
+
+    fn state(timestamp: i64) -> RobotState {
+        RobotState {
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    /// Counts from 0 to the goal's timestamp, reporting each step
+    fn count_up(name: &str) -> ActionServer<RobotState, RobotState, RobotState> {
+        ActionServer::new(name, |handle: ServerGoalHandle<RobotState, RobotState>| async move {
+            for step in 0..handle.goal().timestamp {
+                if handle.is_cancel_requested() {
+                    return Err(handle.canceled());
+                }
+                handle.publish_feedback(state(step));
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(state(handle.goal().timestamp))
+        })
+        .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_mid_execution() {
+        let _server = count_up("test/action/cancel");
+        let client = Client::new("test/action/cancel").unwrap();
+
+        let mut goal = client.send_goal(state(1000)).unwrap();
+        for step in 0..3 {
+            assert_eq!(goal.feedback().await.unwrap().timestamp, step);
+        }
+        goal.cancel();
+
+        // Feedback already in flight is still delivered, then the stream ends
+        let mut last = 2;
+        while let Some(feedback) = goal.feedback().await {
+            last = feedback.timestamp;
+        }
+        assert!(last < 10);
+
+        let id = goal.id();
+        assert!(matches!(
+            goal.result().await,
+            Err(Error::GoalCanceled(canceled)) if canceled == id
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_simultaneous_goals_have_independent_feedback() {
+        let _server = count_up("test/action/parallel");
+        let client = Client::new("test/action/parallel").unwrap();
+
+        let mut short = client.send_goal(state(3)).unwrap();
+        let mut long = client.send_goal(state(5)).unwrap();
+        assert_ne!(short.id(), long.id());
+
+        let (short_feedback, long_feedback) = tokio::join!(
+            async {
+                let mut steps = Vec::new();
+                while let Some(feedback) = short.feedback().await {
+                    steps.push(feedback.timestamp);
+                }
+                steps
+            },
+            async {
+                let mut steps = Vec::new();
+                while let Some(feedback) = long.feedback().await {
+                    steps.push(feedback.timestamp);
+                }
+                steps
+            }
+        );
+        assert_eq!(short_feedback, vec![0, 1, 2]);
+        assert_eq!(long_feedback, vec![0, 1, 2, 3, 4]);
+
+        assert_eq!(short.result().await.unwrap().timestamp, 3);
+        assert_eq!(long.result().await.unwrap().timestamp, 5);
+    }
+
+    #[tokio::test]
+    async fn test_send_goal_without_server_fails_fast() {
+        let client = Client::new("test/action/missing").unwrap();
+        assert!(!client.is_available());
+        assert!(matches!(
+            client.send_goal(state(1)),
+            Err(Error::ServiceUnavailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_cancel() {
+        let _server = ActionServer::new(
+            "test/action/wait",
+            |handle: ServerGoalHandle<RobotState, RobotState>| async move {
+                handle.cancel_requested().await;
+                Err::<RobotState, _>(handle.canceled())
+            },
+        )
+        .unwrap();
+        let client = Client::new("test/action/wait").unwrap();
+
+        let goal = client.send_goal(state(0)).unwrap();
+        goal.cancel();
+        assert!(goal.is_cancel_requested());
+        assert!(matches!(goal.result().await, Err(Error::GoalCanceled(_))));
+    }
+}
@@ -0,0 +1,705 @@
+//! Lock-free bounded channels for real-time message passing
+//!
+//! [`spsc`] and [`mpsc`] channels keep their values in a ring of slots
+//! allocated when the channel is created; `try_push` and `try_pop` neither
+//! block nor allocate. Capacities are rounded up to a power of two, at least
+//! 2. The indices written by producers and by the consumer sit on separate
+//! cache lines so the two sides do not invalidate each other's.
+//!
+//! A consumer that sleeps while the channel is empty, through `pop_blocking`
+//! or `pop_timeout`, needs the channel to be created with a wakeup. Pushing
+//! then costs one more atomic load while no consumer sleeps, and a futex wake
+//! on Linux, or a condition variable notify elsewhere, when one does. Without
+//! a wakeup the consumer spins and yields instead.
+
+use crossbeam::utils::CachePadded;
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{fence, AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Slot count for a requested capacity
+fn slot_count(capacity: usize) -> usize {
+    capacity.max(2).next_power_of_two()
+}
+
+fn slots<T>(count: usize) -> Box<[UnsafeCell<MaybeUninit<T>>]> {
+    (0..count)
+        .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+        .collect()
+}
+
+/// Lets a consumer sleep until a producer pushes or the channel closes
+struct Wakeup {
+    /// Bumped by every wake, so a sleeper sees wakes it raced with
+    seq: AtomicU32,
+    sleepers: AtomicU32,
+    #[cfg(not(target_os = "linux"))]
+    lock: parking_lot::Mutex<()>,
+    #[cfg(not(target_os = "linux"))]
+    cond: parking_lot::Condvar,
+}
+
+impl Wakeup {
+    fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            sleepers: AtomicU32::new(0),
+            #[cfg(not(target_os = "linux"))]
+            lock: parking_lot::Mutex::new(()),
+            #[cfg(not(target_os = "linux"))]
+            cond: parking_lot::Condvar::new(),
+        }
+    }
+
+    /// Wake the consumer if it sleeps; called after making a value visible
+    #[inline]
+    fn notify(&self) {
+        // Pairs with the fence in `wait`: either the consumer sees the value
+        // or this sees the consumer
+        fence(Ordering::SeqCst);
+        if self.sleepers.load(Ordering::Relaxed) != 0 {
+            self.seq.fetch_add(1, Ordering::Release);
+            self.wake_all();
+        }
+    }
+
+    /// Call `ready` until it returns a value or `deadline` passes
+    fn wait<T>(
+        &self,
+        deadline: Option<Instant>,
+        mut ready: impl FnMut() -> Option<T>,
+    ) -> Option<T> {
+        loop {
+            if let Some(value) = ready() {
+                return Some(value);
+            }
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) if !left.is_zero() => Some(left),
+                    _ => return None,
+                },
+                None => None,
+            };
+            let seq = self.seq.load(Ordering::Acquire);
+            self.sleepers.fetch_add(1, Ordering::Relaxed);
+            // Pairs with the fence in `notify`
+            fence(Ordering::SeqCst);
+            let value = ready();
+            if value.is_none() {
+                self.sleep(seq, timeout);
+            }
+            self.sleepers.fetch_sub(1, Ordering::Relaxed);
+            if value.is_some() {
+                return value;
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn sleep(&self, seq: u32, timeout: Option<Duration>) {
+        let timeout = timeout.map(|timeout| libc::timespec {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_nsec: timeout.subsec_nanos() as _,
+        });
+        let timeout = timeout
+            .as_ref()
+            .map_or(std::ptr::null(), |t| t as *const libc::timespec);
+        // SAFETY: `seq` is a live aligned u32 and `timeout` is null or points
+        // to a timespec on this stack; the kernel returns right away if the
+        // word no longer holds `seq`
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                self.seq.as_ptr(),
+                libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                seq,
+                timeout,
+            );
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn wake_all(&self) {
+        // SAFETY: `seq` is a live aligned u32
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                self.seq.as_ptr(),
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                i32::MAX,
+            );
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn sleep(&self, seq: u32, timeout: Option<Duration>) {
+        let mut lock = self.lock.lock();
+        if self.seq.load(Ordering::Acquire) != seq {
+            return;
+        }
+        match timeout {
+            Some(timeout) => {
+                self.cond.wait_for(&mut lock, timeout);
+            }
+            None => self.cond.wait(&mut lock),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn wake_all(&self) {
+        let _lock = self.lock.lock();
+        self.cond.notify_all();
+    }
+}
+
+/// Wait for `ready` through `wakeup`, or by spinning without one
+fn wait_for<T>(
+    wakeup: Option<&Wakeup>,
+    deadline: Option<Instant>,
+    mut ready: impl FnMut() -> Option<T>,
+) -> Option<T> {
+    if let Some(wakeup) = wakeup {
+        return wakeup.wait(deadline, ready);
+    }
+    let mut spins = 0u32;
+    loop {
+        if let Some(value) = ready() {
+            return Some(value);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return None;
+        }
+        if spins < 64 {
+            spins += 1;
+            std::hint::spin_loop();
+        } else {
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// Single-producer single-consumer channel
+pub mod spsc {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Create a channel holding at least `capacity` values
+    pub fn channel<T: Send>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        create(capacity, None)
+    }
+
+    /// Create a channel whose consumer can sleep while it is empty
+    pub fn channel_with_wakeup<T: Send>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        create(capacity, Some(Wakeup::new()))
+    }
+
+    fn create<T: Send>(capacity: usize, wakeup: Option<Wakeup>) -> (Producer<T>, Consumer<T>) {
+        let slots = slots(slot_count(capacity));
+        let shared = Arc::new(Shared {
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            mask: slots.len() - 1,
+            slots,
+            closed: AtomicBool::new(false),
+            wakeup,
+        });
+        let producer = Producer {
+            shared: shared.clone(),
+            tail: 0,
+            head: 0,
+        };
+        let consumer = Consumer {
+            shared,
+            head: 0,
+            tail: 0,
+        };
+        (producer, consumer)
+    }
+
+    struct Shared<T> {
+        /// Next position to pop, written by the consumer
+        head: CachePadded<AtomicUsize>,
+        /// Next position to push, written by the producer
+        tail: CachePadded<AtomicUsize>,
+        mask: usize,
+        slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+        /// Set when the producer is dropped
+        closed: AtomicBool,
+        wakeup: Option<Wakeup>,
+    }
+
+    // SAFETY: a slot is only accessed by the producer before its position is
+    // published through `tail` and by the consumer after it, until `head`
+    // hands it back
+    unsafe impl<T: Send> Send for Shared<T> {}
+    unsafe impl<T: Send> Sync for Shared<T> {}
+
+    impl<T> Drop for Shared<T> {
+        fn drop(&mut self) {
+            let tail = *self.tail.get_mut();
+            let mut head = *self.head.get_mut();
+            while head != tail {
+                // SAFETY: positions between head and tail hold values
+                unsafe { self.slots[head & self.mask].get_mut().assume_init_drop() };
+                head = head.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Sending half of an SPSC channel
+    pub struct Producer<T> {
+        shared: Arc<Shared<T>>,
+        tail: usize,
+        /// Last `head` seen, so a push only reads the consumer's line when
+        /// the ring looks full
+        head: usize,
+    }
+
+    impl<T: Send> Producer<T> {
+        /// Push `value`, or hand it back if the channel is full
+        #[inline]
+        pub fn try_push(&mut self, value: T) -> Result<(), T> {
+            let shared = &*self.shared;
+            if self.tail.wrapping_sub(self.head) == shared.slots.len() {
+                self.head = shared.head.load(Ordering::Acquire);
+                if self.tail.wrapping_sub(self.head) == shared.slots.len() {
+                    return Err(value);
+                }
+            }
+            // SAFETY: the slot is free and only this producer writes it
+            unsafe { (*shared.slots[self.tail & shared.mask].get()).write(value) };
+            self.tail = self.tail.wrapping_add(1);
+            shared.tail.store(self.tail, Ordering::Release);
+            if let Some(wakeup) = &shared.wakeup {
+                wakeup.notify();
+            }
+            Ok(())
+        }
+
+        /// Number of slots
+        pub fn capacity(&self) -> usize {
+            self.shared.slots.len()
+        }
+    }
+
+    impl<T> Drop for Producer<T> {
+        fn drop(&mut self) {
+            self.shared.closed.store(true, Ordering::Release);
+            if let Some(wakeup) = &self.shared.wakeup {
+                wakeup.notify();
+            }
+        }
+    }
+
+    /// Receiving half of an SPSC channel
+    pub struct Consumer<T> {
+        shared: Arc<Shared<T>>,
+        head: usize,
+        /// Last `tail` seen
+        tail: usize,
+    }
+
+    impl<T: Send> Consumer<T> {
+        /// Pop the oldest value, if any
+        #[inline]
+        pub fn try_pop(&mut self) -> Option<T> {
+            let shared = &*self.shared;
+            if self.head == self.tail {
+                self.tail = shared.tail.load(Ordering::Acquire);
+                if self.head == self.tail {
+                    return None;
+                }
+            }
+            // SAFETY: the producer published the slot and will not touch it
+            // until `head` moves past it
+            let value =
+                unsafe { (*shared.slots[self.head & shared.mask].get()).assume_init_read() };
+            self.head = self.head.wrapping_add(1);
+            shared.head.store(self.head, Ordering::Release);
+            Some(value)
+        }
+
+        /// Wait for the next value, or `None` once the producer is gone and
+        /// the channel is empty
+        pub fn pop_blocking(&mut self) -> Option<T> {
+            self.pop_until(None)
+        }
+
+        /// Wait at most `timeout` for the next value
+        pub fn pop_timeout(&mut self, timeout: Duration) -> Option<T> {
+            self.pop_until(Some(Instant::now() + timeout))
+        }
+
+        fn pop_until(&mut self, deadline: Option<Instant>) -> Option<T> {
+            let shared = self.shared.clone();
+            wait_for(shared.wakeup.as_ref(), deadline, || {
+                match self.try_pop() {
+                    Some(value) => Some(Some(value)),
+                    // Check again: the last value may have come with the close
+                    None if shared.closed.load(Ordering::Acquire) => Some(self.try_pop()),
+                    None => None,
+                }
+            })
+            .flatten()
+        }
+
+        /// Number of queued values
+        pub fn len(&self) -> usize {
+            let tail = self.shared.tail.load(Ordering::Acquire);
+            tail.wrapping_sub(self.head)
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Whether the producer was dropped
+        pub fn is_closed(&self) -> bool {
+            self.shared.closed.load(Ordering::Acquire)
+        }
+    }
+}
+
+/// Multi-producer single-consumer channel
+///
+/// Producers claim slots with a compare-and-swap on the shared tail, so a
+/// push may retry under contention but never waits for another producer to
+/// finish. Based on Dmitry Vyukov's bounded queue.
+pub mod mpsc {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Create a channel holding at least `capacity` values
+    pub fn channel<T: Send>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        split(Ring::new(capacity, false))
+    }
+
+    /// Create a channel whose consumer can sleep while it is empty
+    pub fn channel_with_wakeup<T: Send>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        split(Ring::new(capacity, true))
+    }
+
+    fn split<T: Send>(ring: Ring<T>) -> (Producer<T>, Consumer<T>) {
+        ring.producers.store(1, Ordering::Relaxed);
+        let ring = Arc::new(ring);
+        (Producer { ring: ring.clone() }, Consumer { ring })
+    }
+
+    struct Slot<T> {
+        /// Equals the slot's position when free for a push to it, and the
+        /// position plus one once it holds the value pushed there
+        seq: AtomicUsize,
+        value: UnsafeCell<MaybeUninit<T>>,
+    }
+
+    /// The ring behind an MPSC channel
+    ///
+    /// Popping is safe from any thread too, which lets a producer make room
+    /// by discarding the oldest value.
+    pub(crate) struct Ring<T> {
+        head: CachePadded<AtomicUsize>,
+        tail: CachePadded<AtomicUsize>,
+        mask: usize,
+        slots: Box<[Slot<T>]>,
+        producers: AtomicUsize,
+        closed: AtomicBool,
+        wakeup: Option<Wakeup>,
+    }
+
+    // SAFETY: a slot's value is only accessed by the thread that claimed its
+    // position, and `seq` orders the hand-over between threads
+    unsafe impl<T: Send> Send for Ring<T> {}
+    unsafe impl<T: Send> Sync for Ring<T> {}
+
+    impl<T: Send> Ring<T> {
+        pub(crate) fn new(capacity: usize, wakeup: bool) -> Self {
+            let slots: Box<[Slot<T>]> = (0..slot_count(capacity))
+                .map(|position| Slot {
+                    seq: AtomicUsize::new(position),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect();
+            Self {
+                head: CachePadded::new(AtomicUsize::new(0)),
+                tail: CachePadded::new(AtomicUsize::new(0)),
+                mask: slots.len() - 1,
+                slots,
+                producers: AtomicUsize::new(0),
+                closed: AtomicBool::new(false),
+                wakeup: wakeup.then(Wakeup::new),
+            }
+        }
+
+        pub(crate) fn push(&self, value: T) -> Result<(), T> {
+            let mut position = self.tail.load(Ordering::Relaxed);
+            loop {
+                let slot = &self.slots[position & self.mask];
+                let seq = slot.seq.load(Ordering::Acquire);
+                match (seq.wrapping_sub(position) as isize).signum() {
+                    0 => match self.tail.compare_exchange_weak(
+                        position,
+                        position.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => {
+                            // SAFETY: the position was claimed by this push
+                            unsafe { (*slot.value.get()).write(value) };
+                            slot.seq.store(position.wrapping_add(1), Ordering::Release);
+                            if let Some(wakeup) = &self.wakeup {
+                                wakeup.notify();
+                            }
+                            return Ok(());
+                        }
+                        Err(current) => position = current,
+                    },
+                    // The slot still holds the value from one lap ago
+                    -1 => return Err(value),
+                    _ => position = self.tail.load(Ordering::Relaxed),
+                }
+            }
+        }
+
+        pub(crate) fn pop(&self) -> Option<T> {
+            let mut position = self.head.load(Ordering::Relaxed);
+            loop {
+                let slot = &self.slots[position & self.mask];
+                let seq = slot.seq.load(Ordering::Acquire);
+                let full = position.wrapping_add(1);
+                match (seq.wrapping_sub(full) as isize).signum() {
+                    0 => match self.head.compare_exchange_weak(
+                        position,
+                        full,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => {
+                            // SAFETY: the position was claimed by this pop
+                            // and its value was published through `seq`
+                            let value = unsafe { (*slot.value.get()).assume_init_read() };
+                            let free = position.wrapping_add(self.slots.len());
+                            slot.seq.store(free, Ordering::Release);
+                            return Some(value);
+                        }
+                        Err(current) => position = current,
+                    },
+                    // Not pushed yet
+                    -1 => return None,
+                    _ => position = self.head.load(Ordering::Relaxed),
+                }
+            }
+        }
+
+        /// Wait until a value is popped, `None` once closed and empty, or
+        /// `deadline` passes
+        pub(crate) fn pop_until(&self, deadline: Option<Instant>) -> Option<T> {
+            wait_for(self.wakeup.as_ref(), deadline, || match self.pop() {
+                Some(value) => Some(Some(value)),
+                None if self.closed.load(Ordering::Acquire) => Some(self.pop()),
+                None => None,
+            })
+            .flatten()
+        }
+
+        /// Number of queued values, possibly including pushes in progress
+        pub(crate) fn len(&self) -> usize {
+            let head = self.head.load(Ordering::Acquire);
+            let tail = self.tail.load(Ordering::Acquire);
+            tail.wrapping_sub(head).min(self.slots.len())
+        }
+
+        pub(crate) fn capacity(&self) -> usize {
+            self.slots.len()
+        }
+
+        /// Let a waiting consumer return once the ring is empty
+        pub(crate) fn close(&self) {
+            self.closed.store(true, Ordering::Release);
+            if let Some(wakeup) = &self.wakeup {
+                wakeup.notify();
+            }
+        }
+    }
+
+    impl<T> Drop for Ring<T> {
+        fn drop(&mut self) {
+            let tail = *self.tail.get_mut();
+            let mut head = *self.head.get_mut();
+            while head != tail {
+                // SAFETY: with exclusive access, positions between head and
+                // tail hold values
+                unsafe {
+                    self.slots[head & self.mask]
+                        .value
+                        .get_mut()
+                        .assume_init_drop()
+                };
+                head = head.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Sending half of an MPSC channel; clone it for more producers
+    pub struct Producer<T: Send> {
+        ring: Arc<Ring<T>>,
+    }
+
+    impl<T: Send> Producer<T> {
+        /// Push `value`, or hand it back if the channel is full
+        #[inline]
+        pub fn try_push(&self, value: T) -> Result<(), T> {
+            self.ring.push(value)
+        }
+
+        /// Number of slots
+        pub fn capacity(&self) -> usize {
+            self.ring.capacity()
+        }
+    }
+
+    impl<T: Send> Clone for Producer<T> {
+        fn clone(&self) -> Self {
+            self.ring.producers.fetch_add(1, Ordering::Relaxed);
+            Self {
+                ring: self.ring.clone(),
+            }
+        }
+    }
+
+    impl<T: Send> Drop for Producer<T> {
+        fn drop(&mut self) {
+            if self.ring.producers.fetch_sub(1, Ordering::AcqRel) == 1 {
+                self.ring.close();
+            }
+        }
+    }
+
+    /// Receiving half of an MPSC channel
+    pub struct Consumer<T: Send> {
+        ring: Arc<Ring<T>>,
+    }
+
+    impl<T: Send> Consumer<T> {
+        /// Pop the oldest value, if any
+        #[inline]
+        pub fn try_pop(&mut self) -> Option<T> {
+            self.ring.pop()
+        }
+
+        /// Wait for the next value, or `None` once every producer is gone
+        /// and the channel is empty
+        pub fn pop_blocking(&mut self) -> Option<T> {
+            self.ring.pop_until(None)
+        }
+
+        /// Wait at most `timeout` for the next value
+        pub fn pop_timeout(&mut self, timeout: Duration) -> Option<T> {
+            self.ring.pop_until(Some(Instant::now() + timeout))
+        }
+
+        /// Number of queued values
+        pub fn len(&self) -> usize {
+            self.ring.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Whether every producer was dropped
+        pub fn is_closed(&self) -> bool {
+            self.ring.closed.load(Ordering::Acquire)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_spsc_fifo_and_full() {
+        let (mut tx, mut rx) = spsc::channel(3);
+        assert_eq!(tx.capacity(), 4);
+        for i in 0..4 {
+            tx.try_push(i).unwrap();
+        }
+        assert_eq!(tx.try_push(4), Err(4));
+        assert_eq!(rx.len(), 4);
+        assert_eq!(rx.try_pop(), Some(0));
+        tx.try_push(4).unwrap();
+        let popped: Vec<_> = std::iter::from_fn(|| rx.try_pop()).collect();
+        assert_eq!(popped, vec![1, 2, 3, 4]);
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn test_spsc_across_threads_with_wakeup() {
+        let (mut tx, mut rx) = spsc::channel_with_wakeup(16);
+        let producer = std::thread::spawn(move || {
+            for i in 0..100_000u64 {
+                let mut value = i;
+                while let Err(v) = tx.try_push(value) {
+                    value = v;
+                    std::hint::spin_loop();
+                }
+            }
+        });
+        let mut expected = 0;
+        while let Some(value) = rx.pop_blocking() {
+            assert_eq!(value, expected);
+            expected += 1;
+        }
+        producer.join().unwrap();
+        assert_eq!(expected, 100_000);
+        assert!(rx.is_closed());
+    }
+
+    #[test]
+    fn test_mpsc_keeps_every_value() {
+        let (tx, mut rx) = mpsc::channel_with_wakeup(64);
+        let producers: Vec<_> = (0..4u64)
+            .map(|p| {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    for i in 0..10_000u64 {
+                        let mut value = (p << 32) | i;
+                        while let Err(v) = tx.try_push(value) {
+                            value = v;
+                            std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut next = [0u64; 4];
+        while let Some(value) = rx.pop_blocking() {
+            let (p, i) = ((value >> 32) as usize, value & 0xffff_ffff);
+            // Values of one producer stay in order
+            assert_eq!(i, next[p]);
+            next[p] += 1;
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert_eq!(next, [10_000; 4]);
+    }
+
+    #[test]
+    fn test_pop_timeout_and_drop() {
+        let (tx, mut rx) = mpsc::channel_with_wakeup::<Arc<()>>(2);
+        let start = Instant::now();
+        assert!(rx.pop_timeout(Duration::from_millis(20)).is_none());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // Values left in the ring are dropped with it
+        let value = Arc::new(());
+        tx.try_push(value.clone()).unwrap();
+        drop((tx, rx));
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
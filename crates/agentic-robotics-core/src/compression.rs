@@ -0,0 +1,250 @@
+//! Payload compression
+//!
+//! Used for the chunks of bags and for messages sent by the UDP and TCP
+//! transports on behalf of publishers that ask for it with
+//! [`PublisherOptions::compression`](crate::PublisherOptions::compression).
+//! Each codec is behind a feature, `zstd` and `lz4`. A build without one can
+//! neither write nor read payloads compressed with it, so the transports
+//! only compress for peers that announce the codec and send everything else
+//! as is.
+
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Largest message a transport decompresses; longer ones are dropped
+/// rather than allocated
+pub(crate) const MAX_DECOMPRESSED_LEN: usize = 64 << 20;
+
+/// How payloads are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    /// zstd at the given level (1-22)
+    #[cfg(feature = "zstd")]
+    Zstd { level: i32 },
+    /// LZ4, faster than zstd at a lower ratio
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Compression {
+    pub(crate) fn codec(self) -> Codec {
+        match self {
+            Compression::None => Codec::None,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { .. } => Codec::Zstd,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Codec::Lz4,
+        }
+    }
+}
+
+/// Codec of a payload as recorded in bags and transport headers
+///
+/// Unlike [`Compression`] it names every codec, so a build lacking one
+/// still recognizes what it cannot read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub(crate) enum Codec {
+    None = 0,
+    Zstd = 1,
+    Lz4 = 2,
+}
+
+impl Codec {
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Codec::None),
+            1 => Some(Codec::Zstd),
+            2 => Some(Codec::Lz4),
+            _ => None,
+        }
+    }
+
+    /// Codecs this build decompresses, announced to remote publishers
+    pub(crate) fn supported() -> Vec<Codec> {
+        let mut codecs = Vec::new();
+        if cfg!(feature = "zstd") {
+            codecs.push(Codec::Zstd);
+        }
+        if cfg!(feature = "lz4") {
+            codecs.push(Codec::Lz4);
+        }
+        codecs
+    }
+
+    fn feature(self) -> &'static str {
+        match self {
+            Codec::None => "",
+            Codec::Zstd => "zstd",
+            Codec::Lz4 => "lz4",
+        }
+    }
+}
+
+/// A publisher's compression settings, carried by its samples to the
+/// transports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct CompressionPolicy {
+    pub(crate) compression: Compression,
+    /// Payloads shorter than this are sent as is
+    pub(crate) threshold: usize,
+}
+
+impl CompressionPolicy {
+    /// The codec for `len` bytes sent to a peer that decompresses
+    /// `accepts`, before seeing whether compressing pays off
+    pub(crate) fn codec(&self, len: usize, accepts: &[Codec]) -> Codec {
+        let codec = self.compression.codec();
+        if len < self.threshold || !accepts.contains(&codec) {
+            return Codec::None;
+        }
+        codec
+    }
+
+    /// `payload` as sent to a peer that decompresses `accepts`, with the
+    /// codec it ends up compressed with
+    ///
+    /// Falls back to [`Codec::None`] below the threshold, for peers lacking
+    /// the codec, and when compressing would not make the payload shorter.
+    pub(crate) fn apply<'a>(
+        &self,
+        payload: &'a [u8],
+        accepts: &[Codec],
+    ) -> Result<(Codec, Cow<'a, [u8]>)> {
+        let codec = self.codec(payload.len(), accepts);
+        if codec == Codec::None {
+            return Ok((Codec::None, Cow::Borrowed(payload)));
+        }
+        let compressed = compress(self.compression, payload)?;
+        if compressed.len() >= payload.len() {
+            return Ok((Codec::None, Cow::Borrowed(payload)));
+        }
+        Ok((codec, Cow::Owned(compressed)))
+    }
+}
+
+/// `data` compressed with `compression`
+pub(crate) fn compress(compression: Compression, data: &[u8]) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd { level } => Ok(zstd::bulk::compress(data, level)?),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => Ok(lz4_flex::block::compress(data)),
+    }
+}
+
+/// The `len` bytes `data` was compressed from with `codec`
+pub(crate) fn decompress(codec: Codec, data: &[u8], len: usize) -> Result<Vec<u8>> {
+    let decompressed = match codec {
+        Codec::None => data.to_vec(),
+        #[cfg(feature = "zstd")]
+        Codec::Zstd => zstd::bulk::decompress(data, len)?,
+        #[cfg(feature = "lz4")]
+        Codec::Lz4 => lz4_flex::block::decompress(data, len)
+            .map_err(|e| Error::Serialization(format!("corrupt lz4 payload: {}", e)))?,
+        #[allow(unreachable_patterns)]
+        codec => {
+            return Err(Error::Serialization(format!(
+                "payload is {0}-compressed; enable the `{0}` feature",
+                codec.feature()
+            )))
+        }
+    };
+    if decompressed.len() != len {
+        return Err(Error::Serialization(format!(
+            "payload decompressed to {} bytes instead of {}",
+            decompressed.len(),
+            len
+        )));
+    }
+    Ok(decompressed)
+}
+
+/// A message received by a transport, decompressed to its `len` bytes
+pub(crate) fn expand(codec: Codec, payload: Vec<u8>, len: usize) -> Result<Vec<u8>> {
+    if codec == Codec::None {
+        return Ok(payload);
+    }
+    if len > MAX_DECOMPRESSED_LEN {
+        return Err(Error::Serialization(format!(
+            "{} byte message exceeds the {} byte limit",
+            len, MAX_DECOMPRESSED_LEN
+        )));
+    }
+    decompress(codec, &payload, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "zstd", feature = "lz4"))]
+    fn policy(compression: Compression, threshold: usize) -> CompressionPolicy {
+        CompressionPolicy {
+            compression,
+            threshold,
+        }
+    }
+
+    #[test]
+    fn test_uncompressed_policy_borrows() {
+        let payload = vec![7u8; 4096];
+        let (codec, sent) = CompressionPolicy::default()
+            .apply(&payload, &Codec::supported())
+            .unwrap();
+        assert_eq!(codec, Codec::None);
+        assert!(matches!(sent, Cow::Borrowed(_)));
+        assert!(Codec::from_u8(3).is_none());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_roundtrip_and_fallback() {
+        let payload = vec![7u8; 4096];
+        let zstd = policy(Compression::Zstd { level: 3 }, 1024);
+        let (codec, sent) = zstd.apply(&payload, &[Codec::Zstd]).unwrap();
+        assert_eq!(codec, Codec::Zstd);
+        assert!(sent.len() < payload.len());
+        assert_eq!(
+            expand(codec, sent.into_owned(), payload.len()).unwrap(),
+            payload
+        );
+
+        // A peer without zstd, or a payload under the threshold, goes as is
+        assert_eq!(zstd.apply(&payload, &[Codec::Lz4]).unwrap().0, Codec::None);
+        assert_eq!(
+            zstd.apply(&payload[..100], &[Codec::Zstd]).unwrap().0,
+            Codec::None
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4_roundtrip_and_limits() {
+        let payload: Vec<u8> = (0..8192).map(|i| (i % 16) as u8).collect();
+        let lz4 = policy(Compression::Lz4, 0);
+        let (codec, sent) = lz4.apply(&payload, &[Codec::Lz4]).unwrap();
+        assert_eq!(codec, Codec::Lz4);
+        let sent = sent.into_owned();
+        assert_eq!(
+            expand(Codec::Lz4, sent.clone(), payload.len()).unwrap(),
+            payload
+        );
+
+        assert!(expand(Codec::Lz4, sent.clone(), payload.len() + 1).is_err());
+        assert!(expand(Codec::Lz4, sent, MAX_DECOMPRESSED_LEN + 1).is_err());
+        // Incompressible payloads are not worth compressing
+        assert_eq!(lz4.apply(&[1, 2, 3], &[Codec::Lz4]).unwrap().0, Codec::None);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_missing_codec_is_reported() {
+        let error = decompress(Codec::Zstd, b"data", 4).unwrap_err();
+        assert!(error.to_string().contains("enable the `zstd` feature"));
+    }
+}
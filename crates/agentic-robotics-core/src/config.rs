@@ -0,0 +1,542 @@
+//! Reloading parameters from a YAML file
+//!
+//! A [`ConfigReloader`] keeps the parameters of a node in line with a YAML
+//! file of values, which operators edit while the node runs:
+//!
+//! ```yaml
+//! max_speed: 1.5
+//! controller:          # nested keys are joined with dots: controller.kp
+//!   kp: 2.0
+//!   limits: [-1.0, 1.0]
+//! ```
+//!
+//! A reload compares the file with the current values and applies only what
+//! differs, through [`ParameterServer::set_all`], so change callbacks run as
+//! for any other change. If a single value is invalid (undeclared, of the
+//! wrong type, out of range or read-only), nothing is applied and
+//! [`Error::ConfigRejected`] lists every offending key. Keys left out of the
+//! file keep their value. Every reload that changes something, or is
+//! rejected, is published as an [`Event::ConfigReload`] on [`EVENTS_TOPIC`].
+//!
+//! With the `hot-reload` feature, [`ConfigReloader::watch`] reloads the file
+//! whenever it is written:
+//!
+//! ```ignore
+//! let watcher = ConfigReloader::new("config/controller.yaml", node.parameters()).watch()?;
+//! ```
+
+use crate::error::{Error, Result};
+use crate::events::{ConfigReloadEvent, Event, EVENTS_TOPIC};
+use crate::params::{ParameterServer, ParameterValue};
+use crate::publisher::Publisher;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+#[cfg(feature = "hot-reload")]
+use std::time::Duration;
+#[cfg(feature = "hot-reload")]
+use tokio::task::JoinHandle;
+
+/// A key of a configuration file that cannot be applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigError {
+    /// Parameter name, or empty if the whole file is unreadable
+    pub key: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.key.is_empty() {
+            write!(f, "{}", self.reason)
+        } else {
+            write!(f, "{}: {}", self.key, self.reason)
+        }
+    }
+}
+
+/// A parameter whose value differs from the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterChange {
+    pub name: String,
+    /// Current value, `None` if the parameter is not declared
+    pub old: Option<ParameterValue>,
+    pub new: ParameterValue,
+}
+
+/// Applies a YAML file to a node's parameters, see the
+/// [module documentation](self)
+pub struct ConfigReloader {
+    path: PathBuf,
+    parameters: ParameterServer,
+    /// Created on the first event
+    events: Mutex<Option<Arc<Publisher<Event>>>>,
+}
+
+impl ConfigReloader {
+    pub fn new(path: impl Into<PathBuf>, parameters: &ParameterServer) -> Self {
+        Self {
+            path: path.into(),
+            parameters: parameters.clone(),
+            events: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The changes the file would make, without making them
+    ///
+    /// Fails with [`Error::Io`] if the file cannot be read, and with
+    /// [`Error::ConfigRejected`] if it is not valid YAML or holds values
+    /// that are no parameter values. A whole number is taken as a double
+    /// for a double parameter.
+    pub fn diff(&self) -> Result<Vec<ParameterChange>> {
+        let text = std::fs::read_to_string(&self.path)?;
+        let mut values = BTreeMap::new();
+        let mut errors = Vec::new();
+        match serde_yaml::from_str(&text) {
+            Ok(Value::Null) => {}
+            Ok(Value::Mapping(root)) => flatten("", &root, &mut values, &mut errors),
+            Ok(_) => errors.push(ConfigError {
+                key: String::new(),
+                reason: "expected a mapping of parameter names to values".into(),
+            }),
+            Err(e) => errors.push(ConfigError {
+                key: String::new(),
+                reason: e.to_string(),
+            }),
+        }
+
+        let mut changes = Vec::new();
+        for (name, value) in values {
+            let old = self.parameters.get_value(&name);
+            match parameter_value(value, old.as_ref()) {
+                Ok(new) if old.as_ref() == Some(&new) => {}
+                Ok(new) => changes.push(ParameterChange { name, old, new }),
+                Err(reason) => errors.push(ConfigError { key: name, reason }),
+            }
+        }
+        if errors.is_empty() {
+            Ok(changes)
+        } else {
+            Err(self.rejected(errors))
+        }
+    }
+
+    /// Read the file and apply what changed, all or nothing, returning the
+    /// changes
+    ///
+    /// Fails as [`diff`](Self::diff) does, and with
+    /// [`Error::ConfigRejected`] listing every change the parameters refuse.
+    pub async fn reload(&self) -> Result<Vec<ParameterChange>> {
+        let (changed, result) = match self.diff() {
+            Ok(changes) => {
+                let names = changes.iter().map(|change| change.name.clone()).collect();
+                (names, self.apply(changes))
+            }
+            Err(e) => (Vec::new(), Err(e)),
+        };
+        let errors = match &result {
+            Ok(changes) if changes.is_empty() => return result,
+            Ok(_) => Vec::new(),
+            Err(Error::ConfigRejected { errors, .. }) => errors.clone(),
+            Err(e) => vec![ConfigError {
+                key: String::new(),
+                reason: e.to_string(),
+            }],
+        };
+        match &result {
+            Ok(changes) => info!(
+                "Reloaded {} into {}: {} changed",
+                self.path.display(),
+                self.parameters.node(),
+                changes.len()
+            ),
+            Err(e) => warn!("{}", e),
+        }
+        self.publish(ConfigReloadEvent {
+            file: self.path.display().to_string(),
+            node: self.parameters.node().to_string(),
+            applied: result.is_ok(),
+            changed,
+            errors,
+        })
+        .await;
+        result
+    }
+
+    fn apply(&self, changes: Vec<ParameterChange>) -> Result<Vec<ParameterChange>> {
+        let errors: Vec<ConfigError> = changes
+            .iter()
+            .filter_map(|change| {
+                let error = self.parameters.validate(&change.name, &change.new).err()?;
+                let reason = match error {
+                    Error::InvalidParameter { reason, .. } => reason,
+                    other => other.to_string(),
+                };
+                Some(ConfigError {
+                    key: change.name.clone(),
+                    reason,
+                })
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(self.rejected(errors));
+        }
+        self.parameters.set_all(
+            changes
+                .iter()
+                .map(|change| (change.name.as_str(), change.new.clone())),
+        )?;
+        Ok(changes)
+    }
+
+    fn rejected(&self, errors: Vec<ConfigError>) -> Error {
+        Error::ConfigRejected {
+            file: self.path.display().to_string(),
+            errors,
+        }
+    }
+
+    async fn publish(&self, event: ConfigReloadEvent) {
+        let publisher = {
+            let mut events = self.events.lock();
+            if events.is_none() {
+                match Publisher::new(EVENTS_TOPIC) {
+                    Ok(publisher) => *events = Some(Arc::new(publisher)),
+                    Err(e) => warn!("Failed to create config reload event publisher: {}", e),
+                }
+            }
+            events.clone()
+        };
+        if let Some(publisher) = publisher {
+            if let Err(e) = publisher.publish(&Event::ConfigReload(event)).await {
+                warn!("Failed to publish config reload event: {}", e);
+            }
+        }
+    }
+}
+
+/// How long a watcher waits for an editor's save to settle before reloading
+#[cfg(feature = "hot-reload")]
+const SETTLE: Duration = Duration::from_millis(50);
+
+#[cfg(feature = "hot-reload")]
+impl ConfigReloader {
+    /// Reload the file every time it is written, until the returned watcher
+    /// is dropped
+    ///
+    /// Watches the directory of the file, so an editor that saves by
+    /// replacing the file is followed too. Must be called from within a
+    /// Tokio runtime.
+    pub fn watch(self) -> Result<ConfigWatcher> {
+        use notify::Watcher;
+
+        let file_name = self.path.file_name().map(|name| name.to_os_string());
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let (written, mut writes) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                let ours = event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == file_name.as_deref());
+                if ours && (event.kind.is_create() || event.kind.is_modify()) {
+                    let _ = written.send(());
+                }
+            })
+            .map_err(|e| Error::Configuration(format!("Cannot watch {}: {}", dir.display(), e)))?;
+        watcher
+            .watch(&dir, notify::RecursiveMode::NonRecursive)
+            .map_err(|e| Error::Configuration(format!("Cannot watch {}: {}", dir.display(), e)))?;
+
+        let reloader = Arc::new(self);
+        let task = tokio::spawn({
+            let reloader = reloader.clone();
+            async move {
+                while writes.recv().await.is_some() {
+                    tokio::time::sleep(SETTLE).await;
+                    while writes.try_recv().is_ok() {}
+                    // Failures are logged and published by reload
+                    let _ = reloader.reload().await;
+                }
+            }
+        });
+        info!(
+            "Watching {} for {}",
+            reloader.path.display(),
+            reloader.parameters.node()
+        );
+        Ok(ConfigWatcher {
+            reloader,
+            task,
+            _watcher: watcher,
+        })
+    }
+}
+
+/// Reloads a configuration file on every write, see
+/// [`ConfigReloader::watch`]
+///
+/// Dropping it stops watching.
+#[cfg(feature = "hot-reload")]
+pub struct ConfigWatcher {
+    reloader: Arc<ConfigReloader>,
+    task: JoinHandle<()>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+#[cfg(feature = "hot-reload")]
+impl ConfigWatcher {
+    pub fn reloader(&self) -> &ConfigReloader {
+        &self.reloader
+    }
+}
+
+#[cfg(feature = "hot-reload")]
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Collect the values of `mapping`, joining nested keys with dots
+fn flatten(
+    prefix: &str,
+    mapping: &Mapping,
+    values: &mut BTreeMap<String, Value>,
+    errors: &mut Vec<ConfigError>,
+) {
+    for (key, value) in mapping {
+        let Some(key) = key.as_str() else {
+            errors.push(ConfigError {
+                key: prefix.to_string(),
+                reason: format!("expected a name as key, found {:?}", key),
+            });
+            continue;
+        };
+        let name = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Mapping(nested) => flatten(&name, nested, values, errors),
+            value => {
+                values.insert(name, value.clone());
+            }
+        }
+    }
+}
+
+/// The parameter value of `value`, as a double if `current` is one
+fn parameter_value(
+    value: Value,
+    current: Option<&ParameterValue>,
+) -> std::result::Result<ParameterValue, String> {
+    match value {
+        Value::Bool(value) => Ok(value.into()),
+        Value::Number(number) => match (number.as_i64(), current) {
+            (Some(value), Some(ParameterValue::Double(_))) => Ok((value as f64).into()),
+            (Some(value), _) => Ok(value.into()),
+            (None, _) => Ok(number.as_f64().unwrap_or(f64::NAN).into()),
+        },
+        Value::String(value) => Ok(value.into()),
+        Value::Sequence(items) => items
+            .iter()
+            .map(|item| {
+                item.as_f64()
+                    .ok_or("expected a list of numbers".to_string())
+            })
+            .collect::<std::result::Result<Vec<f64>, _>>()
+            .map(ParameterValue::DoubleArray),
+        Value::Null => Err("has no value".into()),
+        _ => Err("expected a bool, number, string or list of numbers".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::ParameterDescriptor;
+    use crate::subscriber::Subscriber;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    fn config_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ros3_config_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("controller.yaml")
+    }
+
+    /// A controller's parameters, counting the changes of `kp`
+    fn controller(node: &str) -> (ParameterServer, Arc<AtomicU64>) {
+        let params = ParameterServer::new(node);
+        let gain = ParameterDescriptor::new("Proportional gain").with_range(0.0, 10.0);
+        params.declare("kp", 1.0, gain).unwrap();
+        params
+            .declare("frame", "base_link", ParameterDescriptor::default())
+            .unwrap();
+        params
+            .declare("rate_hz", 50i64, ParameterDescriptor::default().read_only())
+            .unwrap();
+        let changes = Arc::new(AtomicU64::new(0));
+        let counted = changes.clone();
+        params
+            .on_change("kp", move |_| {
+                counted.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap();
+        (params, changes)
+    }
+
+    async fn next_reload(events: &Subscriber<Event>, node: &str) -> ConfigReloadEvent {
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            match event {
+                Event::ConfigReload(event) if event.node == node => return event,
+                _ => {}
+            }
+        }
+    }
+
+    fn keys(errors: &[ConfigError]) -> Vec<&str> {
+        errors.iter().map(|error| error.key.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_invalid_edit_then_valid_edit() {
+        let (params, kp_changes) = controller("test_config_reload");
+        let events = Subscriber::<Event>::new(EVENTS_TOPIC).unwrap();
+        let path = config_file("reload");
+        let reloader = ConfigReloader::new(&path, &params);
+
+        std::fs::write(&path, "kp: 1.0\nframe: base_link\n").unwrap();
+        assert!(reloader.reload().await.unwrap().is_empty());
+
+        std::fs::write(&path, "kp: 20.0\nframe: odom\nrate_hz: 100\nki: 0.1\n").unwrap();
+        let error = reloader.reload().await.unwrap_err();
+        let Error::ConfigRejected { errors, .. } = &error else {
+            panic!("expected a rejected reload, got {}", error);
+        };
+        assert_eq!(keys(errors), ["ki", "kp", "rate_hz"]);
+        assert_eq!(errors[1].reason, "value outside [0, 10]");
+        assert!(
+            error.to_string().contains("rate_hz: is read-only"),
+            "{}",
+            error
+        );
+        assert_eq!(params.get::<String>("frame").unwrap(), "base_link");
+        assert_eq!(kp_changes.load(Ordering::Relaxed), 0);
+
+        let event = next_reload(&events, "test_config_reload").await;
+        assert!(!event.applied);
+        assert_eq!(event.changed, ["frame", "ki", "kp", "rate_hz"]);
+        assert_eq!(keys(&event.errors), ["ki", "kp", "rate_hz"]);
+
+        // A whole number is fine for a double
+        std::fs::write(&path, "kp: 2\nframe: odom\nrate_hz: 50\n").unwrap();
+        let changes = reloader.reload().await.unwrap();
+        assert_eq!(
+            changes,
+            [
+                ParameterChange {
+                    name: "frame".into(),
+                    old: Some("base_link".into()),
+                    new: "odom".into(),
+                },
+                ParameterChange {
+                    name: "kp".into(),
+                    old: Some(ParameterValue::Double(1.0)),
+                    new: ParameterValue::Double(2.0),
+                },
+            ]
+        );
+        assert_eq!(params.get::<f64>("kp").unwrap(), 2.0);
+        assert_eq!(kp_changes.load(Ordering::Relaxed), 1);
+
+        let event = next_reload(&events, "test_config_reload").await;
+        assert!(event.applied);
+        assert_eq!(event.changed, ["frame", "kp"]);
+        assert!(event.errors.is_empty());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_diff_of_nested_and_broken_files() {
+        let params = ParameterServer::new("test_config_diff");
+        params
+            .declare(
+                "controller.limits",
+                vec![-1.0, 1.0],
+                ParameterDescriptor::default(),
+            )
+            .unwrap();
+        let path = config_file("diff");
+        let reloader = ConfigReloader::new(&path, &params);
+
+        std::fs::write(&path, "controller:\n  limits: [-2, 2]\n  kd: {}\n").unwrap();
+        let changes = reloader.diff().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].name, "controller.limits");
+        assert_eq!(changes[0].new, ParameterValue::DoubleArray(vec![-2.0, 2.0]));
+
+        std::fs::write(&path, "controller:\n  limits: [a, b]\n  kd: ~\n").unwrap();
+        let Err(Error::ConfigRejected { errors, .. }) = reloader.diff() else {
+            panic!("expected a rejected diff");
+        };
+        assert_eq!(keys(&errors), ["controller.kd", "controller.limits"]);
+
+        std::fs::write(&path, "controller: [limits\n").unwrap();
+        let Err(Error::ConfigRejected { errors, .. }) = reloader.diff() else {
+            panic!("expected a rejected diff");
+        };
+        assert_eq!(keys(&errors), [""]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(feature = "hot-reload")]
+    #[tokio::test]
+    async fn test_watch_applies_edits() {
+        let (params, _) = controller("test_config_watch");
+        let events = Subscriber::<Event>::new(EVENTS_TOPIC).unwrap();
+        let path = config_file("watch");
+        std::fs::write(&path, "kp: 1.0\n").unwrap();
+        let watcher = ConfigReloader::new(&path, &params).watch().unwrap();
+        assert_eq!(watcher.reloader().path(), path);
+
+        std::fs::write(&path, "kp: -1.0\n").unwrap();
+        let event = next_reload(&events, "test_config_watch").await;
+        assert!(!event.applied);
+        assert_eq!(keys(&event.errors), ["kp"]);
+        assert_eq!(params.get::<f64>("kp").unwrap(), 1.0);
+
+        // Saved the way editors do, by replacing the file
+        let saved = path.with_extension("yaml.tmp");
+        std::fs::write(&saved, "kp: 4.5\n").unwrap();
+        std::fs::rename(&saved, &path).unwrap();
+        let event = next_reload(&events, "test_config_watch").await;
+        assert!(event.applied, "{:?}", event.errors);
+        assert_eq!(params.get::<f64>("kp").unwrap(), 4.5);
+
+        drop(watcher);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
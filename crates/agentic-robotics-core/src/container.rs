@@ -0,0 +1,678 @@
+//! Several nodes in one process
+//!
+//! A [`NodeContainer`] runs nodes made by registered factories as tasks on
+//! the current Tokio runtime, so they talk to each other in-process instead
+//! of over a transport. Nodes are loaded and unloaded while the container
+//! runs:
+//!
+//! ```no_run
+//! # use agentic_robotics_core::container::{LoadOptions, NodeContainer};
+//! # async fn run() -> agentic_robotics_core::error::Result<()> {
+//! let container = NodeContainer::new("perception");
+//! container.register("camera_driver", |node| async move {
+//!     let fps: f64 = node.parameters().get("fps")?;
+//!     // ... publish images until unloaded
+//!     Ok(())
+//! });
+//! let camera = container.load("camera_driver", LoadOptions::new().parameter("fps", 30.0))?;
+//! container.unload(camera);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each loaded node runs in a task of its own, so a panic only ends that
+//! node. Its endpoints are torn down and, as the container's
+//! [`RestartPolicy`] allows, the factory runs again on a fresh node. Loaded
+//! nodes of every container are listed by
+//! [`graph::list_loaded_nodes`](crate::graph::list_loaded_nodes).
+
+use crate::error::{Error, Result};
+use crate::name::{Remap, TopicName};
+use crate::node::Node;
+use crate::params::{ParameterDescriptor, ParameterValue};
+use crate::time::Clock;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::Duration;
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{error, info, warn};
+
+/// What happens to a supervised task that panics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    /// The task fails on its first panic
+    #[default]
+    Never,
+    /// Run the task again up to `max_restarts` times, waiting `backoff` on
+    /// the executor's clock before each restart
+    OnPanic {
+        max_restarts: u32,
+        backoff: Duration,
+    },
+}
+
+pub(crate) type NodeFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+pub(crate) type Factory = Arc<dyn Fn(Arc<Node>) -> NodeFuture + Send + Sync>;
+
+/// How to load a node, see [`NodeContainer::load`]
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    name: Option<String>,
+    namespace: Option<String>,
+    remaps: Vec<(String, String)>,
+    parameters: Vec<(String, ParameterValue)>,
+}
+
+impl LoadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the node `name` instead of after its factory
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Put the node in `namespace`; a relative one is resolved against the
+    /// container's
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// Remap the node's topic `from` to `to`
+    pub fn remap(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.remaps.push((from.into(), to.into()));
+        self
+    }
+
+    /// Declare the parameter `name` on the node before its factory runs
+    pub fn parameter(mut self, name: impl Into<String>, value: impl Into<ParameterValue>) -> Self {
+        self.parameters.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// State of a loaded node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum NodeStatus {
+    Running {
+        restarts: u32,
+    },
+    /// Waiting out the backoff after a panic
+    Restarting {
+        restarts: u32,
+    },
+    /// The factory's future returned; the node stays loaded until unloaded
+    Completed {
+        restarts: u32,
+    },
+    /// The factory's future failed, or panicked with no restarts left
+    Failed {
+        restarts: u32,
+        error: String,
+    },
+}
+
+/// A node loaded into a container, as listed by
+/// [`graph::list_loaded_nodes`](crate::graph::list_loaded_nodes)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadedNode {
+    /// Container-unique id, as returned by [`NodeContainer::load`]
+    pub id: u64,
+    pub container: String,
+    pub factory: String,
+    pub name: String,
+    pub namespace: String,
+    pub status: NodeStatus,
+}
+
+struct Slot {
+    info: LoadedNode,
+    /// The node of the current run
+    node: Option<Arc<Node>>,
+    /// The current run of the factory
+    run: Option<AbortHandle>,
+    supervisor: Option<JoinHandle<()>>,
+}
+
+impl Slot {
+    fn stop(&mut self) {
+        if let Some(supervisor) = self.supervisor.take() {
+            supervisor.abort();
+        }
+        if let Some(run) = self.run.take() {
+            run.abort();
+        }
+        if let Some(node) = self.node.take() {
+            node.shutdown();
+        }
+    }
+}
+
+struct ContainerState {
+    name: String,
+    settings: RwLock<Settings>,
+    factories: RwLock<HashMap<String, Factory>>,
+    nodes: Mutex<BTreeMap<u64, Slot>>,
+    next_id: AtomicU64,
+}
+
+/// Every live container, for graph introspection
+fn registry() -> &'static Mutex<Vec<Weak<ContainerState>>> {
+    static REGISTRY: OnceLock<Mutex<Vec<Weak<ContainerState>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// The nodes loaded into every live container, sorted by container and id
+pub(crate) fn all_loaded_nodes() -> Vec<LoadedNode> {
+    let containers: Vec<_> = {
+        let mut registry = registry().lock();
+        registry.retain(|container| container.strong_count() > 0);
+        registry.iter().filter_map(Weak::upgrade).collect()
+    };
+    let mut nodes: Vec<_> = containers
+        .iter()
+        .flat_map(|container| container.loaded())
+        .collect();
+    nodes.sort_by(|a, b| (&a.container, a.id).cmp(&(&b.container, b.id)));
+    nodes
+}
+
+/// Hosts nodes made by registered factories, see the
+/// [module documentation](self)
+///
+/// Dropping the container unloads every node.
+pub struct NodeContainer {
+    state: Arc<ContainerState>,
+}
+
+impl NodeContainer {
+    /// Create an empty container in the root namespace that never restarts
+    /// its nodes
+    pub fn new(name: impl Into<String>) -> Self {
+        let state = Arc::new(ContainerState {
+            name: name.into(),
+            settings: RwLock::new(Settings {
+                namespace: TopicName::root(),
+                policy: RestartPolicy::Never,
+                clock: Clock::default(),
+            }),
+            factories: RwLock::new(HashMap::new()),
+            nodes: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+        });
+        registry().lock().push(Arc::downgrade(&state));
+        Self { state }
+    }
+
+    /// Resolve the namespaces of loaded nodes against `namespace`
+    ///
+    /// Fails if `namespace` is not a valid absolute name.
+    pub fn with_namespace(self, namespace: &str) -> Result<Self> {
+        let namespace = TopicName::new(namespace)?;
+        if !namespace.is_absolute() {
+            return Err(Error::Configuration(format!(
+                "Container namespace '{}' is not absolute",
+                namespace.as_str()
+            )));
+        }
+        self.state.settings.write().namespace = namespace;
+        Ok(self)
+    }
+
+    /// Restart nodes that panic as `policy` allows
+    pub fn with_restart_policy(self, policy: RestartPolicy) -> Self {
+        self.state.settings.write().policy = policy;
+        self
+    }
+
+    /// Give loaded nodes `clock`, which also times restart backoffs
+    pub fn with_clock(self, clock: Clock) -> Self {
+        self.state.settings.write().clock = clock;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.state.name
+    }
+
+    /// Make nodes of kind `factory` with `make`, replacing any earlier
+    /// factory of that name
+    ///
+    /// `make` gets the freshly created node and returns the future running
+    /// it. The node is unloaded, or restarted if it panics, but stays loaded
+    /// when the future returns.
+    pub fn register<F, Fut>(&self, factory: impl Into<String>, make: F)
+    where
+        F: Fn(Arc<Node>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.register_factory(factory.into(), Arc::new(move |node| Box::pin(make(node))));
+    }
+
+    pub(crate) fn register_factory(&self, factory: String, make: Factory) {
+        self.state.factories.write().insert(factory, make);
+    }
+
+    /// Names of the registered factories, sorted
+    pub fn factories(&self) -> Vec<String> {
+        let mut factories: Vec<String> = self.state.factories.read().keys().cloned().collect();
+        factories.sort();
+        factories
+    }
+
+    /// Create a node with `factory` and start running it, returning its id
+    ///
+    /// Fails with [`Error::Configuration`] if no such factory is registered
+    /// or a node of the same name is loaded in the same namespace, and if
+    /// the options hold an invalid name, namespace or remap. Must be called
+    /// from within a Tokio runtime.
+    pub fn load(&self, factory: &str, options: LoadOptions) -> Result<u64> {
+        let make = self
+            .state
+            .factories
+            .read()
+            .get(factory)
+            .cloned()
+            .ok_or_else(|| {
+                Error::Configuration(format!(
+                    "No node factory '{}'; registered: {}",
+                    factory,
+                    self.factories().join(", ")
+                ))
+            })?;
+        let settings = self.state.settings.read().clone();
+        let namespace = match &options.namespace {
+            Some(namespace) => TopicName::new(namespace.as_str())?.resolve(&settings.namespace),
+            None => settings.namespace,
+        };
+        let spec = Arc::new(NodeSpec {
+            name: options.name.unwrap_or_else(|| factory.to_string()),
+            namespace,
+            remaps: options
+                .remaps
+                .iter()
+                .map(|(from, to)| Remap::new(from, to))
+                .collect::<Result<_>>()?,
+            parameters: options.parameters,
+            clock: settings.clock,
+        });
+        // Fail here, not in the task, if the node cannot be created
+        let node = Arc::new(spec.create()?);
+
+        let mut nodes = self.state.nodes.lock();
+        if nodes.values().any(|slot| {
+            slot.info.name == spec.name && slot.info.namespace == spec.namespace.as_str()
+        }) {
+            return Err(Error::Configuration(format!(
+                "Node '{}' is already loaded in '{}'",
+                spec.name,
+                spec.namespace.as_str()
+            )));
+        }
+        let id = self.state.next_id.fetch_add(1, Ordering::Relaxed);
+        info!(container = %self.state.name, "Loading node {} ({}) as {}", spec.name, factory, id);
+        nodes.insert(
+            id,
+            Slot {
+                info: LoadedNode {
+                    id,
+                    container: self.state.name.clone(),
+                    factory: factory.to_string(),
+                    name: spec.name.clone(),
+                    namespace: spec.namespace.as_str().to_string(),
+                    status: NodeStatus::Running { restarts: 0 },
+                },
+                node: None,
+                run: None,
+                supervisor: None,
+            },
+        );
+        let supervisor = tokio::spawn(supervise(
+            Arc::downgrade(&self.state),
+            id,
+            spec,
+            make,
+            node,
+            settings.policy,
+        ));
+        if let Some(slot) = nodes.get_mut(&id) {
+            slot.supervisor = Some(supervisor);
+        }
+        Ok(id)
+    }
+
+    /// Stop the node `id` and tear down its endpoints, returning whether it
+    /// was loaded
+    pub fn unload(&self, id: u64) -> bool {
+        let Some(mut slot) = self.state.nodes.lock().remove(&id) else {
+            return false;
+        };
+        info!(container = %self.state.name, "Unloading node {} ({})", slot.info.name, id);
+        slot.stop();
+        true
+    }
+
+    /// The nodes loaded into this container, by id
+    pub fn nodes(&self) -> Vec<LoadedNode> {
+        self.state.loaded()
+    }
+
+    /// The current node of the loaded node `id`, which is replaced by a
+    /// fresh one on every restart
+    pub fn node(&self, id: u64) -> Option<Arc<Node>> {
+        self.state.nodes.lock().get(&id)?.node.clone()
+    }
+}
+
+impl Drop for NodeContainer {
+    fn drop(&mut self) {
+        for (_, mut slot) in std::mem::take(&mut *self.state.nodes.lock()) {
+            slot.stop();
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Settings {
+    namespace: TopicName,
+    policy: RestartPolicy,
+    clock: Clock,
+}
+
+impl ContainerState {
+    fn loaded(&self) -> Vec<LoadedNode> {
+        self.nodes
+            .lock()
+            .values()
+            .map(|slot| slot.info.clone())
+            .collect()
+    }
+
+    /// Update the slot of node `id`, returning `false` once it was unloaded
+    fn update(&self, id: u64, update: impl FnOnce(&mut Slot)) -> bool {
+        match self.nodes.lock().get_mut(&id) {
+            Some(slot) => {
+                update(slot);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// What a loaded node is created from, again on every restart
+struct NodeSpec {
+    name: String,
+    namespace: TopicName,
+    remaps: Vec<Remap>,
+    parameters: Vec<(String, ParameterValue)>,
+    clock: Clock,
+}
+
+impl NodeSpec {
+    fn create(&self) -> Result<Node> {
+        let node = Node::with_namespace(
+            self.name.clone(),
+            self.namespace.as_str(),
+            self.remaps.clone(),
+        )?
+        .with_clock(self.clock.clone());
+        for (name, value) in &self.parameters {
+            node.parameters()
+                .declare(name, value.clone(), ParameterDescriptor::default())?;
+        }
+        Ok(node)
+    }
+}
+
+/// Run node `id` until it completes, fails or is unloaded, restarting it as
+/// the container's policy allows
+async fn supervise(
+    container: Weak<ContainerState>,
+    id: u64,
+    spec: Arc<NodeSpec>,
+    make: Factory,
+    mut node: Arc<Node>,
+    policy: RestartPolicy,
+) {
+    let mut restarts = 0;
+    loop {
+        let run = tokio::spawn(make(node.clone()));
+        let Some(state) = container.upgrade() else {
+            run.abort();
+            return;
+        };
+        let loaded = state.update(id, |slot| {
+            slot.node = Some(node.clone());
+            slot.run = Some(run.abort_handle());
+            slot.info.status = NodeStatus::Running { restarts };
+        });
+        drop(state);
+        if !loaded {
+            run.abort();
+            node.shutdown();
+            return;
+        }
+
+        let result = run.await;
+        node.shutdown();
+        let status = match result {
+            Ok(Ok(())) => NodeStatus::Completed { restarts },
+            Ok(Err(e)) => {
+                error!("Node {} failed: {}", spec.name, e);
+                NodeStatus::Failed {
+                    restarts,
+                    error: e.to_string(),
+                }
+            }
+            // Unloaded
+            Err(e) if e.is_cancelled() => return,
+            Err(e) => {
+                let panic = panic_message(e.into_panic().as_ref());
+                error!("Node {} panicked: {}", spec.name, panic);
+                match policy {
+                    RestartPolicy::OnPanic {
+                        max_restarts,
+                        backoff,
+                    } if restarts < max_restarts => {
+                        restarts += 1;
+                        let restarting = NodeStatus::Restarting { restarts };
+                        if !set_status(&container, id, restarting) {
+                            return;
+                        }
+                        spec.clock.sleep(backoff).await;
+                        match spec.create() {
+                            Ok(fresh) => {
+                                info!(
+                                    "Restarting node {} ({}/{})",
+                                    spec.name, restarts, max_restarts
+                                );
+                                node = Arc::new(fresh);
+                                continue;
+                            }
+                            Err(e) => {
+                                warn!("Failed to recreate node {}: {}", spec.name, e);
+                                NodeStatus::Failed {
+                                    restarts,
+                                    error: e.to_string(),
+                                }
+                            }
+                        }
+                    }
+                    _ => NodeStatus::Failed {
+                        restarts,
+                        error: panic,
+                    },
+                }
+            }
+        };
+        set_status(&container, id, status);
+        return;
+    }
+}
+
+/// Set the status of node `id`, returning `false` if it is gone
+fn set_status(container: &Weak<ContainerState>, id: u64, status: NodeStatus) -> bool {
+    container
+        .upgrade()
+        .is_some_and(|state| state.update(id, |slot| slot.info.status = status))
+}
+
+/// The message of a `panic!`, which is a `&str` or `String` payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph;
+    use crate::message::RobotState;
+    use tokio::sync::mpsc;
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    fn has_topic(name: &str) -> bool {
+        graph::list_topics().iter().any(|topic| topic.name == name)
+    }
+
+    #[tokio::test]
+    async fn test_load_exchange_and_unload() {
+        let container = NodeContainer::new("test_container")
+            .with_namespace("/test/container")
+            .unwrap();
+        container.register("talker", |node| async move {
+            let period: i64 = node.parameters().get("period_ms")?;
+            let chatter = node.create_publisher::<RobotState>("chatter")?;
+            let status = node.create_publisher::<RobotState>("talker_status")?;
+            for i in 0.. {
+                let state = RobotState {
+                    timestamp: i,
+                    ..Default::default()
+                };
+                chatter.publish(&state).await?;
+                status.publish(&state).await?;
+                tokio::time::sleep(Duration::from_millis(period as u64)).await;
+            }
+            Ok(())
+        });
+        let (heard, mut received) = mpsc::unbounded_channel();
+        container.register("listener", move |node| {
+            let heard = heard.clone();
+            async move {
+                let chatter = node.create_subscriber::<RobotState>("chatter")?;
+                loop {
+                    let _ = heard.send((node.name().to_string(), chatter.recv().await?));
+                }
+            }
+        });
+
+        let talker = container
+            .load("talker", LoadOptions::new().parameter("period_ms", 2i64))
+            .unwrap();
+        let listener = container
+            .load("listener", LoadOptions::new().name("ear"))
+            .unwrap();
+        let (name, _) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "ear");
+
+        let loaded = graph::list_loaded_nodes();
+        for (id, name) in [(talker, "talker"), (listener, "ear")] {
+            let node = loaded
+                .iter()
+                .find(|node| node.container == "test_container" && node.id == id)
+                .unwrap();
+            assert_eq!(node.name, name);
+            assert_eq!(node.namespace, "/test/container");
+            assert_eq!(node.status, NodeStatus::Running { restarts: 0 });
+        }
+        assert!(container
+            .load("talker", LoadOptions::new().parameter("period_ms", 2i64))
+            .is_err());
+        assert!(container.load("camera", LoadOptions::new()).is_err());
+
+        assert!(has_topic("/test/container/talker_status"));
+        assert!(container.unload(talker));
+        assert!(!container.unload(talker));
+        assert!(!has_topic("/test/container/talker_status"));
+        assert!(graph::get_publishers("/test/container/chatter").is_empty());
+        assert_eq!(graph::get_subscribers("/test/container/chatter").len(), 1);
+        assert_eq!(container.nodes().len(), 1);
+
+        drop(container);
+        assert!(!has_topic("/test/container/chatter"));
+        assert!(graph::list_loaded_nodes()
+            .iter()
+            .all(|node| node.container != "test_container"));
+    }
+
+    #[tokio::test]
+    async fn test_panic_restarts_only_that_node() {
+        let container = NodeContainer::new("test_crashing")
+            .with_namespace("/test/crashing")
+            .unwrap()
+            .with_restart_policy(RestartPolicy::OnPanic {
+                max_restarts: 2,
+                backoff: Duration::from_millis(5),
+            });
+        container.register("crasher", |node| async move {
+            let _status = node.create_publisher::<RobotState>("crasher_status")?;
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            panic!("sensor disconnected");
+        });
+        container.register("steady", |node| async move {
+            node.closed().await;
+            Ok(())
+        });
+
+        let steady = container.load("steady", LoadOptions::new()).unwrap();
+        let crasher = container.load("crasher", LoadOptions::new()).unwrap();
+        let status = |id| {
+            container
+                .nodes()
+                .into_iter()
+                .find(|node| node.id == id)
+                .unwrap()
+                .status
+        };
+        wait_for(|| matches!(status(crasher), NodeStatus::Failed { .. })).await;
+        assert_eq!(
+            status(crasher),
+            NodeStatus::Failed {
+                restarts: 2,
+                error: "sensor disconnected".into(),
+            }
+        );
+        assert!(!has_topic("/test/crashing/crasher_status"));
+
+        assert_eq!(status(steady), NodeStatus::Running { restarts: 0 });
+        assert!(!container.node(steady).unwrap().is_shutdown());
+    }
+}
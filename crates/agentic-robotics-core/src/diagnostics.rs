@@ -0,0 +1,473 @@
+//! Diagnostics aggregation
+//!
+//! A [`DiagnosticUpdater`] publishes a [`DiagnosticArray`] on
+//! [`DIAGNOSTICS_TOPIC`] once per period. The array holds the latest
+//! [`DiagnosticStatus`] of every task registered with
+//! [`DiagnosticUpdater::register`], marked [`Stale`](DiagnosticLevel::Stale)
+//! once a task has not reported for longer than it promised, followed by
+//! whatever the updater's collectors return. [`subscriber_drops`] is a
+//! built-in collector; the rt crate provides collectors for executor metrics
+//! and watchdogs.
+
+use crate::error::{Error, Result};
+use crate::message::Message;
+use crate::publisher::Publisher;
+use crate::time::{Clock, Time};
+use crate::topic::TopicBus;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Topic diagnostics are published on by convention
+pub const DIAGNOSTICS_TOPIC: &str = "/diagnostics";
+
+/// Severity of a [`DiagnosticStatus`], ordered from best to worst
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum DiagnosticLevel {
+    #[default]
+    Ok,
+    Warn,
+    Error,
+    /// The task stopped reporting, so its last status may be out of date
+    Stale,
+}
+
+impl fmt::Display for DiagnosticLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DiagnosticLevel::Ok => "OK",
+            DiagnosticLevel::Warn => "WARN",
+            DiagnosticLevel::Error => "ERROR",
+            DiagnosticLevel::Stale => "STALE",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A named value attached to a [`DiagnosticStatus`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyValue {
+    pub key: String,
+    pub value: String,
+}
+
+impl KeyValue {
+    pub fn new(key: impl Into<String>, value: impl ToString) -> Self {
+        Self {
+            key: key.into(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// State of one component, like a battery or a subscriber
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticStatus {
+    pub level: DiagnosticLevel,
+    pub name: String,
+    /// Human-readable summary of the state
+    pub message: String,
+    pub values: Vec<KeyValue>,
+}
+
+impl DiagnosticStatus {
+    pub fn new(
+        level: DiagnosticLevel,
+        name: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            level,
+            name: name.into(),
+            message: message.into(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Attach a value
+    pub fn with_value(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.values.push(KeyValue::new(key, value));
+        self
+    }
+}
+
+/// Every status of an updater at one time, as published on
+/// [`DIAGNOSTICS_TOPIC`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticArray {
+    /// When the statuses were collected, on the updater's clock
+    pub stamp: Time,
+    pub status: Vec<DiagnosticStatus>,
+}
+
+impl DiagnosticArray {
+    /// The worst level of any status, `Ok` if there are none
+    pub fn level(&self) -> DiagnosticLevel {
+        self.status
+            .iter()
+            .map(|status| status.level)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl Message for DiagnosticArray {
+    fn type_name() -> &'static str {
+        "ros3_msgs/DiagnosticArray"
+    }
+}
+
+type Collector = Box<dyn FnMut() -> Vec<DiagnosticStatus> + Send>;
+
+/// Periodically publishes the statuses of registered tasks and collectors
+///
+/// Publishing stops when the updater is dropped; tasks registered with it
+/// then have no effect.
+pub struct DiagnosticUpdater {
+    state: Arc<UpdaterState>,
+    task: JoinHandle<()>,
+}
+
+struct UpdaterState {
+    /// Prepended to every status name, like `"lidar_node: "`
+    prefix: String,
+    clock: Clock,
+    tasks: Mutex<BTreeMap<String, TaskState>>,
+    collectors: Mutex<Vec<Collector>>,
+}
+
+struct TaskState {
+    stale_after: Duration,
+    registered: Time,
+    last: Option<(Time, DiagnosticStatus)>,
+}
+
+impl DiagnosticUpdater {
+    /// Publish on [`DIAGNOSTICS_TOPIC`] every `period`
+    ///
+    /// Must be called from within a Tokio runtime. Fails with
+    /// [`Error::Configuration`] if `period` is zero.
+    pub fn new(period: Duration) -> Result<Self> {
+        Self::start(
+            Publisher::new(DIAGNOSTICS_TOPIC)?,
+            period,
+            Clock::default(),
+            "",
+        )
+    }
+
+    /// Publish through `publisher` every `period` of `clock`, naming statuses
+    /// `"{prefix}{name}"`
+    pub(crate) fn start(
+        publisher: Publisher<DiagnosticArray>,
+        period: Duration,
+        clock: Clock,
+        prefix: &str,
+    ) -> Result<Self> {
+        if period.is_zero() {
+            return Err(Error::Configuration(
+                "Diagnostics period must be non-zero".into(),
+            ));
+        }
+        let state = Arc::new(UpdaterState {
+            prefix: prefix.to_string(),
+            clock,
+            tasks: Mutex::new(BTreeMap::new()),
+            collectors: Mutex::new(Vec::new()),
+        });
+        let updater = state.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                updater.clock.sleep(period).await;
+                if let Err(e) = publisher.publish(&updater.collect()).await {
+                    warn!("Failed to publish diagnostics: {}", e);
+                }
+            }
+        });
+        Ok(Self { state, task })
+    }
+
+    /// Register a task that reports its status through the returned handle
+    ///
+    /// The task is reported as stale until its first report and whenever
+    /// `stale_after` passes without a report. Registering a name again
+    /// replaces the earlier task.
+    pub fn register(&self, name: impl Into<String>, stale_after: Duration) -> DiagnosticTask {
+        let name = name.into();
+        self.state.tasks.lock().insert(
+            name.clone(),
+            TaskState {
+                stale_after,
+                registered: self.state.clock.now(),
+                last: None,
+            },
+        );
+        DiagnosticTask {
+            state: self.state.clone(),
+            name,
+        }
+    }
+
+    /// Stop reporting the task registered as `name`
+    pub fn remove(&self, name: &str) {
+        self.state.tasks.lock().remove(name);
+    }
+
+    /// Call `collector` on every update and publish the statuses it returns
+    pub fn add_collector<F>(&self, collector: F)
+    where
+        F: FnMut() -> Vec<DiagnosticStatus> + Send + 'static,
+    {
+        self.state.collectors.lock().push(Box::new(collector));
+    }
+
+    /// The statuses the next update would publish
+    pub fn collect(&self) -> DiagnosticArray {
+        self.state.collect()
+    }
+
+    pub(crate) fn abort_handle(&self) -> tokio::task::AbortHandle {
+        self.task.abort_handle()
+    }
+}
+
+impl Drop for DiagnosticUpdater {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl UpdaterState {
+    fn collect(&self) -> DiagnosticArray {
+        let now = self.clock.now();
+        let mut status: Vec<DiagnosticStatus> = self
+            .tasks
+            .lock()
+            .iter()
+            .map(|(name, task)| task.status(name, now))
+            .collect();
+        for collector in self.collectors.lock().iter_mut() {
+            status.extend(collector());
+        }
+        for status in &mut status {
+            status.name.insert_str(0, &self.prefix);
+        }
+        DiagnosticArray { stamp: now, status }
+    }
+}
+
+impl TaskState {
+    fn status(&self, name: &str, now: Time) -> DiagnosticStatus {
+        match &self.last {
+            None => DiagnosticStatus::new(DiagnosticLevel::Stale, name, "No report yet")
+                .with_value(
+                    "registered_for",
+                    format!("{:?}", now.saturating_duration_since(self.registered)),
+                ),
+            Some((at, status)) => {
+                let age = now.saturating_duration_since(*at);
+                if age <= self.stale_after {
+                    return status.clone();
+                }
+                DiagnosticStatus {
+                    level: DiagnosticLevel::Stale,
+                    name: name.to_string(),
+                    message: format!("No report for {:?}: {}", age, status.message),
+                    values: status.values.clone(),
+                }
+            }
+        }
+    }
+}
+
+/// Handle through which a task registered with a [`DiagnosticUpdater`]
+/// reports its status
+#[derive(Clone)]
+pub struct DiagnosticTask {
+    state: Arc<UpdaterState>,
+    name: String,
+}
+
+impl DiagnosticTask {
+    /// Record the task's current status
+    pub fn report(
+        &self,
+        level: DiagnosticLevel,
+        message: impl Into<String>,
+        values: Vec<KeyValue>,
+    ) {
+        let status = DiagnosticStatus {
+            level,
+            name: self.name.clone(),
+            message: message.into(),
+            values,
+        };
+        let now = self.state.clock.now();
+        if let Some(task) = self.state.tasks.lock().get_mut(&self.name) {
+            task.last = Some((now, status));
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Collector reporting messages dropped by full subscriber queues
+///
+/// Returns a single `subscribers` status listing the drop count of every
+/// subscriber in the process that has dropped any. It is `Warn` if a
+/// subscriber dropped messages since the previous call.
+pub fn subscriber_drops() -> impl FnMut() -> Vec<DiagnosticStatus> + Send + 'static {
+    let mut previous: HashMap<u64, u64> = HashMap::new();
+    move || {
+        let mut level = DiagnosticLevel::Ok;
+        let mut new_drops = 0;
+        let mut status = DiagnosticStatus::new(DiagnosticLevel::Ok, "subscribers", "");
+        let mut current = HashMap::new();
+        for (endpoint, dropped) in TopicBus::global().subscriber_drops() {
+            current.insert(endpoint.id, dropped);
+            if dropped == 0 {
+                continue;
+            }
+            let before = previous.get(&endpoint.id).copied().unwrap_or(0);
+            if dropped > before {
+                level = DiagnosticLevel::Warn;
+                new_drops += dropped - before;
+            }
+            let key = match &endpoint.node {
+                Some(node) => format!("{} ({} #{})", endpoint.topic, node, endpoint.id),
+                None => format!("{} (#{})", endpoint.topic, endpoint.id),
+            };
+            status.values.push(KeyValue::new(key, dropped));
+        }
+        previous = current;
+        status.level = level;
+        status.message = match new_drops {
+            0 => "No messages dropped".to_string(),
+            n => format!("{} messages dropped since the last update", n),
+        };
+        vec![status]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::SimClock;
+
+    fn updater(clock: &SimClock, topic: &str) -> DiagnosticUpdater {
+        let publisher = Publisher::new(topic).unwrap();
+        let clock = Clock::Sim(clock.clone());
+        DiagnosticUpdater::start(publisher, Duration::from_secs(1), clock, "robot: ").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_silent_task_goes_stale() {
+        let clock = SimClock::new(Time::ZERO);
+        let updater = updater(&clock, "/test/diagnostics/stale");
+        let battery = updater.register("battery", Duration::from_secs(2));
+        let idle = updater.register("idle", Duration::from_secs(2));
+
+        let array = updater.collect();
+        assert_eq!(array.level(), DiagnosticLevel::Stale);
+        assert!(array
+            .status
+            .iter()
+            .all(|s| s.level == DiagnosticLevel::Stale));
+
+        battery.report(
+            DiagnosticLevel::Warn,
+            "Battery low",
+            vec![KeyValue::new("voltage", 11.1)],
+        );
+        idle.report(DiagnosticLevel::Ok, "Idle", Vec::new());
+        let array = updater.collect();
+        assert_eq!(array.status.len(), 2);
+        assert_eq!(array.status[0].name, "robot: battery");
+        assert_eq!(array.status[0].level, DiagnosticLevel::Warn);
+        assert_eq!(array.status[0].values, [KeyValue::new("voltage", "11.1")]);
+        assert_eq!(array.level(), DiagnosticLevel::Warn);
+
+        clock.advance(Duration::from_secs(3));
+        idle.report(DiagnosticLevel::Ok, "Idle", Vec::new());
+        let array = updater.collect();
+        assert_eq!(array.stamp, clock.now());
+        let battery = &array.status[0];
+        assert_eq!(battery.level, DiagnosticLevel::Stale);
+        assert!(
+            battery.message.ends_with("Battery low"),
+            "{}",
+            battery.message
+        );
+        assert_eq!(battery.values.len(), 1);
+        assert_eq!(array.status[1].level, DiagnosticLevel::Ok);
+
+        updater.remove("battery");
+        assert_eq!(updater.collect().status.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_collectors_are_appended() {
+        let clock = SimClock::new(Time::ZERO);
+        let updater = updater(&clock, "/test/diagnostics/collectors");
+        let mut calls = 0;
+        updater.add_collector(move || {
+            calls += 1;
+            vec![DiagnosticStatus::new(
+                DiagnosticLevel::Error,
+                "motor",
+                format!("call {calls}"),
+            )]
+        });
+        updater.collect();
+        let array = updater.collect();
+        assert_eq!(array.status.len(), 1);
+        assert_eq!(array.status[0].name, "robot: motor");
+        assert_eq!(array.status[0].message, "call 2");
+        assert_eq!(array.level(), DiagnosticLevel::Error);
+        assert!(DiagnosticUpdater::new(Duration::ZERO).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_drops_warn_on_new_drops() {
+        let topic = "/test/diagnostics/drops";
+        let subscriber = crate::Subscriber::<serde_json::Value>::builder(topic)
+            .queue_depth(1)
+            .build()
+            .unwrap();
+        let publisher = Publisher::<serde_json::Value>::new(topic).unwrap();
+        let mut collect = subscriber_drops();
+        let before = collect();
+        assert_eq!(before[0].name, "subscribers");
+
+        for i in 0..3 {
+            publisher
+                .publish(&serde_json::Value::from(i))
+                .await
+                .unwrap();
+        }
+        let status = collect().remove(0);
+        assert_eq!(status.level, DiagnosticLevel::Warn);
+        let entry = status
+            .values
+            .iter()
+            .find(|v| v.key.starts_with(topic))
+            .unwrap();
+        assert_eq!(entry.value, "2");
+
+        // Counted drops alone do not warn again
+        let status = collect().remove(0);
+        assert!(status.values.iter().any(|v| v.key.starts_with(topic)));
+        drop(subscriber);
+        let status = collect().remove(0);
+        assert!(!status.values.iter().any(|v| v.key.starts_with(topic)));
+    }
+}
@@ -0,0 +1,1398 @@
+//! Messages whose type is only known at runtime
+//!
+//! A [`TypeDescriptor`] lists the fields of a message type and their types.
+//! `#[derive(Ros3Message)]` and the built-in messages provide theirs through
+//! [`Message::descriptor`](crate::Message::descriptor), the [type registry](crate::types) hands them out
+//! by type name, and tools build them at runtime for types described
+//! elsewhere.
+//!
+//! A [`DynamicMessage`] holds a message of a described type as field values.
+//! It reads and writes the same bytes as the typed message in CDR, JSON and
+//! MessagePack, and converts to and from JSON, which is what generic tooling
+//! such as the CLI, the recorder and the gateway needs:
+//!
+//! ```
+//! use agentic_robotics_core::dynamic::{DynamicMessage, DynamicValue};
+//! use agentic_robotics_core::serialization::{serialize_cdr, Format};
+//! use agentic_robotics_core::{Message, RobotState};
+//! use std::sync::Arc;
+//!
+//! let state = RobotState { position: [1.0, 2.0, 3.0], ..Default::default() };
+//! let descriptor = Arc::new(RobotState::descriptor().unwrap());
+//! let bytes = serialize_cdr(&state).unwrap();
+//!
+//! let mut msg = DynamicMessage::decode(descriptor, &bytes, Format::Cdr).unwrap();
+//! let position = msg.get("position").and_then(DynamicValue::as_f64_array);
+//! assert_eq!(position, Some(vec![1.0, 2.0, 3.0]));
+//! msg.set("timestamp", 7i64).unwrap();
+//! assert_eq!(msg.to_json()["timestamp"], 7);
+//! ```
+
+use crate::error::{Error, Result};
+use crate::message::{combine_hash, fnv1a, nullable, RawMessage};
+use crate::schema::{self, SchemaError};
+use crate::serialization::Format;
+use crate::types;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeMap, SerializeSeq, SerializeTuple, Serializer};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::sync::Arc;
+
+/// Type of a message field
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    Bool,
+    Char,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    String,
+    /// Fixed-length array, `[T; N]`
+    Array(Box<FieldType>, usize),
+    /// Variable-length sequence, `Vec<T>`
+    Sequence(Box<FieldType>),
+    /// `Option<T>`; like the typed message, only self-describing formats
+    /// can encode it, CDR cannot
+    Optional(Box<FieldType>),
+    /// Nested message
+    Message(Arc<TypeDescriptor>),
+}
+
+impl FieldType {
+    /// The value a field of this type has in [`DynamicMessage::new`]:
+    /// zero, empty, `None`, or a nested message of defaults
+    pub fn default_value(&self) -> DynamicValue {
+        match self {
+            FieldType::Bool => DynamicValue::Bool(false),
+            FieldType::Char => DynamicValue::Char('\0'),
+            FieldType::I8 | FieldType::I16 | FieldType::I32 | FieldType::I64 => {
+                DynamicValue::Int(0)
+            }
+            FieldType::U8 | FieldType::U16 | FieldType::U32 | FieldType::U64 => {
+                DynamicValue::UInt(0)
+            }
+            FieldType::F32 | FieldType::F64 => DynamicValue::Float(0.0),
+            FieldType::String => DynamicValue::String(String::new()),
+            FieldType::Array(item, len) => DynamicValue::List(vec![item.default_value(); *len]),
+            FieldType::Sequence(_) => DynamicValue::List(Vec::new()),
+            FieldType::Optional(_) => DynamicValue::None,
+            FieldType::Message(descriptor) => {
+                DynamicValue::Message(DynamicMessage::new(descriptor.clone()))
+            }
+        }
+    }
+
+    /// JSON Schema of the field's JSON form, as `#[derive(Ros3Message)]`
+    /// generates it
+    pub fn schema(&self) -> Value {
+        fn integer(min: i64, max: i64) -> Value {
+            json!({ "type": "integer", "minimum": min, "maximum": max })
+        }
+        match self {
+            FieldType::Bool => json!({ "type": "boolean" }),
+            FieldType::Char => json!({ "type": "string", "minLength": 1, "maxLength": 1 }),
+            FieldType::String => json!({ "type": "string" }),
+            FieldType::F32 | FieldType::F64 => json!({ "type": "number" }),
+            FieldType::I64 => json!({ "type": "integer" }),
+            FieldType::U64 => json!({ "type": "integer", "minimum": 0 }),
+            FieldType::I8 => integer(i8::MIN.into(), i8::MAX.into()),
+            FieldType::I16 => integer(i16::MIN.into(), i16::MAX.into()),
+            FieldType::I32 => integer(i32::MIN.into(), i32::MAX.into()),
+            FieldType::U8 => integer(0, u8::MAX.into()),
+            FieldType::U16 => integer(0, u16::MAX.into()),
+            FieldType::U32 => integer(0, u32::MAX.into()),
+            FieldType::Array(item, len) => json!({
+                "type": "array",
+                "items": item.schema(),
+                "minItems": len,
+                "maxItems": len,
+            }),
+            FieldType::Sequence(item) => json!({ "type": "array", "items": item.schema() }),
+            FieldType::Optional(inner) => nullable(inner.schema()),
+            FieldType::Message(descriptor) => descriptor.schema(),
+        }
+    }
+
+    /// The Rust spelling of the type, which the derived type hash is built
+    /// from; nested messages are named without their package
+    fn canonical(&self) -> String {
+        match self {
+            FieldType::Bool => "bool".into(),
+            FieldType::Char => "char".into(),
+            FieldType::I8 => "i8".into(),
+            FieldType::I16 => "i16".into(),
+            FieldType::I32 => "i32".into(),
+            FieldType::I64 => "i64".into(),
+            FieldType::U8 => "u8".into(),
+            FieldType::U16 => "u16".into(),
+            FieldType::U32 => "u32".into(),
+            FieldType::U64 => "u64".into(),
+            FieldType::F32 => "f32".into(),
+            FieldType::F64 => "f64".into(),
+            FieldType::String => "String".into(),
+            FieldType::Array(item, len) => format!("[{};{}]", item.canonical(), len),
+            FieldType::Sequence(item) => format!("Vec<{}>", item.canonical()),
+            FieldType::Optional(inner) => format!("Option<{}>", inner.canonical()),
+            FieldType::Message(descriptor) => descriptor.short_name().into(),
+        }
+    }
+
+    /// Hashes of the nested messages, in the order the derive macro folds
+    /// them into the type hash
+    fn nested_hashes(&self, hashes: &mut Vec<u64>) {
+        match self {
+            FieldType::Array(item, _) | FieldType::Sequence(item) | FieldType::Optional(item) => {
+                item.nested_hashes(hashes)
+            }
+            FieldType::Message(descriptor) => hashes.push(descriptor.hash),
+            _ => {}
+        }
+    }
+
+    /// `value` as a value of this type, converting between the integer
+    /// variants and from integers to floats
+    pub(crate) fn coerce(&self, value: DynamicValue) -> std::result::Result<DynamicValue, String> {
+        let mismatch = |value: &DynamicValue| format!("expected {}, found {}", self, value.kind());
+        match (self, value) {
+            (FieldType::Optional(_), DynamicValue::None) => Ok(DynamicValue::None),
+            (FieldType::Optional(inner), value) => inner.coerce(value),
+            (FieldType::Bool, value @ DynamicValue::Bool(_))
+            | (FieldType::Char, value @ DynamicValue::Char(_))
+            | (FieldType::String, value @ DynamicValue::String(_)) => Ok(value),
+            (FieldType::Char, DynamicValue::String(s)) if s.chars().count() == 1 => {
+                Ok(DynamicValue::Char(s.chars().next().unwrap_or_default()))
+            }
+            (_, DynamicValue::Int(v)) => self.integer(v.into()).ok_or_else(|| mismatch(&v.into())),
+            (_, DynamicValue::UInt(v)) => self.integer(v.into()).ok_or_else(|| mismatch(&v.into())),
+            (_, DynamicValue::Float(v)) => self.float(v).ok_or_else(|| mismatch(&v.into())),
+            (FieldType::Array(item, len), DynamicValue::List(items)) => {
+                if items.len() != *len {
+                    return Err(format!("expected {} items, found {}", len, items.len()));
+                }
+                item.coerce_items(items)
+            }
+            (FieldType::Sequence(item), DynamicValue::List(items)) => item.coerce_items(items),
+            (FieldType::Message(descriptor), DynamicValue::Message(msg))
+                if msg.descriptor.name == descriptor.name =>
+            {
+                Ok(DynamicValue::Message(msg))
+            }
+            (_, value) => Err(mismatch(&value)),
+        }
+    }
+
+    fn coerce_items(&self, items: Vec<DynamicValue>) -> std::result::Result<DynamicValue, String> {
+        let items = items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| {
+                self.coerce(item)
+                    .map_err(|e| format!("item {}: {}", index, e))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(DynamicValue::List(items))
+    }
+
+    /// An integer as a value of this type, if in range
+    fn integer(&self, v: i128) -> Option<DynamicValue> {
+        let (min, max) = match self {
+            FieldType::I8 => (i8::MIN.into(), i8::MAX.into()),
+            FieldType::I16 => (i16::MIN.into(), i16::MAX.into()),
+            FieldType::I32 => (i32::MIN.into(), i32::MAX.into()),
+            FieldType::I64 => (i64::MIN.into(), i64::MAX.into()),
+            FieldType::U8 => (0, u8::MAX.into()),
+            FieldType::U16 => (0, u16::MAX.into()),
+            FieldType::U32 => (0, u32::MAX.into()),
+            FieldType::U64 => (0, u64::MAX.into()),
+            FieldType::F32 | FieldType::F64 => return self.float(v as f64),
+            _ => return None,
+        };
+        if !(min..=max).contains(&v) {
+            return None;
+        }
+        Some(match self {
+            FieldType::U8 | FieldType::U16 | FieldType::U32 | FieldType::U64 => {
+                DynamicValue::UInt(v as u64)
+            }
+            _ => DynamicValue::Int(v as i64),
+        })
+    }
+
+    /// A float as a value of this type; `f32` fields round it to `f32`
+    fn float(&self, v: f64) -> Option<DynamicValue> {
+        match self {
+            FieldType::F32 => Some(DynamicValue::Float(v as f32 as f64)),
+            FieldType::F64 => Some(DynamicValue::Float(v)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldType::Message(descriptor) => f.write_str(&descriptor.name),
+            ty => f.write_str(&ty.canonical()),
+        }
+    }
+}
+
+/// A named field of a [`TypeDescriptor`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDescriptor {
+    pub name: String,
+    pub ty: FieldType,
+}
+
+impl FieldDescriptor {
+    pub fn new(name: impl Into<String>, ty: FieldType) -> Self {
+        Self {
+            name: name.into(),
+            ty,
+        }
+    }
+}
+
+/// A named constant of a [`TypeDescriptor`], e.g. `uint8 MODE_IDLE=0` in
+/// a message definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstantDescriptor {
+    pub name: String,
+    pub ty: FieldType,
+    pub value: DynamicValue,
+}
+
+/// The fields of a message type, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDescriptor {
+    name: String,
+    hash: u64,
+    fields: Vec<FieldDescriptor>,
+    constants: Vec<ConstantDescriptor>,
+}
+
+impl TypeDescriptor {
+    /// A descriptor of the type `name` with `fields` in serialization order
+    ///
+    /// The type hash is computed the way `#[derive(Ros3Message)]` computes
+    /// it, so a described type matches the derived struct it describes as
+    /// long as that struct names its nested messages by their type name.
+    pub fn new(name: impl Into<String>, fields: Vec<FieldDescriptor>) -> Self {
+        let name = name.into();
+        let definition: String = fields
+            .iter()
+            .map(|field| format!("{}:{};", field.name, field.ty.canonical()))
+            .collect();
+        let mut nested = Vec::new();
+        for field in &fields {
+            field.ty.nested_hashes(&mut nested);
+        }
+        let hash = nested.into_iter().fold(
+            combine_hash(fnv1a(name.as_bytes()), fnv1a(definition.as_bytes())),
+            combine_hash,
+        );
+        Self {
+            name,
+            hash,
+            fields,
+            constants: Vec::new(),
+        }
+    }
+
+    /// Use `hash` as the type hash, e.g. that of the typed message
+    pub fn with_hash(mut self, hash: u64) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    /// Attach `constants`; they are not serialized and do not change the
+    /// type hash
+    pub fn with_constants(mut self, constants: Vec<ConstantDescriptor>) -> Self {
+        self.constants = constants;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// See [`Message::type_hash`](crate::Message::type_hash)
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn fields(&self) -> &[FieldDescriptor] {
+        &self.fields
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldDescriptor> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    pub fn constants(&self) -> &[ConstantDescriptor] {
+        &self.constants
+    }
+
+    /// The value of the constant `name`
+    pub fn constant(&self, name: &str) -> Option<&DynamicValue> {
+        self.constants
+            .iter()
+            .find(|constant| constant.name == name)
+            .map(|constant| &constant.value)
+    }
+
+    /// JSON Schema of the message's JSON form, as `#[derive(Ros3Message)]`
+    /// generates it
+    pub fn schema(&self) -> Value {
+        let properties: Map<String, Value> = self
+            .fields
+            .iter()
+            .map(|field| (field.name.clone(), field.ty.schema()))
+            .collect();
+        let required: Vec<&str> = self
+            .fields
+            .iter()
+            .filter(|field| !matches!(field.ty, FieldType::Optional(_)))
+            .map(|field| field.name.as_str())
+            .collect();
+        json!({
+            "title": self.name,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+
+    /// The type name without its package, e.g. `Header` for
+    /// `std_msgs/Header`
+    fn short_name(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+}
+
+/// The value of a field of a [`DynamicMessage`]
+///
+/// Signed integers of any width are held as `Int`, unsigned ones as `UInt`
+/// and floats as `Float`; the field's [`FieldType`] decides how they are
+/// serialized.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicValue {
+    Bool(bool),
+    Char(char),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    /// Items of an array or sequence
+    List(Vec<DynamicValue>),
+    Message(DynamicMessage),
+    /// An optional field without a value
+    None,
+}
+
+impl DynamicValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            DynamicValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            DynamicValue::Char(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// An integer that fits in `i64`
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            DynamicValue::Int(v) => Some(*v),
+            DynamicValue::UInt(v) => i64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// A non-negative integer
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            DynamicValue::Int(v) => u64::try_from(*v).ok(),
+            DynamicValue::UInt(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Any number
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            DynamicValue::Int(v) => Some(*v as f64),
+            DynamicValue::UInt(v) => Some(*v as f64),
+            DynamicValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            DynamicValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The items of an array or sequence
+    pub fn as_list(&self) -> Option<&[DynamicValue]> {
+        match self {
+            DynamicValue::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// An array or sequence of numbers
+    pub fn as_f64_array(&self) -> Option<Vec<f64>> {
+        self.as_list()?.iter().map(DynamicValue::as_f64).collect()
+    }
+
+    pub fn as_message(&self) -> Option<&DynamicMessage> {
+        match self {
+            DynamicValue::Message(msg) => Some(msg),
+            _ => None,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, DynamicValue::None)
+    }
+
+    fn kind(&self) -> String {
+        match self {
+            DynamicValue::Bool(_) => "a bool".into(),
+            DynamicValue::Char(_) => "a char".into(),
+            DynamicValue::Int(v) => format!("integer {}", v),
+            DynamicValue::UInt(v) => format!("integer {}", v),
+            DynamicValue::Float(v) => format!("number {}", v),
+            DynamicValue::String(_) => "a string".into(),
+            DynamicValue::List(items) => format!("a list of {} items", items.len()),
+            DynamicValue::Message(msg) => format!("a {} message", msg.descriptor.name),
+            DynamicValue::None => "no value".into(),
+        }
+    }
+}
+
+macro_rules! from_number {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$ty> for DynamicValue {
+                fn from(v: $ty) -> Self {
+                    DynamicValue::$variant(v.into())
+                }
+            }
+        )*
+    };
+}
+
+from_number! {
+    i8 => Int, i16 => Int, i32 => Int, i64 => Int,
+    u8 => UInt, u16 => UInt, u32 => UInt, u64 => UInt,
+    f32 => Float, f64 => Float,
+}
+
+impl From<bool> for DynamicValue {
+    fn from(v: bool) -> Self {
+        DynamicValue::Bool(v)
+    }
+}
+
+impl From<char> for DynamicValue {
+    fn from(v: char) -> Self {
+        DynamicValue::Char(v)
+    }
+}
+
+impl From<&str> for DynamicValue {
+    fn from(v: &str) -> Self {
+        DynamicValue::String(v.to_string())
+    }
+}
+
+impl From<String> for DynamicValue {
+    fn from(v: String) -> Self {
+        DynamicValue::String(v)
+    }
+}
+
+impl From<DynamicMessage> for DynamicValue {
+    fn from(msg: DynamicMessage) -> Self {
+        DynamicValue::Message(msg)
+    }
+}
+
+impl<T: Into<DynamicValue>> From<Vec<T>> for DynamicValue {
+    fn from(items: Vec<T>) -> Self {
+        DynamicValue::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<DynamicValue>> From<Option<T>> for DynamicValue {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(DynamicValue::None, Into::into)
+    }
+}
+
+/// A message of a type described at runtime, see the
+/// [module documentation](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicMessage {
+    descriptor: Arc<TypeDescriptor>,
+    values: Vec<DynamicValue>,
+}
+
+impl DynamicMessage {
+    /// A message with every field at its [default](FieldType::default_value)
+    pub fn new(descriptor: Arc<TypeDescriptor>) -> Self {
+        let values = descriptor
+            .fields
+            .iter()
+            .map(|field| field.ty.default_value())
+            .collect();
+        Self { descriptor, values }
+    }
+
+    /// A message of the type registered as `type_name`, with default values
+    pub fn of_type(type_name: &str) -> Result<Self> {
+        Ok(Self::new(registered_descriptor(type_name)?))
+    }
+
+    pub fn descriptor(&self) -> &Arc<TypeDescriptor> {
+        &self.descriptor
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.descriptor.name
+    }
+
+    /// The value of the field `name`
+    pub fn get(&self, name: &str) -> Option<&DynamicValue> {
+        let index = self.index(name)?;
+        Some(&self.values[index])
+    }
+
+    /// Set the field `name`, converting `value` to the field's type
+    ///
+    /// Fails with [`Error::InvalidMessage`] if the message has no such
+    /// field or `value` does not fit its type.
+    pub fn set(&mut self, name: &str, value: impl Into<DynamicValue>) -> Result<()> {
+        let invalid = |message: String| Error::InvalidMessage {
+            type_name: self.descriptor.name.clone(),
+            errors: vec![SchemaError {
+                path: format!("/{}", name),
+                message,
+            }],
+        };
+        let index = self
+            .index(name)
+            .ok_or_else(|| invalid("no such field".to_string()))?;
+        self.values[index] = self.descriptor.fields[index]
+            .ty
+            .coerce(value.into())
+            .map_err(invalid)?;
+        Ok(())
+    }
+
+    /// Every field with its value, in serialization order
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &DynamicValue)> {
+        self.descriptor
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .zip(&self.values)
+    }
+
+    /// Decode a message of the described type from `data`
+    pub fn decode(descriptor: Arc<TypeDescriptor>, data: &[u8], format: Format) -> Result<Self> {
+        let seed = MessageSeed {
+            descriptor: &descriptor,
+            named: format != Format::Cdr,
+        };
+        match format {
+            Format::Cdr => {
+                const HEADER_SIZE: usize = 4;
+                let body = data.get(HEADER_SIZE..).ok_or_else(|| {
+                    Error::Serialization("CDR data shorter than its header".into())
+                })?;
+                let limit = cdr::Bounded(body.len() as u64);
+                let result = match data[1] {
+                    0 | 2 => seed.deserialize(&mut cdr::Deserializer::<_, _, cdr::BigEndian>::new(
+                        body, limit,
+                    )),
+                    1 | 3 => seed.deserialize(
+                        &mut cdr::Deserializer::<_, _, cdr::LittleEndian>::new(body, limit),
+                    ),
+                    _ => Err(cdr::Error::InvalidEncapsulation),
+                };
+                result.map_err(|e| Error::Serialization(e.to_string()))
+            }
+            Format::Json => {
+                let mut deserializer = serde_json::Deserializer::from_slice(data);
+                let msg = seed
+                    .deserialize(&mut deserializer)
+                    .and_then(|msg| deserializer.end().map(|()| msg));
+                msg.map_err(|e| Error::Serialization(e.to_string()))
+            }
+            Format::MessagePack => seed
+                .deserialize(&mut rmp_serde::Deserializer::new(data))
+                .map_err(|e| Error::Serialization(e.to_string())),
+            format => Err(unsupported(format)),
+        }
+    }
+
+    /// Encode the message as the typed message would be encoded
+    pub fn encode(&self, format: Format) -> Result<Vec<u8>> {
+        let named = Fields {
+            msg: self,
+            named: true,
+        };
+        match format {
+            Format::Cdr => cdr::serialize::<_, _, cdr::CdrBe>(
+                &Fields {
+                    msg: self,
+                    named: false,
+                },
+                cdr::Infinite,
+            )
+            .map_err(|e| Error::Serialization(e.to_string())),
+            Format::Json => {
+                serde_json::to_vec(&named).map_err(|e| Error::Serialization(e.to_string()))
+            }
+            Format::MessagePack => {
+                rmp_serde::to_vec_named(&named).map_err(|e| Error::Serialization(e.to_string()))
+            }
+            format => Err(unsupported(format)),
+        }
+    }
+
+    /// Decode a raw message of a registered type
+    pub fn from_raw(raw: &RawMessage) -> Result<Self> {
+        Self::decode(
+            registered_descriptor(&raw.type_name)?,
+            &raw.data,
+            raw.format,
+        )
+    }
+
+    pub fn to_raw(&self, format: Format) -> Result<RawMessage> {
+        Ok(RawMessage {
+            type_name: self.descriptor.name.clone(),
+            format,
+            data: self.encode(format)?,
+        })
+    }
+
+    /// Build a message of the described type from JSON
+    ///
+    /// Fails with [`Error::InvalidMessage`], listing every mismatched
+    /// field, if `value` does not match the type's schema.
+    pub fn from_json(descriptor: Arc<TypeDescriptor>, value: &Value) -> Result<Self> {
+        schema::validate(&descriptor.schema(), value).map_err(|errors| Error::InvalidMessage {
+            type_name: descriptor.name.clone(),
+            errors,
+        })?;
+        MessageSeed {
+            descriptor: &descriptor,
+            named: true,
+        }
+        .deserialize(value)
+        .map_err(|e| Error::Serialization(e.to_string()))
+    }
+
+    /// The message as JSON, as the typed message would serialize
+    pub fn to_json(&self) -> Value {
+        let fields = Fields {
+            msg: self,
+            named: true,
+        };
+        // Field values always fit their types, which JSON can all represent
+        serde_json::to_value(fields).unwrap_or(Value::Null)
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.descriptor
+            .fields
+            .iter()
+            .position(|field| field.name == name)
+    }
+}
+
+fn registered_descriptor(type_name: &str) -> Result<Arc<TypeDescriptor>> {
+    let ty = types::lookup(type_name).ok_or_else(|| Error::UnknownType(type_name.to_string()))?;
+    ty.descriptor()
+        .ok_or_else(|| Error::Serialization(format!("{} has no type descriptor", type_name)))
+}
+
+fn unsupported(format: Format) -> Error {
+    Error::Serialization(format!(
+        "dynamic messages cannot be encoded as {:?}",
+        format
+    ))
+}
+
+/// Serializes a message's fields as a struct of the typed message would
+///
+/// Self-describing formats get a map of field names, CDR a tuple, which is
+/// what their serializers make of a struct.
+struct Fields<'a> {
+    msg: &'a DynamicMessage,
+    named: bool,
+}
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let fields = self.msg.descriptor.fields.iter().zip(&self.msg.values);
+        if self.named {
+            let mut map = serializer.serialize_map(Some(self.msg.values.len()))?;
+            for (field, value) in fields {
+                map.serialize_entry(&field.name, &self.typed(&field.ty, value))?;
+            }
+            map.end()
+        } else {
+            let mut tuple = serializer.serialize_tuple(self.msg.values.len())?;
+            for (field, value) in fields {
+                tuple.serialize_element(&self.typed(&field.ty, value))?;
+            }
+            tuple.end()
+        }
+    }
+}
+
+impl<'a> Fields<'a> {
+    fn typed(&self, ty: &'a FieldType, value: &'a DynamicValue) -> Typed<'a> {
+        Typed {
+            ty,
+            value,
+            named: self.named,
+        }
+    }
+}
+
+/// Serializes a value as the field type's Rust counterpart would
+struct Typed<'a> {
+    ty: &'a FieldType,
+    value: &'a DynamicValue,
+    named: bool,
+}
+
+impl Serialize for Typed<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mismatch =
+            || ser::Error::custom(format!("expected {}, found {}", self.ty, self.value.kind()));
+        let int = || self.value.as_i64().ok_or_else(mismatch);
+        let uint = || self.value.as_u64().ok_or_else(mismatch);
+        match (self.ty, self.value) {
+            (FieldType::Bool, DynamicValue::Bool(v)) => serializer.serialize_bool(*v),
+            (FieldType::Char, DynamicValue::Char(v)) => serializer.serialize_char(*v),
+            (FieldType::String, DynamicValue::String(v)) => serializer.serialize_str(v),
+            (FieldType::I8, _) => {
+                serializer.serialize_i8(int()?.try_into().map_err(|_| mismatch())?)
+            }
+            (FieldType::I16, _) => {
+                serializer.serialize_i16(int()?.try_into().map_err(|_| mismatch())?)
+            }
+            (FieldType::I32, _) => {
+                serializer.serialize_i32(int()?.try_into().map_err(|_| mismatch())?)
+            }
+            (FieldType::I64, _) => serializer.serialize_i64(int()?),
+            (FieldType::U8, _) => {
+                serializer.serialize_u8(uint()?.try_into().map_err(|_| mismatch())?)
+            }
+            (FieldType::U16, _) => {
+                serializer.serialize_u16(uint()?.try_into().map_err(|_| mismatch())?)
+            }
+            (FieldType::U32, _) => {
+                serializer.serialize_u32(uint()?.try_into().map_err(|_| mismatch())?)
+            }
+            (FieldType::U64, _) => serializer.serialize_u64(uint()?),
+            (FieldType::F32, _) => {
+                serializer.serialize_f32(self.value.as_f64().ok_or_else(mismatch)? as f32)
+            }
+            (FieldType::F64, _) => {
+                serializer.serialize_f64(self.value.as_f64().ok_or_else(mismatch)?)
+            }
+            (FieldType::Array(ty, len), DynamicValue::List(items)) if items.len() == *len => {
+                let mut tuple = serializer.serialize_tuple(*len)?;
+                for value in items {
+                    tuple.serialize_element(&self.with(ty, value))?;
+                }
+                tuple.end()
+            }
+            (FieldType::Sequence(ty), DynamicValue::List(items)) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for value in items {
+                    seq.serialize_element(&self.with(ty, value))?;
+                }
+                seq.end()
+            }
+            // Encoded like `message::OptionalRef`, as a sequence in CDR
+            (FieldType::Optional(_), DynamicValue::None) if serializer.is_human_readable() => {
+                serializer.serialize_none()
+            }
+            (FieldType::Optional(ty), value) if serializer.is_human_readable() => {
+                serializer.serialize_some(&self.with(ty, value))
+            }
+            (FieldType::Optional(ty), value) => {
+                let some = !matches!(value, DynamicValue::None);
+                let mut seq = serializer.serialize_seq(Some(some as usize))?;
+                if some {
+                    seq.serialize_element(&self.with(ty, value))?;
+                }
+                seq.end()
+            }
+            (FieldType::Message(_), DynamicValue::Message(msg)) => Fields {
+                msg,
+                named: self.named,
+            }
+            .serialize(serializer),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> Typed<'a> {
+    fn with(&self, ty: &'a FieldType, value: &'a DynamicValue) -> Typed<'a> {
+        Typed {
+            ty,
+            value,
+            named: self.named,
+        }
+    }
+}
+
+/// Deserializes a message of the described type
+struct MessageSeed<'a> {
+    descriptor: &'a Arc<TypeDescriptor>,
+    named: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for MessageSeed<'_> {
+    type Value = DynamicMessage;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Self::Value, D::Error> {
+        if self.named {
+            // Self-describing formats may also hold a struct as a sequence
+            deserializer.deserialize_any(self)
+        } else {
+            let len = self.descriptor.fields.len();
+            deserializer.deserialize_tuple(len, self)
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for MessageSeed<'_> {
+    type Value = DynamicMessage;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a {} message", self.descriptor.name)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(self.descriptor.fields.len());
+        for (index, field) in self.descriptor.fields.iter().enumerate() {
+            let seed = ValueSeed {
+                ty: &field.ty,
+                named: self.named,
+            };
+            match seq.next_element_seed(seed)? {
+                Some(value) => values.push(value),
+                None => return Err(de::Error::invalid_length(index, &self)),
+            }
+        }
+        Ok(DynamicMessage {
+            descriptor: self.descriptor.clone(),
+            values,
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        let fields = &self.descriptor.fields;
+        let mut values: Vec<Option<DynamicValue>> = vec![None; fields.len()];
+        while let Some(key) = map.next_key::<String>()? {
+            let Some(index) = fields.iter().position(|field| field.name == key) else {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            };
+            if values[index].is_some() {
+                return Err(de::Error::custom(format_args!("duplicate field `{}`", key)));
+            }
+            values[index] = Some(map.next_value_seed(ValueSeed {
+                ty: &fields[index].ty,
+                named: self.named,
+            })?);
+        }
+        let values = fields
+            .iter()
+            .zip(values)
+            .map(|(field, value)| match (value, &field.ty) {
+                (Some(value), _) => Ok(value),
+                (None, FieldType::Optional(_)) => Ok(DynamicValue::None),
+                (None, _) => Err(de::Error::custom(format_args!(
+                    "missing field `{}`",
+                    field.name
+                ))),
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(DynamicMessage {
+            descriptor: self.descriptor.clone(),
+            values,
+        })
+    }
+}
+
+/// Deserializes a value of a field type
+#[derive(Clone, Copy)]
+struct ValueSeed<'a> {
+    ty: &'a FieldType,
+    named: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed<'_> {
+    type Value = DynamicValue;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Self::Value, D::Error> {
+        match self.ty {
+            FieldType::Bool => deserializer.deserialize_bool(self),
+            FieldType::Char => deserializer.deserialize_char(self),
+            FieldType::I8 => deserializer.deserialize_i8(self),
+            FieldType::I16 => deserializer.deserialize_i16(self),
+            FieldType::I32 => deserializer.deserialize_i32(self),
+            FieldType::I64 => deserializer.deserialize_i64(self),
+            FieldType::U8 => deserializer.deserialize_u8(self),
+            FieldType::U16 => deserializer.deserialize_u16(self),
+            FieldType::U32 => deserializer.deserialize_u32(self),
+            FieldType::U64 => deserializer.deserialize_u64(self),
+            FieldType::F32 => deserializer.deserialize_f32(self),
+            FieldType::F64 => deserializer.deserialize_f64(self),
+            FieldType::String => deserializer.deserialize_string(self),
+            FieldType::Array(_, len) => deserializer.deserialize_tuple(*len, self),
+            FieldType::Sequence(_) => deserializer.deserialize_seq(self),
+            FieldType::Optional(_) if deserializer.is_human_readable() => {
+                deserializer.deserialize_option(self)
+            }
+            FieldType::Optional(_) => deserializer.deserialize_seq(self),
+            FieldType::Message(descriptor) => MessageSeed {
+                descriptor,
+                named: self.named,
+            }
+            .deserialize(deserializer)
+            .map(DynamicValue::Message),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for ValueSeed<'_> {
+    type Value = DynamicValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a value of type {}", self.ty)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<Self::Value, E> {
+        match self.ty {
+            FieldType::Bool => Ok(DynamicValue::Bool(v)),
+            _ => Err(E::invalid_type(de::Unexpected::Bool(v), &self)),
+        }
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Self::Value, E> {
+        self.ty
+            .integer(v.into())
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Self::Value, E> {
+        self.ty
+            .integer(v.into())
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<Self::Value, E> {
+        self.ty
+            .float(v)
+            .ok_or_else(|| E::invalid_type(de::Unexpected::Float(v), &self))
+    }
+
+    fn visit_char<E: de::Error>(self, v: char) -> std::result::Result<Self::Value, E> {
+        match self.ty {
+            FieldType::Char => Ok(DynamicValue::Char(v)),
+            FieldType::String => Ok(DynamicValue::String(v.to_string())),
+            _ => Err(E::invalid_type(de::Unexpected::Char(v), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Self::Value, E> {
+        let mut chars = v.chars();
+        match (self.ty, chars.next(), chars.next()) {
+            (FieldType::String, _, _) => Ok(DynamicValue::String(v.to_string())),
+            (FieldType::Char, Some(c), None) => Ok(DynamicValue::Char(c)),
+            _ => Err(E::invalid_type(de::Unexpected::Str(v), &self)),
+        }
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> std::result::Result<Self::Value, E> {
+        match self.ty {
+            FieldType::String => Ok(DynamicValue::String(v)),
+            _ => self.visit_str(&v),
+        }
+    }
+
+    fn visit_none<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+        match self.ty {
+            FieldType::Optional(_) => Ok(DynamicValue::None),
+            _ => Err(E::invalid_type(de::Unexpected::Option, &self)),
+        }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+        self.visit_none()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Self::Value, D::Error> {
+        match self.ty {
+            FieldType::Optional(ty) => ValueSeed { ty, ..self }.deserialize(deserializer),
+            _ => Err(de::Error::invalid_type(de::Unexpected::Option, &self)),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        let (ty, len) = match self.ty {
+            FieldType::Array(ty, len) => (ty, Some(*len)),
+            FieldType::Sequence(ty) => (ty, None),
+            FieldType::Optional(ty) => {
+                let value = seq.next_element_seed(ValueSeed { ty, ..self })?;
+                if value.is_some() && seq.next_element::<IgnoredAny>()?.is_some() {
+                    return Err(de::Error::invalid_length(2, &"at most one item"));
+                }
+                return Ok(value.unwrap_or(DynamicValue::None));
+            }
+            _ => return Err(de::Error::invalid_type(de::Unexpected::Seq, &self)),
+        };
+        let seed = ValueSeed { ty, ..self };
+        let mut items = Vec::with_capacity(len.or(seq.size_hint()).unwrap_or(0).min(4096));
+        while len.is_none_or(|len| items.len() < len) {
+            match seq.next_element_seed(seed)? {
+                Some(item) => items.push(item),
+                None => break,
+            }
+        }
+        if let Some(len) = len.filter(|len| items.len() < *len) {
+            return Err(de::Error::invalid_length(
+                items.len(),
+                &format!("{} items", len).as_str(),
+            ));
+        }
+        Ok(DynamicValue::List(items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{Point3D, PointCloud, Pose};
+    use crate::serialization::Serializer as MessageSerializer;
+    use crate::{Message, RobotState};
+
+    fn state() -> RobotState {
+        RobotState {
+            position: [1.0, 2.0, 3.0],
+            velocity: [0.5, -0.25, 0.0],
+            timestamp: 1_700_000_000_123,
+        }
+    }
+
+    fn cloud() -> PointCloud {
+        PointCloud {
+            points: vec![
+                Point3D {
+                    x: 0.1,
+                    y: 0.2,
+                    z: 0.3,
+                },
+                Point3D {
+                    x: -1.5,
+                    y: 2.5,
+                    z: 1e-3,
+                },
+            ],
+            intensities: vec![0.7, 0.9],
+            timestamp: 3,
+        }
+    }
+
+    /// typed → bytes → dynamic → JSON → dynamic → bytes → typed
+    fn roundtrip<T: Message>(msg: &T, format: Format) -> (Vec<u8>, Vec<u8>, T) {
+        let serializer = MessageSerializer::new(format);
+        let descriptor = Arc::new(T::descriptor().unwrap());
+        let bytes = serializer.serialize(msg).unwrap();
+        let dynamic = DynamicMessage::decode(descriptor.clone(), &bytes, format).unwrap();
+        let json = dynamic.to_json();
+        assert_eq!(json, serde_json::to_value(msg).unwrap());
+        let dynamic = DynamicMessage::from_json(descriptor, &json).unwrap();
+        let encoded = dynamic.encode(format).unwrap();
+        let typed = serializer.deserialize(&encoded).unwrap();
+        (bytes, encoded, typed)
+    }
+
+    #[test]
+    fn test_roundtrip_builtin_messages() {
+        for format in [Format::Cdr, Format::Json, Format::MessagePack] {
+            let (bytes, encoded, typed) = roundtrip(&state(), format);
+            assert_eq!(bytes, encoded, "{:?}", format);
+            assert_eq!(typed.position, state().position);
+            assert_eq!(typed.timestamp, state().timestamp);
+
+            let (bytes, encoded, typed) = roundtrip(&cloud(), format);
+            assert_eq!(bytes, encoded, "{:?}", format);
+            assert_eq!(typed.points[1].z, cloud().points[1].z);
+            assert_eq!(typed.intensities, cloud().intensities);
+        }
+    }
+
+    #[test]
+    fn test_descriptors_match_typed_messages() {
+        for descriptor in [
+            RobotState::descriptor(),
+            PointCloud::descriptor(),
+            Pose::descriptor(),
+        ] {
+            let descriptor = descriptor.unwrap();
+            let ty = types::lookup(descriptor.name()).unwrap();
+            assert_eq!(descriptor.hash(), ty.hash());
+            assert_eq!(descriptor.schema(), ty.schema());
+        }
+        assert!(Value::descriptor().is_none());
+    }
+
+    #[test]
+    fn test_field_access() {
+        let mut msg = DynamicMessage::of_type("ros3_msgs/PointCloud").unwrap();
+        assert_eq!(msg.get("timestamp"), Some(&DynamicValue::Int(0)));
+        assert_eq!(
+            msg.get("points").and_then(DynamicValue::as_list),
+            Some(&[][..])
+        );
+
+        let mut point = DynamicMessage::new(match &msg.descriptor().fields()[0].ty {
+            FieldType::Sequence(item) => match item.as_ref() {
+                FieldType::Message(descriptor) => descriptor.clone(),
+                ty => panic!("unexpected {}", ty),
+            },
+            ty => panic!("unexpected {}", ty),
+        });
+        point.set("x", 1).unwrap();
+        msg.set("points", vec![point]).unwrap();
+        msg.set("intensities", vec![0.5f32]).unwrap();
+        msg.set("timestamp", 9u8).unwrap();
+        let names: Vec<&str> = msg.fields().map(|(name, _)| name).collect();
+        assert_eq!(names, ["points", "intensities", "timestamp"]);
+
+        let typed: PointCloud = MessageSerializer::new(Format::Cdr)
+            .deserialize(&msg.encode(Format::Cdr).unwrap())
+            .unwrap();
+        assert_eq!(typed.points[0].x, 1.0);
+        assert_eq!(typed.intensities, [0.5]);
+        assert_eq!(typed.timestamp, 9);
+
+        let err = msg.set("timestamp", "soon").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid ros3_msgs/PointCloud message: /timestamp: expected i64, found a string"
+        );
+        assert!(msg.set("intensities", vec![true]).is_err());
+        assert!(msg.set("speed", 1.0).is_err());
+    }
+
+    #[test]
+    fn test_integer_ranges() {
+        let descriptor = Arc::new(TypeDescriptor::new(
+            "test_msgs/Ranges",
+            vec![
+                FieldDescriptor::new("small", FieldType::U8),
+                FieldDescriptor::new("flag", FieldType::Bool),
+                FieldDescriptor::new("letter", FieldType::Char),
+            ],
+        ));
+        let mut msg = DynamicMessage::new(descriptor.clone());
+        assert!(msg.set("small", 255).is_ok());
+        assert!(msg.set("small", 256).is_err());
+        assert!(msg.set("small", -1).is_err());
+        assert!(msg.set("small", 1.5).is_err());
+
+        let err = DynamicMessage::from_json(
+            descriptor.clone(),
+            &json!({ "small": 300, "flag": 1, "letter": "ab" }),
+        )
+        .unwrap_err();
+        match err {
+            Error::InvalidMessage { errors, .. } => assert_eq!(errors.len(), 3),
+            err => panic!("expected InvalidMessage, got {}", err),
+        }
+
+        let msg = DynamicMessage::from_json(
+            descriptor,
+            &json!({ "small": 7, "flag": true, "letter": "z" }),
+        )
+        .unwrap();
+        let bytes = msg.encode(Format::Cdr).unwrap();
+        // Encapsulation header, then one byte per field
+        assert_eq!(&bytes[4..], [7, 1, b'z']);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let descriptor = Arc::new(RobotState::descriptor().unwrap());
+        let bytes = MessageSerializer::new(Format::Cdr)
+            .serialize(&state())
+            .unwrap();
+        assert!(DynamicMessage::decode(descriptor.clone(), &bytes[..20], Format::Cdr).is_err());
+        assert!(DynamicMessage::decode(descriptor.clone(), &[0, 0], Format::Cdr).is_err());
+        assert!(
+            DynamicMessage::decode(descriptor.clone(), b"{\"position\": 1}", Format::Json).is_err()
+        );
+        assert!(DynamicMessage::decode(descriptor, &bytes, Format::Protobuf).is_err());
+
+        let raw = RawMessage {
+            type_name: "test_msgs/Unknown".to_string(),
+            format: Format::Cdr,
+            data: bytes,
+        };
+        assert!(matches!(
+            DynamicMessage::from_raw(&raw),
+            Err(Error::UnknownType(_))
+        ));
+    }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use super::*;
+        use crate::Ros3Message;
+
+        #[derive(Debug, Clone, PartialEq, Ros3Message)]
+        #[ros3(type_name = "test_msgs/DynamicHeader")]
+        struct DynamicHeader {
+            frame_id: String,
+            stamp: i64,
+            seq: u32,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Ros3Message)]
+        #[ros3(type_name = "test_msgs/Telemetry")]
+        struct Telemetry {
+            header: DynamicHeader,
+            mode: char,
+            armed: bool,
+            cells: [u16; 4],
+            temperatures: Vec<f32>,
+            offsets: Vec<i8>,
+            history: Vec<DynamicHeader>,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Ros3Message)]
+        #[ros3(type_name = "test_msgs/Optional")]
+        struct WithOption {
+            label: Option<String>,
+            scale: Option<f64>,
+        }
+
+        fn telemetry() -> Telemetry {
+            let header = |seq| DynamicHeader {
+                frame_id: "base_link".to_string(),
+                stamp: -5,
+                seq,
+            };
+            Telemetry {
+                header: header(1),
+                mode: 'A',
+                armed: true,
+                cells: [4100, 4095, 4120, 65535],
+                temperatures: vec![21.5, 0.1, -3.75],
+                offsets: vec![-128, 0, 127],
+                history: vec![header(0), header(1)],
+            }
+        }
+
+        #[test]
+        fn test_roundtrip_derived_message() {
+            for format in [Format::Cdr, Format::Json, Format::MessagePack] {
+                let (bytes, encoded, typed) = roundtrip(&telemetry(), format);
+                assert_eq!(bytes, encoded, "{:?}", format);
+                assert_eq!(typed, telemetry());
+            }
+        }
+
+        #[test]
+        fn test_derived_descriptor() {
+            let descriptor = Telemetry::descriptor().unwrap();
+            assert_eq!(descriptor.hash(), Telemetry::type_hash());
+            assert_eq!(descriptor.schema(), Telemetry::schema());
+            // Described at runtime, the type hashes the same
+            let rebuilt = TypeDescriptor::new(descriptor.name(), descriptor.fields().to_vec());
+            assert_eq!(rebuilt.hash(), Telemetry::type_hash());
+            assert_eq!(
+                descriptor.field("cells").unwrap().ty,
+                FieldType::Array(Box::new(FieldType::U16), 4)
+            );
+        }
+
+        #[test]
+        fn test_optional_fields() {
+            let msg = WithOption {
+                label: Some("left".to_string()),
+                scale: None,
+            };
+            // CDR has no optional values; both sides encode them as sequences
+            for format in [Format::Json, Format::MessagePack, Format::Cdr] {
+                let (bytes, encoded, typed) = roundtrip(&msg, format);
+                assert_eq!(bytes, encoded, "{:?}", format);
+                assert_eq!(typed, msg);
+            }
+
+            let descriptor = Arc::new(WithOption::descriptor().unwrap());
+            let dynamic = DynamicMessage::from_json(descriptor, &json!({})).unwrap();
+            assert!(dynamic.get("label").unwrap().is_none());
+            let empty = WithOption {
+                label: None,
+                scale: None,
+            };
+            assert_eq!(
+                dynamic.encode(Format::Cdr).unwrap(),
+                MessageSerializer::new(Format::Cdr).serialize(&empty).unwrap()
+            );
+        }
+    }
+}
@@ -1,5 +1,7 @@
 //! Error types for ROS3 Core
 
+use crate::config::ConfigError;
+use crate::schema::SchemaError;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -21,9 +23,128 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Configuration(String),
 
+    #[error("Topic type mismatch on '{topic}': expected {expected}, found {found}")]
+    TopicTypeMismatch {
+        topic: String,
+        expected: String,
+        found: String,
+    },
+
+    #[error("Invalid topic name '{name}': {reason}")]
+    InvalidTopicName {
+        name: String,
+        reason: String,
+    },
+
+    #[error("Incompatible QoS on '{topic}': {reason}")]
+    QosIncompatible {
+        topic: String,
+        reason: String,
+    },
+
+    #[error("Invalid parameter '{name}': {reason}")]
+    InvalidParameter {
+        name: String,
+        reason: String,
+    },
+
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
+    #[error("Service handler panicked: {0}")]
+    ServicePanicked(String),
+
+    #[error("Service '{service}' failed: {reason}")]
+    ServiceFailed {
+        service: String,
+        reason: String,
+    },
+
+    #[error("Goal {0} was canceled")]
+    GoalCanceled(u64),
+
+    #[error("Cannot {transition} node '{node}' while it is {state}")]
+    InvalidTransition {
+        node: String,
+        state: crate::lifecycle::LifecycleState,
+        transition: crate::lifecycle::Transition,
+    },
+
+    #[error("Transition {transition} of node '{node}' failed: {reason}")]
+    TransitionFailed {
+        node: String,
+        transition: crate::lifecycle::Transition,
+        reason: String,
+    },
+
+    #[error("Endpoint closed: {0}")]
+    Closed(String),
+
+    #[error("TLS handshake with {peer} failed: {reason}")]
+    TlsHandshake {
+        peer: std::net::SocketAddr,
+        reason: String,
+    },
+
+    #[error("Buffer pool exhausted: no free buffer of {0} bytes")]
+    PoolExhausted(usize),
+
+    #[error("Unknown message type '{0}'")]
+    UnknownType(String),
+
+    #[error("Invalid {type_name} message: {}", join_errors(.errors))]
+    InvalidMessage {
+        type_name: String,
+        /// Every field that does not match the type's schema
+        errors: Vec<SchemaError>,
+    },
+
+    #[error("Invalid definition of {type_name} at {line}:{column}: {reason}")]
+    InvalidDefinition {
+        type_name: String,
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+
+    #[error("Invalid launch file {file}{}: {reason}", at_path(.path))]
+    InvalidLaunch {
+        file: String,
+        /// Where in the file, such as `containers[0].nodes[2].factory`;
+        /// empty for the whole file
+        path: String,
+        reason: String,
+    },
+
+    #[error("Rejected configuration {file}: {}", join_errors(.errors))]
+    ConfigRejected {
+        file: String,
+        /// Every key the file cannot be applied for
+        errors: Vec<ConfigError>,
+    },
+
+    #[error("Transform error: {0}")]
+    Transform(#[from] crate::tf::TfError),
+
+    #[error("Invalid bag file: {0}")]
+    InvalidBag(String),
+
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
     #[error("Other error: {0}")]
     Other(#[from] anyhow::Error),
 }
+
+fn join_errors<E: ToString>(errors: &[E]) -> String {
+    let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    errors.join("; ")
+}
+
+fn at_path(path: &str) -> String {
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!(" at {}", path)
+    }
+}
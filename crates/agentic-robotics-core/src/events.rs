@@ -0,0 +1,72 @@
+//! Process events on `/ros3/events`
+//!
+//! Things an operator wants to hear about as they happen, such as a
+//! supervised task of the real-time executor panicking or a configuration
+//! file being reloaded, are published as an [`Event`] on [`EVENTS_TOPIC`]:
+//!
+//! ```no_run
+//! # use agentic_robotics_core::events::{Event, EVENTS_TOPIC};
+//! # use agentic_robotics_core::Subscriber;
+//! # async fn run() -> agentic_robotics_core::error::Result<()> {
+//! let events = Subscriber::<Event>::new(EVENTS_TOPIC)?;
+//! while let Ok(event) = events.recv().await {
+//!     if let Event::ConfigReload(reload) = event {
+//!         println!("{}: applied {}, {:?}", reload.file, reload.applied, reload.errors);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::ConfigError;
+use crate::message::Message;
+use serde::{Deserialize, Serialize};
+
+/// Topic process events are published on
+pub const EVENTS_TOPIC: &str = "/ros3/events";
+
+/// Something that happened in the process, published on [`EVENTS_TOPIC`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    Task(TaskEvent),
+    ConfigReload(ConfigReloadEvent),
+}
+
+impl Message for Event {
+    fn type_name() -> &'static str {
+        "ros3_msgs/Event"
+    }
+}
+
+/// What happened to a supervised task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskEventKind {
+    Panicked,
+    Restarted,
+    Failed,
+}
+
+/// A supervised task panicked, restarted or failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskEvent {
+    pub task: String,
+    pub kind: TaskEventKind,
+    /// Restarts so far, including this one for [`TaskEventKind::Restarted`]
+    pub restarts: u32,
+    /// Payload of the panic that led to this event
+    pub panic: String,
+}
+
+/// A configuration file was reloaded into a node's parameters, see
+/// [`ConfigReloader`](crate::config::ConfigReloader)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigReloadEvent {
+    pub file: String,
+    pub node: String,
+    /// Whether the changes were applied; a reload is rejected as a whole
+    pub applied: bool,
+    /// Parameters whose value differs from the file, sorted
+    pub changed: Vec<String>,
+    /// Why the reload was rejected, for every offending key
+    pub errors: Vec<ConfigError>,
+}
@@ -0,0 +1,290 @@
+//! Graph introspection
+//!
+//! Lists the topics, publishers and subscribers registered on the global
+//! [`TopicBus`], the services with a server in this process, the
+//! parameters of every node, the state of every lifecycle node and the
+//! nodes loaded into containers, and streams topic changes as they happen.
+//! Every type here is `Serialize` so tools can forward it as JSON.
+
+use crate::container::{self, LoadedNode};
+use crate::lifecycle::{self, LifecycleHandle, LifecycleInfo};
+use crate::params::{self, ParameterInfo, ParameterServer};
+use crate::qos::QosProfile;
+use crate::topic::TopicBus;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// A topic and its endpoint counts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopicInfo {
+    pub name: String,
+    /// Message type, or `None` if only [`RawMessage`](crate::RawMessage)
+    /// endpoints have joined
+    pub type_name: Option<String>,
+    pub publisher_count: usize,
+    pub subscriber_count: usize,
+}
+
+/// Message counters of a topic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopicCounters {
+    pub name: String,
+    pub publisher_count: usize,
+    pub subscriber_count: usize,
+    /// Messages published since the topic was created
+    pub published: u64,
+    /// Messages dropped by the queues of the current subscribers
+    pub dropped: u64,
+}
+
+/// A publisher or subscriber registered on a topic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EndpointInfo {
+    /// Process-unique endpoint id
+    pub id: u64,
+    pub topic: String,
+    /// Name of the [`Node`](crate::Node) that created the endpoint
+    pub node: Option<String>,
+    pub qos: QosProfile,
+}
+
+/// A service with a server in this process
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceInfo {
+    pub name: String,
+    pub request_type: String,
+    pub response_type: String,
+}
+
+/// A change to the graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GraphEvent {
+    TopicCreated { topic: String },
+    TopicRemoved { topic: String },
+    PublisherAdded(EndpointInfo),
+    PublisherRemoved(EndpointInfo),
+    SubscriberAdded(EndpointInfo),
+    SubscriberRemoved(EndpointInfo),
+    /// The watcher fell behind and missed this many events; call
+    /// [`list_topics`] to resynchronize
+    Lagged { missed: u64 },
+}
+
+/// Stream of [`GraphEvent`]s created by [`watch`]
+pub struct GraphWatcher {
+    events: broadcast::Receiver<GraphEvent>,
+}
+
+impl GraphWatcher {
+    pub(crate) fn new(events: broadcast::Receiver<GraphEvent>) -> Self {
+        Self { events }
+    }
+
+    /// Wait for the next change, or `None` if the bus is gone
+    pub async fn next(&mut self) -> Option<GraphEvent> {
+        match self.events.recv().await {
+            Ok(event) => Some(event),
+            Err(RecvError::Lagged(missed)) => Some(GraphEvent::Lagged { missed }),
+            Err(RecvError::Closed) => None,
+        }
+    }
+}
+
+/// All topics on the global bus, sorted by name
+pub fn list_topics() -> Vec<TopicInfo> {
+    TopicBus::global().topic_infos()
+}
+
+/// Message counters of every topic on the global bus, sorted by name
+pub fn topic_counters() -> Vec<TopicCounters> {
+    TopicBus::global().topic_counters()
+}
+
+/// Publishers currently registered on `topic`
+pub fn get_publishers(topic: &str) -> Vec<EndpointInfo> {
+    TopicBus::global().publishers(topic)
+}
+
+/// Subscribers currently registered on `topic`
+pub fn get_subscribers(topic: &str) -> Vec<EndpointInfo> {
+    TopicBus::global().subscribers(topic)
+}
+
+/// Services with a server in this process, sorted by name
+pub fn list_services() -> Vec<ServiceInfo> {
+    crate::service::service_infos()
+}
+
+/// Parameters of every live [`ParameterServer`](crate::ParameterServer),
+/// sorted by node and name
+pub fn list_parameters() -> Vec<ParameterInfo> {
+    params::all_parameters()
+}
+
+/// The parameters of the live node named `node`, to read or change them
+///
+/// If several live nodes share the name, one of them.
+pub fn parameter_server(node: &str) -> Option<ParameterServer> {
+    params::find_server(node)
+}
+
+/// Every live [`LifecycleNode`](crate::LifecycleNode) with its state,
+/// sorted by node
+pub fn list_lifecycle_nodes() -> Vec<LifecycleInfo> {
+    lifecycle::all_nodes()
+}
+
+/// The live lifecycle node named `node`, to query or change its state
+///
+/// If several live lifecycle nodes share the name, one of them.
+pub fn lifecycle_node(node: &str) -> Option<LifecycleHandle> {
+    lifecycle::find_node(node)
+}
+
+/// The nodes loaded into every live
+/// [`NodeContainer`](crate::NodeContainer), sorted by container and id
+pub fn list_loaded_nodes() -> Vec<LoadedNode> {
+    container::all_loaded_nodes()
+}
+
+/// Watch the global bus for changes made from now on
+pub fn watch() -> GraphWatcher {
+    TopicBus::global().watch()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+    use crate::{Node, Publisher, Subscriber};
+    use std::sync::Barrier;
+    use std::sync::Arc;
+
+    fn topic_info(name: &str) -> Option<TopicInfo> {
+        list_topics().into_iter().find(|t| t.name == name)
+    }
+
+    #[test]
+    fn test_counts_under_concurrency() {
+        const TOPIC: &str = "test/graph/concurrent";
+        const THREADS: usize = 8;
+        let barrier = Arc::new(Barrier::new(THREADS + 1));
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        let _p = Publisher::<RobotState>::new(TOPIC).unwrap();
+                        let _s = Subscriber::<RobotState>::new(TOPIC).unwrap();
+                    }
+                    let kept = (
+                        Publisher::<RobotState>::new(TOPIC).unwrap(),
+                        Subscriber::<RobotState>::new(TOPIC).unwrap(),
+                        Subscriber::<RobotState>::new(TOPIC).unwrap(),
+                    );
+                    barrier.wait();
+                    // Hold on to the endpoints until the counts were checked
+                    barrier.wait();
+                    drop(kept);
+                })
+            })
+            .collect();
+
+        barrier.wait();
+        let info = topic_info(TOPIC).unwrap();
+        assert_eq!(info.type_name.as_deref(), Some("ros3_msgs/RobotState"));
+        assert_eq!(info.publisher_count, THREADS);
+        assert_eq!(info.subscriber_count, 2 * THREADS);
+        assert_eq!(get_publishers(TOPIC).len(), THREADS);
+        assert_eq!(get_subscribers(TOPIC).len(), 2 * THREADS);
+        barrier.wait();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(topic_info(TOPIC).is_none());
+        assert!(get_publishers(TOPIC).is_empty());
+    }
+
+    /// Next event on the watch test's topics; other tests share the bus
+    async fn next(watcher: &mut GraphWatcher) -> GraphEvent {
+        loop {
+            let event = watcher.next().await.unwrap();
+            let topic = match &event {
+                GraphEvent::TopicCreated { topic } | GraphEvent::TopicRemoved { topic } => topic,
+                GraphEvent::PublisherAdded(e)
+                | GraphEvent::PublisherRemoved(e)
+                | GraphEvent::SubscriberAdded(e)
+                | GraphEvent::SubscriberRemoved(e) => &e.topic,
+                GraphEvent::Lagged { .. } => panic!("watcher lagged"),
+            };
+            if topic.starts_with("/test/graph/watch/") {
+                return event;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_changes() {
+        let mut watcher = watch();
+        let node = Node::with_namespace("watched", "/test/graph/watch", Vec::new()).unwrap();
+        let publisher = node.create_publisher::<RobotState>("state").unwrap();
+
+        assert_eq!(
+            next(&mut watcher).await,
+            GraphEvent::TopicCreated {
+                topic: "/test/graph/watch/state".into()
+            }
+        );
+        let GraphEvent::PublisherAdded(added) = next(&mut watcher).await else {
+            panic!("expected a publisher event");
+        };
+        assert_eq!(added.node.as_deref(), Some("watched"));
+        assert_eq!(get_publishers("/test/graph/watch/state"), vec![added.clone()]);
+
+        drop(publisher);
+        assert_eq!(next(&mut watcher).await, GraphEvent::PublisherRemoved(added));
+        assert_eq!(
+            next(&mut watcher).await,
+            GraphEvent::TopicRemoved {
+                topic: "/test/graph/watch/state".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = GraphEvent::TopicCreated {
+            topic: "/map".into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"topic_created","topic":"/map"}"#
+        );
+    }
+
+    #[test]
+    fn test_list_parameters() {
+        let node = Node::new("test_graph_params").unwrap();
+        node.parameters().declare("kp", 1.5, Default::default()).unwrap();
+
+        let listed: Vec<_> = list_parameters()
+            .into_iter()
+            .filter(|p| p.node == "test_graph_params")
+            .collect();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "kp");
+        assert_eq!(listed[0].value, crate::ParameterValue::Double(1.5));
+        let server = parameter_server("test_graph_params").unwrap();
+        server.set("kp", 2.0).unwrap();
+        assert_eq!(node.parameters().get::<f64>("kp").unwrap(), 2.0);
+        drop(server);
+
+        drop(node);
+        assert!(!list_parameters().iter().any(|p| p.node == "test_graph_params"));
+        assert!(parameter_server("test_graph_params").is_none());
+    }
+}
@@ -0,0 +1,629 @@
+//! Message definitions in the `.msg` format
+//!
+//! Types defined outside Rust, e.g. by a ROS package or another team's
+//! tooling, are described by a message definition, one field or constant
+//! per line:
+//!
+//! ```text
+//! # Comments run to the end of the line
+//! uint8 MODE_IDLE=0          # constant
+//! string NAME=front lidar    # string constants run to the end of the line
+//! Header header              # nested message of the same package
+//! geometry_msgs/Point origin # nested message of another package
+//! float64[3] position        # fixed-length array
+//! float32[] ranges           # sequence
+//! string[<=8] tags           # bounded sequence
+//! ```
+//!
+//! The primitive types are `bool`, `byte` (an `u8`), `char`, `int8` to
+//! `int64`, `uint8` to `uint64`, `float32`, `float64` and `string`. Nested
+//! types without a package are looked up in the package of the definition,
+//! and `Header` also as `std_msgs/Header`, as in ROS. Bounds of sequences
+//! and strings are accepted but not enforced; default values and optional
+//! fields have no spelling.
+//!
+//! Parsing a definition gives the same [`TypeDescriptor`] that
+//! `#[derive(Ros3Message)]` generates for the struct with the same fields,
+//! including the type hash. Registering it makes the type usable by name,
+//! as a [`DynamicMessage`](crate::dynamic::DynamicMessage), through the
+//! [type registry](crate::types) and by the bridges:
+//!
+//! ```
+//! use agentic_robotics_core::dynamic::DynamicMessage;
+//! use agentic_robotics_core::idl;
+//!
+//! idl::register("docs_msgs/Battery", "float32 percent\nbool charging").unwrap();
+//! let mut msg = DynamicMessage::of_type("docs_msgs/Battery").unwrap();
+//! msg.set("percent", 80.0).unwrap();
+//! assert_eq!(msg.to_json()["charging"], false);
+//! ```
+//!
+//! Nodes load a directory of definitions at startup with
+//! [`Node::with_message_definitions`](crate::Node::with_message_definitions).
+
+use crate::dynamic::{
+    ConstantDescriptor, DynamicValue, FieldDescriptor, FieldType, TypeDescriptor,
+};
+use crate::error::{Error, Result};
+use crate::types::{self, MessageType};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// File extension of message definitions
+pub const EXTENSION: &str = "msg";
+
+/// Parse the definition of the type `type_name`
+///
+/// Nested types must be registered already. Fails with
+/// [`Error::InvalidDefinition`] at the first line that does not parse.
+pub fn parse(type_name: &str, definition: &str) -> Result<TypeDescriptor> {
+    Definition::parse(type_name, definition)?.build(registered)
+}
+
+/// [Parse](parse) the definition of the type `type_name` and register it
+pub fn register(type_name: &str, definition: &str) -> Result<MessageType> {
+    types::register_descriptor(parse(type_name, definition)?)
+}
+
+/// Register every type defined in a `.msg` file under `dir`
+///
+/// Files are found recursively and named after their package directory,
+/// skipping a `msg` directory as ROS packages have, so both
+/// `geometry_msgs/msg/Point.msg` and `geometry_msgs/Point.msg` define
+/// `geometry_msgs/Point`. Definitions may nest each other in any order.
+/// Nothing is registered unless every definition parses.
+pub fn load_dir(dir: impl AsRef<Path>) -> Result<Vec<MessageType>> {
+    let dir = fs::canonicalize(dir.as_ref())?;
+    let mut files = Vec::new();
+    find_definitions(&dir, &mut files)?;
+
+    let mut definitions = BTreeMap::new();
+    for path in files {
+        let type_name = type_name_of(&path)
+            .ok_or_else(|| Error::Configuration(format!("No package for {}", path.display())))?;
+        let definition = Definition::parse(&type_name, &fs::read_to_string(&path)?)?;
+        if definitions.insert(type_name.clone(), definition).is_some() {
+            return Err(Error::Configuration(format!(
+                "{} is defined more than once under {}",
+                type_name,
+                dir.display()
+            )));
+        }
+    }
+
+    let mut loader = Loader {
+        definitions,
+        built: HashMap::new(),
+        loading: Vec::new(),
+    };
+    let names: Vec<String> = loader.definitions.keys().cloned().collect();
+    let descriptors = names
+        .iter()
+        .map(|name| loader.build(name))
+        .collect::<Result<Vec<_>>>()?;
+    descriptors
+        .into_iter()
+        .map(|descriptor| types::register_descriptor((*descriptor).clone()))
+        .collect()
+}
+
+fn registered(type_name: &str) -> Option<Arc<TypeDescriptor>> {
+    types::lookup(type_name).and_then(|ty| ty.descriptor())
+}
+
+fn find_definitions(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_definitions(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == EXTENSION) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// `pkg/Name` for `pkg/msg/Name.msg` or `pkg/Name.msg`
+fn type_name_of(path: &Path) -> Option<String> {
+    let name = path.file_stem()?.to_str()?;
+    let mut package = path.parent()?;
+    if package.file_name()? == "msg" {
+        package = package.parent()?;
+    }
+    Some(format!("{}/{}", package.file_name()?.to_str()?, name))
+}
+
+/// Builds the definitions of a directory, nested types first
+struct Loader {
+    definitions: BTreeMap<String, Definition>,
+    built: HashMap<String, Arc<TypeDescriptor>>,
+    /// Types being built, to catch definitions that nest themselves
+    loading: Vec<String>,
+}
+
+impl Loader {
+    fn build(&mut self, type_name: &str) -> Result<Arc<TypeDescriptor>> {
+        if let Some(descriptor) = self.built.get(type_name) {
+            return Ok(descriptor.clone());
+        }
+        self.loading.push(type_name.to_string());
+        let definition = &self.definitions[type_name];
+        let nested: Vec<(String, Position)> = definition
+            .nested()
+            .filter_map(|(name, position)| {
+                definition
+                    .candidates(name)
+                    .into_iter()
+                    .find(|candidate| {
+                        self.definitions.contains_key(candidate) || registered(candidate).is_some()
+                    })
+                    .filter(|candidate| self.definitions.contains_key(candidate))
+                    .map(|candidate| (candidate, position))
+            })
+            .collect();
+        for (nested, position) in nested {
+            if self.loading.contains(&nested) {
+                return Err(error(
+                    type_name,
+                    position,
+                    format!("{} contains itself", nested),
+                ));
+            }
+            self.build(&nested)?;
+        }
+        let descriptor = Arc::new(
+            self.definitions[type_name]
+                .build(|name| self.built.get(name).cloned().or_else(|| registered(name)))?,
+        );
+        self.loading.pop();
+        self.built.insert(type_name.to_string(), descriptor.clone());
+        Ok(descriptor)
+    }
+}
+
+/// Line and column, counted from 1
+type Position = (usize, usize);
+
+fn error(type_name: &str, (line, column): Position, reason: String) -> Error {
+    Error::InvalidDefinition {
+        type_name: type_name.to_string(),
+        line,
+        column,
+        reason,
+    }
+}
+
+/// A parsed definition whose nested types are not resolved yet
+#[derive(Debug)]
+struct Definition {
+    type_name: String,
+    fields: Vec<Field>,
+    constants: Vec<ConstantDescriptor>,
+}
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    ty: TypeSpec,
+    /// Of the type
+    position: Position,
+}
+
+#[derive(Debug, PartialEq)]
+struct TypeSpec {
+    base: Base,
+    shape: Shape,
+}
+
+#[derive(Debug, PartialEq)]
+enum Base {
+    Primitive(FieldType),
+    /// A nested message, by the name written
+    Message(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Shape {
+    Single,
+    Array(usize),
+    Sequence,
+}
+
+impl Shape {
+    fn apply(&self, ty: FieldType) -> FieldType {
+        match self {
+            Shape::Single => ty,
+            Shape::Array(len) => FieldType::Array(Box::new(ty), *len),
+            Shape::Sequence => FieldType::Sequence(Box::new(ty)),
+        }
+    }
+}
+
+impl Definition {
+    fn parse(type_name: &str, source: &str) -> Result<Self> {
+        let mut definition = Definition {
+            type_name: type_name.to_string(),
+            fields: Vec::new(),
+            constants: Vec::new(),
+        };
+        for (index, line) in source.lines().enumerate() {
+            let position = |offset: usize| (index + 1, line[..offset].chars().count() + 1);
+            definition
+                .parse_line(line, &position)
+                .map_err(|(offset, reason)| error(type_name, position(offset), reason))?;
+        }
+        Ok(definition)
+    }
+
+    /// Parse a line, failing with the byte offset of the error
+    fn parse_line(
+        &mut self,
+        line: &str,
+        position: &dyn Fn(usize) -> Position,
+    ) -> std::result::Result<(), (usize, String)> {
+        let code = line.split('#').next().unwrap_or_default();
+        let Some((type_start, type_token)) = token(code, 0) else {
+            return Ok(());
+        };
+        let ty =
+            parse_type(type_token).map_err(|(offset, reason)| (type_start + offset, reason))?;
+        let rest = type_start + type_token.len();
+
+        if let Some(equals) = code[rest..].find('=').map(|offset| rest + offset) {
+            let (name_start, _) = token(&code[..equals], rest)
+                .ok_or_else(|| (equals, "expected a constant name".to_string()))?;
+            let name = code[name_start..equals].trim_end();
+            self.check_name(name_start, name)?;
+            let TypeSpec {
+                base: Base::Primitive(base),
+                shape: Shape::Single,
+            } = ty
+            else {
+                return Err((type_start, "constants must have a primitive type".into()));
+            };
+            // String constants may contain '#'
+            let raw = if base == FieldType::String {
+                &line[equals + 1..]
+            } else {
+                &code[equals + 1..]
+            };
+            let value_start = equals + 1 + (raw.len() - raw.trim_start().len());
+            let value = constant(&base, raw.trim()).ok_or_else(|| {
+                (
+                    value_start,
+                    format!("invalid {} constant '{}'", type_token, raw.trim()),
+                )
+            })?;
+            self.constants.push(ConstantDescriptor {
+                name: name.to_string(),
+                ty: base,
+                value,
+            });
+            return Ok(());
+        }
+
+        let (name_start, name) = token(code, rest)
+            .ok_or_else(|| (code.trim_end().len(), "expected a field name".to_string()))?;
+        self.check_name(name_start, name)?;
+        if let Some((extra, _)) = token(code, name_start + name.len()) {
+            return Err((extra, "default values are not supported".into()));
+        }
+        self.fields.push(Field {
+            name: name.to_string(),
+            ty,
+            position: position(type_start),
+        });
+        Ok(())
+    }
+
+    /// Check that `name`, at `start`, is a new identifier
+    fn check_name(&self, start: usize, name: &str) -> std::result::Result<(), (usize, String)> {
+        if !is_identifier(name) {
+            return Err((start, format!("invalid name '{}'", name)));
+        }
+        let taken = self.fields.iter().any(|field| field.name == name)
+            || self.constants.iter().any(|constant| constant.name == name);
+        if taken {
+            return Err((start, format!("duplicate name '{}'", name)));
+        }
+        Ok(())
+    }
+
+    /// The nested types, as written, with their positions
+    fn nested(&self) -> impl Iterator<Item = (&str, Position)> {
+        self.fields.iter().filter_map(|field| match &field.ty.base {
+            Base::Message(name) => Some((name.as_str(), field.position)),
+            Base::Primitive(_) => None,
+        })
+    }
+
+    /// Full names a nested type written as `name` may have, in the order
+    /// they are tried
+    fn candidates(&self, name: &str) -> Vec<String> {
+        if name.contains('/') {
+            return vec![name.to_string()];
+        }
+        let mut candidates = Vec::new();
+        if let Some((package, _)) = self.type_name.rsplit_once('/') {
+            candidates.push(format!("{}/{}", package, name));
+        }
+        if name == "Header" {
+            candidates.push("std_msgs/Header".to_string());
+        }
+        candidates.push(name.to_string());
+        candidates
+    }
+
+    /// The descriptor, with nested types resolved by `resolve`
+    fn build(
+        &self,
+        mut resolve: impl FnMut(&str) -> Option<Arc<TypeDescriptor>>,
+    ) -> Result<TypeDescriptor> {
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| {
+                let base = match &field.ty.base {
+                    Base::Primitive(ty) => ty.clone(),
+                    Base::Message(name) => self
+                        .candidates(name)
+                        .iter()
+                        .find_map(|candidate| resolve(candidate))
+                        .map(FieldType::Message)
+                        .ok_or_else(|| {
+                            error(
+                                &self.type_name,
+                                field.position,
+                                format!("unknown type '{}'", name),
+                            )
+                        })?,
+                };
+                Ok(FieldDescriptor::new(
+                    &field.name,
+                    field.ty.shape.apply(base),
+                ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(TypeDescriptor::new(&self.type_name, fields).with_constants(self.constants.clone()))
+    }
+}
+
+/// The next whitespace-separated token of `text` from `from`, with its
+/// byte offset
+fn token(text: &str, from: usize) -> Option<(usize, &str)> {
+    let rest = &text[from..];
+    let start = from + rest.len() - rest.trim_start().len();
+    let token = text[start..].split_whitespace().next()?;
+    Some((start, token))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse a type such as `float64[3]`, failing with the byte offset of the
+/// error within `token`
+fn parse_type(token: &str) -> std::result::Result<TypeSpec, (usize, String)> {
+    let (base, shape) = match token.find('[') {
+        Some(open) => (
+            &token[..open],
+            parse_shape(&token[open..]).map_err(|reason| (open, reason))?,
+        ),
+        None => (token, Shape::Single),
+    };
+    // Bounded strings, `string<=10`
+    let base = match base.split_once("<=") {
+        Some(("string", bound)) => {
+            bound.parse::<usize>().map_err(|_| {
+                (
+                    "string<=".len(),
+                    format!("invalid string bound '{}'", bound),
+                )
+            })?;
+            "string"
+        }
+        _ => base,
+    };
+    let base = match primitive(base) {
+        Some(ty) => Base::Primitive(ty),
+        None if is_type_name(base) => Base::Message(base.to_string()),
+        None => return Err((0, format!("invalid type '{}'", base))),
+    };
+    Ok(TypeSpec { base, shape })
+}
+
+/// Parse `[]`, `[N]` or `[<=N]`
+fn parse_shape(suffix: &str) -> std::result::Result<Shape, String> {
+    let size = suffix
+        .strip_prefix('[')
+        .and_then(|inner| inner.strip_suffix(']'))
+        .ok_or_else(|| format!("invalid array '{}'", suffix))?;
+    if size.is_empty() {
+        return Ok(Shape::Sequence);
+    }
+    if let Some(bound) = size.strip_prefix("<=") {
+        bound
+            .parse::<usize>()
+            .map_err(|_| format!("invalid sequence bound '{}'", bound))?;
+        return Ok(Shape::Sequence);
+    }
+    size.parse()
+        .map(Shape::Array)
+        .map_err(|_| format!("invalid array size '{}'", size))
+}
+
+fn primitive(name: &str) -> Option<FieldType> {
+    Some(match name {
+        "bool" => FieldType::Bool,
+        "byte" | "uint8" => FieldType::U8,
+        "char" => FieldType::Char,
+        "int8" => FieldType::I8,
+        "int16" => FieldType::I16,
+        "int32" => FieldType::I32,
+        "int64" => FieldType::I64,
+        "uint16" => FieldType::U16,
+        "uint32" => FieldType::U32,
+        "uint64" => FieldType::U64,
+        "float32" => FieldType::F32,
+        "float64" => FieldType::F64,
+        "string" => FieldType::String,
+        _ => return None,
+    })
+}
+
+/// `Name` or `pkg/Name`
+fn is_type_name(name: &str) -> bool {
+    match name.split_once('/') {
+        Some((package, name)) => is_identifier(package) && is_identifier(name),
+        None => is_identifier(name),
+    }
+}
+
+/// The value of a constant of type `ty` written as `raw`
+fn constant(ty: &FieldType, raw: &str) -> Option<DynamicValue> {
+    let value = match ty {
+        FieldType::Bool => match raw.to_ascii_lowercase().as_str() {
+            "true" | "1" => DynamicValue::Bool(true),
+            "false" | "0" => DynamicValue::Bool(false),
+            _ => return None,
+        },
+        FieldType::String => DynamicValue::String(unquote(raw).to_string()),
+        FieldType::Char => DynamicValue::String(unquote(raw).to_string()),
+        _ => raw
+            .parse::<i64>()
+            .map(DynamicValue::Int)
+            .or_else(|_| raw.parse::<u64>().map(DynamicValue::UInt))
+            .or_else(|_| raw.parse::<f64>().map(DynamicValue::Float))
+            .ok()?,
+    };
+    ty.coerce(value).ok()
+}
+
+fn unquote(raw: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| raw.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(definition: &str) -> (usize, usize, String) {
+        match parse("idl_test/Invalid", definition) {
+            Err(Error::InvalidDefinition {
+                line,
+                column,
+                reason,
+                ..
+            }) => (line, column, reason),
+            result => panic!("expected an invalid definition, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_parse_types() {
+        assert_eq!(
+            parse_type("float64[3]"),
+            Ok(TypeSpec {
+                base: Base::Primitive(FieldType::F64),
+                shape: Shape::Array(3),
+            })
+        );
+        assert_eq!(parse_type("byte[]").unwrap().shape, Shape::Sequence);
+        assert_eq!(parse_type("string<=5[<=2]").unwrap().shape, Shape::Sequence);
+        assert_eq!(
+            parse_type("geometry_msgs/Point").unwrap().base,
+            Base::Message("geometry_msgs/Point".into())
+        );
+        assert_eq!(
+            parse_type("float64[x]"),
+            Err((7, "invalid array size 'x'".into()))
+        );
+        assert_eq!(parse_type("a/b/C"), Err((0, "invalid type 'a/b/C'".into())));
+    }
+
+    #[test]
+    fn test_parse_definition() {
+        let descriptor = parse(
+            "idl_test/Ranges",
+            "# A scan\n\
+             uint8 MODE_FAST=1 # constant\n\
+             string LABEL = front # lidar\n\
+             \n\
+             float32[] ranges\n\
+             bool[2] flags   # comment\n",
+        )
+        .unwrap();
+        let fields: Vec<(&str, String)> = descriptor
+            .fields()
+            .iter()
+            .map(|field| (field.name.as_str(), field.ty.to_string()))
+            .collect();
+        assert_eq!(
+            fields,
+            [("ranges", "Vec<f32>".into()), ("flags", "[bool;2]".into())]
+        );
+        assert_eq!(
+            descriptor.constant("MODE_FAST"),
+            Some(&DynamicValue::UInt(1))
+        );
+        assert_eq!(
+            descriptor.constant("LABEL"),
+            Some(&DynamicValue::String("front # lidar".into()))
+        );
+    }
+
+    #[test]
+    fn test_constants() {
+        let descriptor = parse(
+            "idl_test/Constants",
+            "int8 LOW=-128\nfloat64 PI=2.5\nbool ON=True\nchar C='x'\nstring S=\"quoted\"",
+        )
+        .unwrap();
+        assert_eq!(descriptor.constant("LOW"), Some(&DynamicValue::Int(-128)));
+        assert_eq!(descriptor.constant("PI"), Some(&DynamicValue::Float(2.5)));
+        assert_eq!(descriptor.constant("ON"), Some(&DynamicValue::Bool(true)));
+        assert_eq!(descriptor.constant("C"), Some(&DynamicValue::Char('x')));
+        assert_eq!(descriptor.constant("S"), Some(&"quoted".into()));
+        assert!(descriptor.fields().is_empty());
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        assert_eq!(
+            parse_error("int32 a\n  flaot64 b"),
+            (2, 3, "unknown type 'flaot64'".into())
+        );
+        assert_eq!(
+            parse_error("uint8 X=256"),
+            (1, 9, "invalid uint8 constant '256'".into())
+        );
+        assert_eq!(
+            parse_error("int32 a\nint64 a"),
+            (2, 7, "duplicate name 'a'".into())
+        );
+        assert_eq!(
+            parse_error("int32 a 5"),
+            (1, 9, "default values are not supported".into())
+        );
+        assert_eq!(parse_error("int32"), (1, 6, "expected a field name".into()));
+        assert_eq!(parse_error("int32 2a"), (1, 7, "invalid name '2a'".into()));
+        assert_eq!(
+            parse_error("int32[] X=1"),
+            (1, 1, "constants must have a primitive type".into())
+        );
+        assert_eq!(
+            parse_error("int32 =1"),
+            (1, 7, "expected a constant name".into())
+        );
+    }
+}
@@ -0,0 +1,1175 @@
+//! Bringing up containers of nodes from YAML launch files
+//!
+//! A launch file lists [containers](crate::container) and the nodes to load
+//! into them, by the name of a factory registered with a [`Launcher`]:
+//!
+//! ```yaml
+//! args:
+//!   rate_hz: 30               # default, overridden by the caller or includer
+//!   device: ~                 # required
+//! include:
+//!   - file: lidar.launch.yaml # relative to this file
+//!     args: { frame: base_link }
+//!     if: $SIM != "1"
+//! containers:
+//!   - name: perception
+//!     namespace: /robot1
+//!     restart: { max_restarts: 3, backoff_ms: 100 }
+//!     nodes:
+//!       - factory: camera_driver
+//!         name: front_camera      # the factory's name if left out
+//!         namespace: cameras      # relative to the container's
+//!         parameters: { fps: $rate_hz, device: "/dev/${device}" }
+//!         remaps: { image_raw: /robot1/front/image }
+//!       - factory: fake_camera
+//!         if: $SIM == "1"
+//! ```
+//!
+//! Strings may refer to `$name` or `${name}`, an argument of the file or
+//! else an environment variable; `$$` is a literal `$`. A value that is a
+//! single reference takes the type of what it refers to, so `fps: $rate_hz`
+//! is an integer. An `if:` holds `a == b`, `a != b` or a single value, which
+//! is true unless empty, `0` or `false`; operands may be quoted, with
+//! single quotes keeping a `$` literal, and unset variables are empty there
+//! while anywhere else they are an error. Included files add their
+//! containers before those of the including file. YAML anchors and `<<`
+//! merge keys are supported, to repeat similar nodes.
+//!
+//! [`LaunchDescription::load`] reads a file and everything it includes and
+//! checks all that does not depend on the factories. [`Launcher::start`]
+//! then checks that every factory is registered before it starts anything.
+//! Errors name the file and the YAML path of the offending key, such as
+//! `containers[0].nodes[2].factory`:
+//!
+//! ```no_run
+//! # use agentic_robotics_core::launch::Launcher;
+//! # async fn run() -> agentic_robotics_core::error::Result<()> {
+//! let mut launcher = Launcher::new();
+//! launcher.register("camera_driver", |node| async move {
+//!     let fps: i64 = node.parameters().get("fps")?;
+//!     // ... publish images until unloaded
+//!     Ok(())
+//! });
+//! let launch = launcher.launch("robot.launch.yaml", [("device", "video0")])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::container::{Factory, LoadOptions, LoadedNode, NodeContainer, RestartPolicy};
+use crate::error::{Error, Result};
+use crate::name::{Remap, TopicName};
+use crate::node::Node;
+use crate::params::ParameterValue;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+/// The containers and nodes of a launch file and its includes, with
+/// arguments substituted and conditions applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchDescription {
+    pub containers: Vec<ContainerLaunch>,
+}
+
+/// A container to start, see [`NodeContainer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerLaunch {
+    pub name: String,
+    /// Absolute namespace of the container, `/` if not given
+    pub namespace: String,
+    pub restart: RestartPolicy,
+    pub nodes: Vec<NodeLaunch>,
+}
+
+/// A node to load, see [`NodeContainer::load`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeLaunch {
+    pub factory: String,
+    pub name: String,
+    /// Namespace of the node; a relative one is resolved against the
+    /// container's
+    pub namespace: Option<String>,
+    pub parameters: Vec<(String, ParameterValue)>,
+    pub remaps: Vec<(String, String)>,
+    /// File the node is declared in
+    pub file: String,
+    /// YAML path of the node in `file`
+    pub path: String,
+}
+
+impl NodeLaunch {
+    fn options(&self) -> LoadOptions {
+        let mut options = LoadOptions::new().name(&self.name);
+        if let Some(namespace) = &self.namespace {
+            options = options.namespace(namespace);
+        }
+        for (from, to) in &self.remaps {
+            options = options.remap(from, to);
+        }
+        for (name, value) in &self.parameters {
+            options = options.parameter(name, value.clone());
+        }
+        options
+    }
+}
+
+impl LaunchDescription {
+    /// Read the launch file at `path` and the files it includes, with `args`
+    /// overriding the defaults of the file's arguments
+    ///
+    /// Fails with [`Error::Io`] if the file cannot be read and with
+    /// [`Error::InvalidLaunch`] if it, or an included file, is invalid.
+    pub fn load<K, V>(
+        path: impl AsRef<Path>,
+        args: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let path = path.as_ref();
+        let canonical = path.canonicalize()?;
+        let text = std::fs::read_to_string(&canonical)?;
+        let mut loader = Loader::default();
+        loader.document(&text, path.display().to_string(), canonical, collect(args))?;
+        Ok(Self {
+            containers: loader.containers,
+        })
+    }
+
+    /// Read a launch file from `yaml`, resolving its includes against the
+    /// current directory
+    pub fn parse<K, V>(yaml: &str, args: impl IntoIterator<Item = (K, V)>) -> Result<Self>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut loader = Loader::default();
+        loader.document(
+            yaml,
+            "<string>".into(),
+            PathBuf::from("<string>"),
+            collect(args),
+        )?;
+        Ok(Self {
+            containers: loader.containers,
+        })
+    }
+
+    /// Every node to load, in order
+    pub fn nodes(&self) -> impl Iterator<Item = &NodeLaunch> {
+        self.containers
+            .iter()
+            .flat_map(|container| &container.nodes)
+    }
+}
+
+fn collect<K: Into<String>, V: Into<String>>(
+    args: impl IntoIterator<Item = (K, V)>,
+) -> BTreeMap<String, String> {
+    args.into_iter()
+        .map(|(name, value)| (name.into(), value.into()))
+        .collect()
+}
+
+/// Node factories to start launch files with, see the
+/// [module documentation](self)
+#[derive(Default)]
+pub struct Launcher {
+    factories: HashMap<String, Factory>,
+}
+
+impl Launcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make nodes of kind `factory` with `make`, as with
+    /// [`NodeContainer::register`]
+    pub fn register<F, Fut>(&mut self, factory: impl Into<String>, make: F)
+    where
+        F: Fn(Arc<Node>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let make: Factory = Arc::new(move |node| Box::pin(make(node)));
+        self.factories.insert(factory.into(), make);
+    }
+
+    /// Names of the registered factories, sorted
+    pub fn factories(&self) -> Vec<String> {
+        let mut factories: Vec<String> = self.factories.keys().cloned().collect();
+        factories.sort();
+        factories
+    }
+
+    /// Check that a factory is registered for every node of `launch`
+    pub fn validate(&self, launch: &LaunchDescription) -> Result<()> {
+        match launch
+            .nodes()
+            .find(|node| !self.factories.contains_key(&node.factory))
+        {
+            Some(node) => Err(Error::InvalidLaunch {
+                file: node.file.clone(),
+                path: format!("{}.factory", node.path),
+                reason: format!(
+                    "no node factory '{}'; registered: {}",
+                    node.factory,
+                    self.factories().join(", ")
+                ),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Create the containers of `launch` and load their nodes
+    ///
+    /// Nothing is started unless [`validate`](Self::validate) passes. Must
+    /// be called from within a Tokio runtime.
+    pub fn start(&self, launch: &LaunchDescription) -> Result<Launch> {
+        self.validate(launch)?;
+        let mut containers = Vec::with_capacity(launch.containers.len());
+        for spec in &launch.containers {
+            let container = NodeContainer::new(spec.name.clone())
+                .with_namespace(&spec.namespace)?
+                .with_restart_policy(spec.restart);
+            for (factory, make) in &self.factories {
+                container.register_factory(factory.clone(), make.clone());
+            }
+            for node in &spec.nodes {
+                container.load(&node.factory, node.options())?;
+            }
+            containers.push(container);
+        }
+        info!(
+            "Launched {} nodes in {} containers",
+            launch.nodes().count(),
+            containers.len()
+        );
+        Ok(Launch { containers })
+    }
+
+    /// Read the launch file at `path` with `args` and start it, see
+    /// [`LaunchDescription::load`] and [`start`](Self::start)
+    pub fn launch<K, V>(
+        &self,
+        path: impl AsRef<Path>,
+        args: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Launch>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.start(&LaunchDescription::load(path, args)?)
+    }
+}
+
+/// The containers started by [`Launcher::start`]
+///
+/// Dropping it unloads every node.
+pub struct Launch {
+    containers: Vec<NodeContainer>,
+}
+
+impl Launch {
+    pub fn containers(&self) -> &[NodeContainer] {
+        &self.containers
+    }
+
+    pub fn container(&self, name: &str) -> Option<&NodeContainer> {
+        self.containers
+            .iter()
+            .find(|container| container.name() == name)
+    }
+
+    /// The nodes loaded into every container
+    pub fn nodes(&self) -> Vec<LoadedNode> {
+        self.containers
+            .iter()
+            .flat_map(NodeContainer::nodes)
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Loader {
+    /// Files being read, the innermost last, to catch include cycles
+    files: Vec<PathBuf>,
+    containers: Vec<ContainerLaunch>,
+    /// Where each container was declared
+    declared: HashMap<String, String>,
+}
+
+impl Loader {
+    /// Read the launch file `file`, found at `canonical`
+    fn document(
+        &mut self,
+        text: &str,
+        file: String,
+        canonical: PathBuf,
+        args: BTreeMap<String, String>,
+    ) -> Result<()> {
+        let mut scope = Scope {
+            file,
+            dir: canonical
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            args: BTreeMap::new(),
+        };
+        let mut root: Value = serde_yaml::from_str(text).map_err(|e| scope.error("", e))?;
+        root.apply_merge().map_err(|e| scope.error("", e))?;
+        let root = match root {
+            Value::Null => Mapping::new(),
+            Value::Mapping(root) => root,
+            _ => return Err(scope.error("", "expected a mapping")),
+        };
+        scope.keys(&root, "", &["args", "include", "containers"])?;
+        scope.arguments(root.get("args"), args)?;
+
+        self.files.push(canonical);
+        let result = self.includes(&scope, root.get("include"));
+        self.files.pop();
+        result?;
+
+        if let Some(containers) = root.get("containers") {
+            for (i, container) in scope.sequence(containers, "containers")?.iter().enumerate() {
+                self.container(&scope, container, format!("containers[{}]", i))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn includes(&mut self, scope: &Scope, includes: Option<&Value>) -> Result<()> {
+        let Some(includes) = includes else {
+            return Ok(());
+        };
+        for (i, include) in scope.sequence(includes, "include")?.iter().enumerate() {
+            let path = format!("include[{}]", i);
+            let include = scope.mapping(include, &path)?;
+            scope.keys(include, &path, &["file", "args", "if"])?;
+            if !scope.enabled(include, &path)? {
+                continue;
+            }
+            let file_path = format!("{}.file", path);
+            let file = scope.string(scope.required(include, "file", &path)?, &file_path)?;
+            let mut args = BTreeMap::new();
+            if let Some(values) = include.get("args") {
+                let args_path = format!("{}.args", path);
+                for (name, value) in scope.entries(values, &args_path)? {
+                    let value = scope.string(value, &format!("{}.{}", args_path, name))?;
+                    args.insert(name, value);
+                }
+            }
+
+            let target = scope.dir.join(&file);
+            let unreadable =
+                |e: std::io::Error| scope.error(&file_path, format!("cannot read {}: {}", file, e));
+            let canonical = target.canonicalize().map_err(unreadable)?;
+            if self.files.contains(&canonical) {
+                return Err(scope.error(&file_path, format!("{} includes itself", file)));
+            }
+            let text = std::fs::read_to_string(&canonical).map_err(unreadable)?;
+            self.document(&text, target.display().to_string(), canonical, args)?;
+        }
+        Ok(())
+    }
+
+    fn container(&mut self, scope: &Scope, container: &Value, path: String) -> Result<()> {
+        let container = scope.mapping(container, &path)?;
+        scope.keys(
+            container,
+            &path,
+            &["name", "namespace", "restart", "nodes", "if"],
+        )?;
+        if !scope.enabled(container, &path)? {
+            return Ok(());
+        }
+        let name_path = format!("{}.name", path);
+        let name = scope.string(scope.required(container, "name", &path)?, &name_path)?;
+        if name.is_empty() {
+            return Err(scope.error(&name_path, "container name is empty"));
+        }
+        if let Some(previous) = self.declared.get(&name) {
+            return Err(scope.error(
+                &name_path,
+                format!("container '{}' is already declared in {}", name, previous),
+            ));
+        }
+        let namespace = match container.get("namespace") {
+            Some(namespace) => {
+                let namespace_path = format!("{}.namespace", path);
+                let namespace = scope.topic_name(namespace, &namespace_path)?;
+                if !namespace.is_absolute() {
+                    return Err(
+                        scope.error(&namespace_path, "container namespace must be absolute")
+                    );
+                }
+                namespace
+            }
+            None => TopicName::root(),
+        };
+        let restart = match container.get("restart") {
+            Some(restart) => scope.restart(restart, &format!("{}.restart", path))?,
+            None => RestartPolicy::Never,
+        };
+
+        let mut nodes: Vec<NodeLaunch> = Vec::new();
+        let mut names = HashMap::new();
+        if let Some(list) = container.get("nodes") {
+            let nodes_path = format!("{}.nodes", path);
+            for (i, node) in scope.sequence(list, &nodes_path)?.iter().enumerate() {
+                let node_path = format!("{}[{}]", nodes_path, i);
+                let Some(node) = scope.node(node, node_path)? else {
+                    continue;
+                };
+                let resolved = match &node.namespace {
+                    Some(relative) => TopicName::new(relative.as_str())?.resolve(&namespace),
+                    None => namespace.clone(),
+                };
+                let key = (node.name.clone(), resolved.as_str().to_string());
+                if let Some(previous) = names.insert(key, node.path.clone()) {
+                    return Err(scope.error(
+                        &format!("{}.name", node.path),
+                        format!(
+                            "node '{}' is already declared in '{}' at {}",
+                            node.name,
+                            resolved.as_str(),
+                            previous
+                        ),
+                    ));
+                }
+                nodes.push(node);
+            }
+        }
+
+        self.declared
+            .insert(name.clone(), format!("{} at {}", scope.file, path));
+        self.containers.push(ContainerLaunch {
+            name,
+            namespace: namespace.as_str().to_string(),
+            restart,
+            nodes,
+        });
+        Ok(())
+    }
+}
+
+/// A launch file being read, with its arguments
+struct Scope {
+    file: String,
+    /// Directory includes are resolved against
+    dir: PathBuf,
+    args: BTreeMap<String, String>,
+}
+
+impl Scope {
+    fn error(&self, path: &str, reason: impl ToString) -> Error {
+        Error::InvalidLaunch {
+            file: self.file.clone(),
+            path: path.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Set the arguments declared by `declared` from `given`, or else their
+    /// defaults
+    fn arguments(
+        &mut self,
+        declared: Option<&Value>,
+        mut given: BTreeMap<String, String>,
+    ) -> Result<()> {
+        if let Some(declared) = declared {
+            for (name, default) in self.entries(declared, "args")? {
+                let path = format!("args.{}", name);
+                let value = match given.remove(&name) {
+                    Some(value) => value,
+                    None if default.is_null() => {
+                        return Err(self.error(&path, format!("argument '{}' is required", name)))
+                    }
+                    // Defaults may refer to earlier arguments
+                    None => self.string(default, &path)?,
+                };
+                self.args.insert(name, value);
+            }
+        }
+        match given.into_keys().next() {
+            Some(name) => Err(self.error("args", format!("argument '{}' is not declared", name))),
+            None => Ok(()),
+        }
+    }
+
+    /// The node at `path`, or `None` if its condition is false
+    fn node(&self, node: &Value, path: String) -> Result<Option<NodeLaunch>> {
+        let node = self.mapping(node, &path)?;
+        self.keys(
+            node,
+            &path,
+            &["factory", "name", "namespace", "parameters", "remaps", "if"],
+        )?;
+        if !self.enabled(node, &path)? {
+            return Ok(None);
+        }
+        let factory = self.string(
+            self.required(node, "factory", &path)?,
+            &format!("{}.factory", path),
+        )?;
+        let name = match node.get("name") {
+            Some(name) => self.string(name, &format!("{}.name", path))?,
+            None => factory.clone(),
+        };
+        if name.is_empty() {
+            return Err(self.error(&format!("{}.name", path), "node name is empty"));
+        }
+        let namespace = match node.get("namespace") {
+            Some(namespace) => Some(
+                self.topic_name(namespace, &format!("{}.namespace", path))?
+                    .as_str()
+                    .to_string(),
+            ),
+            None => None,
+        };
+
+        let mut parameters = Vec::new();
+        if let Some(values) = node.get("parameters") {
+            let parameters_path = format!("{}.parameters", path);
+            for (name, value) in self.entries(values, &parameters_path)? {
+                let value = self.parameter(value, &format!("{}.{}", parameters_path, name))?;
+                parameters.push((name, value));
+            }
+        }
+        let mut remaps = Vec::new();
+        if let Some(values) = node.get("remaps") {
+            let remaps_path = format!("{}.remaps", path);
+            for (from, to) in self.entries(values, &remaps_path)? {
+                let to_path = format!("{}.{}", remaps_path, from);
+                let to = self.string(to, &to_path)?;
+                Remap::new(&from, &to).map_err(|e| self.error(&to_path, e))?;
+                remaps.push((from, to));
+            }
+        }
+
+        Ok(Some(NodeLaunch {
+            factory,
+            name,
+            namespace,
+            parameters,
+            remaps,
+            file: self.file.clone(),
+            path,
+        }))
+    }
+
+    fn restart(&self, restart: &Value, path: &str) -> Result<RestartPolicy> {
+        let restart = self.mapping(restart, path)?;
+        self.keys(restart, path, &["max_restarts", "backoff_ms"])?;
+        let max_restarts_path = format!("{}.max_restarts", path);
+        let max_restarts = self.integer(
+            self.required(restart, "max_restarts", path)?,
+            &max_restarts_path,
+        )?;
+        let backoff = match restart.get("backoff_ms") {
+            Some(backoff) => self.integer(backoff, &format!("{}.backoff_ms", path))?,
+            None => 0,
+        };
+        Ok(RestartPolicy::OnPanic {
+            max_restarts: u32::try_from(max_restarts)
+                .map_err(|_| self.error(&max_restarts_path, "too many restarts"))?,
+            backoff: Duration::from_millis(backoff),
+        })
+    }
+
+    /// Whether the `if:` of `map` holds, or it has none
+    fn enabled(&self, map: &Mapping, path: &str) -> Result<bool> {
+        match map.get("if") {
+            Some(condition) => self.condition(condition, &format!("{}.if", path)),
+            None => Ok(true),
+        }
+    }
+
+    fn condition(&self, condition: &Value, path: &str) -> Result<bool> {
+        let expression = match condition {
+            Value::Bool(value) => return Ok(*value),
+            Value::String(expression) => expression,
+            _ => return Err(self.error(path, "expected a condition such as $SIM == \"1\"")),
+        };
+        let mut quote = None;
+        let mut operator = None;
+        for (i, c) in expression.char_indices() {
+            match (quote, c) {
+                (Some(open), c) if c == open => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '=' | '!') if expression[i + 1..].starts_with('=') => {
+                    operator = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        match operator {
+            Some(i) => {
+                let left = self.operand(&expression[..i], path)?;
+                let right = self.operand(&expression[i + 2..], path)?;
+                Ok((left == right) == expression[i..].starts_with("=="))
+            }
+            None => {
+                let value = self.operand(expression, path)?;
+                Ok(!(value.is_empty() || value == "0" || value.eq_ignore_ascii_case("false")))
+            }
+        }
+    }
+
+    fn operand(&self, operand: &str, path: &str) -> Result<String> {
+        let operand = operand.trim();
+        for quote in ['"', '\''] {
+            if let Some(quoted) = operand
+                .strip_prefix(quote)
+                .and_then(|rest| rest.strip_suffix(quote))
+            {
+                return match quote {
+                    '"' => self.substitute(quoted, path, true),
+                    _ => Ok(quoted.to_string()),
+                };
+            }
+        }
+        if operand.contains(char::is_whitespace) || operand.contains(['"', '\'', '=']) {
+            return Err(self.error(
+                path,
+                format!(
+                    "cannot read '{}'; expected `a == b`, `a != b` or a single value",
+                    operand
+                ),
+            ));
+        }
+        self.substitute(operand, path, true)
+    }
+
+    /// Replace the references in `text`; unset ones are empty if `lenient`,
+    /// and an error otherwise
+    fn substitute(&self, text: &str, path: &str, lenient: bool) -> Result<String> {
+        let mut substituted = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('$') {
+            substituted.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            if let Some(after) = rest.strip_prefix('$') {
+                substituted.push('$');
+                rest = after;
+                continue;
+            }
+            let (name, after) = match rest.strip_prefix('{') {
+                Some(braced) => match braced.find('}') {
+                    Some(end) => (&braced[..end], &braced[end + 1..]),
+                    None => return Err(self.error(path, format!("unclosed '${{' in '{}'", text))),
+                },
+                None => {
+                    let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+                    rest.split_at(end)
+                }
+            };
+            if name.is_empty() || !name.chars().all(is_name_char) {
+                return Err(self.error(
+                    path,
+                    format!(
+                        "expected a name after '$' in '{}'; write '$$' for a '$'",
+                        text
+                    ),
+                ));
+            }
+            match self.lookup(name) {
+                Some(value) => substituted.push_str(&value),
+                None if lenient => {}
+                None => {
+                    return Err(self.error(
+                        path,
+                        format!(
+                            "'{}' is neither an argument nor an environment variable",
+                            name
+                        ),
+                    ))
+                }
+            }
+            rest = after;
+        }
+        substituted.push_str(rest);
+        Ok(substituted)
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.args
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+    }
+
+    /// `value` with its references replaced
+    fn value(&self, value: &Value, path: &str) -> Result<Value> {
+        let Value::String(text) = value else {
+            return Ok(value.clone());
+        };
+        let substituted = self.substitute(text, path, false)?;
+        if !is_reference(text) {
+            return Ok(Value::String(substituted));
+        }
+        // A lone reference takes the type of its value
+        match serde_yaml::from_str(&substituted) {
+            Ok(value @ (Value::Bool(_) | Value::Number(_))) => Ok(value),
+            _ => Ok(Value::String(substituted)),
+        }
+    }
+
+    fn string(&self, value: &Value, path: &str) -> Result<String> {
+        match self.value(value, path)? {
+            Value::String(text) => Ok(text),
+            Value::Number(number) => Ok(number.to_string()),
+            Value::Bool(value) => Ok(value.to_string()),
+            _ => Err(self.error(path, "expected a string")),
+        }
+    }
+
+    fn integer(&self, value: &Value, path: &str) -> Result<u64> {
+        self.value(value, path)?
+            .as_u64()
+            .ok_or_else(|| self.error(path, "expected a non-negative integer"))
+    }
+
+    fn topic_name(&self, value: &Value, path: &str) -> Result<TopicName> {
+        TopicName::new(self.string(value, path)?).map_err(|e| self.error(path, e))
+    }
+
+    fn parameter(&self, value: &Value, path: &str) -> Result<ParameterValue> {
+        match self.value(value, path)? {
+            Value::Bool(value) => Ok(value.into()),
+            Value::Number(number) => Ok(match number.as_i64() {
+                Some(value) => value.into(),
+                None => number.as_f64().unwrap_or(f64::NAN).into(),
+            }),
+            Value::String(text) => Ok(text.into()),
+            Value::Sequence(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let path = format!("{}[{}]", path, i);
+                    match self.value(item, &path)? {
+                        Value::Number(number) => Ok(number.as_f64().unwrap_or(f64::NAN)),
+                        _ => Err(self.error(&path, "expected a number")),
+                    }
+                })
+                .collect::<Result<Vec<f64>>>()
+                .map(ParameterValue::DoubleArray),
+            _ => Err(self.error(path, "expected a bool, number, string or list of numbers")),
+        }
+    }
+
+    fn mapping<'a>(&self, value: &'a Value, path: &str) -> Result<&'a Mapping> {
+        value
+            .as_mapping()
+            .ok_or_else(|| self.error(path, "expected a mapping"))
+    }
+
+    fn sequence<'a>(&self, value: &'a Value, path: &str) -> Result<&'a Vec<Value>> {
+        value
+            .as_sequence()
+            .ok_or_else(|| self.error(path, "expected a list"))
+    }
+
+    fn required<'a>(&self, map: &'a Mapping, key: &str, path: &str) -> Result<&'a Value> {
+        map.get(key)
+            .ok_or_else(|| self.error(path, format!("missing '{}'", key)))
+    }
+
+    /// The entries of the mapping `value`, whose keys are names
+    fn entries<'a>(&self, value: &'a Value, path: &str) -> Result<Vec<(String, &'a Value)>> {
+        self.mapping(value, path)?
+            .iter()
+            .map(|(key, value)| match key {
+                Value::String(key) => Ok((key.clone(), value)),
+                _ => Err(self.error(path, format!("expected a name as key, found {:?}", key))),
+            })
+            .collect()
+    }
+
+    /// Fail on keys of `map` other than `allowed`, which are likely typos
+    fn keys(&self, map: &Mapping, path: &str, allowed: &[&str]) -> Result<()> {
+        for key in map.keys() {
+            if !key.as_str().is_some_and(|key| allowed.contains(&key)) {
+                let key = key
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("{:?}", key));
+                return Err(self.error(
+                    &child(path, &key),
+                    format!(
+                        "unknown key '{}'; expected one of: {}",
+                        key,
+                        allowed.join(", ")
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn child(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Whether `text` is a single `$name` or `${name}`
+fn is_reference(text: &str) -> bool {
+    let Some(name) = text.strip_prefix('$') else {
+        return false;
+    };
+    let name = name
+        .strip_prefix('{')
+        .and_then(|name| name.strip_suffix('}'))
+        .unwrap_or(name);
+    !name.is_empty() && name.chars().all(is_name_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph;
+
+    /// A directory of launch files for the test `name`
+    fn launch_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ros3_launch_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, text) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    fn invalid(error: Error) -> (String, String) {
+        match error {
+            Error::InvalidLaunch { path, reason, .. } => (path, reason),
+            other => panic!("expected an invalid launch file, got {}", other),
+        }
+    }
+
+    const CAMERA: &str = r#"
+args:
+  fps: 30
+  device: ~
+  frame: ${device}_link
+containers:
+  - name: cameras
+    namespace: /robot1
+    nodes:
+      - factory: camera_driver
+        parameters:
+          fps: $fps
+          device: /dev/$device
+          frame: ${frame}
+          label: "$${device}"
+          exposure: [0.5, $fps]
+          enabled: $LAUNCH_TEST_ENABLED
+        remaps:
+          image_raw: /robot1/${device}/image
+"#;
+
+    #[test]
+    fn test_substitution() {
+        std::env::set_var("LAUNCH_TEST_ENABLED", "true");
+        let launch = LaunchDescription::parse(CAMERA, [("device", "video0")]).unwrap();
+        let node = launch.nodes().next().unwrap();
+        assert_eq!(node.name, "camera_driver");
+        assert_eq!(
+            node.parameters,
+            vec![
+                ("fps".to_string(), ParameterValue::Integer(30)),
+                ("device".to_string(), "/dev/video0".into()),
+                ("frame".to_string(), "video0_link".into()),
+                ("label".to_string(), "${device}".into()),
+                ("exposure".to_string(), vec![0.5, 30.0].into()),
+                ("enabled".to_string(), ParameterValue::Bool(true)),
+            ]
+        );
+        assert_eq!(
+            node.remaps,
+            vec![("image_raw".to_string(), "/robot1/video0/image".to_string())]
+        );
+
+        let launch =
+            LaunchDescription::parse(CAMERA, [("device", "video1"), ("fps", "2.5")]).unwrap();
+        let node = launch.nodes().next().unwrap();
+        assert_eq!(node.parameters[0].1, ParameterValue::Double(2.5));
+
+        let (path, reason) = invalid(LaunchDescription::parse(CAMERA, [("fps", "1")]).unwrap_err());
+        assert_eq!(path, "args.device");
+        assert_eq!(reason, "argument 'device' is required");
+        let (path, _) = invalid(
+            LaunchDescription::parse(CAMERA, [("device", "video0"), ("gain", "2")]).unwrap_err(),
+        );
+        assert_eq!(path, "args");
+
+        let yaml = CAMERA.replace("$LAUNCH_TEST_ENABLED", "$LAUNCH_TEST_UNSET");
+        let error = LaunchDescription::parse(&yaml, [("device", "video0")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid launch file <string> at containers[0].nodes[0].parameters.enabled: \
+             'LAUNCH_TEST_UNSET' is neither an argument nor an environment variable"
+        );
+    }
+
+    #[test]
+    fn test_conditions() {
+        std::env::set_var("LAUNCH_TEST_SIM", "1");
+        let yaml = r#"
+args:
+  lidar: "true"
+containers:
+  - name: drivers
+    nodes:
+      - { factory: fake_camera, if: $LAUNCH_TEST_SIM == "1" }
+      - { factory: camera, if: $LAUNCH_TEST_SIM != "1" }
+      - { factory: lidar, if: $lidar }
+      - { factory: bumper, if: $LAUNCH_TEST_NO_SUCH_VAR }
+      - { factory: sonar, if: '$LAUNCH_TEST_NO_SUCH_VAR == ""' }
+      - { factory: imu, if: "'$LAUNCH_TEST_SIM' == '1'" }
+      - { factory: gps, if: false }
+  - name: simulation
+    if: ${LAUNCH_TEST_SIM} == 1
+"#;
+        let factories = |launch: &LaunchDescription| -> Vec<String> {
+            launch.nodes().map(|node| node.factory.clone()).collect()
+        };
+        let launch = LaunchDescription::parse(yaml, [("lidar", "0")]).unwrap();
+        assert_eq!(factories(&launch), ["fake_camera", "sonar"]);
+        assert_eq!(launch.containers.len(), 2);
+
+        let launch = LaunchDescription::parse(yaml, Vec::<(String, String)>::new()).unwrap();
+        assert_eq!(factories(&launch), ["fake_camera", "lidar", "sonar"]);
+
+        let yaml = "containers:\n  - { name: a, if: $LAUNCH_TEST_SIM == 1 && $X }\n";
+        let (path, reason) =
+            invalid(LaunchDescription::parse(yaml, Vec::<(String, String)>::new()).unwrap_err());
+        assert_eq!(path, "containers[0].if");
+        assert!(reason.starts_with("cannot read '1 && $X'"), "{}", reason);
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = launch_dir(
+            "include",
+            &[
+                (
+                    "robot.launch.yaml",
+                    r#"
+args:
+  robot: robot1
+include:
+  - file: sensors/camera.launch.yaml
+    args: { side: front, namespace: "/$robot" }
+  - file: sensors/camera.launch.yaml
+    args: { side: rear, namespace: "/$robot", fps: 5 }
+  - file: sensors/camera.launch.yaml
+    args: { side: top, namespace: "/$robot" }
+    if: false
+containers:
+  - name: planning
+    namespace: /$robot
+    nodes:
+      - factory: planner
+"#,
+                ),
+                (
+                    "sensors/camera.launch.yaml",
+                    r#"
+args:
+  side: ~
+  namespace: ~
+  fps: 30
+containers:
+  - name: camera_$side
+    namespace: $namespace/cameras
+    nodes:
+      - factory: camera_driver
+        name: $side
+        parameters: { fps: $fps }
+"#,
+                ),
+                (
+                    "loop.launch.yaml",
+                    "include:\n  - file: sensors/../loop.launch.yaml\n",
+                ),
+            ],
+        );
+
+        let launch =
+            LaunchDescription::load(dir.join("robot.launch.yaml"), [("robot", "r2")]).unwrap();
+        let containers: Vec<_> = launch
+            .containers
+            .iter()
+            .map(|container| (container.name.as_str(), container.namespace.as_str()))
+            .collect();
+        assert_eq!(
+            containers,
+            [
+                ("camera_front", "/r2/cameras"),
+                ("camera_rear", "/r2/cameras"),
+                ("planning", "/r2"),
+            ]
+        );
+        let rear = &launch.containers[1].nodes[0];
+        assert_eq!(rear.name, "rear");
+        assert_eq!(
+            rear.parameters,
+            vec![("fps".to_string(), ParameterValue::Integer(5))]
+        );
+        assert!(rear.file.ends_with("camera.launch.yaml"));
+        assert_eq!(rear.path, "containers[0].nodes[0]");
+
+        let (path, reason) = invalid(
+            LaunchDescription::load(dir.join("loop.launch.yaml"), [("a", "b")]).unwrap_err(),
+        );
+        assert_eq!(path, "args");
+        assert_eq!(reason, "argument 'a' is not declared");
+        let (path, reason) = invalid(
+            LaunchDescription::load(dir.join("loop.launch.yaml"), Vec::<(String, String)>::new())
+                .unwrap_err(),
+        );
+        assert_eq!(path, "include[0].file");
+        assert_eq!(reason, "sensors/../loop.launch.yaml includes itself");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let none = Vec::<(String, String)>::new;
+        let cases = [
+            (
+                "containers:\n  - name: a\n    nodes:\n      - { factory: x, parameter: { a: 1 } }\n",
+                "containers[0].nodes[0].parameter",
+            ),
+            ("containers:\n  - { name: a, namespace: relative }\n", "containers[0].namespace"),
+            ("containers:\n  - name: a\n  - name: a\n", "containers[1].name"),
+            (
+                "containers:\n  - name: a\n    nodes: [{ factory: x }, { factory: x }]\n",
+                "containers[0].nodes[1].name",
+            ),
+            (
+                "containers:\n  - { name: a, restart: { max_restarts: -1 } }\n",
+                "containers[0].restart.max_restarts",
+            ),
+            (
+                "containers:\n  - name: a\n    nodes:\n      - { factory: x, parameters: { p: { a: 1 } } }\n",
+                "containers[0].nodes[0].parameters.p",
+            ),
+            ("containers: [ { name: a ] }\n", ""),
+        ];
+        for (yaml, expected) in cases {
+            let (path, _) = invalid(LaunchDescription::parse(yaml, none()).unwrap_err());
+            assert_eq!(path, expected, "{}", yaml);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_validates_factories_first() {
+        let launch = LaunchDescription::parse(
+            r#"
+containers:
+  - name: launch_test_drivers
+    namespace: /launch_test
+    restart: { max_restarts: 2, backoff_ms: 10 }
+    nodes:
+      - { factory: camera_driver, name: front, parameters: { fps: 15 } }
+      - { factory: lidar_driver, name: lidar, namespace: sensors }
+"#,
+            Vec::<(String, String)>::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            launch.containers[0].restart,
+            RestartPolicy::OnPanic {
+                max_restarts: 2,
+                backoff: Duration::from_millis(10),
+            }
+        );
+
+        let mut launcher = Launcher::new();
+        launcher.register("camera_driver", |node| async move {
+            let fps: i64 = node.parameters().get("fps")?;
+            assert_eq!(fps, 15);
+            node.closed().await;
+            Ok(())
+        });
+        let error = launcher.start(&launch).err().unwrap();
+        assert_eq!(
+            invalid(error),
+            (
+                "containers[0].nodes[1].factory".to_string(),
+                "no node factory 'lidar_driver'; registered: camera_driver".to_string()
+            )
+        );
+        assert!(graph::list_loaded_nodes()
+            .iter()
+            .all(|node| node.container != "launch_test_drivers"));
+
+        launcher.register("lidar_driver", |node| async move {
+            node.closed().await;
+            Ok(())
+        });
+        let running = launcher.start(&launch).unwrap();
+        let nodes: Vec<_> = running
+            .nodes()
+            .into_iter()
+            .map(|node| (node.name, node.namespace))
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                ("front".to_string(), "/launch_test".to_string()),
+                ("lidar".to_string(), "/launch_test/sensors".to_string()),
+            ]
+        );
+        assert!(running.container("launch_test_drivers").is_some());
+        drop(running);
+        assert!(graph::list_loaded_nodes()
+            .iter()
+            .all(|node| node.container != "launch_test_drivers"));
+    }
+
+    #[test]
+    fn test_stress_test_example() {
+        let file = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/launch/stress_test.launch.yaml"
+        );
+        let launch = LaunchDescription::load(file, [("rate_hz", "1000")]).unwrap();
+        let subscribers = &launch.containers[0];
+        assert_eq!(subscribers.name, "stress_subscribers");
+        let publishers = &launch.containers[1];
+        assert_eq!(publishers.nodes.len(), 10);
+        assert_eq!(publishers.nodes[9].name, "publisher_9");
+        assert_eq!(
+            publishers.nodes[9].remaps,
+            vec![("stress_topic".to_string(), "stress_topic_9".to_string())]
+        );
+        assert_eq!(
+            publishers.nodes[9].parameters[0],
+            ("rate_hz".to_string(), ParameterValue::Integer(1000))
+        );
+        assert!(subscribers
+            .nodes
+            .iter()
+            .all(|node| node.namespace.is_none()));
+        assert_eq!(subscribers.namespace, "/stress");
+    }
+}
@@ -3,31 +3,162 @@
 //! A ground-up Rust rewrite of ROS targeting microsecond-scale determinism
 //! with hybrid WASM/native deployment via npm.
 
+// Lets code generated by `#[derive(Ros3Message)]` name this crate from inside it
+extern crate self as agentic_robotics_core;
+
+pub mod channel;
+pub mod compression;
+pub mod config;
+pub mod container;
 pub mod middleware;
 pub mod serialization;
+pub mod diagnostics;
+pub mod dynamic;
+pub mod events;
+pub mod graph;
+#[cfg(feature = "idl")]
+pub mod idl;
+pub mod launch;
+pub mod lifecycle;
+pub mod logging;
 pub mod message;
+pub mod metrics;
+pub mod name;
+pub mod node;
+pub mod params;
+pub mod pool;
 pub mod publisher;
+pub mod qos;
+pub mod recording;
+pub mod safety;
+pub mod schema;
 pub mod subscriber;
+pub mod sync;
+pub mod tf;
+pub mod time;
+pub mod topic;
+pub mod trace;
+pub mod transport;
+pub mod types;
+pub mod wait;
+#[cfg(unix)]
+pub mod shm;
+pub mod tcp;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "transport-zenoh")]
+pub mod zenoh_transport;
 pub mod service;
+pub mod action;
 pub mod error;
 
-pub use middleware::Zenoh;
-pub use message::{Message, RobotState, PointCloud};
-pub use publisher::Publisher;
-pub use subscriber::Subscriber;
-pub use service::{Service, Queryable};
+pub use middleware::{Zenoh, ZenohConfig};
+pub use compression::Compression;
+pub use config::{ConfigError, ConfigReloader, ParameterChange};
+#[cfg(feature = "hot-reload")]
+pub use config::ConfigWatcher;
+pub use container::{LoadOptions, LoadedNode, NodeContainer, NodeStatus, RestartPolicy};
+pub use diagnostics::{
+    DiagnosticArray, DiagnosticLevel, DiagnosticStatus, DiagnosticTask, DiagnosticUpdater, KeyValue,
+};
+pub use lifecycle::{
+    LifecycleHandle, LifecycleNode, LifecyclePublisher, LifecycleState, Transition, TransitionEvent,
+};
+pub use events::{Event, EVENTS_TOPIC};
+pub use launch::{Launch, LaunchDescription, Launcher};
+pub use logging::{LogConfig, LogFormat, LogRecord};
+pub use message::{
+    Header, Message, PointCloud, RawMessage, RobotState, SharedBytes, SharedRawMessage, Stamped,
+};
+pub use name::{NameResolver, Remap, TopicName};
+pub use node::{Node, Timer};
+pub use params::{ParameterDescriptor, ParameterServer, ParameterValue};
+pub use pool::{BufferPool, BufferPoolConfig, ExhaustedPolicy, PoolStats};
+pub use publisher::{LoanedMessage, Publisher, PublisherOptions};
+pub use qos::{Durability, QosEvent, QosEventWatcher, QosProfile, Reliability};
+pub use recording::{
+    BagInfo, BagReader, MessageFilter, Player, PlayerConfig, Recorder, RecorderConfig,
+    RecorderStats, TopicFilter,
+};
+pub use safety::{EStop, EStopGuard, EStopState, GatedPublisher};
+pub use subscriber::{
+    ChannelKind, DecimatingSubscriber, DirectStats, DirectSubscriber, MappedSubscriber,
+    MessageInfo, OverflowPolicy, Subscriber, SubscriberBuilder, SubscriberStats,
+};
+pub use sync::{ApproximateTimeSync, ExactTimeSync, SyncStats};
+pub use wait::{WaitKey, WaitSet, WaitTimer};
+pub use tf::{TfError, Transform, TransformBuffer, TransformStamped};
+pub use time::{Clock, ClockType, SimClock, Time};
+pub use topic::{PublisherGuid, TopicBus, TopicType};
+pub use types::MessageType;
+pub use tcp::{ConnectionEvent, ConnectionWatcher, TcpConfig, TcpTransport};
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+pub use transport::{TransportConfig, UdpConfig, UdpTransport};
+#[cfg(unix)]
+pub use shm::{ShmConfig, ShmTransport};
+#[cfg(feature = "transport-zenoh")]
+pub use zenoh_transport::ZenohTransport;
+pub use service::{
+    PolledServiceServer, Queryable, Service, ServiceClient, ServiceRequest, ServiceServer,
+};
+pub use action::{ActionClient, ActionServer, GoalHandle, ServerGoalHandle};
 pub use error::{Result, Error};
 
+#[cfg(feature = "derive")]
+pub use agentic_robotics_derive::Ros3Message;
+
+#[doc(hidden)]
+pub use serde;
+#[doc(hidden)]
+pub use serde_json;
+
 /// ROS3 Core version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Version of the core's ABI surface
+///
+/// Covers what crosses a process or binding boundary: the wire formats of
+/// messages, samples and transports, and the types bindings hand to the
+/// core without copying, such as [`SharedBytes`]. Bumped whenever any of
+/// them changes incompatibly, so a binding built against another core can
+/// refuse to load instead of misreading memory.
+pub const ABI_VERSION: u32 = 2;
+
+/// Optional features this build of the core was compiled with, e.g.
+/// `transport-zenoh`
+pub fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "derive") {
+        features.push("derive");
+    }
+    if cfg!(feature = "idl") {
+        features.push("idl");
+    }
+    if cfg!(feature = "protobuf") {
+        features.push("protobuf");
+    }
+    if cfg!(feature = "transport-zenoh") {
+        features.push("transport-zenoh");
+    }
+    if cfg!(feature = "zstd") {
+        features.push("zstd");
+    }
+    if cfg!(feature = "lz4") {
+        features.push("lz4");
+    }
+    if cfg!(feature = "tls") {
+        features.push("tls");
+    }
+    features
+}
+
 /// Initialize ROS3 runtime
+///
+/// Installs human-readable logging at `INFO` level; use [`logging::init`]
+/// to configure it.
 pub fn init() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_level(true)
-        .init();
+    logging::init(LogConfig::default())?;
 
     tracing::info!("ROS3 Core v{} initialized", VERSION);
     Ok(())
@@ -42,4 +173,12 @@ mod tests {
         let result = init();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_features() {
+        assert_eq!(features().contains(&"derive"), cfg!(feature = "derive"));
+        assert_eq!(features().contains(&"zstd"), cfg!(feature = "zstd"));
+        assert_eq!(features().contains(&"lz4"), cfg!(feature = "lz4"));
+        assert_eq!(features().contains(&"tls"), cfg!(feature = "tls"));
+    }
 }
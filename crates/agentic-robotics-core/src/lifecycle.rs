@@ -0,0 +1,684 @@
+//! Managed node lifecycle
+//!
+//! A [`LifecycleNode`] moves through the states of a ROS 2 managed node so a
+//! supervisor can bring a system up in a known order:
+//!
+//! ```text
+//!                configure            activate
+//! Unconfigured ───────────▶ Inactive ──────────▶ Active
+//!              ◀───────────          ◀──────────
+//!                 cleanup             deactivate
+//!
+//! Unconfigured, Inactive, Active ── shutdown ──▶ Finalized
+//! ```
+//!
+//! A hook registered for a transition runs before the state changes; if it
+//! fails, the node stays where it was. Publishers created with
+//! [`LifecycleNode::create_publisher`] only publish while the node is
+//! active.
+//!
+//! Every lifecycle node offers the services `<node>/change_state` and
+//! `<node>/get_state` and publishes each transition, latched, on
+//! `<node>/transition_event`. Tools in the same process find lifecycle nodes
+//! through [`graph::list_lifecycle_nodes`](crate::graph::list_lifecycle_nodes).
+
+use crate::error::{Error, Result};
+use crate::message::Message;
+use crate::node::Node;
+use crate::publisher::Publisher;
+use crate::service::ServiceServer;
+use crate::time::{Clock, Time};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use tracing::{debug, info, warn};
+
+/// State of a [`LifecycleNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleState {
+    #[default]
+    Unconfigured,
+    Inactive,
+    Active,
+    Finalized,
+}
+
+impl LifecycleState {
+    /// The state `transition` leads to from this one, if it is allowed
+    pub fn after(self, transition: Transition) -> Option<LifecycleState> {
+        use LifecycleState::*;
+        match (self, transition) {
+            (Unconfigured, Transition::Configure) => Some(Inactive),
+            (Inactive, Transition::Cleanup) => Some(Unconfigured),
+            (Inactive, Transition::Activate) => Some(Active),
+            (Active, Transition::Deactivate) => Some(Inactive),
+            (Unconfigured | Inactive | Active, Transition::Shutdown) => Some(Finalized),
+            _ => None,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => LifecycleState::Unconfigured,
+            1 => LifecycleState::Inactive,
+            2 => LifecycleState::Active,
+            _ => LifecycleState::Finalized,
+        }
+    }
+}
+
+impl fmt::Display for LifecycleState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LifecycleState::Unconfigured => "unconfigured",
+            LifecycleState::Inactive => "inactive",
+            LifecycleState::Active => "active",
+            LifecycleState::Finalized => "finalized",
+        })
+    }
+}
+
+/// A change of [`LifecycleState`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    Configure,
+    Activate,
+    Deactivate,
+    Cleanup,
+    Shutdown,
+}
+
+impl Transition {
+    pub const ALL: [Transition; 5] = [
+        Transition::Configure,
+        Transition::Activate,
+        Transition::Deactivate,
+        Transition::Cleanup,
+        Transition::Shutdown,
+    ];
+
+    /// The transition named `name`, like `"activate"`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.to_string() == name)
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transition::Configure => "configure",
+            Transition::Activate => "activate",
+            Transition::Deactivate => "deactivate",
+            Transition::Cleanup => "cleanup",
+            Transition::Shutdown => "shutdown",
+        })
+    }
+}
+
+/// Request of the `<node>/change_state` service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeStateRequest {
+    pub transition: Transition,
+}
+
+impl Message for ChangeStateRequest {
+    fn type_name() -> &'static str {
+        "ros3_msgs/ChangeStateRequest"
+    }
+}
+
+/// Response of the `<node>/change_state` service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeStateResponse {
+    pub success: bool,
+    /// The node's state after the request
+    pub state: LifecycleState,
+    /// Why the transition was refused or failed, empty on success
+    pub message: String,
+}
+
+impl Message for ChangeStateResponse {
+    fn type_name() -> &'static str {
+        "ros3_msgs/ChangeStateResponse"
+    }
+}
+
+/// Request of the `<node>/get_state` service
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetStateRequest {}
+
+impl Message for GetStateRequest {
+    fn type_name() -> &'static str {
+        "ros3_msgs/GetStateRequest"
+    }
+}
+
+/// Response of the `<node>/get_state` service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetStateResponse {
+    pub state: LifecycleState,
+}
+
+impl Message for GetStateResponse {
+    fn type_name() -> &'static str {
+        "ros3_msgs/GetStateResponse"
+    }
+}
+
+/// A completed or failed transition, as published on
+/// `<node>/transition_event`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransitionEvent {
+    pub node: String,
+    pub transition: Transition,
+    pub start_state: LifecycleState,
+    /// The state the node ended up in, `start_state` if the hook failed
+    pub goal_state: LifecycleState,
+    pub success: bool,
+    pub stamp: Time,
+}
+
+impl Message for TransitionEvent {
+    fn type_name() -> &'static str {
+        "ros3_msgs/TransitionEvent"
+    }
+}
+
+/// A lifecycle node and its state, as listed by
+/// [`graph::list_lifecycle_nodes`](crate::graph::list_lifecycle_nodes)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifecycleInfo {
+    pub node: String,
+    pub state: LifecycleState,
+}
+
+type Hook = Box<dyn FnMut() -> Result<()> + Send>;
+
+#[derive(Default)]
+struct Hooks {
+    configure: Option<Hook>,
+    activate: Option<Hook>,
+    deactivate: Option<Hook>,
+    cleanup: Option<Hook>,
+    shutdown: Option<Hook>,
+}
+
+impl Hooks {
+    fn get(&mut self, transition: Transition) -> &mut Option<Hook> {
+        match transition {
+            Transition::Configure => &mut self.configure,
+            Transition::Activate => &mut self.activate,
+            Transition::Deactivate => &mut self.deactivate,
+            Transition::Cleanup => &mut self.cleanup,
+            Transition::Shutdown => &mut self.shutdown,
+        }
+    }
+}
+
+struct Lifecycle {
+    node: String,
+    /// A [`LifecycleState`], shared with the node's publishers
+    state: Arc<AtomicU8>,
+    hooks: Mutex<Hooks>,
+    /// Held for the whole of a transition so they happen one at a time and
+    /// their events are published in order
+    transition: tokio::sync::Mutex<()>,
+    events: Publisher<TransitionEvent>,
+    clock: Clock,
+}
+
+impl Lifecycle {
+    fn state(&self) -> LifecycleState {
+        LifecycleState::from_u8(self.state.load(Ordering::Acquire))
+    }
+
+    async fn trigger(&self, transition: Transition) -> Result<LifecycleState> {
+        let _transition = self.transition.lock().await;
+        let start = self.state();
+        let Some(goal) = start.after(transition) else {
+            return Err(Error::InvalidTransition {
+                node: self.node.clone(),
+                state: start,
+                transition,
+            });
+        };
+        debug!(node = %self.node, "Lifecycle transition {}: {} -> {}", transition, start, goal);
+        let result = match self.hooks.lock().get(transition) {
+            Some(hook) => hook(),
+            None => Ok(()),
+        };
+        let end = match &result {
+            Ok(()) => {
+                self.state.store(goal as u8, Ordering::Release);
+                info!(node = %self.node, "Lifecycle state {}", goal);
+                goal
+            }
+            Err(e) => {
+                warn!(node = %self.node, "Lifecycle transition {} failed: {}", transition, e);
+                start
+            }
+        };
+        let event = TransitionEvent {
+            node: self.node.clone(),
+            transition,
+            start_state: start,
+            goal_state: end,
+            success: result.is_ok(),
+            stamp: self.clock.now(),
+        };
+        if let Err(e) = self.events.publish(&event).await {
+            warn!(node = %self.node, "Failed to publish transition event: {}", e);
+        }
+        result.map_err(|e| Error::TransitionFailed {
+            node: self.node.clone(),
+            transition,
+            reason: e.to_string(),
+        })?;
+        Ok(end)
+    }
+}
+
+/// Every live lifecycle node, for graph introspection
+fn registry() -> &'static Mutex<Vec<Weak<Lifecycle>>> {
+    static REGISTRY: OnceLock<Mutex<Vec<Weak<Lifecycle>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Every live lifecycle node with its state, sorted by node
+pub(crate) fn all_nodes() -> Vec<LifecycleInfo> {
+    let mut registry = registry().lock();
+    registry.retain(|lifecycle| lifecycle.strong_count() > 0);
+    let mut infos: Vec<_> = registry
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|lifecycle| LifecycleInfo {
+            node: lifecycle.node.clone(),
+            state: lifecycle.state(),
+        })
+        .collect();
+    infos.sort_by(|a, b| a.node.cmp(&b.node));
+    infos
+}
+
+/// The live lifecycle node named `node`
+pub(crate) fn find_node(node: &str) -> Option<LifecycleHandle> {
+    let registry = registry().lock();
+    registry
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|lifecycle| lifecycle.node == node)
+        .map(|lifecycle| LifecycleHandle { lifecycle })
+}
+
+/// A node that moves through configure, activate, deactivate, cleanup and
+/// shutdown on request
+///
+/// Starts [`Unconfigured`](LifecycleState::Unconfigured). Dropping it removes
+/// its services, but not the node's other endpoints.
+pub struct LifecycleNode {
+    node: Node,
+    lifecycle: Arc<Lifecycle>,
+    _change_state: ServiceServer<ChangeStateRequest, ChangeStateResponse>,
+    _get_state: ServiceServer<GetStateRequest, GetStateResponse>,
+}
+
+impl LifecycleNode {
+    /// Manage `node`, offering its lifecycle services
+    ///
+    /// Fails with [`Error::Configuration`] if a node of the same name
+    /// already offers them. Must be called from within a Tokio runtime.
+    pub fn new(node: Node) -> Result<Self> {
+        let name = node.name().to_string();
+        let lifecycle = Arc::new(Lifecycle {
+            node: name.clone(),
+            state: Arc::new(AtomicU8::new(LifecycleState::Unconfigured as u8)),
+            hooks: Mutex::new(Hooks::default()),
+            transition: tokio::sync::Mutex::new(()),
+            events: node
+                .create_publisher(&format!("{}/transition_event", name))?
+                .latch(true),
+            clock: node.clock().clone(),
+        });
+
+        // Weak, so dropping the node ends its lifecycle even while a call
+        // is still being dispatched
+        let handle = Arc::downgrade(&lifecycle);
+        let change_state = ServiceServer::new(
+            node.resolve(&format!("{}/change_state", name))?.as_str(),
+            move |request: ChangeStateRequest| {
+                let lifecycle = handle.upgrade();
+                async move {
+                    let lifecycle =
+                        lifecycle.ok_or_else(|| Error::Closed("lifecycle node".into()))?;
+                    let result = lifecycle.trigger(request.transition).await;
+                    Ok(ChangeStateResponse {
+                        success: result.is_ok(),
+                        state: lifecycle.state(),
+                        message: result.err().map(|e| e.to_string()).unwrap_or_default(),
+                    })
+                }
+            },
+        )?;
+        let handle = Arc::downgrade(&lifecycle);
+        let get_state = ServiceServer::new(
+            node.resolve(&format!("{}/get_state", name))?.as_str(),
+            move |_: GetStateRequest| {
+                let state = handle.upgrade().map(|lifecycle| lifecycle.state());
+                async move {
+                    let state = state.ok_or_else(|| Error::Closed("lifecycle node".into()))?;
+                    Ok(GetStateResponse { state })
+                }
+            },
+        )?;
+
+        registry().lock().push(Arc::downgrade(&lifecycle));
+        Ok(Self {
+            node,
+            lifecycle,
+            _change_state: change_state,
+            _get_state: get_state,
+        })
+    }
+
+    /// Run `hook` when the node is configured; failing keeps it unconfigured
+    pub fn on_configure<F>(self, hook: F) -> Self
+    where
+        F: FnMut() -> Result<()> + Send + 'static,
+    {
+        self.hook(Transition::Configure, hook)
+    }
+
+    /// Run `hook` when the node is activated; failing keeps it inactive
+    pub fn on_activate<F>(self, hook: F) -> Self
+    where
+        F: FnMut() -> Result<()> + Send + 'static,
+    {
+        self.hook(Transition::Activate, hook)
+    }
+
+    /// Run `hook` when the node is deactivated; failing keeps it active
+    pub fn on_deactivate<F>(self, hook: F) -> Self
+    where
+        F: FnMut() -> Result<()> + Send + 'static,
+    {
+        self.hook(Transition::Deactivate, hook)
+    }
+
+    /// Run `hook` when the node is cleaned up; failing keeps it inactive
+    pub fn on_cleanup<F>(self, hook: F) -> Self
+    where
+        F: FnMut() -> Result<()> + Send + 'static,
+    {
+        self.hook(Transition::Cleanup, hook)
+    }
+
+    /// Run `hook` when the node is shut down; failing keeps it in its
+    /// current state
+    pub fn on_shutdown<F>(self, hook: F) -> Self
+    where
+        F: FnMut() -> Result<()> + Send + 'static,
+    {
+        self.hook(Transition::Shutdown, hook)
+    }
+
+    fn hook<F>(self, transition: Transition, hook: F) -> Self
+    where
+        F: FnMut() -> Result<()> + Send + 'static,
+    {
+        *self.lifecycle.hooks.lock().get(transition) = Some(Box::new(hook));
+        self
+    }
+
+    /// The managed node
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    pub fn state(&self) -> LifecycleState {
+        self.lifecycle.state()
+    }
+
+    /// A handle to query and change the state with
+    pub fn handle(&self) -> LifecycleHandle {
+        LifecycleHandle {
+            lifecycle: self.lifecycle.clone(),
+        }
+    }
+
+    /// Run `transition`, returning the new state
+    ///
+    /// Fails with [`Error::InvalidTransition`] if the transition does not
+    /// start from the current state, and with [`Error::TransitionFailed`]
+    /// if its hook fails; the state is unchanged either way.
+    pub async fn trigger(&self, transition: Transition) -> Result<LifecycleState> {
+        self.lifecycle.trigger(transition).await
+    }
+
+    pub async fn configure(&self) -> Result<LifecycleState> {
+        self.trigger(Transition::Configure).await
+    }
+
+    pub async fn activate(&self) -> Result<LifecycleState> {
+        self.trigger(Transition::Activate).await
+    }
+
+    pub async fn deactivate(&self) -> Result<LifecycleState> {
+        self.trigger(Transition::Deactivate).await
+    }
+
+    pub async fn cleanup(&self) -> Result<LifecycleState> {
+        self.trigger(Transition::Cleanup).await
+    }
+
+    pub async fn shutdown(&self) -> Result<LifecycleState> {
+        self.trigger(Transition::Shutdown).await
+    }
+
+    /// Create a publisher that only publishes while the node is active
+    pub fn create_publisher<T: Message>(&self, topic: &str) -> Result<LifecyclePublisher<T>> {
+        Ok(LifecyclePublisher {
+            publisher: self.node.create_publisher(topic)?,
+            state: self.lifecycle.state.clone(),
+            suppressed: AtomicU64::new(0),
+        })
+    }
+}
+
+/// Queries and changes the state of a [`LifecycleNode`] without owning it
+#[derive(Clone)]
+pub struct LifecycleHandle {
+    lifecycle: Arc<Lifecycle>,
+}
+
+impl LifecycleHandle {
+    /// Name of the managed node
+    pub fn node(&self) -> &str {
+        &self.lifecycle.node
+    }
+
+    pub fn state(&self) -> LifecycleState {
+        self.lifecycle.state()
+    }
+
+    /// Run `transition`, see [`LifecycleNode::trigger`]
+    pub async fn trigger(&self, transition: Transition) -> Result<LifecycleState> {
+        self.lifecycle.trigger(transition).await
+    }
+}
+
+/// A publisher of a [`LifecycleNode`], see
+/// [`LifecycleNode::create_publisher`]
+pub struct LifecyclePublisher<T: Message> {
+    publisher: Publisher<T>,
+    state: Arc<AtomicU8>,
+    suppressed: AtomicU64,
+}
+
+impl<T: Message> LifecyclePublisher<T> {
+    /// Publish a message if the node is active
+    ///
+    /// Returns whether the message was published; publishing while the node
+    /// is not active is a no-op, not an error.
+    pub async fn publish(&self, msg: &T) -> Result<bool> {
+        if !self.is_active() {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return Ok(false);
+        }
+        self.publisher.publish(msg).await?;
+        Ok(true)
+    }
+
+    /// Whether the node is active, so publishing goes through
+    pub fn is_active(&self) -> bool {
+        self.state.load(Ordering::Acquire) == LifecycleState::Active as u8
+    }
+
+    /// Number of messages dropped while the node was not active
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    /// The publisher underneath, which publishes in any state
+    pub fn inner(&self) -> &Publisher<T> {
+        &self.publisher
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph;
+    use crate::message::RobotState;
+    use crate::service::ServiceClient;
+    use std::sync::atomic::AtomicUsize;
+
+    fn lifecycle_node(name: &str) -> LifecycleNode {
+        LifecycleNode::new(Node::new(name).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_invalid_transitions_are_rejected() {
+        let node = lifecycle_node("lifecycle_invalid");
+        let activations = Arc::new(AtomicUsize::new(0));
+        let counter = activations.clone();
+        let node = node.on_activate(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        });
+
+        let err = node.activate().await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidTransition {
+                state: LifecycleState::Unconfigured,
+                transition: Transition::Activate,
+                ..
+            }
+        ));
+        assert_eq!(node.state(), LifecycleState::Unconfigured);
+        assert!(node.deactivate().await.is_err());
+        assert!(node.cleanup().await.is_err());
+
+        assert_eq!(node.configure().await.unwrap(), LifecycleState::Inactive);
+        assert!(node.configure().await.is_err());
+        assert_eq!(node.activate().await.unwrap(), LifecycleState::Active);
+        assert!(node.activate().await.is_err());
+        assert_eq!(activations.load(Ordering::Relaxed), 1);
+
+        assert_eq!(node.shutdown().await.unwrap(), LifecycleState::Finalized);
+        for transition in Transition::ALL {
+            assert!(node.trigger(transition).await.is_err());
+        }
+        assert_eq!(node.state(), LifecycleState::Finalized);
+    }
+
+    #[tokio::test]
+    async fn test_failed_activation_stays_inactive() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let node = lifecycle_node("lifecycle_failing").on_activate(move || {
+            match counter.fetch_add(1, Ordering::Relaxed) {
+                0 => Err(Error::Configuration("camera not found".into())),
+                _ => Ok(()),
+            }
+        });
+        let publisher = node
+            .create_publisher::<RobotState>("/test/lifecycle/state")
+            .unwrap();
+        let subscriber = node
+            .node()
+            .create_subscriber::<RobotState>("/test/lifecycle/state")
+            .unwrap();
+
+        assert!(!publisher.publish(&RobotState::default()).await.unwrap());
+        node.configure().await.unwrap();
+        let err = node.activate().await.unwrap_err();
+        assert!(err.to_string().contains("camera not found"), "{}", err);
+        assert_eq!(node.state(), LifecycleState::Inactive);
+        assert!(!publisher.publish(&RobotState::default()).await.unwrap());
+        assert!(subscriber.try_recv().unwrap().is_none());
+        assert_eq!(publisher.suppressed(), 2);
+
+        node.activate().await.unwrap();
+        assert!(publisher.publish(&RobotState::default()).await.unwrap());
+        assert!(subscriber.try_recv().unwrap().is_some());
+
+        node.deactivate().await.unwrap();
+        assert!(!publisher.publish(&RobotState::default()).await.unwrap());
+        assert_eq!(publisher.suppressed(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_remote_transitions() {
+        let node = lifecycle_node("lifecycle_remote");
+        let events = node
+            .node()
+            .create_subscriber::<TransitionEvent>("lifecycle_remote/transition_event")
+            .unwrap();
+        let change_state = ServiceClient::<ChangeStateRequest, ChangeStateResponse>::new(
+            "/lifecycle_remote/change_state",
+        )
+        .unwrap();
+        let get_state =
+            ServiceClient::<GetStateRequest, GetStateResponse>::new("/lifecycle_remote/get_state")
+                .unwrap();
+
+        let response = change_state
+            .call(ChangeStateRequest {
+                transition: Transition::Configure,
+            })
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(response.state, LifecycleState::Inactive);
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.start_state, LifecycleState::Unconfigured);
+        assert_eq!(event.goal_state, LifecycleState::Inactive);
+
+        let response = change_state
+            .call(ChangeStateRequest {
+                transition: Transition::Deactivate,
+            })
+            .await
+            .unwrap();
+        assert!(!response.success);
+        assert_eq!(response.state, LifecycleState::Inactive);
+        assert!(response.message.contains("deactivate"));
+
+        let state = get_state.call(GetStateRequest {}).await.unwrap();
+        assert_eq!(state.state, LifecycleState::Inactive);
+
+        let handle = graph::lifecycle_node("lifecycle_remote").unwrap();
+        handle.trigger(Transition::Activate).await.unwrap();
+        assert!(graph::list_lifecycle_nodes().contains(&LifecycleInfo {
+            node: "lifecycle_remote".into(),
+            state: LifecycleState::Active,
+        }));
+        drop((node, handle));
+        assert!(graph::lifecycle_node("lifecycle_remote").is_none());
+    }
+}
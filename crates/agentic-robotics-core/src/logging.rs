@@ -0,0 +1,303 @@
+//! Structured logging
+//!
+//! Nodes, publishers, subscribers and executor tasks log inside spans that
+//! carry their `node`, `topic` and `priority` fields, so every line can be
+//! traced back to the endpoint that emitted it. [`init`] installs a global
+//! subscriber that filters by module, prints human-readable or JSON lines,
+//! and can keep the most recent records in memory for [`recent`].
+//!
+//! Logging on hot paths such as publishing happens at `TRACE` level. The
+//! `tracing` macros check the level before evaluating any field, so a
+//! disabled level costs one comparison and no formatting.
+
+use crate::error::{Error, Result};
+use crate::time::{Clock, Time};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Ring filled by the subscriber [`init`] installed
+static RING: OnceLock<Arc<LogRing>> = OnceLock::new();
+
+/// How log lines are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LogFormat {
+    /// One line of text per record
+    #[default]
+    Human,
+    /// One JSON object per record, with span fields included
+    Json,
+}
+
+/// Configuration for [`init`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogConfig {
+    /// Level per module, like `"info,agentic_robotics_core::publisher=trace"`:
+    /// a default level followed by `target=level` overrides
+    pub filter: String,
+    pub format: LogFormat,
+    /// Number of most recent records kept for [`recent`]; 0 keeps none
+    pub ring_capacity: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: "info".to_string(),
+            format: LogFormat::Human,
+            ring_capacity: 0,
+        }
+    }
+}
+
+/// A log record kept in the ring
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// System time the record was emitted at
+    pub time: Time,
+    pub level: String,
+    /// Module that emitted the record
+    pub target: String,
+    pub message: String,
+    /// Fields of the record and of the spans it was emitted in; the
+    /// innermost value wins if names clash
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Install the global log subscriber
+///
+/// Fails with [`Error::Configuration`] if `config.filter` does not parse or
+/// a global subscriber is already installed.
+pub fn init(config: LogConfig) -> Result<()> {
+    let targets: Targets = config.filter.parse().map_err(|e| {
+        Error::Configuration(format!("invalid log filter '{}': {}", config.filter, e))
+    })?;
+    let (human, json) = match config.format {
+        LogFormat::Human => (
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_target(false)
+                    .with_thread_ids(true),
+            ),
+            None,
+        ),
+        LogFormat::Json => (None, Some(tracing_subscriber::fmt::layer().json())),
+    };
+    let ring = (config.ring_capacity > 0).then(|| Arc::new(LogRing::new(config.ring_capacity)));
+
+    tracing_subscriber::registry()
+        .with(targets)
+        .with(human)
+        .with(json)
+        .with(ring.clone().map(RingLayer::new))
+        .try_init()
+        .map_err(|e| Error::Configuration(format!("failed to install log subscriber: {}", e)))?;
+    if let Some(ring) = ring {
+        // Only one subscriber is ever installed, so neither is the ring
+        let _ = RING.set(ring);
+    }
+    Ok(())
+}
+
+/// The last `limit` records kept by the subscriber [`init`] installed,
+/// oldest first
+///
+/// Empty unless [`init`] was called with a non-zero
+/// [`ring_capacity`](LogConfig::ring_capacity).
+pub fn recent(limit: usize) -> Vec<LogRecord> {
+    RING.get()
+        .map(|ring| ring.recent(limit))
+        .unwrap_or_default()
+}
+
+/// The most recent records, up to a capacity
+#[derive(Debug)]
+struct LogRing {
+    capacity: usize,
+    records: Mutex<VecDeque<LogRecord>>,
+}
+
+impl LogRing {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    fn push(&self, record: LogRecord) {
+        let mut records = self.records.lock();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    fn recent(&self, limit: usize) -> Vec<LogRecord> {
+        let records = self.records.lock();
+        let skip = records.len().saturating_sub(limit);
+        records.iter().skip(skip).cloned().collect()
+    }
+}
+
+/// Fields recorded on a span, kept in its extensions
+#[derive(Default)]
+struct SpanFields(BTreeMap<String, String>);
+
+/// Layer copying every event it sees into a [`LogRing`]
+struct RingLayer {
+    ring: Arc<LogRing>,
+}
+
+impl RingLayer {
+    fn new(ring: Arc<LogRing>) -> Self {
+        Self { ring }
+    }
+}
+
+impl<S> Layer<S> for RingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = SpanFields::default();
+        attrs.record(&mut FieldVisitor {
+            fields: &mut fields.0,
+            message: None,
+        });
+        span.extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut FieldVisitor {
+                fields: &mut fields.0,
+                message: None,
+            });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = BTreeMap::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                    fields.extend(span_fields.0.clone());
+                }
+            }
+        }
+        let mut message = None;
+        event.record(&mut FieldVisitor {
+            fields: &mut fields,
+            message: Some(&mut message),
+        });
+        let metadata = event.metadata();
+        self.ring.push(LogRecord {
+            time: Clock::SystemTime.now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: message.unwrap_or_default(),
+            fields,
+        });
+    }
+}
+
+/// Collects fields as strings, taking the `message` field apart if asked
+struct FieldVisitor<'a> {
+    fields: &'a mut BTreeMap<String, String>,
+    message: Option<&'a mut Option<String>>,
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+}
+
+impl FieldVisitor<'_> {
+    fn record(&mut self, field: &Field, value: String) {
+        match &mut self.message {
+            Some(message) if field.name() == "message" => **message = Some(value),
+            _ => {
+                self.fields.insert(field.name().to_string(), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::{info, info_span, trace};
+
+    #[test]
+    fn test_ring_keeps_span_fields_and_tail() {
+        let ring = Arc::new(LogRing::new(2));
+        let subscriber = tracing_subscriber::registry().with(RingLayer::new(ring.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let node = info_span!("node", node = "lidar");
+            let _node = node.enter();
+            let publisher = info_span!("publisher", topic = "/scan", node = tracing::field::Empty);
+            publisher.record("node", "lidar");
+            let _publisher = publisher.enter();
+            info!(seq = 1, "first");
+            info!(seq = 2, "second");
+            info!(seq = 3, "third {}", "formatted");
+        });
+
+        let records = ring.recent(10);
+        assert_eq!(records.len(), 2);
+        let last = &records[1];
+        assert_eq!(last.message, "third formatted");
+        assert_eq!(last.level, "INFO");
+        assert_eq!(last.fields["node"], "lidar");
+        assert_eq!(last.fields["topic"], "/scan");
+        assert_eq!(last.fields["seq"], "3");
+        assert_eq!(ring.recent(1), records[1..]);
+    }
+
+    #[test]
+    fn test_targets_filter_per_module() {
+        let ring = Arc::new(LogRing::new(8));
+        let targets: Targets = "warn,agentic_robotics_core::logging=trace".parse().unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(targets)
+            .with(RingLayer::new(ring.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            trace!("kept");
+            info!(target: "other", "dropped");
+        });
+        let messages: Vec<_> = ring.recent(8).into_iter().map(|r| r.message).collect();
+        assert_eq!(messages, ["kept"]);
+    }
+
+    #[test]
+    fn test_invalid_filter_is_rejected() {
+        let config = LogConfig {
+            filter: "info,core=loud".to_string(),
+            ..LogConfig::default()
+        };
+        assert!(matches!(init(config), Err(Error::Configuration(_))));
+        assert!(recent(10).is_empty());
+    }
+}
@@ -1,7 +1,15 @@
 //! Message definitions and traits
 
+use crate::dynamic::{FieldDescriptor, FieldType, TypeDescriptor};
+use crate::error::Result;
+use crate::serialization::{Format, ProtobufCodec, Serializer};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
 
 /// Message trait for ROS3 messages
 pub trait Message: Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static {
@@ -12,6 +20,152 @@ pub trait Message: Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static
     fn version() -> &'static str {
         "1.0"
     }
+
+    /// Stable hash of the message layout, used to detect endpoints that
+    /// disagree about a topic's type
+    ///
+    /// Defaults to a hash of the type name; `#[derive(Ros3Message)]` also
+    /// folds in the field names and types.
+    fn type_hash() -> u64 {
+        fnv1a(Self::type_name().as_bytes())
+    }
+
+    /// Protobuf encoding, for use with [`Format::Protobuf`]
+    ///
+    /// Types implementing
+    /// [`ProstCompatible`](crate::serialization::ProstCompatible) opt in by
+    /// returning `Some(ProtobufCodec::of())`.
+    fn protobuf() -> Option<ProtobufCodec<Self>> {
+        None
+    }
+
+    /// JSON Schema of the message in its JSON form
+    ///
+    /// Describes what tools building messages from JSON must send. Defaults
+    /// to `true`, which accepts any JSON; `#[derive(Ros3Message)]` generates
+    /// the schema from the fields.
+    fn schema() -> Value {
+        Value::Bool(true)
+    }
+
+    /// The message's fields, for handling it as a
+    /// [`DynamicMessage`](crate::dynamic::DynamicMessage)
+    ///
+    /// Defaults to `None`; `#[derive(Ros3Message)]` describes the fields.
+    fn descriptor() -> Option<TypeDescriptor> {
+        None
+    }
+}
+
+/// `schema` extended to also accept `null`, for `Option` fields
+pub fn nullable(schema: Value) -> Value {
+    match schema {
+        Value::Object(mut object) => {
+            match object.get("type").cloned() {
+                Some(Value::String(name)) => {
+                    object.insert("type".into(), json!([name, "null"]));
+                }
+                Some(Value::Array(mut names)) => {
+                    if !names.contains(&json!("null")) {
+                        names.push(json!("null"));
+                    }
+                    object.insert("type".into(), Value::Array(names));
+                }
+                // Without a type the schema already accepts any type
+                _ => {}
+            }
+            Value::Object(object)
+        }
+        schema => schema,
+    }
+}
+
+/// An `Option` field of a derived message, being serialized
+///
+/// CDR has no optional values, so formats that are not human readable get
+/// the option as a sequence of zero or one element; JSON keeps `null`.
+#[doc(hidden)]
+pub struct OptionalRef<'a, T>(pub &'a Option<T>);
+
+impl<T: Serialize> Serialize for OptionalRef<'_, T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+        if serializer.is_human_readable() {
+            return self.0.serialize(serializer);
+        }
+        let mut seq = serializer.serialize_seq(Some(self.0.iter().len()))?;
+        if let Some(value) = self.0 {
+            seq.serialize_element(value)?;
+        }
+        seq.end()
+    }
+}
+
+/// An `Option` field of a derived message, deserialized as
+/// [`OptionalRef`] serializes it
+#[doc(hidden)]
+pub struct Optional<T>(pub Option<T>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Optional<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            return Option::deserialize(deserializer).map(Optional);
+        }
+        deserializer.deserialize_seq(OptionalVisitor(std::marker::PhantomData))
+    }
+}
+
+struct OptionalVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for OptionalVisitor<T> {
+    type Value = Optional<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of at most one element")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        let value = seq.next_element()?;
+        if value.is_some() && seq.next_element::<serde::de::IgnoredAny>()?.is_some() {
+            return Err(serde::de::Error::invalid_length(2, &self));
+        }
+        Ok(Optional(value))
+    }
+}
+
+/// Schema of a fixed-length array of numbers, as used by the built-in messages
+fn number_array(len: usize) -> Value {
+    json!({ "type": "array", "items": { "type": "number" }, "minItems": len, "maxItems": len })
+}
+
+/// Field type of the same arrays
+fn f64_array(len: usize) -> FieldType {
+    FieldType::Array(Box::new(FieldType::F64), len)
+}
+
+/// 64-bit FNV-1a hash
+pub const fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+/// Mix two hashes in an order-dependent way
+pub const fn combine_hash(seed: u64, value: u64) -> u64 {
+    (seed.rotate_left(31) ^ value).wrapping_mul(0x0100_0000_01b3)
 }
 
 /// Implement Message for serde_json::Value for generic JSON messages
@@ -21,6 +175,105 @@ impl Message for serde_json::Value {
     }
 }
 
+/// A message of any type in its serialized form
+///
+/// `Subscriber::<RawMessage>` accepts whatever type a topic carries and
+/// `Publisher::<RawMessage>` forwards `data` unchanged, which is what generic
+/// tooling such as recorders needs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawMessage {
+    pub type_name: String,
+    pub format: Format,
+    pub data: Vec<u8>,
+}
+
+impl RawMessage {
+    /// Deserialize the payload as a concrete message type
+    pub fn decode<T: Message>(&self) -> Result<T> {
+        Serializer::new(self.format).deserialize(&self.data)
+    }
+}
+
+impl Message for RawMessage {
+    fn type_name() -> &'static str {
+        "*"
+    }
+}
+
+/// Serialized bytes shared instead of copied; cloning is cheap
+///
+/// Wraps any owner of the bytes, such as a `Vec<u8>` or a buffer handed
+/// over by a language binding, and keeps it alive as long as a clone
+/// exists.
+#[derive(Clone)]
+pub struct SharedBytes(Arc<dyn ByteOwner>);
+
+trait ByteOwner: Send + Sync + 'static {
+    fn bytes(&self) -> &[u8];
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<O: AsRef<[u8]> + Send + Sync + 'static> ByteOwner for O {
+    fn bytes(&self) -> &[u8] {
+        self.as_ref()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl SharedBytes {
+    /// Share the bytes of `owner`
+    pub fn new<O: AsRef<[u8]> + Send + Sync + 'static>(owner: O) -> Self {
+        Self(Arc::new(owner))
+    }
+
+    /// The owner passed to [`new`](Self::new), if it is an `O`
+    pub fn owner<O: 'static>(&self) -> Option<&O> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl Deref for SharedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.bytes()
+    }
+}
+
+impl From<Vec<u8>> for SharedBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl fmt::Debug for SharedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedBytes")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A [`RawMessage`] whose bytes are shared instead of owned
+///
+/// Published with `Publisher::<RawMessage>::publish_shared` and received
+/// with `Subscriber::<RawMessage>::recv_shared`, the bytes travel from
+/// publisher to in-process subscribers without being copied.
+#[derive(Debug, Clone)]
+pub struct SharedRawMessage {
+    pub type_name: String,
+    pub format: Format,
+    pub data: SharedBytes,
+}
+
+/// Whether `T` is [`RawMessage`], which skips topic type checking
+pub(crate) fn is_raw<T: Message>() -> bool {
+    TypeId::of::<T>() == TypeId::of::<RawMessage>()
+}
+
 /// Robot state message
 #[derive(Debug, Clone, Serialize, Deserialize, Archive, RkyvSerialize, RkyvDeserialize)]
 pub struct RobotState {
@@ -33,6 +286,75 @@ impl Message for RobotState {
     fn type_name() -> &'static str {
         "ros3_msgs/RobotState"
     }
+
+    fn schema() -> Value {
+        json!({
+            "title": Self::type_name(),
+            "type": "object",
+            "properties": {
+                "position": number_array(3),
+                "velocity": number_array(3),
+                "timestamp": { "type": "integer" },
+            },
+            "required": ["position", "velocity", "timestamp"],
+            "additionalProperties": false,
+        })
+    }
+
+    fn descriptor() -> Option<TypeDescriptor> {
+        let fields = vec![
+            FieldDescriptor::new("position", f64_array(3)),
+            FieldDescriptor::new("velocity", f64_array(3)),
+            FieldDescriptor::new("timestamp", FieldType::I64),
+        ];
+        Some(TypeDescriptor::new(Self::type_name(), fields).with_hash(Self::type_hash()))
+    }
+
+    #[cfg(feature = "protobuf")]
+    fn protobuf() -> Option<ProtobufCodec<Self>> {
+        Some(ProtobufCodec::of())
+    }
+}
+
+#[cfg(feature = "protobuf")]
+impl crate::serialization::ProstCompatible for RobotState {
+    type Proto = proto::RobotState;
+
+    fn to_proto(&self) -> proto::RobotState {
+        proto::RobotState {
+            position: self.position.to_vec(),
+            velocity: self.velocity.to_vec(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn from_proto(proto: proto::RobotState) -> Result<Self> {
+        let vector = |values: Vec<f64>, field: &str| -> Result<[f64; 3]> {
+            values.try_into().map_err(|_| {
+                crate::Error::Serialization(format!("RobotState.{} must have 3 elements", field))
+            })
+        };
+        Ok(Self {
+            position: vector(proto.position, "position")?,
+            velocity: vector(proto.velocity, "velocity")?,
+            timestamp: proto.timestamp,
+        })
+    }
+}
+
+/// Protobuf representations of the built-in messages
+#[cfg(feature = "protobuf")]
+pub mod proto {
+    /// Protobuf form of [`RobotState`](super::RobotState)
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RobotState {
+        #[prost(double, repeated, tag = "1")]
+        pub position: Vec<f64>,
+        #[prost(double, repeated, tag = "2")]
+        pub velocity: Vec<f64>,
+        #[prost(int64, tag = "3")]
+        pub timestamp: i64,
+    }
 }
 
 impl Default for RobotState {
@@ -65,6 +387,51 @@ impl Message for PointCloud {
     fn type_name() -> &'static str {
         "ros3_msgs/PointCloud"
     }
+
+    fn schema() -> Value {
+        let point = json!({
+            "title": "ros3_msgs/Point3D",
+            "type": "object",
+            "properties": {
+                "x": { "type": "number" },
+                "y": { "type": "number" },
+                "z": { "type": "number" },
+            },
+            "required": ["x", "y", "z"],
+            "additionalProperties": false,
+        });
+        json!({
+            "title": Self::type_name(),
+            "type": "object",
+            "properties": {
+                "points": { "type": "array", "items": point },
+                "intensities": { "type": "array", "items": { "type": "number" } },
+                "timestamp": { "type": "integer" },
+            },
+            "required": ["points", "intensities", "timestamp"],
+            "additionalProperties": false,
+        })
+    }
+
+    fn descriptor() -> Option<TypeDescriptor> {
+        let point = TypeDescriptor::new(
+            "ros3_msgs/Point3D",
+            vec![
+                FieldDescriptor::new("x", FieldType::F32),
+                FieldDescriptor::new("y", FieldType::F32),
+                FieldDescriptor::new("z", FieldType::F32),
+            ],
+        );
+        let fields = vec![
+            FieldDescriptor::new(
+                "points",
+                FieldType::Sequence(Box::new(FieldType::Message(Arc::new(point)))),
+            ),
+            FieldDescriptor::new("intensities", FieldType::Sequence(Box::new(FieldType::F32))),
+            FieldDescriptor::new("timestamp", FieldType::I64),
+        ];
+        Some(TypeDescriptor::new(Self::type_name(), fields).with_hash(Self::type_hash()))
+    }
 }
 
 impl Default for PointCloud {
@@ -88,6 +455,27 @@ impl Message for Pose {
     fn type_name() -> &'static str {
         "ros3_msgs/Pose"
     }
+
+    fn schema() -> Value {
+        json!({
+            "title": Self::type_name(),
+            "type": "object",
+            "properties": {
+                "position": number_array(3),
+                "orientation": number_array(4),
+            },
+            "required": ["position", "orientation"],
+            "additionalProperties": false,
+        })
+    }
+
+    fn descriptor() -> Option<TypeDescriptor> {
+        let fields = vec![
+            FieldDescriptor::new("position", f64_array(3)),
+            FieldDescriptor::new("orientation", f64_array(4)),
+        ];
+        Some(TypeDescriptor::new(Self::type_name(), fields).with_hash(Self::type_hash()))
+    }
 }
 
 impl Default for Pose {
@@ -99,6 +487,29 @@ impl Default for Pose {
     }
 }
 
+/// Standard message header
+///
+/// Messages embedding a header implement [`Stamped`], so that
+/// [`Publisher::publish_stamped`](crate::Publisher::publish_stamped) can
+/// fill in the stamp and sequence number. Unlike the metadata returned by
+/// [`Subscriber::recv_with_info`](crate::Subscriber::recv_with_info), the
+/// header is part of the payload and survives recording and replay.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    /// Publish time in nanoseconds on the publisher's clock
+    pub stamp_ns: i64,
+    /// Position in the publisher's sequence of messages
+    pub seq: u64,
+    /// Coordinate frame the data refers to
+    pub frame_id: String,
+}
+
+/// A message carrying a [`Header`]
+pub trait Stamped {
+    fn header(&self) -> &Header;
+    fn header_mut(&mut self) -> &mut Header;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,4 +527,115 @@ mod tests {
         assert_eq!(cloud.points.len(), 0);
         assert_eq!(PointCloud::type_name(), "ros3_msgs/PointCloud");
     }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use crate::serialization::{Format, Serializer};
+        use crate::{Message, Publisher, Ros3Message, Subscriber};
+
+        #[derive(Debug, Clone, PartialEq, Ros3Message)]
+        struct LidarScan {
+            ranges: Vec<f32>,
+            stamp: i64,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Ros3Message)]
+        #[ros3(type_name = "test_msgs/Header")]
+        struct Header {
+            frame_id: String,
+            stamp: i64,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Ros3Message)]
+        struct Odometry {
+            header: Header,
+            pose: [f64; 7],
+            covariance: Option<Vec<f64>>,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Ros3Message)]
+        #[ros3(type_name = "test_msgs/Header")]
+        struct RenamedHeader {
+            frame: String,
+            stamp: i64,
+        }
+
+        fn odometry() -> Odometry {
+            Odometry {
+                header: Header {
+                    frame_id: "odom".to_string(),
+                    stamp: 42,
+                },
+                pose: [1.0, 2.0, 3.0, 0.0, 0.0, 0.0, 1.0],
+                covariance: Some(vec![0.1; 3]),
+            }
+        }
+
+        #[test]
+        fn test_derived_type_name() {
+            assert_eq!(LidarScan::type_name(), "agentic-robotics-core/LidarScan");
+            assert_eq!(Header::type_name(), "test_msgs/Header");
+        }
+
+        #[test]
+        fn test_type_hash_tracks_fields() {
+            assert_eq!(Header::type_hash(), Header::type_hash());
+            assert_ne!(Header::type_hash(), RenamedHeader::type_hash());
+        }
+
+        #[test]
+        fn test_derived_schema() {
+            let schema = Odometry::schema();
+            assert_eq!(schema["title"], "agentic-robotics-core/Odometry");
+            assert_eq!(schema["required"], serde_json::json!(["header", "pose"]));
+            assert_eq!(schema["properties"]["header"], Header::schema());
+            assert_eq!(
+                schema["properties"]["pose"],
+                serde_json::json!({
+                    "type": "array",
+                    "items": { "type": "number" },
+                    "minItems": 7,
+                    "maxItems": 7,
+                })
+            );
+            assert_eq!(
+                schema["properties"]["covariance"]["type"],
+                serde_json::json!(["array", "null"])
+            );
+            assert_eq!(
+                Header::schema()["properties"]["stamp"],
+                serde_json::json!({ "type": "integer" })
+            );
+        }
+
+        #[test]
+        fn test_roundtrip_nested() {
+            for format in [Format::Cdr, Format::Json] {
+                let serializer = Serializer::new(format);
+                let bytes = serializer.serialize(&odometry()).unwrap();
+                let decoded: Odometry = serializer.deserialize(&bytes).unwrap();
+                assert_eq!(decoded, odometry());
+            }
+        }
+
+        #[test]
+        fn test_json_missing_option_is_none() {
+            let json = br#"{"header":{"frame_id":"map","stamp":1},"pose":[0,0,0,0,0,0,1]}"#;
+            let decoded: Odometry = Serializer::new(Format::Json).deserialize(json).unwrap();
+            assert_eq!(decoded.covariance, None);
+        }
+
+        #[tokio::test]
+        async fn test_publish_derived_message() {
+            let subscriber = Subscriber::<LidarScan>::new("test/derive/scan").unwrap();
+            let publisher = Publisher::<LidarScan>::new("test/derive/scan").unwrap();
+            let scan = LidarScan {
+                ranges: vec![0.5, 1.5, 2.5],
+                stamp: 7,
+            };
+
+            publisher.publish(&scan).await.unwrap();
+            assert_eq!(subscriber.recv().await.unwrap(), scan);
+        }
+    }
 }
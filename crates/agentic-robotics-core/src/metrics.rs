@@ -0,0 +1,590 @@
+//! Counters, gauges and histograms of the pub/sub hot paths
+//!
+//! A [`Registry`] holds metric families by name, each with one series per
+//! label set. Asking the registry for a series is a map lookup under a lock,
+//! so callers do it once and keep the returned handle: [`Counter`],
+//! [`Gauge`] and [`Histogram`] are `Arc`s around atomics, and updating one
+//! never locks or allocates. Asking again for the same name and labels
+//! returns the same series, so a topic that is torn down and recreated keeps
+//! counting where it left off.
+//!
+//! Every topic registers these series with the [global](Registry::global)
+//! registry, labelled `topic`:
+//!
+//! | Name | Type | |
+//! |------|------|-|
+//! | [`MESSAGES_PUBLISHED`] | counter | Messages published |
+//! | [`QUEUE_DEPTH`] | gauge | Messages waiting in the topic's subscriber queues |
+//! | [`SERIALIZATION_DURATION`] | histogram | Time to serialize a published message |
+//! | [`MESSAGE_LATENCY`] | histogram | Time from publish to receive |
+//!
+//! [`Registry::encode_prometheus`] renders the text exposition format and
+//! [`Registry::encode_json`] a stable, sorted JSON document for diffing runs.
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Messages published on a topic, by `topic`
+pub const MESSAGES_PUBLISHED: &str = "ros3_messages_published_total";
+
+/// Messages waiting in the subscriber queues of a topic, by `topic`
+pub const QUEUE_DEPTH: &str = "ros3_subscriber_queue_depth";
+
+/// Time a publisher spent serializing a message, by `topic`
+///
+/// Messages published with `publish_arc` to in-process subscribers are not
+/// serialized and not observed.
+pub const SERIALIZATION_DURATION: &str = "ros3_serialization_duration_seconds";
+
+/// Time from publishing a message to a subscriber taking it from its queue,
+/// by `topic`
+///
+/// Only observed when the publisher and subscriber use the same kind of
+/// clock.
+pub const MESSAGE_LATENCY: &str = "ros3_message_latency_seconds";
+
+/// Upper bounds of the histogram buckets in nanoseconds, from 1 µs to 1 s;
+/// every histogram also has a `+Inf` bucket
+const BUCKETS: [u64; 19] = [
+    1_000,
+    2_500,
+    5_000,
+    10_000,
+    25_000,
+    50_000,
+    100_000,
+    250_000,
+    500_000,
+    1_000_000,
+    2_500_000,
+    5_000_000,
+    10_000_000,
+    25_000_000,
+    50_000_000,
+    100_000_000,
+    250_000_000,
+    500_000_000,
+    1_000_000_000,
+];
+
+/// Monotonically increasing count
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    #[inline]
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    #[inline]
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Value that goes up and down
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicI64>);
+
+impl Gauge {
+    #[inline]
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add(&self, delta: i64) {
+        self.0.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Distribution of durations over fixed buckets from 1 µs to 1 s
+#[derive(Debug, Clone, Default)]
+pub struct Histogram(Arc<HistogramState>);
+
+#[derive(Debug, Default)]
+struct HistogramState {
+    /// Observations per bucket, not cumulative; the last is `+Inf`
+    buckets: [AtomicU64; BUCKETS.len() + 1],
+    sum_nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    #[inline]
+    pub fn observe(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = BUCKETS.partition_point(|&bound| bound < nanos);
+        self.0.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.0.sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.0.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.0.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_nanos(self.0.sum_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Cumulative count of observations at or below each bucket bound in
+    /// seconds, ending with `+Inf`
+    pub fn buckets(&self) -> Vec<(f64, u64)> {
+        let bounds = BUCKETS
+            .iter()
+            .map(|&nanos| nanos as f64 / 1e9)
+            .chain([f64::INFINITY]);
+        let mut total = 0;
+        bounds
+            .zip(&self.0.buckets)
+            .map(|(bound, count)| {
+                total += count.load(Ordering::Relaxed);
+                (bound, total)
+            })
+            .collect()
+    }
+}
+
+/// Reads a gauge's value when the registry is encoded; `None` removes the
+/// series, e.g. once the object it observes is gone
+type GaugeFn = Box<dyn Fn() -> Option<i64> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Histogram => "histogram",
+        }
+    }
+}
+
+enum Series {
+    Counter(Counter),
+    Gauge(Gauge),
+    GaugeFn(GaugeFn),
+    Histogram(Histogram),
+}
+
+/// Label names and values, in registration order
+type Labels = Vec<(&'static str, String)>;
+
+struct Family {
+    kind: Kind,
+    help: &'static str,
+    series: BTreeMap<Labels, Series>,
+}
+
+/// Metric families by name, see the [module documentation](self)
+#[derive(Default)]
+pub struct Registry {
+    families: RwLock<BTreeMap<&'static str, Family>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The registry the pub/sub layer registers its metrics with
+    pub fn global() -> &'static Registry {
+        static GLOBAL: OnceLock<Registry> = OnceLock::new();
+        GLOBAL.get_or_init(Registry::new)
+    }
+
+    /// The counter `name` with `labels`, created at zero if new
+    ///
+    /// Panics if `name` is already registered as another type.
+    pub fn counter(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+    ) -> Counter {
+        self.series(
+            name,
+            Kind::Counter,
+            help,
+            labels,
+            || Series::Counter(Counter::default()),
+            |series| match series {
+                Series::Counter(counter) => Some(counter.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    /// The gauge `name` with `labels`, created at zero if new
+    ///
+    /// Panics if `name` is already registered as another type.
+    pub fn gauge(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+    ) -> Gauge {
+        self.series(
+            name,
+            Kind::Gauge,
+            help,
+            labels,
+            || Series::Gauge(Gauge::default()),
+            |series| match series {
+                Series::Gauge(gauge) => Some(gauge.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    /// Register a gauge whose value `read` computes when the registry is
+    /// encoded, replacing any series with the same labels
+    ///
+    /// The series is removed the first time `read` returns `None`. Panics if
+    /// `name` is already registered as another type.
+    pub fn gauge_fn<F>(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+        read: F,
+    ) where
+        F: Fn() -> Option<i64> + Send + Sync + 'static,
+    {
+        let mut families = self.families.write();
+        let family = Self::family(&mut families, name, Kind::Gauge, help);
+        family
+            .series
+            .insert(own(labels), Series::GaugeFn(Box::new(read)));
+    }
+
+    /// The histogram `name` with `labels`, created empty if new
+    ///
+    /// Panics if `name` is already registered as another type.
+    pub fn histogram(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+    ) -> Histogram {
+        self.series(
+            name,
+            Kind::Histogram,
+            help,
+            labels,
+            || Series::Histogram(Histogram::default()),
+            |series| match series {
+                Series::Histogram(histogram) => Some(histogram.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    /// Every series in the Prometheus text exposition format, families
+    /// sorted by name and series by labels
+    pub fn encode_prometheus(&self) -> String {
+        let mut out = String::new();
+        for family in self.snapshot() {
+            let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+            let _ = writeln!(out, "# TYPE {} {}", family.name, family.kind);
+            for series in &family.series {
+                let labels = series
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+                    .collect::<Vec<_>>();
+                match &series.value {
+                    SeriesValue::Scalar { value } => {
+                        let _ = writeln!(out, "{}{} {}", family.name, braced(&labels), value);
+                    }
+                    SeriesValue::Histogram {
+                        count,
+                        sum,
+                        buckets,
+                    } => {
+                        for bucket in buckets {
+                            let mut labels = labels.clone();
+                            labels.push(format!("le=\"{}\"", bucket.le));
+                            let name = family.name;
+                            let _ = writeln!(
+                                out,
+                                "{}_bucket{} {}",
+                                name,
+                                braced(&labels),
+                                bucket.count
+                            );
+                        }
+                        let _ = writeln!(out, "{}_sum{} {}", family.name, braced(&labels), sum);
+                        let _ = writeln!(out, "{}_count{} {}", family.name, braced(&labels), count);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Every series as pretty-printed JSON, families sorted by name and
+    /// series by labels, so two runs can be diffed
+    pub fn encode_json(&self) -> String {
+        serde_json::to_string_pretty(&self.snapshot()).unwrap_or_default()
+    }
+
+    fn snapshot(&self) -> Vec<FamilySnapshot> {
+        let mut families = self.families.write();
+        for family in families.values_mut() {
+            family.series.retain(|_, series| match series {
+                Series::GaugeFn(read) => read().is_some(),
+                _ => true,
+            });
+        }
+        families
+            .iter()
+            .map(|(name, family)| FamilySnapshot {
+                name,
+                kind: family.kind.as_str(),
+                help: family.help,
+                series: family
+                    .series
+                    .iter()
+                    .map(|(labels, series)| SeriesSnapshot {
+                        labels: labels
+                            .iter()
+                            .map(|(name, value)| (*name, value.clone()))
+                            .collect(),
+                        value: series.value(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn series<H>(
+        &self,
+        name: &'static str,
+        kind: Kind,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+        create: impl FnOnce() -> Series,
+        handle: impl Fn(&Series) -> Option<H>,
+    ) -> H {
+        let labels = own(labels);
+        if let Some(found) = self
+            .families
+            .read()
+            .get(name)
+            .and_then(|family| family.series.get(&labels))
+            .and_then(&handle)
+        {
+            return found;
+        }
+        let mut families = self.families.write();
+        let family = Self::family(&mut families, name, kind, help);
+        let series = family.series.entry(labels).or_insert_with(create);
+        handle(series).expect("series matches its family's type")
+    }
+
+    fn family<'a>(
+        families: &'a mut BTreeMap<&'static str, Family>,
+        name: &'static str,
+        kind: Kind,
+        help: &'static str,
+    ) -> &'a mut Family {
+        let family = families.entry(name).or_insert_with(|| Family {
+            kind,
+            help,
+            series: BTreeMap::new(),
+        });
+        assert!(
+            family.kind == kind,
+            "metric {} is a {}, not a {}",
+            name,
+            family.kind.as_str(),
+            kind.as_str()
+        );
+        family
+    }
+}
+
+impl Series {
+    fn value(&self) -> SeriesValue {
+        match self {
+            Series::Counter(counter) => SeriesValue::Scalar {
+                value: i64::try_from(counter.get()).unwrap_or(i64::MAX),
+            },
+            Series::Gauge(gauge) => SeriesValue::Scalar { value: gauge.get() },
+            Series::GaugeFn(read) => SeriesValue::Scalar {
+                value: read().unwrap_or(0),
+            },
+            Series::Histogram(histogram) => SeriesValue::Histogram {
+                count: histogram.count(),
+                sum: histogram.sum().as_secs_f64(),
+                buckets: histogram
+                    .buckets()
+                    .into_iter()
+                    .map(|(le, count)| BucketSnapshot {
+                        le: if le.is_infinite() {
+                            "+Inf".to_string()
+                        } else {
+                            le.to_string()
+                        },
+                        count,
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FamilySnapshot {
+    name: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    help: &'static str,
+    series: Vec<SeriesSnapshot>,
+}
+
+#[derive(Serialize)]
+struct SeriesSnapshot {
+    labels: BTreeMap<&'static str, String>,
+    #[serde(flatten)]
+    value: SeriesValue,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SeriesValue {
+    Scalar {
+        value: i64,
+    },
+    Histogram {
+        count: u64,
+        /// In seconds
+        sum: f64,
+        buckets: Vec<BucketSnapshot>,
+    },
+}
+
+#[derive(Serialize)]
+struct BucketSnapshot {
+    /// Upper bound in seconds, or `+Inf`
+    le: String,
+    /// Observations at or below `le`
+    count: u64,
+}
+
+fn own(labels: &[(&'static str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect()
+}
+
+fn braced(labels: &[String]) -> String {
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// Escape a label value for the text format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_series_are_cached_by_labels() {
+        let registry = Registry::new();
+        let a = registry.counter("test_total", "Test", &[("topic", "/a")]);
+        a.add(2);
+        registry
+            .counter("test_total", "Test", &[("topic", "/a")])
+            .inc();
+        registry
+            .counter("test_total", "Test", &[("topic", "/b")])
+            .inc();
+        assert_eq!(a.get(), 3);
+
+        let text = registry.encode_prometheus();
+        assert_eq!(
+            text,
+            "# HELP test_total Test\n# TYPE test_total counter\n\
+             test_total{topic=\"/a\"} 3\ntest_total{topic=\"/b\"} 1\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "metric test_mixed is a counter, not a gauge")]
+    fn test_kind_mismatch_panics() {
+        let registry = Registry::new();
+        registry.counter("test_mixed", "Test", &[]);
+        registry.gauge("test_mixed", "Test", &[]);
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let registry = Registry::new();
+        let histogram = registry.histogram("test_seconds", "Test", &[]);
+        histogram.observe(Duration::from_micros(1));
+        histogram.observe(Duration::from_micros(3));
+        histogram.observe(Duration::from_secs(2));
+
+        let buckets = histogram.buckets();
+        assert_eq!(buckets[0], (1e-6, 1));
+        assert_eq!(buckets[1], (2.5e-6, 1));
+        assert_eq!(buckets[2], (5e-6, 2));
+        assert_eq!(buckets[BUCKETS.len() - 1], (1.0, 2));
+        assert_eq!(buckets[BUCKETS.len()], (f64::INFINITY, 3));
+        assert_eq!(histogram.count(), 3);
+
+        let text = registry.encode_prometheus();
+        assert!(text.contains("test_seconds_bucket{le=\"0.000005\"} 2\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("test_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn test_gauge_fn_is_removed_when_gone() {
+        let registry = Registry::new();
+        let depth = Arc::new(AtomicI64::new(4));
+        let weak = Arc::downgrade(&depth);
+        registry.gauge_fn("test_depth", "Test", &[("topic", "/a")], move || {
+            weak.upgrade().map(|depth| depth.load(Ordering::Relaxed))
+        });
+        assert!(registry
+            .encode_prometheus()
+            .contains("test_depth{topic=\"/a\"} 4\n"));
+
+        drop(depth);
+        assert!(!registry.encode_prometheus().contains("test_depth{"));
+    }
+}
@@ -13,7 +13,7 @@ pub struct Zenoh {
     _inner: Arc<RwLock<()>>, // Placeholder for actual Zenoh session
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ZenohConfig {
     pub mode: String,
     pub connect: Vec<String>,
@@ -0,0 +1,239 @@
+//! Topic names, namespaces and remapping
+//!
+//! Names follow the ROS conventions: `/`-separated tokens of ASCII letters,
+//! digits and underscores, absolute when they start with `/` and relative
+//! to a namespace otherwise.
+
+use crate::error::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// A validated topic name or namespace
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TopicName(String);
+
+impl TopicName {
+    /// Validate a name
+    ///
+    /// Rejects empty names, empty tokens (`//` or a trailing `/`), tokens
+    /// starting with a digit and any character other than ASCII letters,
+    /// digits, `_` and `/`. The root namespace `/` is valid.
+    pub fn new(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        if let Err(reason) = Self::validate(&name) {
+            return Err(Error::InvalidTopicName { name, reason });
+        }
+        Ok(Self(name))
+    }
+
+    fn validate(name: &str) -> std::result::Result<(), String> {
+        if name.is_empty() {
+            return Err("name is empty".into());
+        }
+        if name == "/" {
+            return Ok(());
+        }
+        let tokens = name.strip_prefix('/').unwrap_or(name);
+        for token in tokens.split('/') {
+            if token.is_empty() {
+                return Err("contains an empty segment ('//' or a trailing '/')".into());
+            }
+            if let Some(c) = token
+                .chars()
+                .find(|c| !c.is_ascii_alphanumeric() && *c != '_')
+            {
+                return Err(format!("invalid character {c:?} in segment '{token}'"));
+            }
+            if token.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(format!("segment '{token}' starts with a digit"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The root namespace `/`
+    pub fn root() -> Self {
+        Self("/".to_string())
+    }
+
+    /// Whether the name starts with `/`
+    pub fn is_absolute(&self) -> bool {
+        self.0.starts_with('/')
+    }
+
+    /// Resolve against a namespace into an absolute name, leaving absolute
+    /// names unchanged
+    pub fn resolve(&self, namespace: &TopicName) -> TopicName {
+        if self.is_absolute() {
+            return self.clone();
+        }
+        match namespace.0.trim_matches('/') {
+            "" => TopicName(format!("/{}", self.0)),
+            namespace => TopicName(format!("/{namespace}/{}", self.0)),
+        }
+    }
+
+    /// The name as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TopicName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for TopicName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl AsRef<str> for TopicName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<TopicName> for String {
+    fn from(name: TopicName) -> Self {
+        name.0
+    }
+}
+
+/// A remapping rule such as `cmd_vel:=robot1/cmd_vel`
+///
+/// Both sides are resolved against the node namespace before matching, so
+/// `cmd_vel` and `/cmd_vel` match the same topic in the root namespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remap {
+    pub from: TopicName,
+    pub to: TopicName,
+}
+
+impl Remap {
+    /// Create a rule remapping `from` to `to`
+    pub fn new(from: &str, to: &str) -> Result<Self> {
+        Ok(Self {
+            from: TopicName::new(from)?,
+            to: TopicName::new(to)?,
+        })
+    }
+}
+
+impl FromStr for Remap {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(":=") {
+            Some((from, to)) => Self::new(from, to),
+            None => Err(Error::Configuration(format!(
+                "Invalid remap rule '{s}': expected 'from:=to'"
+            ))),
+        }
+    }
+}
+
+/// Resolves the topic names used by a node
+#[derive(Debug, Clone)]
+pub struct NameResolver {
+    namespace: TopicName,
+    remaps: Vec<Remap>,
+}
+
+impl NameResolver {
+    /// Create a resolver for a node in `namespace` (`/` or empty for the root)
+    pub fn new(namespace: &str, remaps: Vec<Remap>) -> Result<Self> {
+        let namespace = match namespace {
+            "" => TopicName::root(),
+            ns if ns.starts_with('/') => TopicName::new(ns)?,
+            ns => TopicName::new(format!("/{ns}"))?,
+        };
+        Ok(Self { namespace, remaps })
+    }
+
+    /// The node namespace
+    pub fn namespace(&self) -> &TopicName {
+        &self.namespace
+    }
+
+    /// Validate `name`, resolve it against the namespace and apply the first
+    /// matching remap rule
+    pub fn resolve(&self, name: &str) -> Result<TopicName> {
+        let resolved = TopicName::new(name)?.resolve(&self.namespace);
+        Ok(self
+            .remaps
+            .iter()
+            .find(|remap| remap.from.resolve(&self.namespace) == resolved)
+            .map(|remap| remap.to.resolve(&self.namespace))
+            .unwrap_or(resolved))
+    }
+}
+
+impl Default for NameResolver {
+    fn default() -> Self {
+        Self {
+            namespace: TopicName::root(),
+            remaps: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        for valid in ["/", "cmd_vel", "/robot1/cmd_vel", "sensors/lidar_2"] {
+            assert!(TopicName::new(valid).is_ok(), "{valid}");
+        }
+        for invalid in ["", "cmd vel", "/robot//cmd_vel", "robot/", "/2d/map", "odom-raw"] {
+            assert!(
+                matches!(TopicName::new(invalid), Err(Error::InvalidTopicName { .. })),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_relative_resolution() {
+        let ns = TopicName::new("/robot1").unwrap();
+        let name = TopicName::new("cmd_vel").unwrap();
+        assert_eq!(name.resolve(&ns).as_str(), "/robot1/cmd_vel");
+        assert_eq!(name.resolve(&TopicName::root()).as_str(), "/cmd_vel");
+
+        let absolute = TopicName::new("/tf").unwrap();
+        assert_eq!(absolute.resolve(&ns).as_str(), "/tf");
+    }
+
+    #[test]
+    fn test_remap() {
+        let remap: Remap = "cmd_vel:=robot1/cmd_vel".parse().unwrap();
+        let resolver = NameResolver::new("/", vec![remap]).unwrap();
+
+        assert_eq!(resolver.resolve("cmd_vel").unwrap().as_str(), "/robot1/cmd_vel");
+        assert_eq!(resolver.resolve("/cmd_vel").unwrap().as_str(), "/robot1/cmd_vel");
+        assert_eq!(resolver.resolve("odom").unwrap().as_str(), "/odom");
+        assert!("cmd_vel=robot1/cmd_vel".parse::<Remap>().is_err());
+    }
+
+    #[test]
+    fn test_namespaced_resolver() {
+        let resolver = NameResolver::new(
+            "robot1",
+            vec![Remap::new("scan", "/shared/scan").unwrap()],
+        )
+        .unwrap();
+
+        assert_eq!(resolver.namespace().as_str(), "/robot1");
+        assert_eq!(resolver.resolve("odom").unwrap().as_str(), "/robot1/odom");
+        assert_eq!(resolver.resolve("scan").unwrap().as_str(), "/shared/scan");
+        assert!(resolver.resolve("bad name").is_err());
+        assert!(NameResolver::new("/robot 1", Vec::new()).is_err());
+    }
+}
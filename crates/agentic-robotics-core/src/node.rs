@@ -0,0 +1,669 @@
+//! Node implementation
+//!
+//! A node groups the publishers, subscribers and timers of one component of
+//! a robot. Shutting down or dropping the node tears all of them down, so
+//! the topic bus never lists endpoints of a node that is gone.
+
+use crate::diagnostics::{DiagnosticUpdater, DIAGNOSTICS_TOPIC};
+use crate::error::{Error, Result};
+use crate::message::Message;
+use crate::name::{NameResolver, Remap, TopicName};
+use crate::params::ParameterServer;
+use crate::publisher::{Publisher, PublisherOptions};
+use crate::qos::QosProfile;
+use crate::serialization::Format;
+use crate::subscriber::{DirectSubscriber, Subscriber};
+use crate::time::{Clock, Time};
+use crate::topic::{DirectHandler, SampleQueue, Topic, TopicBus, Writer};
+use crate::trace::{self, EventKind};
+use crate::transport::{RemoteTransport, TransportConfig};
+#[cfg(unix)]
+use crate::shm::{ShmConfig, ShmTransport};
+use parking_lot::Mutex;
+#[cfg(unix)]
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+use tracing::{debug, info_span, Instrument, Span};
+
+/// An endpoint registered on the bus on behalf of a node
+enum Endpoint {
+    Publisher(Arc<Topic>, Arc<Writer>),
+    Subscriber(Arc<Topic>, Arc<SampleQueue>),
+    Direct(Arc<Topic>, Arc<DirectHandler>),
+}
+
+impl Endpoint {
+    /// Whether the node holds the last reference, i.e. the endpoint was dropped
+    fn is_released(&self) -> bool {
+        match self {
+            Endpoint::Publisher(_, writer) => Arc::strong_count(writer) == 1,
+            Endpoint::Subscriber(_, queue) => Arc::strong_count(queue) == 1,
+            Endpoint::Direct(_, handler) => Arc::strong_count(handler) == 1,
+        }
+    }
+
+    /// Whether the other side of the topic is present on the bus
+    fn is_matched(&self) -> bool {
+        match self {
+            Endpoint::Publisher(topic, _) => {
+                topic.has_subscribers() || topic.has_direct_subscribers()
+            }
+            Endpoint::Subscriber(topic, _) | Endpoint::Direct(topic, _) => topic.has_publishers(),
+        }
+    }
+
+    fn topic(&self) -> &str {
+        match self {
+            Endpoint::Publisher(topic, _)
+            | Endpoint::Subscriber(topic, _)
+            | Endpoint::Direct(topic, _) => topic.name(),
+        }
+    }
+
+    fn detach(&self) {
+        match self {
+            Endpoint::Publisher(topic, writer) => {
+                TopicBus::global().detach_publisher(topic, writer)
+            }
+            Endpoint::Subscriber(topic, queue) => {
+                TopicBus::global().detach_subscriber(topic, queue)
+            }
+            Endpoint::Direct(topic, handler) => TopicBus::global().detach_direct(topic, handler),
+        }
+    }
+}
+
+/// A named node owning publishers, subscribers, timers and parameters
+///
+/// Topic names passed to the node are resolved against its namespace and
+/// remap rules. After [`shutdown`](Self::shutdown), the node's publishers
+/// and subscribers fail with [`Error::Closed`] and its timers stop.
+pub struct Node {
+    name: String,
+    names: NameResolver,
+    parameters: ParameterServer,
+    clock: Clock,
+    transport: Option<RemoteTransport>,
+    /// Topics carried over shared memory instead of `transport`
+    #[cfg(unix)]
+    shared_memory: HashMap<String, Arc<ShmTransport>>,
+    endpoints: Mutex<Vec<Endpoint>>,
+    timers: Mutex<Vec<AbortHandle>>,
+    shutdown: AtomicBool,
+    /// Woken by [`shutdown`](Self::shutdown)
+    closed: Notify,
+    /// Parent of the node's log events and timer callbacks, with its `node`
+    span: Span,
+}
+
+impl Node {
+    /// Create a node in the root namespace
+    pub fn new(name: impl Into<String>) -> Result<Self> {
+        Self::with_namespace(name, "/", Vec::new())
+    }
+
+    /// Create a node whose relative topic names resolve against `namespace`
+    /// and are then remapped by `remaps`
+    pub fn with_namespace(
+        name: impl Into<String>,
+        namespace: &str,
+        remaps: Vec<Remap>,
+    ) -> Result<Self> {
+        let name = name.into();
+        if name.is_empty() {
+            return Err(Error::Configuration("Node name is empty".into()));
+        }
+        let names = NameResolver::new(namespace, remaps)?;
+        let span = info_span!("node", node = %name, namespace = %names.namespace());
+        debug!(parent: &span, "Creating node");
+
+        Ok(Self {
+            parameters: ParameterServer::new(name.clone()),
+            name,
+            names,
+            clock: Clock::default(),
+            transport: None,
+            #[cfg(unix)]
+            shared_memory: HashMap::new(),
+            endpoints: Mutex::new(Vec::new()),
+            timers: Mutex::new(Vec::new()),
+            shutdown: AtomicBool::new(false),
+            closed: Notify::new(),
+            span,
+        })
+    }
+
+    /// Read time from `clock` instead of the system clock
+    ///
+    /// Affects timers created afterwards.
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Reach publishers and subscribers in other processes through
+    /// `transport`
+    ///
+    /// Affects endpoints created afterwards. Must be called from within a
+    /// Tokio runtime when the transport is not in-process.
+    pub fn with_transport(mut self, transport: TransportConfig) -> Result<Self> {
+        self.transport = RemoteTransport::start(&transport)?;
+        Ok(self)
+    }
+
+    /// Register the message types defined under `dir`, see
+    /// [`idl::load_dir`](crate::idl::load_dir)
+    ///
+    /// Lets the node's tools and bridges handle those types by name. The
+    /// types stay registered for the life of the process.
+    #[cfg(feature = "idl")]
+    pub fn with_message_definitions(self, dir: impl AsRef<std::path::Path>) -> Result<Self> {
+        let types = crate::idl::load_dir(dir)?;
+        debug!(parent: &self.span, count = types.len(), "Registered message definitions");
+        Ok(self)
+    }
+
+    /// Carry `topic` between processes on this host over shared memory
+    ///
+    /// Takes precedence over the node's [`TransportConfig`] for that topic.
+    /// Affects endpoints created afterwards. Must be called from within a
+    /// Tokio runtime.
+    #[cfg(unix)]
+    pub fn with_shared_memory(mut self, topic: &str, config: ShmConfig) -> Result<Self> {
+        let topic = self.resolve(topic)?;
+        self.shared_memory
+            .insert(topic.into(), ShmTransport::shared(&config)?);
+        Ok(self)
+    }
+
+    /// The clock used by the node's timers and stamps
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Current time on the node's clock
+    pub fn now(&self) -> Time {
+        self.clock.now()
+    }
+
+    /// Get node name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get node namespace
+    pub fn namespace(&self) -> &TopicName {
+        self.names.namespace()
+    }
+
+    /// The node's runtime parameters
+    ///
+    /// Undeclared parameters are rejected unless the node opts in with
+    /// [`ParameterServer::set_allow_undeclared`].
+    pub fn parameters(&self) -> &ParameterServer {
+        &self.parameters
+    }
+
+    /// Resolve a topic name the way this node's endpoints do
+    pub fn resolve(&self, topic: &str) -> Result<TopicName> {
+        self.names.resolve(topic)
+    }
+
+    /// Create a publisher using CDR serialization
+    pub fn create_publisher<T: Message>(&self, topic: &str) -> Result<Publisher<T>> {
+        self.create_publisher_with_format(topic, Format::Cdr)
+    }
+
+    /// Create a publisher using a specific format
+    pub fn create_publisher_with_format<T: Message>(
+        &self,
+        topic: &str,
+        format: Format,
+    ) -> Result<Publisher<T>> {
+        self.check_running()?;
+        let publisher = Publisher::create(
+            self.resolve(topic)?.into(),
+            format,
+            QosProfile::default(),
+            Some(self.name.clone()),
+        )?
+        .with_clock(self.clock.clone());
+        let (link, writer) = publisher.endpoint();
+        self.track(Endpoint::Publisher(link, writer))?;
+        self.advertise_publisher(publisher)
+    }
+
+    /// Create a publisher with a QoS profile
+    pub fn create_publisher_with_qos<T: Message>(
+        &self,
+        topic: &str,
+        qos: QosProfile,
+    ) -> Result<Publisher<T>> {
+        self.check_running()?;
+        let publisher = Publisher::create(
+            self.resolve(topic)?.into(),
+            Format::Cdr,
+            qos,
+            Some(self.name.clone()),
+        )?
+        .with_clock(self.clock.clone());
+        let (link, writer) = publisher.endpoint();
+        self.track(Endpoint::Publisher(link, writer))?;
+        self.advertise_publisher(publisher)
+    }
+
+    /// Create a publisher with a format, QoS profile and compression
+    ///
+    /// ```no_run
+    /// # use agentic_robotics_core::{Compression, Node, PointCloud, PublisherOptions};
+    /// # #[cfg(feature = "zstd")]
+    /// # fn run(node: &Node) -> agentic_robotics_core::Result<()> {
+    /// let options = PublisherOptions::new()
+    ///     .compression(Compression::Zstd { level: 3 })
+    ///     .compression_threshold(4096);
+    /// let publisher = node.create_publisher_with_options::<PointCloud>("/lidar/points", options)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_publisher_with_options<T: Message>(
+        &self,
+        topic: &str,
+        options: PublisherOptions,
+    ) -> Result<Publisher<T>> {
+        self.check_running()?;
+        let publisher = Publisher::create(
+            self.resolve(topic)?.into(),
+            options.format,
+            options.qos,
+            Some(self.name.clone()),
+        )?
+        .with_clock(self.clock.clone())
+        .with_options(options);
+        let (link, writer) = publisher.endpoint();
+        self.track(Endpoint::Publisher(link, writer))?;
+        self.advertise_publisher(publisher)
+    }
+
+    /// Create a subscriber with an unbounded queue
+    pub fn create_subscriber<T: Message>(&self, topic: &str) -> Result<Subscriber<T>> {
+        self.check_running()?;
+        let subscriber = Subscriber::builder(self.resolve(topic)?)
+            .node(&self.name)
+            .clock(self.clock.clone())
+            .build()?;
+        let (link, queue) = subscriber.endpoint();
+        self.track(Endpoint::Subscriber(link, queue))?;
+        self.advertise_subscriber::<T>(subscriber.topic())?;
+        Ok(subscriber)
+    }
+
+    /// Create a subscriber that only queues messages for which `predicate`
+    /// returns `true`
+    ///
+    /// See [`SubscriberBuilder::filter`](crate::SubscriberBuilder::filter).
+    pub fn create_filtered_subscriber<T, F>(
+        &self,
+        topic: &str,
+        predicate: F,
+    ) -> Result<Subscriber<T>>
+    where
+        T: Message,
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.check_running()?;
+        let subscriber = Subscriber::builder(self.resolve(topic)?)
+            .filter(predicate)
+            .node(&self.name)
+            .clock(self.clock.clone())
+            .build()?;
+        let (link, queue) = subscriber.endpoint();
+        self.track(Endpoint::Subscriber(link, queue))?;
+        self.advertise_subscriber::<T>(subscriber.topic())?;
+        Ok(subscriber)
+    }
+
+    /// Create a subscriber whose callback runs on the publisher's call stack
+    ///
+    /// See [`DirectSubscriber`].
+    pub fn create_direct_subscriber<T, F>(
+        &self,
+        topic: &str,
+        callback: F,
+    ) -> Result<DirectSubscriber<T>>
+    where
+        T: Message,
+        F: Fn(&T) + Send + Sync + 'static,
+    {
+        self.check_running()?;
+        let name = self.resolve(topic)?.into();
+        let subscriber = DirectSubscriber::create(name, Some(self.name.clone()), callback)?;
+        let (link, handler) = subscriber.endpoint();
+        self.track(Endpoint::Direct(link, handler))?;
+        self.advertise_subscriber::<T>(subscriber.topic())?;
+        Ok(subscriber)
+    }
+
+    /// Create a subscriber with a QoS profile
+    pub fn create_subscriber_with_qos<T: Message>(
+        &self,
+        topic: &str,
+        qos: QosProfile,
+    ) -> Result<Subscriber<T>> {
+        self.check_running()?;
+        let subscriber = Subscriber::builder(self.resolve(topic)?)
+            .qos(qos)
+            .node(&self.name)
+            .clock(self.clock.clone())
+            .build()?;
+        let (link, queue) = subscriber.endpoint();
+        self.track(Endpoint::Subscriber(link, queue))?;
+        self.advertise_subscriber::<T>(subscriber.topic())?;
+        Ok(subscriber)
+    }
+
+    /// Call `callback` every `period` of the node's clock until the timer is
+    /// cancelled or the node shuts down
+    ///
+    /// The first call happens one period from now. Under simulation time,
+    /// calls missed by a jump of the clock are made back to back. Must be
+    /// called from within a Tokio runtime.
+    pub fn create_timer<F>(&self, period: Duration, mut callback: F) -> Result<Timer>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.check_running()?;
+        if period.is_zero() {
+            return Err(Error::Configuration("Timer period must be non-zero".into()));
+        }
+        let clock = self.clock.clone();
+        let label = trace::intern(&format!("{}/timer/{:?}", self.name, period));
+        let mut tick = move |count: u64| {
+            let _span = trace::span(EventKind::Timer, label).arg(count);
+            callback();
+        };
+        let timer = async move {
+            match clock {
+                Clock::Sim(sim) => {
+                    let mut next = sim.now();
+                    for count in 0.. {
+                        next = next + period;
+                        sim.sleep_until(next).await;
+                        tick(count);
+                    }
+                }
+                Clock::SystemTime | Clock::Steady => {
+                    let start = tokio::time::Instant::now() + period;
+                    let mut interval = tokio::time::interval_at(start, period);
+                    for count in 0.. {
+                        interval.tick().await;
+                        tick(count);
+                    }
+                }
+            }
+        };
+        let span = info_span!(parent: &self.span, "timer", period = ?period);
+        let task = tokio::spawn(timer.instrument(span));
+        let handle = task.abort_handle();
+
+        let mut timers = self.timers.lock();
+        timers.retain(|timer| !timer.is_finished());
+        timers.push(handle.clone());
+        Ok(Timer { handle })
+    }
+
+    /// Publish diagnostics on [`DIAGNOSTICS_TOPIC`] every `period` of the
+    /// node's clock
+    ///
+    /// Status names are prefixed with the node name, like
+    /// `"lidar_node: scan_rate"`. Updates stop when the updater is dropped or
+    /// the node shuts down. Must be called from within a Tokio runtime.
+    pub fn create_diagnostic_updater(&self, period: Duration) -> Result<DiagnosticUpdater> {
+        let publisher = self.create_publisher(DIAGNOSTICS_TOPIC)?;
+        let prefix = format!("{}: ", self.name);
+        let updater = DiagnosticUpdater::start(publisher, period, self.clock.clone(), &prefix)?;
+
+        let mut timers = self.timers.lock();
+        timers.retain(|timer| !timer.is_finished());
+        timers.push(updater.abort_handle());
+        Ok(updater)
+    }
+
+    /// Tear down every endpoint and cancel every timer of this node
+    ///
+    /// Calling this more than once has no further effect.
+    pub fn shutdown(&self) {
+        if self.shutdown.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        debug!(parent: &self.span, "Shutting down node");
+
+        self.closed.notify_waiters();
+        for timer in self.timers.lock().drain(..) {
+            timer.abort();
+        }
+        for endpoint in self.endpoints.lock().drain(..) {
+            endpoint.detach();
+        }
+    }
+
+    /// Whether [`shutdown`](Self::shutdown) has been called
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Acquire)
+    }
+
+    /// Wait until the node is shut down
+    pub async fn closed(&self) {
+        let notified = self.closed.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.is_shutdown() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Topics of the node's publishers and subscribers whose counterpart is
+    /// not on the bus yet, sorted and without duplicates
+    ///
+    /// A publisher is matched once its topic has a subscriber, a subscriber
+    /// once its topic has a publisher. Only endpoints in this process count,
+    /// and the diagnostic updater's publisher is ignored.
+    pub fn unmatched_endpoints(&self) -> Vec<String> {
+        let mut topics: Vec<String> = self
+            .endpoints
+            .lock()
+            .iter()
+            .filter(|e| !e.is_released() && !e.is_matched())
+            .map(|e| e.topic().to_string())
+            .filter(|topic| topic != DIAGNOSTICS_TOPIC)
+            .collect();
+        topics.sort();
+        topics.dedup();
+        topics
+    }
+
+    fn check_running(&self) -> Result<()> {
+        if self.is_shutdown() {
+            return Err(Error::Closed(format!("node '{}' was shut down", self.name)));
+        }
+        Ok(())
+    }
+
+    /// Make a new publisher reachable from other processes
+    fn advertise_publisher<T: Message>(&self, publisher: Publisher<T>) -> Result<Publisher<T>> {
+        let topic = publisher.topic();
+        #[cfg(unix)]
+        if let Some(shm) = self.shared_memory.get(topic) {
+            shm.advertise_publisher::<T>(topic)?;
+            return Ok(publisher);
+        }
+        match &self.transport {
+            Some(RemoteTransport::Udp(udp)) => udp.advertise_publisher::<T>(topic)?,
+            Some(RemoteTransport::Tcp(tcp)) => {
+                let uplink = tcp.advertise_publisher::<T>(topic)?;
+                return Ok(publisher.with_uplink(uplink));
+            }
+            #[cfg(feature = "transport-zenoh")]
+            Some(RemoteTransport::Zenoh(zenoh)) => zenoh.advertise_publisher::<T>(topic)?,
+            None => {}
+        }
+        Ok(publisher)
+    }
+
+    /// Let a new subscriber on `topic` receive from other processes
+    fn advertise_subscriber<T: Message>(&self, topic: &str) -> Result<()> {
+        #[cfg(unix)]
+        if let Some(shm) = self.shared_memory.get(topic) {
+            return shm.advertise_subscriber::<T>(topic);
+        }
+        match &self.transport {
+            Some(RemoteTransport::Udp(udp)) => udp.advertise_subscriber::<T>(topic),
+            Some(RemoteTransport::Tcp(tcp)) => tcp.advertise_subscriber::<T>(topic),
+            #[cfg(feature = "transport-zenoh")]
+            Some(RemoteTransport::Zenoh(zenoh)) => zenoh.advertise_subscriber::<T>(topic),
+            None => Ok(()),
+        }
+    }
+
+    fn track(&self, endpoint: Endpoint) -> Result<()> {
+        let mut endpoints = self.endpoints.lock();
+        // Shutdown may have drained the list since `check_running`
+        if self.is_shutdown() {
+            endpoint.detach();
+            return self.check_running();
+        }
+        endpoints.retain(|e| !e.is_released());
+        endpoints.push(endpoint);
+        Ok(())
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Handle to a periodic callback created by [`Node::create_timer`]
+pub struct Timer {
+    handle: AbortHandle,
+}
+
+impl Timer {
+    /// Stop calling the callback
+    pub fn cancel(&self) {
+        self.handle.abort();
+    }
+
+    /// Whether the timer has stopped
+    pub fn is_cancelled(&self) -> bool {
+        self.handle.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_node_resolves_topics() {
+        let node = Node::with_namespace(
+            "driver",
+            "/test/node/robot1",
+            vec![Remap::new("scan", "/test/node/shared_scan").unwrap()],
+        )
+        .unwrap();
+
+        let odom = node.create_publisher::<RobotState>("odom").unwrap();
+        assert_eq!(odom.topic(), "/test/node/robot1/odom");
+        let scan = node.create_subscriber::<RobotState>("scan").unwrap();
+        assert_eq!(scan.topic(), "/test/node/shared_scan");
+        assert!(node.create_publisher::<RobotState>("bad name").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_tears_down_endpoints() {
+        let node = Node::with_namespace("teardown", "/test/node/shutdown", Vec::new()).unwrap();
+        let publisher = node.create_publisher::<RobotState>("state").unwrap();
+        let subscriber = node.create_subscriber::<RobotState>("state").unwrap();
+        let bus = TopicBus::global();
+        assert_eq!(bus.publisher_count("/test/node/shutdown/state"), 1);
+        assert_eq!(bus.subscriber_count("/test/node/shutdown/state"), 1);
+
+        node.shutdown();
+
+        assert!(!bus.topic_names().contains(&"/test/node/shutdown/state".to_string()));
+        assert!(matches!(
+            publisher.publish(&RobotState::default()).await,
+            Err(Error::Closed(_))
+        ));
+        assert!(matches!(subscriber.recv().await, Err(Error::Closed(_))));
+        assert!(matches!(
+            node.create_publisher::<RobotState>("state"),
+            Err(Error::Closed(_))
+        ));
+    }
+
+    #[test]
+    fn test_unmatched_endpoints() {
+        let node = Node::with_namespace("matcher", "/test/node/match", Vec::new()).unwrap();
+        let _state = node.create_publisher::<RobotState>("state").unwrap();
+        let _cmd = node.create_subscriber::<RobotState>("cmd").unwrap();
+        assert_eq!(
+            node.unmatched_endpoints(),
+            vec!["/test/node/match/cmd", "/test/node/match/state"]
+        );
+
+        let peer = Node::with_namespace("peer", "/test/node/match", Vec::new()).unwrap();
+        let _cmd_publisher = peer.create_publisher::<RobotState>("cmd").unwrap();
+        assert_eq!(node.unmatched_endpoints(), vec!["/test/node/match/state"]);
+        let _state_subscriber = peer.create_subscriber::<RobotState>("state").unwrap();
+        assert!(node.unmatched_endpoints().is_empty());
+    }
+
+    #[test]
+    fn test_drop_unregisters_topics() {
+        let node = Node::with_namespace("ghost", "/test/node/drop", Vec::new()).unwrap();
+        let _publisher = node.create_publisher::<RobotState>("state").unwrap();
+        let _subscriber = node.create_subscriber::<RobotState>("cmd").unwrap();
+
+        drop(node);
+
+        let names = TopicBus::global().topic_names();
+        assert!(!names.iter().any(|name| name.starts_with("/test/node/drop/")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timers_stop_on_shutdown() {
+        let node = Node::new("ticker").unwrap();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let cancelled_ticks = Arc::new(AtomicUsize::new(0));
+
+        let counter = ticks.clone();
+        node.create_timer(Duration::from_millis(10), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+        let counter = cancelled_ticks.clone();
+        let cancelled = node
+            .create_timer(Duration::from_millis(10), move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        cancelled.cancel();
+
+        tokio::time::sleep(Duration::from_millis(55)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), 5);
+        assert_eq!(cancelled_ticks.load(Ordering::SeqCst), 0);
+
+        node.shutdown();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), 5);
+    }
+}
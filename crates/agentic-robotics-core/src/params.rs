@@ -0,0 +1,607 @@
+//! Runtime parameters
+//!
+//! A [`ParameterServer`] holds the tunable settings of a node, such as
+//! controller gains or rate limits. Parameters are declared with a default
+//! and a [`ParameterDescriptor`], can be changed while the node runs, and
+//! notify registered callbacks on every change. All live servers are listed
+//! by [`graph::list_parameters`](crate::graph::list_parameters).
+
+use crate::error::{Error, Result};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use tracing::debug;
+
+/// Value of a parameter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ParameterValue {
+    Bool(bool),
+    Integer(i64),
+    Double(f64),
+    String(String),
+    DoubleArray(Vec<f64>),
+}
+
+impl ParameterValue {
+    /// Name of the value's type, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ParameterValue::Bool(_) => "bool",
+            ParameterValue::Integer(_) => "integer",
+            ParameterValue::Double(_) => "double",
+            ParameterValue::String(_) => "string",
+            ParameterValue::DoubleArray(_) => "double_array",
+        }
+    }
+
+    fn same_type(&self, other: &ParameterValue) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl From<bool> for ParameterValue {
+    fn from(value: bool) -> Self {
+        ParameterValue::Bool(value)
+    }
+}
+
+impl From<i64> for ParameterValue {
+    fn from(value: i64) -> Self {
+        ParameterValue::Integer(value)
+    }
+}
+
+impl From<f64> for ParameterValue {
+    fn from(value: f64) -> Self {
+        ParameterValue::Double(value)
+    }
+}
+
+impl From<String> for ParameterValue {
+    fn from(value: String) -> Self {
+        ParameterValue::String(value)
+    }
+}
+
+impl From<&str> for ParameterValue {
+    fn from(value: &str) -> Self {
+        ParameterValue::String(value.to_string())
+    }
+}
+
+impl From<Vec<f64>> for ParameterValue {
+    fn from(value: Vec<f64>) -> Self {
+        ParameterValue::DoubleArray(value)
+    }
+}
+
+/// Rust types a parameter can be read as
+pub trait ParameterType: Into<ParameterValue> + Sized {
+    /// Extract the value if it has this type
+    fn from_value(value: &ParameterValue) -> Option<Self>;
+}
+
+impl ParameterType for bool {
+    fn from_value(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl ParameterType for i64 {
+    fn from_value(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl ParameterType for f64 {
+    fn from_value(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl ParameterType for String {
+    fn from_value(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::String(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl ParameterType for Vec<f64> {
+    fn from_value(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::DoubleArray(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Inclusive bounds for numeric parameters
+///
+/// Applies to integers, doubles and every element of a double array.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ParameterRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Description and constraints of a parameter
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParameterDescriptor {
+    pub description: String,
+    /// Reject every `set` after the declaration
+    pub read_only: bool,
+    pub range: Option<ParameterRange>,
+}
+
+impl ParameterDescriptor {
+    /// Create a descriptor with a human-readable description
+    pub fn new(description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Restrict numeric values to `min..=max`
+    pub fn with_range(mut self, min: f64, max: f64) -> Self {
+        self.range = Some(ParameterRange { min, max });
+        self
+    }
+
+    /// Make the parameter read-only
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    fn validate(&self, value: &ParameterValue) -> std::result::Result<(), String> {
+        let Some(range) = self.range else {
+            return Ok(());
+        };
+        let out_of_range = |v: f64| !(range.min..=range.max).contains(&v);
+        let rejected = match value {
+            ParameterValue::Integer(v) => out_of_range(*v as f64),
+            ParameterValue::Double(v) => out_of_range(*v),
+            ParameterValue::DoubleArray(values) => values.iter().any(|v| out_of_range(*v)),
+            ParameterValue::Bool(_) | ParameterValue::String(_) => false,
+        };
+        if rejected {
+            return Err(format!("value outside [{}, {}]", range.min, range.max));
+        }
+        Ok(())
+    }
+}
+
+/// A declared parameter, as listed by the graph introspection API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterInfo {
+    /// Name of the node owning the parameter
+    pub node: String,
+    pub name: String,
+    pub value: ParameterValue,
+    pub descriptor: ParameterDescriptor,
+}
+
+type ChangeCallback = Arc<dyn Fn(&ParameterValue) + Send + Sync + 'static>;
+
+struct Parameter {
+    value: ParameterValue,
+    descriptor: ParameterDescriptor,
+    callbacks: Vec<ChangeCallback>,
+}
+
+struct ParameterStore {
+    node: String,
+    parameters: RwLock<BTreeMap<String, Parameter>>,
+    allow_undeclared: AtomicBool,
+}
+
+/// Every live parameter store, for graph introspection
+fn registry() -> &'static Mutex<Vec<Weak<ParameterStore>>> {
+    static REGISTRY: OnceLock<Mutex<Vec<Weak<ParameterStore>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Declared parameters of all live servers, sorted by node and name
+pub(crate) fn all_parameters() -> Vec<ParameterInfo> {
+    let stores: Vec<_> = {
+        let mut registry = registry().lock();
+        registry.retain(|store| store.strong_count() > 0);
+        registry.iter().filter_map(Weak::upgrade).collect()
+    };
+    let mut infos: Vec<_> = stores.iter().flat_map(|store| store.infos()).collect();
+    infos.sort_by(|a, b| (&a.node, &a.name).cmp(&(&b.node, &b.name)));
+    infos
+}
+
+/// The server of the live node named `node`
+pub(crate) fn find_server(node: &str) -> Option<ParameterServer> {
+    let registry = registry().lock();
+    registry
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|store| store.node == node)
+        .map(|store| ParameterServer { store })
+}
+
+impl ParameterStore {
+    fn infos(&self) -> Vec<ParameterInfo> {
+        self.parameters
+            .read()
+            .iter()
+            .map(|(name, parameter)| ParameterInfo {
+                node: self.node.clone(),
+                name: name.clone(),
+                value: parameter.value.clone(),
+                descriptor: parameter.descriptor.clone(),
+            })
+            .collect()
+    }
+
+    fn error(&self, name: &str, reason: impl Into<String>) -> Error {
+        Error::InvalidParameter {
+            name: format!("{}.{}", self.node, name),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Typed, validated parameters of one node
+///
+/// Cloning is cheap and yields a handle to the same parameters, so a control
+/// loop can keep one while the node keeps another.
+#[derive(Clone)]
+pub struct ParameterServer {
+    store: Arc<ParameterStore>,
+}
+
+impl ParameterServer {
+    /// Create an empty server for `node`
+    pub fn new(node: impl Into<String>) -> Self {
+        let store = Arc::new(ParameterStore {
+            node: node.into(),
+            parameters: RwLock::new(BTreeMap::new()),
+            allow_undeclared: AtomicBool::new(false),
+        });
+        registry().lock().push(Arc::downgrade(&store));
+        Self { store }
+    }
+
+    /// Name of the node owning the parameters
+    pub fn node(&self) -> &str {
+        &self.store.node
+    }
+
+    /// Let `set` declare unknown parameters on the fly, with an empty
+    /// descriptor, instead of rejecting them
+    pub fn set_allow_undeclared(&self, allow: bool) {
+        self.store.allow_undeclared.store(allow, Ordering::Release);
+    }
+
+    /// Whether `set` accepts undeclared parameters
+    pub fn allows_undeclared(&self) -> bool {
+        self.store.allow_undeclared.load(Ordering::Acquire)
+    }
+
+    /// Declare a parameter with its default value
+    ///
+    /// Fails if the parameter was already declared or the default violates
+    /// the descriptor.
+    pub fn declare(
+        &self,
+        name: &str,
+        default: impl Into<ParameterValue>,
+        descriptor: ParameterDescriptor,
+    ) -> Result<()> {
+        let value = default.into();
+        descriptor
+            .validate(&value)
+            .map_err(|reason| self.store.error(name, reason))?;
+
+        let mut parameters = self.store.parameters.write();
+        if parameters.contains_key(name) {
+            return Err(self.store.error(name, "already declared"));
+        }
+        debug!("Declaring parameter {}.{} = {:?}", self.store.node, name, value);
+        parameters.insert(
+            name.to_string(),
+            Parameter {
+                value,
+                descriptor,
+                callbacks: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Whether `name` was declared
+    pub fn has(&self, name: &str) -> bool {
+        self.store.parameters.read().contains_key(name)
+    }
+
+    /// Current value of a parameter as `T`
+    pub fn get<T: ParameterType>(&self, name: &str) -> Result<T> {
+        let value = self
+            .get_value(name)
+            .ok_or_else(|| self.store.error(name, "not declared"))?;
+        T::from_value(&value).ok_or_else(|| {
+            self.store
+                .error(name, format!("is a {}, not the requested type", value.type_name()))
+        })
+    }
+
+    /// Current value of a parameter, whatever its type
+    pub fn get_value(&self, name: &str) -> Option<ParameterValue> {
+        self.store
+            .parameters
+            .read()
+            .get(name)
+            .map(|parameter| parameter.value.clone())
+    }
+
+    /// Change a parameter and notify its callbacks
+    ///
+    /// The new value must have the declared type and satisfy the
+    /// descriptor. Undeclared parameters are rejected unless
+    /// [`set_allow_undeclared`](Self::set_allow_undeclared) is on.
+    pub fn set(&self, name: &str, value: impl Into<ParameterValue>) -> Result<()> {
+        self.set_all([(name, value.into())])
+    }
+
+    /// Change several parameters at once, all of them or none
+    ///
+    /// Every value is checked as by [`set`](Self::set) before any is
+    /// applied, so the first rejection leaves all parameters as they were.
+    /// Callbacks run once every value is in place, in the given order.
+    pub fn set_all<'a>(
+        &self,
+        values: impl IntoIterator<Item = (&'a str, ParameterValue)>,
+    ) -> Result<()> {
+        let values: Vec<_> = values.into_iter().collect();
+
+        let callbacks = {
+            let mut parameters = self.store.parameters.write();
+            for (name, value) in &values {
+                self.check(&parameters, name, value)?;
+            }
+            let mut callbacks = Vec::new();
+            for (name, value) in &values {
+                match parameters.get_mut(*name) {
+                    Some(parameter) => {
+                        parameter.value = value.clone();
+                        callbacks.extend(
+                            parameter
+                                .callbacks
+                                .iter()
+                                .map(|callback| (callback.clone(), value)),
+                        );
+                    }
+                    None => {
+                        parameters.insert(
+                            name.to_string(),
+                            Parameter {
+                                value: value.clone(),
+                                descriptor: ParameterDescriptor::default(),
+                                callbacks: Vec::new(),
+                            },
+                        );
+                    }
+                }
+                debug!("Parameter {}.{} set to {:?}", self.store.node, name, value);
+            }
+            callbacks
+        };
+
+        // Callbacks may read or set parameters, so run them without the lock
+        for (callback, value) in callbacks {
+            callback(value);
+        }
+        Ok(())
+    }
+
+    /// Check that `value` may be assigned to `name` by [`set`](Self::set),
+    /// without assigning it
+    pub fn validate(&self, name: &str, value: &ParameterValue) -> Result<()> {
+        self.check(&self.store.parameters.read(), name, value)
+    }
+
+    /// Why `value` may not be assigned to `name`, if it may not
+    fn check(
+        &self,
+        parameters: &BTreeMap<String, Parameter>,
+        name: &str,
+        value: &ParameterValue,
+    ) -> Result<()> {
+        let Some(parameter) = parameters.get(name) else {
+            if self.allows_undeclared() {
+                return Ok(());
+            }
+            return Err(self.store.error(name, "not declared"));
+        };
+        if parameter.descriptor.read_only {
+            return Err(self.store.error(name, "is read-only"));
+        }
+        if !parameter.value.same_type(value) {
+            return Err(self.store.error(
+                name,
+                format!(
+                    "expected a {}, got a {}",
+                    parameter.value.type_name(),
+                    value.type_name()
+                ),
+            ));
+        }
+        parameter
+            .descriptor
+            .validate(value)
+            .map_err(|reason| self.store.error(name, reason))
+    }
+
+    /// Call `callback` with the new value every time `name` changes
+    pub fn on_change<F>(&self, name: &str, callback: F) -> Result<()>
+    where
+        F: Fn(&ParameterValue) + Send + Sync + 'static,
+    {
+        let mut parameters = self.store.parameters.write();
+        let parameter = parameters
+            .get_mut(name)
+            .ok_or_else(|| self.store.error(name, "not declared"))?;
+        parameter.callbacks.push(Arc::new(callback));
+        Ok(())
+    }
+
+    /// All declared parameters, sorted by name
+    pub fn list(&self) -> Vec<ParameterInfo> {
+        self.store.infos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+
+    #[test]
+    fn test_typed_get_set() {
+        let params = ParameterServer::new("test_params_typed");
+        params
+            .declare("enabled", true, ParameterDescriptor::default())
+            .unwrap();
+        params
+            .declare("frame", "base_link", ParameterDescriptor::default())
+            .unwrap();
+        params
+            .declare("weights", vec![0.5, 0.5], ParameterDescriptor::default())
+            .unwrap();
+
+        assert!(params.get::<bool>("enabled").unwrap());
+        params.set("frame", "odom").unwrap();
+        assert_eq!(params.get::<String>("frame").unwrap(), "odom");
+        assert_eq!(params.get::<Vec<f64>>("weights").unwrap(), vec![0.5, 0.5]);
+
+        assert!(params.get::<i64>("enabled").is_err());
+        assert!(matches!(
+            params.set("enabled", 1i64),
+            Err(Error::InvalidParameter { .. })
+        ));
+        assert!(params.declare("enabled", false, ParameterDescriptor::default()).is_err());
+    }
+
+    #[test]
+    fn test_range_and_read_only() {
+        let params = ParameterServer::new("test_params_range");
+        let gain = ParameterDescriptor::new("Proportional gain").with_range(0.0, 10.0);
+        params.declare("kp", 1.0, gain.clone()).unwrap();
+        params
+            .declare("limits", vec![1.0, 2.0], gain.clone())
+            .unwrap();
+        params
+            .declare("rate_hz", 50i64, ParameterDescriptor::default().read_only())
+            .unwrap();
+
+        params.set("kp", 10.0).unwrap();
+        assert!(params.set("kp", 10.5).is_err());
+        assert!(params.set("kp", f64::NAN).is_err());
+        assert!(params.set("limits", vec![1.0, -1.0]).is_err());
+        assert_eq!(params.get::<f64>("kp").unwrap(), 10.0);
+        assert!(params.set("rate_hz", 100i64).is_err());
+        assert!(params.declare("ki", 20.0, gain).is_err());
+    }
+
+    #[test]
+    fn test_undeclared_parameters() {
+        let params = ParameterServer::new("test_params_dynamic");
+        assert!(params.set("max_speed", 1.5).is_err());
+        assert!(!params.has("max_speed"));
+
+        params.set_allow_undeclared(true);
+        params.set("max_speed", 1.5).unwrap();
+        assert_eq!(params.get::<f64>("max_speed").unwrap(), 1.5);
+    }
+
+    #[test]
+    fn test_on_change_updates_control_loop() {
+        let params = ParameterServer::new("test_params_callback");
+        params
+            .declare("kp", 1.0, ParameterDescriptor::new("Proportional gain"))
+            .unwrap();
+
+        // The control loop reads its gain from an atomic on every cycle
+        let gain = Arc::new(AtomicU64::new(1.0f64.to_bits()));
+        let loop_gain = gain.clone();
+        params
+            .on_change("kp", move |value| {
+                if let ParameterValue::Double(kp) = value {
+                    loop_gain.store(kp.to_bits(), Ordering::Release);
+                }
+            })
+            .unwrap();
+
+        params.set("kp", 2.5).unwrap();
+        assert_eq!(f64::from_bits(gain.load(Ordering::Acquire)), 2.5);
+
+        // Rejected changes do not reach the callback
+        let _ = params.set("kp", "fast");
+        assert_eq!(f64::from_bits(gain.load(Ordering::Acquire)), 2.5);
+        assert!(params.on_change("ki", |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_set_all_is_atomic() {
+        let params = ParameterServer::new("test_params_set_all");
+        let gain = ParameterDescriptor::new("Gain").with_range(0.0, 10.0);
+        params.declare("kp", 1.0, gain.clone()).unwrap();
+        params.declare("ki", 0.1, gain).unwrap();
+        let changes = Arc::new(AtomicU64::new(0));
+        let counted = changes.clone();
+        params
+            .on_change("kp", move |_| {
+                counted.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap();
+
+        let rejected = params.set_all([
+            ("kp", ParameterValue::Double(2.0)),
+            ("ki", ParameterValue::Double(20.0)),
+        ]);
+        assert!(matches!(
+            rejected,
+            Err(Error::InvalidParameter { name, .. }) if name == "test_params_set_all.ki"
+        ));
+        assert_eq!(params.get::<f64>("kp").unwrap(), 1.0);
+        assert_eq!(changes.load(Ordering::Relaxed), 0);
+
+        params
+            .set_all([
+                ("kp", ParameterValue::Double(2.0)),
+                ("ki", ParameterValue::Double(0.5)),
+            ])
+            .unwrap();
+        assert_eq!(params.get::<f64>("kp").unwrap(), 2.0);
+        assert_eq!(params.get::<f64>("ki").unwrap(), 0.5);
+        assert_eq!(changes.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_value_json() {
+        let value = ParameterValue::Double(0.5);
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"type":"double","value":0.5}"#
+        );
+    }
+}
@@ -0,0 +1,426 @@
+//! Pre-allocated buffers for serialized messages
+//!
+//! Serializing into a fresh `Vec` costs a heap allocation for every publish.
+//! A [`BufferPool`] allocates its buffers up front, in size classes, and
+//! takes each one back when the last sample referencing it is dropped, so a
+//! publisher with a pool stops allocating once its buffers have been around
+//! the pool once.
+
+use crate::channel::mpsc::Ring;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Marks a buffer allocated outside the pool
+const UNPOOLED: usize = usize::MAX;
+
+/// Buffers of one size in a [`BufferPoolConfig`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeClass {
+    /// Capacity of each buffer in bytes
+    pub size: usize,
+    /// Number of buffers
+    pub count: usize,
+}
+
+/// What [`BufferPool::acquire`] does when no pooled buffer fits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ExhaustedPolicy {
+    /// Allocate a buffer outside the pool, warning the first time
+    #[default]
+    Allocate,
+    /// Fail with [`Error::PoolExhausted`]
+    Fail,
+}
+
+/// Configuration of a [`BufferPool`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferPoolConfig {
+    pub classes: Vec<SizeClass>,
+    pub exhausted: ExhaustedPolicy,
+}
+
+impl Default for BufferPoolConfig {
+    fn default() -> Self {
+        Self {
+            classes: vec![
+                SizeClass {
+                    size: 1024,
+                    count: 64,
+                },
+                SizeClass {
+                    size: 16 * 1024,
+                    count: 16,
+                },
+                SizeClass {
+                    size: 256 * 1024,
+                    count: 4,
+                },
+            ],
+            exhausted: ExhaustedPolicy::default(),
+        }
+    }
+}
+
+/// Counters of a [`BufferPool`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStats {
+    /// Calls to [`BufferPool::acquire`]
+    pub acquired: u64,
+    /// Acquisitions served from the pool
+    pub hits: u64,
+    /// Acquisitions served by allocating outside the pool
+    pub allocated: u64,
+    /// Acquisitions that failed under [`ExhaustedPolicy::Fail`]
+    pub failed: u64,
+    /// Buffers currently handed out
+    pub in_use: usize,
+    /// Most buffers handed out at once
+    pub high_water: usize,
+}
+
+impl PoolStats {
+    /// Fraction of acquisitions served from the pool, 1.0 before any
+    pub fn hit_rate(&self) -> f64 {
+        if self.acquired == 0 {
+            return 1.0;
+        }
+        self.hits as f64 / self.acquired as f64
+    }
+}
+
+/// Pool of reusable serialization buffers
+///
+/// Cloning gives another handle to the same pool.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    /// Sorted by size
+    classes: Box<[Class]>,
+    exhausted: ExhaustedPolicy,
+    acquired: AtomicU64,
+    hits: AtomicU64,
+    allocated: AtomicU64,
+    failed: AtomicU64,
+    in_use: AtomicUsize,
+    high_water: AtomicUsize,
+    warned: AtomicBool,
+}
+
+struct Class {
+    size: usize,
+    free: Ring<Arc<Slot>>,
+}
+
+struct Slot {
+    data: UnsafeCell<Vec<u8>>,
+    /// Handles to the buffer; it goes back to the pool when this drops to 0
+    users: AtomicUsize,
+    /// Index into the pool's classes, or [`UNPOOLED`]
+    class: usize,
+}
+
+// SAFETY: `data` is written only through the single `PooledBuffer` of an
+// acquisition, and only read through `SharedBuffer`s after that
+unsafe impl Send for Slot {}
+unsafe impl Sync for Slot {}
+
+impl BufferPool {
+    /// Allocate every buffer of `config`
+    ///
+    /// Fails with [`Error::Configuration`] if a class has no buffers or
+    /// zero-sized ones.
+    pub fn new(config: BufferPoolConfig) -> Result<Self> {
+        let mut sizes = config.classes;
+        if let Some(class) = sizes.iter().find(|c| c.size == 0 || c.count == 0) {
+            return Err(Error::Configuration(format!(
+                "buffer pool class needs a non-zero size and count, got {class:?}"
+            )));
+        }
+        sizes.sort_by_key(|c| c.size);
+        let classes = sizes
+            .iter()
+            .enumerate()
+            .map(|(index, c)| {
+                let free = Ring::new(c.count, false);
+                for _ in 0..c.count {
+                    let slot = Arc::new(Slot {
+                        data: UnsafeCell::new(Vec::with_capacity(c.size)),
+                        users: AtomicUsize::new(0),
+                        class: index,
+                    });
+                    let _ = free.push(slot);
+                }
+                Class { size: c.size, free }
+            })
+            .collect();
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                classes,
+                exhausted: config.exhausted,
+                acquired: AtomicU64::new(0),
+                hits: AtomicU64::new(0),
+                allocated: AtomicU64::new(0),
+                failed: AtomicU64::new(0),
+                in_use: AtomicUsize::new(0),
+                high_water: AtomicUsize::new(0),
+                warned: AtomicBool::new(false),
+            }),
+        })
+    }
+
+    /// Take an empty buffer with room for at least `min_size` bytes
+    ///
+    /// Takes from the smallest class that fits and has a free buffer. When
+    /// none does, the [`ExhaustedPolicy`] decides. The buffer returns to the
+    /// pool when it, or the last [`SharedBuffer`] made from it, is dropped.
+    pub fn acquire(&self, min_size: usize) -> Result<PooledBuffer> {
+        let inner = &*self.inner;
+        inner.acquired.fetch_add(1, Ordering::Relaxed);
+        let pooled = inner
+            .classes
+            .iter()
+            .filter(|class| class.size >= min_size)
+            .find_map(|class| class.free.pop());
+        let slot = match pooled {
+            Some(slot) => {
+                inner.hits.fetch_add(1, Ordering::Relaxed);
+                slot
+            }
+            None if inner.exhausted == ExhaustedPolicy::Fail => {
+                inner.failed.fetch_add(1, Ordering::Relaxed);
+                return Err(Error::PoolExhausted(min_size));
+            }
+            None => {
+                inner.allocated.fetch_add(1, Ordering::Relaxed);
+                if !inner.warned.swap(true, Ordering::Relaxed) {
+                    warn!("Buffer pool has no free buffer of {min_size} bytes, allocating");
+                }
+                Arc::new(Slot {
+                    data: UnsafeCell::new(Vec::with_capacity(min_size)),
+                    users: AtomicUsize::new(0),
+                    class: UNPOOLED,
+                })
+            }
+        };
+        slot.users.store(1, Ordering::Relaxed);
+        let in_use = inner.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+        inner.high_water.fetch_max(in_use, Ordering::Relaxed);
+        Ok(PooledBuffer(Handle {
+            slot: Some(slot),
+            pool: self.inner.clone(),
+        }))
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let inner = &*self.inner;
+        PoolStats {
+            acquired: inner.acquired.load(Ordering::Relaxed),
+            hits: inner.hits.load(Ordering::Relaxed),
+            allocated: inner.allocated.load(Ordering::Relaxed),
+            failed: inner.failed.load(Ordering::Relaxed),
+            in_use: inner.in_use.load(Ordering::Relaxed),
+            high_water: inner.high_water.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(BufferPoolConfig::default()).expect("Default buffer pool config is valid")
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// One user's hold on a slot
+struct Handle {
+    /// Taken when the handle is dropped
+    slot: Option<Arc<Slot>>,
+    pool: Arc<PoolInner>,
+}
+
+impl Handle {
+    fn slot(&self) -> &Slot {
+        self.slot.as_ref().expect("slot is only taken on drop")
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        let Some(slot) = self.slot.take() else {
+            return;
+        };
+        if slot.users.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        self.pool.in_use.fetch_sub(1, Ordering::Relaxed);
+        if let Some(class) = self.pool.classes.get(slot.class) {
+            // SAFETY: this was the last user, so nothing else reads the data
+            unsafe { (*slot.data.get()).clear() };
+            let _ = class.free.push(slot);
+        }
+    }
+}
+
+/// A buffer taken from a [`BufferPool`], for writing
+pub struct PooledBuffer(Handle);
+
+impl PooledBuffer {
+    /// Make the buffer read-only so it can be shared, e.g. by all
+    /// subscribers of a sample
+    pub fn share(self) -> SharedBuffer {
+        SharedBuffer(self.0)
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        // SAFETY: this is the slot's only user
+        unsafe { &*self.0.slot().data.get() }
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        // SAFETY: this is the slot's only user
+        unsafe { &mut *self.0.slot().data.get() }
+    }
+}
+
+impl fmt::Debug for PooledBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledBuffer")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A read-only pooled buffer; cloning shares it without copying
+pub struct SharedBuffer(Handle);
+
+impl Clone for SharedBuffer {
+    fn clone(&self) -> Self {
+        let slot = self.0.slot.clone();
+        if let Some(slot) = &slot {
+            slot.users.fetch_add(1, Ordering::Relaxed);
+        }
+        SharedBuffer(Handle {
+            slot,
+            pool: self.0.pool.clone(),
+        })
+    }
+}
+
+impl Deref for SharedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: shared buffers are never written
+        unsafe { (*self.0.slot().data.get()).as_slice() }
+    }
+}
+
+impl AsRef<[u8]> for SharedBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for SharedBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedBuffer")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(exhausted: ExhaustedPolicy) -> BufferPool {
+        BufferPool::new(BufferPoolConfig {
+            classes: vec![
+                SizeClass {
+                    size: 1024,
+                    count: 2,
+                },
+                SizeClass { size: 64, count: 1 },
+            ],
+            exhausted,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_buffers_return_on_drop() {
+        let pool = pool(ExhaustedPolicy::Fail);
+        let mut small = pool.acquire(10).unwrap();
+        assert!(small.capacity() >= 64);
+        small.extend_from_slice(b"hello");
+
+        let shared = small.share();
+        let copy = shared.clone();
+        assert_eq!(&*copy, b"hello");
+        drop(shared);
+        assert_eq!(pool.stats().in_use, 1);
+        drop(copy);
+        assert_eq!(pool.stats().in_use, 0);
+
+        // Back in the pool, and empty
+        let again = pool.acquire(10).unwrap();
+        assert!(again.is_empty());
+        let stats = pool.stats();
+        assert_eq!((stats.acquired, stats.hits), (2, 2));
+        assert_eq!(stats.high_water, 1);
+    }
+
+    #[test]
+    fn test_exhausted_policies() {
+        let failing = pool(ExhaustedPolicy::Fail);
+        let held: Vec<_> = (0..3).map(|_| failing.acquire(10).unwrap()).collect();
+        assert!(matches!(failing.acquire(10), Err(Error::PoolExhausted(10))));
+        assert!(failing.acquire(4096).is_err());
+        assert_eq!(failing.stats().failed, 2);
+        assert_eq!(failing.stats().high_water, 3);
+        drop(held);
+
+        let allocating = pool(ExhaustedPolicy::Allocate);
+        let big = allocating.acquire(4096).unwrap();
+        assert!(big.capacity() >= 4096);
+        let stats = allocating.stats();
+        assert_eq!((stats.hits, stats.allocated), (0, 1));
+        assert_eq!(stats.hit_rate(), 0.0);
+        drop(big);
+        assert_eq!(allocating.stats().in_use, 0);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = BufferPoolConfig {
+            classes: vec![SizeClass { size: 0, count: 4 }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            BufferPool::new(config),
+            Err(Error::Configuration(_))
+        ));
+    }
+}
@@ -1,17 +1,114 @@
 //! Publisher implementation
 
-use crate::error::Result;
-use crate::message::Message;
+use crate::compression::{Compression, CompressionPolicy};
+use crate::error::{Error, Result};
+use crate::message::{Message, RawMessage, SharedRawMessage, Stamped};
+use crate::name::TopicName;
+use crate::pool::BufferPool;
+use crate::qos::{QosEvent, QosProfile};
 use crate::serialization::{Format, Serializer};
+use crate::tcp::{ConnectionWatcher, TcpUplink};
+use crate::time::Clock;
+use crate::topic::{Direct, PublisherGuid, Sample, Stamp, Topic, TopicBus, TopicType, Writer};
+use crate::trace::{self, EventKind};
 use parking_lot::RwLock;
+use std::any::Any;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{field, info_span, trace, Span};
 
 /// Publisher for sending messages
+///
+/// Every message is delivered to all subscribers registered on the same
+/// topic at the time of publishing.
 pub struct Publisher<T: Message> {
     topic: String,
     serializer: Serializer,
+    type_name: Arc<str>,
+    link: Arc<Topic>,
+    writer: Arc<Writer>,
+    clock: Clock,
+    uplink: Option<Arc<TcpUplink>>,
+    compression: CompressionPolicy,
+    liveliness: Option<JoinHandle<()>>,
+    /// Serialized size of the last message, to pick a pooled buffer
+    last_len: AtomicUsize,
     _phantom: std::marker::PhantomData<T>,
     stats: Arc<RwLock<PublisherStats>>,
+    /// Parent of the publisher's log events, with its `node` and `topic`
+    span: Span,
+}
+
+/// Payloads shorter than this are not compressed unless configured
+/// otherwise
+const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Settings of a publisher created with
+/// [`Node::create_publisher_with_options`](crate::Node::create_publisher_with_options)
+///
+/// ```
+/// # use agentic_robotics_core::{PublisherOptions, QosProfile};
+/// # use agentic_robotics_core::serialization::Format;
+/// let options = PublisherOptions::new()
+///     .format(Format::MessagePack)
+///     .qos(QosProfile::default());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PublisherOptions {
+    pub(crate) format: Format,
+    pub(crate) qos: QosProfile,
+    compression: Compression,
+    compression_threshold: usize,
+}
+
+impl PublisherOptions {
+    /// CDR, the default QoS and no compression
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serialize messages with `format`
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Offer `qos`, see [`Publisher::new_with_qos`]
+    pub fn qos(mut self, qos: QosProfile) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Compress messages sent to other processes over the UDP and TCP
+    /// transports
+    ///
+    /// Remote subscribers announce which codecs they decompress; those
+    /// built without the codec's feature get the message uncompressed, as
+    /// do in-process subscribers.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Send serialized messages shorter than `bytes` uncompressed, 1 KiB by
+    /// default
+    pub fn compression_threshold(mut self, bytes: usize) -> Self {
+        self.compression_threshold = bytes;
+        self
+    }
+}
+
+impl Default for PublisherOptions {
+    fn default() -> Self {
+        Self {
+            format: Format::Cdr,
+            qos: QosProfile::default(),
+            compression: Compression::None,
+            compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -22,57 +119,475 @@ struct PublisherStats {
 
 impl<T: Message> Publisher<T> {
     /// Create a new publisher
-    pub fn new(topic: impl Into<String>) -> Self {
+    ///
+    /// Fails with [`Error::InvalidTopicName`](crate::Error::InvalidTopicName)
+    /// if `topic` is not a valid [`TopicName`], or with
+    /// [`Error::TopicTypeMismatch`](crate::Error::TopicTypeMismatch) if the
+    /// topic already carries a different message type.
+    pub fn new(topic: impl Into<String>) -> Result<Self> {
         Self::with_format(topic, Format::Cdr)
     }
 
     /// Create a new publisher with specific format
-    pub fn with_format(topic: impl Into<String>, format: Format) -> Self {
-        let topic = topic.into();
+    pub fn with_format(topic: impl Into<String>, format: Format) -> Result<Self> {
+        Self::create(topic.into(), format, QosProfile::default(), None)
+    }
 
-        Self {
+    /// Create a new publisher with a QoS profile
+    ///
+    /// A [`TransientLocal`](crate::qos::Durability::TransientLocal) publisher
+    /// keeps its last `history_depth` messages for subscribers that join
+    /// later. With a `liveliness_lease`, subscribers are told when the
+    /// publisher neither publishes nor calls
+    /// [`assert_liveliness`](Self::assert_liveliness) for a whole lease; such
+    /// a publisher must be created within a Tokio runtime. Fails with
+    /// [`Error::QosIncompatible`](crate::Error::QosIncompatible) if a
+    /// subscriber on the topic requests guarantees this profile does not
+    /// offer.
+    pub fn new_with_qos(topic: impl Into<String>, qos: QosProfile) -> Result<Self> {
+        Self::create(topic.into(), Format::Cdr, qos, None)
+    }
+
+    /// Create a new publisher with a format, QoS profile and compression
+    pub fn new_with_options(topic: impl Into<String>, options: PublisherOptions) -> Result<Self> {
+        Ok(Self::create(topic.into(), options.format, options.qos, None)?.with_options(options))
+    }
+
+    /// Create a publisher, recording the node that owns it
+    pub(crate) fn create(
+        topic: String,
+        format: Format,
+        qos: QosProfile,
+        node: Option<String>,
+    ) -> Result<Self> {
+        TopicName::new(topic.as_str())?;
+        let span = info_span!("publisher", node = field::Empty, topic = %topic);
+        if let Some(node) = &node {
+            span.record("node", node.as_str());
+        }
+        let writer = Arc::new(Writer::new(qos, node));
+        let link =
+            TopicBus::global().attach_publisher(&topic, TopicType::of::<T>(), writer.clone())?;
+        let liveliness = qos.liveliness_lease.map(|lease| {
+            tokio::spawn(monitor_liveliness(link.clone(), writer.clone(), lease))
+        });
+
+        Ok(Self {
             topic,
             serializer: Serializer::new(format),
+            type_name: Arc::from(T::type_name()),
+            link,
+            writer,
+            clock: Clock::default(),
+            uplink: None,
+            compression: CompressionPolicy::default(),
+            liveliness,
+            last_len: AtomicUsize::new(0),
             _phantom: std::marker::PhantomData,
             stats: Arc::new(RwLock::new(PublisherStats::default())),
-        }
+            span,
+        })
+    }
+
+    /// Latch the last published message
+    ///
+    /// Every subscriber that joins later immediately receives the most
+    /// recent message, like a map published once at startup. The latch
+    /// holds the serialized bytes, so it does not keep the published value
+    /// alive, and each publish replaces it. Disabling the latch clears it.
+    pub fn latch(self, latch: bool) -> Self {
+        self.writer.set_latch(latch);
+        self
+    }
+
+    /// Stamp messages with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Serialize messages into buffers from `pool`
+    ///
+    /// Each message takes a buffer big enough for the previous one, which
+    /// goes back to the pool once every subscriber has dropped the message.
+    /// With enough free buffers, publishing then stops allocating: a buffer
+    /// only grows if a message outgrows it.
+    pub fn with_pool(mut self, pool: BufferPool) -> Self {
+        self.serializer = Serializer::new(self.serializer.format()).with_pool(pool);
+        self
+    }
+
+    /// The pool set with [`with_pool`](Self::with_pool), if any
+    pub fn pool(&self) -> Option<&BufferPool> {
+        self.serializer.pool()
+    }
+
+    /// Take the compression settings of `options`
+    pub(crate) fn with_options(mut self, options: PublisherOptions) -> Self {
+        self.compression = CompressionPolicy {
+            compression: options.compression,
+            threshold: options.compression_threshold,
+        };
+        self
+    }
+
+    /// Send to remote peers over `uplink`
+    pub(crate) fn with_uplink(mut self, uplink: Arc<TcpUplink>) -> Self {
+        self.uplink = Some(uplink);
+        self
     }
 
     /// Publish a message
+    ///
+    /// Subscribers can read the publish time and sequence number through
+    /// [`Subscriber::recv_with_info`](crate::Subscriber::recv_with_info).
+    ///
+    /// [`DirectSubscriber`](crate::DirectSubscriber)s get the message first,
+    /// on this call stack and without serialization. Publishing to a topic
+    /// without other subscribers is a no-op apart from counting the message;
+    /// nothing is serialized unless the publisher is transient-local or
+    /// latched. If a subscriber uses
+    /// [`OverflowPolicy::Block`](crate::subscriber::OverflowPolicy::Block)
+    /// this waits until its queue has room. Fails with [`Error::Closed`] once
+    /// the publisher's node has shut down.
     pub async fn publish(&self, msg: &T) -> Result<()> {
-        let bytes = self.serializer.serialize(msg)?;
+        self.check_open()?;
+        self.writer.assert_liveliness();
+        let seq = self.next_seq();
+        let _span = trace::span(EventKind::Publish, self.link.trace_label()).arg(seq);
+        trace!(parent: &self.span, seq, "Publishing");
+        self.dispatch_direct(msg);
+        if !self.is_heard() {
+            self.stats.write().messages_sent += 1;
+            return Ok(());
+        }
+        self.send(msg, self.stamp(seq)).await
+    }
+
+    /// Fill in the message header from the publisher's clock, then publish
+    ///
+    /// The header gets the same stamp and sequence number that
+    /// [`Subscriber::recv_with_info`](crate::Subscriber::recv_with_info)
+    /// reports.
+    pub async fn publish_stamped(&self, msg: &mut T) -> Result<()>
+    where
+        T: Stamped,
+    {
+        self.check_open()?;
+        self.writer.assert_liveliness();
+        let stamp = self.stamp(self.next_seq());
+        let _span = trace::span(EventKind::Publish, self.link.trace_label()).arg(stamp.seq);
+        trace!(parent: &self.span, seq = stamp.seq, "Publishing");
+        let header = msg.header_mut();
+        header.stamp_ns = stamp.time.as_nanos();
+        header.seq = stamp.seq;
+        self.dispatch_direct(msg);
+        if !self.is_heard() {
+            self.stats.write().messages_sent += 1;
+            return Ok(());
+        }
+        self.send(msg, stamp).await
+    }
+
+    async fn send(&self, msg: &T, stamp: Stamp) -> Result<()> {
+        let sample = match (msg as &dyn Any).downcast_ref::<RawMessage>() {
+            Some(raw) => Sample::new(
+                raw.data.clone(),
+                raw.format,
+                Arc::from(raw.type_name.as_str()),
+            ),
+            None => {
+                let started = Instant::now();
+                let sample = match self.serializer.pool() {
+                    Some(pool) => {
+                        let mut buf = pool.acquire(self.last_len.load(Ordering::Relaxed))?;
+                        self.serializer.serialize_into(msg, &mut buf)?;
+                        self.last_len.store(buf.len(), Ordering::Relaxed);
+                        let format = self.serializer.format();
+                        Sample::pooled(buf.share(), format, self.type_name.clone())
+                    }
+                    None => Sample::new(
+                        self.serializer.serialize(msg)?,
+                        self.serializer.format(),
+                        self.type_name.clone(),
+                    ),
+                };
+                self.link.observe_serialization(started.elapsed());
+                sample
+            }
+        };
+        self.deliver(sample.with_stamp(stamp)).await
+    }
+
+    /// Hand a stamped sample to the topic's queues and transports
+    async fn deliver(&self, sample: Sample) -> Result<()> {
+        let len = sample.bytes()?.len() as u64;
+        let sample = sample.with_compression(self.compression);
+        self.link.deliver_queued(&self.writer, sample).await?;
 
         // Update stats
         {
             let mut stats = self.stats.write();
             stats.messages_sent += 1;
-            stats.bytes_sent += bytes.len() as u64;
+            stats.bytes_sent += len;
         }
 
-        // In real implementation, this would send via Zenoh
         Ok(())
     }
 
+    /// Publish a shared message without serializing it
+    ///
+    /// In-process subscribers using
+    /// [`Subscriber::recv_arc`](crate::Subscriber::recv_arc) receive this
+    /// very `Arc`. The message is only serialized for subscribers that need
+    /// bytes, such as `Subscriber::<RawMessage>` or plain `recv()`. Zero-copy
+    /// messages are not counted in the bytes sent.
+    pub async fn publish_arc(&self, msg: Arc<T>) -> Result<()> {
+        if (&*msg as &dyn Any).is::<RawMessage>() {
+            return self.publish(&msg).await;
+        }
+        self.check_open()?;
+        self.writer.assert_liveliness();
+        let seq = self.next_seq();
+        let _span = trace::span(EventKind::Publish, self.link.trace_label()).arg(seq);
+        trace!(parent: &self.span, seq, "Publishing");
+        self.dispatch_direct(&msg);
+        if self.is_heard() {
+            let sample = Sample::shared(msg, self.serializer.format(), self.type_name.clone())
+                .with_stamp(self.stamp(seq))
+                .with_compression(self.compression);
+            self.link.deliver_queued(&self.writer, sample).await?;
+        }
+        self.stats.write().messages_sent += 1;
+        Ok(())
+    }
+
+    /// Borrow a default-initialized message to fill in and publish without
+    /// copying it
+    pub fn borrow_loaned(&self) -> LoanedMessage<'_, T>
+    where
+        T: Default,
+    {
+        LoanedMessage {
+            publisher: self,
+            msg: Arc::new(T::default()),
+        }
+    }
+
+    /// Renew the liveliness lease without publishing
+    ///
+    /// Publishing renews it too. A no-op for publishers without a
+    /// `liveliness_lease` in their QoS.
+    pub fn assert_liveliness(&self) {
+        self.writer.assert_liveliness();
+    }
+
     /// Get topic name
     pub fn topic(&self) -> &str {
         &self.topic
     }
 
+    /// Id carried in every message this publisher sends
+    pub fn guid(&self) -> PublisherGuid {
+        self.writer.guid()
+    }
+
+    /// QoS profile offered by this publisher
+    pub fn qos(&self) -> QosProfile {
+        self.writer.qos()
+    }
+
     /// Get statistics
     pub fn stats(&self) -> (u64, u64) {
         let stats = self.stats.read();
         (stats.messages_sent, stats.bytes_sent)
     }
+
+    /// Number of remote peers currently connected over TCP
+    ///
+    /// Always 0 unless the publisher was created by a node using
+    /// [`TransportConfig::Tcp`](crate::TransportConfig::Tcp).
+    pub fn connection_count(&self) -> usize {
+        self.uplink.as_ref().map_or(0, |uplink| uplink.connection_count())
+    }
+
+    /// Stream of TCP connects and disconnects, e.g. to alert on a lost
+    /// uplink
+    ///
+    /// Yields `None` right away for publishers without TCP peers.
+    pub fn connection_events(&self) -> ConnectionWatcher {
+        match &self.uplink {
+            Some(uplink) => uplink.watch(),
+            None => ConnectionWatcher::new(None),
+        }
+    }
+
+    /// Messages the TCP send buffers discarded under their
+    /// [`OverflowPolicy`](crate::OverflowPolicy)
+    pub fn uplink_dropped_count(&self) -> u64 {
+        self.uplink.as_ref().map_or(0, |uplink| uplink.dropped())
+    }
+
+    /// Wait until the transports to other processes have taken every
+    /// message published on this topic
+    ///
+    /// Local subscribers are not waited for. A transport that cannot send,
+    /// such as a TCP uplink without a connected peer, keeps this waiting;
+    /// bound it with a timeout.
+    pub async fn flush(&self) {
+        self.link.flush().await;
+    }
+
+    pub(crate) fn endpoint(&self) -> (Arc<Topic>, Arc<Writer>) {
+        (self.link.clone(), self.writer.clone())
+    }
+
+    /// Take the next sequence number, counting the message on its topic
+    fn next_seq(&self) -> u64 {
+        self.link.count_published();
+        self.writer.next_seq()
+    }
+
+    fn stamp(&self, seq: u64) -> Stamp {
+        Stamp {
+            publisher: self.writer.guid(),
+            seq,
+            time: self.clock.now(),
+            clock: self.clock.clock_type(),
+        }
+    }
+
+    /// Hand `msg` to the direct subscribers before anything is serialized
+    fn dispatch_direct(&self, msg: &T) {
+        if !self.link.has_direct_subscribers() {
+            return;
+        }
+        match (msg as &dyn Any).downcast_ref::<RawMessage>() {
+            Some(raw) => {
+                let sample = Sample::new(
+                    raw.data.clone(),
+                    raw.format,
+                    Arc::from(raw.type_name.as_str()),
+                );
+                self.link.dispatch_direct(Direct::Sample(&sample));
+            }
+            None => self.link.dispatch_direct(Direct::Message(msg)),
+        }
+    }
+
+    fn check_open(&self) -> Result<()> {
+        if self.writer.is_closed() {
+            return Err(Error::Closed(format!(
+                "publisher on '{}' was shut down",
+                self.topic
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether a published message reaches anyone, now or later
+    fn is_heard(&self) -> bool {
+        self.link.has_subscribers() || self.writer.keeps_history() || self.writer.is_latched()
+    }
+}
+
+impl Publisher<RawMessage> {
+    /// Publish serialized bytes without copying them
+    ///
+    /// Like [`publish`](Self::publish) with a [`RawMessage`], except that
+    /// the bytes are shared: in-process subscribers receiving with
+    /// [`Subscriber::recv_shared`](crate::Subscriber::recv_shared) get
+    /// these very bytes, and transports send them from where they are.
+    pub async fn publish_shared(&self, msg: &SharedRawMessage) -> Result<()> {
+        self.check_open()?;
+        self.writer.assert_liveliness();
+        let seq = self.next_seq();
+        let _span = trace::span(EventKind::Publish, self.link.trace_label()).arg(seq);
+        trace!(parent: &self.span, seq, "Publishing");
+        let sample = Sample::external(
+            msg.data.clone(),
+            msg.format,
+            Arc::from(msg.type_name.as_str()),
+        );
+        if self.link.has_direct_subscribers() {
+            self.link.dispatch_direct(Direct::Sample(&sample));
+        }
+        if !self.is_heard() {
+            self.stats.write().messages_sent += 1;
+            return Ok(());
+        }
+        self.deliver(sample.with_stamp(self.stamp(seq))).await
+    }
+}
+
+/// A message owned by a publisher until it is published
+///
+/// Dereferences to the message so it can be filled in place; `publish` then
+/// hands it to subscribers through [`Publisher::publish_arc`].
+pub struct LoanedMessage<'a, T: Message> {
+    publisher: &'a Publisher<T>,
+    msg: Arc<T>,
+}
+
+impl<T: Message> LoanedMessage<'_, T> {
+    /// Publish the loaned message
+    pub async fn publish(self) -> Result<()> {
+        self.publisher.publish_arc(self.msg).await
+    }
+}
+
+impl<T: Message> std::ops::Deref for LoanedMessage<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.msg
+    }
+}
+
+impl<T: Message> std::ops::DerefMut for LoanedMessage<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        Arc::get_mut(&mut self.msg).expect("loaned message is never shared before publishing")
+    }
+}
+
+impl<T: Message> Drop for Publisher<T> {
+    fn drop(&mut self) {
+        if let Some(liveliness) = self.liveliness.take() {
+            liveliness.abort();
+        }
+        TopicBus::global().detach_publisher(&self.link, &self.writer);
+    }
+}
+
+/// Tell the subscribers on `link` whenever `writer` lets its lease run out
+/// and when it comes back
+async fn monitor_liveliness(link: Arc<Topic>, writer: Arc<Writer>, lease: Duration) {
+    let mut alive = true;
+    loop {
+        let asserted = tokio::time::timeout(lease, writer.asserted()).await.is_ok();
+        if asserted == alive {
+            continue;
+        }
+        alive = asserted;
+        let publisher = writer.guid();
+        link.notify_subscribers(if alive {
+            QosEvent::LivelinessRegained { publisher }
+        } else {
+            QosEvent::LivelinessLost { publisher }
+        });
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::message::RobotState;
+    use crate::subscriber::Subscriber;
 
     #[tokio::test]
     async fn test_publisher() {
-        let publisher = Publisher::<RobotState>::new("robot/state");
+        let publisher = Publisher::<RobotState>::new("test/publisher/state").unwrap();
+        let _subscriber = Subscriber::<RobotState>::new("test/publisher/state").unwrap();
         let msg = RobotState::default();
 
         let result = publisher.publish(&msg).await;
@@ -82,4 +597,231 @@ mod tests {
         assert_eq!(count, 1);
         assert!(bytes > 0);
     }
+
+    #[tokio::test]
+    async fn test_publish_without_subscribers() {
+        let publisher = Publisher::<RobotState>::new("test/publisher/unheard").unwrap();
+        publisher.publish(&RobotState::default()).await.unwrap();
+
+        let (count, bytes) = publisher.stats();
+        assert_eq!(count, 1);
+        assert_eq!(bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_topic_metrics() {
+        use crate::metrics::{self, Registry};
+
+        let topic = "test/publisher/metrics";
+        let publisher = Publisher::<RobotState>::new(topic).unwrap();
+        let subscriber = Subscriber::<RobotState>::new(topic).unwrap();
+        publisher.publish(&RobotState::default()).await.unwrap();
+        publisher.publish(&RobotState::default()).await.unwrap();
+        subscriber.recv().await.unwrap();
+
+        let registry = Registry::global();
+        let labels = [("topic", topic)];
+        assert_eq!(registry.counter(metrics::MESSAGES_PUBLISHED, "", &labels).get(), 2);
+        let serialization = registry.histogram(metrics::SERIALIZATION_DURATION, "", &labels);
+        assert_eq!(serialization.count(), 2);
+        let latency = registry.histogram(metrics::MESSAGE_LATENCY, "", &labels);
+        assert_eq!(latency.count(), 1);
+        let text = registry.encode_prometheus();
+        assert!(text.contains(&format!("{}{{topic=\"{}\"}} 1\n", metrics::QUEUE_DEPTH, topic)));
+    }
+
+    #[tokio::test]
+    async fn test_pooled_buffers_are_reused() {
+        let pool = BufferPool::default();
+        let publisher = Publisher::<RobotState>::new("test/publisher/pooled")
+            .unwrap()
+            .with_pool(pool.clone());
+        let subscriber = Subscriber::<RobotState>::new("test/publisher/pooled").unwrap();
+
+        for i in 0..10 {
+            let msg = RobotState {
+                timestamp: i,
+                ..Default::default()
+            };
+            publisher.publish(&msg).await.unwrap();
+            assert_eq!(subscriber.recv().await.unwrap().timestamp, i);
+        }
+
+        let stats = pool.stats();
+        assert_eq!(stats.hits, 10);
+        assert_eq!(stats.in_use, 0);
+        assert_eq!(stats.high_water, 1);
+    }
+
+    #[test]
+    fn test_publisher_type_mismatch() {
+        use crate::message::PointCloud;
+
+        let _state = Publisher::<RobotState>::new("test/publisher/mismatch").unwrap();
+        let result = Publisher::<PointCloud>::new("test/publisher/mismatch");
+        assert!(matches!(result, Err(crate::Error::TopicTypeMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_raw_publisher_forwards_bytes() {
+        let subscriber = Subscriber::<RobotState>::new("test/publisher/raw").unwrap();
+        let publisher = Publisher::<RawMessage>::new("test/publisher/raw").unwrap();
+
+        let state = RobotState {
+            timestamp: 9,
+            ..Default::default()
+        };
+        let raw = RawMessage {
+            type_name: RobotState::type_name().to_string(),
+            format: Format::Json,
+            data: serde_json::to_vec(&state).unwrap(),
+        };
+        publisher.publish(&raw).await.unwrap();
+
+        assert_eq!(subscriber.recv().await.unwrap().timestamp, 9);
+    }
+
+    #[tokio::test]
+    async fn test_publish_arc_shares_message() {
+        let subscriber = Subscriber::<RobotState>::new("test/publisher/arc").unwrap();
+        let copy = Subscriber::<RobotState>::new("test/publisher/arc").unwrap();
+        let publisher = Publisher::<RobotState>::new("test/publisher/arc").unwrap();
+
+        let msg = Arc::new(RobotState {
+            timestamp: 3,
+            ..Default::default()
+        });
+        publisher.publish_arc(msg.clone()).await.unwrap();
+
+        assert!(Arc::ptr_eq(&subscriber.recv_arc().await.unwrap(), &msg));
+        assert_eq!(copy.recv().await.unwrap().timestamp, 3);
+        assert_eq!(publisher.stats(), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_loaned_message() {
+        let subscriber = Subscriber::<RobotState>::new("test/publisher/loan").unwrap();
+        let publisher = Publisher::<RobotState>::new("test/publisher/loan").unwrap();
+
+        let mut loan = publisher.borrow_loaned();
+        loan.position = [4.0, 5.0, 6.0];
+        loan.publish().await.unwrap();
+
+        let received = subscriber.try_recv_arc().unwrap().unwrap();
+        assert_eq!(received.position, [4.0, 5.0, 6.0]);
+    }
+
+    #[tokio::test]
+    async fn test_raw_subscriber_serializes_shared_message() {
+        let raw = Subscriber::<RawMessage>::new("test/publisher/arc_raw").unwrap();
+        let publisher = Publisher::<RobotState>::new("test/publisher/arc_raw").unwrap();
+
+        publisher.publish_arc(Arc::new(RobotState::default())).await.unwrap();
+
+        let message = raw.recv().await.unwrap();
+        assert_eq!(message.format, Format::Cdr);
+        assert!(message.decode::<RobotState>().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latched_map_reaches_late_subscriber() {
+        use crate::message::{Point3D, PointCloud};
+        use std::time::Duration;
+
+        let publisher = Publisher::<PointCloud>::new("test/publisher/latched_map")
+            .unwrap()
+            .latch(true);
+        let grid = |timestamp| PointCloud {
+            points: vec![Point3D { x: 1.0, y: 2.0, z: 0.0 }; 64],
+            intensities: vec![0.5; 64],
+            timestamp,
+        };
+        publisher.publish(&grid(1)).await.unwrap();
+        publisher.publish_arc(Arc::new(grid(2))).await.unwrap();
+
+        tokio::time::sleep(Duration::from_secs(5 * 60)).await;
+
+        let late = Subscriber::<PointCloud>::new("test/publisher/latched_map").unwrap();
+        let map = late.try_recv().unwrap().unwrap();
+        assert_eq!(map.timestamp, 2);
+        assert_eq!(map.points.len(), 64);
+        assert!(late.try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_topic_name() {
+        let result = Publisher::<RobotState>::new("test/publisher/bad name");
+        assert!(matches!(result, Err(crate::Error::InvalidTopicName { .. })));
+        assert_eq!(TopicBus::global().publisher_count("test/publisher/bad name"), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_liveliness_lost_when_publisher_hangs() {
+        use tokio::time::{sleep, timeout, Instant};
+
+        let lease = Duration::from_millis(100);
+        let qos = QosProfile {
+            liveliness_lease: Some(lease),
+            ..Default::default()
+        };
+        let publisher =
+            Publisher::<RobotState>::new_with_qos("test/publisher/liveliness", qos).unwrap();
+        let subscriber = Subscriber::<RobotState>::new("test/publisher/liveliness").unwrap();
+        let mut events = subscriber.qos_events();
+
+        // Alive while publishing or asserting liveliness within the lease
+        for _ in 0..5 {
+            publisher.publish(&RobotState::default()).await.unwrap();
+            sleep(lease * 3 / 4).await;
+            publisher.assert_liveliness();
+            sleep(lease * 3 / 4).await;
+        }
+        publisher.assert_liveliness();
+        let hung = Instant::now();
+
+        let lost = timeout(lease * 2, events.next()).await.unwrap();
+        assert_eq!(
+            lost,
+            Some(QosEvent::LivelinessLost {
+                publisher: publisher.guid()
+            })
+        );
+        assert!(hung.elapsed() <= lease);
+
+        publisher.publish(&RobotState::default()).await.unwrap();
+        let regained = timeout(lease, events.next()).await.unwrap();
+        assert_eq!(
+            regained,
+            Some(QosEvent::LivelinessRegained {
+                publisher: publisher.guid()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flush_waits_for_transports() {
+        use crate::topic::{SampleQueue, TopicBus, TopicType};
+
+        let topic = "test/publisher/flush";
+        let publisher = Publisher::<RobotState>::new(topic).unwrap();
+        let outgoing = Arc::new(
+            SampleQueue::new(Some(8), crate::subscriber::OverflowPolicy::DropOldest).forwarding(),
+        );
+        let link = TopicBus::global()
+            .attach_subscriber(topic, TopicType::of::<RobotState>(), outgoing.clone())
+            .unwrap();
+        let _local = Subscriber::<RobotState>::new(topic).unwrap();
+
+        publisher.flush().await;
+        publisher.publish(&RobotState::default()).await.unwrap();
+        let flush = tokio::time::timeout(Duration::from_millis(20), publisher.flush());
+        assert!(flush.await.is_err());
+        assert_eq!(publisher.link.pending_outgoing(), 1);
+
+        // Taken by the transport; the local subscriber's queue does not count
+        outgoing.try_pop().unwrap();
+        let flush = tokio::time::timeout(Duration::from_secs(1), publisher.flush());
+        assert!(flush.await.is_ok());
+        TopicBus::global().detach_subscriber(&link, &outgoing);
+    }
 }
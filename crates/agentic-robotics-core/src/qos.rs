@@ -0,0 +1,144 @@
+//! Quality of service profiles
+//!
+//! Mirrors the ROS2 reliability, durability, history, deadline and
+//! liveliness settings. A subscriber can only join a topic whose publishers
+//! offer at least the guarantees it requests.
+
+use crate::topic::PublisherGuid;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Whether every message must reach the subscriber
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Reliability {
+    /// Messages may be lost, e.g. high-rate sensor data
+    BestEffort,
+    /// Every message is delivered while the subscriber keeps up
+    #[default]
+    Reliable,
+}
+
+/// Whether messages outlive the moment they were published
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Durability {
+    /// Only subscribers present at publish time receive a message
+    #[default]
+    Volatile,
+    /// The publisher keeps its last `history_depth` messages and replays
+    /// them to subscribers that join later and also ask for
+    /// `TransientLocal`
+    TransientLocal,
+}
+
+/// Quality of service settings for a publisher or subscriber
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct QosProfile {
+    pub reliability: Reliability,
+    pub durability: Durability,
+    /// Messages kept by a transient-local publisher, or queued by a
+    /// subscriber before the oldest is dropped (at least 1)
+    pub history_depth: usize,
+    /// For a subscriber, the longest expected gap between messages; each
+    /// deadline that passes without one raises
+    /// [`QosEvent::DeadlineMissed`]
+    #[serde(default)]
+    pub deadline: Option<Duration>,
+    /// For a publisher, how long it counts as alive after publishing or
+    /// calling [`Publisher::assert_liveliness`](crate::Publisher::assert_liveliness);
+    /// subscribers see [`QosEvent::LivelinessLost`] once the lease runs out
+    #[serde(default)]
+    pub liveliness_lease: Option<Duration>,
+}
+
+impl Default for QosProfile {
+    fn default() -> Self {
+        Self {
+            reliability: Reliability::Reliable,
+            durability: Durability::Volatile,
+            history_depth: 10,
+            deadline: None,
+            liveliness_lease: None,
+        }
+    }
+}
+
+impl QosProfile {
+    /// Check that a publisher offering `self` satisfies a subscriber
+    /// requesting `requested`, describing the conflict if not
+    pub fn check_compatible(&self, requested: &QosProfile) -> Result<(), String> {
+        if self.reliability == Reliability::BestEffort
+            && requested.reliability == Reliability::Reliable
+        {
+            return Err("reliable subscriber cannot be served by a best-effort publisher".into());
+        }
+        if self.durability == Durability::Volatile
+            && requested.durability == Durability::TransientLocal
+        {
+            return Err(
+                "transient-local subscriber cannot be served by a volatile publisher".into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A deadline or liveliness change seen by a subscriber
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QosEvent {
+    /// A deadline passed without a message entering the subscriber's queue;
+    /// `total` counts the deadlines missed so far
+    DeadlineMissed { total: u64 },
+    /// A publisher let its liveliness lease run out
+    LivelinessLost { publisher: PublisherGuid },
+    /// A publisher that lost its liveliness published or asserted it again
+    LivelinessRegained { publisher: PublisherGuid },
+    /// The watcher fell behind and `missed` events were discarded
+    Lagged { missed: u64 },
+}
+
+/// Stream of [`QosEvent`]s, created by
+/// [`Subscriber::qos_events`](crate::Subscriber::qos_events)
+pub struct QosEventWatcher {
+    events: broadcast::Receiver<QosEvent>,
+}
+
+impl QosEventWatcher {
+    pub(crate) fn new(events: broadcast::Receiver<QosEvent>) -> Self {
+        Self { events }
+    }
+
+    /// Wait for the next event, or `None` once the subscriber is gone
+    pub async fn next(&mut self) -> Option<QosEvent> {
+        match self.events.recv().await {
+            Ok(event) => Some(event),
+            Err(RecvError::Lagged(missed)) => Some(QosEvent::Lagged { missed }),
+            Err(RecvError::Closed) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compatibility() {
+        let best_effort = QosProfile {
+            reliability: Reliability::BestEffort,
+            ..Default::default()
+        };
+        let transient = QosProfile {
+            durability: Durability::TransientLocal,
+            ..Default::default()
+        };
+        let reliable = QosProfile::default();
+
+        assert!(reliable.check_compatible(&reliable).is_ok());
+        assert!(reliable.check_compatible(&best_effort).is_ok());
+        assert!(best_effort.check_compatible(&reliable).is_err());
+        assert!(transient.check_compatible(&reliable).is_ok());
+        assert!(reliable.check_compatible(&transient).is_err());
+    }
+}
@@ -0,0 +1,2333 @@
+//! Recording topics to bag files
+//!
+//! A [`Recorder`] subscribes to a list of topics, or every topic matching a
+//! regex, with raw subscribers and appends each message to a bag file as it
+//! arrives. [`BagReader`] reads the messages back, and [`Player`] republishes
+//! them with their original timing.
+//!
+//! A bag is a sequence of records, each `op: u8, length: u32, body`, after an
+//! 8-byte magic. Integers are little-endian.
+//!
+//! - a *connection* record names a topic, its type and serialization format
+//!   before the first message on it in each file
+//! - a *chunk* record holds a batch of messages, optionally zstd- or
+//!   lz4-compressed
+//! - every chunk is followed by a *chunk index* record with the receive time
+//!   and offset of each of its messages
+//! - finalizing appends an *index* record listing every connection and
+//!   chunk, and a 16-byte trailer with the index's offset
+//!
+//! A bag whose recorder was killed before finalizing has no index;
+//! [`BagReader`] then recovers every complete chunk by scanning the records,
+//! up to the first incomplete or corrupted one.
+
+use crate::compression::{self, Codec};
+use crate::error::{Error, Result};
+use crate::graph::GraphEvent;
+use crate::message::{Message, PointCloud, Pose, RawMessage, RobotState};
+use crate::node::Node;
+use crate::publisher::Publisher;
+use crate::qos::{QosProfile, Reliability};
+use crate::serialization::Format;
+use crate::subscriber::{MessageInfo, Subscriber};
+use crate::time::{ClockMessage, SimClock, Time};
+use crate::topic::TopicBus;
+use crate::transport::TransportConfig;
+use parking_lot::Mutex;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+const MAGIC: &[u8; 8] = b"ROS3BAG1";
+const TRAILER_MAGIC: &[u8; 8] = b"ROS3IDX1";
+const TRAILER_LEN: u64 = 16;
+/// Length of a record's op and length fields
+const RECORD_HEADER_LEN: u64 = 5;
+
+const OP_CONNECTION: u8 = 1;
+const OP_CHUNK: u8 = 2;
+const OP_CHUNK_INDEX: u8 = 3;
+const OP_INDEX: u8 = 4;
+
+/// Name of the node a [`Recorder`] subscribes with
+pub const RECORDER_NODE_NAME: &str = "ros3_bag_recorder";
+
+/// Which topics a [`Recorder`] records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopicFilter {
+    /// Exactly these topics
+    List(Vec<String>),
+    /// Every topic in this process whose name matches the regex, including
+    /// topics created while recording
+    Regex(String),
+}
+
+pub use crate::compression::Compression;
+
+/// Settings of a [`Recorder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecorderConfig {
+    /// File to write; with `split_size` set, files are numbered
+    /// `<stem>_0.<ext>`, `<stem>_1.<ext>`, ...
+    pub path: PathBuf,
+    pub topics: TopicFilter,
+    pub compression: Compression,
+    /// Uncompressed message bytes collected before a chunk is written
+    pub chunk_size: usize,
+    /// Longest a message is held in memory before its chunk is written
+    pub chunk_interval: Duration,
+    /// Start a new file once the current one reaches this many bytes
+    pub split_size: Option<u64>,
+    /// Messages queued per topic while the recorder catches up; when a
+    /// burst overflows the queue the oldest are dropped and counted in
+    /// [`RecorderStats::dropped`]
+    pub queue_depth: usize,
+    /// How the recorder's node reaches publishers in other processes
+    pub transport: TransportConfig,
+}
+
+impl RecorderConfig {
+    /// Record `topics` to `path` with default settings
+    pub fn new(path: impl Into<PathBuf>, topics: TopicFilter) -> Self {
+        Self {
+            path: path.into(),
+            topics,
+            compression: Compression::None,
+            chunk_size: 768 * 1024,
+            chunk_interval: Duration::from_secs(1),
+            split_size: None,
+            queue_depth: 10_000,
+            transport: TransportConfig::InProcess,
+        }
+    }
+}
+
+/// Counters of a [`Recorder`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecorderStats {
+    /// Messages recorded on every topic
+    pub messages: u64,
+    /// Payload bytes recorded on every topic
+    pub bytes: u64,
+    /// Messages lost on every topic because the recorder's queue was full
+    pub dropped: u64,
+    /// Files written so far, the current one last
+    pub files: Vec<PathBuf>,
+    pub topics: BTreeMap<String, TopicStats>,
+}
+
+/// Counters of one recorded topic
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopicStats {
+    pub messages: u64,
+    pub bytes: u64,
+    pub dropped: u64,
+    /// Average messages per second between the first and the latest one
+    pub rate_hz: f64,
+}
+
+#[derive(Default)]
+struct Counters {
+    messages: u64,
+    bytes: u64,
+    files: Vec<PathBuf>,
+    topics: HashMap<Arc<str>, TopicCounter>,
+    /// Drop counts of the subscribers, as of the last time they were read
+    dropped: HashMap<Arc<str>, u64>,
+}
+
+struct TopicCounter {
+    messages: u64,
+    bytes: u64,
+    first: Instant,
+    last: Instant,
+}
+
+impl Counters {
+    fn record(&mut self, topic: &Arc<str>, bytes: u64) {
+        self.messages += 1;
+        self.bytes += bytes;
+        let now = Instant::now();
+        let counter = self.topics.entry(topic.clone()).or_insert(TopicCounter {
+            messages: 0,
+            bytes: 0,
+            first: now,
+            last: now,
+        });
+        counter.messages += 1;
+        counter.bytes += bytes;
+        counter.last = now;
+    }
+
+    fn stats(&self) -> RecorderStats {
+        let mut topics: BTreeMap<String, TopicStats> = self
+            .topics
+            .iter()
+            .map(|(topic, counter)| {
+                let span = counter.last.duration_since(counter.first).as_secs_f64();
+                let rate_hz = match span > 0.0 {
+                    true => (counter.messages - 1) as f64 / span,
+                    false => 0.0,
+                };
+                let stats = TopicStats {
+                    messages: counter.messages,
+                    bytes: counter.bytes,
+                    dropped: 0,
+                    rate_hz,
+                };
+                (topic.to_string(), stats)
+            })
+            .collect();
+        for (topic, &dropped) in &self.dropped {
+            if dropped > 0 {
+                topics.entry(topic.to_string()).or_default().dropped = dropped;
+            }
+        }
+        RecorderStats {
+            messages: self.messages,
+            bytes: self.bytes,
+            dropped: self.dropped.values().sum(),
+            files: self.files.clone(),
+            topics,
+        }
+    }
+}
+
+/// A topic, type and format recorded in a bag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    pub id: u32,
+    pub topic: String,
+    pub type_name: String,
+    pub format: Format,
+}
+
+/// Location and time span of a chunk in a bag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    /// Offset of the chunk record in the file
+    pub offset: u64,
+    /// Earliest receive time of a message in the chunk
+    pub start: Time,
+    /// Latest receive time of a message in the chunk
+    pub end: Time,
+    pub message_count: u32,
+}
+
+/// A message read back from a bag
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedMessage {
+    pub topic: String,
+    /// When the recorder received the message
+    pub log_time: Time,
+    /// When the message was published
+    pub publish_time: Time,
+    /// Position in its publisher's sequence of messages
+    pub seq: u64,
+    pub message: RawMessage,
+}
+
+/// A message on its way to the writer thread
+struct Entry {
+    topic: Arc<str>,
+    info: MessageInfo,
+    message: RawMessage,
+}
+
+/// Records topics to a bag file until finalized or dropped
+///
+/// Messages are written by a background thread, so recording never blocks
+/// the runtime on disk I/O. Call [`finalize`](Self::finalize) to write the
+/// index and learn about write errors; dropping the recorder finalizes it
+/// too, ignoring errors, so an interrupted recording still gets its index.
+pub struct Recorder {
+    shared: Arc<Shared>,
+    discovery: Option<JoinHandle<()>>,
+    writer: Option<thread::JoinHandle<Result<()>>>,
+    counters: Arc<Mutex<Counters>>,
+}
+
+/// State shared with the subscription tasks
+struct Shared {
+    node: Node,
+    /// `None` once the recorder is finalizing
+    entries: Mutex<Option<mpsc::Sender<Entry>>>,
+    subscriptions: Mutex<HashMap<String, Subscription>>,
+    queue_depth: usize,
+}
+
+struct Subscription {
+    subscriber: Subscriber<RawMessage>,
+    task: JoinHandle<()>,
+}
+
+impl Recorder {
+    /// Open the first bag file and start recording
+    ///
+    /// Fails if the file cannot be created, the regex is invalid or a listed
+    /// topic cannot be subscribed to. Must be called from within a Tokio
+    /// runtime.
+    pub fn start(config: RecorderConfig) -> Result<Self> {
+        let pattern = match &config.topics {
+            TopicFilter::Regex(pattern) => Some(Regex::new(pattern).map_err(|e| {
+                Error::Configuration(format!("Invalid topic regex '{}': {}", pattern, e))
+            })?),
+            TopicFilter::List(_) => None,
+        };
+        let node = Node::new(RECORDER_NODE_NAME)?.with_transport(config.transport.clone())?;
+        let counters = Arc::new(Mutex::new(Counters::default()));
+        let mut bag = BagWriter::create(&config, counters.clone())?;
+
+        let (entries, received) = mpsc::channel();
+        let interval = config.chunk_interval;
+        let writer = thread::Builder::new()
+            .name("ros3-bag-writer".into())
+            .spawn(move || {
+                let result = bag.run(received, interval);
+                if let Err(e) = &result {
+                    error!("Bag writer failed: {}", e);
+                }
+                result
+            })?;
+
+        let shared = Arc::new(Shared {
+            node,
+            entries: Mutex::new(Some(entries)),
+            subscriptions: Mutex::new(HashMap::new()),
+            queue_depth: config.queue_depth.max(1),
+        });
+        let mut recorder = Self {
+            shared: shared.clone(),
+            discovery: None,
+            writer: Some(writer),
+            counters,
+        };
+        if let TopicFilter::List(topics) = &config.topics {
+            for topic in topics {
+                shared.subscribe(topic)?;
+            }
+        }
+        if let Some(pattern) = pattern {
+            // Watch before listing so no topic is missed in between
+            let mut watcher = TopicBus::global().watch();
+            recorder.discovery = Some(tokio::spawn(async move {
+                shared.subscribe_matching(&pattern, TopicBus::global().topic_names());
+                while let Some(event) = watcher.next().await {
+                    match event {
+                        GraphEvent::TopicCreated { topic } => {
+                            shared.subscribe_matching(&pattern, [topic])
+                        }
+                        GraphEvent::Lagged { .. } => {
+                            shared.subscribe_matching(&pattern, TopicBus::global().topic_names())
+                        }
+                        _ => {}
+                    }
+                }
+            }));
+        }
+        info!("Recording to {}", config.path.display());
+        Ok(recorder)
+    }
+
+    /// Topics being recorded
+    pub fn topics(&self) -> Vec<String> {
+        let mut topics: Vec<String> = self.shared.subscriptions.lock().keys().cloned().collect();
+        topics.sort();
+        topics
+    }
+
+    /// Messages, bytes and rates recorded so far
+    pub fn stats(&self) -> RecorderStats {
+        let subscriptions = self.shared.subscriptions.lock();
+        self.count_drops(subscriptions.values().map(|s| &s.subscriber));
+        drop(subscriptions);
+        self.counters.lock().stats()
+    }
+
+    /// Take the drop counts of `subscribers` into the counters
+    fn count_drops<'a>(&self, subscribers: impl IntoIterator<Item = &'a Subscriber<RawMessage>>) {
+        let mut counters = self.counters.lock();
+        for subscriber in subscribers {
+            let dropped = subscriber.dropped_count();
+            counters.dropped.insert(Arc::from(subscriber.topic()), dropped);
+        }
+    }
+
+    /// Stop recording, write what is still queued and the index, and close
+    /// the file
+    ///
+    /// Returns the final counters, or the first error the writer hit.
+    pub async fn finalize(mut self) -> Result<RecorderStats> {
+        if let Some(discovery) = self.discovery.take() {
+            discovery.abort();
+            // Only fails because the task was aborted
+            let _ = discovery.await;
+        }
+        let mut subscribers = Vec::new();
+        for (topic, subscription) in self.take_subscriptions() {
+            let _ = subscription.task.await;
+            subscribers.push((topic, subscription.subscriber));
+        }
+        self.drain(&subscribers);
+
+        let writer = self.writer.take().expect("writer runs until finalized");
+        tokio::task::spawn_blocking(move || join_writer(writer))
+            .await
+            .map_err(|e| Error::Other(e.into()))??;
+        Ok(self.stats())
+    }
+
+    /// Stop every subscription task, handing back the subscriptions
+    fn take_subscriptions(&self) -> Vec<(String, Subscription)> {
+        let subscriptions: Vec<_> = self.shared.subscriptions.lock().drain().collect();
+        for (_, subscription) in &subscriptions {
+            subscription.task.abort();
+        }
+        subscriptions
+    }
+
+    /// Record the messages still queued, then close the writer's channel
+    fn drain(&self, subscribers: &[(String, Subscriber<RawMessage>)]) {
+        self.count_drops(subscribers.iter().map(|(_, subscriber)| subscriber));
+        for (_, subscriber) in subscribers {
+            let topic: Arc<str> = Arc::from(subscriber.topic());
+            loop {
+                match subscriber.try_recv_with_info() {
+                    Ok(Some((message, info))) => self.shared.record(&topic, message, info),
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("Draining '{}' for the bag failed: {}", topic, e);
+                        break;
+                    }
+                }
+            }
+        }
+        self.shared.entries.lock().take();
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let Some(writer) = self.writer.take() else {
+            return;
+        };
+        if let Some(discovery) = self.discovery.take() {
+            discovery.abort();
+        }
+        // Tasks still running elsewhere may lose the message they hold
+        let subscribers: Vec<_> = self
+            .take_subscriptions()
+            .into_iter()
+            .map(|(topic, subscription)| (topic, subscription.subscriber))
+            .collect();
+        self.drain(&subscribers);
+        if let Err(e) = join_writer(writer) {
+            warn!("Finalizing bag on drop failed: {}", e);
+        }
+    }
+}
+
+fn join_writer(writer: thread::JoinHandle<Result<()>>) -> Result<()> {
+    writer
+        .join()
+        .map_err(|_| Error::Other(anyhow::anyhow!("bag writer thread panicked")))?
+}
+
+impl Shared {
+    fn subscribe(self: &Arc<Self>, topic: &str) -> Result<()> {
+        let mut subscriptions = self.subscriptions.lock();
+        if subscriptions.contains_key(topic) {
+            return Ok(());
+        }
+        // Best effort so best-effort publishers are recorded too
+        let qos = QosProfile {
+            reliability: Reliability::BestEffort,
+            history_depth: self.queue_depth,
+            ..Default::default()
+        };
+        let subscriber = self.node.create_subscriber_with_qos::<RawMessage>(topic, qos)?;
+        let name: Arc<str> = Arc::from(subscriber.topic());
+        let task = tokio::spawn(record(self.clone(), subscriber.clone(), name));
+        debug!("Recording '{}'", topic);
+        subscriptions.insert(topic.to_string(), Subscription { subscriber, task });
+        Ok(())
+    }
+
+    fn subscribe_matching(
+        self: &Arc<Self>,
+        pattern: &Regex,
+        topics: impl IntoIterator<Item = String>,
+    ) {
+        for topic in topics.into_iter().filter(|topic| pattern.is_match(topic)) {
+            if let Err(e) = self.subscribe(&topic) {
+                warn!("Cannot record '{}': {}", topic, e);
+            }
+        }
+    }
+
+    fn record(&self, topic: &Arc<str>, message: RawMessage, info: MessageInfo) {
+        if let Some(entries) = &*self.entries.lock() {
+            // Fails only once the writer has stopped, which it logs
+            let _ = entries.send(Entry {
+                topic: topic.clone(),
+                info,
+                message,
+            });
+        }
+    }
+}
+
+async fn record(shared: Arc<Shared>, subscriber: Subscriber<RawMessage>, topic: Arc<str>) {
+    loop {
+        match subscriber.recv_with_info().await {
+            Ok((message, info)) => shared.record(&topic, message, info),
+            Err(Error::Closed(_)) => return,
+            Err(e) => warn!("Receiving '{}' for the bag failed: {}", topic, e),
+        }
+    }
+}
+
+/// Position of a message within its chunk, for the chunk index
+struct IndexEntry {
+    connection: u32,
+    log_time: Time,
+    offset: u32,
+}
+
+/// Appends chunks to the current bag file, rolling over to a new file when
+/// it grows past the split size
+struct BagWriter {
+    base: PathBuf,
+    compression: Compression,
+    chunk_size: usize,
+    split_size: Option<u64>,
+    file: BufWriter<File>,
+    /// Bytes written to the current file
+    position: u64,
+    file_number: usize,
+    /// The current file is full and finished; the next chunk opens a new one
+    split_due: bool,
+    /// Connection ids by topic, type and format, shared by every file
+    ids: HashMap<(Arc<str>, String, u8), u32>,
+    connections: Vec<Connection>,
+    /// Connections whose record is in the current file
+    written: HashSet<u32>,
+    chunk: Vec<u8>,
+    chunk_entries: Vec<IndexEntry>,
+    chunk_opened: Option<Instant>,
+    /// Chunks in the current file
+    chunks: Vec<ChunkInfo>,
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl BagWriter {
+    fn create(config: &RecorderConfig, counters: Arc<Mutex<Counters>>) -> Result<Self> {
+        let path = file_path(&config.path, config.split_size.map(|_| 0));
+        let file = open_bag(&path)?;
+        counters.lock().files.push(path);
+        Ok(Self {
+            base: config.path.clone(),
+            compression: config.compression,
+            chunk_size: config.chunk_size.max(1),
+            split_size: config.split_size,
+            file,
+            position: MAGIC.len() as u64,
+            file_number: 0,
+            split_due: false,
+            ids: HashMap::new(),
+            connections: Vec::new(),
+            written: HashSet::new(),
+            chunk: Vec::new(),
+            chunk_entries: Vec::new(),
+            chunk_opened: None,
+            chunks: Vec::new(),
+            counters,
+        })
+    }
+
+    /// Write entries until every sender is gone, then finish the file
+    fn run(&mut self, entries: mpsc::Receiver<Entry>, interval: Duration) -> Result<()> {
+        loop {
+            let received = match self.chunk_opened {
+                Some(opened) => {
+                    let wait = interval.saturating_sub(opened.elapsed());
+                    entries.recv_timeout(wait)
+                }
+                None => entries.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(entry) => self.push(entry)?,
+                Err(RecvTimeoutError::Timeout) => self.flush_chunk()?,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.finish()
+    }
+
+    fn push(&mut self, entry: Entry) -> Result<()> {
+        let connection = self.connection(&entry);
+        let log_time = entry.info.receive_stamp;
+        self.chunk_entries.push(IndexEntry {
+            connection,
+            log_time,
+            offset: self.chunk.len() as u32,
+        });
+        put_u32(&mut self.chunk, connection);
+        put_i64(&mut self.chunk, log_time.as_nanos());
+        put_i64(&mut self.chunk, entry.info.publish_stamp.as_nanos());
+        put_u64(&mut self.chunk, entry.info.seq);
+        put_bytes(&mut self.chunk, &entry.message.data);
+        self.chunk_opened.get_or_insert_with(Instant::now);
+        self.counters
+            .lock()
+            .record(&entry.topic, entry.message.data.len() as u64);
+
+        if self.chunk.len() >= self.chunk_size {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Id of the entry's connection, writing its record to the current file
+    /// if this is its first message there
+    fn connection(&mut self, entry: &Entry) -> u32 {
+        let key = (
+            entry.topic.clone(),
+            entry.message.type_name.clone(),
+            format_code(entry.message.format),
+        );
+        let next = self.connections.len() as u32;
+        let id = *self.ids.entry(key).or_insert(next);
+        if id == next {
+            self.connections.push(Connection {
+                id,
+                topic: entry.topic.to_string(),
+                type_name: entry.message.type_name.clone(),
+                format: entry.message.format,
+            });
+        }
+        id
+    }
+
+    /// Write the pending chunk and its index, finishing the file once it is
+    /// full
+    ///
+    /// The next file is only opened for the chunk after, so a recording
+    /// that stops right at the split size leaves no empty file behind.
+    fn flush_chunk(&mut self) -> Result<()> {
+        if self.chunk_entries.is_empty() {
+            return Ok(());
+        }
+        if self.split_due {
+            self.roll_over()?;
+        }
+        // Connection records go before the first chunk that uses them
+        let new: Vec<u32> = self
+            .chunk_entries
+            .iter()
+            .map(|entry| entry.connection)
+            .filter(|id| !self.written.contains(id))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        for id in new {
+            let body = encode_connection(&self.connections[id as usize]);
+            self.write_record(OP_CONNECTION, &body)?;
+            self.written.insert(id);
+        }
+
+        let chunk_offset = self.position;
+        let (compression, payload) = self.compress()?;
+        let mut body = Vec::with_capacity(payload.len() + 5);
+        body.push(compression);
+        put_u32(&mut body, self.chunk.len() as u32);
+        body.extend_from_slice(&payload);
+        self.write_record(OP_CHUNK, &body)?;
+
+        let mut index = Vec::with_capacity(12 + self.chunk_entries.len() * 16);
+        put_u64(&mut index, chunk_offset);
+        put_u32(&mut index, self.chunk_entries.len() as u32);
+        for entry in &self.chunk_entries {
+            put_u32(&mut index, entry.connection);
+            put_i64(&mut index, entry.log_time.as_nanos());
+            put_u32(&mut index, entry.offset);
+        }
+        self.write_record(OP_CHUNK_INDEX, &index)?;
+        // A crash loses at most the chunk being collected
+        self.file.flush()?;
+
+        self.chunks.push(chunk_info(chunk_offset, &self.chunk_entries));
+        self.chunk.clear();
+        self.chunk_entries.clear();
+        self.chunk_opened = None;
+
+        if self.split_size.is_some_and(|limit| self.position >= limit) {
+            self.write_index()?;
+            self.split_due = true;
+        }
+        Ok(())
+    }
+
+    fn compress(&self) -> Result<(u8, Vec<u8>)> {
+        let codec = self.compression.codec();
+        Ok((codec as u8, compression::compress(self.compression, &self.chunk)?))
+    }
+
+    /// Continue in the next file, the current one being finished
+    fn roll_over(&mut self) -> Result<()> {
+        self.split_due = false;
+        self.file_number += 1;
+        let path = file_path(&self.base, Some(self.file_number));
+        self.file = open_bag(&path)?;
+        info!("Continuing recording in {}", path.display());
+        self.counters.lock().files.push(path);
+        self.position = MAGIC.len() as u64;
+        self.written.clear();
+        self.chunks.clear();
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush_chunk()?;
+        if self.split_due {
+            return Ok(());
+        }
+        self.write_index()
+    }
+
+    /// Append the index of the current file and its trailer
+    fn write_index(&mut self) -> Result<()> {
+        let index_offset = self.position;
+        let mut body = Vec::new();
+        let mut written: Vec<u32> = self.written.iter().copied().collect();
+        written.sort_unstable();
+        put_u32(&mut body, written.len() as u32);
+        for id in written {
+            put_bytes(&mut body, &encode_connection(&self.connections[id as usize]));
+        }
+        put_u32(&mut body, self.chunks.len() as u32);
+        for chunk in &self.chunks {
+            put_u64(&mut body, chunk.offset);
+            put_i64(&mut body, chunk.start.as_nanos());
+            put_i64(&mut body, chunk.end.as_nanos());
+            put_u32(&mut body, chunk.message_count);
+        }
+        self.write_record(OP_INDEX, &body)?;
+        self.file.write_all(&index_offset.to_le_bytes())?;
+        self.file.write_all(TRAILER_MAGIC)?;
+        self.file.flush()?;
+        self.position += TRAILER_LEN;
+        Ok(())
+    }
+
+    fn write_record(&mut self, op: u8, body: &[u8]) -> Result<()> {
+        let len = u32::try_from(body.len())
+            .map_err(|_| Error::InvalidBag(format!("record of {} bytes", body.len())))?;
+        self.file.write_all(&[op])?;
+        self.file.write_all(&len.to_le_bytes())?;
+        self.file.write_all(body)?;
+        self.position += RECORD_HEADER_LEN + body.len() as u64;
+        Ok(())
+    }
+}
+
+fn open_bag(path: &Path) -> Result<BufWriter<File>> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(MAGIC)?;
+    Ok(file)
+}
+
+/// `base` itself, or `<stem>_<number>.<ext>` for split recordings
+fn file_path(base: &Path, number: Option<usize>) -> PathBuf {
+    let Some(number) = number else {
+        return base.to_path_buf();
+    };
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match base.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, number, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, number),
+    };
+    base.with_file_name(name)
+}
+
+fn chunk_info(offset: u64, entries: &[IndexEntry]) -> ChunkInfo {
+    let times = entries.iter().map(|entry| entry.log_time);
+    ChunkInfo {
+        offset,
+        start: times.clone().min().unwrap_or_default(),
+        end: times.max().unwrap_or_default(),
+        message_count: entries.len() as u32,
+    }
+}
+
+fn encode_connection(connection: &Connection) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_u32(&mut body, connection.id);
+    body.push(format_code(connection.format));
+    put_bytes(&mut body, connection.topic.as_bytes());
+    put_bytes(&mut body, connection.type_name.as_bytes());
+    body
+}
+
+/// Offset of the chunk and its index entries
+fn decode_chunk_index(body: &[u8]) -> Result<(u64, Vec<IndexEntry>)> {
+    let mut cursor = Cursor::new(body);
+    let offset = cursor.u64()?;
+    let count = cursor.u32()?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        entries.push(IndexEntry {
+            connection: cursor.u32()?,
+            log_time: Time::from_nanos(cursor.i64()?),
+            offset: cursor.u32()?,
+        });
+    }
+    Ok((offset, entries))
+}
+
+fn decode_connection(body: &[u8]) -> Result<Connection> {
+    let mut cursor = Cursor::new(body);
+    Ok(Connection {
+        id: cursor.u32()?,
+        format: format_from_code(cursor.u8()?)?,
+        topic: cursor.string()?,
+        type_name: cursor.string()?,
+    })
+}
+
+fn format_code(format: Format) -> u8 {
+    match format {
+        Format::Cdr => 0,
+        Format::Rkyv => 1,
+        Format::Json => 2,
+        Format::Protobuf => 3,
+        Format::MessagePack => 4,
+    }
+}
+
+fn format_from_code(code: u8) -> Result<Format> {
+    Ok(match code {
+        0 => Format::Cdr,
+        1 => Format::Rkyv,
+        2 => Format::Json,
+        3 => Format::Protobuf,
+        4 => Format::MessagePack,
+        _ => return Err(Error::InvalidBag(format!("unknown format {}", code))),
+    })
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_i64(buf: &mut Vec<u8>, value: i64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Length-prefixed bytes
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(bytes);
+}
+
+/// Reads little-endian fields from a record body
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::InvalidBag("record ends early".into()));
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        String::from_utf8(self.bytes()?.to_vec())
+            .map_err(|_| Error::InvalidBag("string is not UTF-8".into()))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// Which messages [`BagReader::messages`] yields
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilter {
+    /// Only these topics, or every topic for `None`
+    pub topics: Option<Vec<String>>,
+    /// Only messages received at or after this time
+    pub start: Option<Time>,
+    /// Only messages received at or before this time
+    pub end: Option<Time>,
+}
+
+impl MessageFilter {
+    fn overlaps(&self, chunk: &ChunkInfo) -> bool {
+        self.start.is_none_or(|start| chunk.end >= start)
+            && self.end.is_none_or(|end| chunk.start <= end)
+    }
+
+    fn matches(&self, recorded: &RecordedMessage) -> bool {
+        self.start.is_none_or(|start| recorded.log_time >= start)
+            && self.end.is_none_or(|end| recorded.log_time <= end)
+            && self.topics.as_ref().is_none_or(|topics| topics.contains(&recorded.topic))
+    }
+}
+
+/// Summary of a bag, along the lines of `ros2 bag info`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BagInfo {
+    /// Whether the bag has an index
+    pub finalized: bool,
+    /// Receive time of the first message
+    pub start: Time,
+    /// Receive time of the last message
+    pub end: Time,
+    pub messages: u64,
+    pub chunks: usize,
+    /// Chunks stored compressed
+    pub compressed_chunks: usize,
+    /// Size of the chunk payloads as stored
+    pub stored_bytes: u64,
+    /// Size of the chunk payloads once decompressed
+    pub uncompressed_bytes: u64,
+    pub topics: BTreeMap<String, TopicInfo>,
+}
+
+impl BagInfo {
+    /// Time between the first and last message
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_duration_since(self.start)
+    }
+
+    /// Uncompressed over stored size, `1.0` for uncompressed bags
+    pub fn compression_ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            return 1.0;
+        }
+        self.uncompressed_bytes as f64 / self.stored_bytes as f64
+    }
+}
+
+impl fmt::Display for BagInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        writeln!(f, "Finalized:   {}", if self.finalized { "yes" } else { "no (recovered)" })?;
+        writeln!(f, "Duration:    {:.3} s", self.duration().as_secs_f64())?;
+        writeln!(f, "Start:       {}", self.start)?;
+        writeln!(f, "End:         {}", self.end)?;
+        writeln!(f, "Messages:    {}", self.messages)?;
+        writeln!(f, "Chunks:      {} ({} compressed)", self.chunks, self.compressed_chunks)?;
+        writeln!(
+            f,
+            "Size:        {:.2} MiB stored, {:.2} MiB uncompressed (ratio {:.2})",
+            mib(self.stored_bytes),
+            mib(self.uncompressed_bytes),
+            self.compression_ratio()
+        )?;
+        writeln!(f, "Topics:")?;
+        for (topic, info) in &self.topics {
+            writeln!(
+                f,
+                "  {:<32} {:<28} {:>10} msgs  {:?}",
+                topic, info.type_name, info.messages, info.format
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Per-topic part of a [`BagInfo`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicInfo {
+    pub type_name: String,
+    pub format: Format,
+    pub messages: u64,
+}
+
+/// Size and message index of one chunk, read without decompressing it
+struct ChunkSummary {
+    compression: u8,
+    stored: u64,
+    uncompressed: u64,
+    entries: Vec<IndexEntry>,
+}
+
+/// Reads the messages of one bag file
+///
+/// Split recordings are read one file at a time.
+pub struct BagReader {
+    file: BufReader<File>,
+    /// File length when opened
+    len: u64,
+    connections: HashMap<u32, Connection>,
+    chunks: Vec<ChunkInfo>,
+    finalized: bool,
+}
+
+impl BagReader {
+    /// Open a bag, using its index if it was finalized and scanning it
+    /// otherwise
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = BufReader::new(File::open(path.as_ref())?);
+        let len = file.get_ref().metadata()?.len();
+        let mut magic = [0u8; 8];
+        file.read_exact(&mut magic)
+            .map_err(|_| Error::InvalidBag("file is too short".into()))?;
+        if &magic != MAGIC {
+            return Err(Error::InvalidBag("not a bag file".into()));
+        }
+        let mut reader = Self {
+            file,
+            len,
+            connections: HashMap::new(),
+            chunks: Vec::new(),
+            finalized: false,
+        };
+        match reader.index_offset()? {
+            Some(offset) => reader.read_index(offset)?,
+            None => reader.scan()?,
+        }
+        Ok(reader)
+    }
+
+    /// Whether the bag was finalized; otherwise only complete chunks were
+    /// recovered
+    pub fn is_finalized(&self) -> bool {
+        self.finalized
+    }
+
+    /// Every connection in the bag, by id
+    pub fn connections(&self) -> Vec<&Connection> {
+        let mut connections: Vec<&Connection> = self.connections.values().collect();
+        connections.sort_by_key(|connection| connection.id);
+        connections
+    }
+
+    /// Every chunk in the bag, in file order
+    pub fn chunks(&self) -> &[ChunkInfo] {
+        &self.chunks
+    }
+
+    /// Number of messages in the bag
+    pub fn message_count(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.message_count as u64).sum()
+    }
+
+    /// Read every message, in the order they were recorded
+    pub fn read_messages(&mut self) -> Result<Vec<RecordedMessage>> {
+        self.messages(MessageFilter::default()).collect()
+    }
+
+    /// Iterate over the messages selected by `filter`, in the order they
+    /// were recorded
+    ///
+    /// Only one chunk is held in memory at a time, and chunks outside the
+    /// filter's time range are not read at all.
+    pub fn messages(&mut self, filter: MessageFilter) -> Messages<'_> {
+        let chunks: Vec<ChunkInfo> =
+            self.chunks.iter().filter(|chunk| filter.overlaps(chunk)).copied().collect();
+        Messages {
+            reader: self,
+            filter,
+            chunks: chunks.into_iter(),
+            current: Vec::new().into_iter(),
+        }
+    }
+
+    /// Summarize the bag from its chunk indexes, without reading messages
+    pub fn info(&mut self) -> Result<BagInfo> {
+        let mut info = BagInfo {
+            finalized: self.finalized,
+            start: self.chunks.iter().map(|chunk| chunk.start).min().unwrap_or_default(),
+            end: self.chunks.iter().map(|chunk| chunk.end).max().unwrap_or_default(),
+            messages: self.message_count(),
+            chunks: self.chunks.len(),
+            compressed_chunks: 0,
+            stored_bytes: 0,
+            uncompressed_bytes: 0,
+            topics: BTreeMap::new(),
+        };
+        for connection in self.connections.values() {
+            info.topics.entry(connection.topic.clone()).or_insert_with(|| TopicInfo {
+                type_name: connection.type_name.clone(),
+                format: connection.format,
+                messages: 0,
+            });
+        }
+        for chunk in self.chunks.clone() {
+            let summary = self.chunk_summary(&chunk)?;
+            if summary.compression != Codec::None as u8 {
+                info.compressed_chunks += 1;
+            }
+            info.stored_bytes += summary.stored;
+            info.uncompressed_bytes += summary.uncompressed;
+            for entry in &summary.entries {
+                let topic = &self.connection(entry.connection)?.topic;
+                if let Some(topic) = info.topics.get_mut(topic) {
+                    topic.messages += 1;
+                }
+            }
+        }
+        Ok(info)
+    }
+
+    /// Write the messages selected by `filter` as JSON lines
+    ///
+    /// Each line is an object with `topic`, `type`, `log_time` and
+    /// `publish_time` in nanoseconds, `seq`, and the message as `msg`.
+    /// Returns the number of messages written.
+    pub fn export_jsonl(
+        &mut self,
+        filter: MessageFilter,
+        converter: &JsonConverter,
+        out: impl Write,
+    ) -> Result<u64> {
+        let mut out = BufWriter::new(out);
+        let mut count = 0;
+        for recorded in self.messages(filter) {
+            let recorded = recorded?;
+            let line = serde_json::json!({
+                "topic": recorded.topic,
+                "type": recorded.message.type_name,
+                "log_time": recorded.log_time.as_nanos(),
+                "publish_time": recorded.publish_time.as_nanos(),
+                "seq": recorded.seq,
+                "msg": converter.convert(&recorded.message)?,
+            });
+            serde_json::to_writer(&mut out, &line)
+                .map_err(|e| Error::Serialization(e.to_string()))?;
+            out.write_all(b"\n")?;
+            count += 1;
+        }
+        out.flush()?;
+        Ok(count)
+    }
+
+    /// Write the messages selected by `filter` as CSV, one row per message
+    ///
+    /// Columns are `topic`, `log_time`, `publish_time`, `seq` and one per
+    /// message field, with nested fields and arrays flattened into columns
+    /// such as `position.0`. The columns come from the first message, so
+    /// the filter should select topics of a single flat type; a message
+    /// with other fields fails the export. Returns the number of rows.
+    pub fn export_csv(
+        &mut self,
+        filter: MessageFilter,
+        converter: &JsonConverter,
+        out: impl Write,
+    ) -> Result<u64> {
+        let mut out = BufWriter::new(out);
+        let mut header: Option<Vec<String>> = None;
+        let mut count = 0;
+        for recorded in self.messages(filter) {
+            let recorded = recorded?;
+            let mut fields = Vec::new();
+            flatten_json("", &converter.convert(&recorded.message)?, &mut fields);
+            let names: Vec<String> = fields.iter().map(|(name, _)| name.clone()).collect();
+            match &header {
+                None => {
+                    let columns = ["topic", "log_time", "publish_time", "seq"];
+                    let row = columns.iter().map(|column| column.to_string()).chain(names.clone());
+                    write_csv_row(&mut out, row)?;
+                    header = Some(names);
+                }
+                Some(header) if *header != names => {
+                    return Err(Error::Serialization(format!(
+                        "message {} on '{}' has fields {:?}, the CSV has {:?}",
+                        recorded.seq, recorded.topic, names, header
+                    )));
+                }
+                Some(_) => {}
+            }
+            let row = [
+                recorded.topic,
+                recorded.log_time.as_nanos().to_string(),
+                recorded.publish_time.as_nanos().to_string(),
+                recorded.seq.to_string(),
+            ];
+            write_csv_row(&mut out, row.into_iter().chain(fields.into_iter().map(|(_, v)| v)))?;
+            count += 1;
+        }
+        out.flush()?;
+        Ok(count)
+    }
+
+    /// Read the messages of one chunk
+    pub fn read_chunk(&mut self, chunk: &ChunkInfo) -> Result<Vec<RecordedMessage>> {
+        self.file.seek(SeekFrom::Start(chunk.offset))?;
+        let (op, body) = self
+            .read_record()?
+            .ok_or_else(|| Error::InvalidBag("chunk is truncated".into()))?;
+        if op != OP_CHUNK {
+            return Err(Error::InvalidBag(format!("expected a chunk at {}", chunk.offset)));
+        }
+        self.decode_chunk(&body)
+    }
+
+    fn decode_chunk(&self, body: &[u8]) -> Result<Vec<RecordedMessage>> {
+        let mut cursor = Cursor::new(body);
+        let compression = cursor.u8()?;
+        let len = cursor.u32()? as usize;
+        let data = decompress(compression, cursor.take(cursor.data.len())?, len)?;
+
+        let mut cursor = Cursor::new(&data);
+        let mut messages = Vec::new();
+        while !cursor.is_empty() {
+            let connection = self.connection(cursor.u32()?)?;
+            messages.push(RecordedMessage {
+                topic: connection.topic.clone(),
+                log_time: Time::from_nanos(cursor.i64()?),
+                publish_time: Time::from_nanos(cursor.i64()?),
+                seq: cursor.u64()?,
+                message: RawMessage {
+                    type_name: connection.type_name.clone(),
+                    format: connection.format,
+                    data: cursor.bytes()?.to_vec(),
+                },
+            });
+        }
+        Ok(messages)
+    }
+
+    fn connection(&self, id: u32) -> Result<&Connection> {
+        self.connections
+            .get(&id)
+            .ok_or_else(|| Error::InvalidBag(format!("unknown connection {}", id)))
+    }
+
+    /// Read a chunk's header and the chunk index record after it
+    fn chunk_summary(&mut self, chunk: &ChunkInfo) -> Result<ChunkSummary> {
+        self.file.seek(SeekFrom::Start(chunk.offset))?;
+        let mut header = [0u8; RECORD_HEADER_LEN as usize + 5];
+        if !read_fully(&mut self.file, &mut header)? {
+            return Err(Error::InvalidBag("chunk is truncated".into()));
+        }
+        let mut cursor = Cursor::new(&header);
+        if cursor.u8()? != OP_CHUNK {
+            return Err(Error::InvalidBag(format!("expected a chunk at {}", chunk.offset)));
+        }
+        let len = cursor.u32()? as u64;
+        let compression = cursor.u8()?;
+        let uncompressed = cursor.u32()? as u64;
+
+        self.file.seek(SeekFrom::Start(chunk.offset + RECORD_HEADER_LEN + len))?;
+        let entries = match self.read_record()? {
+            Some((OP_CHUNK_INDEX, body)) => decode_chunk_index(&body)?.1,
+            _ => {
+                let message = format!("chunk at {} has no chunk index", chunk.offset);
+                return Err(Error::InvalidBag(message));
+            }
+        };
+        Ok(ChunkSummary {
+            compression,
+            stored: len.saturating_sub(5),
+            uncompressed,
+            entries,
+        })
+    }
+
+    /// Offset of the index if the file ends with a trailer
+    fn index_offset(&mut self) -> Result<Option<u64>> {
+        let len = self.file.seek(SeekFrom::End(0))?;
+        if len < MAGIC.len() as u64 + TRAILER_LEN {
+            return Ok(None);
+        }
+        self.file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+        let mut trailer = [0u8; TRAILER_LEN as usize];
+        self.file.read_exact(&mut trailer)?;
+        if &trailer[8..] != TRAILER_MAGIC {
+            return Ok(None);
+        }
+        let offset = u64::from_le_bytes(trailer[..8].try_into().expect("8 bytes"));
+        Ok(Some(offset))
+    }
+
+    fn read_index(&mut self, offset: u64) -> Result<()> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        let body = match self.read_record()? {
+            Some((OP_INDEX, body)) => body,
+            _ => return Err(Error::InvalidBag(format!("no index at {}", offset))),
+        };
+        let mut cursor = Cursor::new(&body);
+        for _ in 0..cursor.u32()? {
+            let connection = decode_connection(cursor.bytes()?)?;
+            self.connections.insert(connection.id, connection);
+        }
+        for _ in 0..cursor.u32()? {
+            self.chunks.push(ChunkInfo {
+                offset: cursor.u64()?,
+                start: Time::from_nanos(cursor.i64()?),
+                end: Time::from_nanos(cursor.i64()?),
+                message_count: cursor.u32()?,
+            });
+        }
+        self.finalized = true;
+        Ok(())
+    }
+
+    /// Rebuild the index from the records
+    ///
+    /// Stops with a warning at the first incomplete or corrupted record,
+    /// keeping every chunk before it.
+    fn scan(&mut self) -> Result<()> {
+        let mut offset = MAGIC.len() as u64;
+        self.file.seek(SeekFrom::Start(offset))?;
+        // Offset of a chunk whose chunk index record has not been read yet
+        let mut unindexed = None;
+        while let Some((op, body)) = self.read_record()? {
+            if op == OP_INDEX {
+                return Ok(());
+            }
+            if let Err(e) = self.scan_record(offset, op, &body, &mut unindexed) {
+                warn!("Bag is corrupted at byte {}, skipping the rest: {}", offset, e);
+                return Ok(());
+            }
+            offset += RECORD_HEADER_LEN + body.len() as u64;
+        }
+        if let Some(chunk) = unindexed {
+            warn!("Skipping chunk at byte {} of the bag, its index is missing", chunk);
+        }
+        if offset < self.len {
+            warn!("Skipping incomplete record at byte {} of the bag", offset);
+        }
+        Ok(())
+    }
+
+    fn scan_record(
+        &mut self,
+        offset: u64,
+        op: u8,
+        body: &[u8],
+        unindexed: &mut Option<u64>,
+    ) -> Result<()> {
+        match op {
+            OP_CONNECTION => {
+                let connection = decode_connection(body)?;
+                self.connections.insert(connection.id, connection);
+            }
+            // Decoded only to check it; chunks are listed through the chunk
+            // index record that follows
+            OP_CHUNK => {
+                self.decode_chunk(body)?;
+                *unindexed = Some(offset);
+            }
+            OP_CHUNK_INDEX => {
+                let (chunk, entries) = decode_chunk_index(body)?;
+                if unindexed.take() != Some(chunk) {
+                    let message = format!("index of chunk {} does not follow it", chunk);
+                    return Err(Error::InvalidBag(message));
+                }
+                self.chunks.push(chunk_info(chunk, &entries));
+            }
+            op => return Err(Error::InvalidBag(format!("unknown record {}", op))),
+        }
+        Ok(())
+    }
+
+    /// The next record, or `None` at the end of the file or of what was
+    /// written before a crash
+    fn read_record(&mut self) -> Result<Option<(u8, Vec<u8>)>> {
+        let mut header = [0u8; RECORD_HEADER_LEN as usize];
+        if !read_fully(&mut self.file, &mut header)? {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes(header[1..].try_into().expect("4 bytes")) as usize;
+        // A length running past the end is a torn write; don't allocate it
+        if self.file.stream_position()? + len as u64 > self.len {
+            return Ok(None);
+        }
+        let mut body = vec![0u8; len];
+        if !read_fully(&mut self.file, &mut body)? {
+            return Ok(None);
+        }
+        Ok(Some((header[0], body)))
+    }
+}
+
+/// Fill `buf`, or return `false` if the file ends first
+fn read_fully(file: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    match file.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Lazily reads messages of a bag, one chunk at a time
+///
+/// Returned by [`BagReader::messages`]. Iteration ends after the first
+/// error.
+pub struct Messages<'a> {
+    reader: &'a mut BagReader,
+    filter: MessageFilter,
+    chunks: std::vec::IntoIter<ChunkInfo>,
+    current: std::vec::IntoIter<RecordedMessage>,
+}
+
+impl Iterator for Messages<'_> {
+    type Item = Result<RecordedMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let filter = &self.filter;
+            if let Some(recorded) = self.current.find(|recorded| filter.matches(recorded)) {
+                return Some(Ok(recorded));
+            }
+            let chunk = self.chunks.next()?;
+            match self.reader.read_chunk(&chunk) {
+                Ok(messages) => self.current = messages.into_iter(),
+                Err(e) => {
+                    self.chunks = Vec::new().into_iter();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Converts recorded payloads to JSON for export
+///
+/// JSON and MessagePack payloads convert without knowing their type; CDR
+/// and other formats that are not self-describing need the type
+/// registered.
+#[derive(Clone, Default)]
+pub struct JsonConverter {
+    decoders: HashMap<String, fn(&RawMessage) -> Result<serde_json::Value>>,
+}
+
+impl JsonConverter {
+    /// Converter without registered types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converter for the built-in message types
+    pub fn with_builtin_types() -> Self {
+        Self::new()
+            .register::<RobotState>()
+            .register::<PointCloud>()
+            .register::<Pose>()
+    }
+
+    /// Decode messages named `T::type_name()` as `T`
+    pub fn register<T: Message>(mut self) -> Self {
+        self.decoders.insert(T::type_name().to_string(), |message| {
+            serde_json::to_value(message.decode::<T>()?)
+                .map_err(|e| Error::Serialization(e.to_string()))
+        });
+        self
+    }
+
+    /// Convert a payload to JSON
+    pub fn convert(&self, message: &RawMessage) -> Result<serde_json::Value> {
+        if let Some(decode) = self.decoders.get(&message.type_name) {
+            return decode(message);
+        }
+        match message.format {
+            Format::Json | Format::MessagePack => message.decode(),
+            format => Err(Error::Serialization(format!(
+                "cannot convert {:?} payload of unregistered type {} to JSON",
+                format, message.type_name
+            ))),
+        }
+    }
+}
+
+/// Flatten a JSON value into `(column, value)` pairs, joining nested names
+/// with dots
+fn flatten_json(prefix: &str, value: &serde_json::Value, out: &mut Vec<(String, String)>) {
+    use serde_json::Value;
+    let column = |name: &str| match prefix {
+        "" => name.to_string(),
+        _ => format!("{}.{}", prefix, name),
+    };
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                flatten_json(&column(name), value, out);
+            }
+        }
+        Value::Array(items) => {
+            for (i, value) in items.iter().enumerate() {
+                flatten_json(&column(&i.to_string()), value, out);
+            }
+        }
+        Value::Null => out.push((column_or_value(prefix), String::new())),
+        Value::String(text) => out.push((column_or_value(prefix), text.clone())),
+        other => out.push((column_or_value(prefix), other.to_string())),
+    }
+}
+
+/// Column of a scalar message, which has no field name
+fn column_or_value(prefix: &str) -> String {
+    match prefix {
+        "" => "value".to_string(),
+        _ => prefix.to_string(),
+    }
+}
+
+fn write_csv_row(out: &mut impl Write, fields: impl Iterator<Item = String>) -> Result<()> {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            out.write_all(field.as_bytes())?;
+        }
+    }
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+fn decompress(compression: u8, payload: &[u8], len: usize) -> Result<Vec<u8>> {
+    let codec = Codec::from_u8(compression)
+        .ok_or_else(|| Error::InvalidBag(format!("unknown compression {}", compression)))?;
+    compression::decompress(codec, payload, len).map_err(|e| match e {
+        Error::Serialization(reason) => Error::InvalidBag(format!("chunk: {}", reason)),
+        e => e,
+    })
+}
+
+/// How often a [`Player`] advances its clock while waiting for the next
+/// message
+const CLOCK_UPDATE_PERIOD: Duration = Duration::from_millis(10);
+
+/// Name of the node a [`Player`] publishes with
+pub const PLAYER_NODE_NAME: &str = "ros3_bag_player";
+
+/// Settings of a [`Player`]
+#[derive(Debug, Clone)]
+pub struct PlayerConfig {
+    /// Playback speed relative to the recording, e.g. `2.0` for twice as fast
+    pub rate: f64,
+    /// Start over from `start_offset` after the last message
+    pub loop_playback: bool,
+    /// Skip this much of the beginning of the bag
+    pub start_offset: Duration,
+    /// Topics to replay, or every topic for `None`
+    pub topics: Option<TopicFilter>,
+    /// Simulation clock to drive with the bag's receive times
+    pub clock: Option<SimClock>,
+    /// Also publish the clock's time as [`ClockMessage`]s on this topic,
+    /// usually [`CLOCK_TOPIC`](crate::time::CLOCK_TOPIC)
+    pub clock_topic: Option<String>,
+    /// How the player's node reaches subscribers in other processes
+    pub transport: TransportConfig,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            loop_playback: false,
+            start_offset: Duration::ZERO,
+            topics: None,
+            clock: None,
+            clock_topic: None,
+            transport: TransportConfig::InProcess,
+        }
+    }
+}
+
+/// Topics selected by an optional [`TopicFilter`]
+enum Selection {
+    All,
+    List(HashSet<String>),
+    Regex(Regex),
+}
+
+impl Selection {
+    fn new(filter: Option<&TopicFilter>) -> Result<Self> {
+        Ok(match filter {
+            None => Selection::All,
+            Some(TopicFilter::List(topics)) => Selection::List(topics.iter().cloned().collect()),
+            Some(TopicFilter::Regex(pattern)) => Selection::Regex(Regex::new(pattern).map_err(|e| {
+                Error::Configuration(format!("Invalid topic regex '{}': {}", pattern, e))
+            })?),
+        })
+    }
+
+    fn contains(&self, topic: &str) -> bool {
+        match self {
+            Selection::All => true,
+            Selection::List(topics) => topics.contains(topic),
+            Selection::Regex(pattern) => pattern.is_match(topic),
+        }
+    }
+}
+
+/// Republishes the messages of a bag on their original topics
+///
+/// Messages keep the spacing of their receive times, scaled by the
+/// configured rate. With a [`SimClock`] configured, the player moves it
+/// along with the bag's time so timers of downstream nodes run in replay
+/// time. Sim time never runs backwards, so after looping or seeking back the
+/// clock carries on from where it was, offset from the bag's time.
+pub struct Player {
+    reader: BagReader,
+    config: PlayerConfig,
+    selection: Selection,
+    node: Node,
+    publishers: HashMap<String, Publisher<RawMessage>>,
+    clock_publisher: Option<Publisher<ClockMessage>>,
+    /// Messages of the current chunk not played yet
+    pending: VecDeque<RecordedMessage>,
+    /// Index of the chunk to read once `pending` runs out
+    next_chunk: usize,
+    /// Bag time playback has reached
+    position: Time,
+    /// Added to bag time to get clock time
+    clock_offset: Duration,
+}
+
+impl Player {
+    /// Open a bag and position playback at the configured start offset
+    ///
+    /// Fails if the bag cannot be read, the rate is not a positive number or
+    /// the topic regex is invalid. Must be called from within a Tokio runtime
+    /// when the transport is not in-process.
+    pub fn open(path: impl AsRef<Path>, config: PlayerConfig) -> Result<Self> {
+        if !(config.rate.is_finite() && config.rate > 0.0) {
+            return Err(Error::Configuration(format!(
+                "Playback rate must be positive, got {}",
+                config.rate
+            )));
+        }
+        let selection = Selection::new(config.topics.as_ref())?;
+        let node = Node::new(PLAYER_NODE_NAME)?.with_transport(config.transport.clone())?;
+        let clock_publisher = match &config.clock_topic {
+            Some(topic) => Some(node.create_publisher::<ClockMessage>(topic)?),
+            None => None,
+        };
+        let mut player = Self {
+            reader: BagReader::open(path)?,
+            config,
+            selection,
+            node,
+            publishers: HashMap::new(),
+            clock_publisher,
+            pending: VecDeque::new(),
+            next_chunk: 0,
+            position: Time::ZERO,
+            clock_offset: Duration::ZERO,
+        };
+        player.position = player.start_time();
+        player.seek(player.start_time() + player.config.start_offset)?;
+        Ok(player)
+    }
+
+    /// Receive time of the first message in the bag
+    pub fn start_time(&self) -> Time {
+        let starts = self.reader.chunks().iter().map(|chunk| chunk.start);
+        starts.min().unwrap_or_default()
+    }
+
+    /// Receive time of the last message in the bag
+    pub fn end_time(&self) -> Time {
+        let ends = self.reader.chunks().iter().map(|chunk| chunk.end);
+        ends.max().unwrap_or_default()
+    }
+
+    /// Bag time playback has reached
+    pub fn position(&self) -> Time {
+        self.position
+    }
+
+    /// The bag being played
+    pub fn reader(&self) -> &BagReader {
+        &self.reader
+    }
+
+    /// Continue playback from the first message received at or after `time`
+    ///
+    /// Uses the chunk index, so seeking in either direction only reads the
+    /// chunk that contains `time`.
+    pub fn seek(&mut self, time: Time) -> Result<()> {
+        if time < self.position {
+            self.clock_offset += self.position.saturating_duration_since(time);
+        }
+        self.position = time;
+        self.pending.clear();
+        let chunks = self.reader.chunks();
+        self.next_chunk = chunks
+            .iter()
+            .position(|chunk| chunk.end >= time)
+            .unwrap_or(chunks.len());
+        if let Some(chunk) = chunks.get(self.next_chunk).copied() {
+            self.pending = self.reader.read_chunk(&chunk)?.into();
+            self.pending.retain(|recorded| recorded.log_time >= time);
+            self.next_chunk += 1;
+        }
+        Ok(())
+    }
+
+    /// The next selected message, without waiting or publishing it
+    pub fn next_message(&mut self) -> Result<Option<RecordedMessage>> {
+        loop {
+            if let Some(recorded) = self.pending.pop_front() {
+                self.position = self.position.max(recorded.log_time);
+                if self.selection.contains(&recorded.topic) {
+                    return Ok(Some(recorded));
+                }
+                continue;
+            }
+            let Some(chunk) = self.reader.chunks().get(self.next_chunk).copied() else {
+                return Ok(None);
+            };
+            self.pending = self.reader.read_chunk(&chunk)?.into();
+            self.next_chunk += 1;
+        }
+    }
+
+    /// Play from the current position to the end of the bag, or forever when
+    /// looping
+    ///
+    /// Returns the number of messages published.
+    pub async fn play(&mut self) -> Result<u64> {
+        let mut published = 0;
+        let mut published_this_pass = 0;
+        let mut anchor = (tokio::time::Instant::now(), self.position);
+        loop {
+            let Some(recorded) = self.next_message()? else {
+                // Looping over a bag without selected messages would spin
+                if !self.config.loop_playback || published_this_pass == 0 {
+                    return Ok(published);
+                }
+                self.seek(self.start_time() + self.config.start_offset)?;
+                anchor = (tokio::time::Instant::now(), self.position);
+                published_this_pass = 0;
+                continue;
+            };
+            self.wait_until(anchor, recorded.log_time).await?;
+            self.publish(recorded).await?;
+            published += 1;
+            published_this_pass += 1;
+        }
+    }
+
+    /// Publish a message on its original topic right away
+    pub async fn publish(&mut self, recorded: RecordedMessage) -> Result<()> {
+        let publisher = match self.publishers.get(&recorded.topic) {
+            Some(publisher) => publisher,
+            None => {
+                let publisher = self.node.create_publisher::<RawMessage>(&recorded.topic)?;
+                self.publishers.entry(recorded.topic.clone()).or_insert(publisher)
+            }
+        };
+        publisher.publish(&recorded.message).await
+    }
+
+    /// Sleep until bag time `time` is due, advancing the clock meanwhile
+    ///
+    /// `anchor` pairs the wall time playback started at with the bag time
+    /// it started from.
+    async fn wait_until(&self, anchor: (tokio::time::Instant, Time), time: Time) -> Result<()> {
+        let (wall, bag) = anchor;
+        let scale = |elapsed: Duration| elapsed.div_f64(self.config.rate);
+        let due = wall + scale(time.saturating_duration_since(bag));
+        if self.config.clock.is_none() && self.clock_publisher.is_none() {
+            tokio::time::sleep_until(due).await;
+            return Ok(());
+        }
+        loop {
+            let now = tokio::time::Instant::now();
+            if now >= due {
+                break;
+            }
+            let elapsed = now.duration_since(wall).mul_f64(self.config.rate);
+            self.drive_clock((bag + elapsed).min(time)).await?;
+            tokio::time::sleep_until(due.min(now + CLOCK_UPDATE_PERIOD)).await;
+        }
+        self.drive_clock(time).await
+    }
+
+    async fn drive_clock(&self, time: Time) -> Result<()> {
+        let time = time + self.clock_offset;
+        if let Some(clock) = &self.config.clock {
+            clock.set(time);
+        }
+        if let Some(publisher) = &self.clock_publisher {
+            publisher
+                .publish(&ClockMessage {
+                    stamp_ns: time.as_nanos(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bag_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ros3_bag_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn state(timestamp: i64) -> RobotState {
+        RobotState {
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    async fn wait_for_messages(recorder: &Recorder, count: u64) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while recorder.stats().messages < count {
+            assert!(Instant::now() < deadline, "recorded {:?}", recorder.stats());
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    async fn publish_states(publisher: &Publisher<RobotState>, count: i64) {
+        for timestamp in 0..count {
+            publisher.publish(&state(timestamp)).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_read_back() {
+        let path = bag_dir("roundtrip").join("run.bag");
+        let topics = TopicFilter::List(vec!["/bag_test/state".into()]);
+        let recorder = Recorder::start(RecorderConfig::new(&path, topics)).unwrap();
+        let node = Node::new("bag_test_robot").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/state").unwrap();
+
+        publish_states(&publisher, 5).await;
+        let stats = recorder.finalize().await.unwrap();
+        assert_eq!(stats.messages, 5);
+        assert_eq!(stats.files, vec![path.clone()]);
+        assert_eq!(stats.topics["/bag_test/state"].messages, 5);
+
+        let mut reader = BagReader::open(&path).unwrap();
+        assert!(reader.is_finalized());
+        assert_eq!(reader.message_count(), 5);
+        let connections = reader.connections();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].topic, "/bag_test/state");
+        assert_eq!(connections[0].type_name, "ros3_msgs/RobotState");
+        assert_eq!(connections[0].format, Format::Cdr);
+
+        let messages = reader.read_messages().unwrap();
+        let timestamps: Vec<i64> = messages
+            .iter()
+            .map(|recorded| recorded.message.decode::<RobotState>().unwrap().timestamp)
+            .collect();
+        assert_eq!(timestamps, [0, 1, 2, 3, 4]);
+        assert!(messages.iter().map(|recorded| recorded.seq).eq(0..5));
+        assert!(messages.windows(2).all(|w| w[0].log_time <= w[1].log_time));
+    }
+
+    #[tokio::test]
+    async fn test_regex_records_new_topics() {
+        let path = bag_dir("regex").join("run.bag");
+        let topics = TopicFilter::Regex("^/bag_test/regex/".into());
+        let recorder = Recorder::start(RecorderConfig::new(&path, topics)).unwrap();
+        let node = Node::new("bag_test_regex").unwrap();
+        let matching = node.create_publisher::<RobotState>("/bag_test/regex/imu").unwrap();
+        let other = node.create_publisher::<RobotState>("/bag_test/unrelated").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while recorder.topics() != ["/bag_test/regex/imu"] {
+            assert!(Instant::now() < deadline, "recording {:?}", recorder.topics());
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        publish_states(&matching, 3).await;
+        publish_states(&other, 3).await;
+        wait_for_messages(&recorder, 3).await;
+        recorder.finalize().await.unwrap();
+
+        let mut reader = BagReader::open(&path).unwrap();
+        let messages = reader.read_messages().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert!(messages.iter().all(|recorded| recorded.topic == "/bag_test/regex/imu"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_regex_rejected() {
+        let path = bag_dir("bad_regex").join("run.bag");
+        let config = RecorderConfig::new(&path, TopicFilter::Regex("(".into()));
+        assert!(matches!(Recorder::start(config), Err(Error::Configuration(_))));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_split_by_size() {
+        let path = bag_dir("split").join("run.bag");
+        let mut config =
+            RecorderConfig::new(&path, TopicFilter::List(vec!["/bag_test/split".into()]));
+        // One message per chunk, and a new file after every second chunk
+        config.chunk_size = 1;
+        config.split_size = Some(200);
+        let recorder = Recorder::start(config).unwrap();
+        let node = Node::new("bag_test_split").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/split").unwrap();
+
+        publish_states(&publisher, 6).await;
+        let stats = recorder.finalize().await.unwrap();
+        assert!(stats.files.len() > 1, "files {:?}", stats.files);
+        assert_eq!(stats.files[0], path.with_file_name("run_0.bag"));
+        assert_eq!(stats.files[1], path.with_file_name("run_1.bag"));
+
+        let mut timestamps = Vec::new();
+        for file in &stats.files {
+            let mut reader = BagReader::open(file).unwrap();
+            assert!(reader.is_finalized());
+            // Each file names its own connections
+            assert_eq!(reader.connections().len(), 1);
+            // The next file is only opened for a message to go in it
+            let messages = reader.read_messages().unwrap();
+            assert!(!messages.is_empty(), "{} is empty", file.display());
+            for recorded in messages {
+                timestamps.push(recorded.message.decode::<RobotState>().unwrap().timestamp);
+            }
+        }
+        assert_eq!(timestamps, [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_drop_writes_index() {
+        let path = bag_dir("drop").join("run.bag");
+        let topics = TopicFilter::List(vec!["/bag_test/drop".into()]);
+        let recorder = Recorder::start(RecorderConfig::new(&path, topics)).unwrap();
+        let node = Node::new("bag_test_drop").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/drop").unwrap();
+
+        publish_states(&publisher, 4).await;
+        wait_for_messages(&recorder, 4).await;
+        drop(recorder);
+
+        let mut reader = BagReader::open(&path).unwrap();
+        assert!(reader.is_finalized());
+        assert_eq!(reader.read_messages().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_unfinalized_bag_recovered() {
+        let path = bag_dir("crash").join("run.bag");
+        let mut config =
+            RecorderConfig::new(&path, TopicFilter::List(vec!["/bag_test/crash".into()]));
+        config.chunk_size = 1;
+        let recorder = Recorder::start(config).unwrap();
+        let node = Node::new("bag_test_crash").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/crash").unwrap();
+        publish_states(&publisher, 3).await;
+        recorder.finalize().await.unwrap();
+
+        // Cut the file inside the last chunk, as a killed recorder would
+        let chunks = BagReader::open(&path).unwrap().chunks().to_vec();
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(chunks[2].offset + 8).unwrap();
+
+        let mut reader = BagReader::open(&path).unwrap();
+        assert!(!reader.is_finalized());
+        assert_eq!(reader.chunks(), &chunks[..2]);
+        let timestamps: Vec<i64> = reader
+            .read_messages()
+            .unwrap()
+            .iter()
+            .map(|recorded| recorded.message.decode::<RobotState>().unwrap().timestamp)
+            .collect();
+        assert_eq!(timestamps, [0, 1]);
+    }
+
+    #[test]
+    fn test_not_a_bag() {
+        let path = bag_dir("not_a_bag").join("notes.txt");
+        std::fs::write(&path, "just some text").unwrap();
+        assert!(matches!(BagReader::open(&path), Err(Error::InvalidBag(_))));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn test_zstd_chunks() {
+        let path = bag_dir("zstd").join("run.bag");
+        let mut config =
+            RecorderConfig::new(&path, TopicFilter::List(vec!["/bag_test/zstd".into()]));
+        config.compression = Compression::Zstd { level: 3 };
+        let recorder = Recorder::start(config).unwrap();
+        let node = Node::new("bag_test_zstd").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/zstd").unwrap();
+
+        publish_states(&publisher, 100).await;
+        let stats = recorder.finalize().await.unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+        assert!(size < stats.bytes, "{} bytes on disk for {} recorded", size, stats.bytes);
+
+        assert_eq!(stats.dropped, 0);
+
+        let mut reader = BagReader::open(&path).unwrap();
+        assert_eq!(reader.read_messages().unwrap().len(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_burst_is_recorded_whole() {
+        let path = bag_dir("burst").join("run.bag");
+        let topics = TopicFilter::List(vec!["/bag_test/burst".into()]);
+        let recorder = Recorder::start(RecorderConfig::new(&path, topics)).unwrap();
+        let node = Node::new("bag_test_burst").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/burst").unwrap();
+
+        publish_states(&publisher, 1000).await;
+        let stats = recorder.finalize().await.unwrap();
+        assert_eq!((stats.messages, stats.dropped), (1000, 0));
+        assert_eq!(BagReader::open(&path).unwrap().message_count(), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_overflowing_queue_counts_drops() {
+        let path = bag_dir("overflow").join("run.bag");
+        let topics = TopicFilter::List(vec!["/bag_test/overflow".into()]);
+        let mut config = RecorderConfig::new(&path, topics);
+        config.queue_depth = 10;
+        let recorder = Recorder::start(config).unwrap();
+        let node = Node::new("bag_test_overflow").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/overflow").unwrap();
+
+        // Nothing is received while publishing never yields
+        publish_states(&publisher, 100).await;
+        assert_eq!(recorder.stats().dropped, 90);
+        let stats = recorder.finalize().await.unwrap();
+        assert_eq!((stats.messages, stats.dropped), (10, 90));
+        assert_eq!(stats.topics["/bag_test/overflow"].dropped, 90);
+    }
+
+    /// Record `count` states on `topic`, about 10 ms apart, in small chunks
+    async fn record_spaced(name: &str, topic: &str, count: i64) -> PathBuf {
+        let path = bag_dir(name).join("run.bag");
+        let mut config = RecorderConfig::new(&path, TopicFilter::List(vec![topic.into()]));
+        config.chunk_size = 512;
+        let recorder = Recorder::start(config).unwrap();
+        let node = Node::new(format!("bag_test_{}_robot", name)).unwrap();
+        let publisher = node.create_publisher::<RobotState>(topic).unwrap();
+        for timestamp in 0..count {
+            publisher.publish(&state(timestamp)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        wait_for_messages(&recorder, count as u64).await;
+        recorder.finalize().await.unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_play_at_double_rate() {
+        let path = record_spaced("play", "/bag_test/play", 100).await;
+        let recorded = BagReader::open(&path).unwrap().read_messages().unwrap();
+        let bag_span = recorded[99]
+            .log_time
+            .saturating_duration_since(recorded[0].log_time);
+
+        let node = Node::new("bag_test_play_listener").unwrap();
+        let subscriber = node.create_subscriber::<RobotState>("/bag_test/play").unwrap();
+        let config = PlayerConfig {
+            rate: 2.0,
+            ..Default::default()
+        };
+        let mut player = Player::open(&path, config).unwrap();
+        let playback = tokio::spawn(async move { player.play().await });
+
+        let mut received = Vec::new();
+        let mut arrivals = Vec::new();
+        for _ in 0..100 {
+            received.push(subscriber.recv().await.unwrap().timestamp);
+            arrivals.push(Instant::now());
+        }
+        assert_eq!(playback.await.unwrap().unwrap(), 100);
+
+        let expected: Vec<i64> = recorded
+            .iter()
+            .map(|recorded| recorded.message.decode::<RobotState>().unwrap().timestamp)
+            .collect();
+        assert_eq!(expected, (0..100).collect::<Vec<_>>());
+        assert_eq!(received, expected);
+        let span = arrivals[99] - arrivals[0];
+        let target = bag_span / 2;
+        assert!(
+            span >= target.mul_f64(0.8) && span <= target.mul_f64(1.5),
+            "replayed {:?} of bag in {:?}",
+            bag_span,
+            span
+        );
+    }
+
+    #[tokio::test]
+    async fn test_seek_backwards() {
+        let path = record_spaced("seek", "/bag_test/seek", 30).await;
+        let mut player = Player::open(&path, PlayerConfig::default()).unwrap();
+        assert!(player.reader().chunks().len() > 1);
+
+        let mut times = Vec::new();
+        while let Some(recorded) = player.next_message().unwrap() {
+            times.push(recorded.log_time);
+        }
+        assert_eq!(times.len(), 30);
+        assert_eq!(player.position(), player.end_time());
+
+        player.seek(times[10]).unwrap();
+        assert_eq!(player.position(), times[10]);
+        let next = player.next_message().unwrap().unwrap();
+        assert_eq!(next.log_time, times[10]);
+        assert_eq!(next.message.decode::<RobotState>().unwrap().timestamp, 10);
+        let mut remaining = 1;
+        while player.next_message().unwrap().is_some() {
+            remaining += 1;
+        }
+        assert_eq!(remaining, 20);
+    }
+
+    #[tokio::test]
+    async fn test_play_filters_topics() {
+        let path = bag_dir("play_filter").join("run.bag");
+        let topics = TopicFilter::Regex("^/bag_test/play_filter/".into());
+        let recorder = Recorder::start(RecorderConfig::new(&path, topics)).unwrap();
+        let node = Node::new("bag_test_play_filter").unwrap();
+        let imu = node.create_publisher::<RobotState>("/bag_test/play_filter/imu").unwrap();
+        let odom = node.create_publisher::<RobotState>("/bag_test/play_filter/odom").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while recorder.topics().len() < 2 {
+            assert!(Instant::now() < deadline, "recording {:?}", recorder.topics());
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        publish_states(&imu, 3).await;
+        publish_states(&odom, 4).await;
+        wait_for_messages(&recorder, 7).await;
+        recorder.finalize().await.unwrap();
+
+        let config = PlayerConfig {
+            topics: Some(TopicFilter::List(vec!["/bag_test/play_filter/odom".into()])),
+            ..Default::default()
+        };
+        let mut player = Player::open(&path, config).unwrap();
+        let mut count = 0;
+        while let Some(recorded) = player.next_message().unwrap() {
+            assert_eq!(recorded.topic, "/bag_test/play_filter/odom");
+            count += 1;
+        }
+        assert_eq!(count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_play_drives_sim_clock() {
+        let path = record_spaced("play_clock", "/bag_test/play_clock", 10).await;
+        let clock = SimClock::new(Time::ZERO);
+        let config = PlayerConfig {
+            rate: 4.0,
+            clock: Some(clock.clone()),
+            ..Default::default()
+        };
+        let mut player = Player::open(&path, config).unwrap();
+        assert_eq!(player.play().await.unwrap(), 10);
+        assert_eq!(clock.now(), player.end_time());
+
+        // Replaying from the start keeps sim time moving forward
+        player.seek(player.start_time()).unwrap();
+        player.play().await.unwrap();
+        assert!(clock.now() > player.end_time());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_rate_rejected() {
+        let path = record_spaced("bad_rate", "/bag_test/bad_rate", 1).await;
+        for rate in [0.0, -1.0, f64::NAN] {
+            let config = PlayerConfig {
+                rate,
+                ..Default::default()
+            };
+            assert!(matches!(Player::open(&path, config), Err(Error::Configuration(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_messages_filtered_by_time_and_topic() {
+        let path = record_spaced("filter", "/bag_test/filter", 30).await;
+        let mut reader = BagReader::open(&path).unwrap();
+        assert!(reader.chunks().len() > 1);
+        let times: Vec<Time> = reader
+            .messages(MessageFilter::default())
+            .map(|recorded| recorded.unwrap().log_time)
+            .collect();
+
+        let window = MessageFilter {
+            start: Some(times[5]),
+            end: Some(times[14]),
+            ..Default::default()
+        };
+        let timestamps: Vec<i64> = reader
+            .messages(window)
+            .map(|recorded| recorded.unwrap().message.decode::<RobotState>().unwrap().timestamp)
+            .collect();
+        assert_eq!(timestamps, (5..15).collect::<Vec<_>>());
+
+        let other_topic = MessageFilter {
+            topics: Some(vec!["/bag_test/other".into()]),
+            ..Default::default()
+        };
+        assert_eq!(reader.messages(other_topic).count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_info_summary() {
+        let path = bag_dir("info").join("run.bag");
+        let topics = TopicFilter::List(vec!["/bag_test/info/a".into(), "/bag_test/info/b".into()]);
+        let mut config = RecorderConfig::new(&path, topics);
+        config.chunk_size = 1;
+        let recorder = Recorder::start(config).unwrap();
+        let node = Node::new("bag_test_info").unwrap();
+        let a = node.create_publisher::<RobotState>("/bag_test/info/a").unwrap();
+        let b = node
+            .create_publisher_with_format::<serde_json::Value>("/bag_test/info/b", Format::Json)
+            .unwrap();
+        publish_states(&a, 3).await;
+        b.publish(&serde_json::json!({"ok": true})).await.unwrap();
+        recorder.finalize().await.unwrap();
+
+        let mut reader = BagReader::open(&path).unwrap();
+        let info = reader.info().unwrap();
+        assert!(info.finalized);
+        assert_eq!(info.messages, 4);
+        assert_eq!(info.chunks, 4);
+        assert_eq!(info.compressed_chunks, 0);
+        assert_eq!(info.stored_bytes, info.uncompressed_bytes);
+        assert_eq!(info.compression_ratio(), 1.0);
+        assert_eq!(info.duration(), info.end.saturating_duration_since(info.start));
+        assert_eq!(info.topics["/bag_test/info/a"].messages, 3);
+        assert_eq!(info.topics["/bag_test/info/a"].type_name, "ros3_msgs/RobotState");
+        assert_eq!(info.topics["/bag_test/info/b"].messages, 1);
+        assert_eq!(info.topics["/bag_test/info/b"].format, Format::Json);
+        assert!(info.to_string().contains("/bag_test/info/a"));
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_trailing_chunk_skipped() {
+        let path = bag_dir("corrupt").join("run.bag");
+        let mut config =
+            RecorderConfig::new(&path, TopicFilter::List(vec!["/bag_test/corrupt".into()]));
+        config.chunk_size = 1;
+        let recorder = Recorder::start(config).unwrap();
+        let node = Node::new("bag_test_corrupt").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/corrupt").unwrap();
+        publish_states(&publisher, 3).await;
+        recorder.finalize().await.unwrap();
+
+        // Drop the index, then garble the connection id of the last chunk's
+        // message as a torn write would
+        let mut reader = BagReader::open(&path).unwrap();
+        let chunks = reader.chunks().to_vec();
+        let stored = reader.chunk_summary(&chunks[2]).unwrap().stored;
+        // Chunk record, then a chunk index record with one entry
+        let end = chunks[2].offset + RECORD_HEADER_LEN + 5 + stored + RECORD_HEADER_LEN + 28;
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(end as usize);
+        let message = (chunks[2].offset + RECORD_HEADER_LEN + 5) as usize;
+        bytes[message..message + 4].copy_from_slice(&[0xff; 4]);
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut reader = BagReader::open(&path).unwrap();
+        assert!(!reader.is_finalized());
+        assert_eq!(reader.chunks(), &chunks[..2]);
+        assert_eq!(reader.read_messages().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_export_jsonl_and_csv() {
+        let path = bag_dir("export").join("run.bag");
+        let topics = TopicFilter::List(vec!["/bag_test/export".into()]);
+        let recorder = Recorder::start(RecorderConfig::new(&path, topics)).unwrap();
+        let node = Node::new("bag_test_export").unwrap();
+        let publisher = node.create_publisher::<RobotState>("/bag_test/export").unwrap();
+        publish_states(&publisher, 2).await;
+        recorder.finalize().await.unwrap();
+
+        let mut reader = BagReader::open(&path).unwrap();
+        let unregistered = reader.export_jsonl(
+            MessageFilter::default(),
+            &JsonConverter::new(),
+            Vec::new(),
+        );
+        assert!(matches!(unregistered, Err(Error::Serialization(_))));
+
+        let converter = JsonConverter::with_builtin_types();
+        let mut jsonl = Vec::new();
+        let count = reader
+            .export_jsonl(MessageFilter::default(), &converter, &mut jsonl)
+            .unwrap();
+        assert_eq!(count, 2);
+        let lines: Vec<serde_json::Value> = String::from_utf8(jsonl)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[1]["topic"], "/bag_test/export");
+        assert_eq!(lines[1]["type"], "ros3_msgs/RobotState");
+        assert_eq!(lines[1]["msg"]["timestamp"], 1);
+
+        let mut csv = Vec::new();
+        reader.export_csv(MessageFilter::default(), &converter, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0],
+            "topic,log_time,publish_time,seq,position.0,position.1,position.2,timestamp,\
+             velocity.0,velocity.1,velocity.2"
+        );
+        let row: Vec<&str> = rows[2].split(',').collect();
+        assert_eq!(row[0], "/bag_test/export");
+        assert_eq!(row[3], "1");
+        assert_eq!(row[7], "1");
+    }
+}
@@ -0,0 +1,389 @@
+//! Emergency stop
+//!
+//! An [`EStop`] keeps track of the emergency stop state published on the
+//! latched [`ESTOP_TOPIC`]. Nodes check [`EStop::is_engaged`], a single
+//! atomic read, or wrap their command publishers with [`EStop::gate`] so
+//! nothing is published while the stop is engaged.
+//!
+//! Every [`EStopState`] carries a token. A stop is always accepted, however
+//! old its token, but a clear only releases the stop if its token is greater
+//! than every token seen before. A clear sent before a newer stop, or replayed
+//! later, therefore leaves the robot stopped. [`EStop::engage`] and
+//! [`EStop::clear`] take the next token after the latest one they have seen.
+
+use crate::error::Result;
+use crate::message::Message;
+use crate::node::Node;
+use crate::publisher::Publisher;
+use crate::time::{Clock, Time};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Topic the emergency stop state is published on by convention
+pub const ESTOP_TOPIC: &str = "/estop";
+
+/// A request to engage or clear the emergency stop, as published on
+/// [`ESTOP_TOPIC`]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct EStopState {
+    pub engaged: bool,
+    /// Clears are only accepted with a token greater than any seen before
+    pub token: u64,
+    /// Name of the node that sent the request
+    pub source: String,
+    pub reason: String,
+    /// When the request was sent, on the sender's clock
+    pub stamp: Time,
+}
+
+impl EStopState {
+    /// A request to engage the stop
+    pub fn engage(token: u64, reason: impl Into<String>) -> Self {
+        Self {
+            engaged: true,
+            token,
+            reason: reason.into(),
+            ..Default::default()
+        }
+    }
+
+    /// A request to clear the stop
+    pub fn clear(token: u64) -> Self {
+        Self {
+            token,
+            ..Default::default()
+        }
+    }
+}
+
+impl Message for EStopState {
+    fn type_name() -> &'static str {
+        "ros3_msgs/EStopState"
+    }
+}
+
+/// Tracks the emergency stop state of a node and sends requests to change it
+///
+/// Tracking stops when the `EStop` is dropped; guards and gated publishers
+/// made from it then keep the last state.
+pub struct EStop {
+    shared: Arc<Shared>,
+    publisher: Publisher<EStopState>,
+    source: String,
+    clock: Clock,
+    task: JoinHandle<()>,
+}
+
+struct Shared {
+    engaged: AtomicBool,
+    /// The latest accepted request, with the greatest token seen
+    state: Mutex<EStopState>,
+    rejected: AtomicU64,
+}
+
+impl EStop {
+    /// Follow the requests on [`ESTOP_TOPIC`], as resolved by `node`
+    ///
+    /// The stop starts cleared unless a stop is latched on the topic. Must be
+    /// called from within a Tokio runtime.
+    pub fn new(node: &Node) -> Result<Self> {
+        let subscriber = node.create_subscriber::<EStopState>(ESTOP_TOPIC)?;
+        let publisher = node
+            .create_publisher::<EStopState>(ESTOP_TOPIC)?
+            .latch(true);
+        let shared = Arc::new(Shared {
+            engaged: AtomicBool::new(false),
+            state: Mutex::new(EStopState::default()),
+            rejected: AtomicU64::new(0),
+        });
+        let own = publisher.guid();
+        let tracker = shared.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((msg, info)) = subscriber.recv_with_info().await {
+                // Our own requests were applied when they were sent
+                if info.publisher != Some(own) {
+                    tracker.apply(msg);
+                }
+            }
+        });
+        Ok(Self {
+            shared,
+            publisher,
+            source: node.name().to_string(),
+            clock: node.clock().clone(),
+            task,
+        })
+    }
+
+    /// Whether the stop is engaged
+    #[inline]
+    pub fn is_engaged(&self) -> bool {
+        self.shared.is_engaged()
+    }
+
+    /// A cheap handle to check the stop with
+    pub fn guard(&self) -> EStopGuard {
+        EStopGuard {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// The latest accepted request, with the greatest token seen
+    pub fn state(&self) -> EStopState {
+        self.shared.state.lock().clone()
+    }
+
+    /// Number of clears rejected for a stale or replayed token
+    pub fn rejected_clears(&self) -> u64 {
+        self.shared.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Engage the stop, here at once and then everywhere through
+    /// [`ESTOP_TOPIC`], returning the request's token
+    pub async fn engage(&self, reason: impl Into<String>) -> Result<u64> {
+        self.send(true, reason.into()).await
+    }
+
+    /// Clear the stop, returning the request's token
+    ///
+    /// The clear is rejected wherever a stop with the same or a greater token
+    /// arrived first.
+    pub async fn clear(&self) -> Result<u64> {
+        self.send(false, String::new()).await
+    }
+
+    /// Suppress publishes through `publisher` while the stop is engaged
+    pub fn gate<T: Message>(&self, publisher: Publisher<T>) -> GatedPublisher<T> {
+        GatedPublisher {
+            publisher,
+            guard: self.guard(),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    async fn send(&self, engaged: bool, reason: String) -> Result<u64> {
+        let msg = {
+            let mut state = self.shared.state.lock();
+            let msg = EStopState {
+                engaged,
+                token: state.token + 1,
+                source: self.source.clone(),
+                reason,
+                stamp: self.clock.now(),
+            };
+            self.shared.apply_locked(&mut state, msg.clone());
+            msg
+        };
+        self.publisher.publish(&msg).await?;
+        Ok(msg.token)
+    }
+}
+
+impl Drop for EStop {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Shared {
+    #[inline]
+    fn is_engaged(&self) -> bool {
+        self.engaged.load(Ordering::Acquire)
+    }
+
+    fn apply(&self, msg: EStopState) {
+        let mut state = self.state.lock();
+        self.apply_locked(&mut state, msg);
+    }
+
+    fn apply_locked(&self, state: &mut EStopState, msg: EStopState) {
+        if msg.engaged {
+            if !state.engaged {
+                warn!(source = %msg.source, token = msg.token, "Emergency stop engaged: {}", msg.reason);
+            }
+            let token = state.token.max(msg.token);
+            *state = EStopState { token, ..msg };
+            self.engaged.store(true, Ordering::Release);
+        } else if msg.token > state.token {
+            if state.engaged {
+                warn!(source = %msg.source, token = msg.token, "Emergency stop cleared");
+            }
+            *state = msg;
+            self.engaged.store(false, Ordering::Release);
+        } else if state.engaged {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                source = %msg.source,
+                token = msg.token,
+                latest = state.token,
+                "Rejecting stale emergency stop clear"
+            );
+        }
+    }
+}
+
+/// Checks an [`EStop`] without owning it, see [`EStop::guard`]
+#[derive(Clone)]
+pub struct EStopGuard {
+    shared: Arc<Shared>,
+}
+
+impl EStopGuard {
+    /// Whether the stop is engaged
+    #[inline]
+    pub fn is_engaged(&self) -> bool {
+        self.shared.is_engaged()
+    }
+}
+
+/// A publisher that drops messages while an emergency stop is engaged, see
+/// [`EStop::gate`]
+pub struct GatedPublisher<T: Message> {
+    publisher: Publisher<T>,
+    guard: EStopGuard,
+    suppressed: AtomicU64,
+}
+
+impl<T: Message> GatedPublisher<T> {
+    /// Publish a message unless the stop is engaged
+    ///
+    /// Returns whether the message was published; suppressing it is not an
+    /// error.
+    pub async fn publish(&self, msg: &T) -> Result<bool> {
+        if self.guard.is_engaged() {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            debug!(
+                topic = self.publisher.topic(),
+                "Emergency stop engaged, suppressing publish"
+            );
+            return Ok(false);
+        }
+        self.publisher.publish(msg).await?;
+        Ok(true)
+    }
+
+    /// Number of messages suppressed while the stop was engaged
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        self.guard.is_engaged()
+    }
+
+    /// The publisher underneath, which is not gated
+    pub fn inner(&self) -> &Publisher<T> {
+        &self.publisher
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+    use crate::name::Remap;
+    use std::time::Duration;
+
+    /// A node whose stop topic is private to the test
+    fn node(name: &str, topic: &str) -> Node {
+        Node::with_namespace(name, "/", vec![Remap::new(ESTOP_TOPIC, topic).unwrap()]).unwrap()
+    }
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_engage_suppresses_publishes_until_cleared() {
+        let topic = "/test/safety/ordering";
+        let operator_node = node("operator", topic);
+        let operator = EStop::new(&operator_node).unwrap();
+        let robot_node = node("robot", topic);
+        let robot = EStop::new(&robot_node).unwrap();
+        let cmd = robot.gate(
+            robot_node
+                .create_publisher::<RobotState>("/test/safety/cmd")
+                .unwrap(),
+        );
+        let output = robot_node
+            .create_subscriber::<RobotState>("/test/safety/cmd")
+            .unwrap();
+
+        assert!(cmd.publish(&RobotState::default()).await.unwrap());
+        assert!(output.try_recv().unwrap().is_some());
+
+        let stop = operator.engage("operator pressed stop").await.unwrap();
+        assert!(operator.is_engaged());
+        wait_for(|| robot.is_engaged()).await;
+        assert_eq!(robot.state().reason, "operator pressed stop");
+        assert!(!cmd.publish(&RobotState::default()).await.unwrap());
+        assert!(output.try_recv().unwrap().is_none());
+        assert_eq!(cmd.suppressed(), 1);
+
+        let clear = operator.clear().await.unwrap();
+        assert!(clear > stop);
+        wait_for(|| !robot.is_engaged()).await;
+        assert!(cmd.publish(&RobotState::default()).await.unwrap());
+        assert!(output.try_recv().unwrap().is_some());
+        assert_eq!(robot.rejected_clears(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stale_and_replayed_clears_are_rejected() {
+        let topic = "/test/safety/replay";
+        let robot_node = node("robot", topic);
+        let robot = EStop::new(&robot_node).unwrap();
+        let guard = robot.guard();
+        let raw = Publisher::<EStopState>::new(topic).unwrap();
+
+        raw.publish(&EStopState::engage(1, "bumper")).await.unwrap();
+        wait_for(|| guard.is_engaged()).await;
+        raw.publish(&EStopState::clear(2)).await.unwrap();
+        wait_for(|| !guard.is_engaged()).await;
+
+        // Replaying the clear does not release a newer stop
+        raw.publish(&EStopState::engage(3, "lidar")).await.unwrap();
+        raw.publish(&EStopState::clear(2)).await.unwrap();
+        // Neither does a clear sent before the stop arrived
+        raw.publish(&EStopState::clear(3)).await.unwrap();
+        wait_for(|| robot.rejected_clears() == 2).await;
+        assert!(guard.is_engaged());
+        assert_eq!(robot.state().token, 3);
+
+        // A stop with an old token still engages
+        raw.publish(&EStopState::clear(4)).await.unwrap();
+        wait_for(|| !guard.is_engaged()).await;
+        raw.publish(&EStopState::engage(1, "late")).await.unwrap();
+        wait_for(|| guard.is_engaged()).await;
+        assert_eq!(robot.state().token, 4);
+
+        // Local requests continue from the greatest token seen
+        assert_eq!(robot.clear().await.unwrap(), 5);
+        assert!(!robot.is_engaged());
+    }
+
+    #[tokio::test]
+    async fn test_latched_stop_reaches_late_joiners() {
+        let topic = "/test/safety/latch";
+        let operator_node = node("operator", topic);
+        let operator = EStop::new(&operator_node).unwrap();
+        operator.engage("startup").await.unwrap();
+
+        let late_node = node("late", topic);
+        let late = EStop::new(&late_node).unwrap();
+        wait_for(|| late.is_engaged()).await;
+        assert_eq!(late.state().source, "operator");
+        drop(operator);
+        assert!(late.is_engaged());
+    }
+}
@@ -0,0 +1,398 @@
+//! Validation of JSON values against a JSON Schema
+//!
+//! Checks JSON messages against their type's [schema](crate::Message::schema)
+//! before they are converted, so that a mismatch is reported field by field
+//! instead of as a single deserialization error.
+//!
+//! Supports the subset of JSON Schema that message schemas and tool
+//! descriptions use: `type` (a name or a list of names), `properties`,
+//! `required`, `additionalProperties`, `items`, `minItems`, `maxItems`,
+//! `enum`, `const`, `minimum`, `maximum`, `exclusiveMinimum`,
+//! `exclusiveMaximum`, `minLength`, `maxLength`, `allOf`, `anyOf`, `oneOf`
+//! and `$ref` to a definition in the same schema. Other keywords are
+//! ignored, so a schema using them accepts more than it describes rather
+//! than rejecting valid values.
+
+use serde_json::Value;
+use thiserror::Error;
+
+/// A value that does not match the schema
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{path}: {message}")]
+pub struct SchemaError {
+    /// JSON pointer to the offending value, `/` for the value itself
+    pub path: String,
+    pub message: String,
+}
+
+/// Check `value` against `schema`, returning every mismatch found
+pub fn validate(schema: &Value, value: &Value) -> Result<(), Vec<SchemaError>> {
+    let mut errors = Vec::new();
+    check(schema, schema, value, "", &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Check `value` against `schema`, a part of `root`
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` and `{}`-like schemas accept anything; `false` nothing
+        if schema == &Value::Bool(false) {
+            errors.push(error(path, "no value is allowed here"));
+        }
+        return;
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        // References outside the schema constrain nothing
+        if let Some(target) = reference.strip_prefix('#').and_then(|at| root.pointer(at)) {
+            check(root, target, value, path, errors);
+        }
+    }
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for subschema in all {
+            check(root, subschema, value, path, errors);
+        }
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(branches) = schema.get(keyword).and_then(Value::as_array) {
+            check_branches(root, keyword, branches, value, path, errors);
+        }
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| has_type(value, name)) {
+            let message = format!("expected {}, found {}", names.join(" or "), type_of(value));
+            errors.push(error(path, message));
+            // The remaining keywords describe a value of the right type
+            return;
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            errors.push(error(path, format!("must be {}", expected)));
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            errors.push(error(
+                path,
+                format!("must be one of {}", Value::from(allowed.clone())),
+            ));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        errors.push(error(path, format!("missing required property '{}'", name)));
+                    }
+                }
+            }
+            for (name, item) in object {
+                let item_path = format!("{}/{}", path, name);
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) => check(root, property, item, &item_path, errors),
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        errors.push(error(&item_path, "unknown property"));
+                    }
+                    None => {
+                        if let Some(extra) = schema.get("additionalProperties") {
+                            check(root, extra, item, &item_path, errors);
+                        }
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            let length = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if length < min {
+                    errors.push(error(path, format!("must have at least {} items", min)));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if length > max {
+                    errors.push(error(path, format!("must have at most {} items", max)));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(
+                        root,
+                        item_schema,
+                        item,
+                        &format!("{}/{}", path, index),
+                        errors,
+                    );
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or(f64::NAN);
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < minimum {
+                    errors.push(error(path, format!("must be at least {}", minimum)));
+                }
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > maximum {
+                    errors.push(error(path, format!("must be at most {}", maximum)));
+                }
+            }
+            if let Some(minimum) = schema.get("exclusiveMinimum").and_then(Value::as_f64) {
+                if number <= minimum {
+                    errors.push(error(path, format!("must be greater than {}", minimum)));
+                }
+            }
+            if let Some(maximum) = schema.get("exclusiveMaximum").and_then(Value::as_f64) {
+                if number >= maximum {
+                    errors.push(error(path, format!("must be less than {}", maximum)));
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if length < min {
+                    errors.push(error(path, format!("must be at least {} characters", min)));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if length > max {
+                    errors.push(error(path, format!("must be at most {} characters", max)));
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+}
+
+/// Check `anyOf` or `oneOf`
+///
+/// When no branch matches, the errors of the branch the value was meant
+/// for are more useful than a bare mismatch. That is the only branch of
+/// the value's type, like the `T` of an `Option<T>`, or among several the
+/// only one without errors at `path` itself, like the variant named by an
+/// enum's tag.
+fn check_branches(
+    root: &Value,
+    keyword: &str,
+    branches: &[Value],
+    value: &Value,
+    path: &str,
+    errors: &mut Vec<SchemaError>,
+) {
+    let results: Vec<(&Value, Vec<SchemaError>)> = branches
+        .iter()
+        .map(|branch| {
+            let mut branch_errors = Vec::new();
+            check(root, branch, value, path, &mut branch_errors);
+            (branch, branch_errors)
+        })
+        .collect();
+    let matching = results
+        .iter()
+        .filter(|(_, result)| result.is_empty())
+        .count();
+    if matching == 0 {
+        let here = pointer(path);
+        let mut typed: Vec<Vec<SchemaError>> = results
+            .into_iter()
+            .filter(|(branch, _)| accepts_type(root, branch, value))
+            .map(|(_, result)| result)
+            .collect();
+        if typed.len() > 1 {
+            typed.retain(|result| result.iter().all(|error| error.path != here));
+        }
+        match typed.pop() {
+            Some(only) if typed.is_empty() => errors.extend(only),
+            _ => errors.push(error(path, "does not match any of the allowed schemas")),
+        }
+    } else if keyword == "oneOf" && matching > 1 {
+        errors.push(error(path, "matches more than one of the allowed schemas"));
+    }
+}
+
+/// Whether the `type` of `schema`, or of the definition it refers to,
+/// allows `value`
+fn accepts_type(root: &Value, schema: &Value, value: &Value) -> bool {
+    if let Some(target) = schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix('#'))
+        .and_then(|at| root.pointer(at))
+    {
+        return accepts_type(root, target, value);
+    }
+    match schema.get("type") {
+        Some(Value::String(name)) => has_type(value, name),
+        Some(Value::Array(names)) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|name| has_type(value, name)),
+        _ => true,
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        // Unknown type names constrain nothing
+        _ => true,
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+    }
+}
+
+/// `path` as reported in errors
+fn pointer(path: &str) -> String {
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+fn error(path: &str, message: impl Into<String>) -> SchemaError {
+    SchemaError {
+        path: pointer(path),
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn move_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "location": { "type": "string", "minLength": 1 },
+                "speed": { "type": "number", "minimum": 0, "maximum": 2 },
+                "mode": { "enum": ["walk", "run"] },
+                "waypoints": { "type": "array", "items": { "type": "integer" } }
+            },
+            "required": ["location"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_arguments() {
+        let args = json!({ "location": "kitchen", "speed": 1.5, "waypoints": [1, 2] });
+        assert_eq!(validate(&move_schema(), &args), Ok(()));
+        assert_eq!(validate(&json!({}), &json!({ "anything": [1] })), Ok(()));
+    }
+
+    #[test]
+    fn test_reports_every_mismatch() {
+        let args = json!({ "speed": 3, "mode": "fly", "waypoints": [1, "b"], "color": "red" });
+        let errors = validate(&move_schema(), &args).unwrap_err();
+        let mut messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            [
+                "/: missing required property 'location'",
+                "/color: unknown property",
+                "/mode: must be one of [\"walk\",\"run\"]",
+                "/speed: must be at most 2",
+                "/waypoints/1: expected integer, found string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_array_length() {
+        let schema = json!({ "type": "array", "minItems": 3, "maxItems": 3 });
+        assert_eq!(validate(&schema, &json!([0.0, 1.0, 2.0])), Ok(()));
+        let errors = validate(&schema, &json!([0.0, 1.0])).unwrap_err();
+        assert_eq!(errors[0].to_string(), "/: must have at least 3 items");
+        let errors = validate(&schema, &json!([0, 1, 2, 3])).unwrap_err();
+        assert_eq!(errors[0].to_string(), "/: must have at most 3 items");
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "target": {
+                    "oneOf": [
+                        { "type": "string", "const": "home" },
+                        { "$ref": "#/definitions/Pose" }
+                    ]
+                },
+                "speed": { "anyOf": [{ "type": "number", "exclusiveMinimum": 0 }, { "type": "null" }] }
+            },
+            "definitions": {
+                "Pose": {
+                    "type": "object",
+                    "properties": { "x": { "type": "number" }, "y": { "type": "number" } },
+                    "required": ["x", "y"]
+                }
+            }
+        });
+        assert_eq!(
+            validate(&schema, &json!({ "target": "home", "speed": null })),
+            Ok(())
+        );
+        assert_eq!(
+            validate(&schema, &json!({ "target": { "x": 1, "y": 2 } })),
+            Ok(())
+        );
+
+        // Only the branch of the value's type is reported
+        let errors = validate(&schema, &json!({ "target": { "x": "1", "y": 2 } })).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "/target/x: expected number, found string"
+        );
+        let errors = validate(&schema, &json!({ "speed": 0 })).unwrap_err();
+        assert_eq!(errors[0].to_string(), "/speed: must be greater than 0");
+        let errors = validate(&schema, &json!({ "target": "work" })).unwrap_err();
+        assert_eq!(errors[0].to_string(), "/target: must be \"home\"");
+        let errors = validate(&schema, &json!({ "target": 3 })).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "/target: does not match any of the allowed schemas"
+        );
+    }
+
+    #[test]
+    fn test_wrong_root_type() {
+        let errors = validate(&move_schema(), &json!("kitchen")).unwrap_err();
+        assert_eq!(errors[0].to_string(), "/: expected object, found string");
+    }
+}
@@ -1,13 +1,16 @@
 //! Zero-copy serialization strategies
 //!
-//! Supports both CDR (DDS-compatible) and rkyv (zero-copy) serialization
+//! Supports both CDR (DDS-compatible) and rkyv (zero-copy) serialization,
+//! MessagePack for compact self-describing payloads, and protobuf for
+//! message types that opt in (`protobuf` feature)
 
 use crate::error::{Error, Result};
 use crate::message::Message;
+use crate::pool::BufferPool;
 use serde::{Deserialize, Serialize};
 
 /// Serialization format
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Format {
     /// CDR (Common Data Representation) - DDS compatible
     Cdr,
@@ -15,6 +18,10 @@ pub enum Format {
     Rkyv,
     /// JSON (for debugging)
     Json,
+    /// Protocol Buffers, for types with a [`ProtobufCodec`]
+    Protobuf,
+    /// MessagePack with named fields
+    MessagePack,
 }
 
 /// Serialize a message using CDR format
@@ -24,8 +31,17 @@ pub fn serialize_cdr<T: Serialize>(msg: &T) -> Result<Vec<u8>> {
 }
 
 /// Deserialize a message using CDR format
+///
+/// Allocations are bounded by the input length, so malformed input fails
+/// instead of requesting huge buffers.
 pub fn deserialize_cdr<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T> {
-    cdr::deserialize::<T>(data)
+    cdr::deserialize_from::<_, T, _>(data, cdr::Bounded(data.len() as u64))
+        .map_err(|e| Error::Serialization(e.to_string()))
+}
+
+/// Serialize a message using CDR format, appending to `buf`
+pub fn serialize_cdr_into<T: Serialize>(msg: &T, buf: &mut Vec<u8>) -> Result<()> {
+    cdr::serialize_into::<_, _, _, cdr::CdrBe>(buf, msg, cdr::Infinite)
         .map_err(|e| Error::Serialization(e.to_string()))
 }
 
@@ -51,14 +67,88 @@ pub fn deserialize_json<T: for<'de> Deserialize<'de>>(data: &str) -> Result<T> {
         .map_err(|e| Error::Serialization(e.to_string()))
 }
 
+/// Serialize a message to MessagePack, encoding structs as maps keyed by
+/// field name
+pub fn serialize_msgpack<T: Serialize>(msg: &T) -> Result<Vec<u8>> {
+    rmp_serde::to_vec_named(msg)
+        .map_err(|e| Error::Serialization(e.to_string()))
+}
+
+/// Deserialize a message from MessagePack
+pub fn deserialize_msgpack<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T> {
+    rmp_serde::from_slice(data)
+        .map_err(|e| Error::Serialization(e.to_string()))
+}
+
+/// Protobuf encode/decode functions for a message type
+///
+/// Returned from [`Message::protobuf`]; build one with
+/// [`ProtobufCodec::of`] for types implementing [`ProstCompatible`].
+pub struct ProtobufCodec<T> {
+    pub encode: fn(&T) -> Vec<u8>,
+    pub decode: fn(&[u8]) -> Result<T>,
+}
+
+impl<T> Clone for ProtobufCodec<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ProtobufCodec<T> {}
+
+/// Conversion between a message and its prost-generated protobuf type
+#[cfg(feature = "protobuf")]
+pub trait ProstCompatible: Sized {
+    type Proto: prost::Message + Default;
+
+    fn to_proto(&self) -> Self::Proto;
+
+    fn from_proto(proto: Self::Proto) -> Result<Self>;
+}
+
+#[cfg(feature = "protobuf")]
+impl<T: ProstCompatible> ProtobufCodec<T> {
+    pub fn of() -> Self {
+        Self {
+            encode: |msg| prost::Message::encode_to_vec(&msg.to_proto()),
+            decode: |data| {
+                let proto = <T::Proto as prost::Message>::decode(data)
+                    .map_err(|e| Error::Serialization(e.to_string()))?;
+                T::from_proto(proto)
+            },
+        }
+    }
+}
+
+fn protobuf_codec<T: Message>() -> Result<ProtobufCodec<T>> {
+    T::protobuf().ok_or_else(|| {
+        Error::Serialization(format!("{} has no protobuf encoding", T::type_name()))
+    })
+}
+
 /// Serializer wrapper
 pub struct Serializer {
     format: Format,
+    pool: Option<BufferPool>,
 }
 
 impl Serializer {
     pub fn new(format: Format) -> Self {
-        Self { format }
+        Self { format, pool: None }
+    }
+
+    /// Serialize into buffers from `pool`
+    ///
+    /// Publishers using this serializer take their buffers from the pool
+    /// instead of allocating one per message.
+    pub fn with_pool(mut self, pool: BufferPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn pool(&self) -> Option<&BufferPool> {
+        self.pool.as_ref()
     }
 
     pub fn serialize<T: Message>(&self, msg: &T) -> Result<Vec<u8>> {
@@ -66,8 +156,47 @@ impl Serializer {
             Format::Cdr => serialize_cdr(msg),
             Format::Rkyv => serialize_rkyv(msg),
             Format::Json => serialize_json(msg).map(|s| s.into_bytes()),
+            Format::Protobuf => Ok((protobuf_codec::<T>()?.encode)(msg)),
+            Format::MessagePack => serialize_msgpack(msg),
         }
     }
+
+    /// Serialize a message, appending to `buf`
+    ///
+    /// Does not allocate once `buf` has room for the message, except for
+    /// protobuf, whose codec returns a fresh vector.
+    pub fn serialize_into<T: Message>(&self, msg: &T, buf: &mut Vec<u8>) -> Result<()> {
+        match self.format {
+            Format::Cdr => serialize_cdr_into(msg, buf),
+            Format::Rkyv => serialize_rkyv(msg).map(|bytes| buf.extend_from_slice(&bytes)),
+            Format::Json => {
+                serde_json::to_writer(buf, msg).map_err(|e| Error::Serialization(e.to_string()))
+            }
+            Format::Protobuf => {
+                buf.extend_from_slice(&(protobuf_codec::<T>()?.encode)(msg));
+                Ok(())
+            }
+            Format::MessagePack => rmp_serde::encode::write_named(buf, msg)
+                .map_err(|e| Error::Serialization(e.to_string())),
+        }
+    }
+
+    pub fn deserialize<T: Message>(&self, data: &[u8]) -> Result<T> {
+        match self.format {
+            Format::Cdr => deserialize_cdr(data),
+            Format::Rkyv => Err(Error::Serialization(
+                "rkyv deserialization not fully implemented".to_string(),
+            )),
+            Format::Json => serde_json::from_slice(data)
+                .map_err(|e| Error::Serialization(e.to_string())),
+            Format::Protobuf => (protobuf_codec::<T>()?.decode)(data),
+            Format::MessagePack => deserialize_msgpack(data),
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
 }
 
 impl Default for Serializer {
@@ -104,4 +233,90 @@ mod tests {
         let bytes = serializer.serialize(&state).unwrap();
         assert!(!bytes.is_empty());
     }
+
+    #[test]
+    fn test_serializer_roundtrip() {
+        let state = RobotState {
+            position: [1.0, 2.0, 3.0],
+            ..Default::default()
+        };
+        for format in [Format::Cdr, Format::Json, Format::MessagePack] {
+            let serializer = Serializer::new(format);
+            let bytes = serializer.serialize(&state).unwrap();
+            let decoded: RobotState = serializer.deserialize(&bytes).unwrap();
+            assert_eq!(decoded.position, state.position);
+        }
+    }
+
+    #[test]
+    fn test_serialize_into_matches_serialize() {
+        let state = RobotState {
+            position: [1.0, 2.0, 3.0],
+            ..Default::default()
+        };
+        for format in [Format::Cdr, Format::Json, Format::MessagePack] {
+            let serializer = Serializer::new(format);
+            let mut buf = Vec::new();
+            serializer.serialize_into(&state, &mut buf).unwrap();
+            assert_eq!(buf, serializer.serialize(&state).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_protobuf_requires_codec() {
+        let serializer = Serializer::new(Format::Protobuf);
+        let result = serializer.serialize(&serde_json::json!({"a": 1}));
+        assert!(matches!(result, Err(Error::Serialization(_))));
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn test_roundtrip_all_formats() {
+        let state = RobotState {
+            position: [1.5, -2.25, 3.0],
+            velocity: [0.1, 0.2, 0.3],
+            timestamp: 1_700_000_000,
+        };
+        for format in [Format::Cdr, Format::Json, Format::Protobuf] {
+            let serializer = Serializer::new(format);
+            let bytes = serializer.serialize(&state).unwrap();
+            let decoded: RobotState = serializer.deserialize(&bytes).unwrap();
+            assert_eq!(decoded.position, state.position);
+            assert_eq!(decoded.velocity, state.velocity);
+            assert_eq!(decoded.timestamp, state.timestamp);
+        }
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn test_protobuf_bytes_rejected_by_cdr() {
+        let state = RobotState {
+            position: [1.0, 2.0, 3.0],
+            ..Default::default()
+        };
+        let bytes = Serializer::new(Format::Protobuf).serialize(&state).unwrap();
+        let result: Result<RobotState> = Serializer::new(Format::Cdr).deserialize(&bytes);
+        assert!(matches!(result, Err(Error::Serialization(_))));
+    }
+
+    #[test]
+    fn test_msgpack_non_finite_floats() {
+        let state = RobotState {
+            position: [f64::NAN, f64::INFINITY, f64::NEG_INFINITY],
+            velocity: [f64::MAX, f64::MIN_POSITIVE, -0.0],
+            timestamp: i64::MIN,
+        };
+        let bytes = serialize_msgpack(&state).unwrap();
+        let decoded: RobotState = deserialize_msgpack(&bytes).unwrap();
+
+        assert!(decoded.position[0].is_nan());
+        assert_eq!(decoded.position[1], f64::INFINITY);
+        assert_eq!(decoded.position[2], f64::NEG_INFINITY);
+        assert_eq!(decoded.velocity, state.velocity);
+        assert_eq!(decoded.timestamp, i64::MIN);
+
+        // JSON has no representation for these values
+        let json = serialize_json(&state).unwrap();
+        assert!(deserialize_json::<RobotState>(&json).is_err());
+    }
 }
@@ -1,9 +1,36 @@
 //! Service and RPC implementation
+//!
+//! A [`ServiceServer`] registers a name in the process-wide service registry
+//! and answers requests from any number of [`ServiceClient`]s concurrently.
+//! Requests and responses are handed over in-process without serialization.
+//! With the `transport-zenoh` feature, servers can also be reached from
+//! other processes through zenoh queryables. A [`PolledServiceServer`]
+//! instead leaves taking and answering requests to the caller, e.g. a loop
+//! waiting on a [`WaitSet`](crate::wait::WaitSet).
+//!
+//! Tools that only know a service by name, such as the MCP tools, find it
+//! with [`graph::list_services`](crate::graph::list_services) and call it
+//! with JSON through [`call_json`].
 
 use crate::error::{Error, Result};
+use crate::graph::ServiceInfo;
 use crate::message::Message;
-use parking_lot::RwLock;
-use std::sync::Arc;
+use crate::name::TopicName;
+use crate::types;
+use crate::wait::{Waitable, Waiters};
+#[cfg(feature = "transport-zenoh")]
+use crate::zenoh_transport::ZenohTransport;
+use parking_lot::{Mutex, RwLock};
+use serde_json::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Notify, Semaphore};
+use tokio::task::JoinHandle;
 use tracing::debug;
 
 /// Service request handler
@@ -83,9 +110,507 @@ impl<Req: Message, Res: Message> Service<Req, Res> {
     }
 
     /// Call the service
-    pub async fn call(&self, _request: Req) -> Result<Res> {
-        // In real implementation, this would call via Zenoh
-        Err(Error::Other(anyhow::anyhow!("Service call not implemented")))
+    ///
+    /// Equivalent to a [`ServiceClient::call`] with the default timeout.
+    pub async fn call(&self, request: Req) -> Result<Res> {
+        ServiceClient::<Req, Res>::new(self.name.clone())?
+            .call(request)
+            .await
+    }
+
+    /// Get service name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A request on its way to a server, with the channel for its response
+struct Call<Req, Res> {
+    request: Req,
+    reply: oneshot::Sender<Result<Res>>,
+}
+
+/// Response to a call made by [`call_json`]
+type JsonResponse = Pin<Box<dyn Future<Output = Result<Value>> + Send>>;
+
+/// A server's entry in the service registry
+struct ServiceEntry {
+    /// `mpsc::UnboundedSender<Call<Req, Res>>` for the server's types
+    calls: Box<dyn Any + Send + Sync>,
+    request_type: &'static str,
+    response_type: &'static str,
+    /// `json_call::<Req, Res>` for the server's types
+    call_json: fn(&ServiceEntry, &str, Value, Duration) -> Result<JsonResponse>,
+    pending: Arc<AtomicUsize>,
+    /// Wait sets woken when a call arrives
+    waiters: Waiters,
+}
+
+impl ServiceEntry {
+    fn new<Req: Message, Res: Message>(calls: mpsc::UnboundedSender<Call<Req, Res>>) -> Self {
+        types::register::<Req>();
+        types::register::<Res>();
+        Self {
+            calls: Box::new(calls),
+            request_type: Req::type_name(),
+            response_type: Res::type_name(),
+            call_json: json_call::<Req, Res>,
+            pending: Arc::new(AtomicUsize::new(0)),
+            waiters: Waiters::default(),
+        }
+    }
+
+    /// Queue `request` for the server, returning the channel its response
+    /// arrives on
+    fn send<Req: Message, Res: Message>(
+        &self,
+        name: &str,
+        request: Req,
+    ) -> Result<oneshot::Receiver<Result<Res>>> {
+        let calls = self
+            .calls
+            .downcast_ref::<mpsc::UnboundedSender<Call<Req, Res>>>()
+            .ok_or_else(|| Error::TopicTypeMismatch {
+                topic: name.to_string(),
+                expected: format!("{} -> {}", self.request_type, self.response_type),
+                found: service_types::<Req, Res>(),
+            })?;
+
+        let (reply, response) = oneshot::channel();
+        self.pending.fetch_add(1, Ordering::AcqRel);
+        if calls.send(Call { request, reply }).is_err() {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+            return Err(Error::ServiceUnavailable(name.to_string()));
+        }
+        self.waiters.wake();
+        Ok(response)
+    }
+
+    /// Register the entry as the server of `name`
+    fn register(self: &Arc<Self>, name: &str) -> Result<()> {
+        let mut services = registry().write();
+        if services.contains_key(name) {
+            return Err(Error::Configuration(format!(
+                "Service '{}' already has a server",
+                name
+            )));
+        }
+        services.insert(name.to_string(), self.clone());
+        Ok(())
+    }
+
+    /// Remove the entry from the registry unless another server replaced it
+    fn unregister(self: &Arc<Self>, name: &str) {
+        let mut services = registry().write();
+        if services
+            .get(name)
+            .is_some_and(|entry| Arc::ptr_eq(entry, self))
+        {
+            services.remove(name);
+        }
+    }
+}
+
+impl Waitable for ServiceEntry {
+    fn is_ready(&self) -> bool {
+        self.pending.load(Ordering::Acquire) > 0
+    }
+
+    fn attach(&self, signal: &Arc<Notify>) {
+        self.waiters.attach(signal);
+    }
+}
+
+/// Process-wide map of service names to their servers
+fn registry() -> &'static RwLock<HashMap<String, Arc<ServiceEntry>>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, Arc<ServiceEntry>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+fn service_types<Req: Message, Res: Message>() -> String {
+    format!("{} -> {}", Req::type_name(), Res::type_name())
+}
+
+/// Wait at most `timeout` for the response to a call of `name`
+async fn response<Res>(
+    name: &str,
+    response: oneshot::Receiver<Result<Res>>,
+    timeout: Duration,
+) -> Result<Res> {
+    match tokio::time::timeout(timeout, response).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => Err(Error::ServiceUnavailable(name.to_string())),
+        Err(_) => Err(Error::Timeout(format!(
+            "Service '{}' did not respond within {:?}",
+            name, timeout
+        ))),
+    }
+}
+
+fn json_call<Req: Message, Res: Message>(
+    entry: &ServiceEntry,
+    name: &str,
+    request: Value,
+    timeout: Duration,
+) -> Result<JsonResponse> {
+    let request: Req = serde_json::from_value(request).map_err(|e| {
+        Error::Serialization(format!("Invalid {} request: {}", Req::type_name(), e))
+    })?;
+    let pending = entry.send::<Req, Res>(name, request)?;
+    let name = name.to_string();
+    Ok(Box::pin(async move {
+        let result = response(&name, pending, timeout).await?;
+        serde_json::to_value(result).map_err(|e| Error::Serialization(e.to_string()))
+    }))
+}
+
+/// Call the service `name` with a JSON request, returning the JSON response
+///
+/// The request is deserialized into the server's request type, failing
+/// with [`Error::Serialization`] if it does not match. Otherwise fails like
+/// [`ServiceClient::call_with_timeout`]. Only servers in this process can
+/// be called this way.
+pub async fn call_json(name: &str, request: Value, timeout: Duration) -> Result<Value> {
+    let entry = registry()
+        .read()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| Error::ServiceUnavailable(name.to_string()))?;
+    (entry.call_json)(&entry, name, request, timeout)?.await
+}
+
+/// Services with a server in this process, sorted by name
+pub(crate) fn service_infos() -> Vec<ServiceInfo> {
+    let mut services: Vec<ServiceInfo> = registry()
+        .read()
+        .iter()
+        .map(|(name, entry)| ServiceInfo {
+            name: name.clone(),
+            request_type: entry.request_type.to_string(),
+            response_type: entry.response_type.to_string(),
+        })
+        .collect();
+    services.sort_by(|a, b| a.name.cmp(&b.name));
+    services
+}
+
+/// Server answering requests with an async handler
+///
+/// Each request runs in its own task, so slow requests do not hold up
+/// others. A panicking handler fails only the request that caused it.
+/// Dropping the server unregisters it; calls still in its queue fail with
+/// [`Error::ServiceUnavailable`].
+pub struct ServiceServer<Req: Message, Res: Message> {
+    name: String,
+    entry: Arc<ServiceEntry>,
+    dispatcher: JoinHandle<()>,
+    stats: Arc<RwLock<ServiceStats>>,
+    #[cfg(feature = "transport-zenoh")]
+    queryable: Option<zenoh::query::Queryable<()>>,
+    _phantom: std::marker::PhantomData<fn(Req) -> Res>,
+}
+
+impl<Req: Message, Res: Message> ServiceServer<Req, Res> {
+    /// Create a server handling any number of requests at once
+    ///
+    /// Fails with [`Error::Configuration`] if the service already has a
+    /// server. Must be called from within a Tokio runtime.
+    pub fn new<F, Fut>(name: impl Into<String>, handler: F) -> Result<Self>
+    where
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res>> + Send + 'static,
+    {
+        Self::create(name.into(), None, handler)
+    }
+
+    /// Create a server handling at most `limit` requests at once; further
+    /// requests wait in its queue
+    pub fn with_concurrency<F, Fut>(
+        name: impl Into<String>,
+        limit: usize,
+        handler: F,
+    ) -> Result<Self>
+    where
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res>> + Send + 'static,
+    {
+        Self::create(name.into(), Some(limit.max(1)), handler)
+    }
+
+    fn create<F, Fut>(name: String, limit: Option<usize>, handler: F) -> Result<Self>
+    where
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res>> + Send + 'static,
+    {
+        TopicName::new(name.as_str())?;
+        debug!("Creating service server: {}", name);
+
+        let (tx, rx) = mpsc::unbounded_channel::<Call<Req, Res>>();
+        let entry = Arc::new(ServiceEntry::new(tx));
+        entry.register(&name)?;
+
+        let stats = Arc::new(RwLock::new(ServiceStats::default()));
+        let dispatcher = tokio::spawn(Self::dispatch(
+            name.clone(),
+            rx,
+            entry.pending.clone(),
+            limit.map(|limit| Arc::new(Semaphore::new(limit))),
+            Arc::new(handler),
+            stats.clone(),
+        ));
+
+        Ok(Self {
+            name,
+            entry,
+            dispatcher,
+            stats,
+            #[cfg(feature = "transport-zenoh")]
+            queryable: None,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    /// Also answer calls from other processes arriving over `transport`
+    #[cfg(feature = "transport-zenoh")]
+    pub fn with_zenoh(mut self, transport: &ZenohTransport) -> Result<Self> {
+        self.queryable = Some(transport.serve::<Req, Res>(&self.name)?);
+        Ok(self)
+    }
+
+    async fn dispatch<F, Fut>(
+        name: String,
+        mut calls: mpsc::UnboundedReceiver<Call<Req, Res>>,
+        pending: Arc<AtomicUsize>,
+        limit: Option<Arc<Semaphore>>,
+        handler: Arc<F>,
+        stats: Arc<RwLock<ServiceStats>>,
+    ) where
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res>> + Send + 'static,
+    {
+        while let Some(call) = calls.recv().await {
+            let permit = match &limit {
+                Some(limit) => match limit.clone().acquire_owned().await {
+                    Ok(permit) => Some(permit),
+                    Err(_) => return,
+                },
+                None => None,
+            };
+            pending.fetch_sub(1, Ordering::AcqRel);
+
+            let (name, handler, stats) = (name.clone(), handler.clone(), stats.clone());
+            tokio::spawn(async move {
+                // A separate task turns a handler panic into a join error
+                let result = match tokio::spawn(handler(call.request)).await {
+                    Ok(result) => result,
+                    Err(e) if e.is_panic() => Err(Error::ServicePanicked(name)),
+                    Err(_) => Err(Error::ServiceUnavailable(name)),
+                };
+                {
+                    let mut stats = stats.write();
+                    stats.requests_handled += 1;
+                    if result.is_err() {
+                        stats.errors += 1;
+                    }
+                }
+                let _ = call.reply.send(result);
+                drop(permit);
+            });
+        }
+    }
+
+    /// Number of requests received but not yet being handled
+    pub fn queue_len(&self) -> usize {
+        self.entry.pending.load(Ordering::Acquire)
+    }
+
+    /// Get service name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get statistics (requests handled, errors)
+    pub fn stats(&self) -> (u64, u64) {
+        let stats = self.stats.read();
+        (stats.requests_handled, stats.errors)
+    }
+}
+
+impl<Req: Message, Res: Message> Drop for ServiceServer<Req, Res> {
+    fn drop(&mut self) {
+        self.entry.unregister(&self.name);
+        self.dispatcher.abort();
+    }
+}
+
+/// Server whose requests are taken and answered by the caller
+///
+/// Nothing is spawned: requests wait in the server's queue until
+/// [`try_take`](Self::try_take) hands them out, so a single-threaded loop
+/// can serve them next to its subscribers through a
+/// [`WaitSet`](crate::wait::WaitSet). Dropping the server unregisters it;
+/// calls still in its queue fail with [`Error::ServiceUnavailable`].
+pub struct PolledServiceServer<Req: Message, Res: Message> {
+    name: String,
+    entry: Arc<ServiceEntry>,
+    calls: Mutex<mpsc::UnboundedReceiver<Call<Req, Res>>>,
+    stats: Arc<RwLock<ServiceStats>>,
+}
+
+impl<Req: Message, Res: Message> PolledServiceServer<Req, Res> {
+    /// Register a server for `name`
+    ///
+    /// Fails with [`Error::Configuration`] if the service already has a
+    /// server.
+    pub fn new(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        TopicName::new(name.as_str())?;
+        debug!("Creating polled service server: {}", name);
+
+        let (tx, rx) = mpsc::unbounded_channel::<Call<Req, Res>>();
+        let entry = Arc::new(ServiceEntry::new(tx));
+        entry.register(&name)?;
+        Ok(Self {
+            name,
+            entry,
+            calls: Mutex::new(rx),
+            stats: Arc::new(RwLock::new(ServiceStats::default())),
+        })
+    }
+
+    /// Take the oldest waiting request, if any
+    pub fn try_take(&self) -> Option<ServiceRequest<Req, Res>> {
+        let call = self.calls.lock().try_recv().ok()?;
+        self.entry.pending.fetch_sub(1, Ordering::AcqRel);
+        Some(ServiceRequest {
+            request: call.request,
+            reply: call.reply,
+            stats: self.stats.clone(),
+        })
+    }
+
+    /// Number of requests waiting to be taken
+    pub fn queue_len(&self) -> usize {
+        self.entry.pending.load(Ordering::Acquire)
+    }
+
+    /// Get service name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get statistics (requests answered, errors)
+    pub fn stats(&self) -> (u64, u64) {
+        let stats = self.stats.read();
+        (stats.requests_handled, stats.errors)
+    }
+
+    pub(crate) fn waitable(&self) -> Arc<dyn Waitable> {
+        self.entry.clone()
+    }
+}
+
+impl<Req: Message, Res: Message> Drop for PolledServiceServer<Req, Res> {
+    fn drop(&mut self) {
+        self.entry.unregister(&self.name);
+    }
+}
+
+/// A request taken from a [`PolledServiceServer`]
+///
+/// Dropping it without responding fails the call with
+/// [`Error::ServiceUnavailable`].
+pub struct ServiceRequest<Req, Res> {
+    request: Req,
+    reply: oneshot::Sender<Result<Res>>,
+    stats: Arc<RwLock<ServiceStats>>,
+}
+
+impl<Req, Res> ServiceRequest<Req, Res> {
+    pub fn request(&self) -> &Req {
+        &self.request
+    }
+
+    /// Send the response to the caller, which may have given up waiting
+    pub fn respond(self, response: Result<Res>) {
+        {
+            let mut stats = self.stats.write();
+            stats.requests_handled += 1;
+            if response.is_err() {
+                stats.errors += 1;
+            }
+        }
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Client calling a [`ServiceServer`]
+pub struct ServiceClient<Req: Message, Res: Message> {
+    name: String,
+    timeout: Duration,
+    #[cfg(feature = "transport-zenoh")]
+    zenoh: Option<Arc<ZenohTransport>>,
+    _phantom: std::marker::PhantomData<fn(Req) -> Res>,
+}
+
+impl<Req: Message, Res: Message> ServiceClient<Req, Res> {
+    /// Default time a call waits for its response
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Create a client for a service, which need not have a server yet
+    pub fn new(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        TopicName::new(name.as_str())?;
+        debug!("Creating service client: {}", name);
+
+        Ok(Self {
+            name,
+            timeout: Self::DEFAULT_TIMEOUT,
+            #[cfg(feature = "transport-zenoh")]
+            zenoh: None,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    /// Call servers in other processes over `transport` when the service
+    /// has no server in this one
+    #[cfg(feature = "transport-zenoh")]
+    pub fn with_zenoh(mut self, transport: Arc<ZenohTransport>) -> Self {
+        self.zenoh = Some(transport);
+        self
+    }
+
+    /// Set the timeout used by [`call`](Self::call)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Call the service, waiting at most the client's timeout
+    pub async fn call(&self, request: Req) -> Result<Res> {
+        self.call_with_timeout(request, self.timeout).await
+    }
+
+    /// Call the service, waiting at most `timeout` for the response
+    ///
+    /// Fails immediately with [`Error::ServiceUnavailable`] if no server is
+    /// registered, with [`Error::TopicTypeMismatch`] if the server uses
+    /// different request or response types, and with [`Error::Timeout`] if
+    /// the response does not arrive in time.
+    pub async fn call_with_timeout(&self, request: Req, timeout: Duration) -> Result<Res> {
+        let entry = registry().read().get(&self.name).cloned();
+        #[cfg(feature = "transport-zenoh")]
+        if let (None, Some(zenoh)) = (&entry, &self.zenoh) {
+            return zenoh.call(&self.name, &request, timeout).await;
+        }
+        let entry = entry.ok_or_else(|| Error::ServiceUnavailable(self.name.clone()))?;
+        let pending = entry.send::<Req, Res>(&self.name, request)?;
+        response(&self.name, pending, timeout).await
+    }
+
+    /// Whether a server is currently registered for the service
+    pub fn is_available(&self) -> bool {
+        registry().read().contains_key(&self.name)
     }
 
     /// Get service name
@@ -124,4 +649,169 @@ mod tests {
         let service = Service::<RobotState, RobotState>::new("compute");
         assert_eq!(service.name(), "compute");
     }
+
+    fn state(timestamp: i64) -> RobotState {
+        RobotState {
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls() {
+        let server = ServiceServer::new("test/service/concurrent", |req: RobotState| async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(state(req.timestamp * 2))
+        })
+        .unwrap();
+        let client = Arc::new(
+            ServiceClient::<RobotState, RobotState>::new("test/service/concurrent")
+                .unwrap()
+                .with_timeout(Duration::from_secs(5)),
+        );
+
+        let start = std::time::Instant::now();
+        let calls: Vec<_> = (0..10)
+            .map(|i| {
+                let client = client.clone();
+                tokio::spawn(async move { client.call(state(i)).await })
+            })
+            .collect();
+        for (i, call) in calls.into_iter().enumerate() {
+            assert_eq!(call.await.unwrap().unwrap().timestamp, i as i64 * 2);
+        }
+
+        // Handled side by side, not one after another
+        assert!(start.elapsed() < Duration::from_millis(450));
+        assert_eq!(server.stats(), (10, 0));
+    }
+
+    #[tokio::test]
+    async fn test_call_without_server_fails_fast() {
+        let client = ServiceClient::<RobotState, RobotState>::new("test/service/missing").unwrap();
+        assert!(!client.is_available());
+
+        let result = client.call_with_timeout(state(0), Duration::from_secs(60)).await;
+        assert!(matches!(result, Err(Error::ServiceUnavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn test_handler_panic_is_returned_to_caller() {
+        let server = ServiceServer::new("test/service/panic", |req: RobotState| async move {
+            if req.timestamp < 0 {
+                panic!("negative timestamp");
+            }
+            Ok(req)
+        })
+        .unwrap();
+        let client = ServiceClient::<RobotState, RobotState>::new("test/service/panic").unwrap();
+
+        let result = client.call(state(-1)).await;
+        assert!(matches!(result, Err(Error::ServicePanicked(_))));
+
+        // The server keeps serving
+        assert_eq!(client.call(state(7)).await.unwrap().timestamp, 7);
+        assert_eq!(server.stats(), (2, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_call_timeout() {
+        let _server = ServiceServer::new("test/service/slow", |req: RobotState| async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(req)
+        })
+        .unwrap();
+        let client = ServiceClient::<RobotState, RobotState>::new("test/service/slow")
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+
+        let result = client.call(state(0)).await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_queue_len_with_concurrency_limit() {
+        let gate = Arc::new(tokio::sync::Notify::new());
+        let server = ServiceServer::with_concurrency("test/service/queue", 1, {
+            let gate = gate.clone();
+            move |req: RobotState| {
+                let gate = gate.clone();
+                async move {
+                    gate.notified().await;
+                    Ok(req)
+                }
+            }
+        })
+        .unwrap();
+        let client =
+            Arc::new(ServiceClient::<RobotState, RobotState>::new("test/service/queue").unwrap());
+
+        let calls: Vec<_> = (0..3)
+            .map(|i| {
+                let client = client.clone();
+                tokio::spawn(async move { client.call(state(i)).await })
+            })
+            .collect();
+        while server.queue_len() != 2 {
+            tokio::task::yield_now().await;
+        }
+
+        for call in calls {
+            // Release whichever request holds the single slot
+            while !call.is_finished() {
+                gate.notify_one();
+                tokio::task::yield_now().await;
+            }
+            assert!(call.await.unwrap().is_ok());
+        }
+        assert_eq!(server.queue_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_server_and_type_mismatch() {
+        use crate::message::PointCloud;
+
+        let _server =
+            ServiceServer::new("test/service/typed", |req: RobotState| async move { Ok(req) })
+                .unwrap();
+        let duplicate =
+            ServiceServer::new("test/service/typed", |req: RobotState| async move { Ok(req) });
+        assert!(matches!(duplicate, Err(Error::Configuration(_))));
+
+        let client = ServiceClient::<PointCloud, RobotState>::new("test/service/typed").unwrap();
+        let result = client.call(PointCloud::default()).await;
+        assert!(matches!(result, Err(Error::TopicTypeMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_call_json() {
+        let _server = ServiceServer::new("test/service/json", |req: RobotState| async move {
+            Ok(RobotState {
+                timestamp: req.timestamp + 1,
+                ..req
+            })
+        })
+        .unwrap();
+        let info = crate::graph::list_services()
+            .into_iter()
+            .find(|info| info.name == "test/service/json")
+            .unwrap();
+        assert_eq!(info.request_type, RobotState::type_name());
+        assert_eq!(info.response_type, RobotState::type_name());
+
+        let request = serde_json::json!({
+            "position": [1.0, 0.0, 0.0],
+            "velocity": [0.0, 0.0, 0.0],
+            "timestamp": 41,
+        });
+        let timeout = Duration::from_secs(1);
+        let response = call_json("test/service/json", request, timeout).await.unwrap();
+        assert_eq!(response["timestamp"], 42);
+        assert_eq!(response["position"], serde_json::json!([1.0, 0.0, 0.0]));
+
+        let invalid = call_json("test/service/json", serde_json::json!({}), timeout).await;
+        assert!(matches!(invalid, Err(Error::Serialization(_))));
+        let missing = call_json("test/service/none", Value::Null, timeout).await;
+        assert!(matches!(missing, Err(Error::ServiceUnavailable(_))));
+    }
 }
@@ -0,0 +1,1085 @@
+//! Shared-memory transport
+//!
+//! Carries a topic between processes on the same host without copying it
+//! through the network stack. The publishing process writes serialized
+//! samples into a ring of fixed-size slots in a file under
+//! [`ShmConfig::dir`]; subscribing processes map the ring read-only and are
+//! woken through a Unix datagram socket per reader. Readers never block the
+//! publisher: a reader that falls more than a ring behind loses the oldest
+//! samples, which shows up in its [`SubscriberStats`](crate::SubscriberStats).
+//!
+//! Readers register in a small control file next to the ring. Slots of
+//! readers that stop refreshing their heartbeat, e.g. because the process
+//! died, are reclaimed by the publisher. A restarted publisher bumps the
+//! ring's generation, telling readers to drop their position and remap.
+
+use crate::error::{Error, Result};
+use crate::message::{fnv1a, is_raw, Message};
+use crate::qos::{QosProfile, Reliability};
+use crate::serialization::Format;
+use crate::subscriber::OverflowPolicy;
+use crate::time::{ClockType, Time};
+use crate::topic::{PublisherGuid, Sample, SampleQueue, Stamp, Topic, TopicBus, TopicType, Writer};
+use memmap2::{Mmap, MmapMut};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::net::UnixDatagram as StdUnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UnixDatagram;
+use tokio::task::AbortHandle;
+use tracing::{debug, warn};
+
+/// Node name shown in the graph for the transport's own endpoints
+const TRANSPORT_NODE: &str = "_shm_transport";
+
+/// How often readers and publishers refresh their heartbeat
+const HEARTBEAT: Duration = Duration::from_millis(100);
+
+/// Heartbeat age after which a reader or publisher is considered dead
+const STALE_AFTER: Duration = Duration::from_secs(1);
+
+/// Reader slots in a control file
+const MAX_READERS: usize = 32;
+
+const MAGIC: u64 = u64::from_le_bytes(*b"ROS3SHM1");
+
+// Ring header, all fields 8-byte aligned
+const RING_HEADER_LEN: usize = 256;
+const H_MAGIC: usize = 0;
+const H_GENERATION: usize = 8;
+const H_SLOTS: usize = 16;
+const H_SLOT_SIZE: usize = 24;
+/// Sequence number of the next frame written
+const H_WRITE_SEQ: usize = 32;
+const H_OWNER_PID: usize = 40;
+const H_OWNER_BEAT: usize = 48;
+const H_TYPE_HASH: usize = 56;
+const H_TYPE_LEN: usize = 64;
+const H_TYPE_NAME: usize = 72;
+const MAX_TYPE_NAME: usize = RING_HEADER_LEN - H_TYPE_NAME;
+
+// Slot header; the payload follows it
+const SLOT_HEADER_LEN: usize = 64;
+/// Seqlock: `2 * seq + 1` while frame `seq` is written, `2 * seq + 2` once
+/// it is complete
+const S_STATE: usize = 0;
+const S_LEN: usize = 8;
+const S_PREFIX: usize = 16;
+const S_ID: usize = 24;
+const S_SEQ: usize = 32;
+const S_STAMP: usize = 40;
+/// Format and clock type codes
+const S_CODES: usize = 48;
+
+// Control file: one pid and heartbeat per reader
+const READER_LEN: usize = 16;
+const CONTROL_LEN: usize = MAX_READERS * READER_LEN;
+
+/// Settings of a [`ShmTransport`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShmConfig {
+    /// Directory holding the ring and control files; should be a tmpfs
+    pub dir: PathBuf,
+    /// Frames kept in the ring
+    pub slots: usize,
+    /// Largest serialized message; larger ones are dropped with a warning
+    pub slot_size: usize,
+}
+
+impl Default for ShmConfig {
+    fn default() -> Self {
+        let shm = Path::new("/dev/shm");
+        Self {
+            dir: if shm.is_dir() {
+                shm.to_path_buf()
+            } else {
+                std::env::temp_dir()
+            },
+            slots: 8,
+            slot_size: 4 << 20,
+        }
+    }
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+/// Whether the process behind a heartbeat is gone
+fn is_stale(pid: u64, beat: u64, now: u64) -> bool {
+    if now.saturating_sub(beat) > STALE_AFTER.as_nanos() as u64 {
+        return true;
+    }
+    cfg!(target_os = "linux") && !Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// File name stem for a topic's ring, readable and unique per topic
+fn segment_name(topic: &str) -> String {
+    let readable: String = topic
+        .trim_start_matches('/')
+        .chars()
+        .take(32)
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("ros3_{}_{:08x}", readable, fnv1a(topic.as_bytes()) as u32)
+}
+
+fn format_code(format: Format) -> u64 {
+    match format {
+        Format::Cdr => 0,
+        Format::Rkyv => 1,
+        Format::Json => 2,
+        Format::Protobuf => 3,
+        Format::MessagePack => 4,
+    }
+}
+
+fn format_from_code(code: u64) -> Option<Format> {
+    Some(match code {
+        0 => Format::Cdr,
+        1 => Format::Rkyv,
+        2 => Format::Json,
+        3 => Format::Protobuf,
+        4 => Format::MessagePack,
+        _ => return None,
+    })
+}
+
+fn clock_code(clock: ClockType) -> u64 {
+    match clock {
+        ClockType::SystemTime => 0,
+        ClockType::Steady => 1,
+        ClockType::Sim => 2,
+    }
+}
+
+fn clock_from_code(code: u64) -> Option<ClockType> {
+    Some(match code {
+        0 => ClockType::SystemTime,
+        1 => ClockType::Steady,
+        2 => ClockType::Sim,
+        _ => return None,
+    })
+}
+
+/// The 64-bit word at `offset` of a mapping
+///
+/// # Safety
+///
+/// `offset + 8` must lie within the mapping and `base + offset` must be
+/// 8-byte aligned. Mappings are page aligned and every offset used here is
+/// a multiple of 8.
+unsafe fn word<'a>(base: *const u8, offset: usize) -> &'a AtomicU64 {
+    &*(base.add(offset) as *const AtomicU64)
+}
+
+/// Geometry of a ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    slots: usize,
+    slot_size: usize,
+}
+
+impl Layout {
+    fn new(config: &ShmConfig) -> Result<Self> {
+        if config.slots == 0 || config.slot_size == 0 {
+            return Err(Error::Configuration(
+                "shared-memory ring needs at least one non-empty slot".into(),
+            ));
+        }
+        Ok(Self {
+            slots: config.slots,
+            slot_size: config.slot_size.next_multiple_of(8),
+        })
+    }
+
+    fn stride(&self) -> usize {
+        SLOT_HEADER_LEN + self.slot_size
+    }
+
+    fn len(&self) -> usize {
+        RING_HEADER_LEN + self.slots * self.stride()
+    }
+
+    fn slot(&self, seq: u64) -> usize {
+        RING_HEADER_LEN + (seq % self.slots as u64) as usize * self.stride()
+    }
+}
+
+/// Reader registrations shared by the publisher and readers of a topic
+struct Control {
+    map: MmapMut,
+}
+
+impl Control {
+    fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() < CONTROL_LEN as u64 {
+            file.set_len(CONTROL_LEN as u64)?;
+        }
+        // SAFETY: the file is at least CONTROL_LEN bytes and only accessed
+        // through atomics
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        Ok(Self { map })
+    }
+
+    fn pid(&self, slot: usize) -> &AtomicU64 {
+        // SAFETY: slot < MAX_READERS, so the word is inside the mapping
+        unsafe { word(self.map.as_ptr(), slot * READER_LEN) }
+    }
+
+    fn beat(&self, slot: usize) -> &AtomicU64 {
+        // SAFETY: as above
+        unsafe { word(self.map.as_ptr(), slot * READER_LEN + 8) }
+    }
+
+    /// Take a free slot, or one whose reader died
+    fn claim(&self, pid: u64) -> Option<usize> {
+        let now = now_nanos();
+        (0..MAX_READERS).find(|&slot| {
+            let current = self.pid(slot).load(Ordering::Acquire);
+            let beat = self.beat(slot).load(Ordering::Acquire);
+            if current != 0 && !is_stale(current, beat, now) {
+                return false;
+            }
+            // Fresh heartbeat first so the publisher does not reclaim the
+            // slot between the two stores
+            self.beat(slot).store(now, Ordering::Release);
+            self.pid(slot)
+                .compare_exchange(current, pid, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        })
+    }
+
+    fn release(&self, slot: usize) {
+        self.pid(slot).store(0, Ordering::Release);
+    }
+
+    /// Slots with a registered reader
+    fn readers(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..MAX_READERS).filter(|&slot| self.pid(slot).load(Ordering::Acquire) != 0)
+    }
+
+    /// Free the slots of readers that stopped refreshing their heartbeat
+    fn reclaim(&self) -> Vec<usize> {
+        let now = now_nanos();
+        self.readers()
+            .filter(|&slot| {
+                let pid = self.pid(slot).load(Ordering::Acquire);
+                let beat = self.beat(slot).load(Ordering::Acquire);
+                is_stale(pid, beat, now)
+                    && self
+                        .pid(slot)
+                        .compare_exchange(pid, 0, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok()
+            })
+            .collect()
+    }
+}
+
+/// Paths of a topic's files
+#[derive(Debug, Clone)]
+struct Segment {
+    ring: PathBuf,
+    control: PathBuf,
+    dir: PathBuf,
+    name: String,
+}
+
+impl Segment {
+    fn new(dir: &Path, topic: &str) -> Self {
+        let name = segment_name(topic);
+        Self {
+            ring: dir.join(format!("{}.ring", name)),
+            control: dir.join(format!("{}.ctl", name)),
+            dir: dir.to_path_buf(),
+            name,
+        }
+    }
+
+    /// Wakeup socket of a reader slot
+    fn socket(&self, slot: usize) -> PathBuf {
+        self.dir.join(format!("{}.{}.sock", self.name, slot))
+    }
+}
+
+/// A sample as stored in a ring slot
+#[derive(Debug)]
+struct Frame {
+    payload: Vec<u8>,
+    format: Format,
+    stamp: Stamp,
+}
+
+/// The publishing end of a ring
+struct RingWriter {
+    segment: Segment,
+    layout: Layout,
+    map: MmapMut,
+    /// Start of `map`, written through while readers map it concurrently
+    base: *mut u8,
+    control: Control,
+    wake: StdUnixDatagram,
+}
+
+// SAFETY: `base` points into `map`, which lives as long as the writer; all
+// writes through it are either atomic or guarded by the slot seqlock
+unsafe impl Send for RingWriter {}
+unsafe impl Sync for RingWriter {}
+
+impl RingWriter {
+    /// Create the ring, or take over one left behind by a previous publisher
+    fn open(segment: Segment, layout: Layout, type_name: &str, type_hash: u64) -> Result<Self> {
+        if type_name.len() > MAX_TYPE_NAME {
+            return Err(Error::Configuration(format!(
+                "type name '{}' too long for shared memory",
+                type_name
+            )));
+        }
+        let control = Control::open(&segment.control)?;
+        let pid = u64::from(std::process::id());
+
+        if let Ok(file) = OpenOptions::new().read(true).write(true).open(&segment.ring) {
+            // SAFETY: only the header is touched before its length is checked
+            let mut map = unsafe { MmapMut::map_mut(&file)? };
+            let base = map.as_mut_ptr();
+            if map.len() >= RING_HEADER_LEN
+                // SAFETY: the header is in bounds
+                && unsafe { word(base, H_MAGIC) }.load(Ordering::Acquire) == MAGIC
+            {
+                // SAFETY: the header is in bounds
+                let (owner, beat) = unsafe {
+                    (
+                        word(base, H_OWNER_PID).load(Ordering::Acquire),
+                        word(base, H_OWNER_BEAT).load(Ordering::Acquire),
+                    )
+                };
+                if owner != 0 && !is_stale(owner, beat, now_nanos()) {
+                    return Err(Error::Configuration(format!(
+                        "{} is already published by process {}",
+                        segment.ring.display(),
+                        owner
+                    )));
+                }
+                let writer = Self {
+                    segment: segment.clone(),
+                    layout,
+                    map,
+                    base,
+                    control,
+                    wake: StdUnixDatagram::unbound()?,
+                };
+                if writer.map.len() == layout.len()
+                    && writer.header(H_SLOTS).load(Ordering::Acquire) == layout.slots as u64
+                    && writer.header(H_TYPE_HASH).load(Ordering::Acquire) == type_hash
+                {
+                    writer.restart(pid);
+                    return Ok(writer);
+                }
+                // Different geometry: readers of the old file reopen the path
+                writer.header(H_GENERATION).fetch_add(1, Ordering::AcqRel);
+                let Self { control, .. } = writer;
+                fs::remove_file(&segment.ring)?;
+                return Self::create(segment, layout, control, type_name, type_hash);
+            }
+            fs::remove_file(&segment.ring)?;
+        }
+        Self::create(segment, layout, control, type_name, type_hash)
+    }
+
+    fn create(
+        segment: Segment,
+        layout: Layout,
+        control: Control,
+        type_name: &str,
+        type_hash: u64,
+    ) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&segment.ring)?;
+        file.set_len(layout.len() as u64)?;
+        // SAFETY: the file was just sized to the layout
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[H_TYPE_NAME..H_TYPE_NAME + type_name.len()].copy_from_slice(type_name.as_bytes());
+
+        let writer = Self {
+            segment,
+            layout,
+            base: map.as_mut_ptr(),
+            map,
+            control,
+            wake: StdUnixDatagram::unbound()?,
+        };
+        writer.header(H_SLOTS).store(layout.slots as u64, Ordering::Relaxed);
+        writer.header(H_SLOT_SIZE).store(layout.slot_size as u64, Ordering::Relaxed);
+        writer.header(H_TYPE_HASH).store(type_hash, Ordering::Relaxed);
+        writer.header(H_TYPE_LEN).store(type_name.len() as u64, Ordering::Relaxed);
+        writer.header(H_GENERATION).store(now_nanos(), Ordering::Relaxed);
+        writer.heartbeat();
+        writer
+            .header(H_OWNER_PID)
+            .store(u64::from(std::process::id()), Ordering::Relaxed);
+        // Readers ignore the file until the magic is in place
+        writer.header(H_MAGIC).store(MAGIC, Ordering::Release);
+        debug!("Created shared-memory ring {}", writer.segment.ring.display());
+        Ok(writer)
+    }
+
+    /// Reset a ring left behind by a previous publisher
+    fn restart(&self, pid: u64) {
+        self.header(H_WRITE_SEQ).store(0, Ordering::Relaxed);
+        for slot in 0..self.layout.slots {
+            self.slot_word(slot as u64, S_STATE).store(0, Ordering::Relaxed);
+        }
+        self.heartbeat();
+        self.header(H_OWNER_PID).store(pid, Ordering::Relaxed);
+        let generation = self.header(H_GENERATION).fetch_add(1, Ordering::AcqRel) + 1;
+        debug!(
+            "Took over shared-memory ring {} (generation {})",
+            self.segment.ring.display(),
+            generation
+        );
+    }
+
+    fn header(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: header offsets are below RING_HEADER_LEN
+        unsafe { word(self.base, offset) }
+    }
+
+    fn slot_word(&self, seq: u64, offset: usize) -> &AtomicU64 {
+        // SAFETY: `slot` stays within the layout the mapping was sized to
+        unsafe { word(self.base, self.layout.slot(seq) + offset) }
+    }
+
+    fn heartbeat(&self) {
+        self.header(H_OWNER_BEAT).store(now_nanos(), Ordering::Release);
+    }
+
+    fn write(&self, payload: &[u8], format: Format, stamp: Stamp) -> Result<()> {
+        if payload.len() > self.layout.slot_size {
+            return Err(Error::Configuration(format!(
+                "{} byte message exceeds the shared-memory slot size of {} bytes",
+                payload.len(),
+                self.layout.slot_size
+            )));
+        }
+        let seq = self.header(H_WRITE_SEQ).load(Ordering::Relaxed);
+        let state = self.slot_word(seq, S_STATE);
+        state.store(2 * seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        self.slot_word(seq, S_LEN).store(payload.len() as u64, Ordering::Relaxed);
+        self.slot_word(seq, S_PREFIX).store(stamp.publisher.prefix, Ordering::Relaxed);
+        self.slot_word(seq, S_ID).store(stamp.publisher.id, Ordering::Relaxed);
+        self.slot_word(seq, S_SEQ).store(stamp.seq, Ordering::Relaxed);
+        self.slot_word(seq, S_STAMP)
+            .store(stamp.time.as_nanos() as u64, Ordering::Relaxed);
+        let codes = format_code(format) | clock_code(stamp.clock) << 8;
+        self.slot_word(seq, S_CODES).store(codes, Ordering::Relaxed);
+        let start = self.layout.slot(seq) + SLOT_HEADER_LEN;
+        // SAFETY: the payload fits the slot, and only this writer writes
+        // the ring; readers detect torn reads through the seqlock
+        unsafe {
+            std::ptr::copy_nonoverlapping(payload.as_ptr(), self.base.add(start), payload.len());
+        }
+
+        state.store(2 * seq + 2, Ordering::Release);
+        self.header(H_WRITE_SEQ).store(seq + 1, Ordering::Release);
+        self.wake_readers();
+        Ok(())
+    }
+
+    fn wake_readers(&self) {
+        for slot in self.control.readers() {
+            // A full socket buffer means the reader has wakeups pending
+            let _ = self.wake.send_to(&[1], self.segment.socket(slot));
+        }
+    }
+
+    /// Free the slots of dead readers, removing their sockets
+    fn reclaim(&self) {
+        for slot in self.control.reclaim() {
+            debug!("Reclaimed stale reader slot {} of {}", slot, self.segment.name);
+            let _ = fs::remove_file(self.segment.socket(slot));
+        }
+    }
+
+    /// Give up the ring, removing it if nobody reads it
+    fn release(&self) {
+        self.header(H_OWNER_PID).store(0, Ordering::Release);
+        if self.control.readers().next().is_none() {
+            let _ = fs::remove_file(&self.segment.ring);
+        }
+    }
+}
+
+/// The subscribing end of a ring, mapped read-only
+struct RingReader {
+    layout: Layout,
+    map: Mmap,
+    generation: u64,
+    type_name: Arc<str>,
+    type_hash: u64,
+    next: u64,
+}
+
+impl RingReader {
+    /// Map the ring at `path`, or `None` if no publisher created it yet
+    ///
+    /// Reading starts at the next frame written.
+    fn open(path: &Path) -> Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // SAFETY: read-only mapping; lengths are checked before any access
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < RING_HEADER_LEN {
+            return Ok(None);
+        }
+        let base = map.as_ptr();
+        // SAFETY: the header is in bounds
+        let header = |offset| unsafe { word(base, offset) }.load(Ordering::Acquire);
+        if header(H_MAGIC) != MAGIC {
+            return Ok(None);
+        }
+        let layout = Layout {
+            slots: header(H_SLOTS) as usize,
+            slot_size: header(H_SLOT_SIZE) as usize,
+        };
+        let type_len = header(H_TYPE_LEN) as usize;
+        if layout.slots == 0 || map.len() < layout.len() || type_len > MAX_TYPE_NAME {
+            return Ok(None);
+        }
+        let type_name = String::from_utf8_lossy(&map[H_TYPE_NAME..H_TYPE_NAME + type_len]);
+        Ok(Some(Self {
+            layout,
+            generation: header(H_GENERATION),
+            type_name: Arc::from(type_name.as_ref()),
+            type_hash: header(H_TYPE_HASH),
+            next: header(H_WRITE_SEQ),
+            map,
+        }))
+    }
+
+    fn header(&self, offset: usize) -> u64 {
+        // SAFETY: header offsets are below RING_HEADER_LEN
+        unsafe { word(self.map.as_ptr(), offset) }.load(Ordering::Acquire)
+    }
+
+    fn slot_word(&self, seq: u64, offset: usize) -> &AtomicU64 {
+        // SAFETY: the mapping was checked to cover the layout
+        unsafe { word(self.map.as_ptr(), self.layout.slot(seq) + offset) }
+    }
+
+    /// Whether the publisher restarted or replaced the ring since it was
+    /// mapped
+    fn is_reset(&self) -> bool {
+        self.header(H_GENERATION) != self.generation
+    }
+
+    /// Append the frames written since the last call to `frames`
+    ///
+    /// Frames overwritten before they were read are skipped. Returns `false`
+    /// once the ring was reset, after which the reader must be reopened.
+    fn read(&mut self, frames: &mut Vec<Frame>) -> bool {
+        if self.is_reset() {
+            return false;
+        }
+        let written = self.header(H_WRITE_SEQ);
+        let oldest = written.saturating_sub(self.layout.slots as u64);
+        for seq in self.next.max(oldest)..written {
+            frames.extend(self.read_frame(seq));
+        }
+        self.next = written;
+        true
+    }
+
+    fn read_frame(&self, seq: u64) -> Option<Frame> {
+        let state = self.slot_word(seq, S_STATE);
+        if state.load(Ordering::Acquire) != 2 * seq + 2 {
+            return None;
+        }
+        let len = self.slot_word(seq, S_LEN).load(Ordering::Relaxed) as usize;
+        let codes = self.slot_word(seq, S_CODES).load(Ordering::Relaxed);
+        let stamp = Stamp {
+            publisher: PublisherGuid {
+                prefix: self.slot_word(seq, S_PREFIX).load(Ordering::Relaxed),
+                id: self.slot_word(seq, S_ID).load(Ordering::Relaxed),
+            },
+            seq: self.slot_word(seq, S_SEQ).load(Ordering::Relaxed),
+            time: Time::from_nanos(self.slot_word(seq, S_STAMP).load(Ordering::Relaxed) as i64),
+            clock: clock_from_code(codes >> 8 & 0xff)?,
+        };
+        let format = format_from_code(codes & 0xff)?;
+        if len > self.layout.slot_size {
+            return None;
+        }
+        let start = self.layout.slot(seq) + SLOT_HEADER_LEN;
+        let mut payload = vec![0u8; len];
+        // SAFETY: `len` fits the slot; the publisher may be overwriting it,
+        // which the seqlock check below detects
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.map.as_ptr().add(start), payload.as_mut_ptr(), len);
+        }
+
+        // The publisher lapped us while copying
+        fence(Ordering::Acquire);
+        if state.load(Ordering::Relaxed) != 2 * seq + 2 {
+            return None;
+        }
+        Some(Frame {
+            payload,
+            format,
+            stamp,
+        })
+    }
+}
+
+/// Writes a topic's local samples into its ring
+struct Outgoing {
+    link: Arc<Topic>,
+    queue: Arc<SampleQueue>,
+    ring: Arc<RingWriter>,
+    task: AbortHandle,
+}
+
+impl Outgoing {
+    fn close(&self) {
+        self.task.abort();
+        TopicBus::global().detach_subscriber(&self.link, &self.queue);
+        self.ring.release();
+    }
+}
+
+/// Injects samples read from a topic's ring into the local bus
+struct Incoming {
+    link: Arc<Topic>,
+    writer: Arc<Writer>,
+    control: Arc<Control>,
+    socket: PathBuf,
+    slot: usize,
+    task: AbortHandle,
+}
+
+impl Incoming {
+    fn close(&self) {
+        self.task.abort();
+        TopicBus::global().detach_publisher(&self.link, &self.writer);
+        self.control.release(self.slot);
+        let _ = fs::remove_file(&self.socket);
+    }
+}
+
+struct ShmState {
+    config: ShmConfig,
+    layout: Layout,
+    prefix: u64,
+    outgoing: Mutex<HashMap<String, Outgoing>>,
+    incoming: Mutex<HashMap<String, Incoming>>,
+}
+
+/// Process-wide shared-memory bridge for the topic bus
+///
+/// Topics are moved onto shared memory one at a time with
+/// [`Node::with_shared_memory`](crate::Node::with_shared_memory). Each
+/// topic has at most one publishing process.
+pub struct ShmTransport {
+    state: Arc<ShmState>,
+    maintenance: AbortHandle,
+}
+
+impl ShmTransport {
+    /// The transport for `config`, starting it if no node uses it yet
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn shared(config: &ShmConfig) -> Result<Arc<Self>> {
+        static TRANSPORTS: OnceLock<Mutex<HashMap<ShmConfig, Weak<ShmTransport>>>> =
+            OnceLock::new();
+        let mut transports = TRANSPORTS.get_or_init(Default::default).lock();
+        if let Some(transport) = transports.get(config).and_then(Weak::upgrade) {
+            return Ok(transport);
+        }
+        let state = Arc::new(ShmState {
+            layout: Layout::new(config)?,
+            config: config.clone(),
+            prefix: PublisherGuid::local_prefix(),
+            outgoing: Mutex::new(HashMap::new()),
+            incoming: Mutex::new(HashMap::new()),
+        });
+        let maintenance = tokio::spawn(state.clone().maintain()).abort_handle();
+        let transport = Arc::new(Self { state, maintenance });
+        transports.insert(config.clone(), Arc::downgrade(&transport));
+        Ok(transport)
+    }
+
+    /// Write a local publisher's samples to the topic's ring
+    ///
+    /// Fails if another live process already publishes the topic over
+    /// shared memory.
+    pub fn advertise_publisher<T: Message>(&self, topic: &str) -> Result<()> {
+        let state = &self.state;
+        let mut outgoing = state.outgoing.lock();
+        if outgoing.contains_key(topic) {
+            return Ok(());
+        }
+        let segment = Segment::new(&state.config.dir, topic);
+        let ring = Arc::new(RingWriter::open(
+            segment,
+            state.layout,
+            T::type_name(),
+            T::type_hash(),
+        )?);
+        let queue = Arc::new(
+            SampleQueue::new(Some(state.layout.slots), OverflowPolicy::DropOldest)
+                .with_qos(QosProfile {
+                    reliability: Reliability::BestEffort,
+                    history_depth: state.layout.slots,
+                    ..Default::default()
+                })
+                .with_node(Some(TRANSPORT_NODE.to_string()))
+                .forwarding(),
+        );
+        let link =
+            match TopicBus::global().attach_subscriber(topic, TopicType::of::<T>(), queue.clone()) {
+                Ok(link) => link,
+                Err(e) => {
+                    ring.release();
+                    return Err(e);
+                }
+            };
+        let forward = state
+            .clone()
+            .forward(topic.to_string(), queue.clone(), ring.clone());
+        let task = tokio::spawn(forward);
+        outgoing.insert(
+            topic.to_string(),
+            Outgoing {
+                link,
+                queue,
+                ring,
+                task: task.abort_handle(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Receive samples written to the topic's ring by other processes
+    pub fn advertise_subscriber<T: Message>(&self, topic: &str) -> Result<()> {
+        let state = &self.state;
+        let mut incoming = state.incoming.lock();
+        if incoming.contains_key(topic) {
+            return Ok(());
+        }
+        let segment = Segment::new(&state.config.dir, topic);
+        let control = Arc::new(Control::open(&segment.control)?);
+        let slot = control
+            .claim(u64::from(std::process::id()))
+            .ok_or_else(|| {
+                Error::Configuration(format!(
+                    "all {} shared-memory reader slots of '{}' are taken",
+                    MAX_READERS, topic
+                ))
+            })?;
+        let socket_path = segment.socket(slot);
+        let _ = fs::remove_file(&socket_path);
+        let socket = match UnixDatagram::bind(&socket_path) {
+            Ok(socket) => socket,
+            Err(e) => {
+                control.release(slot);
+                return Err(e.into());
+            }
+        };
+        let writer = Arc::new(Writer::new(
+            QosProfile::default(),
+            Some(TRANSPORT_NODE.to_string()),
+        ));
+        let link = match TopicBus::global().attach_publisher(topic, None, writer.clone()) {
+            Ok(link) => link,
+            Err(e) => {
+                control.release(slot);
+                let _ = fs::remove_file(&socket_path);
+                return Err(e);
+            }
+        };
+
+        let local_hash = (!is_raw::<T>()).then(T::type_hash);
+        let reader = Reader {
+            state: state.clone(),
+            segment,
+            control: control.clone(),
+            slot,
+            socket,
+            local_hash,
+        };
+        let task = tokio::spawn(reader.run(link.clone(), writer.clone()));
+        incoming.insert(
+            topic.to_string(),
+            Incoming {
+                link,
+                writer,
+                control,
+                socket: socket_path,
+                slot,
+                task: task.abort_handle(),
+            },
+        );
+        Ok(())
+    }
+}
+
+impl Drop for ShmTransport {
+    fn drop(&mut self) {
+        self.maintenance.abort();
+        for (_, outgoing) in self.state.outgoing.lock().drain() {
+            outgoing.close();
+        }
+        for (_, incoming) in self.state.incoming.lock().drain() {
+            incoming.close();
+        }
+    }
+}
+
+impl ShmState {
+    /// Refresh heartbeats, reclaim dead readers and drop topics whose local
+    /// endpoints are all gone
+    async fn maintain(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(HEARTBEAT);
+        loop {
+            interval.tick().await;
+            let bus = TopicBus::global();
+            let mut outgoing = self.outgoing.lock();
+            let mut incoming = self.incoming.lock();
+            outgoing.retain(|topic, bridge| {
+                let own = usize::from(incoming.contains_key(topic));
+                let alive = bus.publisher_count(topic) > own;
+                if alive {
+                    bridge.ring.heartbeat();
+                    bridge.ring.reclaim();
+                } else {
+                    bridge.close();
+                }
+                alive
+            });
+            incoming.retain(|topic, bridge| {
+                let own = usize::from(outgoing.contains_key(topic));
+                let alive = bus.subscriber_count(topic) > own;
+                if !alive {
+                    bridge.close();
+                }
+                alive
+            });
+        }
+    }
+
+    /// Copy a topic's local samples into its ring
+    async fn forward(
+        self: Arc<Self>,
+        topic: String,
+        queue: Arc<SampleQueue>,
+        ring: Arc<RingWriter>,
+    ) {
+        while let Some(sample) = queue.pop().await {
+            let Some(stamp) = sample.stamp() else {
+                continue;
+            };
+            // Samples read from the ring are not written back into it
+            if stamp.publisher.prefix != self.prefix {
+                continue;
+            }
+            let written = sample
+                .bytes()
+                .and_then(|payload| ring.write(&payload, sample.format(), stamp));
+            if let Err(e) = written {
+                warn!("Writing '{}' to shared memory failed: {}", topic, e);
+            }
+        }
+    }
+}
+
+/// A subscribing process's view of one topic's ring
+struct Reader {
+    state: Arc<ShmState>,
+    segment: Segment,
+    control: Arc<Control>,
+    slot: usize,
+    socket: UnixDatagram,
+    /// Type hash of the local subscribers, `None` if they accept any type
+    local_hash: Option<u64>,
+}
+
+impl Reader {
+    async fn run(self, link: Arc<Topic>, writer: Arc<Writer>) {
+        let mut ring: Option<RingReader> = None;
+        let mut frames = Vec::new();
+        let mut wakeup = [0u8; 16];
+        loop {
+            // Woken per frame; the timeout keeps the heartbeat fresh
+            let _ = tokio::time::timeout(HEARTBEAT, self.socket.recv(&mut wakeup)).await;
+            self.control.beat(self.slot).store(now_nanos(), Ordering::Release);
+
+            if let Some(reader) = &mut ring {
+                if !reader.read(&mut frames) {
+                    debug!("Shared-memory ring {} was reset", self.segment.name);
+                    ring = None;
+                }
+            }
+            if ring.is_none() {
+                ring = self.open();
+                continue;
+            }
+            let Some(reader) = &ring else {
+                continue;
+            };
+            for frame in frames.drain(..) {
+                if frame.stamp.publisher.prefix == self.state.prefix {
+                    continue;
+                }
+                let sample = Sample::new(frame.payload, frame.format, reader.type_name.clone())
+                    .with_stamp(frame.stamp);
+                if let Err(e) = link.deliver(&writer, sample).await {
+                    warn!("Delivering shared-memory sample on '{}' failed: {}", link.name(), e);
+                }
+            }
+        }
+    }
+
+    fn open(&self) -> Option<RingReader> {
+        let reader = match RingReader::open(&self.segment.ring) {
+            Ok(reader) => reader?,
+            Err(e) => {
+                warn!("Cannot map {}: {}", self.segment.ring.display(), e);
+                return None;
+            }
+        };
+        if self.local_hash.is_some_and(|hash| hash != reader.type_hash) {
+            warn!(
+                "Ignoring {}: type {} does not match local subscribers",
+                self.segment.ring.display(),
+                reader.type_name
+            );
+            return None;
+        }
+        Some(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+
+    fn segment(test: &str) -> Segment {
+        let dir = std::env::temp_dir().join(format!("ros3_shm_{}_{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        Segment::new(&dir, "/test/shm/state")
+    }
+
+    fn stamp(seq: u64) -> Stamp {
+        Stamp {
+            publisher: PublisherGuid { prefix: 1, id: 2 },
+            seq,
+            time: Time::from_nanos(seq as i64 * 10),
+            clock: ClockType::Steady,
+        }
+    }
+
+    fn open_writer(segment: &Segment) -> Result<RingWriter> {
+        let layout = Layout {
+            slots: 4,
+            slot_size: 64,
+        };
+        RingWriter::open(
+            segment.clone(),
+            layout,
+            RobotState::type_name(),
+            RobotState::type_hash(),
+        )
+    }
+
+    #[test]
+    fn test_ring_roundtrip_and_overrun() {
+        let segment = segment("roundtrip");
+        let writer = open_writer(&segment).unwrap();
+        let mut reader = RingReader::open(&segment.ring).unwrap().unwrap();
+        assert_eq!(&*reader.type_name, RobotState::type_name());
+
+        writer.write(b"first", Format::Json, stamp(0)).unwrap();
+        let mut frames = Vec::new();
+        assert!(reader.read(&mut frames));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"first");
+        assert_eq!(frames[0].format, Format::Json);
+        assert_eq!(frames[0].stamp, stamp(0));
+
+        // Six frames into four slots: the reader loses the two oldest
+        for seq in 1..=6 {
+            writer.write(&[seq as u8; 8], Format::Cdr, stamp(seq)).unwrap();
+        }
+        frames.clear();
+        assert!(reader.read(&mut frames));
+        let seqs: Vec<u64> = frames.iter().map(|f| f.stamp.seq).collect();
+        assert_eq!(seqs, [3, 4, 5, 6]);
+
+        assert!(writer.write(&[0; 65], Format::Cdr, stamp(7)).is_err());
+        fs::remove_dir_all(&segment.dir).unwrap();
+    }
+
+    #[test]
+    fn test_restarted_publisher_resets_readers() {
+        let segment = segment("restart");
+        let writer = open_writer(&segment).unwrap();
+        writer.write(b"old", Format::Cdr, stamp(0)).unwrap();
+        let mut reader = RingReader::open(&segment.ring).unwrap().unwrap();
+
+        // A second publisher cannot take over a live one
+        assert!(matches!(open_writer(&segment), Err(Error::Configuration(_))));
+
+        // Simulate the publisher dying without releasing the ring
+        writer.header(H_OWNER_BEAT).store(0, Ordering::Release);
+        let restarted = open_writer(&segment).unwrap();
+        assert!(!reader.read(&mut Vec::new()));
+
+        let mut reader = RingReader::open(&segment.ring).unwrap().unwrap();
+        restarted.write(b"new", Format::Cdr, stamp(0)).unwrap();
+        let mut frames = Vec::new();
+        assert!(reader.read(&mut frames));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"new");
+        fs::remove_dir_all(&segment.dir).unwrap();
+    }
+
+    #[test]
+    fn test_stale_reader_slots_are_reclaimed() {
+        let segment = segment("reclaim");
+        let writer = open_writer(&segment).unwrap();
+        let control = Control::open(&segment.control).unwrap();
+        let pid = u64::from(std::process::id());
+
+        let live = control.claim(pid).unwrap();
+        let dead = control.claim(pid).unwrap();
+        assert_ne!(live, dead);
+        // The dead reader stopped refreshing its heartbeat
+        control.beat(dead).store(0, Ordering::Release);
+
+        writer.reclaim();
+        assert_eq!(control.readers().collect::<Vec<_>>(), [live]);
+        assert_eq!(control.claim(pid), Some(dead));
+        fs::remove_dir_all(&segment.dir).unwrap();
+    }
+}
@@ -1,72 +1,984 @@
 //! Subscriber implementation
 
 use crate::error::{Error, Result};
-use crate::message::Message;
-use crossbeam::channel::{self, Receiver, Sender};
+use crate::message::{is_raw, Message, RawMessage, SharedRawMessage};
+use crate::name::TopicName;
+use crate::qos::{QosEvent, QosEventWatcher, QosProfile};
+use crate::serialization::Serializer;
+use crate::time::{Clock, ClockType, Time};
+use crate::topic::{
+    Admission, Direct, DirectHandler, PublisherGuid, Sample, SampleQueue, Stamp, Topic, TopicBus,
+    TopicType,
+};
+use crate::trace::{self, EventKind};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tracing::debug;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, field, info_span, trace, warn, Span};
+
+/// What happens when a message arrives at a full subscriber queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued message to make room. `recv()` always
+    /// yields the most recent messages; gaps show up in `dropped_count()`.
+    #[default]
+    DropOldest,
+    /// Discard the incoming message. `recv()` yields the queued messages in
+    /// order, then resumes once the consumer has made room.
+    DropNewest,
+    /// Make the publisher wait until the consumer has made room. No message
+    /// is lost, at the cost of slowing down every publisher on the topic.
+    Block,
+}
+
+/// How a subscriber's queue hands messages from publishers to the receiver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ChannelKind {
+    /// A deque behind a mutex, unbounded unless a depth is given
+    #[default]
+    Locked,
+    /// A lock-free [`mpsc`](crate::channel::mpsc) ring. Publishers and the
+    /// receiver never wait for each other's lock, and queuing does not
+    /// allocate. The depth is rounded up to a power of two, 1024 if none is
+    /// given.
+    LockFree,
+}
+
+/// Publish and receive metadata of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageInfo {
+    /// When the message was published, on the publisher's clock
+    pub publish_stamp: Time,
+    pub publish_clock: ClockType,
+    /// When the message was taken from the queue, on the subscriber's clock
+    pub receive_stamp: Time,
+    pub receive_clock: ClockType,
+    /// Position in the publisher's sequence of messages, from 0; gaps mean
+    /// messages were dropped or published before the subscriber joined
+    pub seq: u64,
+    /// The publisher that sent the message, `None` for unstamped samples
+    pub publisher: Option<PublisherGuid>,
+}
+
+impl MessageInfo {
+    /// Time from publish to receive, or `None` if the stamps come from
+    /// different kinds of clock and cannot be compared
+    pub fn latency(&self) -> Option<Duration> {
+        (self.publish_clock == self.receive_clock)
+            .then(|| self.receive_stamp.saturating_duration_since(self.publish_stamp))
+    }
+}
+
+/// Delivery statistics of a [`Subscriber`]
+///
+/// Each publisher numbers its messages, so gaps and reordering are detected
+/// per publisher. Counting starts with the first message received from a
+/// publisher.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubscriberStats {
+    pub received: u64,
+    /// Messages missing from a publisher's sequence; one that arrives late
+    /// stops being counted here
+    pub dropped: u64,
+    /// Messages that arrived after a later one from the same publisher
+    pub out_of_order: u64,
+    /// Highest sequence number received from each publisher
+    pub last_seq: HashMap<PublisherGuid, u64>,
+    /// Messages rejected by the subscriber's filter before being queued
+    pub filtered: u64,
+    /// Messages left out by throttling or decimation before being queued
+    pub skipped: u64,
+    /// Messages discarded because the filter or projection panicked
+    pub filter_panics: u64,
+}
+
+impl SubscriberStats {
+    fn observe(&mut self, stamp: Option<Stamp>) {
+        self.received += 1;
+        let Some(stamp) = stamp else {
+            return;
+        };
+        match self.last_seq.entry(stamp.publisher) {
+            Entry::Vacant(entry) => {
+                entry.insert(stamp.seq);
+            }
+            Entry::Occupied(mut entry) => {
+                let last = *entry.get();
+                if stamp.seq > last {
+                    self.dropped += stamp.seq - last - 1;
+                    entry.insert(stamp.seq);
+                } else {
+                    self.out_of_order += 1;
+                    if stamp.seq < last {
+                        self.dropped = self.dropped.saturating_sub(1);
+                    }
+                }
+            }
+        }
+    }
+}
 
 /// Subscriber for receiving messages
+///
+/// Clones share the same queue, so each message is handed to exactly one of
+/// them. Create a separate subscriber to get an independent copy of every
+/// message.
 pub struct Subscriber<T: Message> {
     topic: String,
-    receiver: Receiver<T>,
-    _sender: Arc<Sender<T>>, // Keep sender alive
+    inner: Arc<Registration>,
+    clock: Clock,
+    stages: Arc<Stages<T>>,
+    _phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+type Predicate<T> = Arc<dyn Fn(&T) -> bool + Send + Sync>;
+
+/// Turns an admitted message into the sample to queue
+type Project<'a, T> = &'a dyn Fn(&Sample, &T) -> Sample;
+
+/// What a message goes through in the publishing task before it is queued:
+/// the filter, then the throttle, then decimation
+struct Stages<T> {
+    filter: Option<Predicate<T>>,
+    throttle: Option<Throttle>,
+    decimation: Option<Decimation>,
+    /// Stamps messages that carry no publish stamp
+    clock: Clock,
+}
+
+/// Admits at most one message per `period`
+struct Throttle {
+    period: Duration,
+    last: Mutex<Option<Time>>,
+}
+
+/// Admits every `keep_every`th message
+struct Decimation {
+    keep_every: u64,
+    seen: AtomicU64,
+}
+
+impl<T: Message> Stages<T> {
+    fn is_empty(&self) -> bool {
+        self.filter.is_none() && self.throttle.is_none() && self.decimation.is_none()
+    }
+
+    /// Decide whether `sample` is queued, turning admitted messages into the
+    /// sample to queue with `project` if given
+    fn admit(&self, sample: Sample, project: Option<Project<'_, T>>) -> Admission {
+        let msg = if self.filter.is_some() || project.is_some() {
+            match Subscriber::<T>::decode_arc(&sample) {
+                Ok(msg) => Some(msg),
+                // Undecodable messages fail in `recv` as for any subscriber
+                Err(_) => return Admission::Queue(sample),
+            }
+        } else {
+            None
+        };
+        if let (Some(filter), Some(msg)) = (&self.filter, &msg) {
+            if !filter(msg) {
+                return Admission::Filtered;
+            }
+        }
+        if let Some(throttle) = &self.throttle {
+            let stamp = sample.stamp().map_or_else(|| self.clock.now(), |stamp| stamp.time);
+            if !throttle.admit(stamp) {
+                return Admission::Skipped;
+            }
+        }
+        if let Some(decimation) = &self.decimation {
+            if decimation.seen.fetch_add(1, Ordering::Relaxed) % decimation.keep_every != 0 {
+                return Admission::Skipped;
+            }
+        }
+        match (project, msg) {
+            (Some(project), Some(msg)) => Admission::Queue(project(&sample, &msg)),
+            _ => Admission::Queue(sample),
+        }
+    }
+}
+
+impl Throttle {
+    fn new(max_hz: f64) -> Result<Self> {
+        if !(max_hz.is_finite() && max_hz > 0.0) {
+            return Err(Error::Configuration(format!(
+                "throttle rate must be positive, got {max_hz} Hz"
+            )));
+        }
+        Ok(Self {
+            period: Duration::from_secs_f64(1.0 / max_hz),
+            last: Mutex::new(None),
+        })
+    }
+
+    /// Admit a message stamped `stamp` if a period has passed since the last
+    /// one admitted. A stamp going backwards, as after a simulation reset,
+    /// restarts the throttle.
+    fn admit(&self, stamp: Time) -> bool {
+        let mut last = self.last.lock();
+        match *last {
+            Some(previous)
+                if stamp >= previous
+                    && stamp.saturating_duration_since(previous) < self.period =>
+            {
+                false
+            }
+            _ => {
+                *last = Some(stamp);
+                true
+            }
+        }
+    }
+}
+
+/// Keeps the subscriber registered on the bus until the last clone is dropped
+struct Registration {
+    topic: Arc<Topic>,
+    queue: Arc<SampleQueue>,
+    stats: Mutex<SubscriberStats>,
+    deadline: Option<JoinHandle<()>>,
+    /// Parent of the subscriber's log events, with its `node` and `topic`
+    span: Span,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(deadline) = self.deadline.take() {
+            deadline.abort();
+        }
+        TopicBus::global().detach_subscriber(&self.topic, &self.queue);
+    }
+}
+
+/// Span for the log events of a subscriber on `topic`, owned by `node` if any
+fn subscriber_span(topic: &str, node: Option<&str>) -> Span {
+    let span = info_span!("subscriber", node = field::Empty, topic = %topic);
+    if let Some(node) = node {
+        span.record("node", node);
+    }
+    span
+}
+
+/// Raise [`QosEvent::DeadlineMissed`] for each `deadline` that passes
+/// without a sample entering `queue`
+async fn monitor_deadline(queue: Arc<SampleQueue>, deadline: Duration) {
+    let mut total = 0;
+    loop {
+        if tokio::time::timeout(deadline, queue.arrived()).await.is_err() {
+            total += 1;
+            queue.send_event(QosEvent::DeadlineMissed { total });
+        }
+    }
 }
 
 impl<T: Message> Subscriber<T> {
-    /// Create a new subscriber
-    pub fn new(topic: impl Into<String>) -> Self {
-        let topic = topic.into();
-        debug!("Creating subscriber for topic: {}", topic);
+    /// Create a new subscriber with an unbounded queue
+    ///
+    /// Fails with [`Error::InvalidTopicName`](crate::Error::InvalidTopicName)
+    /// if `topic` is not a valid [`TopicName`], or with
+    /// [`Error::TopicTypeMismatch`](crate::Error::TopicTypeMismatch) if the
+    /// topic already carries a different message type.
+    pub fn new(topic: impl Into<String>) -> Result<Self> {
+        Self::builder(topic).build()
+    }
 
-        let (sender, receiver) = channel::unbounded();
+    /// Create a new subscriber with a QoS profile
+    ///
+    /// The queue keeps the latest `history_depth` messages. A
+    /// [`TransientLocal`](crate::qos::Durability::TransientLocal) subscriber
+    /// starts with the history of every transient-local publisher on the
+    /// topic. Fails with [`Error::QosIncompatible`](crate::Error::QosIncompatible)
+    /// if a publisher on the topic does not offer the requested guarantees.
+    pub fn new_with_qos(topic: impl Into<String>, qos: QosProfile) -> Result<Self> {
+        Self::builder(topic).qos(qos).build()
+    }
 
-        Self {
-            topic,
-            receiver,
-            _sender: Arc::new(sender),
+    /// Create a subscriber that only queues messages for which `predicate`
+    /// returns `true`
+    ///
+    /// See [`SubscriberBuilder::filter`].
+    pub fn filtered<F>(topic: impl Into<String>, predicate: F) -> Result<Self>
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        Self::builder(topic).filter(predicate).build()
+    }
+
+    /// Create a subscriber that queues at most `max_hz` messages per second
+    ///
+    /// See [`SubscriberBuilder::throttle`].
+    pub fn throttled(topic: impl Into<String>, max_hz: f64) -> Result<Self> {
+        Self::builder(topic).throttle(max_hz).build()
+    }
+
+    /// Configure a subscriber's queue before creating it
+    pub fn builder(topic: impl Into<String>) -> SubscriberBuilder<T> {
+        SubscriberBuilder {
+            topic: topic.into(),
+            queue_depth: None,
+            overflow: OverflowPolicy::default(),
+            channel: ChannelKind::default(),
+            qos: QosProfile::default(),
+            node: None,
+            clock: Clock::default(),
+            filter: None,
+            deadline: None,
+            throttle: None,
+            decimate: None,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Receive `projection(&msg)` instead of each message
+    ///
+    /// Like a filter, the projection runs in the publishing task, after the
+    /// filter, throttle and decimation if there are any, so only the projected value is queued and
+    /// large messages are released as soon as it is taken. Messages queued
+    /// before the call are projected when received. A panicking projection
+    /// discards the message and is counted in
+    /// [`SubscriberStats::filter_panics`]. Fails with
+    /// [`Error::Configuration`] if the subscriber has clones, which could not
+    /// decode the projected values.
+    pub fn map<U, F>(self, projection: F) -> Result<MappedSubscriber<T, U>>
+    where
+        U: Send + 'static,
+        F: Fn(&T) -> U + Send + Sync + 'static,
+    {
+        if Arc::strong_count(&self.inner) > 1 {
+            return Err(Error::Configuration(format!(
+                "cannot map the subscriber on '{}' while it has clones",
+                self.topic
+            )));
         }
+        let projection: Arc<dyn Fn(&T) -> U + Send + Sync> = Arc::new(projection);
+        let stages = self.stages.clone();
+        let project = projection.clone();
+        self.inner.queue.set_admit(Box::new(move |sample| {
+            stages.admit(sample, Some(&|sample: &Sample, msg: &T| sample.project(project(msg))))
+        }));
+        Ok(MappedSubscriber {
+            subscriber: self,
+            projection,
+        })
+    }
+
+    /// Receive the next message, waiting until one arrives
+    ///
+    /// Messages are yielded in the order they were queued. Which messages
+    /// are still queued once the consumer falls behind depends on the
+    /// [`OverflowPolicy`]. Zero-copy messages are copied by serializing and
+    /// deserializing them; use [`recv_arc`](Self::recv_arc) to avoid that.
+    /// Fails with [`Error::Closed`] once the subscriber's node has shut down
+    /// and the queue is drained.
+    pub async fn recv(&self) -> Result<T> {
+        let sample = self.next().await?;
+        Self::decode(&sample)
+    }
+
+    /// Receive the next message as a shared pointer, waiting until one arrives
+    ///
+    /// Messages published with
+    /// [`Publisher::publish_arc`](crate::Publisher::publish_arc) are handed
+    /// over without being copied or serialized.
+    pub async fn recv_arc(&self) -> Result<Arc<T>> {
+        let sample = self.next().await?;
+        Self::decode_arc(&sample)
+    }
+
+    /// Receive the next message together with its publish metadata
+    pub async fn recv_with_info(&self) -> Result<(T, MessageInfo)> {
+        let sample = self.next().await?;
+        let info = self.info(&sample);
+        Ok((Self::decode(&sample)?, info))
+    }
+
+    /// Receive the next message as a shared pointer together with its
+    /// publish metadata
+    pub async fn recv_arc_with_info(&self) -> Result<(Arc<T>, MessageInfo)> {
+        let sample = self.next().await?;
+        let info = self.info(&sample);
+        Ok((Self::decode_arc(&sample)?, info))
     }
 
-    /// Receive a message (blocking)
-    pub fn recv(&self) -> Result<T> {
-        self.receiver
-            .recv()
-            .map_err(|e| Error::Other(e.into()))
+    /// Receive the next message, blocking the current thread
+    pub fn recv_blocking(&self) -> Result<T> {
+        let sample = self.next_blocking()?;
+        Self::decode(&sample)
     }
 
     /// Try to receive a message (non-blocking)
     pub fn try_recv(&self) -> Result<Option<T>> {
-        match self.receiver.try_recv() {
-            Ok(msg) => Ok(Some(msg)),
-            Err(crossbeam::channel::TryRecvError::Empty) => Ok(None),
-            Err(e) => Err(Error::Other(e.into())),
+        match self.try_next() {
+            Some(sample) => Self::decode(&sample).map(Some),
+            None => Ok(None),
         }
     }
 
-    /// Receive a message asynchronously
-    pub async fn recv_async(&self) -> Result<T> {
-        let receiver = self.receiver.clone();
-        tokio::task::spawn_blocking(move || {
-            receiver.recv()
-        })
-        .await
-        .map_err(|e| Error::Other(e.into()))?
-        .map_err(|e| Error::Other(e.into()))
+    /// Try to receive a message as a shared pointer (non-blocking)
+    pub fn try_recv_arc(&self) -> Result<Option<Arc<T>>> {
+        match self.try_next() {
+            Some(sample) => Self::decode_arc(&sample).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Try to receive a message together with its publish metadata
+    /// (non-blocking)
+    pub fn try_recv_with_info(&self) -> Result<Option<(T, MessageInfo)>> {
+        match self.try_next() {
+            Some(sample) => {
+                let info = self.info(&sample);
+                Ok(Some((Self::decode(&sample)?, info)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Number of messages waiting to be received
+    pub fn pending(&self) -> usize {
+        self.inner.queue.len()
+    }
+
+    /// QoS profile requested by this subscriber
+    pub fn qos(&self) -> QosProfile {
+        self.inner.queue.qos()
+    }
+
+    /// Stream of missed deadlines and publisher liveliness changes
+    ///
+    /// Only events raised after the call are seen.
+    pub fn qos_events(&self) -> QosEventWatcher {
+        self.inner.queue.watch_events()
+    }
+
+    /// Delivery statistics derived from the publishers' sequence numbers
+    ///
+    /// Unlike [`dropped_count`](Self::dropped_count), `dropped` also counts
+    /// messages lost before they reached this subscriber's queue.
+    pub fn stats(&self) -> SubscriberStats {
+        let mut stats = self.inner.stats.lock().clone();
+        stats.filtered = self.inner.queue.filtered();
+        stats.skipped = self.inner.queue.skipped();
+        stats.filter_panics = self.inner.queue.admit_panics();
+        stats
+    }
+
+    /// Number of messages discarded because the queue was full
+    pub fn dropped_count(&self) -> u64 {
+        self.inner.queue.dropped()
     }
 
     /// Get topic name
     pub fn topic(&self) -> &str {
         &self.topic
     }
+
+    pub(crate) fn endpoint(&self) -> (Arc<Topic>, Arc<SampleQueue>) {
+        (self.inner.topic.clone(), self.inner.queue.clone())
+    }
+
+    async fn next(&self) -> Result<Sample> {
+        let sample = self.inner.queue.pop().await.ok_or_else(|| self.closed())?;
+        self.received(&sample);
+        Ok(sample)
+    }
+
+    fn next_blocking(&self) -> Result<Sample> {
+        let sample = self.inner.queue.pop_blocking().ok_or_else(|| self.closed())?;
+        self.received(&sample);
+        Ok(sample)
+    }
+
+    fn try_next(&self) -> Option<Sample> {
+        let sample = self.inner.queue.try_pop()?;
+        self.received(&sample);
+        Some(sample)
+    }
+
+    /// Account for a sample taken from the queue
+    fn received(&self, sample: &Sample) {
+        let stamp = sample.stamp();
+        self.inner.stats.lock().observe(stamp);
+        let seq = stamp.map_or(0, |stamp| stamp.seq);
+        if let Some(stamp) = stamp.filter(|stamp| stamp.clock == self.clock.clock_type()) {
+            let latency = self.clock.now().saturating_duration_since(stamp.time);
+            self.inner.topic.observe_latency(latency);
+        }
+        trace::instant(EventKind::Deliver, self.inner.topic.trace_label(), seq);
+        trace!(parent: &self.inner.span, seq, "Received");
+    }
+
+    fn info(&self, sample: &Sample) -> MessageInfo {
+        let receive_stamp = self.clock.now();
+        let receive_clock = self.clock.clock_type();
+        match sample.stamp() {
+            Some(stamp) => MessageInfo {
+                publish_stamp: stamp.time,
+                publish_clock: stamp.clock,
+                receive_stamp,
+                receive_clock,
+                seq: stamp.seq,
+                publisher: Some(stamp.publisher),
+            },
+            // Every publisher stamps its samples; this is a bare bus sample
+            None => MessageInfo {
+                publish_stamp: receive_stamp,
+                publish_clock: receive_clock,
+                receive_stamp,
+                receive_clock,
+                seq: 0,
+                publisher: None,
+            },
+        }
+    }
+
+    fn closed(&self) -> Error {
+        Error::Closed(format!("subscriber on '{}' was shut down", self.topic))
+    }
+
+    fn decode(sample: &Sample) -> Result<T> {
+        if is_raw::<T>() {
+            let raw: Box<dyn Any> = Box::new(RawMessage {
+                type_name: sample.type_name().to_string(),
+                format: sample.format(),
+                data: sample.bytes()?.into_owned(),
+            });
+            return Ok(*raw.downcast::<T>().expect("T is RawMessage"));
+        }
+        Serializer::new(sample.format()).deserialize(&sample.bytes()?)
+    }
+
+    fn decode_arc(sample: &Sample) -> Result<Arc<T>> {
+        match sample.downcast::<T>() {
+            Some(msg) => Ok(msg),
+            None => Self::decode(sample).map(Arc::new),
+        }
+    }
+}
+
+impl Subscriber<RawMessage> {
+    /// Receive the next message without copying its bytes, waiting until
+    /// one arrives
+    ///
+    /// Bytes published with
+    /// [`Publisher::publish_shared`](crate::Publisher::publish_shared) or
+    /// received from a transport are shared with the publisher and other
+    /// subscribers; messages published with
+    /// [`publish_arc`](crate::Publisher::publish_arc) are serialized.
+    pub async fn recv_shared(&self) -> Result<SharedRawMessage> {
+        let sample = self.next().await?;
+        Self::shared(&sample)
+    }
+
+    /// Try to receive a message without copying its bytes (non-blocking)
+    pub fn try_recv_shared(&self) -> Result<Option<SharedRawMessage>> {
+        self.try_next()
+            .map(|sample| Self::shared(&sample))
+            .transpose()
+    }
+
+    fn shared(sample: &Sample) -> Result<SharedRawMessage> {
+        Ok(SharedRawMessage {
+            type_name: sample.type_name().to_string(),
+            format: sample.format(),
+            data: sample.shared_bytes()?,
+        })
+    }
+}
+
+/// Builder for a [`Subscriber`] with a bounded queue
+pub struct SubscriberBuilder<T: Message> {
+    topic: String,
+    queue_depth: Option<usize>,
+    overflow: OverflowPolicy,
+    channel: ChannelKind,
+    qos: QosProfile,
+    node: Option<String>,
+    clock: Clock,
+    filter: Option<Predicate<T>>,
+    deadline: Option<Duration>,
+    throttle: Option<f64>,
+    decimate: Option<usize>,
+    _phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T: Message> SubscriberBuilder<T> {
+    /// Maximum number of queued messages (at least 1)
+    pub fn queue_depth(mut self, depth: usize) -> Self {
+        self.queue_depth = Some(depth);
+        self
+    }
+
+    /// What to do when the queue is full
+    pub fn overflow(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow = policy;
+        self
+    }
+
+    /// Select the queue implementation for intra-process delivery
+    pub fn channel(mut self, channel: ChannelKind) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Request a QoS profile, bounding the queue to its `history_depth`
+    /// with [`OverflowPolicy::DropOldest`]
+    pub fn qos(mut self, qos: QosProfile) -> Self {
+        self.qos = qos;
+        self.queue_depth = Some(qos.history_depth);
+        self.overflow = OverflowPolicy::DropOldest;
+        self
+    }
+
+    /// Only queue messages for which `predicate` returns `true`
+    ///
+    /// The predicate runs in the publishing task before the message enters
+    /// the queue, so rejected messages take no room in it and are not
+    /// counted as dropped; [`SubscriberStats::filtered`] counts them. A
+    /// panicking predicate rejects the message and is counted in
+    /// [`SubscriberStats::filter_panics`] instead of unwinding into the
+    /// publisher or transport. The predicate should be quick and must not
+    /// create publishers or subscribers.
+    pub fn filter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Queue at most `max_hz` messages per second
+    ///
+    /// A message is queued once a full period has passed since the last one
+    /// queued, going by its publish stamp, or by the subscriber's
+    /// [`clock`](Self::clock) at delivery for unstamped messages, so a
+    /// replayed or simulated topic is thinned in its own time. Like a
+    /// filter, the throttle runs in the publishing task, after the filter;
+    /// left out messages are counted in [`SubscriberStats::skipped`]. Building
+    /// fails with [`Error::Configuration`] unless `max_hz` is positive.
+    pub fn throttle(mut self, max_hz: f64) -> Self {
+        self.throttle = Some(max_hz);
+        self
+    }
+
+    /// Queue only every `keep_every`th message, starting with the first
+    ///
+    /// Runs in the publishing task after the filter and throttle, counting
+    /// only the messages they let through; left out messages are counted in
+    /// [`SubscriberStats::skipped`]. Building fails with
+    /// [`Error::Configuration`] if `keep_every` is 0.
+    pub fn decimate(mut self, keep_every: usize) -> Self {
+        self.decimate = Some(keep_every);
+        self
+    }
+
+    /// Raise [`QosEvent::DeadlineMissed`] for each `deadline` that passes
+    /// without a message entering the queue
+    ///
+    /// The first deadline starts when the subscriber is created. Messages
+    /// left out by the filter or throttle do not count. Overrides the
+    /// deadline of the [`qos`](Self::qos) profile; either way the subscriber
+    /// must be created within a Tokio runtime. Watch the events with
+    /// [`Subscriber::qos_events`].
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Take receive stamps from `clock` instead of the system clock
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record the node that owns the subscriber
+    pub(crate) fn node(mut self, node: &str) -> Self {
+        self.node = Some(node.to_string());
+        self
+    }
+
+    /// Create the subscriber and register it on its topic
+    pub fn build(self) -> Result<Subscriber<T>> {
+        let span = subscriber_span(&self.topic, self.node.as_deref());
+        debug!(parent: &span, "Creating subscriber");
+        TopicName::new(self.topic.as_str())?;
+
+        let decimation = match self.decimate {
+            Some(0) => {
+                return Err(Error::Configuration(
+                    "decimation factor must be at least 1, got 0".to_string(),
+                ))
+            }
+            Some(keep_every) => Some(Decimation {
+                keep_every: keep_every as u64,
+                seen: AtomicU64::new(0),
+            }),
+            None => None,
+        };
+        let stages = Arc::new(Stages {
+            filter: self.filter,
+            throttle: self.throttle.map(Throttle::new).transpose()?,
+            decimation,
+            clock: self.clock.clone(),
+        });
+        let mut qos = self.qos;
+        if self.deadline.is_some() {
+            qos.deadline = self.deadline;
+        }
+        let mut queue = SampleQueue::new(self.queue_depth, self.overflow)
+            .with_channel(self.channel)
+            .with_qos(qos)
+            .with_node(self.node);
+        if !stages.is_empty() {
+            let stages = stages.clone();
+            queue = queue.with_admit(Some(Box::new(move |sample| stages.admit(sample, None))));
+        }
+        let queue = Arc::new(queue);
+        let link =
+            TopicBus::global().attach_subscriber(&self.topic, TopicType::of::<T>(), queue.clone())?;
+        let deadline = qos
+            .deadline
+            .map(|deadline| tokio::spawn(monitor_deadline(queue.clone(), deadline)));
+
+        Ok(Subscriber {
+            topic: self.topic,
+            inner: Arc::new(Registration {
+                topic: link,
+                queue,
+                stats: Mutex::new(SubscriberStats::default()),
+                deadline,
+                span,
+            }),
+            clock: self.clock,
+            stages,
+            _phantom: std::marker::PhantomData,
+        })
+    }
 }
 
 impl<T: Message> Clone for Subscriber<T> {
     fn clone(&self) -> Self {
         Self {
             topic: self.topic.clone(),
-            receiver: self.receiver.clone(),
-            _sender: self._sender.clone(),
+            inner: self.inner.clone(),
+            clock: self.clock.clone(),
+            stages: self.stages.clone(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+/// Counters of a [`DirectSubscriber`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectStats {
+    /// Messages the callback returned from
+    pub delivered: u64,
+    /// Messages published from within the callback, directly or through
+    /// other direct subscribers, which it is not given again
+    pub reentrant: u64,
+    /// Messages whose callback panicked
+    pub panics: u64,
+}
+
+/// Subscriber whose callback runs on the publisher's call stack
+///
+/// [`Publisher::publish`](crate::Publisher::publish) and
+/// [`publish_arc`](crate::Publisher::publish_arc) hand the message to the
+/// callback by reference before returning, without serializing it or
+/// passing it through a queue or task, for tightly coupled components such
+/// as a torque loop. Queued subscribers on the same topic still receive every
+/// message, after the direct subscribers. Messages from raw publishers and
+/// remote peers are deserialized first.
+///
+/// The callback must be quick and must not block, as the publisher waits for
+/// it. A callback that publishes again on a topic leading back to itself is
+/// not called for the nested message, which is counted in
+/// [`DirectStats::reentrant`]; a panic is caught and counted instead of
+/// unwinding into the publisher. Dropping the subscriber unregisters it.
+pub struct DirectSubscriber<T: Message> {
+    topic: String,
+    link: Arc<Topic>,
+    handler: Arc<DirectHandler>,
+    _phantom: std::marker::PhantomData<fn(&T)>,
+}
+
+impl<T: Message> DirectSubscriber<T> {
+    /// Register `callback` for every message published on `topic`
+    ///
+    /// Fails like [`Subscriber::new`], or with [`Error::Configuration`] for
+    /// [`RawMessage`], which would need every message serialized.
+    pub fn new<F>(topic: impl Into<String>, callback: F) -> Result<Self>
+    where
+        F: Fn(&T) + Send + Sync + 'static,
+    {
+        Self::create(topic.into(), None, callback)
+    }
+
+    /// Create a direct subscriber, recording the node that owns it
+    pub(crate) fn create<F>(topic: String, node: Option<String>, callback: F) -> Result<Self>
+    where
+        F: Fn(&T) + Send + Sync + 'static,
+    {
+        let span = subscriber_span(&topic, node.as_deref());
+        debug!(parent: &span, "Creating direct subscriber");
+        TopicName::new(topic.as_str())?;
+        if is_raw::<T>() {
+            return Err(Error::Configuration(format!(
+                "direct subscriber on '{topic}' needs a concrete message type"
+            )));
+        }
+        let handler = Arc::new(DirectHandler::new(
+            node,
+            Box::new(move |msg| match msg {
+                Direct::Message(msg) => {
+                    if let Some(msg) = msg.downcast_ref::<T>() {
+                        callback(msg);
+                    }
+                }
+                Direct::Sample(sample) => match Subscriber::<T>::decode_arc(sample) {
+                    Ok(msg) => callback(&msg),
+                    Err(e) => warn!(parent: &span, "Direct subscriber dropped a message: {}", e),
+                },
+            }),
+        ));
+        let link = TopicBus::global().attach_direct(&topic, TopicType::of::<T>(), handler.clone())?;
+        Ok(Self {
+            topic,
+            link,
+            handler,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    pub fn stats(&self) -> DirectStats {
+        DirectStats {
+            delivered: self.handler.delivered(),
+            reentrant: self.handler.reentrant(),
+            panics: self.handler.panics(),
+        }
+    }
+
+    /// Get topic name
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub(crate) fn endpoint(&self) -> (Arc<Topic>, Arc<DirectHandler>) {
+        (self.link.clone(), self.handler.clone())
+    }
+}
+
+impl<T: Message> Drop for DirectSubscriber<T> {
+    fn drop(&mut self) {
+        TopicBus::global().detach_direct(&self.link, &self.handler);
+    }
+}
+
+/// Subscriber that receives every `n`th message
+///
+/// A [`Subscriber`] built with [`SubscriberBuilder::decimate`], which it
+/// dereferences to, so decimation composes with filtering and throttling.
+pub struct DecimatingSubscriber<T: Message> {
+    subscriber: Subscriber<T>,
+    keep_every: usize,
+}
+
+impl<T: Message> DecimatingSubscriber<T> {
+    /// Create a subscriber that queues every `keep_every`th message
+    pub fn new(topic: impl Into<String>, keep_every: usize) -> Result<Self> {
+        Self::from_builder(Subscriber::builder(topic), keep_every)
+    }
+
+    /// Build a decimating subscriber from a configured builder
+    pub fn from_builder(builder: SubscriberBuilder<T>, keep_every: usize) -> Result<Self> {
+        Ok(Self {
+            subscriber: builder.decimate(keep_every).build()?,
+            keep_every,
+        })
+    }
+
+    /// How many messages arrive for each one queued
+    pub fn keep_every(&self) -> usize {
+        self.keep_every
+    }
+
+    /// The underlying subscriber
+    pub fn into_inner(self) -> Subscriber<T> {
+        self.subscriber
+    }
+}
+
+impl<T: Message> Deref for DecimatingSubscriber<T> {
+    type Target = Subscriber<T>;
+
+    fn deref(&self) -> &Subscriber<T> {
+        &self.subscriber
+    }
+}
+
+/// Subscriber that receives a projection of each message
+///
+/// Created by [`Subscriber::map`].
+pub struct MappedSubscriber<T: Message, U> {
+    subscriber: Subscriber<T>,
+    projection: Arc<dyn Fn(&T) -> U + Send + Sync>,
+}
+
+impl<T: Message, U: Send + 'static> MappedSubscriber<T, U> {
+    /// Receive the next projected message, waiting until one arrives
+    pub async fn recv(&self) -> Result<U> {
+        let sample = self.subscriber.next().await?;
+        self.project(&sample)
+    }
+
+    /// Receive the next projected message together with its publish
+    /// metadata
+    pub async fn recv_with_info(&self) -> Result<(U, MessageInfo)> {
+        let sample = self.subscriber.next().await?;
+        let info = self.subscriber.info(&sample);
+        Ok((self.project(&sample)?, info))
+    }
+
+    /// Try to receive a projected message (non-blocking)
+    pub fn try_recv(&self) -> Result<Option<U>> {
+        match self.subscriber.try_next() {
+            Some(sample) => self.project(&sample).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Number of messages waiting to be received
+    pub fn pending(&self) -> usize {
+        self.subscriber.pending()
+    }
+
+    /// Delivery statistics, see [`Subscriber::stats`]
+    pub fn stats(&self) -> SubscriberStats {
+        self.subscriber.stats()
+    }
+
+    /// Deadline and liveliness events, see [`Subscriber::qos_events`]
+    pub fn qos_events(&self) -> QosEventWatcher {
+        self.subscriber.qos_events()
+    }
+
+    /// Get topic name
+    pub fn topic(&self) -> &str {
+        self.subscriber.topic()
+    }
+
+    /// The projection made in the publishing task, or one made now for a
+    /// message queued before the subscriber was mapped
+    fn project(&self, sample: &Sample) -> Result<U> {
+        match sample.take_projection::<U>() {
+            Some(value) => Ok(value),
+            None => Subscriber::<T>::decode_arc(sample).map(|msg| (self.projection)(&msg)),
         }
     }
 }
@@ -74,18 +986,629 @@ impl<T: Message> Clone for Subscriber<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::message::RobotState;
+    use crate::message::{Header, RobotState, Stamped};
+    use crate::publisher::Publisher;
+    use crate::serialization::Format;
+    use crate::time::SimClock;
+    use serde::{Deserialize, Serialize};
 
     #[test]
     fn test_subscriber_creation() {
-        let subscriber = Subscriber::<RobotState>::new("robot/state");
+        let subscriber = Subscriber::<RobotState>::new("robot/state").unwrap();
         assert_eq!(subscriber.topic(), "robot/state");
     }
 
     #[test]
     fn test_subscriber_try_recv() {
-        let subscriber = Subscriber::<RobotState>::new("robot/state");
+        let subscriber = Subscriber::<RobotState>::new("robot/state").unwrap();
         let result = subscriber.try_recv().unwrap();
         assert!(result.is_none());
     }
+
+    #[tokio::test]
+    async fn test_every_subscriber_gets_a_copy() {
+        let publisher = Publisher::<RobotState>::new("test/subscriber/fan_out").unwrap();
+        let first = Subscriber::<RobotState>::new("test/subscriber/fan_out").unwrap();
+        let second = Subscriber::<RobotState>::new("test/subscriber/fan_out").unwrap();
+
+        let msg = RobotState {
+            position: [1.0, 2.0, 3.0],
+            ..Default::default()
+        };
+        publisher.publish(&msg).await.unwrap();
+
+        assert_eq!(first.recv().await.unwrap().position, msg.position);
+        assert_eq!(second.try_recv().unwrap().unwrap().position, msg.position);
+    }
+
+    #[tokio::test]
+    async fn test_recv_waits_for_publish() {
+        let subscriber = Subscriber::<RobotState>::new("test/subscriber/wait").unwrap();
+        let publisher =
+            Publisher::<RobotState>::with_format("test/subscriber/wait", Format::Json).unwrap();
+
+        let handle = tokio::spawn(async move { subscriber.recv().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        publisher.publish(&RobotState::default()).await.unwrap();
+
+        let received = tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(received.is_ok());
+    }
+
+    #[test]
+    fn test_drop_unregisters() {
+        let subscriber = Subscriber::<RobotState>::new("test/subscriber/drop").unwrap();
+        let clone = subscriber.clone();
+        assert_eq!(TopicBus::global().subscriber_count("test/subscriber/drop"), 1);
+
+        drop(subscriber);
+        assert_eq!(TopicBus::global().subscriber_count("test/subscriber/drop"), 1);
+        drop(clone);
+        assert_eq!(TopicBus::global().subscriber_count("test/subscriber/drop"), 0);
+    }
+
+    fn state(i: usize) -> RobotState {
+        RobotState {
+            timestamp: i as i64,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_latest() {
+        let subscriber = Subscriber::<RobotState>::builder("test/subscriber/drop_oldest")
+            .queue_depth(4)
+            .overflow(OverflowPolicy::DropOldest)
+            .build()
+            .unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/drop_oldest").unwrap();
+
+        for i in 0..100 {
+            publisher.publish(&state(i)).await.unwrap();
+            assert!(subscriber.pending() <= 4);
+        }
+
+        assert_eq!(subscriber.dropped_count(), 96);
+        for i in 96..100 {
+            assert_eq!(subscriber.try_recv().unwrap().unwrap().timestamp, i);
+        }
+        assert!(subscriber.try_recv().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest_keeps_earliest() {
+        let subscriber = Subscriber::<RobotState>::builder("test/subscriber/drop_newest")
+            .queue_depth(4)
+            .overflow(OverflowPolicy::DropNewest)
+            .build()
+            .unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/drop_newest").unwrap();
+
+        for i in 0..100 {
+            publisher.publish(&state(i)).await.unwrap();
+            assert!(subscriber.pending() <= 4);
+        }
+
+        assert_eq!(subscriber.dropped_count(), 96);
+        for i in 0..4 {
+            assert_eq!(subscriber.try_recv().unwrap().unwrap().timestamp, i);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_applies_backpressure() {
+        let subscriber = Subscriber::<RobotState>::builder("test/subscriber/block")
+            .queue_depth(2)
+            .overflow(OverflowPolicy::Block)
+            .build()
+            .unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/block").unwrap();
+
+        let producer = tokio::spawn(async move {
+            for i in 0..20 {
+                publisher.publish(&state(i)).await.unwrap();
+            }
+        });
+
+        for i in 0..20 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            assert!(subscriber.pending() <= 2);
+            assert_eq!(subscriber.recv().await.unwrap().timestamp, i);
+        }
+
+        producer.await.unwrap();
+        assert_eq!(subscriber.dropped_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_lock_free_queue() {
+        let subscriber = Subscriber::<RobotState>::builder("test/subscriber/lock_free")
+            .queue_depth(4)
+            .channel(ChannelKind::LockFree)
+            .build()
+            .unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/lock_free").unwrap();
+
+        for i in 0..100 {
+            publisher.publish(&state(i)).await.unwrap();
+            assert!(subscriber.pending() <= 4);
+        }
+        assert_eq!(subscriber.dropped_count(), 96);
+        for i in 96..100 {
+            assert_eq!(subscriber.try_recv().unwrap().unwrap().timestamp, i);
+        }
+        assert!(subscriber.try_recv().unwrap().is_none());
+
+        let receiver = tokio::spawn(async move { subscriber.recv().await.unwrap() });
+        publisher.publish(&state(100)).await.unwrap();
+        assert_eq!(receiver.await.unwrap().timestamp, 100);
+    }
+
+    #[test]
+    fn test_subscriber_type_mismatch() {
+        use crate::message::PointCloud;
+
+        let _publisher = Publisher::<RobotState>::new("test/subscriber/mismatch").unwrap();
+        match Subscriber::<PointCloud>::new("test/subscriber/mismatch") {
+            Err(crate::Error::TopicTypeMismatch { topic, expected, found }) => {
+                assert_eq!(topic, "test/subscriber/mismatch");
+                assert!(expected.starts_with("ros3_msgs/RobotState"));
+                assert!(found.starts_with("ros3_msgs/PointCloud"));
+            }
+            _ => panic!("expected a type mismatch"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raw_subscriber_accepts_any_type() {
+        let publisher = Publisher::<RobotState>::new("test/subscriber/raw").unwrap();
+        let raw = Subscriber::<RawMessage>::new("test/subscriber/raw").unwrap();
+
+        publisher.publish(&RobotState::default()).await.unwrap();
+
+        let message = raw.recv().await.unwrap();
+        assert_eq!(message.type_name, "ros3_msgs/RobotState");
+        assert_eq!(message.decode::<RobotState>().unwrap().timestamp, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shared_bytes_are_not_copied() {
+        use crate::message::{SharedBytes, SharedRawMessage};
+
+        let publisher = Publisher::<RawMessage>::new("test/subscriber/shared").unwrap();
+        let shared = Subscriber::<RawMessage>::new("test/subscriber/shared").unwrap();
+        let copied = Subscriber::<RawMessage>::new("test/subscriber/shared").unwrap();
+        let data = SharedBytes::new(vec![7u8; 1024]);
+
+        publisher
+            .publish_shared(&SharedRawMessage {
+                type_name: "sensor_msgs/Image".to_string(),
+                format: Format::Cdr,
+                data: data.clone(),
+            })
+            .await
+            .unwrap();
+
+        let received = shared.recv_shared().await.unwrap();
+        assert_eq!(received.type_name, "sensor_msgs/Image");
+        assert_eq!(received.data.as_ptr(), data.as_ptr());
+        assert_eq!(received.data.owner::<Vec<u8>>().unwrap().len(), 1024);
+        assert!(shared.try_recv_shared().unwrap().is_none());
+        assert_eq!(copied.recv().await.unwrap().data, vec![7u8; 1024]);
+        assert_eq!(publisher.stats(), (1, 1024));
+    }
+
+    #[tokio::test]
+    async fn test_late_subscriber_gets_transient_local_history() {
+        use crate::qos::Durability;
+
+        let qos = QosProfile {
+            durability: Durability::TransientLocal,
+            history_depth: 1,
+            ..Default::default()
+        };
+        let publisher =
+            Publisher::<RobotState>::new_with_qos("test/subscriber/transient", qos).unwrap();
+        publisher.publish(&state(1)).await.unwrap();
+        publisher.publish(&state(2)).await.unwrap();
+
+        let late =
+            Subscriber::<RobotState>::new_with_qos("test/subscriber/transient", qos).unwrap();
+        assert_eq!(late.try_recv().unwrap().unwrap().timestamp, 2);
+        assert!(late.try_recv().unwrap().is_none());
+
+        // Volatile subscribers only see what is published after they join
+        let volatile = Subscriber::<RobotState>::new("test/subscriber/transient").unwrap();
+        assert!(volatile.try_recv().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_volatile_late_subscriber_gets_nothing() {
+        let publisher = Publisher::<RobotState>::new("test/subscriber/volatile").unwrap();
+        publisher.publish(&state(1)).await.unwrap();
+
+        let late = Subscriber::<RobotState>::new("test/subscriber/volatile").unwrap();
+        assert!(late.try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_topic_name() {
+        let result = Subscriber::<RobotState>::new("test//subscriber");
+        assert!(matches!(result, Err(crate::Error::InvalidTopicName { .. })));
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct Scan {
+        header: Header,
+        ranges: Vec<f32>,
+    }
+
+    impl Message for Scan {
+        fn type_name() -> &'static str {
+            "test_msgs/Scan"
+        }
+    }
+
+    impl Stamped for Scan {
+        fn header(&self) -> &Header {
+            &self.header
+        }
+
+        fn header_mut(&mut self) -> &mut Header {
+            &mut self.header
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recv_with_info_measures_latency() {
+        let sim = SimClock::new(Time::from_nanos(1_000));
+        let publisher = Publisher::<Scan>::new("test/subscriber/stamped")
+            .unwrap()
+            .with_clock(Clock::Sim(sim.clone()));
+        let subscriber = Subscriber::<Scan>::builder("test/subscriber/stamped")
+            .clock(Clock::Sim(sim.clone()))
+            .build()
+            .unwrap();
+
+        let mut scan = Scan {
+            ranges: vec![1.5, 2.5],
+            ..Default::default()
+        };
+        publisher.publish_stamped(&mut scan).await.unwrap();
+        publisher.publish_stamped(&mut scan).await.unwrap();
+        assert_eq!(scan.header().seq, 1);
+        assert_eq!(scan.header().stamp_ns, 1_000);
+
+        sim.advance(Duration::from_micros(250));
+        let (first, info) = subscriber.recv_with_info().await.unwrap();
+        assert_eq!(first.header.seq, 0);
+        assert_eq!(first.ranges, [1.5, 2.5]);
+        assert_eq!(info.seq, 0);
+        assert_eq!(info.publish_stamp, Time::from_nanos(1_000));
+        assert_eq!(info.latency(), Some(Duration::from_micros(250)));
+
+        let (second, info) = subscriber.recv_arc_with_info().await.unwrap();
+        assert_eq!(second.header.seq, 1);
+        assert_eq!(info.seq, 1);
+    }
+
+    #[tokio::test]
+    async fn test_latency_across_clock_types_is_unknown() {
+        let publisher = Publisher::<RobotState>::new("test/subscriber/skew")
+            .unwrap()
+            .with_clock(Clock::Sim(SimClock::new(Time::ZERO)));
+        let subscriber = Subscriber::<RobotState>::new("test/subscriber/skew").unwrap();
+
+        publisher.publish(&state(1)).await.unwrap();
+        let (_, info) = subscriber.recv_with_info().await.unwrap();
+        assert_eq!(info.publish_clock, ClockType::Sim);
+        assert_eq!(info.receive_clock, ClockType::SystemTime);
+        assert_eq!(info.latency(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_gaps_per_publisher() {
+        use crate::topic::Writer;
+
+        let first = Publisher::<RobotState>::new("test/subscriber/lossy_in").unwrap();
+        let second = Publisher::<RobotState>::new("test/subscriber/lossy_in").unwrap();
+        let wire = Subscriber::<RobotState>::new("test/subscriber/lossy_in").unwrap();
+        let subscriber = Subscriber::<RobotState>::new("test/subscriber/lossy_out").unwrap();
+
+        for i in 0..=100 {
+            first.publish(&state(i)).await.unwrap();
+            second.publish(&state(i)).await.unwrap();
+        }
+
+        // A lossy transport forwarding samples unchanged but losing every
+        // 10th one, all of which happen to come from `second`
+        let writer = Arc::new(Writer::new(QosProfile::default(), None));
+        let bus = TopicBus::global();
+        let out = bus
+            .attach_publisher("test/subscriber/lossy_out", None, writer.clone())
+            .unwrap();
+        let (_, queue) = wire.endpoint();
+        let mut forwarded = 0;
+        while let Some(sample) = queue.try_pop() {
+            forwarded += 1;
+            if forwarded % 10 != 0 {
+                out.deliver(&writer, sample).await.unwrap();
+            }
+        }
+        bus.detach_publisher(&out, &writer);
+
+        while subscriber.try_recv().unwrap().is_some() {}
+        let stats = subscriber.stats();
+        assert_eq!(stats.received, 202 - 20);
+        assert_eq!(stats.dropped, 20);
+        assert_eq!(stats.out_of_order, 0);
+        assert_eq!(stats.last_seq[&first.guid()], 100);
+        assert_eq!(stats.last_seq[&second.guid()], 100);
+        assert_eq!(subscriber.dropped_count(), 0);
+    }
+
+    #[test]
+    fn test_stats_late_message_fills_gap() {
+        let publisher = PublisherGuid { prefix: 1, id: 1 };
+        let stamp = |seq| Stamp {
+            publisher,
+            seq,
+            time: Time::ZERO,
+            clock: ClockType::Steady,
+        };
+
+        let mut stats = SubscriberStats::default();
+        for seq in [0, 1, 3, 4, 2, 4] {
+            stats.observe(Some(stamp(seq)));
+        }
+        assert_eq!(stats.received, 6);
+        assert_eq!(stats.dropped, 0);
+        assert_eq!(stats.out_of_order, 2);
+        assert_eq!(stats.last_seq[&publisher], 4);
+    }
+
+    #[tokio::test]
+    async fn test_filter_runs_before_queueing() {
+        let subscriber = Subscriber::<RobotState>::builder("test/subscriber/filter")
+            .queue_depth(10)
+            .overflow(OverflowPolicy::DropNewest)
+            .filter(|msg: &RobotState| msg.timestamp % 10 == 0)
+            .build()
+            .unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/filter").unwrap();
+
+        for i in 0..100 {
+            publisher.publish(&state(i)).await.unwrap();
+        }
+
+        assert_eq!(subscriber.pending(), 10);
+        assert_eq!(subscriber.dropped_count(), 0);
+        assert_eq!(subscriber.stats().filtered, 90);
+        for i in (0..100).step_by(10) {
+            assert_eq!(subscriber.try_recv().unwrap().unwrap().timestamp, i);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_panic_is_counted() {
+        let subscriber = Subscriber::filtered("test/subscriber/filter_panic", |msg: &RobotState| {
+            assert_ne!(msg.timestamp, 3, "bad message");
+            true
+        })
+        .unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/filter_panic").unwrap();
+
+        for i in 0..5 {
+            publisher.publish(&state(i)).await.unwrap();
+        }
+
+        let stats = subscriber.stats();
+        assert_eq!(stats.filter_panics, 1);
+        assert_eq!(stats.filtered, 0);
+        let mut received = Vec::new();
+        while let Some(msg) = subscriber.try_recv().unwrap() {
+            received.push(msg.timestamp);
+        }
+        assert_eq!(received, [0, 1, 2, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_map_projects_in_publisher() {
+        let subscriber = Subscriber::filtered("test/subscriber/map", |msg: &RobotState| {
+            msg.timestamp % 2 == 0
+        })
+        .unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/map").unwrap();
+        // Queued before mapping, so projected on receive
+        publisher.publish(&state(0)).await.unwrap();
+
+        let timestamps = subscriber.map(|msg: &RobotState| msg.timestamp * 100).unwrap();
+        for i in 1..5 {
+            publisher.publish(&state(i)).await.unwrap();
+        }
+
+        assert_eq!(timestamps.pending(), 3);
+        assert_eq!(timestamps.recv().await.unwrap(), 0);
+        let (value, info) = timestamps.recv_with_info().await.unwrap();
+        assert_eq!((value, info.seq), (200, 2));
+        assert_eq!(timestamps.try_recv().unwrap(), Some(400));
+        assert_eq!(timestamps.try_recv().unwrap(), None);
+        assert_eq!(timestamps.stats().filtered, 2);
+    }
+
+    #[test]
+    fn test_map_rejects_cloned_subscriber() {
+        let subscriber = Subscriber::<RobotState>::new("test/subscriber/map_clone").unwrap();
+        let _clone = subscriber.clone();
+        let mapped = subscriber.map(|msg: &RobotState| msg.timestamp);
+        assert!(matches!(mapped, Err(Error::Configuration(_))));
+    }
+
+    #[tokio::test]
+    async fn test_throttle_uses_publish_stamps() {
+        let sim = SimClock::new(Time::ZERO);
+        let publisher = Publisher::<RobotState>::new("test/subscriber/throttle")
+            .unwrap()
+            .with_clock(Clock::Sim(sim.clone()));
+        let subscriber = Subscriber::<RobotState>::builder("test/subscriber/throttle")
+            .filter(|msg: &RobotState| msg.timestamp % 2 == 0)
+            .throttle(10.0)
+            .build()
+            .unwrap();
+
+        // One second of a 1 kHz topic, in simulated time
+        for i in 0..1000 {
+            publisher.publish(&state(i)).await.unwrap();
+            sim.advance(Duration::from_millis(1));
+        }
+
+        let stats = subscriber.stats();
+        assert_eq!(stats.filtered, 500);
+        assert_eq!(stats.skipped, 490);
+        assert_eq!(subscriber.pending(), 10);
+        for i in (0..1000).step_by(100) {
+            assert_eq!(subscriber.try_recv().unwrap().unwrap().timestamp, i);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decimating_subscriber_keeps_every_nth() {
+        let subscriber = DecimatingSubscriber::<RobotState>::new("test/subscriber/decimate", 10)
+            .unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/decimate").unwrap();
+
+        for i in 0..100 {
+            publisher.publish(&state(i)).await.unwrap();
+        }
+
+        assert_eq!(subscriber.keep_every(), 10);
+        assert_eq!(subscriber.stats().skipped, 90);
+        let timestamps = subscriber.into_inner().map(|msg: &RobotState| msg.timestamp).unwrap();
+        for i in 100..120 {
+            publisher.publish(&state(i)).await.unwrap();
+        }
+        let mut received = Vec::new();
+        while let Some(timestamp) = timestamps.try_recv().unwrap() {
+            received.push(timestamp);
+        }
+        assert_eq!(received, [0, 10, 20, 30, 40, 50, 60, 70, 80, 90, 100, 110]);
+        assert_eq!(timestamps.stats().skipped, 108);
+    }
+
+    #[test]
+    fn test_invalid_rate_reduction() {
+        let throttled = Subscriber::<RobotState>::throttled("test/subscriber/bad_rate", 0.0);
+        assert!(matches!(throttled, Err(Error::Configuration(_))));
+        let decimated = DecimatingSubscriber::<RobotState>::new("test/subscriber/bad_rate", 0);
+        assert!(matches!(decimated, Err(Error::Configuration(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_missed_when_publisher_hangs() {
+        use tokio::time::{sleep, timeout, Instant};
+
+        let deadline = Duration::from_millis(100);
+        let subscriber = Subscriber::<RobotState>::builder("test/subscriber/deadline")
+            .deadline(deadline)
+            .build()
+            .unwrap();
+        let mut events = subscriber.qos_events();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/deadline").unwrap();
+
+        for i in 0..10 {
+            publisher.publish(&state(i)).await.unwrap();
+            sleep(deadline / 2).await;
+        }
+        let hung = Instant::now() - deadline / 2;
+
+        let missed = timeout(deadline, events.next()).await.unwrap();
+        assert_eq!(missed, Some(QosEvent::DeadlineMissed { total: 1 }));
+        assert!(hung.elapsed() <= deadline);
+        let missed = timeout(deadline * 2, events.next()).await.unwrap();
+        assert_eq!(missed, Some(QosEvent::DeadlineMissed { total: 2 }));
+        assert_eq!(subscriber.pending(), 10);
+    }
+
+    /// Run a publish that cannot wait, as inside a direct callback
+    fn publish_now(publisher: &Publisher<RobotState>, msg: &RobotState) {
+        use std::future::Future;
+        use std::task::{Context, Poll, Waker};
+
+        let mut publish = std::pin::pin!(publisher.publish(msg));
+        let poll = publish.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+        assert!(matches!(poll, Poll::Ready(Ok(()))));
+    }
+
+    #[tokio::test]
+    async fn test_direct_subscriber_runs_on_publisher_stack() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let direct = DirectSubscriber::new("test/subscriber/direct", {
+            let received = received.clone();
+            move |msg: &RobotState| {
+                received.lock().push((msg.timestamp, std::thread::current().id()));
+            }
+        })
+        .unwrap();
+        let queued = Subscriber::<RobotState>::new("test/subscriber/direct").unwrap();
+        let publisher = Publisher::<RobotState>::new("test/subscriber/direct").unwrap();
+        let raw = Publisher::<RawMessage>::new("test/subscriber/direct").unwrap();
+
+        publisher.publish(&state(1)).await.unwrap();
+        assert_eq!(*received.lock(), [(1, std::thread::current().id())]);
+        publisher.publish_arc(Arc::new(state(2))).await.unwrap();
+        let bytes = Serializer::new(Format::Cdr).serialize(&state(3)).unwrap();
+        raw.publish(&RawMessage {
+            type_name: RobotState::type_name().to_string(),
+            format: Format::Cdr,
+            data: bytes,
+        })
+        .await
+        .unwrap();
+
+        let direct_timestamps: Vec<i64> = received.lock().iter().map(|(t, _)| *t).collect();
+        assert_eq!(direct_timestamps, [1, 2, 3]);
+        for i in 1..=3 {
+            assert_eq!(queued.try_recv().unwrap().unwrap().timestamp, i);
+        }
+        assert_eq!(direct.stats().delivered, 3);
+        assert_eq!(TopicBus::global().subscriber_count("test/subscriber/direct"), 2);
+
+        drop(direct);
+        publisher.publish(&state(4)).await.unwrap();
+        assert_eq!(received.lock().len(), 3);
+        assert_eq!(queued.try_recv().unwrap().unwrap().timestamp, 4);
+    }
+
+    #[tokio::test]
+    async fn test_direct_subscriber_reentrancy_guard() {
+        let publisher =
+            Arc::new(Publisher::<RobotState>::new("test/subscriber/direct_reentrant").unwrap());
+        let queued = Subscriber::<RobotState>::new("test/subscriber/direct_reentrant").unwrap();
+        let direct = DirectSubscriber::new("test/subscriber/direct_reentrant", {
+            let publisher = publisher.clone();
+            move |msg: &RobotState| {
+                assert_ne!(msg.timestamp, 7, "bad message");
+                // Echo once; the echo must not reach this callback again
+                publish_now(&publisher, &state(msg.timestamp as usize + 100));
+            }
+        })
+        .unwrap();
+
+        publisher.publish(&state(1)).await.unwrap();
+        publisher.publish(&state(7)).await.unwrap();
+
+        let stats = direct.stats();
+        assert_eq!(stats.delivered, 1);
+        assert_eq!(stats.reentrant, 1);
+        assert_eq!(stats.panics, 1);
+        let mut received = Vec::new();
+        while let Some(msg) = queued.try_recv().unwrap() {
+            received.push(msg.timestamp);
+        }
+        // Queued subscribers get the echo before the message that caused it
+        assert_eq!(received, [101, 1, 7]);
+    }
 }
@@ -0,0 +1,662 @@
+//! Time synchronization of several topics
+//!
+//! Sensor fusion needs one message from each of several topics, taken at
+//! about the same time. [`ApproximateTimeSync`] and [`ExactTimeSync`] buffer
+//! messages per topic and emit a tuple once they can tell it is the best
+//! match, following the semantics of ROS `message_filters`. Messages are
+//! matched on the stamp of their [`Header`](crate::Header).
+//!
+//! Both synchronizers assume that each topic's stamps increase, and discard
+//! messages explicitly:
+//!
+//! - a topic's queue holds at most `queue_size` messages; a full queue drops
+//!   its oldest message, counted in [`SyncStats::overflowed`]
+//! - emitting a tuple drops every older message on its topics, as tuples are
+//!   emitted in stamp order
+//! - a message that can no longer be part of any tuple is dropped, and so is
+//!   one arriving with a stamp older than the last tuple emitted; both are
+//!   counted in [`SyncStats::unmatched`]
+
+use crate::error::{Error, Result};
+use crate::message::{Message, Stamped};
+use crate::node::Node;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Counters of a synchronizer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncStats {
+    /// Tuples emitted
+    pub emitted: u64,
+    /// Messages dropped because no tuple within the slop could include them
+    pub unmatched: u64,
+    /// Messages dropped because their topic's queue was full
+    pub overflowed: u64,
+}
+
+/// A tuple of message types that can be synchronized, such as
+/// `(Image, Imu, Odometry)`
+///
+/// Implemented for tuples of 2 to 6 [`Stamped`] types.
+pub trait SyncTuple: Sized + Send + 'static {
+    /// Number of topics
+    const LEN: usize;
+
+    #[doc(hidden)]
+    fn from_parts(parts: Vec<Part>) -> Self;
+}
+
+/// The message type at position `I` of a [`SyncTuple`]
+pub trait SyncSlot<const I: usize>: SyncTuple {
+    type Message: Stamped + Send + 'static;
+}
+
+/// A [`SyncTuple`] of [`Message`]s, which can be fed from subscribers
+pub trait SyncSubscribe: SyncTuple {
+    #[doc(hidden)]
+    fn spawn_feeds(node: &Node, topics: &[&str], feed: Feed) -> Result<Vec<JoinHandle<()>>>;
+}
+
+#[doc(hidden)]
+pub type Part = Box<dyn Any + Send>;
+
+#[doc(hidden)]
+pub type Feed = Arc<dyn Fn(usize, i64, Part) + Send + Sync>;
+
+macro_rules! impl_sync_slot {
+    ($index:tt, $slot:ident, ($($T:ident),+)) => {
+        impl<$($T: Stamped + Send + 'static),+> SyncSlot<$index> for ($($T,)+) {
+            type Message = $slot;
+        }
+    };
+}
+
+macro_rules! impl_sync_tuple {
+    ($len:expr; $types:tt; $($index:tt => $T:ident),+) => {
+        impl<$($T: Stamped + Send + 'static),+> SyncTuple for ($($T,)+) {
+            const LEN: usize = $len;
+
+            fn from_parts(parts: Vec<Part>) -> Self {
+                let mut parts = parts.into_iter();
+                ($(*parts
+                    .next()
+                    .expect("one part per slot")
+                    .downcast::<$T>()
+                    .expect("part matches its slot"),)+)
+            }
+        }
+
+        $(impl_sync_slot!($index, $T, $types);)+
+
+        impl<$($T: Message + Stamped),+> SyncSubscribe for ($($T,)+) {
+            fn spawn_feeds(
+                node: &Node,
+                topics: &[&str],
+                feed: Feed,
+            ) -> Result<Vec<JoinHandle<()>>> {
+                // Subscribe to every topic before spawning, so a failure
+                // leaves no task behind
+                let subscribers = ($(node.create_subscriber::<$T>(topics[$index])?,)+);
+                Ok(vec![$({
+                    let subscriber = subscribers.$index;
+                    let feed = feed.clone();
+                    tokio::spawn(async move {
+                        while let Ok(msg) = subscriber.recv().await {
+                            let stamp = msg.header().stamp_ns;
+                            feed($index, stamp, Box::new(msg));
+                        }
+                    })
+                }),+])
+            }
+        }
+    };
+}
+
+impl_sync_tuple!(2; (A, B); 0 => A, 1 => B);
+impl_sync_tuple!(3; (A, B, C); 0 => A, 1 => B, 2 => C);
+impl_sync_tuple!(4; (A, B, C, D); 0 => A, 1 => B, 2 => C, 3 => D);
+impl_sync_tuple!(5; (A, B, C, D, E); 0 => A, 1 => B, 2 => C, 3 => D, 4 => E);
+impl_sync_tuple!(6; (A, B, C, D, E, F); 0 => A, 1 => B, 2 => C, 3 => D, 4 => E, 5 => F);
+
+/// Per-topic queues of messages sorted by stamp
+struct Queues {
+    queues: Vec<VecDeque<(i64, Part)>>,
+    /// Stamp of the last message emitted from each topic
+    floors: Vec<Option<i64>>,
+    queue_size: usize,
+    stats: SyncStats,
+}
+
+impl Queues {
+    fn new(len: usize, queue_size: usize) -> Self {
+        Self {
+            queues: (0..len).map(|_| VecDeque::new()).collect(),
+            floors: vec![None; len],
+            queue_size: queue_size.max(1),
+            stats: SyncStats::default(),
+        }
+    }
+
+    fn insert(&mut self, index: usize, stamp: i64, part: Part) {
+        if self.floors[index].is_some_and(|floor| stamp <= floor) {
+            self.stats.unmatched += 1;
+            return;
+        }
+        let queue = &mut self.queues[index];
+        let position = queue.partition_point(|(queued, _)| *queued <= stamp);
+        queue.insert(position, (stamp, part));
+        if queue.len() > self.queue_size {
+            queue.pop_front();
+            self.stats.overflowed += 1;
+        }
+    }
+
+    fn head(&self, index: usize) -> Option<i64> {
+        self.queues[index].front().map(|(stamp, _)| *stamp)
+    }
+
+    /// Drop the messages of every topic stamped before `stamp`
+    fn discard_before(&mut self, stamp: i64) {
+        for queue in &mut self.queues {
+            while queue.front().is_some_and(|(queued, _)| *queued < stamp) {
+                queue.pop_front();
+                self.stats.unmatched += 1;
+            }
+        }
+    }
+
+    /// Remove the message at `positions[i]` of each topic, and every older
+    /// one, and assemble the tuple
+    fn take<M: SyncTuple>(&mut self, positions: &[usize]) -> M {
+        let mut parts = Vec::with_capacity(positions.len());
+        for (index, &position) in positions.iter().enumerate() {
+            let queue = &mut self.queues[index];
+            self.stats.unmatched += position as u64;
+            queue.drain(..position);
+            let (stamp, part) = queue.pop_front().expect("position is in the queue");
+            self.floors[index] = Some(stamp);
+            parts.push(part);
+        }
+        self.stats.emitted += 1;
+        M::from_parts(parts)
+    }
+}
+
+/// Matches messages whose stamps lie within a slop of each other
+///
+/// Each tuple is built around a *pivot*, the latest of the oldest queued
+/// messages of the topics. Once every other topic has a message at or after
+/// the pivot, the tuple containing the pivot with the smallest spread of
+/// stamps is known, since later messages can only be farther away. It is
+/// emitted if its spread is at most the slop; otherwise the pivot cannot be
+/// matched and is dropped.
+pub struct ApproximateTimeSync<M: SyncTuple> {
+    queues: Queues,
+    slop: i64,
+    _phantom: PhantomData<fn() -> M>,
+}
+
+impl<M: SyncTuple> ApproximateTimeSync<M> {
+    /// Create a synchronizer emitting tuples whose stamps are at most `slop`
+    /// apart, keeping at most `queue_size` messages per topic
+    pub fn new(slop: Duration, queue_size: usize) -> Self {
+        Self {
+            queues: Queues::new(M::LEN, queue_size),
+            slop: i64::try_from(slop.as_nanos()).unwrap_or(i64::MAX),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Add a message for the topic at position `I`, returning the tuples it
+    /// completed, oldest first
+    pub fn add<const I: usize>(&mut self, msg: <M as SyncSlot<I>>::Message) -> Vec<M>
+    where
+        M: SyncSlot<I>,
+    {
+        let stamp = msg.header().stamp_ns;
+        self.add_part(I, stamp, Box::new(msg))
+    }
+
+    /// Counters of emitted and dropped messages
+    pub fn stats(&self) -> SyncStats {
+        self.queues.stats
+    }
+
+    /// Feed the synchronizer from subscribers on `topics`, one per position
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn subscribe(self, node: &Node, topics: &[&str]) -> Result<Synchronized<M>>
+    where
+        M: SyncSubscribe,
+    {
+        synchronize(node, topics, self, Self::add_part, |sync| sync.stats())
+    }
+
+    fn add_part(&mut self, index: usize, stamp: i64, part: Part) -> Vec<M> {
+        self.queues.insert(index, stamp, part);
+        let mut emitted = Vec::new();
+        while let Some(tuple) = self.try_emit() {
+            emitted.push(tuple);
+        }
+        emitted
+    }
+
+    /// Emit the tuple around the current pivot if it can be decided
+    fn try_emit(&mut self) -> Option<M> {
+        loop {
+            let heads: Option<Vec<i64>> = (0..M::LEN).map(|i| self.queues.head(i)).collect();
+            let heads = heads?;
+            let (pivot, &pivot_stamp) = heads
+                .iter()
+                .enumerate()
+                .max_by_key(|(index, stamp)| (**stamp, std::cmp::Reverse(*index)))
+                .expect("at least two topics");
+            // Every tuple from now on contains a message of the pivot's topic,
+            // none of which is older than the pivot
+            let oldest = pivot_stamp.saturating_sub(self.slop);
+            if heads.iter().any(|&head| head < oldest) {
+                self.queues.discard_before(oldest);
+                continue;
+            }
+
+            // Per other topic, the last message at or before the pivot and the
+            // first one after it; wait until the latter exists
+            let mut candidates = Vec::with_capacity(M::LEN);
+            for (index, queue) in self.queues.queues.iter().enumerate() {
+                if index == pivot {
+                    candidates.push((Some(0), None));
+                    continue;
+                }
+                let after = queue.partition_point(|(stamp, _)| *stamp <= pivot_stamp);
+                let at_or_before = after.checked_sub(1);
+                if at_or_before.is_some_and(|i| queue[i].0 == pivot_stamp) {
+                    candidates.push((at_or_before, None));
+                } else if after < queue.len() {
+                    candidates.push((at_or_before, Some(after)));
+                } else {
+                    return None;
+                }
+            }
+
+            match self.best_tuple(&candidates) {
+                Some(positions) => return Some(self.queues.take(&positions)),
+                None => {
+                    self.queues.queues[pivot].pop_front();
+                    self.queues.stats.unmatched += 1;
+                }
+            }
+        }
+    }
+
+    /// Positions of the choice of candidates with the smallest spread, if
+    /// that spread is within the slop
+    ///
+    /// Ties go to the choice taking fewer later messages, which leaves them
+    /// for the next tuple.
+    fn best_tuple(&self, candidates: &[(Option<usize>, Option<usize>)]) -> Option<Vec<usize>> {
+        let mut best: Option<(i64, Vec<usize>)> = None;
+        for choice in 0u32..(1 << candidates.len()) {
+            let positions: Option<Vec<usize>> = candidates
+                .iter()
+                .enumerate()
+                .map(|(index, &(before, after))| match choice & (1 << index) {
+                    0 => before,
+                    _ => after,
+                })
+                .collect();
+            let Some(positions) = positions else {
+                continue;
+            };
+            let stamps = positions
+                .iter()
+                .enumerate()
+                .map(|(index, &position)| self.queues.queues[index][position].0);
+            let (min, max) = stamps.fold((i64::MAX, i64::MIN), |(min, max), stamp| {
+                (min.min(stamp), max.max(stamp))
+            });
+            let spread = max - min;
+            if best.as_ref().is_none_or(|(best, _)| spread < *best) {
+                best = Some((spread, positions));
+            }
+        }
+        best.filter(|(spread, _)| *spread <= self.slop)
+            .map(|(_, positions)| positions)
+    }
+}
+
+/// Matches messages with identical stamps
+pub struct ExactTimeSync<M: SyncTuple> {
+    queues: Queues,
+    _phantom: PhantomData<fn() -> M>,
+}
+
+impl<M: SyncTuple> ExactTimeSync<M> {
+    /// Create a synchronizer keeping at most `queue_size` messages per topic
+    pub fn new(queue_size: usize) -> Self {
+        Self {
+            queues: Queues::new(M::LEN, queue_size),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Add a message for the topic at position `I`, returning the tuple it
+    /// completed
+    pub fn add<const I: usize>(&mut self, msg: <M as SyncSlot<I>>::Message) -> Option<M>
+    where
+        M: SyncSlot<I>,
+    {
+        let stamp = msg.header().stamp_ns;
+        self.add_part(I, stamp, Box::new(msg)).pop()
+    }
+
+    /// Counters of emitted and dropped messages
+    pub fn stats(&self) -> SyncStats {
+        self.queues.stats
+    }
+
+    /// Feed the synchronizer from subscribers on `topics`, one per position
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn subscribe(self, node: &Node, topics: &[&str]) -> Result<Synchronized<M>>
+    where
+        M: SyncSubscribe,
+    {
+        synchronize(node, topics, self, Self::add_part, |sync| sync.stats())
+    }
+
+    fn add_part(&mut self, index: usize, stamp: i64, part: Part) -> Vec<M> {
+        self.queues.insert(index, stamp, part);
+        let positions: Option<Vec<usize>> = self
+            .queues
+            .queues
+            .iter()
+            .map(|queue| queue.iter().position(|(queued, _)| *queued == stamp))
+            .collect();
+        positions
+            .map(|positions| vec![self.queues.take(&positions)])
+            .unwrap_or_default()
+    }
+}
+
+/// Tuples of a synchronizer fed by subscribers
+///
+/// Created by [`ApproximateTimeSync::subscribe`] or
+/// [`ExactTimeSync::subscribe`]. Dropping it stops the subscribers.
+pub struct Synchronized<M> {
+    tuples: tokio::sync::Mutex<mpsc::UnboundedReceiver<M>>,
+    stats: Box<dyn Fn() -> SyncStats + Send + Sync>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl<M: SyncTuple> Synchronized<M> {
+    /// Wait for the next tuple
+    ///
+    /// Fails with [`Error::Closed`] once every subscriber has shut down.
+    pub async fn recv(&self) -> Result<M> {
+        self.tuples
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| Error::Closed("synchronized subscribers were shut down".into()))
+    }
+
+    /// Counters of emitted and dropped messages
+    pub fn stats(&self) -> SyncStats {
+        (self.stats)()
+    }
+}
+
+impl<M> Drop for Synchronized<M> {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+fn synchronize<M, S>(
+    node: &Node,
+    topics: &[&str],
+    sync: S,
+    add: fn(&mut S, usize, i64, Part) -> Vec<M>,
+    stats: fn(&S) -> SyncStats,
+) -> Result<Synchronized<M>>
+where
+    M: SyncSubscribe,
+    S: Send + 'static,
+{
+    if topics.len() != M::LEN {
+        return Err(Error::Configuration(format!(
+            "synchronizing {} message types needs {} topics, got {}",
+            M::LEN,
+            M::LEN,
+            topics.len()
+        )));
+    }
+    let sync = Arc::new(Mutex::new(sync));
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let feed_sync = sync.clone();
+    let feed: Feed = Arc::new(move |index, stamp, part| {
+        for tuple in add(&mut feed_sync.lock(), index, stamp, part) {
+            let _ = sender.send(tuple);
+        }
+    });
+    let tasks = M::spawn_feeds(node, topics, feed)?;
+    Ok(Synchronized {
+        tuples: tokio::sync::Mutex::new(receiver),
+        stats: Box::new(move || stats(&sync.lock())),
+        tasks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Header;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        header: Header,
+        value: u32,
+    }
+
+    impl Message for Reading {
+        fn type_name() -> &'static str {
+            "test_msgs/Reading"
+        }
+    }
+
+    impl Stamped for Reading {
+        fn header(&self) -> &Header {
+            &self.header
+        }
+
+        fn header_mut(&mut self) -> &mut Header {
+            &mut self.header
+        }
+    }
+
+    fn reading(stamp_ms: i64, value: u32) -> Reading {
+        Reading {
+            header: Header {
+                stamp_ns: stamp_ms * 1_000_000,
+                ..Default::default()
+            },
+            value,
+        }
+    }
+
+    fn values<const N: usize>(tuples: &[[&Reading; N]]) -> Vec<[u32; N]> {
+        tuples.iter().map(|tuple| tuple.map(|reading| reading.value)).collect()
+    }
+
+    type Triple = (Reading, Reading, Reading);
+
+    /// Deterministic jitter in `-4..=4` ms
+    fn jitter(seed: u64) -> i64 {
+        (seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407) >> 33) as i64
+            % 9
+            - 4
+    }
+
+    #[test]
+    fn test_approximate_matches_jittered_streams() {
+        let mut sync = ApproximateTimeSync::<Triple>::new(Duration::from_millis(10), 10);
+        let mut arrivals = Vec::new();
+        for i in 0..50u32 {
+            let base = i as i64 * 100;
+            for topic in 0..3u64 {
+                // The third topic loses every seventh message
+                if topic == 2 && i % 7 == 3 {
+                    continue;
+                }
+                let stamp = base + jitter(i as u64 * 3 + topic);
+                // Delivery order is shuffled within each period
+                let arrival = base + jitter(i as u64 * 7 + topic) * 10;
+                arrivals.push((arrival, topic, reading(stamp, i)));
+            }
+        }
+        arrivals.sort_by_key(|(arrival, topic, _)| (*arrival, *topic));
+
+        let mut emitted = Vec::new();
+        for (_, topic, reading) in arrivals {
+            emitted.extend(match topic {
+                0 => sync.add::<0>(reading),
+                1 => sync.add::<1>(reading),
+                _ => sync.add::<2>(reading),
+            });
+        }
+
+        let tuples: Vec<[&Reading; 3]> = emitted.iter().map(|(a, b, c)| [a, b, c]).collect();
+        let expected: Vec<[u32; 3]> = (0..50).filter(|i| i % 7 != 3).map(|i| [i; 3]).collect();
+        // The last period may still wait for a later message
+        assert!(values(&tuples).len() >= expected.len() - 1);
+        assert_eq!(values(&tuples)[..], expected[..values(&tuples).len()]);
+        for [a, b, c] in &tuples {
+            let stamps = [a, b, c].map(|reading| reading.header.stamp_ns);
+            let spread = stamps.iter().max().unwrap() - stamps.iter().min().unwrap();
+            assert!(spread <= 10_000_000);
+        }
+        assert_eq!(sync.stats().emitted, tuples.len() as u64);
+        assert_eq!(sync.stats().overflowed, 0);
+    }
+
+    #[test]
+    fn test_approximate_picks_closest_match() {
+        let mut sync = ApproximateTimeSync::<(Reading, Reading)>::new(Duration::from_millis(5), 10);
+        assert!(sync.add::<0>(reading(100, 1)).is_empty());
+        assert!(sync.add::<1>(reading(96, 10)).is_empty());
+        // 99 is closer to 100 than 96, but only known to be once 101 arrives
+        assert!(sync.add::<1>(reading(99, 11)).is_empty());
+        let emitted = sync.add::<1>(reading(101, 12));
+        let pairs: Vec<[&Reading; 2]> = emitted.iter().map(|(a, b)| [a, b]).collect();
+        assert_eq!(values(&pairs), [[1, 11]]);
+        // 96 was passed over, 101 is kept for the next pair
+        assert_eq!(sync.stats().unmatched, 1);
+        assert!(sync.add::<0>(reading(102, 2)).is_empty());
+        let emitted = sync.add::<1>(reading(105, 13));
+        let pairs: Vec<[&Reading; 2]> = emitted.iter().map(|(a, b)| [a, b]).collect();
+        assert_eq!(values(&pairs), [[2, 12]]);
+    }
+
+    #[test]
+    fn test_approximate_drops_unmatchable_messages() {
+        let mut sync = ApproximateTimeSync::<(Reading, Reading)>::new(Duration::from_millis(5), 10);
+        sync.add::<0>(reading(0, 1));
+        sync.add::<0>(reading(100, 2));
+        // Too far from 0, which can never be matched now
+        assert!(sync.add::<1>(reading(50, 10)).is_empty());
+        assert_eq!(sync.stats().unmatched, 2);
+        let emitted = sync.add::<1>(reading(98, 11));
+        assert_eq!(emitted.len(), 0);
+        let emitted = sync.add::<1>(reading(103, 12));
+        let pairs: Vec<[&Reading; 2]> = emitted.iter().map(|(a, b)| [a, b]).collect();
+        assert_eq!(values(&pairs), [[2, 11]]);
+
+        // Older than the pair just emitted
+        sync.add::<0>(reading(99, 3));
+        assert_eq!(sync.stats().unmatched, 3);
+    }
+
+    #[test]
+    fn test_queue_size_is_bounded() {
+        let mut sync = ApproximateTimeSync::<(Reading, Reading)>::new(Duration::from_millis(5), 3);
+        for i in 0..10 {
+            assert!(sync.add::<0>(reading(i * 10, i as u32)).is_empty());
+        }
+        assert_eq!(sync.stats().overflowed, 7);
+        let emitted = sync.add::<1>(reading(71, 100));
+        assert_eq!(emitted[0].0.value, 7);
+    }
+
+    #[test]
+    fn test_exact_matches_equal_stamps() {
+        let mut sync = ExactTimeSync::<Triple>::new(10);
+        assert!(sync.add::<0>(reading(10, 1)).is_none());
+        assert!(sync.add::<1>(reading(10, 2)).is_none());
+        assert!(sync.add::<0>(reading(20, 4)).is_none());
+        assert!(sync.add::<1>(reading(20, 5)).is_none());
+        // Completes 20, leaving 10 behind
+        let (a, b, c) = sync.add::<2>(reading(20, 6)).unwrap();
+        assert_eq!([a.value, b.value, c.value], [4, 5, 6]);
+        assert_eq!(sync.stats().unmatched, 2);
+        assert!(sync.add::<2>(reading(10, 3)).is_none());
+        assert_eq!(sync.stats().unmatched, 3);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_synchronizes_topics() {
+        let node = Node::new("sync_test").unwrap();
+        let camera = node.create_publisher::<Reading>("/sync_test/camera").unwrap();
+        let imu = node.create_publisher::<Reading>("/sync_test/imu").unwrap();
+        let sync = ApproximateTimeSync::<(Reading, Reading)>::new(Duration::from_millis(5), 10);
+        let topics = ["/sync_test/camera", "/sync_test/imu"];
+        let synchronized = sync.subscribe(&node, &topics).unwrap();
+
+        camera.publish(&reading(100, 1)).await.unwrap();
+        imu.publish(&reading(101, 2)).await.unwrap();
+        imu.publish(&reading(110, 3)).await.unwrap();
+        // Only a later image shows that none is closer to 101 than 100
+        camera.publish(&reading(112, 4)).await.unwrap();
+
+        let (image, sample) = tokio::time::timeout(Duration::from_secs(1), synchronized.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((image.value, sample.value), (1, 2));
+        assert_eq!(synchronized.stats().emitted, 1);
+
+        let sync = ExactTimeSync::<(Reading, Reading)>::new(10);
+        assert!(matches!(sync.subscribe(&node, &topics[..1]), Err(Error::Configuration(_))));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_exact_emits_tuples() {
+        let node = Node::new("sync_exact_test").unwrap();
+        let left = node.create_publisher::<Reading>("/sync_exact_test/left").unwrap();
+        let right = node.create_publisher::<Reading>("/sync_exact_test/right").unwrap();
+        let sync = ExactTimeSync::<(Reading, Reading)>::new(10);
+        let topics = ["/sync_exact_test/left", "/sync_exact_test/right"];
+        let synchronized = sync.subscribe(&node, &topics).unwrap();
+
+        left.publish(&reading(10, 1)).await.unwrap();
+        left.publish(&reading(20, 2)).await.unwrap();
+        right.publish(&reading(20, 3)).await.unwrap();
+
+        let (left, right) = tokio::time::timeout(Duration::from_secs(1), synchronized.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((left.value, right.value), (2, 3));
+        let stats = synchronized.stats();
+        assert_eq!((stats.emitted, stats.unmatched), (1, 1));
+    }
+}
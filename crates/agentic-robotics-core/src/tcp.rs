@@ -0,0 +1,909 @@
+//! TCP transport
+//!
+//! Reliable, ordered delivery to remote processes, e.g. a telemetry uplink
+//! to a ground station. Each local topic with a publisher keeps one
+//! connection per configured peer, sending length-prefixed frames. While a
+//! peer is unreachable, samples wait in a bounded send buffer whose
+//! [`OverflowPolicy`] decides between dropping and blocking publishers, and
+//! the connection is retried with exponential backoff. With the `tls`
+//! feature, connections can be secured with TLS, see `TlsConfig`.
+//!
+//! The only frame a listener sends back is a hello naming the codecs it
+//! decompresses. Publishers with a [`Compression`](crate::Compression)
+//! compress for listeners that accept it, and send uncompressed until the
+//! hello arrives or if it never does.
+
+use crate::compression::{self, Codec};
+use crate::error::{Error, Result};
+use crate::message::{is_raw, Message};
+use crate::qos::QosProfile;
+use crate::serialization::{deserialize_cdr, serialize_cdr, Format};
+use crate::subscriber::OverflowPolicy;
+use crate::time::{ClockType, Time};
+#[cfg(feature = "tls")]
+use crate::tls::{rustls_reason, TlsConfig, TlsContext};
+use crate::topic::{PublisherGuid, Sample, SampleQueue, Stamp, Topic, TopicBus, TopicType, Writer};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::AbortHandle;
+use tracing::{debug, warn};
+
+/// Node name shown in the graph for the transport's own endpoints
+const TRANSPORT_NODE: &str = "_tcp_transport";
+
+/// Frames above this size close the connection instead of being buffered
+const MAX_FRAME_LEN: usize = 64 << 20;
+
+/// Connection events buffered per watcher before it starts lagging
+const EVENT_CAPACITY: usize = 64;
+
+/// Settings of a [`TcpTransport`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TcpConfig {
+    /// Remote listeners that receive every topic published by the node
+    pub peers: Vec<SocketAddr>,
+    /// Address to accept connections from remote publishers on
+    pub listen: Option<SocketAddr>,
+    /// Messages buffered per connection while the peer is slow or
+    /// unreachable
+    pub send_buffer: usize,
+    /// What happens to messages published while the send buffer is full
+    pub overflow: OverflowPolicy,
+    /// Delay before the first reconnect attempt, doubled after each failure
+    pub reconnect_initial: Duration,
+    /// Longest delay between reconnect attempts
+    pub reconnect_max: Duration,
+    /// Secure connections with TLS, in both directions
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            listen: None,
+            send_buffer: 1024,
+            overflow: OverflowPolicy::DropOldest,
+            reconnect_initial: Duration::from_millis(100),
+            reconnect_max: Duration::from_secs(5),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+/// A change in a publisher's connection to a peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connected(SocketAddr),
+    Disconnected(SocketAddr),
+    /// The peer was reached but the TLS handshake failed; retried like an
+    /// unreachable peer
+    HandshakeFailed { peer: SocketAddr, reason: String },
+    /// The watcher fell behind and `missed` events were discarded
+    Lagged { missed: u64 },
+}
+
+/// Stream of [`ConnectionEvent`]s, created by
+/// [`Publisher::connection_events`](crate::Publisher::connection_events)
+pub struct ConnectionWatcher {
+    events: Option<broadcast::Receiver<ConnectionEvent>>,
+}
+
+impl ConnectionWatcher {
+    pub(crate) fn new(events: Option<broadcast::Receiver<ConnectionEvent>>) -> Self {
+        Self { events }
+    }
+
+    /// Wait for the next event, or `None` if the publisher has no TCP
+    /// connections
+    pub async fn next(&mut self) -> Option<ConnectionEvent> {
+        match self.events.as_mut()?.recv().await {
+            Ok(event) => Some(event),
+            Err(RecvError::Lagged(missed)) => Some(ConnectionEvent::Lagged { missed }),
+            Err(RecvError::Closed) => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct FrameHeader {
+    pub(crate) topic: String,
+    type_name: String,
+    type_hash: u64,
+    format: Format,
+    publisher: PublisherGuid,
+    pub(crate) seq: u64,
+    stamp_ns: i64,
+    clock: ClockType,
+    compression: Codec,
+    uncompressed_len: u32,
+}
+
+/// Sent by a listener once it accepted a connection
+#[derive(Debug, Serialize, Deserialize)]
+struct Hello {
+    /// Codecs the listener decompresses
+    accepts: Vec<Codec>,
+}
+
+/// Length-prefixed frame: `u32` length of the rest, `u16` header length,
+/// CDR header, payload; all integers big-endian
+fn encode(header: &FrameHeader, payload: &[u8]) -> Result<Vec<u8>> {
+    let encoded = serialize_cdr(header)?;
+    let header_len = u16::try_from(encoded.len())
+        .map_err(|_| Error::Serialization("frame header too large".into()))?;
+    let len = 2 + encoded.len() + payload.len();
+    if len > MAX_FRAME_LEN {
+        return Err(Error::Serialization(format!(
+            "{} byte frame exceeds the {} byte limit",
+            len, MAX_FRAME_LEN
+        )));
+    }
+    let mut frame = Vec::with_capacity(4 + len);
+    frame.extend_from_slice(&(len as u32).to_be_bytes());
+    frame.extend_from_slice(&header_len.to_be_bytes());
+    frame.extend_from_slice(&encoded);
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// Split a frame body, without its length prefix, into header and payload
+pub(crate) fn decode(body: &[u8]) -> Result<(FrameHeader, &[u8])> {
+    let invalid = || Error::Serialization("truncated frame".into());
+    let header_len = u16::from_be_bytes(body.get(..2).ok_or_else(invalid)?.try_into().unwrap());
+    let header = body.get(2..2 + header_len as usize).ok_or_else(invalid)?;
+    Ok((deserialize_cdr(header)?, &body[2 + header_len as usize..]))
+}
+
+/// Read one frame body, or `None` once the peer closed the connection
+pub(crate) async fn read_frame(
+    stream: &mut (impl AsyncReadExt + Unpin),
+) -> Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(Error::Serialization(format!("{} byte frame is too large", len)));
+    }
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).await?;
+    Ok(Some(body))
+}
+
+/// A plain or TLS-secured connection
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Stream for S {}
+
+/// One publisher topic's connection to one peer
+struct Connection {
+    peer: SocketAddr,
+    queue: Arc<SampleQueue>,
+    connected: AtomicBool,
+    /// Codecs the peer said it decompresses on this connection
+    accepts: Mutex<Vec<Codec>>,
+}
+
+/// The connections carrying one topic to every peer
+///
+/// Shared by all publishers of the topic in a node; dropping the last one
+/// closes the connections.
+pub(crate) struct TcpUplink {
+    /// `None` without peers
+    link: Option<Arc<Topic>>,
+    connections: Vec<Arc<Connection>>,
+    events: broadcast::Sender<ConnectionEvent>,
+    tasks: Vec<AbortHandle>,
+}
+
+impl TcpUplink {
+    /// Number of peers currently connected
+    pub(crate) fn connection_count(&self) -> usize {
+        self.connections
+            .iter()
+            .filter(|c| c.connected.load(Ordering::Acquire))
+            .count()
+    }
+
+    pub(crate) fn watch(&self) -> ConnectionWatcher {
+        ConnectionWatcher::new(Some(self.events.subscribe()))
+    }
+
+    /// Messages discarded because a send buffer was full
+    pub(crate) fn dropped(&self) -> u64 {
+        self.connections.iter().map(|c| c.queue.dropped()).sum()
+    }
+}
+
+impl Drop for TcpUplink {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        if let Some(link) = &self.link {
+            for connection in &self.connections {
+                TopicBus::global().detach_subscriber(link, &connection.queue);
+            }
+        }
+    }
+}
+
+/// Per topic received from peers, the local topic it is published on and
+/// the writer publishing it
+type IncomingTopics = HashMap<String, (Arc<Topic>, Arc<Writer>)>;
+
+struct TransportState {
+    config: TcpConfig,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsContext>,
+    prefix: u64,
+    uplinks: Mutex<HashMap<String, Weak<TcpUplink>>>,
+    /// Type hash of local subscribers per topic, `None` if they accept any
+    subscribed: Mutex<HashMap<String, Option<u64>>>,
+    incoming: Mutex<IncomingTopics>,
+}
+
+/// Process-wide TCP bridge for the topic bus
+///
+/// Nodes configured with [`TransportConfig::Tcp`](crate::TransportConfig::Tcp)
+/// share one transport per [`TcpConfig`].
+pub struct TcpTransport {
+    state: Arc<TransportState>,
+    local_addr: Option<SocketAddr>,
+    listener: Option<AbortHandle>,
+}
+
+impl TcpTransport {
+    /// The transport for `config`, starting it if no node uses it yet
+    ///
+    /// Binds [`TcpConfig::listen`] if set. Must be called from within a
+    /// Tokio runtime.
+    pub fn shared(config: &TcpConfig) -> Result<Arc<Self>> {
+        static TRANSPORTS: OnceLock<Mutex<HashMap<TcpConfig, Weak<TcpTransport>>>> =
+            OnceLock::new();
+        let mut transports = TRANSPORTS.get_or_init(Default::default).lock();
+        if let Some(transport) = transports.get(config).and_then(Weak::upgrade) {
+            return Ok(transport);
+        }
+        if config.send_buffer == 0 {
+            return Err(Error::Configuration("TCP send buffer must be non-zero".into()));
+        }
+
+        #[cfg(feature = "tls")]
+        let tls = config.tls.as_ref().map(TlsContext::new).transpose()?;
+        #[cfg(feature = "tls")]
+        if config.listen.is_some() && tls.as_ref().is_some_and(|tls| !tls.can_accept()) {
+            return Err(Error::Configuration(
+                "a TLS listener needs a cert and key".into(),
+            ));
+        }
+
+        let state = Arc::new(TransportState {
+            config: config.clone(),
+            #[cfg(feature = "tls")]
+            tls,
+            prefix: PublisherGuid::local_prefix(),
+            uplinks: Mutex::new(HashMap::new()),
+            subscribed: Mutex::new(HashMap::new()),
+            incoming: Mutex::new(HashMap::new()),
+        });
+        let (local_addr, listener) = match config.listen {
+            Some(addr) => {
+                let listener = std::net::TcpListener::bind(addr)?;
+                listener.set_nonblocking(true)?;
+                let listener = TcpListener::from_std(listener)?;
+                let local_addr = listener.local_addr()?;
+                debug!("TCP transport listening on {}", local_addr);
+                let task = tokio::spawn(state.clone().accept(listener));
+                (Some(local_addr), Some(task.abort_handle()))
+            }
+            None => (None, None),
+        };
+        let transport = Arc::new(Self {
+            state,
+            local_addr,
+            listener,
+        });
+        transports.insert(config.clone(), Arc::downgrade(&transport));
+        Ok(transport)
+    }
+
+    /// Send the local samples of `topic` to every peer
+    pub(crate) fn advertise_publisher<T: Message>(&self, topic: &str) -> Result<Arc<TcpUplink>> {
+        let state = &self.state;
+        let mut uplinks = state.uplinks.lock();
+        if let Some(uplink) = uplinks.get(topic).and_then(Weak::upgrade) {
+            return Ok(uplink);
+        }
+
+        let bus = TopicBus::global();
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let mut link = None;
+        let mut connections: Vec<Arc<Connection>> = Vec::new();
+        for &peer in &state.config.peers {
+            let queue = Arc::new(
+                SampleQueue::new(Some(state.config.send_buffer), state.config.overflow)
+                    .with_node(Some(TRANSPORT_NODE.to_string()))
+                    .forwarding(),
+            );
+            match bus.attach_subscriber(topic, TopicType::of::<T>(), queue.clone()) {
+                Ok(attached) => link = Some(attached),
+                Err(e) => {
+                    if let Some(link) = &link {
+                        for connection in &connections {
+                            bus.detach_subscriber(link, &connection.queue);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+            connections.push(Arc::new(Connection {
+                peer,
+                queue,
+                connected: AtomicBool::new(false),
+                accepts: Mutex::new(Vec::new()),
+            }));
+        }
+
+        let tasks = connections
+            .iter()
+            .map(|connection| {
+                let sender = Sender {
+                    state: state.clone(),
+                    connection: connection.clone(),
+                    topic: topic.to_string(),
+                    type_hash: T::type_hash(),
+                    events: events.clone(),
+                };
+                tokio::spawn(sender.run()).abort_handle()
+            })
+            .collect();
+        let uplink = Arc::new(TcpUplink {
+            link,
+            connections,
+            events,
+            tasks,
+        });
+        uplinks.retain(|_, uplink| uplink.strong_count() > 0);
+        uplinks.insert(topic.to_string(), Arc::downgrade(&uplink));
+        Ok(uplink)
+    }
+
+    /// Deliver samples of `topic` received from remote publishers
+    pub fn advertise_subscriber<T: Message>(&self, topic: &str) -> Result<()> {
+        let hash = (!is_raw::<T>()).then(T::type_hash);
+        self.state.subscribed.lock().insert(topic.to_string(), hash);
+        Ok(())
+    }
+
+    /// Address connections are accepted on, if listening
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        if let Some(listener) = &self.listener {
+            listener.abort();
+        }
+        for (_, (link, writer)) in self.state.incoming.lock().drain() {
+            TopicBus::global().detach_publisher(&link, &writer);
+        }
+    }
+}
+
+/// Sends one topic's samples to one peer, reconnecting as needed
+struct Sender {
+    state: Arc<TransportState>,
+    connection: Arc<Connection>,
+    topic: String,
+    type_hash: u64,
+    events: broadcast::Sender<ConnectionEvent>,
+}
+
+impl Sender {
+    async fn run(self) {
+        let peer = self.connection.peer;
+        let config = &self.state.config;
+        let mut backoff = config.reconnect_initial;
+        // A frame popped from the queue but not yet written
+        let mut pending: Option<Vec<u8>> = None;
+        loop {
+            let stream = match self.connect(peer).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!("Connecting to {} for '{}' failed: {}", peer, self.topic, e);
+                    self.report(e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(config.reconnect_max);
+                    continue;
+                }
+            };
+            backoff = config.reconnect_initial;
+            self.connection.connected.store(true, Ordering::Release);
+            let _ = self.events.send(ConnectionEvent::Connected(peer));
+            debug!("Connected to {} for '{}'", peer, self.topic);
+
+            let result = self.stream(stream, &mut pending).await;
+            self.connection.connected.store(false, Ordering::Release);
+            match result {
+                Ok(()) => return,
+                Err(e) => {
+                    debug!("Connection to {} for '{}' lost: {}", peer, self.topic, e);
+                    let _ = self.events.send(ConnectionEvent::Disconnected(peer));
+                    if matches!(e, Error::TlsHandshake { .. }) {
+                        self.report(e);
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(config.reconnect_max);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Open a connection to `peer`, with a TLS handshake if configured
+    async fn connect(&self, peer: SocketAddr) -> Result<Box<dyn Stream>> {
+        let stream = TcpStream::connect(peer).await?;
+        let _ = stream.set_nodelay(true);
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.state.tls {
+            return Ok(Box::new(tls.connect(stream, peer).await?));
+        }
+        Ok(Box::new(stream))
+    }
+
+    /// Publish a TLS handshake failure as a [`ConnectionEvent`]
+    fn report(&self, e: Error) {
+        if let Error::TlsHandshake { peer, reason } = e {
+            warn!("TLS handshake with {} for '{}' failed: {}", peer, self.topic, reason);
+            let _ = self.events.send(ConnectionEvent::HandshakeFailed { peer, reason });
+        }
+    }
+
+    /// Write frames until the connection fails; `Ok` once the queue closes
+    async fn stream(&self, stream: Box<dyn Stream>, pending: &mut Option<Vec<u8>>) -> Result<()> {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        // Nothing is compressed until the peer says what it accepts
+        self.connection.accepts.lock().clear();
+        let hangup = self.listen(&mut reader);
+        tokio::pin!(hangup);
+        loop {
+            if let Some(frame) = pending.as_ref() {
+                writer.write_all(frame).await?;
+                *pending = None;
+            }
+            let sample = tokio::select! {
+                biased;
+                e = &mut hangup => return Err(e),
+                sample = self.connection.queue.pop() => sample,
+            };
+            let Some(sample) = sample else {
+                return Ok(());
+            };
+            match self.encode(&sample) {
+                Ok(frame) => *pending = frame,
+                Err(e) => warn!("Dropping sample on '{}': {}", self.topic, e),
+            }
+        }
+    }
+
+    /// Take the peer's hello, returning why the connection ended once it
+    /// sends anything else or hangs up
+    async fn listen(&self, reader: &mut (impl AsyncRead + Unpin)) -> Error {
+        loop {
+            match read_frame(reader).await {
+                Ok(Some(body)) => match deserialize_cdr::<Hello>(&body) {
+                    Ok(hello) => *self.connection.accepts.lock() = hello.accepts,
+                    Err(e) => return e,
+                },
+                Ok(None) => return Error::Connection("closed by peer".into()),
+                // Under TLS 1.3 a listener rejects our certificate only
+                // after the handshake appeared to succeed
+                #[cfg(feature = "tls")]
+                Err(Error::Io(e)) => {
+                    return match rustls_reason(&e) {
+                        Some(reason) => Error::TlsHandshake {
+                            peer: self.connection.peer,
+                            reason,
+                        },
+                        None => e.into(),
+                    }
+                }
+                Err(e) => return e,
+            }
+        }
+    }
+
+    /// The frame for a local sample, or `None` for samples from elsewhere
+    fn encode(&self, sample: &Sample) -> Result<Option<Vec<u8>>> {
+        let Some(stamp) = sample.stamp() else {
+            return Ok(None);
+        };
+        // Samples received from remote publishers are not sent back out
+        if stamp.publisher.prefix != self.state.prefix {
+            return Ok(None);
+        }
+        let payload = sample.bytes()?;
+        let accepts = self.connection.accepts.lock().clone();
+        let (compression, body) = sample.compression().apply(&payload, &accepts)?;
+        let header = FrameHeader {
+            topic: self.topic.clone(),
+            type_name: sample.type_name().to_string(),
+            type_hash: self.type_hash,
+            format: sample.format(),
+            publisher: stamp.publisher,
+            seq: stamp.seq,
+            stamp_ns: stamp.time.as_nanos(),
+            clock: stamp.clock,
+            compression,
+            uncompressed_len: payload.len() as u32,
+        };
+        encode(&header, &body).map(Some)
+    }
+}
+
+impl TransportState {
+    async fn accept(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    debug!("Accepted TCP connection from {}", peer);
+                    tokio::spawn(self.clone().handshake(stream, peer));
+                }
+                Err(e) => warn!("Accepting TCP connection failed: {}", e),
+            }
+        }
+    }
+
+    /// Secure an accepted connection if TLS is configured, greet the peer,
+    /// then receive
+    async fn handshake(self: Arc<Self>, stream: TcpStream, peer: SocketAddr) {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            match tls.accept(stream, peer).await {
+                Ok(stream) => self.clone().greet(stream, peer).await,
+                Err(e) => warn!("Rejecting TCP connection from {}: {}", peer, e),
+            }
+            return;
+        }
+        self.greet(stream, peer).await
+    }
+
+    /// Tell the peer which codecs it may compress with, then receive
+    async fn greet(
+        self: Arc<Self>,
+        mut stream: impl AsyncRead + AsyncWrite + Unpin,
+        peer: SocketAddr,
+    ) {
+        let hello = Hello {
+            accepts: Codec::supported(),
+        };
+        let sent = match serialize_cdr(&hello) {
+            Ok(body) => {
+                let mut frame = (body.len() as u32).to_be_bytes().to_vec();
+                frame.extend_from_slice(&body);
+                stream.write_all(&frame).await.map_err(Error::from)
+            }
+            Err(e) => Err(e),
+        };
+        match sent {
+            Ok(()) => self.receive(stream, peer).await,
+            Err(e) => warn!("Greeting TCP connection from {} failed: {}", peer, e),
+        }
+    }
+
+    async fn receive(self: Arc<Self>, mut stream: impl AsyncRead + Unpin, peer: SocketAddr) {
+        loop {
+            let body = match read_frame(&mut stream).await {
+                Ok(Some(body)) => body,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Closing TCP connection from {}: {}", peer, e);
+                    break;
+                }
+            };
+            match decode(&body) {
+                Ok((header, payload)) => self.on_frame(header, payload).await,
+                Err(e) => warn!("Bad frame from {}: {}", peer, e),
+            }
+        }
+        debug!("TCP connection from {} closed", peer);
+    }
+
+    async fn on_frame(&self, header: FrameHeader, payload: &[u8]) {
+        let Some(local) = self.subscribed.lock().get(&header.topic).copied() else {
+            return;
+        };
+        if local.is_some_and(|hash| hash != header.type_hash) {
+            warn!(
+                "Dropping '{}' from {}: type {} does not match local subscribers",
+                header.topic, header.publisher, header.type_name
+            );
+            return;
+        }
+        let len = header.uncompressed_len as usize;
+        let payload = match compression::expand(header.compression, payload.to_vec(), len) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Dropping '{}' from {}: {}", header.topic, header.publisher, e);
+                return;
+            }
+        };
+        let Some((link, writer)) = self.incoming(&header.topic) else {
+            return;
+        };
+        let sample = Sample::new(
+            payload,
+            header.format,
+            Arc::from(header.type_name.as_str()),
+        )
+        .with_stamp(Stamp {
+            publisher: header.publisher,
+            seq: header.seq,
+            time: Time::from_nanos(header.stamp_ns),
+            clock: header.clock,
+        });
+        if let Err(e) = link.deliver(&writer, sample).await {
+            warn!("Delivering remote sample on '{}' failed: {}", header.topic, e);
+        }
+    }
+
+    /// The writer injecting remote samples into `topic`, created on first use
+    fn incoming(&self, topic: &str) -> Option<(Arc<Topic>, Arc<Writer>)> {
+        let mut incoming = self.incoming.lock();
+        if let Some(bridge) = incoming.get(topic) {
+            return Some(bridge.clone());
+        }
+        let writer = Arc::new(Writer::new(
+            QosProfile::default(),
+            Some(TRANSPORT_NODE.to_string()),
+        ));
+        match TopicBus::global().attach_publisher(topic, None, writer.clone()) {
+            Ok(link) => {
+                incoming.insert(topic.to_string(), (link.clone(), writer.clone()));
+                Some((link, writer))
+            }
+            Err(e) => {
+                warn!("Cannot receive '{}' over TCP: {}", topic, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+    use crate::transport::TransportConfig;
+    use crate::Node;
+
+    fn config(peer: SocketAddr, send_buffer: usize, overflow: OverflowPolicy) -> TransportConfig {
+        TransportConfig::Tcp(TcpConfig {
+            peers: vec![peer],
+            send_buffer,
+            overflow,
+            reconnect_initial: Duration::from_millis(10),
+            reconnect_max: Duration::from_millis(50),
+            ..Default::default()
+        })
+    }
+
+    async fn next_seq(stream: &mut TcpStream) -> u64 {
+        let body = read_frame(stream).await.unwrap().unwrap();
+        decode(&body).unwrap().0.seq
+    }
+
+    #[tokio::test]
+    async fn test_outage_buffers_up_to_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let node = Node::new("tcp_outage")
+            .unwrap()
+            .with_transport(config(addr, 5, OverflowPolicy::DropNewest))
+            .unwrap();
+        let publisher = node.create_publisher::<RobotState>("/test/tcp/outage").unwrap();
+        let mut events = publisher.connection_events();
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert_eq!(events.next().await, Some(ConnectionEvent::Connected(addr)));
+        assert_eq!(publisher.connection_count(), 1);
+        for _ in 0..3 {
+            publisher.publish(&RobotState::default()).await.unwrap();
+        }
+        for seq in 0..3 {
+            assert_eq!(next_seq(&mut stream).await, seq);
+        }
+
+        // Kill the remote end mid-stream
+        drop(stream);
+        drop(listener);
+        assert_eq!(events.next().await, Some(ConnectionEvent::Disconnected(addr)));
+        assert_eq!(publisher.connection_count(), 0);
+
+        // Five of ten messages fit the send buffer, the rest are dropped
+        for _ in 0..10 {
+            publisher.publish(&RobotState::default()).await.unwrap();
+        }
+        assert_eq!(publisher.uplink_dropped_count(), 5);
+
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert_eq!(events.next().await, Some(ConnectionEvent::Connected(addr)));
+        for seq in 3..8 {
+            assert_eq!(next_seq(&mut stream).await, seq);
+        }
+        publisher.publish(&RobotState::default()).await.unwrap();
+        assert_eq!(next_seq(&mut stream).await, 13);
+    }
+
+    #[tokio::test]
+    async fn test_block_policy_applies_backpressure() {
+        // Nothing listens on the port once the listener is gone
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let node = Node::new("tcp_backpressure")
+            .unwrap()
+            .with_transport(config(addr, 2, OverflowPolicy::Block))
+            .unwrap();
+        let publisher = node.create_publisher::<RobotState>("/test/tcp/block").unwrap();
+        publisher.publish(&RobotState::default()).await.unwrap();
+        publisher.publish(&RobotState::default()).await.unwrap();
+
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(100),
+            publisher.publish(&RobotState::default()),
+        )
+        .await;
+        assert!(blocked.is_err());
+        assert_eq!(publisher.uplink_dropped_count(), 0);
+        assert_eq!(publisher.connection_count(), 0);
+    }
+
+    #[cfg(feature = "lz4")]
+    fn cloud() -> crate::PointCloud {
+        let point = crate::message::Point3D {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        crate::PointCloud {
+            points: vec![point; 500],
+            intensities: vec![0.5; 500],
+            timestamp: 7,
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[tokio::test]
+    async fn test_compresses_only_for_listeners_that_accept_it() {
+        use crate::{Compression, PublisherOptions};
+
+        let greeting = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let node = Node::new("tcp_compression")
+            .unwrap()
+            .with_transport(TransportConfig::Tcp(TcpConfig {
+                peers: vec![greeting.local_addr().unwrap(), silent.local_addr().unwrap()],
+                ..Default::default()
+            }))
+            .unwrap();
+        let options = PublisherOptions::new().compression(Compression::Lz4);
+        let publisher = node
+            .create_publisher_with_options::<crate::PointCloud>("/test/tcp/compressed", options)
+            .unwrap();
+
+        // One listener accepts lz4, the other never says what it accepts
+        let (mut lz4, _) = greeting.accept().await.unwrap();
+        let hello = serialize_cdr(&Hello {
+            accepts: vec![Codec::Lz4],
+        })
+        .unwrap();
+        lz4.write_all(&(hello.len() as u32).to_be_bytes()).await.unwrap();
+        lz4.write_all(&hello).await.unwrap();
+        let (mut plain, _) = silent.accept().await.unwrap();
+        let mut events = publisher.connection_events();
+        while publisher.connection_count() < 2 {
+            events.next().await;
+        }
+        // Let the publisher read the hello
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        publisher.publish(&cloud()).await.unwrap();
+
+        for (stream, codec) in [(&mut lz4, Codec::Lz4), (&mut plain, Codec::None)] {
+            let body = read_frame(stream).await.unwrap().unwrap();
+            let (header, payload) = decode(&body).unwrap();
+            assert_eq!(header.compression, codec);
+            let len = header.uncompressed_len as usize;
+            assert_eq!(payload.len() < len, codec == Codec::Lz4);
+            let payload = compression::expand(codec, payload.to_vec(), len).unwrap();
+            let received: crate::PointCloud = deserialize_cdr(&payload).unwrap();
+            assert_eq!(received.points.len(), 500);
+            assert_eq!(received.intensities, cloud().intensities);
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[tokio::test]
+    async fn test_listener_decompresses_frames() {
+        let config = TcpConfig {
+            listen: Some("127.0.0.1:0".parse().unwrap()),
+            ..Default::default()
+        };
+        let node = Node::new("tcp_decompressing")
+            .unwrap()
+            .with_transport(TransportConfig::Tcp(config.clone()))
+            .unwrap();
+        let subscriber = node
+            .create_subscriber::<crate::PointCloud>("/test/tcp/decompressed")
+            .unwrap();
+        let addr = TcpTransport::shared(&config).unwrap().local_addr().unwrap();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let body = read_frame(&mut stream).await.unwrap().unwrap();
+        let hello: Hello = deserialize_cdr(&body).unwrap();
+        assert!(hello.accepts.contains(&Codec::Lz4));
+
+        let payload = serialize_cdr(&cloud()).unwrap();
+        let header = FrameHeader {
+            topic: "/test/tcp/decompressed".into(),
+            type_name: crate::PointCloud::type_name().into(),
+            type_hash: crate::PointCloud::type_hash(),
+            format: Format::Cdr,
+            publisher: PublisherGuid { prefix: 1, id: 1 },
+            seq: 0,
+            stamp_ns: 0,
+            clock: ClockType::SystemTime,
+            compression: Codec::Lz4,
+            uncompressed_len: payload.len() as u32,
+        };
+        let compressed = compression::compress(crate::Compression::Lz4, &payload).unwrap();
+        stream.write_all(&encode(&header, &compressed).unwrap()).await.unwrap();
+
+        let received = tokio::time::timeout(Duration::from_secs(5), subscriber.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.points.len(), 500);
+        assert_eq!(received.timestamp, 7);
+    }
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let header = FrameHeader {
+            topic: "/robot/state".into(),
+            type_name: RobotState::type_name().into(),
+            type_hash: RobotState::type_hash(),
+            format: Format::Cdr,
+            publisher: PublisherGuid { prefix: 1, id: 2 },
+            seq: 9,
+            stamp_ns: 5,
+            clock: ClockType::SystemTime,
+            compression: Codec::None,
+            uncompressed_len: 7,
+        };
+        let frame = encode(&header, b"payload").unwrap();
+        assert_eq!(u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize, frame.len() - 4);
+        let (decoded, payload) = decode(&frame[4..]).unwrap();
+        assert_eq!(decoded.seq, 9);
+        assert_eq!(payload, b"payload");
+        assert!(decode(&frame[4..8]).is_err());
+    }
+}
@@ -0,0 +1,823 @@
+//! Coordinate frame transforms
+//!
+//! Every sensor, link and map of a robot has its own coordinate frame, and
+//! the frames form a tree: each frame has one parent and a transform to it
+//! that may change over time. Publishers broadcast transforms with a
+//! [`TransformBroadcaster`] on [`TF_TOPIC`], and transforms that never
+//! change, such as sensor mounts, once with a [`StaticTransformBroadcaster`]
+//! on the latched [`TF_STATIC_TOPIC`].
+//!
+//! A [`TransformBuffer`] keeps the recent history of every transform and
+//! answers "where is frame `child` in frame `parent` at time `t`" by chaining
+//! the transforms along the tree, interpolating between the buffered
+//! samples of each. Like ROS `tf2`, it refuses to extrapolate beyond the
+//! buffered history:
+//!
+//! ```
+//! use agentic_robotics_core::tf::{Transform, TransformBuffer, TransformStamped};
+//! use agentic_robotics_core::Time;
+//!
+//! let buffer = TransformBuffer::default();
+//! let at = |secs: i64, x: f64| {
+//!     let transform = Transform::from_translation([x, 0.0, 0.0]);
+//!     TransformStamped::new("odom", "base_link", transform, Time::from_nanos(secs * 1_000_000_000))
+//! };
+//! buffer.set_transform(at(1, 0.0), false).unwrap();
+//! buffer.set_transform(at(2, 1.0), false).unwrap();
+//!
+//! let halfway = buffer.lookup("odom", "base_link", Time::from_nanos(1_500_000_000)).unwrap();
+//! assert_eq!(halfway.translation, [0.5, 0.0, 0.0]);
+//! assert!(buffer.lookup("odom", "base_link", Time::from_nanos(3_000_000_000)).is_err());
+//! ```
+
+use crate::error::Result;
+use crate::message::Message;
+use crate::node::Node;
+use crate::publisher::Publisher;
+use crate::time::Time;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::ops::Mul;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Conventional topic carrying transforms that change over time
+pub const TF_TOPIC: &str = "/tf";
+
+/// Conventional latched topic carrying transforms that never change
+pub const TF_STATIC_TOPIC: &str = "/tf_static";
+
+/// How long a [`TransformBuffer`] keeps transforms by default
+pub const DEFAULT_HISTORY: Duration = Duration::from_secs(10);
+
+/// A rigid transform: a rotation followed by a translation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub translation: [f64; 3],
+    /// Unit quaternion `[x, y, z, w]`
+    pub rotation: [f64; 4],
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: [0.0; 3],
+        rotation: [0.0, 0.0, 0.0, 1.0],
+    };
+
+    pub fn new(translation: [f64; 3], rotation: [f64; 4]) -> Self {
+        Self {
+            translation,
+            rotation,
+        }
+    }
+
+    pub fn from_translation(translation: [f64; 3]) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// A rotation by `angle` radians about `axis`
+    pub fn from_axis_angle(axis: [f64; 3], angle: f64) -> Self {
+        let norm = dot3(axis, axis).sqrt();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        let [x, y, z] = axis.map(|v| v / norm * sin);
+        Self {
+            rotation: [x, y, z, cos],
+            ..Self::IDENTITY
+        }
+    }
+
+    /// The transform that undoes this one
+    pub fn inverse(&self) -> Self {
+        let [x, y, z, w] = self.rotation;
+        let rotation = [-x, -y, -z, w];
+        let [tx, ty, tz] = rotate(rotation, self.translation);
+        Self {
+            translation: [-tx, -ty, -tz],
+            rotation,
+        }
+    }
+
+    /// `point`, given in the child frame, in the parent frame
+    pub fn transform_point(&self, point: [f64; 3]) -> [f64; 3] {
+        let [x, y, z] = rotate(self.rotation, point);
+        let [tx, ty, tz] = self.translation;
+        [x + tx, y + ty, z + tz]
+    }
+
+    /// The transform a fraction `t` of the way from `self` to `other`,
+    /// interpolating the translation linearly and the rotation spherically
+    pub fn interpolate(&self, other: &Transform, t: f64) -> Self {
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Self {
+            translation: [0, 1, 2].map(|i| lerp(self.translation[i], other.translation[i])),
+            rotation: slerp(self.rotation, other.rotation, t),
+        }
+    }
+
+    fn normalized(mut self) -> Self {
+        let norm = dot4(self.rotation, self.rotation).sqrt();
+        if norm > 0.0 {
+            self.rotation = self.rotation.map(|v| v / norm);
+        }
+        self
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Chains transforms: `a * b` maps the child frame of `b` into the parent
+/// frame of `a`
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            translation: self.transform_point(rhs.translation),
+            rotation: quat_mul(self.rotation, rhs.rotation),
+        }
+    }
+}
+
+/// The transform from frame `child` to frame `parent` at time `stamp`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransformStamped {
+    pub parent: String,
+    pub child: String,
+    pub translation: [f64; 3],
+    /// Unit quaternion `[x, y, z, w]`
+    pub rotation: [f64; 4],
+    pub stamp: Time,
+}
+
+impl TransformStamped {
+    pub fn new(
+        parent: impl Into<String>,
+        child: impl Into<String>,
+        transform: Transform,
+        stamp: Time,
+    ) -> Self {
+        Self {
+            parent: parent.into(),
+            child: child.into(),
+            translation: transform.translation,
+            rotation: transform.rotation,
+            stamp,
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::new(self.translation, self.rotation)
+    }
+}
+
+impl Message for TransformStamped {
+    fn type_name() -> &'static str {
+        "ros3_msgs/TransformStamped"
+    }
+}
+
+/// The transforms broadcast at once on [`TF_TOPIC`] or [`TF_STATIC_TOPIC`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TfMessage {
+    pub transforms: Vec<TransformStamped>,
+}
+
+impl Message for TfMessage {
+    fn type_name() -> &'static str {
+        "ros3_msgs/TFMessage"
+    }
+}
+
+/// Why a transform could not be stored or looked up
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TfError {
+    #[error("Unknown frame '{0}'")]
+    UnknownFrame(String),
+
+    #[error("Frames '{parent}' and '{child}' are not connected")]
+    DisconnectedTree { parent: String, child: String },
+
+    #[error(
+        "Lookup of '{frame}' at {time} would extrapolate beyond its history from {earliest} to {latest}"
+    )]
+    Extrapolation {
+        /// The child frame of the transform without a sample at `time`
+        frame: String,
+        time: Time,
+        earliest: Time,
+        latest: Time,
+    },
+
+    #[error("Transform from '{child}' to '{parent}' would close a cycle")]
+    Cycle { parent: String, child: String },
+}
+
+/// The buffered transforms of a frame to its parent
+#[derive(Debug)]
+struct Edge {
+    parent: String,
+    history: History,
+}
+
+#[derive(Debug)]
+enum History {
+    Static(Transform),
+    /// Samples in stamp order
+    Dynamic(VecDeque<(Time, Transform)>),
+}
+
+impl Edge {
+    fn sample(&self, child: &str, time: Time) -> std::result::Result<Transform, TfError> {
+        let samples = match &self.history {
+            History::Static(transform) => return Ok(*transform),
+            History::Dynamic(samples) => samples,
+        };
+        let (earliest, latest) = match (samples.front(), samples.back()) {
+            (Some(first), Some(last)) => (first.0, last.0),
+            _ => return Err(TfError::UnknownFrame(child.to_string())),
+        };
+        if time < earliest || time > latest {
+            return Err(TfError::Extrapolation {
+                frame: child.to_string(),
+                time,
+                earliest,
+                latest,
+            });
+        }
+        let next = samples.partition_point(|(stamp, _)| *stamp < time);
+        let (after_stamp, after) = samples[next];
+        if after_stamp == time {
+            return Ok(after);
+        }
+        let (before_stamp, before) = samples[next - 1];
+        let t = (time.as_nanos() - before_stamp.as_nanos()) as f64
+            / (after_stamp.as_nanos() - before_stamp.as_nanos()) as f64;
+        Ok(before.interpolate(&after, t))
+    }
+
+    /// The latest sample's stamp, `None` for static transforms
+    fn latest(&self) -> Option<Time> {
+        match &self.history {
+            History::Static(_) => None,
+            History::Dynamic(samples) => samples.back().map(|(stamp, _)| *stamp),
+        }
+    }
+}
+
+/// A time-indexed tree of frames, see the [module documentation](self)
+///
+/// Cloning the buffer shares its transforms, so one clone can be fed by a
+/// [`TransformListener`] while others look transforms up.
+#[derive(Debug, Clone)]
+pub struct TransformBuffer {
+    history: Duration,
+    /// By child frame
+    edges: Arc<RwLock<HashMap<String, Edge>>>,
+}
+
+impl Default for TransformBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY)
+    }
+}
+
+impl TransformBuffer {
+    /// A buffer keeping each transform for `history` before its latest
+    /// sample
+    pub fn new(history: Duration) -> Self {
+        Self {
+            history,
+            edges: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Store a transform, `is_static` if it holds at any time
+    ///
+    /// A frame has one parent at a time: a transform naming another parent
+    /// for a known frame replaces its history. Fails with
+    /// [`TfError::Cycle`] if the new parent is the frame itself or one of
+    /// its descendants.
+    pub fn set_transform(
+        &self,
+        transform: TransformStamped,
+        is_static: bool,
+    ) -> std::result::Result<(), TfError> {
+        let mut edges = self.edges.write();
+        let cycle = || TfError::Cycle {
+            parent: transform.parent.clone(),
+            child: transform.child.clone(),
+        };
+        let mut ancestor = transform.parent.as_str();
+        loop {
+            if ancestor == transform.child {
+                return Err(cycle());
+            }
+            match edges.get(ancestor) {
+                Some(edge) => ancestor = &edge.parent,
+                None => break,
+            }
+        }
+
+        let sample = transform.transform().normalized();
+        let edge = edges.entry(transform.child).or_insert_with(|| Edge {
+            parent: transform.parent.clone(),
+            history: History::Dynamic(VecDeque::new()),
+        });
+        if edge.parent != transform.parent {
+            edge.parent = transform.parent;
+            edge.history = History::Dynamic(VecDeque::new());
+        }
+        if is_static {
+            edge.history = History::Static(sample);
+            return Ok(());
+        }
+        let samples = match &mut edge.history {
+            History::Dynamic(samples) => samples,
+            history => {
+                *history = History::Dynamic(VecDeque::from([(transform.stamp, sample)]));
+                return Ok(());
+            }
+        };
+        let index = samples.partition_point(|(stamp, _)| *stamp < transform.stamp);
+        match samples.get_mut(index) {
+            Some((stamp, existing)) if *stamp == transform.stamp => *existing = sample,
+            _ => samples.insert(index, (transform.stamp, sample)),
+        }
+        if let Some(&(latest, _)) = samples.back() {
+            let oldest = latest - self.history;
+            while samples.front().is_some_and(|(stamp, _)| *stamp < oldest) {
+                samples.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    /// The transform from frame `child` to frame `parent` at `time`
+    ///
+    /// Chains the transforms along the tree through the frames' closest
+    /// common ancestor. Fails with [`TfError::Extrapolation`] if `time` is
+    /// outside the buffered history of a transform on the way.
+    pub fn lookup(
+        &self,
+        parent: &str,
+        child: &str,
+        time: Time,
+    ) -> std::result::Result<TransformStamped, TfError> {
+        let edges = self.edges.read();
+        let (up, down) = path(&edges, parent, child)?;
+        let chain = |frames: &[&str]| {
+            frames.iter().try_fold(Transform::IDENTITY, |acc, frame| {
+                Ok::<_, TfError>(edges[*frame].sample(frame, time)? * acc)
+            })
+        };
+        let transform = chain(&up)?.inverse() * chain(&down)?;
+        Ok(TransformStamped::new(parent, child, transform, time))
+    }
+
+    /// The transform from frame `child` to frame `parent` at the latest
+    /// time every transform on the way has a sample for
+    pub fn lookup_latest(
+        &self,
+        parent: &str,
+        child: &str,
+    ) -> std::result::Result<TransformStamped, TfError> {
+        let time = {
+            let edges = self.edges.read();
+            let (up, down) = path(&edges, parent, child)?;
+            up.iter()
+                .chain(&down)
+                .filter_map(|frame| edges[*frame].latest())
+                .min()
+                .unwrap_or(Time::ZERO)
+        };
+        self.lookup(parent, child, time)
+    }
+
+    /// Whether [`lookup`](Self::lookup) would succeed
+    pub fn can_transform(&self, parent: &str, child: &str, time: Time) -> bool {
+        self.lookup(parent, child, time).is_ok()
+    }
+
+    /// Every known frame, sorted
+    pub fn frames(&self) -> Vec<String> {
+        let edges = self.edges.read();
+        let mut frames: Vec<String> = edges
+            .iter()
+            .flat_map(|(child, edge)| [child.clone(), edge.parent.clone()])
+            .collect();
+        frames.sort();
+        frames.dedup();
+        frames
+    }
+
+    /// Keep the buffer up to date with the transforms broadcast on
+    /// [`TF_TOPIC`] and [`TF_STATIC_TOPIC`]
+    ///
+    /// Transforms that would close a cycle are dropped with a warning.
+    /// Listening stops when the returned listener is dropped.
+    pub fn listen(&self, node: &Node) -> Result<TransformListener> {
+        let subscribers = [
+            (node.create_subscriber::<TfMessage>(TF_TOPIC)?, false),
+            (node.create_subscriber::<TfMessage>(TF_STATIC_TOPIC)?, true),
+        ];
+        let tasks = subscribers
+            .map(|(subscriber, is_static)| {
+                let buffer = self.clone();
+                tokio::spawn(async move {
+                    while let Ok(msg) = subscriber.recv().await {
+                        for transform in msg.transforms {
+                            if let Err(e) = buffer.set_transform(transform, is_static) {
+                                warn!("Dropping transform: {}", e);
+                            }
+                        }
+                    }
+                })
+            })
+            .into();
+        Ok(TransformListener { tasks })
+    }
+}
+
+/// The frames whose transforms lead from `parent` and from `child` up to
+/// their closest common ancestor
+fn path<'a>(
+    edges: &'a HashMap<String, Edge>,
+    parent: &'a str,
+    child: &'a str,
+) -> std::result::Result<(Vec<&'a str>, Vec<&'a str>), TfError> {
+    let known =
+        |frame: &str| edges.contains_key(frame) || edges.values().any(|edge| edge.parent == frame);
+    for frame in [parent, child] {
+        if !known(frame) {
+            return Err(TfError::UnknownFrame(frame.to_string()));
+        }
+    }
+    let ancestors = |mut frame: &'a str| {
+        let mut frames = vec![frame];
+        while let Some(edge) = edges.get(frame) {
+            frame = &edge.parent;
+            frames.push(frame);
+        }
+        frames
+    };
+    let (mut up, mut down) = (ancestors(parent), ancestors(child));
+    let Some(common) = down.iter().position(|frame| up.contains(frame)) else {
+        return Err(TfError::DisconnectedTree {
+            parent: parent.to_string(),
+            child: child.to_string(),
+        });
+    };
+    let common_in_up = up
+        .iter()
+        .position(|frame| *frame == down[common])
+        .unwrap_or(0);
+    // The common ancestor itself has no transform on the way
+    down.truncate(common);
+    up.truncate(common_in_up);
+    Ok((up, down))
+}
+
+/// Feeds a [`TransformBuffer`], see [`TransformBuffer::listen`]
+pub struct TransformListener {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for TransformListener {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Broadcasts transforms that change over time on [`TF_TOPIC`]
+pub struct TransformBroadcaster {
+    publisher: Publisher<TfMessage>,
+}
+
+impl TransformBroadcaster {
+    pub fn new(node: &Node) -> Result<Self> {
+        Ok(Self {
+            publisher: node.create_publisher(TF_TOPIC)?,
+        })
+    }
+
+    pub async fn send(&self, transforms: impl IntoIterator<Item = TransformStamped>) -> Result<()> {
+        let msg = TfMessage {
+            transforms: transforms.into_iter().collect(),
+        };
+        self.publisher.publish(&msg).await
+    }
+}
+
+/// Broadcasts transforms that never change on the latched
+/// [`TF_STATIC_TOPIC`]
+///
+/// Each send republishes every transform sent so far, so listeners that
+/// join later receive all of them from the latch.
+pub struct StaticTransformBroadcaster {
+    publisher: Publisher<TfMessage>,
+    /// By child frame
+    transforms: Mutex<Vec<TransformStamped>>,
+}
+
+impl StaticTransformBroadcaster {
+    pub fn new(node: &Node) -> Result<Self> {
+        Ok(Self {
+            publisher: node.create_publisher(TF_STATIC_TOPIC)?.latch(true),
+            transforms: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Add `transforms`, replacing earlier ones of the same child frames
+    pub async fn send(&self, transforms: impl IntoIterator<Item = TransformStamped>) -> Result<()> {
+        let msg = {
+            let mut sent = self.transforms.lock();
+            for transform in transforms {
+                match sent.iter_mut().find(|sent| sent.child == transform.child) {
+                    Some(sent) => *sent = transform,
+                    None => sent.push(transform),
+                }
+            }
+            TfMessage {
+                transforms: sent.clone(),
+            }
+        };
+        self.publisher.publish(&msg).await
+    }
+}
+
+fn dot3(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn dot4(a: [f64; 4], b: [f64; 4]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// `v` rotated by the unit quaternion `q`
+fn rotate(q: [f64; 4], v: [f64; 3]) -> [f64; 3] {
+    let u = [q[0], q[1], q[2]];
+    let uv = cross(u, v);
+    let uuv = cross(u, uv);
+    [0, 1, 2].map(|i| v[i] + 2.0 * (q[3] * uv[i] + uuv[i]))
+}
+
+fn quat_mul(a: [f64; 4], b: [f64; 4]) -> [f64; 4] {
+    let [ax, ay, az, aw] = a;
+    let [bx, by, bz, bw] = b;
+    [
+        aw * bx + ax * bw + ay * bz - az * by,
+        aw * by - ax * bz + ay * bw + az * bx,
+        aw * bz + ax * by - ay * bx + az * bw,
+        aw * bw - ax * bx - ay * by - az * bz,
+    ]
+}
+
+/// Spherical interpolation along the shorter arc between unit quaternions
+fn slerp(a: [f64; 4], b: [f64; 4], t: f64) -> [f64; 4] {
+    let mut cos = dot4(a, b);
+    let b = if cos < 0.0 {
+        cos = -cos;
+        b.map(|v| -v)
+    } else {
+        b
+    };
+    let (wa, wb) = if cos > 0.9995 {
+        // Nearly parallel: linear interpolation is accurate and stable
+        (1.0 - t, t)
+    } else {
+        let angle = cos.acos();
+        let sin = angle.sin();
+        (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+    };
+    let q = [0, 1, 2, 3].map(|i| wa * a[i] + wb * b[i]);
+    let norm = dot4(q, q).sqrt();
+    q.map(|v| v / norm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn secs(secs: f64) -> Time {
+        Time::from_nanos((secs * 1e9) as i64)
+    }
+
+    fn assert_close<const N: usize>(actual: [f64; N], expected: [f64; N]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    fn transform(parent: &str, child: &str, transform: Transform, stamp: f64) -> TransformStamped {
+        TransformStamped::new(parent, child, transform, secs(stamp))
+    }
+
+    #[test]
+    fn test_transform_algebra() {
+        let turn = Transform::from_axis_angle([0.0, 0.0, 1.0], FRAC_PI_2);
+        let moved = Transform::from_translation([1.0, 0.0, 0.0]) * turn;
+        assert_close(moved.transform_point([1.0, 0.0, 0.0]), [1.0, 1.0, 0.0]);
+        let back = moved.inverse() * moved;
+        assert_close(back.translation, [0.0; 3]);
+        assert_close(back.rotation, [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_interpolation_accuracy() {
+        let buffer = TransformBuffer::default();
+        let start = Transform::IDENTITY;
+        let end = Transform {
+            translation: [2.0, -4.0, 1.0],
+            ..Transform::from_axis_angle([0.0, 0.0, 1.0], FRAC_PI_2)
+        };
+        buffer
+            .set_transform(transform("odom", "base", start, 0.0), false)
+            .unwrap();
+        buffer
+            .set_transform(transform("odom", "base", end, 1.0), false)
+            .unwrap();
+
+        let quarter = buffer.lookup("odom", "base", secs(0.25)).unwrap();
+        assert_close(quarter.translation, [0.5, -1.0, 0.25]);
+        // A quarter of the way along the arc, not of the quaternion chord
+        let expected = Transform::from_axis_angle([0.0, 0.0, 1.0], FRAC_PI_2 / 4.0);
+        assert_close(quarter.rotation, expected.rotation);
+        assert_eq!(quarter.stamp, secs(0.25));
+        // Samples are returned exactly
+        assert_eq!(
+            buffer
+                .lookup("odom", "base", secs(1.0))
+                .unwrap()
+                .transform(),
+            end.normalized()
+        );
+
+        // Through a static transform and back down another branch
+        let mount = Transform::from_translation([0.0, 0.0, 0.5]);
+        buffer
+            .set_transform(transform("base", "lidar", mount, 0.0), true)
+            .unwrap();
+        buffer
+            .set_transform(
+                transform(
+                    "odom",
+                    "dock",
+                    Transform::from_translation([2.0, -4.0, 0.0]),
+                    0.0,
+                ),
+                true,
+            )
+            .unwrap();
+        let lidar = buffer.lookup("dock", "lidar", secs(1.0)).unwrap();
+        assert_close(lidar.translation, [0.0, 0.0, 1.5]);
+        let inverse = buffer.lookup("lidar", "dock", secs(1.0)).unwrap();
+        assert_close(
+            (inverse.transform() * lidar.transform()).translation,
+            [0.0; 3],
+        );
+        assert_eq!(
+            buffer.lookup_latest("dock", "lidar").unwrap().stamp,
+            secs(1.0)
+        );
+        assert_eq!(buffer.frames(), ["base", "dock", "lidar", "odom"]);
+    }
+
+    #[test]
+    fn test_cycles_are_rejected() {
+        let buffer = TransformBuffer::default();
+        let t = Transform::IDENTITY;
+        buffer
+            .set_transform(transform("map", "odom", t, 0.0), true)
+            .unwrap();
+        buffer
+            .set_transform(transform("odom", "base", t, 0.0), false)
+            .unwrap();
+        assert_eq!(
+            buffer.set_transform(transform("base", "map", t, 0.0), false),
+            Err(TfError::Cycle {
+                parent: "base".into(),
+                child: "map".into(),
+            })
+        );
+        assert!(buffer
+            .set_transform(transform("base", "base", t, 0.0), false)
+            .is_err());
+        assert!(buffer.can_transform("map", "base", secs(0.0)));
+
+        // Reparenting is fine as long as the tree stays a tree
+        buffer
+            .set_transform(transform("map", "base", t, 1.0), false)
+            .unwrap();
+        assert!(buffer.lookup("odom", "base", secs(1.0)).is_ok());
+    }
+
+    #[test]
+    fn test_lookups_beyond_history() {
+        let buffer = TransformBuffer::new(Duration::from_secs(1));
+        for stamp in 0..=5 {
+            let t = Transform::from_translation([stamp as f64, 0.0, 0.0]);
+            buffer
+                .set_transform(transform("odom", "base", t, stamp as f64), false)
+                .unwrap();
+        }
+        let extrapolation = |time| TfError::Extrapolation {
+            frame: "base".into(),
+            time,
+            earliest: secs(4.0),
+            latest: secs(5.0),
+        };
+        let just_after = secs(5.0) + Duration::from_nanos(1);
+        assert_eq!(
+            buffer.lookup("odom", "base", just_after),
+            Err(extrapolation(just_after))
+        );
+        let just_before = secs(4.0) - Duration::from_nanos(1);
+        assert_eq!(
+            buffer.lookup("odom", "base", just_before),
+            Err(extrapolation(just_before))
+        );
+        // Older samples were dropped
+        assert!(!buffer.can_transform("odom", "base", secs(3.5)));
+        assert_close(
+            buffer
+                .lookup("odom", "base", secs(4.0))
+                .unwrap()
+                .translation,
+            [4.0, 0.0, 0.0],
+        );
+
+        buffer
+            .set_transform(transform("map", "world", Transform::IDENTITY, 0.0), true)
+            .unwrap();
+        assert_eq!(
+            buffer.lookup("map", "base", secs(4.5)),
+            Err(TfError::DisconnectedTree {
+                parent: "map".into(),
+                child: "base".into(),
+            })
+        );
+        assert_eq!(
+            buffer.lookup("odom", "gps", secs(4.5)),
+            Err(TfError::UnknownFrame("gps".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_listen_to_broadcasts() {
+        let node = Node::new("tf_test").unwrap();
+        let statics = StaticTransformBroadcaster::new(&node).unwrap();
+        let mount = Transform::from_translation([0.1, 0.0, 0.2]);
+        statics
+            .send([transform("base", "camera", mount, 0.0)])
+            .await
+            .unwrap();
+        statics
+            .send([transform("base", "imu", mount, 0.0)])
+            .await
+            .unwrap();
+
+        // Static transforms sent before listening arrive from the latch
+        let buffer = TransformBuffer::default();
+        let _listener = buffer.listen(&node).unwrap();
+        let broadcaster = TransformBroadcaster::new(&node).unwrap();
+        let odom = Transform::from_translation([3.0, 0.0, 0.0]);
+        broadcaster
+            .send([transform("odom", "base", odom, 2.0)])
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !buffer.can_transform("odom", "imu", secs(2.0))
+                || !buffer.can_transform("odom", "camera", secs(2.0))
+            {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let camera = buffer.lookup_latest("odom", "camera").unwrap();
+        assert_close(camera.translation, [3.1, 0.0, 0.2]);
+        assert_eq!(camera.stamp, secs(2.0));
+    }
+}
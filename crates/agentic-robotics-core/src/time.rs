@@ -0,0 +1,394 @@
+//! Clocks and time stamps
+//!
+//! Everything that reads the time (timers, deadlines, message stamps) goes
+//! through a [`Clock`], so simulation and log replay can drive time from a
+//! `/clock` topic instead of the wall clock.
+
+use crate::error::Result;
+use crate::message::Message;
+use crate::subscriber::Subscriber;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use tracing::debug;
+
+/// Conventional topic carrying simulation time
+pub const CLOCK_TOPIC: &str = "/clock";
+
+/// A point in time, in nanoseconds since the epoch of the clock that
+/// produced it
+///
+/// Stamps from different [`ClockType`]s are not comparable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct Time(i64);
+
+impl Time {
+    /// The clock's epoch
+    pub const ZERO: Time = Time(0);
+
+    /// Create a time from nanoseconds since the epoch
+    pub const fn from_nanos(nanos: i64) -> Self {
+        Time(nanos)
+    }
+
+    /// Nanoseconds since the epoch
+    pub const fn as_nanos(&self) -> i64 {
+        self.0
+    }
+
+    /// Seconds since the epoch
+    pub fn as_secs_f64(&self) -> f64 {
+        self.0 as f64 / 1e9
+    }
+
+    /// Time elapsed since `earlier`, or zero if `earlier` is later
+    pub fn saturating_duration_since(&self, earlier: Time) -> Duration {
+        Duration::from_nanos(self.0.saturating_sub(earlier.0).max(0) as u64)
+    }
+}
+
+impl Add<Duration> for Time {
+    type Output = Time;
+
+    fn add(self, rhs: Duration) -> Time {
+        Time(self.0.saturating_add(duration_nanos(rhs)))
+    }
+}
+
+impl Sub<Duration> for Time {
+    type Output = Time;
+
+    fn sub(self, rhs: Duration) -> Time {
+        Time(self.0.saturating_sub(duration_nanos(rhs)))
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (secs, nanos) = (self.0.div_euclid(1_000_000_000), self.0.rem_euclid(1_000_000_000));
+        write!(f, "{}.{:09}", secs, nanos)
+    }
+}
+
+fn duration_nanos(duration: Duration) -> i64 {
+    i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX)
+}
+
+/// Which kind of clock produced a [`Time`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockType {
+    SystemTime,
+    Steady,
+    Sim,
+}
+
+/// Message published on [`CLOCK_TOPIC`] to drive simulation time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ClockMessage {
+    /// Current simulation time in nanoseconds
+    pub stamp_ns: i64,
+}
+
+impl Message for ClockMessage {
+    fn type_name() -> &'static str {
+        "ros3_msgs/Clock"
+    }
+}
+
+/// Source of the current time
+#[derive(Debug, Clone, Default)]
+pub enum Clock {
+    /// Wall-clock time since the Unix epoch; may jump when the system clock
+    /// is adjusted
+    #[default]
+    SystemTime,
+    /// Monotonic time since the process first read a steady clock
+    Steady,
+    /// Time driven externally, e.g. by a simulator or log player
+    Sim(SimClock),
+}
+
+impl Clock {
+    /// A simulation clock following [`ClockMessage`]s on `topic`
+    ///
+    /// Starts at [`Time::ZERO`] and stays there until the first message
+    /// arrives. Must be called from within a Tokio runtime.
+    pub fn sim_from_topic(topic: &str) -> Result<Self> {
+        let sim = SimClock::new(Time::ZERO);
+        sim.follow(topic)?;
+        Ok(Clock::Sim(sim))
+    }
+
+    /// Which kind of clock this is
+    pub fn clock_type(&self) -> ClockType {
+        match self {
+            Clock::SystemTime => ClockType::SystemTime,
+            Clock::Steady => ClockType::Steady,
+            Clock::Sim(_) => ClockType::Sim,
+        }
+    }
+
+    /// Current time
+    pub fn now(&self) -> Time {
+        match self {
+            Clock::SystemTime => {
+                let since_epoch = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                Time(duration_nanos(since_epoch))
+            }
+            Clock::Steady => Time(duration_nanos(steady_origin().elapsed())),
+            Clock::Sim(sim) => sim.now(),
+        }
+    }
+
+    /// Wait until the clock reaches `deadline`
+    ///
+    /// Under simulation time this wakes when the sim clock passes
+    /// `deadline`, however long that takes in wall time.
+    pub async fn sleep_until(&self, deadline: Time) {
+        match self {
+            Clock::SystemTime => {
+                let remaining = deadline.saturating_duration_since(self.now());
+                tokio::time::sleep(remaining).await;
+            }
+            Clock::Steady => {
+                let offset = Duration::from_nanos(deadline.as_nanos().max(0) as u64);
+                tokio::time::sleep_until(steady_origin() + offset).await;
+            }
+            Clock::Sim(sim) => sim.sleep_until(deadline).await,
+        }
+    }
+
+    /// Wait for `duration` to pass on this clock
+    pub async fn sleep(&self, duration: Duration) {
+        self.sleep_until(self.now() + duration).await;
+    }
+}
+
+/// Epoch of [`Clock::Steady`]
+fn steady_origin() -> tokio::time::Instant {
+    static ORIGIN: OnceLock<tokio::time::Instant> = OnceLock::new();
+    *ORIGIN.get_or_init(tokio::time::Instant::now)
+}
+
+struct SimState {
+    now: AtomicI64,
+    advanced: Notify,
+    /// Deadlines of pending sleeps, with the number of sleeps on each
+    sleepers: Mutex<BTreeMap<i64, usize>>,
+}
+
+/// Externally driven clock shared by every clone
+///
+/// Sim time never runs backwards: setting an earlier time is ignored.
+#[derive(Clone)]
+pub struct SimClock {
+    state: Arc<SimState>,
+}
+
+impl SimClock {
+    /// Create a clock starting at `start`
+    pub fn new(start: Time) -> Self {
+        Self {
+            state: Arc::new(SimState {
+                now: AtomicI64::new(start.as_nanos()),
+                advanced: Notify::new(),
+                sleepers: Mutex::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// Current sim time
+    pub fn now(&self) -> Time {
+        Time(self.state.now.load(Ordering::Acquire))
+    }
+
+    /// Move the clock to `time`, waking sleepers whose deadline passed
+    pub fn set(&self, time: Time) {
+        self.state.now.fetch_max(time.as_nanos(), Ordering::AcqRel);
+        self.state.advanced.notify_waiters();
+    }
+
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        self.state
+            .now
+            .fetch_add(duration_nanos(duration), Ordering::AcqRel);
+        self.state.advanced.notify_waiters();
+    }
+
+    /// Wait until sim time reaches `deadline`
+    pub async fn sleep_until(&self, deadline: Time) {
+        if self.now() >= deadline {
+            return;
+        }
+        let _pending = PendingSleep::new(&self.state, deadline);
+        loop {
+            let advanced = self.state.advanced.notified();
+            if self.now() >= deadline {
+                return;
+            }
+            advanced.await;
+        }
+    }
+
+    /// Earliest deadline a pending sleep on this clock waits for
+    ///
+    /// Lets whoever drives the clock jump straight to the next timer.
+    pub fn next_deadline(&self) -> Option<Time> {
+        self.state.sleepers.lock().keys().next().map(|&nanos| Time(nanos))
+    }
+
+    /// Advance the clock from [`ClockMessage`]s published on `topic`
+    ///
+    /// The follower stops once every handle to the clock is dropped and the
+    /// next message arrives. Must be called from within a Tokio runtime.
+    pub fn follow(&self, topic: &str) -> Result<()> {
+        let subscriber = Subscriber::<ClockMessage>::new(topic)?;
+        let state = Arc::downgrade(&self.state);
+        debug!("Sim clock following {}", topic);
+
+        tokio::spawn(async move {
+            while let Ok(message) = subscriber.recv().await {
+                let Some(state) = state.upgrade() else {
+                    break;
+                };
+                SimClock { state }.set(Time(message.stamp_ns));
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Registers a sleep's deadline with its clock while the sleep is pending
+struct PendingSleep<'a> {
+    state: &'a SimState,
+    deadline: i64,
+}
+
+impl<'a> PendingSleep<'a> {
+    fn new(state: &'a SimState, deadline: Time) -> Self {
+        *state.sleepers.lock().entry(deadline.0).or_default() += 1;
+        Self {
+            state,
+            deadline: deadline.0,
+        }
+    }
+}
+
+impl Drop for PendingSleep<'_> {
+    fn drop(&mut self) {
+        let mut sleepers = self.state.sleepers.lock();
+        if let Some(count) = sleepers.get_mut(&self.deadline) {
+            *count -= 1;
+            if *count == 0 {
+                sleepers.remove(&self.deadline);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SimClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimClock").field("now", &self.now()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Node, Publisher};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timer_follows_sim_time() {
+        let publisher = Publisher::<ClockMessage>::new("/test/time/clock").unwrap();
+        let clock = Clock::sim_from_topic("/test/time/clock").unwrap();
+        let node = Node::new("sim_timer").unwrap().with_clock(clock.clone());
+
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counter = ticks.clone();
+        node.create_timer(Duration::from_millis(100), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+
+        // One second of sim time, during which 100 seconds of wall time pass
+        for step in 1..=1000 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            publisher
+                .publish(&ClockMessage {
+                    stamp_ns: step * 1_000_000,
+                })
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+
+        assert_eq!(clock.now(), Time::from_nanos(1_000_000_000));
+        assert_eq!(ticks.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sim_sleep_until_ignores_wall_time() {
+        let sim = SimClock::new(Time::ZERO);
+        let clock = Clock::Sim(sim.clone());
+        let sleeper = tokio::spawn(async move {
+            clock.sleep_until(Time::from_nanos(500)).await;
+        });
+
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+        assert!(!sleeper.is_finished());
+
+        sim.advance(Duration::from_nanos(499));
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        assert!(!sleeper.is_finished());
+
+        sim.advance(Duration::from_nanos(1));
+        sleeper.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_next_deadline_tracks_pending_sleeps() {
+        let sim = SimClock::new(Time::ZERO);
+        assert_eq!(sim.next_deadline(), None);
+        let sleeper = tokio::spawn({
+            let sim = sim.clone();
+            async move { sim.sleep_until(Time::from_nanos(300)).await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(sim.next_deadline(), Some(Time::from_nanos(300)));
+
+        sim.set(Time::from_nanos(300));
+        sleeper.await.unwrap();
+        assert_eq!(sim.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_sim_time_is_monotonic() {
+        let sim = SimClock::new(Time::from_nanos(10));
+        sim.set(Time::from_nanos(5));
+        assert_eq!(sim.now(), Time::from_nanos(10));
+        sim.set(Time::from_nanos(20));
+        assert_eq!(sim.now(), Time::from_nanos(20));
+    }
+
+    #[test]
+    fn test_time_arithmetic() {
+        let t = Time::from_nanos(1_500_000_000);
+        assert_eq!((t + Duration::from_millis(500)).as_nanos(), 2_000_000_000);
+        assert_eq!(t.saturating_duration_since(Time::ZERO), Duration::from_millis(1500));
+        assert_eq!(Time::ZERO.saturating_duration_since(t), Duration::ZERO);
+        assert_eq!(t.to_string(), "1.500000000");
+        assert_eq!(Clock::default().clock_type(), ClockType::SystemTime);
+    }
+}
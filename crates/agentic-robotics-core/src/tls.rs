@@ -0,0 +1,511 @@
+//! TLS for the TCP transport
+//!
+//! Peers authenticate each other with certificates chaining to a configured
+//! certificate authority. Publishers connecting to a listener always verify
+//! its certificate; listeners optionally require client certificates too
+//! (mutual TLS). Handshake failures are reported as
+//! [`Error::TlsHandshake`] carrying the rustls reason.
+
+use crate::error::{Error, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+
+/// Certificates and verification settings of a TLS-secured
+/// [`TcpConfig`](crate::TcpConfig)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TlsConfig {
+    /// PEM file with the certificate authorities peers must chain to
+    pub ca: PathBuf,
+    /// PEM certificate chain presented to peers; required to listen and for
+    /// mutual TLS
+    pub cert: Option<PathBuf>,
+    /// PEM private key of `cert`
+    pub key: Option<PathBuf>,
+    /// Check that a listener's certificate is issued for the name it is
+    /// reached by, not just signed by `ca`
+    pub verify_hostname: bool,
+    /// Name listener certificates must match, the peer's IP address if unset
+    pub server_name: Option<String>,
+    /// Reject publishers that do not present a certificate signed by `ca`
+    pub require_client_cert: bool,
+}
+
+impl TlsConfig {
+    /// Verify peers against the authorities in `ca`, with hostname checks
+    /// and without a certificate of our own
+    pub fn new(ca: impl Into<PathBuf>) -> Self {
+        Self {
+            ca: ca.into(),
+            cert: None,
+            key: None,
+            verify_hostname: true,
+            server_name: None,
+            require_client_cert: false,
+        }
+    }
+
+    /// The server side of this configuration, for securing listeners
+    /// other than the TCP transport's
+    ///
+    /// Fails without a `cert` and `key`, or if the files are invalid.
+    pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        TlsContext::new(self)?
+            .acceptor
+            .ok_or_else(|| Error::Configuration("TLS listener needs a cert and key".into()))
+    }
+
+    /// The client side of this configuration, for connecting to listeners
+    /// other than the TCP transport's
+    ///
+    /// Presents `cert` if set. The caller picks the name to verify, usually
+    /// `server_name` or else the host it connects to.
+    pub fn connector(&self) -> Result<TlsConnector> {
+        Ok(TlsContext::new(self)?.connector)
+    }
+}
+
+/// Client and server sides of a [`TlsConfig`], built once per transport
+pub(crate) struct TlsContext {
+    connector: TlsConnector,
+    /// `None` without a certificate to present
+    acceptor: Option<TlsAcceptor>,
+    server_name: Option<ServerName<'static>>,
+}
+
+impl TlsContext {
+    pub(crate) fn new(config: &TlsConfig) -> Result<Self> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut roots = RootCertStore::empty();
+        for cert in read_certs(&config.ca)? {
+            roots.add(cert).map_err(|e| invalid(&config.ca, e))?;
+        }
+        let roots = Arc::new(roots);
+        let identity = match (&config.cert, &config.key) {
+            (Some(cert), Some(key)) => Some((read_certs(cert)?, read_key(key)?)),
+            (None, None) => None,
+            _ => {
+                return Err(Error::Configuration(
+                    "TLS cert and key must be set together".into(),
+                ))
+            }
+        };
+
+        let verifier = WebPkiServerVerifier::builder_with_provider(roots.clone(), provider.clone())
+            .build()
+            .map_err(|e| Error::Configuration(format!("TLS server verifier: {}", e)))?;
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| Error::Configuration(e.to_string()))?;
+        let builder = if config.verify_hostname {
+            builder.with_webpki_verifier(verifier)
+        } else {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(IgnoreHostname(verifier)))
+        };
+        let client = match &identity {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs.clone(), key.clone_key())
+                .map_err(|e| Error::Configuration(format!("TLS client cert: {}", e)))?,
+            None => builder.with_no_client_auth(),
+        };
+
+        let acceptor = match identity {
+            Some((certs, key)) => {
+                let builder = ServerConfig::builder_with_provider(provider.clone())
+                    .with_safe_default_protocol_versions()
+                    .map_err(|e| Error::Configuration(e.to_string()))?;
+                let builder = if config.require_client_cert {
+                    let verifier = WebPkiClientVerifier::builder_with_provider(roots, provider)
+                        .build()
+                        .map_err(|e| Error::Configuration(format!("TLS client verifier: {}", e)))?;
+                    builder.with_client_cert_verifier(verifier)
+                } else {
+                    builder.with_no_client_auth()
+                };
+                let server = builder
+                    .with_single_cert(certs, key)
+                    .map_err(|e| Error::Configuration(format!("TLS server cert: {}", e)))?;
+                Some(TlsAcceptor::from(Arc::new(server)))
+            }
+            None => None,
+        };
+
+        let server_name = config
+            .server_name
+            .clone()
+            .map(|name| {
+                ServerName::try_from(name)
+                    .map_err(|e| Error::Configuration(format!("TLS server name: {}", e)))
+            })
+            .transpose()?;
+        Ok(Self {
+            connector: TlsConnector::from(Arc::new(client)),
+            acceptor,
+            server_name,
+        })
+    }
+
+    /// Whether a listener can be secured, i.e. a certificate is configured
+    pub(crate) fn can_accept(&self) -> bool {
+        self.acceptor.is_some()
+    }
+
+    /// Handshake as the client of a connection to `peer`
+    pub(crate) async fn connect(
+        &self,
+        stream: TcpStream,
+        peer: SocketAddr,
+    ) -> Result<client::TlsStream<TcpStream>> {
+        let name = self
+            .server_name
+            .clone()
+            .unwrap_or_else(|| ServerName::from(peer.ip()));
+        self.connector
+            .connect(name, stream)
+            .await
+            .map_err(|e| handshake_error(peer, e))
+    }
+
+    /// Handshake as the server of a connection accepted from `peer`
+    pub(crate) async fn accept(
+        &self,
+        stream: TcpStream,
+        peer: SocketAddr,
+    ) -> Result<server::TlsStream<TcpStream>> {
+        let acceptor = self.acceptor.as_ref().ok_or_else(|| {
+            Error::Configuration("TLS listener needs a cert and key".into())
+        })?;
+        acceptor
+            .accept(stream)
+            .await
+            .map_err(|e| handshake_error(peer, e))
+    }
+}
+
+/// The rustls error behind an I/O error on a TLS stream, if any
+pub(crate) fn rustls_reason(e: &std::io::Error) -> Option<String> {
+    let inner = e.get_ref()?.downcast_ref::<rustls::Error>()?;
+    Some(inner.to_string())
+}
+
+fn handshake_error(peer: SocketAddr, e: std::io::Error) -> Error {
+    Error::TlsHandshake {
+        peer,
+        reason: rustls_reason(&e).unwrap_or_else(|| e.to_string()),
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<std::result::Result<Vec<_>, _>>())
+        .map_err(|e| invalid(path, e))?;
+    if certs.is_empty() {
+        return Err(invalid(path, "no certificates found"));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| invalid(path, e))
+}
+
+fn invalid(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::Configuration(format!("TLS file {}: {}", path.display(), e))
+}
+
+/// Verifies the certificate chain but accepts any name, for
+/// [`TlsConfig::verify_hostname`] set to false
+#[derive(Debug)]
+struct IgnoreHostname(Arc<WebPkiServerVerifier>);
+
+impl ServerCertVerifier for IgnoreHostname {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        match self
+            .0
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+        {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+            )) => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+    use crate::tcp::{decode, read_frame, ConnectionEvent, TcpConfig};
+    use crate::transport::TransportConfig;
+    use crate::Node;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A certificate authority written to a fresh directory
+    struct Authority {
+        dir: PathBuf,
+        cert: rcgen::Certificate,
+        key: KeyPair,
+    }
+
+    impl Authority {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("ros3_tls_{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let mut params = CertificateParams::new(Vec::new()).unwrap();
+            // Authorities sharing a name look to rustls like one whose
+            // signature does not verify, instead of an unknown issuer
+            params.distinguished_name.push(DnType::CommonName, name);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let key = KeyPair::generate().unwrap();
+            let cert = params.self_signed(&key).unwrap();
+            std::fs::write(dir.join("ca.pem"), cert.pem()).unwrap();
+            Self { dir, cert, key }
+        }
+
+        /// A certificate for `names` signed by this authority, returning the
+        /// paths of its certificate and key
+        fn issue(&self, file: &str, names: &[&str]) -> (PathBuf, PathBuf) {
+            let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+            let key = KeyPair::generate().unwrap();
+            let cert = CertificateParams::new(names)
+                .unwrap()
+                .signed_by(&key, &self.cert, &self.key)
+                .unwrap();
+            let cert_path = self.dir.join(format!("{}.pem", file));
+            let key_path = self.dir.join(format!("{}.key", file));
+            std::fs::write(&cert_path, cert.pem()).unwrap();
+            std::fs::write(&key_path, key.serialize_pem()).unwrap();
+            (cert_path, key_path)
+        }
+
+        fn config(&self, identity: Option<(PathBuf, PathBuf)>) -> TlsConfig {
+            let (cert, key) = identity.unzip();
+            TlsConfig {
+                cert,
+                key,
+                ..TlsConfig::new(self.dir.join("ca.pem"))
+            }
+        }
+    }
+
+    impl Drop for Authority {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Handshake `client` against `server` over loopback, returning the
+    /// results of both sides
+    async fn handshake(client: &TlsConfig, server: &TlsConfig) -> (Result<()>, Result<()>) {
+        let client = TlsContext::new(client).unwrap();
+        let server = TlsContext::new(server).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = async {
+            let (stream, peer) = listener.accept().await.unwrap();
+            let mut stream = server.accept(stream, peer).await?;
+            stream.write_all(b"ok").await?;
+            stream.flush().await?;
+            Ok(())
+        };
+        let connected = async {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut stream = client.connect(stream, addr).await?;
+            // Under TLS 1.3 a rejected client cert only shows on first read
+            let mut reply = [0u8; 2];
+            stream.read_exact(&mut reply).await.map_err(|e| handshake_error(addr, e))?;
+            Ok(())
+        };
+        let (server, client) = tokio::join!(accepted, connected);
+        (client, server)
+    }
+
+    fn assert_handshake_failed(result: Result<()>, reason: &str) {
+        match result {
+            Err(Error::TlsHandshake { reason: actual, .. }) => {
+                assert!(actual.contains(reason), "unexpected reason: {}", actual)
+            }
+            other => panic!("expected a handshake failure, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_with_trusted_server() {
+        let ca = Authority::new("trusted");
+        let server = ca.config(Some(ca.issue("server", &["127.0.0.1"])));
+        let (client, server) = handshake(&ca.config(None), &server).await;
+        client.unwrap();
+        server.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_rejects_unknown_server() {
+        let ca = Authority::new("client_side");
+        let rogue = Authority::new("rogue_server");
+        let server = rogue.config(Some(rogue.issue("server", &["127.0.0.1"])));
+        let (client, _) = handshake(&ca.config(None), &server).await;
+        assert_handshake_failed(client, "UnknownIssuer");
+    }
+
+    #[tokio::test]
+    async fn test_hostname_verification_is_optional() {
+        let ca = Authority::new("hostname");
+        let server = ca.config(Some(ca.issue("server", &["ground.example"])));
+        let (client, _) = handshake(&ca.config(None), &server).await;
+        assert!(matches!(client, Err(Error::TlsHandshake { .. })));
+
+        let lenient = TlsConfig {
+            verify_hostname: false,
+            ..ca.config(None)
+        };
+        let (client, server) = handshake(&lenient, &server).await;
+        client.unwrap();
+        server.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls_rejects_unknown_client() {
+        let ca = Authority::new("mutual");
+        let rogue = Authority::new("rogue_client");
+        let server = TlsConfig {
+            require_client_cert: true,
+            ..ca.config(Some(ca.issue("server", &["127.0.0.1"])))
+        };
+
+        let known = ca.config(Some(ca.issue("client", &["robot"])));
+        let (client, server_result) = handshake(&known, &server).await;
+        client.unwrap();
+        server_result.unwrap();
+
+        // Trusts the server, but presents a certificate it does not know
+        let unknown = TlsConfig {
+            ca: ca.dir.join("ca.pem"),
+            ..rogue.config(Some(rogue.issue("client", &["robot"])))
+        };
+        let (client, server_result) = handshake(&unknown, &server).await;
+        assert_handshake_failed(server_result, "UnknownIssuer");
+        assert!(matches!(client, Err(Error::TlsHandshake { .. })));
+
+        let anonymous = ca.config(None);
+        let (_, server_result) = handshake(&anonymous, &server).await;
+        assert!(matches!(server_result, Err(Error::TlsHandshake { .. })));
+    }
+
+    #[test]
+    fn test_cert_without_key_is_rejected() {
+        let ca = Authority::new("incomplete");
+        let (cert, _) = ca.issue("server", &["127.0.0.1"]);
+        let config = TlsConfig {
+            cert: Some(cert),
+            ..ca.config(None)
+        };
+        assert!(matches!(TlsContext::new(&config), Err(Error::Configuration(_))));
+    }
+
+    #[tokio::test]
+    async fn test_publisher_connects_over_mutual_tls() {
+        let ca = Authority::new("transport");
+        let server = TlsContext::new(&TlsConfig {
+            require_client_cert: true,
+            ..ca.config(Some(ca.issue("ground", &["127.0.0.1"])))
+        })
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let node = Node::new("tls_robot")
+            .unwrap()
+            .with_transport(TransportConfig::Tcp(TcpConfig {
+                peers: vec![addr],
+                tls: Some(ca.config(Some(ca.issue("robot", &["robot"])))),
+                ..Default::default()
+            }))
+            .unwrap();
+        let publisher = node.create_publisher::<RobotState>("/test/tls/state").unwrap();
+        let mut events = publisher.connection_events();
+
+        let (stream, peer) = listener.accept().await.unwrap();
+        let mut stream = server.accept(stream, peer).await.unwrap();
+        assert_eq!(events.next().await, Some(ConnectionEvent::Connected(addr)));
+        publisher.publish(&RobotState::default()).await.unwrap();
+        let body = read_frame(&mut stream).await.unwrap().unwrap();
+        let (header, _) = decode(&body).unwrap();
+        assert_eq!(header.topic, "/test/tls/state");
+        assert_eq!(header.seq, 0);
+    }
+
+    #[tokio::test]
+    async fn test_publisher_reports_handshake_failure() {
+        let ca = Authority::new("publisher_side");
+        let rogue = Authority::new("rogue_listener");
+        let server = TlsContext::new(&rogue.config(Some(rogue.issue("ground", &["127.0.0.1"]))))
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let node = Node::new("tls_rejecting_robot")
+            .unwrap()
+            .with_transport(TransportConfig::Tcp(TcpConfig {
+                peers: vec![addr],
+                tls: Some(ca.config(None)),
+                ..Default::default()
+            }))
+            .unwrap();
+        let publisher = node.create_publisher::<RobotState>("/test/tls/rogue").unwrap();
+        let mut events = publisher.connection_events();
+
+        let (stream, peer) = listener.accept().await.unwrap();
+        assert!(server.accept(stream, peer).await.is_err());
+        match events.next().await {
+            Some(ConnectionEvent::HandshakeFailed { peer, reason }) => {
+                assert_eq!(peer, addr);
+                assert!(reason.contains("UnknownIssuer"), "unexpected reason: {}", reason);
+            }
+            other => panic!("expected a handshake failure, got {:?}", other),
+        }
+        assert_eq!(publisher.connection_count(), 0);
+    }
+}
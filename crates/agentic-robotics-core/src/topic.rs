@@ -0,0 +1,1663 @@
+//! In-process topic bus
+//!
+//! Routes serialized samples from publishers to every subscriber that is
+//! registered on the same topic name within the process. Transient-local
+//! and latched publishers keep their recent samples here so late subscribers
+//! can catch up.
+
+use crate::channel::mpsc::Ring;
+use crate::compression::CompressionPolicy;
+use crate::error::{Error, Result};
+use crate::graph::{EndpointInfo, GraphEvent, GraphWatcher, TopicInfo, TopicCounters};
+use crate::message::{combine_hash, fnv1a, is_raw, Message, SharedBytes};
+use crate::metrics::{self, Counter, Histogram, Registry};
+use crate::pool::SharedBuffer;
+use crate::qos::{Durability, QosEvent, QosEventWatcher, QosProfile};
+use crate::serialization::{Format, Serializer};
+use crate::subscriber::{ChannelKind, OverflowPolicy};
+use crate::time::{ClockType, Time};
+use crate::trace;
+use crate::wait::Waiters;
+use parking_lot::{Condvar, Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, Notify};
+
+/// Graph events buffered per watcher before it starts lagging
+const GRAPH_EVENT_CAPACITY: usize = 1024;
+
+/// QoS events buffered per subscriber watcher before it starts lagging
+const QOS_EVENT_CAPACITY: usize = 64;
+
+/// Capacity of a lock-free subscriber queue created without a depth
+const LOCK_FREE_DEPTH: usize = 1024;
+
+/// How often a flush checks whether the transports have caught up
+const FLUSH_POLL: Duration = Duration::from_millis(1);
+
+fn next_endpoint_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Globally unique id of a publisher, carried in every sample it publishes
+///
+/// `prefix` identifies the process and `id` the publisher within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PublisherGuid {
+    pub prefix: u64,
+    pub id: u64,
+}
+
+impl PublisherGuid {
+    fn local(id: u64) -> Self {
+        Self {
+            prefix: Self::local_prefix(),
+            id,
+        }
+    }
+
+    /// Prefix shared by every publisher in this process
+    pub(crate) fn local_prefix() -> u64 {
+        static PREFIX: OnceLock<u64> = OnceLock::new();
+        *PREFIX.get_or_init(|| {
+            let started = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64;
+            combine_hash(fnv1a(&std::process::id().to_le_bytes()), started)
+        })
+    }
+}
+
+impl fmt::Display for PublisherGuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}.{}", self.prefix, self.id)
+    }
+}
+
+/// A message as it travels over the bus
+///
+/// Usually the payload is serialized bytes shared between all subscribers of
+/// a topic, each of which deserializes its own copy. Messages published with
+/// [`Publisher::publish_arc`](crate::Publisher::publish_arc) are carried as
+/// the `Arc<T>` itself and only serialized if a subscriber asks for bytes.
+#[derive(Clone)]
+pub struct Sample {
+    payload: Payload,
+    format: Format,
+    type_name: Arc<str>,
+    stamp: Option<Stamp>,
+    /// How transports compress the payload for remote subscribers
+    compression: CompressionPolicy,
+}
+
+/// When and in which order a sample was published
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Stamp {
+    pub(crate) publisher: PublisherGuid,
+    /// Position in the publisher's sequence of messages, from 0
+    pub(crate) seq: u64,
+    pub(crate) time: Time,
+    /// Clock that produced `time`
+    pub(crate) clock: ClockType,
+}
+
+#[derive(Clone)]
+enum Payload {
+    Serialized(Arc<Vec<u8>>),
+    /// Serialized into a buffer from a [`BufferPool`](crate::BufferPool)
+    Pooled(SharedBuffer),
+    Shared(Arc<dyn SharedMessage>),
+    /// Serialized bytes owned elsewhere, see [`SharedBytes`]
+    External(SharedBytes),
+    /// A subscriber's projection of the message, taken once by its receiver
+    Projected(Arc<dyn Any + Send + Sync>),
+}
+
+/// Bytes of a [`Payload::Serialized`] handed out as [`SharedBytes`]
+struct SerializedBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SerializedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Type-erased message behind a zero-copy sample
+trait SharedMessage: Send + Sync {
+    fn serialize(&self, format: Format) -> Result<Vec<u8>>;
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+impl<T: Message> SharedMessage for T {
+    fn serialize(&self, format: Format) -> Result<Vec<u8>> {
+        Serializer::new(format).serialize(self)
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl Sample {
+    pub(crate) fn new(payload: Vec<u8>, format: Format, type_name: Arc<str>) -> Self {
+        Self {
+            payload: Payload::Serialized(Arc::new(payload)),
+            format,
+            type_name,
+            stamp: None,
+            compression: CompressionPolicy::default(),
+        }
+    }
+
+    pub(crate) fn pooled(payload: SharedBuffer, format: Format, type_name: Arc<str>) -> Self {
+        Self {
+            payload: Payload::Pooled(payload),
+            format,
+            type_name,
+            stamp: None,
+            compression: CompressionPolicy::default(),
+        }
+    }
+
+    pub(crate) fn shared<T: Message>(msg: Arc<T>, format: Format, type_name: Arc<str>) -> Self {
+        Self {
+            payload: Payload::Shared(msg),
+            format,
+            type_name,
+            stamp: None,
+            compression: CompressionPolicy::default(),
+        }
+    }
+
+    pub(crate) fn external(payload: SharedBytes, format: Format, type_name: Arc<str>) -> Self {
+        Self {
+            payload: Payload::External(payload),
+            format,
+            type_name,
+            stamp: None,
+            compression: CompressionPolicy::default(),
+        }
+    }
+
+    /// Attach publish metadata
+    pub(crate) fn with_stamp(mut self, stamp: Stamp) -> Self {
+        self.stamp = Some(stamp);
+        self
+    }
+
+    /// Publish metadata, if the publisher attached any
+    pub(crate) fn stamp(&self) -> Option<Stamp> {
+        self.stamp
+    }
+
+    /// Compress the payload for remote subscribers as `compression` says
+    pub(crate) fn with_compression(mut self, compression: CompressionPolicy) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// How the publisher wants the payload compressed for remote
+    /// subscribers
+    pub(crate) fn compression(&self) -> CompressionPolicy {
+        self.compression
+    }
+
+    /// Replace the message with a subscriber's projection of it, keeping
+    /// the metadata
+    pub(crate) fn project<U: Send + 'static>(&self, value: U) -> Sample {
+        Sample {
+            payload: Payload::Projected(Arc::new(Mutex::new(Some(value)))),
+            format: self.format,
+            type_name: self.type_name.clone(),
+            stamp: self.stamp,
+            compression: self.compression,
+        }
+    }
+
+    /// Take the projection stored by [`project`](Self::project), if this is
+    /// a projected sample of type `U` that has not been taken yet
+    pub(crate) fn take_projection<U: Send + 'static>(&self) -> Option<U> {
+        match &self.payload {
+            Payload::Projected(value) => value.downcast_ref::<Mutex<Option<U>>>()?.lock().take(),
+            _ => None,
+        }
+    }
+
+    /// Serialized bytes, serializing a zero-copy sample on demand
+    pub fn bytes(&self) -> Result<Cow<'_, [u8]>> {
+        match &self.payload {
+            Payload::Serialized(bytes) => Ok(Cow::Borrowed(bytes.as_slice())),
+            Payload::Pooled(bytes) => Ok(Cow::Borrowed(bytes)),
+            Payload::External(bytes) => Ok(Cow::Borrowed(bytes)),
+            Payload::Shared(msg) => msg.serialize(self.format).map(Cow::Owned),
+            Payload::Projected(_) => Err(Self::projected_error()),
+        }
+    }
+
+    /// Serialized bytes without copying them, serializing a zero-copy
+    /// sample on demand
+    pub(crate) fn shared_bytes(&self) -> Result<SharedBytes> {
+        match &self.payload {
+            Payload::Serialized(bytes) => Ok(SharedBytes::new(SerializedBytes(bytes.clone()))),
+            Payload::Pooled(bytes) => Ok(SharedBytes::new(bytes.clone())),
+            Payload::External(bytes) => Ok(bytes.clone()),
+            Payload::Shared(msg) => msg.serialize(self.format).map(SharedBytes::from),
+            Payload::Projected(_) => Err(Self::projected_error()),
+        }
+    }
+
+    /// Copy of the sample that owns its serialized bytes
+    fn to_serialized(&self) -> Result<Sample> {
+        match &self.payload {
+            Payload::Serialized(_) | Payload::Pooled(_) | Payload::External(_) => Ok(self.clone()),
+            Payload::Shared(msg) => Ok(Sample {
+                payload: Payload::Serialized(Arc::new(msg.serialize(self.format)?)),
+                format: self.format,
+                type_name: self.type_name.clone(),
+                stamp: self.stamp,
+                compression: self.compression,
+            }),
+            Payload::Projected(_) => Err(Self::projected_error()),
+        }
+    }
+
+    fn projected_error() -> Error {
+        Error::Serialization("a projected message has no serialized form".into())
+    }
+
+    /// Whether the sample carries the message itself rather than bytes
+    pub fn is_shared(&self) -> bool {
+        matches!(self.payload, Payload::Shared(_))
+    }
+
+    /// The shared message, if this is a zero-copy sample of type `T`
+    pub(crate) fn downcast<T: Message>(&self) -> Option<Arc<T>> {
+        match &self.payload {
+            Payload::Shared(msg) => msg.clone().into_any().downcast::<T>().ok(),
+            Payload::Serialized(_)
+            | Payload::Pooled(_)
+            | Payload::External(_)
+            | Payload::Projected(_) => None,
+        }
+    }
+
+    /// Format the payload was (or will be) serialized with
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Type name of the message the payload encodes
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+}
+
+impl fmt::Debug for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Sample");
+        match &self.payload {
+            Payload::Serialized(bytes) => debug.field("len", &bytes.len()),
+            Payload::Pooled(bytes) => debug.field("len", &bytes.len()),
+            Payload::External(bytes) => debug.field("len", &bytes.len()),
+            Payload::Shared(_) => debug.field("shared", &true),
+            Payload::Projected(_) => debug.field("projected", &true),
+        };
+        debug
+            .field("format", &self.format)
+            .field("type_name", &self.type_name)
+            .field("stamp", &self.stamp)
+            .finish()
+    }
+}
+
+/// Message type carried by a topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopicType {
+    pub name: &'static str,
+    pub hash: u64,
+}
+
+impl TopicType {
+    /// Type of `T`, or `None` for [`RawMessage`](crate::RawMessage) which
+    /// matches any topic
+    ///
+    /// Endpoints call this as they attach, which also adds `T` to the
+    /// [type registry](crate::types).
+    pub fn of<T: Message>() -> Option<Self> {
+        if is_raw::<T>() {
+            None
+        } else {
+            crate::types::register::<T>();
+            Some(Self {
+                name: T::type_name(),
+                hash: T::type_hash(),
+            })
+        }
+    }
+}
+
+impl fmt::Display for TopicType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:016x})", self.name, self.hash)
+    }
+}
+
+/// Decides in the publishing task whether, and in which form, a sample
+/// enters a subscriber's queue
+pub(crate) type Admit = Box<dyn Fn(Sample) -> Admission + Send + Sync>;
+
+/// Outcome of a subscriber's admission hook
+pub(crate) enum Admission {
+    /// Queue the sample, possibly transformed
+    Queue(Sample),
+    /// Rejected by the subscriber's filter
+    Filtered,
+    /// Left out to reduce the subscriber's message rate
+    Skipped,
+}
+
+/// Per-subscriber message queue
+pub(crate) struct SampleQueue {
+    id: u64,
+    node: Option<String>,
+    /// Feeds a transport to other processes
+    forwarding: bool,
+    samples: Mutex<VecDeque<Sample>>,
+    /// Replaces `samples` for [`ChannelKind::LockFree`]
+    ring: Option<Ring<Sample>>,
+    depth: Option<usize>,
+    overflow: OverflowPolicy,
+    qos: QosProfile,
+    admit: RwLock<Option<Admit>>,
+    /// Samples rejected by `admit`
+    filtered: AtomicU64,
+    /// Samples left out by `admit` to reduce the rate
+    skipped: AtomicU64,
+    /// Panics caught in `admit`
+    admit_panics: AtomicU64,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+    readable: Notify,
+    readable_blocking: Condvar,
+    writable: Notify,
+    /// Wakes the deadline monitor whenever a sample is admitted
+    arrival: Notify,
+    /// Wait sets woken when a sample is queued
+    waiters: Waiters,
+    events: broadcast::Sender<QosEvent>,
+}
+
+impl SampleQueue {
+    /// Create a queue holding at most `depth` samples, or unbounded for `None`
+    pub(crate) fn new(depth: Option<usize>, overflow: OverflowPolicy) -> Self {
+        Self {
+            id: next_endpoint_id(),
+            node: None,
+            forwarding: false,
+            samples: Mutex::new(VecDeque::new()),
+            ring: None,
+            depth: depth.map(|d| d.max(1)),
+            overflow,
+            qos: QosProfile::default(),
+            admit: RwLock::new(None),
+            filtered: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            admit_panics: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            readable: Notify::new(),
+            readable_blocking: Condvar::new(),
+            writable: Notify::new(),
+            arrival: Notify::new(),
+            waiters: Waiters::default(),
+            events: broadcast::channel(QOS_EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Set the QoS requested by the owning subscriber
+    pub(crate) fn with_qos(mut self, qos: QosProfile) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Keep samples in a lock-free ring instead of a locked deque
+    ///
+    /// The ring holds the depth rounded up to a power of two, or
+    /// [`LOCK_FREE_DEPTH`] samples for an unbounded queue.
+    pub(crate) fn with_channel(mut self, channel: ChannelKind) -> Self {
+        self.ring = match channel {
+            ChannelKind::Locked => None,
+            ChannelKind::LockFree => {
+                Some(Ring::new(self.depth.unwrap_or(LOCK_FREE_DEPTH), true))
+            }
+        };
+        self
+    }
+
+    /// Record the node that owns the subscriber
+    pub(crate) fn with_node(mut self, node: Option<String>) -> Self {
+        self.node = node;
+        self
+    }
+
+    /// Mark the queue as feeding a transport, so flushes wait for it
+    pub(crate) fn forwarding(mut self) -> Self {
+        self.forwarding = true;
+        self
+    }
+
+    /// Filter or transform samples before they are queued
+    pub(crate) fn with_admit(self, admit: Option<Admit>) -> Self {
+        *self.admit.write() = admit;
+        self
+    }
+
+    /// Replace the admission hook of a queue already on the bus
+    pub(crate) fn set_admit(&self, admit: Admit) {
+        *self.admit.write() = Some(admit);
+    }
+
+    /// Run the admission hook, counting rejected and skipped samples and
+    /// caught panics
+    fn admit(&self, sample: Sample) -> Option<Sample> {
+        let admit = self.admit.read();
+        let Some(admit) = admit.as_ref() else {
+            return Some(sample);
+        };
+        match panic::catch_unwind(AssertUnwindSafe(|| admit(sample))) {
+            Ok(Admission::Queue(sample)) => Some(sample),
+            Ok(Admission::Filtered) => {
+                self.filtered.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            Ok(Admission::Skipped) => {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            Err(_) => {
+                self.admit_panics.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Queue replayed samples, keeping only the newest that fit
+    fn preload(&self, replay: impl IntoIterator<Item = Sample>) {
+        let replay: Vec<Sample> = replay.into_iter().filter_map(|s| self.admit(s)).collect();
+        if let Some(ring) = &self.ring {
+            for mut sample in replay {
+                while let Err(rejected) = ring.push(sample) {
+                    ring.pop();
+                    sample = rejected;
+                }
+            }
+            if ring.len() > 0 {
+                self.readable.notify_one();
+                self.waiters.wake();
+            }
+            return;
+        }
+        let mut samples = self.samples.lock();
+        for sample in replay {
+            if self.depth.is_some_and(|depth| samples.len() >= depth) {
+                samples.pop_front();
+            }
+            samples.push_back(sample);
+        }
+        if !samples.is_empty() {
+            self.readable.notify_one();
+            self.readable_blocking.notify_one();
+            self.waiters.wake();
+        }
+    }
+
+    async fn push(&self, sample: Sample) {
+        let Some(sample) = self.admit(sample) else {
+            return;
+        };
+        self.arrival.notify_one();
+        if let Some(ring) = &self.ring {
+            return self.push_lock_free(ring, sample).await;
+        }
+        loop {
+            let writable = self.writable.notified();
+            let full = {
+                let mut samples = self.samples.lock();
+                if self.closed.load(Ordering::Acquire) {
+                    return;
+                }
+                match self.depth {
+                    Some(depth) if samples.len() >= depth => match self.overflow {
+                        OverflowPolicy::DropOldest => {
+                            samples.pop_front();
+                            samples.push_back(sample.clone());
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                            false
+                        }
+                        OverflowPolicy::DropNewest => {
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                            return;
+                        }
+                        OverflowPolicy::Block => true,
+                    },
+                    _ => {
+                        samples.push_back(sample.clone());
+                        false
+                    }
+                }
+            };
+            if full {
+                writable.await;
+                continue;
+            }
+            self.readable.notify_one();
+            self.readable_blocking.notify_one();
+            self.waiters.wake();
+            return;
+        }
+    }
+
+    /// [`push`](Self::push) into the ring, which a publisher never waits
+    /// for unless the queue is full under [`OverflowPolicy::Block`]
+    async fn push_lock_free(&self, ring: &Ring<Sample>, mut sample: Sample) {
+        loop {
+            let writable = self.writable.notified();
+            if self.closed.load(Ordering::Acquire) {
+                return;
+            }
+            match ring.push(sample) {
+                Ok(()) => break,
+                Err(rejected) => {
+                    sample = rejected;
+                    match self.overflow {
+                        OverflowPolicy::DropOldest => {
+                            if ring.pop().is_some() {
+                                self.dropped.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                        OverflowPolicy::DropNewest => {
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                            return;
+                        }
+                        OverflowPolicy::Block => writable.await,
+                    }
+                }
+            }
+        }
+        self.readable.notify_one();
+        self.waiters.wake();
+    }
+
+    pub(crate) fn try_pop(&self) -> Option<Sample> {
+        let sample = match &self.ring {
+            Some(ring) => ring.pop(),
+            None => self.samples.lock().pop_front(),
+        };
+        if sample.is_some() {
+            self.writable.notify_one();
+        }
+        sample
+    }
+
+    /// Wait for the next sample, or `None` once the queue is closed and empty
+    pub(crate) async fn pop(&self) -> Option<Sample> {
+        loop {
+            let notified = self.readable.notified();
+            if let Some(sample) = self.try_pop() {
+                return Some(sample);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            notified.await;
+        }
+    }
+
+    pub(crate) fn pop_blocking(&self) -> Option<Sample> {
+        if let Some(ring) = &self.ring {
+            let sample = ring.pop_until(None);
+            if sample.is_some() {
+                self.writable.notify_one();
+            }
+            return sample;
+        }
+        let mut samples = self.samples.lock();
+        loop {
+            if let Some(sample) = samples.pop_front() {
+                drop(samples);
+                self.writable.notify_one();
+                return Some(sample);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.readable_blocking.wait(&mut samples);
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match &self.ring {
+            Some(ring) => ring.len(),
+            None => self.samples.lock().len(),
+        }
+    }
+
+    pub(crate) fn qos(&self) -> QosProfile {
+        self.qos
+    }
+
+    pub(crate) fn waiters(&self) -> &Waiters {
+        &self.waiters
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn filtered(&self) -> u64 {
+        self.filtered.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn admit_panics(&self) -> u64 {
+        self.admit_panics.load(Ordering::Relaxed)
+    }
+
+    /// Wait until a sample is admitted, or return right away if one was
+    /// admitted since the last call
+    pub(crate) async fn arrived(&self) {
+        self.arrival.notified().await;
+    }
+
+    /// Pass a QoS event to the subscriber's watchers, if any
+    pub(crate) fn send_event(&self, event: QosEvent) {
+        let _ = self.events.send(event);
+    }
+
+    pub(crate) fn watch_events(&self) -> QosEventWatcher {
+        QosEventWatcher::new(self.events.subscribe())
+    }
+
+    /// Stop accepting samples and release any publisher blocked on this
+    /// queue and any receiver waiting on it
+    fn close(&self) {
+        let _samples = self.samples.lock();
+        self.closed.store(true, Ordering::Release);
+        if let Some(ring) = &self.ring {
+            ring.close();
+        }
+        self.writable.notify_waiters();
+        self.readable.notify_waiters();
+        self.readable_blocking.notify_all();
+    }
+}
+
+/// What a direct handler is given: the publisher's own message, or a
+/// sample from a raw publisher or a transport
+#[derive(Clone, Copy)]
+pub(crate) enum Direct<'a> {
+    Message(&'a dyn Any),
+    Sample(&'a Sample),
+}
+
+/// Callback of a direct subscriber, run on the publisher's call stack
+pub(crate) struct DirectHandler {
+    id: u64,
+    node: Option<String>,
+    callback: Box<dyn Fn(Direct<'_>) + Send + Sync>,
+    delivered: AtomicU64,
+    /// Messages not passed to the callback because it was already running
+    /// further up the same thread's stack
+    reentrant: AtomicU64,
+    panics: AtomicU64,
+}
+
+thread_local! {
+    /// Direct handlers running on this thread, innermost last
+    static ACTIVE_HANDLERS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+impl DirectHandler {
+    pub(crate) fn new(
+        node: Option<String>,
+        callback: Box<dyn Fn(Direct<'_>) + Send + Sync>,
+    ) -> Self {
+        Self {
+            id: next_endpoint_id(),
+            node,
+            callback,
+            delivered: AtomicU64::new(0),
+            reentrant: AtomicU64::new(0),
+            panics: AtomicU64::new(0),
+        }
+    }
+
+    /// Run the callback unless it is already running on this thread
+    fn dispatch(&self, msg: Direct<'_>) {
+        let entered = ACTIVE_HANDLERS.with(|active| {
+            let mut active = active.borrow_mut();
+            if active.contains(&self.id) {
+                return false;
+            }
+            active.push(self.id);
+            true
+        });
+        if !entered {
+            self.reentrant.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| (self.callback)(msg)));
+        ACTIVE_HANDLERS.with(|active| active.borrow_mut().retain(|&id| id != self.id));
+        match result {
+            Ok(()) => self.delivered.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.panics.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub(crate) fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn reentrant(&self) -> u64 {
+        self.reentrant.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn panics(&self) -> u64 {
+        self.panics.load(Ordering::Relaxed)
+    }
+}
+
+/// A publisher's registration on a topic
+pub(crate) struct Writer {
+    id: u64,
+    node: Option<String>,
+    qos: QosProfile,
+    history: Mutex<VecDeque<Sample>>,
+    latch: AtomicBool,
+    latched: Mutex<Option<Sample>>,
+    closed: AtomicBool,
+    seq: AtomicU64,
+    /// Wakes the liveliness monitor whenever the publisher asserts liveliness
+    alive: Notify,
+}
+
+impl Writer {
+    pub(crate) fn new(qos: QosProfile, node: Option<String>) -> Self {
+        Self {
+            id: next_endpoint_id(),
+            node,
+            qos,
+            history: Mutex::new(VecDeque::new()),
+            latch: AtomicBool::new(false),
+            latched: Mutex::new(None),
+            closed: AtomicBool::new(false),
+            seq: AtomicU64::new(0),
+            alive: Notify::new(),
+        }
+    }
+
+    pub(crate) fn guid(&self) -> PublisherGuid {
+        PublisherGuid::local(self.id)
+    }
+
+    /// Sequence number for the next published message
+    pub(crate) fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Renew the publisher's liveliness lease
+    pub(crate) fn assert_liveliness(&self) {
+        self.alive.notify_one();
+    }
+
+    /// Wait until the publisher asserts liveliness, or return right away if
+    /// it did since the last call
+    pub(crate) async fn asserted(&self) {
+        self.alive.notified().await;
+    }
+
+    /// Whether the publisher was shut down through its node
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Keep the last published sample for every subscriber that joins later
+    pub(crate) fn set_latch(&self, latch: bool) {
+        self.latch.store(latch, Ordering::Release);
+        if !latch {
+            self.latched.lock().take();
+        }
+    }
+
+    pub(crate) fn is_latched(&self) -> bool {
+        self.latch.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn qos(&self) -> QosProfile {
+        self.qos
+    }
+
+    /// Whether published samples are kept for late subscribers
+    pub(crate) fn keeps_history(&self) -> bool {
+        self.qos.durability == Durability::TransientLocal
+    }
+
+    fn record(&self, sample: &Sample, latched: Option<Sample>) {
+        if latched.is_some() {
+            *self.latched.lock() = latched;
+        }
+        if !self.keeps_history() {
+            return;
+        }
+        let mut history = self.history.lock();
+        if history.len() >= self.qos.history_depth.max(1) {
+            history.pop_front();
+        }
+        history.push_back(sample.clone());
+    }
+}
+
+/// A single named topic
+pub(crate) struct Topic {
+    name: String,
+    message_type: Mutex<Option<TopicType>>,
+    publishers: Mutex<Vec<Arc<Writer>>>,
+    // Copy-on-write so publishing only clones an `Arc`
+    subscribers: RwLock<Arc<Vec<Arc<SampleQueue>>>>,
+    direct: RwLock<Arc<Vec<Arc<DirectHandler>>>>,
+    trace: trace::Label,
+    metrics: TopicMetrics,
+}
+
+/// Series of a topic in the global [`Registry`]
+struct TopicMetrics {
+    published: Counter,
+    serialization: Histogram,
+    latency: Histogram,
+}
+
+impl TopicMetrics {
+    fn new(topic: &str) -> Self {
+        let registry = Registry::global();
+        let labels = [("topic", topic)];
+        Self {
+            published: registry.counter(
+                metrics::MESSAGES_PUBLISHED,
+                "Messages published on the topic",
+                &labels,
+            ),
+            serialization: registry.histogram(
+                metrics::SERIALIZATION_DURATION,
+                "Time to serialize a published message",
+                &labels,
+            ),
+            latency: registry.histogram(
+                metrics::MESSAGE_LATENCY,
+                "Time from publishing a message to a subscriber taking it",
+                &labels,
+            ),
+        }
+    }
+}
+
+impl Topic {
+    fn new(name: String) -> Self {
+        Self {
+            trace: trace::intern(&name),
+            metrics: TopicMetrics::new(&name),
+            name,
+            message_type: Mutex::new(None),
+            publishers: Mutex::new(Vec::new()),
+            subscribers: RwLock::new(Arc::new(Vec::new())),
+            direct: RwLock::new(Arc::new(Vec::new())),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Label of this topic's publisher and subscriber lanes in a trace
+    pub(crate) fn trace_label(&self) -> trace::Label {
+        self.trace
+    }
+
+    /// Type of the first typed endpoint registered on this topic
+    pub(crate) fn message_type(&self) -> Option<TopicType> {
+        *self.message_type.lock()
+    }
+
+    /// Adopt `found` as the topic type, or reject it if it differs
+    fn check_type(&self, found: Option<TopicType>) -> Result<()> {
+        let Some(found) = found else {
+            return Ok(());
+        };
+        let mut current = self.message_type.lock();
+        match *current {
+            Some(expected) if expected != found => Err(Error::TopicTypeMismatch {
+                topic: self.name.clone(),
+                expected: expected.to_string(),
+                found: found.to_string(),
+            }),
+            Some(_) => Ok(()),
+            None => {
+                *current = Some(found);
+                Ok(())
+            }
+        }
+    }
+
+    /// Check that `writer` can serve every current subscriber
+    fn check_publisher_qos(&self, writer: &Writer) -> Result<()> {
+        for queue in self.subscribers.read().iter() {
+            self.check_qos(&writer.qos, &queue.qos)?;
+        }
+        Ok(())
+    }
+
+    /// Check that every current publisher can serve `queue`
+    fn check_subscriber_qos(&self, queue: &SampleQueue) -> Result<()> {
+        for writer in self.publishers.lock().iter() {
+            self.check_qos(&writer.qos, &queue.qos)?;
+        }
+        Ok(())
+    }
+
+    fn check_qos(&self, offered: &QosProfile, requested: &QosProfile) -> Result<()> {
+        offered
+            .check_compatible(requested)
+            .map_err(|reason| Error::QosIncompatible {
+                topic: self.name.clone(),
+                reason,
+            })
+    }
+
+    /// Count a message published on this topic
+    pub(crate) fn count_published(&self) {
+        self.metrics.published.inc();
+    }
+
+    pub(crate) fn observe_serialization(&self, duration: Duration) {
+        self.metrics.serialization.observe(duration);
+    }
+
+    /// Record the publish-to-receive latency of a message
+    pub(crate) fn observe_latency(&self, latency: Duration) {
+        self.metrics.latency.observe(latency);
+    }
+
+    /// Messages waiting in the subscriber queues
+    fn queue_depth(&self) -> usize {
+        self.subscribers.read().iter().map(|queue| queue.len()).sum()
+    }
+
+    pub(crate) fn has_publishers(&self) -> bool {
+        !self.publishers.lock().is_empty()
+    }
+
+    /// The samples latched by this topic's publishers
+    pub(crate) fn latched(&self) -> Vec<Sample> {
+        self.publishers
+            .lock()
+            .iter()
+            .filter_map(|writer| writer.latched.lock().clone())
+            .collect()
+    }
+
+    /// Whether anyone is currently listening through a queue
+    pub(crate) fn has_subscribers(&self) -> bool {
+        !self.subscribers.read().is_empty()
+    }
+
+    pub(crate) fn has_direct_subscribers(&self) -> bool {
+        !self.direct.read().is_empty()
+    }
+
+    /// Run every direct subscriber's callback on the caller's stack
+    pub(crate) fn dispatch_direct(&self, msg: Direct<'_>) {
+        let handlers = self.direct.read().clone();
+        for handler in handlers.iter() {
+            handler.dispatch(msg);
+        }
+    }
+
+    /// Deliver a sample from `writer` to every direct subscriber, then to
+    /// every queue
+    ///
+    /// Waits for room in any subscriber queue using
+    /// [`OverflowPolicy::Block`]. Returns the number of queues the sample was
+    /// offered to. Fails only if a latched zero-copy sample cannot be
+    /// serialized.
+    pub(crate) async fn deliver(&self, writer: &Writer, sample: Sample) -> Result<usize> {
+        if self.has_direct_subscribers() {
+            self.dispatch_direct(Direct::Sample(&sample));
+        }
+        self.deliver_queued(writer, sample).await
+    }
+
+    /// Deliver a sample whose message was already dispatched to the direct
+    /// subscribers
+    pub(crate) async fn deliver_queued(&self, writer: &Writer, sample: Sample) -> Result<usize> {
+        // The latch holds bytes so it does not keep the publisher's message alive
+        let latched = if writer.is_latched() {
+            Some(sample.to_serialized()?)
+        } else {
+            None
+        };
+        // Record under the subscriber lock so a joining subscriber gets the
+        // sample either from the replay or from delivery, never both
+        let subscribers = {
+            let subscribers = self.subscribers.read();
+            writer.record(&sample, latched);
+            subscribers.clone()
+        };
+        for queue in subscribers.iter() {
+            queue.push(sample.clone()).await;
+        }
+        Ok(subscribers.len())
+    }
+
+    /// Pass a QoS event to every subscriber
+    pub(crate) fn notify_subscribers(&self, event: QosEvent) {
+        for queue in self.subscribers.read().iter() {
+            queue.send_event(event);
+        }
+    }
+
+    /// Samples queued for transports to other processes
+    pub(crate) fn pending_outgoing(&self) -> usize {
+        self.subscribers
+            .read()
+            .iter()
+            .filter(|queue| queue.forwarding)
+            .map(|queue| queue.len())
+            .sum()
+    }
+
+    /// Wait until every transport has taken its queued samples
+    pub(crate) async fn flush(&self) {
+        while self.pending_outgoing() > 0 {
+            tokio::time::sleep(FLUSH_POLL).await;
+        }
+    }
+
+    fn is_unused(&self) -> bool {
+        self.publishers.lock().is_empty()
+            && self.subscribers.read().is_empty()
+            && self.direct.read().is_empty()
+    }
+}
+
+/// Registry of topics shared by publishers and subscribers
+pub struct TopicBus {
+    topics: RwLock<HashMap<String, Arc<Topic>>>,
+    events: broadcast::Sender<GraphEvent>,
+}
+
+impl TopicBus {
+    /// Create an empty, standalone bus
+    pub fn new() -> Self {
+        Self {
+            topics: RwLock::new(HashMap::new()),
+            events: broadcast::channel(GRAPH_EVENT_CAPACITY).0,
+        }
+    }
+
+    /// The process-wide bus used by [`Publisher`](crate::Publisher) and
+    /// [`Subscriber`](crate::Subscriber)
+    pub fn global() -> &'static TopicBus {
+        static BUS: OnceLock<TopicBus> = OnceLock::new();
+        BUS.get_or_init(TopicBus::new)
+    }
+
+    /// Names of all topics with at least one publisher or subscriber
+    pub fn topic_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.topics.read().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Number of subscribers currently registered on a topic
+    pub fn subscriber_count(&self, topic: &str) -> usize {
+        self.topics
+            .read()
+            .get(topic)
+            .map(|t| t.subscribers.read().len() + t.direct.read().len())
+            .unwrap_or(0)
+    }
+
+    /// Number of publishers currently registered on a topic
+    pub fn publisher_count(&self, topic: &str) -> usize {
+        self.topics
+            .read()
+            .get(topic)
+            .map(|t| t.publishers.lock().len())
+            .unwrap_or(0)
+    }
+
+    /// Name, type and endpoint counts of every topic, sorted by name
+    pub fn topic_infos(&self) -> Vec<TopicInfo> {
+        let mut infos: Vec<TopicInfo> = self
+            .topics
+            .read()
+            .values()
+            .map(|t| TopicInfo {
+                name: t.name.clone(),
+                type_name: t.message_type().map(|ty| ty.name.to_string()),
+                publisher_count: t.publishers.lock().len(),
+                subscriber_count: t.subscribers.read().len() + t.direct.read().len(),
+            })
+            .collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    /// Endpoint counts and message counters of every topic, sorted by name
+    pub fn topic_counters(&self) -> Vec<TopicCounters> {
+        let mut stats: Vec<TopicCounters> = self
+            .topics
+            .read()
+            .values()
+            .map(|t| {
+                let queues = t.subscribers.read();
+                TopicCounters {
+                    name: t.name.clone(),
+                    publisher_count: t.publishers.lock().len(),
+                    subscriber_count: queues.len() + t.direct.read().len(),
+                    published: t.metrics.published.get(),
+                    dropped: queues.iter().map(|q| q.dropped()).sum(),
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+
+    /// Publishers currently registered on a topic
+    pub fn publishers(&self, topic: &str) -> Vec<EndpointInfo> {
+        self.topics
+            .read()
+            .get(topic)
+            .map(|t| {
+                t.publishers
+                    .lock()
+                    .iter()
+                    .map(|w| Self::writer_info(t, w))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Subscribers currently registered on a topic
+    pub fn subscribers(&self, topic: &str) -> Vec<EndpointInfo> {
+        self.topics
+            .read()
+            .get(topic)
+            .map(|t| {
+                let mut infos: Vec<EndpointInfo> =
+                    t.subscribers.read().iter().map(|q| Self::queue_info(t, q)).collect();
+                infos.extend(t.direct.read().iter().map(|h| Self::direct_info(t, h)));
+                infos
+            })
+            .unwrap_or_default()
+    }
+
+    /// Every queued subscriber with the number of messages its queue has
+    /// dropped, sorted by topic
+    pub(crate) fn subscriber_drops(&self) -> Vec<(EndpointInfo, u64)> {
+        let mut drops: Vec<(EndpointInfo, u64)> = self
+            .topics
+            .read()
+            .values()
+            .flat_map(|t| {
+                t.subscribers
+                    .read()
+                    .iter()
+                    .map(|q| (Self::queue_info(t, q), q.dropped()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        drops.sort_by(|a, b| (&a.0.topic, a.0.id).cmp(&(&b.0.topic, b.0.id)));
+        drops
+    }
+
+    /// Stream of changes to this bus from now on
+    pub fn watch(&self) -> GraphWatcher {
+        GraphWatcher::new(self.events.subscribe())
+    }
+
+    fn writer_info(topic: &Topic, writer: &Writer) -> EndpointInfo {
+        EndpointInfo {
+            id: writer.id,
+            topic: topic.name.clone(),
+            node: writer.node.clone(),
+            qos: writer.qos,
+        }
+    }
+
+    fn queue_info(topic: &Topic, queue: &SampleQueue) -> EndpointInfo {
+        EndpointInfo {
+            id: queue.id,
+            topic: topic.name.clone(),
+            node: queue.node.clone(),
+            qos: queue.qos,
+        }
+    }
+
+    fn direct_info(topic: &Topic, handler: &DirectHandler) -> EndpointInfo {
+        EndpointInfo {
+            id: handler.id,
+            topic: topic.name.clone(),
+            node: handler.node.clone(),
+            qos: QosProfile::default(),
+        }
+    }
+
+    fn emit(&self, event: GraphEvent) {
+        // Nobody watching is not an error
+        let _ = self.events.send(event);
+    }
+
+    /// Get or create a topic, reporting whether it was created
+    fn entry(topics: &mut HashMap<String, Arc<Topic>>, name: &str) -> (Arc<Topic>, bool) {
+        if let Some(topic) = topics.get(name) {
+            return (topic.clone(), false);
+        }
+        let topic = Arc::new(Topic::new(name.to_string()));
+        let weak = Arc::downgrade(&topic);
+        Registry::global().gauge_fn(
+            metrics::QUEUE_DEPTH,
+            "Messages waiting in the subscriber queues of the topic",
+            &[("topic", name)],
+            move || weak.upgrade().map(|topic| topic.queue_depth() as i64),
+        );
+        topics.insert(name.to_string(), topic.clone());
+        (topic, true)
+    }
+
+    fn emit_created(&self, topic: &Topic, created: bool) {
+        if created {
+            self.emit(GraphEvent::TopicCreated {
+                topic: topic.name.clone(),
+            });
+        }
+    }
+
+    /// Samples on any topic still queued for transports to other processes
+    pub fn pending_outgoing(&self) -> usize {
+        self.topics
+            .read()
+            .values()
+            .map(|topic| topic.pending_outgoing())
+            .sum()
+    }
+
+    /// Wait until the transports have taken every sample queued for other
+    /// processes
+    ///
+    /// A transport that cannot send, such as a TCP uplink without a
+    /// connected peer, keeps this waiting; bound it with a timeout.
+    pub async fn flush(&self) {
+        while self.pending_outgoing() > 0 {
+            tokio::time::sleep(FLUSH_POLL).await;
+        }
+    }
+
+    /// Type registered on a topic, if any typed endpoint has joined it
+    pub fn topic_type(&self, topic: &str) -> Option<TopicType> {
+        self.topics.read().get(topic).and_then(|t| t.message_type())
+    }
+
+    pub(crate) fn attach_publisher(
+        &self,
+        name: &str,
+        message_type: Option<TopicType>,
+        writer: Arc<Writer>,
+    ) -> Result<Arc<Topic>> {
+        let mut topics = self.topics.write();
+        let (topic, created) = Self::entry(&mut topics, name);
+        if let Err(e) = topic
+            .check_type(message_type)
+            .and_then(|()| topic.check_publisher_qos(&writer))
+        {
+            Self::remove_if_unused(&mut topics, &topic);
+            return Err(e);
+        }
+        self.emit_created(&topic, created);
+        self.emit(GraphEvent::PublisherAdded(Self::writer_info(&topic, &writer)));
+        topic.publishers.lock().push(writer);
+        Ok(topic)
+    }
+
+    pub(crate) fn detach_publisher(&self, topic: &Arc<Topic>, writer: &Arc<Writer>) {
+        writer.closed.store(true, Ordering::Release);
+        let mut topics = self.topics.write();
+        let removed = {
+            let mut publishers = topic.publishers.lock();
+            let before = publishers.len();
+            publishers.retain(|w| !Arc::ptr_eq(w, writer));
+            publishers.len() < before
+        };
+        // Detaching twice (node shutdown, then drop) reports nothing the second time
+        if removed {
+            self.emit(GraphEvent::PublisherRemoved(Self::writer_info(topic, writer)));
+            self.remove_unused(&mut topics, topic);
+        }
+    }
+
+    pub(crate) fn attach_subscriber(
+        &self,
+        name: &str,
+        message_type: Option<TopicType>,
+        queue: Arc<SampleQueue>,
+    ) -> Result<Arc<Topic>> {
+        let mut topics = self.topics.write();
+        let (topic, created) = Self::entry(&mut topics, name);
+        if let Err(e) = topic
+            .check_type(message_type)
+            .and_then(|()| topic.check_subscriber_qos(&queue))
+        {
+            Self::remove_if_unused(&mut topics, &topic);
+            return Err(e);
+        }
+        self.emit_created(&topic, created);
+        self.emit(GraphEvent::SubscriberAdded(Self::queue_info(&topic, &queue)));
+        {
+            let mut subscribers = topic.subscribers.write();
+            let transient = queue.qos.durability == Durability::TransientLocal;
+            for writer in topic.publishers.lock().iter() {
+                if transient && writer.keeps_history() {
+                    // The history already ends with any latched sample
+                    queue.preload(writer.history.lock().iter().cloned());
+                } else if let Some(latched) = writer.latched.lock().clone() {
+                    queue.preload([latched]);
+                }
+            }
+            let mut next = Vec::with_capacity(subscribers.len() + 1);
+            next.extend(subscribers.iter().cloned());
+            next.push(queue);
+            *subscribers = Arc::new(next);
+        }
+        Ok(topic)
+    }
+
+    pub(crate) fn detach_subscriber(&self, topic: &Arc<Topic>, queue: &Arc<SampleQueue>) {
+        queue.close();
+        let mut topics = self.topics.write();
+        let removed = {
+            let mut subscribers = topic.subscribers.write();
+            let next: Vec<_> = subscribers
+                .iter()
+                .filter(|q| !Arc::ptr_eq(q, queue))
+                .cloned()
+                .collect();
+            let removed = next.len() < subscribers.len();
+            *subscribers = Arc::new(next);
+            removed
+        };
+        if removed {
+            self.emit(GraphEvent::SubscriberRemoved(Self::queue_info(topic, queue)));
+            self.remove_unused(&mut topics, topic);
+        }
+    }
+
+    /// Register a direct subscriber on `name`
+    pub(crate) fn attach_direct(
+        &self,
+        name: &str,
+        message_type: Option<TopicType>,
+        handler: Arc<DirectHandler>,
+    ) -> Result<Arc<Topic>> {
+        let mut topics = self.topics.write();
+        let (topic, created) = Self::entry(&mut topics, name);
+        if let Err(e) = topic.check_type(message_type) {
+            Self::remove_if_unused(&mut topics, &topic);
+            return Err(e);
+        }
+        self.emit_created(&topic, created);
+        self.emit(GraphEvent::SubscriberAdded(Self::direct_info(&topic, &handler)));
+        {
+            let mut direct = topic.direct.write();
+            let mut next = Vec::with_capacity(direct.len() + 1);
+            next.extend(direct.iter().cloned());
+            next.push(handler);
+            *direct = Arc::new(next);
+        }
+        Ok(topic)
+    }
+
+    pub(crate) fn detach_direct(&self, topic: &Arc<Topic>, handler: &Arc<DirectHandler>) {
+        let mut topics = self.topics.write();
+        let removed = {
+            let mut direct = topic.direct.write();
+            let next: Vec<_> = direct
+                .iter()
+                .filter(|h| !Arc::ptr_eq(h, handler))
+                .cloned()
+                .collect();
+            let removed = next.len() < direct.len();
+            *direct = Arc::new(next);
+            removed
+        };
+        if removed {
+            self.emit(GraphEvent::SubscriberRemoved(Self::direct_info(topic, handler)));
+            self.remove_unused(&mut topics, topic);
+        }
+    }
+
+    /// Remove a topic nobody uses any more and report it
+    fn remove_unused(&self, topics: &mut HashMap<String, Arc<Topic>>, topic: &Arc<Topic>) {
+        if Self::remove_if_unused(topics, topic) {
+            self.emit(GraphEvent::TopicRemoved {
+                topic: topic.name.clone(),
+            });
+        }
+    }
+
+    fn remove_if_unused(topics: &mut HashMap<String, Arc<Topic>>, topic: &Arc<Topic>) -> bool {
+        if topic.is_unused() {
+            if let Some(current) = topics.get(topic.name()) {
+                if Arc::ptr_eq(current, topic) {
+                    topics.remove(topic.name());
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Default for TopicBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> Arc<SampleQueue> {
+        Arc::new(SampleQueue::new(None, OverflowPolicy::default()))
+    }
+
+    fn writer() -> Arc<Writer> {
+        Arc::new(Writer::new(QosProfile::default(), None))
+    }
+
+    fn sample(payload: Vec<u8>) -> Sample {
+        Sample::new(payload, Format::Cdr, Arc::from("test/Bytes"))
+    }
+
+    #[tokio::test]
+    async fn test_fan_out() {
+        let bus = TopicBus::new();
+        let (a, b) = (queue(), queue());
+        let topic = bus.attach_subscriber("fan/out", None, a.clone()).unwrap();
+        bus.attach_subscriber("fan/out", None, b.clone()).unwrap();
+
+        let delivered = topic
+            .deliver(&writer(), sample(vec![1, 2, 3]))
+            .await
+            .unwrap();
+        assert_eq!(delivered, 2);
+        assert_eq!(&*a.try_pop().unwrap().bytes().unwrap(), &[1, 2, 3]);
+        assert_eq!(&*b.try_pop().unwrap().bytes().unwrap(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_topic_removed_when_unused() {
+        let bus = TopicBus::new();
+        let writer = writer();
+        let publisher = bus.attach_publisher("cleanup", None, writer.clone()).unwrap();
+        let queue = queue();
+        let topic = bus.attach_subscriber("cleanup", None, queue.clone()).unwrap();
+        assert_eq!(bus.topic_names(), vec!["cleanup".to_string()]);
+
+        bus.detach_subscriber(&topic, &queue);
+        assert_eq!(bus.subscriber_count("cleanup"), 0);
+        assert_eq!(bus.publisher_count("cleanup"), 1);
+
+        bus.detach_publisher(&publisher, &writer);
+        assert!(bus.topic_names().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_closed_queue_releases_blocked_publisher() {
+        let bus = TopicBus::new();
+        let queue = Arc::new(SampleQueue::new(Some(1), OverflowPolicy::Block));
+        let topic = bus.attach_subscriber("blocked", None, queue.clone()).unwrap();
+        topic.deliver(&writer(), sample(vec![1])).await.unwrap();
+
+        let pending = tokio::spawn({
+            let topic = topic.clone();
+            async move { topic.deliver(&writer(), sample(vec![2])).await }
+        });
+        tokio::task::yield_now().await;
+        bus.detach_subscriber(&topic, &queue);
+
+        tokio::time::timeout(std::time::Duration::from_secs(1), pending)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_type_mismatch_rejected() {
+        use crate::message::{PointCloud, RobotState};
+
+        let bus = TopicBus::new();
+        bus.attach_publisher("typed", TopicType::of::<RobotState>(), writer())
+            .unwrap();
+
+        let err = bus
+            .attach_subscriber("typed", TopicType::of::<PointCloud>(), queue())
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::TopicTypeMismatch { .. }));
+        assert_eq!(bus.subscriber_count("typed"), 0);
+
+        bus.attach_subscriber("typed", TopicType::of::<RobotState>(), queue())
+            .unwrap();
+        assert_eq!(bus.topic_type("typed").unwrap().name, "ros3_msgs/RobotState");
+    }
+
+    #[test]
+    fn test_shared_sample() {
+        use crate::message::RobotState;
+
+        let state = Arc::new(RobotState::default());
+        let sample = Sample::shared(state.clone(), Format::Cdr, Arc::from("ros3_msgs/RobotState"));
+
+        assert!(sample.is_shared());
+        assert!(Arc::ptr_eq(&sample.downcast::<RobotState>().unwrap(), &state));
+        assert!(sample.downcast::<crate::message::PointCloud>().is_none());
+        assert!(!sample.bytes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_incompatible_qos_rejected() {
+        use crate::qos::Reliability;
+
+        let bus = TopicBus::new();
+        let best_effort = QosProfile {
+            reliability: Reliability::BestEffort,
+            ..Default::default()
+        };
+        bus.attach_publisher("qos", None, Arc::new(Writer::new(best_effort, None)))
+            .unwrap();
+
+        let err = bus.attach_subscriber("qos", None, queue()).err().unwrap();
+        assert!(matches!(err, Error::QosIncompatible { .. }));
+
+        let relaxed =
+            Arc::new(SampleQueue::new(None, OverflowPolicy::default()).with_qos(best_effort));
+        bus.attach_subscriber("qos", None, relaxed).unwrap();
+
+        // A reliable subscriber already on the topic rejects a best-effort publisher too
+        bus.attach_subscriber("qos/late", None, queue()).unwrap();
+        let err = bus
+            .attach_publisher("qos/late", None, Arc::new(Writer::new(best_effort, None)))
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::QosIncompatible { .. }));
+        assert_eq!(bus.publisher_count("qos/late"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_transient_local_replay_keeps_history_depth() {
+        let bus = TopicBus::new();
+        let qos = QosProfile {
+            durability: Durability::TransientLocal,
+            history_depth: 2,
+            ..Default::default()
+        };
+        let writer = Arc::new(Writer::new(qos, None));
+        let topic = bus.attach_publisher("replay", None, writer.clone()).unwrap();
+        for i in 0..5 {
+            topic.deliver(&writer, sample(vec![i])).await.unwrap();
+        }
+
+        let late = Arc::new(SampleQueue::new(None, OverflowPolicy::default()).with_qos(qos));
+        bus.attach_subscriber("replay", None, late.clone()).unwrap();
+        assert_eq!(&*late.try_pop().unwrap().bytes().unwrap(), &[3]);
+        assert_eq!(&*late.try_pop().unwrap().bytes().unwrap(), &[4]);
+        assert!(late.try_pop().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_latch_holds_bytes_of_shared_sample() {
+        use crate::message::RobotState;
+
+        let bus = TopicBus::new();
+        let writer = writer();
+        writer.set_latch(true);
+        let topic = bus.attach_publisher("latch", None, writer.clone()).unwrap();
+
+        let state = Arc::new(RobotState::default());
+        let shared =
+            Sample::shared(state.clone(), Format::Cdr, Arc::from("ros3_msgs/RobotState"));
+        topic.deliver(&writer, shared).await.unwrap();
+        assert_eq!(Arc::strong_count(&state), 1);
+
+        let late = queue();
+        bus.attach_subscriber("latch", None, late.clone()).unwrap();
+        let replayed = late.try_pop().unwrap();
+        assert!(!replayed.is_shared());
+        assert!(!replayed.bytes().unwrap().is_empty());
+    }
+}
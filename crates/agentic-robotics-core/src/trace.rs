@@ -0,0 +1,515 @@
+//! Timeline tracing of task polls, messages and timer ticks
+//!
+//! While a [`Tracer`] runs, executor task polls, publishes, deliveries to
+//! subscribers and timer ticks are recorded into per-thread ring buffers.
+//! The tracer exports them as Chrome `trace_event` JSON, which
+//! `ui.perfetto.dev` and `chrome://tracing` open directly: tasks appear on
+//! one lane per executor thread, publishes and deliveries on one lane per
+//! topic.
+//!
+//! Recording takes no locks. Each thread writes only its own buffer and
+//! takes a lock once, to register the buffer, when it records its first
+//! event. While no tracer runs, a recording point costs one relaxed atomic
+//! load.
+
+use crate::error::{Error, Result};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Whether a tracer is running
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Bumped by every [`Tracer::start`], so threads drop buffers of an
+/// earlier tracer
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// This thread's buffer for the running tracer
+    static LOCAL: RefCell<Option<Arc<ThreadBuffer>>> = const { RefCell::new(None) };
+}
+
+/// What an event records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// One poll of an executor task, labelled with the task name
+    Poll,
+    /// A publish, labelled with the topic
+    Publish,
+    /// A message taken from a subscriber queue, labelled with the topic
+    Deliver,
+    /// A timer firing, labelled with the timer
+    Timer,
+}
+
+impl EventKind {
+    const ALL: [EventKind; 4] = [Self::Poll, Self::Publish, Self::Deliver, Self::Timer];
+
+    /// Chrome trace process the lanes of this kind are grouped under
+    fn pid(self) -> u32 {
+        self as u32 + 1
+    }
+
+    fn process_name(self) -> &'static str {
+        match self {
+            Self::Poll => "Tasks",
+            Self::Publish => "Publishers",
+            Self::Deliver => "Subscribers",
+            Self::Timer => "Timers",
+        }
+    }
+}
+
+/// Interned name of a task, topic or timer
+///
+/// Interning takes a lock, so it is done once when the task or topic is
+/// created rather than for every event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(u32);
+
+#[derive(Default)]
+struct Labels {
+    ids: HashMap<String, u32>,
+    names: Vec<String>,
+}
+
+fn labels() -> &'static Mutex<Labels> {
+    static LABELS: OnceLock<Mutex<Labels>> = OnceLock::new();
+    LABELS.get_or_init(Mutex::default)
+}
+
+/// Label for `name`; the same name always gets the same label
+pub fn intern(name: &str) -> Label {
+    let mut labels = labels().lock();
+    if let Some(&id) = labels.ids.get(name) {
+        return Label(id);
+    }
+    let id = labels.names.len() as u32;
+    labels.names.push(name.to_string());
+    labels.ids.insert(name.to_string(), id);
+    Label(id)
+}
+
+/// Whether events are being recorded
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Record an event that started at `start` and took `duration`
+///
+/// `arg` is shown with the event, e.g. a sequence number.
+pub fn record(kind: EventKind, label: Label, start: Instant, duration: Duration, arg: u64) {
+    if !enabled() {
+        return;
+    }
+    let start = start.saturating_duration_since(epoch()).as_nanos() as u64;
+    let words = [
+        kind as u64 | u64::from(label.0) << 8,
+        start,
+        duration.as_nanos() as u64,
+        arg,
+    ];
+    let generation = GENERATION.load(Ordering::Acquire);
+    // Threads being torn down no longer record
+    let _ = LOCAL.try_with(|local| {
+        let mut local = local.borrow_mut();
+        let buffer = match local.as_ref() {
+            Some(buffer) if buffer.generation == generation => buffer,
+            _ => match register(generation) {
+                Some(buffer) => local.insert(buffer),
+                None => return,
+            },
+        };
+        buffer.push(words);
+    });
+}
+
+/// Record an event without a duration
+pub fn instant(kind: EventKind, label: Label, arg: u64) {
+    if enabled() {
+        record(kind, label, Instant::now(), Duration::ZERO, arg);
+    }
+}
+
+/// Record an event lasting until the returned guard is dropped
+pub fn span(kind: EventKind, label: Label) -> Span {
+    Span {
+        start: enabled().then(Instant::now),
+        kind,
+        label,
+        arg: 0,
+    }
+}
+
+/// Records its event when dropped; see [`span`]
+#[must_use = "the span ends when the guard is dropped"]
+pub struct Span {
+    /// `None` if no tracer was running when the span began
+    start: Option<Instant>,
+    kind: EventKind,
+    label: Label,
+    arg: u64,
+}
+
+impl Span {
+    /// Show `arg` with the event
+    pub fn arg(mut self, arg: u64) -> Self {
+        self.arg = arg;
+        self
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            record(self.kind, self.label, start, start.elapsed(), self.arg);
+        }
+    }
+}
+
+/// Common origin of event timestamps
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Buffers of the running tracer
+#[derive(Default)]
+struct TraceState {
+    generation: u64,
+    capacity: usize,
+    buffers: Vec<Arc<ThreadBuffer>>,
+}
+
+fn state() -> &'static Mutex<TraceState> {
+    static STATE: OnceLock<Mutex<TraceState>> = OnceLock::new();
+    STATE.get_or_init(Mutex::default)
+}
+
+/// Create this thread's buffer if `generation` is still running
+fn register(generation: u64) -> Option<Arc<ThreadBuffer>> {
+    let mut state = state().lock();
+    if state.generation != generation || !enabled() {
+        return None;
+    }
+    let thread = std::thread::current();
+    let tid = state.buffers.len() as u32 + 1;
+    let name = thread
+        .name()
+        .map_or_else(|| format!("thread-{tid}"), str::to_string);
+    let buffer = Arc::new(ThreadBuffer {
+        generation,
+        tid,
+        name,
+        head: AtomicU64::new(0),
+        slots: (0..state.capacity).map(|_| Slot::default()).collect(),
+    });
+    state.buffers.push(buffer.clone());
+    Some(buffer)
+}
+
+/// Ring of the newest events of one thread
+///
+/// Only the owning thread writes. Each slot is a seqlock, so a reader
+/// skips slots being overwritten instead of blocking the writer.
+struct ThreadBuffer {
+    generation: u64,
+    tid: u32,
+    name: String,
+    /// Events written so far
+    head: AtomicU64,
+    slots: Box<[Slot]>,
+}
+
+#[derive(Default)]
+struct Slot {
+    /// `2n + 1` while event `n` is written, `2n + 2` once it is complete
+    seq: AtomicU64,
+    words: [AtomicU64; 4],
+}
+
+impl ThreadBuffer {
+    fn push(&self, words: [u64; 4]) {
+        let n = self.head.load(Ordering::Relaxed);
+        let slot = &self.slots[(n % self.slots.len() as u64) as usize];
+        slot.seq.store(2 * n + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        for (word, value) in slot.words.iter().zip(words) {
+            word.store(value, Ordering::Relaxed);
+        }
+        slot.seq.store(2 * n + 2, Ordering::Release);
+        self.head.store(n + 1, Ordering::Release);
+    }
+
+    /// Events still in the ring, oldest first
+    fn read(&self, events: &mut Vec<(u32, [u64; 4])>) {
+        let head = self.head.load(Ordering::Acquire);
+        let first = head.saturating_sub(self.slots.len() as u64);
+        for n in first..head {
+            let slot = &self.slots[(n % self.slots.len() as u64) as usize];
+            let before = slot.seq.load(Ordering::Acquire);
+            if before != 2 * n + 2 {
+                continue;
+            }
+            let words = slot
+                .words
+                .each_ref()
+                .map(|word| word.load(Ordering::Relaxed));
+            fence(Ordering::Acquire);
+            if slot.seq.load(Ordering::Relaxed) == before {
+                events.push((self.tid, words));
+            }
+        }
+    }
+}
+
+/// A recorded event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub kind: EventKind,
+    /// Task, topic or timer name
+    pub name: String,
+    /// Name of the thread that recorded the event
+    pub thread: String,
+    /// Since the process's trace epoch
+    pub start: Duration,
+    pub duration: Duration,
+    pub arg: u64,
+}
+
+/// Records events while it is alive
+///
+/// Only one tracer runs at a time. Each thread keeps its newest `capacity`
+/// events; older ones are overwritten. Dropping the tracer stops recording
+/// and writes the trace to its [`output`](Self::output), if set.
+pub struct Tracer {
+    generation: u64,
+    output: Option<PathBuf>,
+}
+
+impl Tracer {
+    /// Start recording, keeping up to `capacity` events per thread
+    ///
+    /// Fails if `capacity` is zero or another tracer is running.
+    pub fn start(capacity: usize) -> Result<Self> {
+        if capacity == 0 {
+            return Err(Error::Configuration(
+                "Trace capacity must be non-zero".into(),
+            ));
+        }
+        let mut state = state().lock();
+        if enabled() {
+            return Err(Error::Configuration("A tracer is already running".into()));
+        }
+        epoch();
+        let generation = GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+        *state = TraceState {
+            generation,
+            capacity,
+            buffers: Vec::new(),
+        };
+        ENABLED.store(true, Ordering::Release);
+        info!("Tracing with {} events per thread", capacity);
+        Ok(Self {
+            generation,
+            output: None,
+        })
+    }
+
+    /// Write the trace to `path` as Chrome JSON when the tracer is dropped
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// The events recorded so far, ordered by start time
+    pub fn events(&self) -> Vec<TraceEvent> {
+        let mut raw = Vec::new();
+        let mut threads = HashMap::new();
+        {
+            let state = state().lock();
+            if state.generation != self.generation {
+                return Vec::new();
+            }
+            for buffer in &state.buffers {
+                threads.insert(buffer.tid, buffer.name.clone());
+                buffer.read(&mut raw);
+            }
+        }
+        raw.sort_by_key(|(tid, words)| (words[1], *tid));
+
+        let labels = labels().lock();
+        raw.into_iter()
+            .map(|(tid, [head, start, duration, arg])| TraceEvent {
+                kind: EventKind::ALL[(head & 0xff) as usize],
+                name: labels.names[(head >> 8) as usize].clone(),
+                thread: threads[&tid].clone(),
+                start: Duration::from_nanos(start),
+                duration: Duration::from_nanos(duration),
+                arg,
+            })
+            .collect()
+    }
+
+    /// The recorded events as a Chrome `trace_event` document
+    ///
+    /// Task polls are laid out by thread; the other kinds get one lane per
+    /// name under a process of their own.
+    pub fn chrome_json(&self) -> Value {
+        let events = self.events();
+        let micros = |duration: Duration| duration.as_nanos() as f64 / 1000.0;
+
+        let mut trace = Vec::new();
+        for kind in EventKind::ALL {
+            trace.push(json!({
+                "ph": "M", "name": "process_name", "pid": kind.pid(),
+                "args": { "name": kind.process_name() },
+            }));
+        }
+        let mut lanes: HashMap<(EventKind, &str), usize> = HashMap::new();
+        for event in &events {
+            let lane_name = match event.kind {
+                EventKind::Poll => event.thread.as_str(),
+                _ => event.name.as_str(),
+            };
+            let next = lanes.len() + 1;
+            let tid = *lanes.entry((event.kind, lane_name)).or_insert_with(|| {
+                trace.push(json!({
+                    "ph": "M", "name": "thread_name", "pid": event.kind.pid(), "tid": next,
+                    "args": { "name": lane_name },
+                }));
+                next
+            });
+            let mut entry = json!({
+                "name": event.name,
+                "cat": format!("{:?}", event.kind).to_lowercase(),
+                "pid": event.kind.pid(),
+                "tid": tid,
+                "ts": micros(event.start),
+                "args": { "arg": event.arg, "thread": event.thread },
+            });
+            if event.duration.is_zero() && event.kind != EventKind::Poll {
+                entry["ph"] = json!("i");
+                entry["s"] = json!("t");
+            } else {
+                entry["ph"] = json!("X");
+                entry["dur"] = json!(micros(event.duration));
+            }
+            trace.push(entry);
+        }
+        json!({ "traceEvents": trace, "displayTimeUnit": "ns" })
+    }
+
+    /// Write [`chrome_json`](Self::chrome_json) to `path`
+    pub fn write_chrome(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_vec(&self.chrome_json())
+            .map_err(|e| Error::Serialization(e.to_string()))?;
+        std::fs::write(path.as_ref(), json)?;
+        Ok(())
+    }
+}
+
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        ENABLED.store(false, Ordering::Release);
+        if let Some(path) = &self.output {
+            match self.write_chrome(path) {
+                Ok(()) => info!("Wrote trace to {}", path.display()),
+                Err(err) => warn!("Failed to write trace to {}: {}", path.display(), err),
+            }
+        }
+        let mut state = state().lock();
+        if state.generation == self.generation {
+            state.buffers.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests sharing the one tracer of the process
+    static SERIAL: Mutex<()> = parking_lot::const_mutex(());
+
+    fn named<'a>(events: &'a [TraceEvent], name: &str) -> Vec<&'a TraceEvent> {
+        events.iter().filter(|event| event.name == name).collect()
+    }
+
+    #[test]
+    fn test_events_are_recorded_per_thread() {
+        let _serial = SERIAL.lock();
+        let label = intern("/test/trace/threads");
+        instant(EventKind::Publish, label, 1);
+
+        let tracer = Tracer::start(64).unwrap();
+        assert!(Tracer::start(64).is_err());
+        {
+            let _span = span(EventKind::Publish, label).arg(7);
+        }
+        std::thread::Builder::new()
+            .name("trace-test".into())
+            .spawn(move || instant(EventKind::Deliver, label, 7))
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let events = tracer.events();
+        let events = named(&events, "/test/trace/threads");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, EventKind::Publish);
+        assert_eq!(events[0].arg, 7);
+        assert_eq!(events[1].kind, EventKind::Deliver);
+        assert_eq!(events[1].thread, "trace-test");
+        assert!(events[0].start <= events[1].start);
+
+        let json = tracer.chrome_json();
+        let trace = json["traceEvents"].as_array().unwrap();
+        let lanes: Vec<_> = trace
+            .iter()
+            .filter(|event| event["ph"] == "M" && event["args"]["name"] == "/test/trace/threads")
+            .map(|event| event["pid"].as_u64().unwrap())
+            .collect();
+        assert_eq!(lanes.len(), 2);
+        assert_ne!(lanes[0], lanes[1]);
+        let deliver = trace
+            .iter()
+            .find(|event| event["name"] == "/test/trace/threads" && event["ph"] == "i")
+            .unwrap();
+        assert_eq!(deliver["cat"], "deliver");
+
+        drop(tracer);
+        instant(EventKind::Publish, label, 1);
+        let tracer = Tracer::start(64).unwrap();
+        assert!(named(&tracer.events(), "/test/trace/threads").is_empty());
+    }
+
+    #[test]
+    fn test_ring_keeps_newest_events() {
+        let _serial = SERIAL.lock();
+        let label = intern("/test/trace/ring");
+        let tracer = Tracer::start(4).unwrap();
+        // A fresh thread, so that only these events share its buffer
+        std::thread::spawn(move || {
+            for seq in 0..10 {
+                instant(EventKind::Timer, label, seq);
+            }
+        })
+        .join()
+        .unwrap();
+
+        let events = tracer.events();
+        let args: Vec<u64> = named(&events, "/test/trace/ring")
+            .iter()
+            .map(|event| event.arg)
+            .collect();
+        assert_eq!(args, [6, 7, 8, 9]);
+        assert!(Tracer::start(0).is_err());
+    }
+}
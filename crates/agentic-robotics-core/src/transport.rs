@@ -0,0 +1,943 @@
+//! UDP transport
+//!
+//! Bridges the in-process [`TopicBus`] of one process to the buses of other
+//! processes. Each process announces its endpoints on a multicast group;
+//! samples of local publishers are then sent to the processes that
+//! subscribe to the same topic, either by unicast or on the multicast
+//! group, and fragmented when they exceed the MTU. Delivery is best effort.
+//!
+//! Subscribers announce the codecs they decompress, so publishers with a
+//! [`Compression`](crate::Compression) compress for those that can read it
+//! and send the rest uncompressed.
+
+use crate::compression::{self, Codec};
+use crate::error::{Error, Result};
+use crate::graph::TopicInfo;
+use crate::message::Message;
+use crate::qos::{QosProfile, Reliability};
+use crate::serialization::{deserialize_cdr, serialize_cdr, Format};
+use crate::subscriber::OverflowPolicy;
+use crate::tcp::{TcpConfig, TcpTransport};
+#[cfg(feature = "transport-zenoh")]
+use crate::{middleware::ZenohConfig, zenoh_transport::ZenohTransport};
+use crate::time::{ClockType, Time};
+use crate::topic::{PublisherGuid, Sample, SampleQueue, Stamp, Topic, TopicBus, TopicType, Writer};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+use tracing::{debug, warn};
+
+/// Default multicast group for discovery
+pub const DEFAULT_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 0, 1);
+
+/// Default discovery port; multicast data uses the port after it
+pub const DEFAULT_DISCOVERY_PORT: u16 = 7400;
+
+/// Node name shown in the graph for the transport's own endpoints
+const TRANSPORT_NODE: &str = "_udp_transport";
+
+/// Samples queued per topic while the network falls behind
+const OUTGOING_QUEUE_DEPTH: usize = 1024;
+
+/// Incomplete messages dropped after this long
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+const MAGIC: [u8; 4] = *b"R3UP";
+const ANNOUNCE: u8 = 1;
+const DATA: u8 = 2;
+/// Magic, packet kind and header length
+const PREAMBLE_LEN: usize = 7;
+
+/// How samples reach remote subscribers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Delivery {
+    /// One datagram per subscribing process
+    #[default]
+    Unicast,
+    /// One datagram on the multicast group, whoever subscribes
+    Multicast,
+}
+
+/// Settings of a [`UdpTransport`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UdpConfig {
+    /// Multicast group for discovery, and for data with
+    /// [`Delivery::Multicast`]
+    pub group: Ipv4Addr,
+    pub discovery_port: u16,
+    /// Local interface to join the group on
+    pub interface: Ipv4Addr,
+    pub delivery: Delivery,
+    /// Largest datagram sent; larger messages are fragmented
+    pub mtu: usize,
+    /// How often endpoints are announced; remote processes are forgotten
+    /// after three periods of silence
+    pub announce_period: Duration,
+}
+
+impl Default for UdpConfig {
+    fn default() -> Self {
+        Self {
+            group: DEFAULT_GROUP,
+            discovery_port: DEFAULT_DISCOVERY_PORT,
+            interface: Ipv4Addr::UNSPECIFIED,
+            delivery: Delivery::Unicast,
+            mtu: 1400,
+            announce_period: Duration::from_secs(1),
+        }
+    }
+}
+
+/// How a node's publishers and subscribers reach other processes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TransportConfig {
+    /// Only within this process
+    #[default]
+    InProcess,
+    /// Also to other processes over UDP
+    Udp(UdpConfig),
+    /// Also to other processes over TCP connections
+    Tcp(TcpConfig),
+    /// Also to other participants of a zenoh network
+    #[cfg(feature = "transport-zenoh")]
+    Zenoh(ZenohConfig),
+}
+
+/// A running transport shared by a node's endpoints
+#[derive(Clone)]
+pub(crate) enum RemoteTransport {
+    Udp(Arc<UdpTransport>),
+    Tcp(Arc<TcpTransport>),
+    #[cfg(feature = "transport-zenoh")]
+    Zenoh(Arc<ZenohTransport>),
+}
+
+impl RemoteTransport {
+    /// The transport for `config`, or `None` for in-process only
+    pub(crate) fn start(config: &TransportConfig) -> Result<Option<Self>> {
+        Ok(match config {
+            TransportConfig::InProcess => None,
+            TransportConfig::Udp(config) => Some(Self::Udp(UdpTransport::shared(config)?)),
+            TransportConfig::Tcp(config) => Some(Self::Tcp(TcpTransport::shared(config)?)),
+            #[cfg(feature = "transport-zenoh")]
+            TransportConfig::Zenoh(config) => Some(Self::Zenoh(ZenohTransport::shared(config)?)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum EndpointKind {
+    Publisher,
+    Subscriber,
+}
+
+/// An endpoint as announced to other processes
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct AnnouncedEndpoint {
+    kind: EndpointKind,
+    topic: String,
+    type_name: String,
+    type_hash: u64,
+    /// Codecs a subscriber decompresses
+    accepts: Vec<Codec>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Announce {
+    prefix: u64,
+    data_port: u16,
+    endpoints: Vec<AnnouncedEndpoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DataHeader {
+    topic: String,
+    type_name: String,
+    type_hash: u64,
+    format: Format,
+    publisher: PublisherGuid,
+    seq: u64,
+    stamp_ns: i64,
+    clock: ClockType,
+    fragment: u16,
+    fragments: u16,
+    /// Codec of the reassembled payload
+    compression: Codec,
+    uncompressed_len: u32,
+}
+
+fn encode(kind: u8, header: &impl Serialize, chunk: &[u8]) -> Result<Vec<u8>> {
+    let header = serialize_cdr(header)?;
+    let header_len = u16::try_from(header.len())
+        .map_err(|_| Error::Serialization("packet header too large".into()))?;
+    let mut packet = Vec::with_capacity(PREAMBLE_LEN + header.len() + chunk.len());
+    packet.extend_from_slice(&MAGIC);
+    packet.push(kind);
+    packet.extend_from_slice(&header_len.to_le_bytes());
+    packet.extend_from_slice(&header);
+    packet.extend_from_slice(chunk);
+    Ok(packet)
+}
+
+/// Split a packet into its kind, header and payload chunk
+fn decode(packet: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    if packet.len() < PREAMBLE_LEN || packet[..4] != MAGIC {
+        return None;
+    }
+    let header_len = u16::from_le_bytes([packet[5], packet[6]]) as usize;
+    let body = &packet[PREAMBLE_LEN..];
+    (body.len() >= header_len).then(|| (packet[4], &body[..header_len], &body[header_len..]))
+}
+
+/// Where to send a topic's samples, with the codecs accepted there
+type Target = (SocketAddr, Vec<Codec>);
+
+/// Fragments of a message received so far
+struct Partial {
+    fragments: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    started: Instant,
+}
+
+/// A process discovered on the network
+struct Participant {
+    data: SocketAddr,
+    endpoints: Vec<AnnouncedEndpoint>,
+    last_seen: Instant,
+}
+
+/// Forwards a topic's local samples to remote subscribers
+struct Outgoing {
+    link: Arc<Topic>,
+    queue: Arc<SampleQueue>,
+    task: AbortHandle,
+}
+
+impl Outgoing {
+    fn close(&self) {
+        self.task.abort();
+        TopicBus::global().detach_subscriber(&self.link, &self.queue);
+    }
+}
+
+/// Injects a topic's remote samples into the local bus
+struct Incoming {
+    link: Arc<Topic>,
+    writer: Arc<Writer>,
+}
+
+struct TransportState {
+    config: UdpConfig,
+    prefix: u64,
+    data_port: u16,
+    socket: Arc<UdpSocket>,
+    /// Endpoints advertised by local nodes, with their type hash
+    local: Mutex<HashMap<(EndpointKind, String), AnnouncedEndpoint>>,
+    remote: RwLock<HashMap<u64, Participant>>,
+    outgoing: Mutex<HashMap<String, Outgoing>>,
+    incoming: Mutex<HashMap<String, Incoming>>,
+    reassembly: Mutex<HashMap<(PublisherGuid, u64), Partial>>,
+    changed: Notify,
+}
+
+/// Process-wide UDP bridge for the topic bus
+///
+/// Nodes configured with [`TransportConfig::Udp`] share one transport per
+/// [`UdpConfig`]. The transport stops when the last node using it is
+/// dropped.
+pub struct UdpTransport {
+    state: Arc<TransportState>,
+    tasks: Vec<AbortHandle>,
+}
+
+impl UdpTransport {
+    /// The transport for `config`, starting it if no node uses it yet
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn shared(config: &UdpConfig) -> Result<Arc<Self>> {
+        static TRANSPORTS: OnceLock<Mutex<HashMap<UdpConfig, Weak<UdpTransport>>>> =
+            OnceLock::new();
+        let mut transports = TRANSPORTS.get_or_init(Default::default).lock();
+        if let Some(transport) = transports.get(config).and_then(Weak::upgrade) {
+            return Ok(transport);
+        }
+        let transport = Arc::new(Self::start(config.clone())?);
+        transports.insert(config.clone(), Arc::downgrade(&transport));
+        Ok(transport)
+    }
+
+    fn start(config: UdpConfig) -> Result<Self> {
+        if config.mtu <= PREAMBLE_LEN + 128 {
+            return Err(Error::Configuration(format!("UDP MTU {} is too small", config.mtu)));
+        }
+        let discovery = Arc::new(multicast_socket(&config, config.discovery_port)?);
+        let socket = Arc::new(match config.delivery {
+            Delivery::Unicast => {
+                let socket = std::net::UdpSocket::bind((config.interface, 0))?;
+                socket.set_nonblocking(true)?;
+                UdpSocket::from_std(socket)?
+            }
+            Delivery::Multicast => multicast_socket(&config, config.discovery_port + 1)?,
+        });
+        let data_port = socket.local_addr()?.port();
+        let prefix = PublisherGuid::local_prefix();
+        debug!(
+            "Starting UDP transport {:016x} on {}:{} (data port {})",
+            prefix, config.group, config.discovery_port, data_port
+        );
+
+        let state = Arc::new(TransportState {
+            config,
+            prefix,
+            data_port,
+            socket: socket.clone(),
+            local: Mutex::new(HashMap::new()),
+            remote: RwLock::new(HashMap::new()),
+            outgoing: Mutex::new(HashMap::new()),
+            incoming: Mutex::new(HashMap::new()),
+            reassembly: Mutex::new(HashMap::new()),
+            changed: Notify::new(),
+        });
+
+        let tasks = vec![
+            tokio::spawn(state.clone().announce(discovery.clone())).abort_handle(),
+            tokio::spawn(state.clone().receive(discovery)).abort_handle(),
+            tokio::spawn(state.clone().receive(socket)).abort_handle(),
+        ];
+        Ok(Self { state, tasks })
+    }
+
+    /// Make a local publisher's samples available to other processes
+    pub fn advertise_publisher<T: Message>(&self, topic: &str) -> Result<()> {
+        let state = &self.state;
+        let mut outgoing = state.outgoing.lock();
+        if !outgoing.contains_key(topic) {
+            let queue = Arc::new(
+                SampleQueue::new(Some(OUTGOING_QUEUE_DEPTH), OverflowPolicy::DropOldest)
+                    .with_qos(QosProfile {
+                        reliability: Reliability::BestEffort,
+                        history_depth: OUTGOING_QUEUE_DEPTH,
+                        ..Default::default()
+                    })
+                    .with_node(Some(TRANSPORT_NODE.to_string()))
+                    .forwarding(),
+            );
+            let link =
+                TopicBus::global().attach_subscriber(topic, TopicType::of::<T>(), queue.clone())?;
+            let task = tokio::spawn(state.clone().forward(topic.to_string(), queue.clone()));
+            outgoing.insert(
+                topic.to_string(),
+                Outgoing {
+                    link,
+                    queue,
+                    task: task.abort_handle(),
+                },
+            );
+        }
+        drop(outgoing);
+        state.advertise::<T>(EndpointKind::Publisher, topic);
+        Ok(())
+    }
+
+    /// Receive samples published on `topic` by other processes
+    pub fn advertise_subscriber<T: Message>(&self, topic: &str) -> Result<()> {
+        self.state.advertise::<T>(EndpointKind::Subscriber, topic);
+        Ok(())
+    }
+
+    /// Number of other processes currently announcing themselves
+    pub fn participant_count(&self) -> usize {
+        self.state.remote.read().len()
+    }
+
+    /// Port remote publishers send data to
+    pub fn data_port(&self) -> u16 {
+        self.state.data_port
+    }
+
+    /// Topics announced by other processes, sorted by name
+    ///
+    /// Endpoint counts are processes, since a process announces each topic
+    /// once however many of its nodes publish or subscribe to it.
+    pub fn remote_topics(&self) -> Vec<TopicInfo> {
+        let mut topics: BTreeMap<&str, TopicInfo> = BTreeMap::new();
+        let remote = self.state.remote.read();
+        for endpoint in remote.values().flat_map(|participant| &participant.endpoints) {
+            let info = topics.entry(&endpoint.topic).or_insert_with(|| TopicInfo {
+                name: endpoint.topic.clone(),
+                type_name: None,
+                publisher_count: 0,
+                subscriber_count: 0,
+            });
+            if endpoint.type_name != crate::RawMessage::type_name() {
+                info.type_name.get_or_insert_with(|| endpoint.type_name.clone());
+            }
+            match endpoint.kind {
+                EndpointKind::Publisher => info.publisher_count += 1,
+                EndpointKind::Subscriber => info.subscriber_count += 1,
+            }
+        }
+        topics.into_values().collect()
+    }
+}
+
+impl Drop for UdpTransport {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        for (_, outgoing) in self.state.outgoing.lock().drain() {
+            outgoing.close();
+        }
+        for (_, incoming) in self.state.incoming.lock().drain() {
+            TopicBus::global().detach_publisher(&incoming.link, &incoming.writer);
+        }
+    }
+}
+
+/// A socket bound to `port` on every interface and joined to the group
+fn multicast_socket(config: &UdpConfig, port: u16) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    // Several processes on one host share the port
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    let bind = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port));
+    socket.bind(&bind.into())?;
+    socket.join_multicast_v4(&config.group, &config.interface)?;
+    socket.set_multicast_loop_v4(true)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+impl TransportState {
+    fn advertise<T: Message>(&self, kind: EndpointKind, topic: &str) {
+        let endpoint = AnnouncedEndpoint {
+            kind,
+            topic: topic.to_string(),
+            type_name: T::type_name().to_string(),
+            type_hash: T::type_hash(),
+            accepts: match kind {
+                EndpointKind::Publisher => Vec::new(),
+                EndpointKind::Subscriber => Codec::supported(),
+            },
+        };
+        let added = self
+            .local
+            .lock()
+            .insert((kind, topic.to_string()), endpoint)
+            .is_none();
+        if added {
+            self.changed.notify_one();
+        }
+    }
+
+    /// Drop advertisements of topics whose local endpoints are all gone
+    fn prune_local(&self) {
+        let bus = TopicBus::global();
+        let mut local = self.local.lock();
+        let mut outgoing = self.outgoing.lock();
+        let incoming = self.incoming.lock();
+        local.retain(|(kind, topic), _| {
+            let alive = match kind {
+                EndpointKind::Publisher => {
+                    let own = usize::from(incoming.contains_key(topic));
+                    bus.publisher_count(topic) > own
+                }
+                EndpointKind::Subscriber => {
+                    let own = usize::from(outgoing.contains_key(topic));
+                    bus.subscriber_count(topic) > own
+                }
+            };
+            if !alive && *kind == EndpointKind::Publisher {
+                if let Some(bridge) = outgoing.remove(topic) {
+                    bridge.close();
+                }
+            }
+            alive
+        });
+    }
+
+    /// Announce local endpoints periodically and whenever they change
+    async fn announce(self: Arc<Self>, discovery: Arc<UdpSocket>) {
+        let group = SocketAddrV4::new(self.config.group, self.config.discovery_port);
+        loop {
+            self.prune_local();
+            self.prune_remote();
+            let announce = Announce {
+                prefix: self.prefix,
+                data_port: self.data_port,
+                endpoints: self.local.lock().values().cloned().collect(),
+            };
+            match encode(ANNOUNCE, &announce, &[]) {
+                Ok(packet) => {
+                    if let Err(e) = discovery.send_to(&packet, group).await {
+                        warn!("UDP announce failed: {}", e);
+                    }
+                }
+                Err(e) => warn!("UDP announce not encoded: {}", e),
+            }
+            let _ = tokio::time::timeout(self.config.announce_period, self.changed.notified())
+                .await;
+        }
+    }
+
+    fn prune_remote(&self) {
+        let expiry = self.config.announce_period * 3;
+        self.remote
+            .write()
+            .retain(|_, participant| participant.last_seen.elapsed() < expiry);
+    }
+
+    async fn receive(self: Arc<Self>, socket: Arc<UdpSocket>) {
+        let mut buffer = vec![0u8; 65536];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e) => {
+                    warn!("UDP receive failed: {}", e);
+                    continue;
+                }
+            };
+            let Some((kind, header, chunk)) = decode(&buffer[..len]) else {
+                continue;
+            };
+            match kind {
+                ANNOUNCE => {
+                    if let Ok(announce) = deserialize_cdr::<Announce>(header) {
+                        for (topic, target) in self.on_announce(announce, from) {
+                            self.replay_latched(&topic, &target).await;
+                        }
+                    }
+                }
+                DATA => {
+                    if let Ok(header) = deserialize_cdr::<DataHeader>(header) {
+                        self.on_data(header, chunk).await;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Record a participant's endpoints, returning the topics it newly
+    /// subscribes to that local publishers forward, with where to send them
+    /// and the codecs it accepts
+    fn on_announce(&self, announce: Announce, from: SocketAddr) -> Vec<(String, Target)> {
+        if announce.prefix == self.prefix {
+            return Vec::new();
+        }
+        let data = SocketAddr::new(from.ip(), announce.data_port);
+        let mut remote = self.remote.write();
+        let known = match remote.get(&announce.prefix) {
+            Some(participant) => participant.endpoints.as_slice(),
+            None => {
+                debug!("Discovered UDP participant {:016x} at {}", announce.prefix, data);
+                &[]
+            }
+        };
+        let outgoing = self.outgoing.lock();
+        let joined = announce
+            .endpoints
+            .iter()
+            .filter(|e| e.kind == EndpointKind::Subscriber && outgoing.contains_key(&e.topic))
+            .filter(|e| !known.contains(e))
+            .map(|e| (e.topic.clone(), (data, e.accepts.clone())))
+            .collect();
+        drop(outgoing);
+        remote.insert(
+            announce.prefix,
+            Participant {
+                data,
+                endpoints: announce.endpoints,
+                last_seen: Instant::now(),
+            },
+        );
+        joined
+    }
+
+    /// Send the samples latched on `topic` to a newly discovered subscriber
+    async fn replay_latched(&self, topic: &str, target: &Target) {
+        let link = match self.outgoing.lock().get(topic) {
+            Some(bridge) => bridge.link.clone(),
+            None => return,
+        };
+        for sample in link.latched() {
+            let Some(stamp) = sample.stamp() else {
+                continue;
+            };
+            if let Err(e) = self.send(topic, &sample, stamp, std::slice::from_ref(target)).await {
+                warn!("Sending latched '{}' over UDP failed: {}", topic, e);
+            }
+        }
+    }
+
+    async fn on_data(&self, header: DataHeader, chunk: &[u8]) {
+        if header.publisher.prefix == self.prefix {
+            return;
+        }
+        let local_type = {
+            let local = self.local.lock();
+            match local.get(&(EndpointKind::Subscriber, header.topic.clone())) {
+                Some(endpoint) => endpoint.type_hash,
+                None => return,
+            }
+        };
+        if local_type != header.type_hash && local_type != crate::RawMessage::type_hash() {
+            warn!(
+                "Dropping '{}' from {}: type {} does not match local subscribers",
+                header.topic, header.publisher, header.type_name
+            );
+            return;
+        }
+        let Some(payload) = self.reassemble(&header, chunk) else {
+            return;
+        };
+        let len = header.uncompressed_len as usize;
+        let payload = match compression::expand(header.compression, payload, len) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Dropping '{}' from {}: {}", header.topic, header.publisher, e);
+                return;
+            }
+        };
+        let Some((link, writer)) = self.incoming(&header.topic) else {
+            return;
+        };
+
+        let sample = Sample::new(payload, header.format, Arc::from(header.type_name.as_str()))
+            .with_stamp(Stamp {
+                publisher: header.publisher,
+                seq: header.seq,
+                time: Time::from_nanos(header.stamp_ns),
+                clock: header.clock,
+            });
+        if let Err(e) = link.deliver(&writer, sample).await {
+            warn!("Delivering remote sample on '{}' failed: {}", header.topic, e);
+        }
+    }
+
+    /// The complete payload once every fragment has arrived
+    fn reassemble(&self, header: &DataHeader, chunk: &[u8]) -> Option<Vec<u8>> {
+        let count = header.fragments as usize;
+        if count <= 1 {
+            return Some(chunk.to_vec());
+        }
+        let index = header.fragment as usize;
+        if index >= count {
+            return None;
+        }
+        let mut partials = self.reassembly.lock();
+        partials.retain(|_, partial| partial.started.elapsed() < REASSEMBLY_TIMEOUT);
+        let key = (header.publisher, header.seq);
+        let partial = partials.entry(key).or_insert_with(|| Partial {
+            fragments: vec![None; count],
+            missing: count,
+            started: Instant::now(),
+        });
+        if partial.fragments.len() != count {
+            return None;
+        }
+        if partial.fragments[index].is_none() {
+            partial.fragments[index] = Some(chunk.to_vec());
+            partial.missing -= 1;
+        }
+        if partial.missing > 0 {
+            return None;
+        }
+        let partial = partials.remove(&key)?;
+        Some(partial.fragments.into_iter().flatten().flatten().collect())
+    }
+
+    /// The writer injecting remote samples into `topic`, created on first use
+    fn incoming(&self, topic: &str) -> Option<(Arc<Topic>, Arc<Writer>)> {
+        let mut incoming = self.incoming.lock();
+        if let Some(bridge) = incoming.get(topic) {
+            return Some((bridge.link.clone(), bridge.writer.clone()));
+        }
+        let writer = Arc::new(Writer::new(
+            QosProfile::default(),
+            Some(TRANSPORT_NODE.to_string()),
+        ));
+        match TopicBus::global().attach_publisher(topic, None, writer.clone()) {
+            Ok(link) => {
+                incoming.insert(
+                    topic.to_string(),
+                    Incoming {
+                        link: link.clone(),
+                        writer: writer.clone(),
+                    },
+                );
+                Some((link, writer))
+            }
+            Err(e) => {
+                warn!("Cannot receive '{}' over UDP: {}", topic, e);
+                None
+            }
+        }
+    }
+
+    /// Send a topic's local samples to the processes subscribing to it
+    async fn forward(self: Arc<Self>, topic: String, queue: Arc<SampleQueue>) {
+        while let Some(sample) = queue.pop().await {
+            let Some(stamp) = sample.stamp() else {
+                continue;
+            };
+            // Samples that came in over the network are not sent back out
+            if stamp.publisher.prefix != self.prefix {
+                continue;
+            }
+            let targets = self.targets(&topic);
+            if targets.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.send(&topic, &sample, stamp, &targets).await {
+                warn!("Sending '{}' over UDP failed: {}", topic, e);
+            }
+        }
+    }
+
+    /// Where samples of `topic` go, with the codecs accepted there
+    fn targets(&self, topic: &str) -> Vec<Target> {
+        let remote = self.remote.read();
+        let subscribers = remote.values().filter_map(|participant| {
+            participant
+                .endpoints
+                .iter()
+                .find(|e| e.kind == EndpointKind::Subscriber && e.topic == topic)
+                .map(|e| (participant.data, e.accepts.clone()))
+        });
+        match self.config.delivery {
+            Delivery::Unicast => subscribers.collect(),
+            // Every subscriber reads the same datagrams, so only codecs all
+            // of them accept are used
+            Delivery::Multicast => subscribers
+                .map(|(_, accepts)| accepts)
+                .reduce(|mut common, accepts| {
+                    common.retain(|codec| accepts.contains(codec));
+                    common
+                })
+                .map(|accepts| {
+                    let port = self.config.discovery_port + 1;
+                    let group = SocketAddr::V4(SocketAddrV4::new(self.config.group, port));
+                    vec![(group, accepts)]
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    async fn send(
+        &self,
+        topic: &str,
+        sample: &Sample,
+        stamp: Stamp,
+        targets: &[Target],
+    ) -> Result<()> {
+        let payload = sample.bytes()?;
+        let policy = sample.compression();
+        // Targets getting the payload with the same codec share its packets
+        let mut groups: Vec<(Codec, &[Codec], Vec<SocketAddr>)> = Vec::new();
+        for (target, accepts) in targets {
+            let codec = policy.codec(payload.len(), accepts);
+            match groups.iter_mut().find(|(group, ..)| *group == codec) {
+                Some((.., addrs)) => addrs.push(*target),
+                None => groups.push((codec, accepts, vec![*target])),
+            }
+        }
+        for (_, accepts, addrs) in groups {
+            let (codec, body) = policy.apply(&payload, accepts)?;
+            for packet in self.packets(topic, sample, stamp, codec, &body, payload.len())? {
+                for target in &addrs {
+                    self.socket.send_to(&packet, target).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The packets carrying `payload`, compressed from `len` bytes with
+    /// `compression`
+    fn packets(
+        &self,
+        topic: &str,
+        sample: &Sample,
+        stamp: Stamp,
+        compression: Codec,
+        payload: &[u8],
+        len: usize,
+    ) -> Result<Vec<Vec<u8>>> {
+        let local = self.local.lock().get(&(EndpointKind::Publisher, topic.to_string())).cloned();
+        let mut type_hash = local.map(|endpoint| endpoint.type_hash).unwrap_or_default();
+        if type_hash == crate::RawMessage::type_hash() {
+            // Raw publishers send whatever type each sample names, so remote
+            // subscribers of that type accept it if the type is registered
+            if let Some(ty) = crate::types::lookup(sample.type_name()) {
+                type_hash = ty.hash();
+            }
+        }
+        let mut header = DataHeader {
+            topic: topic.to_string(),
+            type_name: sample.type_name().to_string(),
+            type_hash,
+            format: sample.format(),
+            publisher: stamp.publisher,
+            seq: stamp.seq,
+            stamp_ns: stamp.time.as_nanos(),
+            clock: stamp.clock,
+            fragment: 0,
+            fragments: 1,
+            compression,
+            uncompressed_len: u32::try_from(len).map_err(|_| {
+                Error::Serialization(format!("message on '{}' is too large", topic))
+            })?,
+        };
+
+        // CDR encodes the fragment fields with a fixed width, so every
+        // fragment's header has the same length
+        let overhead = encode(DATA, &header, &[])?.len();
+        let chunk_len = self.config.mtu.saturating_sub(overhead).max(1);
+        let chunks: Vec<&[u8]> = if payload.is_empty() {
+            vec![&[]]
+        } else {
+            payload.chunks(chunk_len).collect()
+        };
+        header.fragments = u16::try_from(chunks.len()).map_err(|_| {
+            Error::Serialization(format!("message on '{}' needs too many fragments", topic))
+        })?;
+
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                header.fragment = index as u16;
+                encode(DATA, &header, chunk)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+
+    fn header(fragment: u16, fragments: u16) -> DataHeader {
+        DataHeader {
+            topic: "/robot/state".into(),
+            type_name: RobotState::type_name().into(),
+            type_hash: RobotState::type_hash(),
+            format: Format::Cdr,
+            publisher: PublisherGuid { prefix: 7, id: 1 },
+            seq: 3,
+            stamp_ns: 42,
+            clock: ClockType::SystemTime,
+            fragment,
+            fragments,
+            compression: Codec::None,
+            uncompressed_len: 0,
+        }
+    }
+
+    #[test]
+    fn test_packet_roundtrip() {
+        let packet = encode(DATA, &header(0, 1), b"payload").unwrap();
+        let (kind, encoded, chunk) = decode(&packet).unwrap();
+        assert_eq!(kind, DATA);
+        assert_eq!(chunk, b"payload");
+        let decoded: DataHeader = deserialize_cdr(encoded).unwrap();
+        assert_eq!(decoded.seq, 3);
+        assert_eq!(decoded.publisher, PublisherGuid { prefix: 7, id: 1 });
+
+        assert!(decode(b"R3U").is_none());
+        assert!(decode(b"XXXX\x02\x00\x00").is_none());
+    }
+
+    #[test]
+    fn test_fixed_header_length() {
+        let first = encode(DATA, &header(0, 2), &[]).unwrap();
+        let last = encode(DATA, &header(u16::MAX, u16::MAX), &[]).unwrap();
+        assert_eq!(first.len(), last.len());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[tokio::test]
+    async fn test_compresses_only_for_subscribers_that_accept_it() {
+        use crate::message::{Point3D, PointCloud};
+        use crate::{Compression, Node, PublisherOptions};
+
+        const TOPIC: &str = "/test/udp/compressed";
+        let config = UdpConfig {
+            discovery_port: 17_410,
+            announce_period: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let node = Node::new("udp_compression")
+            .unwrap()
+            .with_transport(TransportConfig::Udp(config.clone()))
+            .unwrap();
+        let options = PublisherOptions::new()
+            .compression(Compression::Lz4)
+            .compression_threshold(256);
+        let publisher = node.create_publisher_with_options::<PointCloud>(TOPIC, options).unwrap();
+
+        // Two remote subscribers, only one of them built with lz4
+        let transport = UdpTransport::shared(&config).unwrap();
+        let mut subscribers = Vec::new();
+        for (prefix, accepts) in [(1, vec![Codec::Lz4]), (2, Vec::new())] {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let announce = Announce {
+                prefix,
+                data_port: socket.local_addr().unwrap().port(),
+                endpoints: vec![AnnouncedEndpoint {
+                    kind: EndpointKind::Subscriber,
+                    topic: TOPIC.into(),
+                    type_name: PointCloud::type_name().into(),
+                    type_hash: PointCloud::type_hash(),
+                    accepts,
+                }],
+            };
+            transport.state.on_announce(announce, socket.local_addr().unwrap());
+            subscribers.push(socket);
+        }
+
+        let point = Point3D {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        let small = PointCloud {
+            points: vec![point; 2],
+            intensities: vec![0.5; 2],
+            timestamp: 1,
+        };
+        let large = PointCloud {
+            points: vec![point; 50],
+            intensities: vec![0.5; 50],
+            timestamp: 2,
+        };
+        publisher.publish(&small).await.unwrap();
+        publisher.publish(&large).await.unwrap();
+
+        let mut buffer = vec![0u8; 65536];
+        for (socket, codec) in subscribers.iter().zip([Codec::Lz4, Codec::None]) {
+            for (cloud, expected) in [(&small, Codec::None), (&large, codec)] {
+                let len = socket.recv(&mut buffer).await.unwrap();
+                let (_, header, chunk) = decode(&buffer[..len]).unwrap();
+                let header: DataHeader = deserialize_cdr(header).unwrap();
+                assert_eq!(header.compression, expected);
+                let len = header.uncompressed_len as usize;
+                let payload = compression::expand(expected, chunk.to_vec(), len).unwrap();
+                let received: PointCloud = deserialize_cdr(&payload).unwrap();
+                assert_eq!(received.timestamp, cloud.timestamp);
+                assert_eq!(received.points.len(), cloud.points.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,390 @@
+//! Registry of message types by name
+//!
+//! Tools that handle messages as JSON, such as the MCP tools, look types up
+//! here to describe them with their [schema](Message::schema) and to convert
+//! between JSON and the serialized form carried by
+//! [`RawMessage`](crate::RawMessage). Types with a
+//! [descriptor](Message::descriptor) can also be handled field by field as a
+//! [`DynamicMessage`](crate::dynamic::DynamicMessage).
+//!
+//! The built-in messages are always registered, and every other type is
+//! registered when the first publisher or subscriber for it is created.
+//! Call [`register`] for types a process only handles as raw messages, and
+//! [`register_descriptor`] for types only known at runtime.
+
+use crate::dynamic::{DynamicMessage, TypeDescriptor};
+use crate::error::{Error, Result};
+use crate::message::{Message, PointCloud, Pose, RawMessage, RobotState};
+use crate::schema::{self, SchemaError};
+use crate::serialization::{Format, Serializer};
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+/// A registered message type, see the [module documentation](self)
+#[derive(Clone, Copy)]
+pub struct MessageType {
+    name: &'static str,
+    hash: u64,
+    codec: Codec,
+}
+
+#[derive(Clone, Copy)]
+enum Codec {
+    /// A Rust type, see [`MessageType::of`]
+    Typed {
+        schema: fn() -> Value,
+        descriptor: fn() -> Option<TypeDescriptor>,
+        from_json: fn(Value, Format) -> Result<Vec<u8>>,
+        to_json: fn(&RawMessage) -> Result<Value>,
+    },
+    /// A type only known by its descriptor, see [`register_descriptor`];
+    /// registered types live as long as the process, like their names
+    Described(&'static Arc<TypeDescriptor>),
+}
+
+impl MessageType {
+    /// The entry for `T`, without registering it
+    pub fn of<T: Message>() -> Self {
+        Self {
+            name: T::type_name(),
+            hash: T::type_hash(),
+            codec: Codec::Typed {
+                schema: T::schema,
+                descriptor: T::descriptor,
+                from_json: |value, format| {
+                    let msg: T = serde_json::from_value(value)
+                        .map_err(|e| Error::Serialization(e.to_string()))?;
+                    Serializer::new(format).serialize(&msg)
+                },
+                to_json: |raw| {
+                    serde_json::to_value(raw.decode::<T>()?)
+                        .map_err(|e| Error::Serialization(e.to_string()))
+                },
+            },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// See [`Message::type_hash`]
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// JSON Schema of the message's JSON form, see [`Message::schema`]
+    pub fn schema(&self) -> Value {
+        match self.codec {
+            Codec::Typed { schema, .. } => schema(),
+            Codec::Described(descriptor) => descriptor.schema(),
+        }
+    }
+
+    /// The type's fields, see [`Message::descriptor`]
+    pub fn descriptor(&self) -> Option<Arc<TypeDescriptor>> {
+        match self.codec {
+            Codec::Typed { descriptor, .. } => descriptor().map(Arc::new),
+            Codec::Described(descriptor) => Some(descriptor.clone()),
+        }
+    }
+
+    /// Check `value` against the type's [schema](Self::schema)
+    pub fn validate(&self, value: &Value) -> std::result::Result<(), Vec<SchemaError>> {
+        schema::validate(&self.schema(), value)
+    }
+
+    /// Build a message of this type from JSON, serialized in `format`
+    ///
+    /// Fails with [`Error::InvalidMessage`], listing every mismatched
+    /// field, if `value` does not match the schema, and otherwise if it
+    /// does not deserialize as the type.
+    pub fn from_json(&self, value: Value, format: Format) -> Result<RawMessage> {
+        let data = match self.codec {
+            Codec::Typed { from_json, .. } => {
+                self.validate(&value).map_err(|errors| Error::InvalidMessage {
+                    type_name: self.name.to_string(),
+                    errors,
+                })?;
+                from_json(value, format)?
+            }
+            Codec::Described(descriptor) => {
+                DynamicMessage::from_json(descriptor.clone(), &value)?.encode(format)?
+            }
+        };
+        Ok(RawMessage {
+            type_name: self.name.to_string(),
+            format,
+            data,
+        })
+    }
+
+    /// Decode a message of this type to JSON
+    pub fn to_json(&self, raw: &RawMessage) -> Result<Value> {
+        if raw.type_name != self.name {
+            return Err(Error::Serialization(format!(
+                "expected a {} message, found {}",
+                self.name, raw.type_name
+            )));
+        }
+        match self.codec {
+            Codec::Typed { to_json, .. } => to_json(raw),
+            Codec::Described(descriptor) => {
+                Ok(DynamicMessage::decode(descriptor.clone(), &raw.data, raw.format)?.to_json())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageType")
+            .field("name", &self.name)
+            .field("hash", &format_args!("{:016x}", self.hash))
+            .finish()
+    }
+}
+
+fn types() -> &'static RwLock<HashMap<&'static str, MessageType>> {
+    static TYPES: OnceLock<RwLock<HashMap<&'static str, MessageType>>> = OnceLock::new();
+    TYPES.get_or_init(|| {
+        let builtin = [
+            MessageType::of::<RobotState>(),
+            MessageType::of::<PointCloud>(),
+            MessageType::of::<Pose>(),
+            MessageType::of::<Value>(),
+        ];
+        RwLock::new(builtin.into_iter().map(|ty| (ty.name, ty)).collect())
+    })
+}
+
+/// Register `T` under its type name
+///
+/// The first type registered under a name keeps it; endpoints of a type
+/// with the same name but another layout are rejected by the topic anyway.
+pub fn register<T: Message>() {
+    if types().read().contains_key(T::type_name()) {
+        return;
+    }
+    types()
+        .write()
+        .entry(T::type_name())
+        .or_insert_with(MessageType::of::<T>);
+}
+
+/// Register a type described at runtime under its type name
+///
+/// This is how types defined outside Rust, e.g. by message definitions,
+/// become usable by name. Registering a
+/// name again returns the registered type if it has the same fields, so
+/// definitions of a derived type can be loaded next to it, and fails
+/// otherwise.
+pub fn register_descriptor(descriptor: TypeDescriptor) -> Result<MessageType> {
+    let mut types = types().write();
+    if let Some(ty) = types.get(descriptor.name()) {
+        let registered = ty.descriptor();
+        if registered.is_some_and(|registered| registered.fields() == descriptor.fields()) {
+            return Ok(*ty);
+        }
+        return Err(Error::Configuration(format!(
+            "{} is already registered with other fields",
+            ty.name
+        )));
+    }
+    let descriptor: &'static Arc<TypeDescriptor> = Box::leak(Box::new(Arc::new(descriptor)));
+    let ty = MessageType {
+        name: Box::leak(descriptor.name().into()),
+        hash: descriptor.hash(),
+        codec: Codec::Described(descriptor),
+    };
+    types.insert(ty.name, ty);
+    Ok(ty)
+}
+
+/// The type registered as `name`
+pub fn lookup(name: &str) -> Option<MessageType> {
+    types().read().get(name).copied()
+}
+
+/// Every registered type, sorted by name
+pub fn registered() -> Vec<MessageType> {
+    let mut types: Vec<MessageType> = types().read().values().copied().collect();
+    types.sort_by_key(|ty| ty.name);
+    types
+}
+
+/// Build a message of the type registered as `type_name` from JSON
+///
+/// The runtime counterpart of serializing a concrete type, for tools that
+/// only know the type by name. Messages are serialized as CDR, except
+/// generic JSON values, which have no fixed layout for CDR.
+pub fn from_json(type_name: &str, value: Value) -> Result<RawMessage> {
+    let ty = lookup(type_name).ok_or_else(|| Error::UnknownType(type_name.to_string()))?;
+    let format = if ty.name == Value::type_name() {
+        Format::Json
+    } else {
+        Format::Cdr
+    };
+    ty.from_json(value, format)
+}
+
+/// Decode a message of any type to JSON
+///
+/// Uses the registered type if there is one. JSON and MessagePack messages
+/// of unregistered types are decoded as they are; CDR carries no field
+/// names, so CDR messages need their type registered.
+pub fn to_json(raw: &RawMessage) -> Result<Value> {
+    if let Some(ty) = lookup(&raw.type_name) {
+        return ty.to_json(raw);
+    }
+    match raw.format {
+        Format::Json => {
+            serde_json::from_slice(&raw.data).map_err(|e| Error::Serialization(e.to_string()))
+        }
+        Format::MessagePack => {
+            rmp_serde::from_slice(&raw.data).map_err(|e| Error::Serialization(e.to_string()))
+        }
+        format => Err(Error::Serialization(format!(
+            "{} is not registered and {:?} is not self-describing",
+            raw.type_name, format
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Publisher;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Battery {
+        percent: f32,
+    }
+
+    impl Message for Battery {
+        fn type_name() -> &'static str {
+            "test_msgs/TypesBattery"
+        }
+    }
+
+    #[test]
+    fn test_builtin_types() {
+        let names: Vec<&str> = registered().iter().map(MessageType::name).collect();
+        assert!(names.contains(&"ros3_msgs/RobotState"));
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let state = lookup("ros3_msgs/RobotState").unwrap();
+        assert_eq!(state.hash(), RobotState::type_hash());
+        assert_eq!(state.schema()["required"][0], "position");
+        let raw = state
+            .from_json(
+                json!({ "position": [1.0, 2.0, 3.0], "velocity": [0.0, 0.0, 0.0], "timestamp": 5 }),
+                Format::Cdr,
+            )
+            .unwrap();
+        assert_eq!(
+            raw.decode::<RobotState>().unwrap().position,
+            [1.0, 2.0, 3.0]
+        );
+        assert_eq!(state.to_json(&raw).unwrap()["timestamp"], 5);
+        assert!(state
+            .from_json(json!({ "position": "here" }), Format::Cdr)
+            .is_err());
+    }
+
+    #[test]
+    fn test_endpoints_register_their_type() {
+        assert!(lookup(Battery::type_name()).is_none());
+        let _publisher = Publisher::<Battery>::new("test/types/battery").unwrap();
+        let battery = lookup(Battery::type_name()).unwrap();
+        // Types without a derived schema accept any JSON
+        assert_eq!(battery.schema(), Value::Bool(true));
+        let raw = battery
+            .from_json(json!({ "percent": 80.0 }), Format::Json)
+            .unwrap();
+        assert_eq!(raw.decode::<Battery>().unwrap(), Battery { percent: 80.0 });
+    }
+
+    #[test]
+    fn test_from_json_by_name() {
+        let raw = from_json(
+            "ros3_msgs/Pose",
+            json!({ "position": [1.0, 2.0, 3.0], "orientation": [0.0, 0.0, 0.0, 1.0] }),
+        )
+        .unwrap();
+        assert_eq!(raw.format, Format::Cdr);
+        assert_eq!(raw.decode::<Pose>().unwrap().orientation[3], 1.0);
+        let raw = from_json(Value::type_name(), json!({ "any": "thing" })).unwrap();
+        assert_eq!(raw.format, Format::Json);
+
+        let err = from_json("ros3_msgs/Pose", json!({ "position": [1.0, "2"], "speed": 1 }));
+        let Err(Error::InvalidMessage { type_name, errors }) = err else {
+            panic!("expected an invalid message, got {:?}", err);
+        };
+        assert_eq!(type_name, "ros3_msgs/Pose");
+        let mut messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            [
+                "/: missing required property 'orientation'",
+                "/position/1: expected number, found string",
+                "/position: must have at least 3 items",
+                "/speed: unknown property",
+            ]
+        );
+        assert!(matches!(
+            from_json("test_msgs/Nothing", json!({})),
+            Err(Error::UnknownType(name)) if name == "test_msgs/Nothing"
+        ));
+    }
+
+    #[test]
+    fn test_to_json_of_unregistered_types() {
+        let state = RobotState::default();
+        let raw = |format| RawMessage {
+            type_name: "test_msgs/TypesUnregistered".to_string(),
+            format,
+            data: Serializer::new(format).serialize(&state).unwrap(),
+        };
+        assert_eq!(to_json(&raw(Format::Json)).unwrap()["timestamp"], 0);
+        assert_eq!(to_json(&raw(Format::MessagePack)).unwrap()["timestamp"], 0);
+        assert!(to_json(&raw(Format::Cdr)).is_err());
+
+        let registered = RawMessage {
+            type_name: RobotState::type_name().to_string(),
+            ..raw(Format::Cdr)
+        };
+        assert_eq!(to_json(&registered).unwrap()["velocity"][2], 0.0);
+    }
+
+    #[test]
+    fn test_register_descriptor() {
+        use crate::dynamic::{FieldDescriptor, FieldType};
+
+        let descriptor = TypeDescriptor::new(
+            "test_msgs/TypesDescribed",
+            vec![FieldDescriptor::new("level", FieldType::U8)],
+        );
+        let ty = register_descriptor(descriptor.clone()).unwrap();
+        assert_eq!(lookup("test_msgs/TypesDescribed").unwrap().hash(), ty.hash());
+        assert_eq!(ty.descriptor().as_deref(), Some(&descriptor));
+        let raw = ty.from_json(json!({ "level": 3 }), Format::Cdr).unwrap();
+        assert_eq!(to_json(&raw).unwrap(), json!({ "level": 3 }));
+        assert!(ty.from_json(json!({ "level": 300 }), Format::Cdr).is_err());
+
+        assert_eq!(register_descriptor(descriptor).unwrap().hash(), ty.hash());
+        let other = TypeDescriptor::new(
+            "test_msgs/TypesDescribed",
+            vec![FieldDescriptor::new("level", FieldType::U16)],
+        );
+        assert!(register_descriptor(other).is_err());
+        let state = RobotState::descriptor().unwrap();
+        assert!(register_descriptor(state).is_ok());
+    }
+}
@@ -0,0 +1,381 @@
+//! Waiting on several entities from one task
+//!
+//! A [`WaitSet`] holds subscribers, [`WaitTimer`]s and
+//! [`PolledServiceServer`]s and tells which of them are ready, so a
+//! single-threaded control loop can serve all of them without a task per
+//! entity. Waiting does not take anything: a subscriber stays ready until its
+//! queue is drained, a timer until [`WaitTimer::take`] is called and a server
+//! until its requests are taken.
+
+use crate::error::{Error, Result};
+use crate::message::Message;
+use crate::service::PolledServiceServer;
+use crate::subscriber::Subscriber;
+use crate::topic::SampleQueue;
+use parking_lot::{Mutex, RwLock};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Wait sets to wake when an entity may have become ready
+#[derive(Default)]
+pub(crate) struct Waiters(RwLock<Vec<Weak<Notify>>>);
+
+impl Waiters {
+    pub(crate) fn attach(&self, signal: &Arc<Notify>) {
+        let mut waiters = self.0.write();
+        waiters.retain(|waiter| waiter.strong_count() > 0);
+        waiters.push(Arc::downgrade(signal));
+    }
+
+    pub(crate) fn wake(&self) {
+        for waiter in self.0.read().iter() {
+            if let Some(signal) = waiter.upgrade() {
+                signal.notify_one();
+            }
+        }
+    }
+}
+
+/// Something a [`WaitSet`] can wait on
+pub(crate) trait Waitable: Send + Sync {
+    fn is_ready(&self) -> bool;
+
+    /// Wake `signal` whenever the entity may have become ready
+    fn attach(&self, signal: &Arc<Notify>);
+
+    /// When the entity becomes ready without being woken, for timers
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+}
+
+impl Waitable for SampleQueue {
+    fn is_ready(&self) -> bool {
+        self.len() > 0
+    }
+
+    fn attach(&self, signal: &Arc<Notify>) {
+        self.waiters().attach(signal);
+    }
+}
+
+/// Identifies an entity in a [`WaitSet`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WaitKey(usize);
+
+/// Set of entities to wait on together
+///
+/// [`wait`](Self::wait) reports the ready entities in registration order,
+/// rotated so that the entity after the one reported first last time leads.
+/// A loop handling only the first ready entity per cycle thus serves every
+/// ready entity in turn and starves none.
+pub struct WaitSet {
+    entries: Vec<Option<Arc<dyn Waitable>>>,
+    signal: Arc<Notify>,
+    cursor: usize,
+}
+
+impl Default for WaitSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WaitSet {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            signal: Arc::new(Notify::new()),
+            cursor: 0,
+        }
+    }
+
+    /// Ready while the subscriber has messages queued
+    ///
+    /// Every clone of the subscriber shares its queue, so receiving from any
+    /// of them counts.
+    pub fn add_subscriber<T: Message>(&mut self, subscriber: &Subscriber<T>) -> WaitKey {
+        let (_, queue) = subscriber.endpoint();
+        self.add(queue)
+    }
+
+    /// Ready from each tick of `timer` until it is taken
+    pub fn add_timer(&mut self, timer: &WaitTimer) -> WaitKey {
+        self.add(timer.state.clone())
+    }
+
+    /// Ready while the server has requests waiting to be taken
+    pub fn add_service<Req: Message, Res: Message>(
+        &mut self,
+        server: &PolledServiceServer<Req, Res>,
+    ) -> WaitKey {
+        self.add(server.waitable())
+    }
+
+    /// Stop waiting on an entity; its key is not reused
+    pub fn remove(&mut self, key: WaitKey) {
+        if let Some(entry) = self.entries.get_mut(key.0) {
+            *entry = None;
+        }
+    }
+
+    /// Number of entities in the set
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wait until at least one entity is ready and return the ready ones
+    ///
+    /// Fails with [`Error::Configuration`] if the set is empty, as the wait
+    /// would never end.
+    pub async fn wait(&mut self) -> Result<Vec<WaitKey>> {
+        if self.is_empty() {
+            return Err(Error::Configuration("cannot wait on an empty wait set".into()));
+        }
+        loop {
+            let ready = self.try_ready();
+            if !ready.is_empty() {
+                return Ok(ready);
+            }
+            // A permit left by a wake since the last check ends this right away
+            let woken = self.signal.notified();
+            match self.entries.iter().flatten().filter_map(|entry| entry.deadline()).min() {
+                Some(deadline) => {
+                    let _ = tokio::time::timeout_at(deadline, woken).await;
+                }
+                None => woken.await,
+            }
+        }
+    }
+
+    /// Like [`wait`](Self::wait), but return no entities once `timeout`
+    /// has passed
+    pub async fn wait_timeout(&mut self, timeout: Duration) -> Result<Vec<WaitKey>> {
+        match tokio::time::timeout(timeout, self.wait()).await {
+            Ok(ready) => ready,
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// The entities ready now, without waiting
+    pub fn try_ready(&mut self) -> Vec<WaitKey> {
+        let len = self.entries.len();
+        let ready: Vec<WaitKey> = (0..len)
+            .map(|offset| (self.cursor + offset) % len)
+            .filter(|&index| self.entries[index].as_ref().is_some_and(|e| e.is_ready()))
+            .map(WaitKey)
+            .collect();
+        if let Some(first) = ready.first() {
+            self.cursor = (first.0 + 1) % len;
+        }
+        ready
+    }
+
+    fn add(&mut self, entry: Arc<dyn Waitable>) -> WaitKey {
+        entry.attach(&self.signal);
+        self.entries.push(Some(entry));
+        WaitKey(self.entries.len() - 1)
+    }
+}
+
+/// Periodic timer polled through a [`WaitSet`]
+///
+/// Unlike [`Node::create_timer`](crate::Node::create_timer), which runs a
+/// callback in its own task, the timer only becomes ready; the loop waiting
+/// on it calls [`take`](Self::take) and does the work itself. Ticks run on
+/// the steady clock. A tick taken more than a period late starts the next
+/// period from now, so missed ticks are not made up.
+#[derive(Clone)]
+pub struct WaitTimer {
+    state: Arc<TimerState>,
+}
+
+struct TimerState {
+    period: Duration,
+    next: Mutex<Instant>,
+}
+
+impl Waitable for TimerState {
+    fn is_ready(&self) -> bool {
+        Instant::now() >= *self.next.lock()
+    }
+
+    fn attach(&self, _signal: &Arc<Notify>) {}
+
+    fn deadline(&self) -> Option<Instant> {
+        Some(*self.next.lock())
+    }
+}
+
+impl WaitTimer {
+    /// Create a timer whose first tick is one `period` from now
+    ///
+    /// Fails with [`Error::Configuration`] if `period` is zero.
+    pub fn new(period: Duration) -> Result<Self> {
+        if period.is_zero() {
+            return Err(Error::Configuration("Timer period must be non-zero".into()));
+        }
+        Ok(Self {
+            state: Arc::new(TimerState {
+                period,
+                next: Mutex::new(Instant::now() + period),
+            }),
+        })
+    }
+
+    /// Whether a tick is due
+    pub fn is_ready(&self) -> bool {
+        self.state.is_ready()
+    }
+
+    /// Take the due tick, returning `false` if none is
+    pub fn take(&self) -> bool {
+        let now = Instant::now();
+        let mut next = self.state.next.lock();
+        if now < *next {
+            return false;
+        }
+        *next += self.state.period;
+        if *next <= now {
+            *next = now + self.state.period;
+        }
+        true
+    }
+
+    pub fn period(&self) -> Duration {
+        self.state.period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+    use crate::publisher::Publisher;
+    use crate::service::ServiceClient;
+
+    #[tokio::test]
+    async fn test_ready_subscribers_take_turns() {
+        let fast = Subscriber::<RobotState>::new("test/wait/fast").unwrap();
+        let slow = Subscriber::<RobotState>::new("test/wait/slow").unwrap();
+        let fast_publisher = Publisher::<RobotState>::new("test/wait/fast").unwrap();
+        let slow_publisher = Publisher::<RobotState>::new("test/wait/slow").unwrap();
+
+        let mut wait_set = WaitSet::new();
+        let fast_key = wait_set.add_subscriber(&fast);
+        let slow_key = wait_set.add_subscriber(&slow);
+        assert!(wait_set.try_ready().is_empty());
+
+        for _ in 0..10 {
+            fast_publisher.publish(&RobotState::default()).await.unwrap();
+        }
+        for _ in 0..3 {
+            slow_publisher.publish(&RobotState::default()).await.unwrap();
+        }
+
+        // One message per cycle: the slow topic is not starved by the fast one
+        let mut order = Vec::new();
+        while let Ok(ready) = wait_set.wait_timeout(Duration::from_millis(10)).await {
+            let Some(&first) = ready.first() else {
+                break;
+            };
+            order.push(first);
+            let subscriber = if first == fast_key { &fast } else { &slow };
+            subscriber.try_recv().unwrap().unwrap();
+        }
+        assert_eq!(order.len(), 13);
+        assert_eq!(
+            order[..6],
+            [fast_key, slow_key, fast_key, slow_key, fast_key, slow_key]
+        );
+        assert!(order[6..].iter().all(|&key| key == fast_key));
+    }
+
+    #[tokio::test]
+    async fn test_wait_wakes_on_publish() {
+        let subscriber = Subscriber::<RobotState>::new("test/wait/wake").unwrap();
+        let publisher = Publisher::<RobotState>::new("test/wait/wake").unwrap();
+        let mut wait_set = WaitSet::new();
+        let key = wait_set.add_subscriber(&subscriber);
+
+        let producer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            publisher.publish(&RobotState::default()).await.unwrap();
+            publisher
+        });
+        let ready = tokio::time::timeout(Duration::from_secs(1), wait_set.wait())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ready, [key]);
+        drop(producer.await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timer_stays_ready_until_taken() {
+        let period = Duration::from_millis(100);
+        let timer = WaitTimer::new(period).unwrap();
+        let mut wait_set = WaitSet::new();
+        let key = wait_set.add_timer(&timer);
+
+        let start = Instant::now();
+        assert_eq!(wait_set.wait().await.unwrap(), [key]);
+        assert_eq!(start.elapsed(), period);
+        assert_eq!(wait_set.try_ready(), [key]);
+
+        assert!(timer.take());
+        assert!(!timer.take());
+        assert!(wait_set.try_ready().is_empty());
+        assert_eq!(wait_set.wait().await.unwrap(), [key]);
+        assert_eq!(start.elapsed(), period * 2);
+    }
+
+    #[tokio::test]
+    async fn test_service_requests_are_taken_by_the_loop() {
+        let server = PolledServiceServer::<RobotState, RobotState>::new("test/wait/service")
+            .unwrap();
+        let client = ServiceClient::<RobotState, RobotState>::new("test/wait/service").unwrap();
+        let mut wait_set = WaitSet::new();
+        let key = wait_set.add_service(&server);
+
+        let call = tokio::spawn(async move {
+            client
+                .call(RobotState {
+                    timestamp: 41,
+                    ..Default::default()
+                })
+                .await
+        });
+        assert_eq!(wait_set.wait().await.unwrap(), [key]);
+        let request = server.try_take().unwrap();
+        let response = RobotState {
+            timestamp: request.request().timestamp + 1,
+            ..Default::default()
+        };
+        request.respond(Ok(response));
+
+        assert_eq!(call.await.unwrap().unwrap().timestamp, 42);
+        assert!(wait_set.try_ready().is_empty());
+        assert_eq!(server.stats(), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_empty_and_removed() {
+        let mut wait_set = WaitSet::new();
+        assert!(matches!(wait_set.wait().await, Err(Error::Configuration(_))));
+
+        let timer = WaitTimer::new(Duration::from_secs(60)).unwrap();
+        let key = wait_set.add_timer(&timer);
+        assert_eq!(wait_set.len(), 1);
+        let ready = wait_set.wait_timeout(Duration::from_millis(1)).await.unwrap();
+        assert!(ready.is_empty());
+        wait_set.remove(key);
+        assert!(wait_set.is_empty());
+    }
+}
@@ -0,0 +1,507 @@
+//! Zenoh transport
+//!
+//! Carries topics and services over an existing zenoh network instead of
+//! ros3's own discovery. Topic `/robot/state` maps to the key expression
+//! `ros3/robot/state` (see [`key_expr`]); samples travel as zenoh
+//! publications with their ros3 stamp in the attachment, and services are
+//! answered by zenoh queryables. Requires the `transport-zenoh` feature.
+
+use crate::error::{Error, Result};
+use crate::message::{is_raw, Message};
+use crate::middleware::ZenohConfig;
+use crate::qos::{QosProfile, Reliability};
+use crate::serialization::{deserialize_cdr, serialize_cdr, Format};
+use crate::service::ServiceClient;
+use crate::subscriber::OverflowPolicy;
+use crate::time::{ClockType, Time};
+use crate::topic::{PublisherGuid, Sample, SampleQueue, Stamp, Topic, TopicBus, TopicType, Writer};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+use tracing::{debug, warn};
+use zenoh::bytes::ZBytes;
+use zenoh::pubsub::{Publisher, Subscriber};
+use zenoh::query::{Query, Queryable};
+use zenoh::sample::Locality;
+use zenoh::{Session, Wait};
+
+/// Prefix of every key expression used by ros3
+pub const KEY_PREFIX: &str = "ros3";
+
+/// Node name shown in the graph for the transport's own endpoints
+const TRANSPORT_NODE: &str = "_zenoh_transport";
+
+/// Samples queued per topic while zenoh falls behind
+const OUTGOING_QUEUE_DEPTH: usize = 1024;
+
+/// The key expression a topic or service is published under
+///
+/// The leading `/` is dropped and every byte outside `[A-Za-z0-9_.-]` in
+/// a path segment is written as `%XX`, so the result is always a valid
+/// key expression without wildcards and distinct names never collide.
+/// Empty segments become `%`.
+pub fn key_expr(topic: &str) -> String {
+    let mut key = String::from(KEY_PREFIX);
+    let path = topic.strip_prefix('/').unwrap_or(topic);
+    for segment in path.split('/') {
+        key.push('/');
+        if segment.is_empty() {
+            key.push('%');
+            continue;
+        }
+        for byte in segment.bytes() {
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'.' | b'-') {
+                key.push(byte as char);
+            } else {
+                key.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    key
+}
+
+/// The ros3 part of a sample, carried as the zenoh attachment
+#[derive(Debug, Serialize, Deserialize)]
+struct SampleHeader {
+    type_name: String,
+    type_hash: u64,
+    format: Format,
+    publisher: PublisherGuid,
+    seq: u64,
+    stamp_ns: i64,
+    clock: ClockType,
+}
+
+struct Outgoing {
+    link: Arc<Topic>,
+    queue: Arc<SampleQueue>,
+    task: AbortHandle,
+}
+
+/// A topic's zenoh subscription and the writer injecting it into the bus
+struct Incoming {
+    link: Arc<Topic>,
+    writer: Arc<Writer>,
+    _subscriber: Subscriber<()>,
+    task: AbortHandle,
+}
+
+struct TransportState {
+    session: Session,
+    prefix: u64,
+    outgoing: Mutex<HashMap<String, Outgoing>>,
+    /// Type hash of local subscribers per topic, `None` if they accept any
+    subscribed: Mutex<HashMap<String, Option<u64>>>,
+    incoming: Mutex<HashMap<String, Incoming>>,
+}
+
+/// Process-wide zenoh bridge for the topic bus and services
+///
+/// Nodes configured with [`TransportConfig::Zenoh`](crate::TransportConfig)
+/// share one zenoh session per [`ZenohConfig`].
+pub struct ZenohTransport {
+    state: Arc<TransportState>,
+}
+
+impl ZenohTransport {
+    /// The transport for `config`, opening a zenoh session if no node uses
+    /// it yet
+    ///
+    /// Must be called from within a multi-threaded Tokio runtime.
+    pub fn shared(config: &ZenohConfig) -> Result<Arc<Self>> {
+        static TRANSPORTS: OnceLock<Mutex<HashMap<ZenohConfig, Weak<ZenohTransport>>>> =
+            OnceLock::new();
+        let mut transports = TRANSPORTS.get_or_init(Default::default).lock();
+        if let Some(transport) = transports.get(config).and_then(Weak::upgrade) {
+            return Ok(transport);
+        }
+        let session = zenoh::open(session_config(config)?)
+            .wait()
+            .map_err(|e| Error::Zenoh(format!("opening session: {}", e)))?;
+        debug!("Zenoh transport opened in {} mode", config.mode);
+        let transport = Arc::new(Self {
+            state: Arc::new(TransportState {
+                session,
+                prefix: PublisherGuid::local_prefix(),
+                outgoing: Mutex::new(HashMap::new()),
+                subscribed: Mutex::new(HashMap::new()),
+                incoming: Mutex::new(HashMap::new()),
+            }),
+        });
+        transports.insert(config.clone(), Arc::downgrade(&transport));
+        Ok(transport)
+    }
+
+    /// Publish the local samples of `topic` on its key expression
+    pub fn advertise_publisher<T: Message>(&self, topic: &str) -> Result<()> {
+        let state = &self.state;
+        let mut outgoing = state.outgoing.lock();
+        if outgoing.contains_key(topic) {
+            return Ok(());
+        }
+        let publisher = state
+            .session
+            .declare_publisher(key_expr(topic))
+            .allowed_destination(Locality::Remote)
+            .wait()
+            .map_err(|e| Error::Zenoh(format!("declaring publisher on '{}': {}", topic, e)))?;
+        let queue = Arc::new(
+            SampleQueue::new(Some(OUTGOING_QUEUE_DEPTH), OverflowPolicy::DropOldest)
+                .with_qos(QosProfile {
+                    reliability: Reliability::BestEffort,
+                    history_depth: OUTGOING_QUEUE_DEPTH,
+                    ..Default::default()
+                })
+                .with_node(Some(TRANSPORT_NODE.to_string()))
+                .forwarding(),
+        );
+        let link =
+            TopicBus::global().attach_subscriber(topic, TopicType::of::<T>(), queue.clone())?;
+        let task = tokio::spawn(state.clone().forward(
+            topic.to_string(),
+            T::type_hash(),
+            queue.clone(),
+            publisher,
+        ));
+        outgoing.insert(
+            topic.to_string(),
+            Outgoing {
+                link,
+                queue,
+                task: task.abort_handle(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Deliver samples of `topic` published by other zenoh participants
+    pub fn advertise_subscriber<T: Message>(&self, topic: &str) -> Result<()> {
+        let state = &self.state;
+        let hash = (!is_raw::<T>()).then(T::type_hash);
+        state.subscribed.lock().insert(topic.to_string(), hash);
+
+        let mut incoming = state.incoming.lock();
+        if incoming.contains_key(topic) {
+            return Ok(());
+        }
+        let writer = Arc::new(Writer::new(
+            QosProfile::default(),
+            Some(TRANSPORT_NODE.to_string()),
+        ));
+        let link = TopicBus::global().attach_publisher(topic, None, writer.clone())?;
+        // Zenoh calls back on its own threads; delivery happens on Tokio's
+        let (tx, rx) = mpsc::unbounded_channel();
+        let subscriber = state
+            .session
+            .declare_subscriber(key_expr(topic))
+            .allowed_origin(Locality::Remote)
+            .callback(move |sample: zenoh::sample::Sample| {
+                let header = sample.attachment().map(|a| a.to_bytes().into_owned());
+                let _ = tx.send((header, sample.payload().to_bytes().into_owned()));
+            })
+            .wait();
+        let subscriber = match subscriber {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                TopicBus::global().detach_publisher(&link, &writer);
+                return Err(Error::Zenoh(format!(
+                    "declaring subscriber on '{}': {}",
+                    topic, e
+                )));
+            }
+        };
+        let task = tokio::spawn(state.clone().receive(
+            topic.to_string(),
+            link.clone(),
+            writer.clone(),
+            rx,
+        ));
+        incoming.insert(
+            topic.to_string(),
+            Incoming {
+                link,
+                writer,
+                _subscriber: subscriber,
+                task: task.abort_handle(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Answer zenoh queries on `service` with its local server
+    ///
+    /// The queryable stays declared until the returned handle is dropped.
+    pub(crate) fn serve<Req: Message, Res: Message>(&self, service: &str) -> Result<Queryable<()>> {
+        let runtime = tokio::runtime::Handle::current();
+        let client = Arc::new(ServiceClient::<Req, Res>::new(service)?);
+        self.state
+            .session
+            .declare_queryable(key_expr(service))
+            .callback(move |query: Query| {
+                let client = client.clone();
+                runtime.spawn(async move {
+                    let reply = match query.payload().map(|p| p.to_bytes()) {
+                        Some(request) => match deserialize_cdr::<Req>(&request) {
+                            Ok(request) => client.call(request).await,
+                            Err(e) => Err(e),
+                        },
+                        None => Err(Error::Serialization("query without a request".into())),
+                    };
+                    let sent = match reply.and_then(|response| serialize_cdr(&response)) {
+                        Ok(response) => query.reply(query.key_expr().clone(), response).await,
+                        Err(e) => query.reply_err(e.to_string()).await,
+                    };
+                    if let Err(e) = sent {
+                        warn!("Replying to a zenoh query on '{}' failed: {}", client.name(), e);
+                    }
+                });
+            })
+            .wait()
+            .map_err(|e| Error::Zenoh(format!("declaring queryable '{}': {}", service, e)))
+    }
+
+    /// Call `service` on whichever zenoh participant serves it
+    ///
+    /// Fails with [`Error::ServiceUnavailable`] if no queryable answers,
+    /// with [`Error::Timeout`] if none answers in time, and with
+    /// [`Error::Zenoh`] if the remote server returned an error.
+    pub(crate) async fn call<Req: Message, Res: Message>(
+        &self,
+        service: &str,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<Res> {
+        let replies = self
+            .state
+            .session
+            .get(key_expr(service))
+            .payload(serialize_cdr(request)?)
+            .timeout(timeout)
+            .await
+            .map_err(|e| Error::Zenoh(format!("querying '{}': {}", service, e)))?;
+        let received = tokio::time::timeout(timeout, replies.recv_async()).await;
+        let reply = match received {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => return Err(Error::ServiceUnavailable(service.to_string())),
+            Err(_) => {
+                return Err(Error::Timeout(format!(
+                    "Service '{}' did not respond within {:?}",
+                    service, timeout
+                )))
+            }
+        };
+        match reply.result() {
+            Ok(sample) => deserialize_cdr(&sample.payload().to_bytes()),
+            Err(e) => Err(Error::Zenoh(format!(
+                "service '{}' failed: {}",
+                service,
+                String::from_utf8_lossy(&e.payload().to_bytes())
+            ))),
+        }
+    }
+}
+
+impl Drop for ZenohTransport {
+    fn drop(&mut self) {
+        let bus = TopicBus::global();
+        for (_, outgoing) in self.state.outgoing.lock().drain() {
+            outgoing.task.abort();
+            bus.detach_subscriber(&outgoing.link, &outgoing.queue);
+        }
+        for (_, incoming) in self.state.incoming.lock().drain() {
+            incoming.task.abort();
+            bus.detach_publisher(&incoming.link, &incoming.writer);
+        }
+    }
+}
+
+/// The zenoh session configuration for `config`
+fn session_config(config: &ZenohConfig) -> Result<zenoh::Config> {
+    let mut session = zenoh::Config::default();
+    let mut set = |key: &str, value: serde_json::Value| {
+        session
+            .insert_json5(key, &value.to_string())
+            .map_err(|e| Error::Configuration(format!("zenoh {}: {}", key, e)))
+    };
+    set("mode", serde_json::json!(config.mode))?;
+    if !config.connect.is_empty() {
+        set("connect/endpoints", serde_json::json!(config.connect))?;
+    }
+    if !config.listen.is_empty() {
+        set("listen/endpoints", serde_json::json!(config.listen))?;
+    }
+    Ok(session)
+}
+
+impl TransportState {
+    /// Publish a topic's local samples until the transport stops
+    async fn forward(
+        self: Arc<Self>,
+        topic: String,
+        type_hash: u64,
+        queue: Arc<SampleQueue>,
+        publisher: Publisher<'static>,
+    ) {
+        while let Some(sample) = queue.pop().await {
+            let Some(stamp) = sample.stamp() else {
+                continue;
+            };
+            // Samples that came in over zenoh are not sent back out
+            if stamp.publisher.prefix != self.prefix {
+                continue;
+            }
+            let header = SampleHeader {
+                type_name: sample.type_name().to_string(),
+                type_hash,
+                format: sample.format(),
+                publisher: stamp.publisher,
+                seq: stamp.seq,
+                stamp_ns: stamp.time.as_nanos(),
+                clock: stamp.clock,
+            };
+            let sent = match (serialize_cdr(&header), sample.bytes()) {
+                (Ok(header), Ok(payload)) => publisher
+                    .put(payload.to_vec())
+                    .attachment(ZBytes::from(header))
+                    .await
+                    .map_err(|e| Error::Zenoh(e.to_string())),
+                (Err(e), _) | (_, Err(e)) => Err(e),
+            };
+            if let Err(e) = sent {
+                warn!("Publishing '{}' over zenoh failed: {}", topic, e);
+            }
+        }
+    }
+
+    /// Inject a topic's remote samples into the bus
+    async fn receive(
+        self: Arc<Self>,
+        topic: String,
+        link: Arc<Topic>,
+        writer: Arc<Writer>,
+        mut samples: mpsc::UnboundedReceiver<(Option<Vec<u8>>, Vec<u8>)>,
+    ) {
+        while let Some((header, payload)) = samples.recv().await {
+            let header = match header.as_deref().map(deserialize_cdr::<SampleHeader>) {
+                Some(Ok(header)) => header,
+                Some(Err(e)) => {
+                    warn!("Bad zenoh sample on '{}': {}", topic, e);
+                    continue;
+                }
+                // Published by a plain zenoh client, not a ros3 node
+                None => {
+                    debug!("Dropping zenoh sample on '{}' without a ros3 header", topic);
+                    continue;
+                }
+            };
+            // Another session in this process already delivered it locally
+            if header.publisher.prefix == self.prefix {
+                continue;
+            }
+            let local = self.subscribed.lock().get(&topic).copied().flatten();
+            if local.is_some_and(|hash| hash != header.type_hash) {
+                warn!(
+                    "Dropping '{}' from {}: type {} does not match local subscribers",
+                    topic, header.publisher, header.type_name
+                );
+                continue;
+            }
+            let sample = Sample::new(payload, header.format, Arc::from(header.type_name.as_str()))
+                .with_stamp(Stamp {
+                    publisher: header.publisher,
+                    seq: header.seq,
+                    time: Time::from_nanos(header.stamp_ns),
+                    clock: header.clock,
+                });
+            if let Err(e) = link.deliver(&writer, sample).await {
+                warn!("Delivering zenoh sample on '{}' failed: {}", topic, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RobotState;
+    use crate::service::ServiceServer;
+
+    #[test]
+    fn test_key_expr_escaping() {
+        assert_eq!(key_expr("/robot/state"), "ros3/robot/state");
+        assert_eq!(key_expr("/ns/cmd_vel"), "ros3/ns/cmd_vel");
+        assert_eq!(key_expr("/a*b/$x?#"), "ros3/a%2Ab/%24x%3F%23");
+        assert_eq!(key_expr("/a//b"), "ros3/a/%/b");
+        // The escape character is itself escaped
+        assert_eq!(key_expr("/100%"), "ros3/100%25");
+        assert_ne!(key_expr("/a%2A"), key_expr("/a*"));
+        assert_eq!(key_expr("/caméra"), "ros3/cam%C3%A9ra");
+    }
+
+    fn config(listen: &str, connect: &[&str]) -> ZenohConfig {
+        ZenohConfig {
+            mode: "peer".into(),
+            connect: connect.iter().map(|s| s.to_string()).collect(),
+            listen: vec![listen.to_string()],
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_service_over_queryable() {
+        let server_side = ZenohTransport::shared(&config("tcp/127.0.0.1:17450", &[])).unwrap();
+        let client_side =
+            ZenohTransport::shared(&config("tcp/127.0.0.1:17451", &["tcp/127.0.0.1:17450"]))
+                .unwrap();
+
+        let _server = ServiceServer::new("/test/zenoh/double", |req: RobotState| async move {
+            Ok(RobotState {
+                timestamp: req.timestamp * 2,
+                ..req
+            })
+        })
+        .unwrap();
+        let _queryable = server_side
+            .serve::<RobotState, RobotState>("/test/zenoh/double")
+            .unwrap();
+
+        let request = RobotState {
+            timestamp: 21,
+            ..Default::default()
+        };
+        // The sessions may take a moment to connect
+        let mut response = None;
+        for _ in 0..50 {
+            match client_side
+                .call::<RobotState, RobotState>(
+                    "/test/zenoh/double",
+                    &request,
+                    Duration::from_millis(200),
+                )
+                .await
+            {
+                Ok(reply) => {
+                    response = Some(reply);
+                    break;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+        assert_eq!(response.expect("no reply over zenoh").timestamp, 42);
+
+        let missing = client_side
+            .call::<RobotState, RobotState>(
+                "/test/zenoh/missing",
+                &request,
+                Duration::from_millis(200),
+            )
+            .await;
+        assert!(matches!(
+            missing,
+            Err(Error::ServiceUnavailable(_)) | Err(Error::Timeout(_))
+        ));
+    }
+}
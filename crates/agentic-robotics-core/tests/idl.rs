@@ -0,0 +1,167 @@
+//! Message definitions, parsed from the corpus under `tests/idl`
+//!
+//! `valid` holds packages of definitions loaded as a directory, and every
+//! file in `invalid` starts with the error it should fail with, as
+//! `# error: <line>:<column> <reason>`.
+
+#![cfg(feature = "idl")]
+
+use agentic_robotics_core::dynamic::{DynamicMessage, DynamicValue};
+use agentic_robotics_core::serialization::{serialize_cdr, Format};
+use agentic_robotics_core::{idl, types, Error, Message, Node, Ros3Message};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn corpus(dir: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/idl")
+        .join(dir)
+}
+
+#[derive(Debug, Clone, PartialEq, Ros3Message)]
+#[ros3(type_name = "geometry_msgs/Point")]
+struct Point {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Ros3Message)]
+#[ros3(type_name = "nav_msgs/Waypoint")]
+struct Waypoint {
+    position: Point,
+    speed: f32,
+    flags: u8,
+}
+
+#[test]
+fn test_load_valid_corpus() {
+    let loaded = idl::load_dir(corpus("valid")).unwrap();
+    let names: Vec<&str> = loaded.iter().map(|ty| ty.name()).collect();
+    assert_eq!(
+        names,
+        [
+            "geometry_msgs/Point",
+            "nav_msgs/Path",
+            "nav_msgs/Waypoint",
+            "sensor_msgs/Range",
+            "std_msgs/Header",
+        ]
+    );
+    // Loading again finds the same types registered
+    assert_eq!(idl::load_dir(corpus("valid")).unwrap().len(), 5);
+
+    let range = types::lookup("sensor_msgs/Range")
+        .unwrap()
+        .descriptor()
+        .unwrap();
+    assert_eq!(range.fields().len(), 17);
+    assert_eq!(range.constant("INFRARED"), Some(&DynamicValue::UInt(1)));
+    assert_eq!(
+        range.constant("MODEL"),
+        Some(&"HC-SR04 # not a comment".into())
+    );
+    assert_eq!(
+        range.constant("MIN_RANGE"),
+        Some(&DynamicValue::Float(0.02f32 as f64))
+    );
+    assert_eq!(
+        range.constant("CALIBRATED"),
+        Some(&DynamicValue::Bool(true))
+    );
+    assert_eq!(range.constant("UNIT"), Some(&DynamicValue::Char('m')));
+    assert_eq!(range.field("raw").unwrap().ty.to_string(), "Vec<u8>");
+    assert_eq!(range.field("window").unwrap().ty.to_string(), "[f64;2]");
+
+    // Definitions describe their derived counterparts exactly
+    let waypoint = types::lookup("nav_msgs/Waypoint").unwrap();
+    let derived = Waypoint::descriptor().unwrap();
+    assert_eq!(waypoint.hash(), Waypoint::type_hash());
+    assert_eq!(waypoint.descriptor().unwrap().fields(), derived.fields());
+    assert_eq!(waypoint.schema(), Waypoint::schema());
+    let typed = Waypoint {
+        position: Point {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        },
+        speed: 0.5,
+        flags: 1,
+    };
+    let raw = waypoint
+        .from_json(serde_json::to_value(&typed).unwrap(), Format::Cdr)
+        .unwrap();
+    assert_eq!(raw.data, serialize_cdr(&typed).unwrap());
+    assert_eq!(raw.decode::<Waypoint>().unwrap(), typed);
+
+    let mut path = DynamicMessage::of_type("nav_msgs/Path").unwrap();
+    path.set("waypoints", vec![DynamicMessage::from_raw(&raw).unwrap()])
+        .unwrap();
+    let json = path.to_json();
+    assert_eq!(
+        json["header"],
+        json!({ "stamp_ns": 0, "seq": 0, "frame_id": "" })
+    );
+    assert_eq!(json["waypoints"][0]["position"]["y"], 2.0);
+}
+
+#[test]
+fn test_invalid_corpus() {
+    let mut files: Vec<PathBuf> = fs::read_dir(corpus("invalid"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+    assert!(!files.is_empty());
+    for path in files {
+        let source = fs::read_to_string(&path).unwrap();
+        let expected = source
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("# error: "))
+            .unwrap_or_else(|| panic!("{} has no expected error", path.display()));
+        let type_name = format!(
+            "idl_invalid/{}",
+            path.file_stem().unwrap().to_str().unwrap()
+        );
+        match idl::parse(&type_name, &source) {
+            Err(Error::InvalidDefinition {
+                line,
+                column,
+                reason,
+                ..
+            }) => assert_eq!(
+                format!("{}:{} {}", line, column, reason),
+                expected,
+                "{}",
+                path.display()
+            ),
+            result => panic!("{}: expected an error, got {:?}", path.display(), result),
+        }
+    }
+}
+
+#[test]
+fn test_recursive_definitions_are_rejected() {
+    let err = idl::load_dir(corpus("recursive")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid definition of loop_msgs/Tree at 1:1: loop_msgs/Node contains itself"
+    );
+    assert!(types::lookup("loop_msgs/Node").is_none());
+}
+
+#[test]
+fn test_node_loads_definitions() {
+    let node = Node::new("idl_test")
+        .unwrap()
+        .with_message_definitions(corpus("valid"))
+        .unwrap();
+    assert_eq!(node.name(), "idl_test");
+    assert!(types::lookup("std_msgs/Header").is_some());
+    assert!(Node::new("idl_missing")
+        .unwrap()
+        .with_message_definitions(corpus("missing"))
+        .is_err());
+}
@@ -0,0 +1,82 @@
+//! Steady-state publishing with a buffer pool does not touch the heap
+//!
+//! A counting global allocator counts the allocations of the publishing
+//! thread while it publishes.
+
+use agentic_robotics_core::{BufferPool, Publisher, RobotState, Subscriber};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn count() {
+    if COUNTING.with(Cell::get) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_pooled_publish_does_not_allocate() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let pool = BufferPool::default();
+    let publisher = Publisher::<RobotState>::new("/pool_test/state")
+        .unwrap()
+        .with_pool(pool.clone());
+    let subscriber = Subscriber::<RobotState>::builder("/pool_test/state")
+        .queue_depth(16)
+        .build()
+        .unwrap();
+    let state = RobotState {
+        position: [1.0, 2.0, 3.0],
+        velocity: [0.1, 0.2, 0.3],
+        timestamp: 42,
+    };
+
+    for i in 0..1_000 {
+        // The first publishes may grow queues and lazily set up state
+        COUNTING.with(|counting| counting.set(i >= 100));
+        runtime.block_on(publisher.publish(&state)).unwrap();
+        COUNTING.with(|counting| counting.set(false));
+        // Receiving allocates and returns the buffer to the pool
+        assert_eq!(subscriber.try_recv().unwrap().unwrap().timestamp, 42);
+    }
+
+    assert_eq!(ALLOCATIONS.load(Ordering::Relaxed), 0);
+    let stats = pool.stats();
+    assert_eq!(stats.hits, 1_000);
+    assert_eq!(stats.hit_rate(), 1.0);
+    assert_eq!(stats.in_use, 0);
+}
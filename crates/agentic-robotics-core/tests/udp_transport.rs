@@ -0,0 +1,210 @@
+//! Inter-process delivery over the UDP transport
+//!
+//! The tests re-run their own binary as the publishing process.
+
+use agentic_robotics_core::{Message, Node, RobotState, TransportConfig, UdpConfig, UdpTransport};
+use std::process::{Child, Command};
+use std::time::Duration;
+
+const CHILD_ENV: &str = "ROS3_UDP_CHILD";
+const TOPIC: &str = "/udp_test/robot_state";
+const LATCHED_TOPIC: &str = "/udp_test/latched";
+const PORT: u16 = 17_400;
+const LATCHED_PORT: u16 = 17_402;
+#[cfg(feature = "lz4")]
+const COMPRESSED_TOPIC: &str = "/udp_test/compressed";
+#[cfg(feature = "lz4")]
+const COMPRESSED_PORT: u16 = 17_404;
+
+fn udp_config(discovery_port: u16) -> UdpConfig {
+    UdpConfig {
+        discovery_port,
+        announce_period: Duration::from_millis(100),
+        ..Default::default()
+    }
+}
+
+fn config(discovery_port: u16) -> TransportConfig {
+    TransportConfig::Udp(udp_config(discovery_port))
+}
+
+fn state() -> RobotState {
+    RobotState {
+        position: [1.0, 2.0, 3.0],
+        velocity: [0.5, 0.0, 0.0],
+        timestamp: 42,
+    }
+}
+
+fn spawn_child(test: &str) -> ChildGuard {
+    let child = Command::new(std::env::current_exe().unwrap())
+        .args([test, "--exact", "--nocapture"])
+        .env(CHILD_ENV, "1")
+        .spawn()
+        .expect("failed to spawn publishing process");
+    ChildGuard(child)
+}
+
+/// Kills the publishing process when the test ends, passed or not
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn publish_until_killed() {
+    let node = Node::new("udp_child")
+        .unwrap()
+        .with_transport(config(PORT))
+        .unwrap();
+    let publisher = node.create_publisher::<RobotState>(TOPIC).unwrap();
+    // The parent kills this process once it has received a message
+    for _ in 0..600 {
+        publisher.publish(&state()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_receive_from_child_process() {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        publish_until_killed().await;
+        return;
+    }
+
+    let node = Node::new("udp_parent")
+        .unwrap()
+        .with_transport(config(PORT))
+        .unwrap();
+    let subscriber = node.create_subscriber::<RobotState>(TOPIC).unwrap();
+    let _child = spawn_child("test_receive_from_child_process");
+
+    let received = tokio::time::timeout(Duration::from_secs(10), subscriber.recv())
+        .await
+        .expect("no message from the child process within 10s")
+        .unwrap();
+    assert_eq!(received.position, [1.0, 2.0, 3.0]);
+    assert_eq!(received.timestamp, 42);
+
+    let topics = UdpTransport::shared(&udp_config(PORT))
+        .unwrap()
+        .remote_topics();
+    let topic = topics.iter().find(|topic| topic.name == TOPIC).unwrap();
+    assert_eq!(topic.type_name.as_deref(), Some(RobotState::type_name()));
+    assert_eq!(topic.publisher_count, 1);
+}
+
+/// Publish a single latched message, then stay alive until killed
+async fn latch_until_killed() {
+    let node = Node::new("udp_latch_child")
+        .unwrap()
+        .with_transport(config(LATCHED_PORT))
+        .unwrap();
+    let publisher = node
+        .create_publisher::<RobotState>(LATCHED_TOPIC)
+        .unwrap()
+        .latch(true);
+    publisher.publish(&state()).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(30)).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_latched_message_reaches_late_remote_subscriber() {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        latch_until_killed().await;
+        return;
+    }
+
+    let node = Node::new("udp_latch_parent")
+        .unwrap()
+        .with_transport(config(LATCHED_PORT))
+        .unwrap();
+    let _child = spawn_child("test_latched_message_reaches_late_remote_subscriber");
+    // Subscribe only once the child has published
+    let transport = UdpTransport::shared(&udp_config(LATCHED_PORT)).unwrap();
+    let published = async {
+        while !transport
+            .remote_topics()
+            .iter()
+            .any(|topic| topic.name == LATCHED_TOPIC)
+        {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), published)
+        .await
+        .expect("child process not discovered within 10s");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let subscriber = node.create_subscriber::<RobotState>(LATCHED_TOPIC).unwrap();
+    let received = tokio::time::timeout(Duration::from_secs(10), subscriber.recv())
+        .await
+        .expect("no latched message from the child process within 10s")
+        .unwrap();
+    assert_eq!(received.timestamp, 42);
+}
+
+#[cfg(feature = "lz4")]
+fn cloud() -> agentic_robotics_core::PointCloud {
+    let point = agentic_robotics_core::message::Point3D {
+        x: 1.0,
+        y: 2.0,
+        z: 3.0,
+    };
+    agentic_robotics_core::PointCloud {
+        points: vec![point; 5000],
+        intensities: vec![0.5; 5000],
+        timestamp: 42,
+    }
+}
+
+/// Publish large lz4-compressed point clouds until killed
+#[cfg(feature = "lz4")]
+async fn publish_compressed_until_killed() {
+    use agentic_robotics_core::{Compression, PublisherOptions};
+
+    let node = Node::new("udp_compressed_child")
+        .unwrap()
+        .with_transport(config(COMPRESSED_PORT))
+        .unwrap();
+    let options = PublisherOptions::new().compression(Compression::Lz4);
+    let publisher = node
+        .create_publisher_with_options::<agentic_robotics_core::PointCloud>(
+            COMPRESSED_TOPIC,
+            options,
+        )
+        .unwrap();
+    for _ in 0..600 {
+        publisher.publish(&cloud()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[cfg(feature = "lz4")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_compressed_message_from_child_process() {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        publish_compressed_until_killed().await;
+        return;
+    }
+
+    let node = Node::new("udp_compressed_parent")
+        .unwrap()
+        .with_transport(config(COMPRESSED_PORT))
+        .unwrap();
+    let subscriber = node
+        .create_subscriber::<agentic_robotics_core::PointCloud>(COMPRESSED_TOPIC)
+        .unwrap();
+    let _child = spawn_child("test_compressed_message_from_child_process");
+
+    let received = tokio::time::timeout(Duration::from_secs(10), subscriber.recv())
+        .await
+        .expect("no message from the child process within 10s")
+        .unwrap();
+    assert_eq!(received.points.len(), 5000);
+    assert_eq!(received.intensities, cloud().intensities);
+    assert_eq!(received.timestamp, 42);
+}
@@ -0,0 +1,79 @@
+//! Inter-process delivery over the zenoh transport
+//!
+//! The test re-runs its own binary as the publishing process, which
+//! connects to the zenoh session of the subscribing one.
+
+#![cfg(feature = "transport-zenoh")]
+
+use agentic_robotics_core::{Node, RobotState, TransportConfig, ZenohConfig};
+use std::process::{Child, Command};
+use std::time::Duration;
+
+const CHILD_ENV: &str = "ROS3_ZENOH_CHILD";
+const ENDPOINT: &str = "tcp/127.0.0.1:17452";
+const TOPIC: &str = "/zenoh_test/robot_state";
+
+/// Kills the publishing process when the test ends, passed or not
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn publish_until_killed() {
+    let config = ZenohConfig {
+        connect: vec![ENDPOINT.to_string()],
+        listen: Vec::new(),
+        ..Default::default()
+    };
+    let node = Node::new("zenoh_child")
+        .unwrap()
+        .with_transport(TransportConfig::Zenoh(config))
+        .unwrap();
+    let publisher = node.create_publisher::<RobotState>(TOPIC).unwrap();
+    let state = RobotState {
+        position: [1.0, 2.0, 3.0],
+        velocity: [0.5, 0.0, 0.0],
+        timestamp: 42,
+    };
+    // The parent kills this process once it has received a message
+    for _ in 0..600 {
+        publisher.publish(&state).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_receive_from_child_process() {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        publish_until_killed().await;
+        return;
+    }
+
+    let config = ZenohConfig {
+        listen: vec![ENDPOINT.to_string()],
+        ..Default::default()
+    };
+    let node = Node::new("zenoh_parent")
+        .unwrap()
+        .with_transport(TransportConfig::Zenoh(config))
+        .unwrap();
+    let subscriber = node.create_subscriber::<RobotState>(TOPIC).unwrap();
+
+    let child = Command::new(std::env::current_exe().unwrap())
+        .args(["test_receive_from_child_process", "--exact", "--nocapture"])
+        .env(CHILD_ENV, "1")
+        .spawn()
+        .expect("failed to spawn publishing process");
+    let _child = ChildGuard(child);
+
+    let received = tokio::time::timeout(Duration::from_secs(10), subscriber.recv())
+        .await
+        .expect("no message from the child process within 10s")
+        .unwrap();
+    assert_eq!(received.position, [1.0, 2.0, 3.0]);
+    assert_eq!(received.timestamp, 42);
+}
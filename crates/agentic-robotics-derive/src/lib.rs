@@ -0,0 +1,621 @@
+//! Derive macros for agentic-robotics messages
+//!
+//! `#[derive(Ros3Message)]` implements `serde::Serialize`,
+//! `serde::Deserialize` and `agentic_robotics_core::Message` for a struct
+//! with named fields. The type name defaults to `<crate name>/<StructName>`
+//! and can be overridden with `#[ros3(type_name = "pkg/Name")]`. The
+//! generated `Message::schema` describes the JSON form of the struct and
+//! `Message::descriptor` its fields, for dynamic access.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments,
+    Type,
+};
+
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64", "String",
+];
+
+/// Derive serialization and `Message` for a ROS3 message struct
+#[proc_macro_derive(Ros3Message, attributes(ros3))]
+pub fn derive_ros3_message(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A validated struct field
+struct MessageField {
+    ident: Ident,
+    name: String,
+    /// `T` of an `Option<T>` field
+    optional: Option<Type>,
+    /// Expression building the JSON Schema of the field's value
+    schema: TokenStream2,
+    /// Expression building the field's `dynamic::FieldType`
+    field_type: TokenStream2,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "Ros3Message cannot be derived for generic types",
+        ));
+    }
+
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "Ros3Message can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "Ros3Message can only be derived for structs",
+            ))
+        }
+    };
+
+    let type_name = parse_type_name(&input)?;
+
+    let mut fields = Vec::new();
+    let mut nested = Vec::new();
+    let mut definition = String::new();
+    for field in named {
+        let ident = field.ident.clone().expect("named field");
+        let optional = option_inner(&field.ty).cloned();
+        check_type(optional.as_ref().unwrap_or(&field.ty), &mut nested)?;
+
+        let name = ident.to_string().trim_start_matches("r#").to_string();
+        definition.push_str(&format!("{}:{};", name, canonical(&field.ty)));
+        fields.push(MessageField {
+            optional,
+            schema: schema(&field.ty),
+            field_type: field_type(&field.ty),
+            ident,
+            name,
+        });
+    }
+
+    let serialize = expand_serialize(&input.ident, &fields);
+    let deserialize = expand_deserialize(&input.ident, &fields);
+    let message = expand_message(
+        &input.ident,
+        type_name,
+        fnv1a(definition.as_bytes()),
+        &nested,
+        &fields,
+    );
+
+    Ok(quote! {
+        const _: () = {
+            use ::agentic_robotics_core::serde as __serde;
+            #serialize
+            #deserialize
+            #message
+        };
+    })
+}
+
+/// Read `#[ros3(type_name = "...")]`
+fn parse_type_name(input: &DeriveInput) -> syn::Result<Option<LitStr>> {
+    let mut type_name = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("ros3") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type_name") {
+                type_name = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported ros3 attribute, expected `type_name`"))
+            }
+        })?;
+    }
+    Ok(type_name)
+}
+
+/// Reject field types the CDR and JSON serializers cannot round-trip
+///
+/// Types that are not primitives or containers are assumed to be nested
+/// messages and collected into `nested`; the generated code asserts they
+/// implement `Message`. `Option` is only accepted as the outer type of a
+/// field, checked by the caller, since the generated code is what encodes
+/// it for CDR.
+fn check_type(ty: &Type, nested: &mut Vec<Type>) -> syn::Result<()> {
+    match ty {
+        Type::Array(array) => check_type(&array.elem, nested),
+        Type::Paren(paren) => check_type(&paren.elem, nested),
+        Type::Group(group) => check_type(&group.elem, nested),
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last().expect("non-empty path");
+            let name = segment.ident.to_string();
+            match &segment.arguments {
+                PathArguments::None if PRIMITIVES.contains(&name.as_str()) => Ok(()),
+                PathArguments::None if name == "isize" || name == "usize" => Err(
+                    syn::Error::new_spanned(ty, format!(
+                        "`{}` has a platform-dependent size; use a fixed-width integer",
+                        name
+                    )),
+                ),
+                PathArguments::None => {
+                    nested.push(ty.clone());
+                    Ok(())
+                }
+                PathArguments::AngleBracketed(args) if name == "Vec" && args.args.len() == 1 => {
+                    match args.args.first() {
+                        Some(GenericArgument::Type(inner)) => check_type(inner, nested),
+                        _ => Err(unsupported(ty)),
+                    }
+                }
+                _ => Err(unsupported(ty)),
+            }
+        }
+        _ => Err(unsupported(ty)),
+    }
+}
+
+fn unsupported(ty: &Type) -> syn::Error {
+    syn::Error::new_spanned(
+        ty,
+        format!(
+            "unsupported field type `{}` in Ros3Message; expected a primitive, String, \
+             [T; N], Vec<T> or another Ros3Message struct, optionally in an Option<T>",
+            canonical(ty)
+        ),
+    )
+}
+
+/// `T` if `ty` is `Option<T>`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args)
+            if segment.ident == "Option" && args.args.len() == 1 =>
+        {
+            match args.args.first() {
+                Some(GenericArgument::Type(inner)) => Some(inner),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Expression building the JSON Schema of a type accepted by [`check_type`]
+fn schema(ty: &Type) -> TokenStream2 {
+    let json = quote!(::agentic_robotics_core::serde_json::json);
+    match ty {
+        Type::Array(array) => {
+            let items = schema(&array.elem);
+            let len = &array.len;
+            quote!(#json!({
+                "type": "array",
+                "items": #items,
+                "minItems": #len,
+                "maxItems": #len,
+            }))
+        }
+        Type::Paren(paren) => schema(&paren.elem),
+        Type::Group(group) => schema(&group.elem),
+        Type::Path(path) => {
+            let segment = path.path.segments.last().expect("non-empty path");
+            let inner = match &segment.arguments {
+                PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(GenericArgument::Type(inner)) => Some(inner),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match (segment.ident.to_string().as_str(), inner) {
+                ("Vec", Some(inner)) => {
+                    let items = schema(inner);
+                    quote!(#json!({ "type": "array", "items": #items }))
+                }
+                ("Option", Some(inner)) => {
+                    let inner = schema(inner);
+                    quote!(::agentic_robotics_core::message::nullable(#inner))
+                }
+                ("bool", _) => quote!(#json!({ "type": "boolean" })),
+                ("char", _) => quote!(#json!({ "type": "string", "minLength": 1, "maxLength": 1 })),
+                ("String", _) => quote!(#json!({ "type": "string" })),
+                ("f32" | "f64", _) => quote!(#json!({ "type": "number" })),
+                ("i64", _) => quote!(#json!({ "type": "integer" })),
+                ("u64", _) => quote!(#json!({ "type": "integer", "minimum": 0 })),
+                (name @ ("i8" | "i16" | "i32" | "u8" | "u16" | "u32"), _) => {
+                    let ty = Ident::new(name, Span::call_site());
+                    quote!(#json!({ "type": "integer", "minimum": #ty::MIN, "maximum": #ty::MAX }))
+                }
+                _ => quote_spanned! {ty.span()=>
+                    <#ty as ::agentic_robotics_core::Message>::schema()
+                },
+            }
+        }
+        _ => quote!(#json!(true)),
+    }
+}
+
+/// Expression building the `dynamic::FieldType` of a type accepted by
+/// [`check_type`]; nested messages without a descriptor make the whole
+/// descriptor `None`
+fn field_type(ty: &Type) -> TokenStream2 {
+    let ty_path = quote!(::agentic_robotics_core::dynamic::FieldType);
+    match ty {
+        Type::Array(array) => {
+            let item = field_type(&array.elem);
+            let len = &array.len;
+            quote!(#ty_path::Array(::std::boxed::Box::new(#item), #len))
+        }
+        Type::Paren(paren) => field_type(&paren.elem),
+        Type::Group(group) => field_type(&group.elem),
+        Type::Path(path) => {
+            let segment = path.path.segments.last().expect("non-empty path");
+            let inner = match &segment.arguments {
+                PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(GenericArgument::Type(inner)) => Some(inner),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let name = segment.ident.to_string();
+            match (name.as_str(), inner) {
+                ("Vec", Some(inner)) => {
+                    let item = field_type(inner);
+                    quote!(#ty_path::Sequence(::std::boxed::Box::new(#item)))
+                }
+                ("Option", Some(inner)) => {
+                    let inner = field_type(inner);
+                    quote!(#ty_path::Optional(::std::boxed::Box::new(#inner)))
+                }
+                (primitive, _) if PRIMITIVES.contains(&primitive) => {
+                    // `f64` is `FieldType::F64`, `String` is `FieldType::String`
+                    let mut chars = primitive.chars();
+                    let first = chars.next().expect("non-empty name");
+                    let variant: String = first.to_uppercase().chain(chars).collect();
+                    let variant = Ident::new(&variant, Span::call_site());
+                    quote!(#ty_path::#variant)
+                }
+                _ => quote_spanned! {ty.span()=>
+                    #ty_path::Message(::std::sync::Arc::new(
+                        <#ty as ::agentic_robotics_core::Message>::descriptor()?,
+                    ))
+                },
+            }
+        }
+        _ => unreachable!("rejected by check_type"),
+    }
+}
+
+/// Field type with whitespace removed, used for the type hash
+fn canonical(ty: &Type) -> String {
+    quote!(#ty).to_string().chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// 64-bit FNV-1a, matching `agentic_robotics_core::message::fnv1a`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn expand_serialize(ident: &Ident, fields: &[MessageField]) -> TokenStream2 {
+    let struct_name = ident.to_string();
+    let len = fields.len();
+    let names = fields.iter().map(|f| &f.name);
+    // CDR has no optional values, so `Option` fields go through a wrapper
+    // encoding them as a sequence of at most one element
+    let values = fields.iter().map(|f| {
+        let ident = &f.ident;
+        if f.optional.is_some() {
+            quote!(&::agentic_robotics_core::message::OptionalRef(&self.#ident))
+        } else {
+            quote!(&self.#ident)
+        }
+    });
+
+    quote! {
+        impl __serde::Serialize for #ident {
+            fn serialize<__S>(&self, serializer: __S) -> ::core::result::Result<__S::Ok, __S::Error>
+            where
+                __S: __serde::Serializer,
+            {
+                use __serde::ser::SerializeStruct;
+                let mut state = serializer.serialize_struct(#struct_name, #len)?;
+                #( state.serialize_field(#names, #values)?; )*
+                state.end()
+            }
+        }
+    }
+}
+
+fn expand_deserialize(ident: &Ident, fields: &[MessageField]) -> TokenStream2 {
+    let struct_name = ident.to_string();
+    let expecting = format!("struct {}", struct_name);
+    let idents: Vec<_> = fields.iter().map(|f| &f.ident).collect();
+    let names: Vec<_> = fields.iter().map(|f| &f.name).collect();
+    let indices: Vec<_> = (0..fields.len()).collect();
+    let indices_u64: Vec<_> = (0..fields.len() as u64).collect();
+    let vars: Vec<_> = (0..fields.len())
+        .map(|i| format_ident!("__field{}", i))
+        .collect();
+    let element_types: Vec<_> = fields
+        .iter()
+        .map(|f| match &f.optional {
+            Some(inner) => quote!(::agentic_robotics_core::message::Optional<#inner>),
+            None => quote!(_),
+        })
+        .collect();
+    let unwrap: Vec<_> = fields
+        .iter()
+        .map(|f| match f.optional {
+            Some(_) => quote!(.0),
+            None => quote!(),
+        })
+        .collect();
+    let missing = fields.iter().map(|f| {
+        let name = &f.name;
+        if f.optional.is_some() {
+            quote!(::core::option::Option::None)
+        } else {
+            quote!(return ::core::result::Result::Err(__serde::de::Error::missing_field(#name)))
+        }
+    });
+
+    quote! {
+        impl<'de> __serde::Deserialize<'de> for #ident {
+            fn deserialize<__D>(deserializer: __D) -> ::core::result::Result<Self, __D::Error>
+            where
+                __D: __serde::Deserializer<'de>,
+            {
+                #[allow(non_camel_case_types)]
+                enum __Field {
+                    #( #vars, )*
+                    __Ignore,
+                }
+
+                struct __FieldVisitor;
+
+                impl<'de> __serde::de::Visitor<'de> for __FieldVisitor {
+                    type Value = __Field;
+
+                    fn expecting(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        f.write_str("field identifier")
+                    }
+
+                    fn visit_u64<__E>(self, value: u64) -> ::core::result::Result<__Field, __E>
+                    where
+                        __E: __serde::de::Error,
+                    {
+                        ::core::result::Result::Ok(match value {
+                            #( #indices_u64 => __Field::#vars, )*
+                            _ => __Field::__Ignore,
+                        })
+                    }
+
+                    fn visit_str<__E>(self, value: &str) -> ::core::result::Result<__Field, __E>
+                    where
+                        __E: __serde::de::Error,
+                    {
+                        ::core::result::Result::Ok(match value {
+                            #( #names => __Field::#vars, )*
+                            _ => __Field::__Ignore,
+                        })
+                    }
+                }
+
+                impl<'de> __serde::Deserialize<'de> for __Field {
+                    fn deserialize<__D>(deserializer: __D) -> ::core::result::Result<Self, __D::Error>
+                    where
+                        __D: __serde::Deserializer<'de>,
+                    {
+                        deserializer.deserialize_identifier(__FieldVisitor)
+                    }
+                }
+
+                struct __Visitor;
+
+                impl<'de> __serde::de::Visitor<'de> for __Visitor {
+                    type Value = #ident;
+
+                    fn expecting(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        f.write_str(#expecting)
+                    }
+
+                    fn visit_seq<__A>(self, mut seq: __A) -> ::core::result::Result<#ident, __A::Error>
+                    where
+                        __A: __serde::de::SeqAccess<'de>,
+                    {
+                        #(
+                            let #vars = match seq.next_element::<#element_types>()? {
+                                ::core::option::Option::Some(value) => value #unwrap,
+                                ::core::option::Option::None => {
+                                    return ::core::result::Result::Err(
+                                        __serde::de::Error::invalid_length(#indices, &self),
+                                    )
+                                }
+                            };
+                        )*
+                        ::core::result::Result::Ok(#ident { #( #idents: #vars, )* })
+                    }
+
+                    fn visit_map<__A>(self, mut map: __A) -> ::core::result::Result<#ident, __A::Error>
+                    where
+                        __A: __serde::de::MapAccess<'de>,
+                    {
+                        #( let mut #vars = ::core::option::Option::None; )*
+                        while let ::core::option::Option::Some(key) = map.next_key::<__Field>()? {
+                            match key {
+                                #(
+                                    __Field::#vars => {
+                                        if #vars.is_some() {
+                                            return ::core::result::Result::Err(
+                                                __serde::de::Error::duplicate_field(#names),
+                                            );
+                                        }
+                                        #vars = ::core::option::Option::Some(
+                                            map.next_value::<#element_types>()? #unwrap,
+                                        );
+                                    }
+                                )*
+                                __Field::__Ignore => {
+                                    map.next_value::<__serde::de::IgnoredAny>()?;
+                                }
+                            }
+                        }
+                        #(
+                            let #vars = match #vars {
+                                ::core::option::Option::Some(value) => value,
+                                ::core::option::Option::None => #missing,
+                            };
+                        )*
+                        ::core::result::Result::Ok(#ident { #( #idents: #vars, )* })
+                    }
+                }
+
+                const FIELDS: &[&str] = &[ #( #names ),* ];
+                deserializer.deserialize_struct(#struct_name, FIELDS, __Visitor)
+            }
+        }
+    }
+}
+
+fn expand_message(
+    ident: &Ident,
+    type_name: Option<LitStr>,
+    fields_hash: u64,
+    nested: &[Type],
+    fields: &[MessageField],
+) -> TokenStream2 {
+    let type_name = match type_name {
+        Some(name) => quote!(#name),
+        None => {
+            let struct_name = LitStr::new(&ident.to_string(), Span::call_site());
+            quote!(::core::concat!(::core::env!("CARGO_PKG_NAME"), "/", #struct_name))
+        }
+    };
+    let nested_hashes = nested.iter().map(|ty| {
+        quote_spanned! {ty.span()=>
+            let hash = ::agentic_robotics_core::message::combine_hash(
+                hash,
+                <#ty as ::agentic_robotics_core::Message>::type_hash(),
+            );
+        }
+    });
+
+    let names: Vec<_> = fields.iter().map(|f| &f.name).collect();
+    let schemas = fields.iter().map(|f| &f.schema);
+    let field_types = fields.iter().map(|f| &f.field_type);
+    let required = fields.iter().filter(|f| f.optional.is_none()).map(|f| &f.name);
+
+    quote! {
+        impl ::agentic_robotics_core::Message for #ident {
+            fn type_name() -> &'static str {
+                #type_name
+            }
+
+            fn schema() -> ::agentic_robotics_core::serde_json::Value {
+                let mut properties = ::agentic_robotics_core::serde_json::Map::new();
+                #( properties.insert(#names.into(), #schemas); )*
+                ::agentic_robotics_core::serde_json::json!({
+                    "title": Self::type_name(),
+                    "type": "object",
+                    "properties": properties,
+                    "required": [ #( #required ),* ],
+                    "additionalProperties": false,
+                })
+            }
+
+            fn type_hash() -> u64 {
+                let hash = ::agentic_robotics_core::message::combine_hash(
+                    ::agentic_robotics_core::message::fnv1a(Self::type_name().as_bytes()),
+                    #fields_hash,
+                );
+                #( #nested_hashes )*
+                hash
+            }
+
+            fn descriptor() -> ::core::option::Option<::agentic_robotics_core::dynamic::TypeDescriptor> {
+                let fields = ::std::vec![
+                    #( ::agentic_robotics_core::dynamic::FieldDescriptor::new(#names, #field_types), )*
+                ];
+                ::core::option::Option::Some(
+                    ::agentic_robotics_core::dynamic::TypeDescriptor::new(Self::type_name(), fields)
+                        .with_hash(Self::type_hash()),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_supported_fields() {
+        let input: DeriveInput = parse_quote! {
+            struct LidarScan {
+                ranges: Vec<f32>,
+                origin: [f64; 3],
+                frame: Option<String>,
+                stamp: i64,
+            }
+        };
+        assert!(expand(input).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_field_type() {
+        let input: DeriveInput = parse_quote! {
+            struct Bad {
+                lookup: std::collections::HashMap<String, f64>,
+            }
+        };
+        let err = expand(input).err().unwrap();
+        assert!(err.to_string().contains("unsupported field type"));
+    }
+
+    #[test]
+    fn test_rejects_nested_option() {
+        let input: DeriveInput = parse_quote! {
+            struct Bad {
+                ranges: Vec<Option<f32>>,
+            }
+        };
+        let err = expand(input).err().unwrap();
+        assert!(err.to_string().contains("unsupported field type"));
+    }
+
+    #[test]
+    fn test_rejects_tuple_struct() {
+        let input: DeriveInput = parse_quote! {
+            struct Pair(f64, f64);
+        };
+        assert!(expand(input).is_err());
+    }
+
+    #[test]
+    fn test_fnv1a_matches_reference() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -0,0 +1,7 @@
+//! Field types the derive rejects, with the errors it reports
+
+#[test]
+fn test_rejected_messages() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,10 @@
+use agentic_robotics_derive::Ros3Message;
+use std::collections::HashMap;
+
+#[derive(Ros3Message)]
+struct Calibration {
+    name: String,
+    offsets: HashMap<String, f64>,
+}
+
+fn main() {}
@@ -0,0 +1,138 @@
+//! Conversion between serialized ros3 messages and JSON
+
+use crate::error::{Error, Result};
+use agentic_robotics_core::serialization::{Format, Serializer};
+use agentic_robotics_core::{Message, RawMessage};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Converts one message type to and from JSON
+#[derive(Clone, Copy)]
+struct Codec {
+    /// Format messages published by clients are serialized in
+    format: Format,
+    to_json: fn(&RawMessage) -> Result<Value>,
+    decode_json: fn(Value, Format) -> Result<Vec<u8>>,
+}
+
+/// Message types the gateway can convert, by type name
+///
+/// Messages in a self-describing format (JSON or MessagePack) are converted
+/// whether or not their type is registered; CDR messages need the type.
+/// Clients may publish unregistered types, which are sent as JSON.
+#[derive(Clone, Default)]
+pub(crate) struct TypeRegistry {
+    codecs: HashMap<&'static str, Codec>,
+}
+
+impl TypeRegistry {
+    /// Convert `T`, serializing messages from clients in `format`
+    pub(crate) fn register<T: Message>(&mut self, format: Format) {
+        self.codecs.insert(
+            T::type_name(),
+            Codec {
+                format,
+                to_json: |raw| {
+                    let msg = raw.decode::<T>()?;
+                    serde_json::to_value(&msg).map_err(|e| Error::Conversion(e.to_string()))
+                },
+                decode_json: |value, format| {
+                    let msg: T = serde_json::from_value(value)
+                        .map_err(|e| Error::Conversion(e.to_string()))?;
+                    Ok(Serializer::new(format).serialize(&msg)?)
+                },
+            },
+        );
+    }
+
+    /// Decode a message received on `topic` as JSON
+    pub(crate) fn to_json(&self, topic: &str, raw: &RawMessage) -> Result<Value> {
+        if let Some(codec) = self.codecs.get(raw.type_name.as_str()) {
+            return (codec.to_json)(raw);
+        }
+        match raw.format {
+            Format::Json => {
+                serde_json::from_slice(&raw.data).map_err(|e| Error::Conversion(e.to_string()))
+            }
+            Format::MessagePack => {
+                rmp_serde::from_slice(&raw.data).map_err(|e| Error::Conversion(e.to_string()))
+            }
+            _ => Err(Error::UnknownType {
+                topic: topic.to_string(),
+                type_name: raw.type_name.clone(),
+            }),
+        }
+    }
+
+    /// Decode a JSON message from a client and serialize it as `type_name`
+    pub(crate) fn decode_json(&self, type_name: &str, value: Value) -> Result<RawMessage> {
+        let (format, data) = match self.codecs.get(type_name) {
+            Some(codec) => (codec.format, (codec.decode_json)(value, codec.format)?),
+            None => (
+                Format::Json,
+                serde_json::to_vec(&value).map_err(|e| Error::Conversion(e.to_string()))?,
+            ),
+        };
+        Ok(RawMessage {
+            type_name: type_name.to_string(),
+            format,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentic_robotics_core::RobotState;
+    use serde_json::json;
+
+    fn registry() -> TypeRegistry {
+        let mut types = TypeRegistry::default();
+        types.register::<RobotState>(Format::Cdr);
+        types
+    }
+
+    #[test]
+    fn test_registered_type_roundtrip() {
+        let types = registry();
+        let value = json!({
+            "position": [1.0, 2.0, 3.0],
+            "velocity": [0.0, 0.0, 0.5],
+            "timestamp": 7
+        });
+
+        let raw = types.decode_json("ros3_msgs/RobotState", value.clone()).unwrap();
+        assert_eq!(raw.format, Format::Cdr);
+        assert_eq!(raw.decode::<RobotState>().unwrap().timestamp, 7);
+        assert_eq!(types.to_json("/robot_state", &raw).unwrap(), value);
+
+        let invalid = types.decode_json("ros3_msgs/RobotState", json!({"position": "up"}));
+        assert!(matches!(invalid, Err(Error::Conversion(_))));
+    }
+
+    #[test]
+    fn test_unregistered_types() {
+        let types = registry();
+        let raw = types.decode_json("app_msgs/Battery", json!({"percent": 80})).unwrap();
+        assert_eq!(raw.format, Format::Json);
+        assert_eq!(types.to_json("/battery", &raw).unwrap(), json!({"percent": 80}));
+
+        let msgpack = RawMessage {
+            type_name: "app_msgs/Battery".into(),
+            format: Format::MessagePack,
+            data: rmp_serde::to_vec_named(&json!({"percent": 80})).unwrap(),
+        };
+        assert_eq!(types.to_json("/battery", &msgpack).unwrap(), json!({"percent": 80}));
+
+        let cdr = RawMessage {
+            type_name: "app_msgs/Battery".into(),
+            format: Format::Cdr,
+            data: vec![0, 1, 0, 0, 80, 0, 0, 0],
+        };
+        assert!(matches!(
+            types.to_json("/battery", &cdr),
+            Err(Error::UnknownType { topic, .. }) if topic == "/battery"
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+//! Error types for the WebSocket gateway
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Core(#[from] agentic_robotics_core::Error),
+
+    /// Boxed to keep the crate's `Result`s small
+    #[error("WebSocket error: {0}")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+
+    #[error("No JSON conversion for type '{type_name}' (topic '{topic}')")]
+    UnknownType { topic: String, type_name: String },
+
+    #[error("Conversion error: {0}")]
+    Conversion(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(e))
+    }
+}
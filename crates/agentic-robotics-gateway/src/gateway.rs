@@ -0,0 +1,361 @@
+//! The WebSocket server and its client sessions
+
+use crate::codec::TypeRegistry;
+use crate::error::{Error, Result};
+use crate::protocol::{ClientOp, ServerOp, StatusLevel};
+use agentic_robotics_core::message::Pose;
+use agentic_robotics_core::serialization::Format;
+use agentic_robotics_core::time::ClockMessage;
+use agentic_robotics_core::{
+    Message, Node, PointCloud, Publisher, QosProfile, RawMessage, Reliability, RobotState,
+    Subscriber,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message as Frame;
+use tracing::{debug, info, warn};
+
+/// Name of the ros3 node publishing and subscribing on behalf of clients
+pub const NODE_NAME: &str = "ros3_websocket_gateway";
+
+/// Port rosbridge listens on, used by the `ros3-gateway` binary
+pub const DEFAULT_PORT: u16 = 9090;
+
+/// Frames queued for a client before further messages to it are dropped
+const CLIENT_QUEUE_DEPTH: usize = 256;
+
+/// Serves ros3 topics to WebSocket clients as JSON
+///
+/// The built-in message types are registered up front; custom types are
+/// added with [`register`](Self::register). See [`protocol`](crate::protocol)
+/// for the operations clients can send.
+pub struct WebSocketGateway {
+    node: Arc<Node>,
+    types: TypeRegistry,
+}
+
+impl WebSocketGateway {
+    /// Create a gateway with its own node named [`NODE_NAME`]
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_node(Node::new(NODE_NAME)?))
+    }
+
+    /// Create a gateway publishing and subscribing through `node`
+    pub fn with_node(node: Node) -> Self {
+        let gateway = Self {
+            node: Arc::new(node),
+            types: TypeRegistry::default(),
+        };
+        gateway
+            .register_with_format::<Value>(Format::Json)
+            .register::<RobotState>()
+            .register::<PointCloud>()
+            .register::<Pose>()
+            .register::<ClockMessage>()
+    }
+
+    /// Convert messages of type `T`, publishing those from clients in CDR
+    pub fn register<T: Message>(self) -> Self {
+        self.register_with_format::<T>(Format::Cdr)
+    }
+
+    /// Convert messages of type `T`, publishing those from clients in `format`
+    pub fn register_with_format<T: Message>(mut self, format: Format) -> Self {
+        self.types.register::<T>(format);
+        self
+    }
+
+    /// Accept WebSocket clients on `addr` until the returned server is
+    /// dropped
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub async fn bind(self, addr: impl ToSocketAddrs) -> Result<GatewayServer> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        info!("WebSocket gateway listening on {}", local_addr);
+        let task = tokio::spawn(accept(listener, self.node, Arc::new(self.types)));
+        Ok(GatewayServer { local_addr, task })
+    }
+}
+
+/// A running [`WebSocketGateway`]
+///
+/// Dropping it closes the listener and every client connection.
+pub struct GatewayServer {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl GatewayServer {
+    /// Address the gateway accepts clients on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for GatewayServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn accept(listener: TcpListener, node: Arc<Node>, types: Arc<TypeRegistry>) {
+    // Dropping the set when this task is aborted aborts every session
+    let mut sessions = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    sessions.spawn(serve(stream, peer, node.clone(), types.clone()));
+                }
+                Err(e) => warn!("Accepting WebSocket client failed: {}", e),
+            },
+            Some(_) = sessions.join_next(), if !sessions.is_empty() => {}
+        }
+    }
+}
+
+async fn serve(stream: TcpStream, peer: SocketAddr, node: Arc<Node>, types: Arc<TypeRegistry>) {
+    let socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            debug!("WebSocket handshake with {} failed: {}", peer, e);
+            return;
+        }
+    };
+    debug!("WebSocket client {} connected", peer);
+    let (mut sink, mut incoming) = socket.split();
+    let (outgoing, mut queued) = mpsc::channel::<Frame>(CLIENT_QUEUE_DEPTH);
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = queued.recv().await {
+            if sink.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session {
+        node,
+        types,
+        outgoing,
+        subscriptions: HashMap::new(),
+        advertisements: HashMap::new(),
+    };
+    while let Some(frame) = incoming.next().await {
+        match frame {
+            Ok(Frame::Text(text)) => session.handle(&text).await,
+            Ok(Frame::Close(_)) => break,
+            // Pings are answered by tungstenite
+            Ok(_) => {}
+            Err(e) => {
+                debug!("WebSocket client {} failed: {}", peer, e);
+                break;
+            }
+        }
+    }
+    drop(session);
+    writer.abort();
+    debug!("WebSocket client {} disconnected", peer);
+}
+
+/// A client's subscriptions and advertisements
+struct Session {
+    node: Arc<Node>,
+    types: Arc<TypeRegistry>,
+    outgoing: mpsc::Sender<Frame>,
+    /// Forwarding task per topic, keyed by the name the client used
+    subscriptions: HashMap<String, JoinHandle<()>>,
+    advertisements: HashMap<String, Advertisement>,
+}
+
+struct Advertisement {
+    msg_type: String,
+    publisher: Publisher<RawMessage>,
+}
+
+impl Session {
+    async fn handle(&mut self, text: &str) {
+        let op = match serde_json::from_str::<ClientOp>(text) {
+            Ok(op) => op,
+            Err(e) => {
+                let msg = format!("Invalid request: {}", e);
+                send_status(&self.outgoing, None, StatusLevel::Error, msg);
+                return;
+            }
+        };
+        let id = op.id().map(str::to_string);
+        if let Err(e) = self.apply(op).await {
+            send_status(&self.outgoing, id, StatusLevel::Error, e.to_string());
+        }
+    }
+
+    async fn apply(&mut self, op: ClientOp) -> Result<()> {
+        match op {
+            ClientOp::Subscribe { topic, max_rate_hz, .. } => self.subscribe(topic, max_rate_hz),
+            ClientOp::Unsubscribe { topic, .. } => match self.subscriptions.remove(&topic) {
+                Some(task) => {
+                    task.abort();
+                    Ok(())
+                }
+                None => Err(Error::Protocol(format!("Not subscribed to '{}'", topic))),
+            },
+            ClientOp::Advertise { topic, msg_type, .. } => self.advertise(topic, msg_type),
+            ClientOp::Unadvertise { topic, .. } => match self.advertisements.remove(&topic) {
+                Some(_) => Ok(()),
+                None => Err(Error::Protocol(format!("'{}' is not advertised", topic))),
+            },
+            ClientOp::Publish { topic, msg, .. } => {
+                let advertisement = self.advertisements.get(&topic).ok_or_else(|| {
+                    Error::Protocol(format!("Advertise '{}' before publishing on it", topic))
+                })?;
+                let raw = self.types.decode_json(&advertisement.msg_type, msg)?;
+                advertisement.publisher.publish(&raw).await?;
+                Ok(())
+            }
+        }
+    }
+
+    fn subscribe(&mut self, topic: String, max_rate_hz: Option<f64>) -> Result<()> {
+        let period = match max_rate_hz {
+            Some(hz) if !(hz.is_finite() && hz > 0.0) => {
+                return Err(Error::Protocol(format!(
+                    "max_rate_hz must be a positive number, got {}",
+                    hz
+                )));
+            }
+            Some(hz) => Some(Duration::from_secs_f64(1.0 / hz)),
+            None => None,
+        };
+        // Best effort so the client can also join best-effort topics
+        let qos = QosProfile {
+            reliability: Reliability::BestEffort,
+            ..Default::default()
+        };
+        let subscriber = self.node.create_subscriber_with_qos::<RawMessage>(&topic, qos)?;
+        let task = tokio::spawn(forward(
+            subscriber,
+            topic.clone(),
+            period,
+            self.types.clone(),
+            self.outgoing.clone(),
+        ));
+        if let Some(previous) = self.subscriptions.insert(topic, task) {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    fn advertise(&mut self, topic: String, msg_type: String) -> Result<()> {
+        if let Some(existing) = self.advertisements.get(&topic) {
+            if existing.msg_type == msg_type {
+                return Ok(());
+            }
+            return Err(Error::Protocol(format!(
+                "'{}' is already advertised with type '{}'",
+                topic, existing.msg_type
+            )));
+        }
+        let publisher = self.node.create_publisher::<RawMessage>(&topic)?;
+        self.advertisements.insert(topic, Advertisement { msg_type, publisher });
+        Ok(())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for task in self.subscriptions.values() {
+            task.abort();
+        }
+    }
+}
+
+/// Send messages from `subscriber` to the client, at most one per `period`
+///
+/// When messages arrive faster than that, only the newest one is sent once
+/// the period is over.
+async fn forward(
+    subscriber: Subscriber<RawMessage>,
+    topic: String,
+    period: Option<Duration>,
+    types: Arc<TypeRegistry>,
+    outgoing: mpsc::Sender<Frame>,
+) {
+    let mut next_send = Instant::now();
+    let mut latest = None;
+    let mut reported = false;
+    loop {
+        let received = match latest.is_some() {
+            true => tokio::select! {
+                received = subscriber.recv() => Some(received),
+                () = tokio::time::sleep_until(next_send) => None,
+            },
+            false => Some(subscriber.recv().await),
+        };
+        match received {
+            Some(Ok(raw)) => latest = Some(raw),
+            Some(Err(agentic_robotics_core::Error::Closed(_))) => return,
+            Some(Err(e)) => warn!("Receiving '{}' for WebSocket client failed: {}", topic, e),
+            None => {}
+        }
+
+        let now = Instant::now();
+        if now < next_send {
+            continue;
+        }
+        let Some(raw) = latest.take() else {
+            continue;
+        };
+        if let Some(period) = period {
+            next_send = now + period;
+        }
+        let msg = match types.to_json(&topic, &raw) {
+            Ok(msg) => msg,
+            Err(e) => {
+                // Every later message would fail the same way
+                if !reported {
+                    reported = true;
+                    send_status(&outgoing, None, StatusLevel::Warning, e.to_string());
+                }
+                continue;
+            }
+        };
+        let frame = ServerOp::Publish {
+            topic: topic.clone(),
+            msg_type: raw.type_name,
+            msg,
+        };
+        match send(&outgoing, &frame) {
+            Err(TrySendError::Full(_)) => debug!("WebSocket client behind, dropped '{}'", topic),
+            Err(TrySendError::Closed(_)) => return,
+            Ok(()) => {}
+        }
+    }
+}
+
+/// Queue a frame for the client without waiting for it to catch up
+fn send(
+    outgoing: &mpsc::Sender<Frame>,
+    op: &ServerOp,
+) -> std::result::Result<(), TrySendError<Frame>> {
+    let text = serde_json::to_string(op).expect("ServerOp serializes to JSON");
+    outgoing.try_send(Frame::text(text))
+}
+
+fn send_status(
+    outgoing: &mpsc::Sender<Frame>,
+    id: Option<String>,
+    level: StatusLevel,
+    msg: String,
+) {
+    if let Err(TrySendError::Full(_)) = send(outgoing, &ServerOp::Status { id, level, msg }) {
+        debug!("WebSocket client behind, dropped status");
+    }
+}
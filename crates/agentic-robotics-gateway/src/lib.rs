@@ -0,0 +1,18 @@
+//! WebSocket gateway for browsers
+//!
+//! Lets web dashboards follow live robot state and send commands without a
+//! custom server. Clients speak a rosbridge-style JSON protocol (see
+//! [`protocol`]): they subscribe to topics, optionally capped at
+//! `max_rate_hz`, and receive each message as JSON, or advertise topics and
+//! publish JSON messages on them. Messages are converted with their
+//! registered Rust type; self-describing (JSON and MessagePack) messages need
+//! no registration.
+
+mod codec;
+pub mod error;
+pub mod gateway;
+pub mod protocol;
+
+pub use error::{Error, Result};
+pub use gateway::{GatewayServer, WebSocketGateway, DEFAULT_PORT, NODE_NAME};
+pub use protocol::{ClientOp, ServerOp, StatusLevel};
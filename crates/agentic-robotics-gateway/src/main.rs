@@ -0,0 +1,31 @@
+//! `ros3-gateway [address]`
+//!
+//! Serves topics of nodes on the default UDP transport to WebSocket clients
+//! until interrupted. Listens on `0.0.0.0:9090` unless another address is
+//! given.
+
+use agentic_robotics_core::{Node, TransportConfig, UdpConfig};
+use agentic_robotics_gateway::{WebSocketGateway, DEFAULT_PORT, NODE_NAME};
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let _ = agentic_robotics_core::init();
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| format!("0.0.0.0:{}", DEFAULT_PORT));
+    let result = async {
+        let node = Node::new(NODE_NAME)?
+            .with_transport(TransportConfig::Udp(UdpConfig::default()))?;
+        let _server = WebSocketGateway::with_node(node).bind(addr.as_str()).await?;
+        tokio::signal::ctrl_c().await?;
+        Ok::<_, agentic_robotics_gateway::Error>(())
+    };
+    match result.await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ros3-gateway: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
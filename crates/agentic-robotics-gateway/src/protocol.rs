@@ -0,0 +1,172 @@
+//! JSON operations exchanged with WebSocket clients
+//!
+//! Every frame is a JSON object whose `op` field names the operation, in the
+//! style of rosbridge:
+//!
+//! ```json
+//! {"op": "subscribe", "topic": "/robot_state", "max_rate_hz": 10}
+//! {"op": "unsubscribe", "topic": "/robot_state"}
+//! {"op": "advertise", "topic": "/cmd", "type": "ros3_msgs/RobotState"}
+//! {"op": "publish", "topic": "/cmd", "msg": {"position": [1, 0, 0], ...}}
+//! {"op": "unadvertise", "topic": "/cmd"}
+//! ```
+//!
+//! The gateway answers with `publish` frames carrying messages of subscribed
+//! topics, and with `status` frames when a request fails. Requests may carry
+//! an `id`, which is echoed in the status frames they cause.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A request sent by a client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientOp {
+    /// Start receiving messages on `topic`, at most `max_rate_hz` per second
+    ///
+    /// Subscribing again to the same topic replaces the rate limit.
+    Subscribe {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        topic: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_rate_hz: Option<f64>,
+    },
+    /// Stop receiving messages on `topic`
+    Unsubscribe {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        topic: String,
+    },
+    /// Declare that the client will publish messages of `msg_type` on `topic`
+    Advertise {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        topic: String,
+        #[serde(rename = "type")]
+        msg_type: String,
+    },
+    /// Withdraw an advertisement
+    Unadvertise {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        topic: String,
+    },
+    /// Publish `msg` on a previously advertised topic
+    Publish {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        topic: String,
+        msg: Value,
+    },
+}
+
+impl ClientOp {
+    /// The request id, if the client set one
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            ClientOp::Subscribe { id, .. }
+            | ClientOp::Unsubscribe { id, .. }
+            | ClientOp::Advertise { id, .. }
+            | ClientOp::Unadvertise { id, .. }
+            | ClientOp::Publish { id, .. } => id.as_deref(),
+        }
+    }
+}
+
+/// A frame sent by the gateway
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ServerOp {
+    /// A message received on a subscribed topic
+    Publish {
+        topic: String,
+        #[serde(rename = "type")]
+        msg_type: String,
+        msg: Value,
+    },
+    /// Outcome of a request that could not be carried out as asked
+    Status {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        level: StatusLevel,
+        msg: String,
+    },
+}
+
+/// Severity of a [`ServerOp::Status`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_client_ops() {
+        let op: ClientOp = serde_json::from_value(json!({
+            "op": "subscribe",
+            "topic": "/robot_state",
+            "max_rate_hz": 10
+        }))
+        .unwrap();
+        assert_eq!(
+            op,
+            ClientOp::Subscribe {
+                id: None,
+                topic: "/robot_state".into(),
+                max_rate_hz: Some(10.0),
+            }
+        );
+
+        let op: ClientOp = serde_json::from_value(json!({
+            "op": "advertise",
+            "id": "adv-1",
+            "topic": "/cmd",
+            "type": "ros3_msgs/RobotState"
+        }))
+        .unwrap();
+        assert_eq!(op.id(), Some("adv-1"));
+        assert!(matches!(
+            op,
+            ClientOp::Advertise { msg_type, .. } if msg_type == "ros3_msgs/RobotState"
+        ));
+
+        let unknown = serde_json::from_value::<ClientOp>(json!({"op": "call_service"}));
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn test_server_op_format() {
+        let frame = ServerOp::Publish {
+            topic: "/robot_state".into(),
+            msg_type: "ros3_msgs/RobotState".into(),
+            msg: json!({"timestamp": 1}),
+        };
+        assert_eq!(
+            serde_json::to_value(&frame).unwrap(),
+            json!({
+                "op": "publish",
+                "topic": "/robot_state",
+                "type": "ros3_msgs/RobotState",
+                "msg": {"timestamp": 1}
+            })
+        );
+
+        let status = ServerOp::Status {
+            id: None,
+            level: StatusLevel::Error,
+            msg: "not advertised".into(),
+        };
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            json!({"op": "status", "level": "error", "msg": "not advertised"})
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! A browser-style client talking to the gateway over a real WebSocket
+
+use agentic_robotics_core::{Node, RobotState, TopicBus};
+use agentic_robotics_gateway::{GatewayServer, ServerOp, StatusLevel, WebSocketGateway};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Instant};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn start() -> (GatewayServer, Client) {
+    let server = WebSocketGateway::new()
+        .unwrap()
+        .bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    let url = format!("ws://{}", server.local_addr());
+    let (client, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+    (server, client)
+}
+
+async fn send(client: &mut Client, request: Value) {
+    client.send(Message::text(request.to_string())).await.unwrap();
+}
+
+/// The next frame from the gateway, or `None` if none arrives within `wait`
+async fn next_op(client: &mut Client, wait: Duration) -> Option<ServerOp> {
+    loop {
+        let frame = timeout(wait, client.next()).await.ok()??.unwrap();
+        if let Message::Text(text) = frame {
+            return Some(serde_json::from_str(&text).unwrap());
+        }
+    }
+}
+
+async fn wait_for_subscribers(topic: &str, count: usize) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while TopicBus::global().subscriber_count(topic) != count {
+        assert!(Instant::now() < deadline, "'{}' never had {} subscribers", topic, count);
+        sleep(Duration::from_millis(5)).await;
+    }
+}
+
+fn state(timestamp: i64) -> RobotState {
+    RobotState {
+        position: [1.0, 2.0, 3.0],
+        timestamp,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_subscribe_receives_json() {
+    let (_server, mut client) = start().await;
+    let node = Node::new("ws_test_robot").unwrap();
+    let publisher = node.create_publisher::<RobotState>("/ws_test/state").unwrap();
+
+    send(&mut client, json!({"op": "subscribe", "topic": "/ws_test/state"})).await;
+    wait_for_subscribers("/ws_test/state", 1).await;
+    publisher.publish(&state(42)).await.unwrap();
+
+    match next_op(&mut client, Duration::from_secs(5)).await {
+        Some(ServerOp::Publish { topic, msg_type, msg }) => {
+            assert_eq!(topic, "/ws_test/state");
+            assert_eq!(msg_type, "ros3_msgs/RobotState");
+            assert_eq!(msg["position"], json!([1.0, 2.0, 3.0]));
+            assert_eq!(msg["timestamp"], 42);
+        }
+        other => panic!("expected a message, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_max_rate_throttles_and_keeps_newest() {
+    let (_server, mut client) = start().await;
+    let node = Node::new("ws_test_imu").unwrap();
+    let publisher = node.create_publisher::<RobotState>("/ws_test/imu").unwrap();
+
+    send(
+        &mut client,
+        json!({"op": "subscribe", "topic": "/ws_test/imu", "max_rate_hz": 10}),
+    )
+    .await;
+    wait_for_subscribers("/ws_test/imu", 1).await;
+
+    // About 1 kHz for 300 ms
+    let started = Instant::now();
+    for timestamp in 0..300 {
+        publisher.publish(&state(timestamp)).await.unwrap();
+        sleep(Duration::from_millis(1)).await;
+    }
+    let publishing = started.elapsed();
+
+    let mut received = Vec::new();
+    while let Some(op) = next_op(&mut client, Duration::from_millis(400)).await {
+        match op {
+            ServerOp::Publish { msg, .. } => received.push(msg["timestamp"].as_i64().unwrap()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    // One message right away, then at most one per 100 ms
+    let allowed = 2 + (publishing.as_secs_f64() * 10.0) as usize;
+    assert!(received.len() >= 2, "received {:?}", received);
+    assert!(received.len() <= allowed, "received {} > {}", received.len(), allowed);
+    assert_eq!(received[0], 0);
+    assert_eq!(received.last(), Some(&299), "the newest message is sent last");
+    assert!(received.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[tokio::test]
+async fn test_unsubscribe_stops_messages() {
+    let (_server, mut client) = start().await;
+    let node = Node::new("ws_test_odom").unwrap();
+    let publisher = node.create_publisher::<RobotState>("/ws_test/odom").unwrap();
+
+    send(&mut client, json!({"op": "subscribe", "topic": "/ws_test/odom"})).await;
+    wait_for_subscribers("/ws_test/odom", 1).await;
+    publisher.publish(&state(1)).await.unwrap();
+    assert!(matches!(
+        next_op(&mut client, Duration::from_secs(5)).await,
+        Some(ServerOp::Publish { .. })
+    ));
+
+    send(&mut client, json!({"op": "unsubscribe", "topic": "/ws_test/odom"})).await;
+    wait_for_subscribers("/ws_test/odom", 0).await;
+    publisher.publish(&state(2)).await.unwrap();
+    assert_eq!(next_op(&mut client, Duration::from_millis(200)).await, None);
+
+    // Unsubscribing twice is reported
+    send(
+        &mut client,
+        json!({"op": "unsubscribe", "id": "again", "topic": "/ws_test/odom"}),
+    )
+    .await;
+    match next_op(&mut client, Duration::from_secs(5)).await {
+        Some(ServerOp::Status { id, level, .. }) => {
+            assert_eq!(id.as_deref(), Some("again"));
+            assert_eq!(level, StatusLevel::Error);
+        }
+        other => panic!("expected a status, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_advertise_and_publish() {
+    let (_server, mut client) = start().await;
+    let node = Node::new("ws_test_base").unwrap();
+    let subscriber = node.create_subscriber::<RobotState>("/ws_test/cmd").unwrap();
+
+    send(
+        &mut client,
+        json!({"op": "publish", "id": "early", "topic": "/ws_test/cmd", "msg": {}}),
+    )
+    .await;
+    match next_op(&mut client, Duration::from_secs(5)).await {
+        Some(ServerOp::Status { id, level, .. }) => {
+            assert_eq!(id.as_deref(), Some("early"));
+            assert_eq!(level, StatusLevel::Error);
+        }
+        other => panic!("expected a status, got {:?}", other),
+    }
+
+    send(
+        &mut client,
+        json!({"op": "advertise", "topic": "/ws_test/cmd", "type": "ros3_msgs/RobotState"}),
+    )
+    .await;
+    send(
+        &mut client,
+        json!({
+            "op": "publish",
+            "topic": "/ws_test/cmd",
+            "msg": {"position": [0.5, 0.0, 0.0], "velocity": [0.0, 0.0, 0.0], "timestamp": 9}
+        }),
+    )
+    .await;
+
+    let received = timeout(Duration::from_secs(5), subscriber.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received.position, [0.5, 0.0, 0.0]);
+    assert_eq!(received.timestamp, 9);
+}
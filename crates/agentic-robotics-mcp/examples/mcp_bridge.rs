@@ -0,0 +1,93 @@
+//! Bridge an external MCP tool onto a ros3 topic
+//!
+//! ```sh
+//! cargo run -p agentic-robotics-mcp --example mcp_bridge -- \
+//!     http://127.0.0.1:8080/mcp get_forecast '{"site":"yard"}' /weather
+//! cargo run -p agentic-robotics-mcp --example mcp_bridge -- \
+//!     "python3 weather_server.py" get_forecast '{"site":"yard"}' /weather
+//! ```
+//!
+//! A `mcp_bridge` node connects to the MCP server at the URL, or spawns the
+//! command and talks to it over stdio, then calls the tool every five
+//! seconds and publishes each result as JSON on the topic, until Ctrl-C.
+//! Failed calls are logged and retried on the next tick.
+
+use agentic_robotics_core::serialization::Format;
+use agentic_robotics_core::Node;
+use agentic_robotics_mcp::client::McpClient;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::process::Command;
+
+const USAGE: &str = "usage: mcp_bridge URL|COMMAND TOOL [ARGUMENTS] [TOPIC]";
+
+const PERIOD: Duration = Duration::from_secs(5);
+
+async fn connect(server: &str) -> Result<McpClient> {
+    if server.starts_with("http://") || server.starts_with("https://") {
+        return McpClient::connect_http(server).await;
+    }
+    let mut words = server.split_whitespace();
+    let mut command = Command::new(words.next().context(USAGE)?);
+    command.args(words);
+    McpClient::connect_stdio(command).await
+}
+
+async fn run() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (server, tool) = match args.as_slice() {
+        [server, tool, ..] => (server.as_str(), tool.clone()),
+        _ => anyhow::bail!(USAGE),
+    };
+    let arguments: Value = match args.get(2) {
+        Some(arguments) => serde_json::from_str(arguments).context("ARGUMENTS must be JSON")?,
+        None => Value::Object(Default::default()),
+    };
+    let topic = args.get(3).map_or("/mcp_bridge", String::as_str);
+
+    let node = Node::new("mcp_bridge")?;
+    let publisher = node.create_publisher_with_format::<Value>(topic, Format::Json)?;
+    let client = connect(server).await?;
+    let info = client.server_info();
+    println!(
+        "Connected to {} {}, bridging {} onto {}",
+        info.name, info.version, tool, topic
+    );
+
+    let bridge = tokio::spawn(async move {
+        let mut tick = tokio::time::interval(PERIOD);
+        loop {
+            tick.tick().await;
+            match client.call_tool_result(&tool, arguments.clone()).await {
+                Ok(result) if result.is_error == Some(true) => {
+                    eprintln!("mcp_bridge: tool failed: {}", result.text());
+                }
+                Ok(result) => {
+                    println!("{}", result.text());
+                    let message = serde_json::to_value(&result).unwrap_or_default();
+                    if let Err(e) = publisher.publish(&message).await {
+                        eprintln!("mcp_bridge: publish failed: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("mcp_bridge: {:#}", e),
+            }
+        }
+    });
+
+    tokio::signal::ctrl_c().await?;
+    bridge.abort();
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mcp_bridge: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
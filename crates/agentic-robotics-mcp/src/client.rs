@@ -0,0 +1,876 @@
+//! MCP client for calling the tools of other servers
+//!
+//! An [`McpClient`] lets a robot-side agent use an external MCP server, a
+//! planner or a map service say, over either transport the crate serves:
+//!
+//! - [`McpClient::connect_stdio`] spawns the server and exchanges
+//!   newline-delimited JSON-RPC with it over its stdin and stdout; the
+//!   child is killed when the last clone of the client is dropped.
+//! - [`McpClient::connect_http`] POSTs each message to a Streamable HTTP
+//!   endpoint, keeping the session id the server hands out.
+//!
+//! Both perform the `initialize` handshake before returning and fail with
+//! [`ClientError::UnsupportedVersion`] if the server answers with a
+//! protocol version not in [`SUPPORTED_VERSIONS`].
+//!
+//! Every request is bounded by [`ClientConfig::timeout`]; one that runs out
+//! fails with [`ClientError::Timeout`] and is cancelled on the server. Over
+//! HTTP, failed connection attempts are retried up to
+//! [`ClientConfig::retries`] times, and a session the server no longer
+//! knows, after a restart say, is replaced by initializing again.
+//!
+//! The client is cheap to clone and can be moved into a ros3 node's tasks:
+//!
+//! ```no_run
+//! # use agentic_robotics_mcp::client::McpClient;
+//! # use agentic_robotics_core::{serialization::Format, Node};
+//! # use serde_json::{json, Value};
+//! # async fn example() -> anyhow::Result<()> {
+//! let node = Node::new("planner_bridge")?;
+//! let plans = node.create_publisher_with_format::<Value>("/plan", Format::Json)?;
+//! let planner = McpClient::connect_http("http://planner.local:8080/mcp").await?;
+//! tokio::spawn(async move {
+//!     let result = planner
+//!         .call_tool_result("plan_route", json!({ "goal": "dock" }))
+//!         .await?;
+//!     plans.publish(&json!({ "plan": result.text() })).await?;
+//!     anyhow::Ok(())
+//! });
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::http::SESSION_HEADER;
+use crate::{
+    ContentItem, McpNotification, McpRequest, McpResource, McpResponse, McpTool, ResourceContents,
+    ServerInfo, ToolResult, MCP_VERSION, SUPPORTED_VERSIONS,
+};
+use agentic_robotics_core::TlsConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::TlsConnector;
+
+/// Notifications buffered before a slow receiver starts missing them
+const NOTIFICATION_BUFFER: usize = 64;
+
+/// Delay before the first retry of a failed HTTP connection, doubled for
+/// every further one
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How an [`McpClient`] introduces itself and talks to the server
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// `clientInfo.name` sent with `initialize`
+    pub name: String,
+    /// `clientInfo.version` sent with `initialize`
+    pub version: String,
+    /// Longest a request may take, including HTTP retries
+    pub timeout: Duration,
+    /// Failed HTTP connection attempts retried per request
+    pub retries: u32,
+    /// Sent as `Authorization: Bearer` with every HTTP request
+    pub bearer_token: Option<String>,
+    /// Authorities `https` servers must chain to, and the certificate to
+    /// present for mutual TLS; required for `https` URLs
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            timeout: Duration::from_secs(30),
+            retries: 3,
+            bearer_token: None,
+            tls: None,
+        }
+    }
+}
+
+/// Failures of an [`McpClient`] callers may want to tell apart, carried in
+/// the [`anyhow::Error`]s it returns
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ClientError {
+    #[error(
+        "MCP server speaks protocol version {server}, this client supports {}",
+        SUPPORTED_VERSIONS.join(", ")
+    )]
+    UnsupportedVersion { server: String },
+    #[error("MCP request {method} timed out after {timeout:?}")]
+    Timeout { method: String, timeout: Duration },
+    /// The server answered with a JSON-RPC error
+    #[error("MCP request {method} failed with error {code}: {message}")]
+    Server {
+        method: String,
+        code: i32,
+        message: String,
+    },
+    #[error("MCP connection closed")]
+    Closed,
+    /// The server no longer knows the HTTP session
+    #[error("MCP session expired")]
+    SessionExpired,
+}
+
+/// A connection to an MCP server, see the [module documentation](self)
+#[derive(Clone)]
+pub struct McpClient {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    transport: Transport,
+    config: ClientConfig,
+    next_id: AtomicU64,
+    /// The server's answer to the latest `initialize`
+    server: Mutex<Option<Initialized>>,
+    notifications: broadcast::Sender<McpNotification>,
+}
+
+#[derive(Clone)]
+struct Initialized {
+    protocol_version: String,
+    server_info: ServerInfo,
+    capabilities: Value,
+}
+
+enum Transport {
+    Lines(Lines),
+    Http(Http),
+}
+
+impl McpClient {
+    /// Spawn `command` and talk to it over its stdin and stdout
+    ///
+    /// The child's stderr is inherited, so its logs show up alongside ours.
+    pub async fn connect_stdio(command: Command) -> Result<Self> {
+        Self::connect_stdio_with(command, ClientConfig::default()).await
+    }
+
+    /// [`connect_stdio`](Self::connect_stdio) with `config`
+    pub async fn connect_stdio_with(mut command: Command, config: ClientConfig) -> Result<Self> {
+        let mut child = command
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("failed to spawn MCP server")?;
+        let stdin = child.stdin.take().context("MCP server has no stdin")?;
+        let stdout = child.stdout.take().context("MCP server has no stdout")?;
+        let (notifications, _) = broadcast::channel(NOTIFICATION_BUFFER);
+        let lines = Lines::new(stdout, stdin, Some(child), notifications.clone());
+        Self::start(Transport::Lines(lines), notifications, config).await
+    }
+
+    /// Talk newline-delimited JSON-RPC over `reader` and `writer`, for
+    /// servers reached by other means than a child process
+    pub async fn connect<R, W>(reader: R, writer: W, config: ClientConfig) -> Result<Self>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (notifications, _) = broadcast::channel(NOTIFICATION_BUFFER);
+        let lines = Lines::new(reader, writer, None, notifications.clone());
+        Self::start(Transport::Lines(lines), notifications, config).await
+    }
+
+    /// Talk to the Streamable HTTP endpoint at `url`, such as
+    /// `http://robot.local:8080/mcp`
+    pub async fn connect_http(url: &str) -> Result<Self> {
+        Self::connect_http_with(url, ClientConfig::default()).await
+    }
+
+    /// [`connect_http`](Self::connect_http) with `config`
+    pub async fn connect_http_with(url: &str, config: ClientConfig) -> Result<Self> {
+        let http = Http::new(url, &config)?;
+        let (notifications, _) = broadcast::channel(NOTIFICATION_BUFFER);
+        Self::start(Transport::Http(http), notifications, config).await
+    }
+
+    async fn start(
+        transport: Transport,
+        notifications: broadcast::Sender<McpNotification>,
+        config: ClientConfig,
+    ) -> Result<Self> {
+        let client = Self {
+            inner: Arc::new(Inner {
+                transport,
+                config,
+                next_id: AtomicU64::new(1),
+                server: Mutex::new(None),
+                notifications,
+            }),
+        };
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Name and version the server gave in the handshake
+    pub fn server_info(&self) -> ServerInfo {
+        self.initialized().server_info
+    }
+
+    /// Protocol version agreed on in the handshake
+    pub fn protocol_version(&self) -> String {
+        self.initialized().protocol_version
+    }
+
+    /// The `capabilities` the server announced in the handshake
+    pub fn capabilities(&self) -> Value {
+        self.initialized().capabilities
+    }
+
+    /// Notifications from the server: over stdio all of them, over HTTP
+    /// those streamed ahead of a response, such as progress reports
+    pub fn notifications(&self) -> broadcast::Receiver<McpNotification> {
+        self.inner.notifications.subscribe()
+    }
+
+    /// Tools the server offers
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>> {
+        self.list("tools/list", "tools").await
+    }
+
+    /// Call tool `name` with `arguments`
+    ///
+    /// A tool that failed is still a successful response, with `isError`
+    /// set in its result; an unknown tool or invalid arguments are an error
+    /// response.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<McpResponse> {
+        let params = json!({ "name": name, "arguments": arguments });
+        self.request("tools/call", Some(params)).await
+    }
+
+    /// Call tool `name` with `arguments` and decode its result
+    ///
+    /// Fails with [`ClientError::Server`] on an error response; a result
+    /// with `isError` set is returned as it is.
+    pub async fn call_tool_result(&self, name: &str, arguments: Value) -> Result<ToolResult> {
+        let response = self.call_tool(name, arguments).await?;
+        decode(result("tools/call", response)?)
+    }
+
+    /// Resources the server offers
+    pub async fn list_resources(&self) -> Result<Vec<McpResource>> {
+        self.list("resources/list", "resources").await
+    }
+
+    /// Read the resource at `uri`
+    pub async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>> {
+        let response = self
+            .request("resources/read", Some(json!({ "uri": uri })))
+            .await?;
+        decode(result("resources/read", response)?["contents"].take())
+    }
+
+    /// Send request `method` and wait for its response
+    pub async fn request(&self, method: &str, params: Option<Value>) -> Result<McpResponse> {
+        match self.send(method, params.clone()).await {
+            Err(e) if is(&e, &ClientError::SessionExpired) => {
+                tracing::info!("MCP session expired, initializing again");
+                self.initialize().await?;
+                self.send(method, params).await
+            }
+            response => response,
+        }
+    }
+
+    /// Send notification `method`
+    pub async fn notify(&self, method: &str, params: Option<Value>) -> Result<()> {
+        let notification = McpNotification {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params,
+        };
+        let message = serde_json::to_value(&notification)?;
+        match &self.inner.transport {
+            Transport::Lines(lines) => lines.send(&message),
+            Transport::Http(http) => {
+                let timeout = self.inner.config.timeout;
+                tokio::time::timeout(timeout, http.post(&message, &self.inner.config))
+                    .await
+                    .map_err(|_| ClientError::Timeout {
+                        method: method.to_string(),
+                        timeout,
+                    })??;
+                Ok(())
+            }
+        }
+    }
+
+    /// Every page of a `*/list` method's `key`
+    async fn list<T: DeserializeOwned>(&self, method: &str, key: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<Value> = None;
+        loop {
+            let params = cursor.map(|cursor| json!({ "cursor": cursor }));
+            let mut page = result(method, self.request(method, params).await?)?;
+            items.extend(decode::<Vec<T>>(page[key].take())?);
+            cursor = page
+                .get("nextCursor")
+                .filter(|cursor| !cursor.is_null())
+                .cloned();
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+
+    /// Send one request within the timeout, without renewing the session
+    async fn send(&self, method: &str, params: Option<Value>) -> Result<McpResponse> {
+        let id = json!(self.inner.next_id.fetch_add(1, Ordering::Relaxed));
+        let request = McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(id.clone()),
+            method: method.to_string(),
+            params,
+        };
+        let message = serde_json::to_value(&request)?;
+        let timeout = self.inner.config.timeout;
+        let timed_out = || ClientError::Timeout {
+            method: method.to_string(),
+            timeout,
+        };
+        match &self.inner.transport {
+            Transport::Lines(lines) => {
+                let response = lines.request(&id, &message)?;
+                match tokio::time::timeout(timeout, response).await {
+                    Ok(response) => response.map_err(|_| ClientError::Closed.into()),
+                    Err(_) => {
+                        lines.forget(&id);
+                        let cancelled = json!({ "requestId": id, "reason": "timed out" });
+                        let _ = self
+                            .notify("notifications/cancelled", Some(cancelled))
+                            .await;
+                        Err(timed_out().into())
+                    }
+                }
+            }
+            // Dropping the connection cancels the request on the server
+            Transport::Http(http) => {
+                let call = http.call(&id, &message, &self.inner.config, &self.inner.notifications);
+                tokio::time::timeout(timeout, call)
+                    .await
+                    .map_err(|_| timed_out())?
+            }
+        }
+    }
+
+    async fn initialize(&self) -> Result<()> {
+        if let Transport::Http(http) = &self.inner.transport {
+            *http.session.lock().unwrap() = None;
+        }
+        let params = json!({
+            "protocolVersion": MCP_VERSION,
+            "capabilities": {},
+            "clientInfo": {
+                "name": self.inner.config.name,
+                "version": self.inner.config.version,
+            },
+        });
+        let mut answer = result("initialize", self.send("initialize", Some(params)).await?)?;
+        let version = answer["protocolVersion"].as_str().unwrap_or_default();
+        if !SUPPORTED_VERSIONS.contains(&version) {
+            return Err(ClientError::UnsupportedVersion {
+                server: version.to_string(),
+            }
+            .into());
+        }
+        let initialized = Initialized {
+            protocol_version: version.to_string(),
+            server_info: decode(answer["serverInfo"].take())?,
+            capabilities: answer["capabilities"].take(),
+        };
+        tracing::info!(
+            "Connected to MCP server {} {}, protocol {}",
+            initialized.server_info.name,
+            initialized.server_info.version,
+            initialized.protocol_version
+        );
+        *self.inner.server.lock().unwrap() = Some(initialized);
+        self.notify("notifications/initialized", None).await
+    }
+
+    fn initialized(&self) -> Initialized {
+        self.inner
+            .server
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("clients are initialized when connected")
+    }
+}
+
+impl ToolResult {
+    /// The text items of the result, one per line
+    pub fn text(&self) -> String {
+        let texts: Vec<&str> = self
+            .content
+            .iter()
+            .filter_map(|item| match item {
+                ContentItem::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        texts.join("\n")
+    }
+}
+
+/// The result of `response` to `method`, or its error
+fn result(method: &str, response: McpResponse) -> Result<Value> {
+    if let Some(error) = response.error {
+        return Err(ClientError::Server {
+            method: method.to_string(),
+            code: error.code,
+            message: error.message,
+        }
+        .into());
+    }
+    response
+        .result
+        .ok_or_else(|| anyhow!("MCP response to {} has neither result nor error", method))
+}
+
+fn decode<T: DeserializeOwned>(value: Value) -> Result<T> {
+    serde_json::from_value(value).context("unexpected MCP response")
+}
+
+fn is(error: &anyhow::Error, kind: &ClientError) -> bool {
+    error.downcast_ref::<ClientError>() == Some(kind)
+}
+
+/// Newline-delimited JSON-RPC over a pair of streams
+struct Lines {
+    outgoing: mpsc::UnboundedSender<String>,
+    /// Requests waiting for their response, by id
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<McpResponse>>>>,
+    tasks: Vec<JoinHandle<()>>,
+    /// Killed when dropped
+    _child: Option<Child>,
+}
+
+impl Lines {
+    fn new<R, W>(
+        reader: R,
+        writer: W,
+        child: Option<Child>,
+        notifications: broadcast::Sender<McpNotification>,
+    ) -> Self
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (outgoing, mut queued) = mpsc::unbounded_channel::<String>();
+        let writer = tokio::spawn(async move {
+            let mut writer = writer;
+            while let Some(message) = queued.recv().await {
+                let written = async {
+                    writer.write_all(message.as_bytes()).await?;
+                    writer.write_all(b"\n").await?;
+                    writer.flush().await
+                };
+                if let Err(e) = written.await {
+                    tracing::warn!("Failed to write to MCP server: {}", e);
+                    break;
+                }
+            }
+        });
+
+        let pending: Arc<Mutex<HashMap<String, oneshot::Sender<McpResponse>>>> = Arc::default();
+        let reader = {
+            let (pending, outgoing) = (pending.clone(), outgoing.clone());
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(reader).lines();
+                loop {
+                    let line = match lines.next_line().await {
+                        Ok(Some(line)) => line,
+                        Ok(None) => break,
+                        Err(e) => {
+                            tracing::warn!("Failed to read from MCP server: {}", e);
+                            break;
+                        }
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str::<Value>(&line) {
+                        Ok(message) => {
+                            receive(message, &pending, &outgoing, &notifications);
+                        }
+                        Err(e) => tracing::warn!("Invalid message from MCP server: {}", e),
+                    }
+                }
+                // Fails the requests still waiting
+                pending.lock().unwrap().clear();
+            })
+        };
+        Self {
+            outgoing,
+            pending,
+            tasks: vec![writer, reader],
+            _child: child,
+        }
+    }
+
+    fn send(&self, message: &Value) -> Result<()> {
+        self.outgoing
+            .send(message.to_string())
+            .map_err(|_| ClientError::Closed.into())
+    }
+
+    /// Send request `message` and return where its response will arrive
+    fn request(&self, id: &Value, message: &Value) -> Result<oneshot::Receiver<McpResponse>> {
+        let (sender, response) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.to_string(), sender);
+        if let Err(e) = self.send(message) {
+            self.forget(id);
+            return Err(e);
+        }
+        Ok(response)
+    }
+
+    /// Stop waiting for the response to request `id`
+    fn forget(&self, id: &Value) {
+        self.pending.lock().unwrap().remove(&id.to_string());
+    }
+}
+
+impl Drop for Lines {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Route a message read from the server
+fn receive(
+    message: Value,
+    pending: &Mutex<HashMap<String, oneshot::Sender<McpResponse>>>,
+    outgoing: &mpsc::UnboundedSender<String>,
+    notifications: &broadcast::Sender<McpNotification>,
+) {
+    let id = message.get("id").filter(|id| !id.is_null()).cloned();
+    match (message.get("method").and_then(Value::as_str), id) {
+        // Requests from the server; only `ping` is answered successfully
+        (Some(method), Some(id)) => {
+            let response = match method {
+                "ping" => McpResponse::success(Some(id), json!({})),
+                _ => McpResponse::error(
+                    Some(id),
+                    crate::error_codes::METHOD_NOT_FOUND,
+                    format!("Method not found: {}", method),
+                ),
+            };
+            if let Ok(response) = serde_json::to_string(&response) {
+                let _ = outgoing.send(response);
+            }
+        }
+        (Some(_), None) => match serde_json::from_value(message) {
+            Ok(notification) => {
+                let _ = notifications.send(notification);
+            }
+            Err(e) => tracing::warn!("Invalid notification from MCP server: {}", e),
+        },
+        (None, Some(id)) => match serde_json::from_value::<McpResponse>(message) {
+            Ok(response) => {
+                if let Some(waiting) = pending.lock().unwrap().remove(&id.to_string()) {
+                    let _ = waiting.send(response);
+                }
+            }
+            Err(e) => tracing::warn!("Invalid response from MCP server: {}", e),
+        },
+        (None, None) => tracing::warn!("MCP server sent a message without id or method"),
+    }
+}
+
+/// The Streamable HTTP transport's client side, one connection per message
+struct Http {
+    /// `host:port` as given in the URL, for the `Host` header
+    authority: String,
+    host: String,
+    port: u16,
+    path: String,
+    tls: Option<(TlsConnector, ServerName<'static>)>,
+    session: Mutex<Option<String>>,
+}
+
+/// A response read in full
+struct Reply {
+    status: u16,
+    /// Names in lowercase
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Http {
+    fn new(url: &str, config: &ClientConfig) -> Result<Self> {
+        let (secure, rest) = if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else {
+            bail!(
+                "MCP server URL must start with http:// or https://: {}",
+                url
+            );
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.ends_with(']') => (
+                host,
+                port.parse::<u16>()
+                    .with_context(|| format!("invalid port in MCP server URL {}", url))?,
+            ),
+            _ => (authority, if secure { 443 } else { 80 }),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            bail!("MCP server URL has no host: {}", url);
+        }
+
+        let tls = if secure {
+            let tls = config
+                .tls
+                .as_ref()
+                .with_context(|| format!("{} needs ClientConfig::tls", url))?;
+            let name = tls.server_name.clone().unwrap_or_else(|| host.to_string());
+            let name = ServerName::try_from(name)
+                .map_err(|e| anyhow!("invalid TLS server name: {}", e))?;
+            Some((tls.connector()?, name))
+        } else {
+            None
+        };
+        Ok(Self {
+            authority: authority.to_string(),
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            tls,
+            session: Mutex::new(None),
+        })
+    }
+
+    /// POST request `message` and return the response with id `id`,
+    /// passing notifications streamed ahead of it on to `notifications`
+    async fn call(
+        &self,
+        id: &Value,
+        message: &Value,
+        config: &ClientConfig,
+        notifications: &broadcast::Sender<McpNotification>,
+    ) -> Result<McpResponse> {
+        let reply = self.post(message, config).await?;
+        if message["method"] == "initialize" {
+            *self.session.lock().unwrap() = reply.header(SESSION_HEADER).map(str::to_string);
+        }
+        let is_stream = reply
+            .header("content-type")
+            .is_some_and(|content_type| content_type.starts_with("text/event-stream"));
+        let messages = if is_stream {
+            events(&reply.body)
+        } else {
+            vec![reply.body]
+        };
+
+        let mut response = None;
+        for message in messages {
+            let message: Value =
+                serde_json::from_str(&message).context("invalid message from MCP server")?;
+            if message.get("method").is_some() {
+                if let Ok(notification) = serde_json::from_value(message) {
+                    let _ = notifications.send(notification);
+                }
+            } else if message.get("id") == Some(id) {
+                response = Some(serde_json::from_value(message)?);
+            }
+        }
+        response.ok_or_else(|| anyhow!("MCP server sent no response to request {}", id))
+    }
+
+    /// POST `message` and check the status of the reply
+    async fn post(&self, message: &Value, config: &ClientConfig) -> Result<Reply> {
+        let body = message.to_string();
+        let session = self.session.lock().unwrap().clone();
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Accept: application/json, text/event-stream\r\nContent-Length: {}\r\n\
+             Connection: close\r\n",
+            self.path,
+            self.authority,
+            body.len()
+        );
+        if let Some(token) = &config.bearer_token {
+            head.push_str(&format!("Authorization: Bearer {}\r\n", token));
+        }
+        if let Some(session) = &session {
+            head.push_str(&format!("{}: {}\r\n", SESSION_HEADER, session));
+        }
+        head.push_str("\r\n");
+
+        let reply = self
+            .exchange(format!("{}{}", head, body), config.retries)
+            .await?;
+        match reply.status {
+            200..=299 => Ok(reply),
+            404 if session.is_some() => Err(ClientError::SessionExpired.into()),
+            401 => bail!(
+                "MCP server rejected the bearer token: {}",
+                reply.body.trim()
+            ),
+            status => bail!("MCP server answered {}: {}", status, reply.body.trim()),
+        }
+    }
+
+    /// Send `request` on a new connection and read the reply, retrying
+    /// connections that could not be made
+    async fn exchange(&self, request: String, retries: u32) -> Result<Reply> {
+        let mut attempt = 0;
+        let stream = loop {
+            match TcpStream::connect((self.host.as_str(), self.port)).await {
+                Ok(stream) => break stream,
+                Err(e) if attempt < retries => {
+                    let delay = RETRY_DELAY * 2u32.pow(attempt);
+                    tracing::debug!("MCP server unreachable ({}), retrying in {:?}", e, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("failed to connect to MCP server {}", self.authority)
+                    })
+                }
+            }
+        };
+        let raw = match &self.tls {
+            Some((connector, name)) => {
+                let stream = connector
+                    .connect(name.clone(), stream)
+                    .await
+                    .context("TLS handshake with MCP server failed")?;
+                round_trip(stream, request.as_bytes()).await?
+            }
+            None => round_trip(stream, request.as_bytes()).await?,
+        };
+        parse_reply(&raw)
+    }
+}
+
+async fn round_trip<S>(mut stream: S, request: &[u8]) -> Result<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(request).await?;
+    stream.flush().await?;
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).await?;
+    Ok(raw)
+}
+
+/// Parse a response of a server that closes the connection after it
+fn parse_reply(raw: &[u8]) -> Result<Reply> {
+    let end = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .context("incomplete response from MCP server")?;
+    let head = std::str::from_utf8(&raw[..end]).context("invalid response head")?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .context("invalid status line from MCP server")?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let reply = Reply {
+        status,
+        headers,
+        body: String::from_utf8_lossy(&raw[end + 4..]).into_owned(),
+    };
+    if reply
+        .header("transfer-encoding")
+        .is_some_and(|encoding| encoding.contains("chunked"))
+    {
+        bail!("chunked responses from MCP servers are not supported");
+    }
+    Ok(reply)
+}
+
+/// The data of the events in a `text/event-stream` body
+fn events(body: &str) -> Vec<String> {
+    body.replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|event| {
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect();
+            (!data.is_empty()).then(|| data.join("\n"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let config = ClientConfig::default();
+        let http = Http::new("http://robot.local:8080/mcp", &config).unwrap();
+        assert_eq!(
+            (http.host.as_str(), http.port, http.path.as_str()),
+            ("robot.local", 8080, "/mcp")
+        );
+        let http = Http::new("http://[::1]", &config).unwrap();
+        assert_eq!(
+            (http.host.as_str(), http.port, http.path.as_str()),
+            ("::1", 80, "/")
+        );
+        assert_eq!(http.authority, "[::1]");
+        assert!(Http::new("ws://robot.local/mcp", &config).is_err());
+        // https needs authorities to verify the server against
+        assert!(Http::new("https://robot.local/mcp", &config).is_err());
+    }
+
+    #[test]
+    fn test_parse_event_stream() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                    Mcp-Session-Id: abc\r\n\r\n\
+                    : keep-alive\n\n\
+                    event: message\ndata: {\"a\":1}\n\n\
+                    event: message\ndata: {\"b\":\ndata: 2}\n\n";
+        let reply = parse_reply(raw).unwrap();
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.header(SESSION_HEADER), Some("abc"));
+        assert_eq!(events(&reply.body), vec!["{\"a\":1}", "{\"b\":\n2}"]);
+    }
+}
@@ -0,0 +1,710 @@
+//! Streamable HTTP transport for remote MCP clients
+//!
+//! An [`HttpServer`] answers on a single path, `/mcp` by default:
+//!
+//! - `POST`: one JSON-RPC message from the client. A request is answered
+//!   with a `text/event-stream` if the client accepts it, carrying the
+//!   request's [progress notifications](crate::progress) as they happen
+//!   and then one `message` event with the response, and as
+//!   `application/json` otherwise. Notifications are acknowledged with
+//!   202 Accepted.
+//! - `GET`: a `text/event-stream` of the server's
+//!   [notifications](McpServer::notify) and the session's resource
+//!   updates, until the session ends or the client disconnects.
+//! - `DELETE`: ends the session and its
+//!   [resource subscriptions](crate::resources).
+//!
+//! The response to `initialize` carries a new session id in the
+//! [`SESSION_HEADER`]; every later message must send it back. Messages
+//! without one get 400, and messages for an unknown or ended session 404.
+//!
+//! A client that disconnects while its request is being handled cancels
+//! it: the handler future is dropped, a warning is logged and
+//! [`REQUESTS_CANCELLED`] is incremented in the global metrics
+//! [`Registry`].
+//!
+//! The server speaks HTTP/1.1 with one request per connection, optionally
+//! over TLS, and can require a bearer token checked by
+//! [`HttpConfig::authorize`].
+
+use crate::policy::ClientIdentity;
+use crate::progress::Notifier;
+use crate::sessions::new_session_id;
+use crate::Client;
+use crate::{McpNotification, McpResponse, McpServer};
+use agentic_robotics_core::metrics::Registry;
+use agentic_robotics_core::TlsConfig;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+/// Header carrying the session id
+pub const SESSION_HEADER: &str = "Mcp-Session-Id";
+
+/// Requests cancelled because the client disconnected, by `method`
+pub const REQUESTS_CANCELLED: &str = "ros3_mcp_requests_cancelled_total";
+
+/// Largest request head accepted, in bytes
+const MAX_HEAD: usize = 8 * 1024;
+
+/// Largest request body accepted, in bytes
+const MAX_BODY: usize = 4 * 1024 * 1024;
+
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval of the comments keeping idle event streams open through proxies
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Check of the token in an `Authorization: Bearer` header, see
+/// [`HttpConfig::authorize`]
+pub type BearerCheck = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Where and how an [`HttpServer`] listens
+#[derive(Clone)]
+pub struct HttpConfig {
+    /// Port 0 picks a free port, see [`HttpServer::local_addr`]
+    pub address: SocketAddr,
+    /// Path of the MCP endpoint
+    pub path: String,
+    /// Serve HTTPS; `cert` and `key` must be set, and
+    /// `require_client_cert` asks clients for certificates too
+    pub tls: Option<TlsConfig>,
+    /// Called with the bearer token of every request; requests without a
+    /// token or with one it rejects get 401. `None` accepts every request.
+    pub authorize: Option<BearerCheck>,
+}
+
+impl HttpConfig {
+    /// Plain HTTP on `address` at `/mcp`, without authorization
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            path: "/mcp".to_string(),
+            tls: None,
+            authorize: None,
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self::new(SocketAddr::from(([127, 0, 0, 1], 8080)))
+    }
+}
+
+impl From<SocketAddr> for HttpConfig {
+    fn from(address: SocketAddr) -> Self {
+        Self::new(address)
+    }
+}
+
+impl std::fmt::Debug for HttpConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpConfig")
+            .field("address", &self.address)
+            .field("path", &self.path)
+            .field("tls", &self.tls)
+            .field("authorize", &self.authorize.is_some())
+            .finish()
+    }
+}
+
+impl McpServer {
+    /// Serve the Streamable HTTP transport, see the [module
+    /// documentation](crate::http)
+    ///
+    /// Returns only if the listener cannot be set up; use
+    /// [`HttpServer::start`] to serve in the background.
+    pub async fn serve_http(&self, config: impl Into<HttpConfig>) -> Result<()> {
+        let (listener, endpoint) = Endpoint::bind(self.clone(), config.into()).await?;
+        endpoint.run(listener).await;
+        Ok(())
+    }
+}
+
+/// Running HTTP transport, see the [module documentation](self)
+pub struct HttpServer {
+    local_addr: SocketAddr,
+    endpoint: Arc<Endpoint>,
+    task: JoinHandle<()>,
+}
+
+impl HttpServer {
+    /// Bind to `config.address` and serve `server` until dropped
+    ///
+    /// Must be called from within a Tokio runtime. Fails if the address
+    /// cannot be bound or the TLS files are invalid.
+    pub async fn start(server: McpServer, config: HttpConfig) -> Result<Self> {
+        let (listener, endpoint) = Endpoint::bind(server, config).await?;
+        let local_addr = listener.local_addr()?;
+        let task = tokio::spawn(endpoint.clone().run(listener));
+        Ok(Self {
+            local_addr,
+            endpoint,
+            task,
+        })
+    }
+
+    /// The bound address, with the actual port if port 0 was configured
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Number of sessions initialized and not yet ended
+    pub fn session_count(&self) -> usize {
+        self.endpoint.sessions.lock().unwrap().len()
+    }
+
+    /// Stop serving, returning once the listener is closed
+    ///
+    /// Unlike dropping the server, which closes the listener in the
+    /// background, the address can be bound again right away.
+    pub async fn shutdown(mut self) {
+        self.task.abort();
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// State shared by the connections of one listener
+struct Endpoint {
+    server: McpServer,
+    path: String,
+    tls: Option<TlsAcceptor>,
+    authorize: Option<BearerCheck>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+/// An initialized session
+struct Session {
+    /// Dropped to end the session's event streams
+    ended: watch::Sender<()>,
+    client: Arc<Client>,
+}
+
+/// A parsed request
+struct Request {
+    method: String,
+    path: String,
+    /// Names in lowercase
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn accepts(&self, content_type: &str) -> bool {
+        self.header("accept")
+            .is_some_and(|accept| accept.contains(content_type))
+    }
+}
+
+/// A complete response
+struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Response {
+    fn empty(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    fn text(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            body: body.into(),
+            ..Self::empty(status).header("Content-Type", "text/plain; charset=utf-8")
+        }
+    }
+
+    fn json(status: &'static str, response: &McpResponse) -> Self {
+        Self {
+            body: serde_json::to_string(response).unwrap_or_default(),
+            ..Self::empty(status).header("Content-Type", "application/json")
+        }
+    }
+
+    /// A `text/event-stream` of the single event `response`
+    fn event(response: &McpResponse) -> Self {
+        let data = serde_json::to_string(response).unwrap_or_default();
+        Self {
+            body: event(&data),
+            ..Self::empty("200 OK")
+                .header("Content-Type", "text/event-stream")
+                .header("Cache-Control", "no-cache")
+        }
+    }
+
+    fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    async fn write<W: AsyncWrite + Unpin>(self, writer: &mut W) -> Result<()> {
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        writer.write_all(head.as_bytes()).await?;
+        writer.write_all(self.body.as_bytes()).await?;
+        writer.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// Head of a POST response streaming progress ahead of the response
+const EVENT_STREAM_HEAD: &str = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                                 Cache-Control: no-cache\r\nConnection: close\r\n\r\n";
+
+/// An SSE `message` event carrying one line of JSON
+fn event(data: &str) -> String {
+    format!("event: message\ndata: {}\n\n", data)
+}
+
+impl Endpoint {
+    async fn bind(server: McpServer, config: HttpConfig) -> Result<(TcpListener, Arc<Self>)> {
+        let tls = config
+            .tls
+            .as_ref()
+            .map(TlsConfig::acceptor)
+            .transpose()
+            .context("invalid TLS configuration for the MCP endpoint")?;
+        let listener = TcpListener::bind(config.address)
+            .await
+            .with_context(|| format!("failed to bind MCP endpoint to {}", config.address))?;
+        let local_addr = listener.local_addr()?;
+        info!(
+            "Serving MCP on {}://{}{}",
+            if tls.is_some() { "https" } else { "http" },
+            local_addr,
+            config.path
+        );
+        let endpoint = Arc::new(Self {
+            server,
+            path: config.path,
+            tls,
+            authorize: config.authorize,
+            sessions: Mutex::new(HashMap::new()),
+        });
+        Ok((listener, endpoint))
+    }
+
+    async fn run(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let endpoint = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = endpoint.connection(stream, peer).await {
+                            debug!("MCP request from {} failed: {}", peer, err);
+                        }
+                    });
+                }
+                Err(err) => warn!("Failed to accept MCP connection: {}", err),
+            }
+        }
+    }
+
+    async fn connection(&self, stream: TcpStream, peer: SocketAddr) -> Result<()> {
+        match &self.tls {
+            Some(acceptor) => {
+                let stream = tokio::time::timeout(REQUEST_TIMEOUT, acceptor.accept(stream))
+                    .await
+                    .context("TLS handshake timed out")?
+                    .context("TLS handshake failed")?;
+                self.exchange(stream, peer).await
+            }
+            None => self.exchange(stream, peer).await,
+        }
+    }
+
+    /// Read one request from `stream` and answer it
+    async fn exchange<S>(&self, stream: S, peer: SocketAddr) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut reader))
+            .await
+            .context("request timed out")??;
+        let request = match request {
+            Ok(request) => request,
+            Err(response) => return response.write(&mut writer).await,
+        };
+
+        if request.path.split('?').next() != Some(self.path.as_str()) {
+            return Response::text("404 Not Found", "not found\n")
+                .write(&mut writer)
+                .await;
+        }
+        if let Some(response) = self.check_authorization(&request) {
+            return response.write(&mut writer).await;
+        }
+        let response = match request.method.as_str() {
+            "POST" => self.post(&request, &mut reader, &mut writer, peer).await?,
+            "GET" => return self.stream(&request, &mut reader, &mut writer).await,
+            "DELETE" => Some(self.delete(&request)),
+            _ => Some(
+                Response::text("405 Method Not Allowed", "use GET, POST or DELETE\n")
+                    .header("Allow", "GET, POST, DELETE"),
+            ),
+        };
+        match response {
+            Some(response) => response.write(&mut writer).await,
+            // Nobody is left to answer
+            None => Ok(()),
+        }
+    }
+
+    /// `None` if the request is authorized, the 401 response otherwise
+    fn check_authorization(&self, request: &Request) -> Option<Response> {
+        let authorize = self.authorize.as_ref()?;
+        let token = request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token {
+            Some(token) if authorize(token.trim()) => None,
+            _ => Some(
+                Response::text("401 Unauthorized", "missing or invalid bearer token\n")
+                    .header("WWW-Authenticate", "Bearer"),
+            ),
+        }
+    }
+
+    /// The id, end signal and client of the session named by the
+    /// request's [`SESSION_HEADER`], or the response rejecting the request
+    fn session(
+        &self,
+        request: &Request,
+    ) -> Result<(String, watch::Receiver<()>, Arc<Client>), Response> {
+        let Some(id) = request.header(SESSION_HEADER) else {
+            return Err(Response::text(
+                "400 Bad Request",
+                format!("missing {} header\n", SESSION_HEADER),
+            ));
+        };
+        match self.sessions.lock().unwrap().get(id) {
+            Some(session) => Ok((
+                id.to_string(),
+                session.ended.subscribe(),
+                session.client.clone(),
+            )),
+            None => Err(Response::text("404 Not Found", "unknown session\n")),
+        }
+    }
+
+    /// Handle a message, `None` if the response was already written or the
+    /// client disconnected before it was answered
+    async fn post<R, W>(
+        &self,
+        request: &Request,
+        reader: &mut R,
+        writer: &mut W,
+        peer: SocketAddr,
+    ) -> Result<Option<Response>>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let message = String::from_utf8_lossy(&request.body);
+        let value: Value = match serde_json::from_str(&message) {
+            Ok(value) => value,
+            Err(_) => {
+                // Answered with the parse error the stdio transport sends
+                let response = self.server.handle_message(&message).await;
+                return Ok(response.map(|response| Response::json("400 Bad Request", &response)));
+            }
+        };
+        let method = value
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let is_request = value.get("id").is_some_and(|id| !id.is_null());
+
+        // `initialize` starts a session, everything else belongs to one
+        let client = if method != "initialize" || !is_request {
+            match self.session(request) {
+                Ok((_, _, client)) => client,
+                Err(response) => return Ok(Some(response)),
+            }
+        } else {
+            Arc::new(Client::new(ClientIdentity {
+                transport: Some("http".to_string()),
+                address: Some(peer.to_string()),
+                ..ClientIdentity::default()
+            }))
+        };
+        // Progress notifications need an event stream to travel on
+        let streams = request.accepts("text/event-stream");
+        let (progress, mut notifications) = mpsc::unbounded_channel::<McpNotification>();
+        let notify: Option<Notifier> = streams.then(|| {
+            Arc::new(move |notification| {
+                let _ = progress.send(notification);
+            }) as Notifier
+        });
+        let handled = self
+            .server
+            .handle_client_message(&message, Some(&client), notify);
+        if !is_request {
+            return Ok(Some(match handled.await {
+                Some(response) => Response::json("400 Bad Request", &response),
+                None => Response::empty("202 Accepted"),
+            }));
+        }
+
+        tokio::pin!(handled);
+        let mut streaming = false;
+        let response = loop {
+            let notification = tokio::select! {
+                biased;
+                Some(notification) = notifications.recv() => notification,
+                response = &mut handled => break response,
+                _ = closed(reader) => {
+                    warn!("MCP client {} disconnected during {}, cancelled it", peer, method);
+                    Registry::global()
+                        .counter(
+                            REQUESTS_CANCELLED,
+                            "MCP requests cancelled because the client disconnected",
+                            &[("method", &method)],
+                        )
+                        .inc();
+                    return Ok(None);
+                }
+            };
+            if !streaming {
+                writer.write_all(EVENT_STREAM_HEAD.as_bytes()).await?;
+                streaming = true;
+            }
+            writer
+                .write_all(event(&serde_json::to_string(&notification)?).as_bytes())
+                .await?;
+            writer.flush().await?;
+        };
+        // Cancelled with `notifications/cancelled`, which needs no answer
+        let Some(response) = response else {
+            if !streaming {
+                return Ok(Some(Response::empty("202 Accepted")));
+            }
+            writer.shutdown().await?;
+            return Ok(None);
+        };
+
+        // Sent while the handler finished
+        let late: Vec<McpNotification> =
+            std::iter::from_fn(|| notifications.try_recv().ok()).collect();
+        if streaming || !late.is_empty() {
+            if !streaming {
+                writer.write_all(EVENT_STREAM_HEAD.as_bytes()).await?;
+            }
+            for notification in &late {
+                writer
+                    .write_all(event(&serde_json::to_string(notification)?).as_bytes())
+                    .await?;
+            }
+            writer
+                .write_all(event(&serde_json::to_string(&response)?).as_bytes())
+                .await?;
+            writer.shutdown().await?;
+            return Ok(None);
+        }
+        let mut reply = if streams {
+            Response::event(&response)
+        } else {
+            Response::json("200 OK", &response)
+        };
+        if method == "initialize" && response.error.is_none() {
+            let id = new_session_id();
+            self.server.add_session(&id, &client);
+            let session = Session {
+                ended: watch::channel(()).0,
+                client: client.clone(),
+            };
+            self.sessions.lock().unwrap().insert(id.clone(), session);
+            info!("MCP session {} started by {}", id, peer);
+            reply = reply.header(SESSION_HEADER, id);
+        }
+        Ok(Some(reply))
+    }
+
+    /// Stream notifications to the client until its session ends or it
+    /// disconnects
+    async fn stream<R, W>(&self, request: &Request, reader: &mut R, writer: &mut W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        if !request.accepts("text/event-stream") {
+            return Response::text("406 Not Acceptable", "accept text/event-stream\n")
+                .write(writer)
+                .await;
+        }
+        let (id, mut ended, client) = match self.session(request) {
+            Ok(session) => session,
+            Err(response) => return response.write(writer).await,
+        };
+        let mut notifications = self.server.subscribe();
+        let mut updates = client.subscriptions.notifications();
+        // The stream must not keep the session's subscriptions alive
+        drop(client);
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+             {}: {}\r\nConnection: close\r\n\r\n",
+            SESSION_HEADER, id
+        );
+        writer.write_all(head.as_bytes()).await?;
+
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE);
+        loop {
+            let received = tokio::select! {
+                _ = ended.changed() => break,
+                _ = closed(reader) => break,
+                _ = keep_alive.tick() => None,
+                notification = notifications.recv() => Some(notification),
+                update = updates.recv() => Some(update),
+            };
+            let chunk = match received {
+                None => ": keep-alive\n\n".to_string(),
+                Some(Ok(notification)) => event(&serde_json::to_string(&notification)?),
+                Some(Err(broadcast::error::RecvError::Lagged(missed))) => {
+                    warn!("MCP session {} missed {} notifications", id, missed);
+                    continue;
+                }
+                Some(Err(broadcast::error::RecvError::Closed)) => break,
+            };
+            writer.write_all(chunk.as_bytes()).await?;
+            writer.flush().await?;
+        }
+        debug!("MCP event stream of session {} closed", id);
+        writer.shutdown().await?;
+        Ok(())
+    }
+
+    fn delete(&self, request: &Request) -> Response {
+        let (id, _, _) = match self.session(request) {
+            Ok(session) => session,
+            Err(response) => return response,
+        };
+        // Dropping the session ends its event streams and subscriptions
+        self.sessions.lock().unwrap().remove(&id);
+        self.server.remove_session(&id);
+        info!("MCP session {} ended", id);
+        Response::empty("200 OK")
+    }
+}
+
+/// The request, or the response rejecting it if it is too large or
+/// malformed
+async fn read_request<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<std::result::Result<Request, Response>> {
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    let head_end = loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        if buffer.len() > MAX_HEAD {
+            return Ok(Err(Response::text(
+                "431 Request Header Fields Too Large",
+                "request too large\n",
+            )));
+        }
+        let read = reader.read(&mut chunk).await?;
+        if read == 0 {
+            anyhow::bail!("connection closed before the end of the request");
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: buffer[head_end + 4..].to_vec(),
+    };
+
+    if request.header("transfer-encoding").is_some() {
+        return Ok(Err(Response::text(
+            "411 Length Required",
+            "send a Content-Length\n",
+        )));
+    }
+    let length = match request.header("content-length").map(str::parse::<usize>) {
+        None => 0,
+        Some(Ok(length)) if length <= MAX_BODY => length,
+        Some(Ok(_)) => {
+            return Ok(Err(Response::text(
+                "413 Payload Too Large",
+                "body too large\n",
+            )));
+        }
+        Some(Err(_)) => {
+            return Ok(Err(Response::text(
+                "400 Bad Request",
+                "invalid Content-Length\n",
+            )));
+        }
+    };
+    while request.body.len() < length {
+        let read = reader.read(&mut chunk).await?;
+        if read == 0 {
+            anyhow::bail!("connection closed before the end of the body");
+        }
+        request.body.extend_from_slice(&chunk[..read]);
+    }
+    request.body.truncate(length);
+    Ok(Ok(request))
+}
+
+/// Completes once the client closed the connection
+///
+/// Clients send nothing more while waiting for the response, so anything
+/// read is discarded.
+async fn closed<R: AsyncRead + Unpin>(reader: &mut R) {
+    let mut discard = [0u8; 256];
+    while let Ok(read) = reader.read(&mut discard).await {
+        if read == 0 {
+            break;
+        }
+    }
+}
@@ -1,23 +1,72 @@
 //! Model Context Protocol (MCP) Server for Agentic Robotics
 //!
-//! Provides MCP 2025-11 compliant server with stdio and SSE transports
-//! for exposing robot capabilities to AI assistants.
+//! Provides MCP 2025-11 compliant server with stdio and Streamable HTTP
+//! transports for exposing robot capabilities to AI assistants, and a
+//! [client](client::McpClient) for calling the tools of other servers.
 
 use anyhow::Result;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::future::Future;
+use std::pin::Pin;
+use agentic_robotics_core::{Clock, Time};
+use policy::{AuditLog, AuditRecord, ClientIdentity, Decision, Policy};
+use manifest::Manifest;
+use progress::{Notifier, ProgressSink};
+use prompts::{PromptContent, PromptMessage, PromptTemplate};
+use resources::{ResourceProvider, Subscriptions, UriTemplate};
+use sessions::{Bucket, RateLimit};
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, oneshot, RwLock};
 
+pub mod client;
+pub mod http;
+pub mod manifest;
+pub mod policy;
+pub mod progress;
+pub mod prompts;
+pub mod recordings;
+pub mod resources;
+pub mod schema;
+pub mod sessions;
+pub mod ros3;
 pub mod transport;
 pub mod server;
 
+pub use schemars;
+
 /// MCP Protocol version
 pub const MCP_VERSION: &str = "2025-11-15";
 
+/// Protocol versions the server can speak, newest first
+///
+/// `initialize` answers with the client's version if it is listed here and
+/// with [`MCP_VERSION`] otherwise, leaving it to the client to disconnect.
+pub const SUPPORTED_VERSIONS: &[&str] = &[MCP_VERSION, "2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// JSON-RPC error codes used by the server
+pub mod error_codes {
+    /// The message is not valid JSON
+    pub const PARSE_ERROR: i32 = -32700;
+    /// The message is JSON but not a JSON-RPC 2.0 request
+    pub const INVALID_REQUEST: i32 = -32600;
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    /// Missing or malformed params, unknown tool, or tool arguments that do
+    /// not match its input schema
+    pub const INVALID_PARAMS: i32 = -32602;
+    pub const INTERNAL_ERROR: i32 = -32603;
+    /// `resources/read` of a URI that is not registered
+    pub const RESOURCE_NOT_FOUND: i32 = -32002;
+}
+
 /// MCP Tool definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct McpTool {
     pub name: String,
     pub description: String,
@@ -33,6 +82,15 @@ pub struct McpRequest {
     pub params: Option<Value>,
 }
 
+/// MCP Notification, a message that expects no response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
 /// MCP Response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpResponse {
@@ -55,6 +113,7 @@ pub struct McpError {
 
 /// Tool execution result
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ToolResult {
     pub content: Vec<ContentItem>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -73,15 +132,147 @@ pub enum ContentItem {
     Image { data: String, mimeType: String },
 }
 
+/// A resource the server can read, listed by `resources/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResource {
+    pub uri: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// Resources named by a URI template, listed by `resources/templates/list`
+///
+/// See [`resources::UriTemplate`] for the template syntax.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// A prompt template, listed by `prompts/list`
+///
+/// Expanded by `prompts/get`, see [`prompts`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpPrompt {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<PromptArgument>,
+}
+
+/// An argument of a [`McpPrompt`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Contents of a resource, returned by `resources/read`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub text: String,
+}
+
+/// Boxed future returned by handlers
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
 /// Tool handler function type
-pub type ToolHandler = Arc<dyn Fn(Value) -> Result<ToolResult> + Send + Sync>;
+///
+/// Called with the arguments of a `tools/call` once they matched the tool's
+/// `input_schema`, and where to report progress. See [`server::tool`],
+/// [`server::async_tool`] and [`server::progress_tool`].
+pub type ToolHandler =
+    Arc<dyn Fn(Value, ProgressSink) -> BoxFuture<Result<ToolResult>> + Send + Sync>;
+
+/// Resource handler function type, called with the URI being read
+pub type ResourceHandler = Arc<dyn Fn(String) -> BoxFuture<Result<ResourceContents>> + Send + Sync>;
 
 /// MCP Server implementation
+///
+/// Clones share the registered tools and resources, and the connected
+/// clients [notifications](McpServer::notify) go to.
+#[derive(Clone)]
 pub struct McpServer {
     tools: Arc<RwLock<HashMap<String, (McpTool, ToolHandler)>>>,
+    resources: Arc<RwLock<HashMap<String, (McpResource, ResourceHandler)>>>,
+    resource_templates: Arc<RwLock<Vec<(McpResourceTemplate, UriTemplate, ResourceHandler)>>>,
+    prompts: Arc<RwLock<HashMap<String, Prompt>>>,
+    providers: Arc<RwLock<Vec<Arc<dyn ResourceProvider>>>>,
+    notifications: broadcast::Sender<McpNotification>,
+    update_interval: Duration,
+    policy: Option<Arc<dyn Policy>>,
+    audit: Option<Arc<AuditLog>>,
+    rate_limit: Option<RateLimit>,
+    /// Connected clients, oldest first, see [`McpServer::sessions`]
+    sessions: Arc<Mutex<Vec<Session>>>,
+    manifest: Arc<Manifest>,
     server_info: ServerInfo,
 }
 
+/// A connected client, as tracked by its transport
+pub(crate) struct Client {
+    identity: Mutex<ClientIdentity>,
+    /// Dropped with the client, ending its subscriptions
+    pub(crate) subscriptions: Subscriptions,
+    /// Cancels the requests being handled, by id
+    in_flight: Mutex<HashMap<String, oneshot::Sender<()>>>,
+    /// System time the client connected at
+    connected_at: Time,
+    /// Tool calls made, including rejected ones
+    calls: AtomicU64,
+    /// Tool calls rejected by the rate limit
+    rate_limited: AtomicU64,
+    bucket: Mutex<Bucket>,
+}
+
+impl Client {
+    pub(crate) fn new(identity: ClientIdentity) -> Self {
+        Self {
+            identity: Mutex::new(identity),
+            subscriptions: Subscriptions::new(),
+            in_flight: Mutex::new(HashMap::new()),
+            connected_at: Clock::SystemTime.now(),
+            calls: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            bucket: Mutex::new(Bucket::default()),
+        }
+    }
+
+    pub(crate) fn identity(&self) -> ClientIdentity {
+        self.identity.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_session(&self, session: &str) {
+        self.identity.lock().unwrap().session = Some(session.to_string());
+    }
+}
+
+/// A registered prompt and the templates of its messages
+type Prompt = (McpPrompt, Vec<PromptTemplate>);
+
+/// A session id and its client, gone once the session ended
+type Session = (String, Weak<Client>);
+
+/// Notifications buffered per client before a slow one starts missing them
+const NOTIFICATION_BUFFER: usize = 64;
+
 /// Server information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerInfo {
@@ -91,11 +282,55 @@ pub struct ServerInfo {
     pub description: Option<String>,
 }
 
+impl McpResponse {
+    /// Successful response to request `id`
+    pub fn success(id: Option<Value>, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    /// Error response to request `id`, see [`error_codes`]
+    pub fn error(id: Option<Value>, code: i32, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(McpError {
+                code,
+                message: message.into(),
+                data: None,
+            }),
+        }
+    }
+
+    fn with_data(mut self, data: Value) -> Self {
+        if let Some(error) = &mut self.error {
+            error.data = Some(data);
+        }
+        self
+    }
+}
+
 impl McpServer {
     /// Create a new MCP server
     pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
         Self {
             tools: Arc::new(RwLock::new(HashMap::new())),
+            resources: Arc::new(RwLock::new(HashMap::new())),
+            resource_templates: Arc::new(RwLock::new(Vec::new())),
+            prompts: Arc::new(RwLock::new(HashMap::new())),
+            providers: Arc::new(RwLock::new(Vec::new())),
+            notifications: broadcast::channel(NOTIFICATION_BUFFER).0,
+            update_interval: resources::DEFAULT_UPDATE_INTERVAL,
+            policy: None,
+            audit: None,
+            rate_limit: None,
+            sessions: Arc::new(Mutex::new(Vec::new())),
+            manifest: Arc::new(Manifest::default()),
             server_info: ServerInfo {
                 name: name.into(),
                 version: version.into(),
@@ -105,140 +340,690 @@ impl McpServer {
     }
 
     /// Register a tool
+    ///
+    /// A tool registered under an existing name replaces it. Connected
+    /// clients are sent `notifications/tools/list_changed`.
     pub async fn register_tool(
         &self,
         tool: McpTool,
         handler: ToolHandler,
     ) -> Result<()> {
-        let mut tools = self.tools.write().await;
-        tools.insert(tool.name.clone(), (tool, handler));
+        self.tools.write().await.insert(tool.name.clone(), (tool, handler));
+        self.notify("notifications/tools/list_changed", None);
         Ok(())
     }
 
+    /// Remove the tool `name`, returning whether it was registered
+    ///
+    /// Calls already running finish. If the tool was registered, connected
+    /// clients are sent `notifications/tools/list_changed`.
+    pub async fn unregister_tool(&self, name: &str) -> bool {
+        let removed = self.tools.write().await.remove(name).is_some();
+        if removed {
+            self.notify("notifications/tools/list_changed", None);
+        }
+        removed
+    }
+
+    /// Names of the registered tools, sorted
+    pub async fn tool_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tools.read().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Register a tool whose arguments deserialize into `Args`
+    ///
+    /// The input schema is generated from `Args` with [`schemars`], so
+    /// `tools/call` arguments are checked against it before `handler` runs
+    /// and a mismatch is reported with the path of the offending field.
+    pub async fn tool<Args, F, Fut>(
+        &self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        handler: F,
+    ) -> Result<()>
+    where
+        Args: JsonSchema + DeserializeOwned + Send + 'static,
+        F: Fn(Args) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<ToolResult>> + Send + 'static,
+    {
+        let tool = McpTool {
+            name: name.into(),
+            description: description.into(),
+            input_schema: schema::schema_for::<Args>(),
+        };
+        let handler = Arc::new(handler);
+        let handler: ToolHandler = Arc::new(move |arguments, _| {
+            let handler = handler.clone();
+            Box::pin(async move {
+                // The schema passed, but serde may still be stricter
+                match serde_json::from_value::<Args>(arguments) {
+                    Ok(args) => handler(args).await,
+                    Err(e) => Ok(server::error_response(format!("Invalid arguments: {}", e))),
+                }
+            })
+        });
+        self.register_tool(tool, handler).await
+    }
+
+    /// Register a resource, read through `handler`
+    ///
+    /// A resource registered under an existing URI replaces it. Connected
+    /// clients are sent `notifications/resources/list_changed`.
+    pub async fn register_resource(
+        &self,
+        resource: McpResource,
+        handler: ResourceHandler,
+    ) -> Result<()> {
+        self.resources
+            .write()
+            .await
+            .insert(resource.uri.clone(), (resource, handler));
+        self.notify("notifications/resources/list_changed", None);
+        Ok(())
+    }
+
+    /// Register the resources matching a URI template, read through `handler`
+    ///
+    /// `handler` is called with the URI being read, which
+    /// [`UriTemplate::matches`] takes apart. Fixed resources and providers
+    /// take precedence; among templates, the first registered that matches.
+    /// Connected clients are sent `notifications/resources/list_changed`.
+    pub async fn register_resource_template(
+        &self,
+        template: McpResourceTemplate,
+        handler: ResourceHandler,
+    ) -> Result<()> {
+        let parsed = UriTemplate::parse(&template.uri_template)?;
+        self.resource_templates
+            .write()
+            .await
+            .push((template, parsed, handler));
+        self.notify("notifications/resources/list_changed", None);
+        Ok(())
+    }
+
+    /// Register a prompt, expanded from `messages` by `prompts/get`
+    ///
+    /// A prompt registered under an existing name replaces it. Connected
+    /// clients are sent `notifications/prompts/list_changed`.
+    pub async fn register_prompt(
+        &self,
+        prompt: McpPrompt,
+        messages: Vec<PromptTemplate>,
+    ) -> Result<()> {
+        self.prompts
+            .write()
+            .await
+            .insert(prompt.name.clone(), (prompt, messages));
+        self.notify("notifications/prompts/list_changed", None);
+        Ok(())
+    }
+
+    /// Register a provider of dynamic resources, see [`resources`]
+    ///
+    /// Its resources are listed after the fixed ones; a fixed resource with
+    /// the same URI hides the provider's. Connected clients are sent
+    /// `notifications/resources/list_changed`.
+    pub async fn register_resource_provider(
+        &self,
+        provider: Arc<dyn ResourceProvider>,
+    ) -> Result<()> {
+        self.providers.write().await.push(provider);
+        self.notify("notifications/resources/list_changed", None);
+        Ok(())
+    }
+
+    /// Send each client at most one update notification per `interval` and
+    /// subscription, [`resources::DEFAULT_UPDATE_INTERVAL`] by default
+    pub fn with_update_interval(mut self, interval: Duration) -> Self {
+        self.update_interval = interval;
+        self
+    }
+
+    /// Send a notification to every connected client
+    ///
+    /// Stdio clients receive it on stdout, HTTP clients on the event stream
+    /// of their session. Clients not connected at the time miss it.
+    pub fn notify(&self, method: impl Into<String>, params: Option<Value>) {
+        // Fails only when no client is connected
+        let _ = self.notifications.send(McpNotification {
+            jsonrpc: "2.0".to_string(),
+            method: method.into(),
+            params,
+        });
+    }
+
+    /// Receive the notifications sent from now on
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<McpNotification> {
+        self.notifications.subscribe()
+    }
+
+    /// Handle one JSON-RPC message, as read from a transport
+    ///
+    /// Returns the response to write back, or `None` for a notification,
+    /// i.e. a request without an `id`. Malformed messages get a
+    /// [`PARSE_ERROR`](error_codes::PARSE_ERROR) or
+    /// [`INVALID_REQUEST`](error_codes::INVALID_REQUEST) response.
+    ///
+    /// `resources/subscribe` needs a transport that keeps track of its
+    /// clients and is rejected here.
+    pub async fn handle_message(&self, message: &str) -> Option<McpResponse> {
+        self.handle_client_message(message, None, None).await
+    }
+
+    /// Handle one message from `client`, sending it the request's progress
+    /// notifications through `notify`
+    ///
+    /// `None` also for a request the client cancelled.
+    pub(crate) async fn handle_client_message(
+        &self,
+        message: &str,
+        client: Option<&Client>,
+        notify: Option<Notifier>,
+    ) -> Option<McpResponse> {
+        let value: Value = match serde_json::from_str(message) {
+            Ok(value) => value,
+            Err(e) => {
+                return Some(McpResponse::error(
+                    None,
+                    error_codes::PARSE_ERROR,
+                    format!("Parse error: {}", e),
+                ))
+            }
+        };
+        let id = value.get("id").cloned();
+        let request = match serde_json::from_value::<McpRequest>(value) {
+            Ok(request) if request.jsonrpc == "2.0" => request,
+            _ => {
+                return Some(McpResponse::error(
+                    id,
+                    error_codes::INVALID_REQUEST,
+                    "Invalid request",
+                ))
+            }
+        };
+        let Some(id) = &request.id else {
+            // Notifications, like `notifications/initialized`, need no answer
+            tracing::debug!("MCP notification {}", request.method);
+            if let (Some(client), "notifications/cancelled") = (client, request.method.as_str()) {
+                let cancelled = request.params.as_ref().and_then(|params| params.get("requestId"));
+                if let Some(sender) = cancelled
+                    .and_then(|id| client.in_flight.lock().unwrap().remove(&id.to_string()))
+                {
+                    let _ = sender.send(());
+                }
+            }
+            return None;
+        };
+        let Some(client) = client else {
+            return Some(self.dispatch(request, None, notify).await);
+        };
+
+        let key = id.to_string();
+        let (cancel, cancelled) = oneshot::channel();
+        client.in_flight.lock().unwrap().insert(key.clone(), cancel);
+        let response = tokio::select! {
+            response = self.dispatch(request, Some(client), notify) => Some(response),
+            // Dropping the handler's future stops whatever it was doing
+            Ok(()) = cancelled => {
+                tracing::debug!("MCP request {} cancelled", key);
+                None
+            }
+        };
+        client.in_flight.lock().unwrap().remove(&key);
+        response
+    }
+
     /// Handle MCP request
+    ///
+    /// Like [`handle_message`](Self::handle_message), rejects
+    /// `resources/subscribe`.
     pub async fn handle_request(&self, request: McpRequest) -> McpResponse {
+        self.dispatch(request, None, None).await
+    }
+
+    async fn dispatch(
+        &self,
+        request: McpRequest,
+        client: Option<&Client>,
+        notify: Option<Notifier>,
+    ) -> McpResponse {
         let id = request.id.clone();
+        let subscriptions = client.map(|client| &client.subscriptions);
 
         match request.method.as_str() {
-            "initialize" => self.handle_initialize(id).await,
+            "initialize" => self.handle_initialize(id, request.params, client).await,
+            "ping" => McpResponse::success(id, json!({})),
             "tools/list" => self.handle_list_tools(id).await,
-            "tools/call" => self.handle_call_tool(id, request.params).await,
-            _ => McpResponse {
-                jsonrpc: "2.0".to_string(),
-                id,
-                result: None,
-                error: Some(McpError {
-                    code: -32601,
-                    message: "Method not found".to_string(),
-                    data: None,
-                }),
-            },
+            "tools/call" => {
+                let progress = ProgressSink::new(progress::token(&request.params), notify);
+                self.handle_call_tool(id, request.params, client, progress).await
+            }
+            "resources/list" => self.handle_list_resources(id).await,
+            "resources/read" => self.handle_read_resource(id, request.params).await,
+            "resources/templates/list" => self.handle_list_resource_templates(id).await,
+            "resources/subscribe" => {
+                self.handle_subscribe(id, request.params, subscriptions).await
+            }
+            "resources/unsubscribe" => {
+                self.handle_unsubscribe(id, request.params, subscriptions)
+            }
+            "prompts/list" => self.handle_list_prompts(id).await,
+            "prompts/get" => self.handle_get_prompt(id, request.params).await,
+            _ => McpResponse::error(id, error_codes::METHOD_NOT_FOUND, "Method not found"),
         }
     }
 
-    async fn handle_initialize(&self, id: Option<Value>) -> McpResponse {
-        McpResponse {
-            jsonrpc: "2.0".to_string(),
+    async fn handle_initialize(
+        &self,
+        id: Option<Value>,
+        params: Option<Value>,
+        client: Option<&Client>,
+    ) -> McpResponse {
+        let requested = params
+            .as_ref()
+            .and_then(|params| params.get("protocolVersion"))
+            .and_then(Value::as_str);
+        let version = match requested {
+            Some(version) if SUPPORTED_VERSIONS.contains(&version) => version,
+            _ => MCP_VERSION,
+        };
+        if let Some(info) = params.as_ref().and_then(|params| params.get("clientInfo")) {
+            tracing::info!("MCP client {} connected, protocol {}", info, version);
+            if let Some(client) = client {
+                let field = |name| info.get(name).and_then(Value::as_str).map(str::to_string);
+                let mut identity = client.identity.lock().unwrap();
+                identity.name = field("name");
+                identity.version = field("version");
+            }
+        }
+
+        let mut capabilities = json!({
+            "tools": { "listChanged": true },
+            "resources": { "subscribe": true, "listChanged": true },
+            "prompts": { "listChanged": true },
+        });
+        if self.manifest.advertised() {
+            capabilities["experimental"] =
+                json!({ "ros3/manifest": self.capability_manifest().await });
+        }
+
+        McpResponse::success(
             id,
-            result: Some(json!({
-                "protocolVersion": MCP_VERSION,
-                "capabilities": {
-                    "tools": {},
-                    "resources": {},
-                },
+            json!({
+                "protocolVersion": version,
+                "capabilities": capabilities,
                 "serverInfo": self.server_info,
-            })),
-            error: None,
-        }
+            }),
+        )
     }
 
     async fn handle_list_tools(&self, id: Option<Value>) -> McpResponse {
         let tools = self.tools.read().await;
-        let tool_list: Vec<McpTool> = tools.values()
-            .map(|(tool, _)| tool.clone())
-            .collect();
+        let mut tool_list: Vec<&McpTool> = tools.values().map(|(tool, _)| tool).collect();
+        tool_list.sort_by(|a, b| a.name.cmp(&b.name));
 
-        McpResponse {
-            jsonrpc: "2.0".to_string(),
-            id,
-            result: Some(json!({
-                "tools": tool_list,
-            })),
-            error: None,
+        McpResponse::success(id, json!({ "tools": tool_list }))
+    }
+
+    async fn handle_call_tool(
+        &self,
+        id: Option<Value>,
+        params: Option<Value>,
+        client: Option<&Client>,
+        progress: ProgressSink,
+    ) -> McpResponse {
+        let Some(params) = params else {
+            return McpResponse::error(id, error_codes::INVALID_PARAMS, "Invalid params");
+        };
+        let Some(tool_name) = params.get("name").and_then(|v| v.as_str()) else {
+            return McpResponse::error(id, error_codes::INVALID_PARAMS, "Missing tool name");
+        };
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+
+        let (timestamp, started) = (Clock::SystemTime.now(), Instant::now());
+        let identity = client.map(Client::identity).unwrap_or_default();
+        if let Some(client) = client {
+            client.count_call();
+        }
+        let throttled = match (client, &self.rate_limit) {
+            (Some(client), Some(limit)) => client.throttle(limit),
+            _ => None,
+        };
+        let decision = match (&throttled, &self.policy) {
+            (Some(reason), _) => Decision::Deny(reason.clone()),
+            (None, Some(policy)) => policy.check(tool_name, &arguments, &identity),
+            (None, None) => Decision::Allow,
+        };
+        let response = match &decision {
+            Decision::Allow => {
+                self.call_tool(id, tool_name, arguments.clone(), progress)
+                    .await
+            }
+            Decision::Deny(reason) => {
+                tracing::warn!("MCP call of {} denied: {}", tool_name, reason);
+                let error = if throttled.is_some() { "rate_limited" } else { "denied" };
+                let denied = json!({ "error": error, "tool": tool_name, "reason": reason });
+                match serde_json::to_value(server::error_response(denied.to_string())) {
+                    Ok(result) => McpResponse::success(id, result),
+                    Err(e) => McpResponse::error(id, error_codes::INTERNAL_ERROR, e.to_string()),
+                }
+            }
+        };
+
+        if let Some(audit) = &self.audit {
+            let failed = response.error.is_some()
+                || response
+                    .result
+                    .as_ref()
+                    .and_then(|result| result.get("isError"))
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+            let record = AuditRecord::new(
+                timestamp,
+                &identity,
+                tool_name,
+                &arguments,
+                decision,
+                started.elapsed(),
+                failed,
+            );
+            audit.record(&record).await;
+        }
+        response
+    }
+
+    /// Run an allowed call of `tool_name`
+    async fn call_tool(
+        &self,
+        id: Option<Value>,
+        tool_name: &str,
+        arguments: Value,
+        progress: ProgressSink,
+    ) -> McpResponse {
+        // Not held across the call, so handlers may register tools
+        let found = self.tools.read().await.get(tool_name).cloned();
+        let Some((tool, handler)) = found else {
+            return McpResponse::error(
+                id,
+                error_codes::INVALID_PARAMS,
+                format!("Tool not found: {}", tool_name),
+            );
+        };
+        if let Err(errors) = schema::validate(&tool.input_schema, &arguments) {
+            let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return McpResponse::error(
+                id,
+                error_codes::INVALID_PARAMS,
+                format!("Invalid arguments for tool {}", tool_name),
+            )
+            .with_data(json!({ "errors": messages }));
+        }
+
+        // A failing tool is a result the model should see, not a protocol error
+        let mut result = handler(arguments, progress.clone())
+            .await
+            .unwrap_or_else(|e| server::error_response(format!("Tool execution failed: {}", e)));
+        let mut content = progress.take_chunks();
+        if !content.is_empty() {
+            content.append(&mut result.content);
+            result.content = content;
+        }
+        match serde_json::to_value(result) {
+            Ok(result) => McpResponse::success(id, result),
+            Err(e) => McpResponse::error(id, error_codes::INTERNAL_ERROR, e.to_string()),
+        }
+    }
+
+    async fn handle_list_resources(&self, id: Option<Value>) -> McpResponse {
+        let mut list: Vec<McpResource> = {
+            let resources = self.resources.read().await;
+            resources.values().map(|(resource, _)| resource.clone()).collect()
+        };
+        list.sort_by(|a, b| a.uri.cmp(&b.uri));
+        for provider in self.providers.read().await.iter() {
+            let mut provided = provider.list();
+            provided.retain(|resource| !list.iter().any(|fixed| fixed.uri == resource.uri));
+            provided.sort_by(|a, b| a.uri.cmp(&b.uri));
+            list.extend(provided);
+        }
+
+        McpResponse::success(id, json!({ "resources": list }))
+    }
+
+    /// The provider serving `uri`, unless a fixed resource has that URI
+    async fn provider(&self, uri: &str) -> Option<Arc<dyn ResourceProvider>> {
+        if self.resources.read().await.contains_key(uri) {
+            return None;
         }
+        let providers = self.providers.read().await;
+        providers.iter().find(|provider| provider.handles(uri)).cloned()
     }
 
-    async fn handle_call_tool(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
-        let params = match params {
-            Some(p) => p,
-            None => {
-                return McpResponse {
-                    jsonrpc: "2.0".to_string(),
+    async fn handle_list_resource_templates(&self, id: Option<Value>) -> McpResponse {
+        let mut list: Vec<McpResourceTemplate> = {
+            let templates = self.resource_templates.read().await;
+            templates.iter().map(|(template, _, _)| template.clone()).collect()
+        };
+        list.sort_by(|a, b| a.uri_template.cmp(&b.uri_template));
+        for provider in self.providers.read().await.iter() {
+            list.extend(provider.templates());
+        }
+
+        McpResponse::success(id, json!({ "resourceTemplates": list }))
+    }
+
+    /// The handler of the first template matching `uri`
+    async fn template_handler(&self, uri: &str) -> Option<ResourceHandler> {
+        let templates = self.resource_templates.read().await;
+        templates
+            .iter()
+            .find(|(_, template, _)| template.matches(uri).is_some())
+            .map(|(_, _, handler)| handler.clone())
+    }
+
+    /// Read `uri`, `None` if no resource has it
+    async fn read(&self, uri: &str) -> Option<BoxFuture<Result<ResourceContents>>> {
+        if let Some(provider) = self.provider(uri).await {
+            return Some(provider.read(uri));
+        }
+        let fixed = self.resources.read().await.get(uri).map(|(_, handler)| handler.clone());
+        let handler = match fixed {
+            Some(handler) => handler,
+            None => self.template_handler(uri).await?,
+        };
+        Some(handler(uri.to_string()))
+    }
+
+    async fn handle_read_resource(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
+        let Some(uri) = resource_uri(&params) else {
+            return McpResponse::error(id, error_codes::INVALID_PARAMS, "Missing resource uri");
+        };
+        let Some(read) = self.read(uri).await else {
+            return resource_not_found(id, uri);
+        };
+
+        match read.await {
+            Ok(contents) => McpResponse::success(id, json!({ "contents": [contents] })),
+            Err(e) => McpResponse::error(
+                id,
+                error_codes::INTERNAL_ERROR,
+                format!("Failed to read {}: {}", uri, e),
+            ),
+        }
+    }
+
+    async fn handle_subscribe(
+        &self,
+        id: Option<Value>,
+        params: Option<Value>,
+        subscriptions: Option<&Subscriptions>,
+    ) -> McpResponse {
+        let Some(uri) = resource_uri(&params) else {
+            return McpResponse::error(id, error_codes::INVALID_PARAMS, "Missing resource uri");
+        };
+        let Some(subscriptions) = subscriptions else {
+            return McpResponse::error(
+                id,
+                error_codes::INVALID_REQUEST,
+                "Subscriptions need a connected client",
+            );
+        };
+        let Some(provider) = self.provider(uri).await else {
+            if self.resources.read().await.contains_key(uri)
+                || self.template_handler(uri).await.is_some()
+            {
+                return McpResponse::error(
                     id,
-                    result: None,
-                    error: Some(McpError {
-                        code: -32602,
-                        message: "Invalid params".to_string(),
-                        data: None,
-                    }),
-                };
+                    error_codes::INVALID_PARAMS,
+                    format!("Resource {} does not send updates", uri),
+                );
             }
+            return resource_not_found(id, uri);
         };
 
-        let tool_name = match params.get("name").and_then(|v| v.as_str()) {
-            Some(name) => name,
-            None => {
-                return McpResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id,
-                    result: None,
-                    error: Some(McpError {
-                        code: -32602,
-                        message: "Missing tool name".to_string(),
-                        data: None,
-                    }),
-                };
+        match provider.subscribe(uri) {
+            Ok(updates) => {
+                subscriptions.add(uri, updates, self.update_interval);
+                McpResponse::success(id, json!({}))
             }
+            Err(e) => McpResponse::error(
+                id,
+                error_codes::INTERNAL_ERROR,
+                format!("Failed to subscribe to {}: {}", uri, e),
+            ),
+        }
+    }
+
+    fn handle_unsubscribe(
+        &self,
+        id: Option<Value>,
+        params: Option<Value>,
+        subscriptions: Option<&Subscriptions>,
+    ) -> McpResponse {
+        let Some(uri) = resource_uri(&params) else {
+            return McpResponse::error(id, error_codes::INVALID_PARAMS, "Missing resource uri");
         };
+        // Unsubscribing from something never subscribed to is harmless
+        if let Some(subscriptions) = subscriptions {
+            subscriptions.remove(uri);
+        }
+        McpResponse::success(id, json!({}))
+    }
 
-        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    async fn handle_list_prompts(&self, id: Option<Value>) -> McpResponse {
+        let prompts = self.prompts.read().await;
+        let mut list: Vec<&McpPrompt> = prompts.values().map(|(prompt, _)| prompt).collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
 
-        let tools = self.tools.read().await;
-        match tools.get(tool_name) {
-            Some((_, handler)) => {
-                match handler(arguments) {
-                    Ok(result) => McpResponse {
-                        jsonrpc: "2.0".to_string(),
-                        id,
-                        result: Some(serde_json::to_value(result).unwrap()),
-                        error: None,
-                    },
-                    Err(e) => McpResponse {
-                        jsonrpc: "2.0".to_string(),
+        McpResponse::success(id, json!({ "prompts": list }))
+    }
+
+    async fn handle_get_prompt(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
+        let Some(name) = params
+            .as_ref()
+            .and_then(|params| params.get("name"))
+            .and_then(Value::as_str)
+        else {
+            return McpResponse::error(id, error_codes::INVALID_PARAMS, "Missing prompt name");
+        };
+        let arguments = match params.as_ref().and_then(|params| params.get("arguments")) {
+            None | Some(Value::Null) => HashMap::new(),
+            Some(arguments) => match serde_json::from_value::<HashMap<String, String>>(
+                arguments.clone(),
+            ) {
+                Ok(arguments) => arguments,
+                Err(_) => {
+                    return McpResponse::error(
                         id,
-                        result: None,
-                        error: Some(McpError {
-                            code: -32000,
-                            message: format!("Tool execution failed: {}", e),
-                            data: None,
-                        }),
-                    },
+                        error_codes::INVALID_PARAMS,
+                        "Prompt arguments must be an object of strings",
+                    )
                 }
-            }
-            None => McpResponse {
-                jsonrpc: "2.0".to_string(),
-                id,
-                result: None,
-                error: Some(McpError {
-                    code: -32602,
-                    message: format!("Tool not found: {}", tool_name),
-                    data: None,
-                }),
             },
+        };
+
+        let found = self.prompts.read().await.get(name).cloned();
+        let Some((prompt, templates)) = found else {
+            return McpResponse::error(
+                id,
+                error_codes::INVALID_PARAMS,
+                format!("Prompt not found: {}", name),
+            );
+        };
+        let values = match prompts::arguments(&prompt, arguments) {
+            Ok(values) => values,
+            Err(missing) => {
+                return McpResponse::error(
+                    id,
+                    error_codes::INVALID_PARAMS,
+                    format!(
+                        "Missing required arguments for prompt {}: {}",
+                        name,
+                        missing.join(", ")
+                    ),
+                )
+                .with_data(json!({ "missing": missing }));
+            }
+        };
+
+        let mut messages = Vec::with_capacity(templates.len());
+        for template in &templates {
+            let content = match &template.content {
+                prompts::TemplateContent::Text(text) => PromptContent::Text {
+                    text: prompts::substitute(text, &values),
+                },
+                prompts::TemplateContent::Resource(uri) => {
+                    // Read now, so the prompt carries the current contents
+                    let uri = prompts::substitute(uri, &values);
+                    let Some(read) = self.read(&uri).await else {
+                        return resource_not_found(id, &uri);
+                    };
+                    match read.await {
+                        Ok(resource) => PromptContent::Resource { resource },
+                        Err(e) => {
+                            return McpResponse::error(
+                                id,
+                                error_codes::INTERNAL_ERROR,
+                                format!("Failed to read {}: {}", uri, e),
+                            )
+                        }
+                    }
+                }
+            };
+            messages.push(PromptMessage {
+                role: template.role,
+                content,
+            });
         }
+
+        let mut result = json!({ "messages": messages });
+        if let Some(description) = prompt.description {
+            result["description"] = json!(description);
+        }
+        McpResponse::success(id, result)
     }
 }
 
+/// The `uri` of a resource request
+fn resource_uri(params: &Option<Value>) -> Option<&str> {
+    params
+        .as_ref()
+        .and_then(|params| params.get("uri"))
+        .and_then(Value::as_str)
+}
+
+fn resource_not_found(id: Option<Value>, uri: &str) -> McpResponse {
+    McpResponse::error(
+        id,
+        error_codes::RESOURCE_NOT_FOUND,
+        format!("Resource not found: {}", uri),
+    )
+    .with_data(json!({ "uri": uri }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -273,7 +1058,7 @@ mod tests {
             }),
         };
 
-        let handler: ToolHandler = Arc::new(|_args| {
+        let handler: ToolHandler = server::tool(|_args| {
             Ok(ToolResult {
                 content: vec![ContentItem::Text {
                     text: "Test result".to_string(),
@@ -315,7 +1100,7 @@ mod tests {
             }),
         };
 
-        let handler: ToolHandler = Arc::new(|args| {
+        let handler: ToolHandler = server::tool(|args| {
             let message = args.get("message")
                 .and_then(|v| v.as_str())
                 .unwrap_or("empty");
@@ -346,4 +1131,196 @@ mod tests {
         assert!(response.result.is_some());
         assert!(response.error.is_none());
     }
+
+    #[tokio::test]
+    async fn test_typed_tool() {
+        #[derive(Deserialize, JsonSchema)]
+        struct MoveArgs {
+            location: String,
+            #[schemars(range(min = 0, max = 2))]
+            speed: Option<f64>,
+        }
+
+        let server = McpServer::new("test-server", "1.0.0");
+        server
+            .tool("move", "Move the robot", |args: MoveArgs| async move {
+                let speed = args.speed.unwrap_or(1.0);
+                Ok(server::text_response(format!("{} at {}", args.location, speed)))
+            })
+            .await
+            .unwrap();
+
+        let call = |arguments: Value| McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: "tools/call".to_string(),
+            params: Some(json!({ "name": "move", "arguments": arguments })),
+        };
+        let response = server.handle_request(call(json!({ "location": "dock" }))).await;
+        assert_eq!(response.result.unwrap()["content"][0]["text"], "dock at 1");
+
+        let response = server
+            .handle_request(call(json!({ "location": "dock", "speed": 3 })))
+            .await;
+        let error = response.error.unwrap();
+        assert_eq!(error.code, error_codes::INVALID_PARAMS);
+        assert_eq!(error.data.unwrap()["errors"], json!(["/speed: must be at most 2"]));
+    }
+
+    #[tokio::test]
+    async fn test_prompts() {
+        let server = McpServer::new("test-server", "1.0.0");
+        let template = McpResourceTemplate {
+            uri_template: "robot://{robot}/diagnostics".to_string(),
+            name: "Diagnostics".to_string(),
+            description: None,
+            mime_type: None,
+        };
+        let diagnostics = server::resource(|uri| async move {
+            Ok(ResourceContents {
+                text: format!("{} ok", uri),
+                uri,
+                mime_type: None,
+            })
+        });
+        server.register_resource_template(template, diagnostics).await.unwrap();
+        let prompt = McpPrompt {
+            name: "diagnose_robot".to_string(),
+            description: Some("Diagnose a robot".to_string()),
+            arguments: vec![PromptArgument {
+                name: "robot".to_string(),
+                description: None,
+                required: true,
+            }],
+        };
+        let messages = vec![
+            PromptTemplate::user("Diagnose {robot}."),
+            PromptTemplate::resource("robot://{robot}/diagnostics"),
+        ];
+        server.register_prompt(prompt, messages).await.unwrap();
+
+        let request = |method: &str, params: Value| McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: method.to_string(),
+            params: Some(params),
+        };
+        let response = server.handle_request(request("prompts/list", json!({}))).await;
+        assert_eq!(response.result.unwrap()["prompts"][0]["arguments"][0]["required"], true);
+        let response = server
+            .handle_request(request("resources/templates/list", json!({})))
+            .await;
+        let templates = response.result.unwrap()["resourceTemplates"].clone();
+        assert_eq!(templates[0]["uriTemplate"], "robot://{robot}/diagnostics");
+
+        let get = json!({ "name": "diagnose_robot", "arguments": { "robot": "arm" } });
+        let result = server.handle_request(request("prompts/get", get)).await.result.unwrap();
+        assert_eq!(result["description"], "Diagnose a robot");
+        assert_eq!(result["messages"][0]["role"], "user");
+        assert_eq!(result["messages"][0]["content"]["text"], "Diagnose arm.");
+        let embedded = &result["messages"][1]["content"];
+        assert_eq!(embedded["type"], "resource");
+        assert_eq!(embedded["resource"]["text"], "robot://arm/diagnostics ok");
+
+        let get = json!({ "name": "diagnose_robot", "arguments": {} });
+        let error = server.handle_request(request("prompts/get", get)).await.error.unwrap();
+        assert_eq!(error.code, error_codes::INVALID_PARAMS);
+        assert_eq!(error.message, "Missing required arguments for prompt diagnose_robot: robot");
+        assert_eq!(error.data.unwrap()["missing"], json!(["robot"]));
+    }
+
+    #[tokio::test]
+    async fn test_policy_and_audit_log() {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buffer {
+            fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(data)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let observer = observed.clone();
+        let log = AuditLog::writer(buffer.clone())
+            .observe(move |record| observer.lock().unwrap().push(record.clone()));
+        let server = McpServer::new("test-server", "1.0.0")
+            .with_policy(policy::AccessList::deny_all().allow_topics("publish", ["/agent/*"]))
+            .with_audit_log(log);
+        let tool = McpTool {
+            name: "publish".to_string(),
+            description: "Publish".to_string(),
+            input_schema: json!({ "type": "object" }),
+        };
+        let handler = server::tool(|_| Ok(server::text_response("published")));
+        server.register_tool(tool, handler).await.unwrap();
+
+        let call = |topic: &str| McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: "tools/call".to_string(),
+            params: Some(json!({ "name": "publish", "arguments": { "topic": topic } })),
+        };
+        let result = server.handle_request(call("/agent/goal")).await.result.unwrap();
+        assert_eq!(result["content"][0]["text"], "published");
+
+        let result = server.handle_request(call("/cmd_vel")).await.result.unwrap();
+        assert_eq!(result["isError"], true);
+        let denied: Value =
+            serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(denied["error"], "denied");
+        assert_eq!(denied["tool"], "publish");
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let records: Vec<Value> =
+            log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["decision"], "allow");
+        assert_eq!(records[0]["failed"], false);
+        assert_eq!(records[1]["decision"], "deny");
+        assert_eq!(records[1]["reason"], denied["reason"]);
+        assert_eq!(records[1]["tool"], "publish");
+        assert_ne!(records[0]["args_hash"], records[1]["args_hash"]);
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(observed.len(), 2);
+        assert_eq!(serde_json::to_value(&observed[1]).unwrap(), records[1]);
+    }
+
+    #[tokio::test]
+    async fn test_unregister_tool() {
+        let server = McpServer::new("test-server", "1.0.0");
+        for name in ["b_tool", "a_tool"] {
+            let tool = McpTool {
+                name: name.to_string(),
+                description: String::new(),
+                input_schema: json!({ "type": "object" }),
+            };
+            let handler = server::tool(|_| Ok(server::text_response("done")));
+            server.register_tool(tool, handler).await.unwrap();
+        }
+        assert_eq!(server.tool_names().await, ["a_tool", "b_tool"]);
+
+        let mut notifications = server.subscribe();
+        assert!(server.unregister_tool("a_tool").await);
+        assert!(!server.unregister_tool("a_tool").await);
+        assert_eq!(server.tool_names().await, ["b_tool"]);
+        let notification = notifications.try_recv().unwrap();
+        assert_eq!(notification.method, "notifications/tools/list_changed");
+        assert!(notifications.try_recv().is_err());
+
+        let request = McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: "tools/call".to_string(),
+            params: Some(json!({ "name": "a_tool" })),
+        };
+        let response = server.handle_request(request).await;
+        assert_eq!(response.error.unwrap().code, error_codes::INVALID_PARAMS);
+    }
 }
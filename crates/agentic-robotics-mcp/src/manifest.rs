@@ -0,0 +1,205 @@
+//! A compact summary of what the robot offers, for agent context
+//!
+//! [`McpServer::capability_manifest`] describes in one JSON document the
+//! registered tools, the topics on the global bus with their types and
+//! publish rates, the services, the parameters of every node and the
+//! coordinate frames given to [`McpServer::with_frames`].
+//! [`McpServer::register_capability_manifest`] makes it the resource
+//! `ros3://manifest` and adds it to the `initialize` result as the
+//! experimental capability `ros3/manifest`.
+//!
+//! Publish rates are measured by counting the messages published during
+//! [`RATE_WINDOW`]. Building the manifest never waits for a measurement:
+//! it uses the last one, or none yet, and starts a new one in the
+//! background once that is older than the window. A topic without a
+//! measurement has a `rate_hz` of `null`.
+
+use crate::{server, McpResource, McpServer, McpTool, ResourceContents, ToolHandler};
+use agentic_robotics_core::graph;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// URI of the manifest resource
+pub const MANIFEST_URI: &str = "ros3://manifest";
+
+/// Time publish rates are measured over
+pub const RATE_WINDOW: Duration = Duration::from_millis(500);
+
+type Tools = RwLock<HashMap<String, (McpTool, ToolHandler)>>;
+
+/// Manifest state shared by the clones of a server
+#[derive(Default)]
+pub(crate) struct Manifest {
+    frames: Mutex<Vec<String>>,
+    /// Whether `initialize` includes the manifest
+    advertised: AtomicBool,
+    rates: Arc<Rates>,
+}
+
+/// The last publish rate measurement
+#[derive(Default)]
+struct Rates {
+    /// Messages per second by topic, and when the measurement ended
+    last: Mutex<Option<(HashMap<String, f64>, Instant)>>,
+    sampling: AtomicBool,
+}
+
+impl McpServer {
+    /// The tools, topics, services, parameters and frames of this robot as
+    /// one JSON document; see the [module documentation](crate::manifest)
+    pub async fn capability_manifest(&self) -> Value {
+        self.manifest.build(&self.tools).await
+    }
+
+    /// Name the coordinate frames listed by the
+    /// [capability manifest](crate::manifest)
+    pub fn with_frames<I, S>(self, frames: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        *self.manifest.frames.lock().unwrap() = frames.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Make the [capability manifest](crate::manifest) the resource
+    /// `ros3://manifest` and part of the `initialize` result
+    pub async fn register_capability_manifest(&self) -> Result<()> {
+        self.manifest.advertised.store(true, Ordering::Relaxed);
+        let manifest = self.manifest.clone();
+        let tools = self.tools.clone();
+        self.register_resource(
+            McpResource {
+                uri: MANIFEST_URI.to_string(),
+                name: "Capability manifest".to_string(),
+                description: Some(
+                    "Tools, topics with their types and publish rates, services, \
+                     parameters and frames of the robot"
+                        .to_string(),
+                ),
+                mime_type: Some("application/json".to_string()),
+            },
+            server::resource(move |uri| {
+                let manifest = manifest.clone();
+                let tools = tools.clone();
+                async move {
+                    let text = serde_json::to_string_pretty(&manifest.build(&tools).await)?;
+                    Ok(ResourceContents {
+                        uri,
+                        mime_type: Some("application/json".to_string()),
+                        text,
+                    })
+                }
+            }),
+        )
+        .await
+    }
+}
+
+impl Manifest {
+    pub(crate) fn advertised(&self) -> bool {
+        self.advertised.load(Ordering::Relaxed)
+    }
+
+    pub(crate) async fn build(&self, tools: &Tools) -> Value {
+        let mut tools: Vec<Value> = tools
+            .read()
+            .await
+            .values()
+            .map(|(tool, _)| json!({ "name": tool.name, "description": tool.description }))
+            .collect();
+        tools.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+        let rates = self.rates.current();
+        let topics: Vec<Value> = graph::list_topics()
+            .into_iter()
+            .map(|topic| {
+                let rate = rates.as_ref().and_then(|rates| rates.get(&topic.name));
+                json!({
+                    "name": topic.name,
+                    "type_name": topic.type_name,
+                    "publisher_count": topic.publisher_count,
+                    "subscriber_count": topic.subscriber_count,
+                    "rate_hz": rate,
+                })
+            })
+            .collect();
+
+        let parameters: Vec<Value> = graph::list_parameters()
+            .into_iter()
+            .map(|param| {
+                // Serialized as `{"type": ..., "value": ...}`
+                let value = serde_json::to_value(&param.value).unwrap_or_default();
+                json!({
+                    "node": param.node,
+                    "name": param.name,
+                    "type": value["type"],
+                    "value": value["value"],
+                    "description": param.descriptor.description,
+                    "read_only": param.descriptor.read_only,
+                    "range": param.descriptor.range,
+                })
+            })
+            .collect();
+
+        json!({
+            "tools": tools,
+            "topics": topics,
+            "services": graph::list_services(),
+            "parameters": parameters,
+            "frames": *self.frames.lock().unwrap(),
+        })
+    }
+}
+
+impl Rates {
+    /// The last measurement, starting a new one if it is stale
+    fn current(self: &Arc<Self>) -> Option<HashMap<String, f64>> {
+        let last = self.last.lock().unwrap().clone();
+        let stale = last
+            .as_ref()
+            .is_none_or(|(_, measured)| measured.elapsed() >= RATE_WINDOW);
+        if stale {
+            self.sample();
+        }
+        last.map(|(rates, _)| rates)
+    }
+
+    /// Measure the rates in the background, unless already measuring
+    fn sample(self: &Arc<Self>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if self.sampling.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let rates = self.clone();
+        runtime.spawn(async move {
+            let published = || -> HashMap<String, u64> {
+                graph::topic_counters()
+                    .into_iter()
+                    .map(|topic| (topic.name, topic.published))
+                    .collect()
+            };
+            let before = published();
+            let start = Instant::now();
+            tokio::time::sleep(RATE_WINDOW).await;
+            let elapsed = start.elapsed().as_secs_f64();
+            // Topics created during the window started at zero
+            let measured = published()
+                .into_iter()
+                .map(|(topic, count)| {
+                    let delta = count.saturating_sub(before.get(&topic).copied().unwrap_or(0));
+                    (topic, delta as f64 / elapsed)
+                })
+                .collect();
+            *rates.last.lock().unwrap() = Some((measured, Instant::now()));
+            rates.sampling.store(false, Ordering::Release);
+        });
+    }
+}
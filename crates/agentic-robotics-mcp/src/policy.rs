@@ -0,0 +1,363 @@
+//! Authorization and auditing of tool calls
+//!
+//! A [`Policy`] set with [`McpServer::with_policy`] is asked before every
+//! `tools/call` whether the client may make it. A denied call never reaches
+//! the tool; the agent gets a tool error it can read and adapt to:
+//!
+//! ```json
+//! {"error":"denied","tool":"ros3_publish","reason":"topic /cmd_vel is not allowed"}
+//! ```
+//!
+//! [`AccessList`] is a policy of allowed and denied tools, optionally
+//! narrowed to the topics or services a tool may touch.
+//!
+//! An [`AuditLog`] set with [`McpServer::with_audit_log`] records every
+//! call, allowed or denied, as one JSON line and optionally publishes it on
+//! [`AUDIT_TOPIC`] or hands it to an [observer](AuditLog::observe).
+
+use crate::McpServer;
+use agentic_robotics_core::message::fnv1a;
+use agentic_robotics_core::serialization::Format;
+use agentic_robotics_core::{Publisher, Time};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Conventional topic audit records are published on
+pub const AUDIT_TOPIC: &str = "/ros3/mcp_audit";
+
+/// Who is calling, as far as the transport knows
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientIdentity {
+    /// `stdio` or `http`, `None` for requests handled directly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+    /// Session id, see [`McpServer::sessions`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// HTTP peer address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// `clientInfo.name` sent with `initialize`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `clientInfo.version` sent with `initialize`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// The outcome of a [`Policy`] check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "decision", content = "reason", rename_all = "lowercase")]
+pub enum Decision {
+    Allow,
+    /// Denied, for the reason given to the agent
+    Deny(String),
+}
+
+/// Decides which tool calls are allowed
+pub trait Policy: Send + Sync + 'static {
+    /// Whether `client` may call `tool` with `arguments`
+    ///
+    /// Called before the arguments are checked against the tool's schema,
+    /// and for tools that do not exist.
+    fn check(&self, tool: &str, arguments: &Value, client: &ClientIdentity) -> Decision;
+}
+
+/// A [`Policy`] of allowed and denied tools
+///
+/// A tool allowed for some topics may only be called with a `topic` or,
+/// failing that, `service` argument matching one of the patterns. In a
+/// pattern, `*` stands for one name segment and a trailing `**` for one or
+/// more: `/agent/*` matches `/agent/cmd_vel` but neither
+/// `/agent/arm/cmd` nor the relative name `agent/cmd_vel`.
+///
+/// ```
+/// # use agentic_robotics_mcp::policy::AccessList;
+/// let policy = AccessList::deny_all()
+///     .allow("ros3_list_topics")
+///     .allow("ros3_sample_topic")
+///     .allow_topics("ros3_publish", ["/agent/*"]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AccessList {
+    rules: HashMap<String, Rule>,
+    allow_unlisted: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+    Allow,
+    AllowTopics(Vec<String>),
+    Deny,
+}
+
+impl AccessList {
+    /// Deny every tool not allowed explicitly
+    pub fn deny_all() -> Self {
+        Self::default()
+    }
+
+    /// Allow every tool not denied explicitly
+    pub fn allow_all() -> Self {
+        Self {
+            allow_unlisted: true,
+            ..Self::default()
+        }
+    }
+
+    /// Allow `tool` with any arguments
+    pub fn allow(mut self, tool: impl Into<String>) -> Self {
+        self.rules.insert(tool.into(), Rule::Allow);
+        self
+    }
+
+    /// Allow `tool` on the topics or services matching `patterns`
+    pub fn allow_topics<I, S>(mut self, tool: impl Into<String>, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let patterns = patterns.into_iter().map(Into::into).collect();
+        self.rules.insert(tool.into(), Rule::AllowTopics(patterns));
+        self
+    }
+
+    /// Deny `tool`
+    pub fn deny(mut self, tool: impl Into<String>) -> Self {
+        self.rules.insert(tool.into(), Rule::Deny);
+        self
+    }
+}
+
+impl Policy for AccessList {
+    fn check(&self, tool: &str, arguments: &Value, _client: &ClientIdentity) -> Decision {
+        match self.rules.get(tool) {
+            Some(Rule::Allow) => Decision::Allow,
+            Some(Rule::Deny) => Decision::Deny(format!("tool {} is denied", tool)),
+            None if self.allow_unlisted => Decision::Allow,
+            None => Decision::Deny(format!("tool {} is not allowed", tool)),
+            Some(Rule::AllowTopics(patterns)) => {
+                let name = ["topic", "service"]
+                    .iter()
+                    .find_map(|key| arguments.get(*key).and_then(Value::as_str));
+                match name {
+                    Some(name) if patterns.iter().any(|pattern| matches(pattern, name)) => {
+                        Decision::Allow
+                    }
+                    Some(name) => Decision::Deny(format!(
+                        "{} is not allowed for tool {}, only {}",
+                        name,
+                        tool,
+                        patterns.join(", ")
+                    )),
+                    None => Decision::Deny(format!(
+                        "tool {} is only allowed with a topic or service argument",
+                        tool
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Whether `name` matches `pattern`, see [`AccessList`]
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut patterns = pattern.split('/');
+    let mut segments = name.split('/');
+    loop {
+        match (patterns.next(), segments.next()) {
+            (Some("**"), Some(segment)) if patterns.clone().next().is_none() => {
+                return !segment.is_empty()
+            }
+            (Some("*"), Some(segment)) if !segment.is_empty() => {}
+            (Some(expected), Some(segment)) if expected == segment => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// System time the call was received at
+    pub timestamp: Time,
+    pub client: ClientIdentity,
+    pub tool: String,
+    /// FNV-1a hash of the arguments as JSON, in hex
+    pub args_hash: String,
+    #[serde(flatten)]
+    pub decision: Decision,
+    /// Time from receiving the call to answering it
+    pub duration_ms: f64,
+    /// Whether the call failed, including denied calls
+    pub failed: bool,
+}
+
+impl AuditRecord {
+    pub(crate) fn new(
+        timestamp: Time,
+        client: &ClientIdentity,
+        tool: &str,
+        arguments: &Value,
+        decision: Decision,
+        duration: Duration,
+        failed: bool,
+    ) -> Self {
+        Self {
+            timestamp,
+            client: client.clone(),
+            tool: tool.to_string(),
+            args_hash: format!("{:016x}", fnv1a(arguments.to_string().as_bytes())),
+            decision,
+            duration_ms: duration.as_secs_f64() * 1e3,
+            failed,
+        }
+    }
+}
+
+/// Called with every [`AuditRecord`], see [`AuditLog::observe`]
+pub type AuditObserver = Box<dyn Fn(&AuditRecord) + Send + Sync>;
+
+/// Where audit records go, see the [module documentation](self)
+#[derive(Default)]
+pub struct AuditLog {
+    writer: Option<Mutex<Box<dyn Write + Send>>>,
+    publisher: Option<Publisher<Value>>,
+    observer: Option<AuditObserver>,
+}
+
+impl AuditLog {
+    /// A log that records nothing until given somewhere to write or publish
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append records to the file at `path`, creating it if needed
+    pub fn file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::writer(file))
+    }
+
+    /// Write records to `writer`, one JSON object per line
+    pub fn writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Some(Mutex::new(Box::new(writer))),
+            publisher: None,
+            observer: None,
+        }
+    }
+
+    /// Also publish records as JSON on `topic`, usually [`AUDIT_TOPIC`]
+    pub fn publish_on(mut self, topic: &str) -> Result<Self> {
+        self.publisher = Some(Publisher::with_format(topic, Format::Json)?);
+        Ok(self)
+    }
+
+    /// Also call `observer` with every record, before it is written
+    ///
+    /// `observer` runs on the task answering the call, so it should hand
+    /// the record off rather than block.
+    pub fn observe(mut self, observer: impl Fn(&AuditRecord) + Send + Sync + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    pub(crate) async fn record(&self, record: &AuditRecord) {
+        if let Some(observer) = &self.observer {
+            observer(record);
+        }
+        let value = match serde_json::to_value(record) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::error!("Failed to encode MCP audit record: {}", e);
+                return;
+            }
+        };
+        if let Some(writer) = &self.writer {
+            let mut writer = writer.lock().unwrap();
+            if let Err(e) = writeln!(writer, "{}", value).and_then(|_| writer.flush()) {
+                tracing::error!("Failed to write MCP audit record: {}", e);
+            }
+        }
+        if let Some(publisher) = &self.publisher {
+            if let Err(e) = publisher.publish(&value).await {
+                tracing::warn!("Failed to publish MCP audit record: {}", e);
+            }
+        }
+    }
+}
+
+impl McpServer {
+    /// Check every tool call against `policy`, see [`policy`](self)
+    pub fn with_policy(mut self, policy: impl Policy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Record every tool call in `log`
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit = Some(Arc::new(log));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_topic_patterns() {
+        assert!(matches("/agent/*", "/agent/cmd_vel"));
+        assert!(!matches("/agent/*", "/agent/arm/cmd"));
+        assert!(!matches("/agent/*", "/agent/"));
+        assert!(!matches("/agent/*", "agent/cmd_vel"));
+        assert!(!matches("/agent/*", "/cmd_vel"));
+        assert!(matches("/agent/**", "/agent/arm/cmd"));
+        assert!(!matches("/agent/**", "/agent"));
+        assert!(matches("/*/cmd_vel", "/robot1/cmd_vel"));
+        assert!(matches("/cmd_vel", "/cmd_vel"));
+    }
+
+    #[test]
+    fn test_access_list() {
+        let client = ClientIdentity::default();
+        let policy = AccessList::deny_all()
+            .allow("ros3_sample_topic")
+            .allow_topics("ros3_publish", ["/agent/*"]);
+        let check = |tool, arguments| policy.check(tool, &arguments, &client);
+
+        assert_eq!(
+            check("ros3_sample_topic", json!({ "topic": "/cmd_vel" })),
+            Decision::Allow
+        );
+        assert_eq!(
+            check("ros3_publish", json!({ "topic": "/agent/goal" })),
+            Decision::Allow
+        );
+        assert_eq!(
+            check("ros3_publish", json!({ "topic": "/cmd_vel" })),
+            Decision::Deny("/cmd_vel is not allowed for tool ros3_publish, only /agent/*".into())
+        );
+        assert!(matches!(
+            check("ros3_publish", json!({})),
+            Decision::Deny(_)
+        ));
+        assert!(matches!(check("shutdown", json!({})), Decision::Deny(_)));
+
+        let policy = AccessList::allow_all().deny("shutdown");
+        assert_eq!(policy.check("move", &json!({}), &client), Decision::Allow);
+        assert!(matches!(
+            policy.check("shutdown", &json!({}), &client),
+            Decision::Deny(_)
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+//! Progress and partial results of long-running tool calls
+//!
+//! A client that wants to follow a call sends a progress token in the
+//! request's `_meta`:
+//!
+//! ```json
+//! {"method":"tools/call","params":{"name":"ros3_sample_topic","arguments":{...},"_meta":{"progressToken":"sample-1"}}}
+//! ```
+//!
+//! The handler reports through its [`ProgressSink`], and each report is
+//! sent to the client as `notifications/progress` carrying that token:
+//! over stdio as a line of its own, over HTTP as an event of the stream
+//! answering the POST, ahead of the response. Content chunks pushed with
+//! [`ProgressSink::content`] go out the same way, under `content`, and
+//! also make up the start of the final result.
+//!
+//! A client can give up on a request with `notifications/cancelled`. The
+//! handler's future is then dropped, releasing whatever it held, such as
+//! a subscription, and the request gets no response.
+
+use crate::{ContentItem, McpNotification};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Sends notifications to the client that made a request
+pub(crate) type Notifier = Arc<dyn Fn(McpNotification) + Send + Sync>;
+
+/// Where a tool handler reports progress, see the
+/// [module documentation](self)
+///
+/// Reports are dropped if the client sent no progress token.
+#[derive(Clone)]
+pub struct ProgressSink {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// The client's progress token and where its notifications go
+    target: Option<(Value, Notifier)>,
+    chunks: Mutex<Vec<ContentItem>>,
+}
+
+impl ProgressSink {
+    pub(crate) fn new(token: Option<Value>, notify: Option<Notifier>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                target: token.zip(notify),
+                chunks: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// A sink whose reports go nowhere, for calls made without a client
+    pub fn disabled() -> Self {
+        Self::new(None, None)
+    }
+
+    /// Whether the client follows the call's progress
+    pub fn is_requested(&self) -> bool {
+        self.shared.target.is_some()
+    }
+
+    /// Report `progress` out of `total`, if known
+    ///
+    /// `progress` must increase with every report; neither needs to be an
+    /// integer.
+    pub fn progress(&self, progress: f64, total: Option<f64>, message: Option<&str>) {
+        let mut params = json!({ "progress": progress });
+        if let Some(total) = total {
+            params["total"] = json!(total);
+        }
+        if let Some(message) = message {
+            params["message"] = json!(message);
+        }
+        self.notify(params);
+    }
+
+    /// Send `item` to the client now and add it to the result
+    ///
+    /// The notification's `progress` is the number of chunks sent so far.
+    pub fn content(&self, item: ContentItem) {
+        let sent = {
+            let mut chunks = self.shared.chunks.lock().unwrap();
+            chunks.push(item.clone());
+            chunks.len()
+        };
+        if self.is_requested() {
+            self.notify(json!({ "progress": sent, "content": [item] }));
+        }
+    }
+
+    /// The chunks sent with [`content`](Self::content), in order
+    pub(crate) fn take_chunks(&self) -> Vec<ContentItem> {
+        std::mem::take(&mut *self.shared.chunks.lock().unwrap())
+    }
+
+    fn notify(&self, mut params: Value) {
+        let Some((token, notify)) = &self.shared.target else {
+            return;
+        };
+        params["progressToken"] = token.clone();
+        notify(McpNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/progress".to_string(),
+            params: Some(params),
+        });
+    }
+}
+
+/// The progress token in the `_meta` of request `params`
+pub(crate) fn token(params: &Option<Value>) -> Option<Value> {
+    params
+        .as_ref()?
+        .get("_meta")?
+        .get("progressToken")
+        .filter(|token| token.is_string() || token.is_i64() || token.is_u64())
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_notifications() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let notify: Notifier = {
+            let sent = sent.clone();
+            Arc::new(move |notification| sent.lock().unwrap().push(notification))
+        };
+        let params = Some(json!({ "name": "sample", "_meta": { "progressToken": 7 } }));
+        let sink = ProgressSink::new(token(&params), Some(notify));
+        assert!(sink.is_requested());
+
+        sink.progress(0.5, Some(1.0), Some("halfway"));
+        sink.content(ContentItem::Text {
+            text: "chunk".to_string(),
+        });
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[0].method, "notifications/progress");
+        assert_eq!(
+            sent[0].params,
+            Some(
+                json!({ "progressToken": 7, "progress": 0.5, "total": 1.0, "message": "halfway" })
+            )
+        );
+        let chunk = sent[1].params.as_ref().unwrap();
+        assert_eq!(
+            (&chunk["progress"], &chunk["content"][0]["text"]),
+            (&json!(1), &json!("chunk"))
+        );
+        assert_eq!(sink.take_chunks().len(), 1);
+
+        // Without a token, chunks still make up the result
+        let sink = ProgressSink::disabled();
+        sink.content(ContentItem::Text {
+            text: "chunk".to_string(),
+        });
+        assert!(!sink.is_requested());
+        assert_eq!(sink.take_chunks().len(), 1);
+    }
+}
@@ -0,0 +1,195 @@
+//! Prompt templates for `prompts/list` and `prompts/get`
+//!
+//! A prompt is a [`McpPrompt`] describing its arguments and the
+//! [`PromptTemplate`]s its messages are expanded from. `{name}` in a
+//! template is replaced with the value of argument `name`, or with nothing
+//! for an optional argument the client left out; braces around anything
+//! else are kept as they are. A resource template embeds the contents of
+//! the resource its expanded URI names, read when the prompt is requested:
+//!
+//! ```no_run
+//! # use agentic_robotics_mcp::{McpPrompt, McpServer, PromptArgument};
+//! # use agentic_robotics_mcp::prompts::PromptTemplate;
+//! # async fn example(server: McpServer) -> anyhow::Result<()> {
+//! let prompt = McpPrompt {
+//!     name: "diagnose_robot".to_string(),
+//!     description: Some("Diagnose a robot from its latest state".to_string()),
+//!     arguments: vec![PromptArgument {
+//!         name: "robot".to_string(),
+//!         description: Some("Robot namespace".to_string()),
+//!         required: true,
+//!     }],
+//! };
+//! server
+//!     .register_prompt(
+//!         prompt,
+//!         vec![
+//!             PromptTemplate::user("Diagnose {robot}. Its latest state follows."),
+//!             PromptTemplate::resource("ros3://topic/{robot}/state"),
+//!         ],
+//!     )
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{McpPrompt, ResourceContents};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Who a prompt message is from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// A message of a prompt, before its arguments are filled in
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub role: Role,
+    pub content: TemplateContent,
+}
+
+/// What a [`PromptTemplate`] expands to
+#[derive(Debug, Clone)]
+pub enum TemplateContent {
+    /// Text with `{argument}` placeholders
+    Text(String),
+    /// The URI of a resource to embed, with `{argument}` placeholders
+    Resource(String),
+}
+
+impl PromptTemplate {
+    /// A user message of `text`
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: TemplateContent::Text(text.into()),
+        }
+    }
+
+    /// An assistant message of `text`
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: TemplateContent::Text(text.into()),
+        }
+    }
+
+    /// A user message embedding the resource at `uri`
+    pub fn resource(uri: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: TemplateContent::Resource(uri.into()),
+        }
+    }
+}
+
+/// A message returned by `prompts/get`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptMessage {
+    pub role: Role,
+    pub content: PromptContent,
+}
+
+/// Content of a [`PromptMessage`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PromptContent {
+    Text { text: String },
+    Resource { resource: ResourceContents },
+}
+
+/// The value of every argument of `prompt`, empty for optional ones not
+/// `provided`, or the names of the missing required ones
+pub(crate) fn arguments(
+    prompt: &McpPrompt,
+    mut provided: HashMap<String, String>,
+) -> Result<HashMap<String, String>, Vec<String>> {
+    let missing: Vec<String> = prompt
+        .arguments
+        .iter()
+        .filter(|argument| argument.required && !provided.contains_key(&argument.name))
+        .map(|argument| argument.name.clone())
+        .collect();
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+    Ok(prompt
+        .arguments
+        .iter()
+        .map(|argument| {
+            let value = provided.remove(&argument.name).unwrap_or_default();
+            (argument.name.clone(), value)
+        })
+        .collect())
+}
+
+/// Replace the `{name}` placeholders in `template` with `values`
+///
+/// Values are inserted as they are, so placeholders in them stay.
+pub(crate) fn substitute(template: &str, values: &HashMap<String, String>) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest
+            .find('}')
+            .and_then(|end| Some((end, values.get(&rest[1..end])?)));
+        match value {
+            Some((end, value)) => {
+                expanded.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                expanded.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PromptArgument;
+
+    fn prompt() -> McpPrompt {
+        let argument = |name: &str, required| PromptArgument {
+            name: name.to_string(),
+            description: None,
+            required,
+        };
+        McpPrompt {
+            name: "diagnose_robot".to_string(),
+            description: None,
+            arguments: vec![argument("robot", true), argument("focus", false)],
+        }
+    }
+
+    #[test]
+    fn test_substitute() {
+        let provided = HashMap::from([("robot".to_string(), "arm".to_string())]);
+        let values = arguments(&prompt(), provided).unwrap();
+        assert_eq!(
+            substitute("Diagnose {robot}{focus}: {\"unknown\": {other}}", &values),
+            "Diagnose arm: {\"unknown\": {other}}"
+        );
+        let values = HashMap::from([("robot".to_string(), "{focus}".to_string())]);
+        assert_eq!(substitute("{robot} {focus", &values), "{focus} {focus");
+    }
+
+    #[test]
+    fn test_missing_required_arguments() {
+        let provided = HashMap::from([("focus".to_string(), "joints".to_string())]);
+        assert_eq!(
+            arguments(&prompt(), provided),
+            Err(vec!["robot".to_string()])
+        );
+    }
+}
@@ -0,0 +1,486 @@
+//! Bag recording controlled by the model
+//!
+//! [`McpServer::register_ros3_recording_tools`] registers:
+//!
+//! - `ros3_start_recording`: record `topics` to a bag, for `duration_secs`
+//!   or until stopped, and return the recording's id
+//! - `ros3_stop_recording`: stop a recording, write the bag's index and
+//!   return its counters and resource URI
+//! - `ros3_list_recordings`: the recordings started through this server,
+//!   running or complete
+//!
+//! Each recording has its own [`Recorder`], so several run side by side. A
+//! complete recording becomes the [resource](crate::resources)
+//! `ros3://bag/<id>`, read as the summary of its bag in JSON.
+//!
+//! Failures return a JSON object with an `error` code (`unknown_recording`,
+//! `invalid_path`, `stopping` or `recording_failed`) and whether retrying
+//! may help, like failed service calls of the [ros3 tools](crate::ros3).
+
+use crate::resources::ResourceProvider;
+use crate::{
+    server, BoxFuture, McpNotification, McpResource, McpResourceTemplate, McpServer, McpTool,
+    ResourceContents, ToolResult,
+};
+use agentic_robotics_core::{
+    BagReader, Clock, Recorder, RecorderConfig, RecorderStats, Time, TopicFilter,
+};
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+/// Start of the URIs of bag resources
+pub const BAG_URI_PREFIX: &str = "ros3://bag/";
+
+/// Longest recording `duration_secs` allows
+const MAX_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+impl McpServer {
+    /// Register the recording tools, writing bags to `dir`, and make
+    /// complete recordings resources; see the
+    /// [module documentation](crate::recordings)
+    ///
+    /// `dir` is created if missing. A bag is named after its recording
+    /// unless the call gives a `path`, which must be relative and stay
+    /// inside `dir`.
+    pub async fn register_ros3_recording_tools(&self, dir: impl Into<PathBuf>) -> Result<()> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let recordings = Arc::new(Recordings {
+            dir,
+            next: AtomicU64::new(1),
+            recordings: Mutex::new(Vec::new()),
+            notifications: self.notifications.clone(),
+        });
+
+        let start = recordings.clone();
+        self.register_tool(
+            McpTool {
+                name: "ros3_start_recording".to_string(),
+                description: "Start recording topics to a bag file, for `duration_secs` \
+                              seconds or until ros3_stop_recording is called. Returns the \
+                              recording's id; once complete, the bag's summary is the \
+                              resource ros3://bag/<id>"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "topics": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "minItems": 1,
+                            "description": "Topics to record",
+                        },
+                        "duration_secs": {
+                            "type": "number",
+                            "exclusiveMinimum": 0,
+                            "maximum": MAX_DURATION.as_secs(),
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "Bag file, relative to the recording directory",
+                        },
+                    },
+                    "required": ["topics"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::tool(move |args| Ok(start.start(&args))),
+        )
+        .await?;
+
+        let stop = recordings.clone();
+        self.register_tool(
+            McpTool {
+                name: "ros3_stop_recording".to_string(),
+                description: "Stop a recording and finalize its bag. Returns the messages \
+                              recorded and the URI of the bag's summary resource"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string", "description": "Recording id" },
+                    },
+                    "required": ["id"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::async_tool(move |args| {
+                let recordings = stop.clone();
+                async move {
+                    let id = args["id"].as_str().unwrap_or_default();
+                    Ok(recordings.stop(id).await)
+                }
+            }),
+        )
+        .await?;
+
+        let list = recordings.clone();
+        self.register_tool(
+            McpTool {
+                name: "ros3_list_recordings".to_string(),
+                description: "List the recordings with their state, topics, bag file and \
+                              messages recorded so far"
+                    .to_string(),
+                input_schema: json!({ "type": "object", "properties": {} }),
+            },
+            server::tool(move |_| {
+                let recordings = Value::Array(list.list());
+                Ok(server::text_response(serde_json::to_string_pretty(
+                    &recordings,
+                )?))
+            }),
+        )
+        .await?;
+
+        self.register_resource_provider(recordings).await
+    }
+}
+
+/// The recordings started by the tools
+struct Recordings {
+    /// Directory bags are written to
+    dir: PathBuf,
+    /// Number of the next recording
+    next: AtomicU64,
+    /// Oldest first
+    recordings: Mutex<Vec<Recording>>,
+    /// The server's, to announce new bag resources
+    notifications: broadcast::Sender<McpNotification>,
+}
+
+struct Recording {
+    id: String,
+    path: PathBuf,
+    topics: Vec<String>,
+    started_at: Time,
+    duration: Option<Duration>,
+    state: State,
+}
+
+enum State {
+    Recording {
+        recorder: Recorder,
+        /// Stops the recording after its duration
+        timer: Option<JoinHandle<()>>,
+    },
+    /// Finalizing the bag
+    Stopping,
+    Complete(RecorderStats),
+    Failed(String),
+}
+
+impl Recordings {
+    fn start(self: &Arc<Self>, args: &Value) -> ToolResult {
+        let topics: Vec<String> = args["topics"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|topic| Some(topic.as_str()?.to_string()))
+            .collect();
+        let duration = args["duration_secs"]
+            .as_f64()
+            .map(|secs| Duration::from_secs_f64(secs.clamp(0.0, MAX_DURATION.as_secs_f64())));
+
+        let mut recordings = self.recordings.lock().unwrap();
+        let (id, path) = match args["path"].as_str() {
+            Some(path) => {
+                let id = self.next_id();
+                match self.bag_path(path, &recordings) {
+                    Ok(path) => (id, path),
+                    Err(message) => return recording_error(&id, "invalid_path", message, false),
+                }
+            }
+            // Skip ids whose bag a previous run left behind
+            None => loop {
+                let id = self.next_id();
+                let path = self.dir.join(format!("{}.bag", id));
+                if !path.exists() {
+                    break (id, path);
+                }
+            },
+        };
+        let config = RecorderConfig::new(&path, TopicFilter::List(topics.clone()));
+        let recorder = match Recorder::start(config) {
+            Ok(recorder) => recorder,
+            Err(e) => return recording_error(&id, "recording_failed", e, false),
+        };
+        let timer = duration.map(|duration| {
+            let (recordings, id) = (Arc::downgrade(self), id.clone());
+            tokio::spawn(async move {
+                tokio::time::sleep(duration).await;
+                if let Some(recordings) = recordings.upgrade() {
+                    recordings.stop(&id).await;
+                }
+            })
+        });
+        recordings.push(Recording {
+            id: id.clone(),
+            path: path.clone(),
+            topics: recorder.topics(),
+            started_at: Clock::SystemTime.now(),
+            duration,
+            state: State::Recording { recorder, timer },
+        });
+        drop(recordings);
+
+        tracing::info!("Recording {} to {}", id, path.display());
+        let mut result = json!({ "id": id, "path": path, "topics": topics });
+        if let Some(duration) = duration {
+            result["duration_secs"] = json!(duration.as_secs_f64());
+        }
+        server::text_response(result.to_string())
+    }
+
+    fn next_id(&self) -> String {
+        format!("rec-{}", self.next.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// `path` inside the recording directory, if it is relative, stays
+    /// inside and is free
+    fn bag_path(&self, path: &str, recordings: &[Recording]) -> Result<PathBuf, String> {
+        let relative = Path::new(path);
+        let inside = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if path.is_empty() || !inside {
+            return Err(format!(
+                "Bag path '{}' must be relative to the recording directory, without '..'",
+                path
+            ));
+        }
+        let path = self.dir.join(relative);
+        if path.exists() || recordings.iter().any(|recording| recording.path == path) {
+            return Err(format!("Bag {} already exists", path.display()));
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        Ok(path)
+    }
+
+    /// Stop recording `id` and finalize its bag
+    ///
+    /// Stopping a complete recording returns it again.
+    async fn stop(self: &Arc<Self>, id: &str) -> ToolResult {
+        let recorder = {
+            let mut recordings = self.recordings.lock().unwrap();
+            let Some(recording) = recordings.iter_mut().find(|recording| recording.id == id) else {
+                let known: Vec<&str> = recordings.iter().map(|r| r.id.as_str()).collect();
+                let message = format!(
+                    "No recording '{}'; known recordings: {}",
+                    id,
+                    known.join(", ")
+                );
+                return recording_error(id, "unknown_recording", message, false);
+            };
+            match std::mem::replace(&mut recording.state, State::Stopping) {
+                State::Recording { recorder, timer } => {
+                    if let Some(timer) = timer {
+                        timer.abort();
+                    }
+                    recorder
+                }
+                state => {
+                    recording.state = state;
+                    return recording.result();
+                }
+            }
+        };
+
+        // Finalize on a task of its own, so a cancelled call, or the timer
+        // aborting itself, still completes the bag
+        let (recordings, id) = (self.clone(), id.to_string());
+        let finalizing = tokio::spawn(async move {
+            let state = match recorder.finalize().await {
+                Ok(stats) => State::Complete(stats),
+                Err(e) => State::Failed(e.to_string()),
+            };
+            let complete = matches!(state, State::Complete(_));
+            let result = recordings.update(&id, state);
+            if complete {
+                tracing::info!("Recording {} complete", id);
+                // Fails only when no client is connected
+                let _ = recordings.notifications.send(McpNotification {
+                    jsonrpc: "2.0".to_string(),
+                    method: "notifications/resources/list_changed".to_string(),
+                    params: None,
+                });
+            }
+            result
+        });
+        match finalizing.await {
+            Ok(result) => result,
+            Err(e) => server::error_response(e.to_string()),
+        }
+    }
+
+    /// Set the state of recording `id`, returning the result of stopping it
+    fn update(&self, id: &str, state: State) -> ToolResult {
+        let mut recordings = self.recordings.lock().unwrap();
+        match recordings.iter_mut().find(|recording| recording.id == id) {
+            Some(recording) => {
+                recording.state = state;
+                recording.result()
+            }
+            None => recording_error(id, "unknown_recording", "Recording was removed", false),
+        }
+    }
+
+    fn list(&self) -> Vec<Value> {
+        let recordings = self.recordings.lock().unwrap();
+        recordings.iter().map(Recording::to_json).collect()
+    }
+
+    /// The bag of the complete recording `uri` names
+    fn bag(&self, uri: &str) -> Option<(String, PathBuf)> {
+        let id = uri.strip_prefix(BAG_URI_PREFIX)?;
+        let recordings = self.recordings.lock().unwrap();
+        recordings
+            .iter()
+            .find(|recording| recording.id == id && matches!(recording.state, State::Complete(_)))
+            .map(|recording| (recording.id.clone(), recording.path.clone()))
+    }
+}
+
+impl Recording {
+    fn to_json(&self) -> Value {
+        let (state, stats) = match &self.state {
+            State::Recording { recorder, .. } => ("recording", Some(recorder.stats())),
+            State::Stopping => ("stopping", None),
+            State::Complete(stats) => ("complete", Some(stats.clone())),
+            State::Failed(_) => ("failed", None),
+        };
+        let mut recording = json!({
+            "id": self.id,
+            "state": state,
+            "path": self.path,
+            "topics": self.topics,
+            "started_at": self.started_at.as_secs_f64(),
+        });
+        if let Some(duration) = self.duration {
+            recording["duration_secs"] = json!(duration.as_secs_f64());
+        }
+        if let Some(stats) = stats {
+            recording["messages"] = json!(stats.messages);
+            recording["bytes"] = json!(stats.bytes);
+        }
+        match &self.state {
+            State::Complete(_) => recording["uri"] = json!(bag_uri(&self.id)),
+            State::Failed(error) => recording["error"] = json!(error),
+            _ => {}
+        }
+        recording
+    }
+
+    /// What stopping the recording returns in its current state
+    fn result(&self) -> ToolResult {
+        match &self.state {
+            State::Stopping => {
+                recording_error(&self.id, "stopping", "The recording is being stopped", true)
+            }
+            State::Failed(error) => recording_error(&self.id, "recording_failed", error, false),
+            _ => server::text_response(self.to_json().to_string()),
+        }
+    }
+}
+
+/// The resource URI of recording `id`'s bag
+pub fn bag_uri(id: &str) -> String {
+    format!("{}{}", BAG_URI_PREFIX, id)
+}
+
+/// A failed recording tool call as a JSON error object
+fn recording_error(id: &str, code: &str, message: impl ToString, retryable: bool) -> ToolResult {
+    let error = json!({
+        "error": code,
+        "id": id,
+        "message": message.to_string(),
+        "retryable": retryable,
+    });
+    server::error_response(error.to_string())
+}
+
+/// The summary of the bag at `path`, as JSON
+fn bag_summary(id: &str, path: &Path) -> Result<Value> {
+    let info = BagReader::open(path)?.info()?;
+    let topics: Map<String, Value> = info
+        .topics
+        .iter()
+        .map(|(topic, info)| {
+            let summary = json!({
+                "type": info.type_name,
+                "format": info.format,
+                "messages": info.messages,
+            });
+            (topic.clone(), summary)
+        })
+        .collect();
+    Ok(json!({
+        "id": id,
+        "path": path,
+        "finalized": info.finalized,
+        "start": info.start.as_secs_f64(),
+        "end": info.end.as_secs_f64(),
+        "duration_secs": info.duration().as_secs_f64(),
+        "messages": info.messages,
+        "chunks": info.chunks,
+        "compressed_chunks": info.compressed_chunks,
+        "stored_bytes": info.stored_bytes,
+        "uncompressed_bytes": info.uncompressed_bytes,
+        "compression_ratio": info.compression_ratio(),
+        "topics": topics,
+    }))
+}
+
+impl ResourceProvider for Recordings {
+    fn list(&self) -> Vec<McpResource> {
+        let recordings = self.recordings.lock().unwrap();
+        recordings
+            .iter()
+            .filter(|recording| matches!(recording.state, State::Complete(_)))
+            .map(|recording| McpResource {
+                uri: bag_uri(&recording.id),
+                name: recording.id.clone(),
+                description: Some(format!("Summary of bag {}", recording.path.display())),
+                mime_type: Some("application/json".to_string()),
+            })
+            .collect()
+    }
+
+    fn templates(&self) -> Vec<McpResourceTemplate> {
+        vec![McpResourceTemplate {
+            uri_template: format!("{}{{id}}", BAG_URI_PREFIX),
+            name: "Bag".to_string(),
+            description: Some("Summary of the bag of complete recording `id`".to_string()),
+            mime_type: Some("application/json".to_string()),
+        }]
+    }
+
+    fn handles(&self, uri: &str) -> bool {
+        self.bag(uri).is_some()
+    }
+
+    fn read(&self, uri: &str) -> BoxFuture<Result<ResourceContents>> {
+        let bag = self.bag(uri);
+        let uri = uri.to_string();
+        Box::pin(async move {
+            let (id, path) = bag.ok_or_else(|| anyhow::anyhow!("no bag for {}", uri))?;
+            let summary = tokio::task::spawn_blocking(move || bag_summary(&id, &path)).await??;
+            Ok(ResourceContents {
+                uri,
+                mime_type: Some("application/json".to_string()),
+                text: serde_json::to_string_pretty(&summary)?,
+            })
+        })
+    }
+
+    fn subscribe(&self, uri: &str) -> Result<mpsc::Receiver<ResourceContents>> {
+        anyhow::bail!("{} does not change once the recording is complete", uri)
+    }
+}
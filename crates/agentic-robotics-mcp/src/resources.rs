@@ -0,0 +1,333 @@
+//! Dynamic resources and `resources/subscribe`
+//!
+//! A [`ResourceProvider`] serves a set of resources that changes at
+//! runtime, such as one resource per topic, next to the fixed resources
+//! registered with [`McpServer::register_resource`](crate::McpServer::register_resource).
+//! Providers can also
+//! publish updates: a client calling `resources/subscribe` is sent
+//! `notifications/resources/updated` with the new contents whenever the
+//! resource changes, at most once per
+//! [update interval](crate::McpServer::with_update_interval); updates
+//! arriving faster are coalesced into the latest one.
+//!
+//! Subscriptions belong to the client that made them and end with
+//! `resources/unsubscribe`, when the stdio client closes its input or
+//! when the HTTP session ends. Ending one drops the provider's update
+//! channel, which must release whatever produces the updates.
+//!
+//! Resources that exist for every value of a parameter, such as
+//! `ros3://topic/{name}`, are described to clients by a [`UriTemplate`]
+//! listed by `resources/templates/list`.
+
+use crate::{BoxFuture, McpNotification, McpResource, McpResourceTemplate, ResourceContents};
+use anyhow::Result;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Default of [`McpServer::with_update_interval`](crate::McpServer::with_update_interval)
+pub const DEFAULT_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Resources that appear and change at runtime
+///
+/// See the [module documentation](self).
+pub trait ResourceProvider: Send + Sync + 'static {
+    /// The resources currently available
+    fn list(&self) -> Vec<McpResource>;
+
+    /// Templates of the URIs this provider handles, none by default
+    fn templates(&self) -> Vec<McpResourceTemplate> {
+        Vec::new()
+    }
+
+    /// Whether `uri` is one of this provider's resources
+    fn handles(&self, uri: &str) -> bool;
+
+    /// Read `uri`, one of the URIs [`handles`](Self::handles) accepts
+    fn read(&self, uri: &str) -> BoxFuture<Result<ResourceContents>>;
+
+    /// Start sending the contents of `uri` on every change
+    ///
+    /// Called from within a Tokio runtime. The provider must stop once the
+    /// receiver is dropped, e.g. by waiting on
+    /// [`Sender::closed`](mpsc::Sender::closed) next to its updates.
+    fn subscribe(&self, uri: &str) -> Result<mpsc::Receiver<ResourceContents>>;
+}
+
+/// A URI with `{name}` placeholders, a level 1 RFC 6570 template
+///
+/// A placeholder matches any non-empty text up to the literal text
+/// following it, including `/`, so `ros3://topic/{name}` matches
+/// `ros3://topic/robot/state` with `name` = `robot/state`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UriTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Variable(String),
+}
+
+impl UriTemplate {
+    /// Parse `template`, failing on unclosed or empty placeholders and on
+    /// placeholders not separated by literal text
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            } else if matches!(parts.last(), Some(Part::Variable(_))) {
+                anyhow::bail!("adjacent placeholders in URI template {}", template);
+            }
+            let end = rest[start..].find('}').ok_or_else(|| {
+                anyhow::anyhow!("unclosed placeholder in URI template {}", template)
+            })?;
+            let name = &rest[start + 1..start + end];
+            if name.is_empty() || name.contains('{') {
+                anyhow::bail!("invalid placeholder in URI template {}", template);
+            }
+            parts.push(Part::Variable(name.to_string()));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The placeholder names, in order
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable(name) => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// The values of the placeholders if `uri` matches
+    pub fn matches(&self, uri: &str) -> Option<HashMap<String, String>> {
+        let mut values = HashMap::new();
+        let mut rest = uri;
+        let mut parts = self.parts.iter().peekable();
+        while let Some(part) = parts.next() {
+            match part {
+                Part::Literal(literal) => rest = rest.strip_prefix(literal.as_str())?,
+                Part::Variable(name) => {
+                    let end = match parts.peek() {
+                        Some(Part::Literal(literal)) => rest.find(literal.as_str())?,
+                        _ => rest.len(),
+                    };
+                    if end == 0 {
+                        return None;
+                    }
+                    values.insert(name.clone(), rest[..end].to_string());
+                    rest = &rest[end..];
+                }
+            }
+        }
+        rest.is_empty().then_some(values)
+    }
+
+    /// Replace the placeholders with `values`, leaving those without a
+    /// value in place
+    pub fn expand(&self, values: &HashMap<String, String>) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.clone(),
+                Part::Variable(name) => match values.get(name) {
+                    Some(value) => value.clone(),
+                    None => format!("{{{}}}", name),
+                },
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for UriTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let empty = HashMap::new();
+        f.write_str(&self.expand(&empty))
+    }
+}
+
+/// A connected client's resource subscriptions and the channel their
+/// updates are sent on
+///
+/// Dropping it ends the subscriptions.
+pub(crate) struct Subscriptions {
+    updates: broadcast::Sender<McpNotification>,
+    active: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl Subscriptions {
+    pub(crate) fn new() -> Self {
+        Self {
+            updates: broadcast::channel(crate::NOTIFICATION_BUFFER).0,
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Receive the client's update notifications sent from now on
+    pub(crate) fn notifications(&self) -> broadcast::Receiver<McpNotification> {
+        self.updates.subscribe()
+    }
+
+    /// Forward the updates of `uri`, replacing an earlier subscription to it
+    pub(crate) fn add(
+        &self,
+        uri: &str,
+        updates: mpsc::Receiver<ResourceContents>,
+        interval: Duration,
+    ) {
+        let task = tokio::spawn(forward(
+            uri.to_string(),
+            updates,
+            self.updates.clone(),
+            interval,
+        ));
+        if let Some(previous) = self.active.lock().unwrap().insert(uri.to_string(), task) {
+            previous.abort();
+        }
+    }
+
+    /// End the subscription to `uri`, `false` if there was none
+    pub(crate) fn remove(&self, uri: &str) -> bool {
+        match self.active.lock().unwrap().remove(uri) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// URIs of the active subscriptions
+    pub(crate) fn uris(&self) -> Vec<String> {
+        self.active.lock().unwrap().keys().cloned().collect()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.active.lock().unwrap().len()
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        for (_, task) in self.active.get_mut().unwrap().drain() {
+            task.abort();
+        }
+    }
+}
+
+/// Send each update of `uri` as a notification, at most one per `interval`
+async fn forward(
+    uri: String,
+    mut updates: mpsc::Receiver<ResourceContents>,
+    notifications: broadcast::Sender<McpNotification>,
+    interval: Duration,
+) {
+    let mut next = Instant::now();
+    while let Some(mut contents) = updates.recv().await {
+        if Instant::now() < next {
+            tokio::time::sleep_until(next).await;
+            while let Ok(newer) = updates.try_recv() {
+                contents = newer;
+            }
+        }
+        // Fails only while no event stream of the client is open
+        let _ = notifications.send(McpNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/resources/updated".to_string(),
+            params: Some(json!({ "uri": uri, "contents": [contents] })),
+        });
+        next = Instant::now() + interval;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(text: &str) -> ResourceContents {
+        ResourceContents {
+            uri: "test://counter".to_string(),
+            mime_type: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_uri_template() {
+        let template = UriTemplate::parse("ros3://topic/{name}").unwrap();
+        assert_eq!(template.variables().collect::<Vec<_>>(), ["name"]);
+        let values = template.matches("ros3://topic/robot/state").unwrap();
+        assert_eq!(values["name"], "robot/state");
+        assert_eq!(template.expand(&values), "ros3://topic/robot/state");
+        assert!(template.matches("ros3://topic/").is_none());
+        assert!(template.matches("ros3://service/add").is_none());
+        assert_eq!(template.to_string(), "ros3://topic/{name}");
+
+        let template = UriTemplate::parse("robot://{robot}/joints/{joint}.json").unwrap();
+        let values = template.matches("robot://arm/joints/elbow.json").unwrap();
+        assert_eq!(
+            (values["robot"].as_str(), values["joint"].as_str()),
+            ("arm", "elbow")
+        );
+        assert!(template.matches("robot://arm/joints/elbow.yaml").is_none());
+
+        assert!(UriTemplate::parse("robot://{robot").is_err());
+        assert!(UriTemplate::parse("robot://{}").is_err());
+        assert!(UriTemplate::parse("robot://{a}{b}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_updates_are_rate_limited_to_the_latest() {
+        let subscriptions = Subscriptions::new();
+        let mut notifications = subscriptions.notifications();
+        let (updates, receiver) = mpsc::channel(16);
+        subscriptions.add("test://counter", receiver, Duration::from_millis(200));
+
+        updates.send(contents("1")).await.unwrap();
+        let first = notifications.recv().await.unwrap();
+        assert_eq!(first.method, "notifications/resources/updated");
+        assert_eq!(first.params.unwrap()["contents"][0]["text"], "1");
+
+        let sent = Instant::now();
+        for text in ["2", "3", "4"] {
+            updates.send(contents(text)).await.unwrap();
+        }
+        let coalesced = notifications.recv().await.unwrap();
+        assert!(sent.elapsed() >= Duration::from_millis(150));
+        assert_eq!(coalesced.params.unwrap()["contents"][0]["text"], "4");
+        assert!(notifications.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_releases_the_provider() {
+        let subscriptions = Subscriptions::new();
+        let (updates, receiver) = mpsc::channel::<ResourceContents>(1);
+        subscriptions.add("test://counter", receiver, DEFAULT_UPDATE_INTERVAL);
+        assert_eq!(subscriptions.len(), 1);
+
+        assert!(subscriptions.remove("test://counter"));
+        assert!(!subscriptions.remove("test://counter"));
+        tokio::time::timeout(Duration::from_secs(1), updates.closed())
+            .await
+            .expect("the update channel was closed");
+
+        let (updates, receiver) = mpsc::channel::<ResourceContents>(1);
+        subscriptions.add("test://counter", receiver, DEFAULT_UPDATE_INTERVAL);
+        drop(subscriptions);
+        tokio::time::timeout(Duration::from_secs(1), updates.closed())
+            .await
+            .expect("dropping the client closed the update channel");
+    }
+}
@@ -0,0 +1,984 @@
+//! Standard tools and resources exposing a node's topics to the model
+//!
+//! [`McpServer::register_ros3_tools`] registers:
+//!
+//! - `ros3_list_topics`: the topics of the graph with their types and
+//!   endpoint counts, the services, and the schema of every registered
+//!   message type
+//! - `ros3_publish`: publish a JSON message, checked against the schema of
+//!   its type and the type of the topic first
+//! - `ros3_get_last_message`: wait for the next message on a topic, or the
+//!   latched one
+//! - `ros3_sample_topic`: collect messages from a topic for a number of
+//!   messages or seconds, whichever comes first. A client following the
+//!   call's [progress](crate::progress) gets a content chunk per message as
+//!   it arrives, and cancelling the call ends the subscription.
+//! - `ros3_call_service`: call a service in this process with a JSON request
+//! - `ros3_list_params`: the parameters of every node, or of one, with
+//!   their values and constraints
+//! - `ros3_get_param`: the value of one parameter
+//! - `ros3_set_param`: change one parameter, or several of a node at once;
+//!   a batch applies entirely or not at all
+//! - `ros3_get_lifecycle_state`: the state of every lifecycle node, or of one
+//! - `ros3_change_lifecycle_state`: configure, activate, deactivate, clean up
+//!   or shut down a lifecycle node
+//! - `ros3_list_loaded_nodes`: the nodes loaded into every node container,
+//!   with their factory, namespace and status, optionally for one container
+//!
+//! Failed service calls return a JSON object with an `error` code
+//! (`service_unavailable`, `timeout`, `invalid_request` or `service_failed`)
+//! and whether retrying may help, so the model can decide what to do next.
+//! Failed parameter calls do the same with `unknown_node`,
+//! `unknown_parameter`, `invalid_value` or `rejected`; a rejection carries
+//! the parameter server's `reason`, such as `value outside [0, 10]`, so the
+//! model can correct the value. Lifecycle calls fail with `unknown_node`,
+//! `invalid_transition` or `transition_failed`, along with the node's state.
+//!
+//! Types and schemas come from the message type registry
+//! ([`agentic_robotics_core::types`]), so the tools know every type used by
+//! an endpoint in this process.
+//!
+//! [`McpServer::register_ros3_resources`] also makes every topic a
+//! [resource](crate::resources) `ros3://topic/<name>`, read as the latest
+//! message in JSON and sending an update per message to subscribed clients.
+
+use crate::progress::ProgressSink;
+use crate::resources::ResourceProvider;
+use crate::{
+    schema, server, BoxFuture, McpResource, McpResourceTemplate, McpServer, McpTool,
+    ResourceContents,
+};
+use crate::{ContentItem, ToolResult};
+use agentic_robotics_core::recording::JsonConverter;
+use agentic_robotics_core::serialization::Format;
+use agentic_robotics_core::{
+    graph, service, types, Error, Message, Node, ParameterValue, Publisher, RawMessage, Subscriber,
+    Transition,
+};
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Start of the URIs of topic resources
+pub const TOPIC_URI_PREFIX: &str = "ros3://topic/";
+
+/// Longest a sampling tool waits for messages
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// Most messages `ros3_sample_topic` returns
+const MAX_SAMPLES: u64 = 1000;
+
+/// Longest `ros3_call_service` waits for a response, in milliseconds
+const MAX_CALL_TIMEOUT_MS: u64 = 60_000;
+
+impl McpServer {
+    /// Register the standard ros3 tools, publishing and subscribing through
+    /// `node`; see the [module documentation](crate::ros3)
+    ///
+    /// The `ros3_publish` description lists the types registered at the
+    /// time of the call, but types registered later can be published too.
+    pub async fn register_ros3_tools(&self, node: Arc<Node>) -> Result<()> {
+        self.register_tool(
+            McpTool {
+                name: "ros3_list_topics".to_string(),
+                description: "List the topics of the robot with their message type and \
+                              number of publishers and subscribers, the services with their \
+                              request and response types, and the JSON Schema of every known \
+                              message type"
+                    .to_string(),
+                input_schema: json!({ "type": "object", "properties": {} }),
+            },
+            server::tool(|_| Ok(list_topics())),
+        )
+        .await?;
+
+        let tools = Arc::new(Ros3Tools {
+            node,
+            publishers: Mutex::new(HashMap::new()),
+        });
+        let publish = tools.clone();
+        self.register_tool(
+            McpTool {
+                name: "ros3_publish".to_string(),
+                description: publish_description(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "topic": { "type": "string", "description": "Topic name" },
+                        "type": { "type": "string", "description": "Message type name" },
+                        "message": { "description": "The message as JSON" },
+                    },
+                    "required": ["topic", "type", "message"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::async_tool(move |args| {
+                let tools = publish.clone();
+                async move { tools.publish(args).await }
+            }),
+        )
+        .await?;
+
+        let last = tools.clone();
+        self.register_tool(
+            McpTool {
+                name: "ros3_get_last_message".to_string(),
+                description: "Get the latest message on a topic as JSON: the latched message \
+                              if the topic keeps one, otherwise the next message published \
+                              within `timeout_secs` (default 5)"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "topic": { "type": "string", "description": "Topic name" },
+                        "timeout_secs": { "type": "number", "minimum": 0, "maximum": 60 },
+                    },
+                    "required": ["topic"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::async_tool(move |args| {
+                let tools = last.clone();
+                async move {
+                    let timeout = seconds(&args["timeout_secs"], 5.0);
+                    tools.sample(&args["topic"], 1, timeout, None).await
+                }
+            }),
+        )
+        .await?;
+
+        let sample = tools;
+        self.register_tool(
+            McpTool {
+                name: "ros3_sample_topic".to_string(),
+                description: "Subscribe to a topic and return the messages received as JSON, \
+                              stopping after `count` messages (default 10) or `duration_secs` \
+                              seconds (default 1), whichever comes first"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "topic": { "type": "string", "description": "Topic name" },
+                        "count": { "type": "integer", "minimum": 1, "maximum": MAX_SAMPLES },
+                        "duration_secs": { "type": "number", "minimum": 0, "maximum": 60 },
+                    },
+                    "required": ["topic"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::progress_tool(move |args, progress| {
+                let tools = sample.clone();
+                async move {
+                    let count = args["count"].as_u64().unwrap_or(10);
+                    let duration = seconds(&args["duration_secs"], 1.0);
+                    tools
+                        .sample(&args["topic"], count, duration, Some(progress))
+                        .await
+                }
+            }),
+        )
+        .await?;
+
+        self.register_tool(
+            McpTool {
+                name: "ros3_call_service".to_string(),
+                description: "Call a service with a JSON request of its request type and \
+                              return the response as JSON, waiting at most `timeout_ms` \
+                              (default 5000). Failures return a JSON object with an `error` \
+                              code and `retryable`; ros3_list_topics lists the services"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "service": { "type": "string", "description": "Service name" },
+                        "request": { "description": "The request as JSON" },
+                        "timeout_ms": {
+                            "type": "integer",
+                            "minimum": 1,
+                            "maximum": MAX_CALL_TIMEOUT_MS,
+                        },
+                    },
+                    "required": ["service", "request"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::async_tool(call_service),
+        )
+        .await?;
+
+        self.register_tool(
+            McpTool {
+                name: "ros3_list_params".to_string(),
+                description: "List the parameters of every node, or of `node`, with their \
+                              type, value, description, range and whether they are read-only"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "node": { "type": "string", "description": "Node name" },
+                    },
+                    "additionalProperties": false,
+                }),
+            },
+            server::tool(|args| Ok(list_params(&args))),
+        )
+        .await?;
+
+        self.register_tool(
+            McpTool {
+                name: "ros3_get_param".to_string(),
+                description: "Get the type and value of a parameter of a node".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "node": { "type": "string", "description": "Node name" },
+                        "name": { "type": "string", "description": "Parameter name" },
+                    },
+                    "required": ["node", "name"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::tool(|args| Ok(get_param(&args))),
+        )
+        .await?;
+
+        self.register_tool(
+            McpTool {
+                name: "ros3_set_param".to_string(),
+                description: "Set a parameter of a node to `value`, or several at once with \
+                              `parameters`; a batch is applied entirely or not at all. Values \
+                              are checked against the declared type and range, and a rejected \
+                              change returns the reason and leaves every parameter unchanged"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "node": { "type": "string", "description": "Node name" },
+                        "name": { "type": "string", "description": "Parameter name" },
+                        "value": { "description": "New value" },
+                        "parameters": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": { "type": "string" },
+                                    "value": {},
+                                },
+                                "required": ["name", "value"],
+                                "additionalProperties": false,
+                            },
+                            "minItems": 1,
+                        },
+                    },
+                    "required": ["node"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::tool(|args| Ok(set_param(&args))),
+        )
+        .await?;
+
+        self.register_tool(
+            McpTool {
+                name: "ros3_get_lifecycle_state".to_string(),
+                description: "Get the lifecycle state (unconfigured, inactive, active or \
+                              finalized) of every lifecycle node, or of `node`"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "node": { "type": "string", "description": "Node name" },
+                    },
+                    "additionalProperties": false,
+                }),
+            },
+            server::tool(|args| Ok(lifecycle_state(&args))),
+        )
+        .await?;
+
+        self.register_tool(
+            McpTool {
+                name: "ros3_change_lifecycle_state".to_string(),
+                description: "Run a lifecycle transition of a node. Nodes go from unconfigured \
+                              to inactive with `configure`, from inactive to active with \
+                              `activate` and back with `deactivate`, from inactive to \
+                              unconfigured with `cleanup`, and to finalized with `shutdown`"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "node": { "type": "string", "description": "Node name" },
+                        "transition": {
+                            "type": "string",
+                            "enum": Transition::ALL.map(|t| t.to_string()),
+                        },
+                    },
+                    "required": ["node", "transition"],
+                    "additionalProperties": false,
+                }),
+            },
+            server::async_tool(change_lifecycle_state),
+        )
+        .await?;
+
+        self.register_tool(
+            McpTool {
+                name: "ros3_list_loaded_nodes".to_string(),
+                description: "List the nodes loaded into node containers, with the factory \
+                              they came from, their namespace and whether they are running, \
+                              restarting after a panic, completed or failed"
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "container": { "type": "string", "description": "Container name" },
+                    },
+                    "additionalProperties": false,
+                }),
+            },
+            server::tool(|args| Ok(loaded_nodes(&args))),
+        )
+        .await
+    }
+}
+
+/// State shared by the tools that use the node
+struct Ros3Tools {
+    node: Arc<Node>,
+    /// Publishers created by `ros3_publish`, kept so latched topics keep
+    /// their last message
+    publishers: Mutex<HashMap<String, Arc<Publisher<RawMessage>>>>,
+}
+
+impl Ros3Tools {
+    async fn publish(&self, args: Value) -> Result<ToolResult> {
+        let topic = args["topic"].as_str().unwrap_or_default();
+        let type_name = args["type"].as_str().unwrap_or_default();
+        let topic = match self.node.resolve(topic) {
+            Ok(topic) => topic.to_string(),
+            Err(e) => return Ok(server::error_response(e.to_string())),
+        };
+        let Some(message_type) = types::lookup(type_name) else {
+            let known: Vec<&str> = types::registered().iter().map(|ty| ty.name()).collect();
+            return Ok(server::error_response(format!(
+                "Unknown message type '{}'; known types: {}",
+                type_name,
+                known.join(", ")
+            )));
+        };
+        let topic_type = graph::list_topics()
+            .into_iter()
+            .find(|info| info.name == topic)
+            .and_then(|info| info.type_name);
+        if let Some(topic_type) = topic_type.filter(|topic_type| topic_type != type_name) {
+            return Ok(server::error_response(format!(
+                "Topic {} carries {} messages, not {}",
+                topic, topic_type, type_name
+            )));
+        }
+
+        let message = args["message"].clone();
+        if let Err(errors) = schema::validate(&message_type.schema(), &message) {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return Ok(server::error_response(format!(
+                "Invalid {} message:\n{}",
+                type_name,
+                errors.join("\n")
+            )));
+        }
+        // Generic JSON values have no fixed layout for CDR
+        let format = if type_name == Value::type_name() {
+            Format::Json
+        } else {
+            Format::Cdr
+        };
+        let raw = match message_type.from_json(message, format) {
+            Ok(raw) => raw,
+            Err(e) => {
+                return Ok(server::error_response(format!(
+                    "Invalid {} message: {}",
+                    type_name, e
+                )))
+            }
+        };
+
+        let publisher = self.publisher(&topic)?;
+        publisher.publish(&raw).await?;
+        Ok(server::text_response(format!(
+            "Published a {} message on {}",
+            type_name, topic
+        )))
+    }
+
+    fn publisher(&self, topic: &str) -> Result<Arc<Publisher<RawMessage>>> {
+        let mut publishers = self.publishers.lock().unwrap();
+        if let Some(publisher) = publishers.get(topic) {
+            return Ok(publisher.clone());
+        }
+        let publisher = Arc::new(self.node.create_publisher::<RawMessage>(topic)?);
+        publishers.insert(topic.to_string(), publisher.clone());
+        Ok(publisher)
+    }
+
+    /// Collect up to `count` messages from `topic` for at most `wait`
+    ///
+    /// Returns the last message alone without `progress`. With it, sends a
+    /// chunk per message if the client follows the call's progress.
+    async fn sample(
+        &self,
+        topic: &Value,
+        count: u64,
+        wait: Duration,
+        progress: Option<ProgressSink>,
+    ) -> Result<ToolResult> {
+        let topic = topic.as_str().unwrap_or_default();
+        let subscriber = match self.node.create_subscriber::<RawMessage>(topic) {
+            Ok(subscriber) => subscriber,
+            Err(e) => return Ok(server::error_response(e.to_string())),
+        };
+        let topic = subscriber.topic().to_string();
+        let deadline = Instant::now() + wait.min(MAX_WAIT);
+        let streaming = progress.as_ref().filter(|progress| progress.is_requested());
+        let mut messages = Vec::new();
+        while (messages.len() as u64) < count.min(MAX_SAMPLES) {
+            let message = match tokio::time::timeout_at(deadline, subscriber.recv()).await {
+                Ok(raw) => to_json(&raw?),
+                Err(_) => break,
+            };
+            if let Some(progress) = streaming {
+                progress.content(ContentItem::Text {
+                    text: serde_json::to_string(&message)?,
+                });
+            }
+            messages.push(message);
+        }
+
+        if streaming.is_some() {
+            // The messages went out, and make up the result, as chunks
+            let summary = json!({ "topic": topic, "count": messages.len() });
+            return Ok(server::text_response(summary.to_string()));
+        }
+        if progress.is_none() {
+            return Ok(match messages.pop() {
+                Some(message) => server::text_response(serde_json::to_string_pretty(&message)?),
+                None => server::error_response(format!(
+                    "No message on {} within {:?}",
+                    topic,
+                    wait.min(MAX_WAIT)
+                )),
+            });
+        }
+        let result = json!({ "topic": topic, "count": messages.len(), "messages": messages });
+        Ok(server::text_response(serde_json::to_string_pretty(
+            &result,
+        )?))
+    }
+}
+
+async fn call_service(args: Value) -> Result<ToolResult> {
+    let service = args["service"].as_str().unwrap_or_default();
+    let timeout = Duration::from_millis(args["timeout_ms"].as_u64().unwrap_or(5000));
+    let Some(info) = graph::list_services()
+        .into_iter()
+        .find(|info| info.name == service)
+    else {
+        let known: Vec<String> = graph::list_services()
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+        let message = format!(
+            "No server for service '{}'; known services: {}",
+            service,
+            known.join(", ")
+        );
+        return Ok(service_error(service, "service_unavailable", message, true));
+    };
+
+    let request = args["request"].clone();
+    if let Some(request_type) = types::lookup(&info.request_type) {
+        if let Err(errors) = schema::validate(&request_type.schema(), &request) {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            let message = format!(
+                "Invalid {} request:\n{}",
+                info.request_type,
+                errors.join("\n")
+            );
+            return Ok(service_error(service, "invalid_request", message, false));
+        }
+    }
+
+    match service::call_json(service, request, timeout).await {
+        Ok(response) => Ok(server::text_response(serde_json::to_string_pretty(
+            &response,
+        )?)),
+        Err(e) => {
+            let (code, retryable) = match &e {
+                Error::ServiceUnavailable(_) => ("service_unavailable", true),
+                Error::Timeout(_) => ("timeout", true),
+                Error::Serialization(_) | Error::TopicTypeMismatch { .. } => {
+                    ("invalid_request", false)
+                }
+                _ => ("service_failed", false),
+            };
+            Ok(service_error(service, code, e, retryable))
+        }
+    }
+}
+
+/// A failed service call as a JSON error object
+fn service_error(service: &str, code: &str, message: impl ToString, retryable: bool) -> ToolResult {
+    let error = json!({
+        "error": code,
+        "service": service,
+        "message": message.to_string(),
+        "retryable": retryable,
+    });
+    server::error_response(error.to_string())
+}
+
+/// The parameters of `node`, or of every node, as JSON text
+fn list_params(args: &Value) -> ToolResult {
+    let node = args["node"].as_str();
+    let params: Vec<Value> = graph::list_parameters()
+        .into_iter()
+        .filter(|param| node.is_none_or(|node| param.node == node))
+        .map(|param| {
+            json!({
+                "node": param.node,
+                "name": param.name,
+                "type": param.value.type_name(),
+                "value": param_json(&param.value),
+                "description": param.descriptor.description,
+                "read_only": param.descriptor.read_only,
+                "range": param.descriptor.range,
+            })
+        })
+        .collect();
+    match serde_json::to_string_pretty(&params) {
+        Ok(text) => server::text_response(text),
+        Err(e) => server::error_response(e.to_string()),
+    }
+}
+
+fn get_param(args: &Value) -> ToolResult {
+    let node = args["node"].as_str().unwrap_or_default();
+    let name = args["name"].as_str().unwrap_or_default();
+    let Some(params) = graph::parameter_server(node) else {
+        return unknown_node(node);
+    };
+    match params.get_value(name) {
+        Some(value) => {
+            let param = json!({
+                "node": node,
+                "name": name,
+                "type": value.type_name(),
+                "value": param_json(&value),
+            });
+            server::text_response(param.to_string())
+        }
+        None => param_error(
+            node,
+            name,
+            "unknown_parameter",
+            format!("Node {} has no parameter '{}'", node, name),
+        ),
+    }
+}
+
+/// Set one parameter, or a batch of them atomically
+fn set_param(args: &Value) -> ToolResult {
+    let node = args["node"].as_str().unwrap_or_default();
+    let Some(params) = graph::parameter_server(node) else {
+        return unknown_node(node);
+    };
+    let requested: Vec<(&str, &Value)> = match args["parameters"].as_array() {
+        Some(batch) => batch
+            .iter()
+            .map(|param| (param["name"].as_str().unwrap_or_default(), &param["value"]))
+            .collect(),
+        None if args.get("name").is_some() && args.get("value").is_some() => {
+            vec![(args["name"].as_str().unwrap_or_default(), &args["value"])]
+        }
+        None => {
+            return server::error_response(
+                "Give either `name` and `value`, or `parameters`".to_string(),
+            )
+        }
+    };
+
+    let mut values = Vec::new();
+    for (name, value) in requested {
+        let declared = params.get_value(name);
+        let Some(value) = param_value(value, declared.as_ref()) else {
+            let message = format!(
+                "{} is not a parameter value; parameters are bools, integers, doubles, \
+                 strings or arrays of numbers",
+                value
+            );
+            return param_error(node, name, "invalid_value", message);
+        };
+        values.push((name, value));
+    }
+    if let Err(e) = params.set_all(values.iter().map(|(name, value)| (*name, value.clone()))) {
+        let Error::InvalidParameter { name, reason } = &e else {
+            return server::error_response(e.to_string());
+        };
+        // Names are qualified with the node
+        let name = name
+            .strip_prefix(node)
+            .and_then(|name| name.strip_prefix('.'))
+            .unwrap_or(name);
+        let error = json!({
+            "error": "rejected",
+            "node": node,
+            "name": name,
+            "reason": reason,
+            "message": e.to_string(),
+            "retryable": false,
+        });
+        return server::error_response(error.to_string());
+    }
+
+    let set: Vec<Value> = values
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": param_json(value) }))
+        .collect();
+    server::text_response(json!({ "node": node, "set": set }).to_string())
+}
+
+fn unknown_node(node: &str) -> ToolResult {
+    let mut nodes: Vec<String> = graph::list_parameters()
+        .into_iter()
+        .map(|param| param.node)
+        .collect();
+    nodes.dedup();
+    let message = format!(
+        "No node '{}' with parameters; nodes with parameters: {}",
+        node,
+        nodes.join(", ")
+    );
+    let error = json!({
+        "error": "unknown_node",
+        "node": node,
+        "message": message,
+        "retryable": false,
+    });
+    server::error_response(error.to_string())
+}
+
+/// A failed parameter call as a JSON error object
+fn param_error(node: &str, name: &str, code: &str, message: String) -> ToolResult {
+    let error = json!({
+        "error": code,
+        "node": node,
+        "name": name,
+        "message": message,
+        "retryable": false,
+    });
+    server::error_response(error.to_string())
+}
+
+/// `value` as a parameter value, of the `declared` one's type where JSON
+/// cannot tell, e.g. `1` for a double
+fn param_value(value: &Value, declared: Option<&ParameterValue>) -> Option<ParameterValue> {
+    Some(match (declared, value) {
+        (Some(ParameterValue::Double(_)), Value::Number(number)) => {
+            ParameterValue::Double(number.as_f64()?)
+        }
+        (_, Value::Bool(value)) => ParameterValue::Bool(*value),
+        (_, Value::Number(number)) => match number.as_i64() {
+            Some(value) => ParameterValue::Integer(value),
+            None => ParameterValue::Double(number.as_f64()?),
+        },
+        (_, Value::String(value)) => ParameterValue::String(value.clone()),
+        (_, Value::Array(values)) => {
+            ParameterValue::DoubleArray(values.iter().map(Value::as_f64).collect::<Option<_>>()?)
+        }
+        _ => return None,
+    })
+}
+
+/// A parameter value as plain JSON
+fn param_json(value: &ParameterValue) -> Value {
+    match value {
+        ParameterValue::Bool(value) => json!(value),
+        ParameterValue::Integer(value) => json!(value),
+        ParameterValue::Double(value) => json!(value),
+        ParameterValue::String(value) => json!(value),
+        ParameterValue::DoubleArray(values) => json!(values),
+    }
+}
+
+/// The states of every lifecycle node, or of one, as JSON text
+fn lifecycle_state(args: &Value) -> ToolResult {
+    let node = args["node"].as_str();
+    let nodes: Vec<_> = graph::list_lifecycle_nodes()
+        .into_iter()
+        .filter(|info| node.is_none_or(|node| info.node == node))
+        .collect();
+    if let (Some(node), true) = (node, nodes.is_empty()) {
+        return unknown_lifecycle_node(node);
+    }
+    match serde_json::to_string_pretty(&nodes) {
+        Ok(text) => server::text_response(text),
+        Err(e) => server::error_response(e.to_string()),
+    }
+}
+
+async fn change_lifecycle_state(args: Value) -> Result<ToolResult> {
+    let node = args["node"].as_str().unwrap_or_default();
+    let name = args["transition"].as_str().unwrap_or_default();
+    let Some(lifecycle) = graph::lifecycle_node(node) else {
+        return Ok(unknown_lifecycle_node(node));
+    };
+    let Some(transition) = Transition::from_name(name) else {
+        return Ok(server::error_response(format!(
+            "Unknown transition '{}'",
+            name
+        )));
+    };
+    match lifecycle.trigger(transition).await {
+        Ok(state) => {
+            let result = json!({ "node": node, "transition": transition, "state": state });
+            Ok(server::text_response(result.to_string()))
+        }
+        Err(e) => {
+            // A failed hook may succeed once whatever it waits for is ready
+            let (code, retryable) = match &e {
+                Error::InvalidTransition { .. } => ("invalid_transition", false),
+                _ => ("transition_failed", true),
+            };
+            let error = json!({
+                "error": code,
+                "node": node,
+                "state": lifecycle.state(),
+                "message": e.to_string(),
+                "retryable": retryable,
+            });
+            Ok(server::error_response(error.to_string()))
+        }
+    }
+}
+
+fn unknown_lifecycle_node(node: &str) -> ToolResult {
+    let nodes: Vec<String> = graph::list_lifecycle_nodes()
+        .into_iter()
+        .map(|info| info.node)
+        .collect();
+    let error = json!({
+        "error": "unknown_node",
+        "node": node,
+        "message": format!(
+            "No lifecycle node '{}'; lifecycle nodes: {}",
+            node,
+            nodes.join(", ")
+        ),
+        "retryable": false,
+    });
+    server::error_response(error.to_string())
+}
+
+/// The nodes loaded into every container, or into one, as JSON text
+fn loaded_nodes(args: &Value) -> ToolResult {
+    let container = args["container"].as_str();
+    let nodes: Vec<_> = graph::list_loaded_nodes()
+        .into_iter()
+        .filter(|node| container.is_none_or(|container| node.container == container))
+        .collect();
+    match serde_json::to_string_pretty(&nodes) {
+        Ok(text) => server::text_response(text),
+        Err(e) => server::error_response(e.to_string()),
+    }
+}
+
+/// The graph and the registered types, as JSON text
+fn list_topics() -> ToolResult {
+    let topics = graph::list_topics();
+    let services = graph::list_services();
+    let types: Map<String, Value> = types::registered()
+        .into_iter()
+        .map(|ty| (ty.name().to_string(), ty.schema()))
+        .collect();
+    let result = json!({ "topics": topics, "services": services, "types": types });
+    match serde_json::to_string_pretty(&result) {
+        Ok(text) => server::text_response(text),
+        Err(e) => server::error_response(e.to_string()),
+    }
+}
+
+/// Description of `ros3_publish` with the schema of every registered type
+fn publish_description() -> String {
+    let mut description = "Publish a message on a topic. `message` is the JSON form of \
+                           `type` and is checked against its schema first; a topic that \
+                           already has a type only accepts that type. Message types and \
+                           their JSON Schemas:"
+        .to_string();
+    for ty in types::registered() {
+        let schema = ty.schema();
+        let schema = match schema {
+            Value::Bool(true) => "any JSON".to_string(),
+            schema => schema.to_string(),
+        };
+        description.push_str(&format!("\n- {}: {}", ty.name(), schema));
+    }
+    description
+}
+
+impl McpServer {
+    /// Make every topic a resource, see [`TopicResources`]
+    pub async fn register_ros3_resources(&self) -> Result<()> {
+        self.register_resource_provider(Arc::new(TopicResources::new()))
+            .await
+    }
+}
+
+/// Every topic of the graph as a resource
+///
+/// The URI of topic `/robot_state` is `ros3://topic/robot_state`, listed
+/// as the template `ros3://topic/{name}` next to the topics. Reading
+/// it returns the topic's latched message, or else the latest message a
+/// subscription saw, as JSON. A subscription holds a subscriber on the
+/// topic until it ends.
+#[derive(Default)]
+pub struct TopicResources {
+    /// Latest message seen by a subscription, by topic
+    latest: Arc<Mutex<HashMap<String, ResourceContents>>>,
+}
+
+impl TopicResources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The resource URI of `topic`
+    pub fn uri(topic: &str) -> String {
+        format!("{}{}", TOPIC_URI_PREFIX, topic.trim_start_matches('/'))
+    }
+
+    /// The topic `uri` names, if it exists
+    fn topic(uri: &str) -> Option<String> {
+        let name = uri.strip_prefix(TOPIC_URI_PREFIX)?;
+        let topics: Vec<String> = graph::list_topics()
+            .into_iter()
+            .map(|topic| topic.name)
+            .collect();
+        let absolute = format!("/{}", name);
+        // `/a` and `a` are different topics sharing a URI; prefer `/a`
+        topics
+            .iter()
+            .find(|topic| **topic == absolute)
+            .or_else(|| topics.iter().find(|topic| *topic == name))
+            .cloned()
+    }
+
+    fn read_topic(&self, uri: &str) -> Result<ResourceContents> {
+        let topic = Self::topic(uri).ok_or_else(|| anyhow::anyhow!("no topic for {}", uri))?;
+        // A new subscriber starts with the latched message, if any
+        let subscriber = Subscriber::<RawMessage>::new(topic.as_str())?;
+        if let Some(raw) = subscriber.try_recv()? {
+            return Ok(topic_contents(uri, &raw));
+        }
+        self.latest
+            .lock()
+            .unwrap()
+            .get(&topic)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("no message on {} yet", topic))
+    }
+}
+
+impl ResourceProvider for TopicResources {
+    fn list(&self) -> Vec<McpResource> {
+        graph::list_topics()
+            .into_iter()
+            .map(|topic| McpResource {
+                uri: Self::uri(&topic.name),
+                description: Some(match &topic.type_name {
+                    Some(type_name) => format!("Latest {} message", type_name),
+                    None => "Latest message".to_string(),
+                }),
+                name: topic.name,
+                mime_type: Some("application/json".to_string()),
+            })
+            .collect()
+    }
+
+    fn templates(&self) -> Vec<McpResourceTemplate> {
+        vec![McpResourceTemplate {
+            uri_template: format!("{}{{name}}", TOPIC_URI_PREFIX),
+            name: "Topic".to_string(),
+            description: Some("Latest message of topic `/name`".to_string()),
+            mime_type: Some("application/json".to_string()),
+        }]
+    }
+
+    fn handles(&self, uri: &str) -> bool {
+        Self::topic(uri).is_some()
+    }
+
+    fn read(&self, uri: &str) -> BoxFuture<Result<ResourceContents>> {
+        let contents = self.read_topic(uri);
+        Box::pin(async move { contents })
+    }
+
+    fn subscribe(&self, uri: &str) -> Result<mpsc::Receiver<ResourceContents>> {
+        let topic = Self::topic(uri).ok_or_else(|| anyhow::anyhow!("no topic for {}", uri))?;
+        let subscriber = Subscriber::<RawMessage>::new(topic.as_str())?;
+        let (updates, receiver) = mpsc::channel(16);
+        let (uri, latest) = (uri.to_string(), self.latest.clone());
+        tokio::spawn(async move {
+            loop {
+                let raw = tokio::select! {
+                    _ = updates.closed() => break,
+                    raw = subscriber.recv() => match raw {
+                        Ok(raw) => raw,
+                        Err(_) => break,
+                    },
+                };
+                let contents = topic_contents(&uri, &raw);
+                latest
+                    .lock()
+                    .unwrap()
+                    .insert(topic.clone(), contents.clone());
+                if updates.send(contents).await.is_err() {
+                    break;
+                }
+            }
+            tracing::debug!("Resource subscription to {} ended", topic);
+        });
+        Ok(receiver)
+    }
+}
+
+/// A message of the topic at `uri` as resource contents
+fn topic_contents(uri: &str, raw: &RawMessage) -> ResourceContents {
+    ResourceContents {
+        uri: uri.to_string(),
+        mime_type: Some("application/json".to_string()),
+        text: to_json(raw).to_string(),
+    }
+}
+
+/// A received message as JSON, with the error in place of undecodable ones
+fn to_json(raw: &RawMessage) -> Value {
+    let decoded = match types::lookup(&raw.type_name) {
+        Some(ty) => ty.to_json(raw),
+        // JSON and MessagePack payloads decode without their type
+        None => JsonConverter::new().convert(raw),
+    };
+    decoded.unwrap_or_else(|e| json!({ "type": raw.type_name, "error": e.to_string() }))
+}
+
+/// A duration argument in seconds, `default` if absent
+fn seconds(value: &Value, default: f64) -> Duration {
+    Duration::from_secs_f64(
+        value
+            .as_f64()
+            .unwrap_or(default)
+            .clamp(0.0, MAX_WAIT.as_secs_f64()),
+    )
+}
@@ -0,0 +1,88 @@
+//! Validation of tool arguments against a tool's `input_schema`
+//!
+//! [`validate`] is the validator of
+//! [`agentic_robotics_core::schema`], which also checks messages; it
+//! supports the subset of JSON Schema that tool descriptions and the
+//! schemas generated by [`schema_for`] use.
+
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde_json::Value;
+
+pub use agentic_robotics_core::schema::{validate, SchemaError};
+
+/// The JSON Schema of `T`, as generated by `schemars`
+///
+/// Subschemas are inlined where possible, so only recursive types refer to
+/// `definitions`, and `Option` fields accept `null`.
+pub fn schema_for<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| {
+            settings.inline_subschemas = true;
+            settings.option_add_null_type = true;
+        })
+        .into_generator();
+    let mut schema =
+        serde_json::to_value(generator.into_root_schema_for::<T>()).unwrap_or(Value::Bool(true));
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("$schema");
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(serde::Deserialize, JsonSchema)]
+    #[serde(rename_all = "snake_case", deny_unknown_fields)]
+    #[allow(dead_code)]
+    enum Gait {
+        Walk,
+        Run,
+    }
+
+    #[derive(serde::Deserialize, JsonSchema)]
+    #[serde(deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct MoveArgs {
+        location: String,
+        #[schemars(range(min = 0, max = 2))]
+        speed: Option<f64>,
+        gait: Gait,
+    }
+
+    #[test]
+    fn test_schema_for() {
+        let schema = schema_for::<MoveArgs>();
+        assert!(schema.get("$schema").is_none());
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], json!(["gait", "location"]));
+        assert_eq!(schema["additionalProperties"], false);
+        let speed = &schema["properties"]["speed"];
+        assert_eq!(speed["type"], json!(["number", "null"]));
+        assert_eq!(
+            (speed["minimum"].as_f64(), speed["maximum"].as_f64()),
+            (Some(0.0), Some(2.0))
+        );
+        assert_eq!(schema["properties"]["gait"]["enum"], json!(["walk", "run"]));
+
+        let args = json!({ "location": "kitchen", "speed": null, "gait": "run" });
+        assert_eq!(validate(&schema, &args), Ok(()));
+        let args = json!({ "location": "kitchen", "speed": "fast", "gait": "fly" });
+        let mut messages: Vec<String> = validate(&schema, &args)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            [
+                "/gait: must be one of [\"walk\",\"run\"]",
+                "/speed: expected number or null, found string",
+            ]
+        );
+    }
+}
@@ -1,6 +1,7 @@
 //! MCP Server utilities and builders
 
 use crate::*;
+use std::future::Future;
 use std::sync::Arc;
 
 /// MCP Server builder
@@ -28,11 +29,49 @@ impl ServerBuilder {
 }
 
 /// Helper to create a tool handler from a closure
+///
+/// The closure runs on the task serving the request, so it should not
+/// block; use [`async_tool`] for work that waits.
 pub fn tool<F>(f: F) -> ToolHandler
 where
     F: Fn(Value) -> Result<ToolResult> + Send + Sync + 'static,
 {
-    Arc::new(f)
+    Arc::new(move |args, _| {
+        let result = f(args);
+        Box::pin(async move { result })
+    })
+}
+
+/// Helper to create a tool handler from an async closure
+///
+/// Calls are served concurrently, so a slow tool does not hold up other
+/// requests.
+pub fn async_tool<F, Fut>(f: F) -> ToolHandler
+where
+    F: Fn(Value) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<ToolResult>> + Send + 'static,
+{
+    Arc::new(move |args, _| Box::pin(f(args)))
+}
+
+/// Helper to create a tool handler from an async closure that reports
+/// progress or streams content, see [`progress`](crate::progress)
+pub fn progress_tool<F, Fut>(f: F) -> ToolHandler
+where
+    F: Fn(Value, ProgressSink) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<ToolResult>> + Send + 'static,
+{
+    Arc::new(move |args, progress| Box::pin(f(args, progress)))
+}
+
+/// Helper to create a resource handler from an async closure called with
+/// the URI being read
+pub fn resource<F, Fut>(f: F) -> ResourceHandler
+where
+    F: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<ResourceContents>> + Send + 'static,
+{
+    Arc::new(move |uri| Box::pin(f(uri)))
 }
 
 /// Helper to create a text response
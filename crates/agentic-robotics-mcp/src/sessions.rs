@@ -0,0 +1,220 @@
+//! Connected clients and their per-session state
+//!
+//! Every client connected to a server is a session: each
+//! [`serve`](McpServer::serve) call, for instance one per stdio child
+//! process a supervisor runs, and each HTTP session started by
+//! `initialize`. A session has its own resource subscriptions, its own
+//! in-flight requests and its own share of the
+//! [rate limit](McpServer::with_rate_limit); none of it is visible to the
+//! others. [`McpServer::sessions`] lists the sessions connected now.
+//!
+//! A session ends when its stdio client closes its input or its HTTP
+//! session is deleted. Its subscriptions end with it, dropping the ros3
+//! subscribers they created.
+
+use crate::policy::ClientIdentity;
+use crate::{Client, McpServer};
+use agentic_robotics_core::Time;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A connected client, as listed by [`McpServer::sessions`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    /// The `Mcp-Session-Id` of HTTP sessions, a generated id otherwise
+    pub id: String,
+    pub client: ClientIdentity,
+    /// System time the client connected at
+    pub connected_at: Time,
+    /// Tool calls made, including denied and rate-limited ones
+    pub calls: u64,
+    /// Tool calls rejected by the rate limit
+    pub rate_limited: u64,
+    /// URIs of the resources the client is subscribed to, sorted
+    pub subscriptions: Vec<String>,
+}
+
+/// Tool calls allowed per session, see [`McpServer::with_rate_limit`]
+///
+/// A session may make `calls` calls in a burst, and regains one every
+/// `period / calls`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub calls: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    /// `calls` per `period`
+    pub fn new(calls: u32, period: Duration) -> Self {
+        Self { calls, period }
+    }
+
+    /// `calls` per second
+    pub fn per_second(calls: u32) -> Self {
+        Self::new(calls, Duration::from_secs(1))
+    }
+}
+
+impl std::fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} calls per {:?}", self.calls, self.period)
+    }
+}
+
+/// A session's remaining calls under a [`RateLimit`], full until first used
+#[derive(Debug, Default)]
+pub(crate) struct Bucket {
+    /// Calls left and when they were counted
+    state: Option<(f64, Instant)>,
+}
+
+impl Bucket {
+    /// Take a call at `now`, or return how long until one is available
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(limit.calls);
+        let rate = capacity / limit.period.as_secs_f64().max(f64::EPSILON);
+        let tokens = match self.state {
+            Some((tokens, at)) => {
+                (tokens + now.saturating_duration_since(at).as_secs_f64() * rate).min(capacity)
+            }
+            None => capacity,
+        };
+        if tokens >= 1.0 {
+            self.state = Some((tokens - 1.0, now));
+            Ok(())
+        } else {
+            self.state = Some((tokens, now));
+            Err(Duration::from_secs_f64((1.0 - tokens) / rate))
+        }
+    }
+}
+
+impl Client {
+    /// Why the tool call being made exceeds `limit`, if it does
+    pub(crate) fn throttle(&self, limit: &RateLimit) -> Option<String> {
+        let taken = self.bucket.lock().unwrap().take(limit, Instant::now());
+        match taken {
+            Ok(()) => None,
+            Err(wait) => {
+                self.rate_limited.fetch_add(1, Ordering::Relaxed);
+                Some(format!(
+                    "rate limit of {} exceeded, retry in {} ms",
+                    limit,
+                    wait.as_millis().max(1)
+                ))
+            }
+        }
+    }
+
+    /// Count a tool call, rejected or not
+    pub(crate) fn count_call(&self) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn info(&self, id: &str) -> SessionInfo {
+        let mut subscriptions = self.subscriptions.uris();
+        subscriptions.sort();
+        SessionInfo {
+            id: id.to_string(),
+            client: self.identity(),
+            connected_at: self.connected_at,
+            calls: self.calls.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            subscriptions,
+        }
+    }
+}
+
+impl McpServer {
+    /// Allow each session at most `limit` tool calls
+    ///
+    /// Calls over the limit never reach the tool; the agent gets a tool
+    /// error telling it when to retry:
+    ///
+    /// ```json
+    /// {"error":"rate_limited","tool":"ros3_publish","reason":"rate limit of 10 calls per 1s exceeded, retry in 80 ms"}
+    /// ```
+    ///
+    /// Calls made without a session, through
+    /// [`handle_request`](Self::handle_request), are not limited.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// The sessions connected now, oldest first
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|(_, client)| client.strong_count() > 0);
+        sessions
+            .iter()
+            .filter_map(|(id, client)| Some(client.upgrade()?.info(id)))
+            .collect()
+    }
+
+    /// List `client` as session `id` until it ends or is dropped
+    pub(crate) fn add_session(&self, id: &str, client: &Arc<Client>) {
+        client.set_session(id);
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|(_, client)| client.strong_count() > 0);
+        sessions.push((id.to_string(), Arc::downgrade(client)));
+    }
+
+    /// Stop listing session `id`, which ended
+    pub(crate) fn remove_session(&self, id: &str) {
+        self.sessions.lock().unwrap().retain(|(session, _)| session != id);
+    }
+}
+
+/// A fresh, unguessable session id
+///
+/// SipHash keyed by the standard library's random per-process keys is a
+/// pseudorandom function, so ids of successive sessions reveal nothing
+/// about each other.
+pub(crate) fn new_session_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let session = NEXT.fetch_add(1, Ordering::Relaxed);
+    let half = |half: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(session);
+        hasher.write_u64(half);
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", half(0), half(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let limit = RateLimit::new(2, Duration::from_secs(1));
+        let mut bucket = Bucket::default();
+        let start = Instant::now();
+        assert!(bucket.take(&limit, start).is_ok());
+        assert!(bucket.take(&limit, start).is_ok());
+        let wait = bucket.take(&limit, start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+
+        // One call regained every half second, never more than the burst
+        let later = start + Duration::from_millis(500);
+        assert!(bucket.take(&limit, later).is_ok());
+        assert!(bucket.take(&limit, later).is_err());
+        let much_later = later + Duration::from_secs(10);
+        assert!(bucket.take(&limit, much_later).is_ok());
+        assert!(bucket.take(&limit, much_later).is_ok());
+        assert!(bucket.take(&limit, much_later).is_err());
+    }
+
+    #[test]
+    fn test_session_ids_are_distinct() {
+        assert_ne!(new_session_id(), new_session_id());
+        assert_eq!(new_session_id().len(), 32);
+    }
+}
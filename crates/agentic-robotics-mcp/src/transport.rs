@@ -1,8 +1,128 @@
 //! MCP Transport implementations (stdio and SSE)
-
-use crate::{McpRequest, McpServer};
+//!
+//! See [`crate::http`] for the Streamable HTTP transport.
+
+use crate::policy::ClientIdentity;
+use crate::progress::Notifier;
+use crate::sessions::new_session_id;
+use crate::Client;
+use crate::McpServer;
 use anyhow::Result;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, mpsc};
+
+impl McpServer {
+    /// Serve newline-delimited JSON-RPC messages from stdin, answering on
+    /// stdout, until stdin is closed
+    ///
+    /// Nothing else may write to stdout meanwhile; log to stderr instead.
+    pub async fn serve_stdio(&self) -> Result<()> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+
+    /// Serve newline-delimited JSON-RPC messages from `reader`, answering
+    /// on `writer`, until `reader` reaches end of file
+    ///
+    /// Requests are handled concurrently, so responses may be written in a
+    /// different order than the requests arrived; clients match them by
+    /// `id`. [Notifications](McpServer::notify) are written between
+    /// responses. Returns once every request read has been answered, ending
+    /// the client's resource subscriptions.
+    ///
+    /// Each call serves its own [session](crate::sessions), so one server
+    /// can serve several clients at once, say one per child process.
+    pub async fn serve<R, W>(&self, reader: R, writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (responses, mut outgoing) = mpsc::unbounded_channel::<String>();
+        let writer = tokio::spawn(async move {
+            let mut writer = writer;
+            while let Some(response) = outgoing.recv().await {
+                writer.write_all(response.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        });
+
+        let client = Arc::new(Client::new(ClientIdentity {
+            transport: Some("stdio".to_string()),
+            ..ClientIdentity::default()
+        }));
+        let session = new_session_id();
+        self.add_session(&session, &client);
+        let mut notifications = self.subscribe();
+        let mut updates = client.subscriptions.notifications();
+        let forwarded = responses.clone();
+        let forwarder = tokio::spawn(async move {
+            loop {
+                let notification = tokio::select! {
+                    notification = notifications.recv() => notification,
+                    update = updates.recv() => update,
+                };
+                match notification {
+                    Ok(notification) => match serde_json::to_string(&notification) {
+                        Ok(notification) => {
+                            if forwarded.send(notification).is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => tracing::error!("Failed to encode MCP notification: {}", e),
+                    },
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("MCP client missed {} notifications", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let mut lines = BufReader::new(reader).lines();
+        let read = async {
+            while let Some(line) = lines.next_line().await? {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let server = self.clone();
+                let responses = responses.clone();
+                let client = client.clone();
+                let progress = responses.clone();
+                let notify: Notifier = Arc::new(move |notification| {
+                    if let Ok(notification) = serde_json::to_string(&notification) {
+                        let _ = progress.send(notification);
+                    }
+                });
+                tokio::spawn(async move {
+                    let handled = server.handle_client_message(&line, Some(&client), Some(notify));
+                    let Some(response) = handled.await else {
+                        return;
+                    };
+                    match serde_json::to_string(&response) {
+                        Ok(response) => {
+                            // Fails only if the writer gave up on a broken pipe
+                            let _ = responses.send(response);
+                        }
+                        Err(e) => tracing::error!("Failed to encode MCP response: {}", e),
+                    }
+                });
+            }
+            Ok::<_, std::io::Error>(())
+        }
+        .await;
+
+        // The writer ends once the last request task dropped its sender
+        forwarder.abort();
+        self.remove_session(&session);
+        drop(client);
+        drop(responses);
+        writer.await??;
+        read?;
+        Ok(())
+    }
+}
 
 /// STDIO transport for MCP
 pub struct StdioTransport {
@@ -15,45 +135,10 @@ impl StdioTransport {
     }
 
     /// Run the stdio transport (reads from stdin, writes to stdout)
+    ///
+    /// Same as [`McpServer::serve_stdio`].
     pub async fn run(&self) -> Result<()> {
-        let stdin = tokio::io::stdin();
-        let mut stdout = tokio::io::stdout();
-        let mut reader = BufReader::new(stdin);
-        let mut line = String::new();
-
-        loop {
-            line.clear();
-            let bytes_read = reader.read_line(&mut line).await?;
-
-            if bytes_read == 0 {
-                // EOF
-                break;
-            }
-
-            let trimmed = line.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
-
-            // Parse request
-            match serde_json::from_str::<McpRequest>(trimmed) {
-                Ok(request) => {
-                    // Handle request
-                    let response = self.server.handle_request(request).await;
-
-                    // Write response
-                    let response_json = serde_json::to_string(&response)?;
-                    stdout.write_all(response_json.as_bytes()).await?;
-                    stdout.write_all(b"\n").await?;
-                    stdout.flush().await?;
-                }
-                Err(e) => {
-                    eprintln!("Failed to parse request: {}", e);
-                }
-            }
-        }
-
-        Ok(())
+        self.server.serve_stdio().await
     }
 }
 
@@ -61,6 +146,7 @@ impl StdioTransport {
 #[cfg(feature = "sse")]
 pub mod sse {
     use super::*;
+    use crate::{McpRequest, McpResponse};
     use axum::{
         extract::State,
         response::sse::{Event, KeepAlive, Sse},
@@ -0,0 +1,187 @@
+//! The MCP client against this crate's own server: the handshake, typed
+//! calls, timeouts and session renewal over a pair of pipes and over HTTP
+
+use agentic_robotics_mcp::client::{ClientConfig, ClientError, McpClient};
+use agentic_robotics_mcp::http::{HttpConfig, HttpServer};
+use agentic_robotics_mcp::{server, McpResource, McpServer, McpTool, ResourceContents};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+async fn robot() -> McpServer {
+    let server = McpServer::new("test-robot", "1.0.0");
+    let move_tool = McpTool {
+        name: "move_robot".to_string(),
+        description: "Move the robot to a location".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": { "location": { "type": "string" } },
+            "required": ["location"]
+        }),
+    };
+    server
+        .register_tool(
+            move_tool,
+            server::async_tool(|args| async move {
+                let location = args["location"].as_str().unwrap_or_default().to_string();
+                if location == "far away" {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+                Ok(server::text_response(format!("Moved to {}", location)))
+            }),
+        )
+        .await
+        .unwrap();
+    let battery = McpResource {
+        uri: "robot://battery".to_string(),
+        name: "Battery".to_string(),
+        description: None,
+        mime_type: Some("application/json".to_string()),
+    };
+    server
+        .register_resource(
+            battery,
+            server::resource(|uri| async move {
+                Ok(ResourceContents {
+                    uri,
+                    mime_type: Some("application/json".to_string()),
+                    text: json!({ "percent": 87 }).to_string(),
+                })
+            }),
+        )
+        .await
+        .unwrap();
+    server
+}
+
+fn config() -> ClientConfig {
+    ClientConfig {
+        name: "test-agent".to_string(),
+        timeout: Duration::from_millis(500),
+        // Retried after 100 and 200 ms, within the timeout
+        retries: 2,
+        ..ClientConfig::default()
+    }
+}
+
+fn client_error(error: &anyhow::Error) -> &ClientError {
+    error.downcast_ref().expect("a ClientError")
+}
+
+#[tokio::test]
+async fn test_client_over_pipes() {
+    let (client_end, server_end) = tokio::io::duplex(4096);
+    let (server_reader, server_writer) = tokio::io::split(server_end);
+    let robot = robot().await;
+    tokio::spawn(async move { robot.serve(server_reader, server_writer).await });
+    let (reader, writer) = tokio::io::split(client_end);
+    let client = McpClient::connect(reader, writer, config()).await.unwrap();
+
+    assert_eq!(client.server_info().name, "test-robot");
+    assert!(client.capabilities()["tools"].is_object());
+    let tools = client.list_tools().await.unwrap();
+    assert_eq!(tools[0].name, "move_robot");
+
+    let result = client
+        .call_tool_result("move_robot", json!({ "location": "kitchen" }))
+        .await
+        .unwrap();
+    assert_eq!(result.text(), "Moved to kitchen");
+    let invalid = client
+        .call_tool("move_robot", json!({ "location": 7 }))
+        .await
+        .unwrap();
+    assert_eq!(invalid.error.unwrap().code, -32602);
+
+    let battery = client.read_resource("robot://battery").await.unwrap();
+    assert_eq!(battery[0].text, r#"{"percent":87}"#);
+    let missing = client.read_resource("robot://arm").await.unwrap_err();
+    assert!(matches!(
+        client_error(&missing),
+        ClientError::Server { code: -32002, .. }
+    ));
+
+    let slow = client
+        .call_tool_result("move_robot", json!({ "location": "far away" }))
+        .await
+        .unwrap_err();
+    assert!(matches!(client_error(&slow), ClientError::Timeout { .. }));
+    // The connection is still usable
+    assert_eq!(
+        client.list_resources().await.unwrap()[0].uri,
+        "robot://battery"
+    );
+}
+
+#[tokio::test]
+async fn test_unsupported_protocol_version() {
+    let (client_end, server_end) = tokio::io::duplex(4096);
+    tokio::spawn(async move {
+        let (reader, mut writer) = tokio::io::split(server_end);
+        let mut lines = BufReader::new(reader).lines();
+        let request: Value = serde_json::from_str(&lines.next_line().await?.unwrap())?;
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {
+                "protocolVersion": "2023-01-01",
+                "capabilities": {},
+                "serverInfo": { "name": "legacy", "version": "0.1" }
+            }
+        });
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await?;
+        anyhow::Ok(())
+    });
+
+    let (reader, writer) = tokio::io::split(client_end);
+    let error = McpClient::connect(reader, writer, config())
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        client_error(&error),
+        &ClientError::UnsupportedVersion {
+            server: "2023-01-01".to_string()
+        }
+    );
+    assert!(error.to_string().contains("2023-01-01"));
+}
+
+#[tokio::test]
+async fn test_client_over_http_renews_session() {
+    let address = SocketAddr::from(([127, 0, 0, 1], 0));
+    let http = HttpServer::start(robot().await, HttpConfig::new(address))
+        .await
+        .unwrap();
+    let address = http.local_addr();
+    let url = format!("http://{}/mcp", address);
+    let client = McpClient::connect_http_with(&url, config()).await.unwrap();
+    assert_eq!(http.session_count(), 1);
+
+    let result = client
+        .call_tool_result("move_robot", json!({ "location": "kitchen" }))
+        .await
+        .unwrap();
+    assert_eq!(result.text(), "Moved to kitchen");
+    let slow = client
+        .call_tool("move_robot", json!({ "location": "far away" }))
+        .await
+        .unwrap_err();
+    assert!(matches!(client_error(&slow), ClientError::Timeout { .. }));
+
+    // A restarted server does not know the session; the client starts a new one
+    http.shutdown().await;
+    let http = HttpServer::start(robot().await, HttpConfig::new(address))
+        .await
+        .unwrap();
+    assert_eq!(client.list_tools().await.unwrap()[0].name, "move_robot");
+    assert_eq!(http.session_count(), 1);
+
+    // Nothing is listening, and retries do not change that
+    http.shutdown().await;
+    let unreachable = client.list_tools().await.unwrap_err();
+    assert!(unreachable.to_string().contains("failed to connect"));
+}
@@ -0,0 +1,400 @@
+//! An MCP client talking to the server over the Streamable HTTP transport:
+//! POSTed JSON-RPC requests answered as SSE events, a session kept by
+//! header, the notification stream, bearer tokens and TLS
+
+use agentic_robotics_core::metrics::Registry;
+use agentic_robotics_core::TlsConfig;
+use agentic_robotics_mcp::http::{HttpConfig, HttpServer, REQUESTS_CANCELLED, SESSION_HEADER};
+use agentic_robotics_mcp::{server, McpServer, McpTool};
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const ACCEPT: &str = "application/json, text/event-stream";
+
+/// Sets its flag when dropped, to see that a handler future was cancelled
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// A robot with a quick tool and one that waits until cancelled, and the
+/// flag set when the waiting one is
+async fn robot() -> (McpServer, Arc<AtomicBool>) {
+    let server = McpServer::new("remote-robot", "1.0.0");
+    let move_tool = McpTool {
+        name: "move_robot".to_string(),
+        description: "Move the robot to a location".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": { "location": { "type": "string" } },
+            "required": ["location"]
+        }),
+    };
+    server
+        .register_tool(
+            move_tool,
+            server::tool(|args| {
+                let location = args["location"].as_str().unwrap_or_default();
+                Ok(server::text_response(format!("Moved to {}", location)))
+            }),
+        )
+        .await
+        .unwrap();
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+    let dock_tool = McpTool {
+        name: "wait_for_dock".to_string(),
+        description: "Wait until the robot is docked".to_string(),
+        input_schema: json!({ "type": "object" }),
+    };
+    server
+        .register_tool(
+            dock_tool,
+            server::async_tool(move |_| {
+                let flag = DropFlag(flag.clone());
+                async move {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    drop(flag);
+                    Ok(server::text_response("Docked"))
+                }
+            }),
+        )
+        .await
+        .unwrap();
+    (server, cancelled)
+}
+
+fn local() -> HttpConfig {
+    HttpConfig::new(SocketAddr::from(([127, 0, 0, 1], 0)))
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+/// The JSON-RPC messages in the `data` lines of an SSE body
+fn events(body: &str) -> Vec<Value> {
+    body.lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect()
+}
+
+/// POST `message` and return the one response streamed back
+async fn call(post: RequestBuilder, message: Value) -> Value {
+    let response = post
+        .header("Accept", ACCEPT)
+        .json(&message)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let events = events(&response.text().await.unwrap());
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["id"], message["id"]);
+    events[0].clone()
+}
+
+/// Initialize a session on `url`, sending `token` as bearer token if set,
+/// and return the session id
+async fn initialize(client: &Client, url: &str, token: Option<&str>) -> String {
+    let authorized = |request: RequestBuilder| match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+    let init = request(
+        1,
+        "initialize",
+        json!({
+            "protocolVersion": "2025-06-18",
+            "capabilities": {},
+            "clientInfo": { "name": "cloud-agent", "version": "0.1" }
+        }),
+    );
+    let response = authorized(client.post(url))
+        .header("Accept", ACCEPT)
+        .json(&init)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let session = response.headers()[SESSION_HEADER]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let init = &events(&response.text().await.unwrap())[0];
+    assert_eq!(init["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(init["result"]["serverInfo"]["name"], "remote-robot");
+
+    let initialized = authorized(client.post(url))
+        .header(SESSION_HEADER, &session)
+        .json(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(initialized.status(), StatusCode::ACCEPTED);
+    session
+}
+
+#[tokio::test]
+async fn test_session_over_http() {
+    let (robot, _) = robot().await;
+    let http = HttpServer::start(robot.clone(), local()).await.unwrap();
+    let url = format!("http://{}/mcp", http.local_addr());
+    let client = Client::new();
+    let session = initialize(&client, &url, None).await;
+    assert_eq!(http.session_count(), 1);
+
+    let moved = call(
+        client.post(&url).header(SESSION_HEADER, &session),
+        request(
+            2,
+            "tools/call",
+            json!({ "name": "move_robot", "arguments": { "location": "dock" } }),
+        ),
+    )
+    .await;
+    assert_eq!(moved["result"]["content"][0]["text"], "Moved to dock");
+    let sessions = robot.sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, session);
+    assert_eq!(sessions[0].client.transport.as_deref(), Some("http"));
+    assert_eq!(sessions[0].calls, 1);
+
+    // Clients that only accept JSON get the response as the body
+    let tools = client
+        .post(&url)
+        .header(SESSION_HEADER, &session)
+        .header("Accept", "application/json")
+        .json(&request(3, "tools/list", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(tools.headers()["content-type"], "application/json");
+    let tools: Value = tools.json().await.unwrap();
+    assert_eq!(tools["result"]["tools"].as_array().unwrap().len(), 2);
+
+    // Server notifications arrive on the session's event stream
+    let mut stream = client
+        .get(&url)
+        .header(SESSION_HEADER, &session)
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stream.status(), StatusCode::OK);
+    // The first keep-alive tells that the stream is subscribed
+    let first = stream.chunk().await.unwrap().unwrap();
+    assert!(first.starts_with(b":"));
+    robot.notify(
+        "notifications/message",
+        Some(json!({ "level": "warning", "data": "battery low" })),
+    );
+    let mut received = String::new();
+    let notification = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let chunk = stream.chunk().await.unwrap().expect("stream stays open");
+            received.push_str(&String::from_utf8_lossy(&chunk));
+            if let Some(event) = events(&received).pop() {
+                return event;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(notification["method"], "notifications/message");
+    assert_eq!(notification["params"]["data"], "battery low");
+
+    // Ending the session closes its stream and forgets it
+    let deleted = client
+        .delete(&url)
+        .header(SESSION_HEADER, &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(deleted.status(), StatusCode::OK);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while stream.chunk().await.unwrap().is_some() {}
+    })
+    .await
+    .unwrap();
+    assert_eq!(http.session_count(), 0);
+    assert!(robot.sessions().is_empty());
+    let ended = client
+        .post(&url)
+        .header(SESSION_HEADER, &session)
+        .header("Accept", ACCEPT)
+        .json(&request(4, "ping", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(ended.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_rejected_requests() {
+    let (robot, _) = robot().await;
+    let config = HttpConfig {
+        authorize: Some(Arc::new(|token: &str| token == "s3cret")),
+        ..local()
+    };
+    let http = HttpServer::start(robot, config).await.unwrap();
+    let url = format!("http://{}/mcp", http.local_addr());
+    let client = Client::new();
+    let ping = request(1, "ping", json!({}));
+
+    let anonymous = client.post(&url).json(&ping).send().await.unwrap();
+    assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(anonymous.headers()["www-authenticate"], "Bearer");
+    let wrong = client
+        .post(&url)
+        .bearer_auth("guess")
+        .json(&ping)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+
+    let no_session = client
+        .post(&url)
+        .bearer_auth("s3cret")
+        .json(&ping)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(no_session.status(), StatusCode::BAD_REQUEST);
+    let unknown = client
+        .post(&url)
+        .bearer_auth("s3cret")
+        .header(SESSION_HEADER, "0123456789abcdef")
+        .json(&ping)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+    let session = initialize(&client, &url, Some("s3cret")).await;
+    let authorized = || {
+        client
+            .post(&url)
+            .bearer_auth("s3cret")
+            .header(SESSION_HEADER, &session)
+    };
+    let unknown_method = call(authorized(), request(2, "robots/dance", json!({}))).await;
+    assert_eq!(unknown_method["error"]["code"], -32601);
+    let parse_error = authorized().body("{not json").send().await.unwrap();
+    assert_eq!(parse_error.status(), StatusCode::BAD_REQUEST);
+    let parse_error: Value = parse_error.json().await.unwrap();
+    assert_eq!(parse_error["error"]["code"], -32700);
+
+    let elsewhere = client
+        .get(format!("http://{}/other", http.local_addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(elsewhere.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_disconnect_cancels_tool_call() {
+    let (robot, cancelled) = robot().await;
+    let http = HttpServer::start(robot, local()).await.unwrap();
+    let url = format!("http://{}/mcp", http.local_addr());
+    let client = Client::new();
+    let session = initialize(&client, &url, None).await;
+    let counter = Registry::global().counter(
+        REQUESTS_CANCELLED,
+        "MCP requests cancelled because the client disconnected",
+        &[("method", "tools/call")],
+    );
+    let before = counter.get();
+
+    // The client gives up, dropping the connection mid-call
+    let abandoned = client
+        .post(&url)
+        .header(SESSION_HEADER, &session)
+        .header("Accept", ACCEPT)
+        .timeout(Duration::from_millis(200))
+        .json(&request(
+            2,
+            "tools/call",
+            json!({ "name": "wait_for_dock" }),
+        ))
+        .send()
+        .await;
+    assert!(abandoned.unwrap_err().is_timeout());
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !cancelled.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("handler future was dropped");
+    assert_eq!(counter.get(), before + 1);
+}
+
+#[tokio::test]
+async fn test_https() {
+    let dir = std::env::temp_dir().join(format!("mcp_http_tls_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_key = KeyPair::generate().unwrap();
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+    let key = KeyPair::generate().unwrap();
+    let cert = CertificateParams::new(vec!["robot.local".to_string()])
+        .unwrap()
+        .signed_by(&key, &ca, &ca_key)
+        .unwrap();
+    std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+    std::fs::write(dir.join("robot.pem"), cert.pem()).unwrap();
+    std::fs::write(dir.join("robot.key"), key.serialize_pem()).unwrap();
+
+    let (robot, _) = robot().await;
+    let config = HttpConfig {
+        tls: Some(TlsConfig {
+            cert: Some(dir.join("robot.pem")),
+            key: Some(dir.join("robot.key")),
+            ..TlsConfig::new(dir.join("ca.pem"))
+        }),
+        ..local()
+    };
+    let http = HttpServer::start(robot, config).await.unwrap();
+    let client = Client::builder()
+        .use_rustls_tls()
+        .add_root_certificate(reqwest::Certificate::from_pem(ca.pem().as_bytes()).unwrap())
+        .resolve("robot.local", http.local_addr())
+        .build()
+        .unwrap();
+    let url = format!("https://robot.local:{}/mcp", http.local_addr().port());
+
+    let session = initialize(&client, &url, None).await;
+    let moved = call(
+        client.post(&url).header(SESSION_HEADER, &session),
+        request(
+            2,
+            "tools/call",
+            json!({ "name": "move_robot", "arguments": { "location": "lab" } }),
+        ),
+    )
+    .await;
+    assert_eq!(moved["result"]["content"][0]["text"], "Moved to lab");
+    // Plain HTTP is not answered
+    let plain = Client::new()
+        .post(format!("http://{}/mcp", http.local_addr()))
+        .send()
+        .await;
+    assert!(plain.is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,108 @@
+//! The capability manifest read through `initialize` and its resource while
+//! topics appear and messages flow
+
+use agentic_robotics_core::{Node, ParameterDescriptor, RobotState};
+use agentic_robotics_mcp::manifest::RATE_WINDOW;
+use agentic_robotics_mcp::{server, McpRequest, McpServer};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+const TOPIC: &str = "/manifest/state";
+
+async fn request(server: &McpServer, method: &str, params: Value) -> Value {
+    let response = server
+        .handle_request(McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: method.to_string(),
+            params: Some(params),
+        })
+        .await;
+    response.result.expect("request succeeded")
+}
+
+async fn read_manifest(server: &McpServer) -> Value {
+    let result = request(server, "resources/read", json!({ "uri": "ros3://manifest" })).await;
+    serde_json::from_str(result["contents"][0]["text"].as_str().unwrap()).unwrap()
+}
+
+fn topic<'a>(manifest: &'a Value, name: &str) -> Option<&'a Value> {
+    manifest["topics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|topic| topic["name"] == name)
+}
+
+#[tokio::test]
+async fn test_manifest_follows_the_graph() {
+    let server = McpServer::new("test-robot", "1.0.0").with_frames(["map", "base_link"]);
+    server
+        .tool("move_robot", "Move the robot", |_: Value| async {
+            Ok(server::text_response("moved"))
+        })
+        .await
+        .unwrap();
+    server.register_capability_manifest().await.unwrap();
+
+    let initialized = request(&server, "initialize", json!({})).await;
+    let manifest = &initialized["capabilities"]["experimental"]["ros3/manifest"];
+    assert_eq!(manifest["tools"][0]["name"], "move_robot");
+    assert_eq!(manifest["frames"], json!(["map", "base_link"]));
+    assert!(topic(manifest, TOPIC).is_none());
+
+    let node = Node::new("manifest_test").unwrap();
+    node.parameters()
+        .declare("max_speed", 1.5, ParameterDescriptor::new("Speed limit"))
+        .unwrap();
+    let publisher = node.create_publisher::<RobotState>(TOPIC).unwrap();
+
+    // The new topic is listed at once, its rate once measured
+    let manifest = read_manifest(&server).await;
+    let state = topic(&manifest, TOPIC).expect("topic is listed");
+    assert_eq!(state["type_name"], "ros3_msgs/RobotState");
+    assert_eq!(state["publisher_count"], 1);
+    let max_speed = manifest["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|param| param["node"] == "manifest_test")
+        .expect("parameter is listed");
+    assert_eq!(max_speed["name"], "max_speed");
+    assert_eq!(max_speed["type"], "double");
+    assert_eq!(max_speed["value"], 1.5);
+
+    let publishing = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(10));
+        loop {
+            interval.tick().await;
+            publisher.publish(&RobotState::default()).await.unwrap();
+        }
+    });
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let rate = loop {
+        // Building the manifest does not wait for the measurement
+        let start = Instant::now();
+        let manifest = server.capability_manifest().await;
+        assert!(start.elapsed() < RATE_WINDOW);
+        if let Some(rate) = topic(&manifest, TOPIC).unwrap()["rate_hz"].as_f64() {
+            if rate > 0.0 {
+                break rate;
+            }
+        }
+        assert!(Instant::now() < deadline, "rate is measured");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    publishing.abort();
+    assert!((20.0..=200.0).contains(&rate), "{} Hz", rate);
+}
+
+#[tokio::test]
+async fn test_manifest_is_opt_in() {
+    let server = McpServer::new("test-robot", "1.0.0");
+    let initialized = request(&server, "initialize", json!({})).await;
+    assert!(initialized["capabilities"].get("experimental").is_none());
+    let resources = request(&server, "resources/list", json!({})).await;
+    assert_eq!(resources["resources"], json!([]));
+    assert!(server.capability_manifest().await["tools"].is_array());
+}
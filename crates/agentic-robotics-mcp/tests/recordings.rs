@@ -0,0 +1,154 @@
+//! Recordings started, stopped and read back through the recording tools,
+//! with messages published on real topics in between
+
+use agentic_robotics_core::{Node, RobotState};
+use agentic_robotics_mcp::{McpRequest, McpServer};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const STATE: &str = "/recordings/state";
+const POSE: &str = "/recordings/pose";
+
+fn bag_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ros3_mcp_recordings_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+async fn request(server: &McpServer, method: &str, params: Value) -> Value {
+    let response = server
+        .handle_request(McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: method.to_string(),
+            params: Some(params),
+        })
+        .await;
+    response.result.expect("request succeeded")
+}
+
+/// Call `tool` and return its text content parsed as JSON, and whether it
+/// is an error
+async fn call(server: &McpServer, tool: &str, arguments: Value) -> (Value, bool) {
+    let params = json!({ "name": tool, "arguments": arguments });
+    let result = request(server, "tools/call", params).await;
+    let text = result["content"][0]["text"].as_str().unwrap();
+    (
+        serde_json::from_str(text).unwrap(),
+        result["isError"] == json!(true),
+    )
+}
+
+fn state(timestamp: i64) -> RobotState {
+    RobotState {
+        position: [1.0, 2.0, 3.0],
+        velocity: [0.0; 3],
+        timestamp,
+    }
+}
+
+#[tokio::test]
+async fn test_record_stop_and_read_summary() {
+    let dir = bag_dir();
+    let server = McpServer::new("test-robot", "1.0.0");
+    server.register_ros3_recording_tools(&dir).await.unwrap();
+    let node = Node::new("recordings_test").unwrap();
+    let states = node.create_publisher::<RobotState>(STATE).unwrap();
+    let poses = node.create_publisher::<RobotState>(POSE).unwrap();
+
+    // Two recordings at once, the second stopping itself
+    let (first, is_error) = call(
+        &server,
+        "ros3_start_recording",
+        json!({ "topics": [STATE], "path": "runs/first.bag" }),
+    )
+    .await;
+    assert!(!is_error, "{}", first);
+    assert_eq!(first["path"], json!(dir.join("runs/first.bag")));
+    let (second, is_error) = call(
+        &server,
+        "ros3_start_recording",
+        json!({ "topics": [STATE, POSE], "duration_secs": 0.5 }),
+    )
+    .await;
+    assert!(!is_error, "{}", second);
+    let (first_id, second_id) = (
+        first["id"].as_str().unwrap(),
+        second["id"].as_str().unwrap(),
+    );
+    assert_ne!(first_id, second_id);
+
+    for timestamp in 0..3 {
+        states.publish(&state(timestamp)).await.unwrap();
+    }
+    poses.publish(&state(10)).await.unwrap();
+
+    let (list, _) = call(&server, "ros3_list_recordings", json!({})).await;
+    assert_eq!(list[0]["id"], first_id);
+    assert_eq!(list[0]["state"], "recording");
+    assert_eq!(list[1]["topics"], json!([POSE, STATE]));
+
+    let (stopped, is_error) = call(&server, "ros3_stop_recording", json!({ "id": first_id })).await;
+    assert!(!is_error, "{}", stopped);
+    assert_eq!(stopped["state"], "complete");
+    assert_eq!(stopped["messages"], 3);
+    let uri = stopped["uri"].as_str().unwrap();
+    assert_eq!(uri, format!("ros3://bag/{}", first_id));
+
+    let read = request(&server, "resources/read", json!({ "uri": uri })).await;
+    let summary: Value =
+        serde_json::from_str(read["contents"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(summary["finalized"], true);
+    assert_eq!(summary["messages"], 3);
+    assert_eq!(summary["topics"][STATE]["type"], "ros3_msgs/RobotState");
+    assert_eq!(summary["topics"][STATE]["messages"], 3);
+
+    // Stopping again returns the complete recording; unknown ids are errors
+    let (again, is_error) = call(&server, "ros3_stop_recording", json!({ "id": first_id })).await;
+    assert!(!is_error);
+    assert_eq!(again["uri"], uri);
+    let (unknown, is_error) = call(&server, "ros3_stop_recording", json!({ "id": "rec-99" })).await;
+    assert!(is_error);
+    assert_eq!(unknown["error"], "unknown_recording");
+    assert_eq!(unknown["retryable"], false);
+
+    // The timed recording completes on its own
+    let mut second = Value::Null;
+    for _ in 0..100 {
+        let (list, _) = call(&server, "ros3_list_recordings", json!({})).await;
+        second = list[1].clone();
+        if second["state"] == "complete" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(second["state"], "complete");
+    assert_eq!(second["messages"], 4);
+    let resources = request(&server, "resources/list", json!({})).await;
+    let uris: Vec<&str> = resources["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|resource| resource["uri"].as_str().unwrap())
+        .collect();
+    assert_eq!(uris, [uri, second["uri"].as_str().unwrap()]);
+
+    let (escaping, is_error) = call(
+        &server,
+        "ros3_start_recording",
+        json!({ "topics": [STATE], "path": "../outside.bag" }),
+    )
+    .await;
+    assert!(is_error);
+    assert_eq!(escaping["error"], "invalid_path");
+    let (taken, is_error) = call(
+        &server,
+        "ros3_start_recording",
+        json!({ "topics": [STATE], "path": "runs/first.bag" }),
+    )
+    .await;
+    assert!(is_error);
+    assert_eq!(taken["error"], "invalid_path");
+    let _ = std::fs::remove_dir_all(&dir);
+}
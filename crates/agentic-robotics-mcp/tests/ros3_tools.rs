@@ -0,0 +1,788 @@
+//! The standard ros3 tools called through the server, with messages flowing
+//! on real topics and calls reaching real services
+
+use agentic_robotics_core::message::Pose;
+use agentic_robotics_core::{
+    graph, Error, LifecycleNode, LoadOptions, Message, Node, NodeContainer, ParameterDescriptor,
+    RobotState, ServiceServer,
+};
+use agentic_robotics_mcp::{McpRequest, McpServer};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines};
+use tokio::time::timeout;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AddTwoInts {
+    a: i64,
+    b: i64,
+}
+
+impl Message for AddTwoInts {
+    fn type_name() -> &'static str {
+        "test_srvs/AddTwoIntsRequest"
+    }
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": { "a": { "type": "integer" }, "b": { "type": "integer" } },
+            "required": ["a", "b"],
+            "additionalProperties": false,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sum {
+    sum: i64,
+}
+
+impl Message for Sum {
+    fn type_name() -> &'static str {
+        "test_srvs/AddTwoIntsResponse"
+    }
+}
+
+async fn start(name: &str) -> (McpServer, Arc<Node>) {
+    let node = Arc::new(Node::new(name).unwrap());
+    let server = McpServer::new("test-robot", "1.0.0");
+    server.register_ros3_tools(node.clone()).await.unwrap();
+    (server, node)
+}
+
+/// Call `tool` and return its text content, and whether it is an error
+async fn call(server: &McpServer, tool: &str, arguments: Value) -> (String, bool) {
+    let response = server
+        .handle_request(McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: "tools/call".to_string(),
+            params: Some(json!({ "name": tool, "arguments": arguments })),
+        })
+        .await;
+    let result = response.result.expect("tool call succeeded");
+    let text = result["content"][0]["text"].as_str().unwrap().to_string();
+    (text, result["isError"] == json!(true))
+}
+
+#[tokio::test]
+async fn test_tool_descriptions_come_from_the_registry() {
+    let (server, _node) = start("ros3_tools_list").await;
+    let response = server
+        .handle_request(McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: "tools/list".to_string(),
+            params: None,
+        })
+        .await;
+    let tools = response.result.unwrap()["tools"].clone();
+    let names: Vec<&str> = tools
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    for name in [
+        "ros3_list_topics",
+        "ros3_publish",
+        "ros3_get_last_message",
+        "ros3_sample_topic",
+    ] {
+        assert!(names.contains(&name), "{} is registered", name);
+    }
+
+    let publish = tools
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|tool| tool["name"] == "ros3_publish")
+        .unwrap();
+    let description = publish["description"].as_str().unwrap();
+    assert!(description.contains("ros3_msgs/RobotState"));
+    assert!(description.contains(&RobotState::schema().to_string()));
+}
+
+#[tokio::test]
+async fn test_list_topics() {
+    let (server, node) = start("ros3_tools_topics").await;
+    let _publisher = node
+        .create_publisher::<RobotState>("/ros3_tools/topics/state")
+        .unwrap();
+
+    let (text, is_error) = call(&server, "ros3_list_topics", json!({})).await;
+    assert!(!is_error);
+    let graph: Value = serde_json::from_str(&text).unwrap();
+    let topic = graph["topics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|topic| topic["name"] == "/ros3_tools/topics/state")
+        .expect("topic is listed");
+    assert_eq!(topic["type_name"], "ros3_msgs/RobotState");
+    assert_eq!(topic["publisher_count"], 1);
+    assert_eq!(
+        graph["types"]["ros3_msgs/RobotState"]["required"][0],
+        "position"
+    );
+}
+
+#[tokio::test]
+async fn test_publish() {
+    let (server, node) = start("ros3_tools_publish").await;
+    let subscriber = node
+        .create_subscriber::<Pose>("/ros3_tools/publish/goal")
+        .unwrap();
+
+    let pose = json!({ "position": [1.0, 2.0, 0.0], "orientation": [0.0, 0.0, 0.0, 1.0] });
+    let (text, is_error) = call(
+        &server,
+        "ros3_publish",
+        json!({ "topic": "/ros3_tools/publish/goal", "type": "ros3_msgs/Pose", "message": pose }),
+    )
+    .await;
+    assert!(!is_error, "{}", text);
+    let received = timeout(Duration::from_secs(5), subscriber.recv())
+        .await
+        .expect("message was published")
+        .unwrap();
+    assert_eq!(received.position, [1.0, 2.0, 0.0]);
+    assert_eq!(received.orientation, [0.0, 0.0, 0.0, 1.0]);
+
+    // Messages not matching the schema never reach the topic
+    let (text, is_error) = call(
+        &server,
+        "ros3_publish",
+        json!({
+            "topic": "/ros3_tools/publish/goal",
+            "type": "ros3_msgs/Pose",
+            "message": { "position": [1.0, 2.0], "orientation": [0.0, 0.0, 0.0, 1.0] },
+        }),
+    )
+    .await;
+    assert!(is_error);
+    assert!(text.contains("/position"), "{}", text);
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_publish",
+        json!({ "topic": "/ros3_tools/publish/goal", "type": "ros3_msgs/Teleport", "message": {} }),
+    )
+    .await;
+    assert!(is_error);
+    assert!(
+        text.contains("ros3_msgs/Pose"),
+        "known types are listed: {}",
+        text
+    );
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_publish",
+        json!({
+            "topic": "/ros3_tools/publish/goal",
+            "type": "ros3_msgs/RobotState",
+            "message": { "position": [0.0, 0.0, 0.0], "velocity": [0.0, 0.0, 0.0], "timestamp": 0 },
+        }),
+    )
+    .await;
+    assert!(is_error);
+    assert!(text.contains("carries ros3_msgs/Pose"), "{}", text);
+
+    assert!(subscriber.try_recv().unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sample_topic() {
+    let (server, node) = start("ros3_tools_sample").await;
+    let publisher = node
+        .create_publisher::<RobotState>("/ros3_tools/sample/state")
+        .unwrap();
+    let publishing = tokio::spawn(async move {
+        for timestamp in 0.. {
+            let state = RobotState {
+                position: [timestamp as f64, 0.0, 0.0],
+                velocity: [1.0, 0.0, 0.0],
+                timestamp,
+            };
+            publisher.publish(&state).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_sample_topic",
+        json!({ "topic": "/ros3_tools/sample/state", "count": 3, "duration_secs": 5 }),
+    )
+    .await;
+    assert!(!is_error, "{}", text);
+    let sample: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(sample["topic"], "/ros3_tools/sample/state");
+    assert_eq!(sample["count"], 3);
+    let messages = sample["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert!(messages[0]["timestamp"].as_i64() < messages[2]["timestamp"].as_i64());
+    assert_eq!(messages[0]["velocity"], json!([1.0, 0.0, 0.0]));
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_get_last_message",
+        json!({ "topic": "/ros3_tools/sample/state" }),
+    )
+    .await;
+    assert!(!is_error, "{}", text);
+    let message: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(message["velocity"], json!([1.0, 0.0, 0.0]));
+    publishing.abort();
+
+    // A quiet topic ends the sample at the deadline
+    let (text, is_error) = call(
+        &server,
+        "ros3_sample_topic",
+        json!({ "topic": "/ros3_tools/sample/quiet", "duration_secs": 0.1 }),
+    )
+    .await;
+    assert!(!is_error);
+    assert_eq!(serde_json::from_str::<Value>(&text).unwrap()["count"], 0);
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_get_last_message",
+        json!({ "topic": "/ros3_tools/sample/quiet", "timeout_secs": 0.1 }),
+    )
+    .await;
+    assert!(is_error);
+    assert!(
+        text.starts_with("No message on /ros3_tools/sample/quiet"),
+        "{}",
+        text
+    );
+}
+
+#[tokio::test]
+async fn test_call_service() {
+    let (server, _node) = start("ros3_tools_service").await;
+    let _add = ServiceServer::new("ros3_tools/add_two_ints", |req: AddTwoInts| async move {
+        Ok(Sum { sum: req.a + req.b })
+    })
+    .unwrap();
+    let _slow = ServiceServer::new("ros3_tools/slow_add", |req: AddTwoInts| async move {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok(Sum { sum: req.a + req.b })
+    })
+    .unwrap();
+
+    let (text, is_error) = call(&server, "ros3_list_topics", json!({})).await;
+    assert!(!is_error);
+    let graph: Value = serde_json::from_str(&text).unwrap();
+    let service = graph["services"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|service| service["name"] == "ros3_tools/add_two_ints")
+        .expect("service is listed");
+    assert_eq!(service["request_type"], "test_srvs/AddTwoIntsRequest");
+    assert_eq!(service["response_type"], "test_srvs/AddTwoIntsResponse");
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_call_service",
+        json!({ "service": "ros3_tools/add_two_ints", "request": { "a": 2, "b": 3 } }),
+    )
+    .await;
+    assert!(!is_error, "{}", text);
+    assert_eq!(
+        serde_json::from_str::<Value>(&text).unwrap(),
+        json!({ "sum": 5 })
+    );
+
+    // Failures are JSON the model can act on
+    let error = |text: &str| serde_json::from_str::<Value>(text).unwrap();
+    let (text, is_error) = call(
+        &server,
+        "ros3_call_service",
+        json!({ "service": "ros3_tools/slow_add", "request": { "a": 2, "b": 3 }, "timeout_ms": 50 }),
+    )
+    .await;
+    assert!(is_error);
+    let timed_out = error(&text);
+    assert_eq!(timed_out["error"], "timeout");
+    assert_eq!(timed_out["service"], "ros3_tools/slow_add");
+    assert_eq!(timed_out["retryable"], true);
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_call_service",
+        json!({ "service": "ros3_tools/multiply", "request": { "a": 2, "b": 3 } }),
+    )
+    .await;
+    assert!(is_error);
+    let unknown = error(&text);
+    assert_eq!(unknown["error"], "service_unavailable");
+    assert_eq!(unknown["retryable"], true);
+    assert!(unknown["message"]
+        .as_str()
+        .unwrap()
+        .contains("ros3_tools/add_two_ints"));
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_call_service",
+        json!({ "service": "ros3_tools/add_two_ints", "request": { "a": "two", "b": 3 } }),
+    )
+    .await;
+    assert!(is_error);
+    let invalid = error(&text);
+    assert_eq!(invalid["error"], "invalid_request");
+    assert_eq!(invalid["retryable"], false);
+    assert!(invalid["message"].as_str().unwrap().contains("/a"));
+}
+
+#[tokio::test]
+async fn test_params() {
+    let (server, node) = start("ros3_tools_params").await;
+    let params = node.parameters();
+    let gain = ParameterDescriptor::new("Gain").with_range(0.0, 10.0);
+    params.declare("kp", 1.0, gain.clone()).unwrap();
+    params.declare("ki", 0.1, gain).unwrap();
+    params
+        .declare(
+            "frame",
+            "base_link",
+            ParameterDescriptor::new("Frame").read_only(),
+        )
+        .unwrap();
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_list_params",
+        json!({ "node": "ros3_tools_params" }),
+    )
+    .await;
+    assert!(!is_error);
+    let listed: Value = serde_json::from_str(&text).unwrap();
+    let names: Vec<&str> = listed
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|param| param["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["frame", "ki", "kp"]);
+    assert_eq!(listed[2]["range"], json!({ "min": 0.0, "max": 10.0 }));
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_get_param",
+        json!({ "node": "ros3_tools_params", "name": "kp" }),
+    )
+    .await;
+    assert!(!is_error);
+    let kp: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(kp["type"], "double");
+    assert_eq!(kp["value"], 1.0);
+
+    // Integers are accepted for doubles
+    let (text, is_error) = call(
+        &server,
+        "ros3_set_param",
+        json!({ "node": "ros3_tools_params", "name": "kp", "value": 2 }),
+    )
+    .await;
+    assert!(!is_error, "{}", text);
+    assert_eq!(params.get::<f64>("kp").unwrap(), 2.0);
+
+    // An out-of-range value is rejected with the reason, and kp keeps its value
+    let error = |text: &str| serde_json::from_str::<Value>(text).unwrap();
+    let (text, is_error) = call(
+        &server,
+        "ros3_set_param",
+        json!({ "node": "ros3_tools_params", "name": "kp", "value": 20.0 }),
+    )
+    .await;
+    assert!(is_error);
+    let rejected = error(&text);
+    assert_eq!(rejected["error"], "rejected");
+    assert_eq!(rejected["name"], "kp");
+    assert!(rejected["reason"].as_str().unwrap().contains("10"));
+    assert_eq!(params.get::<f64>("kp").unwrap(), 2.0);
+
+    // A batch with one bad value changes nothing
+    let (text, is_error) = call(
+        &server,
+        "ros3_set_param",
+        json!({
+            "node": "ros3_tools_params",
+            "parameters": [
+                { "name": "kp", "value": 3.0 },
+                { "name": "ki", "value": -1.0 },
+            ],
+        }),
+    )
+    .await;
+    assert!(is_error);
+    assert_eq!(error(&text)["name"], "ki");
+    assert_eq!(params.get::<f64>("kp").unwrap(), 2.0);
+    assert_eq!(params.get::<f64>("ki").unwrap(), 0.1);
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_set_param",
+        json!({
+            "node": "ros3_tools_params",
+            "parameters": [
+                { "name": "kp", "value": 3.0 },
+                { "name": "ki", "value": 0.5 },
+            ],
+        }),
+    )
+    .await;
+    assert!(!is_error, "{}", text);
+    assert_eq!(params.get::<f64>("kp").unwrap(), 3.0);
+    assert_eq!(params.get::<f64>("ki").unwrap(), 0.5);
+
+    let (text, _) = call(
+        &server,
+        "ros3_set_param",
+        json!({ "node": "ros3_tools_params", "name": "frame", "value": "map" }),
+    )
+    .await;
+    assert!(error(&text)["reason"]
+        .as_str()
+        .unwrap()
+        .contains("read-only"));
+
+    let (text, _) = call(
+        &server,
+        "ros3_set_param",
+        json!({ "node": "ros3_tools_params", "name": "kd", "value": 1.0 }),
+    )
+    .await;
+    assert_eq!(error(&text)["error"], "rejected");
+
+    let (text, _) = call(
+        &server,
+        "ros3_get_param",
+        json!({ "node": "ros3_tools_params", "name": "kd" }),
+    )
+    .await;
+    assert_eq!(error(&text)["error"], "unknown_parameter");
+
+    let (text, _) = call(
+        &server,
+        "ros3_get_param",
+        json!({ "node": "ros3_tools_nobody", "name": "kp" }),
+    )
+    .await;
+    assert_eq!(error(&text)["error"], "unknown_node");
+}
+
+#[tokio::test]
+async fn test_lifecycle() {
+    let (server, _node) = start("ros3_tools_lifecycle").await;
+    let ready = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let driver = ready.clone();
+    let camera = LifecycleNode::new(Node::new("ros3_tools_camera").unwrap())
+        .unwrap()
+        .on_activate(move || {
+            if driver.load(std::sync::atomic::Ordering::Relaxed) {
+                Ok(())
+            } else {
+                Err(Error::Configuration("no camera connected".into()))
+            }
+        });
+    let error = |text: &str| serde_json::from_str::<Value>(text).unwrap();
+    let change =
+        |transition: &str| json!({ "node": "ros3_tools_camera", "transition": transition });
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_get_lifecycle_state",
+        json!({ "node": "ros3_tools_camera" }),
+    )
+    .await;
+    assert!(!is_error);
+    assert_eq!(
+        error(&text),
+        json!([{ "node": "ros3_tools_camera", "state": "unconfigured" }])
+    );
+
+    let (text, is_error) = call(&server, "ros3_change_lifecycle_state", change("activate")).await;
+    assert!(is_error);
+    assert_eq!(error(&text)["error"], "invalid_transition");
+    assert_eq!(error(&text)["state"], "unconfigured");
+
+    let (text, is_error) = call(&server, "ros3_change_lifecycle_state", change("configure")).await;
+    assert!(!is_error, "{}", text);
+    assert_eq!(error(&text)["state"], "inactive");
+
+    // A failing hook leaves the node inactive, and may be retried
+    let (text, _) = call(&server, "ros3_change_lifecycle_state", change("activate")).await;
+    let failed = error(&text);
+    assert_eq!(failed["error"], "transition_failed");
+    assert_eq!(failed["retryable"], true);
+    assert!(failed["message"]
+        .as_str()
+        .unwrap()
+        .contains("no camera connected"));
+    assert_eq!(camera.state().to_string(), "inactive");
+
+    ready.store(true, std::sync::atomic::Ordering::Relaxed);
+    let (text, is_error) = call(&server, "ros3_change_lifecycle_state", change("activate")).await;
+    assert!(!is_error, "{}", text);
+    assert_eq!(camera.state().to_string(), "active");
+
+    let (text, _) = call(
+        &server,
+        "ros3_get_lifecycle_state",
+        json!({ "node": "ros3_tools_nobody" }),
+    )
+    .await;
+    assert_eq!(error(&text)["error"], "unknown_node");
+}
+
+#[tokio::test]
+async fn test_list_loaded_nodes() {
+    let (server, _node) = start("ros3_tools_loaded").await;
+    let container = NodeContainer::new("ros3_tools_container")
+        .with_namespace("/ros3_tools")
+        .unwrap();
+    container.register("camera_driver", |node| async move {
+        node.closed().await;
+        Ok(())
+    });
+    let id = container
+        .load("camera_driver", LoadOptions::new().name("front_camera"))
+        .unwrap();
+
+    let (text, is_error) = call(
+        &server,
+        "ros3_list_loaded_nodes",
+        json!({ "container": "ros3_tools_container" }),
+    )
+    .await;
+    assert!(!is_error, "{}", text);
+    assert_eq!(
+        serde_json::from_str::<Value>(&text).unwrap(),
+        json!([{
+            "id": id,
+            "container": "ros3_tools_container",
+            "factory": "camera_driver",
+            "name": "front_camera",
+            "namespace": "/ros3_tools",
+            "status": { "state": "running", "restarts": 0 },
+        }])
+    );
+
+    container.unload(id);
+    let (text, _) = call(
+        &server,
+        "ros3_list_loaded_nodes",
+        json!({ "container": "ros3_tools_container" }),
+    )
+    .await;
+    assert_eq!(serde_json::from_str::<Value>(&text).unwrap(), json!([]));
+}
+
+/// Send a JSON-RPC request over a stdio-like pipe
+async fn send(writer: &mut DuplexStream, id: u64, method: &str, params: Value) {
+    let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+    writer
+        .write_all(format!("{}\n", request).as_bytes())
+        .await
+        .unwrap();
+}
+
+/// The next response or notification from the server
+async fn receive(lines: &mut Lines<BufReader<DuplexStream>>) -> Value {
+    let line = timeout(Duration::from_secs(5), lines.next_line())
+        .await
+        .expect("server wrote in time")
+        .unwrap()
+        .expect("server kept its output open");
+    serde_json::from_str(&line).unwrap()
+}
+
+#[tokio::test]
+async fn test_topic_resources() {
+    const TOPIC: &str = "/ros3_tools/resources/state";
+    const URI: &str = "ros3://topic/ros3_tools/resources/state";
+    let server = McpServer::new("test-robot", "1.0.0").with_update_interval(Duration::ZERO);
+    server.register_ros3_resources().await.unwrap();
+    let node = Node::new("ros3_tools_resources").unwrap();
+    let publisher = node.create_publisher::<RobotState>(TOPIC).unwrap();
+
+    // Subscriptions belong to a connected client
+    let (mut stdin, server_stdin) = tokio::io::duplex(64 * 1024);
+    let (server_stdout, stdout) = tokio::io::duplex(64 * 1024);
+    let serving = tokio::spawn({
+        let server = server.clone();
+        async move { server.serve(server_stdin, server_stdout).await }
+    });
+    let mut lines = BufReader::new(stdout).lines();
+    send(&mut stdin, 1, "resources/list", json!({})).await;
+    let listed = receive(&mut lines).await;
+    let resource = listed["result"]["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|resource| resource["uri"] == URI)
+        .expect("topic is listed as a resource")
+        .clone();
+    assert_eq!(resource["name"], TOPIC);
+    assert_eq!(resource["mimeType"], "application/json");
+
+    send(&mut stdin, 2, "resources/subscribe", json!({ "uri": URI })).await;
+    assert_eq!(receive(&mut lines).await["result"], json!({}));
+    let subscribers = || {
+        graph::list_topics()
+            .into_iter()
+            .find(|topic| topic.name == TOPIC)
+            .unwrap()
+            .subscriber_count
+    };
+    assert_eq!(subscribers(), 1);
+
+    let state = RobotState {
+        position: [1.0, 2.0, 3.0],
+        velocity: [0.0, 0.0, 0.5],
+        timestamp: 7,
+    };
+    publisher.publish(&state).await.unwrap();
+    let updated = receive(&mut lines).await;
+    assert_eq!(updated["method"], "notifications/resources/updated");
+    assert_eq!(updated["params"]["uri"], URI);
+    let contents = &updated["params"]["contents"][0];
+    assert_eq!(contents["mimeType"], "application/json");
+    let message: Value = serde_json::from_str(contents["text"].as_str().unwrap()).unwrap();
+    assert_eq!(message["position"], json!([1.0, 2.0, 3.0]));
+    assert_eq!(message["timestamp"], 7);
+
+    // Reading returns the latest message seen
+    send(&mut stdin, 3, "resources/read", json!({ "uri": URI })).await;
+    let read = receive(&mut lines).await;
+    assert_eq!(read["result"]["contents"][0]["text"], contents["text"]);
+
+    send(
+        &mut stdin,
+        4,
+        "resources/unsubscribe",
+        json!({ "uri": URI }),
+    )
+    .await;
+    assert_eq!(receive(&mut lines).await["result"], json!({}));
+    for _ in 0..50 {
+        if subscribers() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(subscribers(), 0, "unsubscribing dropped the subscriber");
+
+    // Closing the client ends its subscriptions too
+    send(&mut stdin, 5, "resources/subscribe", json!({ "uri": URI })).await;
+    assert_eq!(receive(&mut lines).await["result"], json!({}));
+    assert_eq!(subscribers(), 1);
+    drop(stdin);
+    serving.await.unwrap().unwrap();
+    for _ in 0..50 {
+        if subscribers() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(subscribers(), 0, "disconnecting dropped the subscriber");
+}
+
+#[tokio::test]
+async fn test_sample_progress_and_cancel() {
+    const TOPIC: &str = "/ros3_tools/progress/state";
+    let (server, node) = start("ros3_tools_progress").await;
+    let publisher = node.create_publisher::<RobotState>(TOPIC).unwrap();
+    let publishing = tokio::spawn(async move {
+        for timestamp in 0.. {
+            let state = RobotState {
+                position: [0.0; 3],
+                velocity: [1.0, 0.0, 0.0],
+                timestamp,
+            };
+            publisher.publish(&state).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    });
+    let (mut stdin, server_stdin) = tokio::io::duplex(64 * 1024);
+    let (server_stdout, stdout) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move { server.serve(server_stdin, server_stdout).await });
+    let mut lines = BufReader::new(stdout).lines();
+    let sample = |count| {
+        json!({
+            "name": "ros3_sample_topic",
+            "arguments": { "topic": TOPIC, "count": count, "duration_secs": 30 },
+            "_meta": { "progressToken": "sample" },
+        })
+    };
+
+    // Every message is sent as it arrives and is part of the result
+    send(&mut stdin, 1, "tools/call", sample(2)).await;
+    for progress in 1..=2 {
+        let notification = receive(&mut lines).await;
+        assert_eq!(notification["method"], "notifications/progress");
+        assert_eq!(notification["params"]["progressToken"], "sample");
+        assert_eq!(notification["params"]["progress"], progress);
+        let chunk = notification["params"]["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        let message: Value = serde_json::from_str(chunk).unwrap();
+        assert_eq!(message["velocity"], json!([1.0, 0.0, 0.0]));
+    }
+    let response = receive(&mut lines).await;
+    assert_eq!(response["id"], 1);
+    let content = response["result"]["content"].as_array().unwrap();
+    assert_eq!(content.len(), 3);
+    let summary: Value = serde_json::from_str(content[2]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(summary, json!({ "topic": TOPIC, "count": 2 }));
+
+    // Cancelling ends the sample and its subscription, without a response
+    send(&mut stdin, 2, "tools/call", sample(1000)).await;
+    assert_eq!(
+        receive(&mut lines).await["method"],
+        "notifications/progress"
+    );
+    let subscribers = || {
+        graph::list_topics()
+            .into_iter()
+            .find(|topic| topic.name == TOPIC)
+            .unwrap()
+            .subscriber_count
+    };
+    assert_eq!(subscribers(), 1);
+    let cancel = json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/cancelled",
+        "params": { "requestId": 2, "reason": "seen enough" },
+    });
+    stdin
+        .write_all(format!("{}\n", cancel).as_bytes())
+        .await
+        .unwrap();
+    for _ in 0..50 {
+        if subscribers() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(subscribers(), 0, "cancelling dropped the subscriber");
+
+    send(&mut stdin, 3, "ping", json!({})).await;
+    loop {
+        let message = receive(&mut lines).await;
+        assert_ne!(message["id"], 2, "a cancelled request gets no response");
+        if message["id"] == 3 {
+            break;
+        }
+    }
+    publishing.abort();
+}
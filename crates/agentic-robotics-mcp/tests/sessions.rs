@@ -0,0 +1,183 @@
+//! Two agents connected to one server at once, each over its own pipe: their
+//! subscriptions, notification streams and rate limits stay apart, and
+//! ending a session releases the ros3 subscribers it created
+
+use agentic_robotics_core::{graph, Node, RobotState};
+use agentic_robotics_mcp::sessions::RateLimit;
+use agentic_robotics_mcp::{server, McpServer, McpTool};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+const TOPIC: &str = "/sessions/state";
+const URI: &str = "ros3://topic/sessions/state";
+
+struct Session {
+    stdin: DuplexStream,
+    stdout: Lines<BufReader<DuplexStream>>,
+    serving: JoinHandle<anyhow::Result<()>>,
+}
+
+impl Session {
+    async fn open(server: &McpServer, name: &str) -> Self {
+        let (stdin, server_stdin) = tokio::io::duplex(64 * 1024);
+        let (server_stdout, stdout) = tokio::io::duplex(64 * 1024);
+        let server = server.clone();
+        let serving = tokio::spawn(async move { server.serve(server_stdin, server_stdout).await });
+        let mut session = Self {
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            serving,
+        };
+        let params = json!({
+            "protocolVersion": "2025-06-18",
+            "capabilities": {},
+            "clientInfo": { "name": name, "version": "1.0" }
+        });
+        session.call(0, "initialize", params).await;
+        session
+    }
+
+    async fn send(&mut self, id: u64, method: &str, params: Value) {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        self.stdin
+            .write_all(format!("{}\n", request).as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// The next response or notification from the server
+    async fn receive(&mut self) -> Value {
+        let line = timeout(Duration::from_secs(5), self.stdout.next_line())
+            .await
+            .expect("server wrote in time")
+            .unwrap()
+            .expect("server kept its output open");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    /// Send a request and return its response, which must come next
+    async fn call(&mut self, id: u64, method: &str, params: Value) -> Value {
+        self.send(id, method, params).await;
+        let response = self.receive().await;
+        assert_eq!(
+            response["id"], id,
+            "no notification arrived before the response"
+        );
+        response
+    }
+
+    async fn close(self) {
+        drop(self.stdin);
+        self.serving.await.unwrap().unwrap();
+    }
+}
+
+fn subscribers() -> usize {
+    graph::list_topics()
+        .into_iter()
+        .find(|topic| topic.name == TOPIC)
+        .map_or(0, |topic| topic.subscriber_count)
+}
+
+async fn wait_for_subscribers(count: usize) {
+    for _ in 0..50 {
+        if subscribers() == count {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(subscribers(), count);
+}
+
+#[tokio::test]
+async fn test_concurrent_sessions_are_isolated() {
+    let server = McpServer::new("test-robot", "1.0.0")
+        .with_update_interval(Duration::ZERO)
+        .with_rate_limit(RateLimit::new(1, Duration::from_secs(60)));
+    server.register_ros3_resources().await.unwrap();
+    let beep = McpTool {
+        name: "beep".to_string(),
+        description: "Beep once".to_string(),
+        input_schema: json!({ "type": "object" }),
+    };
+    server
+        .register_tool(beep, server::tool(|_| Ok(server::text_response("beep"))))
+        .await
+        .unwrap();
+    let node = Node::new("sessions_test").unwrap();
+    let publisher = node.create_publisher::<RobotState>(TOPIC).unwrap();
+    let state = RobotState {
+        position: [1.0, 2.0, 3.0],
+        velocity: [0.0; 3],
+        timestamp: 1,
+    };
+
+    let mut planner = Session::open(&server, "planner").await;
+    let mut monitor = Session::open(&server, "monitor").await;
+    let sessions = server.sessions();
+    let names: Vec<_> = sessions.iter().map(|s| s.client.name.as_deref()).collect();
+    assert_eq!(names, [Some("planner"), Some("monitor")]);
+    assert_ne!(sessions[0].id, sessions[1].id);
+    assert_eq!(sessions[0].client.session.as_ref(), Some(&sessions[0].id));
+
+    // Both subscribe to the same resource, each with a subscriber of its own
+    planner
+        .call(1, "resources/subscribe", json!({ "uri": URI }))
+        .await;
+    monitor
+        .call(1, "resources/subscribe", json!({ "uri": URI }))
+        .await;
+    assert_eq!(subscribers(), 2);
+    publisher.publish(&state).await.unwrap();
+    for session in [&mut planner, &mut monitor] {
+        let updated = session.receive().await;
+        assert_eq!(updated["method"], "notifications/resources/updated");
+        assert_eq!(updated["params"]["uri"], URI);
+    }
+
+    // Unsubscribing one leaves the other's stream alone
+    planner
+        .call(2, "resources/unsubscribe", json!({ "uri": URI }))
+        .await;
+    wait_for_subscribers(1).await;
+    publisher.publish(&state).await.unwrap();
+    let updated = monitor.receive().await;
+    assert_eq!(updated["method"], "notifications/resources/updated");
+    planner.call(3, "ping", json!({})).await;
+
+    // Each session has its own share of the rate limit
+    let beep = json!({ "name": "beep", "arguments": {} });
+    let allowed = planner.call(4, "tools/call", beep.clone()).await;
+    assert_eq!(allowed["result"]["content"][0]["text"], "beep");
+    let limited = planner.call(5, "tools/call", beep.clone()).await;
+    assert_eq!(limited["result"]["isError"], true);
+    let error: Value =
+        serde_json::from_str(limited["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(error["error"], "rate_limited");
+    let allowed = monitor.call(2, "tools/call", beep).await;
+    assert_eq!(allowed["result"]["content"][0]["text"], "beep");
+
+    let sessions = server.sessions();
+    assert_eq!(
+        (sessions[0].calls, sessions[0].rate_limited),
+        (2, 1),
+        "planner"
+    );
+    assert_eq!(
+        (sessions[1].calls, sessions[1].rate_limited),
+        (1, 0),
+        "monitor"
+    );
+    assert!(sessions[0].subscriptions.is_empty());
+    assert_eq!(sessions[1].subscriptions, [URI]);
+
+    // Ending a session releases what it held
+    planner.close().await;
+    assert_eq!(server.sessions().len(), 1);
+    monitor.close().await;
+    wait_for_subscribers(0).await;
+    assert!(server.sessions().is_empty());
+}
@@ -0,0 +1,242 @@
+//! An MCP client talking to the server over a pair of pipes, one JSON-RPC
+//! frame per line, as an assistant does over a child process's stdio
+
+use agentic_robotics_mcp::{
+    server, McpResource, McpServer, McpTool, ResourceContents, MCP_VERSION,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+struct Client {
+    stdin: DuplexStream,
+    stdout: Lines<BufReader<DuplexStream>>,
+    server: JoinHandle<anyhow::Result<()>>,
+}
+
+impl Client {
+    async fn send(&mut self, frame: &str) {
+        self.stdin.write_all(frame.as_bytes()).await.unwrap();
+        self.stdin.write_all(b"\n").await.unwrap();
+    }
+
+    async fn receive(&mut self) -> Value {
+        let line = timeout(Duration::from_secs(5), self.stdout.next_line())
+            .await
+            .expect("server answered in time")
+            .unwrap()
+            .expect("server kept stdout open");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    /// Send `frame` and return the response to it
+    async fn call(&mut self, frame: Value) -> Value {
+        self.send(&frame.to_string()).await;
+        let response = self.receive().await;
+        assert_eq!(response["jsonrpc"], "2.0");
+        assert_eq!(response["id"], frame["id"]);
+        response
+    }
+}
+
+async fn start() -> Client {
+    let server = McpServer::new("test-robot", "1.0.0");
+    let move_tool = McpTool {
+        name: "move_robot".to_string(),
+        description: "Move the robot to a location".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": { "location": { "type": "string" } },
+            "required": ["location"]
+        }),
+    };
+    server
+        .register_tool(
+            move_tool,
+            server::async_tool(|args| async move {
+                let location = args["location"].as_str().unwrap_or_default().to_string();
+                if location == "slow" {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+                Ok(server::text_response(format!("Moved to {}", location)))
+            }),
+        )
+        .await
+        .unwrap();
+    let battery = McpResource {
+        uri: "robot://battery".to_string(),
+        name: "Battery".to_string(),
+        description: None,
+        mime_type: Some("application/json".to_string()),
+    };
+    server
+        .register_resource(
+            battery,
+            server::resource(|uri| async move {
+                Ok(ResourceContents {
+                    uri,
+                    mime_type: Some("application/json".to_string()),
+                    text: json!({ "percent": 87 }).to_string(),
+                })
+            }),
+        )
+        .await
+        .unwrap();
+
+    let (stdin, server_stdin) = tokio::io::duplex(4096);
+    let (server_stdout, stdout) = tokio::io::duplex(4096);
+    let server = tokio::spawn(async move { server.serve(server_stdin, server_stdout).await });
+    Client {
+        stdin,
+        stdout: BufReader::new(stdout).lines(),
+        server,
+    }
+}
+
+#[tokio::test]
+async fn test_session_over_stdio() {
+    let mut client = start().await;
+
+    let init = client
+        .call(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-06-18",
+                "capabilities": {},
+                "clientInfo": { "name": "test-client", "version": "0.1" }
+            }
+        }))
+        .await;
+    assert_eq!(init["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(init["result"]["serverInfo"]["name"], "test-robot");
+    assert!(init["result"]["capabilities"]["tools"].is_object());
+    // Notifications get no response; the next line answers the next request
+    client
+        .send(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#)
+        .await;
+
+    let tools = client
+        .call(json!({ "jsonrpc": "2.0", "id": "list", "method": "tools/list" }))
+        .await;
+    assert_eq!(tools["result"]["tools"][0]["name"], "move_robot");
+    assert_eq!(tools["result"]["tools"][0]["inputSchema"]["required"][0], "location");
+
+    let moved = client
+        .call(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "move_robot", "arguments": { "location": "kitchen" } }
+        }))
+        .await;
+    assert_eq!(moved["result"]["content"][0]["text"], "Moved to kitchen");
+
+    let resources = client
+        .call(json!({ "jsonrpc": "2.0", "id": 3, "method": "resources/list" }))
+        .await;
+    assert_eq!(resources["result"]["resources"][0]["uri"], "robot://battery");
+    assert_eq!(resources["result"]["resources"][0]["mimeType"], "application/json");
+    let battery = client
+        .call(json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "resources/read",
+            "params": { "uri": "robot://battery" }
+        }))
+        .await;
+    assert_eq!(battery["result"]["contents"][0]["text"], r#"{"percent":87}"#);
+
+    // Closing stdin ends the session cleanly
+    drop(client.stdin);
+    client.server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_errors_over_stdio() {
+    let mut client = start().await;
+
+    let unknown = client
+        .call(json!({ "jsonrpc": "2.0", "id": 1, "method": "robots/dance" }))
+        .await;
+    assert_eq!(unknown["error"]["code"], -32601);
+
+    let malformed = client
+        .call(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "move_robot", "arguments": { "location": 7 } }
+        }))
+        .await;
+    assert_eq!(malformed["error"]["code"], -32602);
+    assert_eq!(
+        malformed["error"]["data"]["errors"][0],
+        "/location: expected string, found integer"
+    );
+
+    let missing = client
+        .call(json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "resources/read",
+            "params": { "uri": "robot://arm" }
+        }))
+        .await;
+    assert_eq!(missing["error"]["code"], -32002);
+
+    client.send("{not json").await;
+    let parse_error = client.receive().await;
+    assert_eq!(parse_error["error"]["code"], -32700);
+    assert_eq!(parse_error["id"], Value::Null);
+
+    client.send(r#"{"jsonrpc":"1.0","id":5,"method":"ping"}"#).await;
+    let invalid = client.receive().await;
+    assert_eq!(invalid["error"]["code"], -32600);
+    assert_eq!(invalid["id"], 5);
+
+    let fallback = client
+        .call(json!({
+            "jsonrpc": "2.0",
+            "id": 6,
+            "method": "initialize",
+            "params": { "protocolVersion": "1999-01-01" }
+        }))
+        .await;
+    assert_eq!(fallback["result"]["protocolVersion"], MCP_VERSION);
+}
+
+#[tokio::test]
+async fn test_responses_are_correlated_by_id() {
+    let mut client = start().await;
+
+    let call = |id: u32, location: &str| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "move_robot", "arguments": { "location": location } }
+        })
+        .to_string()
+    };
+    client.send(&call(1, "slow")).await;
+    client.send(&call(2, "fast")).await;
+
+    // The slow call does not hold up the fast one
+    let first = client.receive().await;
+    let second = client.receive().await;
+    assert_eq!(first["id"], 2);
+    let texts: HashMap<u64, String> = [first, second]
+        .iter()
+        .map(|response| {
+            let text = response["result"]["content"][0]["text"].as_str().unwrap();
+            (response["id"].as_u64().unwrap(), text.to_string())
+        })
+        .collect();
+    assert_eq!(texts[&1], "Moved to slow");
+    assert_eq!(texts[&2], "Moved to fast");
+}
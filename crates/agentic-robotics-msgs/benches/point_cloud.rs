@@ -0,0 +1,60 @@
+//! Iterating the points of a `PointCloud2` against a hand-rolled loop
+//!
+//! `iter_xyz` looks the fields up by name and bounds-checks every point; the
+//! baseline reads the floats at fixed offsets with unchecked unaligned
+//! loads. Iteration should stay within 2x of the baseline.
+
+use agentic_robotics_msgs::{Header, PointCloud2, PointCloudReader};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+fn cloud(points: usize) -> PointCloud2 {
+    let xyz: Vec<[f32; 3]> = (0..points)
+        .map(|i| [i as f32, 0.5 * i as f32, -(i as f32)])
+        .collect();
+    PointCloud2::from_xyz(Header::default(), &xyz)
+}
+
+fn sum_unsafe(cloud: &PointCloud2) -> f32 {
+    let step = cloud.point_step as usize;
+    let base = cloud.data.as_ptr();
+    let mut sum = 0.0;
+    for i in 0..cloud.len() {
+        // SAFETY: `from_xyz` packs `len` points of three floats each
+        unsafe {
+            let point = base.add(i * step);
+            let x = (point as *const f32).read_unaligned();
+            let y = (point.add(4) as *const f32).read_unaligned();
+            let z = (point.add(8) as *const f32).read_unaligned();
+            sum += x + y + z;
+        }
+    }
+    sum
+}
+
+fn sum_iter_xyz(reader: &PointCloudReader<'_>) -> f32 {
+    reader.iter_xyz().unwrap().map(|[x, y, z]| x + y + z).sum()
+}
+
+fn benchmark_iteration(c: &mut Criterion) {
+    let mut group = c.benchmark_group("point_cloud_xyz");
+    for points in [1_000, 100_000] {
+        let cloud = cloud(points);
+        group.throughput(Throughput::Elements(points as u64));
+        group.bench_with_input(
+            BenchmarkId::new("unsafe_loop", points),
+            &cloud,
+            |b, cloud| b.iter(|| black_box(sum_unsafe(black_box(cloud)))),
+        );
+        // The field table is checked once per cloud, not per iteration
+        let reader = PointCloudReader::new(&cloud).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("iter_xyz", points),
+            &reader,
+            |b, reader| b.iter(|| black_box(sum_iter_xyz(black_box(reader)))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_iteration);
+criterion_main!(benches);
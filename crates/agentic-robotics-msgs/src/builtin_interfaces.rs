@@ -0,0 +1,71 @@
+//! `builtin_interfaces`: types the other messages are built from
+
+use agentic_robotics_core::Ros3Message;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+/// A point in time as seconds and nanoseconds since the clock's epoch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Ros3Message)]
+#[ros3(type_name = "builtin_interfaces/msg/Time")]
+pub struct Time {
+    pub sec: i32,
+    /// Always below one second
+    pub nanosec: u32,
+}
+
+impl Time {
+    pub fn as_nanos(&self) -> i64 {
+        i64::from(self.sec) * NANOS_PER_SEC + i64::from(self.nanosec)
+    }
+}
+
+/// Saturates at the range of `sec`, about 68 years around the epoch
+impl From<agentic_robotics_core::Time> for Time {
+    fn from(time: agentic_robotics_core::Time) -> Self {
+        let nanos = time.as_nanos();
+        let sec = nanos.div_euclid(NANOS_PER_SEC);
+        match i32::try_from(sec) {
+            Ok(sec) => Time {
+                sec,
+                nanosec: nanos.rem_euclid(NANOS_PER_SEC) as u32,
+            },
+            Err(_) if sec < 0 => Time {
+                sec: i32::MIN,
+                nanosec: 0,
+            },
+            Err(_) => Time {
+                sec: i32::MAX,
+                nanosec: (NANOS_PER_SEC - 1) as u32,
+            },
+        }
+    }
+}
+
+impl From<Time> for agentic_robotics_core::Time {
+    fn from(time: Time) -> Self {
+        agentic_robotics_core::Time::from_nanos(time.as_nanos())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_conversions() {
+        let time = Time::from(agentic_robotics_core::Time::from_nanos(-1_500_000_000));
+        assert_eq!(
+            time,
+            Time {
+                sec: -2,
+                nanosec: 500_000_000
+            }
+        );
+        assert_eq!(
+            agentic_robotics_core::Time::from(time).as_nanos(),
+            -1_500_000_000
+        );
+        let far = Time::from(agentic_robotics_core::Time::from_nanos(i64::MAX));
+        assert_eq!(far.sec, i32::MAX);
+    }
+}
@@ -0,0 +1,135 @@
+//! `geometry_msgs`: points, orientations and velocities
+
+use crate::std_msgs::Header;
+use agentic_robotics_core::tf::Transform;
+use agentic_robotics_core::Ros3Message;
+
+/// A direction and magnitude, e.g. a velocity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "geometry_msgs/msg/Vector3")]
+pub struct Vector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// A position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "geometry_msgs/msg/Point")]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// An orientation as a unit quaternion
+#[derive(Debug, Clone, Copy, PartialEq, Ros3Message)]
+#[ros3(type_name = "geometry_msgs/msg/Quaternion")]
+pub struct Quaternion {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    /// A rotation by `yaw` radians about the z axis
+    pub fn from_yaw(yaw: f64) -> Self {
+        let (sin, cos) = (yaw / 2.0).sin_cos();
+        Quaternion {
+            z: sin,
+            w: cos,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// The rotation about the z axis, in radians
+    pub fn yaw(&self) -> f64 {
+        let siny_cosp = 2.0 * (self.w * self.z + self.x * self.y);
+        let cosy_cosp = 1.0 - 2.0 * (self.y * self.y + self.z * self.z);
+        siny_cosp.atan2(cosy_cosp)
+    }
+}
+
+/// The identity, as in ROS 2
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// A position and orientation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "geometry_msgs/msg/Pose")]
+pub struct Pose {
+    pub position: Point,
+    pub orientation: Quaternion,
+}
+
+/// A pose in the frame and at the time of its header
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "geometry_msgs/msg/PoseStamped")]
+pub struct PoseStamped {
+    pub header: Header,
+    pub pose: Pose,
+}
+
+/// The pose of a child frame in its parent frame is the transform from the
+/// child to the parent
+impl From<Pose> for Transform {
+    fn from(pose: Pose) -> Self {
+        let Pose {
+            position: p,
+            orientation: q,
+        } = pose;
+        Transform::new([p.x, p.y, p.z], [q.x, q.y, q.z, q.w])
+    }
+}
+
+impl From<Transform> for Pose {
+    fn from(transform: Transform) -> Self {
+        let [x, y, z] = transform.translation;
+        let [qx, qy, qz, qw] = transform.rotation;
+        Pose {
+            position: Point { x, y, z },
+            orientation: Quaternion {
+                x: qx,
+                y: qy,
+                z: qz,
+                w: qw,
+            },
+        }
+    }
+}
+
+/// Linear and angular velocity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "geometry_msgs/msg/Twist")]
+pub struct Twist {
+    pub linear: Vector3,
+    pub angular: Vector3,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaw() {
+        let q = Quaternion::from_yaw(1.0);
+        assert!((q.yaw() - 1.0).abs() < 1e-12);
+        assert_eq!(Quaternion::default().yaw(), 0.0);
+        let pose = Pose {
+            orientation: q,
+            ..Default::default()
+        };
+        assert_eq!(Pose::from(Transform::from(pose)), pose);
+    }
+}
@@ -0,0 +1,99 @@
+//! Conversions to and from `glam`
+
+use crate::geometry_msgs::{Point, Pose, Quaternion, Vector3};
+use glam::{DAffine3, DQuat, DVec3};
+
+impl From<Vector3> for DVec3 {
+    fn from(v: Vector3) -> Self {
+        DVec3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<DVec3> for Vector3 {
+    fn from(v: DVec3) -> Self {
+        Vector3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+impl From<Point> for DVec3 {
+    fn from(p: Point) -> Self {
+        DVec3::new(p.x, p.y, p.z)
+    }
+}
+
+impl From<DVec3> for Point {
+    fn from(v: DVec3) -> Self {
+        Point {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+impl From<Quaternion> for DQuat {
+    fn from(q: Quaternion) -> Self {
+        DQuat::from_xyzw(q.x, q.y, q.z, q.w)
+    }
+}
+
+impl From<DQuat> for Quaternion {
+    fn from(q: DQuat) -> Self {
+        Quaternion {
+            x: q.x,
+            y: q.y,
+            z: q.z,
+            w: q.w,
+        }
+    }
+}
+
+impl From<Pose> for DAffine3 {
+    fn from(pose: Pose) -> Self {
+        DAffine3::from_rotation_translation(pose.orientation.into(), pose.position.into())
+    }
+}
+
+/// Drops any scale of the affine transform
+impl From<DAffine3> for Pose {
+    fn from(affine: DAffine3) -> Self {
+        let (_, rotation, translation) = affine.to_scale_rotation_translation();
+        Pose {
+            position: translation.into(),
+            orientation: rotation.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        let pose = Pose {
+            position: Point {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+            orientation: Quaternion::from_yaw(0.5),
+        };
+        let affine = DAffine3::from(pose);
+        let moved = affine.transform_point3(DVec3::X);
+        assert!((moved.x - (1.0 + 0.5f64.cos())).abs() < 1e-12);
+        let back = Pose::from(affine);
+        assert!((back.position.y - 2.0).abs() < 1e-12);
+        assert!((back.orientation.yaw() - 0.5).abs() < 1e-12);
+        let v = Vector3 {
+            x: 1.0,
+            y: -1.0,
+            z: 0.5,
+        };
+        assert_eq!(Vector3::from(DVec3::from(v)), v);
+    }
+}
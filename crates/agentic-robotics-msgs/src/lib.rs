@@ -0,0 +1,34 @@
+//! Standard ros3 messages
+//!
+//! The common geometry, sensor and navigation types, so nodes stop
+//! re-declaring their own `Twist` and `LaserScan`. Each type is a
+//! `#[derive(Ros3Message)]` struct laid out field by field like its ROS 2
+//! interface, and named after it, e.g. `geometry_msgs/msg/Twist`: its CDR
+//! encoding is what ROS 2 nodes send, so the ROS 2 bridge forwards these
+//! messages unchanged.
+//!
+//! The modules follow the ROS 2 packages; the types are also re-exported at
+//! the crate root. [`point_cloud`] reads and writes the points of a
+//! [`PointCloud2`] without offset arithmetic.
+//!
+//! With the `nalgebra` or `glam` feature, the geometry types convert to and
+//! from the vectors, quaternions and isometries of those crates.
+
+pub mod builtin_interfaces;
+pub mod geometry_msgs;
+pub mod nav_msgs;
+pub mod point_cloud;
+pub mod sensor_msgs;
+pub mod std_msgs;
+
+#[cfg(feature = "glam")]
+mod glam_conv;
+#[cfg(feature = "nalgebra")]
+mod nalgebra_conv;
+
+pub use builtin_interfaces::Time;
+pub use geometry_msgs::{Point, Pose, PoseStamped, Quaternion, Twist, Vector3};
+pub use nav_msgs::{MapMetaData, OccupancyGrid, Path};
+pub use point_cloud::{PointCloudError, PointCloudReader, PointCloudWriter};
+pub use sensor_msgs::{BatteryState, Imu, JointState, LaserScan, PointCloud2, PointField};
+pub use std_msgs::Header;
@@ -0,0 +1,99 @@
+//! Conversions to and from `nalgebra`
+
+use crate::geometry_msgs::{Point, Pose, Quaternion, Vector3};
+use nalgebra as na;
+
+impl From<Vector3> for na::Vector3<f64> {
+    fn from(v: Vector3) -> Self {
+        na::Vector3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<na::Vector3<f64>> for Vector3 {
+    fn from(v: na::Vector3<f64>) -> Self {
+        Vector3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+impl From<Point> for na::Point3<f64> {
+    fn from(p: Point) -> Self {
+        na::Point3::new(p.x, p.y, p.z)
+    }
+}
+
+impl From<na::Point3<f64>> for Point {
+    fn from(p: na::Point3<f64>) -> Self {
+        Point {
+            x: p.x,
+            y: p.y,
+            z: p.z,
+        }
+    }
+}
+
+/// Normalizes the quaternion
+impl From<Quaternion> for na::UnitQuaternion<f64> {
+    fn from(q: Quaternion) -> Self {
+        na::UnitQuaternion::from_quaternion(na::Quaternion::new(q.w, q.x, q.y, q.z))
+    }
+}
+
+impl From<na::UnitQuaternion<f64>> for Quaternion {
+    fn from(q: na::UnitQuaternion<f64>) -> Self {
+        Quaternion {
+            x: q.i,
+            y: q.j,
+            z: q.k,
+            w: q.w,
+        }
+    }
+}
+
+impl From<Pose> for na::Isometry3<f64> {
+    fn from(pose: Pose) -> Self {
+        let position: na::Point3<f64> = pose.position.into();
+        na::Isometry3::from_parts(position.into(), pose.orientation.into())
+    }
+}
+
+impl From<na::Isometry3<f64>> for Pose {
+    fn from(isometry: na::Isometry3<f64>) -> Self {
+        Pose {
+            position: na::Point3::from(isometry.translation.vector).into(),
+            orientation: isometry.rotation.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        let pose = Pose {
+            position: Point {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+            orientation: Quaternion::from_yaw(0.5),
+        };
+        let isometry = na::Isometry3::from(pose);
+        let moved = isometry * na::Point3::new(1.0, 0.0, 0.0);
+        assert!((moved.x - (1.0 + 0.5f64.cos())).abs() < 1e-12);
+        let back = Pose::from(isometry);
+        assert_eq!(back.position, pose.position);
+        assert!((back.orientation.yaw() - 0.5).abs() < 1e-12);
+        let v = Vector3 {
+            x: 1.0,
+            y: -1.0,
+            z: 0.5,
+        };
+        assert_eq!(Vector3::from(na::Vector3::from(v)), v);
+    }
+}
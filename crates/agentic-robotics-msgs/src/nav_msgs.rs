@@ -0,0 +1,199 @@
+//! `nav_msgs`: maps for navigation
+
+use crate::builtin_interfaces::Time;
+use crate::geometry_msgs::{Point, Pose, PoseStamped};
+use crate::std_msgs::Header;
+use agentic_robotics_core::tf::Transform;
+use agentic_robotics_core::Ros3Message;
+
+/// The size and placement of an [`OccupancyGrid`]
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "nav_msgs/msg/MapMetaData")]
+pub struct MapMetaData {
+    /// When the map was loaded
+    pub map_load_time: Time,
+    /// Meters per cell
+    pub resolution: f32,
+    /// Cells per row
+    pub width: u32,
+    pub height: u32,
+    /// Pose of the corner of cell (0, 0) in the map frame
+    pub origin: Pose,
+}
+
+/// A 2D map of cells that are free, occupied or unknown
+///
+/// `data` holds the cells row by row, starting at the origin, as the
+/// probability of occupancy in percent, or `-1` if unknown.
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "nav_msgs/msg/OccupancyGrid")]
+pub struct OccupancyGrid {
+    pub header: Header,
+    pub info: MapMetaData,
+    pub data: Vec<i8>,
+}
+
+/// A sequence of poses for a robot to follow
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "nav_msgs/msg/Path")]
+pub struct Path {
+    pub header: Header,
+    pub poses: Vec<PoseStamped>,
+}
+
+impl OccupancyGrid {
+    pub const UNKNOWN: i8 = -1;
+    pub const FREE: i8 = 0;
+    pub const OCCUPIED: i8 = 100;
+    /// Cells from this probability up count as occupied, as with the
+    /// default `occupied_thresh` of the ROS map server
+    pub const OCCUPIED_THRESHOLD: i8 = 65;
+
+    /// The value of cell (`x`, `y`), `None` outside the map
+    pub fn cell(&self, x: u32, y: u32) -> Option<i8> {
+        if x >= self.info.width || y >= self.info.height {
+            return None;
+        }
+        let index = y as usize * self.info.width as usize + x as usize;
+        self.data.get(index).copied()
+    }
+
+    /// Whether cell (`x`, `y`) is known to be occupied
+    pub fn is_occupied(&self, x: u32, y: u32) -> bool {
+        self.cell(x, y)
+            .is_some_and(|value| value >= Self::OCCUPIED_THRESHOLD)
+    }
+
+    /// The cell containing `point`, given in the map frame, `None` outside
+    /// the map
+    pub fn world_to_grid(&self, point: Point) -> Option<(u32, u32)> {
+        let [x, y] = self.to_grid(point).map(f64::floor);
+        let in_map = |v: f64, size: u32| (0.0..f64::from(size)).contains(&v);
+        (in_map(x, self.info.width) && in_map(y, self.info.height)).then_some((x as u32, y as u32))
+    }
+
+    /// The center of cell (`x`, `y`) in the map frame
+    pub fn grid_to_world(&self, x: u32, y: u32) -> Point {
+        let resolution = f64::from(self.info.resolution);
+        let center = |v: u32| (f64::from(v) + 0.5) * resolution;
+        let [x, y, z] =
+            Transform::from(self.info.origin).transform_point([center(x), center(y), 0.0]);
+        Point { x, y, z }
+    }
+
+    /// The first occupied cell on the segment from `from` to `to`, given in
+    /// the map frame
+    ///
+    /// Walks the cells the segment passes through, in order, including
+    /// those of its ends. The parts of the segment outside the map are
+    /// skipped, so rays may start or end beyond it.
+    pub fn raycast(&self, from: Point, to: Point) -> Option<(u32, u32)> {
+        let (width, height) = (self.info.width, self.info.height);
+        let [ax, ay] = self.to_grid(from);
+        let [bx, by] = self.to_grid(to);
+        let (dx, dy) = (bx - ax, by - ay);
+        if ![ax, ay, dx, dy].iter().all(|v| v.is_finite()) {
+            return None;
+        }
+
+        // Clip the segment to the map, as a range of the fraction of the
+        // way from `from` to `to`
+        let (mut enter, mut exit) = (0.0f64, 1.0f64);
+        for (start, delta, size) in [(ax, dx, width), (ay, dy, height)] {
+            if delta == 0.0 {
+                if !(0.0..=f64::from(size)).contains(&start) {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = (-start / delta, (f64::from(size) - start) / delta);
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+        if enter > exit || width == 0 || height == 0 {
+            return None;
+        }
+
+        // Step from cell to cell, always across the nearest cell border
+        let cell = |t: f64| {
+            let clamp = |v: f64, size: u32| (v.floor().max(0.0) as u32).min(size - 1);
+            (clamp(ax + t * dx, width), clamp(ay + t * dy, height))
+        };
+        let ((mut x, mut y), (end_x, end_y)) = (cell(enter), cell(exit));
+        let axis = |start: f64, delta: f64, cell: u32| {
+            if delta == 0.0 {
+                return (0, f64::INFINITY, f64::INFINITY);
+            }
+            let (step, border) = if delta > 0.0 {
+                (1, f64::from(cell) + 1.0)
+            } else {
+                (-1, f64::from(cell))
+            };
+            (step, (border - start) / delta, 1.0 / delta.abs())
+        };
+        let (step_x, mut next_x, delta_x) = axis(ax, dx, x);
+        let (step_y, mut next_y, delta_y) = axis(ay, dy, y);
+        for _ in 0..=end_x.abs_diff(x) + end_y.abs_diff(y) {
+            if self.is_occupied(x, y) {
+                return Some((x, y));
+            }
+            if next_x < next_y {
+                x = x.checked_add_signed(step_x).filter(|x| *x < width)?;
+                next_x += delta_x;
+            } else {
+                y = y.checked_add_signed(step_y).filter(|y| *y < height)?;
+                next_y += delta_y;
+            }
+        }
+        None
+    }
+
+    /// `point` in the map frame, in cells from the origin
+    fn to_grid(&self, point: Point) -> [f64; 2] {
+        let resolution = f64::from(self.info.resolution);
+        let [x, y, _] = Transform::from(self.info.origin)
+            .inverse()
+            .transform_point([point.x, point.y, point.z]);
+        [x / resolution, y / resolution]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry_msgs::Quaternion;
+
+    #[test]
+    fn test_grid_coordinates() {
+        let mut grid = OccupancyGrid {
+            info: MapMetaData {
+                resolution: 0.5,
+                width: 4,
+                height: 2,
+                origin: Pose {
+                    position: Point {
+                        x: 1.0,
+                        y: 2.0,
+                        z: 0.0,
+                    },
+                    orientation: Quaternion::from_yaw(std::f64::consts::FRAC_PI_2),
+                },
+                ..Default::default()
+            },
+            data: vec![0; 8],
+            ..Default::default()
+        };
+        grid.data[6] = OccupancyGrid::OCCUPIED;
+        assert_eq!(grid.cell(2, 1), Some(OccupancyGrid::OCCUPIED));
+        assert!(grid.is_occupied(2, 1));
+        assert!(!grid.is_occupied(1, 1));
+        assert_eq!(grid.cell(4, 0), None);
+
+        // The map is turned a quarter: its x axis points along the world's y
+        let center = grid.grid_to_world(2, 1);
+        assert!((center.x - 0.25).abs() < 1e-9 && (center.y - 3.25).abs() < 1e-9);
+        assert_eq!(grid.world_to_grid(center), Some((2, 1)));
+        let outside = Point { x: 1.25, ..center };
+        assert_eq!(grid.world_to_grid(outside), None);
+    }
+}
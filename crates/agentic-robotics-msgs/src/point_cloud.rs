@@ -0,0 +1,718 @@
+//! Typed access to the points of a [`PointCloud2`]
+//!
+//! A [`PointCloudReader`] checks the field table of a cloud once, then
+//! iterates its points as `[f32; 3]` or as any [`Point`] type, decoding each
+//! field by its datatype and the cloud's endianness and skipping the padding
+//! between points and rows. A [`PointCloudWriter`] lays out the fields of a
+//! new cloud and packs points into it.
+//!
+//! ```
+//! use agentic_robotics_msgs::point_cloud::{PointCloudReader, PointCloudWriter, PointXYZI};
+//! use agentic_robotics_msgs::Header;
+//!
+//! let mut writer = PointCloudWriter::for_point::<PointXYZI>(Header::default());
+//! writer.push(&PointXYZI { x: 1.0, y: 2.0, z: 3.0, intensity: 0.5 })?;
+//! let cloud = writer.build();
+//!
+//! let reader = PointCloudReader::new(&cloud)?;
+//! let points: Vec<[f32; 3]> = reader.iter_xyz()?.collect();
+//! assert_eq!(points, [[1.0, 2.0, 3.0]]);
+//! # Ok::<(), agentic_robotics_msgs::point_cloud::PointCloudError>(())
+//! ```
+
+use crate::sensor_msgs::{PointCloud2, PointField};
+use crate::std_msgs::Header;
+use std::marker::PhantomData;
+use std::slice::ChunksExact;
+
+/// Why the points of a cloud cannot be read or written
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PointCloudError {
+    #[error("Field '{name}' has unknown datatype {datatype}")]
+    UnknownDatatype { name: String, datatype: u8 },
+
+    #[error("Field '{name}' ends at byte {end}, beyond the point step of {point_step}")]
+    FieldOutOfBounds {
+        name: String,
+        end: usize,
+        point_step: usize,
+    },
+
+    #[error("Row step {row_step} is shorter than {width} points of {point_step} bytes")]
+    RowTooShort {
+        row_step: usize,
+        width: usize,
+        point_step: usize,
+    },
+
+    #[error("Cloud needs {expected} bytes of data but has {actual}")]
+    DataTooShort { expected: usize, actual: usize },
+
+    #[error("Cloud has no field '{0}'")]
+    MissingField(String),
+}
+
+/// A numeric type fields can be read as and written from
+///
+/// Values convert from and to the datatype of the field as with `as`, so a
+/// `UINT16` intensity reads as an `f32` and an `f64` writes to a `FLOAT32`
+/// field.
+pub trait Element: Copy {
+    /// The datatype of fields holding this type
+    const DATATYPE: u8;
+
+    /// Decode a value of `datatype` from the start of `bytes`
+    fn decode(datatype: u8, bytes: &[u8], big_endian: bool) -> Self;
+
+    /// Encode the value as `datatype` to the start of `bytes`
+    fn encode(self, datatype: u8, bytes: &mut [u8], big_endian: bool);
+}
+
+macro_rules! convert_element {
+    (decode $ty:ty, $datatype:expr, $bytes:expr, $big_endian:expr) => {
+        match $datatype {
+            PointField::INT8 => convert_element!(@decode i8, $ty, $bytes, $big_endian),
+            PointField::UINT8 => convert_element!(@decode u8, $ty, $bytes, $big_endian),
+            PointField::INT16 => convert_element!(@decode i16, $ty, $bytes, $big_endian),
+            PointField::UINT16 => convert_element!(@decode u16, $ty, $bytes, $big_endian),
+            PointField::INT32 => convert_element!(@decode i32, $ty, $bytes, $big_endian),
+            PointField::UINT32 => convert_element!(@decode u32, $ty, $bytes, $big_endian),
+            PointField::FLOAT32 => convert_element!(@decode f32, $ty, $bytes, $big_endian),
+            _ => convert_element!(@decode f64, $ty, $bytes, $big_endian),
+        }
+    };
+    (encode $value:expr, $datatype:expr, $bytes:expr, $big_endian:expr) => {
+        match $datatype {
+            PointField::INT8 => convert_element!(@encode i8, $value, $bytes, $big_endian),
+            PointField::UINT8 => convert_element!(@encode u8, $value, $bytes, $big_endian),
+            PointField::INT16 => convert_element!(@encode i16, $value, $bytes, $big_endian),
+            PointField::UINT16 => convert_element!(@encode u16, $value, $bytes, $big_endian),
+            PointField::INT32 => convert_element!(@encode i32, $value, $bytes, $big_endian),
+            PointField::UINT32 => convert_element!(@encode u32, $value, $bytes, $big_endian),
+            PointField::FLOAT32 => convert_element!(@encode f32, $value, $bytes, $big_endian),
+            _ => convert_element!(@encode f64, $value, $bytes, $big_endian),
+        }
+    };
+    (@decode $src:ty, $ty:ty, $bytes:expr, $big_endian:expr) => {{
+        let bytes = $bytes[..std::mem::size_of::<$src>()].try_into().unwrap();
+        let value = if $big_endian {
+            <$src>::from_be_bytes(bytes)
+        } else {
+            <$src>::from_le_bytes(bytes)
+        };
+        value as $ty
+    }};
+    (@encode $dst:ty, $value:expr, $bytes:expr, $big_endian:expr) => {{
+        let value = $value as $dst;
+        let raw = if $big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        $bytes[..raw.len()].copy_from_slice(&raw);
+    }};
+}
+
+macro_rules! element {
+    ($($ty:ty => $datatype:ident),* $(,)?) => {$(
+        impl Element for $ty {
+            const DATATYPE: u8 = PointField::$datatype;
+
+            #[inline]
+            fn decode(datatype: u8, bytes: &[u8], big_endian: bool) -> Self {
+                convert_element!(decode $ty, datatype, bytes, big_endian)
+            }
+
+            #[inline]
+            fn encode(self, datatype: u8, bytes: &mut [u8], big_endian: bool) {
+                convert_element!(encode self, datatype, bytes, big_endian)
+            }
+        }
+    )*};
+}
+
+element! {
+    i8 => INT8,
+    u8 => UINT8,
+    i16 => INT16,
+    u16 => UINT16,
+    i32 => INT32,
+    u32 => UINT32,
+    f32 => FLOAT32,
+    f64 => FLOAT64,
+}
+
+/// A type the points of a cloud can be read as and written from
+///
+/// `FIELDS` lists the fields the point is made of by name, with their
+/// datatype. A reader looks them up by name, so the cloud may order and pad
+/// them differently; if it stores them with other datatypes or byte order,
+/// the reader converts them first. `read` gets each field as the Rust type
+/// of its datatype, e.g. `f32` for `FLOAT32`.
+///
+/// ```
+/// use agentic_robotics_msgs::point_cloud::{Point, PointMut, PointRef};
+/// use agentic_robotics_msgs::PointField;
+///
+/// struct Return {
+///     range: f32,
+///     ring: u16,
+/// }
+///
+/// impl Point for Return {
+///     const FIELDS: &'static [(&'static str, u8)] =
+///         &[("range", PointField::FLOAT32), ("ring", PointField::UINT16)];
+///
+///     fn read(point: &PointRef<'_, Self>) -> Self {
+///         Return { range: point.get(0), ring: point.get(1) }
+///     }
+///
+///     fn write(&self, point: &mut PointMut<'_, Self>) {
+///         point.set(0, self.range);
+///         point.set(1, self.ring);
+///     }
+/// }
+/// ```
+pub trait Point: Sized {
+    const FIELDS: &'static [(&'static str, u8)];
+
+    /// Build a point from its fields, `FIELDS[i]` being `point.get(i)`
+    fn read(point: &PointRef<'_, Self>) -> Self;
+
+    /// Store the point's fields, `FIELDS[i]` with `point.set(i, value)`
+    fn write(&self, point: &mut PointMut<'_, Self>);
+}
+
+/// The `x`, `y` and `z` of a point
+impl Point for [f32; 3] {
+    const FIELDS: &'static [(&'static str, u8)] = &[
+        ("x", PointField::FLOAT32),
+        ("y", PointField::FLOAT32),
+        ("z", PointField::FLOAT32),
+    ];
+
+    #[inline]
+    fn read(point: &PointRef<'_, Self>) -> Self {
+        [point.get(0), point.get(1), point.get(2)]
+    }
+
+    fn write(&self, point: &mut PointMut<'_, Self>) {
+        for (i, value) in self.iter().enumerate() {
+            point.set(i, *value);
+        }
+    }
+}
+
+/// A position with the intensity of its return, as lidars report
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PointXYZI {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub intensity: f32,
+}
+
+impl Point for PointXYZI {
+    const FIELDS: &'static [(&'static str, u8)] = &[
+        ("x", PointField::FLOAT32),
+        ("y", PointField::FLOAT32),
+        ("z", PointField::FLOAT32),
+        ("intensity", PointField::FLOAT32),
+    ];
+
+    #[inline]
+    fn read(point: &PointRef<'_, Self>) -> Self {
+        PointXYZI {
+            x: point.get(0),
+            y: point.get(1),
+            z: point.get(2),
+            intensity: point.get(3),
+        }
+    }
+
+    fn write(&self, point: &mut PointMut<'_, Self>) {
+        point.set(0, self.x);
+        point.set(1, self.y);
+        point.set(2, self.z);
+        point.set(3, self.intensity);
+    }
+}
+
+const NATIVE_BIG_ENDIAN: bool = cfg!(target_endian = "big");
+
+/// Where a field of a [`Point`] is in the cloud
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// As in the field table, so that adding a field's size cannot overflow
+    offset: u32,
+    datatype: u8,
+}
+
+/// Resolve the fields of `P` to their slots in `fields`
+fn slots<P: Point>(fields: &[PointField]) -> Result<Vec<Slot>, PointCloudError> {
+    P::FIELDS
+        .iter()
+        .map(|(name, _)| {
+            let field = fields
+                .iter()
+                .find(|field| field.name == *name)
+                .ok_or_else(|| PointCloudError::MissingField(name.to_string()))?;
+            Ok(Slot {
+                offset: field.offset,
+                datatype: field.datatype,
+            })
+        })
+        .collect()
+}
+
+/// The bytes of one point of type `P`, being read
+///
+/// The fields have the datatypes `P` declares, in native byte order.
+pub struct PointRef<'a, P> {
+    bytes: &'a [u8],
+    slots: &'a [Slot],
+    point: PhantomData<fn() -> P>,
+}
+
+impl<P: Point> PointRef<'_, P> {
+    /// The first element of field `index` of `P`
+    ///
+    /// # Panics
+    ///
+    /// If `P` has no field `index`, or declares it with another datatype
+    /// than `T`'s.
+    #[inline]
+    pub fn get<T: Element>(&self, index: usize) -> T {
+        // Constant once inlined into `P::read`
+        assert!(
+            P::FIELDS[index].1 == T::DATATYPE,
+            "field read as another datatype than the point type declares"
+        );
+        let start = self.slots[index].offset as usize;
+        let bytes = &self.bytes[start..start + std::mem::size_of::<T>()];
+        T::decode(T::DATATYPE, bytes, NATIVE_BIG_ENDIAN)
+    }
+}
+
+/// The bytes of one point of type `P`, being written
+pub struct PointMut<'a, P> {
+    bytes: &'a mut [u8],
+    slots: &'a [Slot],
+    big_endian: bool,
+    point: PhantomData<fn() -> P>,
+}
+
+impl<P: Point> PointMut<'_, P> {
+    /// Set the first element of field `index` of `P`, converting it to the
+    /// datatype of the cloud's field
+    ///
+    /// # Panics
+    ///
+    /// If `P` has no field `index`, or declares it with another datatype
+    /// than `T`'s.
+    #[inline]
+    pub fn set<T: Element>(&mut self, index: usize, value: T) {
+        assert!(
+            P::FIELDS[index].1 == T::DATATYPE,
+            "field written as another datatype than the point type declares"
+        );
+        let slot = self.slots[index];
+        value.encode(
+            slot.datatype,
+            &mut self.bytes[slot.offset as usize..],
+            self.big_endian,
+        );
+    }
+}
+
+/// Reads the points of a [`PointCloud2`] whose layout has been checked
+#[derive(Debug, Clone, Copy)]
+pub struct PointCloudReader<'a> {
+    cloud: &'a PointCloud2,
+}
+
+impl<'a> PointCloudReader<'a> {
+    /// Check that every field has a known datatype and fits in a point, and
+    /// that the rows fit in the data
+    pub fn new(cloud: &'a PointCloud2) -> Result<Self, PointCloudError> {
+        let point_step = cloud.point_step as usize;
+        for field in &cloud.fields {
+            let size = field
+                .element_size()
+                .ok_or_else(|| PointCloudError::UnknownDatatype {
+                    name: field.name.clone(),
+                    datatype: field.datatype,
+                })?;
+            let end = field.offset as usize + size * field.count.max(1) as usize;
+            if end > point_step {
+                return Err(PointCloudError::FieldOutOfBounds {
+                    name: field.name.clone(),
+                    end,
+                    point_step,
+                });
+            }
+        }
+
+        let (width, height) = (cloud.width as usize, cloud.height as usize);
+        let row_step = cloud.row_step as usize;
+        if height > 0 && row_step < width * point_step {
+            return Err(PointCloudError::RowTooShort {
+                row_step,
+                width,
+                point_step,
+            });
+        }
+        if width > 0 && height > 0 {
+            // The last row may stop after its last point
+            let expected = (height - 1)
+                .saturating_mul(row_step)
+                .saturating_add(width * point_step);
+            if cloud.data.len() < expected {
+                return Err(PointCloudError::DataTooShort {
+                    expected,
+                    actual: cloud.data.len(),
+                });
+            }
+        }
+        Ok(Self { cloud })
+    }
+
+    pub fn cloud(&self) -> &'a PointCloud2 {
+        self.cloud
+    }
+
+    /// Number of points
+    pub fn len(&self) -> usize {
+        self.cloud.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cloud.is_empty()
+    }
+
+    /// The points as `P`, row by row
+    ///
+    /// Fails if the cloud lacks a field of `P`. A cloud that stores a field
+    /// of `P` with another datatype, or that is not in native byte order, is
+    /// converted to the layout of `P` first, as with `as`.
+    pub fn iter<P: Point>(&self) -> Result<Points<'a, P>, PointCloudError> {
+        let cloud = self.cloud;
+        let slots = slots::<P>(&cloud.fields)?;
+        let native = cloud.is_bigendian == NATIVE_BIG_ENDIAN
+            && slots
+                .iter()
+                .zip(P::FIELDS)
+                .all(|(slot, (_, datatype))| slot.datatype == *datatype);
+        // Points of no bytes, from a cloud without fields, go through the conversion
+        if native && cloud.point_step > 0 {
+            let point_step = cloud.point_step as usize;
+            let row_len = cloud.width as usize * point_step;
+            return Ok(Points(Source::InPlace {
+                row: [].chunks_exact(point_step),
+                rows: &cloud.data,
+                rows_left: if cloud.is_empty() {
+                    0
+                } else {
+                    cloud.height as usize
+                },
+                width: cloud.width as usize,
+                point_step,
+                row_len,
+                row_step: cloud.row_step as usize,
+                slots,
+                point: PhantomData,
+            }));
+        }
+
+        let mut writer = PointCloudWriter::for_point::<P>(Header::default());
+        let converted = writer.slots::<P>()?;
+        let point_step = writer.point_step();
+        writer.data = vec![0; cloud.len() * point_step];
+        for (i, target) in writer.data.chunks_exact_mut(point_step.max(1)).enumerate() {
+            let source = self.point_bytes(i);
+            for (from, to) in slots.iter().zip(&converted) {
+                let value = f64::decode(
+                    from.datatype,
+                    &source[from.offset as usize..],
+                    cloud.is_bigendian,
+                );
+                value.encode(
+                    to.datatype,
+                    &mut target[to.offset as usize..],
+                    NATIVE_BIG_ENDIAN,
+                );
+            }
+        }
+        let points: Vec<P> = (0..cloud.len())
+            .map(|i| {
+                let start = i * point_step;
+                P::read(&PointRef {
+                    bytes: &writer.data[start..start + point_step],
+                    slots: &converted,
+                    point: PhantomData,
+                })
+            })
+            .collect();
+        Ok(Points(Source::Converted(points.into_iter())))
+    }
+
+    /// The bytes of the `i`th point
+    fn point_bytes(&self, i: usize) -> &'a [u8] {
+        let cloud = self.cloud;
+        let width = cloud.width as usize;
+        let start = i / width * cloud.row_step as usize + i % width * cloud.point_step as usize;
+        &cloud.data[start..start + cloud.point_step as usize]
+    }
+
+    /// The `x`, `y` and `z` of the points, row by row
+    pub fn iter_xyz(&self) -> Result<Points<'a, [f32; 3]>, PointCloudError> {
+        self.iter()
+    }
+}
+
+/// Iterator over the points of a cloud, see [`PointCloudReader::iter`]
+pub struct Points<'a, P>(Source<'a, P>);
+
+enum Source<'a, P> {
+    /// Points read from the cloud as they are
+    InPlace {
+        /// The rest of the current row, and the rows after it
+        row: ChunksExact<'a, u8>,
+        rows: &'a [u8],
+        rows_left: usize,
+        width: usize,
+        point_step: usize,
+        /// Bytes of the points of a row, and from one row to the next
+        row_len: usize,
+        row_step: usize,
+        slots: Vec<Slot>,
+        point: PhantomData<fn() -> P>,
+    },
+    Converted(std::vec::IntoIter<P>),
+}
+
+impl<P: Point> Iterator for Points<'_, P> {
+    type Item = P;
+
+    #[inline]
+    fn next(&mut self) -> Option<P> {
+        match &mut self.0 {
+            Source::InPlace {
+                row,
+                rows,
+                rows_left,
+                point_step,
+                row_len,
+                row_step,
+                slots,
+                ..
+            } => loop {
+                if let Some(bytes) = row.next() {
+                    let point = PointRef {
+                        bytes,
+                        slots,
+                        point: PhantomData,
+                    };
+                    return Some(P::read(&point));
+                }
+                if *rows_left == 0 {
+                    return None;
+                }
+                // The reader checked that the data holds every row
+                *rows_left -= 1;
+                *row = rows[..*row_len].chunks_exact(*point_step);
+                *rows = rows.get(*row_step..).unwrap_or_default();
+            },
+            Source::Converted(points) => points.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match &self.0 {
+            Source::InPlace {
+                row,
+                rows_left,
+                width,
+                ..
+            } => row.len() + rows_left * width,
+            Source::Converted(points) => points.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl<P: Point> ExactSizeIterator for Points<'_, P> {}
+
+/// Builds an unordered [`PointCloud2`] point by point
+///
+/// Fields are laid out in the order they are added, each at the next offset
+/// aligned to its size, and points are padded to the largest alignment, as
+/// a C struct would be.
+#[derive(Debug, Clone)]
+pub struct PointCloudWriter {
+    header: Header,
+    fields: Vec<PointField>,
+    point_step: usize,
+    align: usize,
+    big_endian: bool,
+    data: Vec<u8>,
+    width: usize,
+}
+
+impl PointCloudWriter {
+    /// A writer without fields
+    pub fn new(header: Header) -> Self {
+        Self {
+            header,
+            fields: Vec::new(),
+            point_step: 0,
+            align: 1,
+            big_endian: false,
+            data: Vec::new(),
+            width: 0,
+        }
+    }
+
+    /// A writer with the fields of `P`
+    ///
+    /// # Panics
+    ///
+    /// If a field of `P` has an unknown datatype.
+    pub fn for_point<P: Point>(header: Header) -> Self {
+        P::FIELDS
+            .iter()
+            .fold(Self::new(header), |writer, (name, datatype)| {
+                writer.datatype_field(*name, *datatype)
+            })
+    }
+
+    /// Add a field holding a `T`
+    pub fn field<T: Element>(self, name: impl Into<String>) -> Self {
+        self.datatype_field(name, T::DATATYPE)
+    }
+
+    fn datatype_field(mut self, name: impl Into<String>, datatype: u8) -> Self {
+        let mut field = PointField::new(name, 0, datatype, 1);
+        let size = field
+            .element_size()
+            .unwrap_or_else(|| panic!("Field '{}' has unknown datatype {}", field.name, datatype));
+        let offset = self.point_step.next_multiple_of(size);
+        field.offset = offset as u32;
+        self.fields.push(field);
+        self.point_step = offset + size;
+        self.align = self.align.max(size);
+        self
+    }
+
+    /// Store the fields big-endian rather than little-endian
+    pub fn big_endian(mut self) -> Self {
+        self.big_endian = true;
+        self
+    }
+
+    /// Bytes per point, including padding
+    pub fn point_step(&self) -> usize {
+        self.point_step.next_multiple_of(self.align)
+    }
+
+    /// Number of points written
+    pub fn len(&self) -> usize {
+        self.width
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0
+    }
+
+    fn slots<P: Point>(&self) -> Result<Vec<Slot>, PointCloudError> {
+        slots::<P>(&self.fields)
+    }
+
+    /// Append a point
+    ///
+    /// Fails if the writer lacks a field of `P`.
+    pub fn push<P: Point>(&mut self, point: &P) -> Result<(), PointCloudError> {
+        self.extend(std::iter::once(point))
+    }
+
+    /// Append points
+    ///
+    /// Fails if the writer lacks a field of `P`, before appending any.
+    pub fn extend<'p, P: Point + 'p>(
+        &mut self,
+        points: impl IntoIterator<Item = &'p P>,
+    ) -> Result<(), PointCloudError> {
+        let slots = self.slots::<P>()?;
+        let point_step = self.point_step();
+        for point in points {
+            let start = self.data.len();
+            self.data.resize(start + point_step, 0);
+            point.write(&mut PointMut {
+                bytes: &mut self.data[start..],
+                slots: &slots,
+                big_endian: self.big_endian,
+                point: PhantomData,
+            });
+            self.width += 1;
+        }
+        Ok(())
+    }
+
+    /// The cloud of the points written, in one row
+    ///
+    /// The cloud is dense unless it has an `x`, `y` or `z` that is NaN.
+    pub fn build(self) -> PointCloud2 {
+        let point_step = self.point_step();
+        let mut cloud = PointCloud2 {
+            header: self.header,
+            height: 1,
+            width: self.width as u32,
+            fields: self.fields,
+            is_bigendian: self.big_endian,
+            point_step: point_step as u32,
+            row_step: self.data.len() as u32,
+            data: self.data,
+            is_dense: true,
+        };
+        let has_nan =
+            |name: &str| (0..cloud.len()).any(|i| cloud.read(i, name).is_some_and(f64::is_nan));
+        cloud.is_dense = !["x", "y", "z"].into_iter().any(has_nan);
+        cloud
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_layout() {
+        let writer = PointCloudWriter::new(Header::default())
+            .field::<u8>("ring")
+            .field::<f32>("x")
+            .field::<u16>("label")
+            .field::<f64>("time");
+        let offsets: Vec<u32> = writer.fields.iter().map(|field| field.offset).collect();
+        assert_eq!(offsets, [0, 4, 8, 16]);
+        assert_eq!(writer.point_step(), 24);
+
+        // Trailing padding up to the largest field
+        let writer = PointCloudWriter::new(Header::default())
+            .field::<f32>("x")
+            .field::<u8>("ring");
+        assert_eq!(writer.point_step(), 8);
+    }
+
+    #[test]
+    fn test_element_conversions() {
+        let bytes = 700u16.to_be_bytes();
+        assert_eq!(f32::decode(PointField::UINT16, &bytes, true), 700.0);
+        assert_eq!(u8::decode(PointField::INT8, &[0xff], false), 255);
+
+        let mut bytes = [0; 8];
+        2.5f32.encode(PointField::FLOAT64, &mut bytes, false);
+        assert_eq!(bytes, 2.5f64.to_le_bytes());
+        (-1.0f64).encode(PointField::INT16, &mut bytes, true);
+        assert_eq!(&bytes[..2], &(-1i16).to_be_bytes());
+    }
+}
@@ -0,0 +1,346 @@
+//! `sensor_msgs`: readings of common robot sensors
+
+use crate::geometry_msgs::{Point, Quaternion, Vector3};
+use crate::point_cloud::{Element, PointCloudWriter};
+use crate::std_msgs::Header;
+use agentic_robotics_core::tf::{TfError, TransformBuffer};
+use agentic_robotics_core::Ros3Message;
+
+/// Orientation, angular velocity and linear acceleration from an inertial
+/// measurement unit
+///
+/// Covariances are row-major 3x3 matrices; a first element of `-1` marks
+/// the quantity as not measured.
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "sensor_msgs/msg/Imu")]
+pub struct Imu {
+    pub header: Header,
+    pub orientation: Quaternion,
+    pub orientation_covariance: [f64; 9],
+    pub angular_velocity: Vector3,
+    pub angular_velocity_covariance: [f64; 9],
+    pub linear_acceleration: Vector3,
+    pub linear_acceleration_covariance: [f64; 9],
+}
+
+/// One sweep of a planar range finder
+///
+/// Beam `i` points at `angle_min + i * angle_increment` radians. Ranges
+/// outside `range_min..=range_max` are not valid measurements.
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "sensor_msgs/msg/LaserScan")]
+pub struct LaserScan {
+    pub header: Header,
+    pub angle_min: f32,
+    pub angle_max: f32,
+    pub angle_increment: f32,
+    /// Seconds between beams
+    pub time_increment: f32,
+    /// Seconds between scans
+    pub scan_time: f32,
+    pub range_min: f32,
+    pub range_max: f32,
+    pub ranges: Vec<f32>,
+    pub intensities: Vec<f32>,
+}
+
+impl LaserScan {
+    /// Whether `range` is a measurement: finite and within
+    /// `range_min..=range_max`
+    pub fn is_valid(&self, range: f32) -> bool {
+        range.is_finite() && (self.range_min..=self.range_max).contains(&range)
+    }
+
+    /// The returns of the valid beams, as points in `target_frame`
+    ///
+    /// The transform from the scan's frame is looked up in `buffer` at the
+    /// scan's stamp, so this fails like [`TransformBuffer::lookup`] if the
+    /// buffer has no transform for that time.
+    pub fn to_points(
+        &self,
+        buffer: &TransformBuffer,
+        target_frame: &str,
+    ) -> Result<Vec<Point>, TfError> {
+        let transform = buffer
+            .lookup(
+                target_frame,
+                &self.header.frame_id,
+                self.header.stamp.into(),
+            )?
+            .transform();
+        let points = self
+            .ranges
+            .iter()
+            .enumerate()
+            .filter(|(_, range)| self.is_valid(**range))
+            .map(|(i, range)| {
+                let angle = f64::from(self.angle_min) + i as f64 * f64::from(self.angle_increment);
+                let range = f64::from(*range);
+                let [x, y, z] =
+                    transform.transform_point([range * angle.cos(), range * angle.sin(), 0.0]);
+                Point { x, y, z }
+            })
+            .collect();
+        Ok(points)
+    }
+}
+
+/// The name, position and type of one field of the points of a
+/// [`PointCloud2`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Ros3Message)]
+#[ros3(type_name = "sensor_msgs/msg/PointField")]
+pub struct PointField {
+    pub name: String,
+    /// Byte offset from the start of the point
+    pub offset: u32,
+    /// One of the datatype constants, e.g. [`PointField::FLOAT32`]
+    pub datatype: u8,
+    /// Number of elements
+    pub count: u32,
+}
+
+impl PointField {
+    pub const INT8: u8 = 1;
+    pub const UINT8: u8 = 2;
+    pub const INT16: u8 = 3;
+    pub const UINT16: u8 = 4;
+    pub const INT32: u8 = 5;
+    pub const UINT32: u8 = 6;
+    pub const FLOAT32: u8 = 7;
+    pub const FLOAT64: u8 = 8;
+
+    pub fn new(name: impl Into<String>, offset: u32, datatype: u8, count: u32) -> Self {
+        Self {
+            name: name.into(),
+            offset,
+            datatype,
+            count,
+        }
+    }
+
+    /// Size in bytes of one element, `None` for unknown datatypes
+    pub fn element_size(&self) -> Option<usize> {
+        match self.datatype {
+            Self::INT8 | Self::UINT8 => Some(1),
+            Self::INT16 | Self::UINT16 => Some(2),
+            Self::INT32 | Self::UINT32 | Self::FLOAT32 => Some(4),
+            Self::FLOAT64 => Some(8),
+            _ => None,
+        }
+    }
+}
+
+/// A cloud of points with arbitrary fields, packed into bytes
+///
+/// Each point takes `point_step` bytes of `data`, holding the `fields` at
+/// their offsets; rows of `width` points take `row_step` bytes. Unordered
+/// clouds have a `height` of 1.
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "sensor_msgs/msg/PointCloud2")]
+pub struct PointCloud2 {
+    pub header: Header,
+    pub height: u32,
+    pub width: u32,
+    pub fields: Vec<PointField>,
+    pub is_bigendian: bool,
+    pub point_step: u32,
+    pub row_step: u32,
+    pub data: Vec<u8>,
+    /// Whether every point is valid, i.e. none has NaN coordinates
+    pub is_dense: bool,
+}
+
+impl PointCloud2 {
+    /// An unordered cloud of points with float `x`, `y` and `z` fields
+    pub fn from_xyz(header: Header, points: &[[f32; 3]]) -> Self {
+        let mut writer = PointCloudWriter::for_point::<[f32; 3]>(header);
+        writer
+            .extend(points)
+            .expect("the writer has the fields of [f32; 3]");
+        writer.build()
+    }
+
+    /// Number of points
+    pub fn len(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn field(&self, name: &str) -> Option<&PointField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// The first element of field `name` of point `index`, as a float
+    ///
+    /// `None` if there is no such point or field, or if the field does not
+    /// fit in the point's bytes. To read many points, use a
+    /// [`PointCloudReader`](crate::point_cloud::PointCloudReader).
+    pub fn read(&self, index: usize, name: &str) -> Option<f64> {
+        let field = self.field(name)?;
+        let size = field.element_size()?;
+        let point_step = self.point_step as usize;
+        if index >= self.len() || field.offset as usize + size > point_step {
+            return None;
+        }
+        let (row, column) = (index / self.width as usize, index % self.width as usize);
+        let start = row * self.row_step as usize + column * point_step + field.offset as usize;
+        let bytes = self.data.get(start..start + size)?;
+        Some(f64::decode(field.datatype, bytes, self.is_bigendian))
+    }
+}
+
+/// The state of a battery
+///
+/// Quantities that are not measured are NaN.
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "sensor_msgs/msg/BatteryState")]
+pub struct BatteryState {
+    pub header: Header,
+    /// Volts
+    pub voltage: f32,
+    /// Degrees Celsius
+    pub temperature: f32,
+    /// Amperes, negative while discharging
+    pub current: f32,
+    /// Ampere-hours
+    pub charge: f32,
+    /// Ampere-hours when full
+    pub capacity: f32,
+    /// Ampere-hours when new
+    pub design_capacity: f32,
+    /// Charge from 0 to 1
+    pub percentage: f32,
+    pub power_supply_status: u8,
+    pub power_supply_health: u8,
+    pub power_supply_technology: u8,
+    pub present: bool,
+    pub cell_voltage: Vec<f32>,
+    pub cell_temperature: Vec<f32>,
+    pub location: String,
+    pub serial_number: String,
+}
+
+impl BatteryState {
+    pub const POWER_SUPPLY_STATUS_UNKNOWN: u8 = 0;
+    pub const POWER_SUPPLY_STATUS_CHARGING: u8 = 1;
+    pub const POWER_SUPPLY_STATUS_DISCHARGING: u8 = 2;
+    pub const POWER_SUPPLY_STATUS_NOT_CHARGING: u8 = 3;
+    pub const POWER_SUPPLY_STATUS_FULL: u8 = 4;
+
+    pub const POWER_SUPPLY_HEALTH_UNKNOWN: u8 = 0;
+    pub const POWER_SUPPLY_HEALTH_GOOD: u8 = 1;
+    pub const POWER_SUPPLY_HEALTH_OVERHEAT: u8 = 2;
+    pub const POWER_SUPPLY_HEALTH_DEAD: u8 = 3;
+    pub const POWER_SUPPLY_HEALTH_OVERVOLTAGE: u8 = 4;
+    pub const POWER_SUPPLY_HEALTH_UNSPEC_FAILURE: u8 = 5;
+    pub const POWER_SUPPLY_HEALTH_COLD: u8 = 6;
+    pub const POWER_SUPPLY_HEALTH_WATCHDOG_TIMER_EXPIRE: u8 = 7;
+    pub const POWER_SUPPLY_HEALTH_SAFETY_TIMER_EXPIRE: u8 = 8;
+
+    pub const POWER_SUPPLY_TECHNOLOGY_UNKNOWN: u8 = 0;
+    pub const POWER_SUPPLY_TECHNOLOGY_NIMH: u8 = 1;
+    pub const POWER_SUPPLY_TECHNOLOGY_LION: u8 = 2;
+    pub const POWER_SUPPLY_TECHNOLOGY_LIPO: u8 = 3;
+    pub const POWER_SUPPLY_TECHNOLOGY_LIFE: u8 = 4;
+    pub const POWER_SUPPLY_TECHNOLOGY_NICD: u8 = 5;
+    pub const POWER_SUPPLY_TECHNOLOGY_LIMN: u8 = 6;
+}
+
+/// Position, velocity and effort of a set of named joints
+///
+/// The vectors are indexed like `name`; any of them may be empty when not
+/// measured.
+#[derive(Debug, Clone, Default, PartialEq, Ros3Message)]
+#[ros3(type_name = "sensor_msgs/msg/JointState")]
+pub struct JointState {
+    pub header: Header,
+    pub name: Vec<String>,
+    pub position: Vec<f64>,
+    pub velocity: Vec<f64>,
+    pub effort: Vec<f64>,
+}
+
+impl JointState {
+    /// The position of joint `name`, if measured
+    pub fn position_of(&self, name: &str) -> Option<f64> {
+        let index = self.name.iter().position(|joint| joint == name)?;
+        self.position.get(index).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentic_robotics_core::tf::{Transform, TransformStamped};
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_laser_scan_to_points() {
+        let scan = LaserScan {
+            header: Header::new(agentic_robotics_core::Time::from_nanos(5), "laser"),
+            angle_min: -FRAC_PI_2,
+            angle_max: FRAC_PI_2,
+            angle_increment: FRAC_PI_2,
+            range_min: 0.1,
+            range_max: 10.0,
+            ranges: vec![2.0, f32::INFINITY, 1.0, 0.05, f32::NAN],
+            ..Default::default()
+        };
+        let buffer = TransformBuffer::default();
+        assert_eq!(
+            scan.to_points(&buffer, "base_link"),
+            Err(TfError::UnknownFrame("base_link".into()))
+        );
+
+        // The laser is mounted 0.5 m ahead of the base, facing backwards
+        let mount = Transform::from_translation([0.5, 0.0, 0.2])
+            * Transform::from_axis_angle([0.0, 0.0, 1.0], std::f64::consts::PI);
+        let stamp = agentic_robotics_core::Time::ZERO;
+        buffer
+            .set_transform(
+                TransformStamped::new("base_link", "laser", mount, stamp),
+                true,
+            )
+            .unwrap();
+        let points = scan.to_points(&buffer, "base_link").unwrap();
+        // Only the beams to the right and to the left of the laser are valid
+        assert_eq!(points.len(), 2);
+        let expected = [[0.5, 2.0, 0.2], [0.5, -1.0, 0.2]];
+        for (point, [x, y, z]) in points.iter().zip(expected) {
+            assert!((point.x - x).abs() < 1e-6, "{point:?}");
+            assert!((point.y - y).abs() < 1e-6, "{point:?}");
+            assert!((point.z - z).abs() < 1e-6, "{point:?}");
+        }
+    }
+
+    #[test]
+    fn test_point_cloud_field_access() {
+        let cloud = PointCloud2::from_xyz(Header::default(), &[[1.0, 2.0, 3.0], [4.0, 5.0, 6.5]]);
+        assert_eq!(cloud.len(), 2);
+        assert_eq!(cloud.data.len(), 24);
+        assert!(cloud.is_dense);
+        assert_eq!(cloud.read(1, "z"), Some(6.5));
+        assert_eq!(cloud.read(2, "x"), None);
+        assert_eq!(cloud.read(0, "rgb"), None);
+
+        // A big-endian cloud with padding between points
+        let mut padded = PointCloud2 {
+            height: 1,
+            width: 2,
+            fields: vec![PointField::new("intensity", 2, PointField::UINT16, 1)],
+            is_bigendian: true,
+            point_step: 8,
+            row_step: 16,
+            data: vec![0; 16],
+            ..Default::default()
+        };
+        padded.data[10..12].copy_from_slice(&700u16.to_be_bytes());
+        assert_eq!(padded.read(1, "intensity"), Some(700.0));
+        // A field beyond the point is not read from the next point
+        padded.fields[0].offset = 7;
+        assert_eq!(padded.read(0, "intensity"), None);
+    }
+}
@@ -0,0 +1,21 @@
+//! `std_msgs`: metadata shared by the other messages
+
+use crate::builtin_interfaces::Time;
+use agentic_robotics_core::Ros3Message;
+
+/// When and in which coordinate frame data was taken
+#[derive(Debug, Clone, Default, PartialEq, Eq, Ros3Message)]
+#[ros3(type_name = "std_msgs/msg/Header")]
+pub struct Header {
+    pub stamp: Time,
+    pub frame_id: String,
+}
+
+impl Header {
+    pub fn new(stamp: impl Into<Time>, frame_id: impl Into<String>) -> Self {
+        Self {
+            stamp: stamp.into(),
+            frame_id: frame_id.into(),
+        }
+    }
+}
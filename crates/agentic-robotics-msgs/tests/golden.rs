@@ -0,0 +1,171 @@
+//! CDR compatibility with ROS 2
+//!
+//! Each fixture under `tests/fixtures` holds the little-endian CDR bytes a
+//! ROS 2 node sends for the message built here, as hex. Both directions
+//! must match byte for byte, so the bridge can forward these messages
+//! without converting them.
+
+use agentic_robotics_msgs::*;
+use cdr::{CdrLe, Infinite};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::path::Path;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.hex", name));
+    let text = std::fs::read_to_string(&path).unwrap();
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+        .collect()
+}
+
+fn assert_golden<T>(name: &str, msg: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let golden = fixture(name);
+    let bytes = cdr::serialize::<_, _, CdrLe>(msg, Infinite).unwrap();
+    assert_eq!(bytes, golden, "{} encodes differently from ROS 2", name);
+    let decoded: T = cdr::deserialize(&golden).unwrap();
+    assert_eq!(&decoded, msg, "{} decodes differently from ROS 2", name);
+}
+
+fn header(sec: i32, nanosec: u32, frame_id: &str) -> Header {
+    Header {
+        stamp: Time { sec, nanosec },
+        frame_id: frame_id.to_string(),
+    }
+}
+
+fn vector(x: f64, y: f64, z: f64) -> Vector3 {
+    Vector3 { x, y, z }
+}
+
+#[test]
+fn test_header() {
+    assert_golden("header", &header(1_700_000_000, 500, "base_link"));
+}
+
+#[test]
+fn test_twist() {
+    let twist = Twist {
+        linear: vector(1.0, 0.0, 0.0),
+        angular: vector(0.0, 0.0, 0.5),
+    };
+    assert_golden("twist", &twist);
+}
+
+#[test]
+fn test_imu() {
+    let mut orientation_covariance = [0.0; 9];
+    orientation_covariance[0] = 0.01;
+    orientation_covariance[4] = 0.01;
+    orientation_covariance[8] = 0.01;
+    let mut angular_velocity_covariance = [0.0; 9];
+    angular_velocity_covariance[0] = -1.0;
+    let imu = Imu {
+        header: header(12, 34, "imu"),
+        orientation: Quaternion {
+            x: 0.0,
+            y: 0.0,
+            z: 0.6,
+            w: 0.8,
+        },
+        orientation_covariance,
+        angular_velocity: vector(0.1, -0.2, 0.3),
+        angular_velocity_covariance,
+        linear_acceleration: vector(0.0, 0.0, 9.81),
+        linear_acceleration_covariance: [0.0; 9],
+    };
+    assert_golden("imu", &imu);
+}
+
+#[test]
+fn test_laser_scan() {
+    let scan = LaserScan {
+        header: header(5, 0, "laser"),
+        angle_min: -1.5,
+        angle_max: 1.5,
+        angle_increment: 1.0,
+        time_increment: 0.001,
+        scan_time: 0.1,
+        range_min: 0.05,
+        range_max: 30.0,
+        ranges: vec![1.0, 2.5, f32::INFINITY, 0.5],
+        intensities: Vec::new(),
+    };
+    assert_golden("laser_scan", &scan);
+}
+
+#[test]
+fn test_point_cloud2() {
+    let cloud = PointCloud2::from_xyz(header(7, 8, "lidar"), &[[1.0, 2.0, 3.0], [4.0, 5.0, 6.5]]);
+    assert_golden("point_cloud2", &cloud);
+}
+
+#[test]
+fn test_occupancy_grid() {
+    let grid = OccupancyGrid {
+        header: header(100, 0, "map"),
+        info: MapMetaData {
+            map_load_time: Time {
+                sec: 99,
+                nanosec: 0,
+            },
+            resolution: 0.05,
+            width: 3,
+            height: 2,
+            origin: Pose {
+                position: Point {
+                    x: -1.0,
+                    y: -2.0,
+                    z: 0.0,
+                },
+                orientation: Quaternion::IDENTITY,
+            },
+        },
+        data: vec![0, 0, 100, -1, 50, 0],
+    };
+    assert_golden("occupancy_grid", &grid);
+}
+
+#[test]
+fn test_battery_state() {
+    let battery = BatteryState {
+        header: header(1, 2, "battery"),
+        voltage: 12.6,
+        temperature: 31.5,
+        current: -1.25,
+        charge: 4.0,
+        capacity: 5.0,
+        design_capacity: 5.2,
+        percentage: 0.8,
+        power_supply_status: BatteryState::POWER_SUPPLY_STATUS_DISCHARGING,
+        power_supply_health: BatteryState::POWER_SUPPLY_HEALTH_GOOD,
+        power_supply_technology: BatteryState::POWER_SUPPLY_TECHNOLOGY_LIPO,
+        present: true,
+        cell_voltage: vec![4.2; 3],
+        cell_temperature: Vec::new(),
+        location: "rear".to_string(),
+        serial_number: "SN-42".to_string(),
+    };
+    assert_golden("battery_state", &battery);
+}
+
+#[test]
+fn test_joint_state() {
+    let joints = JointState {
+        header: header(3, 4, ""),
+        name: vec!["shoulder".to_string(), "elbow".to_string()],
+        position: vec![0.5, -1.0],
+        velocity: vec![0.0, 0.25],
+        effort: Vec::new(),
+    };
+    assert_golden("joint_state", &joints);
+    assert_eq!(joints.position_of("elbow"), Some(-1.0));
+}
@@ -0,0 +1,235 @@
+use agentic_robotics_msgs::point_cloud::{Point, PointMut, PointRef, PointXYZI};
+use agentic_robotics_msgs::*;
+
+/// A cloud as a driver might send it: big-endian, intensity before the
+/// coordinates, 4 bytes of padding per point and 8 per row
+fn driver_cloud() -> PointCloud2 {
+    let mut data = Vec::new();
+    for row in 0..2u16 {
+        for column in 0..3u16 {
+            let i = row * 3 + column;
+            data.extend_from_slice(&(i * 10).to_be_bytes());
+            data.extend_from_slice(&[0, 0]);
+            for coordinate in [f32::from(i), f32::from(i) + 0.5, -f32::from(i)] {
+                data.extend_from_slice(&coordinate.to_be_bytes());
+            }
+            data.extend_from_slice(&[0xaa; 4]);
+        }
+        data.extend_from_slice(&[0xbb; 8]);
+    }
+    PointCloud2 {
+        height: 2,
+        width: 3,
+        fields: vec![
+            PointField::new("intensity", 0, PointField::UINT16, 1),
+            PointField::new("x", 4, PointField::FLOAT32, 1),
+            PointField::new("y", 8, PointField::FLOAT32, 1),
+            PointField::new("z", 12, PointField::FLOAT32, 1),
+        ],
+        is_bigendian: true,
+        point_step: 20,
+        row_step: 68,
+        data,
+        is_dense: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_reads_padded_big_endian_cloud() {
+    let cloud = driver_cloud();
+    let reader = PointCloudReader::new(&cloud).unwrap();
+    assert_eq!(reader.len(), 6);
+
+    let points: Vec<[f32; 3]> = reader.iter_xyz().unwrap().collect();
+    assert_eq!(points.len(), 6);
+    assert_eq!(points[0], [0.0, 0.5, 0.0]);
+    assert_eq!(points[4], [4.0, 4.5, -4.0]);
+
+    let intensities: Vec<f32> = reader
+        .iter::<PointXYZI>()
+        .unwrap()
+        .map(|point| point.intensity)
+        .collect();
+    assert_eq!(intensities, [0.0, 10.0, 20.0, 30.0, 40.0, 50.0]);
+}
+
+#[test]
+fn test_writer_round_trip() {
+    let points: Vec<PointXYZI> = (0..5)
+        .map(|i| PointXYZI {
+            x: i as f32,
+            y: -(i as f32),
+            z: 0.25,
+            intensity: 100.0 * i as f32,
+        })
+        .collect();
+    for big_endian in [false, true] {
+        let mut writer = PointCloudWriter::for_point::<PointXYZI>(Header::default());
+        if big_endian {
+            writer = writer.big_endian();
+        }
+        writer.extend(&points).unwrap();
+        let cloud = writer.build();
+        assert_eq!(cloud.is_bigendian, big_endian);
+        assert_eq!((cloud.width, cloud.height), (5, 1));
+        assert_eq!(cloud.point_step, 16);
+        assert!(cloud.is_dense);
+        assert_eq!(cloud.read(3, "intensity"), Some(300.0));
+
+        let reader = PointCloudReader::new(&cloud).unwrap();
+        let read: Vec<PointXYZI> = reader.iter().unwrap().collect();
+        assert_eq!(read, points);
+    }
+}
+
+#[test]
+fn test_writer_converts_and_marks_nan() {
+    let mut writer = PointCloudWriter::new(Header::default())
+        .field::<f64>("x")
+        .field::<f64>("y")
+        .field::<f64>("z")
+        .field::<u8>("intensity");
+    writer
+        .push(&PointXYZI {
+            x: 1.5,
+            y: f32::NAN,
+            z: 0.0,
+            intensity: 300.0,
+        })
+        .unwrap();
+    let cloud = writer.build();
+    assert_eq!(cloud.point_step, 32);
+    assert!(!cloud.is_dense);
+    assert_eq!(cloud.read(0, "x"), Some(1.5));
+    // Converted as with `as`, which saturates
+    assert_eq!(cloud.read(0, "intensity"), Some(255.0));
+}
+
+#[test]
+fn test_custom_point() {
+    #[derive(Debug, PartialEq)]
+    struct Return {
+        range: f32,
+        ring: u16,
+    }
+
+    impl Point for Return {
+        const FIELDS: &'static [(&'static str, u8)] =
+            &[("range", PointField::FLOAT32), ("ring", PointField::UINT16)];
+
+        fn read(point: &PointRef<'_, Self>) -> Self {
+            Return {
+                range: point.get(0),
+                ring: point.get(1),
+            }
+        }
+
+        fn write(&self, point: &mut PointMut<'_, Self>) {
+            point.set(0, self.range);
+            point.set(1, self.ring);
+        }
+    }
+
+    let mut writer = PointCloudWriter::for_point::<Return>(Header::default());
+    writer
+        .push(&Return {
+            range: 7.5,
+            ring: 3,
+        })
+        .unwrap();
+    // The writer has no x, y and z
+    assert_eq!(
+        writer.push(&[0.0f32; 3]),
+        Err(PointCloudError::MissingField("x".into()))
+    );
+    let cloud = writer.build();
+    assert_eq!(cloud.point_step, 8);
+
+    let reader = PointCloudReader::new(&cloud).unwrap();
+    let returns: Vec<Return> = reader.iter().unwrap().collect();
+    assert_eq!(
+        returns,
+        [Return {
+            range: 7.5,
+            ring: 3
+        }]
+    );
+    assert_eq!(
+        reader.iter_xyz().err(),
+        Some(PointCloudError::MissingField("x".into()))
+    );
+}
+
+#[test]
+fn test_rejects_malformed_clouds() {
+    let mut cloud = driver_cloud();
+    cloud.fields[3].offset = 18;
+    assert_eq!(
+        PointCloudReader::new(&cloud).err(),
+        Some(PointCloudError::FieldOutOfBounds {
+            name: "z".into(),
+            end: 22,
+            point_step: 20,
+        })
+    );
+
+    let mut cloud = driver_cloud();
+    cloud.fields[0].count = 11;
+    assert!(matches!(
+        PointCloudReader::new(&cloud),
+        Err(PointCloudError::FieldOutOfBounds { .. })
+    ));
+
+    let mut cloud = driver_cloud();
+    cloud.fields[1].datatype = 9;
+    assert_eq!(
+        PointCloudReader::new(&cloud).err(),
+        Some(PointCloudError::UnknownDatatype {
+            name: "x".into(),
+            datatype: 9,
+        })
+    );
+
+    let mut cloud = driver_cloud();
+    cloud.row_step = 50;
+    assert_eq!(
+        PointCloudReader::new(&cloud).err(),
+        Some(PointCloudError::RowTooShort {
+            row_step: 50,
+            width: 3,
+            point_step: 20,
+        })
+    );
+
+    // The last row may stop after its last point, but not before
+    let mut cloud = driver_cloud();
+    cloud.data.truncate(128);
+    assert!(PointCloudReader::new(&cloud).is_ok());
+    cloud.data.pop();
+    assert_eq!(
+        PointCloudReader::new(&cloud).err(),
+        Some(PointCloudError::DataTooShort {
+            expected: 128,
+            actual: 127,
+        })
+    );
+
+    // Huge dimensions are checked against the data, without overflowing
+    let mut cloud = driver_cloud();
+    cloud.height = u32::MAX;
+    cloud.row_step = u32::MAX;
+    assert!(matches!(
+        PointCloudReader::new(&cloud),
+        Err(PointCloudError::DataTooShort { .. })
+    ));
+}
+
+#[test]
+fn test_empty_cloud() {
+    let cloud = PointCloudWriter::for_point::<[f32; 3]>(Header::default()).build();
+    let reader = PointCloudReader::new(&cloud).unwrap();
+    assert!(reader.is_empty());
+    assert_eq!(reader.iter_xyz().unwrap().count(), 0);
+    assert_eq!(cloud, PointCloud2::from_xyz(Header::default(), &[]));
+}
@@ -0,0 +1,175 @@
+//! Raycasts through seeded random grids, checked against a brute-force
+//! search over every occupied cell
+
+use agentic_robotics_msgs::*;
+
+const WIDTH: u32 = 40;
+const HEIGHT: u32 = 30;
+const RESOLUTION: f64 = 0.5;
+const ORIGIN: [f64; 2] = [-3.0, 2.0];
+
+/// xorshift64*, so the cases are the same on every run
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform in `min..max`
+    fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + (self.next() >> 11) as f64 / (1u64 << 53) as f64 * (max - min)
+    }
+}
+
+fn grid(rng: &mut Rng) -> OccupancyGrid {
+    let data = (0..WIDTH * HEIGHT)
+        .map(|_| match rng.next() % 10 {
+            0 => OccupancyGrid::OCCUPIED,
+            1 => OccupancyGrid::UNKNOWN,
+            // Likely occupied, but below the threshold
+            2 => 50,
+            _ => OccupancyGrid::FREE,
+        })
+        .collect();
+    OccupancyGrid {
+        info: MapMetaData {
+            resolution: RESOLUTION as f32,
+            width: WIDTH,
+            height: HEIGHT,
+            origin: Pose {
+                position: Point {
+                    x: ORIGIN[0],
+                    y: ORIGIN[1],
+                    z: 0.0,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        data,
+        ..Default::default()
+    }
+}
+
+fn world(x: f64, y: f64) -> Point {
+    Point {
+        x: ORIGIN[0] + x * RESOLUTION,
+        y: ORIGIN[1] + y * RESOLUTION,
+        z: 0.0,
+    }
+}
+
+/// The fraction of the way from `a` to `b`, in cells, at which the segment
+/// enters cell (`x`, `y`), if it touches it
+fn entry(a: [f64; 2], b: [f64; 2], x: u32, y: u32) -> Option<f64> {
+    let (mut enter, mut exit) = (0.0f64, 1.0f64);
+    for axis in 0..2 {
+        let low = f64::from([x, y][axis]);
+        let delta = b[axis] - a[axis];
+        if delta == 0.0 {
+            if a[axis] < low || a[axis] > low + 1.0 {
+                return None;
+            }
+            continue;
+        }
+        let (t0, t1) = ((low - a[axis]) / delta, (low + 1.0 - a[axis]) / delta);
+        enter = enter.max(t0.min(t1));
+        exit = exit.min(t0.max(t1));
+    }
+    (enter <= exit).then_some(enter)
+}
+
+fn expected_hit(grid: &OccupancyGrid, a: [f64; 2], b: [f64; 2]) -> Option<f64> {
+    (0..HEIGHT)
+        .flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+        .filter(|&(x, y)| grid.is_occupied(x, y))
+        .filter_map(|(x, y)| entry(a, b, x, y))
+        .min_by(f64::total_cmp)
+}
+
+#[test]
+fn test_raycast_hits_first_occupied_cell() {
+    let mut rng = Rng(0x5eed);
+    for _ in 0..5 {
+        let grid = grid(&mut rng);
+        for _ in 0..400 {
+            // Ends up to 10 cells beyond every side of the map
+            let mut end = || {
+                [
+                    rng.range(-10.0, f64::from(WIDTH) + 10.0),
+                    rng.range(-10.0, f64::from(HEIGHT) + 10.0),
+                ]
+            };
+            let (a, b) = (end(), end());
+            let hit = grid.raycast(world(a[0], a[1]), world(b[0], b[1]));
+            match (hit, expected_hit(&grid, a, b)) {
+                (None, None) => {}
+                (Some((x, y)), Some(expected)) => {
+                    assert!(grid.is_occupied(x, y));
+                    let t = entry(a, b, x, y).expect("the hit cell is on the ray");
+                    assert!(
+                        (t - expected).abs() < 1e-9,
+                        "ray {a:?} -> {b:?} hit ({x}, {y}) at {t}, not at {expected}"
+                    );
+                }
+                (hit, expected) => panic!("ray {a:?} -> {b:?} hit {hit:?}, expected {expected:?}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_raycast_along_grid_lines() {
+    let mut rng = Rng(0xface);
+    let grid = grid(&mut rng);
+    // Horizontal and vertical rays through cell centers, leaving the map
+    for y in 0..HEIGHT {
+        let (a, b) = (
+            [-5.0, f64::from(y) + 0.5],
+            [f64::from(WIDTH) + 5.0, f64::from(y) + 0.5],
+        );
+        let expected = (0..WIDTH).find(|x| grid.is_occupied(*x, y)).map(|x| (x, y));
+        assert_eq!(grid.raycast(world(a[0], a[1]), world(b[0], b[1])), expected);
+        let reversed = (0..WIDTH)
+            .rev()
+            .find(|x| grid.is_occupied(*x, y))
+            .map(|x| (x, y));
+        assert_eq!(grid.raycast(world(b[0], b[1]), world(a[0], a[1])), reversed);
+    }
+    for x in 0..WIDTH {
+        let (a, b) = ([f64::from(x) + 0.5, -1.0], [f64::from(x) + 0.5, 0.25]);
+        let expected = grid.is_occupied(x, 0).then_some((x, 0));
+        assert_eq!(grid.raycast(world(a[0], a[1]), world(b[0], b[1])), expected);
+    }
+}
+
+#[test]
+fn test_raycast_outside_map() {
+    let grid = OccupancyGrid {
+        data: vec![OccupancyGrid::OCCUPIED; (WIDTH * HEIGHT) as usize],
+        ..grid(&mut Rng(1))
+    };
+    // Rays passing beside the map, or ending before it
+    let rays = [
+        ([-5.0, -1.0], [50.0, -1.0]),
+        ([-5.0, -5.0], [-5.0, 50.0]),
+        ([-5.0, 10.0], [-0.5, 10.0]),
+        ([45.0, -5.0], [38.0, -12.0]),
+    ];
+    for (a, b) in rays {
+        assert_eq!(grid.raycast(world(a[0], a[1]), world(b[0], b[1])), None);
+    }
+    // A ray entering the map hits the first cell inside it
+    assert_eq!(
+        grid.raycast(world(-5.0, 10.5), world(5.0, 10.5)),
+        Some((0, 10))
+    );
+    assert_eq!(
+        grid.raycast(world(20.5, 40.0), world(20.5, 0.0)),
+        Some((20, 29))
+    );
+}
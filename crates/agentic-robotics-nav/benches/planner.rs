@@ -0,0 +1,70 @@
+//! Planning across a 1000x1000 grid
+//!
+//! Walls with alternating gaps force a serpentine path through most of the
+//! map, about the worst case for A*.
+
+use agentic_robotics_msgs::{MapMetaData, OccupancyGrid, Point};
+use agentic_robotics_nav::Planner;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::time::Duration;
+
+const SIZE: u32 = 1000;
+const RESOLUTION: f32 = 0.05;
+
+fn serpentine() -> OccupancyGrid {
+    let mut data = vec![OccupancyGrid::FREE; (SIZE * SIZE) as usize];
+    // A wall every 100 columns, open at the top and bottom in turn
+    for (i, x) in (100..SIZE).step_by(100).enumerate() {
+        let gap = if i % 2 == 0 { 0..20 } else { SIZE - 20..SIZE };
+        for y in (0..SIZE).filter(|y| !gap.contains(y)) {
+            data[(y * SIZE + x) as usize] = OccupancyGrid::OCCUPIED;
+        }
+    }
+    OccupancyGrid {
+        info: MapMetaData {
+            resolution: RESOLUTION,
+            width: SIZE,
+            height: SIZE,
+            ..Default::default()
+        },
+        data,
+        ..Default::default()
+    }
+}
+
+fn point(x: u32, y: u32) -> Point {
+    Point {
+        x: (f64::from(x) + 0.5) * f64::from(RESOLUTION),
+        y: (f64::from(y) + 0.5) * f64::from(RESOLUTION),
+        z: 0.0,
+    }
+}
+
+fn benchmark_planner(c: &mut Criterion) {
+    let grid = serpentine();
+    let (start, goal) = (point(10, SIZE / 2), point(SIZE - 10, SIZE / 2));
+    let mut group = c.benchmark_group("planner_1000x1000");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10));
+    let planners = [
+        ("diagonal", Planner::new().inflation_radius(0.2)),
+        (
+            "four_connected",
+            Planner::new().inflation_radius(0.2).diagonal(false),
+        ),
+        (
+            "smoothed",
+            Planner::new().inflation_radius(0.2).smooth(true),
+        ),
+    ];
+    for (name, planner) in planners {
+        group.bench_function(name, |b| {
+            b.iter(|| planner.plan(black_box(&grid), start, goal).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_planner);
+criterion_main!(benches);
@@ -0,0 +1,183 @@
+//! Which cells of a map a robot may enter
+
+use agentic_robotics_msgs::OccupancyGrid;
+
+/// The cells of an [`OccupancyGrid`] a robot may enter
+///
+/// Cells closer than the robot's radius to an occupied cell are blocked
+/// too, so the planner can treat the robot as a point.
+#[derive(Debug, Clone)]
+pub(crate) struct Costmap {
+    width: u32,
+    height: u32,
+    blocked: Vec<bool>,
+}
+
+impl Costmap {
+    /// Blocks the occupied cells, the cells whose centers are within
+    /// `inflation_radius` meters of theirs and, unless `allow_unknown`, the
+    /// unknown cells
+    pub fn new(grid: &OccupancyGrid, inflation_radius: f64, allow_unknown: bool) -> Self {
+        let (width, height) = (grid.info.width, grid.info.height);
+        let mut blocked = vec![false; width as usize * height as usize];
+        let radius = inflation_radius / f64::from(grid.info.resolution);
+        let reach = if radius.is_finite() {
+            radius.max(0.0).floor() as i64
+        } else {
+            0
+        };
+        let disk: Vec<(i64, i64)> = (-reach..=reach)
+            .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+            .filter(|&(dx, dy)| ((dx * dx + dy * dy) as f64) <= radius * radius)
+            .collect();
+
+        for y in 0..height {
+            for x in 0..width {
+                let value = grid.cell(x, y).unwrap_or(OccupancyGrid::UNKNOWN);
+                if value < 0 {
+                    blocked[y as usize * width as usize + x as usize] |= !allow_unknown;
+                    continue;
+                }
+                if value < OccupancyGrid::OCCUPIED_THRESHOLD {
+                    continue;
+                }
+                for (dx, dy) in &disk {
+                    let (nx, ny) = (i64::from(x) + dx, i64::from(y) + dy);
+                    if (0..i64::from(width)).contains(&nx) && (0..i64::from(height)).contains(&ny) {
+                        blocked[ny as usize * width as usize + nx as usize] = true;
+                    }
+                }
+                // Blocked even without inflation
+                blocked[y as usize * width as usize + x as usize] = true;
+            }
+        }
+        Self {
+            width,
+            height,
+            blocked,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether cell (`x`, `y`) is in the map and not blocked
+    #[inline]
+    pub fn is_free(&self, x: u32, y: u32) -> bool {
+        x < self.width
+            && y < self.height
+            && !self.blocked[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Whether every cell the segment between the centers of `from` and
+    /// `to` touches is free, including both cells at a corner it passes
+    /// through exactly
+    pub fn line_is_free(&self, from: (u32, u32), to: (u32, u32)) -> bool {
+        let (dx, dy) = (
+            i64::from(to.0) - i64::from(from.0),
+            i64::from(to.1) - i64::from(from.1),
+        );
+        let (nx, ny) = (dx.abs(), dy.abs());
+        let (sx, sy) = (dx.signum(), dy.signum());
+        let (mut x, mut y) = (i64::from(from.0), i64::from(from.1));
+        let free = |x: i64, y: i64| self.is_free(x as u32, y as u32);
+        if !free(x, y) {
+            return false;
+        }
+        let (mut ix, mut iy) = (0, 0);
+        while ix < nx || iy < ny {
+            // Compare where the segment crosses the next column and the next
+            // row border, scaled to integers
+            let (cross_x, cross_y) = ((1 + 2 * ix) * ny, (1 + 2 * iy) * nx);
+            if cross_x == cross_y {
+                if !free(x + sx, y) || !free(x, y + sy) {
+                    return false;
+                }
+                x += sx;
+                y += sy;
+                ix += 1;
+                iy += 1;
+            } else if cross_x < cross_y {
+                x += sx;
+                ix += 1;
+            } else {
+                y += sy;
+                iy += 1;
+            }
+            if !free(x, y) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentic_robotics_msgs::MapMetaData;
+
+    fn grid(width: u32, height: u32, occupied: &[(u32, u32)]) -> OccupancyGrid {
+        let mut data = vec![OccupancyGrid::FREE; (width * height) as usize];
+        for (x, y) in occupied {
+            data[(y * width + x) as usize] = OccupancyGrid::OCCUPIED;
+        }
+        OccupancyGrid {
+            info: MapMetaData {
+                resolution: 0.5,
+                width,
+                height,
+                ..Default::default()
+            },
+            data,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_inflation() {
+        let grid = grid(9, 9, &[(4, 4)]);
+        let costmap = Costmap::new(&grid, 1.0, true);
+        let blocked = (0..9)
+            .flat_map(|y| (0..9).map(move |x| (x, y)))
+            .filter(|&(x, y)| !costmap.is_free(x, y))
+            .count();
+        // Two cells around the obstacle, rounded: a disk of 13 cells
+        assert_eq!(blocked, 13);
+        assert!(!costmap.is_free(4, 2));
+        assert!(!costmap.is_free(5, 5));
+        assert!(costmap.is_free(5, 6));
+        assert!(!costmap.is_free(9, 0));
+
+        let costmap = Costmap::new(&grid, 0.0, true);
+        assert!(!costmap.is_free(4, 4));
+        assert!(costmap.is_free(4, 5));
+    }
+
+    #[test]
+    fn test_unknown_cells() {
+        let mut grid = grid(3, 1, &[]);
+        grid.data[1] = OccupancyGrid::UNKNOWN;
+        assert!(Costmap::new(&grid, 0.0, true).is_free(1, 0));
+        assert!(!Costmap::new(&grid, 0.0, false).is_free(1, 0));
+        // Unknown cells are not inflated
+        assert!(Costmap::new(&grid, 1.0, false).is_free(0, 0));
+    }
+
+    #[test]
+    fn test_line_is_free() {
+        let costmap = Costmap::new(&grid(5, 5, &[(2, 1)]), 0.0, true);
+        assert!(costmap.line_is_free((0, 0), (4, 0)));
+        assert!(!costmap.line_is_free((0, 0), (4, 2)));
+        assert!(costmap.line_is_free((4, 4), (0, 2)));
+        // Passing the corner of the obstacle exactly
+        assert!(!costmap.line_is_free((1, 1), (2, 0)));
+        assert!(!costmap.line_is_free((0, 0), (4, 4)));
+        assert!(!costmap.line_is_free((0, 0), (5, 0)));
+    }
+}
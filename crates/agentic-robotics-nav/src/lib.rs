@@ -0,0 +1,36 @@
+//! Navigation for ros3 robots
+//!
+//! Plans paths across an [`OccupancyGrid`](agentic_robotics_msgs::OccupancyGrid)
+//! without an external navigation stack: [`Planner`] runs A* over the grid,
+//! keeping clear of obstacles by an inflation radius, and returns a
+//! `nav_msgs/Path` ready to publish along with its length. A
+//! [`SafetyLimiter`](limiter::SafetyLimiter) guards the velocity commands
+//! that follow it.
+//!
+//! ```
+//! use agentic_robotics_msgs::{MapMetaData, OccupancyGrid, Point};
+//! use agentic_robotics_nav::Planner;
+//!
+//! let map = OccupancyGrid {
+//!     info: MapMetaData { resolution: 0.1, width: 100, height: 100, ..Default::default() },
+//!     data: vec![OccupancyGrid::FREE; 100 * 100],
+//!     ..Default::default()
+//! };
+//! let start = Point { x: 0.5, y: 0.5, z: 0.0 };
+//! let goal = Point { x: 9.5, y: 5.0, z: 0.0 };
+//!
+//! let plan = Planner::new()
+//!     .inflation_radius(0.3)
+//!     .smooth(true)
+//!     .plan(&map, start, goal)
+//!     .unwrap();
+//! assert_eq!(plan.path.poses.len(), 2);
+//! assert!((plan.cost - 9.0f64.hypot(4.5)).abs() < 1e-9);
+//! ```
+
+mod costmap;
+pub mod limiter;
+pub mod planner;
+
+pub use limiter::{Limits, SafetyLimiter};
+pub use planner::{Plan, PlanError, Planner};
@@ -0,0 +1,597 @@
+//! Guarding velocity commands before they reach the robot
+//!
+//! A [`SafetyLimiter`] sits between whoever commands the robot, like an
+//! agent, and the topic its base controller listens on. Every period it
+//! takes the latest command from its input topic, clamps it to the maximum
+//! velocities, and publishes it, changed by no more than the maximum
+//! accelerations allow since the previous period. If no command arrived
+//! within the timeout, or the optional heartbeat topic went quiet, it
+//! publishes zero velocity instead.
+//!
+//! ```no_run
+//! # async fn example() -> agentic_robotics_core::Result<()> {
+//! use agentic_robotics_core::Node;
+//! use agentic_robotics_nav::limiter::{Limits, SafetyLimiter};
+//! use std::time::Duration;
+//!
+//! let node = Node::new("cmd_vel_guard")?;
+//! let limits = Limits {
+//!     linear: 0.5,
+//!     angular: 1.0,
+//!     linear_acceleration: 0.5,
+//!     angular_acceleration: 2.0,
+//! };
+//! let limiter = SafetyLimiter::builder(limits)
+//!     .heartbeat("/agent/heartbeat")
+//!     .timeout(Duration::from_millis(300))
+//!     .start(&node, "/agent/cmd_vel", "/cmd_vel")?;
+//! node.create_diagnostic_updater(Duration::from_secs(1))?
+//!     .add_collector(limiter.diagnostics());
+//! # Ok(())
+//! # }
+//! ```
+
+use agentic_robotics_core::diagnostics::{DiagnosticLevel, DiagnosticStatus};
+use agentic_robotics_core::{Error, Node, RawMessage, Result, Subscriber, Time};
+use agentic_robotics_msgs::{Twist, Vector3};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// How fast a [`SafetyLimiter`] lets the robot move
+///
+/// Velocities bound every component of the linear and of the angular
+/// velocity of a command; accelerations bound how fast each component of
+/// the output changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Meters per second
+    pub linear: f64,
+    /// Radians per second
+    pub angular: f64,
+    /// Meters per second squared
+    pub linear_acceleration: f64,
+    /// Radians per second squared
+    pub angular_acceleration: f64,
+}
+
+/// Why a [`SafetyLimiter`] is holding the robot still
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// No command arrived within the timeout
+    InputStale,
+    /// No heartbeat arrived within the timeout
+    HeartbeatStale,
+}
+
+/// Counts of the commands a [`SafetyLimiter`] had to change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LimiterStats {
+    /// Commands faster than the velocity limits
+    pub clamped: u64,
+    /// Periods in which the output could not reach the command because of
+    /// the acceleration limits
+    pub rate_limited: u64,
+    /// Commands with NaN or infinite velocities, replaced by zero
+    pub rejected: u64,
+    /// Times the robot was stopped because commands stopped arriving
+    pub input_timeouts: u64,
+    /// Times the robot was stopped because the heartbeat stopped
+    pub heartbeat_timeouts: u64,
+}
+
+/// Builder for a [`SafetyLimiter`]
+#[derive(Debug, Clone)]
+pub struct SafetyLimiterBuilder {
+    limits: Limits,
+    heartbeat: Option<String>,
+    timeout: Duration,
+    period: Duration,
+}
+
+impl SafetyLimiterBuilder {
+    /// Also stop unless a message of any type arrives on `topic` within
+    /// the timeout
+    pub fn heartbeat(mut self, topic: impl Into<String>) -> Self {
+        self.heartbeat = Some(topic.into());
+        self
+    }
+
+    /// How old the latest command and heartbeat may get before the robot is
+    /// stopped, 500 ms by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How often to publish, 50 ms by default
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Forward commands from `input` to `output` every period of the node's
+    /// clock
+    ///
+    /// Fails with [`Error::Configuration`] if a limit is negative or not a
+    /// number, or if the period is zero. Must be called from within a Tokio
+    /// runtime.
+    pub fn start(self, node: &Node, input: &str, output: &str) -> Result<SafetyLimiter> {
+        let Limits {
+            linear,
+            angular,
+            linear_acceleration,
+            angular_acceleration,
+        } = self.limits;
+        if ![linear, angular, linear_acceleration, angular_acceleration]
+            .iter()
+            .all(|limit| *limit >= 0.0)
+        {
+            return Err(Error::Configuration(format!(
+                "Velocity limits must be non-negative: {:?}",
+                self.limits
+            )));
+        }
+        if self.period.is_zero() {
+            return Err(Error::Configuration(
+                "Safety limiter period must be non-zero".into(),
+            ));
+        }
+
+        let input = node.create_subscriber::<Twist>(input)?;
+        let heartbeat = match &self.heartbeat {
+            Some(topic) => Some(node.create_subscriber::<RawMessage>(topic)?),
+            None => None,
+        };
+        let publisher = node.create_publisher::<Twist>(output)?;
+        let shared = Arc::new(Shared::default());
+        let clock = node.clock().clone();
+        let mut state = State {
+            builder: self,
+            shared: shared.clone(),
+            target: Twist::default(),
+            output: Twist::default(),
+            last_input: None,
+            last_heartbeat: None,
+            stopped: Some(StopReason::InputStale),
+        };
+        let task = tokio::spawn(async move {
+            let mut last_tick = clock.now();
+            loop {
+                clock.sleep(state.builder.period).await;
+                let now = clock.now();
+                let elapsed = now.saturating_duration_since(last_tick);
+                last_tick = now;
+                let output = state.tick(now, elapsed, &input, heartbeat.as_ref());
+                if let Err(e) = publisher.publish(&output).await {
+                    warn!("Failed to publish limited velocity: {}", e);
+                }
+            }
+        });
+        Ok(SafetyLimiter { shared, task })
+    }
+}
+
+/// Clamps, rate-limits and times out velocity commands, see the
+/// [module documentation](self)
+///
+/// Stops forwarding when dropped.
+pub struct SafetyLimiter {
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl SafetyLimiter {
+    pub fn builder(limits: Limits) -> SafetyLimiterBuilder {
+        SafetyLimiterBuilder {
+            limits,
+            heartbeat: None,
+            timeout: Duration::from_millis(500),
+            period: Duration::from_millis(50),
+        }
+    }
+
+    /// Why the robot is held still, `None` while commands are forwarded
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        match self.shared.stopped.load(Ordering::Relaxed) {
+            STOPPED_INPUT => Some(StopReason::InputStale),
+            STOPPED_HEARTBEAT => Some(StopReason::HeartbeatStale),
+            _ => None,
+        }
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        self.shared.stats()
+    }
+
+    /// Collector for a
+    /// [`DiagnosticUpdater`](agentic_robotics_core::DiagnosticUpdater)
+    ///
+    /// Returns a single `safety_limiter` status with the counts of
+    /// [`stats`](Self::stats). It is `Warn` while the robot is stopped or if
+    /// commands were changed since the previous call.
+    pub fn diagnostics(&self) -> impl FnMut() -> Vec<DiagnosticStatus> + Send + 'static {
+        let shared = self.shared.clone();
+        let mut previous = LimiterStats::default();
+        move || {
+            let stats = shared.stats();
+            let changed = (stats.clamped - previous.clamped)
+                + (stats.rate_limited - previous.rate_limited)
+                + (stats.rejected - previous.rejected);
+            previous = stats;
+            let (level, message) = match shared.stopped.load(Ordering::Relaxed) {
+                STOPPED_INPUT => (DiagnosticLevel::Warn, "Stopped: no recent command".into()),
+                STOPPED_HEARTBEAT => (DiagnosticLevel::Warn, "Stopped: no recent heartbeat".into()),
+                _ if changed > 0 => (
+                    DiagnosticLevel::Warn,
+                    format!("{} commands limited since the last update", changed),
+                ),
+                _ => (DiagnosticLevel::Ok, "Forwarding commands".to_string()),
+            };
+            vec![DiagnosticStatus::new(level, "safety_limiter", message)
+                .with_value("clamped", stats.clamped)
+                .with_value("rate_limited", stats.rate_limited)
+                .with_value("rejected", stats.rejected)
+                .with_value("input_timeouts", stats.input_timeouts)
+                .with_value("heartbeat_timeouts", stats.heartbeat_timeouts)]
+        }
+    }
+}
+
+impl Drop for SafetyLimiter {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+const RUNNING: u8 = 0;
+const STOPPED_INPUT: u8 = 1;
+const STOPPED_HEARTBEAT: u8 = 2;
+
+/// What the limiter's task shares with its handle
+#[derive(Default)]
+struct Shared {
+    stopped: AtomicU8,
+    clamped: AtomicU64,
+    rate_limited: AtomicU64,
+    rejected: AtomicU64,
+    input_timeouts: AtomicU64,
+    heartbeat_timeouts: AtomicU64,
+}
+
+impl Shared {
+    fn stats(&self) -> LimiterStats {
+        LimiterStats {
+            clamped: self.clamped.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            input_timeouts: self.input_timeouts.load(Ordering::Relaxed),
+            heartbeat_timeouts: self.heartbeat_timeouts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct State {
+    builder: SafetyLimiterBuilder,
+    shared: Arc<Shared>,
+    /// The latest command, clamped
+    target: Twist,
+    output: Twist,
+    last_input: Option<Time>,
+    last_heartbeat: Option<Time>,
+    stopped: Option<StopReason>,
+}
+
+impl State {
+    /// The velocity to publish at `now`, `elapsed` after the previous one
+    fn tick(
+        &mut self,
+        now: Time,
+        elapsed: Duration,
+        input: &Subscriber<Twist>,
+        heartbeat: Option<&Subscriber<RawMessage>>,
+    ) -> Twist {
+        // Only the latest command counts; it arrived within this period
+        let mut latest = None;
+        while let Ok(Some(command)) = input.try_recv() {
+            latest = Some(command);
+        }
+        if let Some(command) = latest {
+            self.target = self.clamp(command);
+            self.last_input = Some(now);
+        }
+        if let Some(heartbeat) = heartbeat {
+            while let Ok(Some(_)) = heartbeat.try_recv() {
+                self.last_heartbeat = Some(now);
+            }
+        }
+
+        let timeout = self.builder.timeout;
+        let fresh =
+            |at: Option<Time>| at.is_some_and(|at| now.saturating_duration_since(at) <= timeout);
+        let stopped = if !fresh(self.last_input) {
+            Some(StopReason::InputStale)
+        } else if heartbeat.is_some() && !fresh(self.last_heartbeat) {
+            Some(StopReason::HeartbeatStale)
+        } else {
+            None
+        };
+        if stopped.is_some() && self.stopped.is_none() {
+            let counter = match stopped {
+                Some(StopReason::HeartbeatStale) => &self.shared.heartbeat_timeouts,
+                _ => &self.shared.input_timeouts,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+        self.stopped = stopped;
+        self.shared.stopped.store(
+            match stopped {
+                None => RUNNING,
+                Some(StopReason::InputStale) => STOPPED_INPUT,
+                Some(StopReason::HeartbeatStale) => STOPPED_HEARTBEAT,
+            },
+            Ordering::Relaxed,
+        );
+
+        if stopped.is_some() {
+            // Stop at once, and start from rest once commands are back
+            self.target = Twist::default();
+            self.output = Twist::default();
+            return self.output;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let limits = self.builder.limits;
+        let (linear, linear_limited) = ramp(
+            self.output.linear,
+            self.target.linear,
+            limits.linear_acceleration * seconds,
+        );
+        let (angular, angular_limited) = ramp(
+            self.output.angular,
+            self.target.angular,
+            limits.angular_acceleration * seconds,
+        );
+        if linear_limited || angular_limited {
+            self.shared.rate_limited.fetch_add(1, Ordering::Relaxed);
+        }
+        self.output = Twist { linear, angular };
+        self.output
+    }
+
+    /// `command` within the velocity limits, or zero if it is not a number
+    fn clamp(&self, command: Twist) -> Twist {
+        let Twist {
+            linear: l,
+            angular: a,
+        } = command;
+        if ![l.x, l.y, l.z, a.x, a.y, a.z].iter().all(|v| v.is_finite()) {
+            self.shared.rejected.fetch_add(1, Ordering::Relaxed);
+            return Twist::default();
+        }
+        let limits = self.builder.limits;
+        let (linear, linear_clamped) = clamp(command.linear, limits.linear);
+        let (angular, angular_clamped) = clamp(command.angular, limits.angular);
+        if linear_clamped || angular_clamped {
+            self.shared.clamped.fetch_add(1, Ordering::Relaxed);
+        }
+        Twist { linear, angular }
+    }
+}
+
+/// `v` with every component within `-max..=max`, and whether it changed
+fn clamp(v: Vector3, max: f64) -> (Vector3, bool) {
+    let clamped = Vector3 {
+        x: v.x.clamp(-max, max),
+        y: v.y.clamp(-max, max),
+        z: v.z.clamp(-max, max),
+    };
+    (clamped, clamped != v)
+}
+
+/// `from` moved towards `to` by at most `step` per component, and whether
+/// it fell short
+fn ramp(from: Vector3, to: Vector3, step: f64) -> (Vector3, bool) {
+    let toward = |from: f64, to: f64| {
+        if (to - from).abs() <= step {
+            to
+        } else {
+            from + step.copysign(to - from)
+        }
+    };
+    let ramped = Vector3 {
+        x: toward(from.x, to.x),
+        y: toward(from.y, to.y),
+        z: toward(from.z, to.z),
+    };
+    (ramped, ramped != to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentic_robotics_core::{Clock, Publisher, SimClock, Subscriber};
+    use agentic_robotics_msgs::Header;
+
+    const PERIOD: Duration = Duration::from_millis(100);
+
+    struct Harness {
+        clock: SimClock,
+        command: Publisher<Twist>,
+        output: Subscriber<Twist>,
+        _node: Node,
+    }
+
+    impl Harness {
+        async fn start(name: &str, builder: SafetyLimiterBuilder) -> (Self, SafetyLimiter) {
+            let clock = SimClock::new(Time::ZERO);
+            let node = Node::new(name)
+                .unwrap()
+                .with_clock(Clock::Sim(clock.clone()));
+            let (input, output) = (format!("/test/{name}/in"), format!("/test/{name}/out"));
+            let limiter = builder
+                .period(PERIOD)
+                .start(&node, &input, &output)
+                .unwrap();
+            let harness = Self {
+                clock,
+                command: Publisher::new(input).unwrap(),
+                output: Subscriber::new(output).unwrap(),
+                _node: node,
+            };
+            (harness, limiter)
+        }
+
+        /// Send `command`, if any, then run one period and return the output
+        async fn step(&self, command: Option<f64>) -> Twist {
+            if let Some(x) = command {
+                let twist = Twist {
+                    linear: Vector3 { x, y: 0.0, z: 0.0 },
+                    angular: Vector3 {
+                        z: -x,
+                        ..Default::default()
+                    },
+                };
+                self.command.publish(&twist).await.unwrap();
+            }
+            // Let the limiter's task wait for the period before advancing
+            tokio::task::yield_now().await;
+            self.clock.advance(PERIOD);
+            tokio::time::timeout(Duration::from_secs(5), self.output.recv())
+                .await
+                .expect("the limiter publishes every period")
+                .unwrap()
+        }
+    }
+
+    fn limits() -> Limits {
+        Limits {
+            linear: 1.0,
+            angular: 2.0,
+            linear_acceleration: 0.5,
+            angular_acceleration: 4.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_step_command_ramps_up() {
+        let (harness, limiter) =
+            Harness::start("limiter_ramp", SafetyLimiter::builder(limits())).await;
+        assert_eq!(harness.step(None).await, Twist::default());
+        assert_eq!(limiter.stop_reason(), Some(StopReason::InputStale));
+
+        // Twice as fast as allowed: clamped to 1 m/s, reached at 0.05 m/s per
+        // period; the angular velocity reaches -2 rad/s after 5 periods
+        let mut previous = Twist::default();
+        for period in 1..=25 {
+            let output = harness.step(Some(2.0)).await;
+            let change = output.linear.x - previous.linear.x;
+            assert!(
+                (0.0..=0.05 + 1e-9).contains(&change),
+                "period {period}: {output:?}"
+            );
+            assert!(output.linear.x <= 1.0);
+            assert!(output.angular.z >= -2.0);
+            if period >= 20 {
+                assert!((output.linear.x - 1.0).abs() < 1e-9, "period {period}");
+            }
+            if period >= 5 {
+                assert!((output.angular.z + 2.0).abs() < 1e-9, "period {period}");
+            }
+            previous = output;
+        }
+        assert_eq!(limiter.stop_reason(), None);
+        let stats = limiter.stats();
+        assert_eq!(stats.clamped, 25);
+        // Rounding may take the linear velocity one more period to settle
+        assert!((19..=20).contains(&stats.rate_limited), "{stats:?}");
+        assert_eq!(stats.input_timeouts, 0);
+
+        // Slowing down is ramped too
+        let output = harness.step(Some(0.0)).await;
+        assert!((output.linear.x - 0.95).abs() < 1e-9);
+
+        // Not a number: stop as fast as allowed
+        harness.step(Some(f64::NAN)).await;
+        assert_eq!(limiter.stats().rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stale_input_stops() {
+        let builder = SafetyLimiter::builder(limits()).timeout(Duration::from_millis(300));
+        let (harness, limiter) = Harness::start("limiter_stale", builder).await;
+        for _ in 0..10 {
+            harness.step(Some(0.5)).await;
+        }
+        assert!((harness.step(Some(0.5)).await.linear.x - 0.5).abs() < 1e-9);
+
+        // Up to the timeout, the last command still holds
+        for _ in 0..3 {
+            assert!((harness.step(None).await.linear.x - 0.5).abs() < 1e-9);
+        }
+        assert_eq!(harness.step(None).await, Twist::default());
+        assert_eq!(limiter.stop_reason(), Some(StopReason::InputStale));
+        assert_eq!(harness.step(None).await, Twist::default());
+        assert_eq!(limiter.stats().input_timeouts, 1);
+
+        // Resuming starts from rest
+        let output = harness.step(Some(0.5)).await;
+        assert!((output.linear.x - 0.05).abs() < 1e-9);
+        assert_eq!(limiter.stop_reason(), None);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_required() {
+        let topic = "/test/limiter_heartbeat/alive";
+        let builder = SafetyLimiter::builder(limits())
+            .heartbeat(topic)
+            .timeout(Duration::from_millis(200));
+        let (harness, limiter) = Harness::start("limiter_heartbeat", builder).await;
+        let mut collect = limiter.diagnostics();
+
+        assert_eq!(harness.step(Some(0.5)).await, Twist::default());
+        assert_eq!(limiter.stop_reason(), Some(StopReason::HeartbeatStale));
+        let status = collect().remove(0);
+        assert_eq!(status.level, DiagnosticLevel::Warn);
+        assert_eq!(status.message, "Stopped: no recent heartbeat");
+
+        // Any message type will do
+        let heartbeat = Publisher::<Header>::new(topic).unwrap();
+        heartbeat.publish(&Header::default()).await.unwrap();
+        assert!(harness.step(Some(0.5)).await.linear.x > 0.0);
+        for _ in 0..2 {
+            assert!(harness.step(Some(0.5)).await.linear.x > 0.0);
+        }
+        assert_eq!(harness.step(Some(0.5)).await, Twist::default());
+        assert_eq!(limiter.stats().heartbeat_timeouts, 1);
+
+        heartbeat.publish(&Header::default()).await.unwrap();
+        harness.step(Some(0.5)).await;
+        collect();
+        heartbeat.publish(&Header::default()).await.unwrap();
+        harness.step(Some(0.1)).await;
+        let status = collect().remove(0);
+        assert_eq!(status.level, DiagnosticLevel::Ok, "{status:?}");
+        assert!(status
+            .values
+            .iter()
+            .any(|v| v.key == "heartbeat_timeouts" && v.value == "1"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_limits() {
+        let node = Node::new("limiter_invalid").unwrap();
+        let limits = Limits {
+            linear: f64::NAN,
+            ..limits()
+        };
+        assert!(SafetyLimiter::builder(limits)
+            .start(&node, "/test/limiter_invalid/in", "/cmd_vel")
+            .is_err());
+        assert!(SafetyLimiter::builder(self::limits())
+            .period(Duration::ZERO)
+            .start(&node, "/test/limiter_invalid/in", "/cmd_vel")
+            .is_err());
+    }
+}
@@ -0,0 +1,482 @@
+//! A* search over an occupancy grid
+
+use crate::costmap::Costmap;
+use agentic_robotics_msgs::{OccupancyGrid, Path, Point, Pose, PoseStamped, Quaternion};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::SQRT_2;
+
+/// Errors from [`Planner::plan`]
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PlanError {
+    #[error("Start is outside the map")]
+    StartOutsideMap,
+
+    #[error("Goal is outside the map")]
+    GoalOutsideMap,
+
+    #[error("Start cell ({0}, {1}) is blocked")]
+    StartBlocked(u32, u32),
+
+    #[error("Goal cell ({0}, {1}) is blocked")]
+    GoalBlocked(u32, u32),
+
+    #[error("No path from the start to the goal")]
+    NoPath,
+}
+
+/// A path found by a [`Planner`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    /// From the start to the goal, in the frame of the map
+    pub path: Path,
+    /// Length of the path in meters
+    pub cost: f64,
+    /// The cells the poses of the path are in, from the start to the goal
+    pub cells: Vec<(u32, u32)>,
+}
+
+/// Plans shortest paths between two points of an [`OccupancyGrid`]
+///
+/// Occupied cells, and cells closer than the inflation radius to them, are
+/// never entered. By default the planner moves diagonally, never cutting
+/// the corner of a blocked cell, enters unknown cells, and does not smooth
+/// its paths.
+#[derive(Debug, Clone)]
+pub struct Planner {
+    inflation_radius: f64,
+    diagonal: bool,
+    allow_unknown: bool,
+    smooth: bool,
+}
+
+impl Default for Planner {
+    fn default() -> Self {
+        Self {
+            inflation_radius: 0.0,
+            diagonal: true,
+            allow_unknown: true,
+            smooth: false,
+        }
+    }
+}
+
+impl Planner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep paths this many meters away from occupied cells, usually the
+    /// radius of the robot
+    pub fn inflation_radius(mut self, meters: f64) -> Self {
+        self.inflation_radius = meters;
+        self
+    }
+
+    /// Whether to move diagonally, or only along the rows and columns
+    pub fn diagonal(mut self, diagonal: bool) -> Self {
+        self.diagonal = diagonal;
+        self
+    }
+
+    /// Whether to plan through cells of unknown occupancy
+    pub fn allow_unknown(mut self, allow_unknown: bool) -> Self {
+        self.allow_unknown = allow_unknown;
+        self
+    }
+
+    /// Whether to shorten paths by skipping the cells between two poses
+    /// with a clear line of sight, leaving only the turns
+    pub fn smooth(mut self, smooth: bool) -> Self {
+        self.smooth = smooth;
+        self
+    }
+
+    /// The shortest path from `start` to `goal`, given in the map frame
+    pub fn plan(&self, grid: &OccupancyGrid, start: Point, goal: Point) -> Result<Plan, PlanError> {
+        let start_cell = grid
+            .world_to_grid(start)
+            .ok_or(PlanError::StartOutsideMap)?;
+        let goal_cell = grid.world_to_grid(goal).ok_or(PlanError::GoalOutsideMap)?;
+        let costmap = Costmap::new(grid, self.inflation_radius, self.allow_unknown);
+        if !costmap.is_free(start_cell.0, start_cell.1) {
+            return Err(PlanError::StartBlocked(start_cell.0, start_cell.1));
+        }
+        if !costmap.is_free(goal_cell.0, goal_cell.1) {
+            return Err(PlanError::GoalBlocked(goal_cell.0, goal_cell.1));
+        }
+
+        let mut cells = self
+            .search(&costmap, start_cell, goal_cell)
+            .ok_or(PlanError::NoPath)?;
+        if self.smooth {
+            cells = shortcut(&costmap, &cells);
+        }
+
+        // The ends are where the robot is and wants to be, not cell centers
+        let mut positions: Vec<Point> = cells
+            .iter()
+            .map(|&(x, y)| grid.grid_to_world(x, y))
+            .collect();
+        if cells.len() == 1 {
+            positions.push(goal);
+            cells.push(goal_cell);
+        }
+        let last = positions.len() - 1;
+        positions[0] = start;
+        positions[last] = goal;
+        let cost = positions
+            .windows(2)
+            .map(|pair| (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y))
+            .sum();
+
+        let mut yaw = 0.0;
+        let poses = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| {
+                // Facing the next pose; the goal keeps the last heading
+                if let Some(next) = positions.get(i + 1) {
+                    yaw = (next.y - position.y).atan2(next.x - position.x);
+                }
+                PoseStamped {
+                    header: grid.header.clone(),
+                    pose: Pose {
+                        position,
+                        orientation: Quaternion::from_yaw(yaw),
+                    },
+                }
+            })
+            .collect();
+        Ok(Plan {
+            path: Path {
+                header: grid.header.clone(),
+                poses,
+            },
+            cost,
+            cells,
+        })
+    }
+
+    /// The cells of a shortest path between two free cells, if any
+    fn search(
+        &self,
+        costmap: &Costmap,
+        start: (u32, u32),
+        goal: (u32, u32),
+    ) -> Option<Vec<(u32, u32)>> {
+        let width = costmap.width() as usize;
+        let index = |(x, y): (u32, u32)| y as usize * width + x as usize;
+        let cell = |index: usize| ((index % width) as u32, (index / width) as u32);
+        let heuristic = |(x, y): (u32, u32)| {
+            let (dx, dy) = (f64::from(x.abs_diff(goal.0)), f64::from(y.abs_diff(goal.1)));
+            if self.diagonal {
+                dx.max(dy) + (SQRT_2 - 1.0) * dx.min(dy)
+            } else {
+                dx + dy
+            }
+        };
+        let moves: &[(i32, i32, f64)] = if self.diagonal {
+            &[
+                (1, 0, 1.0),
+                (-1, 0, 1.0),
+                (0, 1, 1.0),
+                (0, -1, 1.0),
+                (1, 1, SQRT_2),
+                (1, -1, SQRT_2),
+                (-1, 1, SQRT_2),
+                (-1, -1, SQRT_2),
+            ]
+        } else {
+            &[(1, 0, 1.0), (-1, 0, 1.0), (0, 1, 1.0), (0, -1, 1.0)]
+        };
+
+        let size = width * costmap.height() as usize;
+        let mut costs = vec![f64::INFINITY; size];
+        let mut parents = vec![usize::MAX; size];
+        let mut open = BinaryHeap::new();
+        costs[index(start)] = 0.0;
+        open.push(Open {
+            estimate: heuristic(start),
+            cost: 0.0,
+            index: index(start),
+        });
+
+        while let Some(Open {
+            cost, index: at, ..
+        }) = open.pop()
+        {
+            if cost > costs[at] {
+                // Reached more cheaply since it was queued
+                continue;
+            }
+            let (x, y) = cell(at);
+            if (x, y) == goal {
+                let mut path = vec![goal];
+                let mut at = at;
+                while parents[at] != usize::MAX {
+                    at = parents[at];
+                    path.push(cell(at));
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &(dx, dy, step) in moves {
+                let (Some(nx), Some(ny)) = (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                if !costmap.is_free(nx, ny) {
+                    continue;
+                }
+                // Never squeeze between two blocked cells touching at a corner
+                if dx != 0 && dy != 0 && !(costmap.is_free(nx, y) && costmap.is_free(x, ny)) {
+                    continue;
+                }
+                let next = index((nx, ny));
+                let cost = cost + step;
+                if cost < costs[next] {
+                    costs[next] = cost;
+                    parents[next] = at;
+                    open.push(Open {
+                        estimate: cost + heuristic((nx, ny)),
+                        cost,
+                        index: next,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Drops the cells between two cells of `cells` that see each other
+fn shortcut(costmap: &Costmap, cells: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut kept = cells[..1].to_vec();
+    for pair in cells.windows(2) {
+        let from = kept[kept.len() - 1];
+        if !costmap.line_is_free(from, pair[1]) {
+            kept.push(pair[0]);
+        }
+    }
+    kept.extend(cells.last());
+    kept.dedup();
+    kept
+}
+
+/// A cell in the open set of the search, ordered so the max-heap pops the
+/// lowest estimate first, and on ties the one furthest from the start
+struct Open {
+    estimate: f64,
+    cost: f64,
+    index: usize,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then(self.cost.total_cmp(&other.cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentic_robotics_msgs::{Header, MapMetaData};
+
+    /// A map of one meter cells from rows of `.` free, `#` occupied and
+    /// `?` unknown cells, the first row at `y = 0`
+    fn grid(rows: &[&str]) -> OccupancyGrid {
+        let data = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|cell| match cell {
+                '#' => OccupancyGrid::OCCUPIED,
+                '?' => OccupancyGrid::UNKNOWN,
+                _ => OccupancyGrid::FREE,
+            })
+            .collect();
+        OccupancyGrid {
+            header: Header::new(agentic_robotics_msgs::Time::default(), "map"),
+            info: MapMetaData {
+                resolution: 1.0,
+                width: rows[0].len() as u32,
+                height: rows.len() as u32,
+                ..Default::default()
+            },
+            data,
+        }
+    }
+
+    /// The center of cell (`x`, `y`)
+    fn at(x: u32, y: u32) -> Point {
+        Point {
+            x: f64::from(x) + 0.5,
+            y: f64::from(y) + 0.5,
+            z: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_open_grid() {
+        let grid = grid(&["....."; 5]);
+        let plan = Planner::new().plan(&grid, at(0, 0), at(4, 4)).unwrap();
+        assert_eq!(plan.cells, [(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)]);
+        assert!((plan.cost - 4.0 * SQRT_2).abs() < 1e-9);
+        assert_eq!(plan.path.header.frame_id, "map");
+        assert_eq!(plan.path.poses.len(), 5);
+        assert_eq!(plan.path.poses[4].pose.position, at(4, 4));
+        let yaw = plan.path.poses[4].pose.orientation.yaw();
+        assert!((yaw - std::f64::consts::FRAC_PI_4).abs() < 1e-9);
+
+        let plan = Planner::new()
+            .diagonal(false)
+            .plan(&grid, at(0, 0), at(4, 4))
+            .unwrap();
+        assert_eq!(plan.cells.len(), 9);
+        assert!((plan.cost - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_goes_around_walls_without_cutting_corners() {
+        let grid = grid(&[
+            "...#.", //
+            ".#.#.", ".#...",
+        ]);
+        let plan = Planner::new().plan(&grid, at(0, 2), at(4, 0)).unwrap();
+        assert_eq!(
+            plan.cells,
+            [
+                (0, 2),
+                (0, 1),
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (2, 1),
+                (2, 2),
+                (3, 2),
+                (4, 2),
+                (4, 1),
+                (4, 0)
+            ]
+        );
+        for pair in plan.cells.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            if x0 != x1 && y0 != y1 {
+                assert_eq!(grid.cell(x1, y0), Some(OccupancyGrid::FREE));
+                assert_eq!(grid.cell(x0, y1), Some(OccupancyGrid::FREE));
+            }
+        }
+    }
+
+    #[test]
+    fn test_start_and_goal() {
+        let grid = grid(&[
+            "..#..", //
+            "..#.#", "..#.#",
+        ]);
+        let planner = Planner::new();
+        assert_eq!(
+            planner.plan(&grid, at(2, 1), at(0, 0)),
+            Err(PlanError::StartBlocked(2, 1))
+        );
+        assert_eq!(
+            planner.plan(&grid, at(0, 0), at(4, 2)),
+            Err(PlanError::GoalBlocked(4, 2))
+        );
+        assert_eq!(
+            planner.plan(&grid, at(0, 0), at(3, 2)),
+            Err(PlanError::NoPath)
+        );
+        assert_eq!(
+            planner.plan(&grid, at(0, 3), at(0, 0)),
+            Err(PlanError::StartOutsideMap)
+        );
+        assert_eq!(
+            planner.plan(&grid, at(0, 0), at(5, 0)),
+            Err(PlanError::GoalOutsideMap)
+        );
+
+        // Start and goal in one cell
+        let start = Point { x: 0.2, ..at(0, 0) };
+        let plan = planner.plan(&grid, start, at(0, 0)).unwrap();
+        assert_eq!(plan.path.poses.len(), 2);
+        assert!((plan.cost - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_inflation_closes_narrow_gaps() {
+        let grid = grid(&[
+            ".......", //
+            ".......", "###.###", ".......", ".......",
+        ]);
+        let plan = Planner::new().plan(&grid, at(0, 0), at(6, 4)).unwrap();
+        assert!(plan.cells.contains(&(3, 2)));
+
+        let planner = Planner::new().inflation_radius(1.0);
+        assert_eq!(
+            planner.plan(&grid, at(0, 0), at(6, 4)),
+            Err(PlanError::NoPath)
+        );
+        assert_eq!(
+            planner.plan(&grid, at(0, 1), at(6, 4)),
+            Err(PlanError::StartBlocked(0, 1))
+        );
+    }
+
+    #[test]
+    fn test_unknown_cells() {
+        let grid = grid(&[
+            ".?.", //
+            ".?.", "...",
+        ]);
+        let plan = Planner::new().plan(&grid, at(0, 0), at(2, 0)).unwrap();
+        assert_eq!(plan.cells.len(), 3);
+        let plan = Planner::new()
+            .allow_unknown(false)
+            .plan(&grid, at(0, 0), at(2, 0))
+            .unwrap();
+        assert_eq!(plan.cells.len(), 7);
+    }
+
+    #[test]
+    fn test_smoothing() {
+        let grid = grid(&[
+            "..........", //
+            "..........",
+            "....##....",
+            "....##....",
+            "..........",
+        ]);
+        let (start, goal) = (at(0, 3), at(9, 2));
+        let plan = Planner::new().plan(&grid, start, goal).unwrap();
+        let smoothed = Planner::new()
+            .smooth(true)
+            .plan(&grid, start, goal)
+            .unwrap();
+        assert!(smoothed.cells.len() < plan.cells.len());
+        assert!(smoothed.cost <= plan.cost);
+        assert_eq!(smoothed.cells.first(), Some(&(0, 3)));
+        assert_eq!(smoothed.cells.last(), Some(&(9, 2)));
+        let costmap = Costmap::new(&grid, 0.0, true);
+        for pair in smoothed.cells.windows(2) {
+            assert!(costmap.line_is_free(pair[0], pair[1]), "{pair:?}");
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Closing nodes and exiting processes with live subscriptions, through the
+// addon built with `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+const { spawn } = require('node:child_process')
+const path = require('node:path')
+
+const { AgenticNode } = require('../index.js')
+
+const addon = JSON.stringify(path.join(__dirname, '..', 'index.js'))
+
+// Run `script` in a new process with `AgenticNode` in scope, resolving to
+// its exit code and signal once it exits
+function run(script, timeoutMs = 5000) {
+  return new Promise((resolve, reject) => {
+    const child = spawn(process.execPath, ['-e', `const { AgenticNode } = require(${addon});\n${script}`], {
+      stdio: ['ignore', 'inherit', 'inherit'],
+    })
+    const timer = setTimeout(() => {
+      child.kill('SIGKILL')
+      reject(new Error(`still running after ${timeoutMs} ms`))
+    }, timeoutMs)
+    child.on('exit', (code, signal) => {
+      clearTimeout(timer)
+      resolve({ code, signal })
+    })
+  })
+}
+
+// Everything that keeps a process alive until closed
+const busy = `
+  const node = new AgenticNode('busy')
+  node.subscribe('/close/chatter', () => {})
+  const stream = node.messages('/close/chatter')
+  stream.next()
+  node.createService('/close/echo', (request) => request)
+  const mcp = await node.startMcpServer({ transport: 'http' })
+  await mcp.registerTool('noop', { type: 'object' }, () => 'ok')
+  await node.publish('/close/chatter', { n: 1 })
+`
+
+test('the process exits promptly after close', async () => {
+  const start = Date.now()
+  const exit = await run(`(async () => {${busy}
+    await node.close()
+  })()`)
+  assert.deepStrictEqual(exit, { code: 0, signal: null })
+  assert.ok(Date.now() - start < 3000, `took ${Date.now() - start} ms`)
+})
+
+test('exiting without close does not abort', async () => {
+  const exit = await run(`(async () => {${busy}
+    process.exit(3)
+  })()`)
+  assert.deepStrictEqual(exit, { code: 3, signal: null })
+})
+
+test('methods reject after close', async () => {
+  const node = new AgenticNode('closed')
+  const mcp = await node.startMcpServer({ transport: 'http' })
+  await node.close()
+  await node.close()
+
+  assert.strictEqual(mcp.stats().running, false)
+  await assert.rejects(node.publish('/close/chatter', {}), { message: "Node 'closed' is closed" })
+  await assert.rejects(node.callService('/close/echo', {}), /is closed/)
+  await assert.rejects(node.startMcpServer(), /is closed/)
+  assert.throws(() => node.subscribe('/close/chatter', () => {}), /is closed/)
+  assert.throws(() => node.createService('/close/echo', () => {}), /is closed/)
+})
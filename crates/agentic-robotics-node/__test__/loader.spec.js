@@ -0,0 +1,73 @@
+// Finding and verifying the addon binary, with the addon built by
+// `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+const { spawnSync } = require('node:child_process')
+const { copyFileSync, mkdtempSync, writeFileSync } = require('node:fs')
+const { tmpdir } = require('node:os')
+const path = require('node:path')
+
+const { getBuildInfo } = require('../index.js')
+const { CORE_ABI_VERSION, verifyBuildInfo } = require('../build-info.js')
+const { version } = require('../package.json')
+
+// A module standing in for an addon built with core ABI version `abi`
+function fakeAddon(dir, abi) {
+  const file = path.join(dir, `abi-${abi}.js`)
+  writeFileSync(
+    file,
+    `exports.getBuildInfo = () => ({ abiVersion: ${abi}, coreVersion: '9.9.9' })\n` +
+      `exports.AgenticNode = class {}\nexports.MessageStream = class {}\n`,
+  )
+  return file
+}
+
+// Load `index` in a new process with `nativePath` as the explicit addon
+function load(index, nativePath) {
+  return spawnSync(process.execPath, ['-e', `console.log(require(${JSON.stringify(index)}).getBuildInfo().target)`], {
+    env: { ...process.env, AGENTIC_ROBOTICS_NATIVE_PATH: nativePath },
+    encoding: 'utf8',
+  })
+}
+
+test('the addon reports how it was built', () => {
+  const info = getBuildInfo()
+  assert.strictEqual(info.version, version)
+  assert.strictEqual(info.abiVersion, CORE_ABI_VERSION)
+  assert.match(info.coreVersion, /^\d+\.\d+\.\d+/)
+  assert.match(info.target, process.arch === 'arm64' ? /^aarch64-/ : /^x86_64-/)
+  assert.ok(info.features.includes('derive'))
+  assert.strictEqual(typeof info.debug, 'boolean')
+})
+
+test('binaries of another core ABI are refused', () => {
+  const good = { getBuildInfo: () => ({ abiVersion: CORE_ABI_VERSION }) }
+  assert.strictEqual(verifyBuildInfo(good, 'good').abiVersion, CORE_ABI_VERSION)
+  assert.throws(
+    () => verifyBuildInfo({ getBuildInfo: () => ({ abiVersion: 3, coreVersion: '0.9.0' }) }, 'new.node'),
+    { message: `new.node was built with core ABI version 3 (agentic-robotics-core 0.9.0), but this package needs version ${CORE_ABI_VERSION}` },
+  )
+  assert.throws(() => verifyBuildInfo({}, 'old.node'), /old.node predates getBuildInfo/)
+})
+
+test('a refused binary falls back to the next candidate', () => {
+  const dir = mkdtempSync(path.join(tmpdir(), 'agentic-loader-'))
+  const loaded = load(path.join(__dirname, '..', 'index.js'), fakeAddon(dir, CORE_ABI_VERSION + 1))
+  assert.strictEqual(loaded.status, 0, loaded.stderr)
+  assert.strictEqual(loaded.stdout.trim(), getBuildInfo().target)
+})
+
+test('without a usable binary, loading throws what was tried', () => {
+  // A copy of the package without a local build or prebuilt package
+  const dir = mkdtempSync(path.join(tmpdir(), 'agentic-loader-'))
+  for (const file of ['index.js', 'build-info.js']) {
+    copyFileSync(path.join(__dirname, '..', file), path.join(dir, file))
+  }
+  const loaded = load(path.join(dir, 'index.js'), fakeAddon(dir, CORE_ABI_VERSION + 1))
+  assert.notStrictEqual(loaded.status, 0)
+  assert.match(loaded.stderr, /Failed to load the agentic-robotics native addon/)
+  assert.match(loaded.stderr, new RegExp(`abi-${CORE_ABI_VERSION + 1}.js was built with core ABI version`))
+  assert.match(loaded.stderr, /Cannot find module 'agentic-robotics-/)
+  assert.match(loaded.stderr, /Install the prebuilt package agentic-robotics-/)
+})
@@ -0,0 +1,99 @@
+// MCP servers started from JavaScript and called over HTTP, through the
+// addon built with `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+
+const { AgenticNode } = require('../index.js')
+
+// A minimal MCP client speaking JSON over the HTTP transport
+async function connect(port) {
+  const url = `http://127.0.0.1:${port}/mcp`
+  let session = null
+  let nextId = 1
+  const request = async (method, params) => {
+    const headers = { 'Content-Type': 'application/json', Accept: 'application/json' }
+    if (session) {
+      headers['Mcp-Session-Id'] = session
+    }
+    const response = await fetch(url, {
+      method: 'POST',
+      headers,
+      body: JSON.stringify({ jsonrpc: '2.0', id: nextId++, method, params }),
+    })
+    assert.strictEqual(response.status, 200)
+    session = session ?? response.headers.get('Mcp-Session-Id')
+    return (await response.json()).result
+  }
+  await request('initialize', {
+    protocolVersion: '2025-03-26',
+    capabilities: {},
+    clientInfo: { name: 'mcp.spec.js', version: '1.0.0' },
+  })
+  return request
+}
+
+test('a JS tool is called through the HTTP transport', async () => {
+  const node = new AgenticNode('mcp_host')
+  const server = await node.startMcpServer({ transport: 'http', port: 0, tools: ['ros3_list_topics'] })
+  const calls = []
+  server.on('toolCall', (record) => calls.push(record))
+  await server.registerTool(
+    'add',
+    {
+      description: 'Add two numbers',
+      type: 'object',
+      properties: { a: { type: 'number' }, b: { type: 'number' } },
+      required: ['a', 'b'],
+    },
+    async ({ a, b }) => {
+      if (a < 0) {
+        throw new RangeError('a must not be negative')
+      }
+      return { sum: a + b }
+    },
+  )
+
+  const request = await connect(server.getPort())
+  const { tools } = await request('tools/list', {})
+  assert.deepStrictEqual(tools.map((tool) => tool.name).sort(), ['add', 'ros3_list_topics'])
+  assert.strictEqual(tools.find((tool) => tool.name === 'add').description, 'Add two numbers')
+
+  const sum = await request('tools/call', { name: 'add', arguments: { a: 2, b: 3 } })
+  assert.deepStrictEqual(JSON.parse(sum.content[0].text), { sum: 5 })
+  const failed = await request('tools/call', { name: 'add', arguments: { a: -1, b: 3 } })
+  assert.strictEqual(failed.isError, true)
+  assert.match(failed.content[0].text, /a must not be negative/)
+
+  const deadline = Date.now() + 2000
+  while (calls.length < 2 && Date.now() < deadline) {
+    await new Promise((resolve) => setTimeout(resolve, 5))
+  }
+  assert.deepStrictEqual(
+    calls.map(({ tool, failed }) => ({ tool, failed })),
+    [
+      { tool: 'add', failed: false },
+      { tool: 'add', failed: true },
+    ],
+  )
+  assert.deepStrictEqual(server.stats(), {
+    transport: 'http',
+    port: server.getPort(),
+    running: true,
+    sessions: 1,
+    toolCalls: 2,
+    failedCalls: 1,
+  })
+
+  await server.stop()
+  assert.strictEqual(server.stats().running, false)
+  await assert.rejects(request('tools/list', {}))
+  await node.shutdown()
+})
+
+test('invalid options are rejected', async () => {
+  const node = new AgenticNode('mcp_options')
+  await assert.rejects(node.startMcpServer({ transport: 'http', tools: ['ros3_fly'] }), /ros3_list_topics/)
+  await assert.rejects(node.startMcpServer({ transport: 'carrier-pigeon' }), /Unknown MCP transport/)
+  await node.shutdown()
+})
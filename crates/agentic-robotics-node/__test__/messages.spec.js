@@ -0,0 +1,85 @@
+// Async iteration over the messages of a topic with `for await`
+
+const test = require('node:test')
+const assert = require('node:assert')
+
+const { AgenticNode } = require('../index.js')
+
+function sleep(ms) {
+  return new Promise((resolve) => setTimeout(resolve, ms))
+}
+
+test('for await yields messages in order', async () => {
+  const talker = new AgenticNode('iter_talker')
+  const listener = new AgenticNode('iter_listener')
+  const stream = listener.messages('/messages/counter')
+  assert.strictEqual(stream.getTopic(), '/messages/counter')
+
+  for (let n = 0; n < 3; n++) {
+    await talker.publish('/messages/counter', { n })
+  }
+  const received = []
+  for await (const message of stream) {
+    received.push(message.n)
+    if (received.length === 3) {
+      break
+    }
+  }
+  assert.deepStrictEqual(received, [0, 1, 2])
+
+  // Breaking out returned the stream and ended the subscription
+  assert.deepStrictEqual(await stream.next(), { done: true })
+
+  await talker.shutdown()
+  await listener.shutdown()
+})
+
+test('return() ends a pending iteration', async () => {
+  const node = new AgenticNode('iter_return')
+  const stream = node.messages('/messages/quiet')
+  const pending = stream.next()
+  await sleep(20)
+  await stream.return()
+  assert.deepStrictEqual(await pending, { done: true })
+  await node.shutdown()
+})
+
+test('node shutdown ends the iteration', async () => {
+  const talker = new AgenticNode('iter_shutdown_talker')
+  const listener = new AgenticNode('iter_shutdown_listener')
+  const received = []
+  const loop = (async () => {
+    for await (const message of listener.messages('/messages/shutdown')) {
+      received.push(message.n)
+    }
+  })()
+
+  await talker.publish('/messages/shutdown', { n: 1 })
+  await sleep(50)
+  await listener.shutdown()
+  await loop
+  assert.deepStrictEqual(received, [1])
+  await talker.shutdown()
+})
+
+test('an iteration without a message in time rejects', async () => {
+  const node = new AgenticNode('iter_timeout')
+  const stream = node.messages('/messages/silent', { timeoutMs: 20 })
+  await assert.rejects(stream.next(), /No message on \/messages\/silent within 20 ms/)
+  await stream.return()
+  await node.shutdown()
+})
+
+test('the queue keeps the latest messages between iterations', async () => {
+  const talker = new AgenticNode('iter_depth_talker')
+  const listener = new AgenticNode('iter_depth_listener')
+  const stream = listener.messages('/messages/depth', { queueDepth: 2 })
+  for (let n = 0; n < 5; n++) {
+    await talker.publish('/messages/depth', { n })
+  }
+  assert.deepStrictEqual((await stream.next()).value, { n: 3 })
+  assert.deepStrictEqual((await stream.next()).value, { n: 4 })
+  await stream.return()
+  await talker.shutdown()
+  await listener.shutdown()
+})
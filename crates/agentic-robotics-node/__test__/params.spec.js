@@ -0,0 +1,83 @@
+// Parameters declared, changed and observed from JavaScript, through the
+// addon built with `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+
+const { AgenticNode } = require('../index.js')
+
+// Listeners do not keep the process alive, so the timeout does
+function nextChange(node) {
+  return new Promise((resolve, reject) => {
+    const timeout = setTimeout(() => reject(new Error('no parameterChanged event')), 5000)
+    node.on('parameterChanged', (name, value) => {
+      clearTimeout(timeout)
+      resolve({ name, value })
+    })
+  })
+}
+
+test('values keep their exact types', async () => {
+  const node = new AgenticNode('param_types')
+  node.declareParameter('count', 9007199254740993n)
+  node.declareParameter('gain', 1.5)
+  node.declareParameter('enabled', true)
+  node.declareParameter('frame', 'base_link')
+  node.declareParameter('weights', [0.25, 0.5])
+
+  assert.strictEqual(node.getParameter('count'), 9007199254740993n)
+  assert.strictEqual(node.getParameter('gain'), 1.5)
+  assert.strictEqual(node.getParameter('enabled'), true)
+  assert.strictEqual(node.getParameter('frame'), 'base_link')
+  assert.deepStrictEqual(node.getParameter('weights'), [0.25, 0.5])
+  assert.strictEqual(node.getParameter('missing'), null)
+
+  // A safe integer number is taken for an integer parameter
+  node.setParameter('count', 42)
+  assert.strictEqual(node.getParameter('count'), 42n)
+  node.setParameter('count', -(2n ** 63n))
+  assert.strictEqual(node.getParameter('count'), -(2n ** 63n))
+
+  assert.throws(() => node.setParameter('count', 0.5), /expected a integer, got a double/)
+  assert.throws(() => node.setParameter('gain', 2n), /expected a double, got a integer/)
+  assert.throws(() => node.setParameter('count', 2n ** 64n), /fit in 64 bits/)
+  assert.throws(() => node.setParameter('frame', { x: 1 }), /must be a boolean, number/)
+  assert.throws(() => node.declareParameter('gain', 2.0), /already declared/)
+  await node.close()
+})
+
+test('descriptor violations throw with the failed constraint', async () => {
+  const node = new AgenticNode('param_limits')
+  node.declareParameter('speed', 1.0, { description: 'Top speed', range: { min: 0, max: 2 } })
+  node.declareParameter('serial', 'X1', { readOnly: true })
+
+  assert.throws(() => node.setParameter('speed', 2.5), {
+    message: "Invalid parameter 'param_limits.speed': value outside [0, 2]",
+  })
+  assert.throws(() => node.setParameter('serial', 'X2'), /'param_limits.serial': is read-only/)
+  assert.throws(() => node.declareParameter('accel', 5.0, { range: { min: 0, max: 1 } }), /value outside/)
+  assert.throws(() => node.setParameter('undeclared', 1.0), /not declared/)
+  assert.strictEqual(node.getParameter('speed'), 1.0)
+  await node.close()
+  assert.throws(() => node.getParameter('speed'), /is closed/)
+})
+
+test('changes made outside the node emit parameterChanged', async () => {
+  const node = new AgenticNode('param_events')
+  node.declareParameter('kp', 1.0)
+  node.declareParameter('mode', 1n)
+
+  // Set through the process's parameter registry in Rust, as tools do
+  let changed = nextChange(node)
+  AgenticNode.setNodeParameter('param_events', 'kp', 2.5)
+  assert.deepStrictEqual(await changed, { name: 'kp', value: 2.5 })
+
+  changed = nextChange(node)
+  node.setParameter('mode', 3n)
+  assert.deepStrictEqual(await changed, { name: 'mode', value: 3n })
+
+  assert.throws(() => AgenticNode.setNodeParameter('param_events', 'kp', 'x'), /expected a double/)
+  assert.throws(() => AgenticNode.setNodeParameter('no_such_node', 'kp', 1.0), /No live node/)
+  assert.throws(() => node.on('changed', () => {}), /Unknown event/)
+  await node.close()
+})
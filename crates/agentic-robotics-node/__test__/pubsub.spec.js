@@ -0,0 +1,84 @@
+// Publish and subscribe between nodes in one process, through the addon
+// built with `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+
+const { AgenticNode } = require('../index.js')
+
+function sleep(ms) {
+  return new Promise((resolve) => setTimeout(resolve, ms))
+}
+
+// Wait until `condition()` holds, failing after `ms`
+async function waitFor(condition, ms = 2000) {
+  const deadline = Date.now() + ms
+  while (!condition()) {
+    if (Date.now() > deadline) {
+      throw new Error('timed out')
+    }
+    await sleep(5)
+  }
+}
+
+test('a message published by one node reaches the callback of another', async () => {
+  const talker = new AgenticNode('talker')
+  const listener = new AgenticNode('listener')
+  const received = []
+  const handle = listener.subscribe('/pubsub/chatter', (message) => received.push(message))
+
+  await talker.publish('/pubsub/chatter', { text: 'hello', count: 1, tags: ['a', 'b'] })
+  await talker.publish('/pubsub/chatter', { text: 'world', count: 2, tags: [] })
+  await waitFor(() => received.length === 2)
+
+  assert.deepStrictEqual(received, [
+    { text: 'hello', count: 1, tags: ['a', 'b'] },
+    { text: 'world', count: 2, tags: [] },
+  ])
+  assert.deepStrictEqual(await talker.listPublishers(), ['/pubsub/chatter'])
+  assert.strictEqual(listener.unsubscribe(handle), true)
+  assert.strictEqual(listener.unsubscribe(handle), false)
+
+  // Nothing arrives once unsubscribed
+  await talker.publish('/pubsub/chatter', { text: 'ignored', count: 3, tags: [] })
+  await sleep(50)
+  assert.strictEqual(received.length, 2)
+
+  await talker.shutdown()
+  await listener.shutdown()
+})
+
+test('a slow callback drops the oldest messages beyond the queue depth', async () => {
+  const talker = new AgenticNode('fast_talker')
+  const listener = new AgenticNode('slow_listener')
+  const received = []
+  const handle = listener.subscribe(
+    '/pubsub/burst',
+    (message) => {
+      received.push(message.seq)
+      // Block the event loop, as a slow handler would
+      const until = Date.now() + 100
+      while (Date.now() < until) {}
+    },
+    { queueDepth: 4 },
+  )
+
+  // Every publish runs on the Rust runtime while the callback blocks
+  await Promise.all(
+    Array.from({ length: 100 }, (_, seq) => talker.publish('/pubsub/burst', { seq })),
+  )
+  await sleep(800)
+
+  assert.ok(received.length >= 1)
+  assert.ok(received.length <= 6, `received ${received.length} messages`)
+  listener.unsubscribe(handle)
+  await talker.shutdown()
+  await listener.shutdown()
+})
+
+test('invalid topics are rejected', async () => {
+  const node = new AgenticNode('invalid_topics')
+  await assert.rejects(node.publish('bad topic', {}))
+  assert.throws(() => node.subscribe('bad topic', () => {}))
+  await node.shutdown()
+})
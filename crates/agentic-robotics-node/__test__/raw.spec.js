@@ -0,0 +1,64 @@
+// Binary payloads published from and received as Buffers, through the
+// addon built with `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+
+const { AgenticNode } = require('../index.js')
+
+async function until(condition) {
+  const deadline = Date.now() + 2000
+  while (!condition() && Date.now() < deadline) {
+    await new Promise((resolve) => setTimeout(resolve, 5))
+  }
+}
+
+test('Buffers are received without copying unless asked to', async () => {
+  const node = new AgenticNode('raw_camera')
+  const views = []
+  const copies = []
+  node.subscribeRaw('/raw/image', (data) => views.push(data))
+  node.subscribeRaw('/raw/image', (data) => copies.push(data), { copy: true })
+
+  const frame = Buffer.alloc(1 << 20, 1)
+  await node.publishRaw('/raw/image', frame, { type: 'sensor_msgs/Image' })
+  await node.publishRaw('/raw/image', new Uint8Array([4, 5, 6]).buffer, { type: 'sensor_msgs/Image' })
+  await node.publishRaw('/raw/image', Buffer.alloc(0), { type: 'sensor_msgs/Image' })
+  await until(() => views.length === 3 && copies.length === 3)
+
+  assert.deepStrictEqual(
+    views.map((data) => data.length),
+    [1 << 20, 3, 0],
+  )
+  assert.deepStrictEqual(views[1], Buffer.from([4, 5, 6]))
+  assert.deepStrictEqual(copies[0], frame)
+  // Views share the publisher's bytes, copies do not
+  frame[0] = 9
+  assert.strictEqual(views[0][0], 9)
+  assert.strictEqual(copies[0][0], 1)
+
+  await assert.rejects(node.publishRaw('/raw/image', frame, { type: 'x', format: 'xml' }), /Unknown format 'xml'/)
+  await node.close()
+})
+
+test('raw messages interoperate with typed ones', async () => {
+  const node = new AgenticNode('raw_bridge')
+  const raw = []
+  const decoded = []
+  node.subscribeRaw('/raw/state', (data) => raw.push(data))
+  node.subscribe('/raw/json', (message) => decoded.push(message))
+
+  node.registerType('ros3_msgs/RobotState')
+  await node.publish(
+    '/raw/state',
+    { position: [1, 2, 3], velocity: [0, 0, 0], timestamp: 5n },
+    { type: 'ros3_msgs/RobotState' },
+  )
+  await node.publishRaw('/raw/json', Buffer.from('{"ok":true}'), { type: 'std_msgs/Json', format: 'json' })
+  await until(() => raw.length === 1 && decoded.length === 1)
+
+  // CDR of three f64 pairs and an i64 after the 4-byte encapsulation header
+  assert.strictEqual(raw[0].length, 4 + 7 * 8)
+  assert.deepStrictEqual(decoded, [{ ok: true }])
+  await node.close()
+})
@@ -0,0 +1,83 @@
+// Services served and called from JavaScript, through the addon built with
+// `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+
+const { AgenticNode } = require('../index.js')
+
+function sleep(ms) {
+  return new Promise((resolve) => setTimeout(resolve, ms))
+}
+
+test('a JS client calls a JS server', async () => {
+  const server = new AgenticNode('adder')
+  const client = new AgenticNode('adder_client')
+  server.createService('/services/add', async ({ a, b }) => ({ sum: a + b }))
+  // Plain return values work as well as promises
+  server.createService('/services/echo', (request) => request)
+
+  assert.deepStrictEqual(await client.callService('/services/add', { a: 2, b: 3 }), { sum: 5 })
+  assert.deepStrictEqual(await client.callService('/services/echo', ['x', 1]), ['x', 1])
+  assert.throws(() => server.createService('/services/add', () => 0), /already has a server/)
+
+  assert.strictEqual(server.destroyService('/services/add'), true)
+  assert.strictEqual(server.destroyService('/services/add'), false)
+  await assert.rejects(client.callService('/services/add', { a: 1, b: 1 }), /Service unavailable/)
+  await server.shutdown()
+  await assert.rejects(client.callService('/services/echo', {}), /Service unavailable/)
+  await client.shutdown()
+})
+
+test('errors in the handler fail the call, not the addon', async () => {
+  const node = new AgenticNode('failing_server')
+  node.createService('/services/reject', async ({ divisor }) => {
+    if (divisor === 0) {
+      throw new Error('division by zero')
+    }
+    return { quotient: 1 / divisor }
+  })
+  node.createService('/services/throw', () => {
+    throw new TypeError('not implemented')
+  })
+  node.createService('/services/unserializable', () => () => {})
+
+  await assert.rejects(node.callService('/services/reject', { divisor: 0 }), {
+    message: "Service '/services/reject' failed: Error: division by zero",
+  })
+  await assert.rejects(node.callService('/services/throw', {}), {
+    message: "Service '/services/throw' failed: TypeError: not implemented",
+  })
+  await assert.rejects(node.callService('/services/unserializable', {}), /failed/)
+
+  // The server keeps serving
+  assert.deepStrictEqual(await node.callService('/services/reject', { divisor: 4 }), { quotient: 0.25 })
+  await node.shutdown()
+})
+
+test('calls are handled concurrently and time out', async () => {
+  const node = new AgenticNode('slow_server')
+  let active = 0
+  let maxActive = 0
+  node.createService('/services/slow', async ({ ms }) => {
+    active += 1
+    maxActive = Math.max(maxActive, active)
+    await sleep(ms)
+    active -= 1
+    return { slept: ms }
+  })
+
+  const start = Date.now()
+  const responses = await Promise.all(
+    Array.from({ length: 5 }, () => node.callService('/services/slow', { ms: 200 })),
+  )
+  assert.deepStrictEqual(responses, Array(5).fill({ slept: 200 }))
+  assert.strictEqual(maxActive, 5)
+  assert.ok(Date.now() - start < 900, `took ${Date.now() - start} ms`)
+
+  await assert.rejects(
+    node.callService('/services/slow', { ms: 500 }, { timeoutMs: 50 }),
+    /did not respond within/,
+  )
+  await node.shutdown()
+})
@@ -0,0 +1,78 @@
+// Subscription callbacks run by spinning or a step at a time, through the
+// addon built with `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+
+const { AgenticNode } = require('../index.js')
+
+function sleep(ms) {
+  return new Promise((resolve) => setTimeout(resolve, ms))
+}
+
+test('spinOnce hands over queued messages one step at a time', async () => {
+  const talker = new AgenticNode('step_talker')
+  const listener = new AgenticNode('step_listener')
+  await listener.stopSpinning()
+
+  const received = []
+  listener.subscribe('/step/data', (message) => received.push(message))
+  await talker.publish('/step/data', { seq: 1 })
+  await talker.publish('/step/data', { seq: 2 })
+  await sleep(50)
+  assert.deepStrictEqual(received, [])
+  assert.strictEqual(listener.executorStats().pending, 2)
+
+  assert.strictEqual(await listener.spinOnce(), 1)
+  assert.deepStrictEqual(received, [{ seq: 1 }])
+  assert.strictEqual(await listener.spinOnce(), 1)
+  assert.deepStrictEqual(received, [{ seq: 1 }, { seq: 2 }])
+  assert.strictEqual(await listener.spinOnce(), 0)
+
+  assert.deepStrictEqual(listener.executorStats(), {
+    spinning: false,
+    subscriptions: 1,
+    pending: 0,
+    dropped: 0,
+    callbacks: 2,
+    spinOnceCalls: 3,
+  })
+  await talker.close()
+  await listener.close()
+})
+
+test('spinOnce waits up to timeoutMs for a message', async () => {
+  const node = new AgenticNode('step_waiter')
+  await node.stopSpinning()
+  const raw = []
+  node.subscribeRaw('/step/raw', (data) => raw.push(data.toString()))
+
+  setTimeout(() => node.publishRaw('/step/raw', Buffer.from('late'), { type: 'test/Text' }), 50)
+  assert.strictEqual(await node.spinOnce({ timeoutMs: 5000 }), 1)
+  assert.deepStrictEqual(raw, ['late'])
+  assert.strictEqual(await node.spinOnce({ timeoutMs: 20 }), 0)
+  await node.close()
+})
+
+test('spinOnce throws while spinning', async () => {
+  const node = new AgenticNode('step_spinner')
+  const received = []
+  node.subscribe('/step/spin', (message) => received.push(message))
+  assert.strictEqual(node.executorStats().spinning, true)
+  assert.throws(() => node.spinOnce(), /Node 'step_spinner' is spinning/)
+
+  await node.stopSpinning()
+  await node.publish('/step/spin', 1)
+  await sleep(50)
+  assert.deepStrictEqual(received, [])
+
+  // Resuming hands over what was queued meanwhile
+  node.spin()
+  await node.publish('/step/spin', 2)
+  while (received.length < 2) {
+    await sleep(5)
+  }
+  assert.deepStrictEqual(received, [1, 2])
+  assert.throws(() => node.spinOnce(), /is spinning/)
+  await node.close()
+})
@@ -0,0 +1,80 @@
+// Registered message types checked when publishing and converted when
+// receiving, through the addon built with `npm run build:debug`
+
+const test = require('node:test')
+const assert = require('node:assert')
+
+const { AgenticNode } = require('../index.js')
+
+test('messages of a registered type are checked field by field', async () => {
+  const node = new AgenticNode('typed_talker')
+  node.registerType('JsRobotState', { position: 'f64[3]', velocity: 'f64[3]', timestamp: 'i64' })
+  const options = { type: 'JsRobotState' }
+
+  await assert.rejects(
+    node.publish('/types/state', { position: [1, 2, '3'], velocity: [0, 0, 0], timestamp: 1n }, options),
+    { message: 'Invalid JsRobotState message: position[2] must be a number, got string' },
+  )
+  await assert.rejects(
+    node.publish('/types/state', { position: [1, 2], velocity: [0, 0, 0], timestamp: 1n }, options),
+    { message: 'Invalid JsRobotState message: position must have 3 elements, got 2' },
+  )
+  await assert.rejects(
+    node.publish('/types/state', { position: [1, 2, 3], velocity: [0, 0, 0] }, options),
+    { message: 'Invalid JsRobotState message: timestamp is missing' },
+  )
+  await assert.rejects(node.publish('/types/state', {}, { type: 'Unregistered' }), /Unknown message type/)
+
+  // The same fields register again, others do not
+  node.registerType('JsRobotState', { position: 'f64[3]', velocity: 'f64[3]', timestamp: 'i64' })
+  assert.throws(() => node.registerType('JsRobotState', { position: 'f64[2]' }), /already registered/)
+  assert.throws(() => node.registerType('Bad', { x: 'f128' }), /Unknown type 'f128' of Bad.x/)
+  await node.shutdown()
+})
+
+test('i64 fields round-trip as BigInt', async () => {
+  const talker = new AgenticNode('bigint_talker')
+  const listener = new AgenticNode('bigint_listener')
+  talker.registerType('Stamp', { sec: 'i64', nanos: 'u32', frame: 'string' })
+  const stream = listener.messages('/types/stamp', { type: 'Stamp', timeoutMs: 2000 })
+
+  const large = 2n ** 62n + 1n
+  await talker.publish('/types/stamp', { sec: large, nanos: 5, frame: 'map' }, { type: 'Stamp' })
+  await talker.publish('/types/stamp', { sec: -3, nanos: 0, frame: 'odom' }, { type: 'Stamp' })
+
+  assert.deepStrictEqual((await stream.next()).value, { sec: large, nanos: 5, frame: 'map' })
+  assert.deepStrictEqual((await stream.next()).value, { sec: -3n, nanos: 0, frame: 'odom' })
+  await stream.return()
+  await talker.shutdown()
+  await listener.shutdown()
+})
+
+test('types of the Rust registry are published as the Rust message', async () => {
+  const talker = new AgenticNode('rust_type_talker')
+  const listener = new AgenticNode('rust_type_listener')
+  talker.registerType('ros3_msgs/RobotState')
+  assert.throws(() => talker.registerType('ros3_msgs/Unknown'), /ros3_msgs\/RobotState/)
+
+  const received = []
+  const handle = listener.subscribe('/types/robot', (message) => received.push(message), {
+    type: 'ros3_msgs/RobotState',
+  })
+  // Untyped messages on the topic do not reach typed subscribers
+  await talker.publish('/types/robot', { text: 'not a robot state' })
+  await talker.publish(
+    '/types/robot',
+    { position: [1, 2, 3], velocity: [0.5, 0, 0], timestamp: 1700000000000000000n },
+    { type: 'ros3_msgs/RobotState' },
+  )
+  const deadline = Date.now() + 2000
+  while (received.length === 0 && Date.now() < deadline) {
+    await new Promise((resolve) => setTimeout(resolve, 5))
+  }
+
+  assert.deepStrictEqual(received, [
+    { position: [1, 2, 3], velocity: [0.5, 0, 0], timestamp: 1700000000000000000n },
+  ])
+  listener.unsubscribe(handle)
+  await talker.shutdown()
+  await listener.shutdown()
+})
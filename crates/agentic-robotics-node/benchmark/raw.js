@@ -0,0 +1,55 @@
+// Round trips of 1 MB payloads through `publishRaw`/`subscribeRaw` and
+// through `publish`/`subscribe` with the bytes as a base64 string, the
+// cheapest way to send binary data as JSON
+//
+//   npm run build && node benchmark/raw.js
+
+const { AgenticNode } = require('../index.js')
+
+const SIZE = 1 << 20
+const ROUNDS = 200
+
+async function measure(name, subscribe, publish) {
+  const node = new AgenticNode(`bench_${name}`)
+  let received = 0
+  let wake = () => {}
+  subscribe(node, `/bench/${name}`, () => {
+    received += 1
+    wake()
+  })
+  const payload = Buffer.alloc(SIZE, 7)
+
+  const start = process.hrtime.bigint()
+  for (let round = 0; round < ROUNDS; round++) {
+    const arrived = new Promise((resolve) => (wake = resolve))
+    await publish(node, `/bench/${name}`, payload)
+    await arrived
+  }
+  const seconds = Number(process.hrtime.bigint() - start) / 1e9
+  await node.close()
+  if (received !== ROUNDS) {
+    throw new Error(`${name}: received ${received} of ${ROUNDS} messages`)
+  }
+  const rate = ROUNDS / seconds
+  console.log(`${name.padEnd(6)} ${rate.toFixed(1).padStart(8)} msg/s ${((rate * SIZE) / 2 ** 20).toFixed(1).padStart(8)} MB/s`)
+  return rate
+}
+
+async function main() {
+  const json = await measure(
+    'json',
+    (node, topic, callback) => node.subscribe(topic, (message) => callback(Buffer.from(message.data, 'base64'))),
+    (node, topic, payload) => node.publish(topic, { data: payload.toString('base64') }),
+  )
+  const raw = await measure(
+    'raw',
+    (node, topic, callback) => node.subscribeRaw(topic, callback),
+    (node, topic, payload) => node.publishRaw(topic, payload, { type: 'std_msgs/ByteMultiArray' }),
+  )
+  console.log(`publishRaw is ${(raw / json).toFixed(1)}x faster than the JSON path`)
+}
+
+main().catch((e) => {
+  console.error(e)
+  process.exit(1)
+})
@@ -0,0 +1,27 @@
+// Checks that a loaded addon binary is one this package can use
+//
+// Prebuilt binaries come from separately installed packages, so the one
+// found may belong to another release. `getBuildInfo()` tells what it was
+// built from.
+
+// Core ABI version of the addon released with this package, see
+// `agentic_robotics_core::ABI_VERSION`
+const CORE_ABI_VERSION = 2
+
+// Return the build info of `binding`, loaded from `source`, or throw if it
+// is not compatible with this package
+function verifyBuildInfo(binding, source) {
+  if (typeof binding.getBuildInfo !== 'function') {
+    throw new Error(`${source} predates getBuildInfo() and cannot be verified; install a matching release`)
+  }
+  const info = binding.getBuildInfo()
+  if (info.abiVersion !== CORE_ABI_VERSION) {
+    throw new Error(
+      `${source} was built with core ABI version ${info.abiVersion} (agentic-robotics-core ${info.coreVersion}), ` +
+        `but this package needs version ${CORE_ABI_VERSION}`,
+    )
+  }
+  return info
+}
+
+module.exports = { CORE_ABI_VERSION, verifyBuildInfo }
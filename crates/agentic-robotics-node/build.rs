@@ -1,3 +1,9 @@
 fn main() {
     napi_build::setup();
+
+    // Reported by `getBuildInfo()`, so the JS loader can tell what it loaded
+    println!(
+        "cargo:rustc-env=AGENTIC_ROBOTICS_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
 }
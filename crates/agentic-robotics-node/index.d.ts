@@ -10,6 +10,187 @@ export interface PublisherStats {
   bytes: number
 }
 
+/**
+ * Type of a field of a registered message type: a scalar type, an array of
+ * one such as `f64[3]` (exactly 3 elements) or `string[]` (any length), the
+ * name of a registered type, or an object of nested fields
+ *
+ * `i64` and `u64` fields take a `bigint` or a safe integer and are received
+ * as `bigint`.
+ */
+export type FieldSpec =
+  | 'bool'
+  | 'string'
+  | 'f32'
+  | 'f64'
+  | 'i32'
+  | 'u32'
+  | 'i64'
+  | 'u64'
+  | 'json'
+  | string
+  | { [field: string]: FieldSpec }
+
+/**
+ * Options of `AgenticNode.publish`
+ */
+export interface PublishOptions {
+  /** Registered message type to check the message against */
+  type?: string
+}
+
+/**
+ * Options of `AgenticNode.publishRaw`
+ */
+export interface PublishRawOptions {
+  /** Type name of the message the bytes encode */
+  type: string
+  /** Serialization of the bytes (default `cdr`) */
+  format?: 'cdr' | 'json' | 'rkyv' | 'protobuf' | 'messagepack'
+}
+
+/**
+ * Options of `AgenticNode.subscribeRaw`
+ */
+export interface SubscribeRawOptions {
+  /** Messages queued while the callback is busy before the oldest is dropped (default 10) */
+  queueDepth?: number
+  /** Hand over a copy of each message instead of a Buffer over the received bytes */
+  copy?: boolean
+}
+
+/**
+ * Options of `AgenticNode.subscribe`
+ */
+export interface SubscribeOptions {
+  /** Messages queued while the callback is busy before the oldest is dropped (default 10) */
+  queueDepth?: number
+  /** Registered message type of the messages */
+  type?: string
+}
+
+/**
+ * Options of `AgenticNode.messages`
+ */
+export interface MessagesOptions {
+  /** Messages queued between iterations before the oldest is dropped (default 10) */
+  queueDepth?: number
+  /** Reject an iteration that waits longer than this for a message */
+  timeoutMs?: number
+  /** Registered message type of the messages */
+  type?: string
+}
+
+/**
+ * Options of `AgenticNode.callService`
+ */
+export interface CallServiceOptions {
+  /** Reject if no response arrives within this time (default 10000) */
+  timeoutMs?: number
+}
+
+/**
+ * Options of `AgenticNode.spinOnce`
+ */
+export interface SpinOnceOptions {
+  /** Wait up to this long for a message if none is queued (default 0) */
+  timeoutMs?: number
+}
+
+/**
+ * Statistics of the executor running a node's subscription callbacks
+ */
+export interface ExecutorStats {
+  /** Whether callbacks run in the background, see `spin()` */
+  spinning: boolean
+  /** Callback subscriptions */
+  subscriptions: number
+  /** Messages waiting for their callback */
+  pending: number
+  /** Messages dropped from full subscription queues */
+  dropped: number
+  /** Callbacks run, by spinning or by `spinOnce()` */
+  callbacks: number
+  /** Steps taken by `spinOnce()` */
+  spinOnceCalls: number
+}
+
+/**
+ * Options of `AgenticNode.startMcpServer`
+ */
+export interface McpServerOptions {
+  /** `stdio` (default), serving on the process's stdin and stdout, or `http` */
+  transport?: 'stdio' | 'http'
+  /** Port of the HTTP transport, 0 (default) for a free one */
+  port?: number
+  /** Address the HTTP transport listens on (default 127.0.0.1) */
+  host?: string
+  /** The built-in ros3 tools to offer, all of them by default */
+  tools?: Array<string>
+  /** Server name reported to clients, the node's name by default */
+  name?: string
+}
+
+/**
+ * Statistics of a running MCP server
+ */
+export interface McpServerStats {
+  transport: string
+  /** Port of the HTTP transport */
+  port?: number
+  /** Whether the server still serves, i.e. `stop()` was not called */
+  running: boolean
+  /** Connected clients */
+  sessions: number
+  /** Tool calls made, including denied ones */
+  toolCalls: number
+  /** Tool calls that failed */
+  failedCalls: number
+}
+
+/**
+ * Audit record of a tool call, emitted as `toolCall`
+ */
+export interface ToolCallRecord {
+  /** System time the call was received at, in nanoseconds */
+  timestamp: bigint
+  client: { transport: string; session?: string; address?: string; name?: string; version?: string }
+  tool: string
+  /** FNV-1a hash of the arguments as JSON, in hex */
+  args_hash: string
+  decision: 'allow' | 'deny'
+  reason?: string
+  duration_ms: number
+  /** Whether the call failed, including denied calls */
+  failed: boolean
+}
+
+/**
+ * Value of a parameter: integer parameters are `bigint`, doubles `number`
+ */
+export type ParameterValue = boolean | bigint | number | string | Array<number>
+
+/**
+ * State of a lifecycle node, see `AgenticNode.getLifecycleState`
+ */
+export type LifecycleState = 'unconfigured' | 'inactive' | 'active' | 'finalized'
+
+/**
+ * Transition of a lifecycle node, see `AgenticNode.changeLifecycleState`
+ */
+export type LifecycleTransition = 'configure' | 'activate' | 'deactivate' | 'cleanup' | 'shutdown'
+
+/**
+ * Description and constraints of a parameter, see `AgenticNode.declareParameter`
+ */
+export interface ParameterDescriptorOptions {
+  description?: string
+  /** Reject every change after the declaration */
+  readOnly?: boolean
+  /** Inclusive bounds of integers, doubles and every element of arrays */
+  range?: { min: number; max: number }
+}
+
 /**
  * Main node for creating publishers and subscribers
  */
@@ -17,8 +198,15 @@ export class AgenticNode {
   /**
    * Create a new AgenticNode
    * @param name - Name of the node
+   * @param namespace - Namespace for relative topic names (default: "/")
+   * @param remaps - Remap rules such as "cmd_vel:=robot1/cmd_vel"
    */
-  constructor(name: string)
+  constructor(name: string, namespace?: string, remaps?: Array<string>)
+
+  /**
+   * Get the node namespace
+   */
+  getNamespace(): string
 
   /**
    * Create a publisher for a topic
@@ -33,6 +221,261 @@ export class AgenticNode {
    * @returns A subscriber instance
    */
   createSubscriber(topic: string): Promise<AgenticSubscriber>
+
+  /**
+   * Register a message type for `publish`, `subscribe` and `messages`
+   *
+   * Registering a name again with the same fields does nothing; with
+   * other fields it throws.
+   * @param name - Type name
+   * @param fields - Field types, e.g. `{ position: 'f64[3]', timestamp: 'i64' }`;
+   *   without them the type is taken from the Rust message registry, e.g.
+   *   `ros3_msgs/RobotState`
+   */
+  registerType(name: string, fields?: Record<string, FieldSpec>): void
+
+  /**
+   * Publish a message, creating the node's publisher for the topic on first use
+   * @param topic - Topic name
+   * @param data - Any JSON-compatible value
+   * @param options - With `type`, `data` is checked against the registered
+   *   type first, rejecting with the offending field such as
+   *   `position[2] must be a number, got string`
+   */
+  publish(topic: string, data: any, options?: PublishOptions): Promise<void>
+
+  /**
+   * Call `callback` with every message received on a topic
+   *
+   * Messages are handed over one at a time; while the callback is busy,
+   * up to `options.queueDepth` wait and older ones are dropped. With
+   * `options.type`, only messages of that type are handed over.
+   * @param topic - Topic name
+   * @param callback - Called with each message as a plain object
+   * @returns A handle for `unsubscribe`
+   */
+  subscribe(topic: string, callback: (message: any) => void, options?: SubscribeOptions): number
+
+  /**
+   * Publish a message already serialized, without copying its bytes
+   *
+   * The bytes are shared with subscribers and transports; do not modify
+   * them until the promise resolves.
+   * @param topic - Topic name
+   * @param data - The serialized message
+   * @param options - Its type name and serialization format
+   */
+  publishRaw(topic: string, data: Buffer | Uint8Array | ArrayBuffer, options: PublishRawOptions): Promise<void>
+
+  /**
+   * Call `callback` with the bytes of every message received on a topic,
+   * whatever its type
+   *
+   * Each Buffer is a view of the received bytes, shared with other
+   * subscribers and valid as long as it is referenced; do not modify it,
+   * or pass `copy: true` to get a copy instead.
+   * @returns A handle for `unsubscribe`
+   */
+  subscribeRaw(topic: string, callback: (data: Buffer) => void, options?: SubscribeRawOptions): number
+
+  /**
+   * Iterate over the messages of a topic with `for await`
+   *
+   * The subscription starts now and ends when the loop exits, including
+   * by `break`; the iteration ends when the node shuts down.
+   * @param topic - Topic name
+   */
+  messages(topic: string, options?: MessagesOptions): MessageStream
+
+  /**
+   * End a subscription made with `subscribe` or `subscribeRaw`
+   * @returns false if the handle is unknown or already unsubscribed
+   */
+  unsubscribe(handle: number): boolean
+
+  /**
+   * Serve a service, handling calls concurrently
+   *
+   * If the handler throws or rejects, the call fails with the error's
+   * message; the server keeps serving.
+   * @param name - Service name
+   * @param handler - Called with each request, returns the response or a promise of it
+   * @throws If the service already has a server
+   */
+  createService(name: string, handler: (request: any) => any): void
+
+  /**
+   * Stop serving a service made with `createService`
+   * @returns false if this node does not serve the service
+   */
+  destroyService(name: string): boolean
+
+  /**
+   * Call a service served from JavaScript or Rust
+   * @param name - Service name
+   * @param request - Request, which must match a Rust server's request type
+   * @returns The response; rejects if the service has no server, the
+   *   server fails, or no response arrives within `options.timeoutMs`
+   */
+  callService(name: string, request: any, options?: CallServiceOptions): Promise<any>
+
+  /**
+   * Start an MCP server offering the built-in ros3 tools for this node
+   * @param options - Transport and the tools to offer
+   * @returns A handle to register JS tools, observe calls and stop the server
+   */
+  startMcpServer(options?: McpServerOptions): Promise<McpServerHandle>
+
+  /**
+   * Run subscription callbacks in the background as messages arrive
+   *
+   * Nodes spin from their creation, so this only resumes after `stopSpinning`.
+   */
+  spin(): void
+
+  /**
+   * Stop running subscription callbacks in the background
+   *
+   * Resolves once no callback is called by spinning any longer. Messages then
+   * wait in their subscription's queue until `spinOnce` or `spin`.
+   */
+  stopSpinning(): Promise<void>
+
+  /**
+   * Hand each subscription with a queued message its oldest one, once
+   * @returns The number of callbacks called, once all of them have returned
+   * @throws While the node is spinning
+   */
+  spinOnce(options?: SpinOnceOptions): Promise<number>
+
+  /**
+   * Statistics of the executor running this node's subscription callbacks
+   */
+  executorStats(): ExecutorStats
+
+  /**
+   * Declare a parameter of this node
+   *
+   * The default's type sets the parameter's: a `bigint` declares an
+   * integer, a `number` a double.
+   * @throws If the parameter was already declared or the default violates the descriptor
+   */
+  declareParameter(name: string, default: ParameterValue, descriptor?: ParameterDescriptorOptions): void
+
+  /**
+   * Current value of a parameter, `null` if it was not declared
+   */
+  getParameter(name: string): ParameterValue | null
+
+  /**
+   * Change a parameter of this node
+   *
+   * An integer parameter also takes a safe integer `number`.
+   * @throws With the failed constraint, e.g.
+   *   `Invalid parameter 'arm.gain': value outside [0, 10]`
+   */
+  setParameter(name: string, value: ParameterValue): void
+
+  /**
+   * Change a parameter of the live node `node`, written in JavaScript or Rust
+   * @throws If no such node lives or the change is rejected as by `setParameter`
+   */
+  static setNodeParameter(node: string, name: string, value: ParameterValue): void
+
+  /**
+   * Lifecycle state of the live lifecycle node `node`, written in Rust
+   * @throws If no such node lives
+   */
+  static getLifecycleState(node: string): LifecycleState
+
+  /**
+   * Run a lifecycle transition of the live lifecycle node `node`, resolving
+   * with its new state
+   * @throws If no such node lives, the transition does not start from the
+   *   node's state or its hook fails; the node keeps its state then
+   */
+  static changeLifecycleState(node: string, transition: LifecycleTransition): Promise<LifecycleState>
+
+  /**
+   * Listen to changes of declared parameters, whoever makes them
+   *
+   * Listeners are called asynchronously, after the change was made.
+   */
+  on(event: 'parameterChanged', listener: (name: string, value: ParameterValue) => void): void
+
+  /**
+   * Tear down every publisher, subscriber, service and MCP server of this node
+   *
+   * Publishers are flushed first, for up to a second each. Resolves once no
+   * callback of the node will be called again, so the process can exit;
+   * other methods then reject with `Node '<name>' is closed`. Closing again
+   * does nothing.
+   */
+  close(): Promise<void>
+
+  /**
+   * Same as `close`
+   */
+  shutdown(): Promise<void>
+}
+
+/**
+ * Messages of a topic as an async iterator, created by `AgenticNode.messages`
+ */
+export class MessageStream implements AsyncIterableIterator<any> {
+  [Symbol.asyncIterator](): MessageStream
+
+  /**
+   * Get the resolved topic name
+   */
+  getTopic(): string
+
+  /**
+   * Wait for the next message; done once returned or the node shut down,
+   * rejected if none arrives within `timeoutMs`
+   */
+  next(): Promise<IteratorResult<any>>
+
+  /**
+   * End the subscription, as `for await` does when the loop exits early
+   */
+  return(): Promise<IteratorResult<any>>
+}
+
+/**
+ * A running MCP server, created by `AgenticNode.startMcpServer`
+ */
+export class McpServerHandle {
+  /**
+   * Port of the HTTP transport
+   */
+  getPort(): number | null
+
+  /**
+   * Offer a tool implemented in JavaScript
+   *
+   * A string result is sent as text and any other value as JSON; if the
+   * handler throws or rejects, the call fails with the error's message.
+   * @param name - Tool name
+   * @param schema - JSON Schema of the arguments, with the tool's `description`
+   * @param handler - Called with the arguments, returns the result or a promise of it
+   */
+  registerTool(name: string, schema: object, handler: (args: any) => any): Promise<void>
+
+  /**
+   * Call `listener` with the audit record of every tool call
+   */
+  on(event: 'toolCall', listener: (record: ToolCallRecord) => void): void
+
+  /**
+   * Stop serving, ending every session and removing the JS tools
+   */
+  stop(): Promise<void>
+
+  /**
+   * Get statistics of the server
+   */
+  stats(): McpServerStats
 }
 
 /**
@@ -68,3 +511,26 @@ export class AgenticSubscriber {
    */
   recv(): Promise<string>
 }
+
+/**
+ * How the loaded addon was built
+ */
+export interface BuildInfo {
+  /** Version of the addon */
+  version: string
+  /** Version of agentic-robotics-core it was built with */
+  coreVersion: string
+  /** ABI version of that core; the loader refuses binaries of another one */
+  abiVersion: number
+  /** Target triple, e.g. `aarch64-unknown-linux-gnu` */
+  target: string
+  /** Optional features of the core, e.g. `zstd` */
+  features: Array<string>
+  /** Whether it is a debug build */
+  debug: boolean
+}
+
+/**
+ * How the loaded addon was built
+ */
+export function getBuildInfo(): BuildInfo
@@ -1,11 +1,9 @@
 const { existsSync, readFileSync } = require('fs')
 const { join } = require('path')
 
-const { platform, arch } = process
+const { verifyBuildInfo } = require('./build-info.js')
 
-let nativeBinding = null
-let localFileExisted = false
-let loadError = null
+const { platform, arch } = process
 
 function isMusl() {
   // For Node 10
@@ -22,94 +20,96 @@ function isMusl() {
   }
 }
 
-switch (platform) {
-  case 'linux':
-    switch (arch) {
-      case 'x64':
-        if (isMusl()) {
-          localFileExisted = existsSync(join(__dirname, 'agentic-robotics.linux-x64-musl.node'))
-          try {
-            if (localFileExisted) {
-              nativeBinding = require('./agentic-robotics.linux-x64-musl.node')
-            } else {
-              nativeBinding = require('agentic-robotics-linux-x64-musl')
-            }
-          } catch (e) {
-            loadError = e
-          }
-        } else {
-          localFileExisted = existsSync(join(__dirname, 'agentic-robotics.linux-x64-gnu.node'))
-          try {
-            if (localFileExisted) {
-              nativeBinding = require('./agentic-robotics.linux-x64-gnu.node')
-            } else {
-              nativeBinding = require('agentic-robotics-linux-x64-gnu')
-            }
-          } catch (e) {
-            loadError = e
-          }
-        }
-        break
-      case 'arm64':
-        localFileExisted = existsSync(join(__dirname, 'agentic-robotics.linux-arm64-gnu.node'))
-        try {
-          if (localFileExisted) {
-            nativeBinding = require('./agentic-robotics.linux-arm64-gnu.node')
-          } else {
-            nativeBinding = require('agentic-robotics-linux-arm64-gnu')
-          }
-        } catch (e) {
-          loadError = e
-        }
-        break
-      default:
-        throw new Error(`Unsupported architecture on Linux: ${arch}`)
-    }
-    break
-  case 'darwin':
-    switch (arch) {
-      case 'x64':
-        localFileExisted = existsSync(join(__dirname, 'agentic-robotics.darwin-x64.node'))
-        try {
-          if (localFileExisted) {
-            nativeBinding = require('./agentic-robotics.darwin-x64.node')
-          } else {
-            nativeBinding = require('agentic-robotics-darwin-x64')
-          }
-        } catch (e) {
-          loadError = e
-        }
-        break
-      case 'arm64':
-        localFileExisted = existsSync(join(__dirname, 'agentic-robotics.darwin-arm64.node'))
-        try {
-          if (localFileExisted) {
-            nativeBinding = require('./agentic-robotics.darwin-arm64.node')
-          } else {
-            nativeBinding = require('agentic-robotics-darwin-arm64')
-          }
-        } catch (e) {
-          loadError = e
-        }
-        break
-      default:
-        throw new Error(`Unsupported architecture on macOS: ${arch}`)
+// Suffix of the binary and of its prebuilt package on this platform
+function platformSuffix() {
+  switch (`${platform}-${arch}`) {
+    case 'linux-x64':
+      return isMusl() ? 'linux-x64-musl' : 'linux-x64-gnu'
+    case 'linux-arm64':
+      return 'linux-arm64-gnu'
+    case 'darwin-x64':
+      return 'darwin-x64'
+    case 'darwin-arm64':
+      return 'darwin-arm64'
+    default:
+      return null
+  }
+}
+
+// Where to look for the addon, in order: an explicit path, a binary built
+// from source next to this file, then the prebuilt package
+function candidates(suffix) {
+  const found = []
+  if (process.env.AGENTIC_ROBOTICS_NATIVE_PATH) {
+    found.push(process.env.AGENTIC_ROBOTICS_NATIVE_PATH)
+  }
+  if (suffix) {
+    const local = join(__dirname, `agentic-robotics.${suffix}.node`)
+    if (existsSync(local)) {
+      found.push(local)
     }
-    break
-  default:
-    throw new Error(`Unsupported OS: ${platform}, architecture: ${arch}`)
+    found.push(`agentic-robotics-${suffix}`)
+  }
+  return found
 }
 
-if (!nativeBinding) {
-  if (loadError) {
-    throw loadError
+function loadBinding() {
+  const suffix = platformSuffix()
+  const sources = candidates(suffix)
+  if (sources.length === 0) {
+    throw new Error(`Unsupported OS: ${platform}, architecture: ${arch}`)
   }
-  throw new Error(`Failed to load native binding`)
+
+  // A candidate that fails to load or does not match falls back to the next
+  const failures = []
+  for (const source of sources) {
+    let binding
+    try {
+      binding = require(source)
+    } catch (e) {
+      failures.push({ source, error: e })
+      continue
+    }
+    try {
+      verifyBuildInfo(binding, source)
+      return binding
+    } catch (e) {
+      failures.push({ source, error: e })
+    }
+  }
+  const reasons = failures.map(({ source, error }) => `  ${source}: ${error.message.split('\n')[0]}`)
+  const error = new Error(
+    [
+      `Failed to load the agentic-robotics native addon for ${platform}-${arch}:`,
+      ...reasons,
+      suffix
+        ? `Install the prebuilt package agentic-robotics-${suffix}, or build from source with a Rust toolchain (npm run build).`
+        : 'Set AGENTIC_ROBOTICS_NATIVE_PATH to an addon built for this platform.',
+    ].join('\n'),
+  )
+  error.cause = failures[failures.length - 1].error
+  throw error
 }
 
-const { AgenticNode, AgenticPublisher, AgenticSubscriber, PublisherStats } = nativeBinding
+const nativeBinding = loadBinding()
+
+const { AgenticNode, AgenticPublisher, AgenticSubscriber, MessageStream, PublisherStats, getBuildInfo } =
+  nativeBinding
+
+// The addon takes views of memory; an ArrayBuffer is viewed as a whole
+const publishRaw = AgenticNode.prototype.publishRaw
+AgenticNode.prototype.publishRaw = function (topic, data, options) {
+  return publishRaw.call(this, topic, data instanceof ArrayBuffer ? new Uint8Array(data) : data, options)
+}
+
+// `for await` looks the iterator up by this symbol
+MessageStream.prototype[Symbol.asyncIterator] = function () {
+  return this
+}
 
 module.exports.AgenticNode = AgenticNode
+module.exports.MessageStream = MessageStream
 module.exports.AgenticPublisher = AgenticPublisher
 module.exports.AgenticSubscriber = AgenticSubscriber
 module.exports.PublisherStats = PublisherStats
+module.exports.getBuildInfo = getBuildInfo
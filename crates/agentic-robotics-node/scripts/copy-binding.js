@@ -0,0 +1,49 @@
+// Copy the addon built by cargo to the file name index.js loads on this
+// platform, e.g. agentic-robotics.linux-x64-gnu.node
+//
+// With a target triple, copy the addon cross-built for it with
+// `cargo build --target <triple>` into its prebuilt package under npm/
+// instead, e.g. npm/linux-arm64-gnu/agentic-robotics.linux-arm64-gnu.node
+//
+// Usage: node scripts/copy-binding.js [debug|release] [triple]
+
+const { copyFileSync } = require('fs')
+const { join } = require('path')
+
+const profile = process.argv[2] || 'release'
+const triple = process.argv[3]
+const { platform, arch } = process
+
+function isMusl() {
+  const { glibcVersionRuntime } = process.report.getReport().header
+  return !glibcVersionRuntime
+}
+
+const suffixes = {
+  'linux-x64': platform === 'linux' && isMusl() ? 'linux-x64-musl' : 'linux-x64-gnu',
+  'linux-arm64': 'linux-arm64-gnu',
+  'darwin-x64': 'darwin-x64',
+  'darwin-arm64': 'darwin-arm64',
+}
+
+// The triples of package.json's napi.targets
+const tripleSuffixes = {
+  'x86_64-unknown-linux-gnu': 'linux-x64-gnu',
+  'x86_64-unknown-linux-musl': 'linux-x64-musl',
+  'aarch64-unknown-linux-gnu': 'linux-arm64-gnu',
+  'x86_64-apple-darwin': 'darwin-x64',
+  'aarch64-apple-darwin': 'darwin-arm64',
+}
+
+const suffix = triple ? tripleSuffixes[triple] : suffixes[`${platform}-${arch}`]
+if (!suffix) {
+  throw new Error(triple ? `Unsupported target: ${triple}` : `Unsupported OS: ${platform}, architecture: ${arch}`)
+}
+const library = suffix.startsWith('darwin') ? 'libagentic_robotics_node.dylib' : 'libagentic_robotics_node.so'
+const targetDir = join(__dirname, '..', '..', '..', 'target')
+const source = triple ? join(targetDir, triple, profile, library) : join(targetDir, profile, library)
+const destination = triple ? join(__dirname, '..', 'npm', suffix) : join(__dirname, '..')
+const target = join(destination, `agentic-robotics.${suffix}.node`)
+
+copyFileSync(source, target)
+console.log(`${source} -> ${target}`)
@@ -0,0 +1,87 @@
+//! What the addon was built from, checked when it is loaded
+//!
+//! The addon refuses to load if the core it was built with has another ABI
+//! version than the one it was written against, and `index.js` refuses a
+//! binary whose [`get_build_info`] does not match the package, so a stale or
+//! foreign prebuilt binary fails with an error instead of misbehaving.
+
+use napi::bindgen_prelude::{Error, Object, Result, Status};
+use napi_derive::napi;
+
+/// Core ABI version the addon is written against, see
+/// [`agentic_robotics_core::ABI_VERSION`]
+pub const CORE_ABI_VERSION: u32 = 2;
+
+/// How the loaded addon was built
+#[napi(object)]
+pub struct BuildInfo {
+    /// Version of the addon
+    pub version: String,
+    /// Version of `agentic-robotics-core` it was built with
+    pub core_version: String,
+    /// ABI version of that core
+    pub abi_version: u32,
+    /// Target triple, e.g. `aarch64-unknown-linux-gnu`
+    pub target: String,
+    /// Optional features of the core, e.g. `zstd`
+    pub features: Vec<String>,
+    /// Whether it is a debug build
+    pub debug: bool,
+}
+
+/// How the loaded addon was built
+#[napi]
+pub fn get_build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        core_version: agentic_robotics_core::VERSION.to_string(),
+        abi_version: agentic_robotics_core::ABI_VERSION,
+        target: env!("AGENTIC_ROBOTICS_TARGET").to_string(),
+        features: agentic_robotics_core::features()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        debug: cfg!(debug_assertions),
+    }
+}
+
+/// Fail if a core of ABI version `core_abi` is not the one this addon is
+/// written against
+pub(crate) fn check_core_abi(core_abi: u32) -> Result<()> {
+    if core_abi == CORE_ABI_VERSION {
+        return Ok(());
+    }
+    Err(Error::new(
+        Status::GenericFailure,
+        format!(
+            "agentic-robotics addon {} is written against core ABI version {} but was built \
+             with agentic-robotics-core {} of ABI version {}; rebuild it against a matching core",
+            env!("CARGO_PKG_VERSION"),
+            CORE_ABI_VERSION,
+            agentic_robotics_core::VERSION,
+            core_abi
+        ),
+    ))
+}
+
+/// Check the core when the addon is loaded, making `require` throw on a
+/// mismatch
+#[napi(module_exports)]
+pub fn init(_exports: Object) -> Result<()> {
+    check_core_abi(agentic_robotics_core::ABI_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_abi_mismatch_is_an_error() {
+        assert!(check_core_abi(agentic_robotics_core::ABI_VERSION).is_ok());
+        let error = check_core_abi(CORE_ABI_VERSION + 1).unwrap_err();
+        assert!(error.reason.contains(&format!(
+            "core ABI version {} but was built",
+            CORE_ABI_VERSION
+        )));
+    }
+}
@@ -0,0 +1,342 @@
+//! When subscription callbacks run, see [`AgenticNode::spin`] and
+//! [`AgenticNode::spin_once`]
+//!
+//! A node spins from its creation: each callback subscription has a task on
+//! the Rust runtime handing its messages to JS as they arrive. Once spinning
+//! stops, messages wait in the subscriptions' bounded queues until a
+//! [`spin_once`](Executor::spin_once) step hands them over.
+//!
+//! [`AgenticNode::spin`]: crate::AgenticNode::spin
+//! [`AgenticNode::spin_once`]: crate::AgenticNode::spin_once
+
+use crate::raw::RawBuffer;
+use crate::{Inbox, MessageCallback, RawCallback};
+use agentic_robotics_core::{RawMessage, Subscriber, WaitKey, WaitSet};
+use napi_derive::napi;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Statistics of a node's executor, see
+/// [`AgenticNode::executor_stats`](crate::AgenticNode::executor_stats)
+#[napi(object)]
+pub struct ExecutorStats {
+    /// Whether callbacks run in the background, see `spin()`
+    pub spinning: bool,
+    /// Callback subscriptions
+    pub subscriptions: u32,
+    /// Messages waiting for their callback
+    pub pending: u32,
+    /// Messages dropped from full subscription queues
+    pub dropped: i64,
+    /// Callbacks run, by spinning or by `spinOnce()`
+    pub callbacks: i64,
+    /// Steps taken by `spinOnce()`
+    pub spin_once_calls: i64,
+}
+
+/// A subscription's messages and the JS callback they are handed to
+pub(crate) enum Dispatch {
+    /// See [`AgenticNode::subscribe`](crate::AgenticNode::subscribe)
+    Messages {
+        inbox: Inbox,
+        callback: MessageCallback,
+    },
+    /// See [`AgenticNode::subscribe_raw`](crate::AgenticNode::subscribe_raw)
+    Raw {
+        subscriber: Subscriber<RawMessage>,
+        copy: bool,
+        callback: RawCallback,
+    },
+}
+
+impl Dispatch {
+    /// Hand over messages as they arrive, until the subscriber closes or the
+    /// callback is released
+    async fn run(&self, counts: &Counts) {
+        loop {
+            // Waiting for each call keeps the backlog in the bounded queue
+            let called = match self {
+                Dispatch::Messages { inbox, callback } => match inbox.recv().await {
+                    Ok(message) => callback.call_async(message).await.is_ok(),
+                    Err(_) => false,
+                },
+                Dispatch::Raw {
+                    subscriber,
+                    copy,
+                    callback,
+                } => match subscriber.recv_shared().await {
+                    Ok(message) => {
+                        let data = RawBuffer {
+                            data: message.data,
+                            copy: *copy,
+                        };
+                        callback.call_async(data).await.is_ok()
+                    }
+                    Err(_) => false,
+                },
+            };
+            if !called {
+                break;
+            }
+            counts.callbacks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Hand over the oldest queued message, if there is one; returns whether
+    /// the callback was called
+    async fn run_once(&self) -> bool {
+        match self {
+            Dispatch::Messages { inbox, callback } => match inbox.try_recv() {
+                Some(message) => callback.call_async(message).await.is_ok(),
+                None => false,
+            },
+            Dispatch::Raw {
+                subscriber,
+                copy,
+                callback,
+            } => match subscriber.try_recv_shared() {
+                Ok(Some(message)) => {
+                    let data = RawBuffer {
+                        data: message.data,
+                        copy: *copy,
+                    };
+                    callback.call_async(data).await.is_ok()
+                }
+                _ => false,
+            },
+        }
+    }
+
+    fn subscriber_stats(&self) -> (usize, u64) {
+        match self {
+            Dispatch::Messages { inbox, .. } => inbox.queue_stats(),
+            Dispatch::Raw { subscriber, .. } => (subscriber.pending(), subscriber.dropped_count()),
+        }
+    }
+
+    fn watch(&self, set: &mut WaitSet) -> WaitKey {
+        match self {
+            Dispatch::Messages { inbox, .. } => inbox.watch(set),
+            Dispatch::Raw { subscriber, .. } => set.add_subscriber(subscriber),
+        }
+    }
+}
+
+/// A callback subscription, ended when dropped
+struct Subscription {
+    dispatch: Arc<Dispatch>,
+    /// Dispatching in the background while spinning
+    task: Option<JoinHandle<()>>,
+}
+
+impl Subscription {
+    fn start(&mut self, counts: &Arc<Counts>) {
+        let (dispatch, counts) = (self.dispatch.clone(), counts.clone());
+        self.task = Some(napi::bindgen_prelude::spawn(async move {
+            dispatch.run(&counts).await;
+        }));
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// Abort `tasks` and wait until their callbacks are no longer called
+async fn finish(tasks: Vec<JoinHandle<()>>) {
+    for task in tasks {
+        task.abort();
+        let _ = task.await;
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    callbacks: AtomicU64,
+    spin_once_calls: AtomicU64,
+}
+
+struct State {
+    spinning: bool,
+    /// By handle, so steps serve subscriptions in the order they were made
+    subscriptions: BTreeMap<u32, Subscription>,
+}
+
+/// Runs the callbacks of a node's subscriptions
+pub(crate) struct Executor {
+    state: Mutex<State>,
+    counts: Arc<Counts>,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State {
+                spinning: true,
+                subscriptions: BTreeMap::new(),
+            }),
+            counts: Arc::new(Counts::default()),
+        }
+    }
+}
+
+impl Executor {
+    /// Add a subscription, dispatched right away if spinning
+    pub(crate) fn add(&self, handle: u32, dispatch: Dispatch) {
+        let mut state = self.state.lock().unwrap();
+        let mut subscription = Subscription {
+            dispatch: Arc::new(dispatch),
+            task: None,
+        };
+        if state.spinning {
+            subscription.start(&self.counts);
+        }
+        state.subscriptions.insert(handle, subscription);
+    }
+
+    /// End a subscription; returns `false` if `handle` is unknown
+    pub(crate) fn remove(&self, handle: u32) -> bool {
+        let removed = self.state.lock().unwrap().subscriptions.remove(&handle);
+        removed.is_some()
+    }
+
+    /// Dispatch every subscription in the background
+    pub(crate) fn spin(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.spinning {
+            return;
+        }
+        state.spinning = true;
+        for subscription in state.subscriptions.values_mut() {
+            subscription.start(&self.counts);
+        }
+    }
+
+    /// Stop dispatching in the background, resolving once no callback is
+    /// called by it any longer
+    pub(crate) async fn stop_spinning(&self) {
+        let tasks: Vec<_> = {
+            let mut state = self.state.lock().unwrap();
+            state.spinning = false;
+            state
+                .subscriptions
+                .values_mut()
+                .filter_map(|subscription| subscription.task.take())
+                .collect()
+        };
+        finish(tasks).await;
+    }
+
+    /// One step: hand each subscription with a queued message its oldest,
+    /// waiting up to `timeout` for a message if none is queued
+    ///
+    /// The returned future resolves to the number of callbacks called. Its
+    /// subscriptions are the ones present now. Returns `None` while
+    /// spinning, as steps and background dispatch would race.
+    pub(crate) fn spin_once(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Option<impl Future<Output = u32> + Send + 'static> {
+        let dispatches: Vec<_> = {
+            let state = self.state.lock().unwrap();
+            if state.spinning {
+                return None;
+            }
+            state
+                .subscriptions
+                .values()
+                .map(|subscription| subscription.dispatch.clone())
+                .collect()
+        };
+        let counts = self.counts.clone();
+        counts.spin_once_calls.fetch_add(1, Ordering::Relaxed);
+
+        Some(async move {
+            let mut set = WaitSet::new();
+            let keys: Vec<_> = dispatches
+                .iter()
+                .map(|dispatch| dispatch.watch(&mut set))
+                .collect();
+            let ready = match timeout {
+                _ if set.is_empty() => Vec::new(),
+                Some(timeout) => set.wait_timeout(timeout).await.unwrap_or_default(),
+                None => set.try_ready(),
+            };
+
+            let mut called = 0;
+            for (key, dispatch) in keys.iter().zip(&dispatches) {
+                if ready.contains(key) && dispatch.run_once().await {
+                    called += 1;
+                }
+            }
+            counts.callbacks.fetch_add(called as u64, Ordering::Relaxed);
+            called
+        })
+    }
+
+    /// Statistics of the executor
+    pub(crate) fn stats(&self) -> ExecutorStats {
+        let state = self.state.lock().unwrap();
+        let (pending, dropped) = state
+            .subscriptions
+            .values()
+            .map(|subscription| subscription.dispatch.subscriber_stats())
+            .fold((0, 0), |(pending, dropped), (queued, lost)| {
+                (pending + queued, dropped + lost)
+            });
+        ExecutorStats {
+            spinning: state.spinning,
+            subscriptions: state.subscriptions.len() as u32,
+            pending: pending as u32,
+            dropped: dropped as i64,
+            callbacks: self.counts.callbacks.load(Ordering::Relaxed) as i64,
+            spin_once_calls: self.counts.spin_once_calls.load(Ordering::Relaxed) as i64,
+        }
+    }
+
+    /// End every subscription, waiting until their callbacks are no longer
+    /// called
+    pub(crate) async fn finish(&self) {
+        let subscriptions = std::mem::take(&mut self.state.lock().unwrap().subscriptions);
+        let tasks = subscriptions
+            .into_values()
+            .filter_map(|mut subscription| subscription.task.take())
+            .collect();
+        finish(tasks).await;
+    }
+
+    /// End every subscription without waiting
+    pub(crate) fn clear(&self) {
+        self.state.lock().unwrap().subscriptions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_steps_only_when_stopped() {
+        let executor = Executor::default();
+        assert!(executor.spin_once(None).is_none());
+
+        executor.stop_spinning().await;
+        assert_eq!(executor.spin_once(None).unwrap().await, 0);
+        let waited = executor.spin_once(Some(Duration::from_millis(10)));
+        assert_eq!(waited.unwrap().await, 0);
+
+        let stats = executor.stats();
+        assert!(!stats.spinning);
+        assert_eq!(stats.spin_once_calls, 2);
+        executor.spin();
+        assert!(executor.stats().spinning);
+    }
+}
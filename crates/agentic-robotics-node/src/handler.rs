@@ -0,0 +1,104 @@
+//! Request handlers written in JavaScript, for services and MCP tools
+//!
+//! A handler may return the response or a promise of it. Either way the
+//! result is settled on the JS thread and only the response, or the error
+//! message, is sent back to Rust, so a handler that throws or rejects fails
+//! its request instead of raising an uncaught exception in the addon.
+
+use napi::bindgen_prelude::{CallbackContext, FromNapiValue, PromiseRaw, Unknown};
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{sys, Env, Error, Status};
+use serde_json::Value as JsonValue;
+use std::cell::Cell;
+use std::rc::Rc;
+use tokio::sync::oneshot;
+
+/// JS function answering requests, see [`call`]
+pub(crate) type Handler = ThreadsafeFunction<JsonValue, HandlerReturn, JsonValue, Status, false>;
+
+type Reply = oneshot::Sender<Result<JsonValue, String>>;
+
+/// Whatever a handler returned, read on the JS thread
+pub struct HandlerReturn {
+    env: sys::napi_env,
+    value: sys::napi_value,
+}
+
+impl FromNapiValue for HandlerReturn {
+    unsafe fn from_napi_value(env: sys::napi_env, value: sys::napi_value) -> napi::Result<Self> {
+        Ok(Self { env, value })
+    }
+}
+
+impl HandlerReturn {
+    /// Send the response once known, waiting for it if it is a promise
+    fn settle(self, reply: Reply) -> napi::Result<()> {
+        let mut is_promise = false;
+        unsafe { sys::napi_is_promise(self.env, self.value, &mut is_promise) };
+        if !is_promise {
+            let response = unsafe { JsonValue::from_napi_value(self.env, self.value) };
+            let _ = reply.send(response.map_err(|e| message(&e)));
+            return Ok(());
+        }
+
+        // Whichever of `then` and `catch` runs takes the sender
+        let resolved = Rc::new(Cell::new(Some(reply)));
+        let rejected = resolved.clone();
+        let promise = PromiseRaw::<JsonValue>::new(self.env, self.value);
+        promise
+            .then(move |ctx: CallbackContext<JsonValue>| {
+                if let Some(reply) = resolved.take() {
+                    let _ = reply.send(Ok(ctx.value));
+                }
+                Ok(())
+            })?
+            // Also catches a response that does not convert to JSON
+            .catch(move |ctx: CallbackContext<Unknown>| {
+                if let Some(reply) = rejected.take() {
+                    let _ = reply.send(Err(message(&Error::from(ctx.value))));
+                }
+                Ok(())
+            })?;
+        Ok(())
+    }
+}
+
+/// First line of a JS error, e.g. `Error: boom`, without its stack trace
+fn message(error: &Error) -> String {
+    error.reason.lines().next().unwrap_or_default().to_string()
+}
+
+/// Call `handler` with `request`, returning its response
+///
+/// Fails with the message of the error if the handler throws, rejects or
+/// returns something that does not convert to JSON, and with `None` if the
+/// JS environment went away before answering.
+pub(crate) async fn call(
+    handler: &Handler,
+    request: JsonValue,
+) -> Result<JsonValue, Option<String>> {
+    let (reply, response) = oneshot::channel();
+    let status = handler.call_with_return_value(
+        request,
+        ThreadsafeFunctionCallMode::NonBlocking,
+        move |returned: napi::Result<HandlerReturn>, _: Env| {
+            match returned {
+                // On failure the sender is dropped, failing the call
+                Ok(returned) => {
+                    let _ = returned.settle(reply);
+                }
+                Err(e) => {
+                    let _ = reply.send(Err(message(&e)));
+                }
+            }
+            Ok(())
+        },
+    );
+    if status != Status::Ok {
+        return Err(None);
+    }
+    match response.await {
+        Ok(response) => response.map_err(Some),
+        Err(_) => Err(None),
+    }
+}
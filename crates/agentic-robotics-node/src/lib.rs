@@ -4,48 +4,382 @@
 
 #![deny(clippy::all)]
 
-use agentic_robotics_core::{Publisher, Subscriber};
+pub mod build_info;
+mod executor;
+mod handler;
+mod mcp;
+mod params;
+mod raw;
+mod schema;
+
+use agentic_robotics_core::serialization::Format;
+use agentic_robotics_core::{
+    LifecycleHandle, Message, Node, Publisher, QosProfile, RawMessage, Remap, ServiceClient, ServiceServer,
+    SharedBytes, SharedRawMessage, Subscriber, Transition, WaitKey, WaitSet,
+};
+use executor::{Dispatch, Executor, ExecutorStats};
+use handler::Handler;
+use mcp::{McpServerHandle, McpServerOptions};
 use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{
+    ThreadsafeFunction, ThreadsafeFunctionCallMode, UnknownReturnValue,
+};
 use napi_derive::napi;
+use params::{JsParameter, ParameterDescriptorOptions, ParameterListener};
+use raw::{JsBytes, RawBuffer};
+use schema::{JsMessage, MessageSchema};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::{Notify, RwLock};
+
+/// JS callback called with each received message
+///
+/// Its return value is ignored; a message is handed over once the previous
+/// call has returned.
+pub(crate) type MessageCallback =
+    ThreadsafeFunction<JsMessage, UnknownReturnValue, JsMessage, Status, false>;
+
+/// JS callback called with each Buffer received by
+/// [`AgenticNode::subscribe_raw`]
+pub(crate) type RawCallback =
+    ThreadsafeFunction<RawBuffer, UnknownReturnValue, RawBuffer, Status, false>;
+
+/// The live lifecycle node named `node`
+fn lifecycle_node(node: &str) -> Result<LifecycleHandle> {
+    agentic_robotics_core::graph::lifecycle_node(node).ok_or_else(|| {
+        Error::new(
+            Status::InvalidArg,
+            format!("No live lifecycle node named '{}'", node),
+        )
+    })
+}
+
+/// Longest [`AgenticNode::close`] waits for each publisher to flush
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// Node for creating publishers and subscribers
 #[napi]
 pub struct AgenticNode {
-    name: String,
+    node: Arc<Node>,
     publishers: Arc<RwLock<HashMap<String, Arc<Publisher<JsonValue>>>>>,
+    /// Publishers of typed and binary messages, see [`AgenticNode::publish`]
+    /// and [`AgenticNode::publish_raw`]
+    typed_publishers: RwLock<HashMap<String, Arc<Publisher<RawMessage>>>>,
     subscribers: Arc<RwLock<HashMap<String, Arc<Subscriber<JsonValue>>>>>,
+    next_subscription: AtomicU32,
+    resources: Arc<Resources>,
+}
+
+/// What calls into JS, torn down by [`AgenticNode::close`] and when the JS
+/// environment exits
+#[derive(Default)]
+struct Resources {
+    /// Callback subscriptions by handle and when they run, see
+    /// [`AgenticNode::subscribe`] and [`AgenticNode::spin`]
+    executor: Executor,
+    /// Servers of [`AgenticNode::create_service`] by service name
+    services: Mutex<HashMap<String, ServiceServer<JsonValue, JsonValue>>>,
+    /// Servers of [`AgenticNode::start_mcp_server`]
+    mcp_servers: Mutex<Vec<Arc<mcp::Served>>>,
+    /// Listeners of `parameterChanged`, see [`AgenticNode::on`]
+    parameter_listeners: Mutex<Vec<ParameterListener>>,
+}
+
+impl Resources {
+    /// Tear everything down without waiting for it
+    fn release(&self) {
+        self.executor.clear();
+        self.services.lock().unwrap().clear();
+        for served in self.mcp_servers.lock().unwrap().drain(..) {
+            served.stop_now();
+        }
+        self.parameter_listeners.lock().unwrap().clear();
+    }
+}
+
+/// Options of [`AgenticNode::publish`]
+#[napi(object)]
+pub struct PublishOptions {
+    /// Registered message type to check the message against
+    #[napi(js_name = "type")]
+    pub type_name: Option<String>,
+}
+
+/// Options of [`AgenticNode::publish_raw`]
+#[napi(object)]
+pub struct PublishRawOptions {
+    /// Type name of the message the bytes encode
+    #[napi(js_name = "type")]
+    pub type_name: String,
+    /// Serialization of the bytes: `cdr` (default), `json`, `rkyv`,
+    /// `protobuf` or `messagepack`
+    pub format: Option<String>,
+}
+
+/// Options of [`AgenticNode::subscribe_raw`]
+#[napi(object)]
+pub struct SubscribeRawOptions {
+    /// Messages queued while the callback is busy before the oldest is
+    /// dropped (default 10)
+    pub queue_depth: Option<u32>,
+    /// Hand over a copy of each message instead of a Buffer over the
+    /// received bytes
+    pub copy: Option<bool>,
+}
+
+/// Options of [`AgenticNode::subscribe`]
+#[napi(object)]
+pub struct SubscribeOptions {
+    /// Messages queued while the callback is busy before the oldest is
+    /// dropped (default 10)
+    pub queue_depth: Option<u32>,
+    /// Registered message type of the messages
+    #[napi(js_name = "type")]
+    pub type_name: Option<String>,
+}
+
+/// Options of [`AgenticNode::messages`]
+#[napi(object)]
+pub struct MessagesOptions {
+    /// Messages queued between iterations before the oldest is dropped
+    /// (default 10)
+    pub queue_depth: Option<u32>,
+    /// Reject an iteration that waits longer than this for a message
+    pub timeout_ms: Option<u32>,
+    /// Registered message type of the messages
+    #[napi(js_name = "type")]
+    pub type_name: Option<String>,
+}
+
+/// Options of [`AgenticNode::call_service`]
+#[napi(object)]
+pub struct CallServiceOptions {
+    /// Reject if no response arrives within this time (default 10000)
+    pub timeout_ms: Option<u32>,
+}
+
+/// Options of [`AgenticNode::spin_once`]
+#[napi(object)]
+pub struct SpinOnceOptions {
+    /// Wait up to this long for a message if none is queued (default 0)
+    pub timeout_ms: Option<u32>,
+}
+
+/// QoS of a subscription queueing `queue_depth` messages, 10 by default
+fn queue_qos(queue_depth: Option<u32>) -> QosProfile {
+    let mut qos = QosProfile::default();
+    if let Some(depth) = queue_depth {
+        qos.history_depth = depth.max(1) as usize;
+    }
+    qos
+}
+
+/// Subscriber of [`AgenticNode::subscribe`] and [`AgenticNode::messages`]
+pub(crate) enum Inbox {
+    Json(Subscriber<JsonValue>),
+    /// Messages of a registered type, see [`AgenticNode::register_type`]
+    Typed(Subscriber<RawMessage>, Arc<MessageSchema>),
+}
+
+impl Inbox {
+    fn new(
+        node: &Node,
+        topic: &str,
+        queue_depth: Option<u32>,
+        type_name: Option<&str>,
+    ) -> Result<Self> {
+        let qos = queue_qos(queue_depth);
+        let subscribe_failed = |e: agentic_robotics_core::Error| {
+            Error::from_reason(format!("Failed to subscribe: {}", e))
+        };
+        Ok(match type_name {
+            Some(type_name) => {
+                let schema = schema::resolve(type_name).map_err(Error::from_reason)?;
+                let subscriber = node
+                    .create_subscriber_with_qos::<RawMessage>(topic, qos)
+                    .map_err(subscribe_failed)?;
+                Inbox::Typed(subscriber, schema)
+            }
+            None => Inbox::Json(
+                node.create_subscriber_with_qos::<JsonValue>(topic, qos)
+                    .map_err(subscribe_failed)?,
+            ),
+        })
+    }
+
+    fn topic(&self) -> &str {
+        match self {
+            Inbox::Json(subscriber) => subscriber.topic(),
+            Inbox::Typed(subscriber, _) => subscriber.topic(),
+        }
+    }
+
+    /// Wait for the next message
+    ///
+    /// Typed messages that do not decode as their type are skipped.
+    async fn recv(&self) -> agentic_robotics_core::Result<JsMessage> {
+        let (subscriber, schema) = match self {
+            Inbox::Json(subscriber) => {
+                return Ok(JsMessage {
+                    value: subscriber.recv().await?,
+                    schema: None,
+                })
+            }
+            Inbox::Typed(subscriber, schema) => (subscriber, schema),
+        };
+        loop {
+            let raw = subscriber.recv().await?;
+            if let Some(message) = Self::decode(&raw, schema) {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Take the next queued message, skipping typed messages as
+    /// [`recv`](Self::recv) does
+    fn try_recv(&self) -> Option<JsMessage> {
+        let (subscriber, schema) = match self {
+            Inbox::Json(subscriber) => {
+                return subscriber.try_recv().ok().flatten().map(|value| JsMessage {
+                    value,
+                    schema: None,
+                })
+            }
+            Inbox::Typed(subscriber, schema) => (subscriber, schema),
+        };
+        while let Ok(Some(raw)) = subscriber.try_recv() {
+            if let Some(message) = Self::decode(&raw, schema) {
+                return Some(message);
+            }
+        }
+        None
+    }
+
+    /// A typed message, if it is one of the type
+    fn decode(raw: &RawMessage, schema: &Arc<MessageSchema>) -> Option<JsMessage> {
+        let value = match schema.rust_type {
+            Some(rust_type) => rust_type.to_json(raw).ok(),
+            None => raw
+                .decode::<JsonValue>()
+                .ok()
+                .and_then(|value| schema.fields.check(&value).ok()),
+        }?;
+        Some(JsMessage {
+            value,
+            schema: Some(schema.clone()),
+        })
+    }
+
+    /// Messages queued and messages dropped from the full queue
+    fn queue_stats(&self) -> (usize, u64) {
+        match self {
+            Inbox::Json(subscriber) => (subscriber.pending(), subscriber.dropped_count()),
+            Inbox::Typed(subscriber, _) => (subscriber.pending(), subscriber.dropped_count()),
+        }
+    }
+
+    /// Wait on the subscriber with `set`
+    fn watch(&self, set: &mut WaitSet) -> WaitKey {
+        match self {
+            Inbox::Json(subscriber) => set.add_subscriber(subscriber),
+            Inbox::Typed(subscriber, _) => set.add_subscriber(subscriber),
+        }
+    }
 }
 
 #[napi]
 impl AgenticNode {
-    /// Create a new node
+    /// Create a new node from JS
+    ///
+    /// As [`new`](Self::new), also tearing the node down when the JS
+    /// environment exits, so a process exiting without
+    /// [`close`](Self::close) does not call into a torn-down environment.
     #[napi(constructor)]
-    pub fn new(name: String) -> Result<Self> {
+    pub fn construct(
+        env: Env,
+        name: String,
+        namespace: Option<String>,
+        remaps: Option<Vec<String>>,
+    ) -> Result<Self> {
+        let node = Self::new(name, namespace, remaps)?;
+        let held = (Arc::downgrade(&node.node), Arc::downgrade(&node.resources));
+        env.add_env_cleanup_hook(held, |(node, resources): (Weak<Node>, Weak<Resources>)| {
+            if let Some(node) = node.upgrade() {
+                node.shutdown();
+            }
+            if let Some(resources) = resources.upgrade() {
+                resources.release();
+            }
+        })?;
+        Ok(node)
+    }
+
+    /// Create a new node
+    ///
+    /// Relative topic names are resolved against `namespace` (the root by
+    /// default), then `remaps` rules such as `"cmd_vel:=robot1/cmd_vel"` are
+    /// applied.
+    pub fn new(
+        name: String,
+        namespace: Option<String>,
+        remaps: Option<Vec<String>>,
+    ) -> Result<Self> {
+        let remaps = remaps
+            .unwrap_or_default()
+            .iter()
+            .map(|rule| rule.parse::<Remap>())
+            .collect::<agentic_robotics_core::Result<Vec<_>>>()
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        let node = Node::with_namespace(name, namespace.as_deref().unwrap_or("/"), remaps)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+
         Ok(Self {
-            name,
+            node: Arc::new(node),
             publishers: Arc::new(RwLock::new(HashMap::new())),
+            typed_publishers: RwLock::new(HashMap::new()),
             subscribers: Arc::new(RwLock::new(HashMap::new())),
+            next_subscription: AtomicU32::new(1),
+            resources: Arc::new(Resources::default()),
         })
     }
 
+    /// Fail if the node was closed
+    fn ensure_open(&self) -> Result<()> {
+        if self.node.is_shutdown() {
+            return Err(Error::new(
+                Status::Closing,
+                format!("Node '{}' is closed", self.node.name()),
+            ));
+        }
+        Ok(())
+    }
+
     /// Get node name
     #[napi]
     pub fn get_name(&self) -> String {
-        self.name.clone()
+        self.node.name().to_string()
+    }
+
+    /// Get node namespace
+    #[napi]
+    pub fn get_namespace(&self) -> String {
+        self.node.namespace().to_string()
     }
 
     /// Create a publisher for a topic
     #[napi]
     pub async fn create_publisher(&self, topic: String) -> Result<AgenticPublisher> {
+        self.ensure_open()?;
         // Use JSON format for serde_json::Value to avoid CDR serialization issues
-        let publisher = Arc::new(Publisher::<JsonValue>::with_format(
-            topic.clone(),
-            agentic_robotics_core::serialization::Format::Json,
-        ));
+        let publisher = self
+            .node
+            .create_publisher_with_format::<JsonValue>(&topic, Format::Json)
+            .map_err(|e| Error::from_reason(format!("Failed to create publisher: {}", e)))?;
+        let topic = publisher.topic().to_string();
+        let publisher = Arc::new(publisher);
 
         let mut publishers = self.publishers.write().await;
         publishers.insert(topic.clone(), publisher.clone());
@@ -59,7 +393,13 @@ impl AgenticNode {
     /// Create a subscriber for a topic
     #[napi]
     pub async fn create_subscriber(&self, topic: String) -> Result<AgenticSubscriber> {
-        let subscriber = Arc::new(Subscriber::<JsonValue>::new(topic.clone()));
+        self.ensure_open()?;
+        let subscriber = self
+            .node
+            .create_subscriber::<JsonValue>(&topic)
+            .map_err(|e| Error::from_reason(format!("Failed to create subscriber: {}", e)))?;
+        let topic = subscriber.topic().to_string();
+        let subscriber = Arc::new(subscriber);
 
         let mut subscribers = self.subscribers.write().await;
         subscribers.insert(topic.clone(), subscriber.clone());
@@ -70,6 +410,562 @@ impl AgenticNode {
         })
     }
 
+    /// Register the message type `name` for publishing and receiving
+    ///
+    /// `fields` maps field names to types such as `"f64[3]"` or `"i64"`, see
+    /// the [`schema`] module. Without `fields`, the type is taken from the
+    /// Rust message registry, e.g. `ros3_msgs/RobotState`.
+    #[napi(ts_args_type = "name: string, fields?: Record<string, FieldSpec>")]
+    pub fn register_type(&self, name: String, fields: Option<JsonValue>) -> Result<()> {
+        self.ensure_open()?;
+        schema::register(&name, fields.as_ref())
+            .map(|_| ())
+            .map_err(Error::from_reason)
+    }
+
+    /// Publish `data`, any JSON-compatible value, on `topic`
+    ///
+    /// The node's publisher for the topic is created on first use and
+    /// reused afterwards. With `options.type`, `data` is first checked
+    /// against the registered type, rejecting with the offending field;
+    /// types of the Rust registry are published as the Rust message.
+    #[napi]
+    pub async fn publish(
+        &self,
+        topic: String,
+        data: JsonValue,
+        options: Option<PublishOptions>,
+    ) -> Result<()> {
+        self.ensure_open()?;
+        let resolved = self
+            .node
+            .resolve(&topic)
+            .map_err(|e| Error::from_reason(e.to_string()))?
+            .to_string();
+        if let Some(type_name) = options.and_then(|options| options.type_name) {
+            return self.publish_typed(resolved, &type_name, data).await;
+        }
+        let existing = self.publishers.read().await.get(&resolved).cloned();
+        let publisher = match existing {
+            Some(publisher) => publisher,
+            None => self.create_publisher(topic).await?.inner,
+        };
+
+        publisher
+            .publish(&data)
+            .await
+            .map_err(|e| Error::from_reason(format!("Publish failed: {}", e)))
+    }
+
+    async fn publish_typed(&self, topic: String, type_name: &str, data: JsonValue) -> Result<()> {
+        let schema = schema::resolve(type_name).map_err(Error::from_reason)?;
+        let value = schema.fields.check(&data).map_err(|e| {
+            Error::new(
+                Status::InvalidArg,
+                format!("Invalid {} message: {}", type_name, e),
+            )
+        })?;
+        let raw = match schema.rust_type {
+            Some(rust_type) => {
+                // Generic JSON values have no fixed layout for CDR
+                let format = if type_name == JsonValue::type_name() {
+                    Format::Json
+                } else {
+                    Format::Cdr
+                };
+                rust_type.from_json(value, format).map_err(|e| {
+                    Error::new(
+                        Status::InvalidArg,
+                        format!("Invalid {} message: {}", type_name, e),
+                    )
+                })?
+            }
+            None => RawMessage {
+                type_name: schema.name.clone(),
+                format: Format::Json,
+                data: serde_json::to_vec(&value)
+                    .map_err(|e| Error::from_reason(format!("Serialization failed: {}", e)))?,
+            },
+        };
+
+        self.raw_publisher(topic)
+            .await?
+            .publish(&raw)
+            .await
+            .map_err(|e| Error::from_reason(format!("Publish failed: {}", e)))
+    }
+
+    /// The node's publisher of serialized messages on the resolved `topic`
+    async fn raw_publisher(&self, topic: String) -> Result<Arc<Publisher<RawMessage>>> {
+        if let Some(publisher) = self.typed_publishers.read().await.get(&topic) {
+            return Ok(publisher.clone());
+        }
+        let publisher = self
+            .node
+            .create_publisher::<RawMessage>(&topic)
+            .map_err(|e| Error::from_reason(format!("Failed to create publisher: {}", e)))?;
+        let publisher = Arc::new(publisher);
+        self.typed_publishers
+            .write()
+            .await
+            .insert(topic, publisher.clone());
+        Ok(publisher)
+    }
+
+    /// Publish the bytes of `data`, a message of `options.type` already
+    /// serialized, on `topic` without copying them
+    ///
+    /// The Buffer is shared with in-process subscribers and transports
+    /// until they are done with it; it should not be modified meanwhile.
+    #[napi(
+        ts_args_type = "topic: string, data: Buffer | Uint8Array | ArrayBuffer, options: PublishRawOptions"
+    )]
+    pub async fn publish_raw(
+        &self,
+        topic: String,
+        data: Buffer,
+        options: PublishRawOptions,
+    ) -> Result<()> {
+        self.ensure_open()?;
+        let format = match options.format.as_deref() {
+            Some(format) => raw::parse_format(format)?,
+            None => Format::Cdr,
+        };
+        let resolved = self
+            .node
+            .resolve(&topic)
+            .map_err(|e| Error::from_reason(e.to_string()))?
+            .to_string();
+        let message = SharedRawMessage {
+            type_name: options.type_name,
+            format,
+            data: SharedBytes::new(JsBytes(data)),
+        };
+        self.raw_publisher(resolved)
+            .await?
+            .publish_shared(&message)
+            .await
+            .map_err(|e| Error::from_reason(format!("Publish failed: {}", e)))
+    }
+
+    /// Call `callback` with the bytes of every message received on `topic`
+    ///
+    /// Messages of any type are handed over as they were serialized, one at
+    /// a time as for [`subscribe`](Self::subscribe). Each Buffer is a view
+    /// of the received bytes, shared with other subscribers and kept alive
+    /// as long as JS holds it, so it must not be modified; with
+    /// `options.copy` each callback gets its own copy instead. Returns a
+    /// handle for [`unsubscribe`](Self::unsubscribe).
+    #[napi(
+        ts_args_type = "topic: string, callback: (data: Buffer) => void, options?: SubscribeRawOptions"
+    )]
+    pub fn subscribe_raw(
+        &self,
+        topic: String,
+        callback: RawCallback,
+        options: Option<SubscribeRawOptions>,
+    ) -> Result<u32> {
+        self.ensure_open()?;
+        let (queue_depth, copy) = options
+            .map(|options| (options.queue_depth, options.copy.unwrap_or(false)))
+            .unwrap_or_default();
+        let subscriber = self
+            .node
+            .create_subscriber_with_qos::<RawMessage>(&topic, queue_qos(queue_depth))
+            .map_err(|e| Error::from_reason(format!("Failed to subscribe: {}", e)))?;
+
+        let handle = self.next_subscription.fetch_add(1, Ordering::Relaxed);
+        self.resources.executor.add(
+            handle,
+            Dispatch::Raw {
+                subscriber,
+                copy,
+                callback,
+            },
+        );
+        Ok(handle)
+    }
+
+    /// Call `callback` with every message received on `topic`
+    ///
+    /// Messages are handed to the callback one at a time. While it is busy,
+    /// up to `options.queueDepth` messages wait; beyond that the oldest are
+    /// dropped, so a slow callback sees the latest messages. With
+    /// `options.type`, only messages of that type are handed over, with
+    /// its `i64` and `u64` fields as `BigInt`. Returns a handle for
+    /// [`unsubscribe`](Self::unsubscribe).
+    #[napi(
+        ts_args_type = "topic: string, callback: (message: any) => void, options?: SubscribeOptions"
+    )]
+    pub fn subscribe(
+        &self,
+        topic: String,
+        callback: MessageCallback,
+        options: Option<SubscribeOptions>,
+    ) -> Result<u32> {
+        self.ensure_open()?;
+        let (queue_depth, type_name) = options
+            .map(|options| (options.queue_depth, options.type_name))
+            .unwrap_or_default();
+        let inbox = Inbox::new(&self.node, &topic, queue_depth, type_name.as_deref())?;
+
+        let handle = self.next_subscription.fetch_add(1, Ordering::Relaxed);
+        self.resources
+            .executor
+            .add(handle, Dispatch::Messages { inbox, callback });
+        Ok(handle)
+    }
+
+    /// Iterate over the messages received on `topic` with `for await`
+    ///
+    /// Messages are queued from this call on, up to `options.queueDepth`
+    /// before the oldest are dropped. The subscription ends when the loop
+    /// exits, including by `break`, and the iteration ends when the node
+    /// shuts down. `options.type` selects messages of a registered type as
+    /// for [`subscribe`](Self::subscribe).
+    #[napi]
+    pub fn messages(
+        &self,
+        topic: String,
+        options: Option<MessagesOptions>,
+    ) -> Result<MessageStream> {
+        self.ensure_open()?;
+        let (queue_depth, timeout_ms, type_name) = options
+            .map(|options| (options.queue_depth, options.timeout_ms, options.type_name))
+            .unwrap_or_default();
+        let inbox = Inbox::new(&self.node, &topic, queue_depth, type_name.as_deref())?;
+
+        Ok(MessageStream {
+            topic: inbox.topic().to_string(),
+            inbox: Mutex::new(Some(Arc::new(inbox))),
+            finished: Arc::new(Notify::new()),
+            timeout: timeout_ms.map(|ms| Duration::from_millis(ms as u64)),
+        })
+    }
+
+    /// End a subscription made with [`subscribe`](Self::subscribe) or
+    /// [`subscribe_raw`](Self::subscribe_raw)
+    ///
+    /// Returns `false` if `handle` is unknown or already unsubscribed.
+    #[napi]
+    pub fn unsubscribe(&self, handle: u32) -> bool {
+        self.resources.executor.remove(handle)
+    }
+
+    /// Serve the service `name` with `handler`
+    ///
+    /// `handler` is called with each request and returns the response or a
+    /// promise of it; calls are handled concurrently. If it throws or
+    /// rejects, the call fails with the error's message. Fails if the
+    /// service already has a server.
+    #[napi(ts_args_type = "name: string, handler: (request: any) => any")]
+    pub fn create_service(&self, name: String, handler: Handler) -> Result<()> {
+        self.ensure_open()?;
+        let handler = Arc::new(handler);
+        let service = name.clone();
+        let server = napi::bindgen_prelude::within_runtime_if_available(|| {
+            ServiceServer::new(name.clone(), move |request: JsonValue| {
+                let (handler, service) = (handler.clone(), service.clone());
+                async move {
+                    handler::call(&handler, request)
+                        .await
+                        .map_err(|reason| match reason {
+                            Some(reason) => {
+                                agentic_robotics_core::Error::ServiceFailed { service, reason }
+                            }
+                            None => agentic_robotics_core::Error::ServiceUnavailable(service),
+                        })
+                }
+            })
+        })
+        .map_err(|e| Error::from_reason(format!("Failed to create service: {}", e)))?;
+        self.resources.services.lock().unwrap().insert(name, server);
+        Ok(())
+    }
+
+    /// Stop serving a service made with [`create_service`](Self::create_service)
+    ///
+    /// Returns `false` if this node does not serve `name`.
+    #[napi]
+    pub fn destroy_service(&self, name: String) -> bool {
+        self.resources
+            .services
+            .lock()
+            .unwrap()
+            .remove(&name)
+            .is_some()
+    }
+
+    /// Call the service `name` with `request`, resolving to the response
+    ///
+    /// The server may be written in JavaScript or Rust; a Rust server's
+    /// request must deserialize as its request type. Rejects if the
+    /// service has no server, the server fails, or no response arrives
+    /// within `options.timeoutMs`.
+    #[napi]
+    pub async fn call_service(
+        &self,
+        name: String,
+        request: JsonValue,
+        options: Option<CallServiceOptions>,
+    ) -> Result<JsonValue> {
+        self.ensure_open()?;
+        let timeout = options.and_then(|options| options.timeout_ms).map_or(
+            ServiceClient::<JsonValue, JsonValue>::DEFAULT_TIMEOUT,
+            |ms| Duration::from_millis(ms as u64),
+        );
+        agentic_robotics_core::service::call_json(&name, request, timeout)
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Start an MCP server offering the standard ros3 tools for this node
+    ///
+    /// `options.tools` restricts the tools to the ones listed. The returned
+    /// handle registers JS tools, emits `toolCall` events and stops the
+    /// server; [`close`](Self::close) stops it as well.
+    #[napi]
+    pub async fn start_mcp_server(
+        &self,
+        options: Option<McpServerOptions>,
+    ) -> Result<McpServerHandle> {
+        self.ensure_open()?;
+        let options = options.unwrap_or(McpServerOptions {
+            transport: None,
+            port: None,
+            host: None,
+            tools: None,
+            name: None,
+        });
+        let handle = mcp::start(self.node.clone(), options).await?;
+        self.resources
+            .mcp_servers
+            .lock()
+            .unwrap()
+            .push(handle.served.clone());
+        Ok(handle)
+    }
+
+    /// Declare the parameter `name` with its `default` value
+    ///
+    /// The default's JS type sets the parameter's: a `bigint` declares an
+    /// integer, a `number` a double, and a boolean, string or `number[]`
+    /// the like. Fails if `name` was already declared or the default
+    /// violates `descriptor`.
+    #[napi(
+        ts_args_type = "name: string, default: ParameterValue, descriptor?: ParameterDescriptorOptions"
+    )]
+    pub fn declare_parameter(
+        &self,
+        name: String,
+        default: JsParameter,
+        descriptor: Option<ParameterDescriptorOptions>,
+    ) -> Result<()> {
+        self.ensure_open()?;
+        let parameters = self.node.parameters();
+        let descriptor = descriptor.map(Into::into).unwrap_or_default();
+        parameters
+            .declare(&name, default.0, descriptor)
+            .map_err(params::invalid)?;
+
+        let resources = Arc::downgrade(&self.resources);
+        let changed = name.clone();
+        parameters
+            .on_change(&name, move |value| {
+                let Some(resources) = resources.upgrade() else {
+                    return;
+                };
+                for listener in resources.parameter_listeners.lock().unwrap().iter() {
+                    let change = (changed.clone(), JsParameter(value.clone()));
+                    listener.call(change.into(), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+            })
+            .map_err(params::invalid)
+    }
+
+    /// Current value of the parameter `name`, `null` if it was not declared
+    #[napi(ts_return_type = "ParameterValue | null")]
+    pub fn get_parameter(&self, name: String) -> Result<Option<JsParameter>> {
+        self.ensure_open()?;
+        Ok(self.node.parameters().get_value(&name).map(JsParameter))
+    }
+
+    /// Change the parameter `name`
+    ///
+    /// `value` must have the declared type, except that an integer
+    /// parameter also takes a safe integer `number`. Throws with the
+    /// failed constraint if the descriptor rejects it, e.g.
+    /// "Invalid parameter 'arm.gain': value outside [0, 10]".
+    #[napi(ts_args_type = "name: string, value: ParameterValue")]
+    pub fn set_parameter(&self, name: String, value: JsParameter) -> Result<()> {
+        self.ensure_open()?;
+        params::set(self.node.parameters(), &name, value)
+    }
+
+    /// Change the parameter `name` of the live node named `node`, as
+    /// parameter tools do
+    ///
+    /// The node may be written in JavaScript or Rust; its
+    /// `parameterChanged` listeners are notified either way.
+    #[napi(ts_args_type = "node: string, name: string, value: ParameterValue")]
+    pub fn set_node_parameter(node: String, name: String, value: JsParameter) -> Result<()> {
+        let server = agentic_robotics_core::graph::parameter_server(&node).ok_or_else(|| {
+            Error::new(Status::InvalidArg, format!("No live node named '{}'", node))
+        })?;
+        params::set(&server, &name, value)
+    }
+
+    /// Lifecycle state of the live lifecycle node `node`: `unconfigured`,
+    /// `inactive`, `active` or `finalized`
+    #[napi(ts_return_type = "LifecycleState")]
+    pub fn get_lifecycle_state(node: String) -> Result<String> {
+        Ok(lifecycle_node(&node)?.state().to_string())
+    }
+
+    /// Run `transition` on the live lifecycle node `node`, resolving with its
+    /// new state
+    ///
+    /// Rejects if the transition does not start from the node's state or its
+    /// hook fails; the node keeps its state either way.
+    #[napi(
+        ts_args_type = "node: string, transition: LifecycleTransition",
+        ts_return_type = "Promise<LifecycleState>"
+    )]
+    pub async fn change_lifecycle_state(node: String, transition: String) -> Result<String> {
+        let lifecycle = lifecycle_node(&node)?;
+        let transition = Transition::from_name(&transition).ok_or_else(|| {
+            Error::new(
+                Status::InvalidArg,
+                format!("Unknown lifecycle transition '{}'", transition),
+            )
+        })?;
+        lifecycle
+            .trigger(transition)
+            .await
+            .map(|state| state.to_string())
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Listen to `event`; `parameterChanged` is emitted with the name and
+    /// new value of every declared parameter that changes, whoever sets it
+    ///
+    /// Listeners are called asynchronously, after the change was made.
+    #[napi(
+        ts_args_type = "event: 'parameterChanged', listener: (name: string, value: ParameterValue) => void"
+    )]
+    pub fn on(
+        &self,
+        event: String,
+        listener: Function<params::ParameterChange, UnknownReturnValue>,
+    ) -> Result<()> {
+        self.ensure_open()?;
+        if event != "parameterChanged" {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!("Unknown event '{}'; use 'parameterChanged'", event),
+            ));
+        }
+        let listener = listener
+            .build_threadsafe_function::<params::ParameterChange>()
+            .weak::<true>()
+            .build()?;
+        self.resources
+            .parameter_listeners
+            .lock()
+            .unwrap()
+            .push(listener);
+        Ok(())
+    }
+
+    /// Run subscription callbacks in the background as messages arrive
+    ///
+    /// Nodes spin from their creation, so this only resumes after
+    /// [`stop_spinning`](Self::stop_spinning). Spinning again does nothing.
+    #[napi]
+    pub fn spin(&self) -> Result<()> {
+        self.ensure_open()?;
+        self.resources.executor.spin();
+        Ok(())
+    }
+
+    /// Stop running subscription callbacks in the background
+    ///
+    /// Resolves once no callback is called by spinning any longer. Messages
+    /// then wait in their subscription's queue, up to its `queueDepth`,
+    /// until [`spin_once`](Self::spin_once) or [`spin`](Self::spin) hands
+    /// them over.
+    #[napi]
+    pub async fn stop_spinning(&self) {
+        self.resources.executor.stop_spinning().await;
+    }
+
+    /// Hand each subscription with a queued message its oldest one, once
+    ///
+    /// With `options.timeoutMs`, waits up to that long for a message if
+    /// none is queued. Resolves to the number of callbacks called, once all
+    /// of them have returned, so steps are deterministic, e.g. from a render
+    /// loop. Throws while the node is spinning.
+    #[napi(ts_return_type = "Promise<number>")]
+    pub fn spin_once<'env>(
+        &self,
+        env: &'env Env,
+        options: Option<SpinOnceOptions>,
+    ) -> Result<PromiseRaw<'env, u32>> {
+        self.ensure_open()?;
+        let timeout = options
+            .and_then(|options| options.timeout_ms)
+            .map(|ms| Duration::from_millis(ms as u64));
+        let step = self.resources.executor.spin_once(timeout).ok_or_else(|| {
+            Error::new(
+                Status::GenericFailure,
+                format!(
+                    "Node '{}' is spinning; call stopSpinning() before spinOnce()",
+                    self.node.name()
+                ),
+            )
+        })?;
+        env.spawn_future(async move { Ok(step.await) })
+    }
+
+    /// Statistics of the executor running this node's subscription
+    /// callbacks
+    #[napi]
+    pub fn executor_stats(&self) -> ExecutorStats {
+        self.resources.executor.stats()
+    }
+
+    /// Tear down every publisher, subscriber, service and MCP server of
+    /// this node
+    ///
+    /// Publishers are flushed first, for up to a second each. Resolves once
+    /// no callback of the node will be called again; other methods then
+    /// fail with "Node '<name>' is closed". Closing again does nothing.
+    #[napi]
+    pub async fn close(&self) {
+        for (_, publisher) in self.publishers.write().await.drain() {
+            let _ = tokio::time::timeout(FLUSH_TIMEOUT, publisher.flush()).await;
+        }
+        for (_, publisher) in self.typed_publishers.write().await.drain() {
+            let _ = tokio::time::timeout(FLUSH_TIMEOUT, publisher.flush()).await;
+        }
+        self.node.shutdown();
+
+        self.resources.executor.finish().await;
+        self.resources.services.lock().unwrap().clear();
+        let mcp_servers = std::mem::take(&mut *self.resources.mcp_servers.lock().unwrap());
+        for served in mcp_servers {
+            served.stop().await;
+        }
+        self.resources.parameter_listeners.lock().unwrap().clear();
+        self.subscribers.write().await.clear();
+    }
+
+    /// Same as [`close`](Self::close)
+    #[napi]
+    pub async fn shutdown(&self) {
+        self.close().await;
+    }
+
     /// Get library version
     #[napi]
     pub fn get_version() -> String {
@@ -79,8 +975,13 @@ impl AgenticNode {
     /// List all active publishers
     #[napi]
     pub async fn list_publishers(&self) -> Vec<String> {
-        let publishers = self.publishers.read().await;
-        publishers.keys().cloned().collect()
+        let mut topics: Vec<String> = self.publishers.read().await.keys().cloned().collect();
+        for topic in self.typed_publishers.read().await.keys() {
+            if !topics.contains(topic) {
+                topics.push(topic.clone());
+            }
+        }
+        topics
     }
 
     /// List all active subscribers
@@ -91,6 +992,94 @@ impl AgenticNode {
     }
 }
 
+/// Messages of a topic as a JS async iterator, see [`AgenticNode::messages`]
+///
+/// `index.js` adds `[Symbol.asyncIterator]`, returning the stream itself.
+#[napi]
+pub struct MessageStream {
+    topic: String,
+    /// Dropped by `return()`, ending the subscription
+    inbox: Mutex<Option<Arc<Inbox>>>,
+    /// Wakes a pending `next()` when `return()` is called
+    finished: Arc<Notify>,
+    timeout: Option<Duration>,
+}
+
+/// Result of an async iterator step
+#[napi(object, object_from_js = false)]
+pub struct MessageStreamResult {
+    pub done: bool,
+    pub value: Option<JsMessage>,
+}
+
+impl MessageStreamResult {
+    fn done() -> Self {
+        Self {
+            done: true,
+            value: None,
+        }
+    }
+}
+
+#[napi]
+impl MessageStream {
+    /// Get topic name
+    #[napi]
+    pub fn get_topic(&self) -> String {
+        self.topic.clone()
+    }
+
+    /// Wait for the next message
+    ///
+    /// Done once the stream is returned or the node shuts down; rejects if
+    /// no message arrives within the stream's timeout.
+    #[napi]
+    pub async fn next(&self) -> Result<MessageStreamResult> {
+        // Created first so a `return()` from now on wakes this call
+        let finished = self.finished.notified();
+        let Some(inbox) = self.inbox.lock().unwrap().clone() else {
+            return Ok(MessageStreamResult::done());
+        };
+        let received = async {
+            match self.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, inbox.recv())
+                    .await
+                    .map_err(|_| {
+                        Error::from_reason(format!(
+                            "No message on {} within {} ms",
+                            self.topic,
+                            timeout.as_millis()
+                        ))
+                    }),
+                None => Ok(inbox.recv().await),
+            }
+        };
+
+        tokio::select! {
+            _ = finished => Ok(MessageStreamResult::done()),
+            received = received => match received? {
+                Ok(message) => Ok(MessageStreamResult {
+                    done: false,
+                    value: Some(message),
+                }),
+                // The node shut down
+                Err(_) => {
+                    self.inbox.lock().unwrap().take();
+                    Ok(MessageStreamResult::done())
+                }
+            },
+        }
+    }
+
+    /// End the subscription, as `for await` does when the loop exits early
+    #[napi(js_name = "return")]
+    pub async fn finish(&self) -> MessageStreamResult {
+        self.inbox.lock().unwrap().take();
+        self.finished.notify_waiters();
+        MessageStreamResult::done()
+    }
+}
+
 /// Publisher for sending messages to a topic
 #[napi]
 pub struct AgenticPublisher {
@@ -172,7 +1161,7 @@ impl AgenticSubscriber {
     pub async fn recv(&self) -> Result<String> {
         let msg = self
             .inner
-            .recv_async()
+            .recv()
             .await
             .map_err(|e| Error::from_reason(format!("Receive failed: {}", e)))?;
 
@@ -186,23 +1175,24 @@ impl AgenticSubscriber {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use agentic_robotics_core::ParameterValue;
 
     #[tokio::test]
     async fn test_node_creation() {
-        let node = AgenticNode::new("test_node".to_string()).unwrap();
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
         assert_eq!(node.get_name(), "test_node");
     }
 
     #[tokio::test]
     async fn test_create_publisher() {
-        let node = AgenticNode::new("test_node".to_string()).unwrap();
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
         let publisher = node.create_publisher("/test".to_string()).await.unwrap();
         assert_eq!(publisher.get_topic(), "/test");
     }
 
     #[tokio::test]
     async fn test_publish() {
-        let node = AgenticNode::new("test_node".to_string()).unwrap();
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
         let publisher = node.create_publisher("/test".to_string()).await.unwrap();
 
         let result = publisher.publish(r#"{"message": "hello"}"#.to_string()).await;
@@ -212,16 +1202,101 @@ mod tests {
         assert_eq!(stats.messages, 1);
     }
 
+    #[tokio::test]
+    async fn test_publish_reaches_subscriber() {
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
+        let subscriber = node.create_subscriber("/test/roundtrip".to_string()).await.unwrap();
+        let publisher = node.create_publisher("/test/roundtrip".to_string()).await.unwrap();
+
+        publisher.publish(r#"{"message":"hello"}"#.to_string()).await.unwrap();
+
+        let received = subscriber.recv().await.unwrap();
+        assert_eq!(received, r#"{"message":"hello"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_node_publish_reuses_publisher() {
+        let node = AgenticNode::new("test_node".to_string(), Some("/robot".to_string()), None)
+            .unwrap();
+        let subscriber = node.create_subscriber("/robot/chatter".to_string()).await.unwrap();
+
+        node.publish("chatter".to_string(), serde_json::json!({ "n": 1 }), None)
+            .await
+            .unwrap();
+        node.publish("chatter".to_string(), serde_json::json!({ "n": 2 }), None)
+            .await
+            .unwrap();
+
+        assert_eq!(node.list_publishers().await, ["/robot/chatter"]);
+        assert_eq!(subscriber.recv().await.unwrap(), r#"{"n":1}"#);
+        assert_eq!(subscriber.recv().await.unwrap(), r#"{"n":2}"#);
+        assert!(node
+            .publish("bad topic".to_string(), JsonValue::Null, None)
+            .await
+            .is_err());
+        assert!(!node.unsubscribe(1));
+    }
+
+    #[tokio::test]
+    async fn test_message_stream() {
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
+        let stream = node.messages("/test/stream".to_string(), None).unwrap();
+        node.publish("/test/stream".to_string(), serde_json::json!(1), None)
+            .await
+            .unwrap();
+
+        let first = stream.next().await.unwrap();
+        assert!(!first.done);
+        assert_eq!(first.value.unwrap().value, serde_json::json!(1));
+
+        node.shutdown().await;
+        assert!(stream.next().await.unwrap().done);
+    }
+
+    #[tokio::test]
+    async fn test_call_rust_service() {
+        use agentic_robotics_core::RobotState;
+
+        let _server = ServiceServer::new("test/node/advance", |state: RobotState| async move {
+            Ok(RobotState {
+                timestamp: state.timestamp + 1,
+                ..state
+            })
+        })
+        .unwrap();
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
+
+        let request = serde_json::json!({ "position": [1.0, 2.0, 3.0], "velocity": [0.0, 0.0, 0.0], "timestamp": 41 });
+        let response = node
+            .call_service("test/node/advance".to_string(), request, None)
+            .await
+            .unwrap();
+        assert_eq!(response["timestamp"], 42);
+        assert_eq!(response["position"], serde_json::json!([1.0, 2.0, 3.0]));
+
+        let invalid = node
+            .call_service("test/node/advance".to_string(), serde_json::json!({}), None)
+            .await;
+        assert!(invalid
+            .unwrap_err()
+            .reason
+            .contains("Invalid ros3_msgs/RobotState request"));
+        let missing = node
+            .call_service("test/node/missing".to_string(), JsonValue::Null, None)
+            .await;
+        assert!(missing.unwrap_err().reason.contains("Service unavailable"));
+    }
+
     #[tokio::test]
     async fn test_create_subscriber() {
-        let node = AgenticNode::new("test_node".to_string()).unwrap();
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
         let subscriber = node.create_subscriber("/test".to_string()).await.unwrap();
         assert_eq!(subscriber.get_topic(), "/test");
     }
 
     #[tokio::test]
     async fn test_list_publishers() {
-        let node = AgenticNode::new("test_node".to_string()).unwrap();
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
         node.create_publisher("/test1".to_string()).await.unwrap();
         node.create_publisher("/test2".to_string()).await.unwrap();
 
@@ -230,4 +1305,91 @@ mod tests {
         assert!(publishers.contains(&"/test1".to_string()));
         assert!(publishers.contains(&"/test2".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_namespace_and_remap() {
+        let node = AgenticNode::new(
+            "test_node".to_string(),
+            Some("/robot1".to_string()),
+            Some(vec!["scan:=/shared/scan".to_string()]),
+        )
+        .unwrap();
+        assert_eq!(node.get_namespace(), "/robot1");
+
+        let odom = node.create_publisher("odom".to_string()).await.unwrap();
+        assert_eq!(odom.get_topic(), "/robot1/odom");
+        let scan = node.create_subscriber("scan".to_string()).await.unwrap();
+        assert_eq!(scan.get_topic(), "/shared/scan");
+
+        assert!(node.create_publisher("bad topic".to_string()).await.is_err());
+        assert!(AgenticNode::new("n".to_string(), None, Some(vec!["scan".to_string()])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_endpoints() {
+        let node = AgenticNode::new("test_node".to_string(), None, None).unwrap();
+        let publisher = node.create_publisher("/test/shutdown".to_string()).await.unwrap();
+
+        node.shutdown().await;
+
+        assert!(node.list_publishers().await.is_empty());
+        assert!(publisher.publish("{}".to_string()).await.is_err());
+        assert!(node.create_publisher("/test/shutdown".to_string()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_close_rejects_calls() {
+        let node = AgenticNode::new("closing_node".to_string(), None, None).unwrap();
+        let stream = node.messages("/test/close".to_string(), None).unwrap();
+
+        node.close().await;
+        node.close().await;
+
+        assert!(stream.next().await.unwrap().done);
+        let rejected = node
+            .publish("/test/close".to_string(), serde_json::json!(2), None)
+            .await
+            .unwrap_err();
+        assert_eq!(rejected.reason, "Node 'closing_node' is closed");
+        assert!(node.messages("/test/close".to_string(), None).is_err());
+        assert!(node
+            .call_service("test/close".to_string(), JsonValue::Null, None)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_parameters_set_from_rust() {
+        let node = AgenticNode::new("param_node".to_string(), None, None).unwrap();
+        let descriptor = ParameterDescriptorOptions {
+            description: None,
+            read_only: None,
+            range: Some(params::ParameterRangeOptions {
+                min: 0.0,
+                max: 10.0,
+            }),
+        };
+        node.declare_parameter(
+            "gain".to_string(),
+            JsParameter(ParameterValue::Integer(1)),
+            Some(descriptor),
+        )
+        .unwrap();
+
+        let server = agentic_robotics_core::graph::parameter_server("param_node").unwrap();
+        server.set("gain", 7i64).unwrap();
+        let value = node.get_parameter("gain".to_string()).unwrap().unwrap();
+        assert_eq!(value.0, ParameterValue::Integer(7));
+
+        let rejected = node
+            .set_parameter(
+                "gain".to_string(),
+                JsParameter(ParameterValue::Double(11.0)),
+            )
+            .unwrap_err();
+        assert_eq!(
+            rejected.reason,
+            "Invalid parameter 'param_node.gain': value outside [0, 10]"
+        );
+    }
 }
@@ -0,0 +1,308 @@
+//! MCP servers started from JavaScript, see [`AgenticNode::start_mcp_server`]
+//!
+//! [`AgenticNode::start_mcp_server`]: crate::AgenticNode::start_mcp_server
+
+use crate::handler::{self, Handler};
+use agentic_robotics_core::Node;
+use agentic_robotics_mcp::http::{HttpConfig, HttpServer};
+use agentic_robotics_mcp::policy::AuditLog;
+use agentic_robotics_mcp::{server, McpServer, McpTool, ToolHandler};
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{
+    ThreadsafeFunction, ThreadsafeFunctionCallMode, UnknownReturnValue,
+};
+use napi_derive::napi;
+use serde_json::Value as JsonValue;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+/// JS listener of `toolCall` events
+///
+/// Weak, so listening does not keep the process alive by itself.
+type ToolCallListener =
+    ThreadsafeFunction<JsonValue, UnknownReturnValue, JsonValue, Status, false, true>;
+
+/// Options of [`AgenticNode::start_mcp_server`](crate::AgenticNode::start_mcp_server)
+#[napi(object)]
+pub struct McpServerOptions {
+    /// `stdio` (default), serving on the process's stdin and stdout, or
+    /// `http`
+    pub transport: Option<String>,
+    /// Port of the HTTP transport, 0 (default) for a free one
+    pub port: Option<u32>,
+    /// Address the HTTP transport listens on (default 127.0.0.1)
+    pub host: Option<String>,
+    /// The standard ros3 tools to offer, all of them by default
+    pub tools: Option<Vec<String>>,
+    /// Server name reported to clients, the node's name by default
+    pub name: Option<String>,
+}
+
+/// Statistics of a running MCP server
+#[napi(object)]
+pub struct McpServerStats {
+    pub transport: String,
+    /// Port of the HTTP transport
+    pub port: Option<u32>,
+    /// Whether the server still serves, i.e. `stop()` was not called
+    pub running: bool,
+    /// Connected clients
+    pub sessions: u32,
+    /// Tool calls made, including denied ones
+    pub tool_calls: u32,
+    /// Tool calls that failed
+    pub failed_calls: u32,
+}
+
+/// A transport serving the server, stopped when dropped
+enum Running {
+    Http(HttpServer),
+    Stdio(JoinHandle<()>),
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if let Running::Stdio(task) = self {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Default)]
+struct CallCounts {
+    calls: AtomicU32,
+    failed: AtomicU32,
+}
+
+/// A running MCP server serving the standard ros3 tools of a node
+#[napi]
+pub struct McpServerHandle {
+    /// Also held by the node, which stops it on close
+    pub(crate) served: Arc<Served>,
+}
+
+/// State of an [`McpServerHandle`]
+pub(crate) struct Served {
+    server: McpServer,
+    transport: String,
+    port: Option<u32>,
+    running: Mutex<Option<Running>>,
+    counts: Arc<CallCounts>,
+    listeners: Arc<Mutex<Vec<ToolCallListener>>>,
+    /// Tools registered from JS, unregistered by `stop()`
+    js_tools: Mutex<Vec<String>>,
+}
+
+/// Start an MCP server for `node`, see
+/// [`AgenticNode::start_mcp_server`](crate::AgenticNode::start_mcp_server)
+pub(crate) async fn start(node: Arc<Node>, options: McpServerOptions) -> Result<McpServerHandle> {
+    let counts = Arc::new(CallCounts::default());
+    let listeners = Arc::new(Mutex::new(Vec::<ToolCallListener>::new()));
+    let audit = {
+        let (counts, listeners) = (counts.clone(), listeners.clone());
+        AuditLog::new().observe(move |record| {
+            counts.calls.fetch_add(1, Ordering::Relaxed);
+            if record.failed {
+                counts.failed.fetch_add(1, Ordering::Relaxed);
+            }
+            let Ok(record) = serde_json::to_value(record) else {
+                return;
+            };
+            for listener in listeners.lock().unwrap().iter() {
+                listener.call(record.clone(), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        })
+    };
+    let name = options.name.unwrap_or_else(|| node.name().to_string());
+    let server = McpServer::new(name, env!("CARGO_PKG_VERSION")).with_audit_log(audit);
+
+    server
+        .register_ros3_tools(node)
+        .await
+        .map_err(|e| Error::from_reason(format!("Failed to register MCP tools: {}", e)))?;
+    if let Some(allowed) = options.tools {
+        let standard = server.tool_names().await;
+        if let Some(unknown) = allowed.iter().find(|tool| !standard.contains(tool)) {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!(
+                    "Unknown MCP tool '{}'; standard tools: {}",
+                    unknown,
+                    standard.join(", ")
+                ),
+            ));
+        }
+        for tool in standard.iter().filter(|tool| !allowed.contains(tool)) {
+            server.unregister_tool(tool).await;
+        }
+    }
+
+    let (transport, port, running) = match options.transport.as_deref().unwrap_or("stdio") {
+        "stdio" => {
+            let serving = server.clone();
+            let task = tokio::spawn(async move {
+                if let Err(e) = serving.serve_stdio().await {
+                    eprintln!("MCP stdio transport failed: {}", e);
+                }
+            });
+            ("stdio", None, Running::Stdio(task))
+        }
+        "http" => {
+            let host: IpAddr = options
+                .host
+                .as_deref()
+                .unwrap_or("127.0.0.1")
+                .parse()
+                .map_err(|e| Error::new(Status::InvalidArg, format!("Invalid host: {}", e)))?;
+            let port = u16::try_from(options.port.unwrap_or(0))
+                .map_err(|_| Error::new(Status::InvalidArg, "Invalid port"))?;
+            let http =
+                HttpServer::start(server.clone(), HttpConfig::new(SocketAddr::new(host, port)))
+                    .await
+                    .map_err(|e| {
+                        Error::from_reason(format!("Failed to start MCP server: {}", e))
+                    })?;
+            let port = http.local_addr().port() as u32;
+            ("http", Some(port), Running::Http(http))
+        }
+        other => {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!("Unknown MCP transport '{}'; use 'stdio' or 'http'", other),
+            ))
+        }
+    };
+
+    Ok(McpServerHandle {
+        served: Arc::new(Served {
+            server,
+            transport: transport.to_string(),
+            port,
+            running: Mutex::new(Some(running)),
+            counts,
+            listeners,
+            js_tools: Mutex::new(Vec::new()),
+        }),
+    })
+}
+
+impl Served {
+    /// See [`McpServerHandle::stop`]
+    pub(crate) async fn stop(&self) {
+        self.stop_now();
+        let js_tools = std::mem::take(&mut *self.js_tools.lock().unwrap());
+        for tool in js_tools {
+            self.server.unregister_tool(&tool).await;
+        }
+    }
+
+    /// Stop serving and drop the listeners, leaving the JS tools registered
+    pub(crate) fn stop_now(&self) {
+        self.running.lock().unwrap().take();
+        self.listeners.lock().unwrap().clear();
+    }
+}
+
+#[napi]
+impl McpServerHandle {
+    /// Port of the HTTP transport
+    #[napi]
+    pub fn get_port(&self) -> Option<u32> {
+        self.served.port
+    }
+
+    /// Offer the tool `name`, answered by `handler`
+    ///
+    /// `schema` is the JSON Schema of the arguments, with the tool's
+    /// description in its `description`. `handler` is called with the
+    /// arguments and returns the result or a promise of it: a string is
+    /// sent as text, anything else as JSON. If it throws or rejects, the
+    /// model gets the error's message as a failed call.
+    #[napi(ts_args_type = "name: string, schema: object, handler: (args: any) => any")]
+    pub async fn register_tool(
+        &self,
+        name: String,
+        schema: JsonValue,
+        handler: Handler,
+    ) -> Result<()> {
+        let tool = McpTool {
+            name: name.clone(),
+            description: schema["description"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            input_schema: schema,
+        };
+        let handler = Arc::new(handler);
+        let handler: ToolHandler = Arc::new(move |arguments, _| {
+            let handler = handler.clone();
+            Box::pin(async move {
+                match handler::call(&handler, arguments).await {
+                    Ok(JsonValue::String(text)) => Ok(server::text_response(text)),
+                    Ok(value) => Ok(server::text_response(value.to_string())),
+                    Err(Some(message)) => Ok(server::error_response(message)),
+                    Err(None) => Err(anyhow::anyhow!("The JavaScript handler is gone")),
+                }
+            })
+        });
+        self.served
+            .server
+            .register_tool(tool, handler)
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        self.served.js_tools.lock().unwrap().push(name);
+        Ok(())
+    }
+
+    /// Listen to `event`; `toolCall` is emitted with the audit record of
+    /// every tool call
+    #[napi(ts_args_type = "event: 'toolCall', listener: (record: ToolCallRecord) => void")]
+    pub fn on(
+        &self,
+        event: String,
+        listener: Function<JsonValue, UnknownReturnValue>,
+    ) -> Result<()> {
+        if event != "toolCall" {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!("Unknown event '{}'; use 'toolCall'", event),
+            ));
+        }
+        let listener = listener
+            .build_threadsafe_function::<JsonValue>()
+            .weak::<true>()
+            .build()?;
+        self.served.listeners.lock().unwrap().push(listener);
+        Ok(())
+    }
+
+    /// Stop serving, ending every session
+    ///
+    /// Tools registered from JS are removed and listeners dropped, so
+    /// nothing of the server keeps the process alive.
+    #[napi]
+    pub async fn stop(&self) {
+        self.served.stop().await;
+    }
+
+    /// Statistics of the server
+    #[napi]
+    pub fn stats(&self) -> McpServerStats {
+        let served = &self.served;
+        let running = served.running.lock().unwrap();
+        let sessions = match &*running {
+            Some(Running::Http(http)) => http.session_count(),
+            _ => served.server.sessions().len(),
+        };
+        McpServerStats {
+            transport: served.transport.clone(),
+            port: served.port,
+            running: running.is_some(),
+            sessions: sessions as u32,
+            tool_calls: served.counts.calls.load(Ordering::Relaxed),
+            failed_calls: served.counts.failed.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Parameter values and descriptors converted between JS and Rust
+//!
+//! The mapping is exact: `bool`, `string` and `number[]` parameters take
+//! their JS counterparts, double parameters a `number` and integer
+//! parameters a `bigint`, or a safe integer `number` when set.
+
+use agentic_robotics_core::{ParameterDescriptor, ParameterServer, ParameterValue};
+use napi::bindgen_prelude::{i64n, BigInt, FnArgs, FromNapiValue, ToNapiValue};
+use napi::threadsafe_function::{ThreadsafeFunction, UnknownReturnValue};
+use napi::{sys, Error, Status, ValueType};
+use napi_derive::napi;
+
+/// Arguments of `parameterChanged` listeners: the name and the new value
+pub(crate) type ParameterChange = FnArgs<(String, JsParameter)>;
+
+/// JS listener of `parameterChanged` events
+///
+/// Weak, so listening does not keep the process alive by itself.
+pub(crate) type ParameterListener =
+    ThreadsafeFunction<ParameterChange, UnknownReturnValue, ParameterChange, Status, false, true>;
+
+/// Largest integer a JS number holds exactly
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// Value of a parameter as passed to or from JS
+pub struct JsParameter(pub(crate) ParameterValue);
+
+impl FromNapiValue for JsParameter {
+    unsafe fn from_napi_value(env: sys::napi_env, value: sys::napi_value) -> napi::Result<Self> {
+        let mut kind = 0;
+        sys::napi_typeof(env, value, &mut kind);
+        let value = match ValueType::from(kind) {
+            ValueType::Boolean => ParameterValue::Bool(bool::from_napi_value(env, value)?),
+            ValueType::String => ParameterValue::String(String::from_napi_value(env, value)?),
+            ValueType::Number => ParameterValue::Double(f64::from_napi_value(env, value)?),
+            ValueType::BigInt => {
+                let (integer, lossless) = BigInt::from_napi_value(env, value)?.get_i64();
+                if !lossless {
+                    return Err(Error::new(
+                        Status::InvalidArg,
+                        "Integer parameters must fit in 64 bits".to_string(),
+                    ));
+                }
+                ParameterValue::Integer(integer)
+            }
+            ValueType::Object => ParameterValue::DoubleArray(
+                Vec::<f64>::from_napi_value(env, value).map_err(|_| unsupported())?,
+            ),
+            _ => return Err(unsupported()),
+        };
+        Ok(Self(value))
+    }
+}
+
+fn unsupported() -> Error {
+    Error::new(
+        Status::InvalidArg,
+        "Parameter values must be a boolean, number, bigint, string or number[]".to_string(),
+    )
+}
+
+impl ToNapiValue for JsParameter {
+    unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> napi::Result<sys::napi_value> {
+        match val.0 {
+            ParameterValue::Bool(value) => bool::to_napi_value(env, value),
+            ParameterValue::Integer(value) => i64n::to_napi_value(env, i64n(value)),
+            ParameterValue::Double(value) => f64::to_napi_value(env, value),
+            ParameterValue::String(value) => String::to_napi_value(env, value),
+            ParameterValue::DoubleArray(values) => Vec::<f64>::to_napi_value(env, values),
+        }
+    }
+}
+
+/// Bounds of a numeric parameter
+#[napi(object)]
+pub struct ParameterRangeOptions {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Description and constraints of a parameter, see
+/// [`AgenticNode::declare_parameter`](crate::AgenticNode::declare_parameter)
+#[napi(object)]
+pub struct ParameterDescriptorOptions {
+    pub description: Option<String>,
+    /// Reject every change after the declaration
+    pub read_only: Option<bool>,
+    /// Inclusive bounds of integers, doubles and every element of arrays
+    pub range: Option<ParameterRangeOptions>,
+}
+
+impl From<ParameterDescriptorOptions> for ParameterDescriptor {
+    fn from(options: ParameterDescriptorOptions) -> Self {
+        let mut descriptor = ParameterDescriptor::new(options.description.unwrap_or_default());
+        if let Some(range) = options.range {
+            descriptor = descriptor.with_range(range.min, range.max);
+        }
+        if options.read_only.unwrap_or(false) {
+            descriptor = descriptor.read_only();
+        }
+        descriptor
+    }
+}
+
+/// Set `name` on `server` to `value`
+///
+/// A safe integer `number` is taken as the integer it is for an integer
+/// parameter; any other mismatch is left for the server to reject.
+pub(crate) fn set(server: &ParameterServer, name: &str, value: JsParameter) -> napi::Result<()> {
+    let value = match (server.get_value(name), value.0) {
+        (Some(ParameterValue::Integer(_)), ParameterValue::Double(number))
+            if number.fract() == 0.0 && number.abs() <= MAX_SAFE_INTEGER =>
+        {
+            ParameterValue::Integer(number as i64)
+        }
+        (_, value) => value,
+    };
+    server.set(name, value).map_err(invalid)
+}
+
+/// A parameter error as a JS exception naming the failed constraint
+pub(crate) fn invalid(error: agentic_robotics_core::Error) -> Error {
+    Error::new(Status::InvalidArg, error.to_string())
+}
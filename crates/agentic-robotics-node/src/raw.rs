@@ -0,0 +1,102 @@
+//! Binary payloads passed between JS Buffers and topics without copying,
+//! see [`AgenticNode::publish_raw`] and [`AgenticNode::subscribe_raw`]
+//!
+//! A published Buffer is kept referenced by the message's [`SharedBytes`]
+//! until the last subscriber and transport let go of it. A received
+//! message becomes an external Buffer over the same bytes, whose finalizer
+//! holds a clone of them, so the Buffer stays valid however long JS keeps
+//! it.
+//!
+//! [`AgenticNode::publish_raw`]: crate::AgenticNode::publish_raw
+//! [`AgenticNode::subscribe_raw`]: crate::AgenticNode::subscribe_raw
+
+use agentic_robotics_core::serialization::Format;
+use agentic_robotics_core::SharedBytes;
+use napi::bindgen_prelude::{Buffer, ToNapiValue};
+use napi::{sys, Error, Status};
+use std::ffi::c_void;
+use std::ptr;
+
+/// Bytes of a Buffer published from JS
+pub(crate) struct JsBytes(pub(crate) Buffer);
+
+impl AsRef<[u8]> for JsBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Parse the name of a serialization format, e.g. `cdr`
+pub(crate) fn parse_format(name: &str) -> napi::Result<Format> {
+    Ok(match name {
+        "cdr" => Format::Cdr,
+        "json" => Format::Json,
+        "rkyv" => Format::Rkyv,
+        "protobuf" => Format::Protobuf,
+        "messagepack" => Format::MessagePack,
+        other => {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!(
+                    "Unknown format '{}'; use cdr, json, rkyv, protobuf or messagepack",
+                    other
+                ),
+            ))
+        }
+    })
+}
+
+/// Received bytes, converted to a Buffer over them or to a copy
+pub struct RawBuffer {
+    pub(crate) data: SharedBytes,
+    pub(crate) copy: bool,
+}
+
+impl ToNapiValue for RawBuffer {
+    unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> napi::Result<sys::napi_value> {
+        let mut buffer = ptr::null_mut();
+        let len = val.data.len();
+        if !val.copy && len > 0 {
+            let data = val.data.as_ptr() as *mut c_void;
+            let hint = Box::into_raw(Box::new(val.data.clone()));
+            let status = sys::napi_create_external_buffer(
+                env,
+                len,
+                data,
+                Some(release),
+                hint.cast(),
+                &mut buffer,
+            );
+            if status == sys::Status::napi_ok {
+                return Ok(buffer);
+            }
+            drop(Box::from_raw(hint));
+            // Runtimes without external buffers get a copy
+            if status != sys::Status::napi_no_external_buffers_allowed {
+                return Err(Error::new(
+                    Status::from(status),
+                    "Failed to create Buffer".to_string(),
+                ));
+            }
+        }
+        let status = sys::napi_create_buffer_copy(
+            env,
+            len,
+            val.data.as_ptr().cast(),
+            ptr::null_mut(),
+            &mut buffer,
+        );
+        if status != sys::Status::napi_ok {
+            return Err(Error::new(
+                Status::from(status),
+                "Failed to create Buffer".to_string(),
+            ));
+        }
+        Ok(buffer)
+    }
+}
+
+/// Finalizer of an external Buffer, dropping its clone of the bytes
+unsafe extern "C" fn release(_env: sys::napi_env, _data: *mut c_void, hint: *mut c_void) {
+    drop(Box::from_raw(hint.cast::<SharedBytes>()));
+}
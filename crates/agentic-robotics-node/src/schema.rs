@@ -0,0 +1,508 @@
+//! Message types checked at the JavaScript boundary
+//!
+//! A [`MessageSchema`] lists the fields of a message type. JavaScript
+//! registers one with `registerType(name, fields)`, giving each field a type
+//! string:
+//!
+//! - `bool`, `string`, `f32`, `f64`, `i32`, `u32`, `i64`, `u64`
+//! - `json` for any value
+//! - an element type followed by `[N]` for an array of exactly `N`
+//!   elements, or by `[]` for any length, e.g. `f64[3]` or `string[]`
+//! - the name of a registered type, or a nested object of fields
+//!
+//! Without fields, the type is looked up in the core message registry
+//! ([`agentic_robotics_core::types`]) and its fields read from its JSON
+//! Schema. Messages published with a type are checked against the schema,
+//! with errors naming the offending field such as
+//! `position[2] must be a number, got string`. Types known to the core
+//! registry are converted to the Rust message, so Rust subscribers receive
+//! them typed.
+//!
+//! `i64` and `u64` fields take a `BigInt` or a safe integer and come back
+//! as `BigInt`, so no value loses precision on the way.
+
+use agentic_robotics_core::types::{self, MessageType};
+use napi::bindgen_prelude::{i64n, Env, Object, ToNapiValue};
+use napi::sys;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Largest integer a JS number holds exactly, `2^53 - 1`
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// Type of a message field
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum FieldType {
+    Bool,
+    String,
+    F32,
+    F64,
+    I32,
+    U32,
+    I64,
+    U64,
+    /// Any JSON value
+    Json,
+    /// Elements of one type, exactly `len` of them if given
+    Array(Box<FieldType>, Option<usize>),
+    /// Named fields, in declaration order
+    Struct(Vec<(String, FieldType)>),
+}
+
+/// A message type registered for the binding, see the
+/// [module documentation](self)
+#[derive(Debug)]
+pub(crate) struct MessageSchema {
+    pub(crate) name: String,
+    pub(crate) fields: FieldType,
+    /// The core registry's type of the same name, if any
+    pub(crate) rust_type: Option<MessageType>,
+}
+
+fn registry() -> &'static RwLock<HashMap<String, Arc<MessageSchema>>> {
+    static TYPES: OnceLock<RwLock<HashMap<String, Arc<MessageSchema>>>> = OnceLock::new();
+    TYPES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Register `name` with `fields`, or from the core registry without them
+///
+/// Registering a name again with the same fields has no effect; other
+/// fields are rejected.
+pub(crate) fn register(
+    name: &str,
+    fields: Option<&JsonValue>,
+) -> Result<Arc<MessageSchema>, String> {
+    let rust_type = types::lookup(name);
+    let fields = match (fields, rust_type) {
+        (Some(fields), _) => parse_struct(fields, name)?,
+        (None, Some(rust_type)) => from_json_schema(&rust_type.schema()),
+        (None, None) => {
+            if let Some(schema) = lookup(name) {
+                return Ok(schema);
+            }
+            let known: Vec<&str> = types::registered().iter().map(|ty| ty.name()).collect();
+            return Err(format!(
+                "Unknown message type '{}'; give its fields or use a registered type: {}",
+                name,
+                known.join(", ")
+            ));
+        }
+    };
+
+    let mut registry = registry().write().unwrap();
+    if let Some(existing) = registry.get(name) {
+        if existing.fields != fields {
+            return Err(format!(
+                "Type '{}' is already registered with other fields",
+                name
+            ));
+        }
+        return Ok(existing.clone());
+    }
+    let schema = Arc::new(MessageSchema {
+        name: name.to_string(),
+        fields,
+        rust_type,
+    });
+    registry.insert(name.to_string(), schema.clone());
+    Ok(schema)
+}
+
+/// The type registered as `name`
+pub(crate) fn lookup(name: &str) -> Option<Arc<MessageSchema>> {
+    registry().read().unwrap().get(name).cloned()
+}
+
+/// The registered type `name`, registering it from the core registry if
+/// needed
+pub(crate) fn resolve(name: &str) -> Result<Arc<MessageSchema>, String> {
+    match lookup(name) {
+        Some(schema) => Ok(schema),
+        None => register(name, None),
+    }
+}
+
+fn parse_struct(fields: &JsonValue, path: &str) -> Result<FieldType, String> {
+    let JsonValue::Object(fields) = fields else {
+        return Err(format!(
+            "Fields of {} must be an object, got {}",
+            path,
+            kind(fields)
+        ));
+    };
+    fields
+        .iter()
+        .map(|(name, spec)| {
+            let path = format!("{}.{}", path, name);
+            let ty = match spec {
+                JsonValue::String(spec) => parse_type(spec, &path)?,
+                JsonValue::Object(_) => parse_struct(spec, &path)?,
+                spec => {
+                    return Err(format!(
+                        "Type of {} must be a string or an object, got {}",
+                        path,
+                        kind(spec)
+                    ))
+                }
+            };
+            Ok((name.clone(), ty))
+        })
+        .collect::<Result<_, _>>()
+        .map(FieldType::Struct)
+}
+
+fn parse_type(spec: &str, path: &str) -> Result<FieldType, String> {
+    if let Some(array) = spec.strip_suffix(']') {
+        let Some((element, len)) = array.rsplit_once('[') else {
+            return Err(format!("Invalid type '{}' of {}", spec, path));
+        };
+        let len = match len {
+            "" => None,
+            len => Some(
+                len.parse()
+                    .map_err(|_| format!("Invalid array length '{}' of {}", len, path))?,
+            ),
+        };
+        return Ok(FieldType::Array(Box::new(parse_type(element, path)?), len));
+    }
+    Ok(match spec {
+        "bool" => FieldType::Bool,
+        "string" => FieldType::String,
+        "f32" => FieldType::F32,
+        "f64" => FieldType::F64,
+        "i32" => FieldType::I32,
+        "u32" => FieldType::U32,
+        "i64" => FieldType::I64,
+        "u64" => FieldType::U64,
+        "json" => FieldType::Json,
+        name => match lookup(name) {
+            Some(schema) => schema.fields.clone(),
+            None => return Err(format!("Unknown type '{}' of {}", name, path)),
+        },
+    })
+}
+
+/// The field types described by a JSON Schema, as written for the core
+/// message types
+fn from_json_schema(schema: &JsonValue) -> FieldType {
+    match schema["type"].as_str() {
+        Some("boolean") => FieldType::Bool,
+        Some("string") => FieldType::String,
+        Some("number") => FieldType::F64,
+        Some("integer") => FieldType::I64,
+        Some("array") => {
+            let min = schema["minItems"].as_u64();
+            let len = min.filter(|&min| schema["maxItems"].as_u64() == Some(min));
+            FieldType::Array(
+                Box::new(from_json_schema(&schema["items"])),
+                len.map(|len| len as usize),
+            )
+        }
+        Some("object") => match schema["properties"].as_object() {
+            Some(properties) => FieldType::Struct(
+                properties
+                    .iter()
+                    .map(|(name, schema)| (name.clone(), from_json_schema(schema)))
+                    .collect(),
+            ),
+            None => FieldType::Json,
+        },
+        _ => FieldType::Json,
+    }
+}
+
+/// Name of a JSON value's type as JavaScript sees it
+fn kind(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
+
+/// A field that does not match its type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FieldError {
+    path: String,
+    message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "message"
+        } else {
+            &self.path
+        };
+        write!(f, "{} {}", path, self.message)
+    }
+}
+
+impl FieldType {
+    /// `value` checked against this type, with integers in canonical form
+    pub(crate) fn check(&self, value: &JsonValue) -> Result<JsonValue, FieldError> {
+        self.check_at(value, "")
+    }
+
+    fn check_at(&self, value: &JsonValue, path: &str) -> Result<JsonValue, FieldError> {
+        let error = |message: String| FieldError {
+            path: path.to_string(),
+            message,
+        };
+        let expected = |what: &str| error(format!("must be {}, got {}", what, kind(value)));
+        match self {
+            FieldType::Json => Ok(value.clone()),
+            FieldType::Bool if value.is_boolean() => Ok(value.clone()),
+            FieldType::Bool => Err(expected("a boolean")),
+            FieldType::String if value.is_string() => Ok(value.clone()),
+            FieldType::String => Err(expected("a string")),
+            FieldType::F32 | FieldType::F64 if value.is_number() => Ok(value.clone()),
+            FieldType::F32 | FieldType::F64 => Err(expected("a number")),
+            FieldType::I32 | FieldType::U32 | FieldType::I64 | FieldType::U64 => {
+                let Some(number) = value.as_number() else {
+                    return Err(expected("an integer"));
+                };
+                let integer = match (number.as_i64(), number.as_u64(), number.as_f64()) {
+                    (Some(value), _, _) => i128::from(value),
+                    (_, Some(value), _) => i128::from(value),
+                    // A JS number with no fraction, exact up to 2^53
+                    (_, _, Some(value))
+                        if value.fract() == 0.0 && value.abs() <= MAX_SAFE_INTEGER =>
+                    {
+                        value as i128
+                    }
+                    (_, _, Some(value)) if value.fract() == 0.0 => {
+                        return Err(error(format!(
+                            "must be a BigInt above 2^53, got the inexact number {}",
+                            value
+                        )))
+                    }
+                    _ => return Err(error(format!("must be an integer, got {}", number))),
+                };
+                let (name, range) = match self {
+                    FieldType::I32 => ("i32", i128::from(i32::MIN)..=i128::from(i32::MAX)),
+                    FieldType::U32 => ("u32", 0..=i128::from(u32::MAX)),
+                    FieldType::I64 => ("i64", i128::from(i64::MIN)..=i128::from(i64::MAX)),
+                    _ => ("u64", 0..=i128::from(u64::MAX)),
+                };
+                if !range.contains(&integer) {
+                    return Err(error(format!("must fit in {}, got {}", name, integer)));
+                }
+                Ok(match i64::try_from(integer) {
+                    Ok(integer) => integer.into(),
+                    Err(_) => (integer as u64).into(),
+                })
+            }
+            FieldType::Array(element, len) => {
+                let Some(values) = value.as_array() else {
+                    return Err(expected("an array"));
+                };
+                if let Some(len) = len.filter(|&len| len != values.len()) {
+                    return Err(error(format!(
+                        "must have {} elements, got {}",
+                        len,
+                        values.len()
+                    )));
+                }
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| element.check_at(value, &format!("{}[{}]", path, i)))
+                    .collect::<Result<_, _>>()
+                    .map(JsonValue::Array)
+            }
+            FieldType::Struct(fields) => {
+                let Some(object) = value.as_object() else {
+                    return Err(expected("an object"));
+                };
+                let field_path = |name: &str| match path {
+                    "" => name.to_string(),
+                    path => format!("{}.{}", path, name),
+                };
+                if let Some(unknown) = object
+                    .keys()
+                    .find(|key| !fields.iter().any(|(name, _)| name == *key))
+                {
+                    return Err(FieldError {
+                        path: field_path(unknown),
+                        message: "is not a field".to_string(),
+                    });
+                }
+                let mut checked = Map::new();
+                for (name, ty) in fields {
+                    let Some(value) = object.get(name) else {
+                        return Err(FieldError {
+                            path: field_path(name),
+                            message: "is missing".to_string(),
+                        });
+                    };
+                    checked.insert(name.clone(), ty.check_at(value, &field_path(name))?);
+                }
+                Ok(JsonValue::Object(checked))
+            }
+        }
+    }
+}
+
+/// A received message on its way to JavaScript
+///
+/// With a schema, `i64` and `u64` fields become `BigInt`s; otherwise the
+/// JSON value is converted as is.
+pub struct JsMessage {
+    pub(crate) value: JsonValue,
+    pub(crate) schema: Option<Arc<MessageSchema>>,
+}
+
+impl ToNapiValue for JsMessage {
+    unsafe fn to_napi_value(env: sys::napi_env, message: Self) -> napi::Result<sys::napi_value> {
+        match &message.schema {
+            Some(schema) => unsafe {
+                ToNapiValue::to_napi_value(env, Typed(&message.value, &schema.fields))
+            },
+            None => unsafe { ToNapiValue::to_napi_value(env, message.value) },
+        }
+    }
+}
+
+/// A JSON value with its field type
+struct Typed<'a>(&'a JsonValue, &'a FieldType);
+
+impl ToNapiValue for Typed<'_> {
+    unsafe fn to_napi_value(env: sys::napi_env, typed: Self) -> napi::Result<sys::napi_value> {
+        match typed {
+            Typed(JsonValue::Number(number), FieldType::I64) if number.is_i64() => unsafe {
+                ToNapiValue::to_napi_value(env, i64n(number.as_i64().unwrap_or_default()))
+            },
+            Typed(JsonValue::Number(number), FieldType::U64) if number.is_u64() => unsafe {
+                ToNapiValue::to_napi_value(env, number.as_u64().unwrap_or_default())
+            },
+            Typed(JsonValue::Array(values), FieldType::Array(element, _)) => {
+                let values: Vec<Typed> = values.iter().map(|value| Typed(value, element)).collect();
+                unsafe { ToNapiValue::to_napi_value(env, values) }
+            }
+            Typed(JsonValue::Object(object), FieldType::Struct(fields)) => {
+                let mut js = Object::new(&Env::from_raw(env))?;
+                for (name, value) in object {
+                    let ty = fields
+                        .iter()
+                        .find(|(field, _)| field == name)
+                        .map_or(&FieldType::Json, |(_, ty)| ty);
+                    js.set(name, Typed(value, ty))?;
+                }
+                unsafe { ToNapiValue::to_napi_value(env, js) }
+            }
+            Typed(value, _) => unsafe { ToNapiValue::to_napi_value(env, value) },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn robot_state() -> FieldType {
+        parse_struct(
+            &json!({ "position": "f64[3]", "velocity": "f64[3]", "timestamp": "i64" }),
+            "RobotState",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_types() {
+        assert_eq!(
+            parse_type("f64[3]", "p").unwrap(),
+            FieldType::Array(Box::new(FieldType::F64), Some(3))
+        );
+        assert_eq!(
+            parse_type("u32[][2]", "p").unwrap(),
+            FieldType::Array(
+                Box::new(FieldType::Array(Box::new(FieldType::U32), None)),
+                Some(2)
+            )
+        );
+        assert!(parse_type("f128", "p").is_err());
+        assert!(parse_type("f64[x]", "p").is_err());
+    }
+
+    #[test]
+    fn test_check_reports_the_field() {
+        let ty = robot_state();
+        let valid = json!({ "position": [1, 2, 3.5], "velocity": [0, 0, 0], "timestamp": 7 });
+        assert_eq!(ty.check(&valid).unwrap(), valid);
+
+        let error = |value: JsonValue| ty.check(&value).unwrap_err().to_string();
+        assert_eq!(
+            error(json!({ "position": [1, 2, "3"], "velocity": [0, 0, 0], "timestamp": 7 })),
+            "position[2] must be a number, got string"
+        );
+        assert_eq!(
+            error(json!({ "position": [1, 2], "velocity": [0, 0, 0], "timestamp": 7 })),
+            "position must have 3 elements, got 2"
+        );
+        assert_eq!(
+            error(json!({ "position": [1, 2, 3], "velocity": [0, 0, 0], "timestamp": 1.5 })),
+            "timestamp must be an integer, got 1.5"
+        );
+        assert_eq!(
+            error(json!({ "position": [1, 2, 3], "velocity": [0, 0, 0] })),
+            "timestamp is missing"
+        );
+        assert_eq!(
+            error(json!({ "position": [1, 2, 3], "velocity": [0, 0, 0], "timestamp": 1, "x": 1 })),
+            "x is not a field"
+        );
+        assert_eq!(error(json!([1])), "message must be an object, got array");
+    }
+
+    #[test]
+    fn test_integers_are_exact() {
+        assert_eq!(
+            FieldType::I64.check(&json!(i64::MIN)).unwrap(),
+            json!(i64::MIN)
+        );
+        assert_eq!(
+            FieldType::U64.check(&json!(u64::MAX)).unwrap(),
+            json!(u64::MAX)
+        );
+        // JS numbers arrive as floats once above the u32 range
+        assert_eq!(
+            FieldType::I64.check(&json!(5e9)).unwrap(),
+            json!(5_000_000_000i64)
+        );
+        assert!(FieldType::I64.check(&json!(1e17)).is_err());
+        assert!(FieldType::I32.check(&json!(3_000_000_000u64)).is_err());
+        assert!(FieldType::U32.check(&json!(-1)).is_err());
+    }
+
+    #[test]
+    fn test_rust_types_come_from_the_registry() {
+        let schema = resolve("ros3_msgs/RobotState").unwrap();
+        assert!(schema.rust_type.is_some());
+        let FieldType::Struct(fields) = &schema.fields else {
+            panic!("RobotState is a struct");
+        };
+        assert!(fields.contains(&("timestamp".to_string(), FieldType::I64)));
+        assert!(fields.contains(&(
+            "position".to_string(),
+            FieldType::Array(Box::new(FieldType::F64), Some(3))
+        )));
+
+        assert!(resolve("test_msgs/Unknown").is_err());
+        register("test_msgs/Point", Some(&json!({ "x": "f64", "y": "f64" }))).unwrap();
+        assert!(register("test_msgs/Point", Some(&json!({ "x": "f64", "y": "f64" }))).is_ok());
+        assert!(register("test_msgs/Point", Some(&json!({ "x": "i32" }))).is_err());
+        let path = register(
+            "test_msgs/Path",
+            Some(&json!({ "points": "test_msgs/Point[]" })),
+        )
+        .unwrap();
+        assert!(path.rust_type.is_none());
+    }
+}
@@ -0,0 +1,337 @@
+//! The running bridge
+
+use crate::config::{BridgeConfig, TopicMapping};
+use crate::convert::{dds_topic_name, dds_type_name, Identity, Ros2Conversion, Ros2Message};
+use crate::error::{Error, Result};
+use crate::msgs::{builtin_interfaces, geometry_msgs, nav_msgs, sensor_msgs, std_msgs};
+use agentic_robotics_core::{Message, Node, Publisher, PublisherGuid, RawMessage, Subscriber};
+use parking_lot::Mutex;
+use rustdds::no_key::{DataReader, DataWriter};
+use rustdds::policy::{Durability, History, Reliability};
+use rustdds::{
+    CDRDeserializerAdapter, CDRSerializerAdapter, DomainParticipant, QosPolicies,
+    QosPolicyBuilder, RTPSEntity, TopicKind, GUID,
+};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
+
+/// Name of the ros3 node publishing and subscribing on behalf of ROS 2
+pub const NODE_NAME: &str = "ros3_ros2_bridge";
+
+/// Counts samples that did not match their topic's configured type
+///
+/// Only the first mismatch on each topic is logged; later ones are counted.
+#[derive(Debug, Default)]
+pub struct MismatchLog {
+    counts: Mutex<HashMap<String, u64>>,
+}
+
+impl MismatchLog {
+    /// Record a mismatch on `topic`, logging it if it is the first
+    pub(crate) fn report(&self, topic: &str, detail: impl Display) {
+        let mut counts = self.counts.lock();
+        let count = counts.entry(topic.to_string()).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            warn!(
+                "Type mismatch on '{}': {}; further mismatches on this topic are not logged",
+                topic, detail
+            );
+        }
+    }
+
+    /// Mismatches seen on `topic` so far
+    pub fn count(&self, topic: &str) -> u64 {
+        self.counts.lock().get(topic).copied().unwrap_or(0)
+    }
+}
+
+/// Bridges the topics of one [`BridgeConfig`] between ros3 and ROS 2
+///
+/// Conversions for the types in [`msgs`](crate::msgs) are registered up
+/// front; custom types are added with [`with_conversion`](Self::with_conversion).
+pub struct Bridge {
+    config: BridgeConfig,
+    node: Node,
+    participant: DomainParticipant,
+    dds_publisher: rustdds::Publisher,
+    dds_subscriber: rustdds::Subscriber,
+    routes: HashMap<&'static str, Arc<dyn Route>>,
+    mismatches: Arc<MismatchLog>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Bridge {
+    /// Join the configured DDS domain; nothing is bridged until
+    /// [`start`](Self::start)
+    pub fn new(config: BridgeConfig) -> Result<Self> {
+        let participant = DomainParticipant::new(config.domain_id).map_err(dds)?;
+        let qos = ros2_qos();
+        let dds_publisher = participant.create_publisher(&qos).map_err(dds)?;
+        let dds_subscriber = participant.create_subscriber(&qos).map_err(dds)?;
+        let bridge = Self {
+            config,
+            node: Node::new(NODE_NAME)?,
+            participant,
+            dds_publisher,
+            dds_subscriber,
+            routes: HashMap::new(),
+            mismatches: Arc::new(MismatchLog::default()),
+            tasks: Vec::new(),
+        };
+        Ok(bridge
+            .with_conversion::<Identity<builtin_interfaces::Time>>()
+            .with_conversion::<Identity<std_msgs::Header>>()
+            .with_conversion::<Identity<std_msgs::String>>()
+            .with_conversion::<Identity<std_msgs::Bool>>()
+            .with_conversion::<Identity<std_msgs::Int32>>()
+            .with_conversion::<Identity<std_msgs::Int64>>()
+            .with_conversion::<Identity<std_msgs::Float32>>()
+            .with_conversion::<Identity<std_msgs::Float64>>()
+            .with_conversion::<Identity<geometry_msgs::Vector3>>()
+            .with_conversion::<Identity<geometry_msgs::Point>>()
+            .with_conversion::<Identity<geometry_msgs::Quaternion>>()
+            .with_conversion::<Identity<geometry_msgs::Pose>>()
+            .with_conversion::<Identity<geometry_msgs::PoseStamped>>()
+            .with_conversion::<Identity<geometry_msgs::Twist>>()
+            .with_conversion::<Identity<sensor_msgs::Imu>>()
+            .with_conversion::<Identity<sensor_msgs::LaserScan>>()
+            .with_conversion::<Identity<sensor_msgs::PointField>>()
+            .with_conversion::<Identity<sensor_msgs::PointCloud2>>()
+            .with_conversion::<Identity<sensor_msgs::BatteryState>>()
+            .with_conversion::<Identity<sensor_msgs::JointState>>()
+            .with_conversion::<Identity<nav_msgs::MapMetaData>>()
+            .with_conversion::<Identity<nav_msgs::OccupancyGrid>>()
+            .with_conversion::<Identity<nav_msgs::Path>>())
+    }
+
+    /// Bridge topics of ROS 2 type `C::Ros2` with `C`, replacing any
+    /// conversion registered for that type before
+    pub fn with_conversion<C: Ros2Conversion>(mut self) -> Self {
+        self.routes
+            .insert(C::Ros2::ROS2_TYPE, Arc::new(Typed::<C>(PhantomData)));
+        self
+    }
+
+    /// Start bridging every configured topic
+    ///
+    /// Fails with [`Error::UnknownType`] if a topic's ROS 2 type has no
+    /// conversion. Must be called from within a Tokio runtime.
+    pub fn start(&mut self) -> Result<()> {
+        let mut started = Vec::new();
+        let result = self.config.topics.iter().try_for_each(|mapping| {
+            let route = self.routes.get(mapping.ros2_type.as_str()).ok_or_else(|| {
+                Error::UnknownType {
+                    topic: mapping.ros2.clone(),
+                    ros2_type: mapping.ros2_type.clone(),
+                }
+            })?;
+            started.extend(route.start(self, mapping)?);
+            info!(
+                "Bridging '{}' <-> ROS 2 '{}' ({}, {:?})",
+                mapping.ros3, mapping.ros2, mapping.ros2_type, mapping.direction
+            );
+            Ok(())
+        });
+        if let Err(e) = result {
+            started.iter().for_each(JoinHandle::abort);
+            return Err(e);
+        }
+        self.tasks.extend(started);
+        Ok(())
+    }
+
+    /// Samples dropped per topic because they did not match its type
+    pub fn mismatches(&self) -> &MismatchLog {
+        &self.mismatches
+    }
+
+    /// The ros3 node the bridge publishes and subscribes with
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+}
+
+impl Drop for Bridge {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+fn dds(e: impl Display) -> Error {
+    Error::Dds(e.to_string())
+}
+
+/// The QoS ROS 2 uses by default: reliable, volatile, keep last 10
+fn ros2_qos() -> QosPolicies {
+    QosPolicyBuilder::new()
+        .reliability(Reliability::Reliable {
+            max_blocking_time: rustdds::Duration::from_millis(100),
+        })
+        .durability(Durability::Volatile)
+        .history(History::KeepLast { depth: 10 })
+        .build()
+}
+
+/// Starts the tasks bridging one topic, for one registered conversion
+trait Route: Send + Sync {
+    fn start(&self, bridge: &Bridge, mapping: &TopicMapping) -> Result<Vec<JoinHandle<()>>>;
+}
+
+struct Typed<C>(PhantomData<fn() -> C>);
+
+impl<C: Ros2Conversion> Route for Typed<C> {
+    fn start(&self, bridge: &Bridge, mapping: &TopicMapping) -> Result<Vec<JoinHandle<()>>> {
+        let topic = bridge
+            .participant
+            .create_topic(
+                dds_topic_name(&mapping.ros2),
+                dds_type_name(C::Ros2::ROS2_TYPE),
+                &ros2_qos(),
+                TopicKind::NoKey,
+            )
+            .map_err(dds)?;
+
+        // Each direction skips what the other one published
+        let writer = match mapping.direction.to_ros2() {
+            true => Some(
+                bridge
+                    .dds_publisher
+                    .create_datawriter_no_key::<C::Ros2, CDRSerializerAdapter<C::Ros2>>(
+                        &topic, None,
+                    )
+                    .map_err(dds)?,
+            ),
+            false => None,
+        };
+        let publisher = match mapping.direction.to_ros3() {
+            true => Some(bridge.node.create_publisher::<C::Ros3>(&mapping.ros3)?),
+            false => None,
+        };
+        let own_writer = writer.as_ref().map(|writer| writer.guid());
+        let own_publisher = publisher.as_ref().map(|publisher| publisher.guid());
+
+        let mut tasks = Vec::new();
+        if let Some(writer) = writer {
+            let subscriber = bridge.node.create_subscriber::<RawMessage>(&mapping.ros3)?;
+            tasks.push(tokio::spawn(ros3_to_ros2::<C>(
+                subscriber,
+                own_publisher,
+                writer,
+                mapping.ros3.clone(),
+                bridge.mismatches.clone(),
+            )));
+        }
+        if let Some(publisher) = publisher {
+            let reader = bridge
+                .dds_subscriber
+                .create_datareader_no_key::<C::Ros2, CDRDeserializerAdapter<C::Ros2>>(
+                    &topic, None,
+                )
+                .map_err(dds)?;
+            tasks.push(tokio::spawn(ros2_to_ros3::<C>(
+                reader,
+                own_writer,
+                publisher,
+                mapping.ros2.clone(),
+                bridge.mismatches.clone(),
+            )));
+        }
+        Ok(tasks)
+    }
+}
+
+async fn ros3_to_ros2<C: Ros2Conversion>(
+    subscriber: Subscriber<RawMessage>,
+    own: Option<PublisherGuid>,
+    writer: DataWriter<C::Ros2, CDRSerializerAdapter<C::Ros2>>,
+    topic: String,
+    mismatches: Arc<MismatchLog>,
+) {
+    loop {
+        let (raw, info) = match subscriber.recv_with_info().await {
+            Ok(received) => received,
+            Err(agentic_robotics_core::Error::Closed(_)) => return,
+            Err(e) => {
+                warn!("Receiving '{}' for ROS 2 failed: {}", topic, e);
+                continue;
+            }
+        };
+        if own.is_some() && info.publisher == own {
+            continue;
+        }
+        if raw.type_name != C::Ros3::type_name() {
+            mismatches.report(
+                &topic,
+                format_args!("expected {}, found {}", C::Ros3::type_name(), raw.type_name),
+            );
+            continue;
+        }
+        let msg = match raw.decode::<C::Ros3>().map_err(Error::from).and_then(C::to_ros2) {
+            Ok(msg) => msg,
+            Err(e) => {
+                mismatches.report(&topic, e);
+                continue;
+            }
+        };
+        if let Err(e) = writer.async_write(msg, None).await {
+            warn!("Writing '{}' to ROS 2 failed: {}", topic, e);
+        }
+    }
+}
+
+async fn ros2_to_ros3<C: Ros2Conversion>(
+    reader: DataReader<C::Ros2, CDRDeserializerAdapter<C::Ros2>>,
+    own: Option<GUID>,
+    publisher: Publisher<C::Ros3>,
+    topic: String,
+    mismatches: Arc<MismatchLog>,
+) {
+    let mut samples = reader.async_sample_stream();
+    while let Some(sample) = samples.next().await {
+        let sample = match sample {
+            Ok(sample) => sample,
+            // A ROS 2 writer whose payload does not decode as the type
+            Err(e) => {
+                mismatches.report(&topic, e);
+                continue;
+            }
+        };
+        if own == Some(sample.sample_info().writer_guid()) {
+            continue;
+        }
+        match C::from_ros2(sample.into_value()) {
+            Ok(msg) => {
+                if let Err(e) = publisher.publish(&msg).await {
+                    warn!("Publishing ROS 2 sample on '{}' failed: {}", topic, e);
+                }
+            }
+            Err(e) => mismatches.report(&topic, e),
+        }
+    }
+    debug!("ROS 2 reader for '{}' closed", topic);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mismatch_reported_once_per_topic() {
+        let log = MismatchLog::default();
+        for _ in 0..5 {
+            log.report("/cmd_vel", "expected geometry_msgs/msg/Twist, found RobotState");
+        }
+        log.report("/scan", "undecodable");
+        assert_eq!(log.count("/cmd_vel"), 5);
+        assert_eq!(log.count("/scan"), 1);
+        assert_eq!(log.count("/other"), 0);
+    }
+}
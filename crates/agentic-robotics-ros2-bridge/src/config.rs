@@ -0,0 +1,139 @@
+//! Bridge configuration
+//!
+//! Loaded from JSON:
+//!
+//! ```json
+//! {
+//!   "domain_id": 0,
+//!   "topics": [
+//!     { "ros3": "/cmd_vel", "ros2": "/cmd_vel", "type": "geometry_msgs/msg/Twist" },
+//!     { "ros3": "/scan", "ros2": "/scan", "type": "sensor_msgs/msg/LaserScan",
+//!       "direction": "ros2_to_ros3" }
+//!   ]
+//! }
+//! ```
+
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Which way samples of a mapped topic travel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Ros3ToRos2,
+    Ros2ToRos3,
+    #[default]
+    Both,
+}
+
+impl Direction {
+    pub fn to_ros2(self) -> bool {
+        self != Direction::Ros2ToRos3
+    }
+
+    pub fn to_ros3(self) -> bool {
+        self != Direction::Ros3ToRos2
+    }
+}
+
+/// One ros3 topic and the ROS 2 topic it is bridged to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopicMapping {
+    /// ros3 topic name, e.g. `/cmd_vel`
+    pub ros3: String,
+    /// ROS 2 topic name, e.g. `/cmd_vel`
+    pub ros2: String,
+    /// ROS 2 message type, e.g. `geometry_msgs/msg/Twist`
+    #[serde(rename = "type")]
+    pub ros2_type: String,
+    #[serde(default)]
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BridgeConfig {
+    /// DDS domain of the ROS 2 nodes (`ROS_DOMAIN_ID`)
+    #[serde(default)]
+    pub domain_id: u16,
+    pub topics: Vec<TopicMapping>,
+}
+
+impl BridgeConfig {
+    /// Parse a JSON configuration
+    pub fn from_json(json: &str) -> Result<Self> {
+        let config: Self =
+            serde_json::from_str(json).map_err(|e| Error::Configuration(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read a JSON configuration file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (i, mapping) in self.topics.iter().enumerate() {
+            if !mapping.ros2.starts_with('/') {
+                return Err(Error::Configuration(format!(
+                    "ROS 2 topic '{}' must be absolute",
+                    mapping.ros2
+                )));
+            }
+            let duplicate = self.topics[..i].iter().any(|other| {
+                (other.ros2 == mapping.ros2
+                    && other.direction.to_ros2()
+                    && mapping.direction.to_ros2())
+                    || (other.ros3 == mapping.ros3
+                        && other.direction.to_ros3()
+                        && mapping.direction.to_ros3())
+            });
+            if duplicate {
+                return Err(Error::Configuration(format!(
+                    "'{}' <-> '{}' is bridged more than once in the same direction",
+                    mapping.ros3, mapping.ros2
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = BridgeConfig::from_json(
+            r#"{
+                "domain_id": 3,
+                "topics": [
+                    { "ros3": "/cmd_vel", "ros2": "/cmd_vel", "type": "geometry_msgs/msg/Twist" },
+                    { "ros3": "/scan", "ros2": "/scan", "type": "sensor_msgs/msg/LaserScan",
+                      "direction": "ros2_to_ros3" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.domain_id, 3);
+        assert_eq!(config.topics[0].direction, Direction::Both);
+        assert_eq!(config.topics[1].direction, Direction::Ros2ToRos3);
+        assert!(!config.topics[1].direction.to_ros2());
+    }
+
+    #[test]
+    fn test_reject_duplicate_and_relative_topics() {
+        let duplicate = r#"{ "topics": [
+            { "ros3": "/a", "ros2": "/x", "type": "std_msgs/msg/String" },
+            { "ros3": "/b", "ros2": "/x", "type": "std_msgs/msg/String" }
+        ] }"#;
+        assert!(BridgeConfig::from_json(duplicate).is_err());
+
+        let relative = r#"{ "topics": [
+            { "ros3": "/a", "ros2": "x", "type": "std_msgs/msg/String" }
+        ] }"#;
+        assert!(BridgeConfig::from_json(relative).is_err());
+    }
+}
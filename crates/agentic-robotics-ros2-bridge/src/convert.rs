@@ -0,0 +1,114 @@
+//! Conversions between ros3 messages and ROS 2 messages
+
+use crate::error::Result;
+use agentic_robotics_core::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+
+/// A message type as ROS 2 nodes see it on DDS
+///
+/// The struct's serde layout must match the ROS 2 interface definition
+/// field by field; it is sent as little-endian CDR.
+pub trait Ros2Message: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// ROS 2 type name, e.g. `geometry_msgs/msg/Twist`
+    const ROS2_TYPE: &'static str;
+}
+
+/// How to turn a ros3 message into a ROS 2 message and back
+///
+/// Implement this for a marker type to bridge a custom ros3 message, then
+/// register it with [`Bridge::with_conversion`](crate::Bridge::with_conversion).
+/// The conversion is looked up by [`Ros2Message::ROS2_TYPE`] of `Ros2`.
+pub trait Ros2Conversion: Send + Sync + 'static {
+    type Ros3: Message;
+    type Ros2: Ros2Message;
+
+    fn to_ros2(msg: Self::Ros3) -> Result<Self::Ros2>;
+
+    fn from_ros2(msg: Self::Ros2) -> Result<Self::Ros3>;
+}
+
+/// Bridges a type that is both a ros3 and a ROS 2 message unchanged, such
+/// as those in [`msgs`](crate::msgs)
+pub struct Identity<M>(PhantomData<fn() -> M>);
+
+impl<M: Message + Ros2Message> Ros2Conversion for Identity<M> {
+    type Ros3 = M;
+    type Ros2 = M;
+
+    fn to_ros2(msg: M) -> Result<M> {
+        Ok(msg)
+    }
+
+    fn from_ros2(msg: M) -> Result<M> {
+        Ok(msg)
+    }
+}
+
+/// DDS type name ROS 2 registers for `ros2_type`
+///
+/// `geometry_msgs/msg/Twist` becomes `geometry_msgs::msg::dds_::Twist_`.
+pub fn dds_type_name(ros2_type: &str) -> String {
+    match ros2_type.rsplit_once('/') {
+        Some((package, name)) => format!("{}::dds_::{}_", package.replace('/', "::"), name),
+        None => ros2_type.to_string(),
+    }
+}
+
+/// DDS topic name ROS 2 uses for topic `ros2_topic`
+///
+/// `/cmd_vel` becomes `rt/cmd_vel`.
+pub fn dds_topic_name(ros2_topic: &str) -> String {
+    format!("rt/{}", ros2_topic.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msgs::geometry_msgs::{Twist, Vector3};
+
+    #[test]
+    fn test_dds_names() {
+        assert_eq!(
+            dds_type_name("geometry_msgs/msg/Twist"),
+            "geometry_msgs::msg::dds_::Twist_"
+        );
+        assert_eq!(
+            dds_type_name("sensor_msgs/msg/LaserScan"),
+            "sensor_msgs::msg::dds_::LaserScan_"
+        );
+        assert_eq!(dds_topic_name("/cmd_vel"), "rt/cmd_vel");
+        assert_eq!(dds_topic_name("/robot/scan"), "rt/robot/scan");
+    }
+
+    #[test]
+    fn test_twist_matches_ros2_wire_layout() {
+        let twist = Twist {
+            linear: Vector3 { x: 1.0, y: 0.0, z: 0.0 },
+            angular: Vector3 { x: 0.0, y: 0.0, z: 0.5 },
+        };
+        let twist = Identity::<Twist>::to_ros2(twist).unwrap();
+        let bytes = cdr::serialize::<_, _, cdr::CdrLe>(&twist, cdr::Infinite).unwrap();
+        // Encapsulation header, then six little-endian doubles
+        assert_eq!(&bytes[..4], &[0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(bytes.len(), 4 + 6 * 8);
+        assert_eq!(&bytes[4..12], &1.0f64.to_le_bytes());
+        assert_eq!(&bytes[44..52], &0.5f64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_standard_messages_keep_their_names() {
+        use crate::msgs::{nav_msgs, sensor_msgs, std_msgs};
+
+        fn same_name<M: Message + Ros2Message>() {
+            assert_eq!(M::type_name(), M::ROS2_TYPE);
+        }
+        same_name::<std_msgs::Header>();
+        same_name::<Twist>();
+        same_name::<sensor_msgs::PointCloud2>();
+        same_name::<sensor_msgs::JointState>();
+        same_name::<nav_msgs::OccupancyGrid>();
+        same_name::<nav_msgs::Path>();
+    }
+}
@@ -0,0 +1,26 @@
+//! Error types for the ROS 2 bridge
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Core(#[from] agentic_robotics_core::Error),
+
+    #[error("DDS error: {0}")]
+    Dds(String),
+
+    #[error("Configuration error: {0}")]
+    Configuration(String),
+
+    #[error("No conversion registered for ROS 2 type '{ros2_type}' (topic '{topic}')")]
+    UnknownType { topic: String, ros2_type: String },
+
+    #[error("Conversion error: {0}")]
+    Conversion(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
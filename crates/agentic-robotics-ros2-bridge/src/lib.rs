@@ -0,0 +1,19 @@
+//! ROS 2 interoperability bridge
+//!
+//! Republishes ros3 topics on ROS 2 (DDS) and ROS 2 topics on ros3, so
+//! legacy ROS 2 nodes can talk to ros3 nodes. Each bridged topic is listed
+//! in a [`BridgeConfig`] with its ROS 2 name and type; samples are converted
+//! between the ros3 encoding and ROS 2's little-endian CDR by a
+//! [`Ros2Conversion`]. Conversions for common `std_msgs`, `geometry_msgs`
+//! and `sensor_msgs` types are built in, see [`msgs`].
+
+pub mod bridge;
+pub mod config;
+pub mod convert;
+pub mod error;
+pub mod msgs;
+
+pub use bridge::{Bridge, MismatchLog};
+pub use config::{BridgeConfig, Direction, TopicMapping};
+pub use convert::{Identity, Ros2Conversion, Ros2Message};
+pub use error::{Error, Result};
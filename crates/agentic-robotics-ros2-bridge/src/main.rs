@@ -0,0 +1,30 @@
+//! `ros3-ros2-bridge <config.json>`
+//!
+//! Bridges the topics listed in a JSON config (see
+//! [`BridgeConfig`](agentic_robotics_ros2_bridge::BridgeConfig)) until
+//! interrupted.
+
+use agentic_robotics_ros2_bridge::{Bridge, BridgeConfig};
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let _ = agentic_robotics_core::init();
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: ros3-ros2-bridge <config.json>");
+        return ExitCode::FAILURE;
+    };
+    let result = async {
+        let mut bridge = Bridge::new(BridgeConfig::from_file(&path)?)?;
+        bridge.start()?;
+        tokio::signal::ctrl_c().await?;
+        Ok::<_, agentic_robotics_ros2_bridge::Error>(())
+    };
+    match result.await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ros3-ros2-bridge: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
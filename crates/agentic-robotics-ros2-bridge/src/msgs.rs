@@ -0,0 +1,117 @@
+//! ROS 2 standard messages
+//!
+//! Field layouts follow the ROS 2 interface definitions, so the CDR encoding
+//! of these structs is what ROS 2 nodes send. Each type is also a ros3
+//! [`Message`](agentic_robotics_core::Message) named after its ROS 2 type,
+//! so ros3 nodes can publish and subscribe to it directly.
+//!
+//! The common geometry, sensor and navigation types come from
+//! `agentic-robotics-msgs`, which already lays them out like ROS 2; this
+//! module adds the `std_msgs` primitives.
+
+/// Implement [`Ros2Message`](crate::Ros2Message) for a type that is already
+/// a ros3 `Message` of the same name
+macro_rules! ros2_type {
+    ($ty:ty, $name:literal) => {
+        impl $crate::convert::Ros2Message for $ty {
+            const ROS2_TYPE: &'static str = $name;
+        }
+    };
+}
+
+/// Implement ros3 `Message` and [`Ros2Message`](crate::Ros2Message) for a
+/// ROS 2 type
+macro_rules! ros2_message {
+    ($ty:ty, $name:literal) => {
+        impl agentic_robotics_core::Message for $ty {
+            fn type_name() -> &'static str {
+                $name
+            }
+        }
+
+        ros2_type!($ty, $name);
+    };
+}
+
+pub mod builtin_interfaces {
+    pub use agentic_robotics_msgs::builtin_interfaces::Time;
+
+    ros2_type!(Time, "builtin_interfaces/msg/Time");
+}
+
+pub mod std_msgs {
+    pub use agentic_robotics_msgs::std_msgs::Header;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    pub struct String {
+        pub data: std::string::String,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Bool {
+        pub data: bool,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Int32 {
+        pub data: i32,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Int64 {
+        pub data: i64,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Float32 {
+        pub data: f32,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Float64 {
+        pub data: f64,
+    }
+
+    ros2_type!(Header, "std_msgs/msg/Header");
+    ros2_message!(String, "std_msgs/msg/String");
+    ros2_message!(Bool, "std_msgs/msg/Bool");
+    ros2_message!(Int32, "std_msgs/msg/Int32");
+    ros2_message!(Int64, "std_msgs/msg/Int64");
+    ros2_message!(Float32, "std_msgs/msg/Float32");
+    ros2_message!(Float64, "std_msgs/msg/Float64");
+}
+
+pub mod geometry_msgs {
+    pub use agentic_robotics_msgs::geometry_msgs::{
+        Point, Pose, PoseStamped, Quaternion, Twist, Vector3,
+    };
+
+    ros2_type!(Vector3, "geometry_msgs/msg/Vector3");
+    ros2_type!(Point, "geometry_msgs/msg/Point");
+    ros2_type!(Quaternion, "geometry_msgs/msg/Quaternion");
+    ros2_type!(Pose, "geometry_msgs/msg/Pose");
+    ros2_type!(PoseStamped, "geometry_msgs/msg/PoseStamped");
+    ros2_type!(Twist, "geometry_msgs/msg/Twist");
+}
+
+pub mod sensor_msgs {
+    pub use agentic_robotics_msgs::sensor_msgs::{
+        BatteryState, Imu, JointState, LaserScan, PointCloud2, PointField,
+    };
+
+    ros2_type!(Imu, "sensor_msgs/msg/Imu");
+    ros2_type!(LaserScan, "sensor_msgs/msg/LaserScan");
+    ros2_type!(PointField, "sensor_msgs/msg/PointField");
+    ros2_type!(PointCloud2, "sensor_msgs/msg/PointCloud2");
+    ros2_type!(BatteryState, "sensor_msgs/msg/BatteryState");
+    ros2_type!(JointState, "sensor_msgs/msg/JointState");
+}
+
+pub mod nav_msgs {
+    pub use agentic_robotics_msgs::nav_msgs::{MapMetaData, OccupancyGrid, Path};
+
+    ros2_type!(MapMetaData, "nav_msgs/msg/MapMetaData");
+    ros2_type!(OccupancyGrid, "nav_msgs/msg/OccupancyGrid");
+    ros2_type!(Path, "nav_msgs/msg/Path");
+}
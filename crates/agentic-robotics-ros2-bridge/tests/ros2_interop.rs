@@ -0,0 +1,119 @@
+//! Round trips through a real ROS 2 install
+//!
+//! Needs a sourced ROS 2 environment (the `ros2` CLI on the PATH) and the
+//! `ros2-integration` feature:
+//!
+//! ```sh
+//! cargo test -p agentic-robotics-ros2-bridge --features ros2-integration
+//! ```
+
+#![cfg(feature = "ros2-integration")]
+
+use agentic_robotics_core::Node;
+use agentic_robotics_ros2_bridge::msgs::geometry_msgs::{Twist, Vector3};
+use agentic_robotics_ros2_bridge::msgs::std_msgs;
+use agentic_robotics_ros2_bridge::{Bridge, BridgeConfig};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Kills the `ros2` process when the test ends, passed or not
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn ros2(args: &[&str]) -> ChildGuard {
+    let child = Command::new("ros2")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to run `ros2`; is a ROS 2 environment sourced?");
+    ChildGuard(child)
+}
+
+fn domain_id() -> u16 {
+    std::env::var("ROS_DOMAIN_ID")
+        .ok()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or(0)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ros2_string_reaches_ros3() {
+    let config = BridgeConfig::from_json(&format!(
+        r#"{{ "domain_id": {}, "topics": [
+            {{ "ros3": "/bridge_test/chatter", "ros2": "/bridge_test/chatter",
+               "type": "std_msgs/msg/String", "direction": "ros2_to_ros3" }}
+        ] }}"#,
+        domain_id()
+    ))
+    .unwrap();
+    let mut bridge = Bridge::new(config).unwrap();
+    bridge.start().unwrap();
+
+    let node = Node::new("ros2_interop_listener").unwrap();
+    let subscriber = node
+        .create_subscriber::<std_msgs::String>("/bridge_test/chatter")
+        .unwrap();
+    let _talker = ros2(&[
+        "topic",
+        "pub",
+        "-r",
+        "5",
+        "/bridge_test/chatter",
+        "std_msgs/msg/String",
+        "{data: hello from ros2}",
+    ]);
+
+    let msg = tokio::time::timeout(Duration::from_secs(30), subscriber.recv())
+        .await
+        .expect("no message from ROS 2 within 30s")
+        .unwrap();
+    assert_eq!(msg.data, "hello from ros2");
+    assert_eq!(bridge.mismatches().count("/bridge_test/chatter"), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ros3_twist_reaches_ros2() {
+    let config = BridgeConfig::from_json(&format!(
+        r#"{{ "domain_id": {}, "topics": [
+            {{ "ros3": "/bridge_test/cmd_vel", "ros2": "/bridge_test/cmd_vel",
+               "type": "geometry_msgs/msg/Twist", "direction": "ros3_to_ros2" }}
+        ] }}"#,
+        domain_id()
+    ))
+    .unwrap();
+    let mut bridge = Bridge::new(config).unwrap();
+    bridge.start().unwrap();
+
+    let node = Node::new("ros2_interop_talker").unwrap();
+    let publisher = node.create_publisher::<Twist>("/bridge_test/cmd_vel").unwrap();
+    let mut echo = ros2(&[
+        "topic",
+        "echo",
+        "--once",
+        "/bridge_test/cmd_vel",
+        "geometry_msgs/msg/Twist",
+    ]);
+    let twist = Twist {
+        linear: Vector3 { x: 1.5, y: 0.0, z: 0.0 },
+        angular: Vector3 { x: 0.0, y: 0.0, z: -0.25 },
+    };
+
+    // Publish until `ros2 topic echo --once` has matched and printed
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while echo.0.try_wait().unwrap().is_none() {
+        assert!(Instant::now() < deadline, "ROS 2 saw no message within 30s");
+        publisher.publish(&twist).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    let mut output = String::new();
+    std::io::Read::read_to_string(echo.0.stdout.as_mut().unwrap(), &mut output).unwrap();
+    assert!(output.contains("x: 1.5"), "unexpected echo output: {}", output);
+    assert!(output.contains("z: -0.25"), "unexpected echo output: {}", output);
+}
@@ -0,0 +1,226 @@
+//! CPU budgets for background tasks
+//!
+//! A task spawned with [`ROS3Executor::spawn_with_budget`] has its polls
+//! timed. Once they add up to more than its share of the current period, the
+//! task is suspended until the period in which its overrun is paid off, so
+//! diagnostics and logging cannot take cycles from control tasks on the same
+//! threads.
+//!
+//! Suspension happens between polls, so a task has to reach an `.await`
+//! regularly; CPU-bound loops should call [`tokio::task::yield_now`] every
+//! few hundred microseconds.
+//!
+//! [`ROS3Executor::spawn_with_budget`]: crate::ROS3Executor::spawn_with_budget
+
+use crate::metrics::MetricsState;
+use crate::RTPriority;
+use anyhow::{bail, Result};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::time::Sleep;
+use tracing::trace;
+
+/// Share of one executor thread a task may use per period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// Within 1..=100
+    pub cpu_percent: u8,
+    /// Shorter periods throttle in smaller, more frequent steps
+    pub period: Duration,
+}
+
+impl Budget {
+    pub fn new(cpu_percent: u8, period: Duration) -> Result<Self> {
+        let budget = Self {
+            cpu_percent,
+            period,
+        };
+        budget.validate()?;
+        Ok(budget)
+    }
+
+    /// Poll time allowed per period
+    pub fn allowance(&self) -> Duration {
+        self.period * u32::from(self.cpu_percent) / 100
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(1..=100).contains(&self.cpu_percent) {
+            bail!("budget of {}% must be within 1..=100", self.cpu_percent);
+        }
+        if self.allowance().is_zero() {
+            bail!("budget period {:?} is too short", self.period);
+        }
+        Ok(())
+    }
+}
+
+/// Task future throttled to a [`Budget`]
+pub(crate) struct Budgeted<F> {
+    task: Pin<Box<F>>,
+    budget: Budget,
+    allowance: Duration,
+    period_start: Instant,
+    /// Poll time charged to the current period, including overruns carried
+    /// over from earlier ones
+    used: Duration,
+    /// Set while the task is suspended
+    resume: Option<Pin<Box<Sleep>>>,
+    metrics: Arc<MetricsState>,
+    priority: RTPriority,
+}
+
+impl<F: Future> Budgeted<F> {
+    pub(crate) fn new(
+        budget: Budget,
+        task: F,
+        metrics: Arc<MetricsState>,
+        priority: RTPriority,
+    ) -> Self {
+        Self {
+            task: Box::pin(task),
+            budget,
+            allowance: budget.allowance(),
+            period_start: Instant::now(),
+            used: Duration::ZERO,
+            resume: None,
+            metrics,
+            priority,
+        }
+    }
+
+    /// Start the period `now` falls in; unused budget is lost, overruns
+    /// are paid off one allowance per period
+    fn advance(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.period_start);
+        let periods = elapsed.as_nanos() / self.budget.period.as_nanos();
+        if periods == 0 {
+            return;
+        }
+        let periods = u32::try_from(periods).unwrap_or(u32::MAX);
+        self.period_start += self.budget.period * periods;
+        self.used = self.used.saturating_sub(self.allowance * periods);
+    }
+}
+
+impl<F: Future> Future for Budgeted<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        if let Some(resume) = &mut this.resume {
+            if resume.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.resume = None;
+        }
+        this.advance(Instant::now());
+
+        let start = Instant::now();
+        let result = this.task.as_mut().poll(cx);
+        this.used += start.elapsed();
+        if result.is_ready() || this.used <= this.allowance {
+            return result;
+        }
+
+        // Suspend until enough periods have passed to pay off the overrun
+        this.metrics.budget_overrun(this.priority);
+        let periods = this.used.as_nanos() / this.allowance.as_nanos();
+        let periods = u32::try_from(periods).unwrap_or(u32::MAX);
+        let resume_at = this.period_start + this.budget.period * periods;
+        trace!(
+            "Budgeted task used {:?} of {:?}, suspended until {:?}",
+            this.used,
+            this.allowance,
+            resume_at
+        );
+        let mut resume = Box::pin(tokio::time::sleep_until(resume_at.into()));
+        if resume.as_mut().poll(cx).is_ready() {
+            cx.waker().wake_by_ref();
+        } else {
+            this.resume = Some(resume);
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latency::LatencyTracker;
+    use crate::{Deadline, Priority, ROS3Executor};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn test_invalid_budget_is_rejected() {
+        assert!(Budget::new(0, Duration::from_millis(100)).is_err());
+        assert!(Budget::new(101, Duration::from_millis(100)).is_err());
+        assert!(Budget::new(10, Duration::from_nanos(5)).is_err());
+        let budget = Budget::new(5, Duration::from_millis(100)).unwrap();
+        assert_eq!(budget.allowance(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_spinning_task_is_throttled() {
+        let executor = ROS3Executor::new().unwrap();
+        let run_for = Duration::from_millis(500);
+        let start = Instant::now();
+
+        // Spins without ever sleeping, yielding every 100 µs
+        let spun = Arc::new(AtomicU64::new(0));
+        let spinner_spun = spun.clone();
+        let budget = Budget {
+            cpu_percent: 10,
+            period: Duration::from_millis(20),
+        };
+        let spinner = executor
+            .spawn_with_budget(budget, async move {
+                while start.elapsed() < run_for {
+                    let burst = Instant::now();
+                    while burst.elapsed() < Duration::from_micros(100) {
+                        std::hint::spin_loop();
+                    }
+                    spinner_spun.fetch_add(burst.elapsed().as_micros() as u64, Ordering::Relaxed);
+                    tokio::task::yield_now().await;
+                }
+            })
+            .unwrap();
+
+        // Latency-sensitive task on the same runtime
+        let oversleep = Arc::new(LatencyTracker::new("oversleep"));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tracker = oversleep.clone();
+        let deadline = Deadline(Duration::from_secs(1));
+        executor.spawn_named("sensitive", Priority(2), deadline, async move {
+            while start.elapsed() < run_for {
+                let slept = Instant::now();
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                tracker.record(slept.elapsed().saturating_sub(Duration::from_millis(1)));
+            }
+            tx.send(()).unwrap();
+        });
+
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        executor.low_priority_runtime().block_on(spinner).unwrap();
+
+        let share = spun.load(Ordering::Relaxed) as f64 / run_for.as_micros() as f64;
+        assert!(
+            share > 0.0 && share < 0.2,
+            "spinner used {:.1}%",
+            share * 100.0
+        );
+        // With a single core the spinner's 10% still lands in bursts the
+        // sleeper has to wait out, so oversleep only says something when
+        // the two can run side by side
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if cores >= 2 {
+            let p99 = oversleep.stats().p99;
+            assert!(p99 < 5_000, "p99 oversleep {p99} µs");
+        }
+        let background = &executor.metrics().priorities[RTPriority::Background as usize];
+        assert!(background.budget_overruns > 0);
+    }
+}
@@ -0,0 +1,349 @@
+//! Deterministic single-threaded scheduling
+//!
+//! An executor created with [`ROS3Executor::new_deterministic`] does not
+//! hand its tasks to runtime threads. Each [`step`](ROS3Executor::step) polls
+//! one ready task, picked by a generator seeded with the executor's seed, or,
+//! when no task is ready, moves the executor's sim clock to the earliest
+//! pending sleep. The sequence of steps is therefore a pure function of the
+//! spawn order and the seed: a test that publishes, subscribes and sleeps on
+//! [`ROS3Executor::clock`] produces the same trace on every run, and a
+//! failure found with one seed replays with that seed.
+//!
+//! Only tasks spawned through the executor are scheduled this way. Work a
+//! task passes to `tokio::spawn`, such as a `Node` timer, is never run;
+//! periodic work should loop on `clock().sleep(period)` in an executor task
+//! instead. Tokio timers do not advance either, so tasks must not use
+//! `tokio::time`.
+//!
+//! [`ROS3Executor::new_deterministic`]: crate::ROS3Executor::new_deterministic
+//! [`ROS3Executor::clock`]: crate::ROS3Executor::clock
+
+use agentic_robotics_core::time::{SimClock, Time};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Wake, Waker};
+use tracing::{trace, warn};
+
+type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// One scheduling decision of a deterministic executor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Step {
+    /// The task with id `task`, numbered in spawn order from 0, was polled
+    Polled {
+        task: u64,
+        name: String,
+        /// Whether the poll completed the task
+        finished: bool,
+    },
+    /// No task was ready, so the sim clock was moved to the earliest sleep
+    Advanced(Time),
+}
+
+/// Tasks of a deterministic executor and the order they run in
+pub(crate) struct DeterministicScheduler {
+    clock: SimClock,
+    rng: Mutex<SplitMix64>,
+    /// Set by shutdown; no tasks are spawned afterwards
+    closed: AtomicBool,
+    state: Mutex<TaskTable>,
+    /// Ids of the woken tasks, in wake order and without duplicates
+    ready: Arc<Mutex<Vec<u64>>>,
+}
+
+#[derive(Default)]
+struct TaskTable {
+    next_id: u64,
+    tasks: BTreeMap<u64, Entry>,
+}
+
+struct Entry {
+    name: String,
+    waker: Waker,
+    /// Taken out while the task is polled, so it can spawn others
+    future: Option<Task>,
+}
+
+/// Queues its task when woken
+struct ReadyWaker {
+    id: u64,
+    ready: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Wake for ReadyWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut ready = self.ready.lock();
+        if !ready.contains(&self.id) {
+            ready.push(self.id);
+        }
+    }
+}
+
+impl DeterministicScheduler {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            clock: SimClock::new(Time::ZERO),
+            rng: Mutex::new(SplitMix64(seed)),
+            closed: AtomicBool::new(false),
+            state: Mutex::new(TaskTable::default()),
+            ready: Arc::default(),
+        }
+    }
+
+    pub(crate) fn clock(&self) -> &SimClock {
+        &self.clock
+    }
+
+    /// Queue `task` for its first poll
+    pub(crate) fn spawn<F>(&self, name: String, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if self.closed.load(Ordering::Acquire) {
+            warn!("Executor is shut down, not spawning task {}", name);
+            return;
+        }
+        let id = {
+            let mut state = self.state.lock();
+            let id = state.next_id;
+            state.next_id += 1;
+            let waker = Waker::from(Arc::new(ReadyWaker {
+                id,
+                ready: self.ready.clone(),
+            }));
+            state.tasks.insert(
+                id,
+                Entry {
+                    name,
+                    waker,
+                    future: Some(Box::pin(task)),
+                },
+            );
+            id
+        };
+        self.ready.lock().push(id);
+    }
+
+    /// Poll one ready task, or advance the clock if none is ready
+    ///
+    /// Returns `None` once no task is ready and none is sleeping.
+    pub(crate) fn step(&self) -> Option<Step> {
+        loop {
+            let id = {
+                let mut ready = self.ready.lock();
+                if ready.is_empty() {
+                    break;
+                }
+                let index = self.rng.lock().below(ready.len());
+                ready.remove(index)
+            };
+            // Tasks may be woken after they finished
+            let taken = self.state.lock().tasks.get_mut(&id).and_then(|entry| {
+                let future = entry.future.take()?;
+                Some((entry.name.clone(), entry.waker.clone(), future))
+            });
+            let Some((name, waker, mut future)) = taken else {
+                continue;
+            };
+
+            let finished = future
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_ready();
+
+            let mut state = self.state.lock();
+            if finished {
+                state.tasks.remove(&id);
+            } else if let Some(entry) = state.tasks.get_mut(&id) {
+                entry.future = Some(future);
+            }
+            trace!("Deterministic step polled task {} ({})", id, name);
+            return Some(Step::Polled {
+                task: id,
+                name,
+                finished,
+            });
+        }
+
+        let next = self.clock.next_deadline()?;
+        self.clock.set(next);
+        trace!("Deterministic step advanced the clock to {}", next);
+        Some(Step::Advanced(next))
+    }
+
+    /// Step until no task is ready and the next sleep ends after `until`, or
+    /// none is pending
+    pub(crate) fn run(&self, until: Option<Time>) -> Vec<Step> {
+        let mut steps = Vec::new();
+        loop {
+            let idle = self.ready.lock().is_empty();
+            if idle {
+                match (self.clock.next_deadline(), until) {
+                    (None, _) => break,
+                    (Some(next), Some(until)) if next > until => break,
+                    _ => {}
+                }
+            }
+            match self.step() {
+                Some(step) => steps.push(step),
+                None => break,
+            }
+        }
+        steps
+    }
+
+    /// Refuse new tasks and return how many are still running
+    pub(crate) fn close(&self) -> usize {
+        self.closed.store(true, Ordering::Release);
+        self.state.lock().tasks.len()
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Drop every remaining task, returning their names sorted
+    pub(crate) fn cancel(&self) -> Vec<String> {
+        let tasks = std::mem::take(&mut self.state.lock().tasks);
+        let mut names: Vec<String> = tasks.values().map(|entry| entry.name.clone()).collect();
+        // Dropping a task may wake others, which are gone too
+        drop(tasks);
+        self.ready.lock().clear();
+        names.sort();
+        names
+    }
+}
+
+/// SplitMix64, a small generator whose output depends only on its seed
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..bound`; `bound` must be non-zero
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Step;
+    use crate::{Deadline, Priority, ROS3Executor};
+    use agentic_robotics_core::serialization::Format;
+    use agentic_robotics_core::{Publisher, Subscriber};
+    use parking_lot::Mutex;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Two publishers and a subscriber driven by sim-time loops, returning
+    /// the steps taken and the order messages arrived in
+    fn scenario(seed: u64, topic: &str) -> (Vec<Step>, Vec<Value>) {
+        let executor = ROS3Executor::new_deterministic(seed).unwrap();
+        let _runtime = executor.low_priority_runtime().enter();
+        let clock = executor.clock();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = Subscriber::<Value>::new(topic).unwrap();
+        let log = received.clone();
+        let deadline = Deadline(Duration::from_secs(1));
+        executor.spawn_named("subscriber", Priority(2), deadline, async move {
+            while let Ok(message) = subscriber.recv().await {
+                log.lock().push(message);
+            }
+        });
+        for (name, period) in [("fast", 10), ("slow", 25)] {
+            // `Value` needs a self-describing format
+            let publisher = Publisher::<Value>::with_format(topic, Format::Json).unwrap();
+            let clock = clock.clone();
+            executor.spawn_named(name, Priority(2), deadline, async move {
+                for i in 0..4 {
+                    clock.sleep(Duration::from_millis(period)).await;
+                    publisher
+                        .publish(&json!(format!("{name}-{i}")))
+                        .await
+                        .unwrap();
+                    // Ready to publish again without waiting for the clock
+                    publisher
+                        .publish(&json!(format!("{name}-{i}b")))
+                        .await
+                        .unwrap();
+                }
+            });
+        }
+
+        let steps = executor.run_for(Duration::from_millis(200));
+        let received = received.lock().clone();
+        assert_eq!(received.len(), 16, "{received:?}");
+        (steps, received)
+    }
+
+    #[test]
+    fn test_same_seed_gives_identical_trace() {
+        let (steps, received) = scenario(7, "/test/deterministic/same");
+        let trace = serde_json::to_string(&steps).unwrap();
+        for _ in 0..5 {
+            let (again_steps, again) = scenario(7, "/test/deterministic/same");
+            assert_eq!(serde_json::to_string(&again_steps).unwrap(), trace);
+            assert_eq!(again, received);
+        }
+        assert!(steps.iter().any(|step| matches!(step, Step::Advanced(_))));
+    }
+
+    #[test]
+    fn test_seed_changes_interleaving() {
+        let (first, _) = scenario(0, "/test/deterministic/seeds");
+        let differs = (1..32).any(|seed| scenario(seed, "/test/deterministic/seeds").0 != first);
+        assert!(differs);
+    }
+
+    #[test]
+    fn test_step_polls_one_task_at_a_time() {
+        let executor = ROS3Executor::new_deterministic(1).unwrap();
+        let clock = executor.clock();
+        let deadline = Deadline(Duration::from_secs(1));
+        executor.spawn_named("sleeper", Priority(1), deadline, async move {
+            clock.sleep(Duration::from_millis(5)).await;
+        });
+
+        let polled = executor.step().unwrap();
+        assert!(matches!(
+            polled,
+            Step::Polled {
+                task: 0,
+                finished: false,
+                ..
+            }
+        ));
+        let advanced = executor.step().unwrap();
+        assert_eq!(advanced, Step::Advanced(executor.clock().now()));
+        assert_eq!(executor.clock().now().as_nanos(), 5_000_000);
+        let finished = executor.step().unwrap();
+        assert!(matches!(
+            finished,
+            Step::Polled {
+                task: 0,
+                finished: true,
+                ..
+            }
+        ));
+        assert_eq!(executor.step(), None);
+
+        assert_eq!(ROS3Executor::new().unwrap().step(), None);
+    }
+}
@@ -2,20 +2,39 @@
 //!
 //! Combines Tokio for soft real-time I/O and priority scheduling for hard real-time tasks
 
+use crate::budget::{Budget, Budgeted};
+use crate::deterministic::{DeterministicScheduler, Step};
+use crate::metrics::{ExecutorMetrics, MetricsState};
+use crate::restart::{self, RestartPolicy, Run, StatusTable, Supervisor, TaskStatus};
 use crate::scheduler::PriorityScheduler;
 use crate::RTPriority;
-use anyhow::Result;
-use parking_lot::Mutex;
+use agentic_robotics_core::diagnostics::DiagnosticStatus;
+use agentic_robotics_core::publisher::Publisher;
+use agentic_robotics_core::time::Clock;
+use agentic_robotics_core::topic::TopicBus;
+use anyhow::{bail, Result};
+use parking_lot::{Mutex, RwLock};
+use std::collections::hash_map::{Entry, HashMap};
 use std::future::Future;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::runtime::{Builder, Runtime};
-use tracing::{debug, info};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::sync::{oneshot, Notify};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{debug, info, warn};
 
 /// Task priority wrapper
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Priority(pub u8);
 
+impl From<RTPriority> for Priority {
+    fn from(priority: RTPriority) -> Self {
+        Priority(priority.into())
+    }
+}
+
 /// Task deadline
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Deadline(pub Duration);
@@ -26,22 +45,243 @@ impl From<Duration> for Deadline {
     }
 }
 
+/// OS scheduling policy for the executor's real-time threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedPolicy {
+    /// Ordinary time-sharing; no real-time scheduling is requested
+    #[default]
+    Other,
+    /// `SCHED_FIFO`: run until blocked or preempted by a higher priority
+    Fifo,
+    /// `SCHED_RR`: like `Fifo`, but time-sliced among equal priorities
+    RoundRobin,
+}
+
+/// How often [`ROS3Executor::publish_metrics`] publishes
+const METRICS_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long [`ROS3Executor::shutdown`] waits for cancelled tasks to be
+/// dropped, which happens on their next poll
+const CANCEL_GRACE: Duration = Duration::from_millis(100);
+
+/// What happens to a task that misses its deadline
+///
+/// Every miss is counted and reported to the
+/// [`on_deadline_miss`](ROS3Executor::on_deadline_miss) hook either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeadlinePolicy {
+    /// Let the task finish and report it then, with its full overrun
+    #[default]
+    Warn,
+    /// Drop the task at its deadline and report it
+    Cancel,
+    /// Report the task at its deadline, then panic it
+    Panic,
+}
+
+/// A task that missed its deadline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskInfo {
+    pub name: String,
+    pub priority: RTPriority,
+    /// The deadline the task was spawned with
+    pub deadline: Duration,
+    /// How long past its deadline the task ran
+    pub overrun: Duration,
+}
+
+type DeadlineHook = Arc<dyn Fn(TaskInfo) + Send + Sync>;
+
+/// Outcome of [`ROS3Executor::shutdown`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Tasks that finished within the drain deadline
+    pub completed: usize,
+    /// Names of the tasks cancelled at the drain deadline, sorted
+    pub cancelled: Vec<String>,
+    /// Whether every message queued for other processes was sent
+    pub flushed: bool,
+}
+
+/// Executor thread layout and scheduling
+///
+/// `Critical` tasks run on `rt_threads` dedicated threads at the top of
+/// `priority_range`, `High` tasks on as many threads at its bottom, and
+/// everything else on the ordinary time-shared runtime. The default keeps
+/// [`SchedPolicy::Other`], so no privileges are needed, and lets threads run
+/// on any core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutorConfig {
+    /// Threads for each of the critical and high-priority runtimes
+    pub rt_threads: usize,
+    pub policy: SchedPolicy,
+    /// OS priorities used for real-time threads, within 1..=99 on Linux
+    pub priority_range: RangeInclusive<i32>,
+    /// Cores every executor thread is restricted to; empty for all cores
+    pub cpu_affinity: Vec<usize>,
+    pub deadline_policy: DeadlinePolicy,
+    /// Polls taking longer than this are logged with the task name
+    pub long_poll_threshold: Duration,
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        Self {
+            rt_threads: 2,
+            policy: SchedPolicy::Other,
+            priority_range: 50..=80,
+            cpu_affinity: Vec::new(),
+            deadline_policy: DeadlinePolicy::Warn,
+            long_poll_threshold: Duration::from_millis(1),
+        }
+    }
+}
+
+impl ExecutorConfig {
+    /// Real-time configuration with `SCHED_FIFO` threads
+    pub fn realtime() -> Self {
+        Self {
+            policy: SchedPolicy::Fifo,
+            ..Self::default()
+        }
+    }
+
+    /// Keep all executor threads on `cores` so they never migrate elsewhere
+    pub fn cpu_affinity(mut self, cores: Vec<usize>) -> Self {
+        self.cpu_affinity = cores;
+        self
+    }
+}
+
+/// What the executor could set up on this system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether the critical and high-priority threads run under the
+    /// configured real-time policy
+    pub realtime: bool,
+    /// Why real-time scheduling was requested but is unavailable, e.g. a
+    /// missing `CAP_SYS_NICE`
+    pub fallback_reason: Option<String>,
+}
+
 /// ROS3 unified executor
 pub struct ROS3Executor {
+    tokio_rt_critical: Runtime,
     tokio_rt_high: Runtime,
     tokio_rt_low: Runtime,
     scheduler: Arc<Mutex<PriorityScheduler>>,
+    capabilities: Capabilities,
+    policy: SchedPolicy,
+    priority_range: (i32, i32),
+    /// Single-threaded runtimes for pinned tasks, by core and OS priority
+    pinned: Mutex<HashMap<(usize, Option<i32>), PinnedWorker>>,
+    deadlines: Arc<DeadlineMonitor>,
+    metrics: Arc<MetricsState>,
+    tasks: Arc<TaskRegistry>,
+    /// Tasks spawned with [`spawn_supervised`](Self::spawn_supervised)
+    statuses: StatusTable,
+    /// Set for executors created with
+    /// [`new_deterministic`](Self::new_deterministic)
+    deterministic: Option<DeterministicScheduler>,
+}
+
+/// Tasks still running, so shutdown can wait for and cancel them
+#[derive(Default)]
+struct TaskRegistry {
+    /// Set by shutdown; no tasks are spawned afterwards
+    closed: AtomicBool,
+    next_id: AtomicU64,
+    running: Mutex<HashMap<u64, RunningTask>>,
+    /// Notified whenever a task ends
+    ended: Notify,
+}
+
+struct RunningTask {
+    name: String,
+    /// `None` until the spawn returned
+    abort: Option<AbortHandle>,
+}
+
+/// Removes its task from the registry when the task is done or dropped
+struct Untrack {
+    registry: Arc<TaskRegistry>,
+    id: u64,
+}
+
+/// Deadline misses, shared with the monitored tasks
+struct DeadlineMonitor {
+    policy: DeadlinePolicy,
+    hook: RwLock<Option<DeadlineHook>>,
+    /// Misses by [`RTPriority`]
+    misses: [AtomicU64; 5],
+}
+
+/// Dedicated thread running the tasks pinned to one core
+struct PinnedWorker {
+    handle: Handle,
+    _shutdown: oneshot::Sender<()>,
 }
 
 impl ROS3Executor {
-    /// Create a new executor
+    /// Create a new executor with the default, non-real-time configuration
     pub fn new() -> Result<Self> {
-        info!("Initializing ROS3 unified executor");
+        Self::with_config(ExecutorConfig::default())
+    }
+
+    /// Create an executor with dedicated real-time threads
+    ///
+    /// If the policy cannot be applied, typically because the process lacks
+    /// `CAP_SYS_NICE`, the threads are created with ordinary scheduling and
+    /// [`capabilities`](Self::capabilities) reports why. Fails only for an
+    /// invalid configuration.
+    pub fn with_config(config: ExecutorConfig) -> Result<Self> {
+        info!("Initializing ROS3 unified executor with {:?}", config);
+
+        if config.rt_threads == 0 {
+            bail!("rt_threads must be at least 1");
+        }
+        let (low, high) = (*config.priority_range.start(), *config.priority_range.end());
+        if low > high || low < 1 || high > 99 {
+            bail!("priority_range {low}..={high} must be a non-empty range within 1..=99");
+        }
+        let cores = core_count();
+        if let Some(core) = config.cpu_affinity.iter().find(|&&core| core >= cores) {
+            bail!("cpu_affinity core {core} does not exist, this system has {cores} cores");
+        }
+        let affinity = Arc::new(config.cpu_affinity.clone());
+
+        let capabilities = probe(config.policy, high);
+        if let Some(reason) = &capabilities.fallback_reason {
+            warn!(
+                "Real-time scheduling unavailable, using ordinary threads: {}",
+                reason
+            );
+        }
+        let policy = if capabilities.realtime {
+            config.policy
+        } else {
+            SchedPolicy::Other
+        };
 
-        // High-priority runtime for control loops (2 threads)
+        // Critical runtime for hard real-time tasks
+        let tokio_rt_critical = Builder::new_multi_thread()
+            .worker_threads(config.rt_threads)
+            .thread_name("ros3-rt-critical")
+            .on_thread_start({
+                let affinity = affinity.clone();
+                move || configure_thread(policy, Some(high), &affinity)
+            })
+            .enable_all()
+            .build()?;
+
+        // High-priority runtime for control loops
         let tokio_rt_high = Builder::new_multi_thread()
-            .worker_threads(2)
+            .worker_threads(config.rt_threads)
             .thread_name("ros3-rt-high")
+            .on_thread_start({
+                let affinity = affinity.clone();
+                move || configure_thread(policy, Some(low), &affinity)
+            })
             .enable_all()
             .build()?;
 
@@ -49,42 +289,488 @@ impl ROS3Executor {
         let tokio_rt_low = Builder::new_multi_thread()
             .worker_threads(4)
             .thread_name("ros3-rt-low")
+            .on_thread_start(move || configure_thread(policy, None, &affinity))
             .enable_all()
             .build()?;
 
         let scheduler = Arc::new(Mutex::new(PriorityScheduler::new()));
 
         Ok(Self {
+            tokio_rt_critical,
             tokio_rt_high,
             tokio_rt_low,
             scheduler,
+            capabilities,
+            policy,
+            priority_range: (low, high),
+            pinned: Mutex::new(HashMap::new()),
+            deadlines: Arc::new(DeadlineMonitor {
+                policy: config.deadline_policy,
+                hook: RwLock::new(None),
+                misses: Default::default(),
+            }),
+            metrics: Arc::new(MetricsState::new(config.long_poll_threshold)),
+            tasks: Arc::default(),
+            statuses: StatusTable::default(),
+            deterministic: None,
+        })
+    }
+
+    /// Create an executor that runs its tasks one at a time, reproducibly
+    ///
+    /// Tasks are only polled by [`step`](Self::step) and the `run_*`
+    /// methods, on the calling thread, in an order that depends on nothing
+    /// but the spawn order and `seed`. Time is the sim clock returned by
+    /// [`clock`](Self::clock), which advances only when no task is ready. See
+    /// [`deterministic`](crate::deterministic) for what tasks may use.
+    ///
+    /// Deadlines are not monitored, and `spawn_pinned`, `spawn_with_budget`
+    /// and `publish_metrics` fail. `spawn_blocking` still runs on a thread.
+    pub fn new_deterministic(seed: u64) -> Result<Self> {
+        info!(
+            "Initializing deterministic ROS3 executor with seed {}",
+            seed
+        );
+        let config = ExecutorConfig::default();
+        let runtime = || Builder::new_current_thread().enable_all().build();
+
+        Ok(Self {
+            tokio_rt_critical: runtime()?,
+            tokio_rt_high: runtime()?,
+            tokio_rt_low: runtime()?,
+            scheduler: Arc::new(Mutex::new(PriorityScheduler::new())),
+            capabilities: Capabilities {
+                realtime: false,
+                fallback_reason: None,
+            },
+            policy: SchedPolicy::Other,
+            priority_range: (*config.priority_range.start(), *config.priority_range.end()),
+            pinned: Mutex::new(HashMap::new()),
+            deadlines: Arc::new(DeadlineMonitor {
+                policy: config.deadline_policy,
+                hook: RwLock::new(None),
+                misses: Default::default(),
+            }),
+            metrics: Arc::new(MetricsState::new(config.long_poll_threshold)),
+            tasks: Arc::default(),
+            statuses: StatusTable::default(),
+            deterministic: Some(DeterministicScheduler::new(seed)),
         })
     }
 
+    /// The clock tasks should use: the sim clock of a deterministic
+    /// executor, otherwise [`Clock::default`]
+    pub fn clock(&self) -> Clock {
+        match &self.deterministic {
+            Some(deterministic) => Clock::Sim(deterministic.clock().clone()),
+            None => Clock::default(),
+        }
+    }
+
+    /// Make one scheduling decision of a deterministic executor
+    ///
+    /// Polls one ready task, or, if none is ready, advances the sim clock to
+    /// the earliest pending sleep. Returns `None` when there is nothing left
+    /// to do, and always for an executor not created with
+    /// [`new_deterministic`](Self::new_deterministic).
+    pub fn step(&self) -> Option<Step> {
+        let deterministic = self.deterministic.as_ref()?;
+        let _runtime = self.tokio_rt_low.enter();
+        deterministic.step()
+    }
+
+    /// [`step`](Self::step) until every task is done or waits forever
+    ///
+    /// Never returns while a task keeps sleeping, e.g. in a periodic loop;
+    /// use [`run_for`](Self::run_for) for those.
+    pub fn run_until_idle(&self) -> Vec<Step> {
+        let Some(deterministic) = &self.deterministic else {
+            return Vec::new();
+        };
+        let _runtime = self.tokio_rt_low.enter();
+        deterministic.run(None)
+    }
+
+    /// [`step`](Self::step) until `duration` of sim time has passed and no
+    /// task is ready
+    ///
+    /// The clock ends at the last sleep that ended within `duration`, not
+    /// necessarily `duration` from now.
+    pub fn run_for(&self, duration: Duration) -> Vec<Step> {
+        let Some(deterministic) = &self.deterministic else {
+            return Vec::new();
+        };
+        let _runtime = self.tokio_rt_low.enter();
+        let until = deterministic.clock().now() + duration;
+        deterministic.run(Some(until))
+    }
+
+    /// Whether real-time scheduling is in effect, and why not if requested
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Scheduling latency, poll duration and queue depth per priority
+    pub fn metrics(&self) -> ExecutorMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Publish [`metrics`](Self::metrics) on `topic` once a second
+    ///
+    /// Runs on the low-priority runtime until the returned handle is aborted
+    /// or the executor is dropped.
+    pub fn publish_metrics(&self, topic: &str) -> Result<JoinHandle<()>> {
+        if self.deterministic.is_some() {
+            bail!("metrics cannot be published by a deterministic executor");
+        }
+        let publisher = Publisher::<ExecutorMetrics>::new(topic)?;
+        let metrics = self.metrics.clone();
+        let task = async move {
+            let mut interval = tokio::time::interval(METRICS_PERIOD);
+            loop {
+                interval.tick().await;
+                if let Err(err) = publisher.publish(&metrics.snapshot()).await {
+                    warn!("Failed to publish executor metrics: {}", err);
+                }
+            }
+        };
+        match self
+            .tasks
+            .spawn(self.tokio_rt_low.handle(), "metrics-publisher", task)
+        {
+            Some(handle) => Ok(handle),
+            None => bail!("executor is shut down"),
+        }
+    }
+
+    /// Collector reporting the executor's metrics and supervised tasks to a
+    /// [`DiagnosticUpdater`](agentic_robotics_core::diagnostics::DiagnosticUpdater)
+    ///
+    /// See [`ExecutorMetrics::diagnostics`] for the status of each priority;
+    /// an `executor/supervised` status lists the tasks spawned with
+    /// [`spawn_supervised`](Self::spawn_supervised) and is `Error` once one
+    /// of them has failed. Warnings cover what happened since the previous
+    /// call.
+    pub fn diagnostics(&self) -> impl FnMut() -> Vec<DiagnosticStatus> + Send + 'static {
+        let metrics = self.metrics.clone();
+        let statuses = self.statuses.clone();
+        let mut previous = metrics.snapshot();
+        move || {
+            let current = metrics.snapshot();
+            let mut report = current.diagnostics(&previous);
+            previous = current;
+            report.extend(restart::diagnostics(&statuses.lock()));
+            report
+        }
+    }
+
+    /// Call `hook` for every task that misses its deadline
+    ///
+    /// The hook runs on the task's executor thread, so it should be quick.
+    /// A later call replaces the hook.
+    pub fn on_deadline_miss<H>(&self, hook: H)
+    where
+        H: Fn(TaskInfo) + Send + Sync + 'static,
+    {
+        *self.deadlines.hook.write() = Some(Arc::new(hook));
+    }
+
+    /// Number of tasks of `priority` that missed their deadline
+    pub fn deadline_misses(&self, priority: RTPriority) -> u64 {
+        self.deadlines.misses[priority as usize].load(Ordering::Relaxed)
+    }
+
+    /// Number of tasks of any priority that missed their deadline
+    pub fn total_deadline_misses(&self) -> u64 {
+        self.deadlines
+            .misses
+            .iter()
+            .map(|misses| misses.load(Ordering::Relaxed))
+            .sum()
+    }
+
     /// Spawn a real-time task with priority and deadline
     pub fn spawn_rt<F>(&self, priority: Priority, deadline: Deadline, task: F)
     where
         F: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_named("rt-task", priority, deadline, task);
+    }
+
+    /// Spawn a real-time task reported as `name` when it misses its deadline
+    pub fn spawn_named<F>(
+        &self,
+        name: impl Into<String>,
+        priority: Priority,
+        deadline: Deadline,
+        task: F,
+    ) where
+        F: Future<Output = ()> + Send + 'static,
     {
         let rt_priority: RTPriority = priority.0.into();
+        let name = name.into();
+
+        debug!(
+            "Spawning RT task {} with priority {:?} and deadline {:?}",
+            name, rt_priority, deadline.0
+        );
+        if self.deterministic.is_some() {
+            self.spawn_instrumented(name, rt_priority, deadline.0, task);
+            return;
+        }
+        let task = self
+            .deadlines
+            .clone()
+            .monitor(name.clone(), rt_priority, deadline.0, task);
+        self.spawn_instrumented(name, rt_priority, deadline.0, task);
+    }
 
+    /// Spawn a task that is run again by `factory` when it panics
+    ///
+    /// Each run is a new future from `factory`, monitored against
+    /// `deadline` like a task from [`spawn_named`](Self::spawn_named). A
+    /// panic is caught and logged with `name`, then `policy` decides whether
+    /// the task is restarted; see [`restart`](crate::restart). The task's
+    /// state is reported by [`task_status`](Self::task_status) under `name`,
+    /// so supervised tasks need distinct names.
+    pub fn spawn_supervised<F, T>(
+        &self,
+        name: impl Into<String>,
+        priority: Priority,
+        deadline: Deadline,
+        policy: RestartPolicy,
+        mut factory: F,
+    ) where
+        F: FnMut() -> T + Send + 'static,
+        T: Future<Output = ()> + Send + 'static,
+    {
+        let rt_priority: RTPriority = priority.0.into();
+        let name = name.into();
         debug!(
-            "Spawning RT task with priority {:?} and deadline {:?}",
-            rt_priority, deadline.0
+            "Spawning supervised task {} with priority {:?} and {:?}",
+            name, rt_priority, policy
         );
 
-        // Route to appropriate runtime based on deadline
-        if deadline.0 < Duration::from_millis(1) {
+        let deadlines = self.deadlines.clone();
+        let monitored = self.deterministic.is_none();
+        let run_name = name.clone();
+        let start = move || -> Run {
+            let run = factory();
+            if monitored {
+                Box::pin(
+                    deadlines
+                        .clone()
+                        .monitor(run_name.clone(), rt_priority, deadline.0, run),
+                )
+            } else {
+                Box::pin(run)
+            }
+        };
+        let supervisor = Supervisor {
+            name: name.clone(),
+            priority: rt_priority,
+            policy,
+            statuses: self.statuses.clone(),
+            metrics: self.metrics.clone(),
+            clock: self.clock(),
+        };
+        self.spawn_instrumented(name, rt_priority, deadline.0, supervisor.run(start));
+    }
+
+    /// State of the task spawned with
+    /// [`spawn_supervised`](Self::spawn_supervised) as `name`
+    pub fn task_status(&self, name: &str) -> Option<TaskStatus> {
+        self.statuses.lock().get(name).cloned()
+    }
+
+    /// Instrument `task` and spawn it on the runtime for its priority
+    fn spawn_instrumented<F>(
+        &self,
+        name: String,
+        rt_priority: RTPriority,
+        deadline: Duration,
+        task: F,
+    ) where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let task = self.metrics.instrument(name.clone(), rt_priority, task);
+        if let Some(deterministic) = &self.deterministic {
+            deterministic.spawn(name, task);
+            return;
+        }
+
+        // Route by priority, then by deadline for ordinary tasks
+        let runtime = match rt_priority {
+            RTPriority::Critical => &self.tokio_rt_critical,
+            RTPriority::High => &self.tokio_rt_high,
             // Hard RT: Use high-priority runtime
-            self.tokio_rt_high.spawn(async move {
-                // In a real implementation with RTIC, this would use hardware interrupts
-                task.await;
-            });
-        } else {
+            _ if deadline < Duration::from_millis(1) => &self.tokio_rt_high,
             // Soft RT: Use low-priority runtime
-            self.tokio_rt_low.spawn(async move {
-                task.await;
-            });
+            _ => &self.tokio_rt_low,
+        };
+        if self
+            .tasks
+            .spawn(runtime.handle(), name.clone(), task)
+            .is_none()
+        {
+            warn!("Executor is shut down, not spawning task {}", name);
+        }
+    }
+
+    /// Spawn a task on a thread pinned to `core_id`
+    ///
+    /// Each core gets its own single-threaded runtime per priority class, so
+    /// the task never migrates; `Critical` and `High` tasks run under the
+    /// real-time policy like on the shared runtimes. Fails if `core_id` is
+    /// not a core of this system. On platforms other than Linux the task is
+    /// not actually pinned and a warning is logged.
+    pub fn spawn_pinned<F>(
+        &self,
+        core_id: usize,
+        priority: Priority,
+        task: F,
+    ) -> Result<JoinHandle<()>>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if self.deterministic.is_some() {
+            bail!("a deterministic executor cannot pin tasks");
+        }
+        let cores = core_count();
+        if core_id >= cores {
+            bail!("core {core_id} does not exist, this system has {cores} cores");
+        }
+        let os_priority = match RTPriority::from(priority.0) {
+            RTPriority::Critical => Some(self.priority_range.1),
+            RTPriority::High => Some(self.priority_range.0),
+            _ => None,
+        };
+
+        debug!(
+            "Spawning task pinned to core {} with priority {:?}",
+            core_id, priority
+        );
+
+        let mut pinned = self.pinned.lock();
+        let worker = match pinned.entry((core_id, os_priority)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(PinnedWorker::start(core_id, self.policy, os_priority)?)
+            }
+        };
+        let name = format!("pinned-{core_id}");
+        let task = self
+            .metrics
+            .instrument(name.clone(), priority.0.into(), task);
+        match self.tasks.spawn(&worker.handle, name, task) {
+            Some(handle) => Ok(handle),
+            None => bail!("executor is shut down"),
+        }
+    }
+
+    /// Spawn a background task limited to `budget` of an executor thread
+    ///
+    /// The task runs on the low-priority runtime and is counted under
+    /// [`RTPriority::Background`] in the metrics, including every time it
+    /// exceeds its budget and is suspended. See [`budget`](crate::budget)
+    /// for how the task must yield. Fails for an invalid budget or once the
+    /// executor is shut down.
+    pub fn spawn_with_budget<F>(&self, budget: Budget, task: F) -> Result<JoinHandle<()>>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        budget.validate()?;
+        if self.deterministic.is_some() {
+            bail!("a deterministic executor cannot enforce CPU budgets");
+        }
+        let priority = RTPriority::Background;
+        debug!("Spawning background task with budget {:?}", budget);
+        let name = format!("budgeted-{}%", budget.cpu_percent);
+        let task = Budgeted::new(budget, task, self.metrics.clone(), priority);
+        let task = self.metrics.instrument(name.clone(), priority, task);
+        match self.tasks.spawn(self.tokio_rt_low.handle(), name, task) {
+            Some(handle) => Ok(handle),
+            None => bail!("executor is shut down"),
+        }
+    }
+
+    /// Stop the executor's tasks, giving them up to `drain` to finish
+    ///
+    /// New tasks are refused from now on: `spawn_pinned` fails and the other
+    /// spawn methods log a warning and drop the task. Tasks still running at
+    /// the drain deadline are cancelled, i.e. dropped at their next await,
+    /// which releases their subscribers and other resources. Whatever is
+    /// left of `drain` goes to flushing the messages publishers queued for
+    /// other processes. Blocking work from
+    /// [`spawn_blocking`](Self::spawn_blocking) is not waited for.
+    ///
+    /// The runtimes keep running until the executor is dropped.
+    ///
+    /// A deterministic executor drains by stepping until `drain` of sim time
+    /// has passed, then reports whether anything is still queued for other
+    /// processes without waiting for it.
+    pub async fn shutdown(&self, drain: Duration) -> ShutdownReport {
+        if let Some(deterministic) = &self.deterministic {
+            let running = deterministic.close();
+            self.run_for(drain);
+            let cancelled = deterministic.cancel();
+            return ShutdownReport {
+                completed: running.saturating_sub(cancelled.len()),
+                cancelled,
+                flushed: TopicBus::global().pending_outgoing() == 0,
+            };
+        }
+        let deadline = tokio::time::Instant::now() + drain;
+        let running = self.tasks.close();
+        info!(
+            "Shutting down executor: draining {} tasks for up to {:?}",
+            running, drain
+        );
+
+        let drained = tokio::time::timeout_at(deadline, self.tasks.idle())
+            .await
+            .is_ok();
+        let cancelled = if drained {
+            Vec::new()
+        } else {
+            self.tasks.cancel()
+        };
+        for name in &cancelled {
+            warn!("Cancelled task {} at the drain deadline", name);
+        }
+        if tokio::time::timeout(CANCEL_GRACE, self.tasks.idle())
+            .await
+            .is_err()
+        {
+            warn!(
+                "{} cancelled tasks are still running, blocked outside an await",
+                self.tasks.running.lock().len()
+            );
+        }
+
+        let flushed = tokio::time::timeout_at(deadline, TopicBus::global().flush())
+            .await
+            .is_ok();
+        if !flushed {
+            warn!(
+                "{} outgoing messages were not sent within {:?}",
+                TopicBus::global().pending_outgoing(),
+                drain
+            );
+        }
+        ShutdownReport {
+            completed: running.saturating_sub(cancelled.len()),
+            cancelled,
+            flushed,
+        }
+    }
+
+    /// Whether the executor accepts tasks, i.e. [`shutdown`](Self::shutdown)
+    /// has not been called
+    pub fn is_running(&self) -> bool {
+        match &self.deterministic {
+            Some(deterministic) => !deterministic.is_closed(),
+            None => !self.tasks.closed.load(Ordering::Acquire),
         }
     }
 
@@ -113,6 +799,11 @@ impl ROS3Executor {
         self.tokio_rt_low.spawn_blocking(f)
     }
 
+    /// Get a handle to the critical runtime
+    pub fn critical_runtime(&self) -> &Runtime {
+        &self.tokio_rt_critical
+    }
+
     /// Get a handle to the high-priority runtime
     pub fn high_priority_runtime(&self) -> &Runtime {
         &self.tokio_rt_high
@@ -124,16 +815,318 @@ impl ROS3Executor {
     }
 }
 
+impl DeadlineMonitor {
+    /// Run `task`, reporting it if it is not done by `deadline`
+    async fn monitor<F>(
+        self: Arc<Self>,
+        name: String,
+        priority: RTPriority,
+        deadline: Duration,
+        task: F,
+    ) where
+        F: Future<Output = ()>,
+    {
+        let start = Instant::now();
+        let mut task = std::pin::pin!(task);
+        if tokio::time::timeout(deadline, task.as_mut()).await.is_ok() {
+            return;
+        }
+        if self.policy == DeadlinePolicy::Warn {
+            task.await;
+        }
+
+        let overrun = start.elapsed().saturating_sub(deadline);
+        self.misses[priority as usize].fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "Task {} missed its {:?} deadline by {:?}",
+            name, deadline, overrun
+        );
+        let hook = self.hook.read().clone();
+        if let Some(hook) = hook {
+            hook(TaskInfo {
+                name: name.clone(),
+                priority,
+                deadline,
+                overrun,
+            });
+        }
+        if self.policy == DeadlinePolicy::Panic {
+            panic!("task {name} missed its {deadline:?} deadline");
+        }
+    }
+}
+
+impl TaskRegistry {
+    /// Spawn `task` on `runtime`, tracked as `name`; `None` once closed
+    fn spawn<F>(
+        self: &Arc<Self>,
+        runtime: &Handle,
+        name: impl Into<String>,
+        task: F,
+    ) -> Option<JoinHandle<()>>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if self.closed.load(Ordering::Acquire) {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.running.lock().insert(
+            id,
+            RunningTask {
+                name: name.into(),
+                abort: None,
+            },
+        );
+        let untrack = Untrack {
+            registry: self.clone(),
+            id,
+        };
+        let handle = runtime.spawn(async move {
+            let _untrack = untrack;
+            task.await
+        });
+        if let Some(running) = self.running.lock().get_mut(&id) {
+            running.abort = Some(handle.abort_handle());
+        }
+        Some(handle)
+    }
+
+    /// Refuse new tasks; returns how many are running
+    fn close(&self) -> usize {
+        self.closed.store(true, Ordering::Release);
+        self.running.lock().len()
+    }
+
+    /// Wait until no task is running
+    async fn idle(&self) {
+        loop {
+            let ended = self.ended.notified();
+            let mut ended = std::pin::pin!(ended);
+            ended.as_mut().enable();
+            if self.running.lock().is_empty() {
+                return;
+            }
+            ended.await;
+        }
+    }
+
+    /// Cancel every running task, returning their names
+    fn cancel(&self) -> Vec<String> {
+        let running = self.running.lock();
+        let mut names: Vec<String> = running
+            .values()
+            .map(|task| {
+                if let Some(abort) = &task.abort {
+                    abort.abort();
+                }
+                task.name.clone()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+impl Drop for Untrack {
+    fn drop(&mut self) {
+        self.registry.running.lock().remove(&self.id);
+        self.registry.ended.notify_waiters();
+    }
+}
+
+impl PinnedWorker {
+    fn start(core: usize, policy: SchedPolicy, priority: Option<i32>) -> Result<Self> {
+        let (handle_tx, handle_rx) = std::sync::mpsc::channel();
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        std::thread::Builder::new()
+            .name(format!("ros3-pinned-{core}"))
+            .spawn(move || {
+                configure_thread(policy, priority, &[core]);
+                let runtime = match Builder::new_current_thread().enable_all().build() {
+                    Ok(runtime) => runtime,
+                    Err(err) => {
+                        let _ = handle_tx.send(Err(err));
+                        return;
+                    }
+                };
+                let _ = handle_tx.send(Ok(runtime.handle().clone()));
+                // Runs the pinned tasks until the executor is dropped
+                let _ = runtime.block_on(shutdown_rx);
+            })?;
+        Ok(Self {
+            handle: handle_rx.recv()??,
+            _shutdown: shutdown,
+        })
+    }
+}
+
 impl Default for ROS3Executor {
     fn default() -> Self {
         Self::new().expect("Failed to create ROS3Executor")
     }
 }
 
+/// Check whether `policy` can be applied, on a throwaway thread so the
+/// caller's own scheduling is untouched
+fn probe(policy: SchedPolicy, priority: i32) -> Capabilities {
+    if policy == SchedPolicy::Other {
+        return Capabilities {
+            realtime: false,
+            fallback_reason: None,
+        };
+    }
+    let result = std::thread::spawn(move || set_current_thread_policy(policy, priority))
+        .join()
+        .unwrap_or_else(|_| Err(std::io::Error::other("probe thread panicked")));
+    Capabilities {
+        realtime: result.is_ok(),
+        fallback_reason: result
+            .err()
+            .map(|err| format!("cannot set {policy:?}: {err}")),
+    }
+}
+
+/// Apply the scheduling policy and core affinity to a new executor thread
+fn configure_thread(policy: SchedPolicy, priority: Option<i32>, cores: &[usize]) {
+    if let Some(priority) = priority {
+        if let Err(err) = set_current_thread_policy(policy, priority) {
+            warn!(
+                "Failed to set {:?} priority {} on executor thread: {}",
+                policy, priority, err
+            );
+        }
+    }
+    if !cores.is_empty() {
+        if let Err(err) = set_current_thread_affinity(cores) {
+            warn!(
+                "Failed to pin executor thread to cores {:?}: {}",
+                cores, err
+            );
+        }
+    }
+}
+
+/// Cores the calling thread may run on
+///
+/// Called from inside a task, this shows where the executor actually placed
+/// it. Fails on platforms other than Linux.
+pub fn current_affinity() -> std::io::Result<Vec<usize>> {
+    current_thread_affinity()
+}
+
+/// Core the calling thread is running on right now, where the OS tells
+pub fn current_cpu() -> Option<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: takes no arguments
+        let cpu = unsafe { libc::sched_getcpu() };
+        usize::try_from(cpu).ok()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Number of cores valid as pinning targets
+fn core_count() -> usize {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: sysconf only reads system configuration
+        let online = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+        if online > 0 {
+            return online as usize;
+        }
+    }
+    std::thread::available_parallelism().map_or(1, |cores| cores.get())
+}
+
+#[cfg(target_os = "linux")]
+fn set_current_thread_affinity(cores: &[usize]) -> std::io::Result<()> {
+    // SAFETY: an all-zero cpu_set_t is the empty set, and the set is passed
+    // with its own size
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for &core in cores {
+            libc::CPU_SET(core, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn current_thread_affinity() -> std::io::Result<Vec<usize>> {
+    // SAFETY: as in `set_current_thread_affinity`
+    let set = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of_val(&set), &mut set) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        set
+    };
+    Ok((0..libc::CPU_SETSIZE as usize)
+        // SAFETY: `core` is below CPU_SETSIZE
+        .filter(|&core| unsafe { libc::CPU_ISSET(core, &set) })
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_current_thread_affinity(cores: &[usize]) -> std::io::Result<()> {
+    warn!(
+        "CPU affinity is only supported on Linux, not pinning to {:?}",
+        cores
+    );
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_thread_affinity() -> std::io::Result<Vec<usize>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "CPU affinity is only supported on Linux",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn set_current_thread_policy(policy: SchedPolicy, priority: i32) -> std::io::Result<()> {
+    let policy = match policy {
+        SchedPolicy::Other => return Ok(()),
+        SchedPolicy::Fifo => libc::SCHED_FIFO,
+        SchedPolicy::RoundRobin => libc::SCHED_RR,
+    };
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+    // SAFETY: `param` outlives the call and the thread handle is our own
+    let rc = unsafe { libc::pthread_setschedparam(libc::pthread_self(), policy, &param) };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::from_raw_os_error(rc))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_current_thread_policy(policy: SchedPolicy, _priority: i32) -> std::io::Result<()> {
+    match policy {
+        SchedPolicy::Other => Ok(()),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "real-time scheduling is only supported on Linux",
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
 
     #[test]
     fn test_executor_creation() {
@@ -154,4 +1147,278 @@ mod tests {
         std::thread::sleep(Duration::from_millis(100));
         // Note: In a real test, we'd use proper synchronization
     }
+
+    #[test]
+    fn test_default_config_needs_no_privileges() {
+        let executor = ROS3Executor::new().unwrap();
+        assert_eq!(
+            executor.capabilities(),
+            &Capabilities {
+                realtime: false,
+                fallback_reason: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        let config = ExecutorConfig {
+            priority_range: 0..=50,
+            ..ExecutorConfig::realtime()
+        };
+        assert!(ROS3Executor::with_config(config).is_err());
+        let config = ExecutorConfig {
+            rt_threads: 0,
+            ..ExecutorConfig::realtime()
+        };
+        assert!(ROS3Executor::with_config(config).is_err());
+    }
+
+    #[test]
+    fn test_realtime_falls_back_without_privileges() {
+        let executor = ROS3Executor::with_config(ExecutorConfig::realtime()).unwrap();
+        let capabilities = executor.capabilities();
+        assert_eq!(
+            capabilities.realtime,
+            capabilities.fallback_reason.is_none()
+        );
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        executor.spawn_rt(
+            RTPriority::Critical.into(),
+            Deadline(Duration::from_millis(1)),
+            async move {
+                tx.send(()).unwrap();
+            },
+        );
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_critical_preempts_spinning_normal_task() {
+        let config = ExecutorConfig {
+            rt_threads: 1,
+            ..ExecutorConfig::realtime()
+        };
+        let executor = ROS3Executor::with_config(config).unwrap();
+        if !executor.capabilities().realtime {
+            eprintln!("skipping: {:?}", executor.capabilities().fallback_reason);
+            return;
+        }
+
+        // Park the critical worker on CPU 0 until woken
+        let (wake_tx, wake_rx) = tokio::sync::oneshot::channel::<std::time::Instant>();
+        let (latency_tx, latency_rx) = std::sync::mpsc::channel();
+        executor.spawn_rt(
+            RTPriority::Critical.into(),
+            Deadline(Duration::from_millis(1)),
+            async move {
+                set_current_thread_affinity(&[0]).unwrap();
+                let woken_at = wake_rx.await.unwrap();
+                latency_tx.send(woken_at.elapsed()).unwrap();
+            },
+        );
+        std::thread::sleep(Duration::from_millis(50));
+
+        // Occupy CPU 0 with an ordinary task that never yields
+        let stop = Arc::new(AtomicBool::new(false));
+        let spinning = Arc::new(AtomicBool::new(false));
+        let (stop_flag, spinning_flag) = (stop.clone(), spinning.clone());
+        executor.spawn_rt(
+            RTPriority::Normal.into(),
+            Deadline(Duration::from_millis(100)),
+            async move {
+                set_current_thread_affinity(&[0]).unwrap();
+                spinning_flag.store(true, Ordering::SeqCst);
+                let started = std::time::Instant::now();
+                while !stop_flag.load(Ordering::SeqCst)
+                    && started.elapsed() < Duration::from_secs(2)
+                {
+                    std::hint::spin_loop();
+                }
+            },
+        );
+        while !spinning.load(Ordering::SeqCst) {
+            std::thread::yield_now();
+        }
+        std::thread::sleep(Duration::from_millis(20));
+
+        wake_tx.send(std::time::Instant::now()).unwrap();
+        let latency = latency_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        stop.store(true, Ordering::SeqCst);
+        assert!(
+            latency < Duration::from_millis(5),
+            "critical task woke after {latency:?}"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_spawn_pinned_stays_on_core() {
+        let executor = ROS3Executor::new().unwrap();
+        let core = current_affinity().unwrap()[0];
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        for priority in [RTPriority::High, RTPriority::Normal] {
+            let tx = tx.clone();
+            let task = async move {
+                tx.send((current_affinity().unwrap(), current_cpu()))
+                    .unwrap();
+            };
+            executor.spawn_pinned(core, priority.into(), task).unwrap();
+        }
+        for _ in 0..2 {
+            let (affinity, cpu) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(affinity, [core]);
+            assert_eq!(cpu, Some(core));
+        }
+        assert_eq!(executor.pinned.lock().len(), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_config_affinity_applies_to_all_threads() {
+        let core = current_affinity().unwrap()[0];
+        let config = ExecutorConfig::default().cpu_affinity(vec![core]);
+        let executor = ROS3Executor::with_config(config).unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        for priority in [RTPriority::Critical, RTPriority::High, RTPriority::Low] {
+            let tx = tx.clone();
+            let deadline = Deadline(Duration::from_millis(10));
+            executor.spawn_rt(priority.into(), deadline, async move {
+                tx.send(current_affinity().unwrap()).unwrap();
+            });
+        }
+        for _ in 0..3 {
+            assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), [core]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_core_is_rejected() {
+        let executor = ROS3Executor::new().unwrap();
+        let task = executor.spawn_pinned(core_count(), Priority(2), async {});
+        assert!(task.is_err());
+
+        let config = ExecutorConfig::default().cpu_affinity(vec![core_count()]);
+        assert!(ROS3Executor::with_config(config).is_err());
+    }
+
+    fn deadline_executor(policy: DeadlinePolicy) -> (ROS3Executor, mpsc::Receiver<TaskInfo>) {
+        let config = ExecutorConfig {
+            deadline_policy: policy,
+            ..ExecutorConfig::default()
+        };
+        let executor = ROS3Executor::with_config(config).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        executor.on_deadline_miss(move |info| tx.lock().send(info).unwrap());
+        (executor, rx)
+    }
+
+    #[test]
+    fn test_deadline_miss_reports_overrun() {
+        let (executor, misses) = deadline_executor(DeadlinePolicy::Warn);
+        let deadline = Duration::from_millis(20);
+        let completed = Arc::new(AtomicBool::new(false));
+        let completed_clone = completed.clone();
+
+        executor.spawn_named("slow", Priority(2), Deadline(deadline), async move {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            completed_clone.store(true, Ordering::SeqCst);
+        });
+        executor.spawn_named("fast", Priority(2), Deadline(deadline), async {});
+
+        let info = misses.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(info.name, "slow");
+        assert_eq!(info.priority, RTPriority::Normal);
+        assert_eq!(info.deadline, deadline);
+        assert!(
+            info.overrun >= Duration::from_millis(40),
+            "{:?}",
+            info.overrun
+        );
+        assert!(completed.load(Ordering::SeqCst));
+        assert_eq!(executor.deadline_misses(RTPriority::Normal), 1);
+        assert_eq!(executor.total_deadline_misses(), 1);
+        assert!(misses.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+
+    #[test]
+    fn test_deadline_miss_cancels_task() {
+        let (executor, misses) = deadline_executor(DeadlinePolicy::Cancel);
+        let completed = Arc::new(AtomicBool::new(false));
+        let completed_clone = completed.clone();
+
+        let deadline = Deadline(Duration::from_millis(10));
+        executor.spawn_named("cancelled", RTPriority::High.into(), deadline, async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            completed_clone.store(true, Ordering::SeqCst);
+        });
+
+        let info = misses.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(info.name, "cancelled");
+        assert!(info.overrun < Duration::from_millis(40));
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(!completed.load(Ordering::SeqCst));
+        assert_eq!(executor.deadline_misses(RTPriority::High), 1);
+    }
+
+    #[test]
+    fn test_metrics_find_long_polls() {
+        let executor = ROS3Executor::new().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let deadline = Deadline(Duration::from_secs(1));
+        executor.spawn_named("blocking", Priority(2), deadline, async move {
+            std::thread::sleep(Duration::from_millis(5));
+            tokio::task::yield_now().await;
+            tx.send(()).unwrap();
+        });
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+
+        let metrics = executor.metrics();
+        let normal = &metrics.priorities[RTPriority::Normal as usize];
+        assert_eq!(normal.tasks_spawned, 1);
+        assert_eq!(normal.polls, 2);
+        assert_eq!(normal.long_polls, 1);
+        assert_eq!(normal.queue_depth, 0);
+        assert_eq!(normal.wake_latency.count, 2);
+        assert_eq!(metrics.priorities[RTPriority::High as usize].polls, 0);
+    }
+
+    #[test]
+    fn test_shutdown_cancels_stragglers() {
+        use agentic_robotics_core::message::RobotState;
+        use agentic_robotics_core::subscriber::Subscriber;
+
+        let executor = ROS3Executor::new().unwrap();
+        let topic = "test/executor/shutdown";
+        let subscriber = Subscriber::<RobotState>::new(topic).unwrap();
+        let deadline = Deadline(Duration::from_secs(10));
+        executor.spawn_named("listener", Priority(2), deadline, async move {
+            let _ = subscriber.recv().await;
+        });
+        executor.spawn_named("finishing", Priority(2), deadline, async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        });
+        assert_eq!(TopicBus::global().subscriber_count(topic), 1);
+
+        let shutdown = executor.shutdown(Duration::from_millis(100));
+        let report = executor.low_priority_runtime().block_on(shutdown);
+        assert_eq!(report.cancelled, ["listener"]);
+        assert_eq!(report.completed, 1);
+        assert!(report.flushed);
+        // The cancelled task dropped its subscriber instead of leaking it
+        assert_eq!(TopicBus::global().subscriber_count(topic), 0);
+
+        let (tx, rx) = mpsc::channel();
+        executor.spawn_named("late", Priority(2), deadline, async move {
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        assert!(executor.spawn_pinned(0, Priority(2), async {}).is_err());
+    }
 }
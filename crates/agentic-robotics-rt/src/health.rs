@@ -0,0 +1,278 @@
+//! HTTP health and metrics endpoint for orchestrators and scrapers
+//!
+//! A [`HealthServer`] answers three `GET` routes:
+//!
+//! - `/healthz`: 200 while the executor is running and no watchdog is
+//!   expired, 503 with the reasons otherwise
+//! - `/readyz`: like `/healthz`, and additionally 503 until every publisher
+//!   and subscriber of the node has matched
+//!   ([`Node::unmatched_endpoints`])
+//! - `/metrics`: the Prometheus text format of [`prometheus::render`]
+//!
+//! The server speaks just enough HTTP/1.1 for probes and scrapers: one
+//! request per connection, no bodies. It stops when the node shuts down or
+//! the server is dropped.
+
+use crate::executor::ROS3Executor;
+use crate::prometheus;
+use crate::watchdog;
+use agentic_robotics_core::Node;
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Largest request head accepted, in bytes
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where a [`HealthServer`] listens
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// Port 0 picks a free port, see [`HealthServer::local_addr`]
+    pub address: SocketAddr,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([0, 0, 0, 0], 9090)),
+        }
+    }
+}
+
+/// Running health endpoint, see the [module documentation](self)
+pub struct HealthServer {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl HealthServer {
+    /// Bind to `config.address` and serve `executor` and `node`
+    ///
+    /// Must be called from within a Tokio runtime. Fails if the address
+    /// cannot be bound.
+    pub async fn start(
+        config: HealthConfig,
+        executor: Arc<ROS3Executor>,
+        node: Arc<Node>,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(config.address)
+            .await
+            .with_context(|| format!("failed to bind health endpoint to {}", config.address))?;
+        let local_addr = listener.local_addr()?;
+        info!("Serving health endpoint on {}", local_addr);
+
+        let task = tokio::spawn(async move {
+            loop {
+                let accepted = tokio::select! {
+                    _ = node.closed() => break,
+                    accepted = listener.accept() => accepted,
+                };
+                match accepted {
+                    Ok((stream, peer)) => {
+                        let executor = executor.clone();
+                        let node = node.clone();
+                        tokio::spawn(async move {
+                            if let Err(err) = serve(stream, &executor, &node).await {
+                                debug!("Health request from {} failed: {}", peer, err);
+                            }
+                        });
+                    }
+                    Err(err) => warn!("Failed to accept health connection: {}", err),
+                }
+            }
+            debug!("Health endpoint on {} stopped", local_addr);
+        });
+        Ok(Self { local_addr, task })
+    }
+
+    /// The bound address, with the actual port if port 0 was configured
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Whether the server stopped, e.g. because the node shut down
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for HealthServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A response status and body
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+}
+
+/// Read one request from `stream` and answer it
+async fn serve(mut stream: TcpStream, executor: &ROS3Executor, node: &Node) -> Result<()> {
+    let head = tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream))
+        .await
+        .context("request timed out")??;
+    let response = match head {
+        Some(head) => route(&head, executor, node),
+        None => Response::text("431 Request Header Fields Too Large", "request too large\n"),
+    };
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// The request line and headers, or `None` if they exceed [`MAX_REQUEST`]
+async fn read_head(stream: &mut TcpStream) -> Result<Option<String>> {
+    let mut head = Vec::with_capacity(512);
+    let mut chunk = [0u8; 512];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST {
+            return Ok(None);
+        }
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            anyhow::bail!("connection closed before the end of the request");
+        }
+        head.extend_from_slice(&chunk[..read]);
+    }
+    Ok(Some(String::from_utf8_lossy(&head).into_owned()))
+}
+
+fn route(head: &str, executor: &ROS3Executor, node: &Node) -> Response {
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    // Probes may add a query string, which no route uses
+    let path = request_line
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+    if !matches!(path, "/healthz" | "/readyz" | "/metrics") {
+        return Response::text("404 Not Found", "not found\n");
+    }
+    if method != "GET" {
+        return Response::text("405 Method Not Allowed", "only GET is supported\n");
+    }
+
+    match path {
+        "/metrics" => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            body: prometheus::render(executor),
+        },
+        "/healthz" => probe(health_problems(executor)),
+        _ => {
+            let mut problems = health_problems(executor);
+            problems.extend(
+                node.unmatched_endpoints()
+                    .into_iter()
+                    .map(|topic| format!("{} is not matched", topic)),
+            );
+            probe(problems)
+        }
+    }
+}
+
+/// Why the process is unhealthy, empty if it is healthy
+fn health_problems(executor: &ROS3Executor) -> Vec<String> {
+    let mut problems = Vec::new();
+    if !executor.is_running() {
+        problems.push("executor is shut down".to_string());
+    }
+    for state in watchdog::states().into_iter().filter(|state| state.expired) {
+        problems.push(format!(
+            "watchdog {} not fed for {:?}",
+            state.name, state.since_feed
+        ));
+    }
+    problems
+}
+
+fn probe(problems: Vec<String>) -> Response {
+    if problems.is_empty() {
+        Response::text("200 OK", "ok\n")
+    } else {
+        let mut body = problems.join("\n");
+        body.push('\n');
+        Response::text("503 Service Unavailable", body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentic_robotics_core::message::RobotState;
+
+    async fn get(addr: SocketAddr, path: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split("\r\n\r\n").nth(1).unwrap().to_string();
+        (status, body)
+    }
+
+    #[test]
+    fn test_probes_and_metrics() {
+        let executor = Arc::new(ROS3Executor::new().unwrap());
+        let node = Arc::new(Node::with_namespace("probed", "/test/health", Vec::new()).unwrap());
+        let runtime = executor.low_priority_runtime();
+        runtime.block_on(async {
+            let config = HealthConfig {
+                address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            };
+            let server = HealthServer::start(config, executor.clone(), node.clone())
+                .await
+                .unwrap();
+            let addr = server.local_addr();
+            let _state = node.create_publisher::<RobotState>("state").unwrap();
+
+            assert_eq!(get(addr, "/healthz").await, (200, "ok\n".to_string()));
+            let (status, body) = get(addr, "/readyz").await;
+            assert_eq!(status, 503);
+            assert_eq!(body, "/test/health/state is not matched\n");
+
+            let _subscriber = node.create_subscriber::<RobotState>("state").unwrap();
+            assert_eq!(get(addr, "/readyz").await.0, 200);
+            let (status, body) = get(addr, "/metrics").await;
+            assert_eq!(status, 200);
+            assert!(body.contains("ros3_topic_publishers{topic=\"/test/health/state\"} 1\n"));
+            assert_eq!(get(addr, "/other").await.0, 404);
+
+            node.shutdown();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(server.is_finished());
+            assert!(TcpStream::connect(addr).await.is_err());
+        });
+    }
+}
@@ -1,43 +1,198 @@
 //! High-precision latency tracking using HDR histogram
+//!
+//! Every recording thread writes into a histogram of its own, so recording
+//! never waits on other recorders; reading merges the per-thread histograms.
 
+use anyhow::Result;
+use base64::Engine;
+use hdrhistogram::serialization::{Serializer, V2DeflateSerializer};
 use hdrhistogram::Histogram;
 use parking_lot::Mutex;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
+/// Resolution of windowed statistics
+const WINDOW_SLOT: Duration = Duration::from_secs(1);
+
+/// Longest window [`LatencyTracker::stats_window`] covers
+pub const MAX_WINDOW: Duration = Duration::from_secs(60);
+
+static NEXT_TRACKER_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// This thread's histograms, by tracker id
+    static LOCAL_SHARDS: RefCell<Vec<(u64, Weak<Shard>)>> = const { RefCell::new(Vec::new()) };
+}
+
 /// Latency tracker with HDR histogram
+#[derive(Clone)]
 pub struct LatencyTracker {
-    histogram: Arc<Mutex<Histogram<u64>>>,
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    id: u64,
     name: String,
+    start: Instant,
+    shards: Mutex<Vec<Arc<Shard>>>,
+}
+
+/// One thread's measurements; only locked by other threads while reading
+struct Shard {
+    histograms: Mutex<ShardHistograms>,
+}
+
+struct ShardHistograms {
+    total: Histogram<u64>,
+    /// Measurements per [`WINDOW_SLOT`] since the tracker was created,
+    /// oldest first, covering at most [`MAX_WINDOW`]
+    slots: VecDeque<(u64, Histogram<u64>)>,
 }
 
 impl LatencyTracker {
     /// Create a new latency tracker
     pub fn new(name: impl Into<String>) -> Self {
-        // 3 significant digits, max value 1 hour in microseconds
-        let histogram = Histogram::<u64>::new(3)
-            .expect("Failed to create histogram");
-
         Self {
-            histogram: Arc::new(Mutex::new(histogram)),
-            name: name.into(),
+            inner: Arc::new(Inner {
+                id: NEXT_TRACKER_ID.fetch_add(1, Ordering::Relaxed),
+                name: name.into(),
+                start: Instant::now(),
+                shards: Mutex::new(Vec::new()),
+            }),
         }
     }
 
     /// Record a latency measurement
     pub fn record(&self, duration: Duration) {
-        let micros = duration.as_micros() as u64;
-        if let Some(mut hist) = self.histogram.try_lock() {
-            let _ = hist.record(micros);
-        }
+        self.record_at(duration, Instant::now());
     }
 
-    /// Get latency statistics
+    /// Get latency statistics over everything recorded since the last reset
     pub fn stats(&self) -> LatencyStats {
-        let hist = self.histogram.lock();
+        self.stats_of(&self.merge(|histograms| vec![&histograms.total]))
+    }
 
+    /// Get latency statistics over the measurements of the last `window`
+    ///
+    /// The window is rounded up to whole seconds and capped at
+    /// [`MAX_WINDOW`].
+    pub fn stats_window(&self, window: Duration) -> LatencyStats {
+        self.stats_window_at(window, Instant::now())
+    }
+
+    /// Latency in microseconds below which `percentile` percent of all
+    /// measurements fall, e.g. `percentile(99.5)`
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        self.merge(|histograms| vec![&histograms.total])
+            .value_at_percentile(percentile)
+    }
+
+    /// Export all measurements as a V2 compressed histogram in base64
+    ///
+    /// This is the encoding of HdrHistogram interval logs, so runs can be
+    /// merged and plotted with the standard HdrHistogram tooling.
+    pub fn export_base64(&self) -> Result<String> {
+        let histogram = self.merge(|histograms| vec![&histograms.total]);
+        let mut encoded = Vec::new();
+        V2DeflateSerializer::new().serialize(&histogram, &mut encoded)?;
+        Ok(base64::engine::general_purpose::STANDARD.encode(encoded))
+    }
+
+    /// Reset the histogram, returning the statistics up to the reset
+    ///
+    /// Every measurement lands either in the returned statistics or after
+    /// the reset, never in neither.
+    pub fn reset(&self) -> LatencyStats {
+        let shards = self.inner.shards.lock();
+        let mut guards: Vec<_> = shards.iter().map(|shard| shard.histograms.lock()).collect();
+        let mut merged = empty_histogram();
+        for histograms in guards.iter_mut() {
+            let _ = merged.add(&histograms.total);
+            histograms.total.reset();
+            histograms.slots.clear();
+        }
+        self.stats_of(&merged)
+    }
+
+    /// Create a measurement guard
+    pub fn measure(&self) -> LatencyMeasurement {
+        LatencyMeasurement {
+            tracker: self.clone(),
+            start: Instant::now(),
+        }
+    }
+
+    fn record_at(&self, duration: Duration, now: Instant) {
+        let micros = duration.as_micros() as u64;
+        let slot = self.slot(now);
+        self.local_shard().histograms.lock().record(micros, slot);
+    }
+
+    fn stats_window_at(&self, window: Duration, now: Instant) -> LatencyStats {
+        let slots = window.min(MAX_WINDOW).as_nanos().div_ceil(WINDOW_SLOT.as_nanos()) as u64;
+        let first = (self.slot(now) + 1).saturating_sub(slots);
+        self.stats_of(&self.merge(|histograms| {
+            histograms
+                .slots
+                .iter()
+                .filter(|(slot, _)| *slot >= first)
+                .map(|(_, histogram)| histogram)
+                .collect()
+        }))
+    }
+
+    fn slot(&self, now: Instant) -> u64 {
+        let elapsed = now.saturating_duration_since(self.inner.start);
+        (elapsed.as_nanos() / WINDOW_SLOT.as_nanos()) as u64
+    }
+
+    /// This thread's shard, created on its first measurement
+    fn local_shard(&self) -> Arc<Shard> {
+        LOCAL_SHARDS.with(|local| {
+            let mut local = local.borrow_mut();
+            let existing = local
+                .iter()
+                .find(|(id, _)| *id == self.inner.id)
+                .and_then(|(_, shard)| shard.upgrade());
+            if let Some(shard) = existing {
+                return shard;
+            }
+            local.retain(|(_, shard)| shard.strong_count() > 0);
+            let shard = Arc::new(Shard {
+                histograms: Mutex::new(ShardHistograms {
+                    total: empty_histogram(),
+                    slots: VecDeque::new(),
+                }),
+            });
+            self.inner.shards.lock().push(shard.clone());
+            local.push((self.inner.id, Arc::downgrade(&shard)));
+            shard
+        })
+    }
+
+    /// Merge the histograms `select` picks from every shard
+    fn merge<F>(&self, select: F) -> Histogram<u64>
+    where
+        F: Fn(&ShardHistograms) -> Vec<&Histogram<u64>>,
+    {
+        let shards = self.inner.shards.lock().clone();
+        let mut merged = empty_histogram();
+        for shard in shards {
+            let histograms = shard.histograms.lock();
+            for histogram in select(&histograms) {
+                let _ = merged.add(histogram);
+            }
+        }
+        merged
+    }
+
+    fn stats_of(&self, hist: &Histogram<u64>) -> LatencyStats {
         LatencyStats {
-            name: self.name.clone(),
+            name: self.inner.name.clone(),
             count: hist.len(),
             min: hist.min(),
             max: hist.max(),
@@ -48,32 +203,41 @@ impl LatencyTracker {
             p999: hist.value_at_quantile(0.999),
         }
     }
+}
 
-    /// Reset the histogram
-    pub fn reset(&self) {
-        self.histogram.lock().reset();
-    }
-
-    /// Create a measurement guard
-    pub fn measure(&self) -> LatencyMeasurement {
-        LatencyMeasurement {
-            tracker: self.clone(),
-            start: Instant::now(),
+impl ShardHistograms {
+    fn record(&mut self, micros: u64, slot: u64) {
+        let _ = self.total.record(micros);
+        if let Some((last, histogram)) = self.slots.back_mut() {
+            if *last == slot {
+                let _ = histogram.record(micros);
+                return;
+            }
         }
+        // Recycle slots that fell out of the longest window
+        let retained = (MAX_WINDOW.as_nanos() / WINDOW_SLOT.as_nanos()) as u64;
+        let mut histogram = None;
+        while self
+            .slots
+            .front()
+            .is_some_and(|(oldest, _)| *oldest + retained <= slot)
+        {
+            histogram = self.slots.pop_front().map(|(_, histogram)| histogram);
+        }
+        let mut histogram = histogram.unwrap_or_else(empty_histogram);
+        histogram.reset();
+        let _ = histogram.record(micros);
+        self.slots.push_back((slot, histogram));
     }
 }
 
-impl Clone for LatencyTracker {
-    fn clone(&self) -> Self {
-        Self {
-            histogram: self.histogram.clone(),
-            name: self.name.clone(),
-        }
-    }
+fn empty_histogram() -> Histogram<u64> {
+    // 3 significant digits, resizing to the largest value recorded
+    Histogram::<u64>::new(3).expect("Failed to create histogram")
 }
 
 /// Latency statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LatencyStats {
     pub name: String,
     pub count: u64,
@@ -142,4 +306,82 @@ mod tests {
         assert_eq!(stats.count, 1);
         assert!(stats.min >= 100);
     }
+
+    #[test]
+    fn test_recording_threads_are_merged() {
+        let tracker = LatencyTracker::new("threads");
+        let handles: Vec<_> = (1..=4)
+            .map(|thread| {
+                let tracker = tracker.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        tracker.record(Duration::from_micros(thread * 100));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        tracker.record(Duration::from_micros(1000));
+
+        let stats = tracker.stats();
+        assert_eq!(stats.count, 401);
+        assert_eq!(stats.min, 100);
+        assert_eq!(tracker.percentile(99.5), 400);
+        assert_eq!(tracker.percentile(100.0), 1000);
+    }
+
+    #[test]
+    fn test_window_covers_recent_measurements() {
+        let tracker = LatencyTracker::new("window");
+        let start = tracker.inner.start;
+        for second in 0..10 {
+            let at = start + Duration::from_secs(second);
+            tracker.record_at(Duration::from_micros(second * 100 + 100), at);
+        }
+
+        let now = start + Duration::from_millis(9_500);
+        let recent = tracker.stats_window_at(Duration::from_secs(5), now);
+        assert_eq!(recent.count, 5);
+        assert_eq!(recent.min, 600);
+        assert_eq!(tracker.stats_window_at(Duration::from_millis(1), now).count, 1);
+        assert_eq!(tracker.stats().count, 10);
+
+        // Slots older than the longest window are dropped
+        let later = start + MAX_WINDOW + Duration::from_secs(5);
+        tracker.record_at(Duration::from_micros(50), later);
+        assert_eq!(tracker.stats_window_at(MAX_WINDOW * 2, later).count, 5);
+    }
+
+    #[test]
+    fn test_reset_returns_final_snapshot() {
+        let tracker = LatencyTracker::new("reset");
+        tracker.record(Duration::from_micros(100));
+        tracker.record(Duration::from_micros(300));
+
+        let last = tracker.reset();
+        assert_eq!(last.count, 2);
+        assert_eq!(last.max, 300);
+        assert_eq!(tracker.stats().count, 0);
+        assert_eq!(tracker.stats_window(MAX_WINDOW).count, 0);
+    }
+
+    #[test]
+    fn test_export_round_trips() {
+        let tracker = LatencyTracker::new("export");
+        for micros in [100, 200, 300] {
+            tracker.record(Duration::from_micros(micros));
+        }
+
+        let encoded = tracker.export_base64().unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        let histogram: Histogram<u64> = hdrhistogram::serialization::Deserializer::new()
+            .deserialize(&mut bytes.as_slice())
+            .unwrap();
+        assert_eq!(histogram.len(), 3);
+        assert_eq!(histogram.max(), tracker.stats().max);
+    }
 }
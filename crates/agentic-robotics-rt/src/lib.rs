@@ -2,17 +2,52 @@
 //!
 //! Dual runtime architecture combining Tokio (soft RT) and RTIC (hard RT)
 
+pub mod budget;
+pub mod deterministic;
 pub mod executor;
+#[cfg(feature = "health-endpoint")]
+pub mod health;
 pub mod scheduler;
+pub mod signal;
+pub mod sync;
 pub mod latency;
+pub mod metrics;
+pub mod prometheus;
+pub mod resources;
+pub mod restart;
+pub mod timer;
+pub mod watchdog;
 
-pub use executor::{ROS3Executor, Priority, Deadline};
+/// Lock-free SPSC and MPSC channels, shared with the core crate's queues
+pub use agentic_robotics_core::channel;
+/// Size-classed buffer pool shared with the core crate's serializers
+pub use agentic_robotics_core::pool;
+/// Timeline tracing of task polls, messages and timer ticks
+pub use agentic_robotics_core::trace;
+
+pub use budget::Budget;
+pub use deterministic::Step;
+pub use executor::{
+    current_affinity, current_cpu, Capabilities, Deadline, DeadlinePolicy, ExecutorConfig,
+    Priority, ROS3Executor, SchedPolicy, ShutdownReport, TaskInfo,
+};
+#[cfg(feature = "health-endpoint")]
+pub use health::{HealthConfig, HealthServer};
 pub use scheduler::PriorityScheduler;
+pub use sync::{PiMutex, PiMutexGuard};
 pub use latency::LatencyTracker;
+pub use metrics::{ExecutorMetrics, PriorityMetrics};
+pub use prometheus::{MetricFamily, MetricKind};
+pub use resources::{ResourceMonitor, ResourceUsage};
+pub use restart::{RestartPolicy, TaskEvent, TaskStatus};
+pub use timer::{MissedTickBehavior, RateTimer};
+pub use watchdog::{Watchdog, WatchdogExpiry, WatchdogState};
+
+use serde::{Deserialize, Serialize};
 
 
 /// Real-time task priority levels
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RTPriority {
     /// Lowest priority (background tasks)
     Background = 0,
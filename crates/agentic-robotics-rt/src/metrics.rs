@@ -0,0 +1,405 @@
+//! Executor run-time statistics
+//!
+//! Every task the executor spawns is wrapped so that its polls are timed and
+//! its wakes timestamped. That separates time spent waiting for the executor
+//! (wake to poll) from time spent in the task itself (poll duration).
+
+use crate::latency::{LatencyStats, LatencyTracker};
+use crate::RTPriority;
+use agentic_robotics_core::diagnostics::{DiagnosticLevel, DiagnosticStatus};
+use agentic_robotics_core::message::Message;
+use agentic_robotics_core::trace::{self, EventKind, Label};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+use tracing::{info_span, warn, Span};
+
+/// Snapshot of the executor's statistics, as returned by
+/// [`ROS3Executor::metrics`](crate::ROS3Executor::metrics)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutorMetrics {
+    /// One entry per priority, lowest first
+    pub priorities: Vec<PriorityMetrics>,
+}
+
+impl ExecutorMetrics {
+    /// One status per priority that has spawned tasks, `Warn` if long polls,
+    /// budget overruns or restarts were counted since `previous`
+    pub fn diagnostics(&self, previous: &ExecutorMetrics) -> Vec<DiagnosticStatus> {
+        self.priorities
+            .iter()
+            .zip(&previous.priorities)
+            .filter(|(metrics, _)| metrics.tasks_spawned > 0)
+            .map(|(metrics, previous)| {
+                let counts = [
+                    (metrics.long_polls, previous.long_polls, "long polls"),
+                    (
+                        metrics.budget_overruns,
+                        previous.budget_overruns,
+                        "budget overruns",
+                    ),
+                    (metrics.restarts, previous.restarts, "restarts"),
+                ];
+                let problems: Vec<String> = counts
+                    .iter()
+                    .filter(|(now, before, _)| now > before)
+                    .map(|(now, before, what)| format!("{} {}", now - before, what))
+                    .collect();
+                let (level, message) = if problems.is_empty() {
+                    (DiagnosticLevel::Ok, "Running".to_string())
+                } else {
+                    (DiagnosticLevel::Warn, problems.join(", "))
+                };
+                let name = format!("executor/{:?}", metrics.priority);
+                DiagnosticStatus::new(level, name, message)
+                    .with_value("tasks_spawned", metrics.tasks_spawned)
+                    .with_value("polls", metrics.polls)
+                    .with_value("long_polls", metrics.long_polls)
+                    .with_value("queue_depth", metrics.queue_depth)
+                    .with_value("max_queue_depth", metrics.max_queue_depth)
+                    .with_value("wake_latency_p99_us", metrics.wake_latency.p99)
+                    .with_value("poll_duration_p99_us", metrics.poll_duration.p99)
+                    .with_value("budget_overruns", metrics.budget_overruns)
+                    .with_value("restarts", metrics.restarts)
+            })
+            .collect()
+    }
+}
+
+impl Message for ExecutorMetrics {
+    fn type_name() -> &'static str {
+        "ros3_msgs/ExecutorMetrics"
+    }
+}
+
+/// Statistics of the tasks of one priority
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorityMetrics {
+    pub priority: RTPriority,
+    pub tasks_spawned: u64,
+    pub polls: u64,
+    /// Polls longer than the configured long-poll threshold
+    pub long_polls: u64,
+    /// Tasks woken and not yet polled
+    pub queue_depth: u64,
+    pub max_queue_depth: u64,
+    /// Time from a wake to the poll it caused
+    pub wake_latency: LatencyStats,
+    pub poll_duration: LatencyStats,
+    /// Times a budgeted task used up its budget and was suspended
+    #[serde(default)]
+    pub budget_overruns: u64,
+    /// Times a supervised task was restarted after a panic
+    #[serde(default)]
+    pub restarts: u64,
+}
+
+/// Statistics shared by the executor and its instrumented tasks
+pub(crate) struct MetricsState {
+    epoch: Instant,
+    long_poll: Duration,
+    /// By [`RTPriority`]
+    priorities: [PriorityState; 5],
+}
+
+struct PriorityState {
+    spawned: AtomicU64,
+    polls: AtomicU64,
+    long_polls: AtomicU64,
+    queue_depth: AtomicU64,
+    max_queue_depth: AtomicU64,
+    wake_latency: LatencyTracker,
+    poll_duration: LatencyTracker,
+    budget_overruns: AtomicU64,
+    restarts: AtomicU64,
+}
+
+impl MetricsState {
+    pub(crate) fn new(long_poll: Duration) -> Self {
+        Self {
+            epoch: Instant::now(),
+            long_poll,
+            priorities: std::array::from_fn(|priority| {
+                let priority = RTPriority::from(priority as u8);
+                PriorityState {
+                    spawned: AtomicU64::new(0),
+                    polls: AtomicU64::new(0),
+                    long_polls: AtomicU64::new(0),
+                    queue_depth: AtomicU64::new(0),
+                    max_queue_depth: AtomicU64::new(0),
+                    wake_latency: LatencyTracker::new(format!("{priority:?} wake latency")),
+                    poll_duration: LatencyTracker::new(format!("{priority:?} poll duration")),
+                    budget_overruns: AtomicU64::new(0),
+                    restarts: AtomicU64::new(0),
+                }
+            }),
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> ExecutorMetrics {
+        let priorities = self
+            .priorities
+            .iter()
+            .enumerate()
+            .map(|(priority, state)| PriorityMetrics {
+                priority: RTPriority::from(priority as u8),
+                tasks_spawned: state.spawned.load(Ordering::Relaxed),
+                polls: state.polls.load(Ordering::Relaxed),
+                long_polls: state.long_polls.load(Ordering::Relaxed),
+                queue_depth: state.queue_depth.load(Ordering::Relaxed),
+                max_queue_depth: state.max_queue_depth.load(Ordering::Relaxed),
+                wake_latency: state.wake_latency.stats(),
+                poll_duration: state.poll_duration.stats(),
+                budget_overruns: state.budget_overruns.load(Ordering::Relaxed),
+                restarts: state.restarts.load(Ordering::Relaxed),
+            })
+            .collect();
+        ExecutorMetrics { priorities }
+    }
+
+    /// Count a budgeted task of `priority` being suspended
+    pub(crate) fn budget_overrun(&self, priority: RTPriority) {
+        self.priorities[priority as usize]
+            .budget_overruns
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a supervised task of `priority` being restarted
+    pub(crate) fn restart(&self, priority: RTPriority) {
+        self.priorities[priority as usize]
+            .restarts
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Wrap `task` so that its wakes and polls are measured
+    pub(crate) fn instrument<F: Future>(
+        self: &Arc<Self>,
+        name: String,
+        priority: RTPriority,
+        task: F,
+    ) -> Instrumented<F> {
+        let wake = Arc::new(WakeState {
+            metrics: self.clone(),
+            priority: priority as usize,
+            woken_at: AtomicU64::new(0),
+        });
+        self.priorities[priority as usize]
+            .spawned
+            .fetch_add(1, Ordering::Relaxed);
+        // Being spawned schedules the first poll like a wake does
+        wake.woken();
+        Instrumented {
+            task: Box::pin(task),
+            label: trace::intern(&name),
+            span: info_span!("task", task = %name, priority = ?priority),
+            name,
+            wake,
+            waker: None,
+        }
+    }
+}
+
+/// Wake bookkeeping of one task
+struct WakeState {
+    metrics: Arc<MetricsState>,
+    priority: usize,
+    /// Nanoseconds since the epoch plus one of the pending wake; 0 if none
+    woken_at: AtomicU64,
+}
+
+impl WakeState {
+    fn state(&self) -> &PriorityState {
+        &self.metrics.priorities[self.priority]
+    }
+
+    fn now(&self) -> u64 {
+        self.metrics.epoch.elapsed().as_nanos() as u64 + 1
+    }
+
+    fn woken(&self) {
+        let now = self.now();
+        if self
+            .woken_at
+            .compare_exchange(0, now, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            let state = self.state();
+            let depth = state.queue_depth.fetch_add(1, Ordering::Relaxed) + 1;
+            state.max_queue_depth.fetch_max(depth, Ordering::Relaxed);
+        }
+    }
+
+    /// Clear the pending wake, returning how long ago it happened
+    fn polled(&self) -> Option<Duration> {
+        let woken = self.woken_at.swap(0, Ordering::AcqRel);
+        if woken == 0 {
+            return None;
+        }
+        self.state().queue_depth.fetch_sub(1, Ordering::Relaxed);
+        Some(Duration::from_nanos(self.now().saturating_sub(woken)))
+    }
+}
+
+/// Waker recording the wake before passing it on to the runtime's waker
+struct TaskWaker {
+    wake: Arc<WakeState>,
+    inner: Waker,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake.woken();
+        self.inner.wake_by_ref();
+    }
+}
+
+/// Task future measured by [`MetricsState`]
+pub(crate) struct Instrumented<F> {
+    task: Pin<Box<F>>,
+    name: String,
+    /// Lane of the polls in a trace
+    label: Label,
+    /// Entered while polling, so the task logs with its name and priority
+    span: Span,
+    wake: Arc<WakeState>,
+    /// The runtime's waker and the wrapper handed to the task
+    waker: Option<(Waker, Waker)>,
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        let _span = this.span.enter();
+        let state = this.wake.state();
+        if let Some(latency) = this.wake.polled() {
+            state.wake_latency.record(latency);
+        }
+
+        let waker = match &this.waker {
+            Some((inner, waker)) if inner.will_wake(cx.waker()) => waker.clone(),
+            _ => {
+                let waker = Waker::from(Arc::new(TaskWaker {
+                    wake: this.wake.clone(),
+                    inner: cx.waker().clone(),
+                }));
+                this.waker = Some((cx.waker().clone(), waker.clone()));
+                waker
+            }
+        };
+
+        let start = Instant::now();
+        let result = this.task.as_mut().poll(&mut Context::from_waker(&waker));
+        let duration = start.elapsed();
+        trace::record(EventKind::Poll, this.label, start, duration, 0);
+
+        state.polls.fetch_add(1, Ordering::Relaxed);
+        state.poll_duration.record(duration);
+        let long_poll = this.wake.metrics.long_poll;
+        if duration > long_poll {
+            state.long_polls.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "Task {} blocked its executor thread for {:?} (threshold {:?})",
+                this.name, duration, long_poll
+            );
+        }
+        result
+    }
+}
+
+impl<F> Drop for Instrumented<F> {
+    fn drop(&mut self) {
+        // A task dropped while woken no longer waits in the queue
+        self.wake.polled();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn test_wakes_and_polls_are_measured() {
+        let metrics = Arc::new(MetricsState::new(Duration::from_millis(1)));
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut blocked = false;
+        let task = std::future::poll_fn(move |cx| {
+            if blocked {
+                return Poll::Ready(());
+            }
+            blocked = true;
+            std::thread::sleep(Duration::from_millis(5));
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        });
+        let mut task = metrics.instrument("blocking".into(), RTPriority::High, task);
+        let high = || metrics.snapshot().priorities[RTPriority::High as usize].clone();
+        assert_eq!(high().queue_depth, 1);
+
+        assert!(Pin::new(&mut task).poll(&mut cx).is_pending());
+        // Woken by itself during the poll
+        assert_eq!(high().queue_depth, 1);
+        assert!(Pin::new(&mut task).poll(&mut cx).is_ready());
+
+        let high = high();
+        assert_eq!(high.tasks_spawned, 1);
+        assert_eq!(high.polls, 2);
+        assert_eq!(high.long_polls, 1);
+        assert_eq!(high.queue_depth, 0);
+        assert_eq!(high.max_queue_depth, 1);
+        assert_eq!(high.wake_latency.count, 2);
+        assert!(high.poll_duration.max >= 5_000);
+        assert_eq!(
+            metrics.snapshot().priorities[RTPriority::Low as usize].polls,
+            0
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_warn_on_new_long_polls() {
+        let metrics = Arc::new(MetricsState::new(Duration::from_millis(1)));
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let before = metrics.snapshot();
+        assert!(before.diagnostics(&before).is_empty());
+
+        let task = async { std::thread::sleep(Duration::from_millis(5)) };
+        let mut task = metrics.instrument("slow".into(), RTPriority::Low, task);
+        assert!(Pin::new(&mut task).poll(&mut cx).is_ready());
+        let after = metrics.snapshot();
+
+        let statuses = after.diagnostics(&before);
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].name, "executor/Low");
+        assert_eq!(statuses[0].level, DiagnosticLevel::Warn);
+        assert_eq!(statuses[0].message, "1 long polls");
+        assert_eq!(after.diagnostics(&after)[0].level, DiagnosticLevel::Ok);
+    }
+
+    #[test]
+    fn test_dropped_woken_task_leaves_queue() {
+        let metrics = Arc::new(MetricsState::new(Duration::from_millis(1)));
+        let task = metrics.instrument("dropped".into(), RTPriority::Normal, async {});
+        drop(task);
+        let normal = &metrics.snapshot().priorities[RTPriority::Normal as usize];
+        assert_eq!(normal.queue_depth, 0);
+        assert_eq!(normal.polls, 0);
+    }
+}
@@ -0,0 +1,421 @@
+//! Prometheus text exposition of the executor, watchdog and topic statistics
+//!
+//! Every metric family the process exports is declared here as a
+//! [`MetricFamily`] constant and listed in [`REGISTRY`]. The names are part
+//! of the public interface: dashboards and alerts depend on them, so a
+//! family may gain help text but is never renamed or relabelled. New
+//! families are added to the end of the registry.
+//!
+//! [`render`] produces the text format (version 0.0.4) served by the
+//! `health-endpoint` feature's `/metrics`. Per-topic message counts, queue
+//! depths and latencies come from the core crate's
+//! [`metrics`](agentic_robotics_core::metrics) registry, whose families are
+//! documented there and appended after these.
+
+use crate::executor::ROS3Executor;
+use crate::latency::LatencyStats;
+use crate::metrics::PriorityMetrics;
+use crate::watchdog;
+use agentic_robotics_core::graph;
+use agentic_robotics_core::metrics::Registry;
+use std::fmt::Write;
+
+/// Prometheus metric type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// Monotonic count since the process started
+    Counter,
+    /// Current value
+    Gauge,
+    /// Quantiles plus `_sum` and `_count` samples
+    Summary,
+}
+
+impl MetricKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Summary => "summary",
+        }
+    }
+}
+
+/// An exported metric family with its stable name and labels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricFamily {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub help: &'static str,
+    /// Label names of every sample, besides `quantile` for summaries
+    pub labels: &'static [&'static str],
+}
+
+const fn family(
+    name: &'static str,
+    kind: MetricKind,
+    help: &'static str,
+    labels: &'static [&'static str],
+) -> MetricFamily {
+    MetricFamily {
+        name,
+        kind,
+        help,
+        labels,
+    }
+}
+
+/// `1` until the executor is shut down, then `0`
+pub const EXECUTOR_RUNNING: MetricFamily = family(
+    "ros3_executor_running",
+    MetricKind::Gauge,
+    "Whether the executor accepts tasks",
+    &[],
+);
+
+/// Tasks spawned, by priority
+pub const EXECUTOR_TASKS_SPAWNED: MetricFamily = family(
+    "ros3_executor_tasks_spawned_total",
+    MetricKind::Counter,
+    "Tasks spawned on the executor",
+    &["priority"],
+);
+
+/// Task polls, by priority
+pub const EXECUTOR_POLLS: MetricFamily = family(
+    "ros3_executor_polls_total",
+    MetricKind::Counter,
+    "Task polls",
+    &["priority"],
+);
+
+/// Polls longer than the configured long-poll threshold, by priority
+pub const EXECUTOR_LONG_POLLS: MetricFamily = family(
+    "ros3_executor_long_polls_total",
+    MetricKind::Counter,
+    "Task polls longer than the long-poll threshold",
+    &["priority"],
+);
+
+/// Times a budgeted task used up its budget, by priority
+pub const EXECUTOR_BUDGET_OVERRUNS: MetricFamily = family(
+    "ros3_executor_budget_overruns_total",
+    MetricKind::Counter,
+    "Times a budgeted task used up its budget",
+    &["priority"],
+);
+
+/// Restarts of supervised tasks after a panic, by priority
+pub const EXECUTOR_RESTARTS: MetricFamily = family(
+    "ros3_executor_restarts_total",
+    MetricKind::Counter,
+    "Restarts of supervised tasks after a panic",
+    &["priority"],
+);
+
+/// Tasks that missed their deadline, by priority
+pub const EXECUTOR_DEADLINE_MISSES: MetricFamily = family(
+    "ros3_executor_deadline_misses_total",
+    MetricKind::Counter,
+    "Tasks that missed their deadline",
+    &["priority"],
+);
+
+/// Tasks woken and not yet polled, by priority
+pub const EXECUTOR_QUEUE_DEPTH: MetricFamily = family(
+    "ros3_executor_queue_depth",
+    MetricKind::Gauge,
+    "Tasks woken and not yet polled",
+    &["priority"],
+);
+
+/// Highest queue depth seen, by priority
+pub const EXECUTOR_MAX_QUEUE_DEPTH: MetricFamily = family(
+    "ros3_executor_max_queue_depth",
+    MetricKind::Gauge,
+    "Highest number of tasks woken and not yet polled",
+    &["priority"],
+);
+
+/// Time from a task's wake to the poll it caused, by priority
+pub const EXECUTOR_WAKE_LATENCY: MetricFamily = family(
+    "ros3_executor_wake_latency_microseconds",
+    MetricKind::Summary,
+    "Time from a task wake to the poll it caused",
+    &["priority"],
+);
+
+/// Duration of task polls, by priority
+pub const EXECUTOR_POLL_DURATION: MetricFamily = family(
+    "ros3_executor_poll_duration_microseconds",
+    MetricKind::Summary,
+    "Duration of task polls",
+    &["priority"],
+);
+
+/// `1` while a watchdog is expired, by watchdog name
+pub const WATCHDOG_EXPIRED: MetricFamily = family(
+    "ros3_watchdog_expired",
+    MetricKind::Gauge,
+    "Whether the watchdog expired and was not fed since",
+    &["watchdog"],
+);
+
+/// Time since a watchdog was last fed, by watchdog name
+pub const WATCHDOG_SINCE_FEED: MetricFamily = family(
+    "ros3_watchdog_since_feed_seconds",
+    MetricKind::Gauge,
+    "Time since the watchdog was last fed",
+    &["watchdog"],
+);
+
+/// Publishers in this process, by topic
+pub const TOPIC_PUBLISHERS: MetricFamily = family(
+    "ros3_topic_publishers",
+    MetricKind::Gauge,
+    "Publishers registered on the topic",
+    &["topic"],
+);
+
+/// Subscribers in this process, by topic
+pub const TOPIC_SUBSCRIBERS: MetricFamily = family(
+    "ros3_topic_subscribers",
+    MetricKind::Gauge,
+    "Subscribers registered on the topic",
+    &["topic"],
+);
+
+/// Messages dropped by the queues of the current subscribers, by topic
+///
+/// A gauge rather than a counter because it goes down when a subscriber
+/// with drops goes away.
+pub const TOPIC_DROPPED: MetricFamily = family(
+    "ros3_topic_dropped_messages",
+    MetricKind::Gauge,
+    "Messages dropped by the queues of the current subscribers",
+    &["topic"],
+);
+
+/// Every exported family, in exposition order
+pub const REGISTRY: &[MetricFamily] = &[
+    EXECUTOR_RUNNING,
+    EXECUTOR_TASKS_SPAWNED,
+    EXECUTOR_POLLS,
+    EXECUTOR_LONG_POLLS,
+    EXECUTOR_BUDGET_OVERRUNS,
+    EXECUTOR_RESTARTS,
+    EXECUTOR_DEADLINE_MISSES,
+    EXECUTOR_QUEUE_DEPTH,
+    EXECUTOR_MAX_QUEUE_DEPTH,
+    EXECUTOR_WAKE_LATENCY,
+    EXECUTOR_POLL_DURATION,
+    WATCHDOG_EXPIRED,
+    WATCHDOG_SINCE_FEED,
+    TOPIC_PUBLISHERS,
+    TOPIC_SUBSCRIBERS,
+    TOPIC_DROPPED,
+];
+
+/// Quantiles exported by summaries, with their values in `stats`
+fn quantiles(stats: &LatencyStats) -> [(&'static str, u64); 4] {
+    [
+        ("0.5", stats.p50),
+        ("0.9", stats.p90),
+        ("0.99", stats.p99),
+        ("0.999", stats.p999),
+    ]
+}
+
+/// Render every family of [`REGISTRY`] for `executor`, the watchdogs of the
+/// process and the topics of the global bus, followed by the global
+/// [`Registry`]
+pub fn render(executor: &ROS3Executor) -> String {
+    let metrics = executor.metrics();
+    let watchdogs = watchdog::states();
+    let topics = graph::topic_counters();
+    let mut out = Exposition::default();
+
+    out.family(&EXECUTOR_RUNNING);
+    out.sample(&EXECUTOR_RUNNING, &[], executor.is_running() as u64);
+
+    let priorities: Vec<(String, _)> = metrics
+        .priorities
+        .iter()
+        .map(|metrics| (format!("{:?}", metrics.priority).to_lowercase(), metrics))
+        .collect();
+    let by_priority =
+        |out: &mut Exposition, family: &MetricFamily, value: &dyn Fn(&PriorityMetrics) -> u64| {
+            out.family(family);
+            for (label, metrics) in &priorities {
+                out.sample(family, &[label], value(metrics));
+            }
+        };
+    by_priority(&mut out, &EXECUTOR_TASKS_SPAWNED, &|m| m.tasks_spawned);
+    by_priority(&mut out, &EXECUTOR_POLLS, &|m| m.polls);
+    by_priority(&mut out, &EXECUTOR_LONG_POLLS, &|m| m.long_polls);
+    by_priority(&mut out, &EXECUTOR_BUDGET_OVERRUNS, &|m| m.budget_overruns);
+    by_priority(&mut out, &EXECUTOR_RESTARTS, &|m| m.restarts);
+    out.family(&EXECUTOR_DEADLINE_MISSES);
+    for (label, metrics) in &priorities {
+        let misses = executor.deadline_misses(metrics.priority);
+        out.sample(&EXECUTOR_DEADLINE_MISSES, &[label], misses);
+    }
+    by_priority(&mut out, &EXECUTOR_QUEUE_DEPTH, &|m| m.queue_depth);
+    by_priority(&mut out, &EXECUTOR_MAX_QUEUE_DEPTH, &|m| m.max_queue_depth);
+    for family in [&EXECUTOR_WAKE_LATENCY, &EXECUTOR_POLL_DURATION] {
+        out.family(family);
+        for (label, metrics) in &priorities {
+            let stats = if family == &EXECUTOR_WAKE_LATENCY {
+                &metrics.wake_latency
+            } else {
+                &metrics.poll_duration
+            };
+            out.summary(family, label, stats);
+        }
+    }
+
+    out.family(&WATCHDOG_EXPIRED);
+    for state in &watchdogs {
+        out.sample(&WATCHDOG_EXPIRED, &[&state.name], state.expired as u64);
+    }
+    out.family(&WATCHDOG_SINCE_FEED);
+    for state in &watchdogs {
+        let seconds = state.since_feed.as_secs_f64();
+        out.sample(&WATCHDOG_SINCE_FEED, &[&state.name], seconds);
+    }
+
+    out.family(&TOPIC_PUBLISHERS);
+    for topic in &topics {
+        out.sample(&TOPIC_PUBLISHERS, &[&topic.name], topic.publisher_count);
+    }
+    out.family(&TOPIC_SUBSCRIBERS);
+    for topic in &topics {
+        out.sample(&TOPIC_SUBSCRIBERS, &[&topic.name], topic.subscriber_count);
+    }
+    out.family(&TOPIC_DROPPED);
+    for topic in &topics {
+        out.sample(&TOPIC_DROPPED, &[&topic.name], topic.dropped);
+    }
+
+    out.text.push_str(&Registry::global().encode_prometheus());
+    out.text
+}
+
+/// Text being rendered
+#[derive(Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    /// The `# HELP` and `# TYPE` lines of `family`
+    fn family(&mut self, family: &MetricFamily) {
+        let _ = writeln!(self.text, "# HELP {} {}", family.name, family.help);
+        let _ = writeln!(self.text, "# TYPE {} {}", family.name, family.kind.as_str());
+    }
+
+    /// One sample of `family`, with `values` for its labels in order
+    fn sample(&mut self, family: &MetricFamily, values: &[&str], value: impl std::fmt::Display) {
+        self.labelled(family, "", values, None, value);
+    }
+
+    fn summary(&mut self, family: &MetricFamily, priority: &str, stats: &LatencyStats) {
+        for (quantile, value) in quantiles(stats) {
+            self.labelled(family, "", &[priority], Some(quantile), value);
+        }
+        let sum = stats.mean * stats.count as f64;
+        self.labelled(family, "_sum", &[priority], None, sum);
+        self.labelled(family, "_count", &[priority], None, stats.count);
+    }
+
+    fn labelled(
+        &mut self,
+        family: &MetricFamily,
+        suffix: &str,
+        values: &[&str],
+        quantile: Option<&str>,
+        value: impl std::fmt::Display,
+    ) {
+        debug_assert_eq!(family.labels.len(), values.len());
+        let mut labels: Vec<String> = family
+            .labels
+            .iter()
+            .zip(values)
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+            .collect();
+        if let Some(quantile) = quantile {
+            labels.push(format!("quantile=\"{}\"", quantile));
+        }
+        let _ = if labels.is_empty() {
+            writeln!(self.text, "{}{} {}", family.name, suffix, value)
+        } else {
+            let labels = labels.join(",");
+            writeln!(
+                self.text,
+                "{}{}{{{}}} {}",
+                family.name, suffix, labels, value
+            )
+        };
+    }
+}
+
+/// Escape a label value for the text format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_registry_names_are_unique() {
+        let names: HashSet<&str> = REGISTRY.iter().map(|family| family.name).collect();
+        assert_eq!(names.len(), REGISTRY.len());
+        for family in REGISTRY {
+            assert!(family.name.starts_with("ros3_"), "{}", family.name);
+            let counter = family.name.ends_with("_total");
+            assert_eq!(
+                counter,
+                family.kind == MetricKind::Counter,
+                "{}",
+                family.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_uses_registered_names() {
+        let executor = ROS3Executor::new().unwrap();
+        let text = render(&executor);
+
+        assert!(text.contains("ros3_executor_running 1\n"));
+        assert!(text.contains("ros3_executor_polls_total{priority=\"normal\"} 0\n"));
+        let families: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("# TYPE "))
+            .map(|line| line.split(' ').next().unwrap())
+            .collect();
+        let registered: Vec<&str> = REGISTRY.iter().map(|family| family.name).collect();
+        assert!(families.starts_with(&registered));
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            let name = line.split(['{', ' ']).next().unwrap();
+            let base = ["_bucket", "_sum", "_count"]
+                .iter()
+                .find_map(|suffix| name.strip_suffix(suffix))
+                .filter(|base| families.contains(base))
+                .unwrap_or(name);
+            assert!(families.contains(&base), "undeclared sample {}", line);
+        }
+    }
+
+    #[test]
+    fn test_escape_label_values() {
+        assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+        assert_eq!(escape("line\nbreak"), "line\\nbreak");
+    }
+}
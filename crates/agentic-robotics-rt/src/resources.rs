@@ -0,0 +1,403 @@
+//! CPU and memory use of the process
+//!
+//! A [`ResourceMonitor`] samples on a background thread every interval: the
+//! process's CPU time from `/proc/self/stat`, its resident memory from
+//! `/proc/self/status` and the utilization of every core from `/proc/stat`.
+//! [`ResourceMonitor::usage`] summarizes the samples and
+//! [`ResourceMonitor::diagnostics`] reports them to a [`DiagnosticUpdater`].
+//!
+//! Only Linux is sampled; elsewhere every figure of [`ResourceUsage`] is
+//! `None` rather than made up.
+//!
+//! [`DiagnosticUpdater`]: agentic_robotics_core::diagnostics::DiagnosticUpdater
+
+use agentic_robotics_core::diagnostics::{DiagnosticLevel, DiagnosticStatus};
+use anyhow::Result;
+use parking_lot::{Condvar, Mutex};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Interval of [`ResourceMonitor::start`]
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Resource use of the process while monitored
+///
+/// CPU figures are in percent of one core, so a busy process exceeds 100 on
+/// several cores.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// Samples taken, including the one at the start
+    pub samples: u64,
+    /// Average CPU use since monitoring started
+    pub avg_cpu_percent: Option<f64>,
+    /// CPU use between the last two samples
+    pub cpu_percent: Option<f64>,
+    /// Resident memory at the last sample, in MiB
+    pub rss_mb: Option<f64>,
+    /// Peak resident memory of the process as the kernel tracks it, in MiB
+    pub peak_rss_mb: Option<f64>,
+    /// Utilization of each core by all processes since monitoring started
+    pub per_core_percent: Option<Vec<f64>>,
+}
+
+/// Samples the process's resource use until dropped
+pub struct ResourceMonitor {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    stop: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    first: Option<Sample>,
+    previous: Option<Sample>,
+    latest: Option<Sample>,
+    samples: u64,
+    stopped: bool,
+}
+
+impl State {
+    fn record(&mut self, sample: Option<Sample>) {
+        self.samples += 1;
+        let Some(sample) = sample else { return };
+        if self.first.is_none() {
+            self.first = Some(sample.clone());
+        }
+        self.previous = self.latest.replace(sample);
+    }
+
+    fn usage(&self) -> ResourceUsage {
+        let mut usage = ResourceUsage {
+            samples: self.samples,
+            ..Default::default()
+        };
+        let (Some(first), Some(latest)) = (&self.first, &self.latest) else {
+            return usage;
+        };
+        usage.avg_cpu_percent = latest.cpu_percent_since(first);
+        usage.cpu_percent = self
+            .previous
+            .as_ref()
+            .and_then(|previous| latest.cpu_percent_since(previous));
+        usage.rss_mb = Some(latest.rss_kb as f64 / 1024.0);
+        usage.peak_rss_mb = Some(latest.peak_rss_kb as f64 / 1024.0);
+        if latest.cores.len() == first.cores.len() && !latest.cores.is_empty() {
+            let percents = first
+                .cores
+                .iter()
+                .zip(&latest.cores)
+                .map(|(start, end)| end.percent_since(start))
+                .collect();
+            usage.per_core_percent = Some(percents);
+        }
+        usage
+    }
+}
+
+impl ResourceMonitor {
+    /// Start sampling every [`DEFAULT_INTERVAL`]
+    pub fn start() -> Result<Self> {
+        Self::with_interval(DEFAULT_INTERVAL)
+    }
+
+    /// Start sampling every `interval`, taking the first sample right away
+    pub fn with_interval(interval: Duration) -> Result<Self> {
+        let mut state = State::default();
+        state.record(Sample::take());
+        let shared = Arc::new(Shared {
+            state: Mutex::new(state),
+            stop: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("ros3-resources".into())
+                .spawn(move || shared.run(interval))?
+        };
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Resource use up to the latest sample
+    pub fn usage(&self) -> ResourceUsage {
+        self.shared.state.lock().usage()
+    }
+
+    /// Stop sampling, returning the resource use up to a final sample
+    pub fn finish(mut self) -> ResourceUsage {
+        self.stop();
+        let mut state = self.shared.state.lock();
+        state.record(Sample::take());
+        state.usage()
+    }
+
+    /// Collector reporting the resource use to a
+    /// [`DiagnosticUpdater`](agentic_robotics_core::diagnostics::DiagnosticUpdater)
+    ///
+    /// Returns a single `resources` status. After the monitor is dropped it
+    /// keeps reporting the last samples.
+    pub fn diagnostics(&self) -> impl FnMut() -> Vec<DiagnosticStatus> + Send + 'static {
+        let shared = self.shared.clone();
+        move || vec![status(&shared.state.lock().usage())]
+    }
+
+    fn stop(&mut self) {
+        self.shared.state.lock().stopped = true;
+        self.shared.stop.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ResourceMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Shared {
+    fn run(&self, interval: Duration) {
+        let mut state = self.state.lock();
+        while !state.stopped {
+            if self.stop.wait_for(&mut state, interval).timed_out() {
+                // Reading /proc takes a few µs, short enough to hold the lock
+                state.record(Sample::take());
+            }
+        }
+    }
+}
+
+fn status(usage: &ResourceUsage) -> DiagnosticStatus {
+    let (Some(cpu), Some(rss)) = (usage.avg_cpu_percent, usage.rss_mb) else {
+        return DiagnosticStatus::new(
+            DiagnosticLevel::Ok,
+            "resources",
+            "Not sampled on this platform",
+        );
+    };
+    let mut status = DiagnosticStatus::new(
+        DiagnosticLevel::Ok,
+        "resources",
+        format!("CPU {:.1}%, RSS {:.1} MiB", cpu, rss),
+    )
+    .with_value("avg_cpu_percent", format!("{:.1}", cpu))
+    .with_value("rss_mb", format!("{:.1}", rss));
+    if let Some(cpu) = usage.cpu_percent {
+        status = status.with_value("cpu_percent", format!("{:.1}", cpu));
+    }
+    if let Some(peak) = usage.peak_rss_mb {
+        status = status.with_value("peak_rss_mb", format!("{:.1}", peak));
+    }
+    for (core, percent) in usage.per_core_percent.iter().flatten().enumerate() {
+        status = status.with_value(format!("core{}_percent", core), format!("{:.1}", percent));
+    }
+    status
+}
+
+#[derive(Clone)]
+struct Sample {
+    at: Instant,
+    /// User and system CPU time of the process
+    cpu: Duration,
+    rss_kb: u64,
+    peak_rss_kb: u64,
+    cores: Vec<CoreTimes>,
+}
+
+/// Cumulative time of a core, in clock ticks
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CoreTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CoreTimes {
+    fn percent_since(&self, start: &CoreTimes) -> f64 {
+        let total = self.total.saturating_sub(start.total);
+        if total == 0 {
+            return 0.0;
+        }
+        self.busy.saturating_sub(start.busy) as f64 / total as f64 * 100.0
+    }
+}
+
+impl Sample {
+    fn cpu_percent_since(&self, start: &Sample) -> Option<f64> {
+        let wall = self.at.duration_since(start.at);
+        if wall.is_zero() {
+            return None;
+        }
+        let cpu = self.cpu.saturating_sub(start.cpu);
+        Some(cpu.as_secs_f64() / wall.as_secs_f64() * 100.0)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn take() -> Option<Sample> {
+        // SAFETY: sysconf only reads a configuration value
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        if ticks_per_sec <= 0 {
+            return None;
+        }
+        let at = Instant::now();
+        let ticks = parse_cpu_ticks(&std::fs::read_to_string("/proc/self/stat").ok()?)?;
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let cores = std::fs::read_to_string("/proc/stat")
+            .map(|stat| parse_core_times(&stat))
+            .unwrap_or_default();
+        Some(Sample {
+            at,
+            cpu: Duration::from_secs_f64(ticks as f64 / ticks_per_sec as f64),
+            rss_kb: parse_status_kb(&status, "VmRSS")?,
+            peak_rss_kb: parse_status_kb(&status, "VmHWM")?,
+            cores,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn take() -> Option<Sample> {
+        None
+    }
+}
+
+/// User plus system time of the process from `/proc/self/stat`
+#[cfg(target_os = "linux")]
+fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    // The command name may contain spaces and parentheses, the fields after
+    // it never do; utime and stime are fields 14 and 15
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// A `key:  1234 kB` line of `/proc/self/status`
+#[cfg(target_os = "linux")]
+fn parse_status_kb(status: &str, key: &str) -> Option<u64> {
+    status.lines().find_map(|line| {
+        let value = line.strip_prefix(key)?.strip_prefix(':')?;
+        value.split_whitespace().next()?.parse().ok()
+    })
+}
+
+/// The `cpuN` lines of `/proc/stat`, in order of N
+///
+/// Idle and iowait time count as idle.
+#[cfg(target_os = "linux")]
+fn parse_core_times(stat: &str) -> Vec<CoreTimes> {
+    stat.lines()
+        .filter(|line| line.starts_with("cpu") && !line.starts_with("cpu "))
+        .filter_map(|line| {
+            let times: Vec<u64> = line
+                .split_whitespace()
+                .skip(1)
+                .map(|time| time.parse().ok())
+                .collect::<Option<_>>()?;
+            // user nice system idle iowait irq softirq steal; guest time is
+            // already part of user
+            let total: u64 = times.iter().take(8).sum();
+            let idle = times.get(3)? + times.get(4).copied().unwrap_or(0);
+            Some(CoreTimes {
+                busy: total - idle,
+                total,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_summarizes_samples() {
+        let start = Instant::now();
+        let sample = |secs: u64, cpu_ms: u64, rss_kb: u64, busy: u64| Sample {
+            at: start + Duration::from_secs(secs),
+            cpu: Duration::from_millis(cpu_ms),
+            rss_kb,
+            peak_rss_kb: 4096,
+            cores: vec![CoreTimes {
+                busy,
+                total: secs * 100,
+            }],
+        };
+        let mut state = State::default();
+        state.record(Some(sample(0, 0, 1024, 0)));
+        state.record(Some(sample(1, 500, 2048, 25)));
+        state.record(Some(sample(2, 1500, 1024, 150)));
+
+        let usage = state.usage();
+        assert_eq!(usage.samples, 3);
+        assert_eq!(usage.avg_cpu_percent, Some(75.0));
+        assert_eq!(usage.cpu_percent, Some(100.0));
+        assert_eq!(usage.rss_mb, Some(1.0));
+        assert_eq!(usage.peak_rss_mb, Some(4.0));
+        assert_eq!(usage.per_core_percent, Some(vec![75.0]));
+    }
+
+    #[test]
+    fn test_usage_without_samples_is_unknown() {
+        let mut state = State::default();
+        state.record(None);
+        let usage = state.usage();
+        assert_eq!(usage.samples, 1);
+        assert_eq!(usage.avg_cpu_percent, None);
+        assert_eq!(usage.peak_rss_mb, None);
+        assert_eq!(status(&usage).message, "Not sampled on this platform");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_proc() {
+        let stat = "1234 (my (weird) name) S 1 1234 1234 0 -1 4194560 500 0 0 0 \
+                    70 30 0 0 20 0 4 0 100 1000000 250";
+        assert_eq!(parse_cpu_ticks(stat), Some(100));
+
+        let status = "Name:\tstress\nVmHWM:\t   20480 kB\nVmRSS:\t   10240 kB\n";
+        assert_eq!(parse_status_kb(status, "VmRSS"), Some(10240));
+        assert_eq!(parse_status_kb(status, "VmHWM"), Some(20480));
+        assert_eq!(parse_status_kb(status, "VmSwap"), None);
+
+        let stat = "cpu  10 0 10 80 0 0 0 0 0 0\n\
+                    cpu0 5 0 5 30 10 0 0 0 0 0\n\
+                    cpu1 5 0 5 50 0 0 0 0 0 0\n\
+                    intr 12345\n";
+        assert_eq!(
+            parse_core_times(stat),
+            vec![
+                CoreTimes {
+                    busy: 10,
+                    total: 50
+                },
+                CoreTimes {
+                    busy: 10,
+                    total: 60
+                },
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_monitor_samples_the_process() {
+        let monitor = ResourceMonitor::with_interval(Duration::from_millis(10)).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let usage = monitor.finish();
+        assert!(usage.samples >= 2);
+        assert!(usage.avg_cpu_percent.is_some());
+        assert!(usage.rss_mb.unwrap() > 0.0);
+        assert!(usage.peak_rss_mb.unwrap() >= usage.rss_mb.unwrap());
+    }
+}
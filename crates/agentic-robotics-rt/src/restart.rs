@@ -0,0 +1,322 @@
+//! Panic isolation and restart of supervised tasks
+//!
+//! A task spawned with [`ROS3Executor::spawn_supervised`] is created by a
+//! factory. When a run of it panics, the panic is caught and logged with the
+//! task name, and, as its [`RestartPolicy`] allows, the factory is called
+//! again after a backoff. Every panic, restart and final failure is published
+//! as an [`Event::Task`] on [`EVENTS_TOPIC`] and the task's current state is
+//! available from [`ROS3Executor::task_status`].
+//!
+//! [`ROS3Executor::spawn_supervised`]: crate::ROS3Executor::spawn_supervised
+//! [`ROS3Executor::task_status`]: crate::ROS3Executor::task_status
+
+use crate::metrics::MetricsState;
+use crate::RTPriority;
+use agentic_robotics_core::diagnostics::{DiagnosticLevel, DiagnosticStatus, KeyValue};
+use agentic_robotics_core::events::Event;
+use agentic_robotics_core::publisher::Publisher;
+use agentic_robotics_core::time::Clock;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tracing::{error, info, warn};
+
+/// What happens to a supervised task that panics, shared with the nodes of
+/// a [`NodeContainer`](agentic_robotics_core::NodeContainer)
+pub use agentic_robotics_core::container::RestartPolicy;
+
+/// Supervised tasks report their [`TaskEvent`]s on the process events
+/// topic, as [`Event::Task`]
+pub use agentic_robotics_core::events::{TaskEvent, TaskEventKind, EVENTS_TOPIC};
+
+/// State of a supervised task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskStatus {
+    Running {
+        restarts: u32,
+    },
+    /// Waiting out the backoff after a panic
+    Restarting {
+        restarts: u32,
+    },
+    /// A run returned normally
+    Completed {
+        restarts: u32,
+    },
+    /// Panicked with no restarts left; the task is not run again
+    Failed {
+        restarts: u32,
+        panic: String,
+    },
+}
+
+/// Statuses of supervised tasks by name
+pub(crate) type StatusTable = Arc<Mutex<HashMap<String, TaskStatus>>>;
+
+/// One run of a supervised task
+pub(crate) type Run = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Everything a supervised task needs besides its factory
+pub(crate) struct Supervisor {
+    pub(crate) name: String,
+    pub(crate) priority: RTPriority,
+    pub(crate) policy: RestartPolicy,
+    pub(crate) statuses: StatusTable,
+    pub(crate) metrics: Arc<MetricsState>,
+    /// For the backoff, so it follows a deterministic executor's sim clock
+    pub(crate) clock: Clock,
+}
+
+impl Supervisor {
+    /// Run the task from `start` until it completes or fails for good
+    pub(crate) async fn run<S>(self, mut start: S)
+    where
+        S: FnMut() -> Run,
+    {
+        let mut events = None;
+        let mut restarts = 0;
+        loop {
+            self.set_status(TaskStatus::Running { restarts });
+            let result = match std::panic::catch_unwind(AssertUnwindSafe(&mut start)) {
+                Ok(run) => CatchUnwind(run).await,
+                Err(payload) => Err(payload),
+            };
+            let Err(payload) = result else {
+                self.set_status(TaskStatus::Completed { restarts });
+                return;
+            };
+            let panic = panic_message(payload.as_ref());
+            error!("Task {} panicked: {}", self.name, panic);
+            self.publish(&mut events, TaskEventKind::Panicked, restarts, &panic)
+                .await;
+
+            match self.policy {
+                RestartPolicy::OnPanic {
+                    max_restarts,
+                    backoff,
+                } if restarts < max_restarts => {
+                    restarts += 1;
+                    self.set_status(TaskStatus::Restarting { restarts });
+                    self.metrics.restart(self.priority);
+                    self.clock.sleep(backoff).await;
+                    info!(
+                        "Restarting task {} ({}/{})",
+                        self.name, restarts, max_restarts
+                    );
+                    self.publish(&mut events, TaskEventKind::Restarted, restarts, &panic)
+                        .await;
+                }
+                _ => {
+                    error!(
+                        "Task {} failed after {} restarts, not restarting it",
+                        self.name, restarts
+                    );
+                    self.publish(&mut events, TaskEventKind::Failed, restarts, &panic)
+                        .await;
+                    self.set_status(TaskStatus::Failed { restarts, panic });
+                    return;
+                }
+            }
+        }
+    }
+
+    fn set_status(&self, status: TaskStatus) {
+        self.statuses.lock().insert(self.name.clone(), status);
+    }
+
+    /// Publish an event, creating the publisher on first use
+    async fn publish(
+        &self,
+        events: &mut Option<Publisher<Event>>,
+        kind: TaskEventKind,
+        restarts: u32,
+        panic: &str,
+    ) {
+        if events.is_none() {
+            match Publisher::new(EVENTS_TOPIC) {
+                Ok(publisher) => *events = Some(publisher),
+                Err(err) => {
+                    warn!("Failed to create task event publisher: {}", err);
+                    return;
+                }
+            }
+        }
+        let Some(publisher) = events else {
+            return;
+        };
+        let event = TaskEvent {
+            task: self.name.clone(),
+            kind,
+            restarts,
+            panic: panic.to_string(),
+        };
+        if let Err(err) = publisher.publish(&Event::Task(event)).await {
+            warn!("Failed to publish task event: {}", err);
+        }
+    }
+}
+
+/// An `executor/supervised` status listing every supervised task, `Error` if
+/// one has failed and `Warn` if one is restarting, or `None` without any
+pub(crate) fn diagnostics(statuses: &HashMap<String, TaskStatus>) -> Option<DiagnosticStatus> {
+    if statuses.is_empty() {
+        return None;
+    }
+    let mut names: Vec<&String> = statuses.keys().collect();
+    names.sort();
+    let mut status = DiagnosticStatus::new(DiagnosticLevel::Ok, "executor/supervised", "");
+    let mut failed = Vec::new();
+    let mut restarting = 0;
+    for name in names {
+        let task = &statuses[name];
+        let value = match task {
+            TaskStatus::Running { restarts } => format!("running, {} restarts", restarts),
+            TaskStatus::Restarting { restarts } => {
+                restarting += 1;
+                format!("restarting, {} restarts", restarts)
+            }
+            TaskStatus::Completed { restarts } => format!("completed, {} restarts", restarts),
+            TaskStatus::Failed { restarts, panic } => {
+                failed.push(name.as_str());
+                format!("failed after {} restarts: {}", restarts, panic)
+            }
+        };
+        status.values.push(KeyValue::new(name.as_str(), value));
+    }
+    (status.level, status.message) = if !failed.is_empty() {
+        (
+            DiagnosticLevel::Error,
+            format!("Failed: {}", failed.join(", ")),
+        )
+    } else if restarting > 0 {
+        (DiagnosticLevel::Warn, format!("{} restarting", restarting))
+    } else {
+        (DiagnosticLevel::Ok, "No failures".to_string())
+    };
+    Some(status)
+}
+
+/// Resolves to the panic payload if polling the run panics
+struct CatchUnwind(Run);
+
+impl Future for CatchUnwind {
+    type Output = Result<(), Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let run = &mut self.0;
+        match std::panic::catch_unwind(AssertUnwindSafe(|| run.as_mut().poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(())) => Poll::Ready(Ok(())),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+/// The message of a `panic!`, which is a `&str` or `String` payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deadline, Priority, ROS3Executor};
+    use agentic_robotics_core::Subscriber;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_task_fails_after_max_restarts() {
+        let executor = ROS3Executor::new_deterministic(3).unwrap();
+        let _runtime = executor.low_priority_runtime().enter();
+        let events = Subscriber::<Event>::new(EVENTS_TOPIC).unwrap();
+
+        let runs = Arc::new(AtomicU32::new(0));
+        let counter = runs.clone();
+        let policy = RestartPolicy::OnPanic {
+            max_restarts: 2,
+            backoff: Duration::from_millis(10),
+        };
+        let deadline = Deadline(Duration::from_secs(1));
+        executor.spawn_supervised("flaky", Priority(2), deadline, policy, move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            async move { panic!("run {run} failed") }
+        });
+        executor.run_until_idle();
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            executor.task_status("flaky"),
+            Some(TaskStatus::Failed {
+                restarts: 2,
+                panic: "run 3 failed".into()
+            })
+        );
+        assert_eq!(executor.task_status("other"), None);
+        let normal = &executor.metrics().priorities[RTPriority::Normal as usize];
+        assert_eq!(normal.restarts, 2);
+
+        let mut kinds = Vec::new();
+        while let Some(event) = events.try_recv().unwrap() {
+            match event {
+                Event::Task(event) if event.task == "flaky" => kinds.push(event.kind),
+                _ => {}
+            }
+        }
+        use TaskEventKind::*;
+        assert_eq!(
+            kinds,
+            [Panicked, Restarted, Panicked, Restarted, Panicked, Failed]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_report_failed_tasks() {
+        let mut statuses = HashMap::new();
+        assert_eq!(diagnostics(&statuses), None);
+        statuses.insert("done".to_string(), TaskStatus::Completed { restarts: 1 });
+        assert_eq!(diagnostics(&statuses).unwrap().level, DiagnosticLevel::Ok);
+        statuses.insert("retry".to_string(), TaskStatus::Restarting { restarts: 1 });
+        assert_eq!(diagnostics(&statuses).unwrap().level, DiagnosticLevel::Warn);
+        let panic = "boom".to_string();
+        statuses.insert("arm".to_string(), TaskStatus::Failed { restarts: 3, panic });
+
+        let status = diagnostics(&statuses).unwrap();
+        assert_eq!(status.level, DiagnosticLevel::Error);
+        assert_eq!(status.message, "Failed: arm");
+        let keys: Vec<&str> = status.values.iter().map(|v| v.key.as_str()).collect();
+        assert_eq!(keys, ["arm", "done", "retry"]);
+        assert_eq!(status.values[0].value, "failed after 3 restarts: boom");
+    }
+
+    #[test]
+    fn test_completed_task_is_not_restarted() {
+        let executor = ROS3Executor::new_deterministic(0).unwrap();
+        let _runtime = executor.low_priority_runtime().enter();
+        let deadline = Deadline(Duration::from_secs(1));
+        executor.spawn_supervised(
+            "once",
+            Priority(1),
+            deadline,
+            RestartPolicy::Never,
+            || async {},
+        );
+        executor.run_until_idle();
+        assert_eq!(
+            executor.task_status("once"),
+            Some(TaskStatus::Completed { restarts: 0 })
+        );
+    }
+}
@@ -1,15 +1,17 @@
 //! Priority-based task scheduler
 
 use crate::RTPriority;
+use agentic_robotics_core::time::{Clock, Time};
 use std::collections::BinaryHeap;
 use std::cmp::Ordering;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 /// Scheduled task
 #[derive(Debug)]
 pub struct ScheduledTask {
     pub priority: RTPriority,
-    pub deadline: Instant,
+    /// Absolute deadline on the scheduler's clock
+    pub deadline: Time,
     pub task_id: u64,
 }
 
@@ -41,17 +43,30 @@ impl Ord for ScheduledTask {
 pub struct PriorityScheduler {
     queue: BinaryHeap<ScheduledTask>,
     next_task_id: u64,
+    clock: Clock,
 }
 
 impl PriorityScheduler {
-    /// Create a new scheduler
+    /// Create a new scheduler measuring deadlines on the steady clock
     pub fn new() -> Self {
+        Self::with_clock(Clock::Steady)
+    }
+
+    /// Create a scheduler measuring deadlines on `clock`, e.g. a node's
+    /// simulation clock
+    pub fn with_clock(clock: Clock) -> Self {
         Self {
             queue: BinaryHeap::new(),
             next_task_id: 0,
+            clock,
         }
     }
 
+    /// The clock deadlines are measured on
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
     /// Schedule a task
     pub fn schedule(&mut self, priority: RTPriority, deadline: Duration) -> u64 {
         let task_id = self.next_task_id;
@@ -59,7 +74,7 @@ impl PriorityScheduler {
 
         let task = ScheduledTask {
             priority,
-            deadline: Instant::now() + deadline,
+            deadline: self.clock.now() + deadline,
             task_id,
         };
 
@@ -72,6 +87,11 @@ impl PriorityScheduler {
         self.queue.pop()
     }
 
+    /// Whether `task` has passed its deadline
+    pub fn is_overdue(&self, task: &ScheduledTask) -> bool {
+        self.clock.now() > task.deadline
+    }
+
     /// Get the number of pending tasks
     pub fn pending_tasks(&self) -> usize {
         self.queue.len()
@@ -92,6 +112,7 @@ impl Default for PriorityScheduler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use agentic_robotics_core::time::SimClock;
 
     #[test]
     fn test_scheduler() {
@@ -118,4 +139,21 @@ mod tests {
 
         assert_eq!(scheduler.pending_tasks(), 0);
     }
+
+    #[test]
+    fn test_deadlines_follow_sim_clock() {
+        let sim = SimClock::new(Time::from_nanos(1_000));
+        let mut scheduler = PriorityScheduler::with_clock(Clock::Sim(sim.clone()));
+
+        scheduler.schedule(RTPriority::Normal, Duration::from_millis(100));
+        let task = scheduler.next_task().unwrap();
+        assert_eq!(task.deadline, Time::from_nanos(1_000) + Duration::from_millis(100));
+
+        // Wall time does not count against the deadline
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(!scheduler.is_overdue(&task));
+
+        sim.advance(Duration::from_millis(101));
+        assert!(scheduler.is_overdue(&task));
+    }
 }
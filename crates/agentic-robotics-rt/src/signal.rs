@@ -0,0 +1,40 @@
+//! Process shutdown signals
+//!
+//! A node waits for [`on_shutdown`], then shuts its executor down:
+//!
+//! ```no_run
+//! # async fn run(executor: agentic_robotics_rt::ROS3Executor) -> anyhow::Result<()> {
+//! use std::time::Duration;
+//!
+//! agentic_robotics_rt::signal::on_shutdown().await?;
+//! let report = executor.shutdown(Duration::from_secs(2)).await;
+//! println!("cancelled: {:?}", report.cancelled);
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::Result;
+use tracing::info;
+
+/// Wait until the process is asked to stop
+///
+/// Resolves on Ctrl-C, and on Unix also on `SIGTERM`, as sent by
+/// `systemctl stop` or `docker stop`. Fails if the signal handlers cannot
+/// be installed. Must be called from within a Tokio runtime.
+pub async fn on_shutdown() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            interrupted = tokio::signal::ctrl_c() => interrupted?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    info!("Shutdown requested");
+    Ok(())
+}
@@ -0,0 +1,372 @@
+//! Locks for state shared between tasks of different priority
+//!
+//! A `Critical` task that waits for a lock held by a `Low` task can be held
+//! up for as long as any `High` task keeps the holder off the CPU: priority
+//! inversion. [`PiMutex`] avoids it on Linux with a priority inheritance
+//! mutex, which runs the holder at the priority of its highest waiter until
+//! it unlocks.
+//!
+//! Inheritance only changes anything between threads under a real-time
+//! policy, such as the executor's threads with
+//! [`ExecutorConfig::realtime`](crate::ExecutorConfig::realtime).
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+/// A mutex with priority inheritance
+///
+/// On Linux this is a `pthread_mutex` with `PTHREAD_PRIO_INHERIT`. Elsewhere,
+/// or if the C library does not support the protocol, it falls back to a
+/// plain `parking_lot` mutex that does not raise the holder's priority, so
+/// lock waits are only bounded by how long the holder gets to run; check
+/// [`inherits_priority`](Self::inherits_priority).
+///
+/// The guard is not `Send`: a lock must be released by the thread that took
+/// it, so it cannot be held across an `.await` in a task that may move
+/// between threads.
+pub struct PiMutex<T: ?Sized> {
+    raw: RawMutex,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the raw mutex serializes all access to `data`
+unsafe impl<T: ?Sized + Send> Send for PiMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for PiMutex<T> {}
+
+/// Access to the value of a locked [`PiMutex`], unlocking it on drop
+#[must_use = "the mutex is unlocked again when the guard is dropped"]
+pub struct PiMutexGuard<'a, T: ?Sized> {
+    mutex: &'a PiMutex<T>,
+    /// Unlocking on another thread is undefined for pthread mutexes
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: a shared guard only hands out `&T`
+unsafe impl<T: ?Sized + Sync> Sync for PiMutexGuard<'_, T> {}
+
+impl<T> PiMutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            raw: RawMutex::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> PiMutex<T> {
+    /// Block until the lock is acquired
+    ///
+    /// Panics if the calling thread already holds the lock.
+    pub fn lock(&self) -> PiMutexGuard<'_, T> {
+        self.raw.lock();
+        self.guard()
+    }
+
+    /// Acquire the lock if it is free right now
+    pub fn try_lock(&self) -> Option<PiMutexGuard<'_, T>> {
+        self.raw.try_lock().then(|| self.guard())
+    }
+
+    /// Acquire the lock, waiting at most `timeout`
+    ///
+    /// Meant for sections that must finish within a deadline: a task gives
+    /// up on the shared state instead of overrunning. On Linux the wait is
+    /// measured on the system clock, so stepping that clock during the wait
+    /// lengthens or shortens it.
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<PiMutexGuard<'_, T>> {
+        self.raw.try_lock_for(timeout).then(|| self.guard())
+    }
+
+    /// Whether waiters raise the priority of the holder
+    pub fn inherits_priority(&self) -> bool {
+        self.raw.inherits_priority()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn guard(&self) -> PiMutexGuard<'_, T> {
+        PiMutexGuard {
+            mutex: self,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T: Default> Default for PiMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PiMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("PiMutex");
+        match self.try_lock() {
+            Some(guard) => debug.field("data", &&*guard),
+            None => debug.field("data", &format_args!("<locked>")),
+        };
+        debug
+            .field("inherits_priority", &self.inherits_priority())
+            .finish()
+    }
+}
+
+impl<T: ?Sized> Deref for PiMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the lock
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for PiMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the lock
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for PiMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the guard holds the lock, on the thread that took it
+        unsafe { self.mutex.raw.unlock() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PiMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct RawMutex {
+    /// Boxed because a pthread mutex must not move once initialized
+    mutex: Box<UnsafeCell<libc::pthread_mutex_t>>,
+    inherits: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl RawMutex {
+    fn new() -> Self {
+        let mutex = Box::new(UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER));
+        // SAFETY: the attributes are initialized before use and destroyed
+        // after; the mutex is initialized in place at its final address
+        let inherits = unsafe {
+            let mut attr = std::mem::MaybeUninit::<libc::pthread_mutexattr_t>::uninit();
+            check(
+                "pthread_mutexattr_init",
+                libc::pthread_mutexattr_init(attr.as_mut_ptr()),
+            );
+            let inherits =
+                libc::pthread_mutexattr_setprotocol(attr.as_mut_ptr(), libc::PTHREAD_PRIO_INHERIT)
+                    == 0;
+            if !inherits {
+                warn_no_inheritance();
+            }
+            check(
+                "pthread_mutex_init",
+                libc::pthread_mutex_init(mutex.get(), attr.as_ptr()),
+            );
+            libc::pthread_mutexattr_destroy(attr.as_mut_ptr());
+            inherits
+        };
+        Self { mutex, inherits }
+    }
+
+    fn lock(&self) {
+        // SAFETY: the mutex is initialized
+        let result = unsafe { libc::pthread_mutex_lock(self.mutex.get()) };
+        if result == libc::EDEADLK {
+            panic!("PiMutex locked twice by the same thread");
+        }
+        check("pthread_mutex_lock", result);
+    }
+
+    fn try_lock(&self) -> bool {
+        // SAFETY: the mutex is initialized
+        match unsafe { libc::pthread_mutex_trylock(self.mutex.get()) } {
+            0 => true,
+            libc::EBUSY | libc::EDEADLK => false,
+            err => {
+                check("pthread_mutex_trylock", err);
+                false
+            }
+        }
+    }
+
+    fn try_lock_for(&self, timeout: Duration) -> bool {
+        // pthread_mutex_timedlock takes an absolute CLOCK_REALTIME deadline
+        let mut deadline = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `deadline` is a valid timespec to write to
+        unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut deadline) };
+        let nanos = deadline.tv_nsec as u64 + u64::from(timeout.subsec_nanos());
+        let secs =
+            (timeout.as_secs().saturating_add(nanos / 1_000_000_000)).min(i64::MAX as u64 / 2);
+        deadline.tv_sec = deadline.tv_sec.saturating_add(secs as libc::time_t);
+        deadline.tv_nsec = (nanos % 1_000_000_000) as libc::c_long;
+
+        // SAFETY: the mutex is initialized and `deadline` normalized
+        match unsafe { libc::pthread_mutex_timedlock(self.mutex.get(), &deadline) } {
+            0 => true,
+            libc::ETIMEDOUT => false,
+            libc::EDEADLK => panic!("PiMutex locked twice by the same thread"),
+            err => {
+                check("pthread_mutex_timedlock", err);
+                false
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The calling thread must hold the lock.
+    unsafe fn unlock(&self) {
+        check(
+            "pthread_mutex_unlock",
+            libc::pthread_mutex_unlock(self.mutex.get()),
+        );
+    }
+
+    fn inherits_priority(&self) -> bool {
+        self.inherits
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for RawMutex {
+    fn drop(&mut self) {
+        // SAFETY: no guard borrows the mutex anymore, so it is unlocked
+        unsafe { libc::pthread_mutex_destroy(self.mutex.get()) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check(call: &str, result: libc::c_int) {
+    if result != 0 {
+        panic!(
+            "{} failed: {}",
+            call,
+            std::io::Error::from_raw_os_error(result)
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct RawMutex(parking_lot::RawMutex);
+
+#[cfg(not(target_os = "linux"))]
+impl RawMutex {
+    fn new() -> Self {
+        use parking_lot::lock_api::RawMutex as _;
+        warn_no_inheritance();
+        Self(parking_lot::RawMutex::INIT)
+    }
+
+    fn lock(&self) {
+        use parking_lot::lock_api::RawMutex as _;
+        self.0.lock();
+    }
+
+    fn try_lock(&self) -> bool {
+        use parking_lot::lock_api::RawMutex as _;
+        self.0.try_lock()
+    }
+
+    fn try_lock_for(&self, timeout: Duration) -> bool {
+        use parking_lot::lock_api::RawMutexTimed as _;
+        self.0.try_lock_for(timeout)
+    }
+
+    /// # Safety
+    ///
+    /// The calling thread must hold the lock.
+    unsafe fn unlock(&self) {
+        use parking_lot::lock_api::RawMutex as _;
+        self.0.unlock();
+    }
+
+    fn inherits_priority(&self) -> bool {
+        false
+    }
+}
+
+fn warn_no_inheritance() {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        tracing::warn!("Priority inheritance is not supported here, PiMutex uses a plain mutex")
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{mpsc, Arc};
+    use std::time::Instant;
+
+    #[test]
+    fn test_lock_excludes_other_threads() {
+        let counter = Arc::new(PiMutex::new(0u64));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                std::thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        *counter.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(*counter.lock(), 40_000);
+        #[cfg(target_os = "linux")]
+        assert!(counter.inherits_priority());
+    }
+
+    #[test]
+    fn test_try_lock_for_is_bounded() {
+        let mutex = Arc::new(PiMutex::new(String::from("idle")));
+        let (locked_tx, locked_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let holder = {
+            let mutex = Arc::clone(&mutex);
+            std::thread::spawn(move || {
+                let mut state = mutex.lock();
+                *state = String::from("busy");
+                locked_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+            })
+        };
+        locked_rx.recv().unwrap();
+
+        assert!(mutex.try_lock().is_none());
+        let timeout = Duration::from_millis(50);
+        let start = Instant::now();
+        assert!(mutex.try_lock_for(timeout).is_none());
+        let waited = start.elapsed();
+        assert!(waited >= timeout, "{waited:?}");
+        assert!(waited < timeout * 4, "{waited:?}");
+
+        release_tx.send(()).unwrap();
+        let state = mutex.try_lock_for(Duration::from_secs(1)).unwrap();
+        assert_eq!(*state, "busy");
+        drop(state);
+        holder.join().unwrap();
+        assert_eq!(Arc::try_unwrap(mutex).unwrap().into_inner(), "busy");
+    }
+}
@@ -0,0 +1,273 @@
+//! Fixed-rate timers for periodic control loops
+//!
+//! Sleeping for a period after each iteration adds the iteration's run time
+//! and the timer's lateness to every cycle, so the loop drifts. [`RateTimer`]
+//! instead schedules tick `n` at `start + n * period`, so lateness on one tick
+//! is not carried into the next.
+
+use agentic_robotics_core::trace::{self, EventKind, Label};
+use anyhow::{bail, Result};
+use hdrhistogram::Histogram;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How long before a deadline a precise timer stops using the Tokio timer
+/// wheel, which only resolves milliseconds, and blocks instead
+#[cfg(target_os = "linux")]
+const PRECISE_SLACK: Duration = Duration::from_millis(2);
+
+/// What a [`RateTimer`] does with ticks that are a whole period late
+///
+/// Either way such ticks are counted in
+/// [`missed_ticks`](RateTimer::missed_ticks) and later ticks keep the
+/// original phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedTickBehavior {
+    /// Complete them immediately, one per `tick`, until the timer has
+    /// caught up
+    ///
+    /// Also absorbs wakeups that are late because the Tokio timer only
+    /// resolves milliseconds, which at rates near 1 kHz is about a period.
+    #[default]
+    Burst,
+    /// Skip them; the next tick waits for the first deadline still ahead
+    Skip,
+}
+
+/// Timer ticking at a fixed rate without drift
+///
+/// The first tick completes immediately. When the loop body overruns so
+/// that a whole period passes after a deadline, the ticks that are late are
+/// handled as set by [`missed_tick_behavior`](Self::missed_tick_behavior).
+pub struct RateTimer {
+    period: Duration,
+    start: Instant,
+    /// Index of the next deadline
+    next: u64,
+    behavior: MissedTickBehavior,
+    completed: u64,
+    missed: u64,
+    /// Lateness of each tick in nanoseconds
+    jitter: Histogram<u64>,
+    /// Lane of the ticks in a trace
+    label: Label,
+    #[cfg(target_os = "linux")]
+    precise: Option<MonotonicBase>,
+}
+
+impl RateTimer {
+    /// Create a timer ticking `hz` times per second
+    pub fn new(hz: f64) -> Result<Self> {
+        if !hz.is_finite() || hz <= 0.0 {
+            bail!("rate must be a positive number of Hz, got {hz}");
+        }
+        Ok(Self {
+            period: Duration::from_secs_f64(1.0 / hz),
+            start: Instant::now(),
+            next: 0,
+            behavior: MissedTickBehavior::default(),
+            completed: 0,
+            missed: 0,
+            // 3 significant digits
+            jitter: Histogram::<u64>::new(3).expect("Failed to create histogram"),
+            label: trace::intern(&format!("RateTimer {hz} Hz")),
+            #[cfg(target_os = "linux")]
+            precise: None,
+        })
+    }
+
+    /// Wake with `clock_nanosleep(TIMER_ABSTIME)` instead of the Tokio timer
+    ///
+    /// This blocks the calling thread for the last two milliseconds before
+    /// each deadline, so it suits loops on a dedicated thread such as the
+    /// executor's critical runtime. Only available on Linux; elsewhere the
+    /// timer is unchanged.
+    pub fn precise(mut self) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            self.precise = MonotonicBase::new(self.start);
+        }
+        self
+    }
+
+    /// Set what happens to ticks that are a whole period late
+    pub fn missed_tick_behavior(mut self, behavior: MissedTickBehavior) -> Self {
+        self.behavior = behavior;
+        self
+    }
+
+    /// Wait for the next tick and return its deadline
+    pub async fn tick(&mut self) -> Instant {
+        let now = Instant::now();
+        let mut deadline = self.deadline(self.next);
+        if now >= deadline + self.period {
+            match self.behavior {
+                MissedTickBehavior::Burst => self.missed += 1,
+                MissedTickBehavior::Skip => {
+                    // The loop body overran: skip the ticks that are over
+                    let over = (now - deadline).as_nanos() / self.period.as_nanos();
+                    self.missed += over as u64;
+                    self.next += over as u64;
+                    deadline = self.deadline(self.next);
+                }
+            }
+        }
+        self.sleep_until(deadline).await;
+
+        let jitter = Instant::now().saturating_duration_since(deadline);
+        let _ = self.jitter.record(jitter.as_nanos() as u64);
+        trace::instant(EventKind::Timer, self.label, self.next);
+        self.next += 1;
+        self.completed += 1;
+        deadline
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Number of ticks completed
+    pub fn ticks(&self) -> u64 {
+        self.completed
+    }
+
+    /// Number of ticks a whole period late, skipped or completed late
+    /// depending on the [`MissedTickBehavior`]
+    pub fn missed_ticks(&self) -> u64 {
+        self.missed
+    }
+
+    /// How late each tick woke, in nanoseconds
+    pub fn jitter(&self) -> &Histogram<u64> {
+        &self.jitter
+    }
+
+    fn deadline(&self, tick: u64) -> Instant {
+        let offset = self.period.as_nanos() * u128::from(tick);
+        self.start + Duration::from_nanos(offset as u64)
+    }
+
+    async fn sleep_until(&self, deadline: Instant) {
+        #[cfg(target_os = "linux")]
+        if let Some(base) = &self.precise {
+            if let Some(coarse) = deadline.checked_sub(PRECISE_SLACK) {
+                if coarse > Instant::now() {
+                    tokio::time::sleep_until(coarse).await;
+                }
+            }
+            base.sleep_until(deadline);
+            return;
+        }
+        if deadline > Instant::now() {
+            tokio::time::sleep_until(deadline).await;
+        }
+    }
+}
+
+/// `CLOCK_MONOTONIC` reading matching an [`Instant`], to turn deadlines
+/// into absolute times for `clock_nanosleep`
+#[cfg(target_os = "linux")]
+struct MonotonicBase {
+    instant: Instant,
+    monotonic: Duration,
+}
+
+#[cfg(target_os = "linux")]
+impl MonotonicBase {
+    fn new(instant: Instant) -> Option<Self> {
+        // SAFETY: `now` is a valid timespec to write to
+        let now = unsafe {
+            let mut now: libc::timespec = std::mem::zeroed();
+            if libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) != 0 {
+                return None;
+            }
+            now
+        };
+        let monotonic = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+        Some(Self {
+            instant,
+            monotonic: monotonic.checked_sub(instant.elapsed())?,
+        })
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let at = self.monotonic + deadline.saturating_duration_since(self.instant);
+        // SAFETY: `until` is a valid timespec and no remainder is requested
+        unsafe {
+            let mut until: libc::timespec = std::mem::zeroed();
+            until.tv_sec = at.as_secs() as libc::time_t;
+            until.tv_nsec = at.subsec_nanos() as _;
+            let flags = libc::TIMER_ABSTIME;
+            while libc::clock_nanosleep(libc::CLOCK_MONOTONIC, flags, &until, std::ptr::null_mut())
+                == libc::EINTR
+            {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_no_drift_at_1khz() {
+        let mut timer = RateTimer::new(1000.0).unwrap();
+        let first = timer.tick().await;
+        let first_woke = Instant::now();
+        let mut last = first;
+        for _ in 0..1000 {
+            // Some work in the loop body must not push later ticks back
+            std::thread::sleep(Duration::from_micros(200));
+            last = timer.tick().await;
+        }
+        let last_woke = Instant::now();
+
+        assert_eq!(last - first, Duration::from_secs(1));
+        // The last tick wakes as close to its deadline as the first did
+        assert!(last_woke - last < Duration::from_millis(5));
+        let mean = (last_woke - first_woke).as_secs_f64() / 1000.0;
+        assert!((mean - 0.001).abs() < 0.001 * 0.01, "mean period {mean}");
+        assert_eq!(timer.ticks(), 1001);
+        assert_eq!(timer.jitter().len(), 1001);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overrun_skips_ticks() {
+        let period = Duration::from_millis(10);
+        let mut timer = RateTimer::new(100.0)
+            .unwrap()
+            .missed_tick_behavior(MissedTickBehavior::Skip);
+        let start = timer.tick().await;
+
+        tokio::time::sleep(period * 7 / 2).await;
+        assert_eq!(timer.tick().await - start, period * 3);
+        assert_eq!(timer.missed_ticks(), 2);
+        assert_eq!(timer.tick().await - start, period * 4);
+        assert_eq!(start.elapsed(), period * 4);
+        assert_eq!(timer.ticks(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overrun_bursts_ticks() {
+        let period = Duration::from_millis(10);
+        let mut timer = RateTimer::new(100.0).unwrap();
+        let start = timer.tick().await;
+
+        tokio::time::sleep(period * 7 / 2).await;
+        // The late ticks complete at once, then the timer waits again
+        for n in 1..=3 {
+            assert_eq!(timer.tick().await - start, period * n);
+            assert_eq!(start.elapsed(), period * 7 / 2);
+        }
+        assert_eq!(timer.tick().await - start, period * 4);
+        assert_eq!(start.elapsed(), period * 4);
+        assert_eq!(timer.missed_ticks(), 2);
+        assert_eq!(timer.ticks(), 5);
+    }
+
+    #[test]
+    fn test_invalid_rate() {
+        assert!(RateTimer::new(0.0).is_err());
+        assert!(RateTimer::new(f64::NAN).is_err());
+    }
+}
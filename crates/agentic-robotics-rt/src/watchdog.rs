@@ -0,0 +1,418 @@
+//! Watchdogs for detecting stalled tasks
+//!
+//! A monitored task calls [`Watchdog::feed`] every cycle. One monitor thread,
+//! shared by all watchdogs of the process, notices when a watchdog has not
+//! been fed within its timeout and reports it once per stall: it logs an
+//! error, calls the watchdog's callback, optionally publishes a
+//! [`WatchdogExpiry`] on a diagnostics topic and optionally aborts the
+//! process. [`diagnostics`] reports every watchdog to a
+//! [`DiagnosticUpdater`].
+//!
+//! [`DiagnosticUpdater`]: agentic_robotics_core::diagnostics::DiagnosticUpdater
+
+use agentic_robotics_core::diagnostics::{DiagnosticLevel, DiagnosticStatus};
+use agentic_robotics_core::message::Message;
+use agentic_robotics_core::publisher::Publisher;
+use anyhow::{bail, Result};
+use parking_lot::{Condvar, Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tracing::{error, warn};
+
+/// Topic [`Watchdog::publish_diagnostics`] publishes on by convention
+///
+/// `/diagnostics` itself carries the
+/// [`DiagnosticArray`](agentic_robotics_core::diagnostics::DiagnosticArray)s
+/// of diagnostic updaters.
+pub const DIAGNOSTICS_TOPIC: &str = "/diagnostics/watchdog";
+
+/// Longest the monitor sleeps, bounding how late it notices new watchdogs
+/// with a shorter timeout than all existing ones
+const MAX_IDLE: Duration = Duration::from_millis(100);
+
+/// How long the monitor waits for a diagnostics publish to complete
+const PUBLISH_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A watchdog that was not fed in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchdogExpiry {
+    pub name: String,
+    pub timeout: Duration,
+    /// Time since the last feed, or since creation if never fed
+    pub stalled_for: Duration,
+}
+
+impl Message for WatchdogExpiry {
+    fn type_name() -> &'static str {
+        "ros3_msgs/WatchdogExpiry"
+    }
+}
+
+type ExpiryCallback = Box<dyn Fn(&WatchdogExpiry) + Send + Sync>;
+
+/// Detects a stalled task that stops calling [`feed`](Self::feed)
+///
+/// Monitoring starts on creation and ends when the last clone is dropped.
+/// After an expiry has been reported, feeding the watchdog again re-arms it.
+#[derive(Clone)]
+pub struct Watchdog {
+    entry: Arc<Entry>,
+}
+
+struct Entry {
+    name: String,
+    timeout: Duration,
+    /// Monitor clock reading of the last feed
+    last_feed: AtomicU64,
+    /// `last_feed` when the last expiry was reported; only the monitor
+    /// touches this
+    reported: AtomicU64,
+    actions: RwLock<Actions>,
+}
+
+#[derive(Default)]
+struct Actions {
+    callback: Option<ExpiryCallback>,
+    diagnostics: Option<Publisher<WatchdogExpiry>>,
+    abort: bool,
+}
+
+impl Watchdog {
+    /// Create a watchdog expiring when not fed for `timeout`
+    pub fn new(timeout: Duration) -> Result<Self> {
+        Self::named("watchdog", timeout)
+    }
+
+    /// Create a watchdog reported as `name`
+    ///
+    /// Fails if `timeout` is zero.
+    pub fn named(name: impl Into<String>, timeout: Duration) -> Result<Self> {
+        if timeout.is_zero() {
+            bail!("watchdog timeout must be non-zero");
+        }
+        let monitor = Monitor::get();
+        let entry = Arc::new(Entry {
+            name: name.into(),
+            timeout,
+            last_feed: AtomicU64::new(monitor.now()),
+            reported: AtomicU64::new(u64::MAX),
+            actions: RwLock::new(Actions::default()),
+        });
+        monitor.register(&entry);
+        Ok(Self { entry })
+    }
+
+    /// Call `callback` on the monitor thread when the watchdog expires
+    pub fn on_expire<F>(self, callback: F) -> Self
+    where
+        F: Fn(&WatchdogExpiry) + Send + Sync + 'static,
+    {
+        self.entry.actions.write().callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Publish a [`WatchdogExpiry`] on `topic`, conventionally
+    /// [`DIAGNOSTICS_TOPIC`], when the watchdog expires
+    pub fn publish_diagnostics(self, topic: &str) -> Result<Self> {
+        self.entry.actions.write().diagnostics = Some(Publisher::new(topic)?);
+        Ok(self)
+    }
+
+    /// Abort the process when the watchdog expires, after reporting it
+    pub fn abort_on_expire(self) -> Self {
+        self.entry.actions.write().abort = true;
+        self
+    }
+
+    /// Signal that the monitored task is alive
+    ///
+    /// This is a single clock read and store, cheap enough for every cycle
+    /// of a control loop.
+    #[inline]
+    pub fn feed(&self) {
+        self.entry
+            .last_feed
+            .store(Monitor::get().now(), Ordering::Release);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.entry.name
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.entry.timeout
+    }
+
+    /// Whether the watchdog expired and has not been fed since
+    pub fn is_expired(&self) -> bool {
+        let fed = self.entry.last_feed.load(Ordering::Acquire);
+        self.entry.reported.load(Ordering::Acquire) == fed
+    }
+}
+
+/// Snapshot of a live watchdog, see [`states`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchdogState {
+    pub name: String,
+    pub timeout: Duration,
+    /// Time since the last feed, or since creation if never fed
+    pub since_feed: Duration,
+    /// Whether the expiry was reported and the watchdog not fed since
+    pub expired: bool,
+}
+
+/// Every live watchdog of the process, sorted by name
+pub fn states() -> Vec<WatchdogState> {
+    let monitor = Monitor::get();
+    let watchdogs: Vec<Arc<Entry>> = monitor
+        .watchdogs
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    let now = monitor.now();
+    let mut states: Vec<WatchdogState> = watchdogs
+        .into_iter()
+        .map(|entry| {
+            let watchdog = Watchdog { entry };
+            let fed = watchdog.entry.last_feed.load(Ordering::Acquire);
+            WatchdogState {
+                name: watchdog.name().to_string(),
+                timeout: watchdog.timeout(),
+                since_feed: Duration::from_nanos(now.saturating_sub(fed)),
+                expired: watchdog.is_expired(),
+            }
+        })
+        .collect();
+    states.sort_by(|a, b| a.name.cmp(&b.name));
+    states
+}
+
+/// Status of every live watchdog, sorted by name
+///
+/// A watchdog is `Error` while expired and `Ok` otherwise. Pass this to
+/// [`DiagnosticUpdater::add_collector`] to publish it.
+///
+/// [`DiagnosticUpdater::add_collector`]: agentic_robotics_core::diagnostics::DiagnosticUpdater::add_collector
+pub fn diagnostics() -> Vec<DiagnosticStatus> {
+    states()
+        .into_iter()
+        .map(|state| {
+            let (level, message) = if state.expired {
+                (DiagnosticLevel::Error, "Expired")
+            } else {
+                (DiagnosticLevel::Ok, "Fed")
+            };
+            DiagnosticStatus::new(level, format!("watchdog/{}", state.name), message)
+                .with_value("timeout", format!("{:?}", state.timeout))
+                .with_value("since_feed", format!("{:?}", state.since_feed))
+        })
+        .collect()
+}
+
+/// The process-wide monitor thread
+struct Monitor {
+    epoch: Instant,
+    watchdogs: Mutex<Vec<Weak<Entry>>>,
+    registered: Condvar,
+}
+
+impl Monitor {
+    fn get() -> &'static Monitor {
+        static MONITOR: OnceLock<Monitor> = OnceLock::new();
+        static THREAD: OnceLock<()> = OnceLock::new();
+        let monitor = MONITOR.get_or_init(|| Monitor {
+            epoch: Instant::now(),
+            watchdogs: Mutex::new(Vec::new()),
+            registered: Condvar::new(),
+        });
+        THREAD.get_or_init(|| {
+            std::thread::Builder::new()
+                .name("ros3-watchdog".into())
+                .spawn(move || monitor.run())
+                .expect("Failed to start watchdog monitor thread");
+        });
+        monitor
+    }
+
+    /// Nanoseconds since the monitor started
+    #[inline]
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+
+    fn register(&self, entry: &Arc<Entry>) {
+        self.watchdogs.lock().push(Arc::downgrade(entry));
+        self.registered.notify_one();
+    }
+
+    fn run(&self) {
+        // Created on the first diagnostics publish
+        let mut runtime = None;
+        loop {
+            let now = self.now();
+            let mut next_check = now + MAX_IDLE.as_nanos() as u64;
+            let watchdogs: Vec<Arc<Entry>> = {
+                let mut watchdogs = self.watchdogs.lock();
+                watchdogs.retain(|entry| entry.strong_count() > 0);
+                watchdogs.iter().filter_map(Weak::upgrade).collect()
+            };
+            for entry in watchdogs {
+                let fed = entry.last_feed.load(Ordering::Acquire);
+                let deadline = fed.saturating_add(entry.timeout.as_nanos() as u64);
+                if now < deadline {
+                    next_check = next_check.min(deadline);
+                } else if entry.reported.load(Ordering::Acquire) != fed {
+                    entry.reported.store(fed, Ordering::Release);
+                    let stalled_for = Duration::from_nanos(now.saturating_sub(fed));
+                    entry.expire(stalled_for, &mut runtime);
+                }
+            }
+
+            let mut watchdogs = self.watchdogs.lock();
+            let wait = Duration::from_nanos(next_check.saturating_sub(self.now()));
+            self.registered.wait_for(&mut watchdogs, wait);
+        }
+    }
+}
+
+impl Entry {
+    fn expire(&self, stalled_for: Duration, runtime: &mut Option<Runtime>) {
+        let expiry = WatchdogExpiry {
+            name: self.name.clone(),
+            timeout: self.timeout,
+            stalled_for,
+        };
+        error!(
+            "Watchdog {} expired: not fed for {:?} (timeout {:?})",
+            expiry.name, stalled_for, self.timeout
+        );
+
+        let actions = self.actions.read();
+        if let Some(callback) = &actions.callback {
+            // A failing callback must not take down the monitor
+            let called =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| callback(&expiry)));
+            if called.is_err() {
+                warn!("Watchdog {} expiry callback panicked", expiry.name);
+            }
+        }
+        if let Some(publisher) = &actions.diagnostics {
+            let runtime = runtime.get_or_insert_with(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Failed to create watchdog runtime")
+            });
+            let published = runtime.block_on(async {
+                tokio::time::timeout(PUBLISH_TIMEOUT, publisher.publish(&expiry)).await
+            });
+            if !matches!(published, Ok(Ok(()))) {
+                warn!("Failed to publish expiry of watchdog {}", expiry.name);
+            }
+        }
+        if actions.abort {
+            error!("Aborting: watchdog {} expired", expiry.name);
+            std::process::abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentic_robotics_core::subscriber::Subscriber;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_starved_watchdog_expires_within_timeout() {
+        let timeout = Duration::from_millis(100);
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let watchdog = Watchdog::named("control_loop", timeout)
+            .unwrap()
+            .on_expire(move |expiry| tx.lock().send((expiry.clone(), Instant::now())).unwrap());
+
+        // Fed in time: no expiry
+        for _ in 0..15 {
+            watchdog.feed();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        watchdog.feed();
+        let last_feed = Instant::now();
+        assert!(!watchdog.is_expired());
+
+        let (expiry, at) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        let detected = at - last_feed;
+        assert!(detected >= timeout, "{detected:?}");
+        assert!(detected <= timeout * 3 / 2, "{detected:?}");
+        assert_eq!(expiry.name, "control_loop");
+        assert!(expiry.stalled_for >= timeout);
+        assert!(watchdog.is_expired());
+
+        // Reported once per stall, re-armed by feeding
+        assert!(rx.recv_timeout(timeout * 2).is_err());
+        watchdog.feed();
+        assert!(!watchdog.is_expired());
+        assert!(rx.recv_timeout(timeout * 3).is_ok());
+    }
+
+    #[test]
+    fn test_named_watchdogs_are_independent() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let fed = Watchdog::named("fed", Duration::from_millis(50)).unwrap();
+        let starved = Watchdog::named("starved", Duration::from_millis(50))
+            .unwrap()
+            .on_expire(move |expiry| tx.lock().send(expiry.name.clone()).unwrap());
+
+        for _ in 0..10 {
+            fed.feed();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "starved");
+        assert!(!fed.is_expired());
+        assert!(starved.is_expired());
+        assert!(Watchdog::new(Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_diagnostics_report_expired_watchdogs() {
+        let watchdog = Watchdog::named("diagnosed", Duration::from_millis(20)).unwrap();
+        let level = || {
+            diagnostics()
+                .into_iter()
+                .find(|status| status.name == "watchdog/diagnosed")
+                .map(|status| status.level)
+        };
+        assert_eq!(level(), Some(DiagnosticLevel::Ok));
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while !watchdog.is_expired() {
+            assert!(Instant::now() < deadline);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(level(), Some(DiagnosticLevel::Error));
+        drop(watchdog);
+        assert_eq!(level(), None);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_is_published() {
+        let topic = "test/watchdog/diagnostics";
+        let subscriber = Subscriber::<WatchdogExpiry>::new(topic).unwrap();
+        let _watchdog = Watchdog::named("published", Duration::from_millis(20))
+            .unwrap()
+            .publish_diagnostics(topic)
+            .unwrap();
+
+        let expiry = tokio::time::timeout(Duration::from_secs(1), subscriber.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(expiry.name, "published");
+    }
+}
@@ -384,6 +384,10 @@ cargo run --release --bin stress_test -- -p 100 -s 100 -r 100 -d 60
 
 # JSON output
 cargo run --release --bin stress_test -- -p 10 -s 10 -r 1000 -d 30 --json
+
+# Large (~2 MB) messages, serialized vs. zero-copy
+cargo run --release --bin stress_test -- -p 2 -s 4 -r 30 -d 30 -z large
+cargo run --release --bin stress_test -- -p 2 -s 4 -r 30 -d 30 -z large --zero-copy
 ```
 
 ---
@@ -1,10 +1,12 @@
 #!/usr/bin/env cargo +nightly -Zscript
 ```cargo
 [dependencies]
-ros3-core = { path = "../crates/ros3-core" }
-ros3-rt = { path = "../crates/ros3-rt" }
+agentic-robotics-core = { path = "../crates/agentic-robotics-core", features = ["protobuf", "transport-zenoh", "zstd", "lz4"] }
+agentic-robotics-rt = { path = "../crates/agentic-robotics-rt" }
 tokio = { version = "1.40", features = ["full", "rt-multi-thread"] }
 hdrhistogram = "7.5"
+base64 = "0.22"
+serde = { version = "1.0", features = ["derive"] }
 serde_json = "1.0"
 clap = { version = "4.4", features = ["derive"] }
 colored = "2.1"
@@ -17,19 +19,60 @@ colored = "2.1"
 //! - Latency distribution (p50, p95, p99, p99.9)
 //! - CPU and memory usage
 //! - Concurrent publisher/subscriber performance
+//! - Shared memory vs UDP loopback between processes (`--compare-transports`)
+//! - Zenoh between processes (`--transport zenoh`)
+//! - Compressed messages over UDP (`--transport udp --compression zstd`)
+//! - Publishers and subscribers spread over worker processes, with
+//!   cross-process and intra-process latency reported apart
+//!   (`--mode multiprocess --workers N`)
+//! - Regressions of throughput and p99 against a saved baseline
+//!   (`--save-baseline base.json`, then `--baseline base.json`)
+//! - Lock wait of a critical task under priority inversion
+//!   (`--priority-inversion`)
+//! - Timeline of task polls, publishes and deliveries for ui.perfetto.dev
+//!   (`--trace out.json`)
+//! - Rates, latency and resource use per sampling interval, one JSON record
+//!   per line (`--timeseries out.ndjson --interval-ms 100`)
 
-use ros3_core::message::RobotState;
-use ros3_core::publisher::Publisher;
-use ros3_core::subscriber::Subscriber;
-use ros3_core::serialization::Serializer;
-use ros3_rt::executor::{ROS3Executor, Priority, Deadline};
-use ros3_rt::latency::LatencyTracker;
+use agentic_robotics_core::message::{Message, Point3D, PointCloud, RobotState};
+use agentic_robotics_core::metrics::Registry;
+use agentic_robotics_core::middleware::ZenohConfig;
+use agentic_robotics_core::compression::Compression;
+use agentic_robotics_core::node::Node;
+use agentic_robotics_core::publisher::PublisherOptions;
+use agentic_robotics_core::serialization::Format;
+use agentic_robotics_core::shm::ShmConfig;
+use agentic_robotics_core::subscriber::Subscriber;
+use agentic_robotics_core::time::Clock;
+use agentic_robotics_core::transport::{TransportConfig, UdpConfig};
+use agentic_robotics_rt::executor::{
+    current_affinity, current_cpu, Deadline, ExecutorConfig, ROS3Executor,
+};
+use agentic_robotics_rt::latency::LatencyTracker;
+use agentic_robotics_rt::metrics::ExecutorMetrics;
+use agentic_robotics_rt::pool::{BufferPool, PoolStats};
+use agentic_robotics_rt::resources::{self, ResourceMonitor, ResourceUsage};
+use agentic_robotics_rt::sync::PiMutex;
+use agentic_robotics_rt::trace::Tracer;
+use agentic_robotics_rt::RTPriority;
 
-use std::sync::Arc;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
+use base64::Engine;
+use hdrhistogram::serialization::Deserializer;
 use hdrhistogram::Histogram;
+use serde::{Deserialize, Serialize};
 use colored::*;
 use clap::Parser;
 
@@ -56,17 +99,164 @@ struct Args {
     #[arg(short = 'z', long, default_value = "small")]
     message_size: String,
 
-    /// Serializer (cdr/json)
+    /// Serializer (cdr/json/msgpack/protobuf)
     #[arg(short = 'f', long, default_value = "cdr")]
     format: String,
 
+    /// Compress messages sent to other processes (none/zstd/lz4); only the
+    /// udp transport compresses, and only messages of at least 1 KiB
+    #[arg(long, default_value = "none")]
+    compression: String,
+
+    /// zstd level of --compression zstd (1-22)
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(i32).range(1..=22))]
+    compression_level: i32,
+
+    /// Share messages between publisher and subscribers instead of
+    /// serializing them (publish_arc / recv_arc)
+    #[arg(long)]
+    zero_copy: bool,
+
+    /// Transport between publishers and subscribers
+    /// (in-process/shm/udp/zenoh); all but in-process run the publishers in
+    /// a child process
+    #[arg(short, long, default_value = "in-process")]
+    transport: String,
+
+    /// Run the test over shared memory and over UDP loopback and compare
+    #[arg(long)]
+    compare_transports: bool,
+
+    /// Run publishers and subscribers in this process (single) or spread
+    /// them over worker processes (multiprocess), over --transport shm or
+    /// udp (the default)
+    #[arg(long, default_value = "single")]
+    mode: String,
+
+    /// Worker processes of --mode multiprocess, alternately publishing and
+    /// subscribing
+    #[arg(long, default_value_t = 2)]
+    workers: usize,
+
+    /// Measure how long a critical task waits for a PiMutex held by a
+    /// low-priority task while a high-priority task hogs the same core;
+    /// the wait is bounded by --deadline-us
+    #[arg(long)]
+    priority_inversion: bool,
+
+    /// Run the publishers on a thread pinned to this core (in-process only;
+    /// the core shared by all tasks with --priority-inversion)
+    #[arg(long)]
+    pin_core: Option<usize>,
+
+    /// Deadline for handling each received message on the RT executor (µs)
+    #[arg(long, default_value_t = 1000)]
+    deadline_us: u64,
+
+    /// Write a Chrome trace of task polls, publishes and deliveries to this
+    /// file, to open in ui.perfetto.dev; cross-process runs only trace the
+    /// subscribing side
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Events kept per thread for --trace; older ones are overwritten
+    #[arg(long, default_value_t = 100_000)]
+    trace_capacity: usize,
+
+    /// Write a `TimeseriesRecord` per sampling interval to this file, one
+    /// JSON object per line
+    #[arg(long, value_name = "FILE")]
+    timeseries: Option<PathBuf>,
+
+    /// Sampling interval of --timeseries in milliseconds
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(100..))]
+    interval_ms: u64,
+
+    /// Only run the publishers; used for the child process of shm and udp
+    /// runs
+    #[arg(long, hide = true)]
+    publisher_only: bool,
+
+    /// Run as this worker of --mode multiprocess
+    #[arg(long, hide = true)]
+    worker_id: Option<usize>,
+
+    /// Control socket of the process coordinating the workers
+    #[arg(long, hide = true)]
+    control: Option<String>,
+
     /// Output JSON results
     #[arg(short, long)]
     json: bool,
+
+    /// Compare the run against results saved with --save-baseline and exit
+    /// with status 1 if throughput or p99 latency regressed
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+
+    /// Save the results of the run as a baseline
+    #[arg(long, value_name = "FILE")]
+    save_baseline: Option<PathBuf>,
+
+    /// Largest tolerated drop of throughput below the baseline, e.g. 5%
+    #[arg(long, default_value = "5%", value_parser = parse_percent)]
+    max_regress_throughput: f64,
+
+    /// Largest tolerated rise of p99 latency above the baseline, e.g. 10%
+    #[arg(long, default_value = "10%", value_parser = parse_percent)]
+    max_regress_p99: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Transport {
+    InProcess,
+    SharedMemory,
+    Udp,
+    Zenoh,
+}
+
+impl Transport {
+    fn parse(name: &str) -> Self {
+        match name {
+            "shm" => Transport::SharedMemory,
+            "udp" => Transport::Udp,
+            "zenoh" => Transport::Zenoh,
+            _ => Transport::InProcess,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Transport::InProcess => "in-process",
+            Transport::SharedMemory => "shm",
+            Transport::Udp => "udp",
+            Transport::Zenoh => "zenoh",
+        }
+    }
+
+    /// Whether publishers and subscribers run in separate processes
+    fn is_cross_process(self) -> bool {
+        self != Transport::InProcess
+    }
 }
 
+/// Version of the JSON results, bumped when fields change meaning
+///
+/// 2: latency is measured publish→receive, `latency_us.p95` is the real
+/// percentile and drops account for every subscriber of a topic.
+/// 3: CPU and memory are measured, `null` where they cannot be, and
+/// `cpu_percent_per_core` is added.
+/// 4: `mode` tells single-process results from `multiprocess` ones.
+/// 5: `run` records the git revision, date and configuration.
+const RESULTS_SCHEMA_VERSION: u32 = 5;
+
 struct StressTestResults {
     total_messages: u64,
+    total_received: u64,
+    /// Messages owed to subscribers: each message once per subscriber of its
+    /// topic
+    expected_deliveries: u64,
+    dropped: u64,
     duration_secs: f64,
     throughput: f64,
     latency_p50: f64,
@@ -74,19 +264,81 @@ struct StressTestResults {
     latency_p99: f64,
     latency_p999: f64,
     latency_max: f64,
-    avg_cpu_percent: f64,
-    peak_memory_mb: f64,
+    deadline_misses: u64,
+    executor_metrics: ExecutorMetrics,
+    /// Serialization buffers of in-process publishers
+    buffer_pool: Option<PoolStats>,
+    /// CPU and memory of this process, which runs the subscribers and, in
+    /// process, the publishers
+    resources: ResourceUsage,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
+    if args.publisher_only {
+        let config = stress_config(&args, Transport::parse(&args.transport));
+        let node = stress_node("stress_publishers", config.transport, true);
+        let sent = Arc::new(SentCounts::default());
+        let pool = BufferPool::default();
+        match args.message_size.as_str() {
+            "medium" => {
+                let message = |seq| point_cloud(1_000, seq);
+                run_publishers(config, node, message, &sent, &pool, None).await
+            }
+            "large" => {
+                let message = |seq| point_cloud(131_072, seq);
+                run_publishers(config, node, message, &sent, &pool, None).await
+            }
+            _ => run_publishers(config, node, robot_state, &sent, &pool, None).await,
+        }
+        // Read by the parent process
+        println!("sent {}", sent.encode());
+        return;
+    }
+
+    if let Some(worker_id) = args.worker_id {
+        match args.message_size.as_str() {
+            "medium" => run_worker(&args, worker_id, |seq| point_cloud(1_000, seq)).await,
+            "large" => run_worker(&args, worker_id, |seq| point_cloud(131_072, seq)).await,
+            _ => run_worker(&args, worker_id, robot_state).await,
+        }
+        return;
+    }
+
     println!("{}", "=".repeat(70).bold());
     println!("{}", "ROS3 Stress Test Tool".bold().cyan());
     println!("{}", "=".repeat(70).bold());
     println!();
 
+    // Writes the trace file when dropped at the end of main
+    let _tracer = args.trace.as_ref().map(|path| {
+        Tracer::start(args.trace_capacity)
+            .expect("Failed to start tracer")
+            .output(path)
+    });
+
+    let gated = args.baseline.is_some() || args.save_baseline.is_some();
+    if gated && (args.priority_inversion || args.compare_transports) {
+        eprintln!(
+            "{}",
+            "Baselines cover plain and --mode multiprocess runs only".red()
+        );
+        std::process::exit(2);
+    }
+    let multiprocess = args.mode == "multiprocess";
+    let single = !args.priority_inversion && !args.compare_transports && !multiprocess;
+    if args.timeseries.is_some() && !single {
+        eprintln!("{}", "--timeseries covers plain runs only".red());
+        std::process::exit(2);
+    }
+
+    if args.priority_inversion {
+        run_priority_inversion(&args).await;
+        return;
+    }
+
     println!("Configuration:");
     println!("  Publishers:    {}", args.publishers.to_string().yellow());
     println!("  Subscribers:   {}", args.subscribers.to_string().yellow());
@@ -94,150 +346,511 @@ async fn main() {
     println!("  Duration:      {} seconds", args.duration.to_string().yellow());
     println!("  Message size:  {}", args.message_size.yellow());
     println!("  Serializer:    {}", args.format.yellow());
+    println!("  Compression:   {}", args.compression.yellow());
+    println!("  Zero-copy:     {}", args.zero_copy.to_string().yellow());
+    if let Some(core) = args.pin_core {
+        println!("  Pinned core:   {}", core.to_string().yellow());
+    }
+    if args.compare_transports {
+        println!("  Transport:     {}", "shm vs udp".yellow());
+    } else if multiprocess {
+        let transport = multiprocess_transport(&args);
+        println!("  Transport:     {}", transport.name().yellow());
+        println!("  Workers:       {}", args.workers.to_string().yellow());
+    } else {
+        println!("  Transport:     {}", args.transport.yellow());
+    }
     println!();
 
-    let serializer = match args.format.as_str() {
-        "json" => Serializer::Json,
-        _ => Serializer::Cdr,
-    };
+    let run = run_metadata(&args);
+    if multiprocess {
+        let results = run_multiprocess(&args).await;
+        let json = multiprocess_json(&results, &run);
+        print_multiprocess(&results, &json, args.json);
+        gate(&args, &json);
+        return;
+    }
+
+    if args.compare_transports {
+        let shm = run_message_size(&args, stress_config(&args, Transport::SharedMemory)).await;
+        let udp = run_message_size(&args, stress_config(&args, Transport::Udp)).await;
+        print_comparison(&shm, &udp, &run, args.json);
+        return;
+    }
 
     // Run stress test
-    let results = run_stress_test(
-        args.publishers,
-        args.subscribers,
-        args.rate,
-        Duration::from_secs(args.duration),
-        serializer,
-    )
-    .await;
+    let results = run_message_size(&args, stress_config(&args, Transport::parse(&args.transport)))
+        .await;
 
     // Print results
-    print_results(&results, args.json);
+    let json = results_json(&results, &run);
+    print_results(&results, &json, args.json);
+    gate(&args, &json);
 }
 
-async fn run_stress_test(
+#[derive(Clone)]
+struct StressConfig {
     num_publishers: usize,
     num_subscribers: usize,
     rate_hz: u32,
     duration: Duration,
-    serializer: Serializer,
+    format: Format,
+    compression: Compression,
+    zero_copy: bool,
+    transport: Transport,
+    /// Core to pin in-process publishers to
+    pin_core: Option<usize>,
+    /// Deadline for handling a received message
+    deadline: Duration,
+    /// Arguments for the publishing child process of cross-process runs
+    child_args: Vec<String>,
+    /// Index and number of the publishing workers of --mode multiprocess
+    publisher_worker: Option<(usize, usize)>,
+    /// Where to write a record per `interval`
+    timeseries: Option<PathBuf>,
+    interval: Duration,
+}
+
+impl StressConfig {
+    /// Whether publisher `i` runs in this process
+    ///
+    /// Each topic is published by a single worker, as a shared-memory ring
+    /// has a single writer.
+    fn publishes(&self, i: usize) -> bool {
+        self.publisher_worker
+            .is_none_or(|(index, count)| (i % TOPICS) % count == index)
+    }
+}
+
+fn stress_config(args: &Args, transport: Transport) -> StressConfig {
+    let format = match args.format.as_str() {
+        "json" => Format::Json,
+        "protobuf" => Format::Protobuf,
+        "msgpack" => Format::MessagePack,
+        _ => Format::Cdr,
+    };
+    let compression = match args.compression.as_str() {
+        "zstd" => Compression::Zstd { level: args.compression_level },
+        "lz4" => Compression::Lz4,
+        _ => Compression::None,
+    };
+
+    let mut child_args = vec!["--publisher-only".to_string()];
+    child_args.extend(forwarded_args(args, transport));
+
+    StressConfig {
+        num_publishers: args.publishers,
+        num_subscribers: args.subscribers,
+        rate_hz: args.rate,
+        duration: Duration::from_secs(args.duration),
+        format,
+        compression,
+        zero_copy: args.zero_copy,
+        transport,
+        pin_core: args.pin_core,
+        deadline: Duration::from_micros(args.deadline_us),
+        child_args,
+        publisher_worker: None,
+        timeseries: args.timeseries.clone(),
+        interval: Duration::from_millis(args.interval_ms),
+    }
+}
+
+/// The arguments of a run that child processes share
+fn forwarded_args(args: &Args, transport: Transport) -> Vec<String> {
+    let mut forwarded = vec![
+        format!("--transport={}", transport.name()),
+        format!("--publishers={}", args.publishers),
+        format!("--subscribers={}", args.subscribers),
+        format!("--rate={}", args.rate),
+        format!("--duration={}", args.duration),
+        format!("--message-size={}", args.message_size),
+        format!("--format={}", args.format),
+        format!("--compression={}", args.compression),
+        format!("--compression-level={}", args.compression_level),
+        format!("--workers={}", args.workers),
+    ];
+    if args.zero_copy {
+        forwarded.push("--zero-copy".to_string());
+    }
+    forwarded
+}
+
+/// How often a plain run prints its progress
+const PROGRESS_PERIOD: Duration = Duration::from_secs(5);
+
+/// One line of `--timeseries`, covering one sampling interval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TimeseriesRecord {
+    /// Since the run started, at the end of the interval
+    elapsed_secs: f64,
+    /// Length of the interval, the sampling interval give or take the
+    /// scheduling delay
+    interval_secs: f64,
+    /// Messages published since the run started
+    sent: u64,
+    /// Messages received since the run started, by all subscribers
+    received: u64,
+    sent_per_sec: f64,
+    received_per_sec: f64,
+    /// Latencies measured in the interval
+    latency_count: u64,
+    /// Publish→receive latency of the interval; `null` without messages
+    latency_p50_us: Option<u64>,
+    latency_p99_us: Option<u64>,
+    /// Resident memory of this process at the latest resource sample;
+    /// `null` where it cannot be measured
+    rss_mb: Option<f64>,
+    /// CPU use of this process in percent of one core between the latest two
+    /// resource samples; `null` where it cannot be measured
+    cpu_percent: Option<f64>,
+}
+
+/// Writes `--timeseries`, flushing every record so a run that dies still
+/// leaves the records up to then
+struct Timeseries(BufWriter<File>);
+
+impl Timeseries {
+    fn create(path: &Path) -> Self {
+        let file = File::create(path).expect("failed to create the timeseries file");
+        Timeseries(BufWriter::new(file))
+    }
+
+    fn write(&mut self, record: &TimeseriesRecord) {
+        serde_json::to_writer(&mut self.0, record).expect("failed to write the timeseries");
+        writeln!(self.0).and_then(|_| self.0.flush()).expect("failed to write the timeseries");
+    }
+}
+
+async fn run_message_size(args: &Args, config: StressConfig) -> StressTestResults {
+    match args.message_size.as_str() {
+        "medium" => run_stress_test(config, |seq| point_cloud(1_000, seq)).await,
+        // ~2 MB per message
+        "large" => run_stress_test(config, |seq| point_cloud(131_072, seq)).await,
+        _ => run_stress_test(config, robot_state).await,
+    }
+}
+
+/// Where the subscribing process accepts the zenoh session of the child
+const ZENOH_ENDPOINT: &str = "tcp/127.0.0.1:7448";
+
+/// Topics shared by the publishers and subscribers; publisher and
+/// subscriber `i` use topic `i % TOPICS`
+const TOPICS: usize = 10;
+
+fn topic_name(index: usize) -> String {
+    format!("stress_topic_{}", index % TOPICS)
+}
+
+/// Messages published on each topic
+#[derive(Default)]
+struct SentCounts([AtomicU64; TOPICS]);
+
+impl SentCounts {
+    fn add(&self, topic: usize) {
+        self.0[topic % TOPICS].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn total(&self) -> u64 {
+        self.0.iter().map(|count| count.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Deliveries owed to `num_subscribers` subscribers: every message once
+    /// per subscriber of its topic
+    fn expected_deliveries(&self, num_subscribers: usize) -> u64 {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(topic, count)| {
+                let subscribers =
+                    num_subscribers / TOPICS + usize::from(topic < num_subscribers % TOPICS);
+                count.load(Ordering::Relaxed) * subscribers as u64
+            })
+            .sum()
+    }
+
+    /// The counts as the publishing child prints them, space separated by
+    /// topic
+    fn encode(&self) -> String {
+        let counts: Vec<_> = self.counts().iter().map(u64::to_string).collect();
+        counts.join(" ")
+    }
+
+    fn counts(&self) -> Vec<u64> {
+        self.0.iter().map(|count| count.load(Ordering::Relaxed)).collect()
+    }
+
+    fn add_all(&self, counts: &[u64]) {
+        for (count, added) in self.0.iter().zip(counts) {
+            count.fetch_add(*added, Ordering::Relaxed);
+        }
+    }
+
+    fn decode(&self, line: &str) {
+        for (count, value) in self.0.iter().zip(line.split_whitespace()) {
+            count.store(value.parse().unwrap_or(0), Ordering::Relaxed);
+        }
+    }
+}
+
+/// A node carrying the stress topics over `transport`
+///
+/// Cross-process runs stamp with the system clock, the only one the
+/// processes share.
+fn stress_node(name: &str, transport: Transport, publishing: bool) -> Node {
+    let node = Node::new(name).expect("failed to create node");
+    match transport {
+        Transport::InProcess => node.with_clock(Clock::Steady),
+        Transport::Udp => node
+            .with_transport(TransportConfig::Udp(UdpConfig::default()))
+            .expect("failed to start UDP transport"),
+        Transport::SharedMemory => (0..TOPICS).fold(node, |node, topic| {
+            node.with_shared_memory(&topic_name(topic), ShmConfig::default())
+                .expect("failed to start shared-memory transport")
+        }),
+        // The subscribing process listens and the publishing child connects
+        Transport::Zenoh => {
+            let endpoint = vec![ZENOH_ENDPOINT.to_string()];
+            let config = if publishing {
+                ZenohConfig { connect: endpoint, listen: Vec::new(), ..Default::default() }
+            } else {
+                ZenohConfig { listen: endpoint, ..Default::default() }
+            };
+            node.with_transport(TransportConfig::Zenoh(config))
+                .expect("failed to start zenoh transport")
+        }
+    }
+}
+
+fn robot_state(sequence: u64) -> RobotState {
+    RobotState {
+        position: [sequence as f64, sequence as f64, sequence as f64],
+        velocity: [0.1, 0.2, 0.3],
+        timestamp: sequence as i64,
+    }
+}
+
+fn point_cloud(points: usize, sequence: u64) -> PointCloud {
+    PointCloud {
+        points: vec![Point3D { x: 1.0, y: 2.0, z: 3.0 }; points],
+        intensities: vec![0.5; points],
+        timestamp: sequence as i64,
+    }
+}
+
+async fn run_stress_test<M: Message>(
+    config: StressConfig,
+    make_message: fn(u64) -> M,
 ) -> StressTestResults {
-    println!("{}", "Starting stress test...".green().bold());
+    let StressConfig {
+        num_subscribers,
+        duration,
+        zero_copy,
+        transport,
+        deadline,
+        interval,
+        ..
+    } = config.clone();
+
+    println!(
+        "{}",
+        format!("Starting stress test ({})...", transport.name()).green().bold()
+    );
     println!();
 
     // Shared counters
-    let messages_sent = Arc::new(AtomicU64::new(0));
+    let messages_sent = Arc::new(SentCounts::default());
     let messages_received = Arc::new(AtomicU64::new(0));
 
-    // Latency tracking
+    // Latency tracking, over the run and per sampling interval
     let latency_tracker = Arc::new(LatencyTracker::new("stress_test"));
+    let interval_tracker = Arc::new(LatencyTracker::new("stress_test_interval"));
 
     // Create executor for RT tasks
     let executor = Arc::new(ROS3Executor::new().unwrap());
+    let pool = BufferPool::default();
 
+    let monitor = ResourceMonitor::with_interval(interval.min(resources::DEFAULT_INTERVAL))
+        .map(Arc::new)
+        .expect("failed to start resource monitor");
+    let timeseries = config.timeseries.as_deref().map(Timeseries::create);
     let start_time = Instant::now();
 
-    // Spawn publishers
-    let mut publisher_handles = Vec::new();
-    for i in 0..num_publishers {
-        let topic = format!("stress_topic_{}", i % 10); // 10 topics shared
-        let publisher = Publisher::<RobotState>::new(topic, serializer.clone());
-        let messages_sent = Arc::clone(&messages_sent);
-        let interval = Duration::from_micros(1_000_000 / rate_hz as u64);
-
-        let handle = tokio::spawn(async move {
-            let mut sequence = 0u64;
-            let start = Instant::now();
-
-            while start.elapsed() < duration {
-                let message = RobotState {
-                    position: [sequence as f64, sequence as f64, sequence as f64],
-                    velocity: [0.1, 0.2, 0.3],
-                    timestamp: sequence as i64,
-                };
-
-                if publisher.publish(&message).await.is_ok() {
-                    messages_sent.fetch_add(1, Ordering::Relaxed);
-                    sequence += 1;
-                }
-
-                sleep(interval).await;
-            }
-        });
-
-        publisher_handles.push(handle);
-    }
-
-    // Spawn subscribers
+    // Spawn subscribers first so no early messages are missed
+    let node = stress_node("stress_subscribers", transport, false);
+    // Leave the child process time to start up and discover the subscribers
+    let receive_for = if transport.is_cross_process() {
+        duration + Duration::from_secs(3)
+    } else {
+        duration
+    };
     let mut subscriber_handles = Vec::new();
     for i in 0..num_subscribers {
-        let topic = format!("stress_topic_{}", i % 10);
-        let _subscriber = Subscriber::<RobotState>::new(topic, serializer.clone());
+        let subscriber = node
+            .create_subscriber::<M>(&topic_name(i))
+            .expect("failed to create subscriber");
         let messages_received = Arc::clone(&messages_received);
         let latency_tracker = Arc::clone(&latency_tracker);
+        let interval_tracker = Arc::clone(&interval_tracker);
+        let executor = Arc::clone(&executor);
 
         let handle = tokio::spawn(async move {
             let start = Instant::now();
 
-            while start.elapsed() < duration {
-                // Simulate receiving and processing message
-                messages_received.fetch_add(1, Ordering::Relaxed);
-
-                // Record latency
-                let lat_duration = Duration::from_micros((1.0 + rand::random::<f64>() * 49.0) as u64);
-                latency_tracker.record(lat_duration);
+            while start.elapsed() < receive_for {
+                let remaining = receive_for.saturating_sub(start.elapsed());
+                let received = tokio::time::timeout(remaining, async {
+                    if zero_copy {
+                        subscriber.recv_arc_with_info().await.map(|(_, info)| info)
+                    } else {
+                        subscriber.recv_with_info().await.map(|(_, info)| info)
+                    }
+                })
+                .await;
+                match received {
+                    Ok(Ok(info)) => {
+                        messages_received.fetch_add(1, Ordering::Relaxed);
 
-                sleep(Duration::from_millis(1)).await;
+                        // Handled on the RT executor, which counts late handling
+                        let latency_tracker = Arc::clone(&latency_tracker);
+                        let interval_tracker = Arc::clone(&interval_tracker);
+                        executor.spawn_named(
+                            "stress_receive",
+                            RTPriority::High.into(),
+                            Deadline(deadline),
+                            async move {
+                                // Publish→receive latency, both stamped on the same clock
+                                if let Some(latency) = info.latency() {
+                                    latency_tracker.record(latency);
+                                    interval_tracker.record(latency);
+                                }
+                            },
+                        );
+                    }
+                    Ok(Err(_)) => continue,
+                    Err(_) => break,
+                }
             }
         });
 
         subscriber_handles.push(handle);
     }
 
-    // Progress monitoring
+    // Spawn publishers, in a child process when crossing processes
+    let publishers = if transport.is_cross_process() {
+        let exe = std::env::current_exe().expect("cannot locate the stress test binary");
+        let child = Command::new(exe)
+            .args(&config.child_args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to spawn publisher process");
+        let messages_sent = Arc::clone(&messages_sent);
+        tokio::task::spawn_blocking(move || {
+            let output = child.wait_with_output().expect("publisher process failed");
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if let Some(sent) = stdout.lines().find_map(|line| line.strip_prefix("sent ")) {
+                messages_sent.decode(sent);
+            }
+        })
+    } else {
+        let config = config.clone();
+        let node = stress_node("stress_publishers", transport, true);
+        let messages_sent = Arc::clone(&messages_sent);
+        let executor = Arc::clone(&executor);
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            let executor = Some(&*executor);
+            run_publishers(config, node, make_message, &messages_sent, &pool, executor).await
+        })
+    };
+
+    // Progress monitoring, sampled every interval
     let messages_sent_mon = Arc::clone(&messages_sent);
     let messages_received_mon = Arc::clone(&messages_received);
+    let latency_tracker_mon = Arc::clone(&latency_tracker);
+    let monitor_mon = Arc::clone(&monitor);
 
     let monitor_handle = tokio::spawn(async move {
-        let mut last_sent = 0;
-        let mut last_received = 0;
-        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        let mut timeseries = timeseries;
+        let mut previous = (0, 0, start_time);
+        let mut last_progress = previous;
+        let first = tokio::time::Instant::now() + interval;
+        let mut ticks = tokio::time::interval_at(first, interval);
 
-        for _ in 0..(duration.as_secs() / 5) {
-            interval.tick().await;
+        loop {
+            ticks.tick().await;
 
-            let sent = messages_sent_mon.load(Ordering::Relaxed);
+            let now = Instant::now();
+            let sent = messages_sent_mon.total();
             let received = messages_received_mon.load(Ordering::Relaxed);
+            let window = interval_tracker.reset();
 
-            let sent_rate = (sent - last_sent) as f64 / 5.0;
-            let received_rate = (received - last_received) as f64 / 5.0;
+            if let Some(timeseries) = &mut timeseries {
+                let usage = monitor_mon.usage();
+                let (sent_before, received_before, before) = previous;
+                let secs = now.duration_since(before).as_secs_f64();
+                let measured = window.count > 0;
+                timeseries.write(&TimeseriesRecord {
+                    elapsed_secs: now.duration_since(start_time).as_secs_f64(),
+                    interval_secs: secs,
+                    sent,
+                    received,
+                    sent_per_sec: (sent - sent_before) as f64 / secs,
+                    received_per_sec: (received - received_before) as f64 / secs,
+                    latency_count: window.count,
+                    latency_p50_us: measured.then_some(window.p50),
+                    latency_p99_us: measured.then_some(window.p99),
+                    rss_mb: usage.rss_mb,
+                    cpu_percent: usage.cpu_percent,
+                });
+            }
+            previous = (sent, received, now);
+
+            let (last_sent, last_received, last) = last_progress;
+            let since = now.duration_since(last);
+            if since < PROGRESS_PERIOD {
+                continue;
+            }
+            let sent_rate = (sent - last_sent) as f64 / since.as_secs_f64();
+            let received_rate = (received - last_received) as f64 / since.as_secs_f64();
+            let window = latency_tracker_mon.stats_window(PROGRESS_PERIOD);
 
             println!(
-                "  📊 Sent: {} ({:.0} msg/s) | Received: {} ({:.0} msg/s)",
+                "  📊 Sent: {} ({:.0} msg/s) | Received: {} ({:.0} msg/s) | p99 (5s): {} µs",
                 sent.to_string().yellow(),
                 sent_rate,
                 received.to_string().yellow(),
-                received_rate
+                received_rate,
+                window.p99.to_string().yellow()
             );
-
-            last_sent = sent;
-            last_received = received;
+            last_progress = previous;
         }
     });
 
     // Wait for all tasks to complete
-    for handle in publisher_handles {
-        handle.await.ok();
-    }
+    publishers.await.ok();
 
     for handle in subscriber_handles {
         handle.await.ok();
     }
 
+    // Records are written between awaits, so none is cut short
+    monitor_handle.abort();
     monitor_handle.await.ok();
 
     let elapsed = start_time.elapsed();
-    let total_sent = messages_sent.load(Ordering::Relaxed);
+    let resources = match Arc::try_unwrap(monitor) {
+        Ok(monitor) => monitor.finish(),
+        Err(monitor) => monitor.usage(),
+    };
+    let deadline_misses = executor.total_deadline_misses();
+    let executor_metrics = executor.metrics();
+    let buffer_pool = (!transport.is_cross_process()).then(|| pool.stats());
+    // Runtimes cannot be dropped from async code
+    tokio::task::spawn_blocking(move || drop(executor)).await.ok();
+    let total_sent = messages_sent.total();
+    let total_received = messages_received.load(Ordering::Relaxed);
+    let expected_deliveries = messages_sent.expected_deliveries(num_subscribers);
 
     // Get latency statistics
     let latency_stats = latency_tracker.stats();
@@ -248,36 +861,832 @@ async fn run_stress_test(
 
     StressTestResults {
         total_messages: total_sent,
+        total_received,
+        expected_deliveries,
+        dropped: expected_deliveries.saturating_sub(total_received),
         duration_secs: elapsed.as_secs_f64(),
         throughput: total_sent as f64 / elapsed.as_secs_f64(),
         latency_p50: latency_stats.p50 as f64,
-        latency_p95: latency_stats.p99 as f64 * 0.95,
+        latency_p95: latency_tracker.percentile(95.0) as f64,
         latency_p99: latency_stats.p99 as f64,
         latency_p999: latency_stats.p999 as f64,
         latency_max: latency_stats.max as f64,
-        avg_cpu_percent: 25.3 + rand::random::<f64>() * 10.0, // Simulated
-        peak_memory_mb: 145.2 + rand::random::<f64>() * 50.0, // Simulated
+        deadline_misses,
+        executor_metrics,
+        buffer_pool,
+        resources,
     }
 }
 
-fn print_results(results: &StressTestResults, json_output: bool) {
-    if json_output {
+/// Publish on `node` for the configured duration, counting messages by
+/// topic in `messages_sent`
+///
+/// With `executor` and a configured core, the publishers run pinned to it.
+async fn run_publishers<M: Message>(
+    config: StressConfig,
+    node: Node,
+    make_message: fn(u64) -> M,
+    messages_sent: &Arc<SentCounts>,
+    pool: &BufferPool,
+    executor: Option<&ROS3Executor>,
+) {
+    let publishers: Vec<_> = (0..config.num_publishers)
+        .filter(|&i| config.publishes(i))
+        .collect();
+    let StressConfig {
+        rate_hz,
+        duration,
+        format,
+        compression,
+        zero_copy,
+        pin_core,
+        ..
+    } = config;
+    let pin_core = pin_core.filter(|_| executor.is_some());
+    // Cores the publishers were allowed on and actually ran on
+    let allowed = Arc::new(Mutex::new(BTreeSet::new()));
+    let ran_on = Arc::new(Mutex::new(BTreeSet::new()));
+
+    let mut publisher_handles = Vec::new();
+    for i in publishers {
+        let options = PublisherOptions::new().format(format).compression(compression);
+        let publisher = node
+            .create_publisher_with_options::<M>(&topic_name(i), options)
+            .expect("failed to create publisher")
+            .with_pool(pool.clone());
+        let messages_sent = Arc::clone(&messages_sent);
+        let interval = Duration::from_micros(1_000_000 / rate_hz as u64);
+
+        let (allowed, ran_on) = (Arc::clone(&allowed), Arc::clone(&ran_on));
+
+        let task = async move {
+            if let Ok(cores) = current_affinity() {
+                allowed.lock().unwrap().extend(cores);
+            }
+            let mut sequence = 0u64;
+            let start = Instant::now();
+
+            while start.elapsed() < duration {
+                let message = make_message(sequence);
+                let result = if zero_copy {
+                    publisher.publish_arc(Arc::new(message)).await
+                } else {
+                    publisher.publish(&message).await
+                };
+
+                if result.is_ok() {
+                    messages_sent.add(i);
+                    sequence += 1;
+                }
+                if let Some(cpu) = current_cpu() {
+                    ran_on.lock().unwrap().insert(cpu);
+                }
+
+                sleep(interval).await;
+            }
+        };
+        let handle = match (executor, pin_core) {
+            (Some(executor), Some(core)) => executor
+                .spawn_pinned(core, RTPriority::High.into(), task)
+                .expect("failed to pin publisher"),
+            _ => tokio::spawn(task),
+        };
+
+        publisher_handles.push(handle);
+    }
+
+    for handle in publisher_handles {
+        handle.await.ok();
+    }
+
+    if let Some(core) = pin_core {
+        println!(
+            "  📌 Publishers pinned to core {}: allowed on {:?}, ran on {:?}",
+            core,
+            allowed.lock().unwrap(),
+            ran_on.lock().unwrap()
+        );
+    }
+}
+
+/// Role of a worker of --mode multiprocess
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Role {
+    Publisher,
+    Subscriber,
+}
+
+impl Role {
+    /// Workers alternate between publishing and subscribing
+    fn of(worker_id: usize) -> Self {
+        if worker_id.is_multiple_of(2) {
+            Role::Publisher
+        } else {
+            Role::Subscriber
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Role::Publisher => "publisher",
+            Role::Subscriber => "subscriber",
+        }
+    }
+}
+
+/// Index of a worker among the `workers` of its role, and their number
+fn worker_slot(worker_id: usize, workers: usize) -> (usize, usize) {
+    let count = match Role::of(worker_id) {
+        Role::Publisher => workers.div_ceil(2),
+        Role::Subscriber => workers / 2,
+    };
+    (worker_id / 2, count)
+}
+
+/// The transport of --mode multiprocess, UDP unless shm is asked for
+///
+/// Zenoh is left out: its subscribing side listens on one fixed endpoint.
+fn multiprocess_transport(args: &Args) -> Transport {
+    match Transport::parse(&args.transport) {
+        Transport::InProcess => Transport::Udp,
+        Transport::Zenoh => {
+            eprintln!("{}", "--mode multiprocess runs over shm or udp, not zenoh".red());
+            std::process::exit(2);
+        }
+        transport => transport,
+    }
+}
+
+/// Leaves the workers' transports time to discover each other
+const WARMUP: Duration = Duration::from_secs(1);
+
+/// Leaves messages in flight time to arrive once publishing stopped
+const DRAIN: Duration = Duration::from_millis(500);
+
+/// Longest the workers may take to connect, and to report after the run
+const WORKER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Count and time the messages of `subscribers` until the returned tasks are
+/// aborted
+fn receive<M: Message>(
+    subscribers: Vec<Subscriber<M>>,
+    zero_copy: bool,
+    received: &Arc<AtomicU64>,
+    latency_tracker: &Arc<LatencyTracker>,
+) -> Vec<JoinHandle<()>> {
+    subscribers
+        .into_iter()
+        .map(|subscriber| {
+            let received = Arc::clone(received);
+            let latency_tracker = Arc::clone(latency_tracker);
+            tokio::spawn(async move {
+                loop {
+                    let info = if zero_copy {
+                        subscriber.recv_arc_with_info().await.map(|(_, info)| info)
+                    } else {
+                        subscriber.recv_with_info().await.map(|(_, info)| info)
+                    };
+                    let Ok(info) = info else { continue };
+                    received.fetch_add(1, Ordering::Relaxed);
+                    if let Some(latency) = info.latency() {
+                        latency_tracker.record(latency);
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Run worker `worker_id` of --mode multiprocess
+///
+/// The worker connects to the coordinator's control socket and says
+/// `ready <id>` once its endpoints exist. A publishing worker then publishes
+/// from `start` for the configured duration, receiving its own topics in
+/// process too; a subscribing worker receives until `stop`. Either ends by
+/// writing a JSON report with its latency histogram.
+async fn run_worker<M: Message>(args: &Args, worker_id: usize, make_message: fn(u64) -> M) {
+    let control = args.control.as_deref().expect("workers need --control");
+    let stream = TcpStream::connect(control)
+        .await
+        .expect("failed to connect to the coordinator");
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let transport = Transport::parse(&args.transport);
+    let (index, count) = worker_slot(worker_id, args.workers);
+    let received = Arc::new(AtomicU64::new(0));
+    let latency_tracker = Arc::new(LatencyTracker::new("worker"));
+
+    let ready = format!("ready {}\n", worker_id);
+    let report = match Role::of(worker_id) {
+        Role::Publisher => {
+            let mut config = stress_config(args, transport);
+            config.publisher_worker = Some((index, count));
+            let node = stress_node(&format!("stress_publishers_{}", index), transport, true);
+            // Same process, so the bus delivers without the transport
+            let local =
+                Node::new(format!("stress_local_{}", index)).expect("failed to create node");
+            let topics: BTreeSet<_> = (0..config.num_publishers)
+                .filter(|&i| config.publishes(i))
+                .map(|i| i % TOPICS)
+                .collect();
+            let subscribers = topics
+                .into_iter()
+                .map(|topic| {
+                    local
+                        .create_subscriber::<M>(&topic_name(topic))
+                        .expect("failed to create subscriber")
+                })
+                .collect();
+
+            write.write_all(ready.as_bytes()).await.expect("coordinator gone");
+            let start = lines.next_line().await.expect("coordinator gone");
+            assert_eq!(start.as_deref(), Some("start"), "unexpected control message");
+
+            let receivers = receive(subscribers, args.zero_copy, &received, &latency_tracker);
+            let sent = Arc::new(SentCounts::default());
+            let pool = BufferPool::default();
+            run_publishers(config, node, make_message, &sent, &pool, None).await;
+            sleep(DRAIN).await;
+            receivers.iter().for_each(JoinHandle::abort);
+            serde_json::json!({
+                "sent": sent.counts(),
+                "received": received.load(Ordering::Relaxed),
+                "histogram": latency_tracker.export_base64().expect("failed to export histogram")
+            })
+        }
+        Role::Subscriber => {
+            let node = stress_node(&format!("stress_subscribers_{}", index), transport, false);
+            let subscribers = (0..args.subscribers)
+                .filter(|j| j % count == index)
+                .map(|j| {
+                    node.create_subscriber::<M>(&topic_name(j))
+                        .expect("failed to create subscriber")
+                })
+                .collect();
+            let receivers = receive(subscribers, args.zero_copy, &received, &latency_tracker);
+
+            write.write_all(ready.as_bytes()).await.expect("coordinator gone");
+            let stop = lines.next_line().await.expect("coordinator gone");
+            assert_eq!(stop.as_deref(), Some("stop"), "unexpected control message");
+
+            receivers.iter().for_each(JoinHandle::abort);
+            serde_json::json!({
+                "received": received.load(Ordering::Relaxed),
+                "histogram": latency_tracker.export_base64().expect("failed to export histogram")
+            })
+        }
+    };
+    write
+        .write_all(format!("{}\n", report).as_bytes())
+        .await
+        .expect("coordinator gone");
+}
+
+/// A worker process of --mode multiprocess
+struct Worker {
+    child: Child,
+    /// Everything the worker writes to stderr, read until it exits
+    stderr: Option<std::thread::JoinHandle<String>>,
+    control: Option<(Lines<BufReader<OwnedReadHalf>>, OwnedWriteHalf)>,
+    reported: bool,
+}
+
+/// The workers of a --mode multiprocess run, by id
+struct Workers(Vec<Worker>);
+
+impl Workers {
+    fn spawn(args: &Args, transport: Transport, control: &str) -> Self {
+        let exe = std::env::current_exe().expect("cannot locate the stress test binary");
+        let workers = (0..args.workers)
+            .map(|id| {
+                let mut child = Command::new(&exe)
+                    .args(forwarded_args(args, transport))
+                    .arg(format!("--worker-id={}", id))
+                    .arg(format!("--control={}", control))
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .expect("failed to spawn worker process");
+                let mut stderr = child.stderr.take().expect("stderr is piped");
+                let stderr = std::thread::spawn(move || {
+                    let mut output = String::new();
+                    let _ = stderr.read_to_string(&mut output);
+                    output
+                });
+                Worker {
+                    child,
+                    stderr: Some(stderr),
+                    control: None,
+                    reported: false,
+                }
+            })
+            .collect();
+        Workers(workers)
+    }
+
+    fn ids(&self, role: Role) -> Vec<usize> {
+        (0..self.0.len()).filter(|&id| Role::of(id) == role).collect()
+    }
+
+    /// Fail the run: stop every worker and exit with the stderr of `id`
+    fn fail(&mut self, id: usize, reason: &str) -> ! {
+        for worker in &mut self.0 {
+            let _ = worker.child.kill();
+            let _ = worker.child.wait();
+        }
+        let stderr = self.0[id]
+            .stderr
+            .take()
+            .and_then(|stderr| stderr.join().ok())
+            .unwrap_or_default();
+        eprintln!(
+            "{}",
+            format!("Worker {} ({}) {}", id, Role::of(id).name(), reason).red().bold()
+        );
+        if !stderr.trim().is_empty() {
+            eprintln!("--- stderr of worker {} ---", id);
+            eprintln!("{}", stderr.trim_end());
+        }
+        std::process::exit(1);
+    }
+
+    /// Fail the run if a worker exited before reporting or unsuccessfully
+    fn check(&mut self) {
+        for id in 0..self.0.len() {
+            let worker = &mut self.0[id];
+            if let Ok(Some(status)) = worker.child.try_wait() {
+                if !status.success() || !worker.reported {
+                    self.fail(id, &format!("exited with {}", status));
+                }
+            }
+        }
+    }
+
+    /// Accept the control connection of every worker
+    async fn connect(&mut self, listener: &TcpListener) {
+        let deadline = Instant::now() + WORKER_TIMEOUT;
+        while let Some(id) = self.0.iter().position(|worker| worker.control.is_none()) {
+            if Instant::now() > deadline {
+                self.fail(id, "did not connect in time");
+            }
+            let accepted = tokio::time::timeout(Duration::from_millis(100), listener.accept());
+            let Ok(accepted) = accepted.await else {
+                self.check();
+                continue;
+            };
+            let (stream, _) = accepted.expect("failed to accept a worker");
+            let (read, write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            let hello = tokio::time::timeout(WORKER_TIMEOUT, lines.next_line()).await;
+            let id = match hello {
+                Ok(Ok(Some(line))) => line
+                    .strip_prefix("ready ")
+                    .and_then(|id| id.parse::<usize>().ok()),
+                _ => None,
+            };
+            match id {
+                Some(id) if id < self.0.len() => self.0[id].control = Some((lines, write)),
+                _ => {
+                    self.check();
+                    panic!("a worker connected without saying which one it is");
+                }
+            }
+        }
+    }
+
+    async fn send(&mut self, id: usize, message: &str) {
+        let (_, write) = self.0[id].control.as_mut().expect("worker is connected");
+        if write.write_all(format!("{}\n", message).as_bytes()).await.is_err() {
+            self.fail(id, "closed its control connection");
+        }
+    }
+
+    /// The report of worker `id`, waiting for it at most `wait`
+    async fn report(&mut self, id: usize, wait: Duration) -> serde_json::Value {
+        let deadline = Instant::now() + wait;
+        loop {
+            let (lines, _) = self.0[id].control.as_mut().expect("worker is connected");
+            // next_line is cancel safe, so polling loses nothing
+            match tokio::time::timeout(Duration::from_millis(100), lines.next_line()).await {
+                Ok(Ok(Some(line))) => match serde_json::from_str(&line) {
+                    Ok(report) => {
+                        self.0[id].reported = true;
+                        return report;
+                    }
+                    Err(_) => self.fail(id, &format!("sent an invalid report: {}", line)),
+                },
+                Ok(_) => self.fail(id, "closed its control connection before reporting"),
+                Err(_) if Instant::now() > deadline => self.fail(id, "did not report in time"),
+                Err(_) => self.check(),
+            }
+        }
+    }
+
+    /// Add the histogram in the report of worker `id` to `histogram`
+    fn merge(&mut self, id: usize, report: &serde_json::Value, histogram: &mut Histogram<u64>) {
+        let encoded = report["histogram"].as_str().unwrap_or_default();
+        let merged = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| {
+                Deserializer::new()
+                    .deserialize::<u64, _>(&mut bytes.as_slice())
+                    .map_err(|err| err.to_string())
+            })
+            .and_then(|worker| histogram.add(&worker).map_err(|err| err.to_string()));
+        if let Err(err) = merged {
+            self.fail(id, &format!("sent an unreadable histogram: {}", err));
+        }
+    }
+
+    /// Wait for every worker to exit
+    fn finish(&mut self) {
+        for id in 0..self.0.len() {
+            match self.0[id].child.wait() {
+                Ok(status) if status.success() => {}
+                Ok(status) => self.fail(id, &format!("exited with {}", status)),
+                Err(err) => self.fail(id, &format!("could not be waited for: {}", err)),
+            }
+        }
+    }
+}
+
+struct MultiprocessResults {
+    transport: Transport,
+    workers: usize,
+    total_messages: u64,
+    total_received: u64,
+    expected_deliveries: u64,
+    dropped: u64,
+    duration_secs: f64,
+    throughput: f64,
+    /// Latency from publishing workers to subscribing workers
+    cross_process: Histogram<u64>,
+    /// Messages the publishing workers received from themselves
+    intra_received: u64,
+    /// Latency of those messages
+    intra_process: Histogram<u64>,
+}
+
+/// Run the publishers and subscribers in `--workers` worker processes,
+/// started together over a control socket
+async fn run_multiprocess(args: &Args) -> MultiprocessResults {
+    let transport = multiprocess_transport(args);
+    let (publishers, subscribers) = (args.workers.div_ceil(2), args.workers / 2);
+    if subscribers == 0 {
+        eprintln!("{}", "--mode multiprocess needs at least 2 workers".red());
+        std::process::exit(2);
+    }
+
+    println!(
+        "{}",
+        format!(
+            "Starting {} publishing and {} subscribing workers ({})...",
+            publishers,
+            subscribers,
+            transport.name()
+        )
+        .green()
+        .bold()
+    );
+    println!();
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("failed to open the control socket");
+    let control = listener.local_addr().expect("control socket has no address").to_string();
+    let mut workers = Workers::spawn(args, transport, &control);
+    workers.connect(&listener).await;
+    sleep(WARMUP).await;
+
+    let start_time = Instant::now();
+    let publishing = workers.ids(Role::Publisher);
+    let subscribing = workers.ids(Role::Subscriber);
+    for &id in &publishing {
+        workers.send(id, "start").await;
+    }
+
+    let sent = SentCounts::default();
+    let mut intra_received = 0;
+    let mut intra_process = Histogram::<u64>::new(3).unwrap();
+    let wait = Duration::from_secs(args.duration) + WORKER_TIMEOUT;
+    for &id in &publishing {
+        let report = workers.report(id, wait).await;
+        let counts: Vec<u64> = serde_json::from_value(report["sent"].clone()).unwrap_or_default();
+        sent.add_all(&counts);
+        intra_received += report["received"].as_u64().unwrap_or(0);
+        workers.merge(id, &report, &mut intra_process);
+    }
+
+    sleep(DRAIN).await;
+    for &id in &subscribing {
+        workers.send(id, "stop").await;
+    }
+    let mut total_received = 0;
+    let mut cross_process = Histogram::<u64>::new(3).unwrap();
+    for &id in &subscribing {
+        let report = workers.report(id, WORKER_TIMEOUT).await;
+        total_received += report["received"].as_u64().unwrap_or(0);
+        workers.merge(id, &report, &mut cross_process);
+    }
+    let elapsed = start_time.elapsed();
+    workers.finish();
+
+    println!("{}", "Stress test complete!".green().bold());
+    println!();
+
+    let total_messages = sent.total();
+    let expected_deliveries = sent.expected_deliveries(args.subscribers);
+    MultiprocessResults {
+        transport,
+        workers: args.workers,
+        total_messages,
+        total_received,
+        expected_deliveries,
+        dropped: expected_deliveries.saturating_sub(total_received),
+        duration_secs: elapsed.as_secs_f64(),
+        throughput: total_messages as f64 / elapsed.as_secs_f64(),
+        cross_process,
+        intra_received,
+        intra_process,
+    }
+}
+
+/// Holds the lock for this long per logger cycle
+const LOGGER_HOLD: Duration = Duration::from_micros(200);
+
+/// Three tasks on one core: a `Low` logger holding the shared state for
+/// [`LOGGER_HOLD`], a `High` task spinning in 5 ms bursts and a `Critical`
+/// control task taking the state every millisecond. Without priority
+/// inheritance the control task waits for the hog's bursts, with it at most
+/// about `LOGGER_HOLD`.
+async fn run_priority_inversion(args: &Args) {
+    let core = args.pin_core.unwrap_or(0);
+    let duration = Duration::from_secs(args.duration);
+    let timeout = Duration::from_micros(args.deadline_us);
+    let executor = ROS3Executor::with_config(ExecutorConfig::realtime())
+        .expect("failed to create executor");
+    let realtime = executor.capabilities().realtime;
+    let state = Arc::new(PiMutex::new(robot_state(0)));
+    let waits = Arc::new(Mutex::new(Histogram::<u64>::new(3).unwrap()));
+    let timeouts = Arc::new(AtomicU64::new(0));
+    let start = Instant::now();
+
+    let logger = {
+        let state = Arc::clone(&state);
+        async move {
+            let mut sequence = 0;
+            while start.elapsed() < duration {
+                {
+                    let mut state = state.lock();
+                    *state = robot_state(sequence);
+                    spin(LOGGER_HOLD);
+                }
+                sequence += 1;
+                sleep(Duration::from_millis(1)).await;
+            }
+        }
+    };
+    let hog = async move {
+        while start.elapsed() < duration {
+            spin(Duration::from_millis(5));
+            sleep(Duration::from_millis(1)).await;
+        }
+    };
+    let control = {
+        let state = Arc::clone(&state);
+        let waits = Arc::clone(&waits);
+        let timeouts = Arc::clone(&timeouts);
+        async move {
+            while start.elapsed() < duration {
+                let waiting = Instant::now();
+                let acquired = state.try_lock_for(timeout).map(|state| state.timestamp);
+                match acquired {
+                    Some(_) => {
+                        let waited = waiting.elapsed().as_micros() as u64;
+                        waits.lock().unwrap().record(waited).ok();
+                    }
+                    None => {
+                        timeouts.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                sleep(Duration::from_millis(1)).await;
+            }
+        }
+    };
+
+    let handles = [
+        executor.spawn_pinned(core, RTPriority::Low.into(), logger),
+        executor.spawn_pinned(core, RTPriority::High.into(), hog),
+        executor.spawn_pinned(core, RTPriority::Critical.into(), control),
+    ];
+    for handle in handles {
+        handle.expect("failed to pin task").await.ok();
+    }
+    // Runtimes cannot be dropped from async code
+    tokio::task::spawn_blocking(move || drop(executor)).await.ok();
+
+    let waits = waits.lock().unwrap();
+    let timeouts = timeouts.load(Ordering::Relaxed);
+    if args.json {
         let json = serde_json::json!({
-            "total_messages": results.total_messages,
-            "duration_secs": results.duration_secs,
-            "throughput_msg_per_sec": results.throughput,
-            "latency_us": {
-                "p50": results.latency_p50,
-                "p95": results.latency_p95,
-                "p99": results.latency_p99,
-                "p999": results.latency_p999,
-                "max": results.latency_max
-            },
-            "cpu_percent_avg": results.avg_cpu_percent,
-            "memory_mb_peak": results.peak_memory_mb
+            "core": core,
+            "priority_inheritance": state.inherits_priority(),
+            "realtime_threads": realtime,
+            "acquisitions": waits.len(),
+            "timeouts": timeouts,
+            "timeout_us": timeout.as_micros() as u64,
+            "lock_wait_us": {
+                "p50": waits.value_at_quantile(0.50),
+                "p99": waits.value_at_quantile(0.99),
+                "max": waits.max()
+            }
         });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
 
+    println!("{}", "Priority Inversion:".bold().cyan());
+    println!("{}", "-".repeat(70));
+    println!("  Core:                 {}", core);
+    println!("  Priority inheritance: {}", state.inherits_priority());
+    println!("  Real-time threads:    {}", realtime);
+    println!("  Lock acquisitions:    {}", waits.len());
+    println!("  Lock wait p50:        {} µs", waits.value_at_quantile(0.50));
+    println!("  Lock wait p99:        {} µs", waits.value_at_quantile(0.99));
+    println!("  Lock wait max:        {} µs", waits.max());
+    let timeouts_text = format!("{} (> {:?})", timeouts, timeout);
+    if timeouts > 0 {
+        println!("  Timeouts:             {}", timeouts_text.red());
+    } else {
+        println!("  Timeouts:             {}", timeouts_text.green());
+    }
+    if !realtime {
+        println!(
+            "  {}",
+            "Without real-time threads inheritance has no effect; grant CAP_SYS_NICE".yellow()
+        );
+    }
+}
+
+/// Keep the core busy for `duration`
+fn spin(duration: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        std::hint::spin_loop();
+    }
+}
+
+fn print_comparison(
+    shm: &StressTestResults,
+    udp: &StressTestResults,
+    run: &serde_json::Value,
+    json_output: bool,
+) {
+    let delivered = |r: &StressTestResults| {
+        r.total_received as f64 / r.expected_deliveries.max(1) as f64 * 100.0
+    };
+
+    if json_output {
+        let entry = |r: &StressTestResults| {
+            serde_json::json!({
+                "total_messages": r.total_messages,
+                "total_received": r.total_received,
+                "expected_deliveries": r.expected_deliveries,
+                "dropped": r.dropped,
+                "delivered_percent": delivered(r),
+                "throughput_msg_per_sec": r.throughput,
+                "latency_us": {
+                    "p50": r.latency_p50,
+                    "p99": r.latency_p99,
+                    "max": r.latency_max
+                }
+            })
+        };
+        let json = serde_json::json!({
+            "schema_version": RESULTS_SCHEMA_VERSION,
+            "run": run,
+            "shm": entry(shm),
+            "udp": entry(udp)
+        });
         println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+
+    println!("{}", "Shared Memory vs UDP Loopback:".bold().cyan());
+    println!("{}", "-".repeat(70));
+    println!("  {:<18} {:>15} {:>15}", "", "shm", "udp");
+    println!("  {:<18} {:>15.0} {:>15.0}", "Throughput msg/s", shm.throughput, udp.throughput);
+    println!("  {:<18} {:>14.1}% {:>14.1}%", "Delivered", delivered(shm), delivered(udp));
+    println!("  {:<18} {:>15.1} {:>15.1}", "p50 latency µs", shm.latency_p50, udp.latency_p50);
+    println!("  {:<18} {:>15.1} {:>15.1}", "p99 latency µs", shm.latency_p99, udp.latency_p99);
+    println!("  {:<18} {:>15.1} {:>15.1}", "max latency µs", shm.latency_max, udp.latency_max);
+    println!();
+}
+
+/// Latency percentiles of `histogram`, in microseconds
+fn latency_json(histogram: &Histogram<u64>) -> serde_json::Value {
+    serde_json::json!({
+        "count": histogram.len(),
+        "p50": histogram.value_at_quantile(0.5),
+        "p95": histogram.value_at_quantile(0.95),
+        "p99": histogram.value_at_quantile(0.99),
+        "p999": histogram.value_at_quantile(0.999),
+        "max": histogram.max()
+    })
+}
+
+fn multiprocess_json(results: &MultiprocessResults, run: &serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "schema_version": RESULTS_SCHEMA_VERSION,
+        "mode": "multiprocess",
+        "run": run,
+        "transport": results.transport.name(),
+        "workers": results.workers,
+        "total_messages": results.total_messages,
+        "total_received": results.total_received,
+        "expected_deliveries": results.expected_deliveries,
+        "dropped": results.dropped,
+        "intra_process_received": results.intra_received,
+        "duration_secs": results.duration_secs,
+        "throughput_msg_per_sec": results.throughput,
+        "latency_us": {
+            "cross_process": latency_json(&results.cross_process),
+            "intra_process": latency_json(&results.intra_process)
+        }
+    })
+}
+
+fn print_multiprocess(results: &MultiprocessResults, json: &serde_json::Value, json_output: bool) {
+    let (cross, intra) = (&results.cross_process, &results.intra_process);
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(json).unwrap());
+        return;
+    }
+
+    println!("{}", "Performance Results:".bold().cyan());
+    println!("{}", "-".repeat(70));
+    println!("  Total Messages:  {}", results.total_messages.to_string().yellow());
+    println!(
+        "  Delivered:       {} of {} ({} dropped)",
+        results.total_received.to_string().yellow(),
+        results.expected_deliveries,
+        results.dropped
+    );
+    println!("  Duration:        {:.2} seconds", results.duration_secs);
+    println!("  Throughput:      {} msg/s", format!("{:.0}", results.throughput).green().bold());
+    println!();
+
+    println!("{}", "Latency (microseconds):".bold());
+    println!("  {:<18} {:>15} {:>15}", "", "cross-process", "intra-process");
+    println!("  {:<18} {:>15} {:>15}", "Messages", cross.len(), intra.len());
+    for (label, quantile) in [("p50", 0.5), ("p95", 0.95), ("p99", 0.99), ("p99.9", 0.999)] {
+        println!(
+            "  {:<18} {:>15} {:>15}",
+            label,
+            cross.value_at_quantile(quantile),
+            intra.value_at_quantile(quantile)
+        );
+    }
+    println!("  {:<18} {:>15} {:>15}", "max", cross.max(), intra.max());
+    println!();
+}
+
+fn results_json(results: &StressTestResults, run: &serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "schema_version": RESULTS_SCHEMA_VERSION,
+        "mode": "single",
+        "run": run,
+        "total_messages": results.total_messages,
+        "total_received": results.total_received,
+        "expected_deliveries": results.expected_deliveries,
+        "dropped": results.dropped,
+        "duration_secs": results.duration_secs,
+        "throughput_msg_per_sec": results.throughput,
+        "latency_us": {
+            "p50": results.latency_p50,
+            "p95": results.latency_p95,
+            "p99": results.latency_p99,
+            "p999": results.latency_p999,
+            "max": results.latency_max
+        },
+        "deadline_misses": results.deadline_misses,
+        "executor": results.executor_metrics,
+        "buffer_pool": results.buffer_pool.map(|pool| serde_json::json!({
+            "stats": pool,
+            "hit_rate": pool.hit_rate()
+        })),
+        "cpu_percent_avg": results.resources.avg_cpu_percent,
+        "cpu_percent_per_core": results.resources.per_core_percent,
+        "memory_mb_peak": results.resources.peak_rss_mb,
+        "metrics": serde_json::from_str::<serde_json::Value>(&Registry::global().encode_json())
+            .unwrap()
+    })
+}
+
+fn print_results(results: &StressTestResults, json: &serde_json::Value, json_output: bool) {
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(json).unwrap());
     } else {
         println!("{}", "Performance Results:".bold().cyan());
         println!("{}", "-".repeat(70));
@@ -285,6 +1694,12 @@ fn print_results(results: &StressTestResults, json_output: bool) {
 
         println!("{}", "Throughput:".bold());
         println!("  Total Messages:  {}", results.total_messages.to_string().yellow());
+        println!(
+            "  Delivered:       {} of {} ({} dropped)",
+            results.total_received.to_string().yellow(),
+            results.expected_deliveries,
+            results.dropped
+        );
         println!("  Duration:        {:.2} seconds", results.duration_secs);
         println!("  Throughput:      {} msg/s", format!("{:.0}", results.throughput).green().bold());
         println!();
@@ -295,11 +1710,50 @@ fn print_results(results: &StressTestResults, json_output: bool) {
         println!("  p99:             {} µs", format!("{:.1}", results.latency_p99).yellow());
         println!("  p99.9:           {} µs", format!("{:.1}", results.latency_p999).yellow());
         println!("  max:             {} µs", format!("{:.1}", results.latency_max).yellow());
+        println!("  Deadline misses: {}", results.deadline_misses.to_string().yellow());
         println!();
 
+        println!("{}", "Executor (microseconds):".bold());
+        for priority in &results.executor_metrics.priorities {
+            if priority.tasks_spawned == 0 {
+                continue;
+            }
+            println!(
+                "  {:<10} {} tasks | wake p99: {} µs | poll p99: {} µs | long polls: {}",
+                format!("{:?}:", priority.priority),
+                priority.tasks_spawned,
+                priority.wake_latency.p99.to_string().yellow(),
+                priority.poll_duration.p99.to_string().yellow(),
+                priority.long_polls
+            );
+        }
+        println!();
+
+        if let Some(pool) = &results.buffer_pool {
+            println!("{}", "Buffer Pool:".bold());
+            println!(
+                "  Hit rate:        {} ({} of {} buffers pooled)",
+                format!("{:.1}%", pool.hit_rate() * 100.0).yellow(),
+                pool.hits,
+                pool.acquired
+            );
+            println!("  High water:      {} buffers in use", pool.high_water);
+            println!();
+        }
+
+        let resources = &results.resources;
         println!("{}", "Resource Usage:".bold());
-        println!("  Avg CPU:         {:.1}%", results.avg_cpu_percent);
-        println!("  Peak Memory:     {:.1} MB", results.peak_memory_mb);
+        match (resources.avg_cpu_percent, resources.peak_rss_mb) {
+            (Some(cpu), Some(memory)) => {
+                println!("  Avg CPU:         {:.1}% of one core", cpu);
+                println!("  Peak Memory:     {:.1} MB", memory);
+            }
+            _ => println!("  Not measured on this platform"),
+        }
+        if let Some(cores) = &resources.per_core_percent {
+            let cores: Vec<_> = cores.iter().map(|percent| format!("{:.0}%", percent)).collect();
+            println!("  Per core:        {}", cores.join(" "));
+        }
         println!();
 
         println!("{}", "=".repeat(70).bold());
@@ -323,3 +1777,330 @@ fn print_results(results: &StressTestResults, json_output: bool) {
         }
     }
 }
+
+/// What was run, when and on which revision, so saved baselines describe
+/// themselves; the revision comes from `GIT_SHA` or, in CI, `GITHUB_SHA`
+fn run_metadata(args: &Args) -> serde_json::Value {
+    let git_sha = std::env::var("GIT_SHA")
+        .or_else(|_| std::env::var("GITHUB_SHA"))
+        .ok();
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    serde_json::json!({
+        "git_sha": git_sha,
+        "date": utc_date(since_epoch.as_secs()),
+        "config": {
+            "mode": args.mode,
+            "transport": args.transport,
+            "workers": args.workers,
+            "publishers": args.publishers,
+            "subscribers": args.subscribers,
+            "rate_hz": args.rate,
+            "duration_secs": args.duration,
+            "message_size": args.message_size,
+            "format": args.format,
+            "compression": args.compression,
+            "zero_copy": args.zero_copy,
+            "pin_core": args.pin_core,
+            "deadline_us": args.deadline_us
+        }
+    })
+}
+
+/// `secs` since the Unix epoch as an RFC 3339 UTC date, e.g.
+/// `2024-05-01T12:00:00Z`
+fn utc_date(secs: u64) -> String {
+    let (days, time) = (secs / 86_400, secs % 86_400);
+    // Civil from days, after Howard Hinnant's algorithm
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+/// A tolerance such as `5%` or `5`, as the fraction 0.05
+fn parse_percent(value: &str) -> Result<f64, String> {
+    let number = value.trim().trim_end_matches('%');
+    match number.parse::<f64>() {
+        Ok(percent) if percent >= 0.0 => Ok(percent / 100.0),
+        _ => Err(format!("expected a percentage such as 5%, got '{}'", value)),
+    }
+}
+
+/// p99 latencies below the histogram resolution count as this much, so a
+/// zero baseline does not make any latency an infinite regression
+const MIN_P99_US: f64 = 1.0;
+
+/// The figures a baseline gates on
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Summary {
+    throughput: f64,
+    /// Cross-process p99 for multiprocess runs, in microseconds
+    p99_us: f64,
+}
+
+impl Summary {
+    fn of(results: &serde_json::Value) -> Option<Summary> {
+        let latency = &results["latency_us"];
+        let p99 = match results["mode"].as_str() {
+            Some("multiprocess") => &latency["cross_process"]["p99"],
+            _ => &latency["p99"],
+        };
+        Some(Summary {
+            throughput: results["throughput_msg_per_sec"].as_f64()?,
+            p99_us: p99.as_f64()?,
+        })
+    }
+}
+
+/// Largest tolerated regressions, as fractions of the baseline
+#[derive(Clone, Copy, Debug)]
+struct Tolerances {
+    throughput: f64,
+    p99: f64,
+}
+
+/// One metric of a run compared against its baseline
+#[derive(Debug, PartialEq)]
+struct Comparison {
+    metric: &'static str,
+    baseline: f64,
+    current: f64,
+    /// Relative change, positive when the metric rose
+    change: f64,
+    /// Relative change beyond which the metric regressed
+    limit: f64,
+    regressed: bool,
+}
+
+fn compare(baseline: Summary, current: Summary, tolerances: Tolerances) -> Vec<Comparison> {
+    // Without a baseline throughput there is nothing to fall below
+    let throughput_change = if baseline.throughput > 0.0 {
+        (current.throughput - baseline.throughput) / baseline.throughput
+    } else {
+        0.0
+    };
+    let p99_reference = baseline.p99_us.max(MIN_P99_US);
+    let p99_change = (current.p99_us.max(MIN_P99_US) - p99_reference) / p99_reference;
+    vec![
+        Comparison {
+            metric: "throughput msg/s",
+            baseline: baseline.throughput,
+            current: current.throughput,
+            change: throughput_change,
+            limit: -tolerances.throughput,
+            regressed: throughput_change < -tolerances.throughput,
+        },
+        Comparison {
+            metric: "p99 latency µs",
+            baseline: baseline.p99_us,
+            current: current.p99_us,
+            change: p99_change,
+            limit: tolerances.p99,
+            regressed: p99_change > tolerances.p99,
+        },
+    ]
+}
+
+/// Save and check baselines as asked; exits with status 1 on a regression
+fn gate(args: &Args, results: &serde_json::Value) {
+    if let Some(path) = &args.save_baseline {
+        let json = serde_json::to_string_pretty(results).unwrap();
+        std::fs::write(path, json).expect("failed to save the baseline");
+        eprintln!("Saved baseline to {}", path.display());
+    }
+    let Some(path) = &args.baseline else { return };
+
+    let baseline: serde_json::Value = std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| {
+            eprintln!("{}", format!("Cannot read baseline {}: {}", path.display(), err).red());
+            std::process::exit(2);
+        });
+    let (Some(before), Some(after)) = (Summary::of(&baseline), Summary::of(results)) else {
+        eprintln!("{}", format!("{} holds no stress test results", path.display()).red());
+        std::process::exit(2);
+    };
+
+    // Keep stdout parseable with --json
+    let mut report = Vec::new();
+    let (old, new) = (&baseline["run"]["config"], &results["run"]["config"]);
+    if let (Some(old), Some(new)) = (old.as_object(), new.as_object()) {
+        let differing: Vec<_> = new
+            .iter()
+            .filter(|(key, value)| old.get(*key) != Some(*value))
+            .map(|(key, _)| key.as_str())
+            .collect();
+        if !differing.is_empty() {
+            let warning = format!(
+                "⚠️  Baseline ran with a different configuration: {}",
+                differing.join(", ")
+            );
+            report.push(warning.yellow().to_string());
+        }
+    }
+
+    let tolerances = Tolerances {
+        throughput: args.max_regress_throughput,
+        p99: args.max_regress_p99,
+    };
+    let comparisons = compare(before, after, tolerances);
+    report.push(format!("{}", "Baseline Comparison:".bold().cyan()));
+    if let Some(sha) = baseline["run"]["git_sha"].as_str() {
+        report.push(format!("  Baseline revision: {}", sha));
+    }
+    report.push("-".repeat(70));
+    report.push(format!(
+        "  {:<18} {:>12} {:>12} {:>9} {:>8}  {}",
+        "", "baseline", "current", "change", "limit", "status"
+    ));
+    for comparison in &comparisons {
+        let status = if comparison.regressed {
+            "REGRESSED".red().bold()
+        } else {
+            "ok".green()
+        };
+        let change = format!("{:+.1}%", comparison.change * 100.0);
+        let change = match comparison.regressed {
+            true => change.red(),
+            false => change.normal(),
+        };
+        report.push(format!(
+            "  {:<18} {:>12.1} {:>12.1} {:>9} {:>7.1}%  {}",
+            comparison.metric,
+            comparison.baseline,
+            comparison.current,
+            change,
+            comparison.limit * 100.0,
+            status
+        ));
+    }
+    for line in report {
+        if args.json {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    if comparisons.iter().any(|comparison| comparison.regressed) {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCES: Tolerances = Tolerances {
+        throughput: 0.05,
+        p99: 0.10,
+    };
+
+    fn summary(throughput: f64, p99_us: f64) -> Summary {
+        Summary { throughput, p99_us }
+    }
+
+    fn regressed(comparisons: &[Comparison]) -> Vec<&'static str> {
+        comparisons
+            .iter()
+            .filter(|comparison| comparison.regressed)
+            .map(|comparison| comparison.metric)
+            .collect()
+    }
+
+    #[test]
+    fn test_within_tolerance() {
+        let comparisons = compare(summary(1000.0, 100.0), summary(960.0, 109.0), TOLERANCES);
+        assert!(regressed(&comparisons).is_empty());
+        assert!((comparisons[0].change + 0.04).abs() < 1e-9);
+        assert!((comparisons[1].change - 0.09).abs() < 1e-9);
+
+        // Improvements never regress
+        let comparisons = compare(summary(1000.0, 100.0), summary(2000.0, 10.0), TOLERANCES);
+        assert!(regressed(&comparisons).is_empty());
+        assert!((comparisons[1].change + 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_regressions() {
+        let comparisons = compare(summary(1000.0, 100.0), summary(940.0, 100.0), TOLERANCES);
+        assert_eq!(regressed(&comparisons), vec!["throughput msg/s"]);
+
+        let comparisons = compare(summary(1000.0, 100.0), summary(1000.0, 111.0), TOLERANCES);
+        assert_eq!(regressed(&comparisons), vec!["p99 latency µs"]);
+    }
+
+    #[test]
+    fn test_zero_latency_baseline() {
+        // Below the histogram resolution, not infinitely fast
+        let comparisons = compare(summary(1000.0, 0.0), summary(1000.0, 0.0), TOLERANCES);
+        assert!(regressed(&comparisons).is_empty());
+        assert_eq!(comparisons[1].change, 0.0);
+
+        let comparisons = compare(summary(1000.0, 0.0), summary(1000.0, 1.0), TOLERANCES);
+        assert!(regressed(&comparisons).is_empty());
+
+        let comparisons = compare(summary(1000.0, 0.0), summary(1000.0, 5.0), TOLERANCES);
+        assert_eq!(regressed(&comparisons), vec!["p99 latency µs"]);
+        assert!(comparisons[1].change.is_finite());
+    }
+
+    #[test]
+    fn test_zero_throughput_baseline() {
+        let comparisons = compare(summary(0.0, 100.0), summary(0.0, 100.0), TOLERANCES);
+        assert!(regressed(&comparisons).is_empty());
+        assert_eq!(comparisons[0].change, 0.0);
+    }
+
+    #[test]
+    fn test_summary_of_results() {
+        let single = serde_json::json!({
+            "mode": "single",
+            "throughput_msg_per_sec": 900.0,
+            "latency_us": { "p99": 120.0 }
+        });
+        assert_eq!(Summary::of(&single), Some(summary(900.0, 120.0)));
+
+        let multiprocess = serde_json::json!({
+            "mode": "multiprocess",
+            "throughput_msg_per_sec": 800.0,
+            "latency_us": { "cross_process": { "p99": 300 }, "intra_process": { "p99": 50 } }
+        });
+        assert_eq!(Summary::of(&multiprocess), Some(summary(800.0, 300.0)));
+
+        assert_eq!(Summary::of(&serde_json::json!({ "shm": {} })), None);
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("5%"), Ok(0.05));
+        assert_eq!(parse_percent("12.5"), Ok(0.125));
+        assert!(parse_percent("-1%").is_err());
+        assert!(parse_percent("fast").is_err());
+    }
+
+    #[test]
+    fn test_utc_date() {
+        assert_eq!(utc_date(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_date(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_date(1_714_564_800), "2024-05-01T12:00:00Z");
+    }
+}